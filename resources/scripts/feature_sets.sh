@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+
+# Builds the node with its minimal, default and full feature sets, so that none of the optional
+# subsystems rot when compiled out, and reports the size of each binary.
+
+set -e -x
+
+cd "$(dirname "$0")/../../sn_node"
+
+function build() {
+  local set=$1
+  shift
+  cargo build --release --bin sn_node --target-dir "../target/feature_sets/$set" "$@"
+  cargo clippy --all-targets --target-dir "../target/feature_sets/$set" "$@"
+}
+
+build minimal --no-default-features
+build default
+# tokio-console needs the runtime's unstable instrumentation
+RUSTFLAGS="--cfg tokio_unstable" build full --all-features
+
+set +x
+for set in minimal default full; do
+  echo "$set: $(wc -c < "../target/feature_sets/$set/release/sn_node") bytes"
+done
@@ -30,6 +30,7 @@ use std::{
     path::{Path, PathBuf},
     str,
 };
+use tokio::io::AsyncWrite;
 
 pub(crate) use files_map::{file_map_for_path, get_file_link_and_metadata};
 pub(crate) use metadata::FileMeta;
@@ -681,6 +682,39 @@ impl Safe {
         self.fetch_data(&safe_url, range).await
     }
 
+    /// # Stream a file
+    /// Get a file from the network, or the `range` of it, writing its bytes to `writer` as they
+    /// are retrieved, rather than holding it in memory whole. Returns the number of bytes written.
+    pub async fn files_stream<W: AsyncWrite + Unpin>(
+        &self,
+        url: &str,
+        range: Range,
+        writer: &mut W,
+    ) -> Result<u64> {
+        let safe_url = self.parse_and_resolve_url(url).await?;
+        let address = match (safe_url.data_type(), safe_url.scope()) {
+            (DataType::File, Scope::Public) => BytesAddress::Public(safe_url.xorname()),
+            (DataType::File, Scope::Private) => BytesAddress::Private(safe_url.xorname()),
+            (other, _) => return Err(Error::ContentError(format!("{}", other))),
+        };
+
+        debug!("Attempting to stream data from {:?}", address.name());
+        let (position, length) = position_and_length(range);
+        let written = self
+            .get_safe_client()?
+            .stream_from(address, position, length, writer)
+            .await
+            .map_err(|e| Error::NetDataError(format!("Failed to GET file: {:?}", e)))?;
+
+        debug!(
+            "{} bytes of data successfully streamed from: {:?}",
+            written,
+            address.name()
+        );
+
+        Ok(written as u64)
+    }
+
     /// Fetch a file from a SafeUrl without performing any type of URL resolution
     pub(crate) async fn fetch_data(&self, safe_url: &SafeUrl, range: Range) -> Result<Bytes> {
         match (safe_url.data_type(), safe_url.scope()) {
@@ -701,12 +735,8 @@ impl Safe {
     async fn get_bytes(&self, address: BytesAddress, range: Range) -> Result<Bytes> {
         debug!("Attempting to fetch data from {:?}", address.name());
         let client = self.get_safe_client()?;
-        let data = if let Some((start, end)) = range {
-            let start = start.map(|start_index| start_index as usize).unwrap_or(0);
-            let len = end
-                .map(|end_index| end_index as usize - start)
-                .unwrap_or(usize::MAX);
-
+        let data = if range.is_some() {
+            let (start, len) = position_and_length(range);
             client.read_from(address, start, len).await
         } else {
             client.read_bytes(address).await
@@ -743,6 +773,16 @@ impl Safe {
 
 // Helper functions
 
+// The position and length of bytes a range covers, the end of it being exclusive.
+fn position_and_length(range: Range) -> (usize, usize) {
+    let (start, end) = range.unwrap_or((None, None));
+    let start = start.map(|start_index| start_index as usize).unwrap_or(0);
+    let len = end
+        .map(|end_index| (end_index as usize).saturating_sub(start))
+        .unwrap_or(usize::MAX);
+    (start, len)
+}
+
 // Make sure the input params are valid for a files_container_add operation
 async fn validate_files_add_params(
     safe: &Safe,
@@ -1001,7 +1041,19 @@ async fn files_map_sync(
 
 async fn is_file_item_modified(safe: &Safe, local_filename: &Path, file_item: &FileInfo) -> bool {
     if FileMeta::filetype_is_file(&file_item[PREDICATE_TYPE]) {
-        // Use a dry runner only for this next operation
+        // A file whose size differs from the one uploaded changed, there's no need to hash its
+        // content to tell, which is what takes time for large trees.
+        if let (Ok(metadata), Some(uploaded_size)) = (
+            std::fs::metadata(local_filename),
+            file_item.get(PREDICATE_SIZE),
+        ) {
+            if metadata.len().to_string() != *uploaded_size {
+                return true;
+            }
+        }
+
+        // Otherwise it changed if its content address did. Use a dry runner only for this next
+        // operation
         let dry_runner = Safe::dry_runner(Some(safe.xorurl_base));
         let is_uploaded = match upload_file_to_net(&dry_runner, local_filename).await {
             Ok(local_xorurl) => file_item[PREDICATE_LINK] != local_xorurl,
@@ -1792,6 +1844,31 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_files_item_modified_by_size_then_content() -> Result<()> {
+        let dry_runner = Safe::dry_runner(None);
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("index.html");
+        std::fs::write(&path, "hello")?;
+
+        let link = upload_file_to_net(&dry_runner, &path).await?;
+        let file_item = FileInfo::from([
+            (PREDICATE_TYPE.to_string(), "text/html".to_string()),
+            (PREDICATE_SIZE.to_string(), "5".to_string()),
+            (PREDICATE_LINK.to_string(), link),
+        ]);
+        assert!(!is_file_item_modified(&dry_runner, &path, &file_item).await);
+
+        // Same size, different content.
+        std::fs::write(&path, "world")?;
+        assert!(is_file_item_modified(&dry_runner, &path, &file_item).await);
+
+        // Different size.
+        std::fs::write(&path, "hello world")?;
+        assert!(is_file_item_modified(&dry_runner, &path, &file_item).await);
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_files_container_sync_with_versioned_target() -> Result<()> {
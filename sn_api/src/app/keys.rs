@@ -9,7 +9,7 @@
 use super::Safe;
 use crate::{Error, Result};
 use hex::encode;
-use sn_interface::types::SecretKey;
+use sn_interface::types::{PublicKey, SecretKey};
 use xor_name::XorName;
 
 impl Safe {
@@ -20,7 +20,8 @@ impl Safe {
                 let pk: ed25519_dalek::PublicKey = sk.into();
                 XorName(pk.to_bytes())
             }
-            _ => {
+            SecretKey::Bls(sk) => XorName::from(PublicKey::Bls(sk.inner().public_key())),
+            SecretKey::BlsShare(_) => {
                 return Err(Error::InvalidInput(
                     "Cannot form a keypair from a BlsKeyShare at this time.".to_string(),
                 ))
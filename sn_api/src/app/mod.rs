@@ -128,6 +128,18 @@ impl Safe {
         self.client.is_some()
     }
 
+    /// Returns the key and the number of elders of the section closest to us on the network
+    pub fn our_section(&self) -> Result<(bls::PublicKey, usize)> {
+        let client = self.get_safe_client()?;
+        let our_name = XorName::from(client.public_key());
+        client
+            .closest_known_section(&our_name)
+            .map(|(section_key, elders)| (section_key, elders.len()))
+            .ok_or_else(|| {
+                Error::ConnectionError("No section of the network is known yet".to_string())
+            })
+    }
+
     /// Generate a new random Ed25519 keypair
     pub fn new_keypair(&self) -> Keypair {
         Keypair::new_ed25519()
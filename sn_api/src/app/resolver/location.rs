@@ -0,0 +1,78 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{DataType, Safe, SafeUrl};
+use crate::{Error, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sn_interface::types::{BytesAddress, Scope};
+use std::collections::BTreeSet;
+use xor_name::{Prefix, XorName};
+
+/// Where the data at a URL is kept on the network.
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct DataLocation {
+    /// Name of the data.
+    pub xorname: XorName,
+    /// Native type of the data.
+    pub data_type: DataType,
+    /// Prefix of the section responsible for the data.
+    pub section_prefix: Prefix,
+    /// Key of that section.
+    pub section_key: bls::PublicKey,
+    /// The adults holding the data.
+    pub holders: BTreeSet<XorName>,
+    /// Whether the elders recorded the holders as holding the data, rather than only knowing
+    /// which adults should be holding it.
+    pub holders_recorded: bool,
+    /// For files, the chunks they are made of, in order, with the bytes of the file each holds.
+    pub chunks: Option<Vec<(XorName, usize)>>,
+}
+
+impl Safe {
+    /// # Locate data
+    /// Asks the network where the data at a XOR-URL is kept: the section responsible for it,
+    /// the adults holding it and, for files, the chunks it's made of.
+    /// The URL is not resolved, so NRS URLs are to be resolved first, e.g. with
+    /// [`Safe::inspect`].
+    pub async fn locate(&self, xorurl: &str) -> Result<DataLocation> {
+        let safe_url = SafeUrl::from_xorurl(xorurl)?;
+        let xorname = safe_url.xorname();
+        let client = self.get_safe_client()?;
+        debug!("Locating data at {:?}", xorname);
+
+        let holders = client
+            .get_data_holders(xorname)
+            .await
+            .map_err(|e| Error::NetDataError(format!("Failed to get holders: {:?}", e)))?;
+
+        let chunks = if safe_url.data_type() == DataType::File {
+            let address = match safe_url.scope() {
+                Scope::Public => BytesAddress::Public(xorname),
+                Scope::Private => BytesAddress::Private(xorname),
+            };
+            let chunks = client
+                .data_chunks(address)
+                .await
+                .map_err(|e| Error::NetDataError(format!("Failed to get chunks: {:?}", e)))?;
+            Some(chunks)
+        } else {
+            None
+        };
+
+        Ok(DataLocation {
+            xorname,
+            data_type: safe_url.data_type(),
+            section_prefix: holders.prefix,
+            section_key: holders.section_key,
+            holders: holders.holders,
+            holders_recorded: holders.recorded,
+            chunks,
+        })
+    }
+}
@@ -7,11 +7,13 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod handlers;
+mod location;
 mod safe_data;
 
 use super::{files::FileInfo, Safe};
 pub use super::{ContentType, DataType, SafeUrl, VersionHash, XorUrlBase};
 use crate::{Error, Result};
+pub use location::DataLocation;
 use log::{debug, info};
 pub use safe_data::SafeData;
 
@@ -22,8 +22,8 @@ mod safeurl;
 
 // re-export these useful types from sn_data_types
 pub use sn_interface::types::{
-    BytesAddress, DataAddress, Keypair, PublicKey, RegisterAddress, SafeKeyAddress, Scope,
-    SecretKey, Token,
+    BytesAddress, DataAddress, DerivationPath, KeySeed, Keypair, PublicKey, RegisterAddress,
+    SafeKeyAddress, Scope, SecretKey, Token,
 };
 
 #[cfg(feature = "app")]
@@ -42,7 +42,7 @@ const URL_VERSION_QUERY_NAME: &str = "v";
 pub type XorUrl = String;
 
 /// Supported base encoding for XOR URLs
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum XorUrlBase {
     #[allow(missing_docs)]
     Base32z,
@@ -8,7 +8,7 @@
 
 use crate::{
     operations::auth_and_connect::connect,
-    operations::config::{Config, SnLaunchToolNetworkLauncher},
+    operations::config::{Config, NetworkProfile, SnLaunchToolNetworkLauncher},
     subcommands::{
         cat::cat_commander,
         config::config_commander,
@@ -18,7 +18,7 @@ use crate::{
         networks::networks_commander,
         node::node_commander,
         nrs::nrs_commander,
-        setup::setup_commander,
+        setup::{completions_commander, setup_commander},
         update::update_commander,
         wallet::wallet_commander,
         xorurl::{xorurl_commander, xorurl_of_files},
@@ -97,17 +97,23 @@ pub async fn run() -> Result<()> {
                 .map_err(|err| eyre!("Failed to run self update: {:?}", err))?
         }
         SubCommands::Setup(cmd) => setup_commander(cmd, output_fmt),
+        SubCommands::Completions { shell } => completions_commander(shell, output_fmt),
         SubCommands::Node { cmd } => {
             let mut launcher = Box::new(SnLaunchToolNetworkLauncher::default());
-            node_commander(cmd, &mut get_config().await?, &mut launcher).await
+            node_commander(cmd, output_fmt, &mut get_config().await?, &mut launcher).await
+        }
+        SubCommands::Keys(cmd) => {
+            let config = get_config().await?;
+            let _ = use_profile(&mut safe, args.xorurl_base, &config).await;
+            key_commander(cmd, output_fmt, &safe, &config).await
         }
-        SubCommands::Keys(cmd) => key_commander(cmd, output_fmt, &safe).await,
         SubCommands::Xorurl {
             cmd,
             location,
             recursive,
             follow_links,
         } => {
+            let _ = use_profile(&mut safe, args.xorurl_base, &get_config().await?).await;
             if let Some(cmd) = cmd {
                 xorurl_commander(cmd, output_fmt, safe.xorurl_base).await
             } else {
@@ -122,6 +128,8 @@ pub async fn run() -> Result<()> {
             }
         }
         other => {
+            let config = get_config().await?;
+            let profile = use_profile(&mut safe, args.xorurl_base, &config).await;
             // Set dry run mode in Safe instance as per arg provide
             safe.dry_run_mode = args.dry;
             // We treat these commands separatelly since we use the credentials if they are
@@ -137,15 +145,12 @@ pub async fn run() -> Result<()> {
                             timeout
                         )
                     })?,
-                    Err(_) => DEFAULT_OPERATION_TIMEOUT_SECS,
+                    Err(_) => profile
+                        .timeout_secs
+                        .unwrap_or(DEFAULT_OPERATION_TIMEOUT_SECS),
                 };
 
-                connect(
-                    &mut safe,
-                    get_config().await?,
-                    Duration::from_secs(timeout_secs),
-                )
-                .await?;
+                connect(&mut safe, config, Duration::from_secs(timeout_secs)).await?;
             }
 
             match other {
@@ -162,6 +167,21 @@ pub async fn run() -> Result<()> {
     result
 }
 
+/// Applies the profile of the current network to the settings not given as args, returning it.
+async fn use_profile(
+    safe: &mut Safe,
+    xorurl_base: Option<XorUrlBase>,
+    config: &Config,
+) -> NetworkProfile {
+    let profile = config.active_profile().await;
+    if xorurl_base.is_none() {
+        if let Some(base) = profile.xorurl_base {
+            safe.xorurl_base = base;
+        }
+    }
+    profile
+}
+
 /// Gets the configuration, which is used by various parts of the application.
 ///
 /// The SN_CLI_CONFIG_PATH allows the user to define a custom location as an alternative to
@@ -8,8 +8,9 @@
 
 use color_eyre::{eyre::bail, eyre::eyre, eyre::WrapErr, Help, Report, Result};
 use comfy_table::Table;
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
-use sn_api::{NodeConfig, PublicKey};
+use sn_api::{NodeConfig, PublicKey, Safe, XorUrlBase};
 use std::{
     collections::{BTreeMap, BTreeSet},
     default::Default,
@@ -17,7 +18,7 @@ use std::{
     net::SocketAddr,
     path::{Path, PathBuf},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use structopt::StructOpt;
 use tokio::fs;
@@ -100,9 +101,69 @@ impl NetworkInfo {
     }
 }
 
+/// Settings of the CLI for use with a particular network, which apply when it's the current one.
+#[derive(Clone, Deserialize, Debug, Serialize, Default)]
+pub struct NetworkProfile {
+    /// File the credentials used with the network are kept in, instead of the CLI's global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<PathBuf>,
+    /// Timeout of operations, in seconds, unless set with `SN_CLI_QUERY_TIMEOUT`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Base encoding of the XOR-URLs generated, unless set with `--xorurl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xorurl_base: Option<XorUrlBase>,
+}
+
+impl fmt::Display for NetworkProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .map_or_else(|| "global".to_string(), |path| path.display().to_string());
+        let timeout = self
+            .timeout_secs
+            .map_or_else(|| "default".to_string(), |secs| format!("{}s", secs));
+        let xorurl_base = self
+            .xorurl_base
+            .map_or_else(|| "default".to_string(), |base| base.to_string());
+        write!(
+            f,
+            "credentials: {}, timeout: {}, xorurl base: {}",
+            credentials, timeout, xorurl_base
+        )
+    }
+}
+
+/// How bootstrapping to a network in the config went.
+#[derive(Debug)]
+pub struct NetworkCheck {
+    pub name: String,
+    /// The network's genesis key, if its connection info could be read.
+    pub genesis_key: Option<bls::PublicKey>,
+    pub status: NetworkStatus,
+}
+
+#[derive(Debug)]
+pub enum NetworkStatus {
+    /// A section of `elders` was reached, taking `latency` to bootstrap to.
+    Reachable { latency: Duration, elders: usize },
+    /// None of the contacts could be bootstrapped to, as when the connection info is stale.
+    Unreachable(String),
+    /// The connection info couldn't be fetched or read.
+    ConnInfoUnavailable(String),
+}
+
 #[derive(Clone, Deserialize, Debug, Serialize, Default)]
 pub struct Settings {
     networks: BTreeMap<String, NetworkInfo>,
+    /// The profiles of the networks which have one, by network name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, NetworkProfile>,
+    /// The network last switched to, with the connection info it was switched to with, which
+    /// tells whether it's still the current one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    current_network: Option<(String, NodeConfig)>,
 }
 
 #[derive(Clone, Debug)]
@@ -267,6 +328,7 @@ impl Config {
     }
 
     pub async fn remove_network(&mut self, name: &str) -> Result<()> {
+        let _ = self.settings.profiles.remove(name);
         match self.settings.networks.remove(name) {
             Some(NetworkInfo::ConnInfoLocation(location)) => {
                 self.write_settings_to_file().await?;
@@ -305,7 +367,13 @@ impl Config {
         self.write_settings_to_file().await
     }
 
-    pub async fn switch_to_network(&self, name: &str) -> Result<()> {
+    pub async fn switch_to_network(&mut self, name: &str) -> Result<()> {
+        let contacts = self.get_network_info(name).await?;
+        self.use_network(name, contacts).await
+    }
+
+    /// Makes the network `name`, reached through `contacts`, the current one.
+    pub async fn use_network(&mut self, name: &str, contacts: NodeConfig) -> Result<()> {
         let mut base_path = self.node_config_path.clone();
         base_path.pop();
 
@@ -319,7 +387,6 @@ impl Config {
                 .wrap_err("Couldn't create folder for network connection info")?;
         }
 
-        let contacts = self.get_network_info(name).await?;
         let conn_info = serialise_node_config(&contacts)?;
         fs::write(&self.node_config_path, conn_info)
             .await
@@ -328,7 +395,95 @@ impl Config {
                     "Unable to write network connection info in '{}'",
                     base_path.display(),
                 )
-            })
+            })?;
+
+        self.settings.current_network = Some((name.to_string(), contacts));
+        self.write_settings_to_file().await
+    }
+
+    /// Sets the profile of the network `name`.
+    pub async fn set_profile(&mut self, name: &str, profile: NetworkProfile) -> Result<()> {
+        if !self.settings.networks.contains_key(name) {
+            bail!("No network with name '{}' was found in the config. Please use the networks 'add'/'set' subcommand to add it", name);
+        }
+        let _ = self.settings.profiles.insert(name.to_string(), profile);
+        self.write_settings_to_file().await
+    }
+
+    /// The profile of the network `name`, which is the default one unless it's been set.
+    pub fn profile(&self, name: &str) -> Result<NetworkProfile> {
+        if !self.settings.networks.contains_key(name) {
+            bail!("No network with name '{}' was found in the config", name);
+        }
+        Ok(self
+            .settings
+            .profiles
+            .get(name)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// The profile of the current network. The default one applies when the current network isn't
+    /// one switched to, e.g. as a network was launched or joined since.
+    pub async fn active_profile(&self) -> NetworkProfile {
+        let (name, contacts) = match &self.settings.current_network {
+            Some(current) => current,
+            None => return NetworkProfile::default(),
+        };
+        match self.read_current_node_config().await {
+            Ok((_, current_contacts)) if current_contacts == *contacts => self
+                .settings
+                .profiles
+                .get(name)
+                .cloned()
+                .unwrap_or_default(),
+            _ => NetworkProfile::default(),
+        }
+    }
+
+    /// Bootstraps to the network `name` to check it can be reached, giving up after `timeout`.
+    /// Returns the network's connection info, with the key and the number of elders of the
+    /// section we reached.
+    pub async fn probe_network(
+        &self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<(NodeConfig, bls::PublicKey, usize)> {
+        let contacts = self.get_network_info(name).await?;
+        let (section_key, elders) = probe_contacts(&contacts, timeout)
+            .await
+            .wrap_err_with(|| format!("Failed to bootstrap to '{}' network", name))?;
+        Ok((contacts, section_key, elders))
+    }
+
+    /// Bootstraps to every network in the config at once, giving up on each after `timeout`.
+    pub async fn check_networks(&self, timeout: Duration) -> Vec<NetworkCheck> {
+        let checks = self.networks_iter().map(|(name, _)| async move {
+            let contacts = match self.get_network_info(name).await {
+                Ok(contacts) => contacts,
+                Err(err) => {
+                    return NetworkCheck {
+                        name: name.clone(),
+                        genesis_key: None,
+                        status: NetworkStatus::ConnInfoUnavailable(format!("{:#}", err)),
+                    }
+                }
+            };
+            let start = Instant::now();
+            let status = match probe_contacts(&contacts, timeout).await {
+                Ok((_, elders)) => NetworkStatus::Reachable {
+                    latency: start.elapsed(),
+                    elders,
+                },
+                Err(err) => NetworkStatus::Unreachable(format!("{:#}", err)),
+            };
+            NetworkCheck {
+                name: name.clone(),
+                genesis_key: Some(contacts.0),
+                status,
+            }
+        });
+        join_all(checks).await
     }
 
     pub async fn print_networks(&self) {
@@ -400,6 +555,23 @@ impl Config {
     }
 }
 
+/// Bootstraps to the network through `contacts`, giving up after `timeout`. Returns the key and the
+/// number of elders of the section we reached.
+async fn probe_contacts(
+    contacts: &NodeConfig,
+    timeout: Duration,
+) -> Result<(bls::PublicKey, usize)> {
+    let mut safe = Safe::dry_runner(None);
+    tokio::time::timeout(
+        timeout,
+        safe.connect(contacts.clone(), None, None, Some(timeout)),
+    )
+    .await
+    .map_err(|_| eyre!("Timed out after {:?}", timeout))??;
+    let section_key_and_elders = safe.our_section()?;
+    Ok(section_key_and_elders)
+}
+
 async fn retrieve_node_config(location: &str) -> Result<NodeConfig> {
     let is_remote_location = location.starts_with("http");
     let contacts_bytes = if is_remote_location {
@@ -1140,3 +1312,177 @@ mod add_network {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod network_profiles {
+    use super::{serialise_node_config, Config, NetworkInfo, NetworkProfile};
+    use assert_fs::prelude::*;
+    use color_eyre::Result;
+    use sn_api::XorUrlBase;
+    use std::{collections::BTreeSet, path::PathBuf};
+
+    fn profile() -> NetworkProfile {
+        NetworkProfile {
+            credentials: Some(PathBuf::from("/tmp/alpha/credentials")),
+            timeout_secs: Some(30),
+            xorurl_base: Some(XorUrlBase::Base64),
+        }
+    }
+
+    #[tokio::test]
+    async fn given_an_unknown_network_then_its_profile_cannot_be_set() -> Result<()> {
+        let tmp_dir = assert_fs::TempDir::new()?;
+        let cli_config_file = tmp_dir.child(".safe/cli/config.json");
+        let node_config_file = tmp_dir.child(".safe/node/node_connection_info.config");
+        let mut config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+
+        assert!(config.set_profile("alpha", profile()).await.is_err());
+        assert!(config.profile("alpha").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_a_network_is_switched_to_then_its_profile_should_be_active() -> Result<()> {
+        let tmp_dir = assert_fs::TempDir::new()?;
+        let cli_config_file = tmp_dir.child(".safe/cli/config.json");
+        let node_config_file = tmp_dir.child(".safe/node/node_connection_info.config");
+        let mut config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+        let contacts = (
+            bls::SecretKey::random().public_key(),
+            BTreeSet::from(["127.0.0.1:12000".parse()?]),
+        );
+        let _ = config
+            .add_network("alpha", Some(NetworkInfo::NodeConfig(contacts)))
+            .await?;
+        config.set_profile("alpha", profile()).await?;
+        assert!(config.active_profile().await.timeout_secs.is_none());
+
+        config.switch_to_network("alpha").await?;
+        // The profile is kept with the rest of the settings.
+        let config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+        let active = config.active_profile().await;
+        assert_eq!(active.credentials, profile().credentials);
+        assert_eq!(active.timeout_secs, Some(30));
+        assert!(matches!(active.xorurl_base, Some(XorUrlBase::Base64)));
+
+        // Once another network is set up otherwise, e.g. launched, it no longer applies.
+        let other_contacts = (
+            bls::SecretKey::random().public_key(),
+            BTreeSet::from(["127.0.0.1:13000".parse()?]),
+        );
+        node_config_file.write_str(&serialise_node_config(&other_contacts)?)?;
+        assert!(config.active_profile().await.timeout_secs.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_a_network_is_removed_then_its_profile_should_be_removed() -> Result<()> {
+        let tmp_dir = assert_fs::TempDir::new()?;
+        let cli_config_file = tmp_dir.child(".safe/cli/config.json");
+        let node_config_file = tmp_dir.child(".safe/node/node_connection_info.config");
+        let mut config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+        let contacts = (
+            bls::SecretKey::random().public_key(),
+            BTreeSet::from(["127.0.0.1:12000".parse()?]),
+        );
+        let _ = config
+            .add_network("alpha", Some(NetworkInfo::NodeConfig(contacts.clone())))
+            .await?;
+        config.set_profile("alpha", profile()).await?;
+
+        config.remove_network("alpha").await?;
+        let _ = config
+            .add_network("alpha", Some(NetworkInfo::NodeConfig(contacts)))
+            .await?;
+        assert!(config.profile("alpha")?.timeout_secs.is_none());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod check_networks {
+    use super::{serialise_node_config, Config, NetworkInfo, NetworkStatus};
+    use assert_fs::prelude::*;
+    use color_eyre::{eyre::eyre, Result};
+    use std::{collections::BTreeSet, time::Duration, time::Instant};
+
+    #[tokio::test]
+    async fn given_dead_networks_then_they_should_all_be_reported_unreachable_at_once() -> Result<()>
+    {
+        let tmp_dir = assert_fs::TempDir::new()?;
+        let cli_config_file = tmp_dir.child(".safe/cli/config.json");
+        let node_config_file = tmp_dir.child(".safe/node/node_connection_info.config");
+        let mut config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+        // Nothing listens on ports 1 and 2.
+        for (name, contact) in [("alpha", "127.0.0.1:1"), ("beta", "127.0.0.1:2")] {
+            let contacts = (
+                bls::SecretKey::random().public_key(),
+                BTreeSet::from([contact.parse()?]),
+            );
+            let _ = config
+                .add_network(name, Some(NetworkInfo::NodeConfig(contacts)))
+                .await?;
+        }
+        // Its connection info is gone since it was added.
+        let gamma_conn_info = tmp_dir.child("gamma_node_connection_info.config");
+        gamma_conn_info.write_str(&serialise_node_config(&(
+            bls::SecretKey::random().public_key(),
+            BTreeSet::from(["127.0.0.1:3".parse()?]),
+        ))?)?;
+        let _ = config
+            .add_network(
+                "gamma",
+                Some(NetworkInfo::ConnInfoLocation(
+                    gamma_conn_info.path().display().to_string(),
+                )),
+            )
+            .await?;
+        std::fs::remove_file(gamma_conn_info.path())?;
+
+        let timeout = Duration::from_secs(3);
+        let start = Instant::now();
+        let checks = config.check_networks(timeout).await;
+
+        // The networks are checked concurrently, not one after another.
+        assert!(start.elapsed() < 2 * timeout);
+        assert_eq!(checks.len(), 3);
+        for check in &checks[..2] {
+            assert!(check.genesis_key.is_some());
+            assert!(matches!(check.status, NetworkStatus::Unreachable(_)));
+        }
+        let gamma = checks
+            .get(2)
+            .ok_or_else(|| eyre!("failed to check 'gamma' network"))?;
+        assert_eq!(gamma.name, "gamma");
+        assert!(gamma.genesis_key.is_none());
+        assert!(matches!(
+            gamma.status,
+            NetworkStatus::ConnInfoUnavailable(_)
+        ));
+
+        Ok(())
+    }
+}
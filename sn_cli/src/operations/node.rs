@@ -9,6 +9,8 @@
 use super::helpers::download_and_install_node;
 use crate::operations::config::NetworkLauncher;
 use color_eyre::{eyre::bail, eyre::eyre, eyre::WrapErr, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
 use sn_api::NodeConfig;
 use std::{
     fs::create_dir_all,
@@ -17,8 +19,18 @@ use std::{
     path::PathBuf,
     process::{Command, Stdio},
 };
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
 use tracing::debug;
 
+/// Name of the socket a node serves its diagnostics on, in its root dir, when run with
+/// `--control-sockets`.
+pub(crate) const NODE_DIAGNOSTICS_SOCKET: &str = "diagnostics.sock";
+
 #[cfg(not(target_os = "windows"))]
 pub(crate) const SN_NODE_EXECUTABLE: &str = "sn_node";
 
@@ -312,3 +324,120 @@ pub fn node_update(node_path: Option<PathBuf>) -> Result<()> {
         ))
     }
 }
+
+/// Where a running node serves its diagnostics.
+#[derive(Debug, Clone)]
+pub enum NodeRpc {
+    /// The diagnostics socket in the node's root dir.
+    Socket(PathBuf),
+    /// The loopback address the node was run with `--rpc-addr` on.
+    Tcp(SocketAddr),
+}
+
+/// What a running node reports of itself.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct NodeStatus {
+    pub name: String,
+    pub prefix: String,
+    pub age: u8,
+    /// Either "elder" or "adult", or "joining" until the node is a member of its section.
+    pub role: String,
+    pub used_space: u64,
+    pub max_capacity: u64,
+    pub connected_peers: u64,
+    pub section_members: u64,
+    /// The key the node's rewards are paid to, hex encoded, if it has one.
+    pub reward_key: Option<String>,
+    /// The balance of the wallet rewards are paid into, when one was given.
+    pub reward_balance: Option<String>,
+}
+
+/// Queries the status of a running node.
+pub async fn node_status(rpc: &NodeRpc) -> Result<NodeStatus> {
+    let status = node_rpc(rpc, "status").await?;
+    let storage = node_rpc(rpc, "storage").await?;
+    let peers = node_rpc(rpc, "peers").await?;
+
+    let string = |value: &Value, field: &str| -> Result<String> {
+        value[field]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| eyre!("The node's reply lacks its {}", field))
+    };
+    let number = |value: &Value, field: &str| -> Result<u64> {
+        value[field]
+            .as_u64()
+            .ok_or_else(|| eyre!("The node's reply lacks its {}", field))
+    };
+
+    let role = if status["is_elder"].as_bool() == Some(true) {
+        "elder"
+    } else if status["joined"].as_bool() == Some(true) {
+        "adult"
+    } else {
+        "joining"
+    };
+    Ok(NodeStatus {
+        name: string(&status, "name")?,
+        prefix: string(&status, "prefix")?,
+        age: u8::try_from(number(&status, "age")?)?,
+        role: role.to_string(),
+        used_space: number(&storage, "used_bytes")?,
+        max_capacity: number(&storage, "max_capacity_bytes")?,
+        connected_peers: number(&peers, "connected_peers")?,
+        section_members: number(&peers, "section_members")?,
+        reward_key: status["reward_key"].as_str().map(str::to_string),
+        reward_balance: None,
+    })
+}
+
+// Sends `cmd` to the node, returning what it answered with.
+async fn node_rpc(rpc: &NodeRpc, cmd: &str) -> Result<Value> {
+    debug!("Sending '{}' to the node at {:?}", cmd, rpc);
+    match rpc {
+        #[cfg(unix)]
+        NodeRpc::Socket(path) => {
+            let stream = UnixStream::connect(path).await.wrap_err_with(|| {
+                format!(
+                    "Failed to connect to the node's socket at {}, is it running with \
+                    --control-sockets?",
+                    path.display()
+                )
+            })?;
+            rpc_request(stream, cmd).await
+        }
+        #[cfg(not(unix))]
+        NodeRpc::Socket(path) => Err(eyre!(
+            "The node's socket at {} can't be connected to on this platform, run it with \
+            --rpc-addr and use that instead",
+            path.display()
+        )),
+        NodeRpc::Tcp(addr) => {
+            let stream = TcpStream::connect(addr).await.wrap_err_with(|| {
+                format!("Failed to connect to the node at {}, is it running?", addr)
+            })?;
+            rpc_request(stream, cmd).await
+        }
+    }
+}
+
+// Sends a request as one line of JSON, reading the one line answering it.
+async fn rpc_request(stream: impl AsyncRead + AsyncWrite + Unpin, cmd: &str) -> Result<Value> {
+    let mut stream = BufReader::new(stream);
+    let mut request = json!({ "cmd": cmd }).to_string();
+    request.push('\n');
+    stream.get_mut().write_all(request.as_bytes()).await?;
+
+    let mut line = String::new();
+    let _read = stream.read_line(&mut line).await?;
+    let mut response: Value = serde_json::from_str(&line)
+        .wrap_err_with(|| format!("The node's reply to '{}' can't be read", cmd))?;
+    if let Some(error) = response.get("error") {
+        bail!(
+            "The node failed to serve '{}': {}",
+            cmd,
+            error["message"].as_str().unwrap_or("unknown error")
+        );
+    }
+    Ok(response["ok"].take())
+}
@@ -6,8 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::operations::config::{Config, NetworkInfo};
+use crate::operations::config::{Config, NetworkInfo, NetworkProfile};
 use color_eyre::Result;
+use sn_api::XorUrlBase;
+use std::{path::PathBuf, time::Duration};
 use structopt::StructOpt;
 use tracing::debug;
 
@@ -22,6 +24,34 @@ pub enum ConfigSubCommands {
     #[structopt(name = "clear")]
     /// Remove all config settings
     Clear,
+    #[structopt(name = "switch")]
+    /// Switch to another network, once it's been checked to be reachable by bootstrapping to it
+    Switch {
+        /// Network to switch to
+        network_name: String,
+        /// How long to wait for the network to be bootstrapped to, in seconds
+        #[structopt(long, default_value = "60")]
+        timeout: u64,
+    },
+    #[structopt(name = "profile")]
+    /// Show or set the profile of a network, i.e. the settings used with it when it's the current
+    /// network
+    Profile {
+        /// Network whose profile to show or set
+        network_name: String,
+        /// File to keep the credentials used with the network in, instead of the global one
+        #[structopt(long, parse(from_os_str))]
+        credentials: Option<PathBuf>,
+        /// Timeout of operations, in seconds
+        #[structopt(long)]
+        timeout: Option<u64>,
+        /// Base encoding of the XOR-URLs generated: [base32z, base32, base64]
+        #[structopt(long)]
+        xorurl_base: Option<XorUrlBase>,
+        /// Reset the profile to the default settings before setting any others
+        #[structopt(long)]
+        reset: bool,
+    },
 }
 
 #[derive(StructOpt, Debug)]
@@ -78,8 +108,99 @@ pub async fn config_commander(cmd: Option<ConfigSubCommands>, config: &mut Confi
             config.clear().await?;
             debug!("Config settings cleared out");
         }
+        Some(ConfigSubCommands::Switch {
+            network_name,
+            timeout,
+        }) => {
+            println!("Checking '{}' network can be reached...", network_name);
+            let (contacts, section_key, elders) = config
+                .probe_network(&network_name, Duration::from_secs(timeout))
+                .await?;
+            println!(
+                "Reached a section of {} elders, with section key {}",
+                elders,
+                hex::encode(section_key.to_bytes())
+            );
+            config.use_network(&network_name, contacts).await?;
+            println!("Switched to '{}' network", network_name);
+        }
+        Some(ConfigSubCommands::Profile {
+            network_name,
+            credentials,
+            timeout,
+            xorurl_base,
+            reset,
+        }) => {
+            let mut profile = if reset {
+                NetworkProfile::default()
+            } else {
+                config.profile(&network_name)?
+            };
+            if reset || credentials.is_some() || timeout.is_some() || xorurl_base.is_some() {
+                if let Some(path) = credentials {
+                    // Kept absolute, for it not to depend on where the CLI is run from.
+                    profile.credentials = Some(std::env::current_dir()?.join(path));
+                }
+                if timeout.is_some() {
+                    profile.timeout_secs = timeout;
+                }
+                if xorurl_base.is_some() {
+                    profile.xorurl_base = xorurl_base;
+                }
+                config.set_profile(&network_name, profile.clone()).await?;
+                debug!("Profile of '{}' network set", network_name);
+            }
+            println!("Profile of '{}' network: {}", network_name, profile);
+        }
         None => config.print_networks().await,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod config_switch_command {
+    use super::{config_commander, ConfigSubCommands};
+    use crate::operations::config::{Config, NetworkInfo};
+    use assert_fs::prelude::*;
+    use color_eyre::Result;
+    use predicates::prelude::*;
+    use std::collections::BTreeSet;
+
+    #[tokio::test]
+    async fn given_an_unreachable_network_then_the_current_network_should_be_kept() -> Result<()> {
+        // Arrange
+        let config_dir = assert_fs::TempDir::new()?;
+        let cli_config_file = config_dir.child(".safe/cli/config.json");
+        let node_config_file = config_dir.child(".safe/node/node_connection_info.config");
+        let mut config = Config::new(
+            cli_config_file.path().to_path_buf(),
+            node_config_file.path().to_path_buf(),
+        )
+        .await?;
+        // Nothing listens on port 1.
+        let contacts = BTreeSet::from(["127.0.0.1:1".parse()?]);
+        let _ = config
+            .add_network(
+                "dead_network",
+                Some(NetworkInfo::NodeConfig((
+                    bls::SecretKey::random().public_key(),
+                    contacts,
+                ))),
+            )
+            .await?;
+        let cmd = ConfigSubCommands::Switch {
+            network_name: String::from("dead_network"),
+            timeout: 2,
+        };
+
+        // Act
+        let result = config_commander(Some(cmd), &mut config).await;
+
+        // Assert
+        assert!(result.is_err());
+        node_config_file.assert(predicate::path::missing());
+
+        Ok(())
+    }
+}
@@ -14,7 +14,7 @@ use super::{
 };
 use color_eyre::Result;
 use sn_api::{
-    resolver::{ContentType, SafeData},
+    resolver::{ContentType, DataLocation, SafeData},
     Safe, SafeUrl,
 };
 use structopt::StructOpt;
@@ -24,6 +24,10 @@ use tracing::debug;
 pub struct DogCommands {
     /// The safe:// location to inspect
     location: Option<String>,
+    /// Also ask the network where the data resolved into is kept: the section responsible for
+    /// it, the adults holding it and, for files, the chunks it's made of
+    #[structopt(long = "deep")]
+    deep: bool,
 }
 
 pub async fn dog_commander(cmd: DogCommands, output_fmt: OutputFmt, safe: &Safe) -> Result<()> {
@@ -32,11 +36,27 @@ pub async fn dog_commander(cmd: DogCommands, output_fmt: OutputFmt, safe: &Safe)
     debug!("Running dog for: {}", &url);
 
     let resolved_content = safe.inspect(&url.to_string()).await?;
+    let location = if cmd.deep {
+        match resolved_content.last() {
+            Some(content) => Some(safe.locate(&content.xorurl()).await?),
+            None => None,
+        }
+    } else {
+        None
+    };
+
     if OutputFmt::Pretty != output_fmt {
-        println!(
-            "{}",
-            serialise_output(&(url.to_string(), resolved_content), output_fmt)
-        );
+        if let Some(location) = location {
+            println!(
+                "{}",
+                serialise_output(&(url.to_string(), resolved_content, location), output_fmt)
+            );
+        } else {
+            println!(
+                "{}",
+                serialise_output(&(url.to_string(), resolved_content), output_fmt)
+            );
+        }
     } else {
         for (i, ref content) in resolved_content.iter().enumerate() {
             println!();
@@ -173,8 +193,55 @@ pub async fn dog_commander(cmd: DogCommands, output_fmt: OutputFmt, safe: &Safe)
                 }
             }
         }
+        if let Some(location) = location {
+            println!();
+            print_location(&url.to_string(), &resolved_content, &location);
+        }
         println!();
     }
 
     Ok(())
 }
+
+fn print_location(url: &str, resolved_content: &[SafeData], location: &DataLocation) {
+    println!("== Data location ==");
+    let mut chain = resolved_content
+        .iter()
+        .filter_map(|content| content.resolved_from())
+        .collect::<Vec<_>>();
+    if chain.first().map(String::as_str) != Some(url) {
+        chain.insert(0, url.to_string());
+    }
+    if let Some(content) = resolved_content.last() {
+        let xorurl = content.xorurl();
+        if chain.last() != Some(&xorurl) {
+            chain.push(xorurl);
+        }
+    }
+    println!("Resolution chain: {}", chain.join(" -> "));
+    println!("XOR name: 0x{}", xorname_to_hex(&location.xorname));
+    println!("Native data type: {}", location.data_type);
+    println!("Section prefix: {:?}", location.section_prefix);
+    println!(
+        "Section key: {}",
+        hex::encode(location.section_key.to_bytes())
+    );
+    if location.holders_recorded {
+        println!("Holders ({}):", location.holders.len());
+    } else {
+        println!(
+            "Holders ({}, expected, none recorded by the elders):",
+            location.holders.len()
+        );
+    }
+    for holder in &location.holders {
+        println!("  0x{}", xorname_to_hex(holder));
+    }
+    if let Some(chunks) = &location.chunks {
+        let size: usize = chunks.iter().map(|(_, size)| size).sum();
+        println!("Chunks: {} ({} bytes)", chunks.len(), size);
+        for (name, size) in chunks {
+            println!("  0x{} ({} bytes)", xorname_to_hex(name), size);
+        }
+    }
+}
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    files_get::{process_get_command, FileExistsAction, ProgressIndicator},
+    files_get::{process_get_command, ByteRange, FileExistsAction, ProgressIndicator},
     helpers::{
         gen_processed_files_table, get_from_arg_or_stdin, get_from_stdin, get_target_url, if_tty,
         notice_dry_run, parse_stdin_arg, pluralize, serialise_output,
@@ -20,7 +20,7 @@ use color_eyre::{eyre::bail, eyre::eyre, Result};
 use comfy_table::Table;
 use serde::Serialize;
 use sn_api::{
-    files::{FilesMap, ProcessedFiles},
+    files::{FilesMap, FilesMapChange, ProcessedFiles},
     nrs::VersionHash,
     resolver::SafeData,
     Safe, SafeUrl, XorUrl,
@@ -63,6 +63,39 @@ struct FileTreeNode {
     sub: Vec<FileTreeNode>,
 }
 
+// The changes made by `safe files sync`, for `--summary`.
+#[derive(Debug, Default, Serialize)]
+struct SyncSummary {
+    // The FilesContainer synced, at the version resulting from the sync.
+    url: String,
+    added: Vec<String>,
+    updated: Vec<String>,
+    removed: Vec<String>,
+    // The files which failed, with why.
+    failed: BTreeMap<String, String>,
+}
+
+impl SyncSummary {
+    fn new(url: String, processed_files: &ProcessedFiles) -> Self {
+        let mut summary = Self {
+            url,
+            ..Self::default()
+        };
+        for (path, change) in processed_files {
+            let path = path.display().to_string();
+            match change {
+                FilesMapChange::Added(_) => summary.added.push(path),
+                FilesMapChange::Updated(_) => summary.updated.push(path),
+                FilesMapChange::Removed(_) => summary.removed.push(path),
+                FilesMapChange::Failed(reason) => {
+                    let _ = summary.failed.insert(path, reason.clone());
+                }
+            }
+        }
+        summary
+    }
+}
+
 impl FileTreeNode {
     // create a new FileTreeNode (either a Directory, File or Symlink)
     fn new(name: &str, fs_type: FileTreeNodeType, details: FileDetails) -> FileTreeNode {
@@ -126,6 +159,9 @@ pub enum FilesSubCommands {
         /// Preserves modification times, access times, and modes from the original file
         #[structopt(short = "p", long = "preserve")]
         preserve: bool,
+        /// Only get the bytes of a single file from START up to END, exclusive, given as START-END. Either can be left out to get the bytes from the start, or up to the end, of the file
+        #[structopt(long = "range")]
+        range: Option<ByteRange>,
     },
     #[structopt(name = "sync")]
     /// Sync files to the SAFE Network
@@ -146,6 +182,9 @@ pub enum FilesSubCommands {
         /// Automatically update the NRS name to link to the new version of the FilesContainer. This is only allowed if an NRS URL was provided, and if the NRS name is currently linked to a specific version of the FilesContainer
         #[structopt(short = "u", long = "update-nrs")]
         update_nrs: bool,
+        /// Print a summary of the changes made: the files added, updated, removed and those which failed, rather than each file processed when the output is serialised
+        #[structopt(long = "summary")]
+        summary: bool,
     },
     #[structopt(name = "add")]
     /// Add a file to an existing FilesContainer on the network
@@ -242,6 +281,7 @@ pub async fn files_commander(
             follow_links,
             delete,
             update_nrs,
+            summary,
         } => {
             let target = get_from_arg_or_stdin(target, None)?;
             let mut target_url = get_target_url(&target)?;
@@ -286,6 +326,22 @@ pub async fn files_commander(
                         version_str, target
                     );
                 }
+                if summary {
+                    let summary = SyncSummary::new(target.to_string(), &processed_files);
+                    println!(
+                        "{} added, {} updated, {} removed, {} failed",
+                        summary.added.len(),
+                        summary.updated.len(),
+                        summary.removed.len(),
+                        summary.failed.len()
+                    );
+                }
+            } else if summary {
+                if version.is_some() {
+                    target_url.set_content_version(version);
+                }
+                let summary = SyncSummary::new(target_url.to_string(), &processed_files);
+                println!("{}", serialise_output(&summary, output_fmt));
             } else {
                 print_serialized_output(target.to_string(), version, &processed_files, output_fmt);
             }
@@ -410,7 +466,13 @@ pub async fn files_commander(
             exists,
             progress,
             preserve,
-        } => process_get_command(safe, source, dst, exists, progress, preserve, output_fmt).await,
+            range,
+        } => {
+            process_get_command(
+                safe, source, dst, exists, progress, preserve, range, output_fmt,
+            )
+            .await
+        }
     }
 }
 
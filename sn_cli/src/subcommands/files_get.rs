@@ -10,7 +10,6 @@ use super::{
     helpers::{div_or, pluralize, processed_files_err_report, prompt_user},
     OutputFmt,
 };
-use bytes::Buf;
 use color_eyre::{eyre::bail, eyre::eyre, eyre::WrapErr, Result};
 use console::Term;
 use sn_api::{
@@ -19,11 +18,10 @@ use sn_api::{
     resolver::SafeData,
     DataType, Result as ApiResult, Safe, SafeUrl, XorUrl,
 };
-use std::{
-    collections::BTreeMap,
-    fs,
-    io::{BufWriter, Write},
-    path::Path,
+use std::{collections::BTreeMap, fs, path::Path};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
 };
 use tracing::{debug, info, trace, warn};
 
@@ -100,6 +98,57 @@ impl Default for ProgressIndicator {
     }
 }
 
+/// # Range of bytes of a file to get, from `start` up to `end`, exclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    // The size of the range of a file of `size` bytes.
+    fn size_of(&self, size: u64) -> u64 {
+        let end = self.end.map_or(size, |end| end.min(size));
+        end.saturating_sub(self.start.unwrap_or(0))
+    }
+}
+
+impl From<ByteRange> for (Option<u64>, Option<u64>) {
+    fn from(range: ByteRange) -> Self {
+        (range.start, range.end)
+    }
+}
+
+// implement FromStr for parsing "--range" arg, as "START-END" where either can be left out.
+impl std::str::FromStr for ByteRange {
+    type Err = String;
+    fn from_str(str: &str) -> Result<Self, String> {
+        let (start, end) = str
+            .split_once('-')
+            .ok_or_else(|| format!("'{}' is not a range, expected START-END", str))?;
+        let parse = |bound: &str| {
+            if bound.is_empty() {
+                Ok(None)
+            } else {
+                bound
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("'{}' is not a valid position in a file", bound))
+            }
+        };
+        let range = Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if start > end {
+                return Err(format!("range start {} is past its end {}", start, end));
+            }
+        }
+        Ok(range)
+    }
+}
+
 // processes the `safe files get` command.  called by files.rs
 //
 // dst is a local path.  defaults to "."
@@ -117,6 +166,7 @@ impl Default for ProgressIndicator {
 //
 // This command is really similar to cp or scp, and people are fine
 // using those without a report.  So it doesn't seem especially urgent.
+#[allow(clippy::too_many_arguments)]
 pub async fn process_get_command(
     safe: &Safe,
     source: XorUrl,
@@ -124,6 +174,7 @@ pub async fn process_get_command(
     exists: FileExistsAction,
     progress: ProgressIndicator,
     _preserve: bool,
+    range: Option<ByteRange>,
     _output_fmt: OutputFmt,
 ) -> Result<()> {
     let str_path = dst.unwrap_or_else(|| ".".to_string());
@@ -133,7 +184,7 @@ pub async fn process_get_command(
     let mut preserves: u64 = 0;
 
     let (_version, processed_files) =
-        files_container_get_files(safe, &source, &str_path, |status| {
+        files_container_get_files(safe, &source, &str_path, range, |status| {
             let mut overwrite = true;
             let mut mystatus = status.clone();
 
@@ -318,6 +369,7 @@ async fn files_container_get_files(
     safe: &Safe,
     url: &str,
     dirpath: &str,
+    range: Option<ByteRange>,
     callback: impl FnMut(&FilesGetStatus) -> bool,
 ) -> Result<(String, BTreeMap<String, (String, String)>)> {
     // Rather than returning a VersionHash, a String is returned, because there doesn't seem to be
@@ -347,6 +399,9 @@ async fn files_container_get_files(
 
     // Todo: This test will need to be modified once we support empty directories.
     let is_single_file = files_map.len() == 1;
+    if range.is_some() && !is_single_file {
+        bail!("A range can only be given when getting a single file");
+    }
 
     let safeurl = SafeUrl::from_url(url)?;
     let urlpath = safeurl.path_decoded()?;
@@ -360,7 +415,7 @@ async fn files_container_get_files(
     // surprising users.
     ensure_parent_dir_exists(&root)?;
 
-    let processed_files = files_map_get_files(safe, &files_map, &root, callback).await?;
+    let processed_files = files_map_get_files(safe, &files_map, &root, range, callback).await?;
     Ok((version, processed_files))
 }
 
@@ -447,6 +502,7 @@ async fn files_map_get_files(
     safe: &Safe,
     files_map: &FilesMap,
     dirpath: &str,
+    range: Option<ByteRange>,
     mut callback: impl FnMut(&FilesGetStatus) -> bool,
 ) -> Result<BTreeMap<String, (String, String)>> {
     trace!("Fetching files from FilesMap");
@@ -461,6 +517,9 @@ async fn files_map_get_files(
         .iter()
         .map(|(_path, details)| &details["size"]) // todo: use FileItem::getattr()
         .fold(0, |tot, size| tot + size.parse().unwrap_or(0));
+    if let Some(range) = range {
+        total_transfer_bytes = range.size_of(total_transfer_bytes);
+    }
 
     // Loop through files map and download each file.
     // caller may cancel individual files, but not entire transfer.
@@ -474,9 +533,12 @@ async fn files_map_get_files(
 
         // determine the file size from metadata.  string must be parsed.
         let size_str = details.getattr("size")?;
-        let size: u64 = size_str
+        let mut size: u64 = size_str
             .parse()
             .context(format!("Invalid file size: {} for {}", size_str, path))?;
+        if let Some(range) = range {
+            size = range.size_of(size);
+        }
 
         // Setup status to notify our caller of progress in callback.
         let mut status = FilesGetStatus {
@@ -532,7 +594,9 @@ async fn files_map_get_files(
         let xorurl = &details.getattr("link")?;
 
         // Download file
-        match download_file_from_net(safe, xorurl, abspath.as_path(), size).await {
+        match download_file_from_net(safe, xorurl, abspath.as_path(), size, range.map(Into::into))
+            .await
+        {
             Ok(file_bytes_written) => {
                 processed_files.insert(path.to_string(), ("+".to_string(), xorurl.to_string()));
                 transfer_bytes_written += file_bytes_written;
@@ -614,60 +678,53 @@ fn denormalize_slashes(p: &str) -> String {
     p.replace('/', &std::path::MAIN_SEPARATOR.to_string())
 }
 
-// Downloads a file from the network to a given file path
+// Downloads a file, or the range of it, from the network to a given file path,
+// writing its bytes out as they are retrieved.
 // xorurl must point to a file
 // size (in bytes) must be provided
-async fn download_file_from_net(safe: &Safe, xorurl: &str, path: &Path, size: u64) -> Result<u64> {
+async fn download_file_from_net(
+    safe: &Safe,
+    xorurl: &str,
+    path: &Path,
+    size: u64,
+    range: Range,
+) -> Result<u64> {
     debug!("downloading file {} to {}", xorurl, path.display());
+    match SafeUrl::from_url(xorurl)?.data_type() {
+        DataType::File => {}
+        _ => bail!("URL target is not immutable data"),
+    }
 
-    // TODO: download the file by concurrently (spawning tasks/threads) pulling chunks.
-    // The chunk_size can be based on https://stackoverflow.com/questions/8803515/optimal-buffer-size-for-write2
-    // which can be 4096 to match common disk block size, but that seems a bit small for the
-    // network, so I multiplied by 16. Perhaps should make it a param so caller can decide.
-    let mut rcvd: u64 = 0;
-    let mut bytes_written: u64 = 0;
-
-    let fh = file_create(path)?;
+    let fh = file_create(path).await?;
     let mut stream = BufWriter::new(fh);
 
     // gets public or private, based on xorurl type
-    let filedata = files_get(safe, xorurl, None).await?;
-    bytes_written += stream_write(&mut stream, &filedata, path)? as u64;
-    rcvd += filedata.len() as u64;
-    trace!("received {} bytes of {}", rcvd, size,);
+    let bytes_written = safe.files_stream(xorurl, range, &mut stream).await?;
+    trace!("received {} bytes of {}", bytes_written, size);
 
     // Close may generate an error, so we do a flush/sync first to detect such.
     // see https://github.com/rust-lang/rust/pull/63410#issuecomment-519965351
-    let fh = bufwriter_into_inner(stream, path)?;
-    file_sync_all(&fh, path)?;
+    stream
+        .flush()
+        .await
+        .with_context(|| format!("Error flushing file \"{}\"", path.display()))?;
+    file_sync_all(&stream.into_inner(), path).await?;
 
-    Ok(bytes_written as u64)
+    Ok(bytes_written)
 }
 
 // syncs file to filesystem.
-fn file_sync_all(f: &fs::File, path: &Path) -> Result<()> {
+async fn file_sync_all(f: &File, path: &Path) -> Result<()> {
     f.sync_all()
+        .await
         .with_context(|| format!("Error syncing file: \"{}\"", path.display(),))
 }
 
-// causes BufWriter to flush() file.
-fn bufwriter_into_inner<W: Write>(w: BufWriter<W>, path: &Path) -> Result<W> {
-    match w.into_inner() {
-        Ok(inner) => Ok(inner),
-        Err(err) => Err(eyre!("Error flushing file \"{}\": {}", path.display(), err)),
-    }
-}
-
-// Writes data to a file/stream.
-fn stream_write(writer: &mut dyn Write, data: &[u8], path: &Path) -> Result<usize> {
-    writer
-        .write(data)
-        .with_context(|| format!("Error writing to file: \"{}\"", path.display(),))
-}
-
 // Creates a file, ready for writing.
-fn file_create(path: &Path) -> Result<fs::File> {
-    fs::File::create(path).with_context(|| format!("Couldn't create file: \"{}\"", path.display(),))
+async fn file_create(path: &Path) -> Result<File> {
+    File::create(path)
+        .await
+        .with_context(|| format!("Couldn't create file: \"{}\"", path.display(),))
 }
 
 // create all directories in path if possible.
@@ -681,15 +738,3 @@ fn create_dir_all(dir_path: &Path) -> Result<()> {
     fs::create_dir_all(&dir_path)
         .with_context(|| format!("Couldn't create path: \"{}\"", dir_path.display(),))
 }
-
-/// # Get Public or Private file
-/// Get immutable files from the network.
-pub async fn files_get(safe: &Safe, url: &str, range: Range) -> Result<Vec<u8>> {
-    match SafeUrl::from_url(url)?.data_type() {
-        DataType::File => {
-            let bytes = safe.files_get(url, range).await?;
-            Ok(bytes.chunk().to_vec())
-        }
-        _ => Err(eyre!("URL target is not immutable data")),
-    }
-}
@@ -7,14 +7,17 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{helpers::serialise_output, OutputFmt};
-use crate::operations::auth_and_connect::{create_credentials_file, read_credentials};
+use crate::operations::{
+    auth_and_connect::{create_credentials_file, read_credentials},
+    config::Config,
+};
 use color_eyre::{eyre::bail, eyre::eyre, eyre::WrapErr, Result};
 use hex::encode;
 use sn_api::{
     resolver::{SafeData, SafeUrl},
-    sk_to_hex, Keypair, PublicKey, Safe, XorName,
+    sk_to_hex, DerivationPath, KeySeed, Keypair, PublicKey, Safe, XorName,
 };
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -39,10 +42,24 @@ pub enum KeysSubCommands {
         /// Set the newly created keys to be used by CLI
         #[structopt(long = "for-cli")]
         for_cli: bool,
+        /// Derive the keys from this BIP-39 mnemonic rather than generating them at random, for
+        /// them to be recreated from the mnemonic alone
+        #[structopt(long = "mnemonic", env = "SAFE_KEYS_MNEMONIC", hide_env_values = true)]
+        mnemonic: Option<String>,
+        /// Index of the keys to derive from the mnemonic, for it to back up several
+        #[structopt(long = "index", default_value = "0", requires = "mnemonic")]
+        index: u32,
     },
+    /// Generate a new BIP-39 mnemonic, to derive keys from with 'keys create --mnemonic'
+    Mnemonic,
 }
 
-pub async fn key_commander(cmd: KeysSubCommands, output_fmt: OutputFmt, safe: &Safe) -> Result<()> {
+pub async fn key_commander(
+    cmd: KeysSubCommands,
+    output_fmt: OutputFmt,
+    safe: &Safe,
+    config: &Config,
+) -> Result<()> {
     match cmd {
         KeysSubCommands::Show { show_sk, keyurl } => {
             if let Some(url) = keyurl {
@@ -76,7 +93,7 @@ pub async fn key_commander(cmd: KeysSubCommands, output_fmt: OutputFmt, safe: &S
                     Err(err) => bail!(err),
                 }
             } else {
-                match read_credentials()? {
+                match read_credentials(config).await? {
                     (file_path, Some(keypair)) => {
                         let xorname = XorName::from(keypair.public_key());
                         let xorurl = SafeUrl::encode_safekey(xorname, safe.xorurl_base)?;
@@ -95,13 +112,20 @@ pub async fn key_commander(cmd: KeysSubCommands, output_fmt: OutputFmt, safe: &S
 
             Ok(())
         }
-        KeysSubCommands::Create { for_cli, .. } => {
-            let (xorurl, key_pair) = create_new_key(safe).await?;
+        KeysSubCommands::Create {
+            for_cli,
+            mnemonic,
+            index,
+        } => {
+            let (xorurl, key_pair) = match mnemonic {
+                Some(mnemonic) => derive_key(safe, &mnemonic, index)?,
+                None => create_new_key(safe).await?,
+            };
             print_new_key_output(output_fmt, xorurl, Some(&key_pair));
 
             if for_cli {
                 println!("Setting new SafeKey to be used by CLI...");
-                let (mut file, file_path) = create_credentials_file()?;
+                let (mut file, file_path) = create_credentials_file(config).await?;
                 let serialised_keypair = serde_json::to_string(&key_pair)
                     .wrap_err("Unable to serialise the credentials created")?;
 
@@ -119,6 +143,16 @@ pub async fn key_commander(cmd: KeysSubCommands, output_fmt: OutputFmt, safe: &S
 
             Ok(())
         }
+        KeysSubCommands::Mnemonic => {
+            let mnemonic = KeySeed::generate_mnemonic()?;
+            if OutputFmt::Pretty == output_fmt {
+                println!("New mnemonic generated, write it down and keep it safe:");
+                println!("{}", mnemonic);
+            } else {
+                println!("{}", serialise_output(&mnemonic, output_fmt));
+            }
+            Ok(())
+        }
     }
 }
 
@@ -163,6 +197,18 @@ pub fn keypair_to_hex_strings(keypair: &Keypair) -> Result<(String, String)> {
     Ok((pk_hex, sk_hex))
 }
 
+/// Derives the `index`-th SafeKey of `mnemonic`.
+pub fn derive_key(safe: &Safe, mnemonic: &str, index: u32) -> Result<(String, Keypair)> {
+    let key_seed = KeySeed::from_mnemonic(mnemonic, "").context("Invalid mnemonic")?;
+    let key_pair = Keypair::Ed25519(Arc::new(
+        key_seed.ed25519_keypair(&DerivationPath::client(index)),
+    ));
+
+    let xorname = XorName::from(key_pair.public_key());
+    let xorurl = SafeUrl::encode_safekey(xorname, safe.xorurl_base)?;
+    Ok((xorurl, key_pair))
+}
+
 #[cfg(feature = "testing")]
 pub async fn create_new_key(safe: &Safe) -> Result<(String, Keypair)> {
     // '--pay-with' is either a Wallet XOR-URL, or a secret key
@@ -101,6 +101,16 @@ pub enum SubCommands {
     )]
     /// Perform setup tasks
     Setup(setup::SetupSubCommands),
+    #[structopt(
+        name = "completions",
+        no_version,
+        global_settings(&[AppSettings::DisableVersion]),
+    )]
+    /// Generate the completion script of a shell for the CLI
+    Completions {
+        /// one of: [bash, fish, zsh, powershell, elvish]
+        shell: structopt::clap::Shell,
+    },
     #[structopt(
         name = "nrs",
         no_version,
@@ -6,11 +6,13 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::operations::config::{Config, NetworkInfo};
+use crate::operations::config::{Config, NetworkCheck, NetworkInfo, NetworkStatus};
 use color_eyre::{eyre::bail, eyre::eyre, Result};
+use comfy_table::Table;
 use sn_api::PublicKey;
 use std::collections::BTreeSet;
 use std::net::SocketAddr;
+use std::time::Duration;
 use structopt::StructOpt;
 use tracing::debug;
 
@@ -24,7 +26,14 @@ pub enum NetworksSubCommands {
     },
     #[structopt(name = "check")]
     /// Check current network configuration and try to match it to networks in the CLI config
-    Check {},
+    Check {
+        /// Also check every network in the CLI config can be reached, by bootstrapping to them all
+        #[structopt(long)]
+        all: bool,
+        /// How long to wait for each network to be bootstrapped to, in seconds
+        #[structopt(long, default_value = "60")]
+        timeout: u64,
+    },
     #[structopt(name = "add")]
     /// Add a network to the CLI config using an existing network configuration file
     Add {
@@ -68,7 +77,23 @@ pub async fn networks_commander(
                 network_name
             );
         }
-        Some(NetworksSubCommands::Check {}) => {
+        Some(NetworksSubCommands::Check { all: true, timeout }) => {
+            println!("Checking every network in the config can be reached...");
+            let checks = config.check_networks(Duration::from_secs(timeout)).await;
+            print_network_checks(&checks);
+            let unreachable = checks
+                .iter()
+                .filter(|check| !matches!(check.status, NetworkStatus::Reachable { .. }))
+                .count();
+            if unreachable > 0 {
+                bail!(
+                    "{} of the {} networks in the config couldn't be reached",
+                    unreachable,
+                    checks.len()
+                );
+            }
+        }
+        Some(NetworksSubCommands::Check { .. }) => {
             println!("Checking current setup network connection information...");
             let (node_config_path, current_node_config) = config.read_current_node_config().await?;
             let mut matched_network = None;
@@ -139,6 +164,47 @@ pub async fn networks_commander(
     Ok(())
 }
 
+fn print_network_checks(checks: &[NetworkCheck]) {
+    let mut table = Table::new();
+    table.add_row(vec![
+        "Network name",
+        "Reachable",
+        "Genesis key",
+        "Latency",
+        "Notes",
+    ]);
+    for check in checks {
+        let genesis_key = check
+            .genesis_key
+            .map_or_else(String::new, |key| format!("{:?}", key));
+        let (reachable, latency, notes) = match &check.status {
+            NetworkStatus::Reachable { latency, elders } => (
+                "yes",
+                format!("{}ms", latency.as_millis()),
+                format!("reached a section of {} elders", elders),
+            ),
+            NetworkStatus::Unreachable(err) => (
+                "no",
+                String::new(),
+                format!("connection info may be stale: {}", err),
+            ),
+            NetworkStatus::ConnInfoUnavailable(err) => (
+                "no",
+                String::new(),
+                format!("stale connection info: {}", err),
+            ),
+        };
+        table.add_row(vec![
+            check.name.as_str(),
+            reachable,
+            &genesis_key,
+            &latency,
+            &notes,
+        ]);
+    }
+    println!("{table}");
+}
+
 #[cfg(test)]
 mod networks_set_command {
     use super::networks_commander;
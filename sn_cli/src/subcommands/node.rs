@@ -6,15 +6,19 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::operations::{config::Config, config::NetworkLauncher, node::*};
+use super::{helpers::serialise_output, OutputFmt};
+use crate::operations::{
+    auth_and_connect::connect, config::Config, config::NetworkLauncher, node::*,
+};
 use color_eyre::{eyre::eyre, Help, Result};
-use sn_api::PublicKey;
-use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf};
+use sn_api::{PublicKey, Safe};
+use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf, time::Duration};
 use structopt::StructOpt;
 use tracing::debug;
 
 const NODES_DATA_DIR_NAME: &str = "baby-fleming-nodes";
 const LOCAL_NODE_DIR_NAME: &str = "local-node";
+const WALLET_QUERY_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(StructOpt, Debug)]
 pub enum NodeSubCommands {
@@ -125,6 +129,24 @@ pub enum NodeSubCommands {
         #[structopt(long = "node-path", env = "SN_NODE_PATH")]
         node_path: Option<PathBuf>,
     },
+    #[structopt(name = "status")]
+    /// Query the status of a node running locally: its name, prefix, age, role, used space,
+    /// connected peers and reward key. The node must be run with `--control-sockets`, or with
+    /// `--rpc-addr`.
+    Status {
+        /// Root dir of the node, where its diagnostics socket is (default is the one of the node
+        /// started with `node join`, ~/.safe/node/local-node).
+        #[structopt(long = "root-dir")]
+        root_dir: Option<PathBuf>,
+        /// Loopback address the node serves RPCs on, if it was run with `--rpc-addr`, to query
+        /// it there rather than on its socket.
+        #[structopt(long = "rpc-addr")]
+        rpc_addr: Option<SocketAddr>,
+        /// URL of the wallet the node's rewards are paid into, to query its balance too. This
+        /// connects to the network.
+        #[structopt(long = "reward-wallet")]
+        reward_wallet: Option<String>,
+    },
     #[structopt(name = "update")]
     /// Update to latest sn_node released version
     Update {
@@ -137,6 +159,7 @@ pub enum NodeSubCommands {
 
 pub async fn node_commander(
     cmd: Option<NodeSubCommands>,
+    output_fmt: OutputFmt,
     config: &mut Config,
     network_launcher: &mut Box<impl NetworkLauncher>,
 ) -> Result<()> {
@@ -247,11 +270,63 @@ pub async fn node_commander(
             Ok(())
         }
         Some(NodeSubCommands::Killall { node_path }) => node_shutdown(node_path),
+        Some(NodeSubCommands::Status {
+            root_dir,
+            rpc_addr,
+            reward_wallet,
+        }) => {
+            let rpc = match rpc_addr {
+                Some(addr) => NodeRpc::Tcp(addr),
+                None => {
+                    let root_dir = root_dir.unwrap_or_else(|| {
+                        let mut default_node_dir_path = config.node_config_path.clone();
+                        default_node_dir_path.pop();
+                        default_node_dir_path.join(LOCAL_NODE_DIR_NAME)
+                    });
+                    NodeRpc::Socket(root_dir.join(NODE_DIAGNOSTICS_SOCKET))
+                }
+            };
+            let mut status = node_status(&rpc).await?;
+            if let Some(wallet) = reward_wallet {
+                let mut safe = Safe::dry_runner(None);
+                safe.dry_run_mode = false;
+                connect(&mut safe, config.clone(), WALLET_QUERY_TIMEOUT).await?;
+                status.reward_balance = Some(safe.wallet_balance(&wallet).await?.to_string());
+            }
+            print_node_status(&status, output_fmt);
+            Ok(())
+        }
         Some(NodeSubCommands::Update { node_path }) => node_update(node_path),
         None => Err(eyre!("Missing node subcommand")),
     }
 }
 
+fn print_node_status(status: &NodeStatus, output_fmt: OutputFmt) {
+    if OutputFmt::Pretty != output_fmt {
+        println!("{}", serialise_output(status, output_fmt));
+        return;
+    }
+    println!("Name: {}", status.name);
+    println!("Prefix: {}", status.prefix);
+    println!("Age: {}", status.age);
+    println!("Role: {}", status.role);
+    println!(
+        "Used space: {} of {} bytes",
+        status.used_space, status.max_capacity
+    );
+    println!(
+        "Connected peers: {} ({} section members)",
+        status.connected_peers, status.section_members
+    );
+    println!(
+        "Reward key: {}",
+        status.reward_key.as_deref().unwrap_or("none")
+    );
+    if let Some(balance) = &status.reward_balance {
+        println!("Reward wallet balance: {} safecoins", balance);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::operations::config::NetworkLauncher;
@@ -278,7 +353,7 @@ mod test {
 #[cfg(test)]
 mod run_command {
     use super::test::FakeNetworkLauncher;
-    use super::{node_commander, NodeSubCommands, NODES_DATA_DIR_NAME};
+    use super::{node_commander, NodeSubCommands, OutputFmt, NODES_DATA_DIR_NAME};
     use crate::operations::config::{Config, NetworkInfo};
     use crate::operations::node::SN_NODE_EXECUTABLE;
     use assert_fs::prelude::*;
@@ -330,7 +405,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
 
@@ -371,7 +446,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--node-path"));
@@ -411,7 +486,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--nodes-dir"));
@@ -449,7 +524,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--nodes-dir"));
@@ -490,7 +565,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         node_data_dir.assert(predicates::path::is_dir());
@@ -524,7 +599,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--interval"));
@@ -559,7 +634,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--num-nodes"));
@@ -594,7 +669,7 @@ mod run_command {
             ip: Some("10.10.0.1".to_string()),
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--ip"));
@@ -629,7 +704,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--local"));
@@ -665,7 +740,7 @@ mod run_command {
             ip: None,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert_eq!(config.networks_iter().count(), 1);
@@ -693,7 +768,7 @@ mod run_command {
 #[cfg(test)]
 mod join_command {
     use super::test::FakeNetworkLauncher;
-    use super::{node_commander, NodeSubCommands, LOCAL_NODE_DIR_NAME};
+    use super::{node_commander, NodeSubCommands, OutputFmt, LOCAL_NODE_DIR_NAME};
     use crate::operations::config::Config;
     use crate::operations::node::SN_NODE_EXECUTABLE;
     use assert_fs::prelude::*;
@@ -752,7 +827,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher
@@ -807,7 +882,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--node-path"));
@@ -853,7 +928,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--nodes-dir"));
@@ -900,7 +975,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--nodes-dir"));
@@ -946,7 +1021,7 @@ mod join_command {
             skip_auto_port_forwarding: true,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher
@@ -989,7 +1064,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--local-addr"));
@@ -1033,7 +1108,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--public-addr"));
@@ -1074,7 +1149,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--clear-data"));
@@ -1114,7 +1189,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "-yyy"));
@@ -1164,7 +1239,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher
@@ -1227,7 +1302,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_err());
         let error = result.unwrap_err();
@@ -1272,7 +1347,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         node_data_dir.assert(predicates::path::is_dir());
@@ -1323,7 +1398,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_ok());
         assert!(launcher.launch_args.iter().any(|x| x == "--genesis-key"));
@@ -1375,7 +1450,7 @@ mod join_command {
             skip_auto_port_forwarding: false,
         };
 
-        let result = node_commander(Some(cmd), &mut config, &mut launcher).await;
+        let result = node_commander(Some(cmd), OutputFmt::Pretty, &mut config, &mut launcher).await;
 
         assert!(result.is_err());
         let error = result.unwrap_err();
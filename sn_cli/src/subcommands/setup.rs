@@ -31,6 +31,11 @@ pub fn setup_commander(cmd: SetupSubCommands, output_fmt: OutputFmt) -> Result<(
     }
 }
 
+// handles 'completions <shell>' command, the same as 'setup completions <shell>'.
+pub fn completions_commander(shell: clap::Shell, output_fmt: OutputFmt) -> Result<()> {
+    setup_completions_dumpone(shell, output_fmt)
+}
+
 // differentiates between 'setup completions' and 'setup completions <shell>'
 fn setup_completions(shell: Option<clap::Shell>, output_fmt: OutputFmt) -> Result<()> {
     match shell {
@@ -0,0 +1,31 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use assert_cmd::Command;
+use color_eyre::Result;
+use predicates::prelude::*;
+
+#[test]
+fn completions_should_cover_nested_subcommands() -> Result<()> {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let mut cmd = Command::cargo_bin("safe")?;
+        cmd.args(["completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("config"))
+            .stdout(predicate::str::contains("switch"));
+    }
+    Ok(())
+}
+
+#[test]
+fn completions_should_fail_for_an_unknown_shell() -> Result<()> {
+    let mut cmd = Command::cargo_bin("safe")?;
+    cmd.args(["completions", "csh"]).assert().failure();
+    Ok(())
+}
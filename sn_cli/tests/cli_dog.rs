@@ -10,12 +10,12 @@ use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
 use color_eyre::{eyre::eyre, Result};
 use predicates::prelude::*;
-use sn_api::resolver::{SafeData, SafeUrl};
+use sn_api::resolver::{DataLocation, SafeData, SafeUrl};
 use sn_cmd_test_utilities::util::{
     create_and_get_keys, get_random_nrs_string, parse_files_put_or_sync_output,
     parse_wallet_create_output, safe_cmd, safe_cmd_stdout, upload_path,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const TEST_FILE: &str = "../resources/testdata/test.md";
 
@@ -43,6 +43,36 @@ fn calling_safe_dog_files_container_nrsurl() -> Result<()> {
     }
 }
 
+#[test]
+fn calling_safe_dog_file_deep() -> Result<()> {
+    let content = safe_cmd_stdout(["files", "put", TEST_FILE, "--json"], Some(0))?;
+    let (_, processed_files) = parse_files_put_or_sync_output(&content)?;
+    let file_xorurl = processed_files[Path::new(TEST_FILE)]
+        .link()
+        .ok_or_else(|| eyre!("Missing xorurl link of uploaded test file"))?
+        .to_string();
+    let file_url = SafeUrl::from_url(&file_xorurl)?;
+
+    let dog_output = safe_cmd_stdout(["dog", &file_xorurl, "--deep", "--json"], Some(0))?;
+    let (url, _, location): (String, Vec<SafeData>, DataLocation) =
+        serde_json::from_str(&dog_output).expect("Failed to parse output of `safe dog --deep`");
+    assert_eq!(url, file_xorurl);
+    assert_eq!(location.xorname, file_url.xorname());
+    assert!(location.section_prefix.matches(&location.xorname));
+    assert!(!location.holders.is_empty());
+    let chunks = location
+        .chunks
+        .ok_or_else(|| eyre!("No chunks listed for a file"))?;
+    let size: usize = chunks.iter().map(|(_, size)| size).sum();
+    assert_eq!(
+        size as u64,
+        std::fs::metadata(TEST_FILE)?.len(),
+        "chunks should hold the whole file"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn calling_safe_dog_files_container_nrsurl_with_safe_prefix() -> Result<()> {
     let content = safe_cmd_stdout(["files", "put", TEST_FILE, "--json"], Some(0))?;
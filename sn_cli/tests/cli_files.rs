@@ -264,6 +264,53 @@ fn calling_safe_files_sync() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn calling_safe_files_sync_with_summary() -> Result<()> {
+    let tmp_data_dir = assert_fs::TempDir::new()?;
+    tmp_data_dir.copy_from("../resources/testdata", &["**"])?;
+    let content = safe_cmd_stdout(
+        [
+            "files",
+            "put",
+            &tmp_data_dir.path().display().to_string(),
+            "--recursive",
+            "--json",
+        ],
+        Some(0),
+    )?;
+    let (container_xorurl, _) = parse_files_put_or_sync_output(&content)?;
+    let mut url = SafeUrl::from_url(&container_xorurl)?;
+    url.set_content_version(None);
+
+    // One file grows, one is new, and one goes away.
+    tmp_data_dir
+        .child("test.md")
+        .write_str("hello tests! and some more")?;
+    tmp_data_dir.child("new.md").write_str("a new file")?;
+    std::fs::remove_file(tmp_data_dir.child("another.md").path())?;
+
+    let output = safe_cmd_stdout(
+        [
+            "files",
+            "sync",
+            &format!("{}/", tmp_data_dir.path().display()),
+            &url.to_string(),
+            "--recursive",
+            "--delete",
+            "--summary",
+            "--json",
+        ],
+        Some(0),
+    )?;
+    let summary: serde_json::Value = serde_json::from_str(&output)?;
+    let count = |change: &str| summary[change].as_array().map(|paths| paths.len());
+    assert_eq!(count("added"), Some(1));
+    assert_eq!(count("updated"), Some(1));
+    assert_eq!(count("removed"), Some(1));
+    assert_eq!(summary["failed"], serde_json::json!({}));
+    Ok(())
+}
+
 #[test]
 #[ignore = "dry-run issue"]
 fn calling_safe_files_sync_dry_run() -> Result<()> {
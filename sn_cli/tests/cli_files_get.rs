@@ -1695,6 +1695,80 @@ fn files_get_src_is_file_and_dst_newname_not_existing() -> Result<()> {
     Ok(())
 }
 
+/// Synopsis
+/// --------
+/// Test:  safe files get ../resources/testdata/large_markdown_file.md /tmp/newname --range=10-1000
+///    src is a file
+///    dst does not exist
+///    expected result: bytes 10 up to 1000 of ../resources/testdata/large_markdown_file.md
+///    match /tmp/newname
+///
+/// Details
+/// -------
+/// Scenario: the `files get` command downloads only a range of a file when the source is a file.
+///
+/// Given a unique `tmp_data_path` directory is created
+/// And the contents of `tmp_data_path` are uploaded using the `files put tmp_data_path/ --recursive` command
+/// And `src` is set to the `files_container_xor/large_markdown_file.md`
+/// And a dst is set to `dest/newname`
+///
+/// When the `files get src dst --range=10-1000 --exists=overwrite --progress=none` command runs
+///
+/// Then `dest/newname` should have the bytes 10 up to 1000 of `large_markdown_file.md`
+/// And getting the whole files container with a range should fail.
+#[test]
+fn files_get_src_is_file_with_range() -> Result<()> {
+    // Arrange
+    let with_trailing_slash = true;
+    let tmp_data_path = assert_fs::TempDir::new()?;
+    tmp_data_path.copy_from("../resources/testdata", &["**"])?;
+    let (files_container_xor, _processed_files, _) =
+        upload_path(&tmp_data_path, with_trailing_slash)?;
+
+    let mut url = SafeUrl::from_url(&files_container_xor)?;
+    url.set_content_version(None);
+    let container = url.to_string();
+    url.set_path("large_markdown_file.md");
+    let src = url.to_string();
+
+    let dst = assert_fs::TempDir::new()?;
+    let dst = format!("{}/newname", dst.path().display());
+
+    // Act
+    safe_cmd(
+        [
+            "files",
+            "get",
+            &src,
+            &dst,
+            "--range=10-1000",
+            "--exists=overwrite",
+            "--progress=none",
+        ],
+        Some(0),
+    )?;
+
+    // Assert
+    let original = std::fs::read("../resources/testdata/large_markdown_file.md")?;
+    assert_eq!(std::fs::read(&dst)?, original[10..1000]);
+
+    let dst = assert_fs::TempDir::new()?;
+    safe_cmd(
+        [
+            "files",
+            "get",
+            &container,
+            &dst.path().display().to_string(),
+            "--range=10-1000",
+            "--exists=overwrite",
+            "--progress=none",
+        ],
+        Some(1),
+    )?;
+
+    Ok(())
+}
+
 /// Synopsis
 /// --------
 /// Test:  safe files get <src> /tmp/newname
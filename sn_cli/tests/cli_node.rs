@@ -11,6 +11,11 @@ use assert_fs::prelude::*;
 use color_eyre::Result;
 use predicates::prelude::*;
 use sn_cmd_test_utilities::util::get_sn_node_latest_released_version;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener},
+    thread,
+};
 
 #[cfg(not(target_os = "windows"))]
 pub(crate) const SN_NODE_BIN_NAME: &str = "sn_node";
@@ -90,3 +95,70 @@ fn node_install_should_install_to_a_specific_location() -> Result<()> {
     node_bin_path.assert(predicate::path::is_file());
     Ok(())
 }
+
+// Serves a node's replies to the cmds `node status` sends, one connection per cmd, on a
+// loopback address.
+fn fake_node_rpc() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let _handle = thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            if reader.read_line(&mut request).is_err() {
+                continue;
+            }
+            let reply = if request.contains("\"status\"") {
+                r#"{"ok": {"name": "3a5f9c", "prefix": "Prefix(01)", "age": 7, "joined": true, "is_elder": true, "reward_key": "ab12"}}"#
+            } else if request.contains("\"storage\"") {
+                r#"{"ok": {"used_bytes": 1024, "max_capacity_bytes": 2048}}"#
+            } else if request.contains("\"peers\"") {
+                r#"{"ok": {"connected_peers": 12, "section_members": 9}}"#
+            } else {
+                r#"{"error": {"kind": "unknown_command", "message": "No such cmd"}}"#
+            };
+            let _res = writeln!(reader.get_mut(), "{}", reply);
+        }
+    });
+    Ok(addr)
+}
+
+#[test]
+fn node_status_should_print_what_the_node_reports() -> Result<()> {
+    let addr = fake_node_rpc()?;
+
+    let mut cmd = Command::cargo_bin("safe")?;
+    cmd.args(["node", "status", "--rpc-addr", &addr.to_string()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Name: 3a5f9c"))
+        .stdout(predicate::str::contains("Role: elder"))
+        .stdout(predicate::str::contains("Used space: 1024 of 2048 bytes"))
+        .stdout(predicate::str::contains("Connected peers: 12"));
+
+    let mut cmd = Command::cargo_bin("safe")?;
+    let output = cmd
+        .args(["node", "status", "--rpc-addr", &addr.to_string(), "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(status["age"], 7);
+    assert_eq!(status["prefix"], "Prefix(01)");
+    assert_eq!(status["reward_key"], "ab12");
+    assert_eq!(status["reward_balance"], serde_json::Value::Null);
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn node_status_should_fail_without_a_node_running() -> Result<()> {
+    let temp_dir = assert_fs::TempDir::new()?;
+
+    let mut cmd = Command::cargo_bin("safe")?;
+    cmd.args(["node", "status", "--root-dir"])
+        .arg(temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to connect to the node"));
+    Ok(())
+}
@@ -12,7 +12,7 @@ use backoff::{backoff::Backoff, ExponentialBackoff};
 use bytes::Bytes;
 use sn_interface::messaging::{
     data::{DataCmd, ServiceMsg},
-    ServiceAuth, WireMsg,
+    MsgId, ServiceAuth, WireMsg,
 };
 use sn_interface::types::{PublicKey, Signature};
 use tokio::time::Duration;
@@ -60,12 +60,16 @@ impl Client {
         let span = info_span!("Attempting a cmd");
         let _ = span.enter();
 
+        // The same msg id on every attempt, for elders which already applied the cmd to answer
+        // it again rather than apply it twice.
+        let msg_id = MsgId::new();
         let mut attempt = 1.0;
         loop {
             debug!("Attempting {:?} (attempt #{})", debug_cmd, attempt);
 
             let res = self
-                .send_signed_cmd(
+                .send_signed_cmd_with_id(
+                    msg_id,
                     dst_name,
                     client_pk,
                     serialised_cmd.clone(),
@@ -78,6 +82,14 @@ impl Client {
                 break Ok(cmd_result);
             }
 
+            if let Err(Error::ErrorCmd {
+                retryable: false, ..
+            }) = res
+            {
+                debug!("Not retrying {debug_cmd}, as the error it failed with isn't retryable");
+                break res;
+            }
+
             trace!(
                 "Failed response on {debug_cmd} attempt #{attempt}: {:?}",
                 res
@@ -104,6 +116,25 @@ impl Client {
         client_pk: PublicKey,
         serialised_cmd: Bytes,
         signature: Signature,
+    ) -> Result<(), Error> {
+        self.send_signed_cmd_with_id(
+            MsgId::new(),
+            dst_address,
+            client_pk,
+            serialised_cmd,
+            signature,
+        )
+        .await
+    }
+
+    // Sends a signed DataCmd to the network as the msg `msg_id`.
+    async fn send_signed_cmd_with_id(
+        &self,
+        msg_id: MsgId,
+        dst_address: XorName,
+        client_pk: PublicKey,
+        serialised_cmd: Bytes,
+        signature: Signature,
     ) -> Result<(), Error> {
         let auth = ServiceAuth {
             public_key: client_pk,
@@ -111,7 +142,7 @@ impl Client {
         };
 
         self.session
-            .send_cmd(dst_address, auth, serialised_cmd)
+            .send_cmd(msg_id, dst_address, auth, serialised_cmd)
             .await
     }
 
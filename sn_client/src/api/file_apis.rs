@@ -16,13 +16,22 @@ use sn_interface::types::{BytesAddress, Chunk, ChunkAddress, Encryption, PublicK
 
 use bincode::deserialize;
 use bytes::Bytes;
-use futures::future::join_all;
+use futures::{
+    future::join_all,
+    stream::{self, StreamExt},
+};
 use itertools::Itertools;
 use self_encryption::{self, ChunkInfo, DataMap, EncryptedChunk};
-use tokio::task;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    task,
+};
 use tracing::trace;
 use xor_name::XorName;
 
+// Number of chunks fetched ahead of the one being written out, when streaming.
+const CHUNK_READ_AHEAD: usize = 4;
+
 struct HeadChunk {
     chunk: Chunk,
     address: BytesAddress,
@@ -104,6 +113,91 @@ impl Client {
         Ok(bytes)
     }
 
+    /// Reads bytes from the network into `writer`, fetching only the chunks covering the `length`
+    /// bytes from `position`, and writing out the bytes of each chunk as soon as it's decrypted,
+    /// in order, so that the data is never held in memory whole.
+    ///
+    /// As with [`Client::read_from`], `length` is just an upper limit.
+    /// Returns the number of bytes written.
+    #[instrument(skip(self, writer), level = "trace")]
+    pub async fn stream_from<W: AsyncWrite + Unpin>(
+        &self,
+        address: BytesAddress,
+        position: usize,
+        length: usize,
+        writer: &mut W,
+    ) -> Result<usize> {
+        let chunk = self.get_chunk(address.name()).await?;
+
+        let data_map = match self
+            .unpack_head_chunk(HeadChunk {
+                chunk: chunk.clone(),
+                address,
+            })
+            .await
+        {
+            Ok(data_map) => data_map,
+            Err(_) => {
+                // A SmallFile is a single chunk, so there's nothing to stream.
+                let mut bytes = self.get_bytes(chunk, address.scope())?;
+                let _ = bytes.split_to(position.min(bytes.len()));
+                bytes.truncate(length);
+                writer.write_all(&bytes).await?;
+                writer.flush().await?;
+                return Ok(bytes.len());
+            }
+        };
+
+        let data_map = &data_map;
+        let mut parts = stream::iter(covering_chunks(data_map, position, length))
+            .map(|(info, skip, take)| async move {
+                let chunk = self.get_chunk(&info.dst_hash).await?;
+                let encrypted = EncryptedChunk {
+                    index: info.index,
+                    content: chunk.value().clone(),
+                };
+                let bytes = self_encryption::decrypt_range(data_map, &[encrypted], skip, take)?;
+                Ok::<_, Error>(bytes)
+            })
+            .buffered(CHUNK_READ_AHEAD);
+
+        let mut written = 0;
+        while let Some(bytes) = parts.next().await {
+            let bytes = bytes?;
+            writer.write_all(&bytes).await?;
+            written += bytes.len();
+        }
+        writer.flush().await?;
+
+        Ok(written)
+    }
+
+    /// Lists the chunks holding the bytes at `address`, in order, with the number of bytes of
+    /// the data each holds. Bytes small enough not to be self-encrypted are held whole by the
+    /// chunk at `address`.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn data_chunks(&self, address: BytesAddress) -> Result<Vec<(XorName, usize)>> {
+        let chunk = self.get_chunk(address.name()).await?;
+
+        match self
+            .unpack_head_chunk(HeadChunk {
+                chunk: chunk.clone(),
+                address,
+            })
+            .await
+        {
+            Ok(data_map) => Ok(data_map
+                .infos()
+                .into_iter()
+                .map(|info| (info.dst_hash, info.src_size))
+                .collect()),
+            Err(_) => {
+                let bytes = self.get_bytes(chunk, address.scope())?;
+                Ok(vec![(*address.name(), bytes.len())])
+            }
+        }
+    }
+
     #[instrument(skip(self), level = "trace")]
     pub(crate) async fn get_chunk(&self, name: &XorName) -> Result<Chunk> {
         // first check it's not already in our Chunks' cache
@@ -374,6 +468,32 @@ impl Client {
     }
 }
 
+// The chunks of `data_map` covering `length` bytes from `position`, in order, each with the
+// position within its content to start from, and how many bytes of it to take.
+fn covering_chunks(
+    data_map: &DataMap,
+    position: usize,
+    length: usize,
+) -> Vec<(ChunkInfo, usize, usize)> {
+    let end = position.saturating_add(length);
+    let mut chunk_start = 0;
+    let mut covering = vec![];
+    for info in data_map
+        .infos()
+        .into_iter()
+        .sorted_by_key(|info| info.index)
+    {
+        let chunk_end = chunk_start + info.src_size;
+        if chunk_end > position && chunk_start < end {
+            let skip = position.saturating_sub(chunk_start);
+            let take = end.min(chunk_end) - chunk_start - skip;
+            covering.push((info, skip, take));
+        }
+        chunk_start = chunk_end;
+    }
+    covering
+}
+
 #[cfg(test)]
 mod tests {
     use crate::utils::test_utils::create_test_client_with;
@@ -464,6 +584,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn covering_chunks_decrypt_to_exactly_the_range() -> Result<()> {
+        let size = 3 * LARGE_FILE_SIZE_MIN;
+        let bytes = random_bytes(size);
+        let (data_map, encrypted_chunks) = self_encryption::encrypt(bytes.clone())?;
+
+        for (pos, len) in [
+            (0, size),
+            (0, 1),
+            (1, 10),
+            (size / 3 - 1, 2),
+            (size / 2, usize::MAX),
+        ] {
+            let covering = super::covering_chunks(&data_map, pos, len);
+            let mut read = vec![];
+            for (info, skip, take) in covering {
+                let encrypted = encrypted_chunks[info.index].clone();
+                read.extend(self_encryption::decrypt_range(
+                    &data_map,
+                    &[encrypted],
+                    skip,
+                    take,
+                )?);
+            }
+            compare(
+                bytes.slice(pos..size.min(pos.saturating_add(len))),
+                read.into(),
+            )?;
+        }
+        assert!(super::covering_chunks(&data_map, size, 1).is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn seek_with_unknown_length() -> Result<()> {
         init_test_logger();
@@ -530,6 +684,33 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_range_of_data() -> Result<()> {
+        init_test_logger();
+        let _outer_span = tracing::info_span!("stream_range_of_data").entered();
+        let client = create_test_client().await?;
+
+        let size = 4 * LARGE_FILE_SIZE_MIN;
+        let file = LargeFile::new(random_bytes(size))?;
+        let (address, _) = client
+            .upload_and_verify(file.bytes(), Scope::Public)
+            .await?;
+
+        for (pos, len) in [(0, size), (size / 3, size / 2), (size - 10, usize::MAX)] {
+            let mut read_data = vec![];
+            let written = client
+                .stream_from(address, pos, len, &mut read_data)
+                .await?;
+            assert_eq!(written, read_data.len());
+            compare(
+                file.bytes().slice(pos..size.min(pos.saturating_add(len))),
+                read_data.into(),
+            )?;
+        }
+
+        Ok(())
+    }
+
     // Test storing and reading 5mb file. Try and read from many clients and ensure we do not overwelm nodes.
     #[tokio::test(flavor = "multi_thread")]
     async fn store_and_read_5mb_from_many_clients() -> Result<()> {
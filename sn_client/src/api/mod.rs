@@ -11,8 +11,10 @@ mod data;
 mod file_apis;
 mod queries;
 mod register_apis;
+mod upload_session;
 
 pub use register_apis::RegisterWriteAheadLog;
+pub use upload_session::{UploadSession, DEFAULT_UPLOAD_PARALLELISM};
 
 use crate::{connections::Session, errors::Error, ClientConfig};
 use sn_interface::messaging::{
@@ -195,20 +197,18 @@ impl Client {
 
         let (random_dst_addr, auth, serialised_cmd) = generate_probe_msg(&client, client_pk)?;
 
+        // these peers will be nonsense peers, and dropped after we connect. Replaced by whatever SectionAuthorityProvider peers we have received
+        // therefore we use a random name for them initially
+        let fallback_nodes = bootstrap_nodes
+            .iter()
+            .copied()
+            .map(|socket| Peer::new(xor_name::rand::random(), socket))
+            .collect_vec();
         // either use our known prefixmap elders, or fallback to plain node config file
-        let bootstrap_nodes = {
-            if let Some(sap) = prefix_map.closest_or_opposite(&xor_name::rand::random(), None) {
-                sap.elders_vec()
-            } else {
-                // these peers will be nonsense peers, and dropped after we connect. Replaced by whatever SectionAuthorityProvider peers we have received
-                // therefore we use a random name for them initially
-                bootstrap_nodes
-                    .iter()
-                    .copied()
-                    .map(|socket| Peer::new(xor_name::rand::random(), socket))
-                    .collect_vec()
-            }
-        };
+        let mut bootstrap_nodes = prefix_map
+            .closest_or_opposite(&xor_name::rand::random(), None)
+            .map(|sap| sap.elders_vec())
+            .unwrap_or_else(|| fallback_nodes.clone());
 
         let mut attempts = 0;
         let mut initial_probe = client
@@ -234,7 +234,14 @@ impl Client {
 
             attempts += 1;
 
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            // The elders we knew of may have all gone since, so we turn to the nodes we were
+            // given, which are there to tell us of those there are now.
+            if bootstrap_nodes != fallback_nodes && !fallback_nodes.is_empty() {
+                info!("Falling back to bootstrapping from {:?}", fallback_nodes);
+                bootstrap_nodes = fallback_nodes.clone();
+            } else {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
 
             let (random_dst_addr, auth, serialised_cmd) = generate_probe_msg(&client, client_pk)?;
 
@@ -273,6 +280,12 @@ impl Client {
     pub fn public_key(&self) -> PublicKey {
         self.keypair().public_key()
     }
+
+    /// Return the key and elders of the section closest to `name` that the client knows of,
+    /// if it knows of any yet.
+    pub fn closest_known_section(&self, name: &XorName) -> Option<(bls::PublicKey, Vec<Peer>)> {
+        self.session.closest_known_section(name)
+    }
 }
 
 #[cfg(test)]
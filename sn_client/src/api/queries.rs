@@ -11,11 +11,12 @@ use crate::{connections::QueryResult, errors::Error};
 use bytes::Bytes;
 use rand::Rng;
 use sn_interface::messaging::{
-    data::{DataQuery, ServiceMsg},
+    data::{DataHolders, DataQuery, HoldersQuery, QueryResponse, ServiceMsg},
     ServiceAuth, WireMsg,
 };
 use sn_interface::types::{PublicKey, Signature};
 use tracing::{debug, info_span};
+use xor_name::XorName;
 
 // We divide the total query timeout by this number.
 // This also represents the max retries possible, while still staying within the max_timeout.
@@ -26,7 +27,25 @@ impl Client {
     /// Queries are automatically retried using exponential backoff if the timeout is hit.
     #[instrument(skip(self), level = "debug")]
     pub async fn send_query(&self, query: DataQuery) -> Result<QueryResult, Error> {
-        self.send_query_with_retry_count(query, MAX_RETRY_COUNT)
+        self.send_query_with(query, MAX_RETRY_COUNT, false, false)
+            .await
+    }
+
+    /// Send a Query to the network and await a response reading the writes of this client acked
+    /// so far: the elders which acked them hold the query until their adults stored them.
+    /// Queries are automatically retried using exponential backoff if the timeout is hit.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn send_consistent_query(&self, query: DataQuery) -> Result<QueryResult, Error> {
+        self.send_query_with(query, MAX_RETRY_COUNT, false, true)
+            .await
+    }
+
+    /// Send a Query to the network and await a response, along with the trace of the nodes
+    /// which handled it, and when, to see where a slow query spent its time.
+    /// Queries are automatically retried using exponential backoff if the timeout is hit.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn send_traced_query(&self, query: DataQuery) -> Result<QueryResult, Error> {
+        self.send_query_with(query, MAX_RETRY_COUNT, true, false)
             .await
     }
 
@@ -34,20 +53,29 @@ impl Client {
     /// Queries are not retried if the timeout is hit.
     #[instrument(skip(self), level = "debug")]
     pub async fn send_query_without_retry(&self, query: DataQuery) -> Result<QueryResult, Error> {
-        self.send_query_with_retry_count(query, 1.0).await
+        self.send_query_with(query, 1.0, false, false).await
     }
 
     // Send a Query to the network and await a response.
     // Queries are automatically retried if the timeout is hit
     // This function is a private helper.
     #[instrument(skip(self), level = "debug")]
-    async fn send_query_with_retry_count(
+    async fn send_query_with(
         &self,
         query: DataQuery,
         retry_count: f32,
+        traced: bool,
+        consistent: bool,
     ) -> Result<QueryResult, Error> {
         let client_pk = self.public_key();
-        let msg = ServiceMsg::Query(query.clone());
+        let msg = if consistent {
+            ServiceMsg::ConsistentQuery {
+                query: query.clone(),
+                tokens: self.session.session_tokens(),
+            }
+        } else {
+            ServiceMsg::Query(query.clone())
+        };
         let serialised_query = WireMsg::serialize_msg_payload(&msg)?;
         let signature = self.keypair.sign(&serialised_query);
 
@@ -70,11 +98,12 @@ impl Client {
 
             let res = tokio::time::timeout(
                 attempt_timeout,
-                self.send_signed_query(
+                self.send_signed_query_with(
                     query.clone(),
                     client_pk,
                     serialised_query.clone(),
                     signature.clone(),
+                    traced,
                 ),
             )
             .await;
@@ -102,6 +131,19 @@ impl Client {
         client_pk: PublicKey,
         serialised_query: Bytes,
         signature: Signature,
+    ) -> Result<QueryResult, Error> {
+        self.send_signed_query_with(query, client_pk, serialised_query, signature, false)
+            .await
+    }
+
+    // Sends a signed Query to the network, asking for it to be traced if `traced`.
+    async fn send_signed_query_with(
+        &self,
+        query: DataQuery,
+        client_pk: PublicKey,
+        serialised_query: Bytes,
+        signature: Signature,
+        traced: bool,
     ) -> Result<QueryResult, Error> {
         debug!("Sending Query: {:?}", query);
         let auth = ServiceAuth {
@@ -109,6 +151,33 @@ impl Client {
             signature,
         };
 
-        self.session.send_query(query, auth, serialised_query).await
+        self.session
+            .send_query(query, auth, serialised_query, traced)
+            .await
+    }
+
+    /// Asks the elders of the section responsible for `name` which of their adults hold its
+    /// data, as far as they know.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn get_data_holders(&self, name: XorName) -> Result<DataHolders, Error> {
+        let query = HoldersQuery { name };
+        let payload = WireMsg::serialize_msg_payload(&ServiceMsg::HoldersQuery(query.clone()))?;
+        let auth = ServiceAuth {
+            public_key: self.public_key(),
+            signature: self.keypair.sign(&payload),
+        };
+
+        let query_result = tokio::time::timeout(
+            self.query_timeout,
+            self.session.send_holders_query(query, auth, payload),
+        )
+        .await
+        .map_err(|_| Error::NoResponse)??;
+        match query_result.response {
+            QueryResponse::GetDataHolders((res, op_id)) => {
+                res.map_err(|err| Error::ErrorMsg { source: err, op_id })
+            }
+            other => Err(Error::UnexpectedQueryResponse(other)),
+        }
     }
 }
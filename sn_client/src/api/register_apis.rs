@@ -225,6 +225,49 @@ impl Client {
         }
     }
 
+    /// Get the entry of the given version of a Register, its versions counting from 0 in the
+    /// order of [`Register::history`]
+    #[instrument(skip(self), level = "debug")]
+    pub async fn get_register_version(
+        &self,
+        address: Address,
+        version: u64,
+    ) -> Result<(EntryHash, Entry), Error> {
+        let query = DataQuery::Register(RegisterQuery::GetVersion { address, version });
+        let query_result = self.send_query(query).await?;
+        match query_result.response {
+            QueryResponse::GetRegisterVersion((res, op_id)) => {
+                res.map_err(|err| Error::ErrorMsg { source: err, op_id })
+            }
+            _ => Err(Error::ReceivedUnexpectedEvent),
+        }
+    }
+
+    /// Get the entries of the versions of a Register from `start` up to `end`, excluded. No
+    /// more than [`MAX_HISTORY_RANGE`] of them are returned, longer ranges taking several calls.
+    ///
+    /// [`MAX_HISTORY_RANGE`]: sn_interface::messaging::data::MAX_HISTORY_RANGE
+    #[instrument(skip(self), level = "debug")]
+    pub async fn get_register_history(
+        &self,
+        address: Address,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(EntryHash, Entry)>, Error> {
+        let query = DataQuery::Register(RegisterQuery::GetHistoryRange {
+            address,
+            start,
+            end,
+        });
+        let query_result = self.send_query(query).await?;
+        match query_result.response {
+            QueryResponse::GetRegisterHistory((res, op_id)) => {
+                res.map_err(|err| Error::ErrorMsg { source: err, op_id })
+            }
+            _ => Err(Error::ReceivedUnexpectedEvent),
+        }
+    }
+
     //----------------------
     // Ownership
     //---------------------
@@ -304,14 +347,14 @@ mod tests {
     };
     use eyre::{bail, eyre, Result};
     use rand::Rng;
-    use sn_interface::messaging::data::Error as ErrorMsg;
+    use sn_interface::messaging::data::{AddressProblem, Error as ErrorMsg};
     use sn_interface::types::{
         log_markers::LogMarker,
         register::{
             Action, EntryHash, Permissions, Policy, PrivatePolicy, PublicPermissions, PublicPolicy,
             User,
         },
-        Keypair,
+        Keypair, Scope,
     };
     use std::{
         collections::{BTreeMap, BTreeSet},
@@ -782,10 +825,18 @@ mod tests {
 
         let batch2 = client.delete_register(address).await?;
         match client.publish_register_ops(batch2).await {
+            // Elders refuse it for the address not being that of a private register.
             Err(Error::ErrorCmd {
-                source: ErrorMsg::InvalidOperation(_),
+                source:
+                    ErrorMsg::InvalidAddress {
+                        field,
+                        problem:
+                            AddressProblem::WrongKind {
+                                expected: Scope::Private,
+                            },
+                    },
                 ..
-            }) => {}
+            }) if field == "op.address" => {}
             Err(err) => bail!(
                 "Unexpected error returned when attempting to delete a Public Register: {:?}",
                 err
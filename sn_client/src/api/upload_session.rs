@@ -0,0 +1,236 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Client;
+use crate::{Error, Result};
+use sn_interface::messaging::data::DataCmd;
+use sn_interface::types::{BytesAddress, Chunk, Scope};
+
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+use xor_name::XorName;
+
+/// Default number of chunks of an upload session sent to the network at once.
+pub const DEFAULT_UPLOAD_PARALLELISM: usize = 8;
+
+const MANIFEST_FILE: &str = "manifest";
+const MANIFEST_TMP_FILE: &str = "manifest.tmp";
+
+/// What is left of an upload, persisted next to its chunks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct UploadManifest {
+    address: BytesAddress,
+    pending: BTreeSet<XorName>,
+}
+
+/// An upload of [`Bytes`] which can be resumed after being interrupted.
+///
+/// The bytes are self-encrypted locally when the session is started, and their chunks written
+/// to the session's directory, along with a manifest of the chunks yet to be stored on the
+/// network. Each chunk stored is struck off the manifest, so that uploading the session again,
+/// be it from the same process or after [`UploadSession::resume`], only sends those left.
+/// The directory is removed once all chunks are stored.
+#[derive(Clone, Debug)]
+pub struct UploadSession {
+    dir: PathBuf,
+    manifest: UploadManifest,
+}
+
+impl UploadSession {
+    /// Resumes the upload session persisted in `dir`.
+    pub async fn resume(dir: &Path) -> Result<Self> {
+        let bytes = fs::read(dir.join(MANIFEST_FILE)).await?;
+        let manifest = bincode::deserialize(&bytes)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// The address the bytes will be found at once uploaded.
+    pub fn address(&self) -> BytesAddress {
+        self.manifest.address
+    }
+
+    /// Number of chunks yet to be stored on the network.
+    pub fn pending(&self) -> usize {
+        self.manifest.pending.len()
+    }
+
+    /// Whether all chunks have been stored on the network.
+    pub fn is_done(&self) -> bool {
+        self.manifest.pending.is_empty()
+    }
+
+    /// Writes the `chunks` to `dir`, then the manifest listing them all as pending, so that a
+    /// session is never found without the chunks it lists.
+    async fn create(dir: &Path, address: BytesAddress, chunks: Vec<Chunk>) -> Result<Self> {
+        fs::create_dir_all(dir).await?;
+        let mut pending = BTreeSet::new();
+        for chunk in chunks {
+            fs::write(dir.join(hex::encode(chunk.name())), chunk.value()).await?;
+            let _ = pending.insert(*chunk.name());
+        }
+
+        let session = Self {
+            dir: dir.to_path_buf(),
+            manifest: UploadManifest { address, pending },
+        };
+        session.persist().await?;
+        Ok(session)
+    }
+
+    // Reads a pending chunk back from the session in `dir`, checking it is the one we wrote.
+    async fn read_chunk(dir: &Path, name: XorName) -> Result<Chunk> {
+        let bytes = fs::read(dir.join(hex::encode(name)))
+            .await
+            .map_err(|_| Error::UploadSessionChunkCorrupted(name))?;
+        let chunk = Chunk::new(Bytes::from(bytes));
+        if *chunk.name() != name {
+            return Err(Error::UploadSessionChunkCorrupted(name));
+        }
+        Ok(chunk)
+    }
+
+    // Strikes a stored chunk off the manifest and removes it from disk.
+    async fn mark_stored(&mut self, name: XorName) -> Result<()> {
+        let _ = self.manifest.pending.remove(&name);
+        self.persist().await?;
+        if let Err(error) = fs::remove_file(self.dir.join(hex::encode(name))).await {
+            warn!(
+                "Could not remove stored chunk {:?} from disk: {:?}",
+                name, error
+            );
+        }
+        Ok(())
+    }
+
+    // Writes the manifest atomically, so that an interruption never leaves it half written.
+    async fn persist(&self) -> Result<()> {
+        let tmp = self.dir.join(MANIFEST_TMP_FILE);
+        fs::write(&tmp, bincode::serialize(&self.manifest)?).await?;
+        fs::rename(&tmp, self.dir.join(MANIFEST_FILE)).await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Starts a resumable upload of [`Bytes`], self-encrypting them into chunks persisted to
+    /// `dir`. Nothing is stored to the network until the session is uploaded with
+    /// [`Client::upload_session`].
+    #[instrument(skip(self, bytes), level = "debug")]
+    pub async fn start_upload(
+        &self,
+        bytes: Bytes,
+        scope: Scope,
+        dir: &Path,
+    ) -> Result<UploadSession> {
+        let (address, chunks) = self.chunk_bytes(bytes, scope)?;
+        debug!(
+            "Starting upload session of {} chunks for {:?} in {}",
+            chunks.len(),
+            address,
+            dir.display()
+        );
+        UploadSession::create(dir, address, chunks).await
+    }
+
+    /// Stores the chunks of an upload session yet to be stored, `parallelism` of them at once.
+    ///
+    /// Stops at the first chunk which fails to be stored, the others being left pending, so that
+    /// the session can be uploaded again to resume it. Returns the address of the bytes once
+    /// they are all stored, and removes the session from disk.
+    #[instrument(skip(self, session), level = "debug")]
+    pub async fn upload_session(
+        &self,
+        session: &mut UploadSession,
+        parallelism: usize,
+    ) -> Result<BytesAddress> {
+        let pending = session.manifest.pending.iter().copied().collect::<Vec<_>>();
+        debug!(
+            "Uploading {} pending chunks of {:?}",
+            pending.len(),
+            session.address()
+        );
+
+        let dir = session.dir.clone();
+        let dir = &dir;
+        let mut stores = stream::iter(pending)
+            .map(|name| async move {
+                let chunk = UploadSession::read_chunk(dir, name).await?;
+                self.send_cmd(DataCmd::StoreChunk(chunk)).await?;
+                Ok::<_, Error>(name)
+            })
+            .buffer_unordered(parallelism.max(1));
+
+        while let Some(result) = stores.next().await {
+            match result {
+                Ok(name) => session.mark_stored(name).await?,
+                Err(error) => {
+                    warn!(
+                        "Upload of {:?} interrupted with {} chunks pending: {:?}",
+                        session.address(),
+                        session.pending(),
+                        error
+                    );
+                    return Err(error);
+                }
+            }
+        }
+
+        fs::remove_dir_all(&session.dir).await?;
+        Ok(session.address())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::data::encrypt_large, utils::encryption};
+    use sn_interface::types::{utils::random_bytes, Keypair};
+
+    use eyre::Result;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn upload_session_resumes_with_chunks_left_pending() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bytes = random_bytes(self_encryption::MIN_ENCRYPTABLE_BYTES);
+        let owner = encryption(Scope::Private, Keypair::new_ed25519().public_key());
+        let (address, chunks) = encrypt_large(bytes, owner.as_ref())?;
+        let names = chunks.iter().map(|chunk| *chunk.name()).collect::<Vec<_>>();
+
+        let mut session = UploadSession::create(dir.path(), address, chunks).await?;
+        assert_eq!(session.pending(), names.len());
+        session.mark_stored(names[0]).await?;
+
+        let resumed = UploadSession::resume(dir.path()).await?;
+        assert_eq!(resumed.address(), address);
+        assert_eq!(resumed.pending(), names.len() - 1);
+        assert!(!resumed.manifest.pending.contains(&names[0]));
+        for name in &names[1..] {
+            assert_eq!(
+                UploadSession::read_chunk(dir.path(), *name).await?.name(),
+                name
+            );
+        }
+
+        // A chunk altered on disk is not sent.
+        fs::write(dir.path().join(hex::encode(names[1])), b"altered").await?;
+        assert!(matches!(
+            UploadSession::read_chunk(dir.path(), names[1]).await,
+            Err(Error::UploadSessionChunkCorrupted(name)) if name == names[1]
+        ));
+        Ok(())
+    }
+}
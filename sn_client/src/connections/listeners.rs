@@ -15,11 +15,10 @@ use crate::{
     },
     Error, Result,
 };
-use sn_interface::at_least_one_correct_elder;
 use sn_interface::messaging::{
-    data::{CmdError, ServiceMsg},
+    data::{CmdError, ServiceError, ServiceMsg},
     system::{KeyedSig, SectionAuth, SystemMsg},
-    AuthKind, AuthorityProof, DstLocation, MsgId, MsgType, ServiceAuth, WireMsg,
+    AuthKind, AuthorityProof, DstLocation, MsgId, MsgType, ServiceAuth, TraceContext, WireMsg,
 };
 use sn_interface::network_knowledge::utils::compare_and_write_prefix_map_to_disk;
 use sn_interface::network_knowledge::{NetworkKnowledge, SectionAuthorityProvider};
@@ -53,13 +52,13 @@ impl Session {
         let _handle = tokio::spawn(async move {
             loop {
                 match Self::listen_for_incoming_msg(addr, &mut incoming_msgs).await {
-                    Ok(Some(msg)) => {
+                    Ok(Some((msg, trace))) => {
                         if first {
                             first = false;
                             session.peer_links.add_incoming(&peer, conn.clone()).await;
                         }
 
-                        if let Err(err) = Self::handle_msg(msg, peer, session.clone()).await {
+                        if let Err(err) = Self::handle_msg(msg, trace, peer, session.clone()).await {
                             error!("Error while handling incoming msg: {:?}. Listening for next msg...", err);
                         }
                     },
@@ -99,11 +98,13 @@ impl Session {
     pub(crate) async fn listen_for_incoming_msg(
         src: SocketAddr,
         incoming_msgs: &mut IncomingMsgs,
-    ) -> Result<Option<MsgType>, Error> {
+    ) -> Result<Option<(MsgType, Option<TraceContext>)>, Error> {
         if let Some(msg) = incoming_msgs.next().await? {
             trace!("Incoming msg from {:?}", src);
-            let msg_type = WireMsg::deserialize(msg)?;
-            Ok(Some(msg_type))
+            let wire_msg = WireMsg::from(msg)?;
+            let trace = wire_msg.trace().cloned();
+            let msg_type = wire_msg.into_msg()?;
+            Ok(Some((msg_type, trace)))
         } else {
             Ok(None)
         }
@@ -112,12 +113,13 @@ impl Session {
     #[instrument(skip_all, level = "debug")]
     pub(crate) async fn handle_msg(
         msg: MsgType,
+        trace: Option<TraceContext>,
         src_peer: Peer,
         session: Session,
     ) -> Result<(), Error> {
         match msg.clone() {
             MsgType::Service { msg_id, msg, .. } => {
-                Self::handle_client_msg(session, msg_id, msg, src_peer)
+                Self::handle_client_msg(session, msg_id, msg, trace, src_peer)
             }
             MsgType::System {
                 msg:
@@ -204,6 +206,21 @@ impl Session {
                 }
                 result
             }
+            MsgType::System {
+                msg: SystemMsg::ListenerRedirect { addr, bounced_msg },
+                ..
+            } => {
+                debug!("Listener-Redirect msg received");
+                let result =
+                    Self::handle_listener_redirect(session, addr, bounced_msg, src_peer).await;
+                if result.is_err() {
+                    error!(
+                        "Failed to handle Listener-Redirect msg from {:?}",
+                        src_peer.addr()
+                    );
+                }
+                result
+            }
             msg_type => {
                 warn!("Unexpected msg type received: {:?}", msg_type);
                 Ok(())
@@ -211,6 +228,41 @@ impl Session {
         }
     }
 
+    // Resends a msg of ours that reached a node on its listener for nodes, to the one for
+    // clients at `addr`.
+    #[instrument(skip_all, level = "debug")]
+    async fn handle_listener_redirect(
+        session: Session,
+        addr: SocketAddr,
+        bounced_msg: Bytes,
+        src_peer: Peer,
+    ) -> Result<(), Error> {
+        let wire_msg = WireMsg::from(bounced_msg)?;
+        let msg_id = wire_msg.msg_id();
+
+        // Only service msgs are ours to resend.
+        if !matches!(wire_msg.msg_kind(), AuthKind::Service(_)) {
+            warn!(
+                "Dropping Listener-Redirect from {:?}, of a msg that isn't ours: {:?}",
+                src_peer, msg_id
+            );
+            return Ok(());
+        }
+
+        debug!(
+            "Resending {:?} to the listener for clients of {:?}, at {}",
+            msg_id, src_peer, addr
+        );
+
+        send_msg(
+            session,
+            vec![Peer::new(src_peer.name(), addr)],
+            wire_msg,
+            msg_id,
+        )
+        .await
+    }
+
     #[instrument(skip(cmds), level = "debug")]
     fn send_cmd_response(
         cmds: PendingCmdAcks,
@@ -240,6 +292,7 @@ impl Session {
         session: Session,
         msg_id: MsgId,
         msg: ServiceMsg,
+        trace: Option<TraceContext>,
         src_peer: Peer,
     ) -> Result<(), Error> {
         debug!(
@@ -248,7 +301,7 @@ impl Session {
             src_peer.addr()
         );
         let queries = session.pending_queries.clone();
-        let cmds = session.pending_cmds;
+        let cmds = session.pending_cmds.clone();
 
         let _handle = tokio::spawn(async move {
             match msg {
@@ -262,7 +315,7 @@ impl Session {
                         if let Some(entry) = queries.get(&op_id) {
                             let all_senders = entry.value();
                             for (_msg_id, sender) in all_senders {
-                                let res = sender.try_send(response.clone());
+                                let res = sender.try_send((response.clone(), trace.clone()));
                                 if res.is_err() {
                                     trace!("Error relaying query response internally on a channel for {:?} op_id {:?}: {:?}. (It has likely been removed)", msg_id, op_id, res)
                                 }
@@ -289,7 +342,20 @@ impl Session {
                     warn!("CmdError was received for {correlation_id:?}: {:?}", error);
                     Self::send_cmd_response(cmds, correlation_id, src_peer.addr(), Some(error));
                 }
-                ServiceMsg::CmdAck { correlation_id } => {
+                ServiceMsg::ServiceError(ServiceError { reason, .. }) => {
+                    warn!(
+                        "ServiceError was received from {:?}: {:?}",
+                        src_peer.addr(),
+                        reason
+                    );
+                }
+                ServiceMsg::CmdAck {
+                    correlation_id,
+                    token,
+                } => {
+                    if let Some(token) = token {
+                        session.record_session_token(token);
+                    }
                     debug!(
                         "CmdAck was received for Message{:?} w/ID: {:?} from {:?}",
                         msg_id,
@@ -459,9 +525,19 @@ impl Session {
             service_msg
         );
 
+        // Enough elders of the section to reach at least one correct one, as the network's
+        // params carried in its SAP have it.
+        let network_params = received_auth.network_params();
+        let elder_count = network_params.elder_count();
+        let at_least_one_correct_elder =
+            1 + elder_count - network_params.supermajority(elder_count);
+
         let (target_count, dst_address_of_bounced_msg) = match service_msg.clone() {
-            ServiceMsg::Cmd(cmd) => (at_least_one_correct_elder(), cmd.dst_name()),
-            ServiceMsg::Query(query) => (NUM_OF_ELDERS_SUBSET_FOR_QUERIES, query.dst_name()),
+            ServiceMsg::Cmd(cmd) => (at_least_one_correct_elder, cmd.dst_name()),
+            ServiceMsg::Query(query) | ServiceMsg::ConsistentQuery { query, .. } => {
+                (NUM_OF_ELDERS_SUBSET_FOR_QUERIES, query.dst_name())
+            }
+            ServiceMsg::HoldersQuery(query) => (NUM_OF_ELDERS_SUBSET_FOR_QUERIES, query.name),
             _ => {
                 warn!(
                     "Invalid bounced msg {:?} received in AE response: {:?}. Msg is of invalid type",
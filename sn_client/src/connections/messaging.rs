@@ -11,11 +11,14 @@ use super::{QueryResult, Session};
 use crate::{connections::CmdResponse, Error, Result};
 use sn_interface::at_least_one_correct_elder_for_sap;
 use sn_interface::messaging::{
-    data::{CmdError, DataQuery, QueryResponse},
-    AuthKind, DstLocation, MsgId, ServiceAuth, WireMsg,
+    data::{
+        CmdError, DataQuery, Error as DataError, HoldersQuery, OperationId, QueryResponse,
+        SessionToken,
+    },
+    AuthKind, DstLocation, MsgId, ServiceAuth, TraceContext, WireMsg,
 };
 use sn_interface::network_knowledge::prefix_map::NetworkPrefixMap;
-use sn_interface::types::{Peer, PeerLinks, PublicKey, SendToOneError};
+use sn_interface::types::{ChunkAddress, Peer, PeerLinks, PublicKey, SendToOneError};
 
 use backoff::{backoff::Backoff, ExponentialBackoff};
 use bytes::Bytes;
@@ -24,7 +27,7 @@ use futures::future::join_all;
 use qp2p::{Close, Config as QuicP2pConfig, ConnectionError, Endpoint, SendError};
 use rand::{rngs::OsRng, seq::SliceRandom};
 use secured_linked_list::SecuredLinkedList;
-use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use std::{fmt::Debug, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use tokio::{
     sync::mpsc::{channel, Sender},
     sync::RwLock,
@@ -71,14 +74,32 @@ impl Session {
             cmd_ack_wait,
             peer_links,
             all_sections_chains: Arc::new(RwLock::new(SecuredLinkedList::new(genesis_key))),
+            session_tokens: Arc::new(DashMap::default()),
         };
 
         Ok(session)
     }
 
+    /// Tokens of our writes sent back by the elders so far, the latest of each.
+    pub(crate) fn session_tokens(&self) -> Vec<SessionToken> {
+        self.session_tokens
+            .iter()
+            .map(|entry| *entry.value())
+            .collect()
+    }
+
+    // Keeps `token` as the latest of its elder, unless a later one was sent back already.
+    pub(crate) fn record_session_token(&self, token: SessionToken) {
+        let mut latest = self.session_tokens.entry(token.elder).or_insert(token);
+        if !latest.covers(&token) {
+            *latest = token;
+        }
+    }
+
     #[instrument(skip(self, auth, payload), level = "debug", name = "session send cmd")]
     pub(crate) async fn send_cmd(
         &self,
+        msg_id: MsgId,
         dst_address: XorName,
         auth: ServiceAuth,
         payload: Bytes,
@@ -88,8 +109,6 @@ impl Session {
 
         let (section_pk, elders) = self.get_cmd_elders(dst_address).await?;
 
-        let msg_id = MsgId::new();
-
         debug!(
             "Sending cmd w/id {:?}, from {}, to {} Elders w/ dst: {:?}",
             msg_id,
@@ -189,16 +208,61 @@ impl Session {
         query: DataQuery,
         auth: ServiceAuth,
         payload: Bytes,
+        traced: bool,
     ) -> Result<QueryResult> {
-        let endpoint = self.endpoint.clone();
-
         let chunk_addr = if let DataQuery::GetChunk(address) = query {
             Some(address)
         } else {
             None
         };
 
-        let dst = query.dst_name();
+        self.send_query_to_elders(
+            &query,
+            query.dst_name(),
+            query.operation_id(),
+            chunk_addr,
+            auth,
+            payload,
+            traced,
+        )
+        .await
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    /// Send a `HoldersQuery` to the elders of the section of its name, awaiting for the response.
+    pub(crate) async fn send_holders_query(
+        &self,
+        query: HoldersQuery,
+        auth: ServiceAuth,
+        payload: Bytes,
+    ) -> Result<QueryResult> {
+        self.send_query_to_elders(
+            &query,
+            query.name,
+            query.operation_id(),
+            None,
+            auth,
+            payload,
+            false,
+        )
+        .await
+    }
+
+    // Sends a query to the elders closest to `dst`, and awaits the first valid response to it,
+    // responses being routed back to us by `op_id`. Chunks returned are checked against
+    // `chunk_addr` when it is given.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_query_to_elders(
+        &self,
+        query: &(dyn Debug + Sync),
+        dst: XorName,
+        op_id: std::result::Result<OperationId, DataError>,
+        chunk_addr: Option<ChunkAddress>,
+        auth: ServiceAuth,
+        payload: Bytes,
+        traced: bool,
+    ) -> Result<QueryResult> {
+        let endpoint = self.endpoint.clone();
 
         let (section_pk, elders) = self.get_query_elders(dst).await?;
         let elders_len = elders.len();
@@ -213,9 +277,9 @@ impl Session {
             elders
         );
 
-        let (sender, mut receiver) = channel::<QueryResponse>(7);
+        let (sender, mut receiver) = channel::<(QueryResponse, Option<TraceContext>)>(7);
 
-        if let Ok(op_id) = query.operation_id() {
+        if let Ok(op_id) = op_id {
             // Insert the response sender
             trace!("Inserting channel for op_id {:?}", (msg_id, op_id));
             if let Some(mut entry) = self.pending_queries.get_mut(&op_id) {
@@ -235,7 +299,10 @@ impl Session {
             section_pk,
         };
         let msg_kind = AuthKind::Service(auth);
-        let wire_msg = WireMsg::new_msg(msg_id, payload, msg_kind, dst_location)?;
+        let mut wire_msg = WireMsg::new_msg(msg_id, payload, msg_kind, dst_location)?;
+        if traced {
+            wire_msg.set_trace(Some(TraceContext::default()));
+        }
 
         send_msg_in_bg(self.clone(), elders, wire_msg, msg_id)?;
 
@@ -250,10 +317,16 @@ impl Session {
         // from byzantine nodes, however for mutable data (non-Chunk responses) we will
         // have to review the approach.
         let mut discarded_responses: usize = 0;
+        // Trace of the response taken.
+        let mut trace = None;
 
         let response = loop {
             let mut error_response = None;
-            match (receiver.recv().await, chunk_addr) {
+            let received = receiver.recv().await.map(|(response, response_trace)| {
+                trace = response_trace;
+                response
+            });
+            match (received, chunk_addr) {
                 (Some(QueryResponse::GetChunk(Ok(chunk))), Some(chunk_addr)) => {
                     // We are dealing with Chunk query responses, thus we validate its hash
                     // matches its xorname, if so, we don't need to await for more responses
@@ -325,9 +398,13 @@ impl Session {
                 let operation_id = response
                     .operation_id()
                     .map_err(|_| Error::UnknownOperationId)?;
+                if let Some(trace) = &trace {
+                    debug!("Query w/id {:?} went through: {}", msg_id, trace);
+                }
                 Ok(QueryResult {
                     response,
                     operation_id,
+                    trace,
                 })
             }
             None => Err(Error::NoResponse),
@@ -478,6 +555,16 @@ impl Session {
         Ok(())
     }
 
+    /// The key and elders of the section closest to `name` we know of.
+    pub(crate) fn closest_known_section(
+        &self,
+        name: &XorName,
+    ) -> Option<(bls::PublicKey, Vec<Peer>)> {
+        self.network
+            .closest_or_opposite(name, None)
+            .map(|sap| (sap.section_key(), sap.elders_vec()))
+    }
+
     async fn get_query_elders(&self, dst: XorName) -> Result<(bls::PublicKey, Vec<Peer>)> {
         // Get DataSection elders details. Resort to own section if DataSection is not available.
         let sap = self.network.closest_or_opposite(&dst, None);
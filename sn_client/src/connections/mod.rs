@@ -10,8 +10,8 @@ mod listeners;
 mod messaging;
 
 use sn_interface::messaging::{
-    data::{CmdError, OperationId, QueryResponse},
-    MsgId,
+    data::{CmdError, OperationId, QueryResponse, SessionToken},
+    MsgId, TraceContext,
 };
 use sn_interface::network_knowledge::prefix_map::NetworkPrefixMap;
 use sn_interface::types::PeerLinks;
@@ -24,10 +24,11 @@ use tokio::{
     sync::{mpsc::Sender, RwLock},
     time::Duration,
 };
+use xor_name::XorName;
 
 // Here we dont track the msg_id across the network, but just use it as a local identifier to remove the correct listener
 type PendingQueryResponses = Arc<DashMap<OperationId, Vec<(MsgId, QueryResponseSender)>>>;
-type QueryResponseSender = Sender<QueryResponse>;
+type QueryResponseSender = Sender<(QueryResponse, Option<TraceContext>)>;
 
 type CmdResponse = (std::net::SocketAddr, Option<CmdError>);
 type PendingCmdAcks = Arc<DashMap<MsgId, Sender<CmdResponse>>>;
@@ -36,6 +37,8 @@ type PendingCmdAcks = Arc<DashMap<MsgId, Sender<CmdResponse>>>;
 pub struct QueryResult {
     pub response: QueryResponse,
     pub operation_id: OperationId,
+    /// The nodes which handled the query, if a trace of it was asked for.
+    pub trace: Option<TraceContext>,
 }
 
 #[derive(Clone, Debug)]
@@ -61,4 +64,6 @@ pub(super) struct Session {
     cmd_ack_wait: Duration,
     /// Links to nodes
     peer_links: PeerLinks,
+    /// Tokens of our writes, the latest sent back by each elder
+    session_tokens: Arc<DashMap<XorName, SessionToken>>,
 }
@@ -16,6 +16,7 @@ use sn_interface::types::Error as DtError;
 use std::io;
 use std::net::SocketAddr;
 use thiserror::Error;
+use xor_name::XorName;
 
 /// Specialisation of `std::Result` for Client.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -123,6 +124,8 @@ pub enum Error {
     ErrorCmd {
         /// The source of an error msg
         source: ErrorMsg,
+        /// Whether the cmd may succeed if sent again unchanged, after backing off
+        retryable: bool,
         /// MsgId of the cmd
         msg_id: MsgId,
     },
@@ -161,6 +164,9 @@ pub enum Error {
         /// Number of Chunks retrieved
         retrieved: usize,
     },
+    /// A chunk of an upload session is missing from disk, or isn't the one written there.
+    #[error("Chunk {0:?} of the upload session is missing or corrupted on disk.")]
+    UploadSessionChunkCorrupted(XorName),
     /// Could not chunk all the data required to encrypt the data. (Expected, Actual)
     #[error("Not all data was chunked, expected {expected}, but we have {chunked}.)")]
     NotAllDataWasChunked {
@@ -173,8 +179,12 @@ pub enum Error {
 
 impl From<(CmdError, MsgId)> for Error {
     fn from((error, msg_id): (CmdError, MsgId)) -> Self {
-        let CmdError::Data(source) = error;
-        Error::ErrorCmd { source, msg_id }
+        let CmdError::Data(error) = error;
+        Error::ErrorCmd {
+            source: error.error,
+            retryable: error.retryable,
+            msg_id,
+        }
     }
 }
 
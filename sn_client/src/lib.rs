@@ -65,7 +65,7 @@ mod connections;
 mod errors;
 
 // Export public API.
-pub use api::{Client, RegisterWriteAheadLog};
+pub use api::{Client, RegisterWriteAheadLog, UploadSession, DEFAULT_UPLOAD_PARALLELISM};
 pub use config_handler::{ClientConfig, DEFAULT_ACK_WAIT, DEFAULT_OPERATION_TIMEOUT};
 pub use errors::ErrorMsg;
 pub use errors::{Error, Result};
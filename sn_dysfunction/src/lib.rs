@@ -196,6 +196,12 @@ impl DysfunctionDetection {
         self.adults.write().await.push(adult);
     }
 
+    /// Forgets the communication issues logged against a node, e.g. as it's back from a planned
+    /// absence.
+    pub fn clear_communication_issues(&self, node_id: &NodeIdentifier) {
+        let _ = self.communication_issues.remove(node_id);
+    }
+
     /// Removes tracked nodes not present in `current_members`.
     ///
     /// Tracked issues related to nodes that were removed will also be removed.
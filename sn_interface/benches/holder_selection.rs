@@ -0,0 +1,48 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use sn_interface::types::{holders_for, holders_for_indexed, ClosenessIndex};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+const HOLDERS: usize = 4;
+
+/// Cost of choosing the holders of a piece of data, per request, among a section's adults:
+/// sorting them all by distance every time, as it used to be, against walking an index of them
+/// built once per membership change.
+fn holder_selection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("holder-selection");
+    for adults in [50, 200, 500] {
+        let names = (0..adults)
+            .map(|_| xor_name::rand::random())
+            .collect::<BTreeSet<XorName>>();
+        let index = names.iter().copied().collect::<ClosenessIndex>();
+        let targets = (0..64)
+            .map(|_| xor_name::rand::random())
+            .collect::<Vec<XorName>>();
+
+        let _ = group.bench_with_input(BenchmarkId::new("sorted", adults), &names, |b, names| {
+            let mut targets = targets.iter().cycle();
+            b.iter(|| holders_for(black_box(targets.next().unwrap()), names, HOLDERS))
+        });
+        let _ = group.bench_with_input(BenchmarkId::new("indexed", adults), &index, |b, index| {
+            let mut targets = targets.iter().cycle();
+            b.iter(|| holders_for_indexed(black_box(targets.next().unwrap()), index, HOLDERS))
+        });
+        let _ = group.bench_with_input(
+            BenchmarkId::new("index-rebuild", adults),
+            &names,
+            |b, names| b.iter(|| names.iter().copied().collect::<ClosenessIndex>()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, holder_selection);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use bls::SecretKey;
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand_07::rngs::OsRng;
+use sn_interface::{
+    messaging::{
+        system::{NodeCmd, SystemMsg},
+        AuthKind, DstLocation, MsgId, MsgType, NodeAuth, WireMsg,
+    },
+    types::{Chunk, ReplicatedData},
+};
+
+/// Cost, per chunk size, of what a node does with a msg replicating a chunk it forwards: parse
+/// the msg received and deserialise its payload, the chunk sharing the bytes received rather
+/// than being copied out of them, and serialise a msg to send on.
+fn chunk_msgs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk-msg");
+    for size in [64 * 1024, 1024 * 1024, 4 * 1024 * 1024] {
+        let wire_msg = replicate_chunk_msg(size);
+        let received = wire_msg.serialize().expect("msg serialised");
+        let _ = group.throughput(Throughput::Bytes(size as u64));
+
+        let _ = group.bench_with_input(
+            BenchmarkId::new("deserialise", size),
+            &received,
+            |b, received| {
+                b.iter(|| {
+                    let wire_msg = WireMsg::from(black_box(received.clone())).expect("msg parsed");
+                    match wire_msg.into_msg().expect("payload deserialised") {
+                        MsgType::System {
+                            msg: SystemMsg::NodeCmd(NodeCmd::ReplicateData(data)),
+                            ..
+                        } => data,
+                        other => panic!("Unexpected msg {:?}", other),
+                    }
+                })
+            },
+        );
+        let _ = group.bench_with_input(
+            BenchmarkId::new("serialise", size),
+            &wire_msg,
+            |b, wire_msg| b.iter(|| black_box(wire_msg).serialize().expect("msg serialised")),
+        );
+    }
+    group.finish();
+}
+
+fn replicate_chunk_msg(size: usize) -> WireMsg {
+    let value: Vec<u8> = (0..size).map(|_| rand::random()).collect();
+    let msg = SystemMsg::NodeCmd(NodeCmd::ReplicateData(vec![ReplicatedData::Chunk(
+        Chunk::new(Bytes::from(value)),
+    )]));
+    let src_section_pk = SecretKey::random().public_key();
+    let keypair = ed25519_dalek::Keypair::generate(&mut OsRng);
+    let payload = WireMsg::serialize_msg_payload(&msg).expect("payload serialised");
+    let auth = NodeAuth::authorize(src_section_pk, &keypair, &payload);
+    let dst_location = DstLocation::Node {
+        name: xor_name::rand::random(),
+        section_pk: SecretKey::random().public_key(),
+    };
+    WireMsg::new_msg(
+        MsgId::new(),
+        payload,
+        AuthKind::Node(auth.into_inner()),
+        dst_location,
+    )
+    .expect("msg created")
+}
+
+criterion_group!(benches, chunk_msgs);
+criterion_main!(benches);
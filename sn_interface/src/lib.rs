@@ -12,6 +12,7 @@
 pub mod messaging;
 /// Knowledge of the safe network
 pub mod network_knowledge;
+pub mod protocol_constants;
 /// Types on the safe network
 pub mod types;
 
@@ -0,0 +1,66 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Error, OperationId, QueryResponse, Result};
+
+use crate::messaging::MsgId;
+
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Sha3};
+use xor_name::XorName;
+
+/// Most cmds a batch may hold.
+pub const MAX_BATCH_CMDS: usize = 32;
+
+/// Where a batch of cmds is at, as seen by an elder it was sent to.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub enum BatchStatus {
+    /// Not known to the elder: it never received the batch, or has forgotten about it since.
+    Unknown,
+    /// Checked, with holders found for the data of each of its cmds.
+    Reserved,
+    /// The msgs to the holders of its data formed, not all sent yet.
+    Applied,
+    /// Sent to the holders of its data, all of it.
+    Committed,
+    /// Nothing of it was sent to the holders of its data, for the error given.
+    Aborted(Error),
+}
+
+/// Query for the status of a batch of cmds, sent to the elders the batch was sent to.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Serialize, Deserialize, Debug)]
+pub struct BatchQuery {
+    /// Id of the msg of the batch.
+    pub batch: MsgId,
+    /// The name the batch was sent to.
+    pub dst_name: XorName,
+}
+
+impl BatchQuery {
+    /// Creates a Response containing an error, with the Response variant corresponding to the
+    /// Request variant.
+    pub fn error(&self, error: Error) -> Result<QueryResponse> {
+        Ok(QueryResponse::BatchStatus((
+            Err(error),
+            self.operation_id()?,
+        )))
+    }
+
+    /// Retrieves the operation identifier for this response, use in tracking node liveness
+    /// and responses at clients.
+    /// Must be the same as the query response
+    pub fn operation_id(&self) -> Result<OperationId> {
+        let bytes = crate::types::utils::encode(&self).map_err(|_| Error::NoOperationId)?;
+        let mut hasher = Sha3::v256();
+        let mut output = [0; 32];
+        hasher.update(bytes.as_bytes());
+        hasher.finalize(&mut output);
+        Ok(output)
+    }
+}
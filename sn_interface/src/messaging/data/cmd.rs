@@ -39,7 +39,7 @@ impl DataCmd {
         use DataCmd::*;
         match self {
             #[cfg(feature = "chunks")]
-            StoreChunk(_) => CmdError::Data(error),
+            StoreChunk(_) => CmdError::Data(error.into()),
             #[cfg(feature = "registers")]
             Register(c) => c.error(error),
         }
@@ -7,8 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::types::register::User;
-use crate::types::DataAddress;
-use crate::types::PublicKey;
+use crate::types::{DataAddress, PublicKey, Scope};
+use bls::PublicKey as BlsPublicKey;
 use serde::{Deserialize, Serialize};
 use std::result;
 use thiserror::Error;
@@ -73,4 +73,301 @@ pub enum Error {
     /// Destination is either outdated or incorrect
     #[error("Destination is either outdated or wrong")]
     WrongDestination,
+    /// The node has too many requests of ours queued. The request was not processed, and can be
+    /// retried after backing off.
+    #[error("Node is busy, please retry later")]
+    NodeBusy,
+    /// A name or address given with the operation is invalid for it.
+    #[error("Invalid {field}: {problem}")]
+    InvalidAddress {
+        /// Path of the invalid field in the msg, e.g. `dst.name`.
+        field: String,
+        /// What's wrong with it.
+        problem: AddressProblem,
+    },
+    /// The node failed to process the operation for a reason of its own, e.g. its storage
+    /// failing, which other nodes may not run into.
+    #[error("Internal error of the node: {0}")]
+    Internal(String),
+    /// Too many msgs of ours failed lately. They are no longer answered individually, and
+    /// should only be sent again after backing off.
+    #[error("Too many msgs failed, please back off for {back_off_secs}s")]
+    TooManyErrors {
+        /// How long to back off for, in seconds.
+        back_off_secs: u64,
+    },
+    /// The DBC of the key image is already logged as spent, in another tx.
+    #[error("DBC of key image {0:?} is already spent")]
+    DbcAlreadySpent(BlsPublicKey),
+    /// The tx a DBC is to be logged as spent in doesn't hold up.
+    #[error("Invalid tx: {0}")]
+    InvalidTransaction(String),
+    /// A cmd of a batch failed, and none of the batch was applied.
+    #[error("Cmd {index} of the batch failed, none of the batch was applied: {error}")]
+    BatchAborted {
+        /// Index of the cmd in the batch.
+        index: u16,
+        /// Why it failed.
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The stable code of this error, for clients to tell errors apart across versions.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::AccessDenied(_) => ErrorCode::AccessDenied,
+            Self::ChunkNotFound(_) | Self::DataNotFound(_) => ErrorCode::DataNotFound,
+            Self::DataExists => ErrorCode::DataExists,
+            Self::NoSuchEntry | Self::NoSuchKey => ErrorCode::NoSuchEntry,
+            Self::InvalidOwner(_) => ErrorCode::InvalidOwner,
+            Self::InvalidAddress { .. } => ErrorCode::InvalidAddress,
+            Self::InvalidOperation(_)
+            | Self::NoOperationId
+            | Self::InvalidQueryResponseErrorForOperationId => ErrorCode::InvalidOperation,
+            Self::InsufficientAdults { .. } => ErrorCode::InsufficientAdults,
+            Self::NodeBusy => ErrorCode::NodeBusy,
+            Self::WrongDestination => ErrorCode::WrongDestination,
+            Self::FailedToWriteFile | Self::FailedToDelete => ErrorCode::StorageFailure,
+            Self::Internal(_) => ErrorCode::Internal,
+            Self::TooManyErrors { .. } => ErrorCode::TooManyErrors,
+            Self::DbcAlreadySpent(_) => ErrorCode::DbcAlreadySpent,
+            Self::InvalidTransaction(_) => ErrorCode::InvalidTransaction,
+            Self::BatchAborted { error, .. } => error.code(),
+        }
+    }
+
+    /// Whether the operation may succeed if sent again unchanged, after backing off.
+    pub fn is_retryable(&self) -> bool {
+        self.code().is_retryable()
+    }
+
+    /// The address of the data the operation failed on, when the error tells it.
+    pub fn address(&self) -> Option<DataAddress> {
+        match self {
+            Self::DataNotFound(address) => Some(*address),
+            Self::BatchAborted { error, .. } => error.address(),
+            _ => None,
+        }
+    }
+}
+
+/// Version of the [`ErrorCode`]s, bumped whenever the meaning of a code changes. Codes are only
+/// ever added within a version.
+pub const ERROR_CODES_VERSION: u16 = 1;
+
+/// Stable code of an [`Error`], sent as a number so that clients can make sense of the codes of
+/// errors newer than them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(from = "u16", into = "u16")]
+pub enum ErrorCode {
+    /// The requester isn't allowed the operation.
+    AccessDenied,
+    /// The data isn't held, or not yet.
+    DataNotFound,
+    /// The data exists already.
+    DataExists,
+    /// The entry or key isn't in the data.
+    NoSuchEntry,
+    /// The owner given is invalid.
+    InvalidOwner,
+    /// A name or address given with the operation is invalid for it.
+    InvalidAddress,
+    /// The operation doesn't apply.
+    InvalidOperation,
+    /// Too few adults to store the data at.
+    InsufficientAdults,
+    /// The node has too many operations of the requester queued.
+    NodeBusy,
+    /// The operation was sent to the wrong section, or with an outdated section key.
+    WrongDestination,
+    /// The storage of the node failed.
+    StorageFailure,
+    /// The node failed for a reason of its own.
+    Internal,
+    /// Too many operations of the requester failed lately.
+    TooManyErrors,
+    /// The DBC is already spent, in another tx.
+    DbcAlreadySpent,
+    /// The tx a DBC is spent in is invalid.
+    InvalidTransaction,
+    /// A code unknown to this version.
+    Unknown(u16),
+}
+
+impl ErrorCode {
+    /// Whether operations failing with this code may succeed if sent again unchanged, after
+    /// backing off. Unknown codes are taken not to be.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::DataNotFound
+                | Self::InsufficientAdults
+                | Self::NodeBusy
+                | Self::WrongDestination
+                | Self::StorageFailure
+                | Self::Internal
+                | Self::TooManyErrors
+        )
+    }
+}
+
+impl From<u16> for ErrorCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => Self::AccessDenied,
+            2 => Self::DataNotFound,
+            3 => Self::DataExists,
+            4 => Self::NoSuchEntry,
+            5 => Self::InvalidOwner,
+            6 => Self::InvalidAddress,
+            7 => Self::InvalidOperation,
+            8 => Self::InsufficientAdults,
+            9 => Self::NodeBusy,
+            10 => Self::WrongDestination,
+            11 => Self::StorageFailure,
+            12 => Self::Internal,
+            13 => Self::TooManyErrors,
+            14 => Self::DbcAlreadySpent,
+            15 => Self::InvalidTransaction,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<ErrorCode> for u16 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::AccessDenied => 1,
+            ErrorCode::DataNotFound => 2,
+            ErrorCode::DataExists => 3,
+            ErrorCode::NoSuchEntry => 4,
+            ErrorCode::InvalidOwner => 5,
+            ErrorCode::InvalidAddress => 6,
+            ErrorCode::InvalidOperation => 7,
+            ErrorCode::InsufficientAdults => 8,
+            ErrorCode::NodeBusy => 9,
+            ErrorCode::WrongDestination => 10,
+            ErrorCode::StorageFailure => 11,
+            ErrorCode::Internal => 12,
+            ErrorCode::TooManyErrors => 13,
+            ErrorCode::DbcAlreadySpent => 14,
+            ErrorCode::InvalidTransaction => 15,
+            ErrorCode::Unknown(other) => other,
+        }
+    }
+}
+
+/// An error a node ran into processing a client's operation, with what the client needs to
+/// know to go about it: whether to retry, which section key to update its knowledge to first,
+/// and which data the operation failed on.
+#[derive(Error, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[error("{error} (code {code:?}, retryable: {retryable})")]
+pub struct ProcessingError {
+    /// Version of the error codes the node sent.
+    pub version: u16,
+    /// Stable code of the error.
+    pub code: ErrorCode,
+    /// Whether the operation may succeed if sent again unchanged, after backing off.
+    pub retryable: bool,
+    /// Key of the section of the node, which the client should update its knowledge of the
+    /// section to before retrying, if it doesn't know it yet.
+    pub refresh_section_key: Option<BlsPublicKey>,
+    /// Address of the data the operation failed on, if known.
+    pub address: Option<DataAddress>,
+    /// The error itself.
+    pub error: Error,
+}
+
+impl ProcessingError {
+    /// Has the client update its knowledge of the section to `section_key` before retrying.
+    pub fn with_section_key(mut self, section_key: BlsPublicKey) -> Self {
+        self.refresh_section_key = Some(section_key);
+        self
+    }
+}
+
+impl From<Error> for ProcessingError {
+    fn from(error: Error) -> Self {
+        let code = error.code();
+        Self {
+            version: ERROR_CODES_VERSION,
+            code,
+            retryable: code.is_retryable(),
+            refresh_section_key: None,
+            address: error.address(),
+            error,
+        }
+    }
+}
+
+/// What's wrong with a name or address given with an operation.
+#[derive(Error, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum AddressProblem {
+    /// The address is of a kind the operation doesn't apply to.
+    #[error("the operation needs a {expected:?} address")]
+    WrongKind {
+        /// The kind of address the operation needs.
+        expected: Scope,
+    },
+    /// The name isn't the one the data is stored under.
+    #[error("the data is stored under {expected:?}")]
+    NameMismatch {
+        /// The name the data is stored under.
+        expected: XorName,
+    },
+    /// The tag isn't the one of the register operated on.
+    #[error("the register's tag is {expected}")]
+    TagMismatch {
+        /// The tag of the register.
+        expected: u64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_stable_numbers() {
+        for number in 1..=15 {
+            let code = ErrorCode::from(number);
+            assert_ne!(code, ErrorCode::Unknown(number));
+            assert_eq!(u16::from(code), number);
+        }
+
+        // Codes of newer versions are kept, and not retried.
+        let code = ErrorCode::from(1000);
+        assert_eq!(code, ErrorCode::Unknown(1000));
+        assert_eq!(u16::from(code), 1000);
+        assert!(!code.is_retryable());
+    }
+
+    #[test]
+    fn processing_errors_tell_how_to_retry() {
+        let address = DataAddress::bytes(xor_name::rand::random(), Scope::Public);
+        let error = ProcessingError::from(Error::DataNotFound(address));
+        assert_eq!(error.version, ERROR_CODES_VERSION);
+        assert_eq!(error.code, ErrorCode::DataNotFound);
+        assert!(error.retryable);
+        assert_eq!(error.address, Some(address));
+
+        let section_key = bls::SecretKey::random().public_key();
+        let error = ProcessingError::from(Error::DataExists).with_section_key(section_key);
+        assert_eq!(error.code, ErrorCode::DataExists);
+        assert!(!error.retryable);
+        assert_eq!(error.address, None);
+        assert_eq!(error.refresh_section_key, Some(section_key));
+    }
+    #[test]
+    fn batches_aborted_are_retried_as_the_cmd_which_failed() {
+        let address = DataAddress::bytes(xor_name::rand::random(), Scope::Public);
+        let error = ProcessingError::from(Error::BatchAborted {
+            index: 3,
+            error: Box::new(Error::DataNotFound(address)),
+        });
+        assert_eq!(error.code, ErrorCode::DataNotFound);
+        assert!(error.retryable);
+        assert_eq!(error.address, Some(address));
+    }
 }
@@ -0,0 +1,58 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Error, OperationId, QueryResponse, Result};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tiny_keccak::{Hasher, Sha3};
+use xor_name::{Prefix, XorName};
+
+/// Query for the adults holding the data of a name, answered by the elders of its section.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Serialize, Deserialize, Debug)]
+pub struct HoldersQuery {
+    /// Name of the data.
+    pub name: XorName,
+}
+
+/// Where the data of a name is held, as seen by an elder of its section.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DataHolders {
+    /// Prefix of the section responsible for the data.
+    pub prefix: Prefix,
+    /// Key of that section.
+    pub section_key: bls::PublicKey,
+    /// The adults holding the data.
+    pub holders: BTreeSet<XorName>,
+    /// Whether the holders are those the elder recorded as holding the data, rather than those
+    /// which should be holding it, the elder not knowing yet.
+    pub recorded: bool,
+}
+
+impl HoldersQuery {
+    /// Creates a Response containing an error, with the Response variant corresponding to the
+    /// Request variant.
+    pub fn error(&self, error: Error) -> Result<QueryResponse> {
+        Ok(QueryResponse::GetDataHolders((
+            Err(error),
+            self.operation_id()?,
+        )))
+    }
+
+    /// Retrieves the operation identifier for this response, use in tracking node liveness
+    /// and responses at clients.
+    /// Must be the same as the query response
+    pub fn operation_id(&self) -> Result<OperationId> {
+        let bytes = crate::types::utils::encode(&self).map_err(|_| Error::NoOperationId)?;
+        let mut hasher = Sha3::v256();
+        let mut output = [0; 32];
+        hasher.update(bytes.as_bytes());
+        hasher.finalize(&mut output);
+        Ok(output)
+    }
+}
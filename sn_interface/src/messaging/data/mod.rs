@@ -8,21 +8,32 @@
 
 //! Data messages and their possible responses.
 
+mod batch;
 mod cmd;
 mod data_exchange;
 mod errors;
+mod holders;
 mod query;
 mod register;
+mod session;
+mod spentbook;
+mod validation;
 
 pub use self::{
+    batch::{BatchQuery, BatchStatus, MAX_BATCH_CMDS},
     cmd::DataCmd,
     data_exchange::{MetadataExchange, RegisterStoreExport, ReplicatedRegisterLog, StorageLevel},
-    errors::{Error, Result},
+    errors::{AddressProblem, Error, ErrorCode, ProcessingError, Result, ERROR_CODES_VERSION},
+    holders::{DataHolders, HoldersQuery},
     query::DataQuery,
     register::{
         CreateRegister, DeleteRegister, EditRegister, ExtendRegister, RegisterCmd, RegisterQuery,
         SignedRegisterCreate, SignedRegisterDelete, SignedRegisterEdit, SignedRegisterExtend,
+        MAX_HISTORY_RANGE,
     },
+    session::SessionToken,
+    spentbook::{spentbook_name, SpentbookCmd, SpentbookQuery},
+    validation::{validate_batch, validate_cmd, validate_query, validate_spentbook_msg},
 };
 
 use crate::types::{
@@ -35,6 +46,7 @@ use crate::{
 };
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use sn_dbc::{SpentProof, SpentProofShare};
 use std::{collections::BTreeSet, convert::TryFrom};
 use tiny_keccak::{Hasher, Sha3};
 use xor_name::XorName;
@@ -85,6 +97,52 @@ pub enum ServiceMsg {
     /// reply.
     /// [`QueryResponse`]: Self::QueryResponse
     Query(DataQuery),
+    /// A read-only operation, to be answered with the data written along with the writes the
+    /// `tokens` were sent back for.
+    ///
+    /// Each elder finding a token of its own among `tokens` holds the query until the adults
+    /// confirmed storing the data of the write, answering it as a [`Query`] then. Elders with no
+    /// token of their own among them answer it straight away.
+    ///
+    /// [`Query`]: Self::Query
+    ConsistentQuery {
+        /// The query.
+        query: DataQuery,
+        /// Tokens of the writes to read, as sent back with their [`CmdAck`].
+        ///
+        /// [`CmdAck`]: Self::CmdAck
+        tokens: Vec<SessionToken>,
+    },
+    /// A spentbook cmd, handled by the elders themselves rather than stored at adults.
+    ///
+    /// Acked with a [`CmdAck`] once the elder has logged its share of the spent proof, or
+    /// answered with a [`CmdError`] if it refused to.
+    ///
+    /// [`CmdAck`]: Self::CmdAck
+    /// [`CmdError`]: Self::CmdError
+    SpentbookCmd(SpentbookCmd),
+    /// A spentbook query, answered by the elders from their own spentbook.
+    ///
+    /// Senders should eventually receive either a corresponding [`QueryResponse`] or an error in
+    /// reply.
+    /// [`QueryResponse`]: Self::QueryResponse
+    SpentbookQuery(SpentbookQuery),
+    /// A batch of cmds, applied all or nothing by the elders: the data of each of them is only
+    /// sent to its holders once holders are found for all of it. Cmds of a batch must be on
+    /// data of the same section, and the batch not empty.
+    ///
+    /// Acked with a single [`CmdAck`] once all of it is sent, or answered with a [`CmdError`]
+    /// telling the cmd which failed, none of it being sent then.
+    ///
+    /// [`CmdAck`]: Self::CmdAck
+    /// [`CmdError`]: Self::CmdError
+    BatchCmd(Vec<DataCmd>),
+    /// Query for the status of a batch of cmds.
+    ///
+    /// This should eventually lead to a [`BatchStatus`] response.
+    ///
+    /// [`BatchStatus`]: QueryResponse::BatchStatus
+    BatchQuery(BatchQuery),
     /// The response to a query, containing the query result.
     QueryResponse {
         /// The result of the query.
@@ -112,7 +170,19 @@ pub enum ServiceMsg {
         ///
         /// [`Cmd`]: Self::Cmd
         correlation_id: MsgId,
+        /// Token of the write, for the client to read it with a [`ConsistentQuery`]. Only
+        /// acks of data cmds stored at adults carry one.
+        ///
+        /// [`ConsistentQuery`]: Self::ConsistentQuery
+        token: Option<SessionToken>,
     },
+    /// Query for the adults holding the data of a name, answered by the elders of its section
+    /// from their own records.
+    ///
+    /// This should eventually lead to a [`GetDataHolders`] response.
+    ///
+    /// [`GetDataHolders`]: QueryResponse::GetDataHolders
+    HoldersQuery(HoldersQuery),
 }
 
 impl ServiceMsg {
@@ -120,7 +190,12 @@ impl ServiceMsg {
     pub fn dst_address(&self) -> Option<XorName> {
         match self {
             Self::Cmd(cmd) => Some(cmd.dst_name()),
-            Self::Query(query) => Some(query.dst_name()),
+            Self::Query(query) | Self::ConsistentQuery { query, .. } => Some(query.dst_name()),
+            Self::SpentbookCmd(cmd) => Some(cmd.dst_name()),
+            Self::SpentbookQuery(query) => Some(query.dst_name()),
+            Self::BatchCmd(cmds) => cmds.first().map(DataCmd::dst_name),
+            Self::BatchQuery(query) => Some(query.dst_name),
+            Self::HoldersQuery(query) => Some(query.name),
             _ => None,
         }
     }
@@ -133,7 +208,16 @@ impl ServiceMsg {
 pub enum CmdError {
     /// An error response to a [`DataCmd`].
     // FIXME: `Cmd` is not an enum, so should this be?
-    Data(Error), // DataError enum for better differentiation?
+    Data(ProcessingError), // DataError enum for better differentiation?
+}
+
+impl CmdError {
+    /// Has the client update its knowledge of the section to `section_key` before retrying.
+    pub fn with_section_key(self, section_key: bls::PublicKey) -> Self {
+        match self {
+            Self::Data(error) => Self::Data(error.with_section_key(section_key)),
+        }
+    }
 }
 
 /// The response to a query, containing the query result.
@@ -161,11 +245,34 @@ pub enum QueryResponse {
     GetRegisterPolicy((Result<Policy>, OperationId)),
     /// Response to [`RegisterQuery::GetUserPermissions`].
     GetRegisterUserPermissions((Result<Permissions>, OperationId)),
+    /// Response to [`RegisterQuery::GetVersion`].
+    GetRegisterVersion((Result<(EntryHash, Entry)>, OperationId)),
+    /// Response to [`RegisterQuery::GetHistoryRange`].
+    GetRegisterHistory((Result<Vec<(EntryHash, Entry)>>, OperationId)),
+    //
+    // ===== Spentbook =====
+    //
+    /// Response to [`SpentbookQuery::SpentProofShares`], the shares logged so far, none if the
+    /// DBC isn't spent.
+    SpentProofShares((Result<Vec<SpentProofShare>>, OperationId)),
+    /// Response to [`SpentbookQuery::SpentProof`], `None` until enough elders logged the DBC as
+    /// spent.
+    SpentProof((Result<Option<SpentProof>>, OperationId)),
+    //
+    // ===== Batch =====
+    //
+    /// Response to [`BatchQuery`].
+    BatchStatus((Result<BatchStatus>, OperationId)),
     //
     // ===== Other =====
     //
     /// Failed to create id generation
     FailedToCreateOperationId,
+    //
+    // ===== Holders =====
+    //
+    /// Response to [`HoldersQuery`].
+    GetDataHolders((Result<DataHolders>, OperationId)),
 }
 
 impl QueryResponse {
@@ -180,7 +287,13 @@ impl QueryResponse {
             ReadRegister((result, _op_id)) => result.is_ok(),
             GetRegisterPolicy((result, _op_id)) => result.is_ok(),
             GetRegisterUserPermissions((result, _op_id)) => result.is_ok(),
+            GetRegisterVersion((result, _op_id)) => result.is_ok(),
+            GetRegisterHistory((result, _op_id)) => result.is_ok(),
+            SpentProofShares((result, _op_id)) => result.is_ok(),
+            SpentProof((result, _op_id)) => result.is_ok(),
+            BatchStatus((result, _op_id)) => result.is_ok(),
             FailedToCreateOperationId => false,
+            GetDataHolders((result, _op_id)) => result.is_ok(),
         }
     }
 
@@ -217,7 +330,20 @@ impl QueryResponse {
                 Ok(_) => false,
                 Err(error) => matches!(*error, ErrorMsg::DataNotFound(_)),
             },
+            GetRegisterVersion((result, _op_id)) => match result {
+                Ok(_) => false,
+                Err(error) => matches!(*error, ErrorMsg::DataNotFound(_)),
+            },
+            GetRegisterHistory((result, _op_id)) => match result {
+                Ok(_) => false,
+                Err(error) => matches!(*error, ErrorMsg::DataNotFound(_)),
+            },
+            // A DBC not spent is a valid answer, not data missing.
+            SpentProofShares(_) | SpentProof(_) => false,
+            BatchStatus(_) => false,
             FailedToCreateOperationId => false,
+            // No holders found is still an answer.
+            GetDataHolders(_) => false,
         }
     }
 
@@ -251,7 +377,13 @@ impl QueryResponse {
             | GetRegisterOwner((_, operation_id))
             | ReadRegister((_, operation_id))
             | GetRegisterPolicy((_, operation_id))
-            | GetRegisterUserPermissions((_, operation_id)) => Ok(*operation_id),
+            | GetRegisterUserPermissions((_, operation_id))
+            | GetRegisterVersion((_, operation_id))
+            | GetRegisterHistory((_, operation_id))
+            | SpentProofShares((_, operation_id))
+            | SpentProof((_, operation_id))
+            | BatchStatus((_, operation_id))
+            | GetDataHolders((_, operation_id)) => Ok(*operation_id),
             FailedToCreateOperationId => Err(Error::NoOperationId),
         }
     }
@@ -303,6 +435,10 @@ try_from!(User, GetRegisterOwner);
 try_from!(BTreeSet<(EntryHash, Entry)>, ReadRegister);
 try_from!(Policy, GetRegisterPolicy);
 try_from!(Permissions, GetRegisterUserPermissions);
+try_from!((EntryHash, Entry), GetRegisterVersion);
+try_from!(Vec<(EntryHash, Entry)>, GetRegisterHistory);
+try_from!(Vec<SpentProofShare>, SpentProofShares);
+try_from!(Option<SpentProof>, SpentProof);
 
 #[cfg(test)]
 mod tests {
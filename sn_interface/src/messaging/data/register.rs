@@ -70,8 +70,38 @@ pub enum RegisterQuery {
     ///
     /// [`GetRegisterOwner`]: QueryResponse::GetRegisterOwner
     GetOwner(Address),
+    /// Retrieve the entry at the given version of the [`Register`] at the given address, its
+    /// versions being the positions of its entries in [`Register::history`], counting from 0.
+    ///
+    /// This should eventually lead to a [`GetRegisterVersion`] response.
+    ///
+    /// [`GetRegisterVersion`]: QueryResponse::GetRegisterVersion
+    GetVersion {
+        /// Register address.
+        address: Address,
+        /// The version of the entry.
+        version: u64,
+    },
+    /// Retrieve the entries of the versions from `start` up to `end`, excluded, of the
+    /// [`Register`] at the given address, no more than [`MAX_HISTORY_RANGE`] of them.
+    ///
+    /// This should eventually lead to a [`GetRegisterHistory`] response.
+    ///
+    /// [`GetRegisterHistory`]: QueryResponse::GetRegisterHistory
+    GetHistoryRange {
+        /// Register address.
+        address: Address,
+        /// The first version to retrieve.
+        start: u64,
+        /// The version to stop before.
+        end: u64,
+    },
 }
 
+/// The most entries a [`RegisterQuery::GetHistoryRange`] is answered with, longer ranges being
+/// cut short for clients to page through them.
+pub const MAX_HISTORY_RANGE: u64 = 100;
+
 /// A [`Register`] cmd that is stored in a log on Adults.
 #[allow(clippy::large_enum_variant)]
 #[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
@@ -285,6 +315,14 @@ impl RegisterQuery {
                 Err(error),
                 self.operation_id()?,
             ))),
+            RegisterQuery::GetVersion { .. } => Ok(QueryResponse::GetRegisterVersion((
+                Err(error),
+                self.operation_id()?,
+            ))),
+            RegisterQuery::GetHistoryRange { .. } => Ok(QueryResponse::GetRegisterHistory((
+                Err(error),
+                self.operation_id()?,
+            ))),
         }
     }
 
@@ -296,7 +334,9 @@ impl RegisterQuery {
             | RegisterQuery::GetPolicy(ref address)
             | RegisterQuery::GetUserPermissions { ref address, .. }
             | RegisterQuery::GetEntry { ref address, .. }
-            | RegisterQuery::GetOwner(ref address) => *address,
+            | RegisterQuery::GetOwner(ref address)
+            | RegisterQuery::GetVersion { ref address, .. }
+            | RegisterQuery::GetHistoryRange { ref address, .. } => *address,
         }
     }
 
@@ -308,7 +348,9 @@ impl RegisterQuery {
             | RegisterQuery::GetPolicy(ref address)
             | RegisterQuery::GetUserPermissions { ref address, .. }
             | RegisterQuery::GetEntry { ref address, .. }
-            | RegisterQuery::GetOwner(ref address) => *address.name(),
+            | RegisterQuery::GetOwner(ref address)
+            | RegisterQuery::GetVersion { ref address, .. }
+            | RegisterQuery::GetHistoryRange { ref address, .. } => *address.name(),
         }
     }
 
@@ -329,7 +371,7 @@ impl RegisterCmd {
     /// Creates a Response containing an error, with the Response variant corresponding to the
     /// Request variant.
     pub fn error(&self, error: Error) -> CmdError {
-        CmdError::Data(error)
+        CmdError::Data(error.into())
     }
 
     /// Returns the name of the register.
@@ -356,6 +398,25 @@ impl RegisterCmd {
         }
     }
 
+    /// Returns the idempotency key of the cmd, derived from the op its requester signed.
+    ///
+    /// It's the same for every delivery of the cmd, whichever elder forwarded it, so a node
+    /// can tell a retry from a new op and apply each op once only.
+    pub fn idempotency_key(&self) -> Result<OperationId> {
+        let encoded = match self {
+            Self::Create { cmd, .. } => crate::types::utils::encode(&(0_u8, cmd)),
+            Self::Edit(cmd) => crate::types::utils::encode(&(1_u8, cmd)),
+            Self::Delete(cmd) => crate::types::utils::encode(&(2_u8, cmd)),
+            Self::Extend { cmd, .. } => crate::types::utils::encode(&(3_u8, cmd)),
+        }
+        .map_err(|_| Error::NoOperationId)?;
+        let mut hasher = Sha3::v256();
+        let mut output = [0; 32];
+        hasher.update(encoded.as_bytes());
+        hasher.finalize(&mut output);
+        Ok(output)
+    }
+
     /// Owner of the Register
     pub fn owner(&self) -> Option<User> {
         match self {
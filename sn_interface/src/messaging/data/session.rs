@@ -0,0 +1,36 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use bls::PublicKey as BlsPublicKey;
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// Token of a write of a client, sent back with the ack of the cmd by each elder applying it.
+///
+/// A [`ConsistentQuery`] carrying it is held by the elder which issued it until the adults
+/// holding the data of the write, and of every write the elder applied before it, confirmed
+/// storing it, for the client to read its own writes.
+///
+/// [`ConsistentQuery`]: super::ServiceMsg::ConsistentQuery
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionToken {
+    /// Key of the section of the elder when it issued the token.
+    pub section_key: BlsPublicKey,
+    /// Name of the elder which issued the token, the index being its own.
+    pub elder: XorName,
+    /// Index of the write among those the elder applied, counting from 1.
+    pub index: u64,
+}
+
+impl SessionToken {
+    /// Whether this token covers the writes `other` does, being from the same elder and no
+    /// earlier.
+    pub fn covers(&self, other: &Self) -> bool {
+        self.elder == other.elder && self.index >= other.index
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{CmdError, Error, OperationId, QueryResponse, Result};
+
+use serde::{Deserialize, Serialize};
+use sn_dbc::{Commitment, KeyImage, RingCtTransaction};
+use tiny_keccak::{Hasher, Sha3};
+use xor_name::XorName;
+
+/// The name of the spentbook entry of a DBC, which the section closest to it keeps.
+pub fn spentbook_name(key_image: &KeyImage) -> XorName {
+    XorName::from_content(&key_image.to_bytes())
+}
+
+/// Spentbook cmds, logging DBCs as spent. Handled by the elders, which each sign a share of the
+/// spent proof of the DBC.
+#[allow(clippy::large_enum_variant)]
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum SpentbookCmd {
+    /// Log the DBC of `key_image` as spent in `tx`.
+    ///
+    /// A DBC already logged as spent in another tx is refused with [`DbcAlreadySpent`]. Logging
+    /// it again in the same tx is fine, and gets the same spent proof.
+    ///
+    /// [`DbcAlreadySpent`]: Error::DbcAlreadySpent
+    Spend {
+        /// Key image of the DBC spent, one of the inputs of `tx`.
+        key_image: KeyImage,
+        /// The tx the DBC is spent in.
+        tx: RingCtTransaction,
+        /// The public commitments of each input ring of `tx`, in order.
+        public_commitments: Vec<Vec<Commitment>>,
+    },
+}
+
+impl SpentbookCmd {
+    /// Creates a Response containing an error, with the Response variant corresponding to the
+    /// Request variant.
+    pub fn error(&self, error: Error) -> CmdError {
+        CmdError::Data(error.into())
+    }
+
+    /// Returns the xorname of the data destination for `request`.
+    pub fn dst_name(&self) -> XorName {
+        match self {
+            Self::Spend { key_image, .. } => spentbook_name(key_image),
+        }
+    }
+
+    /// The key image of the DBC the cmd is on.
+    pub fn key_image(&self) -> &KeyImage {
+        match self {
+            Self::Spend { key_image, .. } => key_image,
+        }
+    }
+}
+
+/// Spentbook read operations, for wallets to learn whether a DBC is spent, and to get its spent
+/// proof.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Clone, Serialize, Deserialize, Debug)]
+pub enum SpentbookQuery {
+    /// Retrieve the spent proof shares of the DBC of the given key image, logged by the elders.
+    ///
+    /// This should eventually lead to a [`SpentProofShares`] response.
+    ///
+    /// [`SpentProofShares`]: QueryResponse::SpentProofShares
+    SpentProofShares(KeyImage),
+    /// Retrieve the spent proof of the DBC of the given key image, once enough elders logged it
+    /// as spent.
+    ///
+    /// This should eventually lead to a [`SpentProof`] response.
+    ///
+    /// [`SpentProof`]: QueryResponse::SpentProof
+    SpentProof(KeyImage),
+}
+
+impl SpentbookQuery {
+    /// Creates a Response containing an error, with the Response variant corresponding to the
+    /// Request variant.
+    pub fn error(&self, error: Error) -> Result<QueryResponse> {
+        let operation_id = self.operation_id()?;
+        match self {
+            Self::SpentProofShares(_) => {
+                Ok(QueryResponse::SpentProofShares((Err(error), operation_id)))
+            }
+            Self::SpentProof(_) => Ok(QueryResponse::SpentProof((Err(error), operation_id))),
+        }
+    }
+
+    /// Returns the xorname of the data destination for `request`.
+    pub fn dst_name(&self) -> XorName {
+        spentbook_name(self.key_image())
+    }
+
+    /// The key image of the DBC the query is on.
+    pub fn key_image(&self) -> &KeyImage {
+        match self {
+            Self::SpentProofShares(key_image) | Self::SpentProof(key_image) => key_image,
+        }
+    }
+
+    /// Retrieves the operation identifier for this response, use in tracking node liveness
+    /// and responses at clients.
+    /// Must be the same as the query response
+    pub fn operation_id(&self) -> Result<OperationId> {
+        let bytes = crate::types::utils::encode(&self).map_err(|_| Error::NoOperationId)?;
+        let mut hasher = Sha3::v256();
+        let mut output = [0; 32];
+        hasher.update(bytes.as_bytes());
+        hasher.finalize(&mut output);
+        Ok(output)
+    }
+}
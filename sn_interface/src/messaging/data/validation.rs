@@ -0,0 +1,313 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Checks on the names and addresses clients give with their cmds and queries.
+//!
+//! Elders run these before acting on a client msg, and clients can run them before sending one.
+//! Both return the canonical address of the data operated on, derived from the payload where
+//! there's one, which is what the operation is to be keyed on from then on.
+
+use super::{spentbook_name, AddressProblem, DataCmd, DataQuery, Error, Result, MAX_BATCH_CMDS};
+#[cfg(feature = "registers")]
+use super::{CreateRegister, RegisterCmd};
+use crate::types::ReplicatedDataAddress;
+#[cfg(feature = "registers")]
+use crate::types::{
+    register::{Policy, Register},
+    RegisterAddress, Scope,
+};
+use sn_dbc::KeyImage;
+use xor_name::XorName;
+
+/// Validates `cmd`, sent to the section of `dst_name`, returning the address of the data it's on.
+pub fn validate_cmd(cmd: &DataCmd, dst_name: &XorName) -> Result<ReplicatedDataAddress> {
+    let address = match cmd {
+        #[cfg(feature = "chunks")]
+        // A chunk's address is derived from its content when it's deserialised.
+        DataCmd::StoreChunk(chunk) => ReplicatedDataAddress::Chunk(*chunk.address()),
+        #[cfg(feature = "registers")]
+        DataCmd::Register(cmd) => ReplicatedDataAddress::Register(register_cmd_address(cmd)?),
+    };
+    check_name("dst.name", dst_name, address.name())?;
+    Ok(address)
+}
+
+/// Validates `query`, sent to the section of `dst_name`, returning the address of the data it's
+/// on.
+pub fn validate_query(query: &DataQuery, dst_name: &XorName) -> Result<ReplicatedDataAddress> {
+    let address = query.address();
+    check_name("dst.name", dst_name, address.name())?;
+    Ok(address)
+}
+
+/// Validates the batch of `cmds`, sent to the section of `dst_name`, returning the address of
+/// the data each of its cmds is on. A batch is sent to the name of its first cmd.
+pub fn validate_batch(cmds: &[DataCmd], dst_name: &XorName) -> Result<Vec<ReplicatedDataAddress>> {
+    if cmds.is_empty() || cmds.len() > MAX_BATCH_CMDS {
+        return Err(Error::InvalidOperation(format!(
+            "a batch holds 1 to {} cmds, not {}",
+            MAX_BATCH_CMDS,
+            cmds.len()
+        )));
+    }
+    check_name("dst.name", dst_name, &cmds[0].dst_name())?;
+    cmds.iter()
+        .enumerate()
+        .map(|(index, cmd)| {
+            validate_cmd(cmd, &cmd.dst_name()).map_err(|error| Error::BatchAborted {
+                index: index as u16,
+                error: Box::new(error),
+            })
+        })
+        .collect()
+}
+
+/// Validates a spentbook cmd or query on the DBC of `key_image`, sent to the section of
+/// `dst_name`.
+pub fn validate_spentbook_msg(key_image: &KeyImage, dst_name: &XorName) -> Result<()> {
+    check_name("dst.name", dst_name, &spentbook_name(key_image))
+}
+
+#[cfg(feature = "registers")]
+fn register_cmd_address(cmd: &RegisterCmd) -> Result<RegisterAddress> {
+    match cmd {
+        RegisterCmd::Create { cmd, .. } => match &cmd.op {
+            CreateRegister::Empty {
+                name, tag, policy, ..
+            } => Ok(RegisterAddress::new(*name, policy_scope(policy), *tag)),
+            CreateRegister::Populated(register) => {
+                // Who can access a register is down to its policy, which its address must match.
+                let address = canonical_address(register);
+                check_kind("op.register.address", register.address(), address.scope())?;
+                Ok(address)
+            }
+        },
+        RegisterCmd::Edit(cmd) => {
+            let address = cmd.op.address;
+            let edit_address = &cmd.op.edit.address;
+            check_name("op.edit.address", edit_address.name(), address.name())?;
+            check_kind("op.edit.address", edit_address, address.scope())?;
+            if edit_address.tag() != address.tag() {
+                return Err(invalid(
+                    "op.edit.address",
+                    AddressProblem::TagMismatch {
+                        expected: address.tag(),
+                    },
+                ));
+            }
+            Ok(address)
+        }
+        RegisterCmd::Delete(cmd) => {
+            // Public data is never deleted.
+            check_kind("op.address", &cmd.op.0, Scope::Private)?;
+            Ok(cmd.op.0)
+        }
+        RegisterCmd::Extend { cmd, .. } => Ok(cmd.op.address),
+    }
+}
+
+#[cfg(feature = "registers")]
+fn canonical_address(register: &Register) -> RegisterAddress {
+    RegisterAddress::new(
+        *register.name(),
+        policy_scope(register.policy()),
+        register.tag(),
+    )
+}
+
+#[cfg(feature = "registers")]
+fn policy_scope(policy: &Policy) -> Scope {
+    match policy {
+        Policy::Public(_) => Scope::Public,
+        Policy::Private(_) => Scope::Private,
+    }
+}
+
+fn check_name(field: &str, name: &XorName, expected: &XorName) -> Result<()> {
+    if name == expected {
+        Ok(())
+    } else {
+        Err(invalid(
+            field,
+            AddressProblem::NameMismatch {
+                expected: *expected,
+            },
+        ))
+    }
+}
+
+#[cfg(feature = "registers")]
+fn check_kind(field: &str, address: &RegisterAddress, expected: Scope) -> Result<()> {
+    if address.scope() == expected {
+        Ok(())
+    } else {
+        Err(invalid(field, AddressProblem::WrongKind { expected }))
+    }
+}
+
+fn invalid(field: &str, problem: AddressProblem) -> Error {
+    Error::InvalidAddress {
+        field: field.to_string(),
+        problem,
+    }
+}
+
+#[cfg(all(test, feature = "chunks", feature = "registers"))]
+mod tests {
+    use super::*;
+    use crate::messaging::{
+        data::{DeleteRegister, SignedRegisterCreate, SignedRegisterDelete},
+        system::KeyedSig,
+        SectionAuth, ServiceAuth,
+    };
+    use crate::types::{
+        register::{PrivatePolicy, User},
+        utils::random_bytes,
+        Chunk, Keypair,
+    };
+    use std::collections::BTreeMap;
+
+    fn service_auth(keypair: &Keypair) -> ServiceAuth {
+        ServiceAuth {
+            public_key: keypair.public_key(),
+            signature: keypair.sign(b"op"),
+        }
+    }
+
+    fn section_auth() -> SectionAuth {
+        let sk = bls::SecretKey::random();
+        SectionAuth {
+            src_name: xor_name::rand::random(),
+            sig: KeyedSig {
+                public_key: sk.public_key(),
+                signature: sk.sign(b"op"),
+            },
+        }
+    }
+
+    fn create(op: CreateRegister, keypair: &Keypair) -> DataCmd {
+        DataCmd::Register(RegisterCmd::Create {
+            cmd: SignedRegisterCreate {
+                op,
+                auth: service_auth(keypair),
+            },
+            section_auth: section_auth(),
+        })
+    }
+
+    #[test]
+    fn deleting_a_public_register_is_rejected() {
+        let keypair = Keypair::new_ed25519();
+        let address = RegisterAddress::Public {
+            name: xor_name::rand::random(),
+            tag: 15000,
+        };
+        let cmd = DataCmd::Register(RegisterCmd::Delete(SignedRegisterDelete {
+            op: DeleteRegister(address),
+            auth: service_auth(&keypair),
+        }));
+
+        assert_eq!(
+            validate_cmd(&cmd, address.name()),
+            Err(Error::InvalidAddress {
+                field: "op.address".to_string(),
+                problem: AddressProblem::WrongKind {
+                    expected: Scope::Private
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn chunk_sent_under_another_name_is_rejected() {
+        let chunk = Chunk::new(random_bytes(100));
+        let address = *chunk.address();
+        let expected = *address.name();
+        let cmd = DataCmd::StoreChunk(chunk);
+
+        assert_eq!(
+            validate_cmd(&cmd, &xor_name::rand::random()),
+            Err(Error::InvalidAddress {
+                field: "dst.name".to_string(),
+                problem: AddressProblem::NameMismatch { expected },
+            })
+        );
+        assert_eq!(
+            validate_cmd(&cmd, &expected),
+            Ok(ReplicatedDataAddress::Chunk(address))
+        );
+    }
+
+    #[test]
+    fn register_creation_is_keyed_the_same_however_its_sent() {
+        let keypair = Keypair::new_ed25519();
+        let owner = User::Key(keypair.public_key());
+        let policy = Policy::Private(PrivatePolicy {
+            owner,
+            permissions: BTreeMap::new(),
+        });
+        let name = xor_name::rand::random();
+
+        let empty = create(
+            CreateRegister::Empty {
+                name,
+                tag: 15000,
+                size: u16::MAX,
+                policy: policy.clone(),
+            },
+            &keypair,
+        );
+        let populated = create(
+            CreateRegister::Populated(Register::new(name, 15000, policy, u16::MAX)),
+            &keypair,
+        );
+
+        let expected =
+            ReplicatedDataAddress::Register(RegisterAddress::Private { name, tag: 15000 });
+        assert_eq!(validate_cmd(&empty, &name), Ok(expected));
+        assert_eq!(validate_cmd(&populated, &name), Ok(expected));
+    }
+
+    #[test]
+    fn batches_tell_the_cmd_failing_validation() {
+        let chunk = Chunk::new(random_bytes(100));
+        let chunk_address = *chunk.address();
+        let name = *chunk.name();
+        let keypair = Keypair::new_ed25519();
+        let address = RegisterAddress::Public {
+            name: xor_name::rand::random(),
+            tag: 15000,
+        };
+        let delete = DataCmd::Register(RegisterCmd::Delete(SignedRegisterDelete {
+            op: DeleteRegister(address),
+            auth: service_auth(&keypair),
+        }));
+        let store = DataCmd::StoreChunk(chunk);
+
+        assert_eq!(
+            validate_batch(&[store.clone()], &name),
+            Ok(vec![ReplicatedDataAddress::Chunk(chunk_address)])
+        );
+        assert!(matches!(
+            validate_batch(&[store.clone(), delete], &name),
+            Err(Error::BatchAborted { index: 1, .. })
+        ));
+        assert!(matches!(
+            validate_batch(&[store.clone()], &xor_name::rand::random()),
+            Err(Error::InvalidAddress { .. })
+        ));
+        assert!(matches!(
+            validate_batch(&[], &name),
+            Err(Error::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            validate_batch(&vec![store; MAX_BATCH_CMDS + 1], &name),
+            Err(Error::InvalidOperation(_))
+        ));
+    }
+}
@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::serialisation::ProtocolVersions;
 use std::result;
 use thiserror::Error;
 
@@ -36,7 +37,12 @@ pub enum Error {
     #[error("Invalid signature")]
     InvalidSignature,
 
-    /// Message read was built with an unsupported version.
-    #[error("Unsupported messaging protocol version: {0}")]
-    UnsupportedVersion(u16),
+    /// Message read was built with a version of the messaging protocol we can't read.
+    #[error("Incompatible messaging protocol version {version}, we read {supported}")]
+    IncompatibleVersion {
+        /// Version the message was built with.
+        version: u16,
+        /// Versions we read.
+        supported: ProtocolVersions,
+    },
 }
@@ -26,6 +26,9 @@ pub mod serialisation;
 pub mod signature_aggregator;
 /// Operational Messages that can be exchanged on the network.
 pub mod system;
+/// Deterministic instances of every message type, for tests.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 // Message authority - keys and signatures.
 mod authority;
@@ -39,6 +42,8 @@ mod msg_id;
 mod auth_kind;
 // SectionAuthorityProvider
 mod sap;
+// Traces of the nodes requests go through
+mod trace;
 
 pub use self::{
     auth_kind::AuthKind,
@@ -49,5 +54,6 @@ pub use self::{
     location::{DstLocation, EndUser, SrcLocation},
     msg_id::{MsgId, MESSAGE_ID_LEN},
     sap::SectionAuthorityProvider,
-    serialisation::{MsgType, NodeMsgAuthority, WireMsg},
+    serialisation::{MsgType, NodeMsgAuthority, ProtocolVersions, WireMsg},
+    trace::{TraceContext, TraceHop, MAX_DUTY_LEN, MAX_TRACE_HOPS},
 };
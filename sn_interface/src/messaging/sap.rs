@@ -18,6 +18,8 @@ use std::{
 use xor_name::{Prefix, XorName};
 
 use super::system::NodeState;
+use crate::network_knowledge::NetworkParams;
+use crate::protocol_constants::ProtocolDigest;
 
 // TODO: we need to maintain a list of nodes who have previosly been members of this section (archived nodes)
 //       currently, only the final members of the section are preserved on the SAP.
@@ -36,6 +38,10 @@ pub struct SectionAuthorityProvider {
     pub elders: BTreeMap<XorName, SocketAddr>,
     /// The section members at the time of this elder churn.
     pub members: BTreeMap<XorName, NodeState>,
+    /// Digest of the protocol constants of the elders who made it.
+    pub protocol_digest: ProtocolDigest,
+    /// Params of the network the section is part of.
+    pub network_params: NetworkParams,
 }
 
 impl Borrow<Prefix> for SectionAuthorityProvider {
@@ -6,6 +6,9 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod shared_payload;
+#[cfg(test)]
+mod wire_format_tests;
 mod wire_msg;
 mod wire_msg_header;
 
@@ -35,7 +38,15 @@ pub(crate) const SERVICE_QUERY_PRIORITY: i32 = -10;
 
 use crate::types::PublicKey;
 
-pub use self::wire_msg::WireMsg;
+pub(crate) use self::shared_payload::shared_bytes;
+
+pub use self::{
+    wire_msg::WireMsg,
+    wire_msg_header::{
+        ProtocolVersions, MAX_COMPATIBLE_PROTO_VERSION, MESSAGING_PROTO_VERSION,
+        MIN_COMPATIBLE_PROTO_VERSION,
+    },
+};
 use super::{
     data::ServiceMsg,
     system::{NodeEvent, SystemMsg},
@@ -97,7 +108,9 @@ impl MsgType {
                     SystemMsg::AntiEntropyRetry { .. }
                     | SystemMsg::AntiEntropyRedirect { .. }
                     | SystemMsg::AntiEntropyUpdate { .. }
-                    | SystemMsg::AntiEntropyProbe(_),
+                    | SystemMsg::AntiEntropyProbe(_)
+                    | SystemMsg::ListenerRedirect { .. }
+                    | SystemMsg::Relay(_),
                 ..
             } => ANTIENTROPY_MSG_PRIORITY,
 
@@ -114,6 +127,7 @@ impl MsgType {
                     | SystemMsg::JoinRequest(_)
                     | SystemMsg::JoinAsRelocatedRequest(_)
                     | SystemMsg::Propose { .. }
+                    | SystemMsg::ProposeBatch { .. }
                     | SystemMsg::StartConnectivityTest(_)
                     | SystemMsg::MembershipVotes(_)
                     | SystemMsg::MembershipAE(_)
@@ -144,7 +158,9 @@ impl MsgType {
             MsgType::System {
                 msg:
                     SystemMsg::NodeCmd(_)
-                    | SystemMsg::NodeEvent(NodeEvent::CouldNotStoreData { .. })
+                    | SystemMsg::NodeEvent(
+                        NodeEvent::CouldNotStoreData { .. } | NodeEvent::ReplicasStored { .. },
+                    )
                     | SystemMsg::NodeQuery(_)
                     | SystemMsg::NodeQueryResponse { .. },
                 ..
@@ -153,7 +169,7 @@ impl MsgType {
             // Client <-> node service comms
             #[cfg(any(feature = "chunks", feature = "registers"))]
             MsgType::Service {
-                msg: ServiceMsg::Cmd(_),
+                msg: ServiceMsg::Cmd(_) | ServiceMsg::SpentbookCmd(_) | ServiceMsg::BatchCmd(_),
                 ..
             } => SERVICE_CMD_PRIORITY,
             #[cfg(any(feature = "chunks", feature = "registers"))]
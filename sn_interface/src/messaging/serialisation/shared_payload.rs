@@ -0,0 +1,82 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+
+thread_local! {
+    // The payload being deserialised on this thread, if any.
+    static PAYLOAD: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+}
+
+/// Deserialises the Msgpack `payload`, the values deserialised with `shared_bytes` sharing its
+/// bytes rather than being copied out of it, so the chunks of a msg are never copied on their
+/// way from the network to the store or on to other nodes.
+pub(crate) fn from_payload<T: DeserializeOwned>(
+    payload: &Bytes,
+) -> Result<T, rmp_serde::decode::Error> {
+    let _current = CurrentPayload::set(payload.clone());
+    rmp_serde::from_slice(payload)
+}
+
+/// The bytes of `slice` as a slice of the payload being deserialised, if it's part of it, or as
+/// a copy of them otherwise.
+pub(crate) fn shared_bytes(slice: &[u8]) -> Bytes {
+    PAYLOAD.with(|payload| match &*payload.borrow() {
+        Some(payload) if is_within(payload, slice) => payload.slice_ref(slice),
+        _ => Bytes::copy_from_slice(slice),
+    })
+}
+
+fn is_within(payload: &Bytes, slice: &[u8]) -> bool {
+    let start = payload.as_ptr() as usize;
+    let slice_start = slice.as_ptr() as usize;
+    slice_start >= start && slice_start + slice.len() <= start + payload.len()
+}
+
+// Sets the payload being deserialised, restoring the previous one once dropped, even if the
+// deserialisation panics.
+struct CurrentPayload(Option<Bytes>);
+
+impl CurrentPayload {
+    fn set(payload: Bytes) -> Self {
+        Self(PAYLOAD.with(|current| current.replace(Some(payload))))
+    }
+}
+
+impl Drop for CurrentPayload {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        PAYLOAD.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{utils::random_bytes, Chunk};
+
+    #[test]
+    fn chunks_share_the_bytes_of_the_payload() -> Result<(), rmp_serde::decode::Error> {
+        let chunks = vec![Chunk::new(random_bytes(1024)), Chunk::new(random_bytes(0))];
+        let payload = Bytes::from(rmp_serde::to_vec_named(&chunks).expect("serialised"));
+
+        let shared: Vec<Chunk> = from_payload(&payload)?;
+        assert_eq!(shared, chunks);
+        assert!(is_within(&payload, shared[0].value()));
+
+        // Outside of a payload, they're copied.
+        let copied: Vec<Chunk> = rmp_serde::from_slice(&payload)?;
+        assert_eq!(copied, chunks);
+        assert!(!is_within(&payload, copied[0].value()));
+        assert!(PAYLOAD.with(|payload| payload.borrow().is_none()));
+
+        Ok(())
+    }
+}
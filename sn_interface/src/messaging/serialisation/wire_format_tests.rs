@@ -0,0 +1,150 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Wire-format regression tests.
+//!
+//! Each representative message from `messaging::test_utils` is serialized and compared against
+//! its golden file in `sn_interface/fixtures/wire_format/<name>.hex`. Any change to the bytes a
+//! message serializes to (a renamed, reordered, added or removed field or variant) fails here
+//! with a line-wise hex diff.
+//!
+//! Updating the goldens must be a deliberate act, done only when a wire-format change is
+//! intended and the crate version is bumped accordingly:
+//!
+//! ```text
+//! SN_UPDATE_WIRE_GOLDENS=1 cargo test --features=test-utils,back-pressure -p sn_interface wire_format
+//! ```
+//!
+//! then review the resulting `git diff` of the fixtures directory and commit it together with
+//! the change to the message types.
+
+use crate::messaging::{test_utils, WireMsg};
+
+use eyre::{eyre, Result};
+use std::{fmt::Write, fs, path::PathBuf};
+
+const UPDATE_ENV_VAR: &str = "SN_UPDATE_WIRE_GOLDENS";
+const BYTES_PER_LINE: usize = 16;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("wire_format")
+}
+
+fn to_hex_lines(bytes: &[u8]) -> String {
+    bytes
+        .chunks(BYTES_PER_LINE)
+        .fold(String::new(), |mut out, line| {
+            let _ = writeln!(out, "{}", hex::encode(line));
+            out
+        })
+}
+
+// Returns a readable description of the differing lines, or `None` if equal.
+fn diff(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (exp, act) = (expected.get(i), actual.get(i));
+        if exp != act {
+            let _ = writeln!(out, "@ byte offset {:#06x}", i * BYTES_PER_LINE);
+            if let Some(exp) = exp {
+                let _ = writeln!(out, "- {}", exp);
+            }
+            if let Some(act) = act {
+                let _ = writeln!(out, "+ {}", act);
+            }
+        }
+    }
+    Some(out)
+}
+
+fn all_serialized() -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut all = Vec::new();
+    for (name, msg) in test_utils::representative_system_msgs() {
+        all.push((name, WireMsg::serialize_msg_payload(&msg)?.to_vec()));
+    }
+    for (name, msg) in test_utils::representative_service_msgs() {
+        all.push((name, WireMsg::serialize_msg_payload(&msg)?.to_vec()));
+    }
+    for (name, wire_msg) in test_utils::representative_wire_msgs() {
+        all.push((name, wire_msg.serialize()?.to_vec()));
+    }
+    Ok(all)
+}
+
+#[test]
+fn serialization_is_deterministic() -> Result<()> {
+    assert_eq!(all_serialized()?, all_serialized()?);
+    Ok(())
+}
+
+#[test]
+fn wire_format_matches_goldens() -> Result<()> {
+    let dir = fixtures_dir();
+    let update = std::env::var(UPDATE_ENV_VAR).is_ok();
+    if update {
+        fs::create_dir_all(&dir)?;
+    }
+
+    let mut failures = Vec::new();
+    for (name, bytes) in all_serialized()? {
+        let path = dir.join(format!("{}.hex", name));
+        let actual = to_hex_lines(&bytes);
+
+        if update {
+            fs::write(&path, &actual)?;
+            continue;
+        }
+
+        let expected = fs::read_to_string(&path)
+            .map_err(|err| eyre!("missing golden file {}: {}", path.display(), err))?;
+        if let Some(diff) = diff(&expected, &actual) {
+            failures.push(format!("{} ({}):\n{}", name, path.display(), diff));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "wire format changed for {} message(s); if intended, rerun with {}=1 and commit \
+            the updated goldens.\n\n{}",
+            failures.len(),
+            UPDATE_ENV_VAR,
+            failures.join("\n")
+        ))
+    }
+}
+
+#[test]
+fn every_golden_has_a_message() -> Result<()> {
+    let names: Vec<_> = all_serialized()?
+        .into_iter()
+        .map(|(name, _)| format!("{}.hex", name))
+        .collect();
+    for entry in fs::read_dir(fixtures_dir())? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        // `back-pressure` goldens are only produced when that feature is enabled.
+        if file_name.starts_with("system_back_pressure") {
+            continue;
+        }
+        assert!(
+            names.contains(&file_name),
+            "stale golden file without a representative message: {}",
+            file_name
+        );
+    }
+    Ok(())
+}
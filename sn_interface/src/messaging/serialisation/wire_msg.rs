@@ -6,18 +6,20 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::wire_msg_header::WireMsgHeader;
+use super::{
+    shared_payload::from_payload,
+    wire_msg_header::{ProtocolVersions, WireMsgHeader},
+};
 use crate::messaging::{
     data::{ServiceError, ServiceMsg},
     system::SystemMsg,
     AuthKind, AuthorityProof, DstLocation, Error, MsgId, MsgType, NodeMsgAuthority, Result,
-    ServiceAuth,
+    ServiceAuth, TraceContext,
 };
 use bls::PublicKey as BlsPublicKey;
 use bytes::Bytes;
 use custom_debug::Debug;
 use serde::Serialize;
-use std::io::Write;
 use xor_name::XorName;
 
 /// In order to send a message over the wire, it needs to be serialized
@@ -94,25 +96,18 @@ impl WireMsg {
     /// Return the serialized WireMsg, which contains the WireMsgHeader bytes,
     /// followed by the payload bytes, i.e. the serialized Message.
     pub fn serialize(&self) -> Result<Bytes> {
-        // First we create a buffer with the capacity
-        // needed to serialize the wire msg
-        // FIXME: don't multiplying the max size by a factor of 10 and calculate the correct size.
-        let max_length = 10 * (WireMsgHeader::max_size() as usize + self.payload.len());
-        let mut buffer = vec![0u8; max_length];
-
-        let (mut buf_at_payload, bytes_written) = self.header.write(&mut buffer)?;
-
-        // ...and finally we write the bytes of the serialized payload to the original buffer
-        buf_at_payload.write_all(&self.payload).map_err(|err| {
-            Error::Serialisation(format!(
-                "message payload (size {}) couldn't be serialized: {}",
-                self.payload.len(),
-                err
-            ))
-        })?;
-
-        // We can now return the buffer containing the written bytes
-        buffer.truncate(bytes_written as usize + self.payload.len());
+        // The payload is the bulk of the msg, so the buffer is sized for it to be copied in after
+        // the header, once, without reallocating.
+        let trace_len = self
+            .header
+            .msg_envelope
+            .trace
+            .as_ref()
+            .map_or(0, TraceContext::max_serialised_len);
+        let mut buffer =
+            Vec::with_capacity(WireMsgHeader::max_size() as usize + trace_len + self.payload.len());
+        let _header_len = self.header.write(&mut buffer)?;
+        buffer.extend_from_slice(&self.payload);
         Ok(Bytes::from(buffer))
     }
 
@@ -121,8 +116,8 @@ impl WireMsg {
         match self.header.msg_envelope.msg_kind.clone() {
             #[cfg(any(feature = "chunks", feature = "registers"))]
             AuthKind::Service(auth) => {
-                let msg: ServiceMsg = rmp_serde::from_slice(&self.payload).map_err(|err| {
-                    Error::FailedToParse(format!("Data message payload as Msgpack: {}", err))
+                let msg: ServiceMsg = from_payload(&self.payload).map_err(|err| {
+                    self.payload_error(format!("Data message payload as Msgpack: {}", err))
                 })?;
 
                 let auth = if let ServiceMsg::ServiceError(ServiceError {
@@ -143,8 +138,8 @@ impl WireMsg {
                 })
             }
             AuthKind::Node(node_signed) => {
-                let msg: SystemMsg = rmp_serde::from_slice(&self.payload).map_err(|err| {
-                    Error::FailedToParse(format!("Node signed message payload as Msgpack: {}", err))
+                let msg: SystemMsg = from_payload(&self.payload).map_err(|err| {
+                    self.payload_error(format!("Node signed message payload as Msgpack: {}", err))
                 })?;
 
                 Ok(MsgType::System {
@@ -158,8 +153,8 @@ impl WireMsg {
                 })
             }
             AuthKind::NodeBlsShare(bls_share_signed) => {
-                let msg: SystemMsg = rmp_serde::from_slice(&self.payload).map_err(|err| {
-                    Error::FailedToParse(format!(
+                let msg: SystemMsg = from_payload(&self.payload).map_err(|err| {
+                    self.payload_error(format!(
                         "Node message payload (BLS share signed) as Msgpack: {}",
                         err
                     ))
@@ -178,6 +173,21 @@ impl WireMsg {
         }
     }
 
+    // A payload written with another (still compatible) version than ours failing to parse is
+    // most likely due to that version, which the error tells instead of a plain parsing failure.
+    fn payload_error(&self, reason: String) -> Error {
+        let ours = ProtocolVersions::ours();
+        if self.header.version() != ours.current {
+            warn!("Failed to parse {}", reason);
+            Error::IncompatibleVersion {
+                version: self.header.version(),
+                supported: ours,
+            }
+        } else {
+            Error::FailedToParse(reason)
+        }
+    }
+
     /// Return the message id of this message
     pub fn msg_id(&self) -> MsgId {
         self.header.msg_envelope.msg_id
@@ -208,6 +218,16 @@ impl WireMsg {
         self.header.msg_envelope.dst_location.set_name(name)
     }
 
+    /// Return the trace of the request this message is part of, if one was asked for
+    pub fn trace(&self) -> Option<&TraceContext> {
+        self.header.msg_envelope.trace.as_ref()
+    }
+
+    /// Update the trace of the request this message is part of
+    pub fn set_trace(&mut self, trace: Option<TraceContext>) {
+        self.header.msg_envelope.trace = trace;
+    }
+
     /// Return the destination for this message
     pub fn dst_location(&self) -> &DstLocation {
         &self.header.msg_envelope.dst_location
@@ -248,11 +268,14 @@ impl WireMsg {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messaging::serialisation::{
+        MAX_COMPATIBLE_PROTO_VERSION, MESSAGING_PROTO_VERSION, MIN_COMPATIBLE_PROTO_VERSION,
+    };
     use crate::{
         messaging::{
             data::{DataQuery, ServiceMsg, StorageLevel},
             system::{NodeCmd, SystemMsg},
-            AuthorityProof, MsgId, NodeAuth, ServiceAuth,
+            AuthorityProof, MsgId, NodeAuth, ServiceAuth, MAX_TRACE_HOPS,
         },
         types::{ChunkAddress, Keypair},
     };
@@ -361,4 +384,112 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn msgs_of_incompatible_versions_are_told_apart() -> Result<()> {
+        let src_client_keypair = Keypair::new_ed25519();
+        let client_msg =
+            ServiceMsg::Query(DataQuery::GetChunk(ChunkAddress(xor_name::rand::random())));
+        let payload = WireMsg::serialize_msg_payload(&client_msg)?;
+        let auth = ServiceAuth {
+            public_key: src_client_keypair.public_key(),
+            signature: src_client_keypair.sign(&payload),
+        };
+        let dst_location = DstLocation::Section {
+            name: xor_name::rand::random(),
+            section_pk: SecretKey::random().public_key(),
+        };
+        let wire_msg =
+            WireMsg::new_msg(MsgId::new(), payload, AuthKind::Service(auth), dst_location)?;
+        assert_eq!(wire_msg.header.version(), MESSAGING_PROTO_VERSION);
+
+        // The version follows the header length, both big-endian `u16`s.
+        let with_version = |version: u16| -> Result<Bytes> {
+            let mut bytes = wire_msg.serialize()?.to_vec();
+            bytes[2..4].copy_from_slice(&version.to_be_bytes());
+            Ok(bytes.into())
+        };
+
+        // The first version is also refused, its layouts no longer being read.
+        for version in [
+            1,
+            MIN_COMPATIBLE_PROTO_VERSION - 1,
+            MAX_COMPATIBLE_PROTO_VERSION + 1,
+        ] {
+            assert!(matches!(
+                WireMsg::from(with_version(version)?),
+                Err(Error::IncompatibleVersion { version: v, supported })
+                    if v == version && supported == ProtocolVersions::ours()
+            ));
+        }
+        assert_eq!(
+            WireMsg::from(with_version(MESSAGING_PROTO_VERSION)?)?,
+            wire_msg
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn trace_goes_along_with_the_msg() -> Result<()> {
+        let client_msg =
+            ServiceMsg::Query(DataQuery::GetChunk(ChunkAddress(xor_name::rand::random())));
+        let payload = WireMsg::serialize_msg_payload(&client_msg)?;
+        let src_client_keypair = Keypair::new_ed25519();
+        let auth = ServiceAuth {
+            public_key: src_client_keypair.public_key(),
+            signature: src_client_keypair.sign(&payload),
+        };
+        let dst_location = DstLocation::Section {
+            name: xor_name::rand::random(),
+            section_pk: SecretKey::random().public_key(),
+        };
+        let mut wire_msg =
+            WireMsg::new_msg(MsgId::new(), payload, AuthKind::Service(auth), dst_location)?;
+        let untraced_len = wire_msg.serialize()?.len();
+
+        let mut trace = TraceContext::default();
+        for _ in 0..MAX_TRACE_HOPS {
+            trace.add_hop(xor_name::rand::random(), "elder: client query");
+        }
+        wire_msg.set_trace(Some(trace.clone()));
+        let serialized = wire_msg.serialize()?;
+        assert!(serialized.len() > untraced_len);
+
+        let deserialized = WireMsg::from(serialized)?;
+        assert_eq!(deserialized.trace(), Some(&trace));
+        assert_eq!(deserialized.into_msg()?, wire_msg.into_msg()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn adjacent_releases_are_compatible_if_each_reads_what_the_other_writes() {
+        let versions = |current, min_compatible, max_compatible| ProtocolVersions {
+            current,
+            min_compatible,
+            max_compatible,
+        };
+        // Rolling out v2: readers of it first, then writers, then dropping v1.
+        let releases = [
+            versions(1, 1, 1),
+            versions(1, 1, 2),
+            versions(2, 1, 2),
+            versions(2, 2, 2),
+        ];
+
+        for (i, a) in releases.iter().enumerate() {
+            for (j, b) in releases.iter().enumerate() {
+                let adjacent = i.abs_diff(j) <= 1;
+                assert_eq!(a.compatible_with(b), adjacent, "{} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn releases_predating_negotiation_are_incompatible() {
+        let ours = ProtocolVersions::ours();
+        assert!(!ours.compatible_with(&ProtocolVersions::initial()));
+        assert!(!ProtocolVersions::initial().compatible_with(&ours));
+    }
 }
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::messaging::{AuthKind, DstLocation, Error, MsgId, Result};
+use crate::messaging::{AuthKind, DstLocation, Error, MsgId, Result, TraceContext};
 use bincode::{
     config::{BigEndian, FixintEncoding, WithOtherEndian, WithOtherIntEncoding},
     Options,
@@ -14,11 +14,80 @@ use bincode::{
 use bytes::Bytes;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::{io::Write, mem::size_of};
+use std::mem::size_of;
+
+/// Current version of the messaging protocol, which all msgs are written with.
+pub const MESSAGING_PROTO_VERSION: u16 = 8u16;
+
+/// Oldest version of the messaging protocol msgs are still read with.
+pub const MIN_COMPATIBLE_PROTO_VERSION: u16 = 8u16;
+
+/// Newest version of the messaging protocol msgs are read with. A new version is rolled out by
+/// first releasing readers of it, and only then writers, so nodes running adjacent releases
+/// interoperate.
+pub const MAX_COMPATIBLE_PROTO_VERSION: u16 = 8u16;
+
+// Last version of the messaging protocol written by releases which didn't negotiate versions.
+const UNNEGOTIATED_PROTO_VERSION: u16 = 4u16;
+
+/// The version of the messaging protocol a node writes msgs with, and those it reads them with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProtocolVersions {
+    /// Version written.
+    pub current: u16,
+    /// Oldest version read.
+    pub min_compatible: u16,
+    /// Newest version read.
+    pub max_compatible: u16,
+}
 
-// Current version of the messaging protocol.
-// At this point this implementation supports only this version.
-const MESSAGING_PROTO_VERSION: u16 = 1u16;
+impl ProtocolVersions {
+    /// The versions this implementation writes and reads.
+    pub const fn ours() -> Self {
+        Self {
+            current: MESSAGING_PROTO_VERSION,
+            min_compatible: MIN_COMPATIBLE_PROTO_VERSION,
+            max_compatible: MAX_COMPATIBLE_PROTO_VERSION,
+        }
+    }
+
+    /// The versions of releases predating the negotiation of versions, which read and wrote only
+    /// the last version before it.
+    pub const fn initial() -> Self {
+        Self {
+            current: UNNEGOTIATED_PROTO_VERSION,
+            min_compatible: UNNEGOTIATED_PROTO_VERSION,
+            max_compatible: UNNEGOTIATED_PROTO_VERSION,
+        }
+    }
+
+    /// Whether msgs written with `version` are read.
+    pub fn supports(&self, version: u16) -> bool {
+        (self.min_compatible..=self.max_compatible).contains(&version)
+    }
+
+    /// Whether peers with these versions and `other` read each other's msgs, which is the case
+    /// when each of them reads the version the other one writes.
+    pub fn compatible_with(&self, other: &Self) -> bool {
+        self.supports(other.current) && other.supports(self.current)
+    }
+}
+
+impl Default for ProtocolVersions {
+    fn default() -> Self {
+        Self::ours()
+    }
+}
+
+impl std::fmt::Display for ProtocolVersions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "v{} (reading v{}..=v{})",
+            self.current, self.min_compatible, self.max_compatible
+        )
+    }
+}
 
 // Header to be serialisied at the front of the wire message.
 // This header contains the information needed to deserialize the payload.
@@ -40,6 +109,9 @@ pub struct MsgEnvelope {
     pub msg_id: MsgId,
     pub msg_kind: AuthKind,
     pub dst_location: DstLocation,
+    // Left out of the envelope unless asked for, so untraced msgs read the same as before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
 }
 
 // The first two fields in the header. This is not part of the public interface.
@@ -81,6 +153,7 @@ impl WireMsgHeader {
                 msg_id,
                 msg_kind,
                 dst_location,
+                trace: None,
             },
         }
     }
@@ -107,8 +180,11 @@ impl WireMsgHeader {
         }
 
         // Make sure we support this version
-        if meta.version != MESSAGING_PROTO_VERSION {
-            return Err(Error::UnsupportedVersion(meta.version));
+        if !ProtocolVersions::ours().supports(meta.version) {
+            return Err(Error::IncompatibleVersion {
+                version: meta.version,
+                supported: ProtocolVersions::ours(),
+            });
         }
 
         // ...finally, we read the message envelope bytes
@@ -133,7 +209,13 @@ impl WireMsgHeader {
         Ok((header, payload_bytes))
     }
 
-    pub fn write<'a>(&self, mut buffer: &'a mut [u8]) -> Result<(&'a mut [u8], u16)> {
+    /// Version of the messaging protocol the msg was written with.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Appends the header to `buffer`, returning its length.
+    pub fn write(&self, buffer: &mut Vec<u8>) -> Result<u16> {
         // first serialise the msg envelope so we can figure out the total header size
         let msg_envelope_vec = rmp_serde::to_vec_named(&self.msg_envelope).map_err(|err| {
             Error::Serialisation(format!(
@@ -150,7 +232,7 @@ impl WireMsgHeader {
 
         // Write the leading metadata
         BINCODE_OPTIONS
-            .serialize_into(&mut buffer, &meta)
+            .serialize_into(&mut *buffer, &meta)
             .map_err(|err| {
                 Error::Serialisation(format!(
                     "header metadata couldn't be serialized into the header: {}",
@@ -159,14 +241,9 @@ impl WireMsgHeader {
             })?;
 
         // ...now write the message envelope
-        buffer.write_all(&msg_envelope_vec).map_err(|err| {
-            Error::Serialisation(format!(
-                "message envelope couldn't be serialized into the header: {}",
-                err
-            ))
-        })?;
+        buffer.extend_from_slice(&msg_envelope_vec);
 
-        Ok((buffer, meta.header_len))
+        Ok(meta.header_len)
     }
 
     // Message Pack uses type tags, but also variable length encoding, so we expect that serialized
@@ -7,7 +7,11 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{KeyedSig, NodeState};
-use crate::{messaging::SectionAuthorityProvider, types::Peer};
+use crate::{
+    messaging::{data::StorageLevel, SectionAuthorityProvider},
+    network_knowledge::NetworkParams,
+    types::Peer,
+};
 use ed25519_dalek::{PublicKey, Signature};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -34,6 +38,8 @@ pub struct DkgSessionId {
     pub section_chain_len: u64,
     /// The bootstrap members for the next Membership instance.
     pub bootstrap_members: BTreeSet<NodeState>,
+    /// Params of the network, which the section key and SAP are generated with.
+    pub network_params: NetworkParams,
 }
 
 impl DkgSessionId {
@@ -57,6 +63,8 @@ impl DkgSessionId {
         for member in self.bootstrap_members.iter() {
             hasher.update(&member.name);
         }
+
+        self.network_params.hash_update(hasher);
     }
 
     pub fn elder_names(&self) -> impl Iterator<Item = XorName> + '_ {
@@ -170,4 +178,12 @@ pub enum Proposal {
     NewElders(SectionAuth<SectionAuthorityProvider>),
     /// Proposal to change whether new nodes are allowed to join our section.
     JoinsAllowed(bool),
+    /// Proposal to record how full the storage of an adult of our section is, as reported by it,
+    /// for chunks to be placed by the level the elders agreed on.
+    StorageFull {
+        /// The adult.
+        node: crate::types::PublicKey,
+        /// The storage level it reported.
+        level: StorageLevel,
+    },
 }
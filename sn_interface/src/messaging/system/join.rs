@@ -7,7 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{agreement::SectionAuth, KeyedSig, NodeState};
-use crate::messaging::SectionAuthorityProvider;
+use crate::messaging::{ProtocolVersions, SectionAuthorityProvider};
+use crate::protocol_constants::ProtocolDigest;
 use bls::PublicKey as BlsPublicKey;
 use ed25519_dalek::Signature;
 use secured_linked_list::SecuredLinkedList;
@@ -19,6 +20,12 @@ use std::{collections::VecDeque, net::SocketAddr};
 pub struct JoinRequest {
     /// The public key of the section to join.
     pub section_key: BlsPublicKey,
+    /// Digest of the protocol constants the joining node runs with.
+    pub protocol_digest: ProtocolDigest,
+    /// Messaging protocol versions the joining node reads and writes. Absent from requests of
+    /// nodes predating the negotiation of versions.
+    #[serde(default = "ProtocolVersions::initial")]
+    pub protocol_versions: ProtocolVersions,
     /// Proof of the resource proofing.
     pub resource_proof_response: Option<ResourceProofResponse>,
 }
@@ -31,6 +38,14 @@ pub struct ResourceProofResponse {
     #[allow(missing_docs)]
     #[debug(skip)]
     pub data: VecDeque<u8>,
+    /// Difficulty of the challenge solved, as given with it.
+    pub difficulty: u8,
+    /// Memory of the memory-hard challenge solved, in KiB, as given with it.
+    pub memory_kib: u32,
+    /// Difficulty of the memory-hard challenge solved, as given with it.
+    pub memory_difficulty: u8,
+    /// Solution to the memory-hard challenge.
+    pub memory_solution: u64,
     #[allow(missing_docs)]
     #[debug(skip)]
     pub nonce: [u8; 32],
@@ -44,11 +59,18 @@ pub struct ResourceProofResponse {
 #[allow(clippy::large_enum_variant)]
 pub enum JoinResponse {
     /// Challenge sent from existing elder nodes to the joining peer for resource proofing.
+    ///
+    /// Each elder sets its challenge by how many peers it challenged lately, for joining to get
+    /// costlier the more peers are trying to.
     ResourceChallenge {
         #[allow(missing_docs)]
         data_size: usize,
         /// how hard the challenge should be to solve
         difficulty: u8,
+        /// Memory the memory-hard challenge takes to solve, in KiB.
+        memory_kib: u32,
+        /// How hard the memory-hard challenge should be to solve.
+        memory_difficulty: u8,
         #[allow(missing_docs)]
         nonce: [u8; 32],
         #[allow(missing_docs)]
@@ -92,4 +114,30 @@ pub enum JoinRejectionReason {
     JoinsDisallowed,
     /// The requesting node is not externally reachable
     NodeNotReachable(SocketAddr),
+    /// The relocation being joined with was issued longer ago than the section accepts
+    RelocationExpired {
+        /// When the relocation was issued, in seconds since the Unix epoch.
+        issued_at: u64,
+    },
+    /// The relocation being joined with was issued under a section key too many generations
+    /// behind the one of the section
+    RelocationKeyOutdated {
+        /// How many generations behind the section's key the issuing key is.
+        generations_behind: u64,
+    },
+    /// The relocation being joined with was already used to join, by another node
+    RelocationAlreadyUsed,
+    /// The relocation being joined with doesn't follow from the churn it was drawn from: the
+    /// node wasn't old enough to be drawn, or was relocated elsewhere than drawn to
+    RelocationNotDrawn,
+    /// The joining node runs with other protocol constants than the section
+    ProtocolMismatch {
+        /// Digest of the section's protocol constants.
+        section: ProtocolDigest,
+    },
+    /// The joining node and the section can't read each other's messages
+    IncompatibleVersion {
+        /// Messaging protocol versions the section reads and writes.
+        section: ProtocolVersions,
+    },
 }
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{NodeState, SectionAuth};
+use super::{JoinRejectionReason, NodeState, SectionAuth};
 use crate::messaging::SectionAuthorityProvider;
 use bls::PublicKey as BlsPublicKey;
 use ed25519_dalek::Signature;
@@ -36,4 +36,6 @@ pub enum JoinAsRelocatedResponse {
     Redirect(SectionAuthorityProvider),
     /// The requesting node is not externally reachable
     NodeNotReachable(SocketAddr),
+    /// The relocation being joined with isn't accepted
+    Rejected(JoinRejectionReason),
 }
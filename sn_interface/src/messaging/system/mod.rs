@@ -12,6 +12,7 @@ mod join_as_relocated;
 mod msg_authority;
 mod node_msgs;
 mod node_state;
+mod relay;
 mod signed;
 
 use crate::network_knowledge::SapCandidate;
@@ -21,6 +22,7 @@ pub use join_as_relocated::{JoinAsRelocatedRequest, JoinAsRelocatedResponse};
 pub use msg_authority::NodeMsgAuthorityUtils;
 pub use node_msgs::{NodeCmd, NodeEvent, NodeQuery, NodeQueryResponse};
 pub use node_state::{MembershipState, NodeState, RelocateDetails};
+pub use relay::{RelayMsg, RelayRefusal};
 pub use signed::{KeyedSig, SigShare};
 use sn_consensus::{Generation, SignedVote};
 
@@ -33,6 +35,7 @@ use bytes::Bytes;
 use secured_linked_list::SecuredLinkedList;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
+use std::net::SocketAddr;
 use xor_name::XorName;
 
 use super::authority::SectionAuth as SectionAuthProof;
@@ -200,4 +203,26 @@ pub enum SystemMsg {
         /// TEMP: Add user here as part of return flow. Remove this as we have chunk routing etc
         user: EndUser,
     },
+    /// Message sent to a peer that reached us on the listener meant for the other kind of peers
+    /// (clients on the one for nodes, or the other way around), attaching the bounced message
+    /// so the peer can resend it to the right listener.
+    ListenerRedirect {
+        /// Address of the listener for the peer's kind.
+        addr: SocketAddr,
+        /// Message bounced for having reached the wrong listener.
+        #[debug(skip)]
+        bounced_msg: Bytes,
+    },
+    /// Message between a node the network can't connect to and the peer relaying its messages.
+    Relay(RelayMsg),
+    /// Message containing several `Proposal`s raised together, e.g. on churn, to be aggregated
+    /// as one in the proposal aggregator, with a single signature share over them all.
+    ///
+    /// A lone proposal is still sent as `Propose`, for nodes which don't know of batches.
+    ProposeBatch {
+        /// The proposals, in the order they were signed in
+        proposals: Vec<Proposal>,
+        /// BLS signature share over the whole batch
+        sig_share: SigShare,
+    },
 }
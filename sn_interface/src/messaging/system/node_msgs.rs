@@ -14,12 +14,16 @@ use crate::messaging::{
 };
 use crate::types::{
     register::{Entry, EntryHash, Permissions, Policy, Register, User},
-    Chunk, PublicKey, ReplicatedData, ReplicatedDataAddress,
+    Chunk, ChunkShare, PublicKey, ReplicatedData, ReplicatedDataAddress,
 };
 
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
-use xor_name::XorName;
+use sn_dbc::SpentProofShare;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+use xor_name::{Prefix, XorName};
 
 /// cmd message sent among nodes
 #[allow(clippy::large_enum_variant)]
@@ -55,6 +59,175 @@ pub enum NodeCmd {
         /// Metadata
         metadata: MetadataExchange,
     },
+    /// Notify Elders that an Adult's disk started failing, or recovered from failing, its
+    /// periodic self-test. Unhealthy Adults are not given new data to store.
+    RecordStorageHealth {
+        /// Node Id
+        node_id: PublicKey,
+        /// Section to which the message needs to be sent to. (NB: this is the section of the node id).
+        section: XorName,
+        /// Whether the node's storage is healthy.
+        healthy: bool,
+    },
+    /// Notify Elders that an Adult is about to restart and expects to be back within the given
+    /// time. Until then, Elders don't propose it offline nor give it new data to store.
+    PrepareRestart {
+        /// Node Id
+        node_id: PublicKey,
+        /// Section to which the message needs to be sent to. (NB: this is the section of the node id).
+        section: XorName,
+        /// How long the Adult expects to be away, at most.
+        within: Duration,
+    },
+    /// Asks an Adult for the names of the data it holds within the given slice of the section,
+    /// for a newly promoted Elder to rebuild its registry of data holders.
+    SendInventory {
+        /// Slice of the section's namespace.
+        slice: Prefix,
+    },
+    /// Response to [`SendInventory`], with the names of the data the Adult holds within the slice.
+    ///
+    /// [`SendInventory`]: NodeCmd::SendInventory
+    RecordInventory {
+        /// Node Id
+        node_id: PublicKey,
+        /// Slice of the section's namespace.
+        slice: Prefix,
+        /// Names of the data held within the slice.
+        names: BTreeSet<XorName>,
+    },
+    /// Asks an Adult which of the given data it holds, for an Elder checking that data is held
+    /// where it should be.
+    SendHeld {
+        /// Batch of the check, to be echoed back.
+        batch: u64,
+        /// Names of the data to look up.
+        names: BTreeSet<XorName>,
+    },
+    /// Response to [`SendHeld`], with the addresses of the data the Adult holds among those asked.
+    ///
+    /// [`SendHeld`]: NodeCmd::SendHeld
+    RecordHeld {
+        /// Node Id
+        node_id: PublicKey,
+        /// Batch of the check.
+        batch: u64,
+        /// Addresses of the data held.
+        held: BTreeSet<ReplicatedDataAddress>,
+    },
+    /// Asks an Adult holding data to replicate it to another Adult, which should hold it but
+    /// doesn't.
+    ReplicateDataTo {
+        /// Addresses of the data to replicate.
+        addresses: Vec<ReplicatedDataAddress>,
+        /// Name of the Adult to replicate it to.
+        holder: XorName,
+    },
+    /// Tells an Adult to store a replica of the data, each along with the address its sender
+    /// holds it at. The address of a chunk is the hash of its content, which the Adult checks
+    /// the chunk it received against before storing it.
+    ReplicateCheckedData(Vec<(ReplicatedDataAddress, ReplicatedData)>),
+    /// Notify Elders that an Adult found chunks it holds corrupt, scrubbing its storage, and
+    /// dropped them, for them to be replicated to it again from their other holders.
+    RequestChunkReplication {
+        /// Node Id
+        node_id: PublicKey,
+        /// Section to which the message needs to be sent to. (NB: this is the section of the node id).
+        section: XorName,
+        /// Names of the chunks dropped.
+        names: BTreeSet<XorName>,
+    },
+    /// Notify Elders that a node is shutting down for good, for them to propose it offline
+    /// right away rather than once they fail to reach it.
+    Leaving {
+        /// Node Id
+        node_id: PublicKey,
+        /// Section to which the message needs to be sent to. (NB: this is the section of the node id).
+        section: XorName,
+    },
+    /// Challenges an Adult to prove it holds the given chunks, by hashing each of them along
+    /// with a random nonce, for an Elder auditing that chunks are replicated.
+    SendHoldingProofs {
+        /// Audit the challenge is part of, to be echoed back.
+        audit: u64,
+        /// Nonce to hash the chunks with, so that proofs can't be made without their content.
+        nonce: [u8; 32],
+        /// Names of the chunks to prove holding.
+        names: BTreeSet<XorName>,
+    },
+    /// Response to [`SendHoldingProofs`], with the sha3 of the nonce followed by the content of
+    /// each of the chunks the Adult holds among those asked.
+    ///
+    /// [`SendHoldingProofs`]: NodeCmd::SendHoldingProofs
+    RecordHoldingProofs {
+        /// Node Id
+        node_id: PublicKey,
+        /// Audit the proofs are for.
+        audit: u64,
+        /// Proof of each chunk held, by name.
+        proofs: BTreeMap<XorName, [u8; 32]>,
+    },
+    /// Probes an Adult for liveness, for an Elder to tell how long it takes to answer. The
+    /// Adult is to prove it holds the chunk named, if any, the same way as for
+    /// [`SendHoldingProofs`], and answer before the Elder's deadline.
+    ///
+    /// [`SendHoldingProofs`]: NodeCmd::SendHoldingProofs
+    ProbeLiveness {
+        /// Probe to be echoed back.
+        probe: u64,
+        /// Nonce to hash the chunk with.
+        nonce: [u8; 32],
+        /// Name of a chunk the Adult is expected to hold.
+        name: Option<XorName>,
+    },
+    /// Response to [`ProbeLiveness`], with the proof of holding the chunk named, if it's held.
+    ///
+    /// [`ProbeLiveness`]: NodeCmd::ProbeLiveness
+    RecordLiveness {
+        /// Node Id
+        node_id: PublicKey,
+        /// Probe answered.
+        probe: u64,
+        /// Proof of holding the chunk probed with.
+        proof: Option<[u8; 32]>,
+    },
+    /// Tells an Adult to store shares of chunks erasure coded across the section's Adults,
+    /// rather than replicated in full.
+    StoreChunkShares(Vec<ChunkShare>),
+    /// Asks an Adult for the shares it holds of the erasure coded chunk named, for an Elder
+    /// to rebuild the chunk from.
+    SendChunkShares {
+        /// Name of the chunk.
+        chunk: XorName,
+    },
+    /// Response to [`SendChunkShares`], with the shares the Adult holds of the chunk.
+    ///
+    /// [`SendChunkShares`]: NodeCmd::SendChunkShares
+    RecordChunkShares {
+        /// Node Id
+        node_id: PublicKey,
+        /// Name of the chunk.
+        chunk: XorName,
+        /// Shares held, none if the Adult holds none.
+        shares: Vec<ChunkShare>,
+    },
+    /// Share of the spent proof of a DBC, signed by an Elder logging the DBC as spent, for the
+    /// other Elders of the section to log too and aggregate into the full spent proof.
+    RecordSpentProofShare(SpentProofShare),
+    /// Notify Elders that an Adult is done handing the data it held which belongs to the sibling
+    /// section over to the sibling's Adults, after a split.
+    RecordSplitHandover {
+        /// Node Id
+        node_id: PublicKey,
+        /// Section to which the message needs to be sent to. (NB: this is the section of the node id).
+        section: XorName,
+        /// Prefix of the sibling section the data was handed over to.
+        sibling: Prefix,
+        /// Copies of the data the sibling's Adults confirmed holding.
+        handed_over: u64,
+        /// Copies given up on, unconfirmed, whose data the Adult kept.
+        failed: u64,
+    },
 }
 
 /// Event message sent among nodes
@@ -73,6 +246,14 @@ pub enum NodeEvent {
     },
     /// Inform Adults of a possible suspect node
     SuspiciousNodesDetected(BTreeSet<XorName>),
+    /// Sent by an Adult having stored replicas of data, for the Elders to release the
+    /// consistent queries held on their writes.
+    ReplicasStored {
+        /// Node Id
+        node_id: PublicKey,
+        /// Ids of the replicas stored.
+        replicas: BTreeSet<XorName>,
+    },
 }
 
 /// Query originating at a node
@@ -132,6 +313,12 @@ pub enum NodeQueryResponse {
     #[cfg(feature = "registers")]
     /// Response to [`RegisterQuery::GetUserPermissions`].
     GetRegisterUserPermissions((Result<Permissions>, OperationId)),
+    #[cfg(feature = "registers")]
+    /// Response to [`RegisterQuery::GetVersion`].
+    GetRegisterVersion((Result<(EntryHash, Entry)>, OperationId)),
+    #[cfg(feature = "registers")]
+    /// Response to [`RegisterQuery::GetHistoryRange`].
+    GetRegisterHistory((Result<Vec<(EntryHash, Entry)>>, OperationId)),
     //
     // ===== Other =====
     //
@@ -157,6 +344,10 @@ impl NodeQueryResponse {
             GetRegisterPolicy(res) => QueryResponse::GetRegisterPolicy(res),
             #[cfg(feature = "registers")]
             GetRegisterUserPermissions(res) => QueryResponse::GetRegisterUserPermissions(res),
+            #[cfg(feature = "registers")]
+            GetRegisterVersion(res) => QueryResponse::GetRegisterVersion(res),
+            #[cfg(feature = "registers")]
+            GetRegisterHistory(res) => QueryResponse::GetRegisterHistory(res),
             FailedToCreateOperationId => QueryResponse::FailedToCreateOperationId,
         }
     }
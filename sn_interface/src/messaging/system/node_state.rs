@@ -71,4 +71,15 @@ pub struct RelocateDetails {
     pub dst_section_key: BlsPublicKey,
     /// The age the node will have post-relocation.
     pub age: u8,
+    /// When the relocation was issued, in seconds since the Unix epoch, rounded down to the
+    /// minute for the elders of the issuing section to agree on it.
+    pub issued_at: u64,
+    /// Generation of the issuing section's key when the relocation was issued, i.e. the length of
+    /// its section chain.
+    pub key_generation: u64,
+    /// Entropy of the churn the relocation was drawn from: the signature of the issuing section
+    /// agreeing on the churn. It lets the destination check the node was relocated as the draw
+    /// has it, to where it has it. None for relocations not drawn on churn, as of nodes
+    /// rejoining.
+    pub churn_id: Option<Vec<u8>>,
 }
@@ -0,0 +1,52 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::types::Peer;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Msgs between a node the network can't connect to, e.g. one behind a symmetric NAT, and the
+/// reachable peer relaying its msgs, over the connection the node keeps open to it. The relay's
+/// address stands for the node's meanwhile: msgs sent there for the node are passed on to it.
+#[derive(Clone, PartialEq, Serialize, Deserialize, custom_debug::Debug)]
+pub enum RelayMsg {
+    /// Asks the recipient to relay our msgs until `lease` runs out. Sent again before then to
+    /// renew the registration.
+    Register {
+        /// How long the registration is asked for.
+        lease: Duration,
+    },
+    /// The registration is accepted, until `lease` runs out.
+    Registered {
+        /// How long the registration holds, which may be shorter than asked for.
+        lease: Duration,
+    },
+    /// The registration is refused.
+    Refused(RelayRefusal),
+    /// Ends our registration.
+    Deregister,
+    /// A msg of ours for the relay to send on to `recipients`, from its address.
+    Forward {
+        /// Where the msg is to be sent.
+        recipients: Vec<Peer>,
+        /// The msg, serialized.
+        #[debug(skip)]
+        msg: Bytes,
+    },
+}
+
+/// Why a peer won't relay for a node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RelayRefusal {
+    /// The peer doesn't relay for others.
+    Disabled,
+    /// The peer relays for as many nodes as it takes already.
+    Full,
+}
@@ -0,0 +1,1176 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Deterministic, representative instances of the messages exchanged on the network.
+//!
+//! Every key, signature, name, address and id used here is derived from fixed seeds, so that
+//! serializing the same instance always yields the same bytes. These instances back the
+//! wire-format regression tests (see `serialisation::wire_format_tests`), and can be reused by
+//! any test needing a realistic message without pulling in randomness.
+
+use crate::messaging::{
+    data::{
+        BatchQuery, BatchStatus, CmdError, CreateRegister, DataCmd, DataHolders, DataQuery,
+        EditRegister, Error as DataError, HoldersQuery, MetadataExchange, ProcessingError,
+        QueryResponse, RegisterCmd, RegisterQuery, ServiceError, ServiceMsg, SessionToken,
+        SignedRegisterCreate, SignedRegisterEdit, SpentbookCmd, SpentbookQuery, StorageLevel,
+    },
+    system::{
+        DkgFailureSig, DkgFailureSigSet, DkgSessionId, JoinAsRelocatedRequest,
+        JoinAsRelocatedResponse, JoinRejectionReason, JoinRequest, JoinResponse, KeyedSig,
+        MembershipState, NodeCmd, NodeEvent, NodeQuery, NodeQueryResponse, NodeState, Proposal,
+        RelayMsg, RelayRefusal, RelocateDetails, ResourceProofResponse, SectionAuth, SigShare,
+        SystemMsg,
+    },
+    AuthKind, AuthorityProof, DstLocation, EndUser, MsgId, NodeAuth, ProtocolVersions,
+    SectionAuth as SectionAuthProof, SectionAuthorityProvider, ServiceAuth, WireMsg,
+};
+use crate::network_knowledge::{NetworkParams, SapCandidate};
+use crate::protocol_constants::ProtocolDigest;
+use crate::types::{
+    register::{EntryHash, Policy, PublicPolicy, Register, User},
+    Chunk, ChunkShare, DataAddress, Keypair, Peer, PublicKey, ReplicatedData,
+    ReplicatedDataAddress, Scope,
+};
+
+use bls_dkg::key_gen::message::Message as DkgMessage;
+use bytes::Bytes;
+use ed25519_dalek::Signer;
+use rand::{rngs::StdRng, SeedableRng};
+use secured_linked_list::SecuredLinkedList;
+use serde::Serialize;
+use sn_consensus::{Ballot, SignedVote, Vote};
+use sn_dbc::{
+    bls_ringct::{bls_bulletproofs::PedersenGens, group::Curve},
+    Commitment, GenesisMaterial, Hash, IndexedSignatureShare, KeyImage, SpentProof,
+    SpentProofContent, SpentProofShare,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+use xor_name::{Prefix, XorName};
+
+/// Number of elders in the fixed section.
+const ELDER_COUNT: u8 = 3;
+
+/// BLS secret key set of the fixed section, with a threshold suitable for `ELDER_COUNT` elders.
+pub fn fixed_secret_key_set() -> bls::SecretKeySet {
+    let mut rng = StdRng::seed_from_u64(0);
+    bls::SecretKeySet::random(ELDER_COUNT as usize / 3, &mut rng)
+}
+
+/// A standalone BLS secret key, distinct from the fixed section key, e.g. for chain tips.
+pub fn fixed_bls_secret_key(seed: u64) -> bls::SecretKey {
+    let mut rng = StdRng::seed_from_u64(seed + 1);
+    rand::Rng::gen(&mut rng)
+}
+
+/// Ed25519 keypair derived from a single seed byte.
+pub fn fixed_ed25519_keypair(seed: u8) -> ed25519_dalek::Keypair {
+    let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; ed25519_dalek::SECRET_KEY_LENGTH])
+        .expect("fixed secret key bytes have a valid length");
+    ed25519_dalek::Keypair {
+        public: (&secret).into(),
+        secret,
+    }
+}
+
+/// A name filled with the given byte.
+pub fn fixed_name(seed: u8) -> XorName {
+    XorName([seed; xor_name::XOR_NAME_LEN])
+}
+
+/// A loopback address whose port is derived from the given seed.
+pub fn fixed_addr(seed: u8) -> SocketAddr {
+    ([127, 0, 0, 1], 12000 + seed as u16).into()
+}
+
+/// A message id filled with the given byte.
+pub fn fixed_msg_id(seed: u8) -> MsgId {
+    MsgId::from_xor_name(fixed_name(seed))
+}
+
+/// Token of a write by the elder of the given seed, under the fixed section key.
+pub fn fixed_session_token(seed: u8, index: u64) -> SessionToken {
+    SessionToken {
+        section_key: fixed_secret_key_set().public_keys().public_key(),
+        elder: fixed_name(seed),
+        index,
+    }
+}
+
+/// Deterministic section authority provider for the fixed section.
+pub fn fixed_sap() -> SectionAuthorityProvider {
+    let elders: BTreeMap<_, _> = (1..=ELDER_COUNT)
+        .map(|i| (fixed_name(i), fixed_addr(i)))
+        .collect();
+    let members = elders
+        .iter()
+        .map(|(name, addr)| (*name, NodeState::joined(*name, *addr, None)))
+        .collect();
+    SectionAuthorityProvider {
+        prefix: Prefix::default(),
+        public_key_set: fixed_secret_key_set().public_keys(),
+        elders,
+        members,
+        protocol_digest: fixed_protocol_digest(),
+        network_params: fixed_network_params(),
+    }
+}
+
+/// Network params of the fixed section, rather than the defaults, so the wire format doesn't
+/// change along with those.
+pub fn fixed_network_params() -> NetworkParams {
+    NetworkParams::new(ELDER_COUNT as usize, (2, 3)).expect("invalid network params")
+}
+
+/// A protocol constants digest of fixed bytes, so the wire format doesn't change along with
+/// the constants.
+pub fn fixed_protocol_digest() -> ProtocolDigest {
+    ProtocolDigest([0xab; 32])
+}
+
+/// Signature of the fixed section key over the bincode-serialized `payload`.
+pub fn fixed_keyed_sig<T: Serialize>(payload: &T) -> KeyedSig {
+    let secret_key = fixed_secret_key_set().secret_key();
+    let bytes = bincode::serialize(payload).expect("failed to serialize payload");
+    KeyedSig {
+        public_key: secret_key.public_key(),
+        signature: secret_key.sign(&bytes),
+    }
+}
+
+/// Wraps the `payload` with the fixed section signature over it.
+pub fn fixed_section_auth<T: Serialize>(payload: T) -> SectionAuth<T> {
+    let sig = fixed_keyed_sig(&payload);
+    SectionAuth {
+        value: payload,
+        sig,
+    }
+}
+
+/// Signature share of the elder at `index` over the bincode-serialized `payload`.
+pub fn fixed_sig_share<T: Serialize>(index: usize, payload: &T) -> SigShare {
+    let secret_key_set = fixed_secret_key_set();
+    let bytes = bincode::serialize(payload).expect("failed to serialize payload");
+    SigShare::new(
+        secret_key_set.public_keys(),
+        index,
+        &secret_key_set.secret_key_share(index),
+        &bytes,
+    )
+}
+
+/// Section chain from a fixed genesis key to the fixed section key.
+pub fn fixed_section_chain() -> SecuredLinkedList {
+    let genesis_sk = fixed_bls_secret_key(0);
+    let section_pk = fixed_secret_key_set().public_keys().public_key();
+    let mut chain = SecuredLinkedList::new(genesis_sk.public_key());
+    let sig = genesis_sk.sign(bincode::serialize(&section_pk).expect("failed to serialize key"));
+    chain
+        .insert(&genesis_sk.public_key(), section_pk, sig)
+        .expect("fixed chain links are valid");
+    chain
+}
+
+/// Fixed client authority over `payload`, signed by the Ed25519 key of seed `seed`.
+pub fn fixed_service_auth(seed: u8, payload: &[u8]) -> ServiceAuth {
+    let keypair = Keypair::Ed25519(Arc::new(fixed_ed25519_keypair(seed)));
+    ServiceAuth {
+        public_key: keypair.public_key(),
+        signature: keypair.sign(payload),
+    }
+}
+
+fn fixed_node_state(seed: u8) -> NodeState {
+    NodeState::joined(fixed_name(seed), fixed_addr(seed), None)
+}
+
+fn fixed_relocated_node_state() -> NodeState {
+    NodeState {
+        name: fixed_name(4),
+        addr: fixed_addr(4),
+        state: MembershipState::Relocated(Box::new(RelocateDetails {
+            previous_name: fixed_name(4),
+            dst: fixed_name(200),
+            dst_section_key: fixed_bls_secret_key(1).public_key(),
+            age: 6,
+            issued_at: 1_650_000_000,
+            key_generation: 2,
+            churn_id: Some(vec![7; 96]),
+        })),
+        previous_name: None,
+    }
+}
+
+fn fixed_dkg_session_id() -> DkgSessionId {
+    DkgSessionId {
+        prefix: Prefix::default(),
+        elders: fixed_sap().elders,
+        section_chain_len: 2,
+        bootstrap_members: (1..=ELDER_COUNT).map(fixed_node_state).collect(),
+        network_params: fixed_network_params(),
+    }
+}
+
+fn fixed_dkg_message() -> DkgMessage {
+    DkgMessage::Initialization {
+        key_gen_id: 0,
+        m: 2,
+        n: ELDER_COUNT as usize,
+        member_list: (1..=ELDER_COUNT).map(fixed_name).collect(),
+    }
+}
+
+fn fixed_dkg_failure_sig() -> DkgFailureSig {
+    let session_id = fixed_dkg_session_id();
+    let keypair = fixed_ed25519_keypair(1);
+    DkgFailureSig {
+        public_key: keypair.public,
+        signature: keypair.sign(&session_id.hash()),
+        session_id,
+    }
+}
+
+fn fixed_signed_vote<T>(proposition: T) -> SignedVote<T>
+where
+    T: sn_consensus::Proposition,
+{
+    let vote = Vote {
+        gen: 1,
+        ballot: Ballot::Propose(proposition),
+        faults: BTreeSet::new(),
+    };
+    let bytes = bincode::serialize(&vote).expect("failed to serialize vote");
+    SignedVote {
+        voter: 0,
+        sig: fixed_secret_key_set().secret_key_share(0usize).sign(&bytes),
+        vote,
+    }
+}
+
+fn fixed_chunk() -> Chunk {
+    Chunk::new(Bytes::from_static(b"fixed chunk content"))
+}
+
+fn fixed_chunk_share() -> ChunkShare {
+    ChunkShare {
+        chunk: fixed_name(60),
+        index: 9,
+        data_shares: 8,
+        parity_shares: 4,
+        chunk_len: 20,
+        bytes: Bytes::from_static(b"pari"),
+    }
+}
+
+/// The genesis DBC's key image and public commitment, which are fixed.
+fn fixed_genesis_input() -> (KeyImage, Commitment) {
+    let genesis = GenesisMaterial::default();
+    let commitment = genesis.ringct_material.inputs[0]
+        .true_input
+        .revealed_commitment()
+        .commit(&PedersenGens::default())
+        .to_affine();
+    (genesis.input_key_image, commitment)
+}
+
+fn fixed_spend() -> SpentbookCmd {
+    let genesis = GenesisMaterial::default();
+    let (tx, _revealed_commitments) = genesis
+        .ringct_material
+        .sign(sn_dbc::rng::from_seed([70; 32]))
+        .expect("failed to sign the genesis tx");
+    let (key_image, commitment) = fixed_genesis_input();
+    SpentbookCmd::Spend {
+        key_image,
+        tx,
+        public_commitments: vec![vec![commitment]],
+    }
+}
+
+fn fixed_spent_proof_content() -> SpentProofContent {
+    let (key_image, commitment) = fixed_genesis_input();
+    SpentProofContent {
+        key_image,
+        transaction_hash: Hash::from([71; 32]),
+        public_commitments: vec![commitment],
+    }
+}
+
+fn fixed_spent_proof_share() -> SpentProofShare {
+    let content = fixed_spent_proof_content();
+    let sig_share = fixed_secret_key_set()
+        .secret_key_share(0)
+        .sign(content.hash());
+    SpentProofShare {
+        content,
+        spentbook_pks: fixed_secret_key_set().public_keys(),
+        spentbook_sig_share: IndexedSignatureShare::new(0, sig_share),
+    }
+}
+
+fn fixed_spent_proof() -> SpentProof {
+    let content = fixed_spent_proof_content();
+    let secret_key = fixed_secret_key_set().secret_key();
+    SpentProof {
+        spentbook_sig: secret_key.sign(content.hash()),
+        spentbook_pub_key: secret_key.public_key(),
+        content,
+    }
+}
+
+fn fixed_owner() -> PublicKey {
+    PublicKey::Ed25519(fixed_ed25519_keypair(10).public)
+}
+
+fn fixed_register() -> Register {
+    let owner = User::Key(fixed_owner());
+    let policy = Policy::Public(PublicPolicy {
+        owner,
+        permissions: BTreeMap::new(),
+    });
+    Register::new(fixed_name(20), 15000, policy, u16::MAX)
+}
+
+fn fixed_register_create() -> RegisterCmd {
+    let op = CreateRegister::Populated(fixed_register());
+    let bytes = bincode::serialize(&op).expect("failed to serialize register op");
+    RegisterCmd::Create {
+        cmd: SignedRegisterCreate {
+            op,
+            auth: fixed_service_auth(10, &bytes),
+        },
+        section_auth: SectionAuthProof {
+            src_name: fixed_name(1),
+            sig: fixed_keyed_sig(&bytes),
+        },
+    }
+}
+
+fn fixed_register_edit() -> RegisterCmd {
+    let mut register = fixed_register();
+    let (_, edit) = register
+        .write(b"fixed entry".to_vec(), BTreeSet::new())
+        .expect("fixed register has room for an entry");
+    let op = EditRegister {
+        address: *register.address(),
+        edit,
+    };
+    let bytes = bincode::serialize(&op).expect("failed to serialize register op");
+    RegisterCmd::Edit(SignedRegisterEdit {
+        op,
+        auth: fixed_service_auth(10, &bytes),
+    })
+}
+
+fn fixed_end_user() -> EndUser {
+    EndUser(fixed_name(30))
+}
+
+/// A representative instance of every `SystemMsg` variant, with a stable name for each.
+///
+/// Variants carrying nested enums are instantiated once per nested variant where the nested
+/// type is only ever sent wrapped in that message.
+pub fn representative_system_msgs() -> Vec<(&'static str, SystemMsg)> {
+    let sap = fixed_sap();
+    let section_signed = fixed_keyed_sig(&sap);
+    let section_auth = fixed_section_auth(sap.clone());
+    let bounced_msg = Bytes::from_static(b"bounced message bytes");
+    let service_auth = fixed_service_auth(10, b"query");
+
+    let mut msgs = vec![
+        (
+            "system_anti_entropy_retry",
+            SystemMsg::AntiEntropyRetry {
+                section_auth: sap.clone(),
+                section_signed: section_signed.clone(),
+                proof_chain: fixed_section_chain(),
+                bounced_msg: bounced_msg.clone(),
+            },
+        ),
+        (
+            "system_anti_entropy_redirect",
+            SystemMsg::AntiEntropyRedirect {
+                section_auth: sap.clone(),
+                section_signed: section_signed.clone(),
+                section_chain: fixed_section_chain(),
+                bounced_msg,
+            },
+        ),
+        (
+            "system_anti_entropy_update",
+            SystemMsg::AntiEntropyUpdate {
+                section_auth: sap.clone(),
+                section_signed: section_signed.clone(),
+                proof_chain: fixed_section_chain(),
+                members: (1..=ELDER_COUNT)
+                    .map(|i| fixed_section_auth(fixed_node_state(i)))
+                    .collect(),
+            },
+        ),
+        (
+            "system_anti_entropy_probe",
+            SystemMsg::AntiEntropyProbe(fixed_name(100)),
+        ),
+        (
+            "system_relocate",
+            SystemMsg::Relocate(fixed_section_auth(fixed_relocated_node_state())),
+        ),
+        (
+            "system_membership_votes",
+            SystemMsg::MembershipVotes(vec![fixed_signed_vote(fixed_node_state(4))]),
+        ),
+        ("system_membership_ae", SystemMsg::MembershipAE(7)),
+        (
+            "system_join_request",
+            SystemMsg::JoinRequest(Box::new(JoinRequest {
+                section_key: sap.public_key_set.public_key(),
+                protocol_digest: fixed_protocol_digest(),
+                protocol_versions: ProtocolVersions {
+                    current: 1,
+                    min_compatible: 1,
+                    max_compatible: 2,
+                },
+                resource_proof_response: Some(ResourceProofResponse {
+                    solution: 42,
+                    data: vec![1, 2, 3].into(),
+                    difficulty: 2,
+                    memory_kib: 64,
+                    memory_difficulty: 1,
+                    memory_solution: 3,
+                    nonce: [7; 32],
+                    nonce_signature: fixed_ed25519_keypair(1).sign(&[7; 32]),
+                }),
+            })),
+        ),
+        (
+            "system_join_response_resource_challenge",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::ResourceChallenge {
+                data_size: 1024,
+                difficulty: 2,
+                memory_kib: 64,
+                memory_difficulty: 1,
+                nonce: [7; 32],
+                nonce_signature: fixed_ed25519_keypair(1).sign(&[7; 32]),
+            })),
+        ),
+        (
+            "system_join_response_retry",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Retry {
+                section_auth: sap.clone(),
+                section_signed: section_signed.clone(),
+                proof_chain: fixed_section_chain(),
+                expected_age: 5,
+            })),
+        ),
+        (
+            "system_join_response_redirect",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Redirect(sap.clone()))),
+        ),
+        (
+            "system_join_response_approval",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Approval {
+                genesis_key: fixed_bls_secret_key(0).public_key(),
+                section_auth: section_auth.clone(),
+                node_state: fixed_section_auth(fixed_node_state(4)),
+                section_chain: fixed_section_chain(),
+            })),
+        ),
+        (
+            "system_join_response_rejected",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                JoinRejectionReason::NodeNotReachable(fixed_addr(4)),
+            ))),
+        ),
+        (
+            "system_join_response_rejected_protocol_mismatch",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                JoinRejectionReason::ProtocolMismatch {
+                    section: fixed_protocol_digest(),
+                },
+            ))),
+        ),
+        (
+            "system_join_response_rejected_incompatible_version",
+            SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                JoinRejectionReason::IncompatibleVersion {
+                    section: ProtocolVersions {
+                        current: 1,
+                        min_compatible: 1,
+                        max_compatible: 2,
+                    },
+                },
+            ))),
+        ),
+        (
+            "system_join_as_relocated_request",
+            SystemMsg::JoinAsRelocatedRequest(Box::new(JoinAsRelocatedRequest {
+                section_key: sap.public_key_set.public_key(),
+                relocate_proof: fixed_section_auth(fixed_relocated_node_state()),
+                signature_over_new_name: fixed_ed25519_keypair(4).sign(&fixed_name(5).0),
+            })),
+        ),
+        (
+            "system_join_as_relocated_response",
+            SystemMsg::JoinAsRelocatedResponse(Box::new(JoinAsRelocatedResponse::Retry(
+                sap.clone(),
+            ))),
+        ),
+        (
+            "system_join_as_relocated_response_rejected",
+            SystemMsg::JoinAsRelocatedResponse(Box::new(JoinAsRelocatedResponse::Rejected(
+                JoinRejectionReason::RelocationKeyOutdated {
+                    generations_behind: 9,
+                },
+            ))),
+        ),
+        (
+            "system_dkg_start",
+            SystemMsg::DkgStart(fixed_dkg_session_id()),
+        ),
+        (
+            "system_dkg_session_unknown",
+            SystemMsg::DkgSessionUnknown {
+                session_id: fixed_dkg_session_id(),
+                message: fixed_dkg_message(),
+            },
+        ),
+        (
+            "system_dkg_session_info",
+            SystemMsg::DkgSessionInfo {
+                session_id: fixed_dkg_session_id(),
+                section_auth: AuthorityProof(SectionAuthProof {
+                    src_name: fixed_name(1),
+                    sig: fixed_keyed_sig(&fixed_dkg_session_id()),
+                }),
+                message_cache: vec![fixed_dkg_message()],
+                message: fixed_dkg_message(),
+            },
+        ),
+        (
+            "system_dkg_message",
+            SystemMsg::DkgMessage {
+                session_id: fixed_dkg_session_id(),
+                message: fixed_dkg_message(),
+            },
+        ),
+        (
+            "system_dkg_not_ready",
+            SystemMsg::DkgNotReady {
+                session_id: fixed_dkg_session_id(),
+                message: fixed_dkg_message(),
+            },
+        ),
+        (
+            "system_dkg_retry",
+            SystemMsg::DkgRetry {
+                message_history: vec![fixed_dkg_message()],
+                session_id: fixed_dkg_session_id(),
+                message: fixed_dkg_message(),
+            },
+        ),
+        (
+            "system_dkg_failure_observation",
+            SystemMsg::DkgFailureObservation {
+                session_id: fixed_dkg_session_id(),
+                sig: fixed_dkg_failure_sig(),
+                failed_participants: BTreeSet::from([fixed_name(2)]),
+            },
+        ),
+        (
+            "system_dkg_failure_agreement",
+            SystemMsg::DkgFailureAgreement(DkgFailureSigSet {
+                sigs: vec![fixed_dkg_failure_sig()],
+                failed_participants: BTreeSet::from([fixed_name(2)]),
+                session_id: fixed_dkg_session_id(),
+            }),
+        ),
+        (
+            "system_handover_vote",
+            SystemMsg::HandoverVote(fixed_signed_vote(SapCandidate::ElderHandover(
+                fixed_section_auth(sap.clone().into_state()),
+            ))),
+        ),
+        (
+            "system_propose_offline",
+            SystemMsg::Propose {
+                proposal: Proposal::Offline(fixed_node_state(3)),
+                sig_share: fixed_sig_share(0, &fixed_node_state(3)),
+            },
+        ),
+        (
+            "system_propose_section_info",
+            SystemMsg::Propose {
+                proposal: Proposal::SectionInfo {
+                    sap: sap.clone(),
+                    generation: 3,
+                },
+                sig_share: fixed_sig_share(1, &sap),
+            },
+        ),
+        (
+            "system_propose_new_elders",
+            SystemMsg::Propose {
+                proposal: Proposal::NewElders(section_auth),
+                sig_share: fixed_sig_share(2, &sap.public_key_set.public_key()),
+            },
+        ),
+        (
+            "system_propose_joins_allowed",
+            SystemMsg::Propose {
+                proposal: Proposal::JoinsAllowed(true),
+                sig_share: fixed_sig_share(0, &true),
+            },
+        ),
+        (
+            "system_propose_storage_full",
+            SystemMsg::Propose {
+                proposal: Proposal::StorageFull {
+                    node: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                    level: StorageLevel::from(StorageLevel::MAX)
+                        .expect("MAX is a valid storage level"),
+                },
+                sig_share: fixed_sig_share(1, &StorageLevel::MAX),
+            },
+        ),
+        (
+            "system_propose_batch",
+            SystemMsg::ProposeBatch {
+                proposals: vec![
+                    Proposal::Offline(fixed_node_state(3)),
+                    Proposal::JoinsAllowed(false),
+                ],
+                sig_share: fixed_sig_share(2, &false),
+            },
+        ),
+        (
+            "system_start_connectivity_test",
+            SystemMsg::StartConnectivityTest(fixed_name(4)),
+        ),
+        (
+            "system_node_event_suspicious_nodes",
+            SystemMsg::NodeEvent(NodeEvent::SuspiciousNodesDetected(BTreeSet::from([
+                fixed_name(3),
+            ]))),
+        ),
+        (
+            "system_node_event_replicas_stored",
+            SystemMsg::NodeEvent(NodeEvent::ReplicasStored {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                replicas: BTreeSet::from([fixed_name(5), fixed_name(6)]),
+            }),
+        ),
+        (
+            "system_node_msg_error",
+            SystemMsg::NodeMsgError {
+                error: DataError::ChunkNotFound(*fixed_chunk().name()),
+                correlation_id: fixed_msg_id(50),
+            },
+        ),
+        (
+            "system_listener_redirect",
+            SystemMsg::ListenerRedirect {
+                addr: fixed_addr(7),
+                bounced_msg: Bytes::from_static(b"bounced message bytes"),
+            },
+        ),
+        (
+            "system_relay_register",
+            SystemMsg::Relay(RelayMsg::Register {
+                lease: Duration::from_secs(120),
+            }),
+        ),
+        (
+            "system_relay_refused",
+            SystemMsg::Relay(RelayMsg::Refused(RelayRefusal::Full)),
+        ),
+        (
+            "system_relay_forward",
+            SystemMsg::Relay(RelayMsg::Forward {
+                recipients: vec![Peer::new(fixed_name(8), fixed_addr(8))],
+                msg: Bytes::from_static(b"relayed message bytes"),
+            }),
+        ),
+    ];
+
+    #[cfg(feature = "back-pressure")]
+    msgs.push(("system_back_pressure", SystemMsg::BackPressure(12.5)));
+
+    #[cfg(any(feature = "chunks", feature = "registers"))]
+    msgs.extend([
+        (
+            "system_node_event_could_not_store_data",
+            SystemMsg::NodeEvent(NodeEvent::CouldNotStoreData {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                data: ReplicatedData::Chunk(fixed_chunk()),
+                full: true,
+            }),
+        ),
+        (
+            "system_node_cmd_metadata",
+            SystemMsg::NodeCmd(NodeCmd::Metadata {
+                cmd: DataCmd::Register(fixed_register_edit()),
+                auth: service_auth.clone(),
+                origin: fixed_end_user(),
+            }),
+        ),
+        (
+            "system_node_cmd_record_storage_level",
+            SystemMsg::NodeCmd(NodeCmd::RecordStorageLevel {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+                level: StorageLevel::from(3).expect("3 is a valid storage level"),
+            }),
+        ),
+        (
+            "system_node_cmd_replicate_data",
+            SystemMsg::NodeCmd(NodeCmd::ReplicateData(vec![
+                ReplicatedData::Chunk(fixed_chunk()),
+                ReplicatedData::RegisterWrite(fixed_register_create()),
+            ])),
+        ),
+        (
+            "system_node_cmd_send_replicate_data_address",
+            SystemMsg::NodeCmd(NodeCmd::SendReplicateDataAddress(vec![
+                ReplicatedDataAddress::Chunk(*fixed_chunk().address()),
+            ])),
+        ),
+        (
+            "system_node_cmd_fetch_replicate_data",
+            SystemMsg::NodeCmd(NodeCmd::FetchReplicateData(vec![
+                ReplicatedDataAddress::Register(*fixed_register().address()),
+            ])),
+        ),
+        (
+            "system_node_cmd_receive_metadata",
+            SystemMsg::NodeCmd(NodeCmd::ReceiveMetadata {
+                metadata: MetadataExchange {
+                    adult_levels: BTreeMap::from([(
+                        fixed_name(4),
+                        StorageLevel::from(1).expect("1 is a valid storage level"),
+                    )]),
+                },
+            }),
+        ),
+        (
+            "system_node_cmd_record_storage_health",
+            SystemMsg::NodeCmd(NodeCmd::RecordStorageHealth {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+                healthy: false,
+            }),
+        ),
+        (
+            "system_node_cmd_prepare_restart",
+            SystemMsg::NodeCmd(NodeCmd::PrepareRestart {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+                within: Duration::from_secs(300),
+            }),
+        ),
+        (
+            "system_node_cmd_send_inventory",
+            SystemMsg::NodeCmd(NodeCmd::SendInventory {
+                slice: Prefix::default().pushed(true).pushed(false),
+            }),
+        ),
+        (
+            "system_node_cmd_record_inventory",
+            SystemMsg::NodeCmd(NodeCmd::RecordInventory {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                slice: Prefix::default().pushed(true).pushed(false),
+                names: BTreeSet::from([fixed_name(60), fixed_name(61)]),
+            }),
+        ),
+        (
+            "system_node_cmd_leaving",
+            SystemMsg::NodeCmd(NodeCmd::Leaving {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+            }),
+        ),
+        (
+            "system_node_cmd_send_held",
+            SystemMsg::NodeCmd(NodeCmd::SendHeld {
+                batch: 7,
+                names: BTreeSet::from([fixed_name(60), fixed_name(61)]),
+            }),
+        ),
+        (
+            "system_node_cmd_record_held",
+            SystemMsg::NodeCmd(NodeCmd::RecordHeld {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                batch: 7,
+                held: BTreeSet::from([ReplicatedDataAddress::Chunk(*fixed_chunk().address())]),
+            }),
+        ),
+        (
+            "system_node_cmd_replicate_data_to",
+            SystemMsg::NodeCmd(NodeCmd::ReplicateDataTo {
+                addresses: vec![ReplicatedDataAddress::Chunk(*fixed_chunk().address())],
+                holder: fixed_name(5),
+            }),
+        ),
+        (
+            "system_node_cmd_replicate_checked_data",
+            SystemMsg::NodeCmd(NodeCmd::ReplicateCheckedData(vec![(
+                ReplicatedDataAddress::Chunk(*fixed_chunk().address()),
+                ReplicatedData::Chunk(fixed_chunk()),
+            )])),
+        ),
+        (
+            "system_node_cmd_request_chunk_replication",
+            SystemMsg::NodeCmd(NodeCmd::RequestChunkReplication {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+                names: BTreeSet::from([fixed_name(60), fixed_name(61)]),
+            }),
+        ),
+        (
+            "system_node_cmd_send_holding_proofs",
+            SystemMsg::NodeCmd(NodeCmd::SendHoldingProofs {
+                audit: 7,
+                nonce: [9; 32],
+                names: BTreeSet::from([fixed_name(60), fixed_name(61)]),
+            }),
+        ),
+        (
+            "system_node_cmd_record_holding_proofs",
+            SystemMsg::NodeCmd(NodeCmd::RecordHoldingProofs {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                audit: 7,
+                proofs: BTreeMap::from([(fixed_name(60), [3; 32])]),
+            }),
+        ),
+        (
+            "system_node_cmd_probe_liveness",
+            SystemMsg::NodeCmd(NodeCmd::ProbeLiveness {
+                probe: 8,
+                nonce: [9; 32],
+                name: Some(fixed_name(60)),
+            }),
+        ),
+        (
+            "system_node_cmd_record_liveness",
+            SystemMsg::NodeCmd(NodeCmd::RecordLiveness {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                probe: 8,
+                proof: Some([3; 32]),
+            }),
+        ),
+        (
+            "system_node_cmd_store_chunk_shares",
+            SystemMsg::NodeCmd(NodeCmd::StoreChunkShares(vec![fixed_chunk_share()])),
+        ),
+        (
+            "system_node_cmd_send_chunk_shares",
+            SystemMsg::NodeCmd(NodeCmd::SendChunkShares {
+                chunk: fixed_name(60),
+            }),
+        ),
+        (
+            "system_node_cmd_record_chunk_shares",
+            SystemMsg::NodeCmd(NodeCmd::RecordChunkShares {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                chunk: fixed_name(60),
+                shares: vec![fixed_chunk_share()],
+            }),
+        ),
+        (
+            "system_node_cmd_record_spent_proof_share",
+            SystemMsg::NodeCmd(NodeCmd::RecordSpentProofShare(fixed_spent_proof_share())),
+        ),
+        (
+            "system_node_cmd_record_split_handover",
+            SystemMsg::NodeCmd(NodeCmd::RecordSplitHandover {
+                node_id: PublicKey::Ed25519(fixed_ed25519_keypair(4).public),
+                section: fixed_name(1),
+                sibling: Prefix::default().pushed(true),
+                handed_over: 12,
+                failed: 1,
+            }),
+        ),
+        (
+            "system_node_query_metadata",
+            SystemMsg::NodeQuery(NodeQuery::Metadata {
+                query: DataQuery::Register(RegisterQuery::Get(*fixed_register().address())),
+                auth: service_auth.clone(),
+                origin: fixed_end_user(),
+            }),
+        ),
+        (
+            "system_node_query_data",
+            SystemMsg::NodeQuery(NodeQuery::Data {
+                query: DataQuery::GetChunk(*fixed_chunk().address()),
+                auth: service_auth,
+                origin: fixed_end_user(),
+                correlation_id: fixed_msg_id(51),
+            }),
+        ),
+        (
+            "system_node_query_response",
+            SystemMsg::NodeQueryResponse {
+                response: NodeQueryResponse::GetChunk(Ok(fixed_chunk())),
+                correlation_id: fixed_msg_id(51),
+                user: fixed_end_user(),
+            },
+        ),
+    ]);
+
+    msgs
+}
+
+/// A representative instance of every `ServiceMsg` variant, with a stable name for each.
+pub fn representative_service_msgs() -> Vec<(&'static str, ServiceMsg)> {
+    let chunk = fixed_chunk();
+    let register_address = *fixed_register().address();
+
+    vec![
+        (
+            "service_cmd_store_chunk",
+            ServiceMsg::Cmd(DataCmd::StoreChunk(chunk.clone())),
+        ),
+        (
+            "service_cmd_register_create",
+            ServiceMsg::Cmd(DataCmd::Register(fixed_register_create())),
+        ),
+        (
+            "service_cmd_register_edit",
+            ServiceMsg::Cmd(DataCmd::Register(fixed_register_edit())),
+        ),
+        (
+            "service_query_get_chunk",
+            ServiceMsg::Query(DataQuery::GetChunk(*chunk.address())),
+        ),
+        (
+            "service_query_register_read",
+            ServiceMsg::Query(DataQuery::Register(RegisterQuery::Read(register_address))),
+        ),
+        (
+            "service_query_register_history_range",
+            ServiceMsg::Query(DataQuery::Register(RegisterQuery::GetHistoryRange {
+                address: register_address,
+                start: 3,
+                end: 5,
+            })),
+        ),
+        (
+            "service_consistent_query",
+            ServiceMsg::ConsistentQuery {
+                query: DataQuery::Register(RegisterQuery::Read(register_address)),
+                tokens: vec![fixed_session_token(1, 7), fixed_session_token(2, 3)],
+            },
+        ),
+        (
+            "service_spentbook_cmd_spend",
+            ServiceMsg::SpentbookCmd(fixed_spend()),
+        ),
+        (
+            "service_spentbook_query_spent_proof",
+            ServiceMsg::SpentbookQuery(SpentbookQuery::SpentProof(fixed_genesis_input().0)),
+        ),
+        (
+            "service_batch_cmd",
+            ServiceMsg::BatchCmd(vec![
+                DataCmd::StoreChunk(chunk.clone()),
+                DataCmd::Register(fixed_register_edit()),
+            ]),
+        ),
+        (
+            "service_batch_query",
+            ServiceMsg::BatchQuery(BatchQuery {
+                batch: fixed_msg_id(66),
+                dst_name: *chunk.name(),
+            }),
+        ),
+        (
+            "service_query_response_get_chunk",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::GetChunk(Ok(chunk.clone())),
+                correlation_id: fixed_msg_id(60),
+            },
+        ),
+        (
+            "service_query_response_get_register",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::GetRegister((Ok(fixed_register()), [61; 32])),
+                correlation_id: fixed_msg_id(61),
+            },
+        ),
+        (
+            "service_query_response_register_history",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::GetRegisterHistory((
+                    Ok(vec![(EntryHash([69; 32]), b"fixed entry".to_vec())]),
+                    [69; 32],
+                )),
+                correlation_id: fixed_msg_id(69),
+            },
+        ),
+        (
+            "service_query_response_spent_proof_shares",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::SpentProofShares((
+                    Ok(vec![fixed_spent_proof_share()]),
+                    [64; 32],
+                )),
+                correlation_id: fixed_msg_id(64),
+            },
+        ),
+        (
+            "service_query_response_spent_proof",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::SpentProof((Ok(Some(fixed_spent_proof())), [65; 32])),
+                correlation_id: fixed_msg_id(65),
+            },
+        ),
+        (
+            "service_query_response_batch_status",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::BatchStatus((
+                    Ok(BatchStatus::Aborted(DataError::BatchAborted {
+                        index: 1,
+                        error: Box::new(DataError::NodeBusy),
+                    })),
+                    [67; 32],
+                )),
+                correlation_id: fixed_msg_id(67),
+            },
+        ),
+        (
+            "service_cmd_error",
+            ServiceMsg::CmdError {
+                error: CmdError::Data(
+                    ProcessingError::from(DataError::InsufficientAdults {
+                        prefix: Prefix::default(),
+                        expected: 4,
+                        found: 1,
+                    })
+                    .with_section_key(fixed_secret_key_set().public_keys().public_key()),
+                ),
+                correlation_id: fixed_msg_id(62),
+            },
+        ),
+        (
+            "service_error",
+            ServiceMsg::ServiceError(ServiceError {
+                reason: Some(DataError::DataNotFound(DataAddress::bytes(
+                    *chunk.name(),
+                    Scope::Public,
+                ))),
+                source_message: Some(Bytes::from_static(b"source message")),
+            }),
+        ),
+        (
+            "service_cmd_ack",
+            ServiceMsg::CmdAck {
+                correlation_id: fixed_msg_id(63),
+                token: None,
+            },
+        ),
+        (
+            "service_cmd_ack_with_token",
+            ServiceMsg::CmdAck {
+                correlation_id: fixed_msg_id(68),
+                token: Some(fixed_session_token(1, 7)),
+            },
+        ),
+        (
+            "service_holders_query",
+            ServiceMsg::HoldersQuery(HoldersQuery {
+                name: *chunk.name(),
+            }),
+        ),
+        (
+            "service_query_response_data_holders",
+            ServiceMsg::QueryResponse {
+                response: QueryResponse::GetDataHolders((
+                    Ok(DataHolders {
+                        prefix: Prefix::default(),
+                        section_key: fixed_secret_key_set().public_keys().public_key(),
+                        holders: BTreeSet::from([fixed_name(70), fixed_name(71)]),
+                        recorded: true,
+                    }),
+                    [70; 32],
+                )),
+                correlation_id: fixed_msg_id(52),
+            },
+        ),
+    ]
+}
+
+/// Fully serialized `WireMsg`s, header included, for each kind of message authority.
+pub fn representative_wire_msgs() -> Vec<(&'static str, WireMsg)> {
+    let section_pk = fixed_secret_key_set().public_keys().public_key();
+    let dst = DstLocation::Section {
+        name: fixed_name(100),
+        section_pk,
+    };
+
+    let service_msg = ServiceMsg::Query(DataQuery::GetChunk(*fixed_chunk().address()));
+    let service_payload =
+        WireMsg::serialize_msg_payload(&service_msg).expect("failed to serialize payload");
+    let service_auth = fixed_service_auth(10, &service_payload);
+
+    let node_msg = SystemMsg::AntiEntropyProbe(fixed_name(100));
+    let node_payload =
+        WireMsg::serialize_msg_payload(&node_msg).expect("failed to serialize payload");
+    let node_auth = NodeAuth::authorize(section_pk, &fixed_ed25519_keypair(1), &node_payload);
+
+    let share_msg = SystemMsg::Propose {
+        proposal: Proposal::JoinsAllowed(false),
+        sig_share: fixed_sig_share(0, &false),
+    };
+    let share_payload =
+        WireMsg::serialize_msg_payload(&share_msg).expect("failed to serialize payload");
+    let share_auth = crate::messaging::BlsShareAuth {
+        section_pk,
+        src_name: fixed_name(1),
+        sig_share: fixed_sig_share(0, &share_payload),
+    };
+
+    vec![
+        (
+            "wire_msg_service_auth",
+            WireMsg::new_msg(
+                fixed_msg_id(70),
+                service_payload,
+                AuthKind::Service(service_auth),
+                dst,
+            )
+            .expect("failed to build wire msg"),
+        ),
+        (
+            "wire_msg_node_auth",
+            WireMsg::new_msg(
+                fixed_msg_id(71),
+                node_payload,
+                AuthKind::Node(node_auth.into_inner()),
+                dst,
+            )
+            .expect("failed to build wire msg"),
+        ),
+        (
+            "wire_msg_node_bls_share_auth",
+            WireMsg::new_msg(
+                fixed_msg_id(72),
+                share_payload,
+                AuthKind::NodeBlsShare(share_auth),
+                DstLocation::Node {
+                    name: fixed_name(2),
+                    section_pk,
+                },
+            )
+            .expect("failed to build wire msg"),
+        ),
+    ]
+}
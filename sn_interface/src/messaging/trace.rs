@@ -0,0 +1,106 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use xor_name::XorName;
+
+/// Most hops a trace records, those past it being left out, so msgs bouncing around don't grow
+/// without bound.
+pub const MAX_TRACE_HOPS: usize = 32;
+/// Longest description of a duty, longer ones being cut short.
+pub const MAX_DUTY_LEN: usize = 64;
+
+/// The nodes a request went through, and what each of them did with it, for the client to see
+/// which of them handled a slow request. Requested by the client setting it, empty, in the
+/// header of its msg, and passed on by every node with the msgs the request leads it to send.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    /// The hops, in the order they were taken.
+    pub hops: Vec<TraceHop>,
+}
+
+/// A node handling a traced msg.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHop {
+    /// Name of the node.
+    pub node: XorName,
+    /// When the node handled the msg, in milliseconds since the Unix epoch.
+    pub at: u64,
+    /// What the node did with the msg.
+    pub duty: String,
+}
+
+impl TraceContext {
+    /// Records `node` handling the msg now, unless the trace is full already.
+    pub fn add_hop(&mut self, node: XorName, duty: &str) {
+        if self.hops.len() >= MAX_TRACE_HOPS {
+            return;
+        }
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        self.hops.push(TraceHop {
+            node,
+            at,
+            duty: duty.chars().take(MAX_DUTY_LEN).collect(),
+        });
+    }
+
+    /// Upper bound of the length of the trace serialised.
+    pub fn max_serialised_len(&self) -> usize {
+        // Name, timestamp, the duty's UTF-8 bytes and Msgpack's markers for all of them.
+        16 + self.hops.len() * (64 + 4 * MAX_DUTY_LEN)
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = self.hops.first().map_or(0, |hop| hop.at);
+        for (i, hop) in self.hops.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(
+                f,
+                "{} {} (+{}ms)",
+                hop.node,
+                hop.duty,
+                hop.at.saturating_sub(first)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hops_stop_being_recorded_once_the_trace_is_full() {
+        let mut trace = TraceContext::default();
+        let long_duty = "x".repeat(2 * MAX_DUTY_LEN);
+        for _ in 0..MAX_TRACE_HOPS + 1 {
+            trace.add_hop(xor_name::rand::random(), &long_duty);
+        }
+
+        assert_eq!(trace.hops.len(), MAX_TRACE_HOPS);
+        assert!(trace.hops.iter().all(|hop| hop.duty.len() == MAX_DUTY_LEN));
+        assert!(trace.hops.windows(2).all(|hops| hops[0].at <= hops[1].at));
+        assert!(
+            rmp_serde::to_vec_named(&trace)
+                .map(|bytes| bytes.len())
+                .ok()
+                <= Some(trace.max_serialised_len())
+        );
+    }
+}
@@ -35,6 +35,8 @@ pub enum Error {
     PermitAcquisitionFailed,
     #[error("Section authority provider cannot be trusted: {0}")]
     UntrustedSectionAuthProvider(String),
+    #[error("Section authority provider is invalid: {0}")]
+    InvalidSap(#[from] super::SapValidationError),
     #[error("Proof chain cannot be trusted: {0}")]
     UntrustedProofChain(String),
     #[error("Invalid genesis key of provided prefix map: {}", hex::encode(_0.to_bytes()))]
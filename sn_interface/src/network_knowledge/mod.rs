@@ -7,9 +7,11 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod errors;
+mod network_params;
 mod node_info;
 pub mod node_state;
 pub mod prefix_map;
+mod sap_validation;
 pub mod section_authority_provider;
 pub mod section_keys;
 mod section_peers;
@@ -20,8 +22,10 @@ pub use self::section_authority_provider::test_utils;
 
 pub use self::section_keys::{SectionKeyShare, SectionKeysProvider};
 
+pub use network_params::NetworkParams;
 pub use node_info::NodeInfo;
 pub use node_state::NodeState;
+pub use sap_validation::{InvalidSapCounts, SapValidationError};
 pub use section_authority_provider::{SapCandidate, SectionAuthUtils, SectionAuthorityProvider};
 
 use crate::messaging::{
@@ -57,7 +61,7 @@ const SN_ELDER_COUNT: &str = "SN_ELDER_COUNT";
 /// Number of elders per section.
 pub const DEFAULT_ELDER_COUNT: usize = 7;
 
-/// Get the expected elder count for our network.
+/// Get the elder count a new network is set up with, see `NetworkParams`.
 /// Defaults to DEFAULT_ELDER_COUNT, but can be overridden by the env var SN_ELDER_COUNT.
 pub fn elder_count() -> usize {
     // if we have an env var for this, lets override
@@ -79,14 +83,14 @@ pub fn elder_count() -> usize {
     }
 }
 
-/// Recommended section size.
+/// Recommended section size of a new network, see `NetworkParams::recommended_section_size`.
 /// The section will keep adding nodes when requested by the upper layers, until it can split.
 /// A split happens if both post-split sections would have at least this number of nodes.
 pub fn recommended_section_size() -> usize {
     2 * crate::network_knowledge::elder_count()
 }
 
-/// SuperMajority of a given group (i.e. > 2/3)
+/// SuperMajority of a given group (i.e. > 2/3), with the default ratio of `NetworkParams`.
 #[inline]
 pub const fn supermajority(group_size: usize) -> usize {
     1 + group_size * 2 / 3
@@ -153,6 +157,8 @@ impl NetworkKnowledge {
             )));
         }
 
+        signed_sap.validate()?;
+
         // Make sure the section chain can be trusted, i.e. check that
         // each key is signed by its parent/predecesor key.
         if !chain.self_verify() {
@@ -615,6 +621,11 @@ impl NetworkKnowledge {
         self.signed_sap.read().await.clone()
     }
 
+    /// Params of the network, as carried in our section's SAP.
+    pub async fn network_params(&self) -> NetworkParams {
+        self.signed_sap.read().await.network_params()
+    }
+
     /// Prefix of our section.
     pub async fn prefix(&self) -> Prefix {
         self.signed_sap.read().await.prefix()
@@ -631,6 +642,12 @@ impl NetworkKnowledge {
         self.signed_sap.read().await.contains_elder(name)
     }
 
+    /// Changes whenever our section's members do, though not with its elders: together with
+    /// the section key, it tells when what's derived from our adults is to be recomputed.
+    pub fn members_version(&self) -> u64 {
+        self.section_peers.version()
+    }
+
     /// Returns members that are joined.
     pub async fn section_members(&self) -> BTreeSet<NodeState> {
         self.section_peers
@@ -0,0 +1,179 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{elder_count, SapValidationError, DEFAULT_ELDER_COUNT};
+
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Sha3};
+
+const SN_SUPERMAJORITY: &str = "SN_SUPERMAJORITY";
+// A supermajority of a group is more than this fraction of it, by default.
+const DEFAULT_SUPERMAJORITY: (usize, usize) = (2, 3);
+
+/// Parameters of a network which all its sections run with, set by its genesis node and carried
+/// in every `SectionAuthorityProvider` from then on.
+///
+/// A private network can so run smaller sections than the default, without nodes being built
+/// for it: the genesis node takes them from the `SN_ELDER_COUNT` and `SN_SUPERMAJORITY` (e.g.
+/// `3/4`) env vars, other nodes from their section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NetworkParams {
+    elder_count: usize,
+    // A supermajority of a group is more than this fraction of it, as numerator and denominator.
+    supermajority: (usize, usize),
+}
+
+impl Default for NetworkParams {
+    /// The params compiled in, disregarding the env vars overriding them.
+    fn default() -> Self {
+        Self {
+            elder_count: DEFAULT_ELDER_COUNT,
+            supermajority: DEFAULT_SUPERMAJORITY,
+        }
+    }
+}
+
+impl NetworkParams {
+    /// Params of `elder_count` elders per section, a supermajority of a group being more than
+    /// `numerator / denominator` of it, which must be at least half.
+    pub fn new(
+        elder_count: usize,
+        (numerator, denominator): (usize, usize),
+    ) -> Result<Self, SapValidationError> {
+        let params = Self {
+            elder_count,
+            supermajority: (numerator, denominator),
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// The params a new network is set up with: the default ones, but for those overridden by
+    /// env vars.
+    pub fn from_env() -> Self {
+        let supermajority = match std::env::var(SN_SUPERMAJORITY) {
+            Ok(ratio) => match parse_ratio(&ratio) {
+                Some(ratio) => {
+                    warn!(
+                        "Supermajority set from env var {}: {:?}",
+                        SN_SUPERMAJORITY, ratio
+                    );
+                    ratio
+                }
+                None => {
+                    warn!(
+                        "There was an error parsing {:?} env var {:?}, expected e.g. 2/3. \
+                        The default supermajority will be used",
+                        SN_SUPERMAJORITY, ratio
+                    );
+                    DEFAULT_SUPERMAJORITY
+                }
+            },
+            Err(_) => DEFAULT_SUPERMAJORITY,
+        };
+        Self::new(elder_count(), supermajority).unwrap_or_else(|error| {
+            warn!("Invalid network params from env vars, the default ones will be used: {error}");
+            Self::default()
+        })
+    }
+
+    /// Checks the params make sense: there's at least one elder, and two supermajorities of a
+    /// group can't be disjoint.
+    pub fn validate(&self) -> Result<(), SapValidationError> {
+        let (numerator, denominator) = self.supermajority;
+        if self.elder_count == 0 || numerator >= denominator || 2 * numerator < denominator {
+            return Err(SapValidationError::InvalidNetworkParams(*self));
+        }
+        Ok(())
+    }
+
+    /// Number of elders per section.
+    pub fn elder_count(&self) -> usize {
+        self.elder_count
+    }
+
+    /// Supermajority of a group of `group_size`, i.e. the least number of its members which is
+    /// more than the supermajority fraction of it.
+    pub fn supermajority(&self, group_size: usize) -> usize {
+        let (numerator, denominator) = self.supermajority;
+        1 + group_size * numerator / denominator
+    }
+
+    /// Threshold of the section key set of `elder_count` elders, so that a supermajority of them
+    /// is needed to sign.
+    pub fn threshold(&self, elder_count: usize) -> usize {
+        self.supermajority(elder_count) - 1
+    }
+
+    /// Feeds the params to `hasher`, for them to be part of what's hashed.
+    pub fn hash_update(&self, hasher: &mut Sha3) {
+        let (numerator, denominator) = self.supermajority;
+        for value in [self.elder_count, numerator, denominator] {
+            hasher.update(&(value as u64).to_le_bytes());
+        }
+    }
+
+    /// Recommended section size.
+    /// A split happens if both post-split sections would have at least this number of nodes.
+    pub fn recommended_section_size(&self) -> usize {
+        2 * self.elder_count
+    }
+}
+
+// Parses a ratio such as `2/3`.
+fn parse_ratio(ratio: &str) -> Option<(usize, usize)> {
+    let (numerator, denominator) = ratio.trim().split_once('/')?;
+    Some((
+        numerator.trim().parse().ok()?,
+        denominator.trim().parse().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_knowledge::supermajority;
+
+    #[test]
+    fn default_params_agree_with_the_compiled_in_quorums() {
+        let params = NetworkParams::default();
+        for group_size in 0..100 {
+            assert_eq!(params.supermajority(group_size), supermajority(group_size));
+        }
+        assert_eq!(params.threshold(7), 4);
+        assert_eq!(params.recommended_section_size(), 2 * DEFAULT_ELDER_COUNT);
+    }
+
+    #[test]
+    fn smaller_sections_with_other_supermajorities() -> Result<(), SapValidationError> {
+        let params = NetworkParams::new(3, (3, 4))?;
+        assert_eq!(params.elder_count(), 3);
+        assert_eq!(params.supermajority(3), 3);
+        assert_eq!(params.supermajority(4), 4);
+        assert_eq!(params.supermajority(8), 7);
+        assert_eq!(params.threshold(3), 2);
+
+        let majority = NetworkParams::new(4, (1, 2))?;
+        assert_eq!(majority.supermajority(4), 3);
+        assert_eq!(majority.supermajority(5), 3);
+
+        for (elder_count, ratio) in [(0, (2, 3)), (7, (1, 3)), (7, (3, 3)), (7, (1, 0))] {
+            assert!(
+                NetworkParams::new(elder_count, ratio).is_err(),
+                "{} {:?}",
+                elder_count,
+                ratio
+            );
+        }
+
+        assert_eq!(parse_ratio(" 3/4 "), Some((3, 4)));
+        assert_eq!(parse_ratio("3"), None);
+        assert_eq!(parse_ratio("a/4"), None);
+        Ok(())
+    }
+}
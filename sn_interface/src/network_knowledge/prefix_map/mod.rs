@@ -182,6 +182,8 @@ impl NetworkPrefixMap {
         proof_chain: &SecuredLinkedList,
         section_chain: &SecuredLinkedList,
     ) -> Result<bool> {
+        signed_sap.validate()?;
+
         // Check if SAP signature is valid
         if !signed_sap.self_verify() {
             return Err(Error::UntrustedSectionAuthProvider(format!(
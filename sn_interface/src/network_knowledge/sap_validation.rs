@@ -0,0 +1,376 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{NetworkParams, SectionAuthorityProvider};
+
+use crate::types::Peer;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use xor_name::{Prefix, XorName};
+
+// Compressed encoding of the G1 identity point.
+const IDENTITY_KEY_BYTES: [u8; bls::PK_SIZE] = {
+    let mut bytes = [0; bls::PK_SIZE];
+    bytes[0] = 0xc0;
+    bytes
+};
+
+/// A structural rule a `SectionAuthorityProvider` received from the network violates.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SapValidationError {
+    /// The SAP has no elders, or more than a section can have.
+    #[error("SAP has {count} elders, expected between 1 and {max}")]
+    ElderCount {
+        /// Number of elders in the SAP.
+        count: usize,
+        /// Maximum number of elders of a section.
+        max: usize,
+    },
+    /// Two elders share the same name.
+    #[error("SAP lists elder name {0} more than once")]
+    DuplicateElderName(XorName),
+    /// Two elders share the same endpoint.
+    #[error("SAP lists elder endpoint {0} more than once")]
+    DuplicateElderAddr(SocketAddr),
+    /// An elder's name falls outside the SAP's prefix.
+    #[error("SAP elder {name} doesn't match the section prefix {prefix:?}")]
+    ElderOutsidePrefix {
+        /// Name of the elder.
+        name: XorName,
+        /// Prefix of the SAP.
+        prefix: Prefix,
+    },
+    /// An elder's endpoint can't be connected to.
+    #[error("SAP elder {name} has an invalid endpoint {addr}")]
+    InvalidElderAddr {
+        /// Name of the elder.
+        name: XorName,
+        /// The offending endpoint.
+        addr: SocketAddr,
+    },
+    /// The SAP carries an empty public key set, so has no section key.
+    #[error("SAP has no section key")]
+    MissingSectionKey,
+    /// The section key is the identity key, which any signature trivially verifies against.
+    #[error("SAP section key is not a valid key")]
+    InvalidSectionKey,
+    /// The network params make no sense, e.g. with no elders.
+    #[error("invalid network params {0:?}")]
+    InvalidNetworkParams(NetworkParams),
+}
+
+impl SectionAuthorityProvider {
+    /// Checks the SAP is well-formed: its network params make sense, its elder count is within
+    /// their bounds, elder names and endpoints are unique, every elder belongs to the SAP's
+    /// prefix and has a connectable endpoint, and the section key is a proper key.
+    ///
+    /// This doesn't verify the SAP is signed by the section, which is checked against a
+    /// section chain when the SAP is used to update our knowledge.
+    pub fn validate(&self) -> Result<(), SapValidationError> {
+        self.network_params().validate()?;
+        let count = self.elder_count();
+        let max = self.network_params().elder_count();
+        if count == 0 || count > max {
+            return Err(SapValidationError::ElderCount { count, max });
+        }
+
+        let mut names = BTreeSet::new();
+        let mut addrs = BTreeSet::new();
+        for elder in self.elders() {
+            if !names.insert(elder.name()) {
+                return Err(SapValidationError::DuplicateElderName(elder.name()));
+            }
+            if !addrs.insert(elder.addr()) {
+                return Err(SapValidationError::DuplicateElderAddr(elder.addr()));
+            }
+            if !self.prefix().matches(&elder.name()) {
+                return Err(SapValidationError::ElderOutsidePrefix {
+                    name: elder.name(),
+                    prefix: self.prefix(),
+                });
+            }
+            if !is_valid_endpoint(&elder.addr()) {
+                return Err(SapValidationError::InvalidElderAddr {
+                    name: elder.name(),
+                    addr: elder.addr(),
+                });
+            }
+        }
+
+        // Checked before anything reads the section key, as that panics on an empty key set.
+        let public_key_set = self.public_key_set();
+        if public_key_set.to_bytes().is_empty() {
+            return Err(SapValidationError::MissingSectionKey);
+        }
+        if public_key_set.public_key().to_bytes() == IDENTITY_KEY_BYTES {
+            return Err(SapValidationError::InvalidSectionKey);
+        }
+
+        Ok(())
+    }
+}
+
+// Loopback and private addresses are fine, as those are what local and LAN networks use.
+fn is_valid_endpoint(addr: &SocketAddr) -> bool {
+    if addr.port() == 0 || addr.ip().is_unspecified() || addr.ip().is_multicast() {
+        return false;
+    }
+    match addr.ip() {
+        IpAddr::V4(ip) => !ip.is_broadcast(),
+        IpAddr::V6(_) => true,
+    }
+}
+
+/// Number of invalid SAPs received from each source.
+#[derive(Clone, Debug, Default)]
+pub struct InvalidSapCounts {
+    counts: Arc<RwLock<BTreeMap<XorName, usize>>>,
+}
+
+impl InvalidSapCounts {
+    /// Validates a SAP received from `source`, counting it against the source if invalid.
+    pub async fn check(
+        &self,
+        sap: &SectionAuthorityProvider,
+        source: &Peer,
+    ) -> Result<(), SapValidationError> {
+        if let Err(error) = sap.validate() {
+            let mut counts = self.counts.write().await;
+            let count = counts.entry(source.name()).or_default();
+            *count += 1;
+            warn!(
+                "Invalid SAP for {:?} received from {} ({} so far): {}",
+                sap.prefix(),
+                source,
+                count,
+                error
+            );
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Number of invalid SAPs received from the source with the given name.
+    pub async fn get(&self, source: &XorName) -> usize {
+        self.counts.read().await.get(source).copied().unwrap_or(0)
+    }
+
+    /// Number of invalid SAPs received, per source name.
+    pub async fn all(&self) -> BTreeMap<XorName, usize> {
+        self.counts.read().await.clone()
+    }
+}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+    use super::*;
+    use crate::network_knowledge::{
+        elder_count,
+        test_utils::{gen_addr, gen_section_authority_provider},
+        NodeState,
+    };
+
+    use eyre::Result;
+
+    // Rebuilds `sap` with the given elders, keeping its prefix, members and key.
+    fn with_elders(sap: &SectionAuthorityProvider, elders: Vec<Peer>) -> SectionAuthorityProvider {
+        SectionAuthorityProvider::new(
+            elders,
+            sap.prefix(),
+            sap.elders().map(|peer| NodeState::joined(*peer, None)),
+            sap.public_key_set(),
+        )
+    }
+
+    fn valid_sap() -> SectionAuthorityProvider {
+        gen_section_authority_provider(Prefix::default().pushed(true), elder_count()).0
+    }
+
+    #[test]
+    fn valid_sap_passes() {
+        assert_eq!(valid_sap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn elder_count_out_of_bounds_is_rejected() {
+        let sap = valid_sap();
+        assert_eq!(
+            with_elders(&sap, vec![]).validate(),
+            Err(SapValidationError::ElderCount {
+                count: 0,
+                max: elder_count()
+            })
+        );
+
+        let (too_many, _, _) = gen_section_authority_provider(Prefix::default(), elder_count() + 1);
+        assert_eq!(
+            too_many.validate(),
+            Err(SapValidationError::ElderCount {
+                count: elder_count() + 1,
+                max: elder_count()
+            })
+        );
+    }
+
+    #[test]
+    fn elder_count_is_bounded_by_the_network_params() -> Result<()> {
+        let smaller = NetworkParams::new(3, (2, 3))?;
+        assert_eq!(
+            valid_sap().with_network_params(smaller).validate(),
+            Err(SapValidationError::ElderCount {
+                count: elder_count(),
+                max: 3
+            })
+        );
+
+        let (sap, _, _) = gen_section_authority_provider(Prefix::default(), 3);
+        assert_eq!(sap.with_network_params(smaller).validate(), Ok(()));
+
+        let no_elders: NetworkParams =
+            bincode::deserialize(&bincode::serialize(&(0_u64, (2_u64, 3_u64)))?)?;
+        assert_eq!(
+            valid_sap().with_network_params(no_elders).validate(),
+            Err(SapValidationError::InvalidNetworkParams(no_elders))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_elder_name_is_rejected() {
+        let sap = valid_sap();
+        let mut elders = sap.elders_vec();
+        let duplicate = Peer::new(elders[0].name(), gen_addr());
+        elders.pop();
+        elders.push(duplicate);
+
+        assert_eq!(
+            with_elders(&sap, elders).validate(),
+            Err(SapValidationError::DuplicateElderName(duplicate.name()))
+        );
+    }
+
+    #[test]
+    fn duplicate_elder_addr_is_rejected() {
+        let sap = valid_sap();
+        let mut elders = sap.elders_vec();
+        let addr = elders[0].addr();
+        elders[1] = Peer::new(elders[1].name(), addr);
+
+        assert_eq!(
+            with_elders(&sap, elders).validate(),
+            Err(SapValidationError::DuplicateElderAddr(addr))
+        );
+    }
+
+    #[test]
+    fn elder_outside_prefix_is_rejected() {
+        let sap = valid_sap();
+        let outsider =
+            gen_section_authority_provider(Prefix::default().pushed(false), 1).1[0].peer();
+        let mut elders = sap.elders_vec();
+        elders[0] = outsider;
+
+        assert_eq!(
+            with_elders(&sap, elders).validate(),
+            Err(SapValidationError::ElderOutsidePrefix {
+                name: outsider.name(),
+                prefix: sap.prefix()
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_elder_endpoints_are_rejected() -> Result<()> {
+        let sap = valid_sap();
+        for addr in [
+            "0.0.0.0:12000",
+            "192.0.2.1:0",
+            "224.0.0.1:12000",
+            "255.255.255.255:12000",
+            "[::]:12000",
+            "[ff02::1]:12000",
+        ] {
+            let addr: SocketAddr = addr.parse()?;
+            let mut elders = sap.elders_vec();
+            elders[0] = Peer::new(elders[0].name(), addr);
+
+            assert_eq!(
+                with_elders(&sap, elders.clone()).validate(),
+                Err(SapValidationError::InvalidElderAddr {
+                    name: elders[0].name(),
+                    addr
+                })
+            );
+        }
+
+        // Local and private networks are fine.
+        for addr in ["127.0.0.1:12000", "10.0.0.1:12000", "[::1]:12000"] {
+            let mut elders = sap.elders_vec();
+            elders[0] = Peer::new(elders[0].name(), addr.parse()?);
+            assert_eq!(with_elders(&sap, elders).validate(), Ok(()));
+        }
+
+        Ok(())
+    }
+
+    // Rebuilds `sap` with the given key set.
+    fn with_key_set(
+        sap: &SectionAuthorityProvider,
+        public_key_set: bls::PublicKeySet,
+    ) -> SectionAuthorityProvider {
+        SectionAuthorityProvider::new(
+            sap.elders_vec(),
+            sap.prefix(),
+            sap.elders().map(|peer| NodeState::joined(*peer, None)),
+            public_key_set,
+        )
+    }
+
+    #[test]
+    fn missing_section_key_is_rejected() {
+        let empty = bls::PublicKeySet::from(bls::poly::Poly::zero().commitment());
+        assert_eq!(
+            with_key_set(&valid_sap(), empty).validate(),
+            Err(SapValidationError::MissingSectionKey)
+        );
+    }
+
+    #[test]
+    fn identity_section_key_is_rejected() -> Result<()> {
+        let identity = bls::PublicKeySet::from_bytes(IDENTITY_KEY_BYTES.to_vec())?;
+        assert_eq!(
+            with_key_set(&valid_sap(), identity).validate(),
+            Err(SapValidationError::InvalidSectionKey)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_saps_are_counted_per_source() {
+        let counts = InvalidSapCounts::default();
+        let sap = valid_sap();
+        let invalid = with_elders(&sap, vec![]);
+        let source = sap.elders_vec()[0];
+        let other_source = sap.elders_vec()[1];
+
+        assert!(counts.check(&sap, &source).await.is_ok());
+        assert!(counts.check(&invalid, &source).await.is_err());
+        assert!(counts.check(&invalid, &source).await.is_err());
+        assert!(counts.check(&invalid, &other_source).await.is_err());
+
+        assert_eq!(counts.get(&source.name()).await, 2);
+        assert_eq!(counts.get(&other_source.name()).await, 1);
+        assert_eq!(counts.get(&xor_name::rand::random()).await, 0);
+    }
+}
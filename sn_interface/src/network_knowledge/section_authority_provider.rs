@@ -6,13 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::NodeState;
+use super::{NetworkParams, NodeState};
 
 use crate::messaging::system::DkgSessionId;
 use crate::messaging::{
     system::{KeyedSig, SectionAuth},
     SectionAuthorityProvider as SectionAuthorityProviderMsg,
 };
+use crate::protocol_constants::{ProtocolConstants, ProtocolDigest};
 use crate::types::Peer;
 use xor_name::{Prefix, XorName};
 
@@ -64,6 +65,8 @@ pub struct SectionAuthorityProvider {
     public_key_set: PublicKeySet,
     elders: BTreeSet<Peer>,
     members: BTreeSet<NodeState>,
+    protocol_digest: ProtocolDigest,
+    network_params: NetworkParams,
 }
 
 /// SectionAuthorityProvider candidates for handover consensus to vote on
@@ -119,7 +122,9 @@ impl<'de> serde::Deserialize<'de> for SectionAuthorityProvider {
 }
 
 impl SectionAuthorityProvider {
-    /// Creates a new `SectionAuthorityProvider` with the given members, prefix and public keyset.
+    /// Creates a new `SectionAuthorityProvider` with the given members, prefix and public keyset,
+    /// recording the digest of the protocol constants we run with, and the network params a new
+    /// network would be set up with.
     pub fn new<E, M>(elders: E, prefix: Prefix, members: M, pk_set: PublicKeySet) -> Self
     where
         E: IntoIterator<Item = Peer>,
@@ -130,10 +135,26 @@ impl SectionAuthorityProvider {
             public_key_set: pk_set,
             elders: elders.into_iter().collect(),
             members: members.into_iter().collect(),
+            protocol_digest: ProtocolConstants::current().digest(),
+            network_params: NetworkParams::from_env(),
         }
     }
 
+    /// Records `digest` as the one of the protocol constants of the elders who made it instead.
+    pub fn with_protocol_digest(mut self, digest: ProtocolDigest) -> Self {
+        self.protocol_digest = digest;
+        self
+    }
+
+    /// Records `params` as the ones of the network instead.
+    pub fn with_network_params(mut self, params: NetworkParams) -> Self {
+        self.network_params = params;
+        self
+    }
+
+    /// The SAP resulting from the DKG session, with the network params it was run with.
     pub fn from_dkg_session(session_id: DkgSessionId, pk_set: PublicKeySet) -> Self {
+        let network_params = session_id.network_params;
         Self::new(
             session_id.elder_peers(),
             session_id.prefix,
@@ -144,6 +165,7 @@ impl SectionAuthorityProvider {
                 .map(|n| n.into_state()),
             pk_set,
         )
+        .with_network_params(network_params)
     }
 
     pub fn prefix(&self) -> Prefix {
@@ -189,6 +211,21 @@ impl SectionAuthorityProvider {
         self.public_key_set.public_key()
     }
 
+    /// Public key set of the section's elders.
+    pub fn public_key_set(&self) -> PublicKeySet {
+        self.public_key_set.clone()
+    }
+
+    /// Digest of the protocol constants of the elders who made it.
+    pub fn protocol_digest(&self) -> ProtocolDigest {
+        self.protocol_digest
+    }
+
+    /// Params of the network the section is part of.
+    pub fn network_params(&self) -> NetworkParams {
+        self.network_params
+    }
+
     // We prefer this over `From<...>` to make it easier to read the conversion.
     pub fn to_msg(&self) -> SectionAuthorityProviderMsg {
         SectionAuthorityProviderMsg {
@@ -204,6 +241,8 @@ impl SectionAuthorityProvider {
                 .iter()
                 .map(|state| (state.name(), state.to_msg()))
                 .collect(),
+            protocol_digest: self.protocol_digest,
+            network_params: self.network_params,
         }
     }
 }
@@ -229,6 +268,8 @@ impl SectionAuthorityProviderMsg {
                 .map(|(_name, state)| state.into_state()),
             self.public_key_set,
         )
+        .with_protocol_digest(self.protocol_digest)
+        .with_network_params(self.network_params)
     }
 }
 
@@ -293,13 +334,23 @@ pub mod test_utils {
     pub fn gen_section_authority_provider(
         prefix: Prefix,
         count: usize,
+    ) -> (SectionAuthorityProvider, Vec<NodeInfo>, SecretKeySet) {
+        gen_section_authority_provider_with_params(prefix, count, NetworkParams::from_env())
+    }
+
+    // Generate random `SectionAuthorityProvider` of a network of `params` for testing purposes.
+    pub fn gen_section_authority_provider_with_params(
+        prefix: Prefix,
+        count: usize,
+        params: NetworkParams,
     ) -> (SectionAuthorityProvider, Vec<NodeInfo>, SecretKeySet) {
         let nodes = gen_sorted_nodes(&prefix, count, false);
         let elders = nodes.iter().map(NodeInfo::peer);
         let members = nodes.iter().map(|i| NodeState::joined(i.peer(), None));
-        let secret_key_set = SecretKeySet::random();
+        let secret_key_set = SecretKeySet::random_with_params(params);
         let section_auth =
-            SectionAuthorityProvider::new(elders, prefix, members, secret_key_set.public_keys());
+            SectionAuthorityProvider::new(elders, prefix, members, secret_key_set.public_keys())
+                .with_network_params(params);
 
         (section_auth, nodes, secret_key_set)
     }
@@ -11,7 +11,13 @@ use crate::network_knowledge::NodeState;
 
 use dashmap::{mapref::entry::Entry, DashMap};
 use secured_linked_list::SecuredLinkedList;
-use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 use xor_name::{Prefix, XorName};
 
 // Number of Elder churn events before a Left/Relocated member
@@ -23,6 +29,8 @@ const ELDER_CHURN_EVENTS_TO_PRUNE_ARCHIVE: usize = 5;
 pub(super) struct SectionPeers {
     members: Arc<DashMap<XorName, SectionAuth<NodeState>>>,
     archive: Arc<DashMap<XorName, SectionAuth<NodeState>>>,
+    // Bumped whenever the set of current members changes.
+    version: Arc<AtomicU64>,
 }
 
 impl SectionPeers {
@@ -42,6 +50,12 @@ impl SectionPeers {
         self.members.len()
     }
 
+    /// Changes whenever the set of current members does, for what's derived from it to be
+    /// recomputed only then.
+    pub(super) fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
     /// Get the `NodeState` for the member with the given name.
     pub(super) fn get(&self, name: &XorName) -> Option<NodeState> {
         self.members.get(name).map(|state| state.value.clone())
@@ -114,13 +128,20 @@ impl SectionPeers {
         if should_remove {
             let _prev = self.members.remove(&node_name);
         }
+        if should_insert || should_remove {
+            let _prev = self.version.fetch_add(1, Ordering::AcqRel);
+        }
 
         updating_something
     }
 
     /// Remove all members whose name does not match `prefix`.
     pub(super) fn retain(&self, prefix: &Prefix) {
-        self.members.retain(|name, _| prefix.matches(name))
+        let before = self.members.len();
+        self.members.retain(|name, _| prefix.matches(name));
+        if self.members.len() != before {
+            let _prev = self.version.fetch_add(1, Ordering::AcqRel);
+        }
     }
 
     // Remove any member which Left, or was Relocated, more
@@ -0,0 +1,186 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The manifest of the protocol constants nodes must agree on, for a section to work.
+//!
+//! Builds of the node differing in one of these constants (or in an env var overriding one)
+//! disagree about fundamentals of the network, without any error until data goes missing. So
+//! each node digests the manifest of the constants it runs with: joining nodes present theirs,
+//! and each `SectionAuthorityProvider` records the one of the elders who made it. A node whose
+//! digest isn't its section's is refused joining, and a member which finds its own isn't its
+//! section's anymore, e.g. after an upgrade, takes no part in DKG nor proposals until it is.
+//!
+//! The digest of the default constants is pinned per release of this crate in
+//! `fixtures/protocol_constants`, so changing one of them can't go unnoticed.
+//!
+//! The elder count isn't one of them: it's a `NetworkParams`, which nodes take from their
+//! section instead.
+
+use crate::network_knowledge::MIN_ADULT_AGE;
+use crate::types::MAX_CHUNK_SIZE_IN_BYTES;
+use crate::{data_copy_count, DEFAULT_DATA_COPY_COUNT};
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Display, Formatter, Write};
+use tiny_keccak::{Hasher, Sha3};
+
+/// Size of the data joining nodes are challenged to prove their resources with.
+pub const RESOURCE_PROOF_DATA_SIZE: usize = 128;
+/// How hard the resource proof challenge to joining nodes is.
+pub const RESOURCE_PROOF_DIFFICULTY: u8 = 10;
+
+// Version of the manifest's layout, bumped when a constant is added to or removed from it.
+const MANIFEST_VERSION: u16 = 2;
+
+/// The SHA3-256 of a `ProtocolConstants` manifest.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ProtocolDigest(pub [u8; 32]);
+
+impl Display for ProtocolDigest {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl Debug for ProtocolDigest {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "ProtocolDigest({:.8})", hex::encode(self.0))
+    }
+}
+
+/// The consensus-critical constants of the protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolConstants {
+    /// Number of copies of a chunk.
+    pub data_copy_count: usize,
+    /// Max size of a chunk, in bytes.
+    pub max_chunk_size: usize,
+    /// The age at which a node becomes an adult.
+    pub min_adult_age: u8,
+    /// Size of the resource proof challenge data.
+    pub resource_proof_data_size: usize,
+    /// Difficulty of the resource proof challenge.
+    pub resource_proof_difficulty: u8,
+}
+
+impl Default for ProtocolConstants {
+    /// The constants compiled in, disregarding the env vars overriding some of them.
+    fn default() -> Self {
+        Self {
+            data_copy_count: DEFAULT_DATA_COPY_COUNT,
+            max_chunk_size: MAX_CHUNK_SIZE_IN_BYTES,
+            min_adult_age: MIN_ADULT_AGE,
+            resource_proof_data_size: RESOURCE_PROOF_DATA_SIZE,
+            resource_proof_difficulty: RESOURCE_PROOF_DIFFICULTY,
+        }
+    }
+}
+
+impl ProtocolConstants {
+    /// The constants this node runs with, including those overridden by env vars.
+    pub fn current() -> Self {
+        Self {
+            data_copy_count: data_copy_count(),
+            ..Self::default()
+        }
+    }
+
+    /// The canonical form of the manifest, which is digested: one `name=value` line per
+    /// constant, after the manifest version, in a fixed order.
+    pub fn canonical(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in [
+            ("manifest_version", MANIFEST_VERSION as u64),
+            ("data_copy_count", self.data_copy_count as u64),
+            ("max_chunk_size", self.max_chunk_size as u64),
+            ("min_adult_age", self.min_adult_age as u64),
+            (
+                "resource_proof_data_size",
+                self.resource_proof_data_size as u64,
+            ),
+            (
+                "resource_proof_difficulty",
+                self.resource_proof_difficulty as u64,
+            ),
+        ] {
+            let _ = writeln!(out, "{}={}", name, value);
+        }
+        out
+    }
+
+    /// The digest of the manifest.
+    pub fn digest(&self) -> ProtocolDigest {
+        let mut hasher = Sha3::v256();
+        hasher.update(self.canonical().as_bytes());
+        let mut digest = [0; 32];
+        hasher.finalize(&mut digest);
+        ProtocolDigest(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::{eyre, Result};
+    use std::{fs, path::PathBuf};
+
+    // Lines of `<crate version> <digest>`, one per release.
+    fn golden_file() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("protocol_constants")
+    }
+
+    #[test]
+    fn digest_of_the_default_constants_is_pinned_for_this_release() -> Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        let digest = ProtocolConstants::default().digest().to_string();
+        let goldens = fs::read_to_string(golden_file())?;
+        let pinned = goldens
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(release, _)| *release == version)
+            .map(|(_, digest)| digest.trim())
+            .ok_or_else(|| {
+                eyre!(
+                    "No protocol constants digest pinned for {}, add `{} {}` to {} \
+                    once the constants are final for the release",
+                    version,
+                    version,
+                    digest,
+                    golden_file().display()
+                )
+            })?;
+        assert_eq!(
+            pinned,
+            digest,
+            "The protocol constants changed within release {}:\n{}",
+            version,
+            ProtocolConstants::default().canonical()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn any_changed_constant_changes_the_digest() {
+        let default = ProtocolConstants::default();
+        let changes: [fn(&mut ProtocolConstants); 5] = [
+            |c| c.data_copy_count += 1,
+            |c| c.max_chunk_size += 1,
+            |c| c.min_adult_age += 1,
+            |c| c.resource_proof_data_size += 1,
+            |c| c.resource_proof_difficulty += 1,
+        ];
+        for change in changes {
+            let mut changed = default.clone();
+            change(&mut changed);
+            assert_ne!(changed.digest(), default.digest(), "{:?}", changed);
+        }
+    }
+}
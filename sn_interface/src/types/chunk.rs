@@ -7,12 +7,22 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{ChunkAddress, XorName};
+use crate::messaging::serialisation::shared_bytes;
 use bytes::Bytes;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
 
 /// Maximum allowed size for a serialised Chunk to grow to.
 pub const MAX_CHUNK_SIZE_IN_BYTES: usize = 1024 * 1024 + 10 * 1024;
 
+/// The address a chunk holding `value` is stored at.
+pub fn chunk_address(value: &[u8]) -> ChunkAddress {
+    ChunkAddress(XorName::from_content(value))
+}
+
 /// Chunk, an immutable chunk of data
 #[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, custom_debug::Debug)]
 pub struct Chunk {
@@ -28,7 +38,7 @@ impl Chunk {
     /// Creates a new instance of `Chunk`.
     pub fn new(value: Bytes) -> Self {
         Self {
-            address: ChunkAddress(XorName::from_content(value.as_ref())),
+            address: chunk_address(value.as_ref()),
             value,
         }
     }
@@ -73,11 +83,43 @@ impl Serialize for Chunk {
 
 impl<'de> Deserialize<'de> for Chunk {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let value = Deserialize::deserialize(deserializer)?;
+        let value = deserializer.deserialize_byte_buf(ValueVisitor)?;
         Ok(Self::new(value))
     }
 }
 
+// Deserialises the value of a chunk as a slice of the msg payload it's in, when it's borrowed
+// from one, rather than copying it.
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("the bytes of a chunk")
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, value: &'de [u8]) -> Result<Bytes, E> {
+        Ok(shared_bytes(value))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(value))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Bytes, E> {
+        Ok(Bytes::from(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+        let mut value = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            value.push(byte);
+        }
+        Ok(Bytes::from(value))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::types::{utils, Chunk, Error, Result};
@@ -0,0 +1,49 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// A share of a chunk erasure coded across adults, rather than replicated in full to each of
+/// them. Any `data_shares` of the `data_shares + parity_shares` shares of a chunk rebuild it.
+#[derive(
+    Hash, Eq, PartialEq, PartialOrd, Ord, Clone, custom_debug::Debug, Serialize, Deserialize,
+)]
+pub struct ChunkShare {
+    /// Name of the chunk.
+    pub chunk: XorName,
+    /// Index of the share: the first `data_shares` hold the content of the chunk, the others
+    /// parity.
+    pub index: u8,
+    /// Number of shares holding the content of the chunk.
+    pub data_shares: u8,
+    /// Number of shares holding parity.
+    pub parity_shares: u8,
+    /// Length of the content of the chunk, which the last data share is padded past.
+    pub chunk_len: u32,
+    /// Content of the share.
+    #[debug(skip)]
+    pub bytes: Bytes,
+}
+
+impl ChunkShare {
+    /// Total number of shares of the chunk.
+    pub fn total_shares(&self) -> usize {
+        usize::from(self.data_shares) + usize::from(self.parity_shares)
+    }
+
+    /// Whether the share is of the same coding, and so can be combined with, `other`.
+    pub fn matches(&self, other: &Self) -> bool {
+        self.chunk == other.chunk
+            && self.data_shares == other.data_shares
+            && self.parity_shares == other.parity_shares
+            && self.chunk_len == other.chunk_len
+            && self.bytes.len() == other.bytes.len()
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet};
+use tiny_keccak::{Hasher, Sha3};
+use xor_name::XorName;
+
+/// Share of the data each name among the closest to it is assigned, from 0 to 1, for names
+/// warming up. Names without a weight are of full weight.
+pub type Weights = BTreeMap<XorName, f64>;
+
+/// The `count` names closest to `target`, found by sorting all of them by their distance to it.
+pub fn holders_for(target: &XorName, names: &BTreeSet<XorName>, count: usize) -> BTreeSet<XorName> {
+    names
+        .iter()
+        .sorted_by(|lhs, rhs| target.cmp_distance(lhs, rhs))
+        .take(count)
+        .copied()
+        .collect()
+}
+
+/// The `count` names closest to `target`, as `holders_for`, but found through an index of them.
+pub fn holders_for_indexed(
+    target: &XorName,
+    index: &ClosenessIndex,
+    count: usize,
+) -> BTreeSet<XorName> {
+    index.closest(target).take(count).copied().collect()
+}
+
+/// The holders of `target` among `names`, as `holders_for`, but with the names warming up
+/// holding only their share of the data, as `weighted_holders`.
+pub fn weighted_holders_for(
+    target: &XorName,
+    names: &BTreeSet<XorName>,
+    count: usize,
+    weights: &Weights,
+) -> BTreeSet<XorName> {
+    weighted_holders(
+        target,
+        names
+            .iter()
+            .sorted_by(|lhs, rhs| target.cmp_distance(lhs, rhs)),
+        count,
+        weights,
+    )
+}
+
+/// The holders of `target`, from the `closest` names to it, in order of distance: the `count`
+/// closest names of full weight, along with the names warming up which are closer than the
+/// farthest of them and `target` is assigned to, as per `is_assigned`.
+///
+/// So the names of full weight always hold `count` copies, as long as there are enough of
+/// them. Should there be too few, the closest names warming up make up for them.
+pub fn weighted_holders<'a>(
+    target: &XorName,
+    closest: impl IntoIterator<Item = &'a XorName>,
+    count: usize,
+    weights: &Weights,
+) -> BTreeSet<XorName> {
+    let mut holders = BTreeSet::new();
+    let mut full = 0;
+    let mut passed_over = vec![];
+    for name in closest {
+        if full == count {
+            break;
+        }
+        match weights.get(name) {
+            Some(weight) if *weight < 1.0 => {
+                if is_assigned(target, name, *weight) {
+                    let _new = holders.insert(*name);
+                } else {
+                    passed_over.push(*name);
+                }
+            }
+            _ => {
+                let _new = holders.insert(*name);
+                full += 1;
+            }
+        }
+    }
+    holders.extend(passed_over.into_iter().take(count.saturating_sub(full)));
+    holders
+}
+
+/// Whether data named `target` is assigned to `name`, among the closest to it, with `name`
+/// being of `weight`. The same wherever it's computed, and true for a `weight` share of all
+/// the data.
+pub fn is_assigned(target: &XorName, name: &XorName, weight: f64) -> bool {
+    let mut hasher = Sha3::v256();
+    hasher.update(&target.0);
+    hasher.update(&name.0);
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    let mut draw = [0; 8];
+    draw.copy_from_slice(&hash[..8]);
+    (u64::from_le_bytes(draw) as f64) < weight.clamp(0.0, 1.0) * u64::MAX as f64
+}
+
+/// Names kept in order, for those closest to any name to be found without sorting them all
+/// by their distance to it.
+///
+/// The names of a subtree of the XOR space are contiguous in order, so the names closest to a
+/// target are those of the subtree sharing the longest prefix with it, then those of its
+/// sibling subtree, and so on up. Walking the subtrees costs a binary search per level, only
+/// as deep as it takes for names to be told apart.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClosenessIndex {
+    // Sorted, without duplicates.
+    names: Vec<XorName>,
+}
+
+impl ClosenessIndex {
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn contains(&self, name: &XorName) -> bool {
+        self.names.binary_search(name).is_ok()
+    }
+
+    /// Adds a name, returning whether it wasn't in already.
+    pub fn insert(&mut self, name: XorName) -> bool {
+        match self.names.binary_search(&name) {
+            Ok(_) => false,
+            Err(at) => {
+                self.names.insert(at, name);
+                true
+            }
+        }
+    }
+
+    /// Removes a name, returning whether it was in.
+    pub fn remove(&mut self, name: &XorName) -> bool {
+        match self.names.binary_search(name) {
+            Ok(at) => {
+                let _name = self.names.remove(at);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Iterates over the names from the closest to `target` to the farthest, lazily.
+    pub fn closest<'a>(&'a self, target: &XorName) -> Closest<'a> {
+        let mut subtrees = Vec::new();
+        if !self.names.is_empty() {
+            subtrees.push((self.names.as_slice(), 0));
+        }
+        Closest {
+            target: *target,
+            subtrees,
+            leaf: [].iter(),
+        }
+    }
+}
+
+impl FromIterator<XorName> for ClosenessIndex {
+    fn from_iter<I: IntoIterator<Item = XorName>>(names: I) -> Self {
+        let names = names.into_iter().collect::<BTreeSet<_>>();
+        Self {
+            names: names.into_iter().collect(),
+        }
+    }
+}
+
+/// Iterator over the names of a `ClosenessIndex`, by increasing distance to a target.
+pub struct Closest<'a> {
+    target: XorName,
+    // Subtrees still to visit, with the depth they're split at, the closest last.
+    subtrees: Vec<(&'a [XorName], usize)>,
+    leaf: std::slice::Iter<'a, XorName>,
+}
+
+impl<'a> Iterator for Closest<'a> {
+    type Item = &'a XorName;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(name) = self.leaf.next() {
+                return Some(name);
+            }
+            let (names, depth) = self.subtrees.pop()?;
+            // All names of a subtree share its first `depth` bits, so whichever is left at the
+            // last bit is the only one.
+            if names.len() == 1 || depth == XorName::default().0.len() * 8 {
+                self.leaf = names.iter();
+                continue;
+            }
+            let bit = depth as u8;
+            let split = names.partition_point(|name| !name.bit(bit));
+            let (zeros, ones) = names.split_at(split);
+            let (near, far) = if self.target.bit(bit) {
+                (ones, zeros)
+            } else {
+                (zeros, ones)
+            };
+            for subtree in [far, near] {
+                if !subtree.is_empty() {
+                    self.subtrees.push((subtree, depth + 1));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::btree_set, prelude::*};
+
+    #[test]
+    fn names_are_assigned_their_weight_of_the_data() {
+        let name: XorName = xor_name::rand::random();
+        let targets = (0..10_000)
+            .map(|_| xor_name::rand::random())
+            .collect::<Vec<XorName>>();
+        for weight in [0.0, 0.25, 0.5, 1.0] {
+            let assigned = targets
+                .iter()
+                .filter(|target| is_assigned(target, &name, weight))
+                .count();
+            let share = assigned as f64 / targets.len() as f64;
+            assert!((share - weight).abs() < 0.03, "{} for {}", share, weight);
+        }
+    }
+
+    fn name() -> impl Strategy<Value = XorName> {
+        any::<[u8; 32]>().prop_map(XorName)
+    }
+
+    // Names sharing long prefixes with each other, as those of a section do.
+    fn clustered_name() -> impl Strategy<Value = XorName> {
+        (0..4u8, any::<[u8; 32]>()).prop_map(|(first, mut bytes)| {
+            bytes[0] = first;
+            XorName(bytes)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_indexed_holders_match_sorted_ones(
+            names in btree_set(name(), 0..300),
+            target in name(),
+            count in 0..10usize,
+        ) {
+            let index = names.iter().copied().collect::<ClosenessIndex>();
+            prop_assert_eq!(
+                holders_for_indexed(&target, &index, count),
+                holders_for(&target, &names, count)
+            );
+        }
+
+        #[test]
+        fn proptest_index_yields_all_names_by_distance(
+            names in btree_set(clustered_name(), 0..100),
+            target in clustered_name(),
+        ) {
+            let index = names.iter().copied().collect::<ClosenessIndex>();
+            let sorted = names
+                .iter()
+                .sorted_by(|lhs, rhs| target.cmp_distance(lhs, rhs))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(index.closest(&target).collect::<Vec<_>>(), sorted);
+        }
+
+        #[test]
+        fn proptest_weighted_holders_keep_the_count_of_full_weight(
+            names in btree_set(name(), 0..100),
+            warming_up in btree_set(name(), 0..20),
+            target in name(),
+            weight in 0.0..1.0f64,
+            count in 0..10usize,
+        ) {
+            let weights = warming_up.iter().map(|name| (*name, weight)).collect::<Weights>();
+            let all = names.union(&warming_up).copied().collect::<BTreeSet<_>>();
+            let holders = weighted_holders_for(&target, &all, count, &weights);
+
+            // Without any weight, holders are the closest as ever.
+            prop_assert_eq!(
+                weighted_holders_for(&target, &all, count, &Weights::new()),
+                holders_for(&target, &all, count)
+            );
+            // Holders of full weight are the closest of full weight, and those warming up are
+            // only among the closest, unless they make up for too few of full weight.
+            let full = all
+                .iter()
+                .filter(|name| !weights.contains_key(name))
+                .copied()
+                .collect::<BTreeSet<_>>();
+            let full_holders = holders_for(&target, &full, count);
+            prop_assert_eq!(
+                holders.iter().filter(|name| full.contains(name)).copied().collect::<BTreeSet<_>>(),
+                full_holders.clone()
+            );
+            prop_assert!(holders.len() >= count.min(all.len()));
+            if full_holders.len() == count {
+                for name in holders.difference(&full_holders) {
+                    prop_assert!(is_assigned(&target, name, weight));
+                }
+            }
+        }
+
+        #[test]
+        fn proptest_index_updates_match_a_rebuild(
+            names in btree_set(name(), 0..50),
+            joined in btree_set(name(), 0..10),
+            target in name(),
+        ) {
+            let mut index = names.iter().copied().collect::<ClosenessIndex>();
+            let mut members = names.clone();
+            for name in &joined {
+                prop_assert_eq!(index.insert(*name), members.insert(*name));
+            }
+            for name in names.iter().step_by(2) {
+                prop_assert!(index.remove(name));
+                prop_assert!(members.remove(name));
+            }
+            prop_assert_eq!(&index, &members.iter().copied().collect());
+            prop_assert_eq!(
+                holders_for_indexed(&target, &index, 4),
+                holders_for(&target, &members, 4)
+            );
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Deterministic derivation of keypairs from a BIP-39 mnemonic.
+//!
+//! A mnemonic, and an optional BIP-39 passphrase, give a seed from which keys are derived down a
+//! path of hardened indices, e.g. `m/1'/0'`, as per SLIP-0010. Ed25519 keys are SLIP-0010's own.
+//! BLS keys are derived the same way from a master key of their own, the top two bits of the key
+//! at the path being cleared for it to be a BLS12-381 scalar.
+//!
+//! Backing up the mnemonic is then enough to recreate a node's or a client's keys anywhere.
+
+use super::super::{Error, Result};
+use super::ed25519::name;
+
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+use xor_name::{XorName, XOR_NAME_LEN};
+
+// Keys of the HMAC deriving the master key of each scheme from the seed.
+const ED25519_DOMAIN: &[u8] = b"ed25519 seed";
+const BLS_DOMAIN: &[u8] = b"bls12-381 seed";
+// Hardened indices have this bit set.
+const HARDENED: u32 = 1 << 31;
+
+// Paths of the keys of a node, and of clients.
+const NODE_KEYPAIRS_PATH: [u32; 1] = [0];
+const REWARD_KEYPAIR_PATH: [u32; 2] = [1, 0];
+const CLIENT_KEYPAIRS_PATH: [u32; 1] = [2];
+
+/// Path of hardened indices down which a key is derived from a seed, written `m/1'/0'`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Path of the keypairs a node joins the network with, the first of its children naming the
+    /// node as required being used.
+    pub fn node() -> Self {
+        Self(NODE_KEYPAIRS_PATH.to_vec())
+    }
+
+    /// Path of a node's reward keypair.
+    pub fn reward() -> Self {
+        Self(REWARD_KEYPAIR_PATH.to_vec())
+    }
+
+    /// Path of the `index`-th keypair of a client.
+    pub fn client(index: u32) -> Self {
+        Self(CLIENT_KEYPAIRS_PATH.to_vec()).child(index)
+    }
+
+    /// Path of the `index`-th child of this path.
+    pub fn child(&self, index: u32) -> Self {
+        let mut indices = self.0.clone();
+        indices.push(index & !HARDENED);
+        Self(indices)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let mut components = path.trim().split('/');
+        if components.next() != Some("m") {
+            return Err(Error::FailedToParse(format!(
+                "Derivation path '{}' doesn't start with 'm'",
+                path
+            )));
+        }
+        let indices = components
+            .map(|component| {
+                component
+                    .strip_suffix('\'')
+                    .and_then(|index| index.parse::<u32>().ok())
+                    .filter(|index| index & HARDENED == 0)
+                    .ok_or_else(|| {
+                        Error::FailedToParse(format!(
+                            "Derivation path '{}' has '{}', not a hardened index like 0'",
+                            path, component
+                        ))
+                    })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(indices))
+    }
+}
+
+impl Display for DerivationPath {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.0 {
+            write!(f, "/{}'", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Seed of a BIP-39 mnemonic, keys being derived from it.
+#[derive(Clone)]
+pub struct KeySeed([u8; 64]);
+
+impl KeySeed {
+    /// The seed of `mnemonic`, with BIP-39 `passphrase` (empty for none).
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(mnemonic)
+            .map_err(|err| Error::FailedToParse(format!("Invalid mnemonic: {}", err)))?;
+        Ok(Self(mnemonic.to_seed(passphrase)))
+    }
+
+    /// Generates a new random mnemonic, of 24 words for 256 bits of entropy.
+    pub fn generate_mnemonic() -> Result<String> {
+        let entropy: [u8; 32] = rand::random();
+        Mnemonic::from_entropy(&entropy)
+            .map(|mnemonic| mnemonic.to_string())
+            .map_err(|err| Error::FailedToParse(format!("Invalid mnemonic entropy: {}", err)))
+    }
+
+    /// Derives the Ed25519 keypair at `path`.
+    pub fn ed25519_keypair(&self, path: &DerivationPath) -> ed25519_dalek::Keypair {
+        let secret_bytes = derive(&self.0, ED25519_DOMAIN, path);
+        // A secret key is any 32 bytes.
+        let secret = match ed25519_dalek::SecretKey::from_bytes(&secret_bytes) {
+            Ok(secret) => secret,
+            Err(_) => unreachable!("an Ed25519 secret key is any 32 bytes"),
+        };
+        ed25519_dalek::Keypair {
+            public: (&secret).into(),
+            secret,
+        }
+    }
+
+    /// Derives the BLS secret key at `path`.
+    pub fn bls_secret_key(&self, path: &DerivationPath) -> Result<bls::SecretKey> {
+        let mut secret_bytes = derive(&self.0, BLS_DOMAIN, path);
+        // Cleared, the key is below the order of the BLS12-381 scalar field.
+        secret_bytes[0] &= 0x3f;
+        bls::SecretKey::from_bytes(secret_bytes)
+            .map_err(|err| Error::FailedToParse(format!("Couldn't derive BLS secret key: {}", err)))
+    }
+
+    /// Derives the first keypair under `DerivationPath::node()` whose name is in `range` and
+    /// of `age`, as `ed25519::gen_keypair` generates random ones.
+    pub fn node_keypair(&self, range: &RangeInclusive<XorName>, age: u8) -> ed25519_dalek::Keypair {
+        let parent = DerivationPath::node();
+        let mut index = 0;
+        loop {
+            let keypair = self.ed25519_keypair(&parent.child(index));
+            let new_name = name(&keypair.public);
+            if range.contains(&new_name) && age == new_name[XOR_NAME_LEN - 1] {
+                return keypair;
+            }
+            index += 1;
+        }
+    }
+}
+
+impl Debug for KeySeed {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "KeySeed(<redacted>)")
+    }
+}
+
+// The 32 bytes of the key at `path` from the master key of `domain`, as per SLIP-0010.
+fn derive(seed: &[u8], domain: &[u8], path: &DerivationPath) -> [u8; 32] {
+    let mut node = hmac_sha512(domain, &[seed]);
+    for index in &path.0 {
+        let (key, chain_code) = node.split_at(32);
+        node = hmac_sha512(chain_code, &[&[0], key, &(index | HARDENED).to_be_bytes()]);
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(&node[..32]);
+    key
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    let mut mac = match Hmac::<Sha512>::new_from_slice(key) {
+        Ok(mac) => mac,
+        Err(_) => unreachable!("HMAC takes keys of any length"),
+    };
+    for data in data {
+        mac.update(data);
+    }
+    let mut output = [0; 64];
+    output.copy_from_slice(&mac.finalize().into_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use xor_name::Prefix;
+
+    // From the BIP-39 test vectors, with passphrase "TREZOR".
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon about";
+
+    #[test]
+    fn ed25519_keys_are_slip10s() -> Result<()> {
+        // SLIP-0010 test vector 1 for Ed25519.
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f")?;
+        let key_at = |path: &str| -> Result<String> {
+            Ok(hex::encode(derive(&seed, ED25519_DOMAIN, &path.parse()?)))
+        };
+        assert_eq!(
+            key_at("m")?,
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            key_at("m/0'/1'")?,
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keys_are_derived_deterministically() -> Result<()> {
+        let seed = KeySeed::from_mnemonic(MNEMONIC, "TREZOR")?;
+        assert_eq!(
+            hex::encode(seed.0),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+            1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+
+        let again = KeySeed::from_mnemonic(MNEMONIC, "TREZOR")?;
+        let path = DerivationPath::client(0);
+        assert_eq!(
+            seed.ed25519_keypair(&path).to_bytes(),
+            again.ed25519_keypair(&path).to_bytes()
+        );
+        assert_ne!(
+            seed.ed25519_keypair(&path).public,
+            seed.ed25519_keypair(&DerivationPath::client(1)).public
+        );
+        assert_eq!(seed.bls_secret_key(&path)?, again.bls_secret_key(&path)?);
+
+        // Another passphrase is another seed.
+        let other = KeySeed::from_mnemonic(MNEMONIC, "")?;
+        assert_ne!(
+            seed.ed25519_keypair(&path).public,
+            other.ed25519_keypair(&path).public
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn node_keypair_is_named_as_required() -> Result<()> {
+        let seed = KeySeed::from_mnemonic(&KeySeed::generate_mnemonic()?, "")?;
+        let range = Prefix::default().range_inclusive();
+        let keypair = seed.node_keypair(&range, 5);
+        assert_eq!(name(&keypair.public)[XOR_NAME_LEN - 1], 5);
+        assert_eq!(seed.node_keypair(&range, 5).public, keypair.public);
+        Ok(())
+    }
+
+    #[test]
+    fn derivation_paths_parse_back() -> Result<()> {
+        let path = DerivationPath::client(7);
+        assert_eq!(path.to_string(), "m/2'/7'");
+        assert_eq!(path.to_string().parse::<DerivationPath>()?, path);
+        assert!("m/2/7'".parse::<DerivationPath>().is_err());
+        assert!("2'/7'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648'".parse::<DerivationPath>().is_err());
+        assert!(KeySeed::from_mnemonic("abandon abandon", "").is_err());
+        Ok(())
+    }
+}
@@ -54,13 +54,10 @@ impl OwnerType {
             Ok(data) => data,
         };
         match signature {
-            Signature::Bls(sig) => {
-                if let OwnerType::Multi(set) = self {
-                    set.public_key().verify(sig, data)
-                } else {
-                    false
-                }
-            }
+            Signature::Bls(sig) => match self {
+                OwnerType::Multi(set) => set.public_key().verify(sig, data),
+                OwnerType::Single(key) => key.verify(signature, data).is_ok(),
+            },
             ed @ Signature::Ed25519(_) => self.public_key().verify(ed, data).is_ok(),
             Signature::BlsShare(share) => {
                 if let OwnerType::Multi(set) = self {
@@ -99,6 +96,7 @@ impl Signing for Keypair {
         match self {
             Keypair::Ed25519(pair) => OwnerType::Single(PublicKey::Ed25519(pair.public)),
             Keypair::BlsShare(share) => OwnerType::Multi(share.public_key_set.clone()),
+            Keypair::Bls(keypair) => OwnerType::Single(PublicKey::Bls(keypair.public)),
         }
     }
 
@@ -119,6 +117,8 @@ pub enum Keypair {
     Ed25519(#[debug(skip)] Arc<ed25519_dalek::Keypair>),
     /// BLS keypair share.
     BlsShare(Arc<BlsKeypairShare>),
+    /// BLS keypair.
+    Bls(Arc<BlsKeypair>),
 }
 
 // Need to manually implement this due to a missing impl in `Ed25519::Keypair`.
@@ -130,6 +130,7 @@ impl PartialEq for Keypair {
                 keypair.to_bytes().to_vec() == other_keypair.to_bytes().to_vec()
             }
             (Self::BlsShare(keypair), Self::BlsShare(other_keypair)) => keypair == other_keypair,
+            (Self::Bls(keypair), Self::Bls(other_keypair)) => keypair == other_keypair,
             _ => false,
         }
     }
@@ -160,11 +161,17 @@ impl Keypair {
         }))
     }
 
+    /// Constructs a random BLS keypair.
+    pub fn new_bls() -> Self {
+        Self::from(bls::SecretKey::random())
+    }
+
     /// Returns the public key associated with this keypair.
     pub fn public_key(&self) -> PublicKey {
         match self {
             Self::Ed25519(keypair) => PublicKey::Ed25519(keypair.public),
             Self::BlsShare(keypair) => PublicKey::BlsShare(keypair.public),
+            Self::Bls(keypair) => PublicKey::Bls(keypair.public),
         }
     }
 
@@ -181,6 +188,7 @@ impl Keypair {
                 }
             }
             Self::BlsShare(keypair) => Ok(SecretKey::BlsShare(keypair.secret.clone())),
+            Self::Bls(keypair) => Ok(SecretKey::Bls(keypair.secret.clone())),
         }
     }
 
@@ -192,6 +200,7 @@ impl Keypair {
                 index: keypair.index,
                 share: keypair.secret.sign(data),
             }),
+            Self::Bls(keypair) => Signature::Bls(keypair.secret.sign(data)),
         }
     }
 }
@@ -207,6 +216,25 @@ impl From<ed25519_dalek::SecretKey> for Keypair {
     }
 }
 
+impl From<bls::SecretKey> for Keypair {
+    fn from(secret: bls::SecretKey) -> Self {
+        Self::Bls(Arc::new(BlsKeypair {
+            public: secret.public_key(),
+            secret: SerdeSecret(secret),
+        }))
+    }
+}
+
+/// BLS keypair.
+#[derive(Clone, PartialEq, Serialize, Deserialize, custom_debug::Debug)]
+pub struct BlsKeypair {
+    /// Secret key.
+    #[debug(skip)]
+    pub secret: SerdeSecret<bls::SecretKey>,
+    /// Public key.
+    pub public: bls::PublicKey,
+}
+
 /// BLS keypair share.
 #[derive(Clone, PartialEq, Serialize, Deserialize, custom_debug::Debug)]
 pub struct BlsKeypairShare {
@@ -236,9 +264,25 @@ mod tests {
                 bls_secret_key.secret_key_share(0),
                 bls_secret_key.public_keys(),
             ),
+            Keypair::new_bls(),
         ]
     }
 
+    // A full BLS keypair owns data singly, its signatures verifying against its public key.
+    #[test]
+    fn bls_keypair_signs_as_single_owner() -> Result<()> {
+        let keypair = Keypair::new_bls();
+        let data = "section-signed data".to_string();
+        let signature = Signing::sign(&keypair, &data)?;
+
+        assert!(matches!(signature, Signature::Bls(_)));
+        assert_eq!(keypair.id(), OwnerType::Single(keypair.public_key()));
+        assert!(Signing::verify(&keypair, &signature, &data));
+        assert!(!Signing::verify(&Keypair::new_bls(), &signature, &data));
+        assert_eq!(keypair.secret_key()?.public_key(), keypair.public_key());
+        Ok(())
+    }
+
     // Test serialising and deserialising key pairs.
     #[test]
     fn serialisation_key_pair() -> Result<()> {
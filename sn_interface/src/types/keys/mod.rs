@@ -12,6 +12,7 @@
 //! `new` functions. A `PublicKey` can't be generated by itself; it must always be derived from a
 //! secret key.
 
+pub(super) mod derivation;
 pub mod ed25519;
 pub(super) mod keypair;
 pub(super) mod node_keypairs;
@@ -255,6 +255,7 @@ pub mod tests {
                 bls_secret_key.secret_key_share(0),
                 bls_secret_key.public_keys(),
             ),
+            Keypair::new_bls(),
         ]
     }
 
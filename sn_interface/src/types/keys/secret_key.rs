@@ -12,7 +12,7 @@
 //! `new` functions. A `PublicKey` can't be generated by itself; it must always be derived from a
 //! secret key.
 
-use super::super::{Error, Result};
+use super::super::{Error, PublicKey, Result};
 use bls::{self, serde_impl::SerdeSecret};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug, Display, Formatter};
@@ -25,6 +25,8 @@ pub enum SecretKey {
     Ed25519(ed25519_dalek::SecretKey),
     /// BLS secretkey share.
     BlsShare(SerdeSecret<bls::SecretKeyShare>),
+    /// BLS secretkey.
+    Bls(SerdeSecret<bls::SecretKey>),
 }
 
 impl SecretKey {
@@ -49,6 +51,51 @@ impl SecretKey {
         })?;
         Ok(Self::Ed25519(ed25519_sk))
     }
+
+    /// Construct a BLS secret key from a hex string
+    ///
+    /// The counterpart of `to_hex`, for apps holding
+    /// full BLS keys, e.g. owning section-signed data
+    pub fn bls_from_hex(hex: &str) -> Result<Self> {
+        let bytes = hex::decode(hex).map_err(|err| {
+            Error::FailedToParse(format!(
+                "Couldn't parse BLS secret key bytes from hex: {}",
+                err
+            ))
+        })?;
+        let bytes_fixed_len: [u8; bls::SK_SIZE] = bytes.as_slice().try_into().map_err(|_| {
+            Error::FailedToParse(format!(
+                "Couldn't parse BLS secret key bytes from hex. The provided string must \
+                represent exactly {} bytes.",
+                bls::SK_SIZE
+            ))
+        })?;
+        let bls_sk = bls::SecretKey::from_bytes(bytes_fixed_len).map_err(|err| {
+            Error::FailedToParse(format!(
+                "Couldn't parse BLS secret key from fixed-length byte array: {}",
+                err
+            ))
+        })?;
+        Ok(Self::Bls(SerdeSecret(bls_sk)))
+    }
+
+    /// Returns the hex encoding of the bytes of the underlying secret key
+    pub fn to_hex(&self) -> String {
+        match self {
+            Self::Ed25519(sk) => hex::encode(sk.to_bytes()),
+            Self::BlsShare(sk) => hex::encode(sk.inner().to_bytes()),
+            Self::Bls(sk) => hex::encode(sk.inner().to_bytes()),
+        }
+    }
+
+    /// Returns the public key of the underlying secret key
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            Self::Ed25519(sk) => PublicKey::Ed25519(sk.into()),
+            Self::BlsShare(sk) => PublicKey::BlsShare(sk.inner().public_key_share()),
+            Self::Bls(sk) => PublicKey::Bls(sk.inner().public_key()),
+        }
+    }
 }
 
 impl Display for SecretKey {
@@ -57,16 +104,38 @@ impl Display for SecretKey {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_keys_to_and_from_hex() -> Result<()> {
+        let bls_sk = bls::SecretKey::random();
+        let sk = SecretKey::Bls(SerdeSecret(bls_sk.clone()));
+        let hex = sk.to_hex();
+        assert_eq!(hex, hex::encode(bls_sk.to_bytes()));
+
+        let parsed = SecretKey::bls_from_hex(&hex)?;
+        assert_eq!(parsed.to_hex(), hex);
+        assert_eq!(parsed.public_key(), PublicKey::Bls(bls_sk.public_key()));
+
+        let ed_hex = hex::encode([7u8; ed25519_dalek::SECRET_KEY_LENGTH]);
+        assert_eq!(SecretKey::ed25519_from_hex(&ed_hex)?.to_hex(), ed_hex);
+
+        assert!(matches!(
+            SecretKey::bls_from_hex(&hex[2..]),
+            Err(Error::FailedToParse(_))
+        ));
+        Ok(())
+    }
+}
+
 #[cfg(feature = "test-utils")]
 pub mod test_utils {
     use crate::messaging::system::KeyedSig;
-    use crate::network_knowledge::{elder_count, supermajority};
+    use crate::network_knowledge::NetworkParams;
     use std::ops::Deref;
 
-    fn threshold() -> usize {
-        supermajority(elder_count()) - 1
-    }
-
     // Wrapper for `bls::SecretKeySet` that also allows to retrieve the corresponding `bls::SecretKey`.
     // Note: `bls::SecretKeySet` does have a `secret_key` method, but it's test-only and not available
     // for the consumers of the crate.
@@ -77,7 +146,13 @@ pub mod test_utils {
 
     impl SecretKeySet {
         pub fn random() -> Self {
-            let poly = bls::poly::Poly::random(threshold(), &mut rand::thread_rng());
+            Self::random_with_params(NetworkParams::from_env())
+        }
+
+        /// A key set for a section of the full elder count of a network of `params`.
+        pub fn random_with_params(params: NetworkParams) -> Self {
+            let threshold = params.threshold(params.elder_count());
+            let poly = bls::poly::Poly::random(threshold, &mut rand::thread_rng());
             let key = bls::SecretKey::from_mut(&mut poly.evaluate(0));
             let set = bls::SecretKeySet::from(poly);
 
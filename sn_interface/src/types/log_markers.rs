@@ -71,6 +71,8 @@ pub enum LogMarker {
     DkgMessageHandling,
     DkgSessionComplete,
     DkgSessionAlreadyCompleted,
+    DkgSessionRestarted,
+    DkgSessionStalled,
     HandlingDkgSuccessfulOutcome,
     HandlingNewEldersAgreement,
     NewSignedSap,
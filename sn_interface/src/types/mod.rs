@@ -22,6 +22,8 @@ pub mod utils;
 mod address;
 mod cache;
 mod chunk;
+mod chunk_share;
+mod closeness;
 mod errors;
 mod peer;
 mod token;
@@ -34,10 +36,16 @@ pub use address::{
     SafeKeyAddress, Scope,
 };
 pub use cache::Cache;
-pub use chunk::{Chunk, MAX_CHUNK_SIZE_IN_BYTES};
+pub use chunk::{chunk_address, Chunk, MAX_CHUNK_SIZE_IN_BYTES};
+pub use chunk_share::ChunkShare;
+pub use closeness::{
+    holders_for, holders_for_indexed, is_assigned, weighted_holders, weighted_holders_for,
+    ClosenessIndex, Closest, Weights,
+};
 pub use errors::{convert_dt_error_to_error_msg, Error, Result};
 pub use keys::{
-    keypair::{BlsKeypairShare, Encryption, Keypair, OwnerType, Signing},
+    derivation::{DerivationPath, KeySeed},
+    keypair::{BlsKeypair, BlsKeypairShare, Encryption, Keypair, OwnerType, Signing},
     node_keypairs::NodeKeypairs,
     public_key::PublicKey,
     secret_key::SecretKey,
@@ -30,6 +30,15 @@ use xor_name::XorName;
 /// Arbitrary maximum size of a register entry.
 const MAX_REG_ENTRY_SIZE: usize = MIN_ENCRYPTABLE_BYTES / 3; // 1024 bytes
 
+/// Checks `entry` is no larger than register entries may be.
+pub fn check_entry_size(entry: &[u8]) -> Result<()> {
+    let size = entry.len();
+    if size > MAX_REG_ENTRY_SIZE {
+        return Err(Error::EntryTooBig(size, MAX_REG_ENTRY_SIZE));
+    }
+    Ok(())
+}
+
 /// Register mutation operation to apply to Register.
 pub type RegisterOp<T> = CrdtOperation<T>;
 
@@ -173,6 +182,14 @@ impl Register {
         self.crdt.read()
     }
 
+    /// Read every entry ever written, oldest first, the position of each being its version.
+    ///
+    /// Each entry comes after those it points at, and entries written concurrently come in the
+    /// order of their hashes, so replicas holding the same entries agree on their versions.
+    pub fn history(&self) -> Vec<(EntryHash, Entry)> {
+        self.crdt.history()
+    }
+
     /// Return user permissions, if applicable.
     pub fn permissions(&self, user: User) -> Result<Permissions> {
         self.policy.permissions(user).ok_or(Error::NoSuchEntry)
@@ -196,10 +213,7 @@ impl Register {
         entry: Entry,
         children: BTreeSet<EntryHash>,
     ) -> Result<(EntryHash, RegisterOp<Entry>)> {
-        let size = entry.len();
-        if size > MAX_REG_ENTRY_SIZE {
-            return Err(Error::EntryTooBig(size, MAX_REG_ENTRY_SIZE));
-        }
+        check_entry_size(&entry)?;
         if self.crdt.size() >= self.cap() as u64 {
             return Err(Error::TooManyEntries(self.crdt.size() as usize));
         }
@@ -207,8 +221,10 @@ impl Register {
         self.crdt.write(entry, children, self.authority)
     }
 
-    /// Apply a signed data CRDT operation.
+    /// Apply a signed data CRDT operation. Its entry is held to the same bound as those written
+    /// locally, for replicas not to take anything larger than writers would.
     pub fn apply_op(&mut self, op: RegisterOp<Entry>) -> Result<()> {
+        check_entry_size(&op.crdt_op.value)?;
         self.crdt.apply_op(op)
     }
 
@@ -233,6 +249,7 @@ mod tests {
         },
         utils, Error, Keypair, Result,
     };
+    use super::MAX_REG_ENTRY_SIZE;
     use crate::{types::RegisterAddress as Address, types::Scope};
     use proptest::prelude::*;
     use rand_07::{rngs::OsRng, seq::SliceRandom, thread_rng, Rng};
@@ -385,6 +402,63 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn register_history_is_ordered_alike_on_replicas() -> eyre::Result<()> {
+        let mut replicas = create_public_reg_replicas(2);
+        let (_, mut replica2) = replicas.pop().ok_or_else(|| eyre::eyre!("no replica"))?;
+        let (keypair, mut replica1) = replicas.pop().ok_or_else(|| eyre::eyre!("no replica"))?;
+
+        // Two concurrent entries, then one merging them.
+        let (hash1, op1) = replica1.write(random_register_entry(), BTreeSet::new())?;
+        let (hash2, op2) = replica1.write(random_register_entry(), BTreeSet::new())?;
+        let children = BTreeSet::from([hash1, hash2]);
+        let (hash3, op3) = replica1.write(random_register_entry(), children)?;
+
+        // Applied in another order on the other replica.
+        for op in [op2, op1, op3] {
+            replica2.apply_op(sign_register_op(op, &keypair)?)?;
+        }
+
+        let history = replica1.history();
+        assert_eq!(history, replica2.history());
+        let hashes: Vec<_> = history.iter().map(|(hash, _)| *hash).collect();
+        assert_eq!(hashes, vec![hash1.min(hash2), hash1.max(hash2), hash3],);
+        assert_eq!(history[2].1, *replica1.get(hash3)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn register_ops_of_entries_too_big_are_not_applied() -> eyre::Result<()> {
+        let mut replicas = create_public_reg_replicas(2);
+        let (_, mut replica2) = replicas.pop().ok_or_else(|| eyre::eyre!("no replica"))?;
+        let (keypair, mut replica1) = replicas.pop().ok_or_else(|| eyre::eyre!("no replica"))?;
+
+        let too_big = vec![0; MAX_REG_ENTRY_SIZE + 1];
+        assert_eq!(
+            replica1.write(too_big.clone(), BTreeSet::new()),
+            Err(Error::EntryTooBig(
+                MAX_REG_ENTRY_SIZE + 1,
+                MAX_REG_ENTRY_SIZE
+            ))
+        );
+
+        // An op crafted past the writer's check is refused by the replica applying it.
+        let (_, mut op) = replica1.write(random_register_entry(), BTreeSet::new())?;
+        op.crdt_op.value = too_big;
+        let op = sign_register_op(op, &keypair)?;
+        assert_eq!(
+            replica2.apply_op(op),
+            Err(Error::EntryTooBig(
+                MAX_REG_ENTRY_SIZE + 1,
+                MAX_REG_ENTRY_SIZE
+            ))
+        );
+        assert!(replica2.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn register_query_public_policy() -> eyre::Result<()> {
         let name = xor_name::rand::random();
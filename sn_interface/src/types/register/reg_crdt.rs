@@ -19,7 +19,7 @@ use crdts::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::{self, Debug, Display, Formatter, Result as FmtResult},
     hash::Hash,
 };
@@ -153,4 +153,54 @@ impl RegisterCrdt {
             .map(|(hash, node)| (EntryHash(hash), node.value.clone()))
             .collect()
     }
+
+    /// Every entry ever written, oldest first: each one comes after the entries it points at,
+    /// and entries written concurrently come in the order of their hashes. Entries whose
+    /// children haven't been seen yet are left out, as they are from reads.
+    pub(super) fn history(&self) -> Vec<(EntryHash, Entry)> {
+        // Gather the entries reachable from the current ones, with the parents of each.
+        let mut nodes = BTreeMap::new();
+        let mut parents: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut to_visit: Vec<_> = self.data.read().hashes().into_iter().collect();
+        while let Some(hash) = to_visit.pop() {
+            if nodes.contains_key(&hash) {
+                continue;
+            }
+            let node = match self.data.node(hash) {
+                Some(node) => node,
+                None => continue,
+            };
+            for child in &node.children {
+                parents.entry(*child).or_default().push(hash);
+                to_visit.push(*child);
+            }
+            let _prev = nodes.insert(hash, node);
+        }
+
+        // Emit them once all their children are, the lowest hash first among those ready.
+        let mut pending: BTreeMap<_, _> = nodes
+            .iter()
+            .map(|(hash, node)| (*hash, node.children.len()))
+            .collect();
+        let mut ready: BTreeSet<_> = pending
+            .iter()
+            .filter(|(_, children)| **children == 0)
+            .map(|(hash, _)| *hash)
+            .collect();
+        let mut history = Vec::with_capacity(nodes.len());
+        while let Some(hash) = ready.pop_first() {
+            for parent in parents.get(&hash).into_iter().flatten() {
+                if let Some(children) = pending.get_mut(parent) {
+                    *children -= 1;
+                    if *children == 0 {
+                        let _inserted = ready.insert(*parent);
+                    }
+                }
+            }
+            if let Some(node) = nodes.get(&hash) {
+                history.push((EntryHash(hash), node.value.clone()));
+            }
+        }
+        history
+    }
 }
@@ -71,14 +71,17 @@ async fn main() -> Result<()> {
         assert_eq!(file_config.log_dir, config.log_dir)
     }
 
-    assert_eq!(
-        config.update,
-        file_config.update || command_line_args.update
-    );
-    assert_eq!(
-        config.update_only,
-        file_config.update_only || command_line_args.update_only
-    );
+    #[cfg(feature = "self-update")]
+    {
+        assert_eq!(
+            config.update,
+            file_config.update || command_line_args.update
+        );
+        assert_eq!(
+            config.update_only,
+            file_config.update_only || command_line_args.update_only
+        );
+    }
     assert_eq!(
         config.clear_data,
         file_config.clear_data || command_line_args.clear_data
@@ -107,6 +110,15 @@ async fn main() -> Result<()> {
         );
     }
 
+    if command_line_args.client_local_addr.is_some() {
+        assert_eq!(
+            command_line_args.client_local_addr,
+            config.client_local_addr
+        );
+    } else {
+        assert_eq!(file_config.client_local_addr, config.client_local_addr);
+    }
+
     assert_eq!(
         config.network_config.forward_port,
         !command_line_args.skip_auto_port_forwarding
@@ -46,7 +46,7 @@ use std::{
 };
 use structopt::StructOpt;
 use tokio::task::JoinHandle;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
 /// Minimal example node.
@@ -277,6 +277,22 @@ async fn handle_event(index: usize, node: &mut NodeApi, event: Event) -> bool {
             "Node #{} received message from user: {:?}, msg: {:?}",
             index, user, msg
         ),
+        Event::StorageHealthChanged { healthy } => {
+            info!(
+                "Node #{} storage health changed - healthy: {}",
+                index, healthy
+            )
+        }
+        Event::DbReserveApproached { chunk_headroom } => {
+            warn!(
+                "Node #{} is close to its db reserve - chunk headroom: {} bytes",
+                index, chunk_headroom
+            )
+        }
+        Event::CapacityChanged { max_capacity, used } => info!(
+            "Node #{} capacity changed - max: {} bytes, used: {} bytes",
+            index, max_capacity, used
+        ),
         Event::AdultsChanged {
             remaining,
             added,
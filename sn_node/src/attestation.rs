@@ -0,0 +1,269 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Attestation of the version and configuration a node runs with, for fleet compliance.
+//!
+//! On start, and whenever its effective configuration changes at runtime, the node signs a
+//! digest of its version, its effective configuration (secrets redacted) and the network's
+//! genesis key with its node key, and writes the record to its root dir. `sn_node attestation
+//! verify` checks a record against the node's public key and reports the attested values.
+
+use crate::persisted::{self, Error as PersistedError};
+
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use thiserror::Error;
+use tiny_keccak::{Hasher, Sha3};
+
+/// Name of the attestation file in the node's root dir.
+pub const ATTESTATION_FILE: &str = "attestation";
+// Format of the file, JSON.
+const ATTESTATION_VERSION: u16 = 1;
+/// What redacted secrets are attested as.
+pub const REDACTED: &str = "<redacted>";
+
+/// The version and configuration a node attests to running with, signed with its node key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// Version of the node's binary.
+    pub version: String,
+    /// The node's effective configuration, secrets redacted.
+    pub config: Value,
+    /// Hex encoding of the network's genesis key.
+    pub genesis_key: String,
+    /// Hex encoding of the SHA3-256 of the above.
+    pub digest: String,
+    /// Hex encoding of the node's signature of the digest.
+    pub signature: String,
+}
+
+/// Why an attestation can't be trusted.
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    /// There's no attestation in the root dir.
+    #[error("no attestation at {}", .0.display())]
+    Missing(PathBuf),
+    /// The attestation file can't be read back.
+    #[error("{0}")]
+    Unreadable(String),
+    /// The digest isn't that of the attested values.
+    #[error("the digest doesn't match the attested values")]
+    DigestMismatch,
+    /// The signature isn't the node's, or not of the digest.
+    #[error("the signature isn't the node's: {0}")]
+    BadSignature(String),
+}
+
+impl Attestation {
+    /// Attests `config`, the node's effective configuration, along with the node's version and
+    /// the network's `genesis_key`, signed with the node's `keypair`.
+    pub fn new(keypair: &Keypair, config: Value, genesis_key: &bls::PublicKey) -> Self {
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        let genesis_key = hex::encode(genesis_key.to_bytes());
+        let digest = digest(&version, &config, &genesis_key);
+        let signature = keypair.sign(&digest);
+        Self {
+            version,
+            config,
+            genesis_key,
+            digest: hex::encode(digest),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Checks the attestation is of the attested values, and signed by the node with
+    /// `public_key`.
+    pub fn verify(&self, public_key: &PublicKey) -> Result<(), AttestationError> {
+        let digest = digest(&self.version, &self.config, &self.genesis_key);
+        if hex::encode(digest) != self.digest {
+            return Err(AttestationError::DigestMismatch);
+        }
+        let signature = hex::decode(&self.signature)
+            .map_err(|err| AttestationError::BadSignature(err.to_string()))?;
+        let signature = Signature::from_bytes(&signature)
+            .map_err(|err| AttestationError::BadSignature(err.to_string()))?;
+        public_key
+            .verify(&digest, &signature)
+            .map_err(|err| AttestationError::BadSignature(err.to_string()))
+    }
+}
+
+// SHA3-256 of the attested values, as canonical JSON (object keys sorted).
+fn digest(version: &str, config: &Value, genesis_key: &str) -> [u8; 32] {
+    let attested = json!({
+        "version": version,
+        "config": config,
+        "genesis_key": genesis_key,
+    });
+    let mut hasher = Sha3::v256();
+    hasher.update(attested.to_string().as_bytes());
+    let mut digest = [0; 32];
+    hasher.finalize(&mut digest);
+    digest
+}
+
+/// Writes the attestation to the root dir of its node.
+pub fn write(root_dir: &Path, attestation: &Attestation) -> io::Result<()> {
+    let json = serde_json::to_vec(attestation)?;
+    persisted::write(&root_dir.join(ATTESTATION_FILE), ATTESTATION_VERSION, &json)
+}
+
+/// Reads back the attestation in the given root dir.
+pub fn read(root_dir: &Path) -> Result<Attestation, AttestationError> {
+    let path = root_dir.join(ATTESTATION_FILE);
+    let read = persisted::read(&path)
+        .map_err(|err| AttestationError::Unreadable(err.to_string()))?
+        .ok_or_else(|| AttestationError::Missing(path.clone()))?;
+    if read.version != ATTESTATION_VERSION {
+        let error = PersistedError::WrongVersion {
+            path,
+            found: read.version,
+            expected: ATTESTATION_VERSION,
+        };
+        return Err(AttestationError::Unreadable(error.to_string()));
+    }
+    serde_json::from_slice(&read.payload).map_err(|err| {
+        AttestationError::Unreadable(PersistedError::corrupt(&path, err).to_string())
+    })
+}
+
+/// Checks on a node's attestation.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_node attestation")]
+pub enum AttestationCmd {
+    /// Verify the attestation in a node's root dir, and print the attested values
+    Verify {
+        /// The node's root dir
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+        /// The node's public key, hex encoded
+        #[structopt(long, parse(try_from_str = parse_public_key))]
+        public_key: PublicKey,
+    },
+}
+
+impl AttestationCmd {
+    /// Runs the command, writing its output to `out`.
+    pub fn run(&self, out: &mut impl Write) -> eyre::Result<()> {
+        match self {
+            Self::Verify {
+                root_dir,
+                public_key,
+            } => {
+                let attestation = read(root_dir)?;
+                attestation.verify(public_key)?;
+                writeln!(out, "Attestation verified")?;
+                writeln!(out, "version: {}", attestation.version)?;
+                writeln!(out, "genesis key: {}", attestation.genesis_key)?;
+                writeln!(
+                    out,
+                    "config: {}",
+                    serde_json::to_string_pretty(&attestation.config)?
+                )?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse_public_key(input: &str) -> Result<PublicKey, String> {
+    let bytes = hex::decode(input.trim()).map_err(|err| err.to_string())?;
+    PublicKey::from_bytes(&bytes).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use rand_07::rngs::OsRng;
+    use tempfile::tempdir;
+
+    fn attest(keypair: &Keypair, config: Value) -> Attestation {
+        let genesis_key = bls::SecretKey::random().public_key();
+        Attestation::new(keypair, config, &genesis_key)
+    }
+
+    #[test]
+    fn attestation_verifies_against_the_node_key_only() -> Result<()> {
+        let root = tempdir()?;
+        let keypair = Keypair::generate(&mut OsRng);
+        let attestation = attest(&keypair, json!({ "max_capacity": 1024 }));
+        write(root.path(), &attestation)?;
+
+        let read_back = read(root.path())?;
+        assert_eq!(read_back, attestation);
+        read_back.verify(&keypair.public)?;
+
+        let other = Keypair::generate(&mut OsRng);
+        assert!(matches!(
+            read_back.verify(&other.public),
+            Err(AttestationError::BadSignature(_))
+        ));
+
+        let mut out = Vec::new();
+        AttestationCmd::from_iter_safe([
+            "attestation",
+            "verify",
+            root.path().to_str().unwrap_or_default(),
+            "--public-key",
+            &hex::encode(keypair.public.to_bytes()),
+        ])?
+        .run(&mut out)?;
+        assert!(String::from_utf8(out)?.contains("\"max_capacity\": 1024"));
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_attestation_fails_verification() -> Result<()> {
+        let root = tempdir()?;
+        let keypair = Keypair::generate(&mut OsRng);
+        write(
+            root.path(),
+            &attest(&keypair, json!({ "max_capacity": 1024 })),
+        )?;
+
+        // A changed value, re-framed so the file itself reads back fine..
+        let mut tampered = read(root.path())?;
+        tampered.config = json!({ "max_capacity": 2048 });
+        write(root.path(), &tampered)?;
+        assert!(matches!(
+            read(root.path())?.verify(&keypair.public),
+            Err(AttestationError::DigestMismatch)
+        ));
+
+        // ..or a digest made to match it, without the node's signature.
+        tampered.digest = hex::encode(digest(
+            &tampered.version,
+            &tampered.config,
+            &tampered.genesis_key,
+        ));
+        write(root.path(), &tampered)?;
+        assert!(matches!(
+            read(root.path())?.verify(&keypair.public),
+            Err(AttestationError::BadSignature(_))
+        ));
+
+        // A damaged file doesn't read back at all.
+        let path = root.path().join(ATTESTATION_FILE);
+        for way in 0..persisted::damage::ways(&path)? {
+            write(root.path(), &tampered)?;
+            persisted::damage::apply(&path, way)?;
+            assert!(matches!(
+                read(root.path()),
+                Err(AttestationError::Unreadable(_))
+            ));
+        }
+        Ok(())
+    }
+}
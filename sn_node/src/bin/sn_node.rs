@@ -30,11 +30,19 @@
 use color_eyre::{Section, SectionExt};
 use eyre::{eyre, Result, WrapErr};
 use file_rotate::{compression::Compression, suffix::AppendCount, ContentLimit, FileRotate};
-use sn_node::node::{add_connection_info, set_connection_info, Config, Error, NodeApi};
-
+use sn_node::attestation::AttestationCmd;
+#[cfg(feature = "binary-logs")]
+use sn_node::binary_log::{BinaryLogGuard, BinaryLogLayer, LogsCmd, RotationConfig};
+use sn_node::control::ControlCmd;
+use sn_node::dns_contacts::ContactsCmd;
+use sn_node::keystore::KeystoreCmd;
+use sn_node::node::{
+    add_connection_info, set_connection_info, Config, Error, LogFilterReloader, NodeApi,
+};
+
+#[cfg(feature = "self-update")]
 use self_update::{cargo_crate_version, Status};
-#[cfg(not(feature = "tokio-console"))]
-use sn_node::LogFormatter;
+use sn_node::{LogFormatter, StorageInitError};
 use std::{fmt::Debug, fs::File, io, path::Path};
 use std::{io::Write, process::exit};
 use structopt::{clap, StructOpt};
@@ -43,24 +51,72 @@ use tokio::time::{sleep, Duration};
 use tracing::{self, error, info, trace, warn};
 
 use tracing_appender::non_blocking::WorkerGuard;
-#[cfg(not(feature = "tokio-console"))]
-use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::{
+    filter::{EnvFilter, Filtered},
+    prelude::*,
+    reload, Layer, Registry,
+};
 
-#[cfg(not(feature = "tokio-console"))]
 const MODULE_NAME: &str = "sn_node";
 const BOOTSTRAP_RETRY_TIME_SEC: u64 = 30;
+// Number of events queued for the binary log writer before further events are dropped.
+#[cfg(feature = "binary-logs")]
+const BINARY_LOG_QUEUE_LEN: usize = 100_000;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
-    #[cfg(feature = "tokio-console")]
-    console_subscriber::init();
+
+    // `sn_node logs ...` works on log files, without starting a node.
+    #[cfg(feature = "binary-logs")]
+    if std::env::args().nth(1).as_deref() == Some("logs") {
+        let cmd = LogsCmd::from_iter(std::env::args().skip(1));
+        return cmd
+            .run(&mut io::stdout().lock())
+            .wrap_err("Failed to read binary log");
+    }
+
+    // `sn_node control ...` leaves a request for a running node.
+    if std::env::args().nth(1).as_deref() == Some("control") {
+        let cmd = ControlCmd::from_iter(std::env::args().skip(1));
+        return cmd
+            .run(&mut io::stdout().lock())
+            .wrap_err("Failed to make the request to the node");
+    }
+
+    // `sn_node attestation ...` checks a node's attestation, without starting a node.
+    if std::env::args().nth(1).as_deref() == Some("attestation") {
+        let cmd = AttestationCmd::from_iter(std::env::args().skip(1));
+        return cmd
+            .run(&mut io::stdout().lock())
+            .wrap_err("Failed to verify the node's attestation");
+    }
+
+    // `sn_node keystore ...` manages a node's keystore, without starting a node.
+    if std::env::args().nth(1).as_deref() == Some("keystore") {
+        let cmd = KeystoreCmd::from_iter(std::env::args().skip(1));
+        return cmd
+            .run(&mut io::stdout().lock())
+            .wrap_err("Failed to encrypt the node's keys");
+    }
+
+    // `sn_node contacts ...` manages the contacts of a network published in DNS.
+    if std::env::args().nth(1).as_deref() == Some("contacts") {
+        let cmd = ContactsCmd::from_iter(std::env::args().skip(1));
+        return cmd
+            .run(&mut io::stdout().lock())
+            .wrap_err("Failed to manage the network's DNS contacts");
+    }
 
     let handle = std::thread::Builder::new()
         .name("sn_node".to_string())
         .stack_size(16 * 1024 * 1024)
         .spawn(move || {
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(run_node())?;
+            let result = rt.block_on(run_node());
+            // Flushes the spans yet to be exported, while the runtime exporting them is still up.
+            #[cfg(feature = "otlp")]
+            opentelemetry::global::shutdown_tracer_provider();
+            result?;
             Ok(())
         })
         .wrap_err("Failed to spawn node thread")?;
@@ -143,6 +199,101 @@ impl Debug for FileRotateAppender {
         f.debug_struct("FileRotateAppender").finish()
     }
 }
+// Sets up tracing with the layer of our logs, along with tokio-console's and the OTLP exporter's
+// when enabled. Our logs' filter only applies to their layer, as tokio-console needs the runtime's
+// own spans.
+#[cfg_attr(
+    not(any(feature = "tokio-console", feature = "otlp")),
+    allow(unused_variables)
+)]
+fn init_tracing<L>(logs: L, config: &Config) -> Result<()>
+where
+    L: Layer<Registry> + Send + Sync,
+{
+    let registry = tracing_subscriber::registry().with(logs);
+
+    #[cfg(feature = "tokio-console")]
+    let registry = {
+        let console = console_subscriber::ConsoleLayer::builder().with_default_env();
+        let console = match config.console_addr {
+            Some(addr) => console.server_addr(addr),
+            None => console,
+        };
+        registry.with(console.spawn())
+    };
+
+    #[cfg(feature = "otlp")]
+    let registry = {
+        let otlp = match &config.otlp_endpoint {
+            Some(endpoint) => {
+                use opentelemetry_otlp::WithExportConfig;
+
+                let tracer = opentelemetry_otlp::new_pipeline()
+                    .tracing()
+                    .with_exporter(
+                        opentelemetry_otlp::new_exporter()
+                            .tonic()
+                            .with_endpoint(endpoint),
+                    )
+                    .with_trace_config(opentelemetry::sdk::trace::config().with_resource(
+                        opentelemetry::sdk::Resource::new(vec![opentelemetry::KeyValue::new(
+                            "service.name",
+                            MODULE_NAME,
+                        )]),
+                    ))
+                    .install_batch(opentelemetry::runtime::Tokio)
+                    .wrap_err("Failed to set up the OTLP exporter")?;
+                let layer = tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(log_filter(config)?);
+                Some(layer)
+            }
+            None => None,
+        };
+        registry.with(otlp)
+    };
+
+    registry.init();
+    Ok(())
+}
+
+// Filters `logs` with `filter`, which the returned reloader replaces while we run.
+fn with_reloadable_filter<L>(
+    logs: L,
+    filter: EnvFilter,
+) -> (
+    reload::Layer<Filtered<L, EnvFilter, Registry>, Registry>,
+    LogFilterReloader,
+)
+where
+    L: Layer<Registry> + Send + Sync + 'static,
+{
+    let (layer, handle) = reload::Layer::new(logs.with_filter(filter));
+    let reloader: LogFilterReloader = Box::new(move |filter| {
+        let filter = EnvFilter::try_new(filter).map_err(|err| err.to_string())?;
+        handle
+            .modify(|layer| *layer.filter_mut() = filter)
+            .map_err(|err| err.to_string())
+    });
+    (layer, reloader)
+}
+
+// The `RUST_LOG` env var's filter, or else one by the verbosity flags.
+fn log_filter(config: &Config) -> Result<EnvFilter> {
+    match EnvFilter::try_from_env("RUST_LOG") {
+        Ok(filter) => Ok(filter),
+        // If we have an error (ie RUST_LOG not set or otherwise), we check the verbosity flags
+        Err(_) => {
+            // we manually determine level filter instead of using tracing EnvFilter.
+            let level_filter = config.verbose();
+            let module_filter = format!("{}={}", MODULE_NAME, level_filter)
+                .parse()
+                .wrap_err("BUG: invalid module filter constructed")?;
+            Ok(EnvFilter::from_default_env().add_directive(module_filter))
+        }
+    }
+}
+
 async fn run_node() -> Result<()> {
     let config = Config::new().await?;
 
@@ -159,30 +310,15 @@ async fn run_node() -> Result<()> {
     // ==============
 
     let mut _optional_guard: Option<WorkerGuard> = None;
+    #[cfg(feature = "binary-logs")]
+    let mut _binary_log_guard: Option<BinaryLogGuard> = None;
 
-    #[cfg(not(feature = "tokio-console"))]
-    {
-        let filter = match EnvFilter::try_from_env("RUST_LOG") {
-            Ok(filter) => filter,
-            // If we have an error (ie RUST_LOG not set or otherwise), we check the verbosity flags
-            Err(_) => {
-                // we manually determine level filter instead of using tracing EnvFilter.
-                let level_filter = config.verbose();
-                let module_filter = format!("{}={}", MODULE_NAME, level_filter)
-                    .parse()
-                    .wrap_err("BUG: invalid module filter constructed")?;
-                EnvFilter::from_default_env().add_directive(module_filter)
-            }
-        };
+    let log_filter_reloader = {
+        let filter = log_filter(&config)?;
 
-        _optional_guard = if let Some(log_dir) = config.log_dir() {
+        if let Some(log_dir) = config.log_dir() {
             println!("Starting logging to directory: {:?}", log_dir);
 
-            let mut content_limit = ContentLimit::BytesSurpassed(config.logs_max_bytes);
-            if config.logs_max_lines > 0 {
-                content_limit = ContentLimit::Lines(config.logs_max_lines);
-            }
-
             // FileRotate crate changed `0 means for all` to `0 means only original`
             // Here set the retained value to be same as uncompressed in case of 0.
             let logs_retained = if config.logs_retained == 0 {
@@ -190,54 +326,95 @@ async fn run_node() -> Result<()> {
             } else {
                 config.logs_retained
             };
-            let file_appender = FileRotateAppender::make_rotate_appender(
-                log_dir,
-                "sn_node.log",
-                AppendCount::new(logs_retained),
-                content_limit,
-                Compression::OnRotate(config.logs_uncompressed),
-            );
-
-            // configure how tracing non-blocking works: https://tracing.rs/tracing_appender/non_blocking/struct.nonblockingbuilder#method.default
-            let non_blocking_builder =
-                tracing_appender::non_blocking::NonBlockingBuilder::default();
-
-            let (non_blocking, guard) = non_blocking_builder
-                // lose lines and keep perf, or exert backpressure?
-                .lossy(false)
-                // optionally change buffered lines limit
-                // .buffered_lines_limit(buffered_lines_limit)
-                .finish(file_appender);
-
-            let builder = tracing_subscriber::fmt()
-            // eg : RUST_LOG=my_crate=info,my_crate::my_mod=debug,[my_span]=trace
-                .with_env_filter(filter)
-                .with_thread_names(true)
-                .with_ansi(false)
-                .with_writer(non_blocking);
 
-            if config.json_logs {
-                builder.json().init();
+            #[cfg(feature = "binary-logs")]
+            let binary_layer = if config.binary_logs || config.binary_logs_only {
+                let (layer, writer) = BinaryLogLayer::new(BINARY_LOG_QUEUE_LEN);
+                let guard = writer
+                    .spawn(RotationConfig {
+                        dir: log_dir.clone(),
+                        file_name: "sn_node.blog".to_string(),
+                        max_bytes: config.logs_max_bytes as u64,
+                        retained: logs_retained,
+                    })
+                    .wrap_err("Failed to start the binary log writer")?;
+                _binary_log_guard = Some(guard);
+                Some(layer)
             } else {
-                builder.event_format(LogFormatter::default()).init();
-            }
+                None
+            };
+            #[cfg(not(feature = "binary-logs"))]
+            let binary_layer: Option<tracing_subscriber::layer::Identity> = None;
 
-            Some(guard)
+            #[cfg(feature = "binary-logs")]
+            let binary_logs_only = config.binary_logs_only;
+            #[cfg(not(feature = "binary-logs"))]
+            let binary_logs_only = false;
+
+            let text_layer = if binary_logs_only {
+                None
+            } else {
+                let mut content_limit = ContentLimit::BytesSurpassed(config.logs_max_bytes);
+                if config.logs_max_lines > 0 {
+                    content_limit = ContentLimit::Lines(config.logs_max_lines);
+                }
+
+                let file_appender = FileRotateAppender::make_rotate_appender(
+                    log_dir,
+                    "sn_node.log",
+                    AppendCount::new(logs_retained),
+                    content_limit,
+                    Compression::OnRotate(config.logs_uncompressed),
+                );
+
+                // configure how tracing non-blocking works: https://tracing.rs/tracing_appender/non_blocking/struct.nonblockingbuilder#method.default
+                let non_blocking_builder =
+                    tracing_appender::non_blocking::NonBlockingBuilder::default();
+
+                let (non_blocking, guard) = non_blocking_builder
+                    // lose lines and keep perf, or exert backpressure?
+                    .lossy(false)
+                    // optionally change buffered lines limit
+                    // .buffered_lines_limit(buffered_lines_limit)
+                    .finish(file_appender);
+                _optional_guard = Some(guard);
+
+                let layer = tracing_subscriber::fmt::layer()
+                    .with_thread_names(true)
+                    .with_ansi(false)
+                    .with_writer(non_blocking);
+                let layer: Box<dyn Layer<Registry> + Send + Sync> = if config.json_logs {
+                    Box::new(layer.json())
+                } else {
+                    Box::new(layer.event_format(LogFormatter::default()))
+                };
+                Some(layer)
+            };
+
+            // eg : RUST_LOG=my_crate=info,my_crate::my_mod=debug,[my_span]=trace
+            let (layer, reloader) =
+                with_reloadable_filter(Layer::and_then(text_layer, binary_layer), filter);
+            init_tracing(layer, &config)?;
+            reloader
         } else {
             println!("Starting logging to stdout");
 
-            tracing_subscriber::fmt()
+            let layer = tracing_subscriber::fmt::layer()
                 .with_thread_names(true)
                 .with_ansi(false)
-                .with_env_filter(filter)
-                .with_target(false)
-                .event_format(LogFormatter::default())
-                .init();
-
-            None
-        };
-    }
+                .with_target(false);
+            let layer: Box<dyn Layer<Registry> + Send + Sync> = if config.json_logs {
+                Box::new(layer.json())
+            } else {
+                Box::new(layer.event_format(LogFormatter::default()))
+            };
+            let (layer, reloader) = with_reloadable_filter(layer, filter);
+            init_tracing(layer, &config)?;
+            reloader
+        }
+    };
 
+    #[cfg(feature = "self-update")]
     if config.update() || config.update_only() {
         match update() {
             Ok(status) => {
@@ -297,6 +474,15 @@ async fn run_node() -> Result<()> {
                 error!("{}", err_msg);
                 exit(1);
             }
+            Err(error) if error.storage_init().is_some() => {
+                let suggestion = error
+                    .storage_init()
+                    .map(StorageInitError::suggestion)
+                    .unwrap_or_default();
+                return Err(error)
+                    .wrap_err("Cannot start node, its storage can't be initialised")
+                    .suggestion(suggestion);
+            }
             Err(Error::JoinTimeout) => {
                 let message = format!("Encountered a timeout while trying to join the network. Retrying after {BOOTSTRAP_RETRY_TIME_SEC} seconds.");
                 println!("{}", &message);
@@ -318,6 +504,8 @@ async fn run_node() -> Result<()> {
         sleep(bootstrap_retry_duration).await;
     };
 
+    node.set_log_filter_reloader(log_filter_reloader).await;
+
     let our_conn_info = node.our_connection_info().await;
 
     if config.is_first() {
@@ -335,7 +523,20 @@ async fn run_node() -> Result<()> {
             });
     }
 
-    // This just keeps the node going as long as routing goes
+    // Leave gracefully when interrupted, e.g. for a rolling upgrade.
+    let shutdown = node.shutdown_handle();
+    let _handle = tokio::spawn(async move {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            error!("Unable to listen for interrupts: {:?}", error);
+            return;
+        }
+        info!("Interrupted, shutting down");
+        if let Err(error) = shutdown.shut_down().await {
+            error!("Failed to shut down gracefully: {:?}", error);
+        }
+    });
+
+    // This just keeps the node going as long as routing goes, or until it's shut down
     while let Some(event) = event_stream.next().await {
         trace!("Routing event! {:?}", event);
     }
@@ -343,6 +544,7 @@ async fn run_node() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "self-update")]
 fn update() -> Result<Status, Box<dyn (::std::error::Error)>> {
     info!("Checking for updates...");
     let target = self_update::get_target();
@@ -0,0 +1,950 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Compact binary log output, for nodes producing more logs than text output can keep up with.
+//!
+//! Every log file starts with a header holding the time it was opened, followed by
+//! length-prefixed records. Targets and field names are interned: the first time one is used in a
+//! file, an `Intern` record assigns it an id which later records refer to. Event timestamps are
+//! stored as the delta from the previous event. Each file is self-contained, so rotated files can
+//! be decoded on their own.
+//!
+//! The sink never blocks the logging thread: events are queued on a bounded channel, and dropped
+//! (and counted) when the writer can't keep up. The number of events dropped is written to the
+//! log as well, so readers can tell where gaps are.
+
+use crate::membership_history::read_history;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use structopt::StructOpt;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+const MAGIC: &[u8; 8] = b"SNBLOG1\0";
+
+// Bound on the size of a single record, to fail fast on a corrupt length prefix.
+const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
+/// A field value of a logged event.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    /// A signed integer.
+    I64(i64),
+    /// An unsigned integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A boolean.
+    Bool(bool),
+    /// A string, recorded as is.
+    Str(String),
+    /// The `Debug` representation of a value (this is how the event's message is recorded).
+    Debug(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I64(v) => write!(f, "{}", v),
+            Self::U64(v) => write!(f, "{}", v),
+            Self::F64(v) => write!(f, "{}", v),
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Str(v) | Self::Debug(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Value {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::I64(v) => (*v).into(),
+            Self::U64(v) => (*v).into(),
+            Self::F64(v) => (*v).into(),
+            Self::Bool(v) => (*v).into(),
+            Self::Str(v) | Self::Debug(v) => v.clone().into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Intern {
+        id: u32,
+        value: String,
+    },
+    Event {
+        level: u8,
+        target: u32,
+        delta_micros: u64,
+        fields: Vec<(u32, Value)>,
+    },
+    Dropped {
+        count: u64,
+    },
+}
+
+fn level_to_u8(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+fn level_from_u8(level: u8) -> io::Result<Level> {
+    Ok(match level {
+        0 => Level::ERROR,
+        1 => Level::WARN,
+        2 => Level::INFO,
+        3 => Level::DEBUG,
+        4 => Level::TRACE,
+        other => return Err(invalid_data(format!("unknown log level {}", other))),
+    })
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn codec() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+fn to_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+// An event as captured on the logging thread, before interning.
+struct CapturedEvent {
+    level: Level,
+    target: &'static str,
+    time: SystemTime,
+    fields: Vec<(&'static str, Value)>,
+}
+
+enum Msg {
+    Event(CapturedEvent),
+    Shutdown,
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.push((field.name(), Value::I64(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.push((field.name(), Value::U64(value)));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.push((field.name(), Value::F64(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.push((field.name(), Value::Bool(value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields
+            .push((field.name(), Value::Str(value.to_string())));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields
+            .push((field.name(), Value::Debug(format!("{:?}", value))));
+    }
+}
+
+/// A `tracing` layer queuing events for a `BinaryLogWriter`.
+#[derive(Debug, Clone)]
+pub struct BinaryLogLayer {
+    sender: SyncSender<Msg>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BinaryLogLayer {
+    /// Creates a layer queuing up to `capacity` events, and the writer consuming them. Nothing is
+    /// written until the writer is spawned.
+    pub fn new(capacity: usize) -> (Self, BinaryLogWriter) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let layer = Self {
+            sender: sender.clone(),
+            dropped: dropped.clone(),
+        };
+        let writer = BinaryLogWriter {
+            sender,
+            receiver,
+            dropped,
+        };
+        (layer, writer)
+    }
+
+    /// Number of events dropped because the writer couldn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for BinaryLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let captured = CapturedEvent {
+            level: *event.metadata().level(),
+            target: event.metadata().target(),
+            time: SystemTime::now(),
+            fields: visitor.fields,
+        };
+        match self.sender.try_send(Msg::Event(captured)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                let _prev = self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Where and how much a `BinaryLogWriter` writes.
+#[derive(Clone, Debug)]
+pub struct RotationConfig {
+    /// Directory holding the log files.
+    pub dir: PathBuf,
+    /// Name of the current log file. Rotated files get a numeric suffix: `<name>.1` is the most
+    /// recent.
+    pub file_name: String,
+    /// Size in bytes after which the current file is rotated.
+    pub max_bytes: u64,
+    /// Number of rotated files kept.
+    pub retained: usize,
+}
+
+impl RotationConfig {
+    fn current(&self) -> PathBuf {
+        self.dir.join(&self.file_name)
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("{}.{}", self.file_name, n))
+    }
+}
+
+/// Writes the events queued by a `BinaryLogLayer` to size-rotated files.
+#[derive(Debug)]
+pub struct BinaryLogWriter {
+    sender: SyncSender<Msg>,
+    receiver: Receiver<Msg>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BinaryLogWriter {
+    /// Spawns the writer thread. Queued events are written until the returned guard is dropped.
+    pub fn spawn(self, rotation: RotationConfig) -> io::Result<BinaryLogGuard> {
+        fs::create_dir_all(&rotation.dir)?;
+        let file = LogFile::create(&rotation.current())?;
+        let Self {
+            sender,
+            receiver,
+            dropped,
+        } = self;
+
+        let thread_dropped = dropped.clone();
+        let handle = std::thread::Builder::new()
+            .name("sn_node_binary_log".to_string())
+            .spawn(move || {
+                let mut state = WriterState {
+                    rotation,
+                    file,
+                    dropped: thread_dropped,
+                    dropped_written: 0,
+                };
+                // Reported through the other layers: events we'd queue now are never written.
+                if let Err(error) = state.run(&receiver) {
+                    error!("Binary log writer stopped: {:?}", error);
+                }
+            })?;
+
+        Ok(BinaryLogGuard {
+            sender,
+            handle: Some(handle),
+            dropped,
+        })
+    }
+}
+
+/// Stops the binary log writer when dropped, after writing the events still queued.
+#[derive(Debug)]
+pub struct BinaryLogGuard {
+    sender: SyncSender<Msg>,
+    handle: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BinaryLogGuard {
+    /// Number of events dropped because the writer couldn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BinaryLogGuard {
+    fn drop(&mut self) {
+        if self.sender.send(Msg::Shutdown).is_ok() {
+            if let Some(handle) = self.handle.take() {
+                let _res = handle.join();
+            }
+        }
+    }
+}
+
+struct LogFile {
+    out: BufWriter<File>,
+    len: u64,
+    interned: BTreeMap<&'static str, u32>,
+    last_micros: u64,
+}
+
+impl LogFile {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let start = to_micros(SystemTime::now());
+        out.write_all(MAGIC)?;
+        out.write_all(&start.to_le_bytes())?;
+        Ok(Self {
+            out,
+            len: (MAGIC.len() + 8) as u64,
+            interned: BTreeMap::new(),
+            last_micros: start,
+        })
+    }
+
+    fn intern(&mut self, value: &'static str) -> io::Result<u32> {
+        if let Some(id) = self.interned.get(value) {
+            return Ok(*id);
+        }
+        let id = self.interned.len() as u32;
+        let _prev = self.interned.insert(value, id);
+        self.write_record(&Record::Intern {
+            id,
+            value: value.to_string(),
+        })?;
+        Ok(id)
+    }
+
+    fn write_event(&mut self, event: CapturedEvent) -> io::Result<()> {
+        let target = self.intern(event.target)?;
+        let mut fields = Vec::with_capacity(event.fields.len());
+        for (name, value) in event.fields {
+            fields.push((self.intern(name)?, value));
+        }
+        let micros = to_micros(event.time);
+        let delta_micros = micros.saturating_sub(self.last_micros);
+        self.last_micros = self.last_micros.max(micros);
+        self.write_record(&Record::Event {
+            level: level_to_u8(&event.level),
+            target,
+            delta_micros,
+            fields,
+        })
+    }
+
+    fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        let bytes = codec()
+            .serialize(record)
+            .map_err(|error| invalid_data(error.to_string()))?;
+        self.out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.out.write_all(&bytes)?;
+        self.len += 4 + bytes.len() as u64;
+        Ok(())
+    }
+}
+
+struct WriterState {
+    rotation: RotationConfig,
+    file: LogFile,
+    dropped: Arc<AtomicU64>,
+    dropped_written: u64,
+}
+
+impl WriterState {
+    fn run(&mut self, receiver: &Receiver<Msg>) -> io::Result<()> {
+        loop {
+            // Flush whenever we've caught up, so the file is readable while the node runs.
+            let msg = match receiver.try_recv() {
+                Ok(msg) => msg,
+                Err(_) => {
+                    self.write_dropped()?;
+                    self.file.out.flush()?;
+                    match receiver.recv() {
+                        Ok(msg) => msg,
+                        Err(_) => return Ok(()),
+                    }
+                }
+            };
+            match msg {
+                Msg::Event(event) => {
+                    self.file.write_event(event)?;
+                    if self.file.len >= self.rotation.max_bytes {
+                        self.rotate()?;
+                    }
+                }
+                Msg::Shutdown => {
+                    self.write_dropped()?;
+                    return self.file.out.flush();
+                }
+            }
+        }
+    }
+
+    fn write_dropped(&mut self) -> io::Result<()> {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > self.dropped_written {
+            self.file.write_record(&Record::Dropped {
+                count: dropped - self.dropped_written,
+            })?;
+            self.dropped_written = dropped;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.out.flush()?;
+        let rotation = &self.rotation;
+        if rotation.retained == 0 {
+            fs::remove_file(rotation.current())?;
+        } else {
+            let oldest = rotation.rotated(rotation.retained);
+            if oldest.exists() {
+                fs::remove_file(oldest)?;
+            }
+            for n in (1..rotation.retained).rev() {
+                let from = rotation.rotated(n);
+                if from.exists() {
+                    fs::rename(from, rotation.rotated(n + 1))?;
+                }
+            }
+            fs::rename(rotation.current(), rotation.rotated(1))?;
+        }
+        self.file = LogFile::create(&rotation.current())?;
+        Ok(())
+    }
+}
+
+/// An event decoded from a binary log file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEvent {
+    /// Level of the event.
+    pub level: Level,
+    /// Target of the event, usually its module path.
+    pub target: String,
+    /// When the event was logged.
+    pub timestamp: SystemTime,
+    /// The event's fields, in the order they were recorded.
+    pub fields: Vec<(String, Value)>,
+}
+
+impl LogEvent {
+    /// Renders the event as a JSON object.
+    pub fn to_json(&self) -> serde_json::Value {
+        let fields: serde_json::Map<_, _> = self
+            .fields
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_json()))
+            .collect();
+        serde_json::json!({
+            "timestamp": to_micros(self.timestamp) as f64 / 1_000_000.0,
+            "level": self.level.to_string(),
+            "target": self.target,
+            "fields": fields,
+        })
+    }
+}
+
+impl Display for LogEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let micros = to_micros(self.timestamp);
+        write!(
+            f,
+            "{}.{:06} {:>5} [{}]:",
+            micros / 1_000_000,
+            micros % 1_000_000,
+            self.level,
+            self.target
+        )?;
+        for (name, value) in &self.fields {
+            if name == "message" {
+                write!(f, " {}", value)?;
+            } else {
+                write!(f, " {}={}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the events of a binary log file.
+#[derive(Debug)]
+pub struct LogReader<R> {
+    input: R,
+    strings: BTreeMap<u32, String>,
+    last_micros: u64,
+    dropped: u64,
+}
+
+impl LogReader<BufReader<File>> {
+    /// Opens the binary log file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> LogReader<R> {
+    /// Reads the file header from `input`, failing if it isn't a binary log.
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a binary sn_node log"));
+        }
+        let mut start = [0; 8];
+        input.read_exact(&mut start)?;
+        Ok(Self {
+            input,
+            strings: BTreeMap::new(),
+            last_micros: u64::from_le_bytes(start),
+            dropped: 0,
+        })
+    }
+
+    /// Number of events the node reported as dropped, in the part of the file read so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Returns `None` at the end of the file. A record cut short, as the last one may be if the
+    // node was killed mid-write, is treated as the end of the file too.
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut len = [0; 4];
+        match self.input.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_LEN {
+            return Err(invalid_data(format!("record of {} bytes is too long", len)));
+        }
+        let mut bytes = vec![0; len];
+        match self.input.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        codec()
+            .deserialize(&bytes)
+            .map(Some)
+            .map_err(|error| invalid_data(error.to_string()))
+    }
+
+    fn lookup(&self, id: u32) -> io::Result<String> {
+        self.strings
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| invalid_data(format!("reference to unknown string {}", id)))
+    }
+}
+
+impl<R: Read> Iterator for LogReader<R> {
+    type Item = io::Result<LogEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => return None,
+                Err(error) => return Some(Err(error)),
+            };
+            match record {
+                Record::Intern { id, value } => {
+                    let _prev = self.strings.insert(id, value);
+                }
+                Record::Dropped { count } => self.dropped += count,
+                Record::Event {
+                    level,
+                    target,
+                    delta_micros,
+                    fields,
+                } => {
+                    self.last_micros += delta_micros;
+                    let event = level_from_u8(level).and_then(|level| {
+                        let fields = fields
+                            .into_iter()
+                            .map(|(name, value)| Ok((self.lookup(name)?, value)))
+                            .collect::<io::Result<_>>()?;
+                        Ok(LogEvent {
+                            level,
+                            target: self.lookup(target)?,
+                            timestamp: UNIX_EPOCH + Duration::from_micros(self.last_micros),
+                            fields,
+                        })
+                    });
+                    return Some(event);
+                }
+            }
+        }
+    }
+}
+
+/// Which decoded events to output.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    /// Least severe level to include.
+    pub level: Option<Level>,
+    /// Only include events whose target starts with this.
+    pub target: Option<String>,
+    /// Only include events logged at or after this time.
+    pub since: Option<SystemTime>,
+    /// Only include events logged before this time.
+    pub until: Option<SystemTime>,
+}
+
+impl LogFilter {
+    /// Whether `event` passes the filter.
+    pub fn matches(&self, event: &LogEvent) -> bool {
+        self.level.is_none_or(|level| event.level <= level)
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|target| event.target.starts_with(target.as_str()))
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp < until)
+    }
+}
+
+/// Operations on the node's binary logs and membership history.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_node logs")]
+pub enum LogsCmd {
+    /// Decode a binary log file to text
+    Read {
+        /// The binary log file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Output one JSON object per event
+        #[structopt(long)]
+        json: bool,
+        /// Least severe level to output: error, warn, info, debug or trace
+        #[structopt(long)]
+        level: Option<Level>,
+        /// Only output events whose target starts with this, e.g. `sn_node::node::core`
+        #[structopt(long)]
+        target: Option<String>,
+        /// Only output events logged at or after this time, in seconds since the Unix epoch
+        #[structopt(long)]
+        since: Option<u64>,
+        /// Only output events logged before this time, in seconds since the Unix epoch
+        #[structopt(long)]
+        until: Option<u64>,
+    },
+    /// Dump the section membership history recorded by a node, one JSON object per snapshot
+    Membership {
+        /// The node's root dir
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+        /// Only output snapshots taken at or after this time, in seconds since the Unix epoch
+        #[structopt(long)]
+        since: Option<u64>,
+        /// Only output snapshots taken before this time, in seconds since the Unix epoch
+        #[structopt(long)]
+        until: Option<u64>,
+    },
+}
+
+impl LogsCmd {
+    /// Runs the command, writing its output to `out`.
+    pub fn run(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Read {
+                file,
+                json,
+                level,
+                target,
+                since,
+                until,
+            } => {
+                let filter = LogFilter {
+                    level: *level,
+                    target: target.clone(),
+                    since: since.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                    until: until.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                };
+                let mut reader = LogReader::open(file)?;
+                for event in &mut reader {
+                    let event = event?;
+                    if !filter.matches(&event) {
+                        continue;
+                    }
+                    if *json {
+                        writeln!(out, "{}", event.to_json())?;
+                    } else {
+                        writeln!(out, "{}", event)?;
+                    }
+                }
+                if reader.dropped() > 0 {
+                    eprintln!(
+                        "{} events were dropped by the node while writing this file",
+                        reader.dropped()
+                    );
+                }
+                Ok(())
+            }
+            Self::Membership {
+                root_dir,
+                since,
+                until,
+            } => {
+                let history = read_history(
+                    root_dir,
+                    since.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                    until.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                )?;
+                for snapshot in history {
+                    writeln!(out, "{}", snapshot.to_json())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use tempfile::tempdir;
+    use tracing_subscriber::prelude::*;
+
+    fn rotation(dir: &Path, max_bytes: u64) -> RotationConfig {
+        RotationConfig {
+            dir: dir.to_path_buf(),
+            file_name: "sn_node.blog".to_string(),
+            max_bytes,
+            retained: 3,
+        }
+    }
+
+    fn read_all(path: &Path) -> Result<(Vec<LogEvent>, u64)> {
+        let mut reader = LogReader::open(path)?;
+        let events = (&mut reader).collect::<io::Result<Vec<_>>>()?;
+        Ok((events, reader.dropped()))
+    }
+
+    #[test]
+    fn burst_is_read_back_faithfully() -> Result<()> {
+        let dir = tempdir()?;
+        let (layer, writer) = BinaryLogLayer::new(10_000);
+        let guard = writer.spawn(rotation(dir.path(), u64::MAX))?;
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        let before = SystemTime::now();
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..1000u64 {
+                info!(i, even = i % 2 == 0, "event number {}", i);
+            }
+            warn!(target: "sn_node::other", ratio = 0.5, name = "x", "done");
+        });
+        drop(guard);
+        let after = SystemTime::now();
+
+        let (events, dropped) = read_all(&dir.path().join("sn_node.blog"))?;
+        assert_eq!(dropped, 0);
+        assert_eq!(events.len(), 1001);
+        for (i, event) in events[..1000].iter().enumerate() {
+            assert_eq!(event.level, Level::INFO);
+            assert_eq!(event.target, module_path!());
+            assert_eq!(
+                event.fields,
+                vec![
+                    (
+                        "message".to_string(),
+                        Value::Debug(format!("event number {}", i))
+                    ),
+                    ("i".to_string(), Value::U64(i as u64)),
+                    ("even".to_string(), Value::Bool(i % 2 == 0)),
+                ]
+            );
+        }
+        let last = &events[1000];
+        assert_eq!(last.level, Level::WARN);
+        assert_eq!(last.target, "sn_node::other");
+        assert_eq!(
+            last.fields[1..],
+            [
+                ("ratio".to_string(), Value::F64(0.5)),
+                ("name".to_string(), Value::Str("x".to_string())),
+            ]
+        );
+
+        // Timestamps are microsecond precise, so allow for truncation.
+        let before = before - Duration::from_micros(1);
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(events[0].timestamp >= before);
+        assert!(last.timestamp <= after);
+
+        Ok(())
+    }
+
+    #[test]
+    fn events_are_dropped_and_counted_when_the_queue_is_full() -> Result<()> {
+        let dir = tempdir()?;
+        let (layer, writer) = BinaryLogLayer::new(4);
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+
+        // Nothing drains the queue until the writer is spawned, so only the first events fit.
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..10 {
+                info!("event {}", i);
+            }
+        });
+        assert_eq!(layer.dropped(), 6);
+
+        let guard = writer.spawn(rotation(dir.path(), u64::MAX))?;
+        assert_eq!(guard.dropped(), 6);
+        drop(guard);
+
+        let (events, dropped) = read_all(&dir.path().join("sn_node.blog"))?;
+        assert_eq!(dropped, 6);
+        let messages: Vec<_> = events
+            .iter()
+            .map(|event| event.fields[0].1.to_string())
+            .collect();
+        assert_eq!(messages, ["event 0", "event 1", "event 2", "event 3"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_are_rotated_and_decode_on_their_own() -> Result<()> {
+        let dir = tempdir()?;
+        let (layer, writer) = BinaryLogLayer::new(10_000);
+        let guard = writer.spawn(rotation(dir.path(), 1024))?;
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..500 {
+                debug!(i, "rotating event");
+            }
+        });
+        drop(guard);
+
+        let config = rotation(dir.path(), 1024);
+        assert!(!config.rotated(4).exists());
+        let mut total = 0;
+        for path in [
+            config.rotated(3),
+            config.rotated(2),
+            config.rotated(1),
+            config.current(),
+        ] {
+            let (events, _) = read_all(&path)?;
+            assert!(events.iter().all(|event| event.level == Level::DEBUG));
+            total += events.len();
+        }
+        // Older files were deleted, so only the most recent events are kept.
+        assert!(total > 0 && total < 500);
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_selects_by_level_target_and_time() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let event = |level, target: &str, secs| LogEvent {
+            level,
+            target: target.to_string(),
+            timestamp: at(secs),
+            fields: vec![],
+        };
+        let filter = LogFilter {
+            level: Some(Level::INFO),
+            target: Some("sn_node::node".to_string()),
+            since: Some(at(100)),
+            until: Some(at(200)),
+        };
+
+        assert!(filter.matches(&event(Level::INFO, "sn_node::node::core", 100)));
+        assert!(filter.matches(&event(Level::ERROR, "sn_node::node", 199)));
+        assert!(!filter.matches(&event(Level::DEBUG, "sn_node::node", 150)));
+        assert!(!filter.matches(&event(Level::INFO, "sn_node::dbs", 150)));
+        assert!(!filter.matches(&event(Level::INFO, "sn_node::node", 99)));
+        assert!(!filter.matches(&event(Level::INFO, "sn_node::node", 200)));
+    }
+
+    #[test]
+    fn read_cmd_outputs_text_and_json() -> Result<()> {
+        let dir = tempdir()?;
+        let (layer, writer) = BinaryLogLayer::new(100);
+        let guard = writer.spawn(rotation(dir.path(), u64::MAX))?;
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            error!(code = 7, "it broke");
+            trace!("noise");
+        });
+        drop(guard);
+
+        let file = dir.path().join("sn_node.blog");
+        let mut out = Vec::new();
+        LogsCmd::from_iter(["logs", "read", "--level", "info", file.to_str().unwrap()])
+            .run(&mut out)?;
+        let text = String::from_utf8(out)?;
+        assert_eq!(text.lines().count(), 1);
+        assert!(text.contains("ERROR"));
+        assert!(text.ends_with(" it broke code=7\n"));
+
+        let mut out = Vec::new();
+        LogsCmd::from_iter(["logs", "read", "--json", file.to_str().unwrap()]).run(&mut out)?;
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<serde_json::Result<_>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "ERROR");
+        assert_eq!(lines[0]["fields"]["code"], 7);
+        assert_eq!(lines[1]["fields"]["message"], "noise");
+
+        Ok(())
+    }
+}
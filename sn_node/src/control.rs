@@ -0,0 +1,268 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Operator requests to a running node.
+//!
+//! `sn_node control prepare-restart` leaves a request file in the node's root dir, which the node
+//! picks up within `CONTROL_POLL_INTERVAL`. The file is written to a temporary name then renamed,
+//! so the node never reads a partial request, and checksummed, so it never acts on a damaged one.
+//!
+//! `sn_node control msg-audit` asks the node over its control socket instead, which it serves
+//! when started with `--control-sockets`, as it's about what the node is doing right now.
+
+use crate::persisted::{self, Error as PersistedError};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{io::BufRead, os::unix::net::UnixStream};
+use structopt::StructOpt;
+
+/// Name of the pending restart request file in the node's root dir.
+pub const PREPARE_RESTART_FILE: &str = "prepare_restart";
+// Format of the request file, JSON.
+const PREPARE_RESTART_VERSION: u16 = 1;
+/// How often the node checks for requests.
+pub const CONTROL_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Name of the socket in the node's root dir serving all cmds, privileged ones included.
+pub const CONTROL_SOCKET: &str = "control.sock";
+
+/// A request for the node to get ready for a restart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrepareRestart {
+    /// How long the node expects to be away, at most.
+    pub within: Duration,
+    /// Whether to push the chunks it holds to the adults that would replace it as their holders.
+    pub drain: bool,
+}
+
+/// Requests to a running node.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_node control")]
+pub enum ControlCmd {
+    /// Tell the node's elders it's about to restart, so they don't treat its absence as a
+    /// departure for as long as given
+    PrepareRestart {
+        /// The node's root dir
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+        /// How long the node will be away, at most, e.g. `90s`, `5m`. Capped at 15 minutes.
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        within: Duration,
+        /// Also push the node's chunks to the adults that would hold them in its absence
+        #[structopt(long)]
+        drain: bool,
+    },
+    /// Dump the last msgs the node received and sent, with what became of them, as JSON
+    MsgAudit {
+        /// The node's root dir
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+        /// The node's control token, if it was started with one
+        #[structopt(long)]
+        token: Option<String>,
+    },
+}
+
+impl ControlCmd {
+    /// Runs the command, writing its output to `out`.
+    pub fn run(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::PrepareRestart {
+                root_dir,
+                within,
+                drain,
+            } => {
+                let request = PrepareRestart {
+                    within: *within,
+                    drain: *drain,
+                };
+                write_request(root_dir, &request)?;
+                writeln!(
+                    out,
+                    "Restart request left for the node at {}, it'll be picked up within {:?}",
+                    root_dir.display(),
+                    CONTROL_POLL_INTERVAL
+                )
+            }
+            Self::MsgAudit { root_dir, token } => {
+                let request = json!({ "cmd": "msg_audit", "token": token });
+                let audit = request_control(root_dir, &request)?;
+                serde_json::to_writer_pretty(&mut *out, &audit)?;
+                writeln!(out)
+            }
+        }
+    }
+}
+
+/// Sends `request` to the control socket of the node with the given root dir, returning what it
+/// was answered with, or the reason it was denied as an error.
+#[cfg(unix)]
+pub fn request_control(root_dir: &Path, request: &Value) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(root_dir.join(CONTROL_SOCKET))?;
+    serde_json::to_writer(&mut stream, request)?;
+    stream.write_all(b"\n")?;
+    let mut line = String::new();
+    let _len = io::BufReader::new(stream).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)?;
+    match response.get_mut("ok") {
+        Some(ok) => Ok(ok.take()),
+        None => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Request denied: {}", response["error"]),
+        )),
+    }
+}
+
+/// The node's control socket is a Unix socket.
+#[cfg(not(unix))]
+pub fn request_control(_root_dir: &Path, _request: &Value) -> io::Result<Value> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The node's control socket is only served on Unix",
+    ))
+}
+
+/// Leaves a restart request for the node with the given root dir.
+pub fn write_request(root_dir: &Path, request: &PrepareRestart) -> io::Result<()> {
+    let json = serde_json::to_vec(request)?;
+    persisted::write(
+        &root_dir.join(PREPARE_RESTART_FILE),
+        PREPARE_RESTART_VERSION,
+        &json,
+    )
+}
+
+/// Takes the pending restart request for the node with the given root dir, if any. A damaged
+/// request is discarded with a warning, for the operator to make it again.
+pub fn take_request(root_dir: &Path) -> io::Result<Option<PrepareRestart>> {
+    let path = root_dir.join(PREPARE_RESTART_FILE);
+    let read = match persisted::read(&path) {
+        Ok(Some(read)) => Ok(read),
+        Ok(None) => return Ok(None),
+        Err(PersistedError::Io(_, err)) => return Err(err),
+        Err(error) => Err(error),
+    };
+    fs::remove_file(&path)?;
+
+    // Version 0 requests, from before files were framed, are the same JSON.
+    let request = read.and_then(|read| match read.version {
+        PREPARE_RESTART_VERSION | 0 => {
+            serde_json::from_slice(&read.payload).map_err(|err| PersistedError::corrupt(&path, err))
+        }
+        found => Err(PersistedError::WrongVersion {
+            path: path.clone(),
+            found,
+            expected: PREPARE_RESTART_VERSION,
+        }),
+    });
+    match request {
+        Ok(request) => Ok(Some(request)),
+        Err(error) => {
+            warn!("Discarding restart request: {}", error);
+            Ok(None)
+        }
+    }
+}
+
+/// Parses a duration given in seconds, minutes or hours, e.g. `90s`, `5m`, `1h`. Bare numbers
+/// are seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let (number, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {:?}", input))?;
+    let secs = match unit {
+        "s" => Some(number),
+        "m" => number.checked_mul(60),
+        "h" => number.checked_mul(3600),
+        _ => return Err(format!("Invalid duration unit in {:?}", input)),
+    };
+    secs.map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration too long: {:?}", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn durations_parse() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 3600 + 1)).is_err());
+        assert_eq!(
+            parse_duration(&format!("{}h", u64::MAX / 3600)),
+            Ok(Duration::from_secs(u64::MAX / 3600 * 3600))
+        );
+    }
+
+    #[test]
+    fn request_is_taken_once() -> Result<()> {
+        let root = tempdir()?;
+        assert_eq!(take_request(root.path())?, None);
+
+        let cmd = ControlCmd::from_iter_safe([
+            "control",
+            "prepare-restart",
+            root.path().to_str().unwrap_or_default(),
+            "--within",
+            "5m",
+            "--drain",
+        ])?;
+        cmd.run(&mut io::sink())?;
+
+        let expected = PrepareRestart {
+            within: Duration::from_secs(300),
+            drain: true,
+        };
+        assert_eq!(take_request(root.path())?, Some(expected));
+        assert_eq!(take_request(root.path())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn damaged_requests_are_discarded() -> Result<()> {
+        let root = tempdir()?;
+        let path = root.path().join(PREPARE_RESTART_FILE);
+        let request = PrepareRestart {
+            within: Duration::from_secs(300),
+            drain: false,
+        };
+        write_request(root.path(), &request)?;
+
+        for way in 0..persisted::damage::ways(&path)? {
+            write_request(root.path(), &request)?;
+            persisted::damage::apply(&path, way)?;
+            assert_eq!(take_request(root.path())?, None, "damage #{}", way);
+            assert!(!path.exists());
+        }
+
+        // Requests left by a node from before request files were framed are still taken.
+        fs::write(&path, serde_json::to_vec(&request)?)?;
+        assert_eq!(take_request(root.path())?, Some(request));
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Result;
+
+use sled::{Db, IVec, Tree};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const APPLIED_OPS_TREE: &[u8] = b"applied_ops";
+
+/// How long the keys of applied ops are kept. Longer than an op can be retried for: clients
+/// give up on cmds within minutes, and adults are back from a restart within 15 minutes, after
+/// which they're replaced rather than retried.
+pub(crate) const APPLIED_OPS_TTL: Duration = Duration::from_secs(30 * 60);
+/// Most keys of applied ops kept, the oldest being dropped beyond it.
+pub(crate) const MAX_APPLIED_OPS: usize = 100_000;
+// Ops recorded between collections of the expired keys.
+const OPS_PER_GC: usize = 1_000;
+
+/// Keys of the ops applied to a db lately, for retries of an op not to be applied again.
+///
+/// Kept in a tree of the db the ops are applied to, so an op can be applied and its key
+/// recorded in one transaction, see `EventStore::append_once`.
+#[derive(Clone, Debug)]
+pub(crate) struct AppliedOps {
+    tree: Tree,
+    ttl: Duration,
+    max: usize,
+    since_gc: Arc<AtomicUsize>,
+}
+
+impl AppliedOps {
+    pub(crate) fn new(db: &Db) -> Result<Self> {
+        Self::with_limits(db, APPLIED_OPS_TTL, MAX_APPLIED_OPS)
+    }
+
+    pub(crate) fn with_limits(db: &Db, ttl: Duration, max: usize) -> Result<Self> {
+        let applied = Self {
+            tree: db.open_tree(APPLIED_OPS_TREE)?,
+            ttl,
+            max,
+            since_gc: Arc::new(AtomicUsize::new(0)),
+        };
+        let _removed = applied.gc()?;
+        Ok(applied)
+    }
+
+    /// Whether the op of `key` was applied.
+    pub(crate) fn contains(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.tree.contains_key(key)?)
+    }
+
+    /// Forgets the op of `key`, e.g. as what it was applied to is gone.
+    pub(crate) fn forget(&self, key: &[u8]) -> Result<()> {
+        let _prev = self.tree.remove(key)?;
+        Ok(())
+    }
+
+    pub(crate) fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// The value to record a key with, as of now.
+    pub(crate) fn timestamp() -> IVec {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        IVec::from(&secs.to_be_bytes())
+    }
+
+    /// To be called once an op is recorded, collecting the expired keys every so often.
+    pub(crate) fn recorded(&self) -> Result<()> {
+        if self.since_gc.fetch_add(1, Ordering::Relaxed) + 1 >= OPS_PER_GC {
+            let _removed = self.gc()?;
+        }
+        Ok(())
+    }
+
+    /// Drops the keys older than the TTL, and then the oldest beyond the max, returning how
+    /// many were dropped.
+    pub(crate) fn gc(&self) -> Result<usize> {
+        self.since_gc.store(0, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let expiry = now.saturating_sub(self.ttl.as_secs());
+
+        let mut kept = vec![];
+        let mut removed = 0;
+        for entry in self.tree.iter() {
+            let (key, stamp) = entry?;
+            let recorded_at = stamp
+                .as_ref()
+                .try_into()
+                .map(u64::from_be_bytes)
+                .unwrap_or_default();
+            if recorded_at < expiry {
+                let _prev = self.tree.remove(&key)?;
+                removed += 1;
+            } else {
+                kept.push((recorded_at, key));
+            }
+        }
+
+        if kept.len() > self.max {
+            kept.sort();
+            let excess = kept.len() - self.max;
+            for (_, key) in kept.into_iter().take(excess) {
+                let _prev = self.tree.remove(key)?;
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            debug!("Dropped {} keys of applied ops", removed);
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn expired_and_excess_keys_are_dropped() -> Result<()> {
+        let dir = tempdir()?;
+        let db = sled::open(dir.path())?;
+        let applied = AppliedOps::with_limits(&db, Duration::from_secs(60), 2)?;
+
+        let expired = (SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            - Duration::from_secs(61))
+        .as_secs();
+        let _prev = applied.tree().insert(b"expired", &expired.to_be_bytes())?;
+        for key in [b"first", b"other", b"third"] {
+            let _prev = applied.tree().insert(key, AppliedOps::timestamp())?;
+        }
+
+        assert_eq!(applied.gc()?, 2);
+        assert!(!applied.contains(b"expired")?);
+        assert_eq!(applied.tree().len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Result;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sn_interface::types::ChunkAddress;
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// Where the bytes of the chunks of a `ChunkStore` are kept, under its index, its accounting of
+/// the space used and its scheduling of reads.
+///
+/// Backends are written to as the index says, so they needn't be consistent with it
+/// themselves: a write or deletion interrupted is brought in line on the next start with
+/// `recover`, and the index is rebuilt from `list` when it's missing.
+pub(crate) trait ChunkBackend: Send + Sync {
+    /// Which backend this is.
+    fn kind(&self) -> ChunkBackendKind;
+
+    /// Stores the chunk at `address`, replacing any already there, synced to disk once the
+    /// future resolves. Leaves nothing of the chunk behind if it fails.
+    fn put<'a>(&'a self, address: &'a ChunkAddress, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+
+    /// Stores the chunk at `address` as `put` does, but leaves syncing it to disk to a later
+    /// `sync`. For chunks already made durable elsewhere, in the write-ahead journal.
+    fn put_unsynced<'a>(
+        &'a self,
+        address: &'a ChunkAddress,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Syncs to disk the chunks at `addresses` stored with `put_unsynced`, skipping those
+    /// deleted since.
+    fn sync<'a>(&'a self, addresses: &'a [ChunkAddress]) -> BoxFuture<'a, Result<()>>;
+
+    /// Reads back the chunk at `address`, failing with an `Error::ChunkNotFound`, or an I/O
+    /// error of `NotFound`, if there's none.
+    fn get<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<Vec<u8>>>;
+
+    /// Removes the chunk at `address`, failing if there's none.
+    fn delete<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<()>>;
+
+    /// Lists all the chunks held, going through the whole backend, and removes what's left of
+    /// interrupted writes. Takes minutes on large stores.
+    fn list(&self) -> Result<Listing>;
+
+    /// Cleans up after an interrupted write or deletion of the chunk at `address`, returning
+    /// the size of the chunk if it's held in the end.
+    fn recover(&self, address: &ChunkAddress) -> Result<Option<u64>>;
+
+    /// Bytes the backend takes on disk, with its overheads.
+    fn used_space(&self) -> Result<u64>;
+}
+
+/// A chunk found listing a backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StoredChunk {
+    pub(crate) address: ChunkAddress,
+    pub(crate) size: u64,
+    /// When it was stored, as seconds since the Unix epoch, if known.
+    pub(crate) stored_at: Option<u64>,
+}
+
+/// What listing a backend found.
+#[derive(Debug, Default)]
+pub(crate) struct Listing {
+    pub(crate) chunks: Vec<StoredChunk>,
+    /// What was left of interrupted writes, and removed.
+    pub(crate) partial_writes_removed: usize,
+}
+
+/// The backends chunks can be kept in, chosen with `--chunk-backend`.
+///
+/// A store keeps the backend it was created with, as recorded in its layout file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkBackendKind {
+    /// A file per chunk, in a tree of dirs of the bits of their names. Best for large chunks
+    /// on disks with a fast filesystem.
+    #[default]
+    Files,
+    /// All chunks in an embedded sled db. Spares the filesystem a file and dirs per chunk, at
+    /// the cost of compactions.
+    Sled,
+}
+
+impl ChunkBackendKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Files => "files",
+            Self::Sled => "sled",
+        }
+    }
+}
+
+impl Display for ChunkBackendKind {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.name())
+    }
+}
+
+impl FromStr for ChunkBackendKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        [Self::Files, Self::Sled]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| format!("Unknown chunk backend {:?}, expected files or sled", name))
+    }
+}
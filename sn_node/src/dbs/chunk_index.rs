@@ -0,0 +1,300 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{deserialise, serialise, Error, Result, StorageInitError, SLED_FLUSH_TIME_MS};
+
+use sn_interface::types::ChunkAddress;
+
+use serde::{Deserialize, Serialize};
+use sled::{
+    transaction::{TransactionError, Transactional},
+    Db, Tree,
+};
+use std::{
+    io,
+    ops::Bound,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use xor_name::XorName;
+
+const CHUNK_INDEX_DB_NAME: &str = "chunk_index";
+const ENTRIES_TREE: &[u8] = b"entries";
+const PENDING_TREE: &[u8] = b"pending";
+// Set once the index was reconciled with the chunk files, i.e. can be relied on.
+const RECONCILED_KEY: &[u8] = b"reconciled";
+
+/// What's recorded of a stored chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct IndexEntry {
+    /// Size of the chunk, in bytes.
+    pub(crate) size: u64,
+    /// When the chunk was stored, in secs since the Unix epoch.
+    pub(crate) stored_at: u64,
+}
+
+impl IndexEntry {
+    /// An entry for a chunk of `size` stored now.
+    pub(crate) fn now(size: u64) -> Self {
+        let stored_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self { size, stored_at }
+    }
+}
+
+/// The chunks held in the `ChunkStore`, so they can be counted and listed without walking the
+/// chunk dir.
+///
+/// A chunk is indexed once its file is in place, and unindexed once its file is removed. Each
+/// change is first recorded as pending, so a crash between the file and the index changing
+/// leaves a pending name for `ChunkStore` to repair from on the next start, without a walk.
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkIndex {
+    db: Db,
+    entries: Tree,
+    pending: Tree,
+}
+
+impl ChunkIndex {
+    /// Opens the index in `root`, starting over with an empty, unreconciled one if the
+    /// existing one can't be opened, unless that's because another process holds it.
+    pub(crate) fn open(root: &Path) -> Result<Self> {
+        let path = Self::path(root);
+        let open = || {
+            sled::Config::default()
+                .path(&path)
+                .flush_every_ms(SLED_FLUSH_TIME_MS)
+                .open()
+        };
+        let db = match open() {
+            Ok(db) => db,
+            Err(sled::Error::Io(error)) if error.kind() == io::ErrorKind::PermissionDenied => {
+                return Err(Error::io_at(&path, error));
+            }
+            Err(error) if is_locked(&error) => {
+                return Err(StorageInitError::CorruptIndex {
+                    path: path.clone(),
+                    cause: error.to_string(),
+                    recovery_attempted: false,
+                    suggestion: format!(
+                        "Make sure no other node is running with the root dir {}",
+                        root.display()
+                    ),
+                }
+                .into());
+            }
+            Err(error) => {
+                warn!(
+                    "Chunk index at {:?} can't be opened ({}), starting a new one",
+                    path, error
+                );
+                let started_over = std::fs::remove_dir_all(&path)
+                    .map_err(Error::from)
+                    .and_then(|()| Ok(open()?));
+                match started_over {
+                    Ok(db) => db,
+                    Err(retry_error) => {
+                        return Err(StorageInitError::CorruptIndex {
+                            cause: format!("{}, then starting a new one: {}", error, retry_error),
+                            recovery_attempted: true,
+                            suggestion: format!(
+                                "Move {} away and restart the node, to have the index rebuilt \
+                                from the chunk files",
+                                path.display()
+                            ),
+                            path,
+                        }
+                        .into());
+                    }
+                }
+            }
+        };
+
+        Ok(Self {
+            entries: db.open_tree(ENTRIES_TREE)?,
+            pending: db.open_tree(PENDING_TREE)?,
+            db,
+        })
+    }
+
+    /// Where the index of the store in `root` is.
+    pub(crate) fn path(root: &Path) -> PathBuf {
+        root.join("db").join(CHUNK_INDEX_DB_NAME)
+    }
+
+    /// Drops all of the index, for it to be rebuilt from the chunk files.
+    pub(crate) fn clear(&self) -> Result<()> {
+        self.set_reconciled(false)?;
+        self.entries.clear()?;
+        self.pending.clear()?;
+        Ok(())
+    }
+
+    /// Writes any buffered changes to disk.
+    pub(crate) async fn flush(&self) -> Result<()> {
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+
+    /// Whether the index was reconciled with the chunk files, since it was created.
+    pub(crate) fn is_reconciled(&self) -> Result<bool> {
+        Ok(self.db.contains_key(RECONCILED_KEY)?)
+    }
+
+    pub(crate) fn set_reconciled(&self, reconciled: bool) -> Result<()> {
+        if reconciled {
+            let _prev = self.db.insert(RECONCILED_KEY, &[])?;
+        } else {
+            let _prev = self.db.remove(RECONCILED_KEY)?;
+        }
+        Ok(())
+    }
+
+    /// Records that the file of the chunk at `addr` is about to change.
+    pub(crate) fn begin(&self, addr: &ChunkAddress) -> Result<()> {
+        let _prev = self.pending.insert(addr.name(), &[])?;
+        Ok(())
+    }
+
+    /// Drops the pending change of the chunk at `addr`, its file being unchanged.
+    pub(crate) fn abandon(&self, addr: &ChunkAddress) -> Result<()> {
+        let _prev = self.pending.remove(addr.name())?;
+        Ok(())
+    }
+
+    /// Indexes the chunk at `addr`, completing its pending change, in one transaction.
+    pub(crate) fn commit_insert(&self, addr: &ChunkAddress, entry: IndexEntry) -> Result<()> {
+        let entry = serialise(&entry)?;
+        self.commit(addr, Some(entry))
+    }
+
+    /// Unindexes the chunk at `addr`, completing its pending change, in one transaction.
+    pub(crate) fn commit_remove(&self, addr: &ChunkAddress) -> Result<()> {
+        self.commit(addr, None)
+    }
+
+    fn commit(&self, addr: &ChunkAddress, entry: Option<Vec<u8>>) -> Result<()> {
+        let key = addr.name().0;
+        let result = (&self.entries, &self.pending).transaction(|(entries, pending)| {
+            match &entry {
+                Some(entry) => {
+                    let _prev = entries.insert(&key, entry.as_slice())?;
+                }
+                None => {
+                    let _prev = entries.remove(&key)?;
+                }
+            }
+            let _prev = pending.remove(&key)?;
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(TransactionError::Abort(())) => Err(Error::SledBatching),
+            Err(TransactionError::Storage(error)) => Err(Error::Sled(error)),
+        }
+    }
+
+    /// Chunks whose file was changing when we last stopped.
+    pub(crate) fn pending(&self) -> Result<Vec<ChunkAddress>> {
+        self.pending
+            .iter()
+            .keys()
+            .map(|key| Ok(ChunkAddress(to_name(&key?)?)))
+            .collect()
+    }
+
+    pub(crate) fn contains(&self, addr: &ChunkAddress) -> Result<bool> {
+        Ok(self.entries.contains_key(addr.name())?)
+    }
+
+    pub(crate) fn get(&self, addr: &ChunkAddress) -> Result<Option<IndexEntry>> {
+        match self.entries.get(addr.name())? {
+            Some(entry) => Ok(Some(deserialise(&entry)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of chunks indexed.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Addresses of the chunks indexed, in order of their names.
+    pub(crate) fn addresses(&self) -> Result<Vec<ChunkAddress>> {
+        self.entries
+            .iter()
+            .keys()
+            .map(|key| Ok(ChunkAddress(to_name(&key?)?)))
+            .collect()
+    }
+
+    /// Addresses of up to `count` chunks indexed with names after `after`, or from the first
+    /// without one, in order of their names.
+    pub(crate) fn addresses_after(
+        &self,
+        after: Option<&XorName>,
+        count: usize,
+    ) -> Result<Vec<ChunkAddress>> {
+        let keys = match after {
+            Some(name) => self
+                .entries
+                .range::<&[u8], _>((Bound::Excluded(&name.0[..]), Bound::Unbounded)),
+            None => self.entries.iter(),
+        };
+        keys.keys()
+            .take(count)
+            .map(|key| Ok(ChunkAddress(to_name(&key?)?)))
+            .collect()
+    }
+
+    /// The chunks indexed, in order of their names.
+    pub(crate) fn entries(&self) -> Result<Vec<(ChunkAddress, IndexEntry)>> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let (key, entry) = entry?;
+                Ok((ChunkAddress(to_name(&key)?), deserialise(&entry)?))
+            })
+            .collect()
+    }
+
+    /// Indexes the chunk at `addr` outside of any pending change, to repair the index.
+    pub(crate) fn insert(&self, addr: &ChunkAddress, entry: IndexEntry) -> Result<()> {
+        let _prev = self.entries.insert(addr.name(), serialise(&entry)?)?;
+        Ok(())
+    }
+
+    /// Records `entry` as is under `key`, to corrupt the index.
+    #[cfg(test)]
+    pub(crate) fn insert_raw(&self, key: &[u8], entry: &[u8]) -> Result<()> {
+        let _prev = self.entries.insert(key, entry)?;
+        Ok(())
+    }
+
+    /// Unindexes the chunk at `addr` outside of any pending change, to repair the index.
+    pub(crate) fn remove(&self, addr: &ChunkAddress) -> Result<()> {
+        let _prev = self.entries.remove(addr.name())?;
+        Ok(())
+    }
+}
+
+// Whether the index couldn't be opened for being held by another process, e.g. another node
+// running with the same root dir.
+fn is_locked(error: &sled::Error) -> bool {
+    matches!(error, sled::Error::Io(error) if error.to_string().contains("could not acquire lock"))
+}
+
+fn to_name(key: &[u8]) -> Result<XorName> {
+    let name = key
+        .try_into()
+        .map_err(|_| Error::CouldNotParseDbKey(key.to_vec()))?;
+    Ok(XorName(name))
+}
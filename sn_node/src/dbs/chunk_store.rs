@@ -6,174 +6,866 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{Error, Result};
+#[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+use super::faulty_disk::FaultyDisk;
+#[cfg(any(test, feature = "metrics"))]
+use super::read_scheduler::ReadPacing;
+use super::{
+    chunk_backend::{ChunkBackend, ChunkBackendKind},
+    chunk_index::{ChunkIndex, IndexEntry},
+    encrypted_backend::{ChunkKey, EncryptedBackend},
+    file_backend::{FileBackend, CHUNK_DB_DIR},
+    kv_backend::{KvBackend, CHUNK_KV_DIR},
+    read_scheduler::{ReadClass, ReadScheduler, ReadThrottle},
+    write_journal::{JournalRecord, WriteJournal},
+    Error, Result, SpaceCategory, StorageInitError,
+};
 
-use crate::UsedSpace;
+use crate::{
+    node::{read_reward_secret_key, read_storage_key, reward_secret_key_path, storage_key_path},
+    persisted::{self, Error as PersistedError},
+    UsedSpace,
+};
 use sn_interface::types::{Chunk, ChunkAddress};
 
 use bytes::Bytes;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+#[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
 use tokio::io::AsyncWriteExt;
-use walkdir::WalkDir;
-use xor_name::{Prefix, XorName};
+use xor_name::XorName;
 
-const BIT_TREE_DEPTH: usize = 20;
-const CHUNK_DB_DIR: &str = "chunkdb";
+// Written next to, rather than within, the chunk dir so it's never mistaken for a chunk.
+const SELF_TEST_FILE: &str = "disk_self_test";
+const SELF_TEST_PATTERN_LEN: usize = 4096;
+// Written in the chunk dir to check it's writable. Were it left behind, reconciliation removes
+// it as a partial write.
+const WRITE_PROBE_FILE: &str = "write_probe.tmp";
+// Records the layout of the chunks and their backend, next to the chunk dir.
+const LAYOUT_FILE: &str = "chunk_store_layout";
+// Format of the layout file, the layout version, the backend and whether the chunks are
+// encrypted as `<version> <backend> <plain|encrypted>`. Layout files of the first format hold
+// the layout version alone, of a store of files, and of the second the layout version and the
+// backend, of a store of plain chunks.
+const LAYOUT_FILE_VERSION: u16 = 3;
+const PLAIN: &str = "plain";
+const ENCRYPTED: &str = "encrypted";
+// Layout of the chunks: for files, a bit tree `BIT_TREE_DEPTH` dirs deep of the bits of their
+// names, of files named after the zbase32 encoding of their address; for sled, a db of chunks
+// keyed by their name.
+const LAYOUT_VERSION: u16 = 1;
+// Free space needed to start a store when none is reserved for the dbs, for its index.
+const MIN_SPACE_TO_START: u64 = 4 * 1024 * 1024;
+// Where corrupt chunks found scrubbing are moved to, next to the chunk dir, named after the hex
+// of their names. Left for the operator to inspect and clear.
+const QUARANTINE_DIR: &str = "chunk_quarantine";
+
+/// What scrubbing a chunk found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Scrubbed {
+    /// It still hashes to its name.
+    Intact,
+    /// It didn't, or was gone from the backend or undecryptable, and was quarantined.
+    Quarantined,
+    /// It's no longer held, having been deleted meanwhile.
+    Gone,
+}
+
+/// How a store keeps its chunks, as recorded in its layout file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct StoreLayout {
+    pub(crate) backend: ChunkBackendKind,
+    /// Whether the chunks are encrypted, with the storage key persisted along the node's keys.
+    pub(crate) encrypted: bool,
+}
+
+/// What a reconciliation of the chunk index with the chunks held changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Reconciliation {
+    /// Chunks found, which weren't indexed.
+    pub(crate) indexed: usize,
+    /// Indexed chunks which were gone.
+    pub(crate) unindexed: usize,
+    /// Indexed chunks of another size than indexed.
+    pub(crate) resized: usize,
+    /// What was left of interrupted writes.
+    pub(crate) partial_writes_removed: usize,
+}
+
+impl Reconciliation {
+    /// Whether the index matched the chunks held.
+    pub(crate) fn was_consistent(&self) -> bool {
+        *self == Self::default()
+    }
+}
 
 /// A disk store for chunks
+///
+/// The chunks are kept by a `ChunkBackend`, files or a sled db, as the store was created with,
+/// encrypted through an `EncryptedBackend` if it was created so.
+///
+/// The chunks held are listed and counted from the `ChunkIndex`, never by going through the
+/// backend, which takes minutes on large stores. That's only done to reconcile the index with
+/// the backend, on demand or when the index is missing.
+///
+/// Chunk reads go through the `ReadScheduler`, which holds background reads back for the
+/// interactive ones to keep their latency.
+///
+/// Chunk writes go through the `WriteJournal` once it's enabled, for bursts of them to share
+/// their fsyncs. It's replayed with `replay_journal` before the store is used.
 #[derive(Clone)]
 pub(crate) struct ChunkStore {
-    bit_tree_depth: usize,
-    chunk_store_path: PathBuf,
+    root: PathBuf,
+    backend: Arc<dyn ChunkBackend>,
+    key: Option<ChunkKey>,
     used_space: UsedSpace,
+    index: ChunkIndex,
+    reads: ReadScheduler,
+    journal: WriteJournal,
+    // What the self-test file is read and written through, as are the chunk files, to simulate
+    // a failing disk.
+    #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+    disk: FaultyDisk,
 }
 
 impl ChunkStore {
-    /// Creates a new `ChunkStore` at location `root/CHUNK_DB_DIR`
+    /// Creates a new `ChunkStore` at location `root`, of the backend it was created with, or of
+    /// files for a new one
     ///
     /// If the location specified already contains a ChunkStore, it is simply used
     ///
     /// Used space of the dir is tracked
+    ///
+    /// The chunk index is repaired for the chunks whose write or deletion was interrupted, or
+    /// rebuilt from the backend if it's missing or corrupt. Why the store can't be initialised,
+    /// if it can't, is told by a `StorageInitError`.
+    ///
+    /// The chunks of an encrypted store are decrypted with the storage key persisted in `root`.
     pub(crate) fn new<P: AsRef<Path>>(root: P, used_space: UsedSpace) -> Result<Self> {
-        let chunk_store_path = root.as_ref().join(CHUNK_DB_DIR);
+        let root = root.as_ref();
+        let layout = Self::check(root, &used_space, None, false)?;
+        let kind = layout.backend;
+        let key = if layout.encrypted {
+            Some(load_key(root)?)
+        } else {
+            None
+        };
+        #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+        let disk = FaultyDisk::default();
+        let backend: Arc<dyn ChunkBackend> = match kind {
+            ChunkBackendKind::Files => {
+                let files = FileBackend::new(root, &used_space);
+                #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+                let files = files.with_disk(disk.clone());
+                Arc::new(files)
+            }
+            ChunkBackendKind::Sled => Arc::new(KvBackend::open(root)?),
+        };
+        let backend = encrypted(backend, &key);
 
-        Ok(ChunkStore {
-            bit_tree_depth: BIT_TREE_DEPTH,
-            chunk_store_path,
+        let journal = WriteJournal::new(root, key.clone());
+        let store = ChunkStore {
+            root: root.to_path_buf(),
+            backend,
+            key,
             used_space,
-        })
+            index: ChunkIndex::open(root)?,
+            reads: ReadScheduler::default(),
+            journal,
+            #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+            disk,
+        };
+
+        let indexed_size = match store.load_index() {
+            Ok(size) => size,
+            Err(Error::Io(error)) if error.kind() == io::ErrorKind::PermissionDenied => {
+                return Err(Error::io_at(&backend_dir(root, kind), error));
+            }
+            Err(error) => {
+                warn!(
+                    "ChunkStore: chunk index is corrupt ({}), rebuilding it from the chunks held",
+                    error
+                );
+                store.rebuild_index().map_err(|rebuild_error| {
+                    let path = ChunkIndex::path(root);
+                    StorageInitError::CorruptIndex {
+                        cause: format!("{}, then rebuilding it: {}", error, rebuild_error),
+                        recovery_attempted: true,
+                        suggestion: format!(
+                            "Move {} away and restart the node, to have the index rebuilt from \
+                            the chunks held",
+                            path.display()
+                        ),
+                        path,
+                    }
+                })?
+            }
+        };
+        store
+            .used_space
+            .increase(indexed_size as usize, SpaceCategory::Chunks);
+
+        Ok(store)
     }
 
-    // ---------------------- helper methods ----------------------
+    /// The storage key to persist in `root` before the store there is checked, if it's to be
+    /// encrypted and there's no key yet: a random one for a new store, or for a store encrypted
+    /// before it had a key of its own, the key its chunks were encrypted with, derived from the
+    /// reward secret key.
+    pub(crate) fn new_key(root: &Path, encrypt: bool) -> Result<Option<[u8; 32]>> {
+        let unavailable =
+            |path: PathBuf, reason: String| StorageInitError::ChunkKeyUnavailable { path, reason };
+        match read_storage_key(root) {
+            Ok(Some(_)) => return Ok(None),
+            Ok(None) => {}
+            Err(error) => return Err(unavailable(storage_key_path(root), error.to_string()).into()),
+        }
+        match read_layout(&root.join(LAYOUT_FILE))? {
+            Some(layout) if layout.encrypted => match read_reward_secret_key(root) {
+                Ok(Some(secret_key)) => {
+                    info!("Taking the key of the encrypted chunk store over as its storage key");
+                    Ok(Some(ChunkKey::legacy(&secret_key)))
+                }
+                Ok(None) => Err(unavailable(
+                    reward_secret_key_path(root),
+                    "there's no reward secret key".to_string(),
+                )
+                .into()),
+                Err(error) => {
+                    Err(unavailable(reward_secret_key_path(root), error.to_string()).into())
+                }
+            },
+            Some(_) => Ok(None),
+            None if encrypt => Ok(Some(rand::random())),
+            None => Ok(None),
+        }
+    }
 
-    // Helper that returns the prefix tree path of depth `bit_count` for a given xorname
-    // Example:
-    // - with a xorname with starting bits `010001110110....`
-    // - and a bit_count of `6`
-    // returns the path `CHUNK_STORE_PATH/0/1/0/0/0/1`
-    // If the provided bit count is larger than `self.bit_tree_depth`, uses `self.bit_tree_depth`
-    // to stay within the prefix tree path
-    fn prefix_tree_path(&self, xorname: XorName, bit_count: usize) -> PathBuf {
-        let bin = format!("{:b}", xorname);
-        let prefix_dir_path: PathBuf = bin
-            .chars()
-            .take(std::cmp::min(bit_count, self.bit_tree_depth))
-            .map(|c| format!("{}", c))
-            .collect();
+    /// Checks a store can be started in `root`: that it's of the `backend` given, if any, that
+    /// it's encrypted if `encrypt` is set, that the dir of its backend is writable, that there's
+    /// space enough on the disk, and that its chunks are of the layout we know. Done before
+    /// joining, so a node doesn't join only to fail on its storage. Returns the layout of the
+    /// store, recording it for a new store.
+    ///
+    /// An encrypted store stays so whether `encrypt` is set or not, as its chunks can only be
+    /// read decrypted. The space needed is the db reserve, or `MIN_SPACE_TO_START` without one.
+    pub(crate) fn check(
+        root: &Path,
+        used_space: &UsedSpace,
+        backend: Option<ChunkBackendKind>,
+        encrypt: bool,
+    ) -> Result<StoreLayout> {
+        let layout_path = root.join(LAYOUT_FILE);
+        let recorded = read_layout(&layout_path)?;
+        // Stores from before the layout file was are of plain files.
+        let has_chunk_files = fs::read_dir(root.join(CHUNK_DB_DIR))
+            .map_or(false, |mut entries| entries.next().is_some());
+        let found = recorded.or_else(|| has_chunk_files.then(StoreLayout::default));
+        let layout = match (found, backend) {
+            (Some(found), Some(configured)) if found.backend != configured => {
+                return Err(StorageInitError::IncompatibleBackend {
+                    path: layout_path,
+                    found: found.backend.to_string(),
+                    configured: configured.to_string(),
+                }
+                .into());
+            }
+            (Some(found), _) if encrypt && !found.encrypted => {
+                return Err(StorageInitError::UnencryptedStore { path: layout_path }.into());
+            }
+            (Some(found), _) => found,
+            (None, configured) => StoreLayout {
+                backend: configured.unwrap_or_default(),
+                encrypted: encrypt,
+            },
+        };
+        if layout.encrypted {
+            let _key = load_key(root)?;
+        }
+        let kind = layout.backend;
+
+        let backend_dir = backend_dir(root, kind);
+        fs::create_dir_all(&backend_dir).map_err(|e| Error::io_at(&backend_dir, e))?;
+        let probe = backend_dir.join(WRITE_PROBE_FILE);
+        fs::write(&probe, []).map_err(|e| Error::io_at(&backend_dir, e))?;
+        fs::remove_file(&probe).map_err(|e| Error::io_at(&backend_dir, e))?;
 
-        let mut path = self.chunk_store_path.clone();
-        path.push(prefix_dir_path);
-        path
+        let required = match used_space.db_reserve() {
+            0 => MIN_SPACE_TO_START,
+            db_reserve => db_reserve as u64,
+        };
+        match used_space.free_space(root) {
+            Ok(available) if (available as u64) < required => {
+                return Err(StorageInitError::InsufficientSpace {
+                    path: root.to_path_buf(),
+                    required,
+                    available: available as u64,
+                }
+                .into());
+            }
+            Ok(_) => {}
+            Err(error) => warn!("Failed to read free disk space at {:?}: {:?}", root, error),
+        }
+
+        if recorded.is_none() {
+            let encryption = if layout.encrypted { ENCRYPTED } else { PLAIN };
+            let payload = format!("{} {} {}", LAYOUT_VERSION, kind, encryption);
+            persisted::write(&layout_path, LAYOUT_FILE_VERSION, payload.as_bytes())
+                .map_err(|error| Error::io_at(&layout_path, error))?;
+        }
+        Ok(layout)
     }
 
-    fn address_to_filepath(&self, addr: &ChunkAddress) -> Result<PathBuf> {
-        let xorname = *addr.name();
-        let filename = addr.encode_to_zbase32()?;
-        let mut path = self.prefix_tree_path(xorname, self.bit_tree_depth);
-        path.push(filename);
-        Ok(path)
+    /// Has our chunks read and written through the given `disk`, whose faults are scripted by
+    /// the test holding a clone of it. Only the self-test goes through it with a backend other
+    /// than files.
+    #[cfg(test)]
+    pub(crate) fn with_disk(mut self, disk: FaultyDisk) -> Self {
+        if self.backend.kind() == ChunkBackendKind::Files {
+            let files = FileBackend::new(&self.root, &self.used_space).with_disk(disk.clone());
+            self.backend = encrypted(Arc::new(files), &self.key);
+        }
+        self.disk = disk;
+        self
     }
 
-    fn filepath_to_address(&self, path: &str) -> Result<ChunkAddress> {
-        let filename = Path::new(path)
-            .file_name()
-            .ok_or(Error::NoFilename)?
-            .to_str()
-            .ok_or(Error::InvalidFilename)?;
-        Ok(ChunkAddress::decode_from_zbase32(filename)?)
+    /// The disk the chunk files are read and written through.
+    #[cfg(any(test, feature = "chaos"))]
+    pub(crate) fn disk(&self) -> &FaultyDisk {
+        &self.disk
+    }
+
+    // ---------------------- helper methods ----------------------
+
+    // Writes `bytes` to a new file at `path`, synced to disk.
+    async fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+        {
+            self.disk.write(path, bytes, self.used_space.ratio()).await
+        }
+        #[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
+        {
+            let mut file = tokio::fs::File::create(path).await?;
+            file.write_all(bytes).await?;
+            file.sync_all().await
+        }
+    }
+
+    async fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+        {
+            self.disk.read(path).await
+        }
+        #[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
+        {
+            tokio::fs::read(path).await
+        }
     }
 
     // ---------------------- api methods ----------------------
 
     pub(crate) fn can_add(&self, size: usize) -> bool {
-        self.used_space.can_add(size)
+        self.used_space.can_add_chunk(size, &self.root)
     }
 
+    pub(crate) fn approaching_db_reserve(&self) -> bool {
+        self.used_space.approaching_db_reserve(&self.root)
+    }
+
+    pub(crate) fn chunk_headroom(&self) -> usize {
+        self.used_space.chunk_headroom(&self.root)
+    }
+
+    /// The backend the chunks are kept in.
+    pub(crate) fn backend_kind(&self) -> ChunkBackendKind {
+        self.backend.kind()
+    }
+
+    /// Whether the chunks are kept encrypted.
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Bytes the backend takes on disk, with its overheads, unlike the used space which counts
+    /// the bytes of the chunks alone.
+    pub(crate) fn backend_used_space(&self) -> Result<u64> {
+        self.backend.used_space()
+    }
+
+    /// Writes the chunk to the backend, and indexes it once it's there.
+    ///
+    /// With the journal enabled, the chunk is made durable by the journal's group commit, then
+    /// written to the backend unsynced. Otherwise the write is recorded as pending beforehand,
+    /// for a crash before the index is updated to be repaired on the next start. A write which
+    /// fails is undone right away.
     pub(crate) async fn write_chunk(&self, data: &Chunk) -> Result<ChunkAddress> {
+        if !self.journal.is_enabled() {
+            return self.write_chunk_synced(data).await;
+        }
         let addr = data.address();
-        let filepath = self.address_to_filepath(addr)?;
-        if let Some(dirs) = filepath.parent() {
-            tokio::fs::create_dir_all(dirs).await?;
+        self.journal
+            .append(&JournalRecord::Write(data.clone()))
+            .await?;
+
+        let written = match self.backend.put_unsynced(addr, data.value()).await {
+            Ok(()) => self.index_written(data),
+            Err(error) => Err(error),
+        };
+        if written.is_err() {
+            // Not to be redone on replay.
+            if let Err(error) = self.journal.append(&JournalRecord::Removal(*addr)).await {
+                warn!(
+                    "ChunkStore: failed to journal the undoing of the write of {:?}: {}",
+                    addr.name(),
+                    error
+                );
+            }
+        }
+        self.journal.applied();
+        written?;
+
+        if let Err(error) = self.checkpoint_journal().await {
+            warn!("ChunkStore: chunk journal checkpoint failed: {}", error);
         }
+        Ok(*addr)
+    }
 
-        let mut file = tokio::fs::File::create(filepath).await?;
-        file.write_all(data.value()).await?;
+    async fn write_chunk_synced(&self, data: &Chunk) -> Result<ChunkAddress> {
+        let addr = data.address();
+        self.index.begin(addr)?;
+        self.index.flush().await?;
 
-        self.used_space.increase(data.value().len());
+        if let Err(error) = self.backend.put(addr, data.value()).await {
+            self.index.abandon(addr)?;
+            return Err(error);
+        }
+        self.index_written(data)?;
 
         Ok(*addr)
     }
 
+    // Indexes the chunk written, accounting for its size.
+    fn index_written(&self, data: &Chunk) -> Result<()> {
+        let addr = data.address();
+        let size = data.value().len();
+        let replaced = self.index.get(addr)?;
+        self.index
+            .commit_insert(addr, IndexEntry::now(size as u64))?;
+
+        if let Some(replaced) = replaced {
+            self.used_space
+                .decrease(replaced.size as usize, SpaceCategory::Chunks);
+        }
+        self.used_space.increase(size, SpaceCategory::Chunks);
+        Ok(())
+    }
+
+    /// Has the chunk writes appended to the journal within `interval` committed together, or
+    /// each synced on its own if it's zero.
+    pub(crate) fn set_journal_flush_interval(&self, interval: Duration) {
+        self.journal.set_flush_interval(interval)
+    }
+
+    /// Applies the writes and deletions left in the journal by a crash, synced, then empties
+    /// it. Returns the number of records replayed.
+    pub(crate) async fn replay_journal(&self) -> Result<usize> {
+        let records = self.journal.records().await?;
+        for record in &records {
+            match record {
+                JournalRecord::Write(chunk) => {
+                    let _addr = self.write_chunk_synced(chunk).await?;
+                }
+                JournalRecord::Removal(addr) => {
+                    if self.index.contains(addr)? {
+                        self.remove_chunk(addr).await?;
+                    }
+                }
+            }
+        }
+        self.journal.clear().await?;
+        if !records.is_empty() {
+            info!(
+                "ChunkStore: replayed {} records of the chunk journal",
+                records.len()
+            );
+        }
+        Ok(records.len())
+    }
+
+    // Syncs the chunks journaled to the backend and empties the journal, once it's large
+    // enough.
+    async fn checkpoint_journal(&self) -> Result<()> {
+        let checkpoint = match self.journal.checkpoint().await {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        let synced = match self.backend.sync(checkpoint.addresses()).await {
+            Ok(()) => self.index.flush().await,
+            Err(error) => Err(error),
+        };
+        match synced {
+            Ok(()) => Ok(checkpoint.complete().await?),
+            Err(error) => {
+                checkpoint.abort();
+                Err(error)
+            }
+        }
+    }
+
+    // Has the deletion of the chunk at `addr` journaled if its write is, for a replay not to
+    // bring it back.
+    async fn journal_removal(&self, addr: &ChunkAddress) -> Result<()> {
+        if self.journal.is_journaled(addr) {
+            self.journal.append(&JournalRecord::Removal(*addr)).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the chunk at `addr` back, as a background read, and checks it still hashes to its
+    /// name. A chunk which doesn't, or is gone from the backend, or doesn't decrypt, is moved
+    /// out of the store to the quarantine dir, for it to be replicated to us again.
+    ///
+    /// Other read errors are returned as they are, being the disk's rather than the chunk's.
+    pub(crate) async fn scrub_chunk(&self, addr: &ChunkAddress) -> Result<Scrubbed> {
+        if !self.index.contains(addr)? {
+            return Ok(Scrubbed::Gone);
+        }
+        let read_back = match self.read_chunk(addr, ReadClass::Background).await {
+            Ok(chunk) if chunk.address() == addr => return Ok(Scrubbed::Intact),
+            Ok(chunk) => Some(chunk.value().clone()),
+            Err(Error::UndecryptableChunk(_)) | Err(Error::ChunkNotFound(_)) => None,
+            Err(Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error),
+        };
+        // Deleted while we were reading it.
+        if !self.index.contains(addr)? {
+            return Ok(Scrubbed::Gone);
+        }
+
+        warn!(
+            "ChunkStore: chunk {:?} is corrupt, quarantining it",
+            addr.name()
+        );
+        if let Some(bytes) = read_back {
+            let dir = self.root.join(QUARANTINE_DIR);
+            tokio::fs::create_dir_all(&dir).await?;
+            tokio::fs::write(dir.join(hex::encode(addr.name())), bytes).await?;
+        }
+
+        let size = self.index.get(addr)?.map_or(0, |entry| entry.size);
+        self.journal_removal(addr).await?;
+        self.index.begin(addr)?;
+        self.index.flush().await?;
+        match self.backend.delete(addr).await {
+            Ok(()) | Err(Error::ChunkNotFound(_)) => {}
+            Err(Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+        self.index.commit_remove(addr)?;
+        self.used_space
+            .decrease(size as usize, SpaceCategory::Chunks);
+        Ok(Scrubbed::Quarantined)
+    }
+
+    /// Addresses of up to `count` chunks held with names after `after`, or from the first
+    /// without one, in order of their names.
+    pub(crate) fn chunk_addresses_after(
+        &self,
+        after: Option<&XorName>,
+        count: usize,
+    ) -> Result<Vec<ChunkAddress>> {
+        self.index.addresses_after(after, count)
+    }
+
     #[allow(dead_code)]
     pub(crate) async fn delete_chunk(&self, addr: &ChunkAddress) -> Result<()> {
-        let filepath = self.address_to_filepath(addr)?;
-        let meta = tokio::fs::metadata(filepath.clone()).await?;
-        tokio::fs::remove_file(filepath).await?;
-        self.used_space.decrease(meta.len() as usize);
+        self.journal_removal(addr).await?;
+        self.remove_chunk(addr).await
+    }
+
+    async fn remove_chunk(&self, addr: &ChunkAddress) -> Result<()> {
+        let size = self.index.get(addr)?.map_or(0, |entry| entry.size);
+
+        self.index.begin(addr)?;
+        self.index.flush().await?;
+        self.backend.delete(addr).await?;
+        self.index.commit_remove(addr)?;
+
+        self.used_space
+            .decrease(size as usize, SpaceCategory::Chunks);
         Ok(())
     }
 
-    pub(crate) async fn read_chunk(&self, addr: &ChunkAddress) -> Result<Chunk> {
-        let file_path = self.address_to_filepath(addr)?;
-        let bytes = Bytes::from(tokio::fs::read(file_path).await?);
+    /// Reads the chunk at `addr`, once the `ReadScheduler` lets it go ahead if it's a
+    /// background read.
+    pub(crate) async fn read_chunk(&self, addr: &ChunkAddress, class: ReadClass) -> Result<Chunk> {
+        let _interactive = match class {
+            ReadClass::Interactive => Some(self.reads.start_interactive()),
+            ReadClass::Background => {
+                let size = self.index.get(addr)?.map_or(0, |entry| entry.size);
+                self.reads.admit_background(size).await;
+                None
+            }
+        };
+
+        let bytes = Bytes::from(self.backend.get(addr).await?);
         let chunk = Chunk::new(bytes);
         Ok(chunk)
     }
 
-    pub(crate) fn chunk_file_exists(&self, addr: &ChunkAddress) -> Result<bool> {
-        let filepath = self.address_to_filepath(addr)?;
-        Ok(filepath.exists())
+    pub(crate) fn set_read_throttle(&self, throttle: ReadThrottle) {
+        self.reads.set_throttle(throttle)
+    }
+
+    /// Where the pacing of background reads is at.
+    #[cfg(any(test, feature = "metrics"))]
+    pub(crate) fn read_pacing(&self) -> ReadPacing {
+        self.reads.pacing()
+    }
+
+    /// Checks the disk holding the store can be written to and read back: writes a random
+    /// pattern, syncs it to disk, reads it back, compares, and removes it.
+    pub(crate) async fn self_test(&self) -> Result<()> {
+        let path = self.root.join(SELF_TEST_FILE);
+        tokio::fs::create_dir_all(&self.root).await?;
+
+        let pattern: Vec<u8> = (0..SELF_TEST_PATTERN_LEN).map(|_| rand::random()).collect();
+        let written = self.write_file(&path, &pattern).await;
+        let read_back = match written {
+            Ok(()) => self.read_file(&path).await,
+            Err(error) => Err(error),
+        };
+        match tokio::fs::remove_file(&path).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+
+        if read_back? != pattern {
+            return Err(Error::SelfTestMismatch);
+        }
+        Ok(())
     }
 
-    pub(crate) fn list_all_files(&self) -> Result<Vec<String>> {
-        list_files_in(&self.chunk_store_path)
+    pub(crate) fn has_chunk(&self, addr: &ChunkAddress) -> Result<bool> {
+        self.index.contains(addr)
+    }
+
+    /// Number of chunks held.
+    pub(crate) fn chunk_count(&self) -> usize {
+        self.index.len()
     }
 
     pub(crate) fn list_all_chunk_addresses(&self) -> Result<Vec<ChunkAddress>> {
-        let all_files = self.list_all_files()?;
-        let all_addrs = all_files
+        self.index.addresses()
+    }
+
+    /// Reconciles the chunk index with the chunks held, going through the whole backend:
+    /// indexes the chunks which aren't, unindexes those which are gone, and removes what's
+    /// left of interrupted writes.
+    pub(crate) fn reconcile(&self) -> Result<Reconciliation> {
+        let size_before = self.indexed_size()?;
+        let reconciliation = self.reconcile_index()?;
+        let size_after = self.indexed_size()?;
+
+        if size_after > size_before {
+            self.used_space
+                .increase((size_after - size_before) as usize, SpaceCategory::Chunks);
+        } else {
+            self.used_space
+                .decrease((size_before - size_after) as usize, SpaceCategory::Chunks);
+        }
+        Ok(reconciliation)
+    }
+
+    fn reconcile_index(&self) -> Result<Reconciliation> {
+        let mut reconciliation = Reconciliation::default();
+        self.index.set_reconciled(false)?;
+
+        let listing = self.backend.list()?;
+        reconciliation.partial_writes_removed = listing.partial_writes_removed;
+        let mut held = BTreeSet::new();
+        for chunk in listing.chunks {
+            let _new = held.insert(chunk.address);
+            match self.index.get(&chunk.address)? {
+                Some(entry) if entry.size == chunk.size => {}
+                Some(mut entry) => {
+                    entry.size = chunk.size;
+                    self.index.insert(&chunk.address, entry)?;
+                    reconciliation.resized += 1;
+                }
+                None => {
+                    let entry = match chunk.stored_at {
+                        Some(stored_at) => IndexEntry {
+                            size: chunk.size,
+                            stored_at,
+                        },
+                        None => IndexEntry::now(chunk.size),
+                    };
+                    self.index.insert(&chunk.address, entry)?;
+                    reconciliation.indexed += 1;
+                }
+            }
+        }
+
+        for addr in self.index.addresses()? {
+            if !held.contains(&addr) {
+                self.index.remove(&addr)?;
+                reconciliation.unindexed += 1;
+            }
+        }
+        for addr in self.index.pending()? {
+            self.index.abandon(&addr)?;
+        }
+
+        self.index.set_reconciled(true)?;
+        if !reconciliation.was_consistent() {
+            warn!("ChunkStore: chunk index reconciled: {:?}", reconciliation);
+        }
+        Ok(reconciliation)
+    }
+
+    // Repairs or reconciles the index as needed, returning the size of the chunks indexed.
+    fn load_index(&self) -> Result<u64> {
+        if self.index.is_reconciled()? {
+            self.repair_pending()?;
+        } else {
+            info!("ChunkStore: no chunk index, rebuilding it from the chunks held");
+            let _reconciliation = self.reconcile_index()?;
+        }
+        self.indexed_size()
+    }
+
+    fn rebuild_index(&self) -> Result<u64> {
+        self.index.clear()?;
+        let reconciliation = self.reconcile_index()?;
+        info!(
+            "ChunkStore: chunk index rebuilt, {} chunks indexed",
+            reconciliation.indexed
+        );
+        self.indexed_size()
+    }
+
+    fn indexed_size(&self) -> Result<u64> {
+        Ok(self
+            .index
+            .entries()?
             .iter()
-            .map(|filepath| self.filepath_to_address(filepath))
-            .collect();
-        all_addrs
-    }
-
-    #[allow(unused)]
-    /// quickly find chunks related or not to a section, might be useful when adults change sections
-    /// not used yet
-    pub(crate) fn list_files_without_prefix(&self, prefix: Prefix) -> Result<Vec<String>> {
-        let all_files = self.list_all_files()?;
-        let prefix_path = self.prefix_tree_path(prefix.name(), prefix.bit_count());
-        let outside_prefix = all_files
-            .into_iter()
-            .filter(|p| !Path::new(&p).starts_with(&prefix_path.as_path()))
-            .collect();
-        Ok(outside_prefix)
+            .map(|(_, entry)| entry.size)
+            .sum())
     }
 
-    #[allow(unused)]
-    /// quickly find chunks related or not to a section, might be useful when adults change sections
-    /// not used yet
-    pub(crate) fn list_files_with_prefix(&self, prefix: Prefix) -> Result<Vec<String>> {
-        let prefix_path = self.prefix_tree_path(prefix.name(), prefix.bit_count());
-        list_files_in(prefix_path.as_path())
+    // Brings the index in line with the backend for the chunks whose write or deletion was
+    // interrupted.
+    fn repair_pending(&self) -> Result<()> {
+        for addr in self.index.pending()? {
+            match self.backend.recover(&addr)? {
+                Some(size) => {
+                    let entry = match self.index.get(&addr)? {
+                        Some(entry) if entry.size == size => entry,
+                        _ => IndexEntry::now(size),
+                    };
+                    self.index.commit_insert(&addr, entry)?
+                }
+                None => self.index.commit_remove(&addr)?,
+            }
+            debug!("ChunkStore: repaired the index entry of {:?}", addr);
+        }
+        Ok(())
     }
 }
 
-fn list_files_in(path: &Path) -> Result<Vec<String>> {
-    let files = WalkDir::new(path)
-        .into_iter()
-        .filter_map(|e| match e {
-            Ok(direntry) => Some(direntry),
-            Err(err) => {
-                warn!("ChunkStore: failed to process file entry: {}", err);
-                None
+// Where the backend of the store in `root` keeps its chunks.
+fn backend_dir(root: &Path, kind: ChunkBackendKind) -> PathBuf {
+    match kind {
+        ChunkBackendKind::Files => root.join(CHUNK_DB_DIR),
+        ChunkBackendKind::Sled => root.join(CHUNK_KV_DIR),
+    }
+}
+
+// Has the chunks of `backend` encrypted with `key`, if there's one.
+fn encrypted(backend: Arc<dyn ChunkBackend>, key: &Option<ChunkKey>) -> Arc<dyn ChunkBackend> {
+    match key {
+        Some(key) => Arc::new(EncryptedBackend::new(backend, key.clone())),
+        None => backend,
+    }
+}
+
+// Reads the key of the encrypted store in `root`, its storage key persisted there.
+fn load_key(root: &Path) -> Result<ChunkKey> {
+    let unavailable = |reason: String| StorageInitError::ChunkKeyUnavailable {
+        path: storage_key_path(root),
+        reason,
+    };
+    match read_storage_key(root) {
+        Ok(Some(key)) => Ok(ChunkKey::new(&key)),
+        Ok(None) => Err(unavailable("there's no storage key".to_string()).into()),
+        Err(error) => Err(unavailable(error.to_string()).into()),
+    }
+}
+
+// Reads back the layout of a store from its layout file at `path`, if there's one, checking
+// its chunks are of the layout we know. Stores from before the file recorded the backend are
+// of files, and from before it recorded the encryption of plain chunks.
+fn read_layout(path: &Path) -> Result<Option<StoreLayout>> {
+    let persisted = match persisted::read(path) {
+        Ok(Some(persisted)) => persisted,
+        Ok(None) => return Ok(None),
+        Err(PersistedError::Io(path, error)) => return Err(Error::io_at(&path, error)),
+        Err(error) => {
+            return Err(StorageInitError::UnreadableLayout {
+                path: path.to_path_buf(),
+                reason: error.to_string(),
             }
+            .into())
+        }
+    };
+
+    let unreadable = |error: PersistedError| StorageInitError::UnreadableLayout {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    };
+    let not_a_layout = || unreadable(PersistedError::corrupt(path, "not a layout"));
+    if !(1..=LAYOUT_FILE_VERSION).contains(&persisted.version) {
+        return Err(unreadable(PersistedError::WrongVersion {
+            path: path.to_path_buf(),
+            found: persisted.version,
+            expected: LAYOUT_FILE_VERSION,
         })
-        .filter(|e| e.file_type().is_file())
-        .map(|e| e.path().display().to_string())
-        .collect();
-    Ok(files)
+        .into());
+    }
+    let layout = std::str::from_utf8(&persisted.payload).map_err(|_| not_a_layout())?;
+    let mut fields = layout.split_whitespace();
+    let found = fields
+        .next()
+        .and_then(|version| version.parse::<u16>().ok())
+        .ok_or_else(not_a_layout)?;
+    let backend = match (persisted.version, fields.next()) {
+        (1, None) => ChunkBackendKind::Files,
+        (_, Some(backend)) if persisted.version > 1 => {
+            backend.parse().map_err(|_| not_a_layout())?
+        }
+        _ => return Err(not_a_layout().into()),
+    };
+    let encrypted = match (persisted.version, fields.next()) {
+        (1 | 2, None) => false,
+        (LAYOUT_FILE_VERSION, Some(PLAIN)) => false,
+        (LAYOUT_FILE_VERSION, Some(ENCRYPTED)) => true,
+        _ => return Err(not_a_layout().into()),
+    };
+    if found != LAYOUT_VERSION {
+        return Err(StorageInitError::IncompatibleLayoutVersion {
+            path: path.to_path_buf(),
+            found,
+            supported: LAYOUT_VERSION,
+        }
+        .into());
+    }
+    Ok(Some(StoreLayout { backend, encrypted }))
 }
 
 #[cfg(test)]
@@ -181,8 +873,14 @@ mod tests {
     use sn_interface::types::utils::random_bytes;
 
     use super::*;
+    use crate::dbs::{
+        encrypted_backend::ENCRYPTION_OVERHEAD,
+        file_backend::{is_partial_write, list_files_in},
+        DiskFaults, Latency,
+    };
     use futures::future::join_all;
     use rayon::prelude::*;
+    use std::time::{Duration, Instant};
     use tempfile::tempdir;
 
     fn init_chunk_disk_store() -> ChunkStore {
@@ -205,7 +903,7 @@ mod tests {
                 .expect("Failed to write chunk.");
 
             let read_chunk = store
-                .read_chunk(&addr)
+                .read_chunk(&addr, ReadClass::Interactive)
                 .await
                 .expect("Failed to read chunk.");
 
@@ -238,7 +936,10 @@ mod tests {
         let results = join_all(tasks).await;
 
         // read all chunks
-        let tasks = results.iter().flatten().map(|addr| store.read_chunk(addr));
+        let tasks = results
+            .iter()
+            .flatten()
+            .map(|addr| store.read_chunk(addr, ReadClass::Interactive));
         let results = join_all(tasks).await;
         let read_chunks: Vec<&Chunk> = results.iter().flatten().collect();
 
@@ -247,4 +948,749 @@ mod tests {
             .par_iter()
             .all(|c| read_chunks.iter().any(|r| r.value() == c.value())))
     }
+
+    #[tokio::test]
+    async fn self_test_passes_on_a_working_disk_and_cleans_up() -> Result<()> {
+        let root = tempdir()?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+
+        store.self_test().await?;
+        assert!(!root.path().join(SELF_TEST_FILE).exists());
+        assert!(store.list_all_chunk_addresses()?.is_empty());
+
+        store.disk().update(|faults| faults.corrupt_reads = true);
+        assert!(matches!(
+            store.self_test().await,
+            Err(Error::SelfTestMismatch)
+        ));
+        assert!(!root.path().join(SELF_TEST_FILE).exists());
+
+        // Failed writes fail the test too, and leave nothing behind either.
+        for faults in [
+            DiskFaults {
+                torn_writes: true,
+                ..DiskFaults::default()
+            },
+            DiskFaults {
+                fsync_fails: true,
+                ..DiskFaults::default()
+            },
+            DiskFaults {
+                write_failure_rate: 1.0,
+                ..DiskFaults::default()
+            },
+        ] {
+            store.disk().set(faults);
+            assert!(matches!(store.self_test().await, Err(Error::Io(_))));
+            assert!(!root.path().join(SELF_TEST_FILE).exists());
+        }
+
+        store.disk().heal();
+        store.self_test().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_writes_are_undone_and_full_disk_refuses_writes() -> Result<()> {
+        let root = tempdir()?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(64))?;
+        store.disk().update(|faults| faults.torn_writes = true);
+
+        let torn = Chunk::new(random_bytes(16));
+        assert!(matches!(store.write_chunk(&torn).await, Err(Error::Io(_))));
+        assert!(!store.has_chunk(torn.address())?);
+        assert!(store.index.pending()?.is_empty());
+        assert!(list_files_in(root.path())?
+            .iter()
+            .all(|file| !is_partial_write(Path::new(file))));
+
+        // The disk fills up at half the max capacity.
+        store.disk().set(DiskFaults {
+            full_at: Some(0.5),
+            ..DiskFaults::default()
+        });
+        let addrs = store_chunks(&store, 2).await?;
+        assert!(matches!(
+            store.write_chunk(&Chunk::new(random_bytes(16))).await,
+            Err(Error::Io(_))
+        ));
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        assert_eq!(store.used_space.used(), 2 * 16);
+
+        Ok(())
+    }
+
+    async fn store_chunks(store: &ChunkStore, count: usize) -> Result<Vec<ChunkAddress>> {
+        let mut addrs = vec![];
+        for _ in 0..count {
+            addrs.push(store.write_chunk(&Chunk::new(random_bytes(16))).await?);
+        }
+        addrs.sort();
+        Ok(addrs)
+    }
+
+    fn walk(store: &ChunkStore) -> Result<Vec<ChunkAddress>> {
+        let mut addrs = store
+            .backend
+            .list()?
+            .chunks
+            .iter()
+            .map(|chunk| chunk.address)
+            .collect::<Vec<_>>();
+        addrs.sort();
+        Ok(addrs)
+    }
+
+    #[tokio::test]
+    async fn index_mismatch_is_repaired_by_reconciliation() -> Result<()> {
+        let root = tempdir()?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+        let addrs = store_chunks(&store, 3).await?;
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        let used = store.used_space.used();
+
+        // Unindex a chunk held, and index one that isn't, as if the used space had been
+        // counted from such an index.
+        let absent = ChunkAddress(xor_name::rand::random());
+        store.index.remove(&addrs[0])?;
+        store.index.insert(&absent, IndexEntry::now(1))?;
+        store.used_space.decrease(16 - 1, SpaceCategory::Chunks);
+        assert_ne!(store.list_all_chunk_addresses()?, walk(&store)?);
+
+        let reconciliation = store.reconcile()?;
+        assert_eq!(reconciliation.indexed, 1);
+        assert_eq!(reconciliation.unindexed, 1);
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        assert!(store.reconcile()?.was_consistent());
+        assert_eq!(store.used_space.used(), used);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn index_is_rebuilt_when_missing() -> Result<()> {
+        let root = tempdir()?;
+        let addrs = {
+            let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+            store_chunks(&store, 3).await?
+        };
+        fs::remove_dir_all(root.path().join("db"))?;
+
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        assert_eq!(store.chunk_count(), 3);
+        assert_eq!(used_space.used(), 3 * 16);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn interrupted_writes_and_deletions_are_repaired_on_start() -> Result<()> {
+        let root = tempdir()?;
+        let (written, deleted, torn) = {
+            let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+            let files = FileBackend::new(root.path(), &store.used_space);
+            let addrs = store_chunks(&store, 2).await?;
+
+            // A write interrupted after its file was renamed in place, a deletion after its
+            // file was removed, and a write before its file was.
+            let written = Chunk::new(random_bytes(16));
+            let filepath = files.address_to_filepath(written.address())?;
+            fs::create_dir_all(filepath.parent().unwrap_or(&filepath))?;
+            store.index.begin(written.address())?;
+            fs::write(&filepath, written.value())?;
+
+            store.index.begin(&addrs[0])?;
+            fs::remove_file(files.address_to_filepath(&addrs[0])?)?;
+
+            let torn = Chunk::new(random_bytes(16));
+            let filepath = files.address_to_filepath(torn.address())?;
+            fs::create_dir_all(filepath.parent().unwrap_or(&filepath))?;
+            store.index.begin(torn.address())?;
+            store.disk().update(|faults| faults.torn_writes = true);
+            assert!(store
+                .disk()
+                .write(&filepath.with_extension("0.tmp"), torn.value(), 0.0)
+                .await
+                .is_err());
+
+            store.index.flush().await?;
+            (*written.address(), addrs[0], *torn.address())
+        };
+
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+        assert!(store.has_chunk(&written)?);
+        assert!(!store.has_chunk(&deleted)?);
+        assert!(!store.has_chunk(&torn)?);
+        assert!(store.index.pending()?.is_empty());
+        assert_eq!(store.list_all_chunk_addresses()?, walk(&store)?);
+
+        Ok(())
+    }
+
+    fn init_error(result: Result<ChunkStore>) -> StorageInitError {
+        match result {
+            Err(Error::StorageInit(error)) => error,
+            Err(error) => panic!("Unexpected error: {:?}", error),
+            Ok(_) => panic!("Store initialised"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unwritable_chunk_dir_is_reported_with_its_path() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempdir()?;
+        let chunk_dir = root.path().join(CHUNK_DB_DIR);
+        fs::create_dir_all(&chunk_dir)?;
+        fs::set_permissions(&chunk_dir, fs::Permissions::from_mode(0o500))?;
+        // Permissions don't bind root, which tests may run as.
+        let enforced = fs::write(chunk_dir.join("probe"), []).is_err();
+        let result = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX));
+        fs::set_permissions(&chunk_dir, fs::Permissions::from_mode(0o700))?;
+
+        let error = if enforced {
+            init_error(result)
+        } else {
+            let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+            init_error(Err(Error::io_at(&chunk_dir, denied)))
+        };
+        assert_eq!(
+            error,
+            StorageInitError::PermissionDenied {
+                path: chunk_dir.clone()
+            }
+        );
+        assert!(error.to_string().contains(&chunk_dir.display().to_string()));
+        assert!(error.suggestion().contains("--root-dir"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_index_is_rebuilt_from_the_chunk_files() -> Result<()> {
+        let root = tempdir()?;
+        let addrs = {
+            let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+            let addrs = store_chunks(&store, 3).await?;
+            store.index.insert_raw(&[0xff; 32], &[0xff])?;
+            store.index.flush().await?;
+            addrs
+        };
+
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        assert_eq!(used_space.used(), 3 * 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_which_cant_be_rebuilt_is_reported_after_trying() -> Result<()> {
+        let root = tempdir()?;
+        let index_path = ChunkIndex::path(root.path());
+        fs::create_dir_all(root.path().join("db"))?;
+        fs::write(&index_path, b"not an index")?;
+
+        let error = init_error(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX)));
+        assert!(matches!(
+            &error,
+            StorageInitError::CorruptIndex {
+                path,
+                recovery_attempted: true,
+                ..
+            } if *path == index_path
+        ));
+        assert!(error
+            .to_string()
+            .contains("rebuilding it from the chunk files failed"));
+        assert!(error
+            .suggestion()
+            .contains(&format!("Move {} away", index_path.display())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_in_use_is_left_alone() -> Result<()> {
+        let root = tempdir()?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+
+        let error = init_error(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX)));
+        assert!(matches!(
+            error,
+            StorageInitError::CorruptIndex {
+                recovery_attempted: false,
+                ..
+            }
+        ));
+        assert!(error
+            .to_string()
+            .contains("wasn't rebuilt as it may be in use"));
+        assert!(error.suggestion().contains("no other node is running"));
+        assert!(store.index.is_reconciled()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn store_of_another_layout_is_refused() -> Result<()> {
+        let root = tempdir()?;
+        let layout_path = root.path().join(LAYOUT_FILE);
+        drop(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?);
+        assert_eq!(
+            persisted::read(&layout_path)
+                .ok()
+                .flatten()
+                .map(|persisted| persisted.payload),
+            Some(b"1 files plain".to_vec())
+        );
+
+        persisted::write(&layout_path, LAYOUT_FILE_VERSION, b"2 files plain")?;
+        let error = init_error(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX)));
+        assert_eq!(
+            error,
+            StorageInitError::IncompatibleLayoutVersion {
+                path: layout_path.clone(),
+                found: 2,
+                supported: LAYOUT_VERSION,
+            }
+        );
+        assert!(error
+            .to_string()
+            .contains("is of layout version 2, this node supports version 1"));
+
+        fs::write(&layout_path, b"")?;
+        let error = init_error(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX)));
+        assert!(matches!(error, StorageInitError::UnreadableLayout { .. }));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn store_keeps_the_backend_it_was_created_with() -> Result<()> {
+        let root = tempdir()?;
+        let layout_path = root.path().join(LAYOUT_FILE);
+        let used_space = UsedSpace::new(usize::MAX);
+        let sled = Some(ChunkBackendKind::Sled);
+        let addrs = {
+            let store = ChunkStore::new(root.path(), used_space.clone())?;
+            store_chunks(&store, 1).await?
+        };
+
+        let error = match ChunkStore::check(root.path(), &used_space, sled, false) {
+            Err(Error::StorageInit(error)) => error,
+            other => panic!("Unexpected check: {:?}", other),
+        };
+        assert_eq!(
+            error,
+            StorageInitError::IncompatibleBackend {
+                path: layout_path.clone(),
+                found: "files".to_string(),
+                configured: "sled".to_string(),
+            }
+        );
+        assert!(error.suggestion().contains("--chunk-backend files"));
+        assert_eq!(
+            ChunkStore::check(root.path(), &used_space, None, false)?.backend,
+            ChunkBackendKind::Files
+        );
+
+        // Stores from before the backend was recorded, or the layout at all, are of files.
+        persisted::write(&layout_path, 1, b"1")?;
+        assert!(ChunkStore::check(root.path(), &used_space, sled, false).is_err());
+        persisted::write(&layout_path, 2, b"1 files")?;
+        assert!(ChunkStore::check(root.path(), &used_space, sled, false).is_err());
+        fs::remove_file(&layout_path)?;
+        assert!(ChunkStore::check(root.path(), &used_space, sled, false).is_err());
+        let store = ChunkStore::new(root.path(), used_space)?;
+        assert_eq!(store.backend_kind(), ChunkBackendKind::Files);
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sled_backend_keeps_chunks_in_a_db() -> Result<()> {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let sled = Some(ChunkBackendKind::Sled);
+        let layout = ChunkStore::check(root.path(), &used_space, sled, false)?;
+        assert_eq!(layout.backend, ChunkBackendKind::Sled);
+
+        let addrs = {
+            let store = ChunkStore::new(root.path(), used_space)?;
+            assert_eq!(store.backend_kind(), ChunkBackendKind::Sled);
+            let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(100)))
+                .take(7)
+                .collect();
+            write_and_read_chunks(&chunks, store.clone()).await;
+            let mut addrs = store.list_all_chunk_addresses()?;
+            assert_eq!(addrs.len(), 7);
+            assert_eq!(walk(&store)?, addrs);
+            assert!(store.backend_used_space()? > 0);
+
+            store.delete_chunk(&addrs[0]).await?;
+            assert!(matches!(
+                store.read_chunk(&addrs[0], ReadClass::Interactive).await,
+                Err(Error::ChunkNotFound(_))
+            ));
+            let _deleted = addrs.remove(0);
+            addrs
+        };
+        assert!(list_files_in(&root.path().join(CHUNK_DB_DIR))?.is_empty());
+
+        // Reopened without the index, it's rebuilt from the db.
+        fs::remove_dir_all(ChunkIndex::path(root.path()))?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        assert_eq!(store.backend_kind(), ChunkBackendKind::Sled);
+        assert_eq!(store.list_all_chunk_addresses()?, addrs);
+        assert_eq!(used_space.used(), 6 * 100);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn encrypted_store_keeps_chunks_unreadable_at_rest() -> Result<()> {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let key_path = storage_key_path(root.path());
+
+        // The chunks are encrypted with the storage key, which must be there.
+        let error = match ChunkStore::check(root.path(), &used_space, None, true) {
+            Err(Error::StorageInit(error)) => error,
+            other => panic!("Unexpected check: {:?}", other),
+        };
+        assert!(
+            matches!(error, StorageInitError::ChunkKeyUnavailable { ref path, .. } if *path == key_path)
+        );
+        let key = ChunkStore::new_key(root.path(), true)?;
+        persisted::write(
+            &key_path,
+            1,
+            &key.expect("A new encrypted store has a new key"),
+        )?;
+        assert_eq!(ChunkStore::new_key(root.path(), true)?, None);
+        assert!(ChunkStore::check(root.path(), &used_space, None, true)?.encrypted);
+
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        assert!(store.is_encrypted());
+        let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(100)))
+            .take(5)
+            .collect();
+        write_and_read_chunks(&chunks, store.clone()).await;
+        assert_eq!(used_space.used(), 5 * 100);
+        assert!(store.reconcile()?.was_consistent());
+
+        let files = FileBackend::new(root.path(), &used_space);
+        for chunk in &chunks {
+            let on_disk = fs::read(files.address_to_filepath(chunk.address())?)?;
+            assert_eq!(on_disk.len(), 100 + ENCRYPTION_OVERHEAD);
+            assert!(!on_disk
+                .windows(16)
+                .any(|window| window == &chunk.value()[..16]));
+        }
+
+        // Tampered chunks don't decrypt.
+        let tampered = chunks[0].address();
+        let path = files.address_to_filepath(tampered)?;
+        let mut on_disk = fs::read(&path)?;
+        on_disk[ENCRYPTION_OVERHEAD] ^= 1;
+        fs::write(&path, on_disk)?;
+        assert!(matches!(
+            store.read_chunk(tampered, ReadClass::Interactive).await,
+            Err(Error::UndecryptableChunk(_))
+        ));
+        drop(store);
+
+        // The store stays encrypted, and can't be opened without the key.
+        assert!(ChunkStore::check(root.path(), &used_space, None, false)?.encrypted);
+        fs::remove_file(&key_path)?;
+        let error = init_error(ChunkStore::new(root.path(), UsedSpace::new(usize::MAX)));
+        assert!(matches!(
+            error,
+            StorageInitError::ChunkKeyUnavailable { .. }
+        ));
+        assert!(error.suggestion().contains(&key_path.display().to_string()));
+
+        // Stores created unencrypted aren't encrypted after the fact.
+        let plain = tempdir()?;
+        drop(ChunkStore::new(plain.path(), UsedSpace::new(usize::MAX))?);
+        let error = match ChunkStore::check(plain.path(), &used_space, None, true) {
+            Err(Error::StorageInit(error)) => error,
+            other => panic!("Unexpected check: {:?}", other),
+        };
+        assert_eq!(
+            error,
+            StorageInitError::UnencryptedStore {
+                path: plain.path().join(LAYOUT_FILE),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_encrypted_before_its_storage_key_takes_the_reward_derived_key_over() -> Result<()>
+    {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let reward_key_path = reward_secret_key_path(root.path());
+        let key_path = storage_key_path(root.path());
+        let reward_key = rand::random::<[u8; 32]>();
+        persisted::write(&reward_key_path, 1, &reward_key)?;
+        let legacy = ChunkKey::legacy(
+            &ed25519_dalek::SecretKey::from_bytes(&reward_key).expect("Any 32 bytes are a key"),
+        );
+
+        // A store whose chunks were encrypted with the key derived from the reward secret key.
+        persisted::write(&key_path, 1, &legacy)?;
+        let _layout = ChunkStore::check(root.path(), &used_space, None, true)?;
+        let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(100)))
+            .take(3)
+            .collect();
+        write_and_read_chunks(&chunks, ChunkStore::new(root.path(), used_space)?).await;
+        fs::remove_file(&key_path)?;
+
+        assert_eq!(ChunkStore::new_key(root.path(), false)?, Some(legacy));
+        persisted::write(&key_path, 1, &legacy)?;
+
+        // Its chunks are then read whatever becomes of the reward key.
+        persisted::write(&reward_key_path, 1, &rand::random::<[u8; 32]>())?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+        for chunk in &chunks {
+            let read = store
+                .read_chunk(chunk.address(), ReadClass::Interactive)
+                .await?;
+            assert_eq!(read.value(), chunk.value());
+        }
+
+        // New stores get a random key, and plain ones none.
+        let new = tempdir()?;
+        persisted::write(&reward_secret_key_path(new.path()), 1, &reward_key)?;
+        let key = ChunkStore::new_key(new.path(), true)?;
+        assert!(key.is_some() && key != Some(legacy));
+        assert_eq!(ChunkStore::new_key(new.path(), false)?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_chunks_are_quarantined_by_scrubbing() -> Result<()> {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        let addresses = store_chunks(&store, 5).await?;
+        let mut in_order = addresses.clone();
+        in_order.sort_by_key(|address| *address.name());
+
+        // Scrubbing walks the chunks in order of their names, a batch at a time.
+        let first = store.chunk_addresses_after(None, 3)?;
+        assert_eq!(first, in_order[..3]);
+        let rest = store.chunk_addresses_after(Some(first[2].name()), 3)?;
+        assert_eq!(rest, in_order[3..]);
+
+        for address in &addresses {
+            assert_eq!(store.scrub_chunk(address).await?, Scrubbed::Intact);
+        }
+
+        let corrupt = addresses[0];
+        let files = FileBackend::new(root.path(), &used_space);
+        let mut on_disk = fs::read(files.address_to_filepath(&corrupt)?)?;
+        on_disk[0] ^= 1;
+        fs::write(files.address_to_filepath(&corrupt)?, &on_disk)?;
+        let used = used_space.used();
+
+        assert_eq!(store.scrub_chunk(&corrupt).await?, Scrubbed::Quarantined);
+        assert!(!store.has_chunk(&corrupt)?);
+        assert!(!files.address_to_filepath(&corrupt)?.exists());
+        assert!(used_space.used() < used);
+        let quarantined = root
+            .path()
+            .join(QUARANTINE_DIR)
+            .join(hex::encode(corrupt.name()));
+        assert_eq!(fs::read(quarantined)?, on_disk);
+        assert!(store.reconcile()?.was_consistent());
+
+        // Chunks gone since they were listed are skipped.
+        assert_eq!(store.scrub_chunk(&corrupt).await?, Scrubbed::Gone);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn journaled_writes_lost_before_a_sync_are_replayed() -> Result<()> {
+        let root = tempdir()?;
+        let (deleted, lost) = {
+            let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+            store.set_journal_flush_interval(Duration::from_millis(10));
+            let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(16)))
+                .take(3)
+                .collect();
+            write_and_read_chunks(&chunks, store.clone()).await;
+            store.delete_chunk(chunks[0].address()).await?;
+
+            // A crash losing a chunk written unsynced, and its index entry.
+            let files = FileBackend::new(root.path(), &store.used_space);
+            fs::remove_file(files.address_to_filepath(chunks[1].address())?)?;
+            store.index.remove(chunks[1].address())?;
+            store.index.flush().await?;
+            (*chunks[0].address(), *chunks[1].address())
+        };
+
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = ChunkStore::new(root.path(), used_space.clone())?;
+        assert_eq!(store.replay_journal().await?, 4);
+        assert!(store.has_chunk(&lost)?);
+        assert!(!store.has_chunk(&deleted)?);
+        assert_eq!(store.chunk_count(), 2);
+        assert_eq!(used_space.used(), 2 * 16);
+        assert!(store.reconcile()?.was_consistent());
+        assert_eq!(store.replay_journal().await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn journal_of_an_encrypted_store_keeps_no_chunk_in_the_clear() -> Result<()> {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        persisted::write(
+            &storage_key_path(root.path()),
+            1,
+            &rand::random::<[u8; 32]>(),
+        )?;
+        drop(ChunkStore::check(root.path(), &used_space, None, true)?);
+
+        let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(100)))
+            .take(3)
+            .collect();
+        {
+            let store = ChunkStore::new(root.path(), used_space.clone())?;
+            store.set_journal_flush_interval(Duration::from_millis(10));
+            write_and_read_chunks(&chunks, store).await;
+        }
+
+        let journal = fs::read(root.path().join("chunk_journal"))?;
+        for chunk in &chunks {
+            assert!(journal
+                .windows(16)
+                .all(|window| window != &chunk.value()[..16]));
+        }
+
+        // The sealed records are still replayed.
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+        assert_eq!(store.replay_journal().await?, chunks.len());
+        for chunk in &chunks {
+            assert_eq!(
+                store
+                    .read_chunk(chunk.address(), ReadClass::Interactive)
+                    .await?,
+                *chunk
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_little_free_space_is_reported_with_how_much_is_needed() -> Result<()> {
+        let root = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX)
+            .with_db_reserve(1024 * 1024)
+            .with_simulated_disk(256 * 1024);
+
+        let error = init_error(ChunkStore::new(root.path(), used_space));
+        assert_eq!(
+            error,
+            StorageInitError::InsufficientSpace {
+                path: root.path().to_path_buf(),
+                required: 1024 * 1024,
+                available: 256 * 1024,
+            }
+        );
+        assert!(error.to_string().contains("needs 1048576 bytes free"));
+        assert!(error.suggestion().contains("at least 786432 bytes"));
+
+        Ok(())
+    }
+
+    // Stands for a store of many chunks: each chunk file is 20 dirs deep, so walking a few
+    // thousand of them is already far slower than listing them from the index.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn background_reads_wait_for_slow_interactive_reads_then_complete() -> Result<()> {
+        let store = init_chunk_disk_store();
+        let addrs = store_chunks(&store, 3).await?;
+        store.set_read_throttle(ReadThrottle {
+            max_interactive_latency: Duration::from_millis(50),
+            latency_window: Duration::from_millis(300),
+            ..ReadThrottle::default()
+        });
+        store.disk().update(|faults| {
+            faults.read_latency = Latency::fixed(Duration::from_millis(200));
+        });
+
+        let interactive = tokio::spawn({
+            let store = store.clone();
+            let addr = addrs[0];
+            async move {
+                let _chunk = store.read_chunk(&addr, ReadClass::Interactive).await?;
+                Ok::<_, Error>(Instant::now())
+            }
+        });
+        // A background pass starting while the interactive read takes longer than allowed.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let background = tokio::spawn({
+            let store = store.clone();
+            let addrs = addrs.clone();
+            async move {
+                let mut done = vec![];
+                for addr in &addrs {
+                    let _chunk = store.read_chunk(addr, ReadClass::Background).await?;
+                    done.push(Instant::now());
+                }
+                Ok::<_, Error>(done)
+            }
+        });
+
+        let interactive_done = interactive.await.expect("interactive read panicked")?;
+        let background_done = background.await.expect("background pass panicked")?;
+
+        // The pass waited for the latency of the interactive read to be out of the window,
+        // and then read all the chunks.
+        assert_eq!(background_done.len(), addrs.len());
+        assert!(background_done[0] >= interactive_done + Duration::from_millis(300));
+        let pacing = store.read_pacing();
+        assert_eq!(pacing.deferred, 1);
+        assert_eq!(pacing.admitted, 3);
+        assert_eq!(pacing.waiting, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn listing_from_the_index_is_much_faster_than_walking() -> Result<()> {
+        let root = tempdir()?;
+        let store = ChunkStore::new(root.path(), UsedSpace::new(usize::MAX))?;
+        let addrs = store_chunks(&store, 2000).await?;
+
+        let started = Instant::now();
+        let walked = walk(&store)?;
+        let walk_time = started.elapsed();
+
+        let started = Instant::now();
+        let listed = store.list_all_chunk_addresses()?;
+        let index_time = started.elapsed();
+
+        assert_eq!(walked, addrs);
+        assert_eq!(listed, addrs);
+        assert!(index_time * 10 < walk_time);
+
+        Ok(())
+    }
 }
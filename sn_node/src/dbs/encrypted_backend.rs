@@ -0,0 +1,164 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    chunk_backend::{ChunkBackend, ChunkBackendKind, Listing},
+    Error, Result,
+};
+
+use sn_interface::types::ChunkAddress;
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use ed25519_dalek::SecretKey;
+use futures::future::BoxFuture;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::sync::Arc;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Bytes an encrypted chunk takes over the chunk: its nonce and its tag.
+pub(crate) const ENCRYPTION_OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+// What the key of the stores encrypted before they had a key of their own was derived for, so
+// it's unrelated to any other use of the reward secret key.
+const LEGACY_KEY_INFO: &[u8] = b"sn_node chunk store key";
+
+/// The key the chunks of an encrypted store are encrypted with, the random storage key persisted
+/// along the node's keys, so the reward key can be rotated without losing the chunks.
+#[derive(Clone)]
+pub(crate) struct ChunkKey {
+    cipher: ChaCha20Poly1305,
+}
+
+impl ChunkKey {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// The key the chunks of stores encrypted before they had a key of their own were
+    /// encrypted with, derived from the node's reward secret key. It's taken over as their
+    /// storage key.
+    pub(crate) fn legacy(reward_secret_key: &SecretKey) -> [u8; 32] {
+        let mut key = [0; 32];
+        Hkdf::<Sha256>::new(None, reward_secret_key.as_bytes())
+            .expand(LEGACY_KEY_INFO, &mut key)
+            .expect("32 bytes is a valid length for HKDF-SHA256 output");
+        key
+    }
+
+    /// Encrypts the chunk at `address` as its nonce, then its ciphertext and tag. The address is
+    /// authenticated along, so chunks can't be swapped for one another on disk.
+    pub(crate) fn encrypt(&self, address: &ChunkAddress, bytes: &[u8]) -> Vec<u8> {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let payload = Payload {
+            msg: bytes,
+            aad: &address.name().0,
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("ChaCha20-Poly1305 encrypts any chunk-sized payload");
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Decrypts what `encrypt` made of the chunk at `address`, failing if it doesn't
+    /// authenticate.
+    pub(crate) fn decrypt(&self, address: &ChunkAddress, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < ENCRYPTION_OVERHEAD {
+            return Err(Error::UndecryptableChunk(*address.name()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &address.name().0,
+        };
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::UndecryptableChunk(*address.name()))
+    }
+}
+
+/// Chunks kept encrypted in another backend, with a `ChunkKey`, and decrypted as they're read.
+///
+/// Sizes are told as those of the chunks, so the index and the used space are the same as
+/// for an unencrypted store.
+pub(crate) struct EncryptedBackend {
+    inner: Arc<dyn ChunkBackend>,
+    key: ChunkKey,
+}
+
+impl EncryptedBackend {
+    pub(crate) fn new(inner: Arc<dyn ChunkBackend>, key: ChunkKey) -> Self {
+        Self { inner, key }
+    }
+}
+
+impl ChunkBackend for EncryptedBackend {
+    fn kind(&self) -> ChunkBackendKind {
+        self.inner.kind()
+    }
+
+    fn put<'a>(&'a self, address: &'a ChunkAddress, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let sealed = self.key.encrypt(address, bytes);
+            self.inner.put(address, &sealed).await
+        })
+    }
+
+    fn put_unsynced<'a>(
+        &'a self,
+        address: &'a ChunkAddress,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let sealed = self.key.encrypt(address, bytes);
+            self.inner.put_unsynced(address, &sealed).await
+        })
+    }
+
+    fn sync<'a>(&'a self, addresses: &'a [ChunkAddress]) -> BoxFuture<'a, Result<()>> {
+        self.inner.sync(addresses)
+    }
+
+    fn get<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let sealed = self.inner.get(address).await?;
+            self.key.decrypt(address, &sealed)
+        })
+    }
+
+    fn delete<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<()>> {
+        self.inner.delete(address)
+    }
+
+    fn list(&self) -> Result<Listing> {
+        let mut listing = self.inner.list()?;
+        for chunk in &mut listing.chunks {
+            chunk.size = chunk.size.saturating_sub(ENCRYPTION_OVERHEAD as u64);
+        }
+        Ok(listing)
+    }
+
+    fn recover(&self, address: &ChunkAddress) -> Result<Option<u64>> {
+        let size = self.inner.recover(address)?;
+        Ok(size.map(|size| size.saturating_sub(ENCRYPTION_OVERHEAD as u64)))
+    }
+
+    fn used_space(&self) -> Result<u64> {
+        self.inner.used_space()
+    }
+}
@@ -10,7 +10,12 @@ use sn_interface::messaging::data::Error as ErrorMsg;
 use sn_interface::types::{
     convert_dt_error_to_error_msg, DataAddress, PublicKey, ReplicatedDataAddress,
 };
-use std::io;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 use xor_name::XorName;
 
@@ -100,18 +105,201 @@ pub enum Error {
     /// No filename found
     #[error("Path contains no file name")]
     NoFilename,
+    /// An encrypted chunk read back didn't authenticate under the store's key.
+    #[error("Chunk {0:?} failed to decrypt, it's damaged or of another key")]
+    UndecryptableChunk(XorName),
+    /// The disk self-test read back something other than what it wrote.
+    #[error("Disk self-test read back corrupted data")]
+    SelfTestMismatch,
+    /// The node's storage keeps failing its disk self-test, so it takes no new data.
+    #[error("Storage is unhealthy, not accepting new data")]
+    StorageUnhealthy,
+    /// The chunk store couldn't be initialised.
+    #[error("Chunk store initialisation failed: {0}")]
+    StorageInit(#[from] StorageInitError),
+}
+
+impl Error {
+    /// An I/O error on `path`, which is a `StorageInitError::PermissionDenied` if it's one.
+    pub(crate) fn io_at(path: &Path, error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::PermissionDenied {
+            StorageInitError::PermissionDenied {
+                path: path.to_path_buf(),
+            }
+            .into()
+        } else {
+            Self::Io(error)
+        }
+    }
+}
+
+/// Why the chunk store couldn't be initialised, for whoever runs the node to act on.
+///
+/// Recorded in the node's startup status file as is, `kind` being the variant.
+#[derive(Clone, Debug, Error, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum StorageInitError {
+    /// The node isn't allowed to read or write a path of its store.
+    #[error("Permission denied on {}", .path.display())]
+    PermissionDenied {
+        /// The path.
+        path: PathBuf,
+    },
+    /// The chunk index can't be used, and wasn't rebuilt from the chunk files.
+    #[error(
+        "The chunk index at {} is unusable ({}), {}",
+        .path.display(),
+        .cause,
+        recovery_outcome(.recovery_attempted)
+    )]
+    CorruptIndex {
+        /// The index.
+        path: PathBuf,
+        /// What's wrong with it.
+        cause: String,
+        /// Whether rebuilding it was tried, which isn't when it may be in use.
+        recovery_attempted: bool,
+        /// What to do about it.
+        suggestion: String,
+    },
+    /// The chunk files are laid out in a way this node doesn't know.
+    #[error(
+        "The chunk store at {} is of layout version {}, this node supports version {}",
+        .path.display(),
+        .found,
+        .supported
+    )]
+    IncompatibleLayoutVersion {
+        /// The file recording the layout.
+        path: PathBuf,
+        /// The layout version of the store.
+        found: u16,
+        /// The layout version this node reads and writes.
+        supported: u16,
+    },
+    /// The store was created with another backend than the node is configured with.
+    #[error(
+        "The chunk store recorded in {} is of the {} backend, the node is configured with {}",
+        .path.display(),
+        .found,
+        .configured
+    )]
+    IncompatibleBackend {
+        /// The file recording the layout.
+        path: PathBuf,
+        /// The backend of the store.
+        found: String,
+        /// The backend the node is configured with.
+        configured: String,
+    },
+    /// The node is configured to encrypt chunks, but the store was created unencrypted.
+    #[error(
+        "The chunk store recorded in {} is unencrypted, the node is configured to encrypt chunks",
+        .path.display()
+    )]
+    UnencryptedStore {
+        /// The file recording the layout.
+        path: PathBuf,
+    },
+    /// The key the chunks of an encrypted store are encrypted with can't be read, or for a store
+    /// encrypted before it had a key of its own, the reward secret key it was derived from.
+    #[error("The key of the encrypted chunk store can't be read from {}: {}", .path.display(), .reason)]
+    ChunkKeyUnavailable {
+        /// The storage key file, or the reward secret key file.
+        path: PathBuf,
+        /// Why.
+        reason: String,
+    },
+    /// The file recording the layout of the chunk files can't be read back.
+    #[error("The chunk store layout file {} can't be read: {}", .path.display(), .reason)]
+    UnreadableLayout {
+        /// The file.
+        path: PathBuf,
+        /// Why.
+        reason: String,
+    },
+    /// There's too little free space on the disk to start.
+    #[error(
+        "Starting needs {} bytes free at {}, only {} are available",
+        .required,
+        .path.display(),
+        .available
+    )]
+    InsufficientSpace {
+        /// The store's root dir.
+        path: PathBuf,
+        /// Bytes of free space needed.
+        required: u64,
+        /// Bytes of free space available.
+        available: u64,
+    },
+}
+
+impl StorageInitError {
+    /// What whoever runs the node can do about the error.
+    pub fn suggestion(&self) -> String {
+        match self {
+            Self::PermissionDenied { path } => format!(
+                "Make sure the user running the node can read and write {}, or run the node \
+                with another --root-dir",
+                path.display()
+            ),
+            Self::CorruptIndex { suggestion, .. } => suggestion.clone(),
+            Self::IncompatibleLayoutVersion { found, .. } => format!(
+                "The store was written by another version of the node: run a version supporting \
+                layout version {}, or move the store away to start with an empty one",
+                found
+            ),
+            Self::IncompatibleBackend { found, .. } => format!(
+                "Run the node with --chunk-backend {}, or move the store away to start with an \
+                empty one",
+                found
+            ),
+            Self::UnencryptedStore { .. } => "Run the node without --encrypt-chunks, or move the \
+                store away to start with an empty, encrypted one"
+                .to_string(),
+            Self::ChunkKeyUnavailable { path, .. } => format!(
+                "Restore {} from a backup, as the chunks held can't be read without it, or move \
+                the store away to start with an empty one",
+                path.display()
+            ),
+            Self::UnreadableLayout { path, .. } => format!(
+                "Restore {} from a backup, or remove it if the store was only ever used by this \
+                version of the node",
+                path.display()
+            ),
+            Self::InsufficientSpace {
+                required,
+                available,
+                ..
+            } => format!(
+                "Free up at least {} bytes on the disk, or lower --db-reserve-mb",
+                required.saturating_sub(*available)
+            ),
+        }
+    }
+}
+
+fn recovery_outcome(recovery_attempted: &bool) -> &'static str {
+    if *recovery_attempted {
+        "and rebuilding it from the chunk files failed"
+    } else {
+        "and it wasn't rebuilt as it may be in use"
+    }
 }
 
 /// Convert db error to messaging error message for sending over the network.
 pub(crate) fn convert_to_error_msg(error: Error) -> ErrorMsg {
     match error {
         Error::NotEnoughSpace => ErrorMsg::FailedToWriteFile,
+        Error::StorageUnhealthy => ErrorMsg::FailedToWriteFile,
         Error::DataIdNotFound(address) => ErrorMsg::DataNotFound(address),
         Error::NoSuchData(address) => ErrorMsg::DataNotFound(address),
         Error::ChunkNotFound(xorname) => ErrorMsg::ChunkNotFound(xorname),
         Error::TempDirCreationFailed(_) => ErrorMsg::FailedToWriteFile,
         Error::DataExists => ErrorMsg::DataExists,
         Error::NetworkData(error) => convert_dt_error_to_error_msg(error),
-        other => ErrorMsg::InvalidOperation(format!("Failed to perform operation: {:?}", other)),
+        other => ErrorMsg::Internal(format!("Failed to perform operation: {:?}", other)),
     }
 }
@@ -6,9 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{deserialise, serialise, Error, Result};
+use super::{deserialise, serialise, AppliedOps, Error, Result};
 use serde::{de::DeserializeOwned, Serialize};
-use sled::{Db, Tree};
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionError},
+    Db, Transactional, Tree,
+};
 use std::{fmt::Debug, marker::PhantomData};
 use xor_name::XorName;
 
@@ -53,6 +56,7 @@ where
     }
 
     /// append a new entry
+    #[cfg(test)]
     pub(crate) fn append(&self, event: TEvent) -> Result<()> {
         let key = &self.tree.len().to_string();
         if self.tree.get(key)?.is_some() {
@@ -64,6 +68,42 @@ where
 
         Ok(())
     }
+
+    /// Appends the event of the op of `op_key` unless the op was applied already, recording its
+    /// key along with the event, in one transaction. Returns whether the event was appended.
+    pub(crate) fn append_once(
+        &self,
+        event: TEvent,
+        op_key: &[u8],
+        applied: &AppliedOps,
+    ) -> Result<bool> {
+        let key = self.tree.len().to_string();
+        let event = serialise(&event)?;
+        let timestamp = AppliedOps::timestamp();
+
+        let result = (&self.tree, applied.tree()).transaction(|(events, applied)| {
+            if applied.get(op_key)?.is_some() {
+                return Ok(false);
+            }
+            if events.get(&key)?.is_some() {
+                return Err(ConflictableTransactionError::Abort(Error::DataExists));
+            }
+            let _old_entry = events.insert(key.as_bytes(), event.clone())?;
+            let _old_entry = applied.insert(op_key, timestamp.clone())?;
+            Ok(true)
+        });
+
+        match result {
+            Ok(appended) => {
+                if appended {
+                    applied.recorded()?;
+                }
+                Ok(appended)
+            }
+            Err(TransactionError::Abort(error)) => Err(error),
+            Err(TransactionError::Storage(error)) => Err(Error::Sled(error)),
+        }
+    }
 }
 
 #[cfg(test)]
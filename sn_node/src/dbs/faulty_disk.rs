@@ -0,0 +1,227 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use rand::Rng;
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::io::AsyncWriteExt;
+
+/// How long a disk operation takes on top of what it takes, picked uniformly between `min`
+/// and `max` for each operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Latency {
+    /// The least added.
+    pub min: Duration,
+    /// The most added.
+    pub max: Duration,
+}
+
+impl Latency {
+    /// Every operation taking `delay` longer.
+    pub fn fixed(delay: Duration) -> Self {
+        Self {
+            min: delay,
+            max: delay,
+        }
+    }
+
+    fn pick(&self) -> Duration {
+        if self.max <= self.min {
+            self.min
+        } else {
+            rand::thread_rng().gen_range(self.min..=self.max)
+        }
+    }
+}
+
+/// The faults of a `FaultyDisk`. The default is a healthy disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DiskFaults {
+    /// Added to each read.
+    pub read_latency: Latency,
+    /// Added to each write.
+    pub write_latency: Latency,
+    /// Probability of a write failing before anything is written.
+    pub write_failure_rate: f64,
+    /// Probability of a read failing, the file being left as it is.
+    pub read_failure_rate: f64,
+    /// Whether syncing a written file to disk fails, its content being written but unsynced.
+    pub fsync_fails: bool,
+    /// Whether writes are torn: half the bytes are written, then the write fails.
+    pub torn_writes: bool,
+    /// Whether reads come back with their last bit flipped.
+    pub corrupt_reads: bool,
+    /// Ratio of the max capacity at which the disk is full, writes failing from then on.
+    pub full_at: Option<f64>,
+}
+
+/// A disk for tests and simulations, whose faults are scripted: slow, lossy, corrupting or
+/// full. The `ChunkStore` reads and writes chunk files through it, as does its self-test.
+///
+/// Shared by the clones of a store, so a test keeps a clone to change the faults of the disk
+/// of a node it runs.
+#[derive(Clone, Debug, Default)]
+pub struct FaultyDisk {
+    faults: Arc<RwLock<DiskFaults>>,
+}
+
+impl FaultyDisk {
+    /// Replaces the faults of the disk.
+    pub fn set(&self, faults: DiskFaults) {
+        *self.faults_mut() = faults;
+    }
+
+    /// Changes some of the faults of the disk.
+    pub fn update(&self, change: impl FnOnce(&mut DiskFaults)) {
+        change(&mut self.faults_mut())
+    }
+
+    /// Makes the disk healthy again.
+    pub fn heal(&self) {
+        self.set(DiskFaults::default())
+    }
+
+    /// The current faults of the disk.
+    pub fn faults(&self) -> DiskFaults {
+        *self
+            .faults
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Writes `bytes` to a new file at `path`, synced to disk, given `used_ratio` of the max
+    /// capacity is used.
+    pub(crate) async fn write(&self, path: &Path, bytes: &[u8], used_ratio: f64) -> io::Result<()> {
+        let faults = self.faults();
+        tokio::time::sleep(faults.write_latency.pick()).await;
+
+        if faults.full_at.map_or(false, |level| used_ratio >= level) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no space left on device (simulated)",
+            ));
+        }
+        if faults.write_failure_rate > 0.0
+            && rand::thread_rng().gen_bool(faults.write_failure_rate.min(1.0))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "write failed (simulated)",
+            ));
+        }
+
+        let mut file = tokio::fs::File::create(path).await?;
+        if faults.torn_writes {
+            file.write_all(&bytes[..bytes.len() / 2]).await?;
+            file.flush().await?;
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "write torn halfway (simulated)",
+            ));
+        }
+        file.write_all(bytes).await?;
+        if faults.fsync_fails {
+            file.flush().await?;
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "fsync failed (simulated)",
+            ));
+        }
+        file.sync_all().await
+    }
+
+    /// Reads the whole file at `path`.
+    pub(crate) async fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let faults = self.faults();
+        tokio::time::sleep(faults.read_latency.pick()).await;
+
+        if faults.read_failure_rate > 0.0
+            && rand::thread_rng().gen_bool(faults.read_failure_rate.min(1.0))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "read failed (simulated)",
+            ));
+        }
+
+        let mut bytes = tokio::fs::read(path).await?;
+        if faults.corrupt_reads {
+            if let Some(last) = bytes.last_mut() {
+                *last ^= 1;
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn faults_mut(&self) -> std::sync::RwLockWriteGuard<'_, DiskFaults> {
+        self.faults
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn faults_apply_until_healed() -> Result<()> {
+        let root = tempdir()?;
+        let path = root.path().join("file");
+        let disk = FaultyDisk::default();
+        let bytes = [7; 16];
+
+        disk.write(&path, &bytes, 0.0).await?;
+        assert_eq!(disk.read(&path).await?, bytes);
+
+        disk.update(|faults| faults.torn_writes = true);
+        assert!(disk.write(&path, &bytes, 0.0).await.is_err());
+        assert_eq!(tokio::fs::read(&path).await?, bytes[..8]);
+
+        disk.set(DiskFaults {
+            full_at: Some(0.5),
+            ..DiskFaults::default()
+        });
+        disk.write(&path, &bytes, 0.4).await?;
+        assert!(disk.write(&path, &bytes, 0.5).await.is_err());
+
+        disk.set(DiskFaults {
+            write_failure_rate: 1.0,
+            corrupt_reads: true,
+            ..DiskFaults::default()
+        });
+        assert!(disk.write(&path, &[1; 16], 0.0).await.is_err());
+        let read = disk.read(&path).await?;
+        assert_eq!(read[..15], bytes[..15]);
+        assert_ne!(read[15], bytes[15]);
+
+        disk.update(|faults| faults.read_failure_rate = 1.0);
+        assert!(disk.read(&path).await.is_err());
+
+        // An unsynced write is in the file, but fails as it might not survive a crash.
+        disk.set(DiskFaults {
+            fsync_fails: true,
+            ..DiskFaults::default()
+        });
+        assert!(disk.write(&path, &[2; 16], 0.0).await.is_err());
+        assert_eq!(disk.read(&path).await?, [2; 16]);
+
+        disk.heal();
+        disk.write(&path, &[1; 16], 0.0).await?;
+        assert_eq!(disk.read(&path).await?, [1; 16]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+#[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+use super::faulty_disk::FaultyDisk;
+use super::{
+    chunk_backend::{ChunkBackend, ChunkBackendKind, Listing, StoredChunk},
+    Error, Result,
+};
+
+use crate::UsedSpace;
+use sn_interface::types::ChunkAddress;
+
+use futures::future::BoxFuture;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+#[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
+use tokio::io::AsyncWriteExt;
+use walkdir::WalkDir;
+use xor_name::{Prefix, XorName};
+
+const BIT_TREE_DEPTH: usize = 20;
+/// Dir of the chunk files, in the store's root dir.
+pub(crate) const CHUNK_DB_DIR: &str = "chunkdb";
+// Extension of a chunk file being written, until it's renamed in place.
+const TMP_EXTENSION: &str = "tmp";
+
+/// Chunks kept a file each, in a tree of dirs `BIT_TREE_DEPTH` deep of the bits of their names,
+/// of files named after the zbase32 encoding of their address.
+///
+/// Chunk files are written to a temporary file, synced, then renamed in place, so a chunk file
+/// is either whole or not there.
+#[derive(Clone)]
+pub(crate) struct FileBackend {
+    bit_tree_depth: usize,
+    chunk_store_path: PathBuf,
+    // What chunk files are read and written through, to simulate a failing disk.
+    #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+    disk: FaultyDisk,
+    // For the disk to tell how full it is.
+    #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+    used_space: UsedSpace,
+}
+
+impl FileBackend {
+    /// The chunk files in `root/CHUNK_DB_DIR`.
+    #[cfg_attr(
+        not(any(test, feature = "test-utils", feature = "chaos")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn new(root: &Path, used_space: &UsedSpace) -> Self {
+        Self {
+            bit_tree_depth: BIT_TREE_DEPTH,
+            chunk_store_path: root.join(CHUNK_DB_DIR),
+            #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+            disk: FaultyDisk::default(),
+            #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+            used_space: used_space.clone(),
+        }
+    }
+
+    /// Has the chunk files read and written through the given `disk`.
+    #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+    pub(crate) fn with_disk(mut self, disk: FaultyDisk) -> Self {
+        self.disk = disk;
+        self
+    }
+
+    // Writes `bytes` to a new file at `path`, synced to disk if `synced` is set. Those of a
+    // simulated disk are always synced.
+    #[cfg_attr(
+        any(test, feature = "test-utils", feature = "chaos"),
+        allow(unused_variables)
+    )]
+    async fn write_file(&self, path: &Path, bytes: &[u8], synced: bool) -> io::Result<()> {
+        #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+        {
+            self.disk.write(path, bytes, self.used_space.ratio()).await
+        }
+        #[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
+        {
+            let mut file = tokio::fs::File::create(path).await?;
+            file.write_all(bytes).await?;
+            if synced {
+                file.sync_all().await?;
+            }
+            Ok(())
+        }
+    }
+
+    // Writes the chunk file of `address` through a temporary file renamed in place.
+    async fn write_chunk_file(
+        &self,
+        address: &ChunkAddress,
+        bytes: &[u8],
+        synced: bool,
+    ) -> Result<()> {
+        let filepath = self.address_to_filepath(address)?;
+        if let Some(dirs) = filepath.parent() {
+            tokio::fs::create_dir_all(dirs).await?;
+        }
+
+        // Unique, as the same chunk can be written concurrently.
+        let tmp_path =
+            filepath.with_extension(format!("{:016x}.{}", rand::random::<u64>(), TMP_EXTENSION));
+        if let Err(error) = self.write_file(&tmp_path, bytes, synced).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(error.into());
+        }
+        tokio::fs::rename(tmp_path, filepath).await?;
+        Ok(())
+    }
+
+    async fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        #[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+        {
+            self.disk.read(path).await
+        }
+        #[cfg(not(any(test, feature = "test-utils", feature = "chaos")))]
+        {
+            tokio::fs::read(path).await
+        }
+    }
+
+    // Helper that returns the prefix tree path of depth `bit_count` for a given xorname
+    // Example:
+    // - with a xorname with starting bits `010001110110....`
+    // - and a bit_count of `6`
+    // returns the path `CHUNK_STORE_PATH/0/1/0/0/0/1`
+    // If the provided bit count is larger than `self.bit_tree_depth`, uses `self.bit_tree_depth`
+    // to stay within the prefix tree path
+    fn prefix_tree_path(&self, xorname: XorName, bit_count: usize) -> PathBuf {
+        let bin = format!("{:b}", xorname);
+        let prefix_dir_path: PathBuf = bin
+            .chars()
+            .take(std::cmp::min(bit_count, self.bit_tree_depth))
+            .map(|c| format!("{}", c))
+            .collect();
+
+        let mut path = self.chunk_store_path.clone();
+        path.push(prefix_dir_path);
+        path
+    }
+
+    pub(crate) fn address_to_filepath(&self, addr: &ChunkAddress) -> Result<PathBuf> {
+        let xorname = *addr.name();
+        let filename = addr.encode_to_zbase32()?;
+        let mut path = self.prefix_tree_path(xorname, self.bit_tree_depth);
+        path.push(filename);
+        Ok(path)
+    }
+
+    fn filepath_to_address(&self, path: &str) -> Result<ChunkAddress> {
+        let filename = Path::new(path)
+            .file_name()
+            .ok_or(Error::NoFilename)?
+            .to_str()
+            .ok_or(Error::InvalidFilename)?;
+        Ok(ChunkAddress::decode_from_zbase32(filename)?)
+    }
+
+    pub(crate) fn list_all_files(&self) -> Result<Vec<String>> {
+        list_files_in(&self.chunk_store_path)
+    }
+
+    #[allow(unused)]
+    /// quickly find chunks related or not to a section, might be useful when adults change sections
+    /// not used yet
+    pub(crate) fn list_files_without_prefix(&self, prefix: Prefix) -> Result<Vec<String>> {
+        let all_files = self.list_all_files()?;
+        let prefix_path = self.prefix_tree_path(prefix.name(), prefix.bit_count());
+        let outside_prefix = all_files
+            .into_iter()
+            .filter(|p| !Path::new(&p).starts_with(&prefix_path.as_path()))
+            .collect();
+        Ok(outside_prefix)
+    }
+
+    #[allow(unused)]
+    /// quickly find chunks related or not to a section, might be useful when adults change sections
+    /// not used yet
+    pub(crate) fn list_files_with_prefix(&self, prefix: Prefix) -> Result<Vec<String>> {
+        let prefix_path = self.prefix_tree_path(prefix.name(), prefix.bit_count());
+        list_files_in(prefix_path.as_path())
+    }
+}
+
+impl ChunkBackend for FileBackend {
+    fn kind(&self) -> ChunkBackendKind {
+        ChunkBackendKind::Files
+    }
+
+    fn put<'a>(&'a self, address: &'a ChunkAddress, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.write_chunk_file(address, bytes, true))
+    }
+
+    fn put_unsynced<'a>(
+        &'a self,
+        address: &'a ChunkAddress,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.write_chunk_file(address, bytes, false))
+    }
+
+    fn sync<'a>(&'a self, addresses: &'a [ChunkAddress]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            for address in addresses {
+                let filepath = self.address_to_filepath(address)?;
+                match tokio::fs::File::open(&filepath).await {
+                    Ok(file) => file.sync_all().await?,
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let filepath = self.address_to_filepath(address)?;
+            Ok(self.read_file(&filepath).await?)
+        })
+    }
+
+    fn delete<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let filepath = self.address_to_filepath(address)?;
+            tokio::fs::remove_file(filepath).await?;
+            Ok(())
+        })
+    }
+
+    fn list(&self) -> Result<Listing> {
+        let mut listing = Listing::default();
+        for filepath in self.list_all_files()? {
+            let path = Path::new(&filepath);
+            if is_partial_write(path) {
+                fs::remove_file(path)?;
+                listing.partial_writes_removed += 1;
+                continue;
+            }
+            let address = match self.filepath_to_address(&filepath) {
+                Ok(address) => address,
+                Err(error) => {
+                    warn!("ChunkStore: ignoring {}, not a chunk: {}", filepath, error);
+                    continue;
+                }
+            };
+            let metadata = fs::metadata(path)?;
+            let stored_at = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|since_epoch| since_epoch.as_secs());
+            listing.chunks.push(StoredChunk {
+                address,
+                size: metadata.len(),
+                stored_at,
+            });
+        }
+        Ok(listing)
+    }
+
+    fn recover(&self, address: &ChunkAddress) -> Result<Option<u64>> {
+        let filepath = self.address_to_filepath(address)?;
+        remove_partial_writes(&filepath)?;
+        match fs::metadata(&filepath) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn used_space(&self) -> Result<u64> {
+        let files = WalkDir::new(&self.chunk_store_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok());
+        Ok(files.map(|metadata| metadata.len()).sum())
+    }
+}
+
+pub(crate) fn is_partial_write(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == TMP_EXTENSION)
+}
+
+// Removes the temporary files of interrupted writes of the chunk file at `filepath`.
+fn remove_partial_writes(filepath: &Path) -> Result<()> {
+    let (dir, name) = match (filepath.parent(), filepath.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Ok(()),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let of_chunk = path
+            .file_name()
+            .map_or(false, |file| file.to_string_lossy().starts_with(&*name));
+        if of_chunk && is_partial_write(&path) {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn list_files_in(path: &Path) -> Result<Vec<String>> {
+    let files = WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| match e {
+            Ok(direntry) => Some(direntry),
+            Err(err) => {
+                warn!("ChunkStore: failed to process file entry: {}", err);
+                None
+            }
+        })
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.path().display().to_string())
+        .collect();
+    Ok(files)
+}
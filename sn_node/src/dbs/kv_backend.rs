@@ -0,0 +1,125 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    chunk_backend::{ChunkBackend, ChunkBackendKind, Listing, StoredChunk},
+    Error, Result, SLED_FLUSH_TIME_MS,
+};
+
+use sn_interface::types::ChunkAddress;
+
+use futures::future::BoxFuture;
+use sled::Db;
+use std::path::Path;
+use xor_name::{XorName, XOR_NAME_LEN};
+
+/// Dir of the chunk db, in the store's root dir.
+pub(crate) const CHUNK_KV_DIR: &str = "chunkkv";
+
+/// Chunks kept in an embedded sled db, keyed by their name.
+///
+/// Sled writes are atomic, so there's nothing left of interrupted writes to clean up.
+#[derive(Clone)]
+pub(crate) struct KvBackend {
+    db: Db,
+}
+
+impl KvBackend {
+    /// Opens the chunk db in `root/CHUNK_KV_DIR`.
+    pub(crate) fn open(root: &Path) -> Result<Self> {
+        let path = root.join(CHUNK_KV_DIR);
+        let db = sled::Config::default()
+            .path(&path)
+            .flush_every_ms(SLED_FLUSH_TIME_MS)
+            .open()
+            .map_err(|error| match error {
+                sled::Error::Io(error) => Error::io_at(&path, error),
+                error => Error::Sled(error),
+            })?;
+        Ok(Self { db })
+    }
+}
+
+impl ChunkBackend for KvBackend {
+    fn kind(&self) -> ChunkBackendKind {
+        ChunkBackendKind::Sled
+    }
+
+    fn put<'a>(&'a self, address: &'a ChunkAddress, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _replaced = self.db.insert(address.name(), bytes)?;
+            let _flushed = self.db.flush_async().await?;
+            Ok(())
+        })
+    }
+
+    fn put_unsynced<'a>(
+        &'a self,
+        address: &'a ChunkAddress,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _replaced = self.db.insert(address.name(), bytes)?;
+            Ok(())
+        })
+    }
+
+    fn sync<'a>(&'a self, _addresses: &'a [ChunkAddress]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _flushed = self.db.flush_async().await?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            match self.db.get(address.name())? {
+                Some(bytes) => Ok(bytes.to_vec()),
+                None => Err(Error::ChunkNotFound(*address.name())),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, address: &'a ChunkAddress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if self.db.remove(address.name())?.is_none() {
+                return Err(Error::ChunkNotFound(*address.name()));
+            }
+            let _flushed = self.db.flush_async().await?;
+            Ok(())
+        })
+    }
+
+    fn list(&self) -> Result<Listing> {
+        let mut listing = Listing::default();
+        for entry in self.db.iter() {
+            let (key, bytes) = entry?;
+            let name = match <[u8; XOR_NAME_LEN]>::try_from(key.as_ref()) {
+                Ok(name) => XorName(name),
+                Err(_) => {
+                    warn!("ChunkStore: ignoring key {:?}, not a chunk name", key);
+                    continue;
+                }
+            };
+            listing.chunks.push(StoredChunk {
+                address: ChunkAddress(name),
+                size: bytes.len() as u64,
+                stored_at: None,
+            });
+        }
+        Ok(listing)
+    }
+
+    fn recover(&self, address: &ChunkAddress) -> Result<Option<u64>> {
+        Ok(self.db.get(address.name())?.map(|bytes| bytes.len() as u64))
+    }
+
+    fn used_space(&self) -> Result<u64> {
+        Ok(self.db.size_on_disk()?)
+    }
+}
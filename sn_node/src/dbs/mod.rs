@@ -6,20 +6,43 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod applied_ops;
+mod chunk_backend;
+mod chunk_index;
 mod chunk_store;
 mod encoding;
+mod encrypted_backend;
 mod errors;
 mod event_store;
+#[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+mod faulty_disk;
+mod file_backend;
+mod kv_backend;
 mod lru_cache;
+mod read_scheduler;
 mod used_space;
+mod write_journal;
 
-pub(crate) use chunk_store::ChunkStore;
+pub(crate) use applied_ops::AppliedOps;
+pub use chunk_backend::ChunkBackendKind;
+pub(crate) use chunk_store::{ChunkStore, Reconciliation, Scrubbed};
 pub(crate) use encoding::{deserialise, serialise};
+pub use errors::StorageInitError;
 pub(crate) use errors::{convert_to_error_msg, Error, Result};
 pub(crate) use event_store::EventStore;
+#[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+pub use faulty_disk::{DiskFaults, FaultyDisk, Latency};
 pub(crate) use lru_cache::LruCache;
+pub(crate) use read_scheduler::{
+    ReadClass, ReadThrottle, DEFAULT_BACKGROUND_READ_BYTES_PER_SEC, DEFAULT_BACKGROUND_READ_IOPS,
+    DEFAULT_INTERACTIVE_READ_LATENCY,
+};
+#[cfg(feature = "metrics")]
+pub(crate) use read_scheduler::{ReadPacing, INTERACTIVE_LATENCY_PERCENTILE};
 use std::path::Path;
-pub use used_space::UsedSpace;
+pub(crate) use used_space::SpaceCategory;
+pub use used_space::{UsedSpace, Watermarks};
+pub(crate) use write_journal::DEFAULT_CHUNK_JOURNAL_FLUSH_INTERVAL;
 
 pub(crate) const SLED_FLUSH_TIME_MS: Option<u64> = Some(10000);
 
@@ -0,0 +1,352 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Background reads allowed per second by default.
+pub(crate) const DEFAULT_BACKGROUND_READ_IOPS: u32 = 100;
+/// Bytes background reads may read per second by default.
+pub(crate) const DEFAULT_BACKGROUND_READ_BYTES_PER_SEC: u64 = 20 * 1024 * 1024;
+/// Interactive read latency above which background reads pause, by default.
+pub(crate) const DEFAULT_INTERACTIVE_READ_LATENCY: Duration = Duration::from_millis(100);
+
+/// Percentile of the interactive read latencies compared to the threshold.
+pub(crate) const INTERACTIVE_LATENCY_PERCENTILE: f64 = 0.95;
+// Interactive read latencies are measured over this long.
+const DEFAULT_LATENCY_WINDOW: Duration = Duration::from_secs(10);
+// The most latencies kept within the window.
+const MAX_LATENCY_SAMPLES: usize = 1024;
+// How often paused background reads check whether they can resume.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Who a read is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReadClass {
+    /// Serving a client, which is waiting for it.
+    Interactive,
+    /// A bulk pass of the node's own, e.g. replication or checks, which can wait.
+    Background,
+}
+
+/// How background reads are throttled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ReadThrottle {
+    /// Background reads allowed per second.
+    pub(crate) iops: u32,
+    /// Bytes background reads may read per second.
+    pub(crate) bytes_per_sec: u64,
+    /// Background reads pause while interactive reads take longer than this, as per their
+    /// `INTERACTIVE_LATENCY_PERCENTILE` within `latency_window`.
+    pub(crate) max_interactive_latency: Duration,
+    /// How long interactive read latencies are measured over.
+    pub(crate) latency_window: Duration,
+}
+
+impl Default for ReadThrottle {
+    fn default() -> Self {
+        Self {
+            iops: DEFAULT_BACKGROUND_READ_IOPS,
+            bytes_per_sec: DEFAULT_BACKGROUND_READ_BYTES_PER_SEC,
+            max_interactive_latency: DEFAULT_INTERACTIVE_READ_LATENCY,
+            latency_window: DEFAULT_LATENCY_WINDOW,
+        }
+    }
+}
+
+/// Where the pacing of background reads is at.
+#[cfg(any(test, feature = "metrics"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ReadPacing {
+    /// Whether background reads are paused for interactive reads being slow.
+    pub(crate) paused: bool,
+    /// Background reads waiting their turn.
+    pub(crate) waiting: usize,
+    /// Background reads which had to wait, since the start.
+    pub(crate) deferred: u64,
+    /// Background reads which went ahead, since the start.
+    pub(crate) admitted: u64,
+    /// Bytes background reads may read right away; negative while in debt to a large read.
+    pub(crate) byte_tokens: f64,
+    /// The `INTERACTIVE_LATENCY_PERCENTILE` of interactive read latencies, within the window.
+    pub(crate) interactive_latency: Duration,
+}
+
+/// Schedules the chunk reads so that background passes don't get in the way of serving
+/// clients: background reads are paced by token buckets of reads and bytes per second, and
+/// paused altogether while interactive reads are slow, be it measured or for one in flight
+/// taking longer than allowed.
+#[derive(Clone, Default)]
+pub(crate) struct ReadScheduler {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    throttle: ReadThrottle,
+    // Read and byte tokens, as of `refilled`.
+    op_tokens: f64,
+    byte_tokens: f64,
+    refilled: Option<Instant>,
+    // Completed interactive reads, as (completed, latency), oldest first.
+    latencies: VecDeque<(Instant, Duration)>,
+    // Interactive reads in flight, by when they started.
+    in_flight: BTreeMap<u64, Instant>,
+    next_read: u64,
+    waiting: usize,
+    deferred: u64,
+    admitted: u64,
+}
+
+impl State {
+    // Tops up the buckets, each holding up to a second's worth.
+    fn refill(&mut self, now: Instant) {
+        let refilled = match self.refilled.replace(now) {
+            Some(refilled) => refilled,
+            None => {
+                self.op_tokens = self.throttle.iops as f64;
+                self.byte_tokens = self.throttle.bytes_per_sec as f64;
+                return;
+            }
+        };
+        let secs = now.saturating_duration_since(refilled).as_secs_f64();
+        self.op_tokens =
+            (self.op_tokens + secs * self.throttle.iops as f64).min(self.throttle.iops as f64);
+        self.byte_tokens = (self.byte_tokens + secs * self.throttle.bytes_per_sec as f64)
+            .min(self.throttle.bytes_per_sec as f64);
+    }
+
+    fn interactive_latency(&mut self, now: Instant) -> Duration {
+        while let Some((completed, _)) = self.latencies.front() {
+            if now.saturating_duration_since(*completed) < self.throttle.latency_window {
+                break;
+            }
+            let _expired = self.latencies.pop_front();
+        }
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut latencies: Vec<_> = self.latencies.iter().map(|(_, latency)| *latency).collect();
+        latencies.sort_unstable();
+        let rank = (latencies.len() as f64 * INTERACTIVE_LATENCY_PERCENTILE).ceil() as usize;
+        latencies[rank.clamp(1, latencies.len()) - 1]
+    }
+
+    fn is_paused(&mut self, now: Instant) -> bool {
+        let max = self.throttle.max_interactive_latency;
+        let slow_in_flight = self.in_flight.values().next().map_or(false, |started| {
+            now.saturating_duration_since(*started) > max
+        });
+        slow_in_flight || self.interactive_latency(now) > max
+    }
+
+    // Takes the tokens of a background read of `size` bytes if it can go ahead, else returns
+    // how long to wait before trying again.
+    fn try_admit(&mut self, size: u64, now: Instant) -> Result<(), Duration> {
+        if self.is_paused(now) {
+            return Err(PAUSED_POLL_INTERVAL);
+        }
+        self.refill(now);
+        let rate = |per_sec| Duration::from_secs_f64(1.0 / f64::max(per_sec, 1.0));
+        if self.op_tokens < 1.0 {
+            let per_sec = self.throttle.iops as f64;
+            return Err(rate(per_sec).mul_f64(1.0 - self.op_tokens));
+        }
+        // Reads go ahead unless the bucket is in debt, which reads larger than what's left in
+        // it put it into.
+        if self.byte_tokens < 0.0 {
+            let per_sec = self.throttle.bytes_per_sec as f64;
+            return Err(rate(per_sec).mul_f64(-self.byte_tokens));
+        }
+        self.op_tokens -= 1.0;
+        self.byte_tokens -= size as f64;
+        Ok(())
+    }
+}
+
+impl ReadScheduler {
+    pub(crate) fn set_throttle(&self, throttle: ReadThrottle) {
+        let mut state = self.state();
+        state.throttle = ReadThrottle {
+            iops: throttle.iops.max(1),
+            bytes_per_sec: throttle.bytes_per_sec.max(1),
+            ..throttle
+        };
+        // Starts over with full buckets.
+        state.refilled = None;
+    }
+
+    /// Waits for a background read of `size` bytes to be allowed to go ahead.
+    pub(crate) async fn admit_background(&self, size: u64) {
+        let mut deferred = false;
+        loop {
+            let wait = {
+                let mut state = self.state();
+                match state.try_admit(size, Instant::now()) {
+                    Ok(()) => {
+                        state.admitted += 1;
+                        if deferred {
+                            state.waiting -= 1;
+                        }
+                        return;
+                    }
+                    Err(wait) => {
+                        if !deferred {
+                            state.waiting += 1;
+                            state.deferred += 1;
+                            deferred = true;
+                        }
+                        wait
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Records an interactive read starting, for its latency to be measured until the returned
+    /// guard is dropped.
+    pub(crate) fn start_interactive(&self) -> InteractiveRead {
+        let mut state = self.state();
+        let id = state.next_read;
+        state.next_read += 1;
+        let started = Instant::now();
+        let _prev = state.in_flight.insert(id, started);
+        InteractiveRead {
+            scheduler: self.clone(),
+            id,
+            started,
+        }
+    }
+
+    #[cfg(any(test, feature = "metrics"))]
+    pub(crate) fn pacing(&self) -> ReadPacing {
+        let mut state = self.state();
+        let now = Instant::now();
+        state.refill(now);
+        ReadPacing {
+            paused: state.is_paused(now),
+            waiting: state.waiting,
+            deferred: state.deferred,
+            admitted: state.admitted,
+            byte_tokens: state.byte_tokens,
+            interactive_latency: state.interactive_latency(now),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// An interactive read in flight, whose latency is recorded once dropped.
+pub(crate) struct InteractiveRead {
+    scheduler: ReadScheduler,
+    id: u64,
+    started: Instant,
+}
+
+impl Drop for InteractiveRead {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        let mut state = self.scheduler.state();
+        let _started = state.in_flight.remove(&self.id);
+        if state.latencies.len() == MAX_LATENCY_SAMPLES {
+            let _oldest = state.latencies.pop_front();
+        }
+        state
+            .latencies
+            .push_back((now, now.saturating_duration_since(self.started)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(iops: u32, bytes_per_sec: u64) -> ReadThrottle {
+        ReadThrottle {
+            iops,
+            bytes_per_sec,
+            max_interactive_latency: Duration::from_millis(100),
+            latency_window: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_reads_are_paced_by_reads_and_bytes_per_sec() {
+        let scheduler = ReadScheduler::default();
+        scheduler.set_throttle(throttle(10, u64::MAX));
+        let start = Instant::now();
+        for _ in 0..30 {
+            scheduler.admit_background(1).await;
+        }
+        // The first second's worth goes ahead right away, then 10 per second.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(2), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(2100), "{:?}", elapsed);
+
+        scheduler.set_throttle(throttle(u32::MAX, 1000));
+        let start = Instant::now();
+        for _ in 0..4 {
+            scheduler.admit_background(1500).await;
+        }
+        // Each read but the first waits for the debt of the one before it to be paid off.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(3500), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(3600), "{:?}", elapsed);
+
+        let pacing = scheduler.pacing();
+        assert_eq!(pacing.admitted, 34);
+        assert_eq!(pacing.waiting, 0);
+        assert!(!pacing.paused);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_reads_pause_while_interactive_reads_are_slow() {
+        let scheduler = ReadScheduler::default();
+        scheduler.set_throttle(throttle(u32::MAX, u64::MAX));
+
+        // Fast interactive reads don't hold background reads back.
+        drop(scheduler.start_interactive());
+        scheduler.admit_background(1).await;
+        assert_eq!(scheduler.pacing().deferred, 0);
+
+        // An interactive read in flight for longer than allowed pauses them.
+        let read = scheduler.start_interactive();
+        tokio::time::advance(Duration::from_millis(150)).await;
+        let background = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.admit_background(1).await }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let pacing = scheduler.pacing();
+        assert!(pacing.paused);
+        assert_eq!(pacing.waiting, 1);
+        assert_eq!(pacing.admitted, 1);
+
+        // As does its latency once done, until it's out of the window.
+        drop(read);
+        let done = Instant::now();
+        assert!(scheduler.pacing().interactive_latency >= Duration::from_millis(650));
+        background.await.expect("background read panicked");
+        assert!(done.elapsed() >= Duration::from_secs(1));
+
+        let pacing = scheduler.pacing();
+        assert!(!pacing.paused);
+        assert_eq!(pacing.deferred, 1);
+        assert_eq!(pacing.admitted, 2);
+        assert_eq!(pacing.interactive_latency, Duration::ZERO);
+    }
+}
@@ -6,45 +6,211 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 use tracing::info;
 
+/// What stored data is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SpaceCategory {
+    /// Chunks stored as an adult.
+    Chunks,
+    /// Registers and other db-backed records, which are the worst to lose to a full disk.
+    Dbs,
+}
+
+/// Marks, in percent of the max capacity, past which an adult tells its elders it's full, for
+/// them to stop giving it new data (`soft`), and refuses new chunks itself (`hard`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watermarks {
+    /// Percent of the max capacity past which we report being full.
+    pub soft: u8,
+    /// Percent of the max capacity past which new chunks are refused.
+    pub hard: u8,
+}
+
+impl Default for Watermarks {
+    fn default() -> Self {
+        Self { soft: 90, hard: 95 }
+    }
+}
+
+// Where the free space left on the disk is read from.
+#[derive(Clone, Copy, Debug)]
+enum Disk {
+    Filesystem,
+    // A disk of the given size, holding nothing but our data.
+    #[cfg(test)]
+    Simulated(usize),
+}
+
 #[derive(Clone, Debug)]
 /// Tracking used space
 pub struct UsedSpace {
-    /// the maximum (inclusive) allocated space for storage
-    max_capacity: usize,
+    /// the maximum (inclusive) allocated space for storage, which can be changed at runtime
+    max_capacity: Arc<AtomicUsize>,
     used_space: Arc<AtomicUsize>,
+    used_by_dbs: Arc<AtomicUsize>,
+    /// space kept for the dbs, which chunks can never take
+    db_reserve: usize,
+    watermarks: Watermarks,
+    disk: Disk,
 }
 
 impl UsedSpace {
     /// Create new UsedSpace tracker
     pub fn new(max_capacity: usize) -> Self {
         Self {
-            max_capacity,
+            max_capacity: Arc::new(AtomicUsize::new(max_capacity)),
             used_space: Arc::new(AtomicUsize::new(0)),
+            used_by_dbs: Arc::new(AtomicUsize::new(0)),
+            db_reserve: 0,
+            watermarks: Watermarks::default(),
+            disk: Disk::Filesystem,
         }
     }
 
-    pub(crate) fn increase(&self, size: usize) {
+    /// Reserves `db_reserve` bytes, of both our capacity and the actual free space on disk, for
+    /// the dbs. Chunks are refused rather than taking any of it.
+    pub fn with_db_reserve(mut self, db_reserve: usize) -> Self {
+        self.db_reserve = db_reserve;
+        self
+    }
+
+    /// Reports being full, and refuses new chunks, past the given `watermarks` rather than the
+    /// default ones. The hard watermark is capped at 100 %, and the soft one at the hard one.
+    pub fn with_watermarks(mut self, watermarks: Watermarks) -> Self {
+        let hard = watermarks.hard.min(100);
+        self.watermarks = Watermarks {
+            soft: watermarks.soft.min(hard),
+            hard,
+        };
+        self
+    }
+
+    /// Measures free space as if we were alone on a disk of `disk_size` bytes.
+    #[cfg(test)]
+    pub(crate) fn with_simulated_disk(mut self, disk_size: usize) -> Self {
+        self.disk = Disk::Simulated(disk_size);
+        self
+    }
+
+    /// Changes the max capacity, for all clones of this tracker. What's stored beyond a lowered
+    /// one is kept, but no more is taken until usage drops below it.
+    pub(crate) fn set_max_capacity(&self, max_capacity: usize) {
+        self.max_capacity.store(max_capacity, Ordering::Relaxed);
+    }
+
+    pub(crate) fn increase(&self, size: usize, category: SpaceCategory) {
         let _ = self.used_space.fetch_add(size, Ordering::Relaxed);
+        if category == SpaceCategory::Dbs {
+            let _ = self.used_by_dbs.fetch_add(size, Ordering::Relaxed);
+        }
     }
 
-    pub(crate) fn decrease(&self, size: usize) {
+    pub(crate) fn decrease(&self, size: usize, category: SpaceCategory) {
         let _ = self.used_space.fetch_sub(size, Ordering::Relaxed);
+        if category == SpaceCategory::Dbs {
+            let _ = self.used_by_dbs.fetch_sub(size, Ordering::Relaxed);
+        }
     }
 
+    /// Whether there's room for `size` more bytes of db records, which may use the reserve.
     pub(crate) fn can_add(&self, size: usize) -> bool {
         let current_used_space = self.used_space.load(Ordering::Relaxed);
-        current_used_space + size <= self.max_capacity
+        current_used_space + size <= self.max_capacity()
+    }
+
+    /// Whether there's room for a chunk of `size` bytes in `dir`, leaving the db reserve alone.
+    pub(crate) fn can_add_chunk(&self, size: usize, dir: &Path) -> bool {
+        size <= self.chunk_headroom(dir)
+    }
+
+    /// Whether chunks have come within one db reserve's worth of running into it.
+    pub(crate) fn approaching_db_reserve(&self, dir: &Path) -> bool {
+        self.db_reserve > 0 && self.chunk_headroom(dir) < self.db_reserve
+    }
+
+    /// Whether we're past the soft watermark, and so to be given no new data.
+    pub(crate) fn past_soft_watermark(&self) -> bool {
+        self.used_space.load(Ordering::Relaxed) >= self.share_of_capacity(self.watermarks.soft)
+    }
+
+    // `percent` of our max capacity, in bytes.
+    fn share_of_capacity(&self, percent: u8) -> usize {
+        let share = self.max_capacity() as u128 * u128::from(percent) / 100;
+        usize::try_from(share).unwrap_or(usize::MAX)
+    }
+
+    /// Bytes of chunks that can still be stored in `dir`, as per both our capacity up to the hard
+    /// watermark and the free space on disk, after setting aside what the dbs haven't used of
+    /// their reserve yet.
+    pub(crate) fn chunk_headroom(&self, dir: &Path) -> usize {
+        let used = self.used_space.load(Ordering::Relaxed);
+        let reserve_left = self
+            .db_reserve
+            .saturating_sub(self.used_by_dbs.load(Ordering::Relaxed));
+
+        let capacity_left = self
+            .share_of_capacity(self.watermarks.hard)
+            .saturating_sub(used)
+            .saturating_sub(reserve_left);
+        let disk_left = match self.free_space(dir) {
+            Ok(free) => free.saturating_sub(reserve_left),
+            Err(error) => {
+                warn!("Failed to read free disk space at {:?}: {:?}", dir, error);
+                usize::MAX
+            }
+        };
+        capacity_left.min(disk_left)
+    }
+
+    /// Bytes reserved for the dbs.
+    pub(crate) fn db_reserve(&self) -> usize {
+        self.db_reserve
+    }
+
+    /// Bytes free on the disk of `dir`.
+    pub(crate) fn free_space(&self, dir: &Path) -> io::Result<usize> {
+        match self.disk {
+            Disk::Filesystem => {
+                // The dir may not have been created yet, its closest existing ancestor is on the
+                // same disk.
+                let existing = dir.ancestors().find(|path| path.exists()).unwrap_or(dir);
+                let free = fs2::available_space(existing)?;
+                Ok(usize::try_from(free).unwrap_or(usize::MAX))
+            }
+            #[cfg(test)]
+            Disk::Simulated(disk_size) => {
+                Ok(disk_size.saturating_sub(self.used_space.load(Ordering::Relaxed)))
+            }
+        }
+    }
+
+    /// Bytes stored, chunks and db records alike.
+    pub(crate) fn used(&self) -> usize {
+        self.used_space.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of db records stored.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn used_by_dbs(&self) -> usize {
+        self.used_by_dbs.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn max_capacity(&self) -> usize {
+        self.max_capacity.load(Ordering::Relaxed)
     }
 
     pub(crate) fn ratio(&self) -> f64 {
         let used = self.used_space.load(Ordering::Relaxed);
-        let max_capacity = self.max_capacity;
+        let max_capacity = self.max_capacity();
         let used_space_ratio = used as f64 / max_capacity as f64;
         info!("Used space: {:?}", used);
         info!("Max capacity: {:?}", max_capacity);
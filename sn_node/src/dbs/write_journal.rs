@@ -0,0 +1,416 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::encrypted_backend::ChunkKey;
+
+use sn_interface::types::{Chunk, ChunkAddress};
+
+use bytes::Bytes;
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{oneshot, Mutex as AsyncMutex, OwnedMutexGuard},
+};
+use xor_name::{XorName, XOR_NAME_LEN};
+
+/// Interval within which chunk writes are committed together by default.
+pub(crate) const DEFAULT_CHUNK_JOURNAL_FLUSH_INTERVAL: Duration = Duration::from_millis(5);
+
+// Written next to, rather than within, the chunk dir so it's never mistaken for a chunk.
+const JOURNAL_FILE: &str = "chunk_journal";
+// Size of the journal past which its chunks are synced to the backend and it's emptied.
+const CHECKPOINT_SIZE: u64 = 64 * 1024 * 1024;
+// Length recorded for a removal, in place of that of the bytes of a chunk.
+const REMOVAL_LEN: u32 = u32::MAX;
+const HEADER_LEN: usize = XOR_NAME_LEN + 4;
+
+/// A record of the journal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum JournalRecord {
+    /// A chunk written.
+    Write(Chunk),
+    /// A chunk journaled earlier, deleted.
+    Removal(ChunkAddress),
+}
+
+/// A write-ahead journal of the chunk writes, for bursts of small writes not to take an fsync
+/// each: the writes appended within the flush interval are written to the journal and synced
+/// together, in a single group commit, then written to the backend unsynced.
+///
+/// Records are `[name][len][bytes]`, a removal being of length `REMOVAL_LEN` without bytes.
+/// The bytes of a chunk are sealed with the `ChunkKey` of an encrypted store, as they are in
+/// its backend, so they're never kept in the clear. Records check themselves, the bytes of a
+/// chunk opening and hashing to its name, so a torn tail is told apart and dropped on replay. Once the journal grows past `CHECKPOINT_SIZE` with all of its
+/// writes applied, their chunks are synced by the backend and it's emptied.
+///
+/// Shared by the clones of a `ChunkStore`. Disabled with a flush interval of zero.
+#[derive(Clone)]
+pub(crate) struct WriteJournal {
+    path: PathBuf,
+    key: Option<ChunkKey>,
+    flush_interval_ms: Arc<AtomicU64>,
+    batch: Arc<Mutex<Batch>>,
+    file: Arc<AsyncMutex<Option<File>>>,
+}
+
+#[derive(Default)]
+struct Batch {
+    bytes: Vec<u8>,
+    waiters: Vec<oneshot::Sender<Result<(), (io::ErrorKind, String)>>>,
+    commit_scheduled: bool,
+    // Chunks written to the journal since it was last emptied.
+    journaled: BTreeSet<XorName>,
+    // Writes appended but not yet applied to the backend and index.
+    unapplied: usize,
+    // Bytes committed to the journal file.
+    size: u64,
+}
+
+/// A checkpoint underway, commits waiting for it to be completed or aborted.
+pub(crate) struct Checkpoint {
+    journal: WriteJournal,
+    file: OwnedMutexGuard<Option<File>>,
+    addresses: Vec<ChunkAddress>,
+}
+
+impl WriteJournal {
+    /// The journal of the store in `root`, disabled, sealing the chunks with `key` if it's
+    /// that of an encrypted store.
+    pub(crate) fn new(root: &Path, key: Option<ChunkKey>) -> Self {
+        Self {
+            path: root.join(JOURNAL_FILE),
+            key,
+            flush_interval_ms: Arc::new(AtomicU64::new(0)),
+            batch: Arc::new(Mutex::new(Batch::default())),
+            file: Arc::new(AsyncMutex::new(None)),
+        }
+    }
+
+    /// Has the writes appended within `interval` committed together, or disables the journal if
+    /// it's zero.
+    pub(crate) fn set_flush_interval(&self, interval: Duration) {
+        self.flush_interval_ms
+            .store(interval.as_millis() as u64, Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.flush_interval_ms.load(Ordering::Relaxed) > 0
+    }
+
+    /// Whether the chunk at `address` was journaled since the journal was last emptied, so its
+    /// deletion is to be journaled too.
+    pub(crate) fn is_journaled(&self, address: &ChunkAddress) -> bool {
+        self.batch().journaled.contains(address.name())
+    }
+
+    /// Appends `record`, returning once it's committed, synced to disk with the others of its
+    /// batch. The write of a chunk is to be reported `applied` once it's in the backend.
+    pub(crate) async fn append(&self, record: &JournalRecord) -> io::Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        let schedule = {
+            let mut batch = self.batch();
+            encode(record, self.key.as_ref(), &mut batch.bytes);
+            batch.waiters.push(sender);
+            if let JournalRecord::Write(chunk) = record {
+                let _new = batch.journaled.insert(*chunk.name());
+                batch.unapplied += 1;
+            }
+            !std::mem::replace(&mut batch.commit_scheduled, true)
+        };
+        if schedule {
+            let journal = self.clone();
+            let interval = Duration::from_millis(self.flush_interval_ms.load(Ordering::Relaxed));
+            let _handle = tokio::spawn(async move {
+                tokio::time::sleep(interval).await;
+                journal.commit().await
+            });
+        }
+        match receiver.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err((kind, error))) => Err(io::Error::new(kind, error)),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "chunk journal commit dropped",
+            )),
+        }
+    }
+
+    /// Reports a chunk write appended as applied to the backend and index, or given up.
+    pub(crate) fn applied(&self) {
+        let mut batch = self.batch();
+        batch.unapplied = batch.unapplied.saturating_sub(1);
+    }
+
+    /// Starts a checkpoint if the journal has grown past `CHECKPOINT_SIZE` and all its writes
+    /// are applied. Commits wait until it's completed or aborted.
+    pub(crate) async fn checkpoint(&self) -> Option<Checkpoint> {
+        if self.batch().size < CHECKPOINT_SIZE {
+            return None;
+        }
+        let file = self.file.clone().lock_owned().await;
+        let mut batch = self.batch();
+        if batch.size < CHECKPOINT_SIZE || batch.unapplied > 0 || !batch.bytes.is_empty() {
+            return None;
+        }
+        let addresses = std::mem::take(&mut batch.journaled)
+            .into_iter()
+            .map(ChunkAddress)
+            .collect();
+        Some(Checkpoint {
+            journal: self.clone(),
+            file,
+            addresses,
+        })
+    }
+
+    /// The records of the journal, up to a torn tail if there's one.
+    pub(crate) async fn records(&self) -> io::Result<Vec<JournalRecord>> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+        let (records, read) = decode(&bytes, self.key.as_ref());
+        if read < bytes.len() {
+            warn!(
+                "ChunkStore: dropping the torn tail of the chunk journal, {} bytes",
+                bytes.len() - read
+            );
+        }
+        Ok(records)
+    }
+
+    /// Empties the journal, its records being all applied and synced.
+    pub(crate) async fn clear(&self) -> io::Result<()> {
+        let mut file = self.file.lock().await;
+        *file = None;
+        match tokio::fs::remove_file(&self.path).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => {}
+        }
+        let mut batch = self.batch();
+        batch.journaled.clear();
+        batch.size = 0;
+        Ok(())
+    }
+
+    // Writes and syncs the batch appended so far, then acks its records.
+    async fn commit(&self) {
+        let mut file = self.file.lock().await;
+        let (bytes, waiters, size) = {
+            let mut batch = self.batch();
+            batch.commit_scheduled = false;
+            (
+                std::mem::take(&mut batch.bytes),
+                std::mem::take(&mut batch.waiters),
+                batch.size,
+            )
+        };
+        if waiters.is_empty() {
+            return;
+        }
+
+        let result = self.write(&mut file, &bytes).await;
+        let result = match result {
+            Ok(()) => {
+                self.batch().size += bytes.len() as u64;
+                Ok(())
+            }
+            Err(error) => {
+                error!(
+                    "ChunkStore: failed to commit to the chunk journal: {}",
+                    error
+                );
+                // Cut what may have been written of the batch, for the records appended later
+                // not to follow a torn one.
+                if let Some(file) = file.as_ref() {
+                    let _ = file.set_len(size).await;
+                }
+                Err((error.kind(), error.to_string()))
+            }
+        };
+        for waiter in waiters {
+            let _ = waiter.send(result.clone());
+        }
+    }
+
+    async fn write(&self, file: &mut Option<File>, bytes: &[u8]) -> io::Result<()> {
+        if file.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            *file = Some(opened);
+        }
+        if let Some(file) = file {
+            file.write_all(bytes).await?;
+            file.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    fn batch(&self) -> MutexGuard<'_, Batch> {
+        self.batch
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Checkpoint {
+    /// The chunks journaled, to be synced by the backend before the journal is emptied.
+    pub(crate) fn addresses(&self) -> &[ChunkAddress] {
+        &self.addresses
+    }
+
+    /// Empties the journal, its chunks being synced.
+    pub(crate) async fn complete(mut self) -> io::Result<()> {
+        if let Some(file) = self.file.as_ref() {
+            file.set_len(0).await?;
+            file.sync_data().await?;
+        }
+        self.journal.batch().size = 0;
+        *self.file = None;
+        Ok(())
+    }
+
+    /// Leaves the journal as it is, its chunks still to be synced.
+    pub(crate) fn abort(self) {
+        let mut batch = self.journal.batch();
+        batch
+            .journaled
+            .extend(self.addresses.iter().map(|address| *address.name()));
+    }
+}
+
+fn encode(record: &JournalRecord, key: Option<&ChunkKey>, bytes: &mut Vec<u8>) {
+    match record {
+        JournalRecord::Write(chunk) => {
+            let sealed = key.map(|key| key.encrypt(chunk.address(), chunk.value()));
+            let value = sealed.as_deref().unwrap_or_else(|| chunk.value());
+            bytes.extend_from_slice(&chunk.name().0);
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value);
+        }
+        JournalRecord::Removal(address) => {
+            bytes.extend_from_slice(&address.name().0);
+            bytes.extend_from_slice(&REMOVAL_LEN.to_le_bytes());
+        }
+    }
+}
+
+// Decodes the records of `bytes`, up to the first which doesn't check, returning them with the
+// number of bytes they took.
+fn decode(bytes: &[u8], key: Option<&ChunkKey>) -> (Vec<JournalRecord>, usize) {
+    let mut records = vec![];
+    let mut read = 0;
+    while bytes.len() - read >= HEADER_LEN {
+        let header = &bytes[read..read + HEADER_LEN];
+        let mut name = [0; XOR_NAME_LEN];
+        name.copy_from_slice(&header[..XOR_NAME_LEN]);
+        let mut len = [0; 4];
+        len.copy_from_slice(&header[XOR_NAME_LEN..]);
+        let len = u32::from_le_bytes(len);
+
+        if len == REMOVAL_LEN {
+            records.push(JournalRecord::Removal(ChunkAddress(XorName(name))));
+            read += HEADER_LEN;
+            continue;
+        }
+        let start = read + HEADER_LEN;
+        let end = start + len as usize;
+        if end > bytes.len() {
+            break;
+        }
+        let value = match key {
+            Some(key) => match key.decrypt(&ChunkAddress(XorName(name)), &bytes[start..end]) {
+                Ok(value) => Bytes::from(value),
+                Err(_) => break,
+            },
+            None => Bytes::copy_from_slice(&bytes[start..end]),
+        };
+        let chunk = Chunk::new(value);
+        if chunk.name().0 != name {
+            break;
+        }
+        records.push(JournalRecord::Write(chunk));
+        read = end;
+    }
+    (records, read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sn_interface::types::utils::random_bytes;
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_appends_are_committed_together_and_replayed() -> io::Result<()> {
+        let root = tempdir()?;
+        let journal = WriteJournal::new(root.path(), None);
+        journal.set_flush_interval(Duration::from_millis(20));
+        assert!(journal.is_enabled());
+
+        let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(100)))
+            .take(10)
+            .collect();
+        let writes: Vec<_> = chunks.iter().cloned().map(JournalRecord::Write).collect();
+        let appends = writes.iter().map(|record| journal.append(record));
+        for result in futures::future::join_all(appends).await {
+            result?;
+        }
+        let removed = JournalRecord::Removal(*chunks[0].address());
+        journal.append(&removed).await?;
+        let journaled = *chunks[1].address();
+        assert!(journal.is_journaled(&journaled));
+
+        let mut expected = writes;
+        expected.push(removed);
+        let records = journal.records().await?;
+        assert_eq!(records.len(), expected.len());
+        assert!(expected.iter().all(|record| records.contains(record)));
+        assert_eq!(records.last(), expected.last());
+
+        journal.clear().await?;
+        assert!(journal.records().await?.is_empty());
+        assert!(!journal.is_journaled(&journaled));
+
+        Ok(())
+    }
+
+    #[test]
+    fn torn_tail_is_dropped() {
+        let chunks: Vec<Chunk> = std::iter::repeat_with(|| Chunk::new(random_bytes(64)))
+            .take(2)
+            .collect();
+        let mut bytes = vec![];
+        encode(&JournalRecord::Write(chunks[0].clone()), None, &mut bytes);
+        let whole = bytes.len();
+        encode(&JournalRecord::Write(chunks[1].clone()), None, &mut bytes);
+
+        // Cut short, and of bytes not hashing to the name.
+        let (records, read) = decode(&bytes[..bytes.len() - 1], None);
+        assert_eq!(records, vec![JournalRecord::Write(chunks[0].clone())]);
+        assert_eq!(read, whole);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(decode(&bytes, None).1, whole);
+    }
+}
@@ -0,0 +1,804 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Bootstrap contacts published in DNS.
+//!
+//! A network's contacts can be published in a TXT record of a DNS name, for nodes to be pointed
+//! at the name with `--bootstrap-dns` rather than given a file of contacts, and for the contacts
+//! to be updated without redistributing anything. The record is signed with a contacts key,
+//! certified by the network's genesis key, which nodes are given already, as
+//!
+//! ```text
+//! sn2 g=<genesis key> k=<contacts key> p=<certificate> t=<issued> c=<addr>,<addr>,... s=<signature>
+//! ```
+//!
+//! the keys and signatures hex-encoded, the certificate being the signature of the genesis key
+//! over the contacts key, the signature that of the contacts key over all that precedes ` s=`,
+//! and `<issued>` the Unix time in seconds the record was signed at. The contacts key is
+//! generated and certified as the network starts, and kept by the node which started it, the
+//! genesis secret key being kept by nobody. Records older than
+//! `MAX_RECORD_AGE`, or than the last one a node got, are rejected, for a captured record not to
+//! be replayed once the contacts have moved on. A record longer than the 255 bytes of a TXT
+//! string is split over several strings, which are concatenated back when read. SRV records have
+//! no room for a signature, hence TXT.
+//!
+//! `sn_node contacts sign` prints the record of a network, from the root dir of the node which
+//! started it, and `sn_node contacts resolve` the contacts a node would get from a name.
+
+use crate::node::get_contacts_key;
+
+use rand::Rng;
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use structopt::StructOpt;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+// Version tag the records start with.
+const RECORD_TAG: &str = "sn2";
+// Most bytes of a single string of a TXT record.
+const MAX_TXT_STRING_LEN: usize = 255;
+// How long a record is accepted for after being signed, records having to be signed anew within it.
+const MAX_RECORD_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+// File of the root dir the issue time of the last record got is kept in.
+const LAST_ISSUED_FILE: &str = "dns_contacts_issued";
+// Port DNS is served on.
+const DNS_PORT: u16 = 53;
+// Size of the UDP responses we tell the resolver we take, records of a few dozen contacts fitting.
+const UDP_PAYLOAD_SIZE: u16 = 4096;
+// How long the resolver is given to respond, over UDP then again over TCP.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+// Where the resolvers of the system are listed.
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+const TYPE_TXT: u16 = 16;
+const TYPE_OPT: u16 = 41;
+const CLASS_IN: u16 = 1;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_NAME_ERROR: u16 = 3;
+
+/// Why no contacts could be had from DNS.
+#[derive(Debug, Error)]
+pub enum ContactsError {
+    /// A record is not a contacts record, or is malformed.
+    #[error("malformed contacts record: {0}")]
+    Malformed(String),
+    /// A record is of another network.
+    #[error("contacts record of another network, with genesis key {0}")]
+    WrongNetwork(String),
+    /// The contacts key of a record isn't certified by the genesis key.
+    #[error("contacts key not certified by the genesis key")]
+    InvalidCertificate,
+    /// The signature of a record doesn't verify against its contacts key.
+    #[error("contacts record not signed with its contacts key")]
+    InvalidSignature,
+    /// A record was issued more than `MAX_RECORD_AGE` ago, or before the last one we got.
+    #[error("stale contacts record, issued at {0}")]
+    Stale(u64),
+    /// No record of the name verified.
+    #[error("no valid contacts record at {0}")]
+    NoRecord(String),
+    /// No resolver was given, and none could be read from the system's config.
+    #[error(
+        "no DNS resolver given with --dns-resolver, nor listed in {}: {0}",
+        RESOLV_CONF
+    )]
+    NoResolver(String),
+    /// The resolver responded with something we can't make sense of.
+    #[error("malformed DNS response: {0}")]
+    MalformedResponse(&'static str),
+    /// The resolver couldn't be reached or failed to respond in time.
+    #[error("DNS query failed: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The contacts of a network, as published in DNS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContactsRecord {
+    /// The network's genesis key.
+    pub genesis_key: bls::PublicKey,
+    /// When the record was signed, in seconds since the Unix epoch.
+    pub issued: u64,
+    /// Nodes to bootstrap from, usually elders.
+    pub contacts: BTreeSet<SocketAddr>,
+}
+
+/// Certifies `contacts_key` with the secret key of the network's genesis key, for contacts
+/// records signed with it to be trusted by the network's nodes.
+pub fn certify_contacts_key(
+    genesis_secret_key: &bls::SecretKey,
+    contacts_key: &bls::PublicKey,
+) -> bls::Signature {
+    genesis_secret_key.sign(certified_part(contacts_key))
+}
+
+// What the genesis key signs to certify a contacts key, tagged so the signature can't be taken
+// for one over anything else.
+fn certified_part(contacts_key: &bls::PublicKey) -> String {
+    format!(
+        "{} contacts key {}",
+        RECORD_TAG,
+        hex::encode(contacts_key.to_bytes())
+    )
+}
+
+impl ContactsRecord {
+    /// Signs the record with the secret key of the contacts key, `certificate` being the
+    /// certificate of the contacts key by the network's genesis key, returning the strings of
+    /// the TXT record to publish it in.
+    pub fn sign(
+        &self,
+        contacts_secret_key: &bls::SecretKey,
+        certificate: &bls::Signature,
+    ) -> Result<Vec<String>, ContactsError> {
+        let contacts_key = contacts_secret_key.public_key();
+        if !self
+            .genesis_key
+            .verify(certificate, certified_part(&contacts_key))
+        {
+            return Err(ContactsError::InvalidCertificate);
+        }
+        let signed = self.signed_part(&contacts_key, certificate);
+        let sig = contacts_secret_key.sign(&signed);
+        let record = format!("{} s={}", signed, hex::encode(sig.to_bytes()));
+        Ok(split_txt(&record))
+    }
+
+    /// Parses the record in `txt`, the strings of a TXT record concatenated, checking it's of
+    /// the network of `genesis_key`, signed with a contacts key it certified, and issued no
+    /// earlier than `not_before` nor more than `MAX_RECORD_AGE` ago.
+    pub fn verify(
+        txt: &str,
+        genesis_key: &bls::PublicKey,
+        not_before: u64,
+    ) -> Result<Self, ContactsError> {
+        let malformed = |what: &str| ContactsError::Malformed(what.to_string());
+        let (signed, sig) = txt
+            .rsplit_once(" s=")
+            .ok_or_else(|| malformed("no signature"))?;
+        let mut fields = signed.split(' ');
+        if fields.next() != Some(RECORD_TAG) {
+            return Err(malformed("not a contacts record"));
+        }
+        let key_hex = fields
+            .next()
+            .and_then(|field| field.strip_prefix("g="))
+            .ok_or_else(|| malformed("no genesis key"))?;
+        let contacts_key_hex = fields
+            .next()
+            .and_then(|field| field.strip_prefix("k="))
+            .ok_or_else(|| malformed("no contacts key"))?;
+        let certificate = fields
+            .next()
+            .and_then(|field| field.strip_prefix("p="))
+            .ok_or_else(|| malformed("no certificate"))?;
+        let issued_field = fields
+            .next()
+            .and_then(|field| field.strip_prefix("t="))
+            .ok_or_else(|| malformed("no issue time"))?;
+        let contacts_list = fields
+            .next()
+            .and_then(|field| field.strip_prefix("c="))
+            .ok_or_else(|| malformed("no contacts"))?;
+        if fields.next().is_some() {
+            return Err(malformed("unexpected field"));
+        }
+
+        let record_key = hex::decode(key_hex)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(|bytes| bls::PublicKey::from_bytes(bytes).ok())
+            .ok_or_else(|| malformed("invalid genesis key"))?;
+        if record_key != *genesis_key {
+            return Err(ContactsError::WrongNetwork(key_hex.to_string()));
+        }
+        let contacts_key = hex::decode(contacts_key_hex)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(|bytes| bls::PublicKey::from_bytes(bytes).ok())
+            .ok_or_else(|| malformed("invalid contacts key"))?;
+        let certificate =
+            decode_signature(certificate).ok_or_else(|| malformed("invalid certificate"))?;
+        if !genesis_key.verify(&certificate, certified_part(&contacts_key)) {
+            return Err(ContactsError::InvalidCertificate);
+        }
+        let sig = decode_signature(sig).ok_or_else(|| malformed("invalid signature"))?;
+        if !contacts_key.verify(&sig, signed) {
+            return Err(ContactsError::InvalidSignature);
+        }
+
+        let issued = issued_field
+            .parse()
+            .map_err(|_| malformed("invalid issue time"))?;
+        let oldest = now_secs().saturating_sub(MAX_RECORD_AGE.as_secs());
+        if issued < oldest.max(not_before) {
+            return Err(ContactsError::Stale(issued));
+        }
+
+        let contacts = contacts_list
+            .split(',')
+            .filter(|contact| !contact.is_empty())
+            .map(|contact| contact.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| malformed("invalid contact"))?;
+        Ok(Self {
+            genesis_key: record_key,
+            issued,
+            contacts,
+        })
+    }
+
+    fn signed_part(&self, contacts_key: &bls::PublicKey, certificate: &bls::Signature) -> String {
+        let contacts: Vec<_> = self.contacts.iter().map(ToString::to_string).collect();
+        format!(
+            "{} g={} k={} p={} t={} c={}",
+            RECORD_TAG,
+            hex::encode(self.genesis_key.to_bytes()),
+            hex::encode(contacts_key.to_bytes()),
+            hex::encode(certificate.to_bytes()),
+            self.issued,
+            contacts.join(",")
+        )
+    }
+}
+
+fn decode_signature(sig: &str) -> Option<bls::Signature> {
+    hex::decode(sig)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .and_then(|bytes| bls::Signature::from_bytes(bytes).ok())
+}
+
+/// Resolves the contacts published at `name` for the network of `genesis_key`, asking
+/// `resolver`, or the first resolver of the system if not given. Of the records which verify,
+/// none issued before `not_before`, the latest issued is returned, the others being ignored.
+///
+/// With no `resolver` given, and none to be read from the system's config, it fails with
+/// `ContactsError::NoResolver`, which is a misconfiguration rather than a lack of contacts.
+pub async fn resolve_contacts(
+    name: &str,
+    resolver: Option<SocketAddr>,
+    genesis_key: &bls::PublicKey,
+    not_before: u64,
+) -> Result<ContactsRecord, ContactsError> {
+    let resolver = match resolver {
+        Some(resolver) => resolver,
+        None => system_resolver(Path::new(RESOLV_CONF)).await?,
+    };
+    let mut latest: Option<ContactsRecord> = None;
+    for txt in query_txt(name, resolver).await? {
+        match ContactsRecord::verify(&txt, genesis_key, not_before) {
+            Ok(record) if latest.as_ref().map(|latest| latest.issued) < Some(record.issued) => {
+                latest = Some(record);
+            }
+            Ok(_) => debug!("Ignoring a TXT record of {} superseded by another", name),
+            Err(error) => warn!("Ignoring a TXT record of {}: {}", name, error),
+        }
+    }
+    latest.ok_or_else(|| ContactsError::NoRecord(name.to_string()))
+}
+
+/// The issue time of the last record got, as kept in `root_dir`, 0 if none was.
+pub async fn last_issued(root_dir: &Path) -> u64 {
+    tokio::fs::read_to_string(root_dir.join(LAST_ISSUED_FILE))
+        .await
+        .ok()
+        .and_then(|issued| issued.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Keeps the issue time of the record got in `root_dir`, for older ones to be rejected.
+pub async fn store_last_issued(root_dir: &Path, issued: u64) -> io::Result<()> {
+    tokio::fs::write(root_dir.join(LAST_ISSUED_FILE), issued.to_string()).await
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// Splits `record` into strings fitting in a TXT record.
+fn split_txt(record: &str) -> Vec<String> {
+    // The record is ASCII, so any byte is a char boundary.
+    record
+        .as_bytes()
+        .chunks(MAX_TXT_STRING_LEN)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect()
+}
+
+// The first resolver listed in the system's config at `resolv_conf`.
+async fn system_resolver(resolv_conf: &Path) -> Result<SocketAddr, ContactsError> {
+    let conf = tokio::fs::read_to_string(resolv_conf)
+        .await
+        .map_err(|error| ContactsError::NoResolver(error.to_string()))?;
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .ok_or_else(|| ContactsError::NoResolver("no nameserver listed".to_string()))
+}
+
+// Queries the TXT records of `name`, each record's strings concatenated. Over UDP first, then
+// over TCP should the response not fit.
+async fn query_txt(name: &str, resolver: SocketAddr) -> Result<Vec<String>, ContactsError> {
+    let id = rand::thread_rng().gen();
+    let query = encode_query(id, name)?;
+
+    let bind_addr = match resolver {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(resolver).await?;
+    let _sent = socket.send(&query).await?;
+    let mut buf = vec![0; UDP_PAYLOAD_SIZE as usize];
+    let response = loop {
+        let len = timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response over UDP"))??;
+        // Responses to other queries, e.g. late ones, are not ours to parse.
+        if len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            break &buf[..len];
+        }
+    };
+
+    match decode_response(id, response)? {
+        Some(records) => Ok(records),
+        None => {
+            debug!(
+                "TXT records of {} truncated over UDP, asking over TCP",
+                name
+            );
+            let response = timeout(QUERY_TIMEOUT, query_over_tcp(&query, resolver))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response over TCP"))??;
+            decode_response(id, &response)?
+                .ok_or(ContactsError::MalformedResponse("truncated over TCP"))
+        }
+    }
+}
+
+async fn query_over_tcp(query: &[u8], resolver: SocketAddr) -> io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(resolver).await?;
+    let len = u16::try_from(query.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "query too long"))?;
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(query).await?;
+    let len = stream.read_u16().await?;
+    let mut response = vec![0; len as usize];
+    let _read = stream.read_exact(&mut response).await?;
+    Ok(response)
+}
+
+// A query for the TXT records of `name`, with an OPT record for responses of up to
+// `UDP_PAYLOAD_SIZE` bytes over UDP.
+fn encode_query(id: u16, name: &str) -> Result<Vec<u8>, ContactsError> {
+    let mut query = vec![];
+    for word in [id, FLAG_RECURSION_DESIRED, 1, 0, 0, 1] {
+        query.extend_from_slice(&word.to_be_bytes());
+    }
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(ContactsError::Malformed(format!(
+                "invalid DNS name {}",
+                name
+            )));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    // OPT: root name, type, payload size as class, no extended rcode nor flags, no data.
+    query.push(0);
+    query.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    query.extend_from_slice(&UDP_PAYLOAD_SIZE.to_be_bytes());
+    query.extend_from_slice(&[0; 6]);
+    Ok(query)
+}
+
+// The TXT records in the response to query `id`, or None if it was truncated.
+fn decode_response(id: u16, response: &[u8]) -> Result<Option<Vec<String>>, ContactsError> {
+    let mut reader = Reader {
+        bytes: response,
+        at: 0,
+    };
+    let header = reader.words::<6>()?;
+    let [response_id, flags, questions, answers, _, _] = header;
+    if response_id != id || flags & FLAG_RESPONSE == 0 {
+        return Err(ContactsError::MalformedResponse(
+            "not a response to our query",
+        ));
+    }
+    if flags & FLAG_TRUNCATED != 0 {
+        return Ok(None);
+    }
+    match flags & 0xf {
+        0 => {}
+        RCODE_NAME_ERROR => return Ok(Some(vec![])),
+        _ => return Err(ContactsError::MalformedResponse("query refused or failed")),
+    }
+
+    for _ in 0..questions {
+        reader.skip_name()?;
+        let _type_and_class = reader.take(4)?;
+    }
+    let mut records = vec![];
+    for _ in 0..answers {
+        reader.skip_name()?;
+        let [rtype, _class, _ttl_high, _ttl_low, len] = reader.words::<5>()?;
+        let data = reader.take(len as usize)?;
+        if rtype != TYPE_TXT {
+            // e.g. the CNAME the name is an alias of.
+            continue;
+        }
+        let mut txt = vec![];
+        let mut data = Reader { bytes: data, at: 0 };
+        while data.at < data.bytes.len() {
+            let len = data.take(1)?[0] as usize;
+            txt.extend_from_slice(data.take(len)?);
+        }
+        records.push(String::from_utf8_lossy(&txt).into_owned());
+    }
+    Ok(Some(records))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ContactsError> {
+        let taken = self
+            .bytes
+            .get(self.at..self.at + len)
+            .ok_or(ContactsError::MalformedResponse("response cut short"))?;
+        self.at += len;
+        Ok(taken)
+    }
+
+    fn words<const N: usize>(&mut self) -> Result<[u16; N], ContactsError> {
+        let mut words = [0; N];
+        for word in &mut words {
+            let bytes = self.take(2)?;
+            *word = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(words)
+    }
+
+    // Skips a name, which ends with the root label or a pointer to another name.
+    fn skip_name(&mut self) -> Result<(), ContactsError> {
+        loop {
+            let len = self.take(1)?[0];
+            match len {
+                0 => return Ok(()),
+                len if len & 0xc0 == 0xc0 => {
+                    let _pointer = self.take(1)?;
+                    return Ok(());
+                }
+                len => {
+                    let _label = self.take(len as usize)?;
+                }
+            }
+        }
+    }
+}
+
+/// Management of the contacts of a network published in DNS.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_node contacts")]
+pub enum ContactsCmd {
+    /// Print the TXT record publishing the given contacts, signed with the contacts key kept in
+    /// the root dir of the node which started the network
+    Sign {
+        /// The root dir of the node which started the network
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+        /// The contacts to publish, e.g. '["1.2.3.4:12000","5.6.7.8:12000"]'
+        #[structopt(parse(try_from_str = serde_json::from_str))]
+        contacts: BTreeSet<SocketAddr>,
+    },
+    /// Print the contacts a node would get from the TXT records of a name
+    Resolve {
+        /// The DNS name the contacts are published at
+        name: String,
+        /// The network's genesis key, in hex
+        genesis_key: String,
+        /// The resolver to ask, rather than the system's
+        #[structopt(long)]
+        resolver: Option<SocketAddr>,
+    },
+}
+
+impl ContactsCmd {
+    /// Runs the command, writing its output to `out`.
+    pub fn run(&self, out: &mut impl Write) -> eyre::Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        match self {
+            Self::Sign { root_dir, contacts } => {
+                let key = runtime
+                    .block_on(get_contacts_key(root_dir))?
+                    .ok_or_else(|| eyre::eyre!("No contacts key in {}", root_dir.display()))?;
+                let record = ContactsRecord {
+                    genesis_key: key.genesis_key,
+                    issued: now_secs(),
+                    contacts: contacts.clone(),
+                };
+                let strings: Vec<_> = record
+                    .sign(&key.secret_key, &key.certificate)?
+                    .iter()
+                    .map(|string| format!("\"{}\"", string))
+                    .collect();
+                writeln!(out, "{}", strings.join(" "))?;
+            }
+            Self::Resolve {
+                name,
+                genesis_key,
+                resolver,
+            } => {
+                let genesis_key = hex::decode(genesis_key)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .and_then(|bytes| bls::PublicKey::from_bytes(bytes).ok())
+                    .ok_or_else(|| eyre::eyre!("Invalid genesis key"))?;
+                let record =
+                    runtime.block_on(resolve_contacts(name, *resolver, &genesis_key, 0))?;
+                for contact in record.contacts {
+                    writeln!(out, "{}", contact)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use tokio::net::TcpListener;
+
+    // A record of `count` contacts, with the contacts key to sign it with and its certificate.
+    fn record(count: usize) -> (ContactsRecord, bls::SecretKey, bls::Signature) {
+        let genesis_secret_key = bls::SecretKey::random();
+        let secret_key = bls::SecretKey::random();
+        let certificate = certify_contacts_key(&genesis_secret_key, &secret_key.public_key());
+        let contacts = (0..count)
+            .map(|i| SocketAddr::from(([10, 0, (i / 256) as u8, (i % 256) as u8], 12000)))
+            .collect();
+        let record = ContactsRecord {
+            genesis_key: genesis_secret_key.public_key(),
+            issued: now_secs(),
+            contacts,
+        };
+        (record, secret_key, certificate)
+    }
+
+    #[test]
+    fn signed_records_verify_against_their_genesis_key_only() -> Result<()> {
+        let (record, secret_key, certificate) = record(20);
+        let strings = record.sign(&secret_key, &certificate)?;
+        assert!(strings.len() > 1);
+        assert!(strings
+            .iter()
+            .all(|string| string.len() <= MAX_TXT_STRING_LEN));
+
+        let txt = strings.concat();
+        assert_eq!(
+            ContactsRecord::verify(&txt, &record.genesis_key, 0)?,
+            record
+        );
+
+        let other_network = bls::SecretKey::random().public_key();
+        assert!(matches!(
+            ContactsRecord::verify(&txt, &other_network, 0),
+            Err(ContactsError::WrongNetwork(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_records_are_rejected() -> Result<()> {
+        let (record, secret_key, certificate) = record(3);
+        let txt = record.sign(&secret_key, &certificate)?.concat();
+
+        let redirected = txt.replacen("10.0.0.1", "66.6.6.6", 1);
+        assert!(matches!(
+            ContactsRecord::verify(&redirected, &record.genesis_key, 0),
+            Err(ContactsError::InvalidSignature)
+        ));
+        let unsigned = txt.split(" s=").next().unwrap_or_default();
+        assert!(matches!(
+            ContactsRecord::verify(unsigned, &record.genesis_key, 0),
+            Err(ContactsError::Malformed(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn records_signed_with_uncertified_keys_are_rejected() -> Result<()> {
+        let (record, secret_key, certificate) = record(3);
+        let uncertified = bls::SecretKey::random();
+        assert!(matches!(
+            record.sign(&uncertified, &certificate),
+            Err(ContactsError::InvalidCertificate)
+        ));
+
+        // Nor can a record be passed off with the key and certificate of another one swapped in.
+        let txt = record.sign(&secret_key, &certificate)?.concat();
+        let forged = txt.replace(
+            &hex::encode(secret_key.public_key().to_bytes()),
+            &hex::encode(uncertified.public_key().to_bytes()),
+        );
+        assert!(matches!(
+            ContactsRecord::verify(&forged, &record.genesis_key, 0),
+            Err(ContactsError::InvalidCertificate)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_missing_resolv_conf_is_no_resolver() -> Result<()> {
+        let root_dir = tempfile::tempdir()?;
+        let resolv_conf = root_dir.path().join("resolv.conf");
+        assert!(matches!(
+            system_resolver(&resolv_conf).await,
+            Err(ContactsError::NoResolver(_))
+        ));
+
+        std::fs::write(&resolv_conf, "# nothing but comments\n")?;
+        assert!(matches!(
+            system_resolver(&resolv_conf).await,
+            Err(ContactsError::NoResolver(_))
+        ));
+
+        std::fs::write(&resolv_conf, "search example.org\nnameserver 10.0.0.53\n")?;
+        assert_eq!(
+            system_resolver(&resolv_conf).await?,
+            SocketAddr::from(([10, 0, 0, 53], DNS_PORT))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn expired_and_superseded_records_are_rejected() -> Result<()> {
+        let (mut record, secret_key, certificate) = record(3);
+        let txt = record.sign(&secret_key, &certificate)?.concat();
+        assert_eq!(
+            ContactsRecord::verify(&txt, &record.genesis_key, record.issued)?,
+            record
+        );
+        assert!(matches!(
+            ContactsRecord::verify(&txt, &record.genesis_key, record.issued + 1),
+            Err(ContactsError::Stale(issued)) if issued == record.issued
+        ));
+
+        record.issued -= MAX_RECORD_AGE.as_secs() + 60;
+        let expired = record.sign(&secret_key, &certificate)?.concat();
+        assert!(matches!(
+            ContactsRecord::verify(&expired, &record.genesis_key, 0),
+            Err(ContactsError::Stale(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn the_last_issue_time_got_is_kept() -> Result<()> {
+        let root_dir = tempfile::tempdir()?;
+        assert_eq!(last_issued(root_dir.path()).await, 0);
+        store_last_issued(root_dir.path(), 1_660_000_000).await?;
+        assert_eq!(last_issued(root_dir.path()).await, 1_660_000_000);
+        Ok(())
+    }
+
+    // A response to `query` with a CNAME then a TXT record of `strings`, the names pointing at
+    // the question's.
+    fn response(query: &[u8], strings: &[String], truncated: bool) -> Vec<u8> {
+        let question_end = query.len() - 11;
+        let mut response = query[..question_end].to_vec();
+        let flags =
+            FLAG_RESPONSE | FLAG_RECURSION_DESIRED | if truncated { FLAG_TRUNCATED } else { 0 };
+        response[2..4].copy_from_slice(&flags.to_be_bytes());
+        response[6..8].copy_from_slice(&2_u16.to_be_bytes());
+        response[10..12].copy_from_slice(&0_u16.to_be_bytes());
+
+        let cname = [4, b'a', b'l', b'i', b'a', 0xc0, 12];
+        response.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, cname.len() as u8]);
+        response.extend_from_slice(&cname);
+
+        let mut data = vec![];
+        for string in strings {
+            data.push(string.len() as u8);
+            data.extend_from_slice(string.as_bytes());
+        }
+        response.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60]);
+        response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        response.extend_from_slice(&data);
+        response
+    }
+
+    #[tokio::test]
+    async fn contacts_are_resolved_over_tcp_once_truncated_over_udp() -> Result<()> {
+        let (record, secret_key, certificate) = record(50);
+        let strings = record.sign(&secret_key, &certificate)?;
+
+        let udp = UdpSocket::bind("127.0.0.1:0").await?;
+        let resolver = udp.local_addr()?;
+        let tcp = TcpListener::bind(resolver).await?;
+        let udp_strings = strings.clone();
+        let _udp = tokio::spawn(async move {
+            let mut buf = vec![0; 512];
+            let (len, from) = udp.recv_from(&mut buf).await?;
+            let _sent = udp
+                .send_to(&response(&buf[..len], &udp_strings, true), from)
+                .await?;
+            Ok::<_, io::Error>(())
+        });
+        let _tcp = tokio::spawn(async move {
+            let (mut stream, _) = tcp.accept().await?;
+            let len = stream.read_u16().await?;
+            let mut query = vec![0; len as usize];
+            let _read = stream.read_exact(&mut query).await?;
+            let response = response(&query, &strings, false);
+            stream
+                .write_all(&(response.len() as u16).to_be_bytes())
+                .await?;
+            stream.write_all(&response).await?;
+            Ok::<_, io::Error>(())
+        });
+
+        let resolved = resolve_contacts(
+            "contacts.example.org.",
+            Some(resolver),
+            &record.genesis_key,
+            0,
+        )
+        .await?;
+        assert_eq!(resolved, record);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn records_of_other_networks_are_no_contacts() -> Result<()> {
+        let (record, secret_key, certificate) = record(3);
+        let strings = record.sign(&secret_key, &certificate)?;
+
+        let udp = UdpSocket::bind("127.0.0.1:0").await?;
+        let resolver = udp.local_addr()?;
+        let _udp = tokio::spawn(async move {
+            let mut buf = vec![0; 512];
+            let (len, from) = udp.recv_from(&mut buf).await?;
+            let _sent = udp
+                .send_to(&response(&buf[..len], &strings, false), from)
+                .await?;
+            Ok::<_, io::Error>(())
+        });
+
+        let other_network = bls::SecretKey::random().public_key();
+        assert!(matches!(
+            resolve_contacts("contacts.example.org", Some(resolver), &other_network, 0).await,
+            Err(ContactsError::NoRecord(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encrypted keystore for the node's secret keys.
+//!
+//! With `--encrypt-keys`, the node's network keypair, reward secret key, the storage key of an
+//! encrypted chunk store and, on the node which started the network, the key its DNS contacts
+//! are signed with are kept in its root dir sealed with a passphrase: a key is derived from it
+//! with Argon2id and a random salt, and the key bytes encrypted with ChaCha20-Poly1305, as
+//!
+//! ```text
+//! salt (16) | nonce (12) | ciphertext and tag
+//! ```
+//!
+//! The passphrase is taken from the `SN_NODE_KEYSTORE_PASSPHRASE` env var, else from the OS
+//! keyring (with the `os-keyring` feature), else prompted for on the terminal, once per process.
+//! `sn_node keystore encrypt` seals the keys of a root dir which are still in the clear.
+
+use crate::node::seal_keys;
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use lazy_static::lazy_static;
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+use structopt::StructOpt;
+use thiserror::Error;
+
+/// Env var the keystore passphrase is taken from first.
+pub const PASSPHRASE_ENV_VAR: &str = "SN_NODE_KEYSTORE_PASSPHRASE";
+/// Service and user the keystore passphrase is kept under in the OS keyring.
+#[cfg(feature = "os-keyring")]
+const KEYRING_ENTRY: (&str, &str) = ("sn_node", "keystore");
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+lazy_static! {
+    // The passphrase once unlocked, for it not to be asked for again.
+    static ref PASSPHRASE: Mutex<Option<Passphrase>> = Mutex::new(None);
+}
+
+/// Why the keystore can't be unlocked.
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    /// None of the env var, the OS keyring or a terminal gave a passphrase.
+    #[error(
+        "no passphrase to unlock the keystore with: set {} or run the node on a terminal",
+        PASSPHRASE_ENV_VAR
+    )]
+    NoPassphrase,
+    /// The sealed key isn't that of this passphrase, or was tampered with.
+    #[error("wrong passphrase, or a tampered key")]
+    WrongPassphrase,
+    /// The sealed key is too short to be one.
+    #[error("not a sealed key")]
+    Malformed,
+    /// The key couldn't be derived from the passphrase.
+    #[error("couldn't derive the key from the passphrase: {0}")]
+    Kdf(String),
+    /// The blocking task unlocking the keystore panicked or was cancelled.
+    #[error("keystore task failed: {0}")]
+    Task(String),
+}
+
+/// Passphrase the keystore is sealed with.
+#[derive(Clone)]
+pub(crate) struct Passphrase(String);
+
+impl Debug for Passphrase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Passphrase(<redacted>)")
+    }
+}
+
+/// Returns the passphrase of the keystore, looking it up on first use.
+pub(crate) fn passphrase() -> Result<Passphrase, KeystoreError> {
+    let mut unlocked = PASSPHRASE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(passphrase) = unlocked.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = lookup_passphrase()?;
+    *unlocked = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+// Takes the passphrase from the env var, the OS keyring, or the terminal, in that order.
+fn lookup_passphrase() -> Result<Passphrase, KeystoreError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        return Ok(Passphrase(passphrase));
+    }
+    #[cfg(feature = "os-keyring")]
+    match keyring::Entry::new(KEYRING_ENTRY.0, KEYRING_ENTRY.1).and_then(|e| e.get_password()) {
+        Ok(passphrase) => return Ok(Passphrase(passphrase)),
+        Err(error) => debug!("No keystore passphrase in the OS keyring: {}", error),
+    }
+    rpassword::prompt_password("Keystore passphrase: ")
+        .map(Passphrase)
+        .map_err(|_| KeystoreError::NoPassphrase)
+}
+
+/// Seals `key` with `passphrase`.
+pub(crate) fn seal(passphrase: &Passphrase, key: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let cipher = cipher(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), key)
+        .map_err(|_| KeystoreError::Malformed)?;
+
+    let mut sealed = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Looks the passphrase of the keystore up, off the async runtime, for it to be at hand before
+/// the node gets going rather than asked for on a worker thread.
+pub(crate) async fn unlock() -> Result<(), KeystoreError> {
+    tokio::task::spawn_blocking(|| passphrase().map(|_| ()))
+        .await
+        .map_err(|err| KeystoreError::Task(err.to_string()))?
+}
+
+/// Seals `key` with the keystore's passphrase, off the async runtime: looking the passphrase up
+/// can wait on the terminal or the OS keyring, and Argon2id takes its time.
+pub(crate) async fn seal_async(key: Vec<u8>) -> Result<Vec<u8>, KeystoreError> {
+    tokio::task::spawn_blocking(move || seal(&passphrase()?, &key))
+        .await
+        .map_err(|err| KeystoreError::Task(err.to_string()))?
+}
+
+/// Opens a key `sealed` with the keystore's passphrase, off the async runtime as `seal_async`.
+pub(crate) async fn open_async(sealed: Vec<u8>) -> Result<Vec<u8>, KeystoreError> {
+    tokio::task::spawn_blocking(move || open(&passphrase()?, &sealed))
+        .await
+        .map_err(|err| KeystoreError::Task(err.to_string()))?
+}
+
+/// Opens a key `sealed` with `passphrase`.
+pub(crate) fn open(passphrase: &Passphrase, sealed: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+    if sealed.len() < SALT_LEN + NONCE_LEN {
+        return Err(KeystoreError::Malformed);
+    }
+    let (salt, rest) = sealed.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| KeystoreError::WrongPassphrase)
+}
+
+fn cipher(passphrase: &Passphrase, salt: &[u8]) -> Result<ChaCha20Poly1305, KeystoreError> {
+    let mut key = [0; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.0.as_bytes(), salt, &mut key)
+        .map_err(|err| KeystoreError::Kdf(err.to_string()))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Unlocks the keystore with `passphrase` rather than looking it up.
+#[cfg(test)]
+pub(crate) fn unlock_with(passphrase: &str) {
+    *PASSPHRASE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+        Some(Passphrase(passphrase.to_string()));
+}
+
+/// Management of a node's keystore.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_node keystore")]
+pub enum KeystoreCmd {
+    /// Encrypt the secret keys of a node's root dir which are still in the clear, for the node
+    /// to be run with `--encrypt-keys`
+    Encrypt {
+        /// The node's root dir
+        #[structopt(parse(from_os_str))]
+        root_dir: PathBuf,
+    },
+}
+
+impl KeystoreCmd {
+    /// Runs the command, writing its output to `out`.
+    pub fn run(&self, out: &mut impl Write) -> eyre::Result<()> {
+        match self {
+            Self::Encrypt { root_dir } => {
+                let sealed = seal_keys(root_dir)?;
+                if sealed.is_empty() {
+                    writeln!(out, "No keys in the clear in {}", root_dir.display())?;
+                }
+                for path in sealed {
+                    writeln!(out, "Encrypted {}", path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    #[test]
+    fn sealed_keys_open_with_their_passphrase_only() -> Result<()> {
+        let passphrase = Passphrase("correct horse".to_string());
+        let key = rand::random::<[u8; 32]>();
+
+        let sealed = seal(&passphrase, &key)?;
+        assert!(!sealed.windows(key.len()).any(|window| window == key));
+        assert_eq!(open(&passphrase, &sealed)?, key);
+
+        // Salt and nonce are random, so sealing twice doesn't give away the same key.
+        assert_ne!(seal(&passphrase, &key)?, sealed);
+
+        let other = Passphrase("battery staple".to_string());
+        assert!(matches!(
+            open(&other, &sealed),
+            Err(KeystoreError::WrongPassphrase)
+        ));
+
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            open(&passphrase, &tampered),
+            Err(KeystoreError::WrongPassphrase)
+        ));
+        assert!(matches!(
+            open(&passphrase, &sealed[..SALT_LEN]),
+            Err(KeystoreError::Malformed)
+        ));
+        Ok(())
+    }
+}
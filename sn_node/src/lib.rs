@@ -41,9 +41,20 @@
 #[macro_use]
 extern crate tracing;
 
+pub mod attestation;
+#[cfg(feature = "binary-logs")]
+pub mod binary_log;
+pub mod control;
 mod dbs;
-
-pub use dbs::UsedSpace;
+pub mod dns_contacts;
+pub mod keystore;
+pub mod membership_history;
+mod persisted;
+pub mod startup_status;
+
+pub use dbs::{ChunkBackendKind, StorageInitError, UsedSpace, Watermarks};
+#[cfg(any(test, feature = "test-utils", feature = "chaos"))]
+pub use dbs::{DiskFaults, FaultyDisk, Latency};
 
 pub mod node;
 
@@ -0,0 +1,290 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! History of what the node knew of its section's membership, kept for post-incident analysis.
+//!
+//! Snapshots are written to a ring file of `MAX_SNAPSHOTS` fixed-size slots, the oldest being
+//! overwritten once it's full. Each slot holds a sequence number, the encoded snapshot and a
+//! checksum, and is written with a single write, so a slot torn by a crash is detected and
+//! skipped when reading rather than corrupting the history.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Debug, Formatter},
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tiny_keccak::{Hasher, Sha3};
+use xor_name::{Prefix, XorName};
+
+/// Name of the history file in the node's root dir.
+pub const MEMBERSHIP_HISTORY_FILE: &str = "membership_history";
+/// Snapshots kept, at most.
+pub const MAX_SNAPSHOTS: u64 = 1024;
+
+// Room for the snapshot of a section with a few dozen elders.
+const SLOT_SIZE: usize = 2048;
+// Sequence number, payload length and checksum.
+const SLOT_HEADER_SIZE: usize = 8 + 4 + 8;
+
+/// What the node knew of its section's membership at some point in time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipSnapshot {
+    /// When the snapshot was taken.
+    pub timestamp: SystemTime,
+    /// Prefix of the section.
+    pub prefix: Prefix,
+    /// Fingerprint of the section key: the hex encoding of its first bytes.
+    pub section_key: String,
+    /// Names of the section's elders.
+    pub elders: Vec<XorName>,
+    /// Number of adults in the section.
+    pub adult_count: usize,
+    /// Length of the section chain.
+    pub generation: u64,
+}
+
+impl MembershipSnapshot {
+    /// Fingerprint of a section key, as recorded in snapshots.
+    pub fn key_fingerprint(key: &bls::PublicKey) -> String {
+        hex::encode(&key.to_bytes()[..8])
+    }
+
+    /// Renders the snapshot as a JSON object.
+    pub fn to_json(&self) -> serde_json::Value {
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        serde_json::json!({
+            "timestamp": micros as f64 / 1_000_000.0,
+            "prefix": format!("{:b}", self.prefix),
+            "section_key": self.section_key,
+            "elders": self.elders.iter().map(|name| hex::encode(name.0)).collect::<Vec<_>>(),
+            "adult_count": self.adult_count,
+            "generation": self.generation,
+        })
+    }
+}
+
+struct Writer {
+    file: File,
+    next_seq: u64,
+}
+
+impl Debug for Writer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("next_seq", &self.next_seq)
+            .finish()
+    }
+}
+
+/// Ring file the node appends membership snapshots to.
+#[derive(Debug)]
+pub struct MembershipHistory {
+    writer: Mutex<Writer>,
+}
+
+impl MembershipHistory {
+    /// Opens the history file in `root_dir`, creating it if need be. Appending carries on after
+    /// the most recent snapshot already in the file.
+    pub fn open(root_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(root_dir)?;
+        let path = root_dir.join(MEMBERSHIP_HISTORY_FILE);
+        let last_seq = read_slots(&path)?.last().map(|(seq, _)| *seq).unwrap_or(0);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        Ok(Self {
+            writer: Mutex::new(Writer {
+                file,
+                next_seq: last_seq + 1,
+            }),
+        })
+    }
+
+    /// Appends a snapshot, overwriting the oldest one if the file is full.
+    pub fn append(&self, snapshot: &MembershipSnapshot) -> io::Result<()> {
+        let payload = bincode::serialize(snapshot)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if SLOT_HEADER_SIZE + payload.len() > SLOT_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("snapshot of {} bytes doesn't fit a slot", payload.len()),
+            ));
+        }
+
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "history writer poisoned"))?;
+        let seq = writer.next_seq;
+
+        let mut slot = Vec::with_capacity(SLOT_SIZE);
+        slot.extend_from_slice(&seq.to_le_bytes());
+        slot.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        slot.extend_from_slice(&checksum(seq, &payload));
+        slot.extend_from_slice(&payload);
+        slot.resize(SLOT_SIZE, 0);
+
+        let offset = ((seq - 1) % MAX_SNAPSHOTS) * SLOT_SIZE as u64;
+        let _pos = writer.file.seek(SeekFrom::Start(offset))?;
+        writer.file.write_all(&slot)?;
+        writer.next_seq += 1;
+        Ok(())
+    }
+}
+
+/// Reads the snapshots in the history file of `root_dir` taken within `[since, until)`, oldest
+/// first. Missing bounds are open.
+pub fn read_history(
+    root_dir: &Path,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+) -> io::Result<Vec<MembershipSnapshot>> {
+    Ok(read_slots(&history_path(root_dir))?
+        .into_iter()
+        .map(|(_, snapshot)| snapshot)
+        .filter(|snapshot| {
+            since.is_none_or(|since| snapshot.timestamp >= since)
+                && until.is_none_or(|until| snapshot.timestamp < until)
+        })
+        .collect())
+}
+
+fn history_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(MEMBERSHIP_HISTORY_FILE)
+}
+
+// Reads all intact slots of the file, ordered by sequence number. A missing file is empty.
+fn read_slots(path: &Path) -> io::Result<Vec<(u64, MembershipSnapshot)>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut snapshots: Vec<_> = bytes
+        .chunks_exact(SLOT_SIZE)
+        .filter_map(parse_slot)
+        .collect();
+    snapshots.sort_by_key(|(seq, _)| *seq);
+    Ok(snapshots)
+}
+
+fn parse_slot(slot: &[u8]) -> Option<(u64, MembershipSnapshot)> {
+    let seq = u64::from_le_bytes(slot[0..8].try_into().ok()?);
+    if seq == 0 {
+        // Never written.
+        return None;
+    }
+    let len = u32::from_le_bytes(slot[8..12].try_into().ok()?) as usize;
+    let payload = slot.get(SLOT_HEADER_SIZE..SLOT_HEADER_SIZE + len)?;
+    if slot[12..SLOT_HEADER_SIZE] != checksum(seq, payload) {
+        warn!("Skipping torn membership snapshot #{}", seq);
+        return None;
+    }
+    bincode::deserialize(payload)
+        .ok()
+        .map(|snapshot| (seq, snapshot))
+}
+
+fn checksum(seq: u64, payload: &[u8]) -> [u8; 8] {
+    let mut hasher = Sha3::v256();
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(payload);
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    let mut checksum = [0; 8];
+    checksum.copy_from_slice(&hash[..8]);
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn snapshot(generation: u64) -> MembershipSnapshot {
+        MembershipSnapshot {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_000 + generation),
+            prefix: Prefix::default().pushed(generation % 2 == 0),
+            section_key: MembershipSnapshot::key_fingerprint(
+                &bls::SecretKey::random().public_key(),
+            ),
+            elders: (0..7).map(|_| xor_name::rand::random()).collect(),
+            adult_count: generation as usize,
+            generation,
+        }
+    }
+
+    #[test]
+    fn ring_keeps_the_latest_snapshots_across_reopening() -> Result<()> {
+        let root = tempdir()?;
+        let written: Vec<_> = (1..=MAX_SNAPSHOTS + 10).map(snapshot).collect();
+
+        let (first, rest) = written.split_at(100);
+        let history = MembershipHistory::open(root.path())?;
+        for snapshot in first {
+            history.append(snapshot)?;
+        }
+        drop(history);
+        let history = MembershipHistory::open(root.path())?;
+        for snapshot in rest {
+            history.append(snapshot)?;
+        }
+
+        let read = read_history(root.path(), None, None)?;
+        assert_eq!(read, written[10..]);
+        assert_eq!(
+            fs::metadata(history_path(root.path()))?.len(),
+            MAX_SNAPSHOTS * SLOT_SIZE as u64
+        );
+
+        let since = UNIX_EPOCH + Duration::from_secs(1_500);
+        let until = UNIX_EPOCH + Duration::from_secs(1_600);
+        let read = read_history(root.path(), Some(since), Some(until))?;
+        assert_eq!(read, written[499..599]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn torn_slot_is_skipped() -> Result<()> {
+        let root = tempdir()?;
+        let history = MembershipHistory::open(root.path())?;
+        for generation in 1..=3 {
+            history.append(&snapshot(generation))?;
+        }
+
+        // Corrupt the middle snapshot, as if the node crashed while writing it.
+        let path = history_path(root.path());
+        let mut bytes = fs::read(&path)?;
+        bytes[SLOT_SIZE + SLOT_HEADER_SIZE + 5] ^= 0xff;
+        fs::write(&path, bytes)?;
+
+        let generations: Vec<_> = read_history(root.path(), None, None)?
+            .iter()
+            .map(|snapshot| snapshot.generation)
+            .collect();
+        assert_eq!(generations, [1, 3]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Faults injected into a running node on purpose, to see its handling of failed sends, slow
+//! disks and failed chunk reads and writes, and the replication making up for them, at work.
+//! Built with the `chaos` feature only, and set at startup from the env vars below, or later with
+//! the privileged `chaos` cmd of the diagnostics sockets.
+
+use crate::dbs::Latency;
+use crate::node::{
+    core::Node,
+    error::{Error, Result},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{net::SocketAddr, time::Duration};
+
+/// Percentage of the msgs we send dropped, to all peers.
+pub(crate) const MSG_DROP_PERCENT_ENV_VAR: &str = "SN_CHAOS_MSG_DROP_PERCENT";
+/// Milliseconds added to each chunk write.
+pub(crate) const CHUNK_WRITE_DELAY_MS_ENV_VAR: &str = "SN_CHAOS_CHUNK_WRITE_DELAY_MS";
+/// Percentage of the chunk reads and writes failing with an I/O error.
+pub(crate) const CHUNK_IO_ERROR_PERCENT_ENV_VAR: &str = "SN_CHAOS_CHUNK_IO_ERROR_PERCENT";
+
+/// Faults to inject, those left out being left as they are.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Chaos {
+    /// Clears all faults first.
+    #[serde(default)]
+    pub(crate) heal: bool,
+    /// Percentage of the msgs we send dropped, to `peer` only if it's set.
+    pub(crate) msg_drop_percent: Option<f64>,
+    pub(crate) peer: Option<SocketAddr>,
+    /// Milliseconds added to each chunk write.
+    pub(crate) chunk_write_delay_ms: Option<u64>,
+    /// Percentage of the chunk reads and writes failing with an I/O error.
+    pub(crate) chunk_io_error_percent: Option<f64>,
+}
+
+impl Chaos {
+    /// The faults set by the env vars, if any.
+    pub(crate) fn from_env() -> Result<Self> {
+        let chaos = Self {
+            msg_drop_percent: env_var(MSG_DROP_PERCENT_ENV_VAR)?,
+            chunk_write_delay_ms: env_var(CHUNK_WRITE_DELAY_MS_ENV_VAR)?,
+            chunk_io_error_percent: env_var(CHUNK_IO_ERROR_PERCENT_ENV_VAR)?,
+            ..Self::default()
+        };
+        chaos.validate().map_err(Error::Configuration)?;
+        Ok(chaos)
+    }
+
+    /// Checks the percentages are percentages, and a peer comes with a drop percentage.
+    pub(crate) fn validate(&self) -> std::result::Result<(), String> {
+        for (name, percent) in [
+            ("msg_drop_percent", self.msg_drop_percent),
+            ("chunk_io_error_percent", self.chunk_io_error_percent),
+        ] {
+            if let Some(percent) = percent {
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(format!(
+                        "{} must be between 0 and 100, not {}",
+                        name, percent
+                    ));
+                }
+            }
+        }
+        if self.peer.is_some() && self.msg_drop_percent.is_none() {
+            return Err("peer must come with msg_drop_percent".to_string());
+        }
+        Ok(())
+    }
+
+    /// Injects the faults into `node`.
+    pub(crate) fn inject(&self, node: &Node) {
+        let disk = node.data_storage.faulty_disk();
+        if self.heal {
+            warn!("Chaos: healing all faults");
+            node.comm.heal_msg_chaos();
+            disk.heal();
+        }
+        if let Some(percent) = self.msg_drop_percent {
+            node.comm.set_msg_drop_rate(self.peer, percent / 100.0);
+        }
+        if let Some(delay) = self.chunk_write_delay_ms {
+            warn!("Chaos: delaying chunk writes by {}ms", delay);
+            disk.update(|faults| {
+                faults.write_latency = Latency::fixed(Duration::from_millis(delay))
+            });
+        }
+        if let Some(percent) = self.chunk_io_error_percent {
+            warn!("Chaos: failing {}% of chunk reads and writes", percent);
+            disk.update(|faults| {
+                faults.write_failure_rate = percent / 100.0;
+                faults.read_failure_rate = percent / 100.0;
+            });
+        }
+    }
+}
+
+/// The faults injected into `node`, as reported to operators.
+pub(crate) fn report(node: &Node) -> Value {
+    let faults = node.data_storage.faulty_disk().faults();
+    json!({
+        "msgs_dropped": node.comm.msgs_dropped_by_chaos(),
+        "chunk_write_delay_ms": faults.write_latency.max.as_millis() as u64,
+        "chunk_write_error_percent": faults.write_failure_rate * 100.0,
+        "chunk_read_error_percent": faults.read_failure_rate * 100.0,
+    })
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>> {
+    match std::env::var(name) {
+        Ok(value) => {
+            value.trim().parse().map(Some).map_err(|_| {
+                Error::Configuration(format!("Invalid value of {}: {:?}", name, value))
+            })
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chaos_is_validated_and_read_from_the_env() -> Result<()> {
+        let chaos: Chaos = serde_json::from_value(json!({
+            "msg_drop_percent": 150.0,
+        }))
+        .map_err(|error| Error::Configuration(error.to_string()))?;
+        assert!(chaos.validate().is_err());
+        let peer = Chaos {
+            peer: Some(SocketAddr::from(([127, 0, 0, 1], 1))),
+            ..Chaos::default()
+        };
+        assert!(peer.validate().is_err());
+        assert!(serde_json::from_value::<Chaos>(json!({ "drop": 1 })).is_err());
+
+        std::env::set_var(MSG_DROP_PERCENT_ENV_VAR, "12.5");
+        std::env::set_var(CHUNK_WRITE_DELAY_MS_ENV_VAR, "200");
+        assert_eq!(
+            Chaos::from_env()?,
+            Chaos {
+                msg_drop_percent: Some(12.5),
+                chunk_write_delay_ms: Some(200),
+                ..Chaos::default()
+            }
+        );
+        std::env::set_var(CHUNK_IO_ERROR_PERCENT_ENV_VAR, "lots");
+        assert!(Chaos::from_env().is_err());
+
+        for name in [
+            MSG_DROP_PERCENT_ENV_VAR,
+            CHUNK_WRITE_DELAY_MS_ENV_VAR,
+            CHUNK_IO_ERROR_PERCENT_ENV_VAR,
+        ] {
+            std::env::remove_var(name);
+        }
+        assert_eq!(Chaos::from_env()?, Chaos::default());
+        Ok(())
+    }
+}
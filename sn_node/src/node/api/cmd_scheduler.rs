@@ -0,0 +1,291 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use futures::{
+    channel::oneshot,
+    future::{join_all, FutureExt, Shared},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Cmds being processed at once at most, not counting those only waiting on timers.
+pub(crate) const CMD_CONCURRENCY: usize = 256;
+
+// Resolves once the cmd it was taken for is done with, however that came about.
+type Done = Shared<oneshot::Receiver<()>>;
+
+// The last turn taken for cmds touching a key.
+struct Tail {
+    turn: u64,
+    done: Done,
+}
+
+struct State<K> {
+    next_turn: u64,
+    tails: BTreeMap<K, Tail>,
+}
+
+/// Schedules the processing of cmds, independent ones being processed concurrently with a limit
+/// to how many are processed at once, while those touching the same keys (e.g. the same peer or
+/// data) are processed one after the other, in the order they were scheduled.
+#[derive(Clone)]
+pub(crate) struct CmdScheduler<K> {
+    state: Arc<Mutex<State<K>>>,
+    permits: Arc<Semaphore>,
+}
+
+/// The place of a cmd in the order of the cmds touching the same keys.
+pub(crate) struct Turn<K: Ord> {
+    slot: Slot<K>,
+    after: Vec<Done>,
+    bounded: bool,
+    permits: Arc<Semaphore>,
+}
+
+/// A cmd being processed. The cmds after it touching the same keys are let go once dropped.
+pub(crate) struct Running<K: Ord> {
+    _slot: Slot<K>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+// Holds the keys of a turn, releasing them once dropped, whether the cmd was processed or not.
+struct Slot<K: Ord> {
+    state: Arc<Mutex<State<K>>>,
+    turn: u64,
+    keys: Vec<K>,
+    _done: oneshot::Sender<()>,
+}
+
+impl<K: Ord> Drop for Slot<K> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.state);
+        for key in &self.keys {
+            if state.tails.get(key).map(|tail| tail.turn) == Some(self.turn) {
+                let _tail = state.tails.remove(key);
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone> CmdScheduler<K> {
+    pub(crate) fn new(concurrency: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                next_turn: 0,
+                tails: BTreeMap::new(),
+            })),
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+        }
+    }
+
+    /// Takes the next turn for a cmd touching `keys`, after the cmds touching any of them that
+    /// took their turn already. An unbounded cmd, e.g. one only waiting on a timer, doesn't count
+    /// towards the cmds processed at once.
+    pub(crate) fn take_turn(&self, mut keys: Vec<K>, bounded: bool) -> Turn<K> {
+        keys.sort();
+        keys.dedup();
+
+        let (done_tx, done_rx) = oneshot::channel();
+        let done = done_rx.shared();
+
+        let mut state = lock(&self.state);
+        let turn = state.next_turn;
+        state.next_turn += 1;
+        let after = keys
+            .iter()
+            .filter_map(|key| {
+                let tail = Tail {
+                    turn,
+                    done: done.clone(),
+                };
+                state.tails.insert(key.clone(), tail).map(|prev| prev.done)
+            })
+            .collect();
+        drop(state);
+
+        Turn {
+            slot: Slot {
+                state: self.state.clone(),
+                turn,
+                keys,
+                _done: done_tx,
+            },
+            after,
+            bounded,
+            permits: self.permits.clone(),
+        }
+    }
+
+    /// Keys with cmds waiting on, or being processed for, them.
+    #[cfg(test)]
+    fn busy_keys(&self) -> usize {
+        lock(&self.state).tails.len()
+    }
+}
+
+impl<K: Ord> Turn<K> {
+    /// Waits for the cmds before this one touching the same keys to be done with, then for one
+    /// of the cmds processed at once to be done too if there are as many as allowed already.
+    pub(crate) async fn wait(self) -> Running<K> {
+        let _done = join_all(self.after).await;
+        let permit = if self.bounded {
+            // The semaphore is never closed.
+            self.permits.acquire_owned().await.ok()
+        } else {
+            None
+        };
+        Running {
+            _slot: self.slot,
+            _permit: permit,
+        }
+    }
+}
+
+fn lock<K>(state: &Mutex<State<K>>) -> MutexGuard<'_, State<K>> {
+    // The lock is only held for map updates, which don't panic.
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use std::time::Duration;
+    use tokio::{
+        sync::mpsc,
+        time::{sleep, Instant},
+    };
+
+    const CMD_TIME: Duration = Duration::from_millis(10);
+
+    // Processes cmds touching the given keys, each taking `CMD_TIME`, reporting their index and
+    // when they completed.
+    async fn process(
+        scheduler: &CmdScheduler<&'static str>,
+        cmds: Vec<Vec<&'static str>>,
+    ) -> Vec<(usize, Instant)> {
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        for (index, keys) in cmds.into_iter().enumerate() {
+            let turn = scheduler.take_turn(keys, true);
+            let done_tx = done_tx.clone();
+            let _handle = tokio::spawn(async move {
+                let _running = turn.wait().await;
+                sleep(CMD_TIME).await;
+                let _result = done_tx.send((index, Instant::now()));
+            });
+        }
+        drop(done_tx);
+
+        let mut completed = vec![];
+        while let Some(done) = done_rx.recv().await {
+            completed.push(done);
+        }
+        completed
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cmds_touching_the_same_key_are_processed_in_order() -> Result<()> {
+        let scheduler = CmdScheduler::new(8);
+        let start = Instant::now();
+
+        let completed = process(&scheduler, vec![vec!["peer"]; 5]).await;
+
+        let order: Vec<_> = completed.iter().map(|(index, _)| *index).collect();
+        assert_eq!(order, [0, 1, 2, 3, 4]);
+        assert_eq!(completed[4].1.duration_since(start), CMD_TIME * 5);
+        assert_eq!(scheduler.busy_keys(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn independent_cmds_are_processed_concurrently_up_to_the_limit() -> Result<()> {
+        let scheduler = CmdScheduler::new(4);
+        let start = Instant::now();
+
+        let cmds = ["a", "b", "c", "d", "e", "f", "g", "h"]
+            .into_iter()
+            .map(|key| vec![key])
+            .chain([vec![], vec![]])
+            .collect();
+        let completed = process(&scheduler, cmds).await;
+
+        assert_eq!(completed.len(), 10);
+        let makespan = completed
+            .iter()
+            .map(|(_, at)| at.duration_since(start))
+            .max();
+        assert_eq!(makespan, Some(CMD_TIME * 3));
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cmds_wait_for_every_key_they_touch() -> Result<()> {
+        let scheduler = CmdScheduler::new(8);
+
+        // The third cmd touches the keys of both the first and the second, and the fourth only
+        // comes after the third, through "b".
+        let completed = process(
+            &scheduler,
+            vec![vec!["a"], vec!["b"], vec!["a", "b"], vec!["b"], vec!["c"]],
+        )
+        .await;
+
+        let at: BTreeMap<_, _> = completed.into_iter().collect();
+        assert_eq!(at[&0], at[&1]);
+        assert_eq!(at[&0], at[&4]);
+        assert_eq!(at[&2], at[&0] + CMD_TIME);
+        assert_eq!(at[&3], at[&2] + CMD_TIME);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unbounded_cmds_take_none_of_the_limit() -> Result<()> {
+        let scheduler = CmdScheduler::<&'static str>::new(1);
+
+        // A cmd waiting on a timer for long...
+        let timer = scheduler.take_turn(vec![], false);
+        let _handle = tokio::spawn(async move {
+            let _running = timer.wait().await;
+            sleep(CMD_TIME * 100).await;
+        });
+        tokio::task::yield_now().await;
+
+        // ...doesn't keep the others from being processed.
+        let start = Instant::now();
+        let completed = process(&scheduler, vec![vec![]]).await;
+        assert_eq!(completed[0].1.duration_since(start), CMD_TIME);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_turns_let_the_next_ones_go() -> Result<()> {
+        let scheduler = CmdScheduler::new(8);
+
+        // A cmd never processed, e.g. refused as its client was busy.
+        let dropped = scheduler.take_turn(vec!["a"], true);
+        let next = scheduler.take_turn(vec!["a"], true);
+        drop(dropped);
+
+        let _running = next.wait().await;
+        assert_eq!(scheduler.busy_keys(), 1);
+        drop(_running);
+        assert_eq!(scheduler.busy_keys(), 0);
+
+        Ok(())
+    }
+}
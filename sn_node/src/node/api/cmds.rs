@@ -9,7 +9,7 @@
 use crate::node::{core::Proposal, XorName};
 use sn_interface::messaging::{
     system::{DkgFailureSigSet, KeyedSig, NodeState, SectionAuth, SystemMsg},
-    DstLocation, WireMsg,
+    AuthKind, DstLocation, WireMsg,
 };
 use sn_interface::network_knowledge::{SectionAuthorityProvider, SectionKeyShare};
 use sn_interface::types::Peer;
@@ -92,6 +92,89 @@ pub(crate) enum Cmd {
     TestConnectivity(XorName),
 }
 
+/// What the processing of a cmd touches. Cmds touching the same thing are processed one after
+/// the other, in the order they were dispatched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum CmdKey {
+    /// A node or client we're in contact with.
+    Peer(XorName),
+    /// The data at the address.
+    Data(XorName),
+    /// The members and elders of our section.
+    Membership,
+}
+
+impl Cmd {
+    /// What the cmd touches, for the cmds touching the same to be processed in order.
+    pub(crate) fn keys(&self) -> Vec<CmdKey> {
+        match self {
+            Cmd::HandleMsg {
+                sender, wire_msg, ..
+            } => {
+                let mut keys = vec![CmdKey::Peer(sender.name())];
+                // Client msgs are addressed to the data they're about.
+                if let AuthKind::Service(_) = wire_msg.msg_kind() {
+                    keys.push(CmdKey::Data(wire_msg.dst_location().name()));
+                }
+                keys
+            }
+            Cmd::HandlePeerLost(peer) => vec![CmdKey::Peer(peer.name())],
+            Cmd::HandleNewNodeOnline(auth) | Cmd::HandleNodeLeft(auth) => {
+                vec![CmdKey::Membership, CmdKey::Peer(auth.value.name)]
+            }
+            Cmd::HandleAgreement { .. }
+            | Cmd::HandleNewEldersAgreement { .. }
+            | Cmd::HandleDkgOutcome { .. }
+            | Cmd::HandleDkgFailure(_)
+            | Cmd::ProposeOffline(_) => vec![CmdKey::Membership],
+            Cmd::StartConnectivityTest(name) | Cmd::TestConnectivity(name) => {
+                vec![CmdKey::Peer(*name)]
+            }
+            Cmd::CleanupPeerLinks
+            | Cmd::HandleTimeout(_)
+            | Cmd::SendMsg { .. }
+            | Cmd::ThrottledSendBatchMsgs { .. }
+            | Cmd::SignOutgoingSystemMsg { .. }
+            | Cmd::SendMsgDeliveryGroup { .. }
+            | Cmd::ScheduleTimeout { .. } => vec![],
+        }
+    }
+
+    /// Whether the cmd counts towards the cmds processed at once. Those spending their time
+    /// waiting on timers don't.
+    pub(crate) fn is_bounded(&self) -> bool {
+        !matches!(
+            self,
+            Cmd::ScheduleTimeout { .. } | Cmd::ThrottledSendBatchMsgs { .. }
+        )
+    }
+
+    /// Name of the cmd's variant, labelling its metrics.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Cmd::CleanupPeerLinks => "CleanupPeerLinks",
+            Cmd::HandleMsg { .. } => "HandleMsg",
+            Cmd::HandleTimeout(_) => "HandleTimeout",
+            Cmd::HandlePeerLost(_) => "HandlePeerLost",
+            Cmd::HandleAgreement { .. } => "HandleAgreement",
+            Cmd::HandleNewNodeOnline(_) => "HandleNewNodeOnline",
+            Cmd::HandleNodeLeft(_) => "HandleNodeLeft",
+            Cmd::HandleNewEldersAgreement { .. } => "HandleNewEldersAgreement",
+            Cmd::HandleDkgOutcome { .. } => "HandleDkgOutcome",
+            Cmd::HandleDkgFailure(_) => "HandleDkgFailure",
+            Cmd::SendMsg { .. } => "SendMsg",
+            Cmd::ThrottledSendBatchMsgs { .. } => "ThrottledSendBatchMsgs",
+            Cmd::SignOutgoingSystemMsg { .. } => "SignOutgoingSystemMsg",
+            Cmd::SendMsgDeliveryGroup { .. } => "SendMsgDeliveryGroup",
+            Cmd::ScheduleTimeout { .. } => "ScheduleTimeout",
+            Cmd::ProposeOffline(_) => "ProposeOffline",
+            Cmd::StartConnectivityTest(_) => "StartConnectivityTest",
+            Cmd::TestConnectivity(_) => "TestConnectivity",
+        }
+    }
+}
+
 impl fmt::Display for Cmd {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -0,0 +1,1308 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Diagnostics and control of a running node, served to local processes:
+//!
+//! - on `diagnostics.sock` in its root dir, open to all local users, which serves the read-only
+//!   tier, and `control.sock` beside it, open to the node's own user only, which serves the
+//!   privileged tier as well, when `Config::control_sockets` is set,
+//! - over TCP on `Config::rpc_addr`, a loopback address, which serves both tiers, but the
+//!   privileged one only with `Config::control_token` set, as it can't tell which user
+//!   connects.
+//!
+//! With a control token set, privileged cmds must carry it wherever they're sent. Each cmd is
+//! declared with its tier in `COMMANDS`, and no handler can be registered for one that isn't,
+//! so a new cmd can't end up served more widely than intended.
+//!
+//! Requests and responses are one line of JSON each, e.g. `{"cmd": "status"}` answered with
+//! `{"ok": {...}}`, or with `{"error": {"kind": "permission_denied", "message": "..."}}`.
+
+use super::{dispatcher::Dispatcher, shutdown::DRAIN_TIMEOUT, supervisor::TaskStatus};
+use crate::control::CONTROL_SOCKET;
+use crate::node::{
+    cfg::{
+        capacity_storage::store_max_capacity,
+        keypair_storage::{get_reward_key_history, get_reward_pk, rotate_reward_keypair},
+    },
+    core::{CacheOptions, CheckOptions, ListenerKind, ReplicationTarget, Shortfall},
+    error::{Error, Result},
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, UNIX_EPOCH},
+};
+#[cfg(unix)]
+use std::{fs, os::unix::fs::PermissionsExt};
+use subtle::ConstantTimeEq;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
+    net::TcpListener,
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task,
+};
+use xor_name::{Prefix, XorName};
+
+/// Name of the socket serving the read-only tier, in the node's root dir.
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) const DIAGNOSTICS_SOCKET: &str = "diagnostics.sock";
+
+/// Longest request line read, newline included; a connection sending a longer one is answered
+/// with a `bad_request` and closed, as anyone local can write to the read-only socket.
+const MAX_REQUEST_LEN: u64 = 64 * 1024;
+
+/// Most connections served at once on each socket; those past it are answered with a `failed`
+/// and closed.
+pub(crate) const MAX_CONNECTIONS: usize = 32;
+
+/// Who a cmd can be served to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Tier {
+    /// Any local process, on either socket.
+    ReadOnly,
+    /// The node's own user, on the control socket, with the token if one is set.
+    Privileged,
+}
+
+/// Every cmd served, with its tier.
+const COMMANDS: &[(&str, Tier)] = &[
+    // What the node is, and what it is doing.
+    ("status", Tier::ReadOnly),
+    // How much the node stores, and how much it can.
+    ("storage", Tier::ReadOnly),
+    // How many members, elders and adults our section has, and how we're connected to peers.
+    ("peers", Tier::ReadOnly),
+    // Whether the node serves client reads and writes, and why not.
+    ("readiness", Tier::ReadOnly),
+    // The status, restarts and last exit of each background task, and whether any was given up.
+    ("tasks", Tier::ReadOnly),
+    // The attestation of the version and config the node runs with.
+    ("attestation", Tier::ReadOnly),
+    // The prefix of our section.
+    ("prefix", Tier::ReadOnly),
+    // Hits and misses of the cache of chunks relayed to clients.
+    ("data_cache", Tier::ReadOnly),
+    // Where the DKG sessions we take part in are at.
+    ("dkg_sessions", Tier::ReadOnly),
+    // On elders, how close the section is to splitting.
+    ("split_rehearsal", Tier::ReadOnly),
+    // On adults, where the handovers of data to sibling sections after splits are at.
+    ("split_handover", Tier::ReadOnly),
+    // The section members, in detail.
+    ("peer_dump", Tier::Privileged),
+    // How far the rebuild of the holder registry of newly promoted elders is.
+    ("holder_registry", Tier::Privileged),
+    // Announces a restart planned within `within_secs` to our elders, draining first if `drain`.
+    ("prepare_restart", Tier::Privileged),
+    // Changes the node's storage capacity to `bytes`.
+    ("set_capacity", Tier::Privileged),
+    // Replaces the node's reward key with a new one, keeping the old one.
+    ("rotate_reward_key", Tier::Privileged),
+    // On elders, starts checking where the section's data is held.
+    ("distribution_check", Tier::Privileged),
+    // Aborts the running distribution check.
+    ("distribution_check_abort", Tier::Privileged),
+    // The report of the last distribution check.
+    ("distribution_report", Tier::Privileged),
+    // Reconciles the chunk index with the chunk files, walking them.
+    ("reconcile_chunk_index", Tier::Privileged),
+    // On elders, replicates chunks to their missing holders right away.
+    ("replicate", Tier::Privileged),
+    // The progress of a job started by `replicate`.
+    ("replication_job", Tier::Privileged),
+    // The peers we're connected to.
+    ("connected_peers", Tier::Privileged),
+    // The scores of the peers which misbehaved lately.
+    ("reputation", Tier::Privileged),
+    // Changes the filter of our logs.
+    ("set_log_level", Tier::Privileged),
+    // Scrubs the next batch of our chunks right away.
+    ("scrub_chunks", Tier::Privileged),
+    // Changes how chunks relayed to clients are cached.
+    ("set_cache_options", Tier::Privileged),
+    // Dumps the last msgs received and sent.
+    ("msg_audit", Tier::Privileged),
+    // Injects faults, with the `chaos` feature.
+    #[cfg(feature = "chaos")]
+    ("chaos", Tier::Privileged),
+    // Shuts the node down gracefully.
+    ("shutdown", Tier::Privileged),
+];
+
+/// Why a request wasn't served.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DenialKind {
+    /// The cmd is privileged, and was sent to the read-only socket, or over TCP with no control
+    /// token set.
+    PermissionDenied,
+    /// The cmd is privileged, and came without the right token.
+    Unauthorized,
+    /// No such cmd.
+    UnknownCommand,
+    /// The request couldn't be parsed, or its args were wrong.
+    BadRequest,
+    /// The cmd failed, or the node stopped.
+    Failed,
+}
+
+/// A request, as sent by local processes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Request {
+    /// Name of the cmd.
+    pub(crate) cmd: String,
+    /// Its args, if any.
+    #[serde(default)]
+    pub(crate) args: Value,
+    /// The control token, for privileged cmds if one is set.
+    #[serde(default)]
+    pub(crate) token: Option<String>,
+}
+
+// The socket a request came in on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(unix), allow(dead_code))]
+enum Surface {
+    ReadOnly,
+    Control,
+    Rpc,
+}
+
+// What handlers are given to serve a request.
+#[derive(Clone)]
+struct Context {
+    dispatcher: Arc<Dispatcher>,
+    root_dir: PathBuf,
+}
+
+type Handler = fn(Context, Value) -> BoxFuture<'static, std::result::Result<Value, String>>;
+
+/// The cmds served, each with its declared tier and handler.
+#[derive(Debug)]
+pub(crate) struct Registry {
+    declared: BTreeMap<&'static str, Tier>,
+    handlers: BTreeMap<&'static str, (Tier, Handler)>,
+}
+
+impl Registry {
+    fn new(declared: &[(&'static str, Tier)]) -> Self {
+        Self {
+            declared: declared.iter().copied().collect(),
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    // Registers the handler of a cmd, which must have been declared.
+    fn handle(mut self, cmd: &'static str, handler: Handler) -> Result<Self> {
+        let tier = *self.declared.get(cmd).ok_or_else(|| {
+            Error::Configuration(format!("Cmd {:?} served without a declared tier", cmd))
+        })?;
+        let _prev = self.handlers.insert(cmd, (tier, handler));
+        Ok(self)
+    }
+
+    // Checks every declared cmd is handled.
+    fn build(self) -> Result<Self> {
+        match self
+            .declared
+            .keys()
+            .find(|cmd| !self.handlers.contains_key(*cmd))
+        {
+            Some(cmd) => Err(Error::Configuration(format!(
+                "Cmd {:?} declared without a handler",
+                cmd
+            ))),
+            None => Ok(self),
+        }
+    }
+}
+
+fn registry() -> Result<Registry> {
+    let registry = Registry::new(COMMANDS)
+        .handle("status", |ctx, _| Box::pin(status(ctx)))?
+        .handle("storage", |ctx, _| Box::pin(storage(ctx)))?
+        .handle("peers", |ctx, _| Box::pin(peers(ctx)))?
+        .handle("readiness", |ctx, _| Box::pin(readiness(ctx)))?
+        .handle("tasks", |ctx, _| Box::pin(tasks(ctx)))?
+        .handle("attestation", |ctx, _| Box::pin(attestation(ctx)))?
+        .handle("prefix", |ctx, _| Box::pin(prefix(ctx)))?
+        .handle("data_cache", |ctx, _| Box::pin(data_cache(ctx)))?
+        .handle("dkg_sessions", |ctx, _| Box::pin(dkg_sessions(ctx)))?
+        .handle("split_rehearsal", |ctx, _| Box::pin(split_rehearsal(ctx)))?
+        .handle("split_handover", |ctx, _| Box::pin(split_handover(ctx)))?
+        .handle("peer_dump", |ctx, _| Box::pin(peer_dump(ctx)))?
+        .handle("holder_registry", |ctx, _| Box::pin(holder_registry(ctx)))?
+        .handle("prepare_restart", |ctx, args| {
+            Box::pin(prepare_restart(ctx, args))
+        })?
+        .handle("set_capacity", |ctx, args| {
+            Box::pin(set_capacity(ctx, args))
+        })?
+        .handle("rotate_reward_key", |ctx, _| {
+            Box::pin(rotate_reward_key(ctx))
+        })?
+        .handle("distribution_check", |ctx, args| {
+            Box::pin(distribution_check(ctx, args))
+        })?
+        .handle("distribution_check_abort", |ctx, _| {
+            Box::pin(distribution_check_abort(ctx))
+        })?
+        .handle("distribution_report", |ctx, _| {
+            Box::pin(distribution_report(ctx))
+        })?
+        .handle("reconcile_chunk_index", |ctx, _| {
+            Box::pin(reconcile_chunk_index(ctx))
+        })?
+        .handle("replicate", |ctx, args| Box::pin(replicate(ctx, args)))?
+        .handle("replication_job", |ctx, args| {
+            Box::pin(replication_job(ctx, args))
+        })?
+        .handle("connected_peers", |ctx, _| Box::pin(connected_peers(ctx)))?
+        .handle("reputation", |ctx, _| Box::pin(reputation(ctx)))?
+        .handle("set_log_level", |ctx, args| {
+            Box::pin(set_log_level(ctx, args))
+        })?
+        .handle("scrub_chunks", |ctx, _| Box::pin(scrub_chunks(ctx)))?
+        .handle("set_cache_options", |ctx, args| {
+            Box::pin(set_cache_options(ctx, args))
+        })?
+        .handle("msg_audit", |ctx, _| Box::pin(msg_audit(ctx)))?
+        .handle("shutdown", |ctx, _| Box::pin(shutdown(ctx)))?;
+    #[cfg(feature = "chaos")]
+    let registry = registry.handle("chaos", |ctx, args| Box::pin(chaos(ctx, args)))?;
+    registry.build()
+}
+
+/// Starts serving diagnostics and control on the sockets in `root_dir`, replacing any left over
+/// by a previous run.
+#[cfg(unix)]
+pub(super) fn serve(
+    dispatcher: &Arc<Dispatcher>,
+    root_dir: &Path,
+    token: Option<String>,
+) -> Result<()> {
+    let registry = Arc::new(registry()?);
+    let token = Arc::new(token);
+
+    for (surface, name, mode) in [
+        (Surface::ReadOnly, DIAGNOSTICS_SOCKET, 0o666),
+        (Surface::Control, CONTROL_SOCKET, 0o600),
+    ] {
+        let path = root_dir.join(name);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        let listener = bind_unix(root_dir, name, mode)?;
+        info!("Serving {:?} diagnostics on {}", surface, path.display());
+
+        // The sockets mustn't keep a stopped node alive.
+        let dispatcher = Arc::downgrade(dispatcher);
+        let root_dir = root_dir.to_path_buf();
+        let registry = registry.clone();
+        let token = token.clone();
+        let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+        let _handle = task::spawn(async move {
+            loop {
+                let (reader, mut writer) = match listener.accept().await {
+                    Ok((stream, _addr)) => stream.into_split(),
+                    Err(error) => {
+                        error!("Diagnostics socket stopped: {:?}", error);
+                        break;
+                    }
+                };
+                let permit = match connections.clone().try_acquire_owned() {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        let _handle = task::spawn(async move { refuse(&mut writer).await });
+                        continue;
+                    }
+                };
+                let _handle = task::spawn(serve_connection(
+                    reader,
+                    writer,
+                    surface,
+                    dispatcher.clone(),
+                    root_dir.clone(),
+                    registry.clone(),
+                    token.clone(),
+                    permit,
+                ));
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Binds the socket `name` in `dir` with `mode`. It's bound in a staging dir only we can enter,
+// and moved into `dir` once its mode is set, so it's never reachable with the umask's.
+#[cfg(unix)]
+fn bind_unix(dir: &Path, name: &str, mode: u32) -> Result<UnixListener> {
+    let staging = tempfile::Builder::new().prefix(".sock").tempdir_in(dir)?;
+    let staged = staging.path().join(name);
+    let listener = UnixListener::bind(&staged)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(mode))?;
+    fs::rename(&staged, dir.join(name))?;
+    Ok(listener)
+}
+
+/// Starts serving diagnostics and control over TCP on `addr`, which must be a loopback address,
+/// returning the address it's bound to.
+pub(super) async fn serve_rpc(
+    dispatcher: &Arc<Dispatcher>,
+    addr: SocketAddr,
+    root_dir: &Path,
+    token: Option<String>,
+) -> Result<SocketAddr> {
+    if !addr.ip().is_loopback() {
+        return Err(Error::Configuration(format!(
+            "Cannot serve control on {}, it's not a loopback address",
+            addr
+        )));
+    }
+    let registry = Arc::new(registry()?);
+    let token = Arc::new(token);
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    info!("Serving diagnostics and control on {}", local_addr);
+
+    // The endpoint mustn't keep a stopped node alive.
+    let dispatcher = Arc::downgrade(dispatcher);
+    let root_dir = root_dir.to_path_buf();
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    let _handle = task::spawn(async move {
+        loop {
+            let (reader, mut writer) = match listener.accept().await {
+                Ok((stream, _addr)) => stream.into_split(),
+                Err(error) => {
+                    error!("Control endpoint stopped: {:?}", error);
+                    break;
+                }
+            };
+            let permit = match connections.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    let _handle = task::spawn(async move { refuse(&mut writer).await });
+                    continue;
+                }
+            };
+            let _handle = task::spawn(serve_connection(
+                reader,
+                writer,
+                Surface::Rpc,
+                dispatcher.clone(),
+                root_dir.clone(),
+                registry.clone(),
+                token.clone(),
+                permit,
+            ));
+        }
+    });
+
+    Ok(local_addr)
+}
+
+// Answers a connection past `MAX_CONNECTIONS`, which is then closed.
+async fn refuse(writer: &mut (impl AsyncWrite + Unpin)) {
+    let message = format!(
+        "too many connections, at most {} are served",
+        MAX_CONNECTIONS
+    );
+    let response = format!("{}\n", denial(DenialKind::Failed, message));
+    let _ = writer.write_all(response.as_bytes()).await;
+}
+
+// Serves the requests of a connection, holding `_permit` until it's closed.
+#[allow(clippy::too_many_arguments)]
+async fn serve_connection(
+    reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    surface: Surface,
+    dispatcher: Weak<Dispatcher>,
+    root_dir: PathBuf,
+    registry: Arc<Registry>,
+    token: Arc<Option<String>>,
+    _permit: OwnedSemaphorePermit,
+) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let line = match read_request(&mut reader).await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(message) => {
+                let response = format!("{}\n", denial(DenialKind::BadRequest, message));
+                let _ = writer.write_all(response.as_bytes()).await;
+                break;
+            }
+        };
+        let response = match dispatcher.upgrade() {
+            Some(dispatcher) => {
+                let ctx = Context {
+                    dispatcher,
+                    root_dir: root_dir.clone(),
+                };
+                respond(&registry, surface, token.as_deref(), ctx, &line).await
+            }
+            None => denial(DenialKind::Failed, "node stopped".to_string()),
+        };
+        let mut response = response.to_string();
+        response.push('\n');
+        if writer.write_all(response.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+// Reads the next request line, of at most `MAX_REQUEST_LEN` bytes, or `None` at the end of
+// the connection.
+async fn read_request(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> std::result::Result<Option<String>, String> {
+    let mut line = Vec::new();
+    let read = reader
+        .take(MAX_REQUEST_LEN)
+        .read_until(b'\n', &mut line)
+        .await
+        .map_err(|error| error.to_string())?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        let _newline = line.pop();
+    } else if read as u64 == MAX_REQUEST_LEN {
+        return Err(format!("request longer than {} bytes", MAX_REQUEST_LEN));
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|error| error.to_string())
+}
+
+async fn respond(
+    registry: &Registry,
+    surface: Surface,
+    token: Option<&str>,
+    ctx: Context,
+    line: &str,
+) -> Value {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(error) => return denial(DenialKind::BadRequest, error.to_string()),
+    };
+    let (tier, handler) = match registry.handlers.get(request.cmd.as_str()) {
+        Some(handled) => *handled,
+        None => {
+            return denial(
+                DenialKind::UnknownCommand,
+                format!("no such cmd: {:?}", request.cmd),
+            )
+        }
+    };
+
+    if tier == Tier::Privileged {
+        if let Err((kind, message)) = authorize(surface, token, request.token.as_deref()) {
+            return denial(kind, format!("{:?} {}", request.cmd, message));
+        }
+    }
+
+    match handler(ctx, request.args).await {
+        Ok(value) => json!({ "ok": value }),
+        Err(message) => denial(DenialKind::Failed, message),
+    }
+}
+
+// Whether a privileged cmd, carrying `given` as token, can be served on `surface`.
+fn authorize(
+    surface: Surface,
+    token: Option<&str>,
+    given: Option<&str>,
+) -> std::result::Result<(), (DenialKind, String)> {
+    match (surface, token) {
+        (Surface::ReadOnly, _) => Err((
+            DenialKind::PermissionDenied,
+            format!("is privileged, and only served on {}", CONTROL_SOCKET),
+        )),
+        (Surface::Rpc, None) => Err((
+            DenialKind::PermissionDenied,
+            "is privileged, and only served over TCP with a control token set".to_string(),
+        )),
+        (_, Some(token)) if !token_matches(token, given) => Err((
+            DenialKind::Unauthorized,
+            "requires the control token".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Compares tokens in constant time, so how long it takes doesn't tell how much of one is right.
+fn token_matches(token: &str, given: Option<&str>) -> bool {
+    given.map_or(false, |given| {
+        bool::from(token.as_bytes().ct_eq(given.as_bytes()))
+    })
+}
+
+fn denial(kind: DenialKind, message: String) -> Value {
+    json!({ "error": { "kind": kind, "message": message } })
+}
+
+async fn status(ctx: Context) -> std::result::Result<Value, String> {
+    let node = &ctx.dispatcher.node;
+    let info = node.info.read().await.clone();
+    let network_knowledge = node.network_knowledge();
+    let section_protocol = network_knowledge
+        .authority_provider()
+        .await
+        .protocol_digest();
+    let reward_key = get_reward_pk(&ctx.root_dir)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": format!("{:x}", info.name()),
+        "age": info.age(),
+        "prefix": format!("{:?}", network_knowledge.prefix().await),
+        "joined": network_knowledge.is_section_member(&info.name()).await,
+        "is_elder": node.is_elder().await,
+        "reward_key": reward_key.map(|key| hex::encode(key.to_bytes())),
+        "protocol": {
+            "digest": node.protocol_digest.to_string(),
+            "section_digest": section_protocol.to_string(),
+            "mismatch": section_protocol != node.protocol_digest,
+        },
+    }))
+}
+
+async fn storage(ctx: Context) -> std::result::Result<Value, String> {
+    let data_storage = &ctx.dispatcher.node.data_storage;
+    let used_space = data_storage.used_space();
+    let backend_used_bytes = data_storage
+        .chunk_backend_used_space()
+        .await
+        .map_err(|error| error.to_string())?;
+    let scrub = data_storage.scrubber().stats().await;
+    let audit = ctx.dispatcher.node.replication_audit.stats().await;
+    let hedged = ctx.dispatcher.node.hedged_reads.stats().await;
+    Ok(json!({
+        "chunks": data_storage.chunk_count(),
+        "backend": data_storage.chunk_backend(),
+        "encrypted": data_storage.chunks_encrypted(),
+        "backend_used_bytes": backend_used_bytes,
+        "used_bytes": used_space.used(),
+        "max_capacity_bytes": used_space.max_capacity(),
+        "healthy": data_storage.disk_health().is_healthy().await,
+        "full": data_storage.is_full().await,
+        "scrub": {
+            "scrubbed": scrub.scrubbed,
+            "quarantined": scrub.quarantined,
+            "passes": scrub.passes,
+        },
+        "replication_audit": {
+            "audits": audit.audits,
+            "chunks_audited": audit.chunks_audited,
+            "missing_copies": audit.missing_copies,
+            "unproven": audit.unproven,
+            "disputed": audit.disputed,
+        },
+        "hedged_reads": {
+            "reads": hedged.reads,
+            "hedges": hedged.hedges,
+            "cancelled": hedged.cancelled,
+        },
+    }))
+}
+
+async fn peers(ctx: Context) -> std::result::Result<Value, String> {
+    let node = &ctx.dispatcher.node;
+    let network_knowledge = node.network_knowledge();
+    let open_conns: serde_json::Map<_, _> = node
+        .comm
+        .open_conns()
+        .into_iter()
+        .map(|(kind, conns)| (ListenerKind::label(kind).to_string(), json!(conns)))
+        .collect();
+    let retries = node.retry_queue.stats().await;
+    let bandwidth = node.comm.bandwidth_totals();
+    let relayed = node.relay_service.stats().await;
+    let our_relay = node.our_relay.current().await;
+    let liveness = node.liveness_probes.adults().await;
+    Ok(json!({
+        "section_members": network_knowledge.section_members().await.len(),
+        "elders": network_knowledge.elders().await.len(),
+        "adults": network_knowledge.adults().await.len(),
+        "open_conns": open_conns,
+        "connected_peers": node.comm.connected_peers().await.len(),
+        "bytes_received": bandwidth.received,
+        "bytes_sent": bandwidth.sent,
+        "send_retries": {
+            "queued": retries.queued,
+            "retried": retries.retried,
+            "dropped": retries.dropped,
+        },
+        "relay": {
+            "our_relay": our_relay.map(|relay| relay.to_string()),
+            "relayed_peers": relayed.relayed_peers,
+            "forwarded_msgs": relayed.forwarded_msgs,
+            "forwarded_bytes": relayed.forwarded_bytes,
+            "throttled_msgs": relayed.throttled_msgs,
+            "refused_registrations": relayed.refused_registrations,
+        },
+        "adult_liveness": {
+            "probed_adults": liveness.len(),
+            "missing_probes": liveness.values().filter(|adult| adult.missed > 0).count(),
+            "slowest_latency_ms": liveness
+                .values()
+                .filter_map(|adult| adult.latency)
+                .max()
+                .map(|latency| latency.as_millis() as u64),
+        },
+    }))
+}
+
+async fn readiness(ctx: Context) -> std::result::Result<Value, String> {
+    let readiness = ctx.dispatcher.readiness().await;
+    Ok(json!({
+        "read": readiness.can_read(),
+        "write": readiness.can_write(),
+        "report": readiness.report(),
+    }))
+}
+
+async fn tasks(ctx: Context) -> std::result::Result<Value, String> {
+    let supervisor = &ctx.dispatcher.supervisor;
+    let tasks: Vec<_> = supervisor
+        .statuses()
+        .await
+        .into_iter()
+        .map(|task| {
+            let status = match task.status {
+                TaskStatus::Running => "running",
+                TaskStatus::Restarting => "restarting",
+                TaskStatus::Dead => "dead",
+                TaskStatus::GaveUp => "gave_up",
+            };
+            json!({
+                "name": task.name,
+                "status": status,
+                "restarts": task.restarts,
+                "last_exit": task.last_exit.map(|exit| exit.to_string()),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "tasks": tasks,
+        "failed": supervisor.has_failed_task(),
+    }))
+}
+
+async fn attestation(ctx: Context) -> std::result::Result<Value, String> {
+    match ctx.dispatcher.attestation().await {
+        Some(attestation) => serde_json::to_value(attestation).map_err(|err| err.to_string()),
+        None => Err("Our config isn't attested yet".to_string()),
+    }
+}
+
+async fn prefix(ctx: Context) -> std::result::Result<Value, String> {
+    let network_knowledge = ctx.dispatcher.node.network_knowledge();
+    Ok(json!({
+        "prefix": format!("{:b}", network_knowledge.prefix().await),
+        "section_key": hex::encode(network_knowledge.section_key().await.to_bytes()),
+    }))
+}
+
+async fn peer_dump(ctx: Context) -> std::result::Result<Value, String> {
+    let network_knowledge = ctx.dispatcher.node.network_knowledge();
+    let elders = network_knowledge.authority_provider().await.names();
+    let members = network_knowledge
+        .section_members()
+        .await
+        .into_iter()
+        .map(|member| {
+            json!({
+                "name": format!("{:x}", member.name()),
+                "addr": member.addr().to_string(),
+                "age": member.age(),
+                "is_elder": elders.contains(&member.name()),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "members": members }))
+}
+
+async fn holder_registry(ctx: Context) -> std::result::Result<Value, String> {
+    let registry = &ctx.dispatcher.node.holder_registry;
+    Ok(json!({
+        "rebuilt_percent": registry.progress().await,
+        "rebuilding": registry.is_rebuilding().await,
+    }))
+}
+
+async fn prepare_restart(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        within_secs: u64,
+        #[serde(default)]
+        drain: bool,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    ctx.dispatcher
+        .prepare_restart(Duration::from_secs(args.within_secs), args.drain)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(Value::Null)
+}
+
+async fn set_capacity(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        bytes: usize,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    store_max_capacity(&ctx.root_dir, args.bytes)
+        .await
+        .map_err(|error| error.to_string())?;
+    ctx.dispatcher
+        .set_capacity(args.bytes)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(Value::Null)
+}
+
+// Our new reward key, and those rotated away from, which are kept for the rewards still paid to
+// them. Elders aren't told of the new key, as they keep no record of reward keys.
+async fn rotate_reward_key(ctx: Context) -> std::result::Result<Value, String> {
+    let reward_key = rotate_reward_keypair(&ctx.root_dir)
+        .await
+        .map_err(|error| error.to_string())?;
+    let history = get_reward_key_history(&ctx.root_dir)
+        .await
+        .map_err(|error| error.to_string())?;
+    let retired: Vec<_> = history
+        .iter()
+        .map(|retired| {
+            json!({
+                "reward_key": hex::encode(retired.public_key.to_bytes()),
+                "retired_at": retired.retired_at,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "reward_key": hex::encode(reward_key.to_bytes()),
+        "retired": retired,
+    }))
+}
+
+// Starts checking where the section's data is held, with the optional args `sample`, `seed`,
+// `repair` and `names_per_tick`. Its report is read with `distribution_report`.
+async fn distribution_check(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        #[serde(default)]
+        sample: Option<usize>,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        repair: bool,
+        #[serde(default)]
+        names_per_tick: Option<usize>,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    let defaults = CheckOptions::default();
+    let options = CheckOptions {
+        sample: args.sample,
+        seed: args.seed,
+        repair: args.repair,
+        names_per_tick: args.names_per_tick.unwrap_or(defaults.names_per_tick),
+    };
+    ctx.dispatcher
+        .start_distribution_check(options)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(Value::Null)
+}
+
+async fn distribution_check_abort(ctx: Context) -> std::result::Result<Value, String> {
+    Ok(json!({ "aborted": ctx.dispatcher.abort_distribution_check().await }))
+}
+
+async fn reconcile_chunk_index(ctx: Context) -> std::result::Result<Value, String> {
+    let reconciliation = ctx
+        .dispatcher
+        .node
+        .data_storage
+        .reconcile_chunk_index()
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(json!({
+        "indexed": reconciliation.indexed,
+        "unindexed": reconciliation.unindexed,
+        "resized": reconciliation.resized,
+        "partial_writes_removed": reconciliation.partial_writes_removed,
+    }))
+}
+
+async fn distribution_report(ctx: Context) -> std::result::Result<Value, String> {
+    let report = match ctx.dispatcher.distribution_report().await {
+        Some(report) => report,
+        None => return Ok(Value::Null),
+    };
+    let names = |names: &BTreeSet<XorName>| {
+        names
+            .iter()
+            .map(|name| format!("{:x}", name))
+            .collect::<Vec<_>>()
+    };
+    let misplacements = report
+        .misplacements
+        .iter()
+        .map(|misplacement| {
+            json!({
+                "name": format!("{:x}", misplacement.name),
+                "severity": format!("{:?}", misplacement.severity),
+                "expected_missing": names(&misplacement.expected_missing),
+                "unexpected": names(&misplacement.unexpected),
+                "repair_enqueued": misplacement.repair_enqueued,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "status": format!("{:?}", report.status),
+        "seed": report.seed,
+        "total": report.total,
+        "checked": report.checked,
+        "misplacements": misplacements,
+        "unresponsive": names(&report.unresponsive),
+    }))
+}
+
+// Replicates the chunk named by `name`, in hex, or those in `prefix`, a string of bits, to
+// their missing holders, making at most `max` copies. Answered with the id of the job making
+// them, whose progress is read with `replication_job` (arg `job`).
+async fn replicate(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        prefix: Option<String>,
+        #[serde(default)]
+        max: Option<usize>,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    let target = match (args.name, args.prefix, args.max) {
+        (Some(name), None, None) => ReplicationTarget::Name(parse_name(&name)?),
+        (None, Some(prefix), Some(max)) => ReplicationTarget::Prefix {
+            prefix: parse_prefix(&prefix)?,
+            max,
+        },
+        _ => return Err("Expected either `name`, or `prefix` and `max`".to_string()),
+    };
+    let job = ctx
+        .dispatcher
+        .start_replication_job(target)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(json!({ "job": job }))
+}
+
+async fn replication_job(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        job: u64,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    let progress = ctx
+        .dispatcher
+        .replication_progress(args.job)
+        .await
+        .ok_or_else(|| format!("No replication job {}", args.job))?;
+    let target = match progress.target {
+        ReplicationTarget::Name(name) => json!({ "name": format!("{:x}", name) }),
+        ReplicationTarget::Prefix { prefix, max } => {
+            json!({ "prefix": format!("{:b}", prefix), "max": max })
+        }
+    };
+    Ok(json!({
+        "job": progress.id,
+        "target": target,
+        "queued": progress.queued,
+        "transferred": progress.transferred,
+        "confirmed": progress.confirmed,
+        "failed": progress.failed,
+        "skipped": progress.skipped,
+        "unsourced": progress.unsourced,
+        "done": progress.done,
+    }))
+}
+
+async fn connected_peers(ctx: Context) -> std::result::Result<Value, String> {
+    let peers = ctx
+        .dispatcher
+        .node
+        .comm
+        .connected_peers()
+        .await
+        .into_iter()
+        .map(|peer| {
+            json!({
+                "name": format!("{:x}", peer.name()),
+                "addr": peer.addr().to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "peers": peers }))
+}
+
+// The scores of the peers which misbehaved lately, and whether they're banned for it.
+async fn reputation(ctx: Context) -> std::result::Result<Value, String> {
+    let peers = ctx
+        .dispatcher
+        .node
+        .comm
+        .peer_reputations()
+        .into_iter()
+        .map(|peer| {
+            let counts: BTreeMap<_, _> = peer
+                .counts
+                .iter()
+                .map(|(misbehaviour, count)| (misbehaviour.to_string(), *count))
+                .collect();
+            json!({
+                "addr": peer.addr.to_string(),
+                "name": peer.name.map(|name| format!("{:x}", name)),
+                "score": peer.score,
+                "counts": counts,
+                "banned_for_secs": peer.banned_for.map(|left| left.as_secs()),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "peers": peers }))
+}
+
+// Changes the filter of our logs to `filter`, in `RUST_LOG`'s syntax.
+async fn set_log_level(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        filter: String,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    ctx.dispatcher
+        .set_log_filter(&args.filter)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(Value::Null)
+}
+
+async fn scrub_chunks(ctx: Context) -> std::result::Result<Value, String> {
+    let data_storage = &ctx.dispatcher.node.data_storage;
+    ctx.dispatcher
+        .clone()
+        .scrub_next_chunks()
+        .await
+        .map_err(|error| error.to_string())?;
+    let scrub = data_storage.scrubber().stats().await;
+    Ok(json!({
+        "scrubbed": scrub.scrubbed,
+        "quarantined": scrub.quarantined,
+        "passes": scrub.passes,
+    }))
+}
+
+async fn data_cache(ctx: Context) -> std::result::Result<Value, String> {
+    let data_cache = &ctx.dispatcher.node.data_cache;
+    let options = data_cache.options().await;
+    let stats = data_cache.stats().await;
+    Ok(json!({
+        "enabled": options.enabled,
+        "max_bytes": options.max_bytes,
+        "ttl_secs": options.ttl.as_secs(),
+        "hits": stats.hits,
+        "misses": stats.misses,
+        "evictions": stats.evictions,
+        "entries": stats.entries,
+        "bytes": stats.bytes,
+    }))
+}
+
+// The DKG sessions we take part in: their participants, phase, the participants whose
+// contributions are missing, and how often they were restarted.
+async fn dkg_sessions(ctx: Context) -> std::result::Result<Value, String> {
+    let names = |names: &BTreeSet<XorName>| {
+        names
+            .iter()
+            .map(|name| format!("{:x}", name))
+            .collect::<Vec<_>>()
+    };
+    let sessions = ctx
+        .dispatcher
+        .node
+        .dkg_sessions()
+        .into_iter()
+        .map(|session| {
+            json!({
+                "session": hex::encode(session.session_id.hash()),
+                "prefix": format!("{:b}", session.session_id.prefix),
+                "section_chain_len": session.session_id.section_chain_len,
+                "generation": session.generation,
+                "participants": names(&session.session_id.elder_names().collect()),
+                "phase": format!("{:?}", session.phase),
+                "missing": names(&session.missing),
+                "elapsed_secs": session.elapsed.as_secs(),
+                "restarts": session.restarts,
+                "retries": session.retries,
+                "complete": session.complete,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "sessions": sessions }))
+}
+
+// The members, elders and adults of each half of the section, the data falling in it and the
+// copies of it to make there, and what it falls short of to be a section of its own.
+async fn split_rehearsal(ctx: Context) -> std::result::Result<Value, String> {
+    let report = ctx
+        .dispatcher
+        .node
+        .rehearse_split(&BTreeSet::new())
+        .await
+        .ok_or_else(|| "Only elders of a section which can split rehearse it".to_string())?;
+    let halves = [&report.zero, &report.one]
+        .into_iter()
+        .map(|half| {
+            let shortfalls = half
+                .shortfalls
+                .iter()
+                .map(|shortfall| {
+                    let (of, have, need) = match *shortfall {
+                        Shortfall::Members { have, need } => ("members", have, need),
+                        Shortfall::Elders { have, need } => ("elders", have, need),
+                        Shortfall::Adults { have, need } => ("adults", have, need),
+                    };
+                    json!({ "of": of, "have": have, "need": need })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "prefix": format!("{:b}", half.prefix),
+                "members": half.members.len(),
+                "elders": half.elders.len(),
+                "adults": half.adults,
+                "chunks": half.chunks,
+                "copies_to_make": half.copies_to_make,
+                "shortfalls": shortfalls,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "prefix": format!("{:b}", report.prefix),
+        "ready": report.is_ready(),
+        "halves": halves,
+    }))
+}
+
+async fn split_handover(ctx: Context) -> std::result::Result<Value, String> {
+    let handovers = ctx
+        .dispatcher
+        .node
+        .split_handover
+        .progress()
+        .await
+        .into_iter()
+        .map(|progress| {
+            json!({
+                "sibling": format!("{:b}", progress.sibling),
+                "queued": progress.queued,
+                "sent": progress.sent,
+                "confirmed": progress.confirmed,
+                "failed": progress.failed,
+                "done": progress.done,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "handovers": handovers }))
+}
+
+// Changes how chunks relayed to clients are cached, with the optional args `enabled`,
+// `max_bytes` and `ttl_secs`.
+async fn set_cache_options(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    #[derive(Deserialize)]
+    struct Args {
+        #[serde(default)]
+        enabled: Option<bool>,
+        #[serde(default)]
+        max_bytes: Option<usize>,
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    }
+    let args: Args = serde_json::from_value(args).map_err(|error| error.to_string())?;
+    let data_cache = &ctx.dispatcher.node.data_cache;
+    let current = data_cache.options().await;
+    data_cache
+        .set_options(CacheOptions {
+            enabled: args.enabled.unwrap_or(current.enabled),
+            max_bytes: args.max_bytes.unwrap_or(current.max_bytes),
+            ttl: args
+                .ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(current.ttl),
+        })
+        .await;
+    Ok(Value::Null)
+}
+
+// Injects faults, with the optional args `heal`, `msg_drop_percent`, `peer`,
+// `chunk_write_delay_ms` and `chunk_io_error_percent`, answering with the faults in effect.
+#[cfg(feature = "chaos")]
+async fn chaos(ctx: Context, args: Value) -> std::result::Result<Value, String> {
+    let chaos: super::chaos::Chaos = match args {
+        Value::Null => Default::default(),
+        args => serde_json::from_value(args).map_err(|error| error.to_string())?,
+    };
+    chaos.validate()?;
+    chaos.inject(&ctx.dispatcher.node);
+    Ok(super::chaos::report(&ctx.dispatcher.node))
+}
+
+// The last msgs received and sent, oldest first, with their peer, the name they're addressed
+// to, their kind, size and what became of them.
+async fn msg_audit(ctx: Context) -> std::result::Result<Value, String> {
+    let audit = ctx.dispatcher.node.comm.msg_audit();
+    let msgs = audit
+        .entries()
+        .into_iter()
+        .map(|entry| {
+            let at_ms = entry
+                .at
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_millis() as u64)
+                .unwrap_or_default();
+            json!({
+                "at_ms": at_ms,
+                "direction": entry.direction.as_str(),
+                "msg_id": hex::encode(entry.msg_id.as_ref()),
+                "peer": {
+                    "name": format!("{:x}", entry.peer.name()),
+                    "addr": entry.peer.addr().to_string(),
+                },
+                "dst": format!("{:x}", entry.dst),
+                "kind": entry.kind,
+                "size": entry.size,
+                "disposition": entry.disposition.as_str(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "capacity": audit.capacity(), "msgs": msgs }))
+}
+
+// Starts shutting down, answering before it's done, as we won't be around after.
+async fn shutdown(ctx: Context) -> std::result::Result<Value, String> {
+    let dispatcher = ctx.dispatcher;
+    let _handle = task::spawn(async move {
+        if let Err(error) = dispatcher.shut_down(DRAIN_TIMEOUT).await {
+            error!("Failed to shut down gracefully: {:?}", error);
+        }
+    });
+    Ok(Value::Null)
+}
+
+// Parses the hex of a name.
+fn parse_name(hex: &str) -> std::result::Result<XorName, String> {
+    let bytes = hex::decode(hex).map_err(|error| format!("Invalid name {:?}: {}", hex, error))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("Invalid name {:?}: not 32 bytes", hex))?;
+    Ok(XorName(bytes))
+}
+
+// Parses a prefix given as a string of bits, e.g. `0110`.
+fn parse_prefix(bits: &str) -> std::result::Result<Prefix, String> {
+    bits.chars()
+        .try_fold(Prefix::default(), |prefix, bit| match bit {
+            '0' => Ok(prefix.pushed(false)),
+            '1' => Ok(prefix.pushed(true)),
+            _ => Err(format!("Invalid prefix {:?}: not a string of bits", bits)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn every_cmd_served_is_declared_with_its_tier() -> Result<()> {
+        let registry = registry()?;
+        assert_eq!(registry.handlers.len(), COMMANDS.len());
+
+        let undeclared = Registry::new(COMMANDS).handle("wipe", |ctx, _| Box::pin(status(ctx)));
+        assert_matches!(undeclared, Err(Error::Configuration(_)));
+
+        let unhandled = Registry::new(&[("status", Tier::ReadOnly), ("wipe", Tier::Privileged)])
+            .handle("status", |ctx, _| Box::pin(status(ctx)))?
+            .build();
+        assert_matches!(unhandled, Err(Error::Configuration(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_lines_are_bounded() {
+        let long = vec![b'x'; MAX_REQUEST_LEN as usize * 2];
+        let input = [b"{\"cmd\": \"status\"}\n".as_slice(), &long, b"\n"].concat();
+        let mut reader = BufReader::new(input.as_slice());
+
+        assert_eq!(
+            read_request(&mut reader).await,
+            Ok(Some("{\"cmd\": \"status\"}".to_string()))
+        );
+        assert!(read_request(&mut reader).await.is_err());
+        assert_eq!(read_request(&mut BufReader::new(&b""[..])).await, Ok(None));
+    }
+
+    #[test]
+    fn privileged_cmds_need_the_exact_token() {
+        assert!(authorize(Surface::Control, Some("secret"), Some("secret")).is_ok());
+        assert!(authorize(Surface::Control, None, None).is_ok());
+        for given in [
+            None,
+            Some(""),
+            Some("secre"),
+            Some("secret!"),
+            Some("Secret"),
+        ] {
+            assert_matches!(
+                authorize(Surface::Control, Some("secret"), given),
+                Err((DenialKind::Unauthorized, _))
+            );
+        }
+    }
+
+    #[test]
+    fn replication_targets_parse() {
+        let name = xor_name::rand::random();
+        assert_eq!(parse_name(&format!("{:x}", name)), Ok(name));
+        assert!(parse_name("abcd").is_err());
+        assert!(parse_name("not hex").is_err());
+
+        let prefix = parse_prefix("011");
+        assert_eq!(
+            prefix,
+            Ok(Prefix::default().pushed(false).pushed(true).pushed(true))
+        );
+        assert_eq!(parse_prefix(""), Ok(Prefix::default()));
+        assert!(parse_prefix("012").is_err());
+    }
+}
@@ -6,36 +6,94 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Cmd;
+#[cfg(feature = "metrics")]
+use super::metrics::Metrics;
+use super::{
+    cmd_scheduler::{CmdScheduler, Turn, CMD_CONCURRENCY},
+    cmds::CmdKey,
+    fair_scheduler::{FairScheduler, CLIENT_MSG_CONCURRENCY, CLIENT_QUEUE_CAP},
+    shutdown::{self, Shutdown},
+    supervisor::{RestartPolicy, TaskSupervisor},
+    Cmd, LogFilterReloader,
+};
 
+use crate::attestation::{self, Attestation};
+use crate::control::{self, CONTROL_POLL_INTERVAL};
 use crate::node::{
-    core::{DeliveryStatus, Node, Proposal},
+    core::{
+        CheckOptions, Condition, DeliveryStatus, Direction, Disposition, DistributionReport,
+        JobProgress, Misbehaviour, Node, Proposal, Readiness, ReplicationTarget, Retry,
+        DISTRIBUTION_CHECK_INTERVAL, LIVENESS_PROBE_INTERVAL, MSG_FILTER_CHECKPOINT_INTERVAL,
+        REGISTRY_BOOTSTRAP_INTERVAL, RELAY_RENEWAL_INTERVAL, REPLICATION_AUDIT_INTERVAL,
+        RETRY_INTERVAL, SCRUB_INTERVAL,
+    },
     messages::WireMsgUtils,
-    Result,
+    Config, Error, Result,
 };
 #[cfg(feature = "back-pressure")]
 use sn_interface::messaging::DstLocation;
-use sn_interface::messaging::{system::SystemMsg, AuthKind, WireMsg};
-use sn_interface::types::{log_markers::LogMarker, Peer};
-use std::{collections::BTreeSet, sync::Arc, time::Duration};
+use sn_interface::messaging::{system::SystemMsg, AuthKind, MsgId, MsgType, WireMsg};
+use sn_interface::types::{log_markers::LogMarker, Peer, PublicKey};
+
+use bytes::Bytes;
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::time::MissedTickBehavior;
-use tokio::{sync::watch, time};
-use tracing::Instrument;
+use tokio::{
+    sync::{watch, RwLock},
+    time,
+};
+use tracing::{Instrument, Span};
 
 const PROBE_INTERVAL: Duration = Duration::from_secs(30);
-#[cfg(feature = "back-pressure")]
-const BACKPRESSURE_INTERVAL: Duration = Duration::from_secs(60);
 const LINK_CLEANUP_INTERVAL: Duration = Duration::from_secs(120);
 const DYSFUNCTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const DISK_SELF_TEST_INTERVAL: Duration = Duration::from_secs(60);
+const MEMBERSHIP_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const EVENT_LOOP_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Lag of the runtime beyond which msgs can't be counted on to be handled in time.
+const MAX_EVENT_LOOP_LAG: Duration = Duration::from_secs(1);
 
 // A command/subcommand id e.g. "963111461", "963111461.0"
 type CmdId = String;
 
+/// The id of the cmd handling `msg_id`, which the ids of all the cmds it leads to extend. Logs of
+/// the whole chain share it as their `trace_id`, for the handling of a msg to be followed through.
+pub(super) fn msg_cmd_id(msg_id: MsgId) -> CmdId {
+    hex::encode(msg_id.as_ref())
+}
+
+// The id of the cmd the chain `cmd_id` belongs to started with.
+fn trace_id(cmd_id: &str) -> &str {
+    cmd_id.split('.').next().unwrap_or(cmd_id)
+}
+
 // Cmd Dispatcher.
 pub(crate) struct Dispatcher {
     pub(crate) node: Node,
+    pub(crate) supervisor: TaskSupervisor,
+    pub(crate) client_scheduler: FairScheduler<PublicKey>,
+    // Orders the cmds touching the same peers or data, and bounds how many are processed at once.
+    cmd_scheduler: CmdScheduler<CmdKey>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Metrics,
+    // How late the last probe of our runtime ran, in ms
+    event_loop_lag_ms: AtomicU64,
+    // The config attested to, the root dir the record is written to, and the latest record.
+    attestation: RwLock<Option<(Config, PathBuf, Attestation)>>,
+    // Changes the filter of our logs, if whoever set them up lets us.
+    log_filter_reloader: RwLock<Option<LogFilterReloader>>,
     cancel_timer_tx: watch::Sender<bool>,
     cancel_timer_rx: watch::Receiver<bool>,
+    pub(crate) shutdown: Shutdown,
 }
 
 impl Drop for Dispatcher {
@@ -50,8 +108,17 @@ impl Dispatcher {
         let (cancel_timer_tx, cancel_timer_rx) = watch::channel(false);
         Self {
             node,
+            supervisor: TaskSupervisor::new(),
+            client_scheduler: FairScheduler::new(CLIENT_MSG_CONCURRENCY, CLIENT_QUEUE_CAP),
+            cmd_scheduler: CmdScheduler::new(CMD_CONCURRENCY),
+            #[cfg(feature = "metrics")]
+            metrics: Metrics::default(),
+            event_loop_lag_ms: AtomicU64::new(0),
+            attestation: RwLock::new(None),
+            log_filter_reloader: RwLock::new(None),
             cancel_timer_tx,
             cancel_timer_rx,
+            shutdown: Shutdown::new(),
         }
     }
 
@@ -61,12 +128,7 @@ impl Dispatcher {
         cmd: Cmd,
         cmd_id: Option<CmdId>,
     ) -> Result<()> {
-        let _ = tokio::spawn(async {
-            let cmd_id: CmdId = cmd_id.unwrap_or_else(|| rand::random::<u32>().to_string());
-
-            self.handle_cmd_and_offshoots(cmd, Some(cmd_id)).await
-        });
-        Ok(())
+        self.handle_cmd_and_offshoots(cmd, cmd_id).await
     }
 
     /// Handles cmd and transitively queues any new cmds that are
@@ -79,10 +141,24 @@ impl Dispatcher {
         cmd_id: Option<CmdId>,
     ) -> Result<()> {
         let cmd_id = cmd_id.unwrap_or_else(|| rand::random::<u32>().to_string());
-        let cmd_id_clone = cmd_id.clone();
-        let cmd_display = cmd.to_string();
-        let _task = tokio::spawn(async move {
-            match self.process_cmd(cmd, &cmd_id).await {
+        self.spawn_cmd_handling(cmd, cmd_id)
+    }
+
+    // Processes cmd once it's its turn, spawning the handling of any sub-cmds it produces within
+    // its span, for them to show nested in it.
+    async fn process_cmd_and_spawn_offshoots(
+        self: Arc<Self>,
+        cmd: Cmd,
+        cmd_id: CmdId,
+        turn: Turn<CmdKey>,
+    ) {
+        let span = self.cmd_span(&cmd, &cmd_id).await;
+        async move {
+            let running = turn.wait().await;
+            let cmds = self.process_cmd_in_current_span(cmd, &cmd_id).await;
+            // The sub-cmds are processed after this one, even when touching the same keys.
+            drop(running);
+            match cmds {
                 Ok(cmds) => {
                     for (sub_cmd_count, cmd) in cmds.into_iter().enumerate() {
                         let sub_cmd_id = format!("{}.{}", &cmd_id, sub_cmd_count);
@@ -94,127 +170,895 @@ impl Dispatcher {
                     error!("Failed to handle cmd {:?} with error {:?}", cmd_id, err);
                 }
             }
-        });
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Queues a msg from a client to be handled once it's the client's turn. If the client has
+    /// too many msgs queued already, it's told we're busy instead.
+    pub(super) async fn schedule_client_msg(
+        self: Arc<Self>,
+        client: PublicKey,
+        sender: Peer,
+        wire_msg: WireMsg,
+        original_bytes: Bytes,
+    ) {
+        let size = original_bytes.len();
+        let cmd = Cmd::HandleMsg {
+            sender,
+            wire_msg: wire_msg.clone(),
+            original_bytes: Some(original_bytes),
+        };
+        let cmd_id = msg_cmd_id(wire_msg.msg_id());
+        let turn = self.cmd_scheduler.take_turn(cmd.keys(), cmd.is_bounded());
+        let job = self
+            .clone()
+            .process_cmd_and_spawn_offshoots(cmd, cmd_id, turn)
+            .in_current_span();
+        let queued = self.client_scheduler.submit(client, job).await.is_ok();
+        let disposition = if queued {
+            Disposition::Queued
+        } else {
+            Disposition::RefusedBusy
+        };
+        self.node
+            .comm
+            .msg_audit()
+            .record(Direction::Inbound, &wire_msg, sender, size, disposition);
+        if queued {
+            return;
+        }
+
+        warn!(
+            "Client {:?} has too many msgs queued, refusing {:?} from {:?}",
+            client,
+            wire_msg.msg_id(),
+            sender
+        );
+        self.refuse_client_msg(client, sender, wire_msg).await;
+    }
+
+    /// Tells a client we're busy rather than handling its msg, for it to retry elsewhere.
+    pub(super) async fn refuse_client_msg(
+        self: Arc<Self>,
+        client: PublicKey,
+        sender: Peer,
+        wire_msg: WireMsg,
+    ) {
+        let cmd_id = msg_cmd_id(wire_msg.msg_id());
+        let cmds = match wire_msg.into_msg() {
+            Ok(MsgType::Service { msg_id, msg, .. }) => {
+                self.node.send_busy_response(&msg, sender, msg_id).await
+            }
+            Ok(_) => Ok(vec![]),
+            Err(err) => Err(err.into()),
+        };
+        match cmds {
+            Ok(cmds) => {
+                for (sub_cmd_count, cmd) in cmds.into_iter().enumerate() {
+                    let sub_cmd_id = format!("{}.{}", &cmd_id, sub_cmd_count);
+                    let _result = self.clone().spawn_cmd_handling(cmd, sub_cmd_id);
+                }
+            }
+            Err(err) => error!("Failed to tell client {:?} we're busy: {:?}", client, err),
+        }
+    }
 
+    pub(super) async fn start_client_msg_scheduling(self: Arc<Self>) {
+        let scheduler = self.client_scheduler.clone();
+        self.supervisor
+            .spawn(
+                "client_msg_scheduling",
+                RestartPolicy::default_backoff(),
+                move || scheduler.clone().run(),
+            )
+            .await;
+    }
+
+    // Takes the turn of the cmd right away, for cmds touching the same keys to be processed in
+    // the order they're dispatched, and spawns its processing.
+    fn spawn_cmd_handling(self: Arc<Self>, cmd: Cmd, cmd_id: CmdId) -> Result<()> {
+        let turn = self.cmd_scheduler.take_turn(cmd.keys(), cmd.is_bounded());
+        let cmd_display = cmd.to_string();
         trace!(
             "{:?} {} cmd_id={}",
             LogMarker::CmdHandlingSpawned,
             cmd_display,
-            &cmd_id_clone
+            &cmd_id
+        );
+        let supervisor = self.supervisor.clone();
+        let _task = supervisor.spawn_counted(
+            "cmd_handling",
+            self.process_cmd_and_spawn_offshoots(cmd, cmd_id, turn)
+                .in_current_span(),
         );
-        Ok(())
-    }
-
-    // Note: this indirecton is needed. Trying to call `spawn(self.handle_cmds(...))` directly
-    // inside `handle_cmds` causes compile error about type check cycle.
-    fn spawn_cmd_handling(self: Arc<Self>, cmd: Cmd, cmd_id: String) -> Result<()> {
-        let _task = tokio::spawn(self.enqueue_and_handle_next_cmd_and_offshoots(cmd, Some(cmd_id)));
         Ok(())
     }
 
     pub(super) async fn start_network_probing(self: Arc<Self>) {
         info!("Starting to probe network");
-        let _handle = tokio::spawn(async move {
-            let dispatcher = self.clone();
-            let mut interval = tokio::time::interval(PROBE_INTERVAL);
-            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-
-            loop {
-                let _instant = interval.tick().await;
-
-                // Send a probe message if we are an elder
-                let node = &dispatcher.node;
-                if node.is_elder().await && !node.network_knowledge().prefix().await.is_empty() {
-                    match node.generate_probe_msg().await {
-                        Ok(cmd) => {
-                            info!("Sending probe msg");
-                            if let Err(e) = dispatcher
-                                .clone()
-                                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
-                                .await
-                            {
-                                error!("Error sending a probe msg to the network: {:?}", e);
-                            }
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "network_probing",
+                RestartPolicy::default_backoff(),
+                move || self.clone().probe_network(),
+            )
+            .await;
+    }
+
+    async fn probe_network(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(PROBE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            // Send a probe message if we are an elder
+            let node = &dispatcher.node;
+            if node.is_elder().await && !node.network_knowledge().prefix().await.is_empty() {
+                match node.generate_probe_msg().await {
+                    Ok(cmd) => {
+                        info!("Sending probe msg");
+                        if let Err(e) = dispatcher
+                            .clone()
+                            .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                            .await
+                        {
+                            error!("Error sending a probe msg to the network: {:?}", e);
                         }
-                        Err(error) => error!("Problem generating probe msg: {:?}", error),
                     }
+                    Err(error) => error!("Problem generating probe msg: {:?}", error),
                 }
             }
-        });
+        }
     }
     pub(super) async fn start_cleaning_peer_links(self: Arc<Self>) {
         info!("Starting cleaning up network links");
-        let _handle = tokio::spawn(async move {
-            let dispatcher = self.clone();
-            let mut interval = tokio::time::interval(LINK_CLEANUP_INTERVAL);
-            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "peer_link_cleanup",
+                RestartPolicy::default_backoff(),
+                move || self.clone().clean_peer_links(),
+            )
+            .await;
+    }
+
+    async fn clean_peer_links(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(LINK_CLEANUP_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let _ = interval.tick().await;
+
+        loop {
             let _ = interval.tick().await;
+            let cmd = Cmd::CleanupPeerLinks;
+            if let Err(e) = dispatcher
+                .clone()
+                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                .await
+            {
+                error!(
+                    "Error requesting a cleaning up of unused PeerLinks: {:?}",
+                    e
+                );
+            }
+        }
+    }
+    pub(super) async fn check_disk_health_periodically(self: Arc<Self>) {
+        info!("Starting disk self-tests");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "disk_self_test",
+                RestartPolicy::default_backoff(),
+                move || self.clone().check_disk_health(),
+            )
+            .await;
+    }
+
+    async fn check_disk_health(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(DISK_SELF_TEST_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
-            loop {
-                let _ = interval.tick().await;
-                let cmd = Cmd::CleanupPeerLinks;
+        loop {
+            let _instant = interval.tick().await;
+
+            let cmds = match dispatcher.node.check_disk_health().await {
+                Ok(cmds) => cmds,
+                Err(error) => {
+                    error!("Error reporting our storage health: {error}");
+                    continue;
+                }
+            };
+            for cmd in cmds {
                 if let Err(e) = dispatcher
                     .clone()
                     .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
                     .await
                 {
-                    error!(
-                        "Error requesting a cleaning up of unused PeerLinks: {:?}",
-                        e
-                    );
+                    error!("Error reporting our storage health: {e}");
                 }
             }
-        });
+        }
     }
-    pub(super) async fn check_for_dysfunction_periodically(self: Arc<Self>) {
-        info!("Starting dysfunction checking");
-        let _handle = tokio::spawn(async move {
-            let dispatcher = self.clone();
-            let mut interval = tokio::time::interval(DYSFUNCTION_CHECK_INTERVAL);
-            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
-            loop {
-                let _instant = interval.tick().await;
+    pub(super) async fn scrub_chunks_periodically(self: Arc<Self>) {
+        info!("Starting chunk scrubbing");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn("chunk_scrub", RestartPolicy::default_backoff(), move || {
+                self.clone().scrub_chunks()
+            })
+            .await;
+    }
 
-                let unresponsive_nodes = match dispatcher.node.get_dysfunctional_node_names().await
-                {
-                    Ok(nodes) => nodes,
-                    Err(error) => {
-                        error!("Error getting dysfunctional nodes: {error}");
-                        BTreeSet::default()
+    async fn scrub_chunks(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(SCRUB_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(error) = self.clone().scrub_next_chunks().await {
+                error!("Error scrubbing our chunks: {error}");
+            }
+        }
+    }
+
+    /// Scrubs the next batch of the chunks we hold right away, rather than waiting for the next
+    /// tick, having those found corrupt replicated to us again.
+    pub(super) async fn scrub_next_chunks(self: Arc<Self>) -> Result<()> {
+        for cmd in self.node.scrub_chunks().await? {
+            self.clone()
+                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn checkpoint_msg_filter_periodically(self: Arc<Self>) {
+        info!("Starting msg filter checkpoints");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "msg_filter_checkpoint",
+                RestartPolicy::default_backoff(),
+                move || self.clone().checkpoint_msg_filter(),
+            )
+            .await;
+    }
+
+    async fn checkpoint_msg_filter(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(MSG_FILTER_CHECKPOINT_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(error) = self.node.msg_filter.checkpoint().await {
+                error!("Error checkpointing the msg filter: {error}");
+            }
+        }
+    }
+
+    pub(super) async fn retry_failed_sends_periodically(self: Arc<Self>) {
+        info!("Starting retries of msgs which failed to be sent");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "send_retries",
+                RestartPolicy::default_backoff(),
+                move || self.clone().retry_failed_sends(),
+            )
+            .await;
+    }
+
+    async fn retry_failed_sends(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+            for retry in self.node.retry_queue.take_due().await {
+                self.clone().retry_send(retry).await;
+            }
+            if let Err(error) = self.node.retry_queue.checkpoint().await {
+                error!("Error checkpointing the retry queue: {error}");
+            }
+        }
+    }
+
+    // Sends a msg again, queueing it once more if it still fails to reach enough recipients.
+    async fn retry_send(self: Arc<Self>, retry: Retry) {
+        let wire_msg = match retry.wire_msg() {
+            Ok(wire_msg) => wire_msg,
+            Err(error) => {
+                error!("Dropping a msg queued for retry which can't be read: {error}");
+                return;
+            }
+        };
+        let msg_id = wire_msg.msg_id();
+        trace!(
+            "Retrying msg {:?} to {:?}, attempt {}",
+            msg_id,
+            retry.recipients,
+            retry.attempts + 1
+        );
+        match self
+            .try_send_msg(&retry.recipients, retry.delivery_group_size, wire_msg)
+            .await
+        {
+            Ok((cmds, undelivered)) => {
+                if let Some((recipients, delivery_group_size)) = undelivered {
+                    self.node
+                        .retry_queue
+                        .requeue(retry.narrowed(recipients, delivery_group_size))
+                        .await;
+                }
+                let cmd_id = msg_cmd_id(msg_id);
+                for (sub_cmd_count, cmd) in cmds.into_iter().enumerate() {
+                    let sub_cmd_id = format!("{}.{}", &cmd_id, sub_cmd_count);
+                    let _result = self.clone().spawn_cmd_handling(cmd, sub_cmd_id);
+                }
+            }
+            Err(error) => {
+                warn!("Failed retrying msg {:?}: {:?}", msg_id, error);
+                self.node.retry_queue.requeue(retry).await;
+            }
+        }
+    }
+
+    pub(super) async fn renew_relays_periodically(self: Arc<Self>) {
+        info!("Starting relay renewals");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "relay_renewals",
+                RestartPolicy::default_backoff(),
+                move || self.clone().renew_relays(),
+            )
+            .await;
+    }
+
+    async fn renew_relays(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(RELAY_RENEWAL_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+            match self.node.renew_relays().await {
+                Ok(cmds) => {
+                    for cmd in cmds {
+                        let _result = self.clone().spawn_cmd_handling(cmd, "relay_renewal".into());
                     }
-                };
+                }
+                Err(error) => error!("Error renewing relays: {error}"),
+            }
+        }
+    }
 
-                if !unresponsive_nodes.is_empty() {
-                    debug!("{:?} : {unresponsive_nodes:?}", LogMarker::ProposeOffline);
-                    let cmd = Cmd::ProposeOffline(unresponsive_nodes);
-                    if let Err(e) = dispatcher
-                        .clone()
-                        .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
-                        .await
-                    {
-                        error!("Error sending Propose Offline for dysfunctional nodes: {e:?}");
+    pub(super) async fn rebuild_holder_registry_periodically(self: Arc<Self>) {
+        info!("Starting holder registry rebuilds");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "holder_registry_rebuild",
+                RestartPolicy::default_backoff(),
+                move || self.clone().rebuild_holder_registry(),
+            )
+            .await;
+    }
+
+    async fn rebuild_holder_registry(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(REGISTRY_BOOTSTRAP_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            let cmds = match dispatcher.node.request_next_inventory_slices().await {
+                Ok(cmds) => cmds,
+                Err(error) => {
+                    error!("Error requesting inventories from adults: {error}");
+                    continue;
+                }
+            };
+            for cmd in cmds {
+                if let Err(e) = dispatcher
+                    .clone()
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .await
+                {
+                    error!("Error requesting inventories from adults: {e}");
+                }
+            }
+        }
+    }
+
+    pub(super) async fn check_data_distribution_periodically(self: Arc<Self>) {
+        info!("Starting data distribution checks");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "distribution_check",
+                RestartPolicy::default_backoff(),
+                move || self.clone().check_data_distribution(),
+            )
+            .await;
+    }
+
+    async fn check_data_distribution(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(DISTRIBUTION_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            // Operators' replication jobs go first, being about data known to be missing.
+            let mut cmds = match dispatcher.node.run_replication_jobs().await {
+                Ok(cmds) => cmds,
+                Err(error) => {
+                    error!("Error running replication jobs: {error}");
+                    vec![]
+                }
+            };
+            match dispatcher.node.run_split_handover().await {
+                Ok(handover_cmds) => cmds.extend(handover_cmds),
+                Err(error) => error!("Error handing data over to our sibling section: {error}"),
+            }
+            match dispatcher.node.check_next_distribution_batch().await {
+                Ok(check_cmds) => cmds.extend(check_cmds),
+                Err(error) => error!("Error checking data distribution: {error}"),
+            }
+            match dispatcher.node.probe_warming_up_adults().await {
+                Ok(probe_cmds) => cmds.extend(probe_cmds),
+                Err(error) => error!("Error probing adults warming up: {error}"),
+            }
+            for cmd in cmds {
+                if let Err(e) = dispatcher
+                    .clone()
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .await
+                {
+                    error!("Error checking data distribution: {e}");
+                }
+            }
+        }
+    }
+
+    pub(super) async fn audit_replication_periodically(self: Arc<Self>) {
+        info!("Starting replication audits");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "replication_audit",
+                RestartPolicy::default_backoff(),
+                move || self.clone().audit_replication(),
+            )
+            .await;
+    }
+
+    async fn audit_replication(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(REPLICATION_AUDIT_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            let cmds = match dispatcher.node.audit_next_chunks().await {
+                Ok(cmds) => cmds,
+                Err(error) => {
+                    error!("Error auditing chunk replication: {error}");
+                    continue;
+                }
+            };
+            for cmd in cmds {
+                if let Err(e) = dispatcher
+                    .clone()
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .await
+                {
+                    error!("Error auditing chunk replication: {e}");
+                }
+            }
+        }
+    }
+
+    pub(super) async fn probe_liveness_periodically(self: Arc<Self>) {
+        info!("Starting adult liveness probes");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "liveness_probes",
+                RestartPolicy::default_backoff(),
+                move || self.clone().probe_liveness(),
+            )
+            .await;
+    }
+
+    async fn probe_liveness(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(LIVENESS_PROBE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            let cmds = match dispatcher.node.probe_adults_liveness().await {
+                Ok(cmds) => cmds,
+                Err(error) => {
+                    error!("Error probing adults liveness: {error}");
+                    continue;
+                }
+            };
+            for cmd in cmds {
+                if let Err(e) = dispatcher
+                    .clone()
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .await
+                {
+                    error!("Error probing adults liveness: {e}");
+                }
+            }
+        }
+    }
+
+    /// Starts checking that the data in our holder registry is held where it should be, as an
+    /// elder. Batches are looked up in the background, the outcome is read with
+    /// `distribution_report`.
+    pub(crate) async fn start_distribution_check(&self, options: CheckOptions) -> Result<()> {
+        self.node.start_distribution_check(options).await
+    }
+
+    /// Aborts the running distribution check, returning whether there was one.
+    pub(crate) async fn abort_distribution_check(&self) -> bool {
+        self.node.distribution_check.abort().await
+    }
+
+    /// The report of the running distribution check, or of the last one.
+    pub(crate) async fn distribution_report(&self) -> Option<DistributionReport> {
+        self.node.distribution_check.report().await
+    }
+
+    /// Starts replicating the chunks of `target` to their holders missing them, as an elder,
+    /// returning the id of the job doing it. Its copies are made in the background, the
+    /// progress is read with `replication_progress`.
+    pub(crate) async fn start_replication_job(&self, target: ReplicationTarget) -> Result<u64> {
+        self.node.start_replication_job(target).await
+    }
+
+    /// The progress of the replication job `id`, if there's one.
+    pub(crate) async fn replication_progress(&self, id: u64) -> Option<JobProgress> {
+        self.node.replication_jobs.progress(id).await
+    }
+
+    /// Whether the node can serve client reads and writes, as per its own state, its
+    /// background tasks and how its runtime keeps up.
+    pub(crate) async fn readiness(&self) -> Readiness {
+        let mut conditions = self.node.readiness_conditions().await;
+        if self.supervisor.has_failed_task() {
+            let _new = conditions.insert(Condition::BackgroundTaskFailed);
+        }
+        if self.event_loop_lag() > MAX_EVENT_LOOP_LAG {
+            let _new = conditions.insert(Condition::EventLoopLag);
+        }
+        Readiness { conditions }
+    }
+
+    pub(crate) fn event_loop_lag(&self) -> Duration {
+        Duration::from_millis(self.event_loop_lag_ms.load(Ordering::Relaxed))
+    }
+
+    pub(super) async fn probe_event_loop_periodically(self: Arc<Self>) {
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "event_loop_probe",
+                RestartPolicy::default_backoff(),
+                move || self.clone().probe_event_loop(),
+            )
+            .await;
+    }
+
+    // Measures how late the probe's ticks fire, which they do when the runtime is too busy to
+    // get to them in time.
+    async fn probe_event_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(EVENT_LOOP_PROBE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let scheduled = interval.tick().await;
+            let lag = scheduled.elapsed().as_millis() as u64;
+            self.event_loop_lag_ms.store(lag, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) async fn record_membership_periodically(self: Arc<Self>) {
+        info!("Starting membership snapshots");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "membership_snapshots",
+                RestartPolicy::default_backoff(),
+                move || self.clone().record_membership(),
+            )
+            .await;
+    }
+
+    async fn record_membership(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(MEMBERSHIP_SNAPSHOT_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+            self.node.record_membership_snapshot().await;
+        }
+    }
+
+    pub(super) async fn watch_control_requests(self: Arc<Self>, root_dir: PathBuf) {
+        info!("Watching for operator requests in {:?}", root_dir);
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "control_requests",
+                RestartPolicy::default_backoff(),
+                move || self.clone().handle_control_requests(root_dir.clone()),
+            )
+            .await;
+    }
+
+    async fn handle_control_requests(self: Arc<Self>, root_dir: PathBuf) {
+        let mut interval = tokio::time::interval(CONTROL_POLL_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            let request = match control::take_request(&root_dir) {
+                Ok(Some(request)) => request,
+                Ok(None) => continue,
+                Err(error) => {
+                    error!("Error reading operator request: {error}");
+                    continue;
+                }
+            };
+            info!("Operator requested: {:?}", request);
+            if let Err(error) = self
+                .clone()
+                .prepare_restart(request.within, request.drain)
+                .await
+            {
+                error!("Error preparing to restart: {error}");
+            }
+        }
+    }
+
+    /// Changes our max capacity, see `Node::set_capacity`, re-attesting our config with it.
+    pub(super) async fn set_capacity(self: Arc<Self>, max_capacity: usize) -> Result<()> {
+        for cmd in self.node.set_capacity(max_capacity).await {
+            self.clone()
+                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                .await?;
+        }
+        self.reattest().await
+    }
+
+    /// Attests `config` as the one we run with, writing the record to `root_dir`, and
+    /// re-attesting it whenever it changes at runtime from now on.
+    pub(super) async fn attest(&self, config: Config, root_dir: PathBuf) -> Result<Attestation> {
+        let attestation = self.attestation_of(&config).await?;
+        attestation::write(&root_dir, &attestation)?;
+        info!("Attested our config, digest: {}", attestation.digest);
+        *self.attestation.write().await = Some((config, root_dir, attestation.clone()));
+        Ok(attestation)
+    }
+
+    /// The latest attestation of our config, if we attested it yet.
+    pub(crate) async fn attestation(&self) -> Option<Attestation> {
+        self.attestation
+            .read()
+            .await
+            .as_ref()
+            .map(|(_, _, attestation)| attestation.clone())
+    }
+
+    pub(super) async fn set_log_filter_reloader(&self, reloader: LogFilterReloader) {
+        *self.log_filter_reloader.write().await = Some(reloader);
+    }
+
+    /// Replaces the filter of our logs with `filter`, in `RUST_LOG`'s syntax.
+    pub(super) async fn set_log_filter(&self, filter: &str) -> Result<()> {
+        match &*self.log_filter_reloader.read().await {
+            Some(reload) => {
+                reload(filter).map_err(Error::Configuration)?;
+                info!("Log filter changed to {:?}", filter);
+                Ok(())
+            }
+            None => Err(Error::Configuration(
+                "Our logs' filter can't be changed at runtime".to_string(),
+            )),
+        }
+    }
+
+    // Attests our config again, after a change at runtime.
+    async fn reattest(&self) -> Result<()> {
+        let attested = self.attestation.read().await.clone();
+        match attested {
+            Some((config, root_dir, _)) => self.attest(config, root_dir).await.map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    async fn attestation_of(&self, config: &Config) -> Result<Attestation> {
+        let max_capacity = self.node.data_storage.used_space().max_capacity();
+        let keypair = self.node.info.read().await.keypair.clone();
+        let genesis_key = *self.node.network_knowledge().genesis_key();
+        Ok(Attestation::new(
+            &keypair,
+            config.attested(max_capacity)?,
+            &genesis_key,
+        ))
+    }
+
+    /// Gets the node ready to restart within `within`, see `Node::prepare_restart`.
+    pub(super) async fn prepare_restart(
+        self: Arc<Self>,
+        within: Duration,
+        drain: bool,
+    ) -> Result<()> {
+        for cmd in self.node.prepare_restart(within, drain).await? {
+            self.clone()
+                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Shuts us down: stops taking client msgs, waits up to `drain_timeout` for the cmds in
+    /// flight to be handled, then flushes our dbs and tells our section we're going offline,
+    /// waiting for that to be sent too, unless we're restarting within the window we announced.
+    /// Either way our state is snapshotted for our next start. Our event stream ends once we're
+    /// done, even if it fails.
+    pub(super) async fn shut_down(self: Arc<Self>, drain_timeout: Duration) -> Result<()> {
+        if !self.shutdown.begin() {
+            shutdown::shut_down(self.shutdown.subscribe()).await;
+            return Ok(());
+        }
+
+        info!("Shutting down, no longer taking client msgs");
+        self.wait_for_cmds_handled(drain_timeout).await;
+        let result = if self.node.is_restart_pending().await {
+            // Our elders keep us on as a member until our restart window runs out, for us to
+            // resume from our state snapshot meanwhile.
+            info!("Shutting down for the restart we announced, staying in our section");
+            self.node.prepare_resuming().await
+        } else {
+            match self.node.prepare_leaving().await {
+                Ok(cmds) => {
+                    for cmd in cmds {
+                        self.clone()
+                            .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                            .await?;
                     }
+                    self.wait_for_cmds_handled(drain_timeout).await;
+                    Ok(())
+                }
+                Err(error) => {
+                    error!("Failed to prepare leaving our section: {:?}", error);
+                    Err(error)
+                }
+            }
+        };
+        if let Err(error) = self.node.write_state_snapshot().await {
+            error!("Failed to snapshot our state: {:?}", error);
+        }
+
+        self.shutdown.finish();
+        info!("Shut down");
+        result
+    }
+
+    // Waits, up to `timeout`, for no cmd or client msg to be left queued or being handled.
+    async fn wait_for_cmds_handled(&self, timeout: Duration) {
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            let pending = self.pending_cmds().await;
+            if pending == 0 {
+                return;
+            }
+            if time::Instant::now() >= deadline {
+                warn!(
+                    "Shutting down with {} cmds still pending after {:?}",
+                    pending, timeout
+                );
+                return;
+            }
+            time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    // Cmds and client msgs queued or being handled.
+    async fn pending_cmds(&self) -> u64 {
+        let cmds = self
+            .supervisor
+            .task_counts()
+            .get("cmd_handling")
+            .map_or(0, |counts| counts.spawned.saturating_sub(counts.completed));
+        let client_msgs: usize = self
+            .client_scheduler
+            .stats()
+            .await
+            .iter()
+            .map(|stats| stats.queued + stats.in_flight)
+            .sum();
+        cmds + client_msgs as u64
+    }
+
+    pub(super) async fn check_for_dysfunction_periodically(self: Arc<Self>) {
+        info!("Starting dysfunction checking");
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "dysfunction_checks",
+                RestartPolicy::default_backoff(),
+                move || self.clone().check_for_dysfunction(),
+            )
+            .await;
+    }
+
+    async fn check_for_dysfunction(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval = tokio::time::interval(DYSFUNCTION_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let _instant = interval.tick().await;
+
+            dispatcher.node.expire_restart_windows().await;
+
+            let unresponsive_nodes = match dispatcher.node.get_dysfunctional_node_names().await {
+                Ok(nodes) => nodes,
+                Err(error) => {
+                    error!("Error getting dysfunctional nodes: {error}");
+                    BTreeSet::default()
+                }
+            };
+
+            if !unresponsive_nodes.is_empty() {
+                debug!("{:?} : {unresponsive_nodes:?}", LogMarker::ProposeOffline);
+                let cmd = Cmd::ProposeOffline(unresponsive_nodes);
+                if let Err(e) = dispatcher
+                    .clone()
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .await
+                {
+                    error!("Error sending Propose Offline for dysfunctional nodes: {e:?}");
                 }
+            }
 
-                match dispatcher.node.notify_about_newly_suspect_nodes().await {
-                    Ok(suspect_cmds) => {
-                        for cmd in suspect_cmds {
-                            if let Err(e) = dispatcher
-                                .clone()
-                                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
-                                .await
-                            {
-                                error!("Error processing suspect node cmds: {:?}", e);
-                            }
+            match dispatcher.node.notify_about_newly_suspect_nodes().await {
+                Ok(suspect_cmds) => {
+                    for cmd in suspect_cmds {
+                        if let Err(e) = dispatcher
+                            .clone()
+                            .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                            .await
+                        {
+                            error!("Error processing suspect node cmds: {:?}", e);
                         }
                     }
-                    Err(error) => {
-                        error!("Error getting suspect nodes: {error}");
-                    }
-                };
-            }
-        });
+                }
+                Err(error) => {
+                    error!("Error getting suspect nodes: {error}");
+                }
+            };
+        }
     }
 
     #[cfg(feature = "back-pressure")]
@@ -227,68 +1071,79 @@ impl Dispatcher {
     /// not be overloaded...
     pub(super) async fn report_backpressure_to_our_section_periodically(self: Arc<Self>) {
         info!("Firing off backpressure reports");
-        let _handle = tokio::spawn(async move {
-            let dispatcher = self.clone();
-            let mut interval = tokio::time::interval(BACKPRESSURE_INTERVAL);
-            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-            let _ = interval.tick().await;
+        let supervisor = self.supervisor.clone();
+        supervisor
+            .spawn(
+                "backpressure_reports",
+                RestartPolicy::default_backoff(),
+                move || self.clone().report_backpressure(),
+            )
+            .await;
+    }
 
-            loop {
-                let _ = interval.tick().await;
+    #[cfg(feature = "back-pressure")]
+    async fn report_backpressure(self: Arc<Self>) {
+        let dispatcher = self;
+        let mut interval =
+            time::interval(dispatcher.node.comm.back_pressure_report_interval().await);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let _ = interval.tick().await;
 
-                let members = dispatcher.node.network_knowledge().section_members().await;
-                let section_pk = dispatcher.node.network_knowledge().section_key().await;
+        loop {
+            let _ = interval.tick().await;
 
-                if let Some(load_report) = dispatcher.node.comm.tolerated_msgs_per_s().await {
-                    trace!("New BackPressure report to disseminate: {:?}", load_report);
+            let members = dispatcher.node.network_knowledge().section_members().await;
+            let section_pk = dispatcher.node.network_knowledge().section_key().await;
 
-                    // TODO: use comms to send report to anyone connected? (can we ID end users there?)
-                    for member in members {
-                        let our_name = dispatcher.node.info.read().await.name();
-                        let peer = member.peer();
+            if let Some(load_report) = dispatcher.node.comm.tolerated_msgs_per_s().await {
+                trace!("New BackPressure report to disseminate: {:?}", load_report);
 
-                        if peer.name() == our_name {
-                            continue;
-                        }
+                // TODO: use comms to send report to anyone connected? (can we ID end users there?)
+                for member in members {
+                    let our_name = dispatcher.node.info.read().await.name();
+                    let peer = member.peer();
 
-                        let wire_msg = match WireMsg::single_src(
-                            &*dispatcher.node.info.read().await,
-                            DstLocation::Node {
-                                name: peer.name(),
-                                section_pk,
-                            },
-                            SystemMsg::BackPressure(load_report),
-                            section_pk,
-                        ) {
-                            Ok(msg) => msg,
-                            Err(e) => {
-                                error!(
-                                    "Error forming backpressure message to section member {:?}",
-                                    e
-                                );
-                                continue;
-                            }
-                        };
-
-                        let cmd = Cmd::SendMsg {
-                            wire_msg,
-                            recipients: vec![*peer],
-                        };
+                    if peer.name() == our_name {
+                        continue;
+                    }
 
-                        if let Err(e) = dispatcher
-                            .clone()
-                            .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
-                            .await
-                        {
+                    let wire_msg = match WireMsg::single_src(
+                        &*dispatcher.node.info.read().await,
+                        DstLocation::Node {
+                            name: peer.name(),
+                            section_pk,
+                        },
+                        SystemMsg::BackPressure(load_report),
+                        section_pk,
+                    ) {
+                        Ok(msg) => msg,
+                        Err(e) => {
                             error!(
-                                "Error sending backpressure report to section member {:?}: {:?}",
-                                peer, e
+                                "Error forming backpressure message to section member {:?}",
+                                e
                             );
+                            continue;
                         }
+                    };
+
+                    let cmd = Cmd::SendMsg {
+                        wire_msg,
+                        recipients: vec![*peer],
+                    };
+
+                    if let Err(e) = dispatcher
+                        .clone()
+                        .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                        .await
+                    {
+                        error!(
+                            "Error sending backpressure report to section member {:?}: {:?}",
+                            peer, e
+                        );
                     }
                 }
             }
-        });
+        }
     }
 
     pub(super) async fn write_prefixmap_to_disk(self: Arc<Self>) {
@@ -297,66 +1152,79 @@ impl Dispatcher {
     }
 
     /// Handles a single cmd.
+    #[cfg(test)]
     pub(super) async fn process_cmd(&self, cmd: Cmd, cmd_id: &str) -> Result<Vec<Cmd>> {
-        // Create a tracing span containing info about the current node. This is very useful when
-        // analyzing logs produced by running multiple nodes within the same process, for example
-        // from integration tests.
-        let span = {
-            let node = &self.node;
-
-            let prefix = node.network_knowledge().prefix().await;
-            let is_elder = node.is_elder().await;
-            let section_key = node.network_knowledge().section_key().await;
-            let age = node.info.read().await.age();
-            trace_span!(
-                "process_cmd",
-                name = %node.info.read().await.name(),
-                prefix = format_args!("({:b})", prefix),
-                age,
-                elder = is_elder,
-                cmd_id = %cmd_id,
-                section_key = ?section_key,
-                %cmd,
-            )
+        let span = self.cmd_span(&cmd, cmd_id).await;
+        self.process_cmd_in_current_span(cmd, cmd_id)
+            .instrument(span)
+            .await
+    }
+
+    // Create a tracing span containing info about the current node. This is very useful when
+    // analyzing logs produced by running multiple nodes within the same process, for example
+    // from integration tests.
+    async fn cmd_span(&self, cmd: &Cmd, cmd_id: &str) -> Span {
+        let node = &self.node;
+
+        let prefix = node.network_knowledge().prefix().await;
+        let is_elder = node.is_elder().await;
+        let section_key = node.network_knowledge().section_key().await;
+        let age = node.info.read().await.age();
+        trace_span!(
+            "process_cmd",
+            name = %node.info.read().await.name(),
+            prefix = format_args!("({:b})", prefix),
+            age,
+            elder = is_elder,
+            trace_id = trace_id(cmd_id),
+            cmd_id = %cmd_id,
+            section_key = ?section_key,
+            %cmd,
+        )
+    }
+
+    async fn process_cmd_in_current_span(&self, cmd: Cmd, cmd_id: &str) -> Result<Vec<Cmd>> {
+        let cmd_display = cmd.to_string();
+        trace!(
+            "{:?} {:?} - {}",
+            LogMarker::CmdProcessStart,
+            cmd_id,
+            cmd_display
+        );
+
+        #[cfg(feature = "metrics")]
+        let (kind, started) = (cmd.kind(), std::time::Instant::now());
+
+        let res = match self.try_processing_cmd(cmd).await {
+            Ok(outcome) => {
+                trace!(
+                    "{:?} {:?} - {}",
+                    LogMarker::CmdProcessEnd,
+                    cmd_id,
+                    cmd_display
+                );
+                Ok(outcome)
+            }
+            Err(error) => {
+                error!(
+                    "Error encountered when processing cmd (cmd_id {}): {:?}",
+                    cmd_id, error
+                );
+                trace!(
+                    "{:?} {}: {:?}",
+                    LogMarker::CmdProcessingError,
+                    cmd_display,
+                    error
+                );
+                Err(error)
+            }
         };
 
-        async {
-            let cmd_display = cmd.to_string();
-            trace!(
-                "{:?} {:?} - {}",
-                LogMarker::CmdProcessStart,
-                cmd_id,
-                cmd_display
-            );
-
-            let res = match self.try_processing_cmd(cmd).await {
-                Ok(outcome) => {
-                    trace!(
-                        "{:?} {:?} - {}",
-                        LogMarker::CmdProcessEnd,
-                        cmd_id,
-                        cmd_display
-                    );
-                    Ok(outcome)
-                }
-                Err(error) => {
-                    error!(
-                        "Error encountered when processing cmd (cmd_id {}): {:?}",
-                        cmd_id, error
-                    );
-                    trace!(
-                        "{:?} {}: {:?}",
-                        LogMarker::CmdProcessingError,
-                        cmd_display,
-                        error
-                    );
-                    Err(error)
-                }
-            };
-            res
-        }
-        .instrument(span)
-        .await
+        #[cfg(feature = "metrics")]
+        self.metrics
+            .record_cmd(kind, started.elapsed(), res.is_ok());
+
+        res
     }
 
     /// Actually process the cmd
@@ -466,6 +1334,13 @@ impl Dispatcher {
                         .await
                         .is_err()
                     {
+                        self.node
+                            .comm
+                            .report_misbehaviour(
+                                member_info.peer(),
+                                Misbehaviour::FailedLivenessProbe,
+                            )
+                            .await;
                         self.node.log_comm_issue(member_info.name()).await?
                     }
                 }
@@ -474,16 +1349,43 @@ impl Dispatcher {
         }
     }
 
+    // Sends a msg, queueing it to be sent again if it fails to reach enough recipients.
     async fn send_msg(
         &self,
         recipients: &[Peer],
         delivery_group_size: usize,
         wire_msg: WireMsg,
     ) -> Result<Vec<Cmd>> {
-        let cmds = match wire_msg.msg_kind() {
+        let (cmds, undelivered) = self
+            .try_send_msg(recipients, delivery_group_size, wire_msg.clone())
+            .await?;
+        if let Some((recipients, delivery_group_size)) = undelivered {
+            self.node
+                .retry_queue
+                .push(recipients, delivery_group_size, &wire_msg)
+                .await?;
+        }
+        Ok(cmds)
+    }
+
+    // Sends a msg, returning the cmds following from it, and which recipients it failed to
+    // reach along with how many of them it still has to, if it fell short.
+    async fn try_send_msg(
+        &self,
+        recipients: &[Peer],
+        delivery_group_size: usize,
+        wire_msg: WireMsg,
+    ) -> Result<(Vec<Cmd>, Option<(Vec<Peer>, usize)>)> {
+        let sent = match wire_msg.msg_kind() {
             AuthKind::Node(_) | AuthKind::NodeBlsShare(_) => {
-                self.deliver_msgs(recipients, delivery_group_size, wire_msg)
-                    .await?
+                let (relayed_to, wire_msg) = self.node.via_our_relay(recipients, wire_msg).await?;
+                if relayed_to == recipients {
+                    self.deliver_msgs(recipients, delivery_group_size, wire_msg)
+                        .await?
+                } else {
+                    // Our relay sends the msg on to each recipient itself.
+                    self.deliver_msgs(&relayed_to, 1, wire_msg).await?
+                }
             }
             AuthKind::Service(_) => {
                 // we should never be sending such a msg to more than one recipient
@@ -492,6 +1394,7 @@ impl Dispatcher {
                     warn!("Unexpected number of client recipients {:?} for msg {:?}. Only sending to first.",
                     recipients.len(), wire_msg);
                 }
+                let mut undelivered = None;
                 if let Some(recipient) = recipients.get(0) {
                     if let Err(err) = self
                         .node
@@ -503,14 +1406,15 @@ impl Dispatcher {
                             "Failed sending message {:?} to client {:?} with error {:?}",
                             wire_msg, recipient, err
                         );
+                        undelivered = Some((vec![*recipient], 1));
                     }
                 }
 
-                vec![]
+                (vec![], undelivered)
             }
         };
 
-        Ok(cmds)
+        Ok(sent)
     }
 
     async fn send_throttled_batch_msgs(
@@ -545,7 +1449,7 @@ impl Dispatcher {
         recipients: &[Peer],
         delivery_group_size: usize,
         wire_msg: WireMsg,
-    ) -> Result<Vec<Cmd>> {
+    ) -> Result<(Vec<Cmd>, Option<(Vec<Peer>, usize)>)> {
         let status = self
             .node
             .comm
@@ -553,14 +1457,27 @@ impl Dispatcher {
             .await?;
 
         match status {
-            DeliveryStatus::MinDeliveryGroupSizeReached(failed_recipients)
-            | DeliveryStatus::MinDeliveryGroupSizeFailed(failed_recipients) => {
-                Ok(failed_recipients
+            DeliveryStatus::MinDeliveryGroupSizeReached(failed_recipients) => Ok((
+                failed_recipients
                     .into_iter()
                     .map(Cmd::HandlePeerLost)
-                    .collect())
+                    .collect(),
+                None,
+            )),
+            DeliveryStatus::MinDeliveryGroupSizeFailed(failed_recipients) => {
+                // Every recipient was tried, those which didn't fail having received it.
+                let delivered = recipients.len().saturating_sub(failed_recipients.len());
+                let missing = delivery_group_size
+                    .min(recipients.len())
+                    .saturating_sub(delivered);
+                let cmds = failed_recipients
+                    .iter()
+                    .copied()
+                    .map(Cmd::HandlePeerLost)
+                    .collect();
+                Ok((cmds, (missing > 0).then_some((failed_recipients, missing))))
             }
-            _ => Ok(vec![]),
+            DeliveryStatus::AllRecipients => Ok((vec![], None)),
         }
     }
 
@@ -124,6 +124,27 @@ pub enum Event {
         /// DstLocation for the message
         dst_location: DstLocation,
     },
+    /// Our storage started failing its periodic disk self-test, or recovered. While unhealthy,
+    /// we don't take on new chunks, but keep serving the ones we hold.
+    StorageHealthChanged {
+        /// Whether our storage is now healthy.
+        healthy: bool,
+    },
+    /// Chunks are close to running into the disk space reserved for our dbs. Once they reach
+    /// it, new chunks are refused so the dbs can keep growing.
+    DbReserveApproached {
+        /// Bytes of chunks we can still store.
+        chunk_headroom: usize,
+    },
+    /// Our max capacity was changed at runtime, and now applies. Raised after every change,
+    /// even to the same capacity. Beyond a lowered capacity, what's stored is kept but no new
+    /// data is taken.
+    CapacityChanged {
+        /// Bytes we can now store at most.
+        max_capacity: usize,
+        /// Bytes we store.
+        used: usize,
+    },
     /// Notify the current list of adult nodes, in case of churning.
     AdultsChanged {
         /// Remaining Adults in our section.
@@ -135,6 +156,66 @@ pub enum Event {
     },
 }
 
+/// A decision on our section's membership, or on our own place in it, as raised to every
+/// subscriber of `NodeApi::events()`.
+///
+/// Unlike `Event`s, these are broadcast: any number of subscribers each get all those raised
+/// after they subscribed, as long as they keep up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MembershipEvent {
+    /// This node was promoted to elder of its section.
+    BecameElder {
+        /// The prefix of our section.
+        prefix: Prefix,
+        /// The section key we're elder under.
+        section_key: BlsPublicKey,
+    },
+    /// This node was demoted from elder to adult.
+    StoppedBeingElder {
+        /// The prefix of our section.
+        prefix: Prefix,
+    },
+    /// This node completed its relocation to another section.
+    Relocated {
+        /// Our name before the relocation.
+        previous_name: XorName,
+        /// Our name after it.
+        new_name: XorName,
+    },
+    /// Our section split, this node being elder of the half it ended up in.
+    SplitOccurred {
+        /// The prefix of our section before the split.
+        previous_prefix: Prefix,
+        /// The prefix of our section after it.
+        prefix: Prefix,
+        /// The key of our section after it.
+        section_key: BlsPublicKey,
+    },
+    /// Our section started or stopped allowing new nodes to join.
+    JoinsAllowedChanged {
+        /// Whether joins are now allowed.
+        allowed: bool,
+    },
+    /// A peer joined our section.
+    PeerJoined {
+        /// Name of the peer.
+        name: XorName,
+        /// Its previous name if it was relocated to us, `None` if it is a new node.
+        previous_name: Option<XorName>,
+        /// Age of the peer.
+        age: u8,
+    },
+    /// A peer left our section.
+    PeerLeft {
+        /// Name of the peer.
+        name: XorName,
+        /// Age of the peer.
+        age: u8,
+        /// Whether it left to be relocated to another section.
+        relocated: bool,
+    },
+}
+
 /// Type of messages that are received from a peer
 #[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
@@ -6,22 +6,78 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Event;
-use tokio::sync::mpsc;
+use super::{
+    event::MembershipEvent,
+    shutdown::{self, ShutdownState},
+    Event,
+};
+use tokio::sync::{broadcast, mpsc, watch};
 
-/// Stream of routing node events
+/// Stream of routing node events, which ends once the node is shut down.
 #[allow(missing_debug_implementations)]
 pub struct EventStream {
     events_rx: mpsc::Receiver<Event>,
+    shutdown: watch::Receiver<ShutdownState>,
 }
 
 impl EventStream {
-    pub(crate) fn new(events_rx: mpsc::Receiver<Event>) -> Self {
-        Self { events_rx }
+    pub(crate) fn new(
+        events_rx: mpsc::Receiver<Event>,
+        shutdown: watch::Receiver<ShutdownState>,
+    ) -> Self {
+        Self {
+            events_rx,
+            shutdown,
+        }
     }
 
     /// Returns next event
     pub async fn next(&mut self) -> Option<Event> {
-        self.events_rx.recv().await
+        tokio::select! {
+            event = self.events_rx.recv() => event,
+            () = shutdown::shut_down(self.shutdown.clone()) => None,
+        }
+    }
+}
+
+/// Subscription to the membership events of a node, which ends once the node is shut down.
+///
+/// A subscriber falling more than `MEMBERSHIP_EVENT_CHANNEL_SIZE` events behind misses the
+/// oldest of them, which is logged.
+#[allow(missing_debug_implementations)]
+pub struct MembershipEvents {
+    events_rx: broadcast::Receiver<MembershipEvent>,
+    shutdown: watch::Receiver<ShutdownState>,
+}
+
+impl MembershipEvents {
+    pub(crate) fn new(
+        events_rx: broadcast::Receiver<MembershipEvent>,
+        shutdown: watch::Receiver<ShutdownState>,
+    ) -> Self {
+        Self {
+            events_rx,
+            shutdown,
+        }
+    }
+
+    /// Returns next event
+    pub async fn next(&mut self) -> Option<MembershipEvent> {
+        loop {
+            let event = tokio::select! {
+                event = self.events_rx.recv() => event,
+                () = shutdown::shut_down(self.shutdown.clone()) => return None,
+            };
+            match event {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(
+                        "Membership events subscriber lagged, missing {} events",
+                        missed
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
     }
 }
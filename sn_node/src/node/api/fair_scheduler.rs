@@ -0,0 +1,420 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, Notify, Semaphore},
+    time::Instant,
+};
+
+/// Client msgs, across all clients, being processed at once at most.
+pub(crate) const CLIENT_MSG_CONCURRENCY: usize = 64;
+/// Msgs of a single client waiting to be processed at most. Any more are refused as busy.
+pub(crate) const CLIENT_QUEUE_CAP: usize = 128;
+// How long the stats of a client with nothing queued or in flight are kept.
+const IDLE_CLIENT_RETENTION: Duration = Duration::from_secs(300);
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+type WeightFn<K> = Arc<dyn Fn(&K) -> usize + Send + Sync>;
+
+/// The client already has as many msgs queued as it is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct QueueFull;
+
+/// Queue depth and service rate of the msgs of a single client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientQueueStats<K> {
+    /// The client.
+    pub client: K,
+    /// Msgs waiting to be processed.
+    pub queued: usize,
+    /// Msgs being processed.
+    pub in_flight: usize,
+    /// Msgs processed, or being processed, so far.
+    pub served: u64,
+    /// Msgs refused because the client's queue was full.
+    pub rejected: u64,
+    /// Msgs served per second, since the client was first seen.
+    pub service_rate: f64,
+}
+
+struct ClientQueue {
+    jobs: VecDeque<Job>,
+    // Jobs left for the client in its current round-robin turn.
+    credit: usize,
+    in_flight: usize,
+    served: u64,
+    rejected: u64,
+    first_seen: Instant,
+    last_active: Instant,
+}
+
+impl ClientQueue {
+    fn new(now: Instant) -> Self {
+        Self {
+            jobs: VecDeque::new(),
+            credit: 0,
+            in_flight: 0,
+            served: 0,
+            rejected: 0,
+            first_seen: now,
+            last_active: now,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.jobs.is_empty() && self.in_flight == 0
+    }
+}
+
+struct State<K> {
+    clients: BTreeMap<K, ClientQueue>,
+    // Clients with queued jobs, in round-robin order. The front one is taking its turn.
+    ring: VecDeque<K>,
+}
+
+/// Schedules jobs of many clients fairly: each client gets its own bounded queue, and the queues
+/// are drained by weighted round-robin, with a limit to the number of jobs running at once.
+///
+/// A lone active client gets its jobs run as soon as they are submitted.
+#[derive(Clone)]
+pub(crate) struct FairScheduler<K> {
+    state: Arc<Mutex<State<K>>>,
+    job_queued: Arc<Notify>,
+    permits: Arc<Semaphore>,
+    queue_cap: usize,
+    weight: WeightFn<K>,
+}
+
+impl<K> FairScheduler<K>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new(concurrency: usize, queue_cap: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                clients: BTreeMap::new(),
+                ring: VecDeque::new(),
+            })),
+            job_queued: Arc::new(Notify::new()),
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            queue_cap: queue_cap.max(1),
+            weight: Arc::new(|_| 1),
+        }
+    }
+
+    /// Sets the number of jobs each client gets run per round-robin turn, e.g. as per its quota.
+    /// Every client has a weight of 1 by default.
+    // Not used by the node until client quotas are tracked.
+    #[allow(dead_code)]
+    pub(crate) fn with_weights(
+        mut self,
+        weight: impl Fn(&K) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.weight = Arc::new(weight);
+        self
+    }
+
+    /// Queues the job of a client, unless its queue is full.
+    pub(crate) async fn submit<F>(&self, client: K, job: F) -> Result<(), QueueFull>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+
+        if !state.clients.contains_key(&client) {
+            state.clients.retain(|_, queue| {
+                !queue.is_idle() || now.duration_since(queue.last_active) < IDLE_CLIENT_RETENTION
+            });
+        }
+        let queue = state
+            .clients
+            .entry(client.clone())
+            .or_insert_with(|| ClientQueue::new(now));
+
+        if queue.jobs.len() >= self.queue_cap {
+            queue.rejected += 1;
+            return Err(QueueFull);
+        }
+
+        queue.last_active = now;
+        queue.jobs.push_back(Box::pin(job));
+        if queue.jobs.len() == 1 {
+            queue.credit = (self.weight)(&client).max(1);
+            state.ring.push_back(client);
+        }
+        drop(state);
+
+        self.job_queued.notify_one();
+        Ok(())
+    }
+
+    /// Runs queued jobs as permits become available, forever.
+    pub(crate) async fn run(self) {
+        loop {
+            let permit = match self.permits.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            let (client, job) = loop {
+                if let Some(next) = self.next_job().await {
+                    break next;
+                }
+                self.job_queued.notified().await;
+            };
+
+            let state = self.state.clone();
+            let _handle = tokio::spawn(async move {
+                job.await;
+                drop(permit);
+                if let Some(queue) = state.lock().await.clients.get_mut(&client) {
+                    queue.in_flight -= 1;
+                    queue.last_active = Instant::now();
+                }
+            });
+        }
+    }
+
+    // Takes the next job of the client whose turn it is, passing the turn on once the client has
+    // used up its credit or has nothing else queued.
+    async fn next_job(&self) -> Option<(K, Job)> {
+        let mut guard = self.state.lock().await;
+        let state = &mut *guard;
+
+        let client = state.ring.front()?.clone();
+        let queue = state.clients.get_mut(&client)?;
+        let job = queue.jobs.pop_front()?;
+        queue.in_flight += 1;
+        queue.served += 1;
+        queue.credit = queue.credit.saturating_sub(1);
+
+        if queue.jobs.is_empty() {
+            let _client = state.ring.pop_front();
+        } else if queue.credit == 0 {
+            queue.credit = (self.weight)(&client).max(1);
+            state.ring.rotate_left(1);
+        }
+
+        Some((client, job))
+    }
+
+    /// Returns the queue depth and service rate of every client seen recently.
+    pub(crate) async fn stats(&self) -> Vec<ClientQueueStats<K>> {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .await
+            .clients
+            .iter()
+            .map(|(client, queue)| {
+                let elapsed = now.duration_since(queue.first_seen).as_secs_f64();
+                ClientQueueStats {
+                    client: client.clone(),
+                    queued: queue.jobs.len(),
+                    in_flight: queue.in_flight,
+                    served: queue.served,
+                    rejected: queue.rejected,
+                    service_rate: if elapsed > 0.0 {
+                        queue.served as f64 / elapsed
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::{eyre, Result};
+    use tokio::{
+        sync::{mpsc, oneshot},
+        time::sleep,
+    };
+
+    const JOB_TIME: Duration = Duration::from_millis(10);
+
+    // Submits `count` jobs of `client`, each taking `JOB_TIME`, which report the client and when
+    // they completed on `done`.
+    async fn submit_jobs(
+        scheduler: &FairScheduler<&'static str>,
+        key: &'static str,
+        client: &'static str,
+        count: usize,
+        done: &mpsc::UnboundedSender<(&'static str, Instant)>,
+    ) -> Result<()> {
+        for _ in 0..count {
+            let done = done.clone();
+            scheduler
+                .submit(key, async move {
+                    sleep(JOB_TIME).await;
+                    let _result = done.send((client, Instant::now()));
+                })
+                .await
+                .map_err(|_| eyre!("queue of {} full", key))?;
+        }
+        Ok(())
+    }
+
+    // One heavy client floods the scheduler before two light ones send a few msgs each. Returns
+    // when the last job of each client completed, relative to the start.
+    async fn heavy_and_light_load(fifo: bool) -> Result<BTreeMap<&'static str, Duration>> {
+        let scheduler = FairScheduler::new(2, 1000);
+        let _handle = tokio::spawn(scheduler.clone().run());
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+
+        let start = Instant::now();
+        // Putting everyone's jobs in a single queue gives plain FIFO, as a baseline.
+        let key = |client| if fifo { "all" } else { client };
+        submit_jobs(&scheduler, key("heavy"), "heavy", 100, &done_tx).await?;
+        submit_jobs(&scheduler, key("light_a"), "light_a", 5, &done_tx).await?;
+        submit_jobs(&scheduler, key("light_b"), "light_b", 5, &done_tx).await?;
+        drop(done_tx);
+
+        let mut completed = BTreeMap::new();
+        while let Some((client, at)) = done_rx.recv().await {
+            let _prev = completed.insert(client, at.duration_since(start));
+        }
+        Ok(completed)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn light_clients_are_not_starved_by_a_heavy_one() -> Result<()> {
+        let fair = heavy_and_light_load(false).await?;
+        let fifo = heavy_and_light_load(true).await?;
+
+        // Taking turns, the light clients are done within their first few turns...
+        for client in ["light_a", "light_b"] {
+            assert!(
+                fair[client] <= JOB_TIME * 10,
+                "{}: {:?}",
+                client,
+                fair[client]
+            );
+            // ...rather than after all of the heavy client's jobs.
+            assert!(
+                fifo[client] >= JOB_TIME * 50,
+                "{}: {:?}",
+                client,
+                fifo[client]
+            );
+        }
+
+        // And everything is done about as quickly as without the scheduling.
+        let makespan = |completed: &BTreeMap<_, Duration>| *completed.values().max().unwrap();
+        assert!(makespan(&fair) <= makespan(&fifo).mul_f64(1.05));
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lone_client_jobs_run_right_away() -> Result<()> {
+        let scheduler = FairScheduler::new(2, 10);
+        let _handle = tokio::spawn(scheduler.clone().run());
+
+        for _ in 0..3 {
+            let (tx, rx) = oneshot::channel();
+            let submitted = Instant::now();
+            scheduler
+                .submit("lone", async move {
+                    let _result = tx.send(Instant::now());
+                })
+                .await
+                .map_err(|_| eyre!("queue full"))?;
+            assert_eq!(rx.await?, submitted);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_queue_refuses_jobs_of_that_client_only() -> Result<()> {
+        let scheduler = FairScheduler::new(1, 2);
+        let _handle = tokio::spawn(scheduler.clone().run());
+
+        // Keep the only permit taken, so jobs stay queued.
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        scheduler
+            .submit("blocker", async move {
+                let _result = release_rx.await;
+            })
+            .await
+            .map_err(|_| eyre!("queue full"))?;
+        tokio::task::yield_now().await;
+
+        for _ in 0..2 {
+            assert_eq!(scheduler.submit("client", async {}).await, Ok(()));
+        }
+        assert_eq!(scheduler.submit("client", async {}).await, Err(QueueFull));
+        assert_eq!(scheduler.submit("other", async {}).await, Ok(()));
+
+        let stats = scheduler.stats().await;
+        let client = stats
+            .iter()
+            .find(|stats| stats.client == "client")
+            .ok_or_else(|| eyre!("no stats"))?;
+        assert_eq!((client.queued, client.rejected), (2, 1));
+
+        drop(release_tx);
+        sleep(Duration::from_millis(1)).await;
+        for stats in scheduler.stats().await {
+            assert_eq!((stats.queued, stats.in_flight), (0, 0));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clients_get_turns_as_per_their_weights() -> Result<()> {
+        let scheduler =
+            FairScheduler::new(1, 10).with_weights(|client| if *client == "a" { 2 } else { 1 });
+        let _handle = tokio::spawn(scheduler.clone().run());
+
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        scheduler
+            .submit("blocker", async move {
+                let _result = release_rx.await;
+            })
+            .await
+            .map_err(|_| eyre!("queue full"))?;
+        tokio::task::yield_now().await;
+
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        for client in ["a", "b"] {
+            for _ in 0..4 {
+                let done_tx = done_tx.clone();
+                scheduler
+                    .submit(client, async move {
+                        let _result = done_tx.send(client);
+                    })
+                    .await
+                    .map_err(|_| eyre!("queue full"))?;
+            }
+        }
+        drop(done_tx);
+        drop(release_tx);
+
+        let mut order = vec![];
+        while let Some(client) = done_rx.recv().await {
+            order.push(client);
+        }
+        assert_eq!(order, ["a", "a", "b", "a", "a", "b", "b", "b"]);
+
+        Ok(())
+    }
+}
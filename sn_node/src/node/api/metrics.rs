@@ -0,0 +1,772 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Prometheus metrics of the node, served in the text exposition format at `/metrics`, next to
+//! health checks, on the address given by `Config::metrics_addr`: `/health/read` and
+//! `/health/write` pass while the node can serve client reads and writes respectively, and
+//! `/health` while it can serve both. Each responds with the conditions it's derived from.
+//!
+//! Only cmd timings and received msg counts are recorded as the node goes. Everything else is
+//! read from the node's state when scraped, so an idle endpoint costs nothing.
+
+use super::{dispatcher::Dispatcher, supervisor::TaskStatus};
+#[cfg(feature = "back-pressure")]
+use crate::node::core::MsgClass;
+use crate::{
+    dbs::INTERACTIVE_LATENCY_PERCENTILE,
+    node::{core::ListenerKind, Error, Result},
+};
+
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::{Display, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+use tokio::task;
+
+/// Peers and clients labelled individually, at most, per metric. The rest are added up under
+/// the label "other", keeping the number of series bounded however many peers we talk to.
+pub(crate) const MAX_LABELLED_PEERS: usize = 32;
+
+const OTHER: &str = "other";
+const CMD_DURATION_BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4";
+
+#[derive(Default)]
+struct CmdTimings {
+    // Cumulative, as per `CMD_DURATION_BUCKETS`.
+    buckets: [u64; CMD_DURATION_BUCKETS.len()],
+    count: u64,
+    errors: u64,
+    seconds: f64,
+}
+
+/// Metrics recorded as the node processes cmds and receives msgs.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    cmds: Mutex<BTreeMap<&'static str, CmdTimings>>,
+    msgs_received: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    pub(crate) fn record_cmd(&self, kind: &'static str, elapsed: Duration, succeeded: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let mut cmds = self
+            .cmds
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let timings = cmds.entry(kind).or_default();
+        for (bucket, le) in timings.buckets.iter_mut().zip(CMD_DURATION_BUCKETS) {
+            if seconds <= le {
+                *bucket += 1;
+            }
+        }
+        timings.count += 1;
+        timings.seconds += seconds;
+        if !succeeded {
+            timings.errors += 1;
+        }
+    }
+
+    pub(crate) fn count_msg_received(&self, auth_kind: &'static str) {
+        let mut msgs = self
+            .msgs_received
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *msgs.entry(auth_kind).or_default() += 1;
+    }
+
+    fn render(&self, out: &mut Exposition) {
+        let cmds = self
+            .cmds
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        out.family(
+            "cmd_duration_seconds",
+            "histogram",
+            "Time taken to process cmds, by cmd kind.",
+        );
+        for (kind, timings) in cmds.iter() {
+            for (le, count) in CMD_DURATION_BUCKETS.iter().zip(timings.buckets) {
+                out.sample(
+                    "cmd_duration_seconds_bucket",
+                    &[("kind", kind), ("le", &le.to_string())],
+                    count,
+                );
+            }
+            out.sample(
+                "cmd_duration_seconds_bucket",
+                &[("kind", kind), ("le", "+Inf")],
+                timings.count,
+            );
+            out.sample(
+                "cmd_duration_seconds_sum",
+                &[("kind", kind)],
+                timings.seconds,
+            );
+            out.sample(
+                "cmd_duration_seconds_count",
+                &[("kind", kind)],
+                timings.count,
+            );
+        }
+
+        out.family(
+            "cmd_errors_total",
+            "counter",
+            "Cmds whose processing failed, by cmd kind.",
+        );
+        for (kind, timings) in cmds.iter() {
+            out.sample("cmd_errors_total", &[("kind", kind)], timings.errors);
+        }
+        drop(cmds);
+
+        out.family(
+            "msgs_received_total",
+            "counter",
+            "Msgs received from the network, by kind of authority.",
+        );
+        let msgs = self
+            .msgs_received
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (auth_kind, count) in msgs.iter() {
+            out.sample("msgs_received_total", &[("auth", auth_kind)], count);
+        }
+    }
+}
+
+/// Starts serving the node's metrics and health on `addr`, returning the address bound.
+pub(super) fn serve(dispatcher: &Arc<Dispatcher>, addr: SocketAddr) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let builder = Server::from_tcp(listener).map_err(|err| {
+        Error::Configuration(format!("Cannot serve metrics on {}: {}", addr, err))
+    })?;
+
+    // The endpoint mustn't keep a stopped node alive.
+    let dispatcher = Arc::downgrade(dispatcher);
+    let make_service = make_service_fn(move |_conn| {
+        let dispatcher = dispatcher.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| respond(dispatcher.clone(), req))) }
+    });
+
+    let server = builder.serve(make_service);
+    let local_addr = server.local_addr();
+    info!("Serving metrics on http://{}/metrics", local_addr);
+
+    let _handle = task::spawn(async move {
+        if let Err(err) = server.await {
+            error!("Metrics endpoint stopped: {:?}", err);
+        }
+    });
+
+    Ok(local_addr)
+}
+
+async fn respond(
+    dispatcher: Weak<Dispatcher>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let dispatcher = match dispatcher.upgrade() {
+        Some(dispatcher) => dispatcher,
+        None => {
+            return Ok(text(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "node stopped\n".into(),
+            ))
+        }
+    };
+
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => text(StatusCode::OK, render(&dispatcher).await),
+        (&Method::GET, "/health") => {
+            let readiness = dispatcher.readiness().await;
+            probe(readiness.is_ready(), readiness.report())
+        }
+        (&Method::GET, "/health/read") => {
+            let readiness = dispatcher.readiness().await;
+            probe(readiness.can_read(), readiness.report())
+        }
+        (&Method::GET, "/health/write") => {
+            let readiness = dispatcher.readiness().await;
+            probe(readiness.can_write(), readiness.report())
+        }
+        _ => text(StatusCode::NOT_FOUND, "not found\n".into()),
+    };
+    Ok(response)
+}
+
+// Health probes fail with the report, for them to be self-explanatory.
+fn probe(passed: bool, report: String) -> Response<Body> {
+    let status = if passed {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    text(status, report)
+}
+
+fn text(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    let _prev = response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_TEXT));
+    response
+}
+
+/// Renders all of the node's metrics.
+pub(super) async fn render(dispatcher: &Dispatcher) -> String {
+    let mut out = Exposition::default();
+    let node = &dispatcher.node;
+
+    out.gauge(
+        "is_elder",
+        "Whether the node is an elder.",
+        node.is_elder().await as u8,
+    );
+    out.gauge("age", "Age of the node.", node.info.read().await.age());
+    out.gauge(
+        "section_members",
+        "Members of the node's section.",
+        node.network_knowledge().section_members().await.len(),
+    );
+
+    out.gauge(
+        "holder_registry_rebuilt_percent",
+        "Share of the section's namespace the elder's registry of data holders covers.",
+        node.holder_registry.progress().await,
+    );
+
+    out.family(
+        "adult_index_rebuilds_total",
+        "counter",
+        "Times the index of adults by closeness, used to choose data holders, was rebuilt.",
+    );
+    out.sample(
+        "adult_index_rebuilds_total",
+        &[],
+        node.adult_index.read().await.rebuilds(),
+    );
+
+    let readiness = dispatcher.readiness().await;
+    out.gauge(
+        "ready",
+        "Whether the node can serve both client reads and writes.",
+        readiness.is_ready() as u8,
+    );
+    out.gauge(
+        "ready_read",
+        "Whether the node can serve client reads.",
+        readiness.can_read() as u8,
+    );
+    out.gauge(
+        "ready_write",
+        "Whether the node can serve client writes.",
+        readiness.can_write() as u8,
+    );
+    out.gauge(
+        "event_loop_lag_seconds",
+        "How late the node's runtime last ran a task due.",
+        dispatcher.event_loop_lag().as_secs_f64(),
+    );
+
+    dispatcher.metrics.render(&mut out);
+
+    // Client msg scheduling, at elders.
+    let clients = capped(
+        dispatcher
+            .client_scheduler
+            .stats()
+            .await
+            .into_iter()
+            .map(|stats| {
+                let counts = [
+                    stats.queued as f64,
+                    stats.in_flight as f64,
+                    stats.served as f64,
+                    stats.rejected as f64,
+                ];
+                (format!("{:x}", stats.client), counts)
+            })
+            .collect(),
+        |[queued, in_flight, served, _]| (queued + in_flight, *served),
+        |sum, counts| sum.iter_mut().zip(counts).for_each(|(sum, n)| *sum += n),
+    );
+    for (i, (name, kind, help)) in [
+        (
+            "client_msgs_queued",
+            "gauge",
+            "Client msgs waiting their turn.",
+        ),
+        (
+            "client_msgs_in_flight",
+            "gauge",
+            "Client msgs being processed.",
+        ),
+        (
+            "client_msgs_served_total",
+            "counter",
+            "Client msgs processed.",
+        ),
+        (
+            "client_msgs_rejected_total",
+            "counter",
+            "Client msgs dropped as the client's queue was full.",
+        ),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        out.family(name, kind, help);
+        for (client, counts) in &clients {
+            out.sample(name, &[("client", client)], counts[i]);
+        }
+    }
+
+    // Storage.
+    let storage = &node.data_storage;
+    let used_space = storage.used_space();
+    out.gauge("storage_used_bytes", "Bytes stored.", used_space.used());
+    out.gauge(
+        "storage_dbs_used_bytes",
+        "Bytes of db records (registers) stored.",
+        used_space.used_by_dbs(),
+    );
+    out.gauge(
+        "storage_capacity_bytes",
+        "Bytes the node may store, at most.",
+        used_space.max_capacity(),
+    );
+    out.gauge(
+        "storage_chunk_headroom_bytes",
+        "Bytes of chunks the node can still take, as per its capacity and free disk space.",
+        storage.chunk_headroom(),
+    );
+    out.gauge(
+        "storage_healthy",
+        "Whether the node's storage passes its disk self-test.",
+        storage.disk_health().is_healthy().await as u8,
+    );
+    let pacing = storage.read_pacing();
+    out.gauge(
+        "storage_background_reads_paused",
+        "Whether background chunk reads are paused, for client reads being slow.",
+        pacing.paused as u8,
+    );
+    out.gauge(
+        "storage_background_reads_waiting",
+        "Background chunk reads waiting their turn.",
+        pacing.waiting,
+    );
+    out.family(
+        "storage_background_reads_deferred_total",
+        "counter",
+        "Background chunk reads which had to wait their turn.",
+    );
+    out.sample(
+        "storage_background_reads_deferred_total",
+        &[],
+        pacing.deferred,
+    );
+    out.family(
+        "storage_background_reads_total",
+        "counter",
+        "Background chunk reads let through.",
+    );
+    out.sample("storage_background_reads_total", &[], pacing.admitted);
+    out.gauge(
+        "storage_background_read_bytes_available",
+        "Bytes background chunk reads may read right away, negative while paying off a large read.",
+        pacing.byte_tokens,
+    );
+    out.family(
+        "storage_interactive_read_latency_seconds",
+        "gauge",
+        "Latency of client chunk reads, at the percentile background reads are paused on.",
+    );
+    out.sample(
+        "storage_interactive_read_latency_seconds",
+        &[("quantile", &INTERACTIVE_LATENCY_PERCENTILE.to_string())],
+        pacing.interactive_latency.as_secs_f64(),
+    );
+
+    // Background tasks.
+    let tasks = dispatcher.supervisor.statuses().await;
+    out.family(
+        "background_task_up",
+        "gauge",
+        "Whether a background task is running.",
+    );
+    for task in &tasks {
+        let up = task.status == TaskStatus::Running;
+        out.sample("background_task_up", &[("task", task.name)], up as u8);
+    }
+    out.family(
+        "background_task_restarts_total",
+        "counter",
+        "Times a background task was restarted.",
+    );
+    for task in &tasks {
+        out.sample(
+            "background_task_restarts_total",
+            &[("task", task.name)],
+            task.restarts,
+        );
+    }
+
+    // Tasks by subsystem.
+    let task_counts = dispatcher.supervisor.task_counts();
+    out.family(
+        "tasks_spawned_total",
+        "counter",
+        "Tasks spawned by a subsystem.",
+    );
+    for (subsystem, counts) in &task_counts {
+        out.sample(
+            "tasks_spawned_total",
+            &[("subsystem", subsystem)],
+            counts.spawned,
+        );
+    }
+    out.family(
+        "tasks_completed_total",
+        "counter",
+        "Tasks of a subsystem which completed.",
+    );
+    for (subsystem, counts) in &task_counts {
+        out.sample(
+            "tasks_completed_total",
+            &[("subsystem", subsystem)],
+            counts.completed,
+        );
+    }
+    out.family(
+        "task_long_polls_total",
+        "counter",
+        "Polls of the tasks of a subsystem which blocked their executor thread.",
+    );
+    for (subsystem, counts) in &task_counts {
+        out.sample(
+            "task_long_polls_total",
+            &[("subsystem", subsystem)],
+            counts.long_polls,
+        );
+    }
+
+    // Peers.
+    let sessions = node.comm.peer_send_stats().await;
+    out.gauge("peer_sessions", "Peers with a session.", sessions.len());
+    let peers = capped(
+        sessions
+            .into_iter()
+            .map(|(peer, throughput, success_ratio)| {
+                (hex::encode(peer.name().0), [throughput, success_ratio, 1.0])
+            })
+            .collect(),
+        |[throughput, _, _]| (*throughput, 0.0),
+        |sum, stats| sum.iter_mut().zip(stats).for_each(|(sum, n)| *sum += n),
+    );
+    out.family(
+        "peer_send_msgs_per_second",
+        "gauge",
+        "Msgs sent to a peer per second, since its session started.",
+    );
+    for (peer, [throughput, _, _]) in &peers {
+        out.sample("peer_send_msgs_per_second", &[("peer", peer)], throughput);
+    }
+    out.family(
+        "peer_send_success_ratio",
+        "gauge",
+        "Ratio of msgs sent to a peer successfully, averaged over \"other\" peers.",
+    );
+    for (peer, [_, success_ratio, sessions]) in &peers {
+        out.sample(
+            "peer_send_success_ratio",
+            &[("peer", peer)],
+            success_ratio / sessions,
+        );
+    }
+
+    let invalid_saps = capped(
+        node.invalid_saps
+            .all()
+            .await
+            .into_iter()
+            .map(|(name, count)| (hex::encode(name.0), count as f64))
+            .collect(),
+        |count| (*count, 0.0),
+        |sum, count| *sum += count,
+    );
+    out.family(
+        "invalid_saps_total",
+        "counter",
+        "Invalid section authority providers received, by sender.",
+    );
+    for (peer, count) in &invalid_saps {
+        out.sample("invalid_saps_total", &[("peer", peer)], count);
+    }
+
+    let corrupted_chunks = capped(
+        node.transfer_checks
+            .corruptions()
+            .await
+            .into_iter()
+            .map(|(name, count)| (hex::encode(name.0), count as f64))
+            .collect(),
+        |count| (*count, 0.0),
+        |sum, count| *sum += count,
+    );
+    out.family(
+        "corrupted_chunks_received_total",
+        "counter",
+        "Chunks received from other nodes whose content didn't match their address, by sender.",
+    );
+    for (peer, count) in &corrupted_chunks {
+        out.sample("corrupted_chunks_received_total", &[("peer", peer)], count);
+    }
+
+    let totals = node.comm.bandwidth_totals();
+    for (name, help, bytes) in [
+        (
+            "received_bytes_total",
+            "Bytes of msgs received from all peers.",
+            totals.received,
+        ),
+        (
+            "sent_bytes_total",
+            "Bytes of msgs sent to all peers.",
+            totals.sent,
+        ),
+    ] {
+        out.family(name, "counter", help);
+        out.sample(name, &[], bytes);
+    }
+    let peer_bytes = capped(
+        node.comm
+            .peer_bandwidth()
+            .into_iter()
+            .map(|(name, bytes)| (hex::encode(name.0), [bytes.received, bytes.sent]))
+            .collect(),
+        |[received, sent]| received + sent,
+        |sum, bytes| sum.iter_mut().zip(bytes).for_each(|(sum, n)| *sum += n),
+    );
+    out.family(
+        "peer_received_bytes_total",
+        "counter",
+        "Bytes of msgs received from a peer, since we last had a session with it.",
+    );
+    for (peer, [received, _]) in &peer_bytes {
+        out.sample("peer_received_bytes_total", &[("peer", peer)], received);
+    }
+    out.family(
+        "peer_sent_bytes_total",
+        "counter",
+        "Bytes of msgs sent to a peer, since we last had a session with it.",
+    );
+    for (peer, [_, sent]) in &peer_bytes {
+        out.sample("peer_sent_bytes_total", &[("peer", peer)], sent);
+    }
+
+    // Listeners.
+    out.family(
+        "listener_open_conns",
+        "gauge",
+        "Connections open on a listener, by the kind of peers it's for.",
+    );
+    for (kind, conns) in node.comm.open_conns() {
+        out.sample(
+            "listener_open_conns",
+            &[("listener", ListenerKind::label(kind))],
+            conns,
+        );
+    }
+    out.family(
+        "listener_refused_conns_total",
+        "counter",
+        "Connections a listener refused, over its budget or accept rate.",
+    );
+    for (kind, conns) in node.comm.refused_conns() {
+        out.sample(
+            "listener_refused_conns_total",
+            &[("listener", ListenerKind::label(kind))],
+            conns,
+        );
+    }
+
+    #[cfg(feature = "back-pressure")]
+    out.gauge(
+        "load_msgs_per_second",
+        "Msgs per second the node is handling, which back pressure reports are based on.",
+        node.comm.load_msgs_per_s().await,
+    );
+    #[cfg(feature = "back-pressure")]
+    {
+        let (cpu, memory, disk_queue_depth) = node.comm.resource_usage().await;
+        out.gauge(
+            "process_cpu_share",
+            "CPU the node's process used, as a share of all cores, when last sampled.",
+            cpu,
+        );
+        out.gauge(
+            "process_memory_share",
+            "Memory resident for the node's process, as a share of the system's.",
+            memory,
+        );
+        if let Some(depth) = disk_queue_depth {
+            out.gauge(
+                "disk_queue_depth",
+                "I/Os in flight on the system's busiest disk, when last sampled.",
+                depth,
+            );
+        }
+    }
+    #[cfg(feature = "back-pressure")]
+    if let Some(load) = node.comm.client_load_msgs_per_s().await {
+        out.gauge(
+            "client_load_msgs_per_second",
+            "Msgs per second the node is handling from clients on their own listener.",
+            load,
+        );
+    }
+    #[cfg(feature = "back-pressure")]
+    {
+        out.family(
+            "tolerated_msgs_per_second",
+            "gauge",
+            "Msgs per second tolerated of each class of msgs, out of all the node can take.",
+        );
+        for (class, tolerated) in node.comm.tolerated_msgs_per_s_by_class().await {
+            out.sample(
+                "tolerated_msgs_per_second",
+                &[("class", MsgClass::label(class))],
+                tolerated,
+            );
+        }
+    }
+
+    out.text
+}
+
+// Keeps the `MAX_LABELLED_PEERS` entries ranking highest, merging the rest into one labelled
+// "other".
+fn capped<T, R: PartialOrd>(
+    mut entries: Vec<(String, T)>,
+    rank: impl Fn(&T) -> R,
+    merge: impl Fn(&mut T, T),
+) -> Vec<(String, T)> {
+    if entries.len() <= MAX_LABELLED_PEERS {
+        return entries;
+    }
+    entries.sort_by(|(_, a), (_, b)| {
+        rank(b)
+            .partial_cmp(&rank(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut rest = entries.split_off(MAX_LABELLED_PEERS).into_iter();
+    if let Some((_, mut other)) = rest.next() {
+        rest.for_each(|(_, value)| merge(&mut other, value));
+        entries.push((OTHER.to_string(), other));
+    }
+    entries
+}
+
+// Metrics in the Prometheus text exposition format.
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP sn_node_{} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE sn_node_{} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let _ = write!(self.text, "sn_node_{}", name);
+        if !labels.is_empty() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: impl Display) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+}
+
+fn escape(label_value: &str) -> String {
+    label_value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_beyond_the_cap_are_added_up_as_other() {
+        let entries = (0..MAX_LABELLED_PEERS + 10)
+            .map(|i| (format!("peer{}", i), i as f64))
+            .collect();
+        let capped = capped(entries, |count| *count, |sum, count| *sum += count);
+
+        assert_eq!(capped.len(), MAX_LABELLED_PEERS + 1);
+        let labelled = &capped[..MAX_LABELLED_PEERS];
+        assert!(labelled.iter().all(|(_, count)| *count >= 10.0));
+        assert_eq!(capped.last(), Some(&(OTHER.to_string(), 45.0)));
+    }
+
+    #[test]
+    fn cmd_timings_render_as_a_histogram() {
+        let metrics = Metrics::default();
+        metrics.record_cmd("SendMsg", Duration::from_millis(3), true);
+        metrics.record_cmd("SendMsg", Duration::from_millis(300), false);
+        metrics.count_msg_received("node");
+
+        let mut out = Exposition::default();
+        metrics.render(&mut out);
+
+        for line in [
+            "# TYPE sn_node_cmd_duration_seconds histogram",
+            "sn_node_cmd_duration_seconds_bucket{kind=\"SendMsg\",le=\"0.001\"} 0",
+            "sn_node_cmd_duration_seconds_bucket{kind=\"SendMsg\",le=\"0.005\"} 1",
+            "sn_node_cmd_duration_seconds_bucket{kind=\"SendMsg\",le=\"0.5\"} 2",
+            "sn_node_cmd_duration_seconds_bucket{kind=\"SendMsg\",le=\"+Inf\"} 2",
+            "sn_node_cmd_duration_seconds_count{kind=\"SendMsg\"} 2",
+            "sn_node_cmd_errors_total{kind=\"SendMsg\"} 1",
+            "sn_node_msgs_received_total{auth=\"node\"} 1",
+        ] {
+            assert!(out.text.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut out = Exposition::default();
+        out.sample("x", &[("task", "a\"b\\c\nd")], 1);
+        assert_eq!(out.text, "sn_node_x{task=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+}
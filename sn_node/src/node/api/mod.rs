@@ -11,42 +11,81 @@ pub(crate) mod tests;
 
 pub(crate) mod cmds;
 
+#[cfg(feature = "chaos")]
+pub(super) mod chaos;
+pub(super) mod cmd_scheduler;
+pub(super) mod diagnostics;
 pub(super) mod dispatcher;
 pub(super) mod event;
 pub(super) mod event_stream;
+pub(super) mod fair_scheduler;
+#[cfg(feature = "metrics")]
+pub(super) mod metrics;
+pub(super) mod shutdown;
+pub(super) mod supervisor;
 
 use self::{
     cmds::Cmd,
-    dispatcher::Dispatcher,
+    dispatcher::{msg_cmd_id, Dispatcher},
     event::{Elders, Event, NodeElderChange},
-    event_stream::EventStream,
+    event_stream::{EventStream, MembershipEvents},
+    fair_scheduler::ClientQueueStats,
+    shutdown::ShutdownHandle,
+    supervisor::{BackgroundTaskStatus, TaskCounts},
 };
 
 use crate::node::{
-    cfg::keypair_storage::{get_reward_pk, store_network_keypair, store_new_reward_keypair},
-    core::{join_network, Comm, MsgEvent, Node},
+    cfg::{
+        capacity_storage::{get_max_capacity, store_max_capacity},
+        keypair_storage::{
+            get_network_keypair, get_reward_key_history, get_reward_pk, has_sealed_keys,
+            store_contacts_key, store_network_keypair, store_new_reward_keypair, store_storage_key,
+            unsealed_keys, ContactsKey,
+        },
+    },
+    core::{
+        cached_contacts, cached_prefix_map, join_network, BandwidthCaps, CacheOptions, CacheStats,
+        Comm, ConnLimits, Direction, Disposition, MsgEvent, Node, OurRelay, RelayLimits,
+        RelocationLimits, RetryPolicy, StateDb, WarmUpPolicy,
+    },
     error::{Error, Result},
     logging::{log_ctx::LogCtx, run_system_logger},
     messages::WireMsgUtils,
     Config, Peer,
 };
-use crate::UsedSpace;
-use sn_interface::messaging::{system::SystemMsg, DstLocation, WireMsg};
-use sn_interface::network_knowledge::{NodeInfo, SectionAuthorityProvider, MIN_ADULT_AGE};
-use sn_interface::types::{keys::ed25519, log_markers::LogMarker, PublicKey as TypesPublicKey};
+use crate::{
+    attestation::Attestation,
+    dbs::{ChunkStore, ReadThrottle},
+    dns_contacts::{
+        certify_contacts_key, last_issued, resolve_contacts, store_last_issued, ContactsError,
+    },
+    keystore,
+    startup_status::{self, StartupStatus},
+    UsedSpace,
+};
+use sn_interface::messaging::{system::SystemMsg, AuthKind, DstLocation, WireMsg};
+use sn_interface::network_knowledge::{
+    prefix_map::NetworkPrefixMap, NetworkKnowledge, NodeInfo, SectionAuthorityProvider,
+    MIN_ADULT_AGE,
+};
+use sn_interface::types::{
+    keys::ed25519, log_markers::LogMarker, DerivationPath, KeySeed, PublicKey as TypesPublicKey,
+};
 
+use bytes::Bytes;
 use ed25519_dalek::PublicKey;
 use itertools::Itertools;
 use rand_07::rngs::OsRng;
 use secured_linked_list::SecuredLinkedList;
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     net::{Ipv4Addr, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 use tokio::{sync::mpsc, task};
+use tracing::Instrument;
 use xor_name::{Prefix, XorName};
 
 /// Interface for sending and receiving messages to and from other nodes, in the role of a full
@@ -59,8 +98,16 @@ use xor_name::{Prefix, XorName};
 #[allow(missing_debug_implementations)]
 pub struct NodeApi {
     dispatcher: Arc<Dispatcher>,
+    root_dir: PathBuf,
+    #[cfg(feature = "metrics")]
+    metrics_addr: Option<SocketAddr>,
+    rpc_addr: Option<SocketAddr>,
 }
 
+/// Replaces the filter of the node's logs with the one given, in `RUST_LOG`'s syntax, or tells
+/// why it can't.
+pub type LogFilterReloader = Box<dyn Fn(&str) -> std::result::Result<(), String> + Send + Sync>;
+
 static EVENT_CHANNEL_SIZE: usize = 20;
 
 impl NodeApi {
@@ -69,41 +116,114 @@ impl NodeApi {
     ////////////////////////////////////////////////////////////////////////////
 
     /// Initialize a new node.
+    ///
+    /// How it goes is recorded in the startup status file of the node's root dir.
     pub async fn new(config: &Config, joining_timeout: Duration) -> Result<(Self, EventStream)> {
-        let root_dir_buf = config.root_dir()?;
+        let root_dir = config.root_dir()?;
+        tokio::fs::create_dir_all(&root_dir).await?;
+
+        record_startup_status(&root_dir, &StartupStatus::Starting);
+        let result = Self::start(config, joining_timeout, root_dir.clone()).await;
+        match &result {
+            Ok(_) => record_startup_status(&root_dir, &StartupStatus::Started),
+            Err(error) => record_startup_status(&root_dir, &StartupStatus::failed(error)),
+        }
+        result
+    }
+
+    async fn start(
+        config: &Config,
+        joining_timeout: Duration,
+        root_dir_buf: PathBuf,
+    ) -> Result<(Self, EventStream)> {
         let root_dir = root_dir_buf.as_path();
-        tokio::fs::create_dir_all(root_dir).await?;
 
+        // Keys left in the clear are sealed by `sn_node keystore encrypt`, not behind our back.
+        if config.encrypt_keys {
+            let unsealed = unsealed_keys(root_dir)?;
+            if let Some(path) = unsealed.first() {
+                return Err(Error::Configuration(format!(
+                    "{} is in the clear, while keys are to be encrypted. Encrypt the node's keys \
+                    with `sn_node keystore encrypt {}`",
+                    path.display(),
+                    root_dir.display()
+                )));
+            }
+        }
+
+        // The passphrase may be prompted for, so it's asked for now, off the runtime, rather
+        // than as the keys are first read.
+        if config.encrypt_keys || has_sealed_keys(root_dir)? {
+            keystore::unlock().await.map_err(|error| {
+                Error::Configuration(format!("can't unlock the keystore: {}", error))
+            })?;
+        }
+
+        let key_seed = config.key_seed()?;
         let _reward_key = match get_reward_pk(root_dir).await? {
             Some(public_key) => TypesPublicKey::Ed25519(public_key),
             None => {
-                let mut rng = OsRng;
-                let keypair = ed25519_dalek::Keypair::generate(&mut rng);
-                store_new_reward_keypair(root_dir, &keypair).await?;
+                let keypair = match &key_seed {
+                    Some(key_seed) => key_seed.ed25519_keypair(&DerivationPath::reward()),
+                    None => ed25519_dalek::Keypair::generate(&mut OsRng),
+                };
+                store_new_reward_keypair(root_dir, &keypair, config.encrypt_keys).await?;
                 TypesPublicKey::Ed25519(keypair.public)
             }
         };
 
-        let used_space = UsedSpace::new(config.max_capacity());
+        // A capacity set at runtime takes precedence over the configured one.
+        let max_capacity = match get_max_capacity(root_dir).await? {
+            Some(max_capacity) => {
+                info!("Using the max capacity last set at runtime: {max_capacity} bytes");
+                max_capacity
+            }
+            None => config.max_capacity(),
+        };
+        let used_space = UsedSpace::new(max_capacity)
+            .with_db_reserve(config.db_reserve())
+            .with_watermarks(config.storage_watermarks());
+        if let Some(key) = ChunkStore::new_key(root_dir, config.encrypt_chunks)? {
+            store_storage_key(root_dir, key, config.encrypt_keys).await?;
+        }
+        let _layout = ChunkStore::check(
+            root_dir,
+            &used_space,
+            config.chunk_backend,
+            config.encrypt_chunks,
+        )?;
 
         let (api, network_events) = tokio::time::timeout(
             joining_timeout,
-            Self::start_node(config, used_space, root_dir),
+            Self::start_node(config, key_seed.as_ref(), used_space, root_dir),
         )
         .await
         .map_err(|_| Error::JoinTimeout)??;
 
         // Network keypair may have to be changed due to naming criteria or network requirements.
         let keypair_as_bytes = api.dispatcher.node.info.read().await.keypair.to_bytes();
-        store_network_keypair(root_dir, keypair_as_bytes).await?;
+        store_network_keypair(root_dir, keypair_as_bytes, config.encrypt_keys).await?;
+
+        let _attestation = api
+            .dispatcher
+            .attest(config.clone(), root_dir_buf.clone())
+            .await?;
 
         let our_pid = std::process::id();
         let node_prefix = api.our_prefix().await;
         let node_name = api.name().await;
         let node_age = api.age().await;
         let our_conn_info = api.our_connection_info().await;
-        let our_conn_info_json = serde_json::to_string(&our_conn_info)
-            .unwrap_or_else(|_| "Failed to serialize connection info".into());
+        let our_client_conn_info = api.our_client_connection_info().await;
+        let our_conn_info_json = if our_client_conn_info == our_conn_info {
+            serde_json::to_string(&our_conn_info)
+        } else {
+            serde_json::to_string(&serde_json::json!({
+                "nodes": our_conn_info,
+                "clients": our_client_conn_info,
+            }))
+        }
+        .unwrap_or_else(|_| "Failed to serialize connection info".into());
         println!(
             "Node PID: {:?}, prefix: {:?}, name: {:?}, age: {}, connection info:\n{}",
             our_pid, node_prefix, node_name, node_age, our_conn_info_json,
@@ -125,6 +245,7 @@ impl NodeApi {
     // caller to handle this case, for example by using a timeout.
     async fn start_node(
         config: &Config,
+        key_seed: Option<&KeySeed>,
         used_space: UsedSpace,
         root_storage_dir: &Path,
     ) -> Result<(Self, EventStream)> {
@@ -137,7 +258,7 @@ impl NodeApi {
 
         let node = if config.is_first() {
             // Genesis node having a fix age of 255.
-            let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), 255);
+            let keypair = gen_keypair(key_seed, 255);
             let node_name = ed25519::name(&keypair.public);
 
             info!(
@@ -152,9 +273,22 @@ impl NodeApi {
                 connection_event_tx,
             )
             .await?;
+            let comm = setup_listeners(comm, config).await?;
             let info = NodeInfo::new(keypair, comm.our_connection_info());
 
             let genesis_sk_set = bls::SecretKeySet::random(0, &mut rand::thread_rng());
+            // The network's contacts are published in DNS signed with a key of their own,
+            // certified by the genesis key now, for its secret key not to be kept.
+            let contacts_secret_key = bls::SecretKey::random();
+            let contacts_key = ContactsKey {
+                certificate: certify_contacts_key(
+                    &genesis_sk_set.secret_key(),
+                    &contacts_secret_key.public_key(),
+                ),
+                genesis_key: genesis_sk_set.public_keys().public_key(),
+                secret_key: contacts_secret_key,
+            };
+            store_contacts_key(root_storage_dir, &contacts_key, config.encrypt_keys).await?;
             let node = Node::first_node(
                 comm,
                 info,
@@ -203,40 +337,106 @@ impl NodeApi {
                     )
                 })?;
 
-            let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE);
+            let keypair = gen_keypair(key_seed, MIN_ADULT_AGE);
             let node_name = ed25519::name(&keypair.public);
             info!("{} Bootstrapping a new node.", node_name);
 
-            let (comm, bootstrap_addr) = Comm::bootstrap(
-                local_addr,
-                config
-                    .hard_coded_contacts
-                    .iter()
-                    .copied()
-                    .collect_vec()
-                    .as_slice(),
-                config.network_config().clone(),
-                connection_event_tx,
-            )
-            .await?;
-            info!(
-                "{} Joining as a new node (PID: {}) our socket: {}, bootstrapper was: {}, network's genesis key: {:?}",
-                node_name,
-                std::process::id(),
-                comm.our_connection_info(),
-                bootstrap_addr,
-                genesis_key
-            );
+            let prefix_map = if config.skip_cached_contacts {
+                NetworkPrefixMap::new(genesis_key)
+            } else {
+                cached_prefix_map(genesis_key, root_storage_dir).await?
+            };
+            let dns_contacts = match &config.bootstrap_dns {
+                Some(name) => match resolve_contacts(
+                    name,
+                    config.dns_resolver,
+                    &genesis_key,
+                    last_issued(root_storage_dir).await,
+                )
+                .await
+                {
+                    Ok(record) => {
+                        if let Err(error) = store_last_issued(root_storage_dir, record.issued).await
+                        {
+                            warn!(
+                                "Failed to keep the issue time of the contacts record: {}",
+                                error
+                            );
+                        }
+                        record.contacts.into_iter().collect_vec()
+                    }
+                    Err(error @ ContactsError::NoResolver(_)) => {
+                        return Err(Error::Configuration(format!(
+                            "can't resolve the contacts published at {}: {}",
+                            name, error
+                        )));
+                    }
+                    Err(error) => {
+                        warn!(
+                            "Failed to resolve the contacts published at {}: {}",
+                            name, error
+                        );
+                        vec![]
+                    }
+                },
+                None => vec![],
+            };
+            // The elders we last knew of are tried first, for us to rejoin even once the
+            // contacts we were given are gone.
+            let contacts = [
+                cached_contacts(&prefix_map, &node_name),
+                dns_contacts,
+                config.hard_coded_contacts.iter().copied().collect_vec(),
+            ];
+            let mut bootstrapped = None;
+            for contacts in contacts.iter().filter(|contacts| !contacts.is_empty()) {
+                match Comm::bootstrap(
+                    local_addr,
+                    contacts,
+                    config.network_config().clone(),
+                    connection_event_tx.clone(),
+                )
+                .await
+                {
+                    Ok(comm) => {
+                        bootstrapped = Some(comm);
+                        break;
+                    }
+                    Err(Error::BootstrapFailed) => {
+                        warn!("Failed to bootstrap from any of {:?}", contacts);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            drop(connection_event_tx);
+            let (comm, bootstrap_addr) = bootstrapped.ok_or(Error::BootstrapFailed)?;
+            let comm = setup_listeners(comm, config).await?;
 
-            let joining_node = NodeInfo::new(keypair, comm.our_connection_info());
-            let (info, network_knowledge) = join_network(
-                joining_node,
-                &comm,
-                &mut connection_event_rx,
-                bootstrap_addr,
-                genesis_key,
-            )
-            .await?;
+            let our_relay = OurRelay::default();
+            let resumed = resumable_state(&comm, genesis_key, root_storage_dir).await?;
+            let (info, network_knowledge) = if let Some(resumed) = resumed {
+                resumed
+            } else {
+                info!(
+                    "{} Joining as a new node (PID: {}) our socket: {}, bootstrapper was: {}, network's genesis key: {:?}",
+                    node_name,
+                    std::process::id(),
+                    comm.our_connection_info(),
+                    bootstrap_addr,
+                    genesis_key
+                );
+
+                let joining_node = NodeInfo::new(keypair, comm.our_connection_info());
+                join_network(
+                    joining_node,
+                    &comm,
+                    &mut connection_event_rx,
+                    bootstrap_addr,
+                    prefix_map,
+                    &our_relay,
+                )
+                .await?
+            };
 
             let node = Node::new(
                 comm,
@@ -247,7 +447,8 @@ impl NodeApi {
                 used_space.clone(),
                 root_storage_dir.to_path_buf(),
             )
-            .await?;
+            .await?
+            .with_our_relay(our_relay);
             info!("{} Joined the network!", node.info.read().await.name());
             info!("Our AGE: {}", node.info.read().await.age());
 
@@ -255,7 +456,17 @@ impl NodeApi {
         };
 
         let dispatcher = Arc::new(Dispatcher::new(node));
-        let event_stream = EventStream::new(event_rx);
+        let event_stream = EventStream::new(event_rx, dispatcher.shutdown.subscribe());
+
+        // Pick up an elder promotion interrupted by our last shutdown, if any.
+        for cmd in dispatcher.node.resume_role_transition().await? {
+            dispatcher
+                .clone()
+                .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                .await?;
+        }
+
+        dispatcher.clone().start_client_msg_scheduling().await;
 
         // Start listening to incoming connections.
         let _handle = task::spawn(handle_connection_events(
@@ -263,6 +474,124 @@ impl NodeApi {
             connection_event_rx,
         ));
 
+        dispatcher
+            .node
+            .data_storage
+            .disk_health()
+            .set_thresholds(
+                config.disk_failure_threshold(),
+                config.disk_recovery_threshold(),
+            )
+            .await;
+        dispatcher.clone().probe_event_loop_periodically().await;
+        dispatcher.clone().check_disk_health_periodically().await;
+        dispatcher
+            .node
+            .data_storage
+            .scrubber()
+            .set_chunks_per_tick(config.scrub_chunks_per_tick());
+        dispatcher.clone().scrub_chunks_periodically().await;
+        if config.msg_filter_capacity() > 0 {
+            dispatcher
+                .node
+                .msg_filter
+                .enable(config.msg_filter_capacity(), config.msg_filter_ttl())
+                .await;
+            dispatcher
+                .clone()
+                .checkpoint_msg_filter_periodically()
+                .await;
+        }
+        dispatcher
+            .node
+            .retry_queue
+            .enable(RetryPolicy {
+                max_attempts: config.send_retry_max_attempts(),
+                base_delay: config.send_retry_base_delay(),
+            })
+            .await;
+        dispatcher.clone().retry_failed_sends_periodically().await;
+        dispatcher
+            .node
+            .relay_service
+            .set_limits(RelayLimits {
+                max_peers: config.relay_max_peers(),
+                bytes_per_sec: config.relay_max_bytes_per_sec(),
+            })
+            .await;
+        dispatcher.clone().renew_relays_periodically().await;
+        dispatcher
+            .node
+            .holder_registry
+            .set_slices_per_tick(config.registry_slices_per_sec());
+        dispatcher
+            .node
+            .warm_up
+            .set_policy(WarmUpPolicy {
+                period: config.warm_up(),
+                initial_share: f64::from(config.warm_up_initial_share_percent()) / 100.0,
+            })
+            .await;
+        dispatcher
+            .node
+            .data_cache
+            .set_options(CacheOptions {
+                enabled: config.data_cache_bytes() > 0,
+                max_bytes: config.data_cache_bytes(),
+                ttl: config.data_cache_ttl(),
+            })
+            .await;
+        dispatcher
+            .node
+            .data_storage
+            .set_read_throttle(ReadThrottle {
+                iops: config.background_read_iops(),
+                bytes_per_sec: config.background_read_bytes_per_sec(),
+                max_interactive_latency: config.interactive_read_latency(),
+                ..ReadThrottle::default()
+            });
+        dispatcher
+            .node
+            .data_storage
+            .set_chunk_journal_flush_interval(config.chunk_journal_flush_interval());
+        #[cfg(feature = "chaos")]
+        chaos::Chaos::from_env()?.inject(&dispatcher.node);
+        dispatcher
+            .node
+            .relocation_freshness
+            .set_limits(RelocationLimits {
+                validity: config.relocation_validity(),
+                max_key_lag: config.relocation_max_key_lag(),
+            })
+            .await;
+        dispatcher
+            .clone()
+            .rebuild_holder_registry_periodically()
+            .await;
+        dispatcher
+            .clone()
+            .check_data_distribution_periodically()
+            .await;
+        dispatcher
+            .node
+            .replication_audit
+            .set_chunks_per_tick(config.audit_chunks_per_tick());
+        dispatcher.clone().audit_replication_periodically().await;
+        dispatcher
+            .node
+            .hedged_reads
+            .set_delay(config.hedged_read_delay());
+        dispatcher
+            .node
+            .erasure_coding
+            .set_threshold(config.erasure_coding_threshold());
+        dispatcher.clone().probe_liveness_periodically().await;
+        dispatcher.clone().record_membership_periodically().await;
+        dispatcher
+            .clone()
+            .watch_control_requests(root_storage_dir.to_path_buf())
+            .await;
+
         dispatcher.clone().start_network_probing().await;
         dispatcher
             .clone()
@@ -278,11 +607,49 @@ impl NodeApi {
         dispatcher.clone().start_cleaning_peer_links().await;
         dispatcher.clone().write_prefixmap_to_disk().await;
 
-        let api = Self { dispatcher };
+        #[cfg(feature = "metrics")]
+        let metrics_addr = match config.metrics_addr {
+            Some(addr) => Some(metrics::serve(&dispatcher, addr)?),
+            None => None,
+        };
+
+        #[cfg(unix)]
+        if config.control_sockets {
+            diagnostics::serve(&dispatcher, root_storage_dir, config.control_token.clone())?;
+        }
+        let rpc_addr = match config.rpc_addr {
+            Some(addr) => Some(
+                diagnostics::serve_rpc(
+                    &dispatcher,
+                    addr,
+                    root_storage_dir,
+                    config.control_token.clone(),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        let api = Self {
+            dispatcher,
+            root_dir: root_storage_dir.to_path_buf(),
+            #[cfg(feature = "metrics")]
+            metrics_addr,
+            rpc_addr,
+        };
 
         Ok((api, event_stream))
     }
 
+    /// Subscribes to the decisions on our section's membership, and on our place in it, from
+    /// now on: see `MembershipEvent`. Any number of subscriptions can be held at once.
+    pub fn events(&self) -> MembershipEvents {
+        MembershipEvents::new(
+            self.dispatcher.node.subscribe_membership_events(),
+            self.dispatcher.shutdown.subscribe(),
+        )
+    }
+
     /// Returns the current age of this node.
     pub async fn age(&self) -> u8 {
         self.dispatcher.node.info.read().await.age()
@@ -303,6 +670,12 @@ impl NodeApi {
         self.dispatcher.node.our_connection_info()
     }
 
+    /// Returns the connection info clients reach this node on, which is the one for nodes
+    /// unless clients have a listener of their own.
+    pub async fn our_client_connection_info(&self) -> SocketAddr {
+        self.dispatcher.node.comm.our_client_connection_info()
+    }
+
     /// Returns the Section Signed Chain
     pub async fn section_chain(&self) -> SecuredLinkedList {
         self.dispatcher.node.section_chain().await
@@ -372,6 +745,126 @@ impl NodeApi {
         Ok(())
     }
 
+    /// Returns the status, restart count and last exit of each of the node's background tasks.
+    pub async fn background_tasks(&self) -> Vec<BackgroundTaskStatus> {
+        self.dispatcher.supervisor.statuses().await
+    }
+
+    /// Returns whether any background task has died without being restarted, in which case
+    /// the node is running with degraded functionality.
+    pub fn has_failed_background_task(&self) -> bool {
+        self.dispatcher.supervisor.has_failed_task()
+    }
+
+    /// Returns the counts of spawned, completed and blocking tasks of each of the node's
+    /// subsystems.
+    pub fn task_counts(&self) -> BTreeMap<&'static str, TaskCounts> {
+        self.dispatcher.supervisor.task_counts()
+    }
+
+    /// Returns the queue depth and service rate of the msgs of each client we've recently
+    /// received msgs from as an elder.
+    pub async fn client_queue_stats(&self) -> Vec<ClientQueueStats<TypesPublicKey>> {
+        self.dispatcher.client_scheduler.stats().await
+    }
+
+    /// Returns whether our storage passes its periodic disk self-test. While it doesn't, the
+    /// node takes on no new chunks.
+    pub async fn is_storage_healthy(&self) -> bool {
+        self.dispatcher
+            .node
+            .data_storage
+            .disk_health()
+            .is_healthy()
+            .await
+    }
+
+    /// Returns whether the node can serve client reads. A storage-full or restarting adult still
+    /// can, a node mid-handover or outside of a section can't.
+    pub async fn can_serve_reads(&self) -> bool {
+        self.dispatcher.readiness().await.can_read()
+    }
+
+    /// Returns whether the node can serve client writes.
+    pub async fn can_serve_writes(&self) -> bool {
+        self.dispatcher.readiness().await.can_write()
+    }
+
+    /// Returns whether the node can serve both client reads and writes.
+    pub async fn is_ready(&self) -> bool {
+        self.dispatcher.readiness().await.is_ready()
+    }
+
+    /// Returns the node's readiness for operators: whether reads and writes can be served, with
+    /// each condition they depend on and whether it holds.
+    pub async fn readiness_report(&self) -> String {
+        self.dispatcher.readiness().await.report()
+    }
+
+    /// Returns the address our metrics are served on, if configured to serve them.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_addr
+    }
+
+    /// Returns the loopback address our control plane is served on over TCP, if configured to
+    /// serve it.
+    pub fn rpc_addr(&self) -> Option<SocketAddr> {
+        self.rpc_addr
+    }
+
+    /// Lets our logs' filter be changed at runtime, with the control plane's `set_log_level`,
+    /// by whoever set up the logs.
+    pub async fn set_log_filter_reloader(&self, reloader: LogFilterReloader) {
+        self.dispatcher.set_log_filter_reloader(reloader).await
+    }
+
+    /// Tells our elders we're about to restart and will be back within `within` (at most 15
+    /// minutes), after flushing our dbs. Until then they neither propose us offline nor give us
+    /// new data to store. With `drain`, the chunks we hold are also pushed to the adults that would
+    /// replace us as their holders. Only adults can do so.
+    pub async fn prepare_restart(&self, within: Duration, drain: bool) -> Result<()> {
+        self.dispatcher.clone().prepare_restart(within, drain).await
+    }
+
+    /// Returns a handle to shut us down gracefully, which ends our `EventStream` once done.
+    /// Handles can be cloned, and used from any task, e.g. one waiting for a signal.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.dispatcher.clone())
+    }
+
+    /// Changes the max capacity of our storage, in bytes, without restarting. Lowering it below
+    /// what's stored doesn't delete anything, new data is refused until usage drops below it;
+    /// raising it lets us take on new data again. `Event::CapacityChanged` is raised once the new
+    /// capacity applies. It's persisted, and takes precedence over the configured capacity from
+    /// then on, across restarts.
+    pub async fn set_capacity(&self, max_capacity: usize) -> Result<()> {
+        store_max_capacity(&self.root_dir, max_capacity).await?;
+        self.dispatcher.clone().set_capacity(max_capacity).await
+    }
+
+    /// Changes how chunks are cached as we relay them to clients, as an elder, without
+    /// restarting. Disabling the cache empties it.
+    pub async fn set_cache_options(&self, options: CacheOptions) {
+        self.dispatcher.node.data_cache.set_options(options).await
+    }
+
+    /// Returns how chunks are cached as we relay them to clients.
+    pub async fn cache_options(&self) -> CacheOptions {
+        self.dispatcher.node.data_cache.options().await
+    }
+
+    /// Returns the hits, misses and evictions of our data cache, and what it holds.
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.dispatcher.node.data_cache.stats().await
+    }
+
+    /// Returns the latest attestation of the version and config we run with, which is
+    /// re-attested whenever the config changes at runtime.
+    pub async fn attestation(&self) -> Option<Attestation> {
+        self.dispatcher.attestation().await
+    }
+
     /// Returns the current BLS public key set if this node has one, or
     /// `Error::MissingSecretKeyShare` otherwise.
     pub async fn public_key_set(&self) -> Result<bls::PublicKeySet> {
@@ -380,6 +873,125 @@ impl NodeApi {
 }
 
 // Listen for incoming connection events and handle them.
+// Has clients connect to a listener of their own, if configured, and sets the limits on the
+// connections each of our listeners takes.
+fn record_startup_status(root_dir: &Path, status: &StartupStatus) {
+    if let Err(error) = startup_status::write(root_dir, status) {
+        warn!(
+            "Failed to record the startup status {:?}: {}",
+            status, error
+        );
+    }
+}
+
+async fn setup_listeners(comm: Comm, config: &Config) -> Result<Comm> {
+    let comm = match config.client_local_addr {
+        Some(client_local_addr) => {
+            comm.with_client_listener(client_local_addr, config.network_config().clone())
+                .await?
+        }
+        None => comm,
+    };
+
+    comm.set_listener_limits(
+        ConnLimits {
+            max_conns: config.max_node_conns(),
+            conns_per_s: config.node_conns_per_sec(),
+        },
+        ConnLimits {
+            max_conns: config.max_client_conns(),
+            conns_per_s: config.client_conns_per_sec(),
+        },
+    );
+    comm.set_stream_threshold(config.stream_threshold());
+    comm.set_compression_threshold(config.compression_threshold());
+    comm.msg_audit().set_capacity(config.msg_audit_len());
+    comm.set_bandwidth_caps(BandwidthCaps {
+        bytes_in_per_sec: config.peer_max_in_bytes_per_sec(),
+        bytes_out_per_sec: config.peer_max_out_bytes_per_sec(),
+    });
+    #[cfg(feature = "back-pressure")]
+    comm.set_back_pressure_strategy(
+        config.back_pressure_strategy(),
+        config.back_pressure_report_interval(),
+    )
+    .await;
+
+    Ok(comm)
+}
+
+// A keypair for the node to join with at `age`, derived from `key_seed` if there's one.
+fn gen_keypair(key_seed: Option<&KeySeed>, age: u8) -> ed25519_dalek::Keypair {
+    let range = Prefix::default().range_inclusive();
+    match key_seed {
+        Some(key_seed) => key_seed.node_keypair(&range, age),
+        None => ed25519::gen_keypair(&range, age),
+    }
+}
+
+// Our identity and network knowledge from the snapshot of our last shutdown, if we can resume
+// as a member of our section from it rather than joining anew: we shut down for a restart we
+// announced, its window hasn't run out, and we're back at the same address with the same keys.
+async fn resumable_state(
+    comm: &Comm,
+    genesis_key: bls::PublicKey,
+    root_dir: &Path,
+) -> Result<Option<(NodeInfo, NetworkKnowledge)>> {
+    let snapshot = match StateDb::new(root_dir).take().await {
+        Some(snapshot) => snapshot,
+        None => return Ok(None),
+    };
+    let keypair = get_network_keypair(root_dir).await?;
+    let reward_key = get_reward_pk(root_dir).await?.map(TypesPublicKey::Ed25519);
+    // A key we rotated away from since is still ours.
+    let rotated_from = get_reward_key_history(root_dir)
+        .await?
+        .into_iter()
+        .any(|retired| snapshot.reward_key == Some(TypesPublicKey::Ed25519(retired.public_key)));
+    let our_addr = comm.our_connection_info();
+
+    let keypair = match keypair {
+        _ if !snapshot.is_resumable() => {
+            info!("Joining anew, as we left our section or our restart window ran out");
+            return Ok(None);
+        }
+        _ if snapshot.addr != our_addr => {
+            info!(
+                "Joining anew, as our address changed from {} to {}",
+                snapshot.addr, our_addr
+            );
+            return Ok(None);
+        }
+        _ if snapshot.reward_key != reward_key && !rotated_from => {
+            warn!("Joining anew, as our reward key changed since our state was snapshotted");
+            return Ok(None);
+        }
+        Some(keypair) if ed25519::name(&keypair.public) == snapshot.name => keypair,
+        _ => {
+            warn!("Joining anew, as our network keypair changed since our state was snapshotted");
+            return Ok(None);
+        }
+    };
+    let network_knowledge = match snapshot.network_knowledge(genesis_key).await {
+        Ok(network_knowledge) => network_knowledge,
+        Err(error) => {
+            warn!(
+                "Joining anew, as our snapshotted network knowledge can't be trusted: {:?}",
+                error
+            );
+            return Ok(None);
+        }
+    };
+
+    info!(
+        "{} Resuming as a member of {:?} from our state snapshot, with {} chunks held",
+        snapshot.name,
+        network_knowledge.prefix().await,
+        snapshot.chunks
+    );
+    Ok(Some((NodeInfo::new(keypair, our_addr), network_knowledge)))
+}
+
 async fn handle_connection_events(
     dispatcher: Arc<Dispatcher>,
     mut incoming_conns: mpsc::Receiver<MsgEvent>,
@@ -399,25 +1011,64 @@ async fn handle_connection_events(
 
                 let span = {
                     let node = &dispatcher.node;
-                    trace_span!("handle_message", name = %node.info.read().await.name(), ?sender, msg_id = ?wire_msg.msg_id())
+                    trace_span!(
+                        "handle_message",
+                        name = %node.info.read().await.name(),
+                        ?sender,
+                        msg_id = ?wire_msg.msg_id(),
+                        dst = ?wire_msg.dst_location(),
+                        trace_id = %msg_cmd_id(wire_msg.msg_id()),
+                    )
                 };
-                let _span_guard = span.enter();
-
-                trace!(
-                    "{:?} from {:?} length {}",
-                    LogMarker::DispatchHandleMsgCmd,
-                    sender,
-                    original_bytes.len(),
-                );
-                let cmd = Cmd::HandleMsg {
-                    sender,
-                    wire_msg,
-                    original_bytes: Some(original_bytes),
+                handle_received_msg(dispatcher.clone(), sender, wire_msg, original_bytes)
+                    .instrument(span)
+                    .await;
+            }
+            MsgEvent::Misdirected {
+                sender,
+                wire_msg,
+                original_bytes,
+                redirect_to,
+            } => {
+                let cmd_id = msg_cmd_id(wire_msg.msg_id());
+                let size = original_bytes.len();
+                let cmd = match dispatcher
+                    .node
+                    .redirect_to_listener(sender, &wire_msg, original_bytes, redirect_to)
+                    .await
+                {
+                    Ok(cmd) => {
+                        record_inbound(
+                            &dispatcher,
+                            &wire_msg,
+                            sender,
+                            size,
+                            Disposition::Redirected,
+                        );
+                        cmd
+                    }
+                    Err(error) => {
+                        record_inbound(
+                            &dispatcher,
+                            &wire_msg,
+                            sender,
+                            size,
+                            Disposition::RedirectFailed,
+                        );
+                        error!(
+                            "Failed to redirect {:?} from {:?} to {}: {:?}",
+                            wire_msg.msg_id(),
+                            sender,
+                            redirect_to,
+                            error
+                        );
+                        continue;
+                    }
                 };
 
                 let _handle = dispatcher
                     .clone()
-                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, None)
+                    .enqueue_and_handle_next_cmd_and_offshoots(cmd, Some(cmd_id))
                     .await;
             }
         }
@@ -425,3 +1076,115 @@ async fn handle_connection_events(
 
     error!("Fatal error, the stream for incoming connections has been unexpectedly closed. No new connections or messages can be received from the network from here on.");
 }
+
+// Hands a msg received over to be handled, unless it's to be dropped or refused.
+async fn handle_received_msg(
+    dispatcher: Arc<Dispatcher>,
+    sender: Peer,
+    wire_msg: WireMsg,
+    original_bytes: Bytes,
+) {
+    let cmd_id = msg_cmd_id(wire_msg.msg_id());
+
+    trace!(
+        "{:?} from {:?} length {}",
+        LogMarker::DispatchHandleMsgCmd,
+        sender,
+        original_bytes.len(),
+    );
+    #[cfg(feature = "metrics")]
+    dispatcher
+        .metrics
+        .count_msg_received(match wire_msg.msg_kind() {
+            AuthKind::Service(_) => "service",
+            AuthKind::Node(_) => "node",
+            AuthKind::NodeBlsShare(_) => "node_bls_share",
+        });
+
+    // Node msgs received again, e.g. both before and after we restarted, are
+    // handled once.
+    if !matches!(wire_msg.msg_kind(), AuthKind::Service(_))
+        && !dispatcher
+            .node
+            .msg_filter
+            .insert(wire_msg.msg_id(), sender.name())
+            .await
+    {
+        debug!(
+            "Dropping {:?} from {:?}, already received",
+            wire_msg.msg_id(),
+            sender
+        );
+        record_inbound(
+            &dispatcher,
+            &wire_msg,
+            sender,
+            original_bytes.len(),
+            Disposition::Duplicate,
+        );
+        return;
+    }
+
+    // Elders process client msgs taking turns between clients, so a few busy clients
+    // can't hold up all others.
+    if let AuthKind::Service(auth) = wire_msg.msg_kind() {
+        if dispatcher.shutdown.is_draining() {
+            let client = auth.public_key;
+            record_inbound(
+                &dispatcher,
+                &wire_msg,
+                sender,
+                original_bytes.len(),
+                Disposition::RefusedDraining,
+            );
+            dispatcher
+                .clone()
+                .refuse_client_msg(client, sender, wire_msg)
+                .await;
+            return;
+        }
+        if dispatcher.node.is_elder().await {
+            let client = auth.public_key;
+            dispatcher
+                .clone()
+                .schedule_client_msg(client, sender, wire_msg, original_bytes)
+                .await;
+            return;
+        }
+    }
+
+    record_inbound(
+        &dispatcher,
+        &wire_msg,
+        sender,
+        original_bytes.len(),
+        Disposition::Handled,
+    );
+    let cmd = Cmd::HandleMsg {
+        sender,
+        wire_msg,
+        original_bytes: Some(original_bytes),
+    };
+
+    let _handle = dispatcher
+        .clone()
+        .enqueue_and_handle_next_cmd_and_offshoots(cmd, Some(cmd_id))
+        .await;
+}
+
+// Records what became of a msg received, for the msg audit.
+fn record_inbound(
+    dispatcher: &Dispatcher,
+    wire_msg: &WireMsg,
+    sender: Peer,
+    size: usize,
+    disposition: Disposition,
+) {
+    dispatcher.node.comm.msg_audit().record(
+        Direction::Inbound,
+        wire_msg,
+        sender,
+        size,
+        disposition,
+    );
+}
@@ -0,0 +1,103 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::dispatcher::Dispatcher;
+use crate::node::Result;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::watch;
+
+/// Longest a shutdown waits for the cmds in flight to be handled, before going ahead anyway.
+pub(crate) const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the node is in shutting down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ShutdownState {
+    Running,
+    // No longer taking client msgs, and handling what's in flight.
+    Draining,
+    ShutDown,
+}
+
+/// Tracks the node's shutdown, for its event stream to end once it's shut down.
+pub(crate) struct Shutdown {
+    started: AtomicBool,
+    state_tx: watch::Sender<ShutdownState>,
+    state_rx: watch::Receiver<ShutdownState>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        let (state_tx, state_rx) = watch::channel(ShutdownState::Running);
+        Self {
+            started: AtomicBool::new(false),
+            state_tx,
+            state_rx,
+        }
+    }
+
+    /// Starts draining, returning whether it was this call that started it.
+    pub(crate) fn begin(&self) -> bool {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let _res = self.state_tx.send(ShutdownState::Draining);
+        true
+    }
+
+    pub(crate) fn finish(&self) {
+        let _res = self.state_tx.send(ShutdownState::ShutDown);
+    }
+
+    /// Whether we've started shutting down, and so no longer take client msgs.
+    pub(crate) fn is_draining(&self) -> bool {
+        *self.state_rx.borrow() != ShutdownState::Running
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<ShutdownState> {
+        self.state_rx.clone()
+    }
+}
+
+/// Waits for `state` to reach `ShutDown`, forever if its sender goes away before.
+pub(crate) async fn shut_down(mut state: watch::Receiver<ShutdownState>) {
+    while *state.borrow() != ShutdownState::ShutDown {
+        if state.changed().await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Shuts the node down gracefully: it stops taking client msgs, lets the cmds in flight be
+/// handled, flushes its dbs, then tells its section it's going offline, for rolling upgrades
+/// not to leave the section waiting for it to time out. An adult which announced a restart stays
+/// in its section instead, to resume from a snapshot of its state if started again within its
+/// window. Its `EventStream` ends once it's done.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct ShutdownHandle {
+    dispatcher: Arc<Dispatcher>,
+}
+
+impl ShutdownHandle {
+    pub(super) fn new(dispatcher: Arc<Dispatcher>) -> Self {
+        Self { dispatcher }
+    }
+
+    /// Shuts the node down, returning once it's done. Calls made while a shutdown is under way
+    /// wait for it instead.
+    pub async fn shut_down(&self) -> Result<()> {
+        self.dispatcher.clone().shut_down(DRAIN_TIMEOUT).await
+    }
+}
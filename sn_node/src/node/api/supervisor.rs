@@ -0,0 +1,530 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    sync::RwLock,
+    task::{JoinError, JoinHandle},
+    time::{sleep, Instant},
+};
+
+/// A single poll of a task taking longer than this blocks its executor thread long enough to be
+/// warned about.
+pub(crate) const LONG_POLL_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// How a supervised background task is treated when it exits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Restart immediately, however often the task exits.
+    Always,
+    /// Restart after an exponentially growing delay, giving up after
+    /// `max_restarts` consecutive exits.
+    WithBackoff {
+        /// Delay before the first restart.
+        initial_delay: Duration,
+        /// Upper bound for the delay between restarts. A task which ran for at least this long
+        /// before exiting is considered to have recovered, resetting its backoff.
+        max_delay: Duration,
+        /// Number of consecutive restarts after which the supervisor gives up on the task.
+        max_restarts: usize,
+    },
+    /// Never restart. The task exiting is considered a failure of the node.
+    Never,
+}
+
+impl RestartPolicy {
+    /// The backoff policy used for the node's periodic background tasks.
+    pub(crate) fn default_backoff() -> Self {
+        Self::WithBackoff {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_restarts: 10,
+        }
+    }
+}
+
+/// Current state of a supervised background task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The task is running.
+    Running,
+    /// The task exited and is waiting to be restarted.
+    Restarting,
+    /// The task exited and, as per its policy, will not be restarted.
+    Dead,
+    /// The task exited too many times in a row and the supervisor stopped restarting it.
+    GaveUp,
+}
+
+/// How a supervised background task last exited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskExit {
+    /// The task's future completed.
+    Returned,
+    /// The task panicked, with the given panic message.
+    Panicked(String),
+    /// The task was cancelled by the runtime.
+    Cancelled,
+}
+
+impl Display for TaskExit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Returned => write!(f, "returned"),
+            Self::Panicked(msg) => write!(f, "panicked: {}", msg),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl From<JoinError> for TaskExit {
+    fn from(error: JoinError) -> Self {
+        if !error.is_panic() {
+            return Self::Cancelled;
+        }
+        let panic = error.into_panic();
+        let msg = if let Some(msg) = panic.downcast_ref::<&str>() {
+            (*msg).to_string()
+        } else if let Some(msg) = panic.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+        Self::Panicked(msg)
+    }
+}
+
+/// Diagnostics snapshot of a supervised background task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackgroundTaskStatus {
+    /// Name the task was registered with.
+    pub name: &'static str,
+    /// The task's restart policy.
+    pub policy: RestartPolicy,
+    /// The task's current state.
+    pub status: TaskStatus,
+    /// Number of times the task has been restarted since the node started.
+    pub restarts: usize,
+    /// How the task last exited, if it ever did.
+    pub last_exit: Option<TaskExit>,
+}
+
+/// Counts of the tasks spawned by a subsystem of the node, since the node started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TaskCounts {
+    /// Tasks spawned.
+    pub spawned: u64,
+    /// Tasks which completed, however they ended.
+    pub completed: u64,
+    /// Polls of the tasks which took longer than `LONG_POLL_THRESHOLD`.
+    pub long_polls: u64,
+}
+
+#[derive(Default)]
+struct TaskCounters {
+    spawned: AtomicU64,
+    completed: AtomicU64,
+    long_polls: AtomicU64,
+}
+
+// A task of a subsystem, counted as completed once dropped, and timing each of its polls.
+struct CountedTask<Fut> {
+    subsystem: &'static str,
+    counters: Arc<TaskCounters>,
+    task: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for CountedTask<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Wall-clock time, as paused time doesn't see the thread being blocked.
+        let started = std::time::Instant::now();
+        let poll = self.task.as_mut().poll(cx);
+        let elapsed = started.elapsed();
+        if elapsed > LONG_POLL_THRESHOLD {
+            let _prev = self.counters.long_polls.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Task of {:?} blocked its executor thread for {:?} in a single poll",
+                self.subsystem, elapsed
+            );
+        }
+        poll
+    }
+}
+
+impl<Fut> Drop for CountedTask<Fut> {
+    fn drop(&mut self) {
+        let _prev = self.counters.completed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Spawns the node's long-lived background tasks, and restarts them as per their
+/// `RestartPolicy` when they panic or exit early. Also counts the tasks of each subsystem,
+/// whether supervised or spawned through `spawn_counted`, warning of those blocking their
+/// executor thread.
+#[derive(Clone, Default)]
+pub(crate) struct TaskSupervisor {
+    tasks: Arc<RwLock<BTreeMap<&'static str, BackgroundTaskStatus>>>,
+    // Set once any task dies without being restarted.
+    failed: Arc<AtomicBool>,
+    counters: Arc<std::sync::RwLock<BTreeMap<&'static str, Arc<TaskCounters>>>>,
+}
+
+impl TaskSupervisor {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns a task built by `make_task`, calling it again for every restart.
+    pub(crate) async fn spawn<F, Fut>(
+        &self,
+        name: &'static str,
+        policy: RestartPolicy,
+        make_task: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let _prev = self.tasks.write().await.insert(
+            name,
+            BackgroundTaskStatus {
+                name,
+                policy,
+                status: TaskStatus::Running,
+                restarts: 0,
+                last_exit: None,
+            },
+        );
+
+        let supervisor = self.clone();
+        let _handle = tokio::spawn(async move {
+            let mut consecutive_exits = 0;
+            loop {
+                let started = Instant::now();
+                let exit = match supervisor.spawn_counted(name, make_task()).await {
+                    Ok(()) => TaskExit::Returned,
+                    Err(error) => TaskExit::from(error),
+                };
+                error!("Background task {:?} exited unexpectedly: {}", name, exit);
+
+                let delay = match policy {
+                    RestartPolicy::Always => Some(Duration::ZERO),
+                    RestartPolicy::Never => None,
+                    RestartPolicy::WithBackoff {
+                        initial_delay,
+                        max_delay,
+                        max_restarts,
+                    } => {
+                        if started.elapsed() >= max_delay {
+                            consecutive_exits = 0;
+                        }
+                        if consecutive_exits >= max_restarts {
+                            None
+                        } else {
+                            let factor = 2u32.saturating_pow(consecutive_exits as u32);
+                            Some(initial_delay.saturating_mul(factor).min(max_delay))
+                        }
+                    }
+                };
+                consecutive_exits += 1;
+
+                let delay = if let Some(delay) = delay {
+                    delay
+                } else {
+                    let status = if policy == RestartPolicy::Never {
+                        TaskStatus::Dead
+                    } else {
+                        TaskStatus::GaveUp
+                    };
+                    error!(
+                        "Background task {:?} will not be restarted ({:?})",
+                        name, status
+                    );
+                    supervisor.failed.store(true, Ordering::SeqCst);
+                    supervisor.record_exit(name, status, exit).await;
+                    break;
+                };
+
+                supervisor
+                    .record_exit(name, TaskStatus::Restarting, exit)
+                    .await;
+                sleep(delay).await;
+                info!("Restarting background task {:?}", name);
+                supervisor.record_restart(name).await;
+            }
+        });
+    }
+
+    /// Spawns a one-off task, counted against `subsystem`.
+    pub(crate) fn spawn_counted<Fut>(
+        &self,
+        subsystem: &'static str,
+        task: Fut,
+    ) -> JoinHandle<Fut::Output>
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let counters = self.counters(subsystem);
+        let _prev = counters.spawned.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(CountedTask {
+            subsystem,
+            counters,
+            task: Box::pin(task),
+        })
+    }
+
+    fn counters(&self, subsystem: &'static str) -> Arc<TaskCounters> {
+        if let Some(counters) = self
+            .counters
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(subsystem)
+        {
+            return counters.clone();
+        }
+        self.counters
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(subsystem)
+            .or_default()
+            .clone()
+    }
+
+    /// Counts of the tasks of every subsystem which spawned any, ordered by subsystem.
+    pub(crate) fn task_counts(&self) -> BTreeMap<&'static str, TaskCounts> {
+        self.counters
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|(subsystem, counters)| {
+                let counts = TaskCounts {
+                    spawned: counters.spawned.load(Ordering::Relaxed),
+                    completed: counters.completed.load(Ordering::Relaxed),
+                    long_polls: counters.long_polls.load(Ordering::Relaxed),
+                };
+                (*subsystem, counts)
+            })
+            .collect()
+    }
+
+    async fn record_exit(&self, name: &'static str, status: TaskStatus, exit: TaskExit) {
+        if let Some(task) = self.tasks.write().await.get_mut(name) {
+            task.status = status;
+            task.last_exit = Some(exit);
+        }
+    }
+
+    async fn record_restart(&self, name: &'static str) {
+        if let Some(task) = self.tasks.write().await.get_mut(name) {
+            task.status = TaskStatus::Running;
+            task.restarts += 1;
+        }
+    }
+
+    /// Snapshot of every supervised task, ordered by name.
+    pub(crate) async fn statuses(&self) -> Vec<BackgroundTaskStatus> {
+        self.tasks.read().await.values().cloned().collect()
+    }
+
+    /// Whether any task has died and will not be restarted.
+    pub(crate) fn has_failed_task(&self) -> bool {
+        self.failed.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::time::timeout;
+
+    async fn wait_for<F>(supervisor: &TaskSupervisor, name: &str, mut cond: F) -> Result<()>
+    where
+        F: FnMut(&BackgroundTaskStatus) -> bool,
+    {
+        timeout(Duration::from_secs(5), async {
+            loop {
+                let statuses = supervisor.statuses().await;
+                if statuses.iter().any(|task| task.name == name && cond(task)) {
+                    break;
+                }
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn task_panicking_twice_is_restarted_with_backoff() -> Result<()> {
+        let supervisor = TaskSupervisor::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let initial_delay = Duration::from_millis(50);
+        let policy = RestartPolicy::WithBackoff {
+            initial_delay,
+            max_delay: Duration::from_secs(10),
+            max_restarts: 5,
+        };
+
+        let started = Instant::now();
+        let task_runs = runs.clone();
+        supervisor
+            .spawn("flaky", policy, move || {
+                let runs = task_runs.clone();
+                async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                        panic!("flaky failure");
+                    }
+                    // Healthy from now on.
+                    futures::future::pending::<()>().await
+                }
+            })
+            .await;
+
+        wait_for(&supervisor, "flaky", |task| {
+            task.restarts == 2 && task.status == TaskStatus::Running
+        })
+        .await?;
+
+        // First restart waits `initial_delay`, the second twice that.
+        assert!(started.elapsed() >= initial_delay * 3);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        let statuses = supervisor.statuses().await;
+        assert_eq!(
+            statuses[0].last_exit,
+            Some(TaskExit::Panicked("flaky failure".to_string()))
+        );
+        assert!(!supervisor.has_failed_task());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn never_restarted_task_dying_flags_failure() -> Result<()> {
+        let supervisor = TaskSupervisor::new();
+
+        supervisor
+            .spawn("one-shot", RestartPolicy::Never, || async {
+                panic!("fatal failure")
+            })
+            .await;
+
+        wait_for(&supervisor, "one-shot", |task| {
+            task.status == TaskStatus::Dead
+        })
+        .await?;
+
+        assert!(supervisor.has_failed_task());
+        let statuses = supervisor.statuses().await;
+        assert_eq!(statuses[0].restarts, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn supervisor_gives_up_after_max_restarts() -> Result<()> {
+        let supervisor = TaskSupervisor::new();
+        let policy = RestartPolicy::WithBackoff {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_secs(10),
+            max_restarts: 3,
+        };
+
+        supervisor.spawn("doomed", policy, || async {}).await;
+
+        wait_for(&supervisor, "doomed", |task| {
+            task.status == TaskStatus::GaveUp
+        })
+        .await?;
+
+        assert!(supervisor.has_failed_task());
+        let statuses = supervisor.statuses().await;
+        assert_eq!(statuses[0].restarts, 3);
+        assert_eq!(statuses[0].last_exit, Some(TaskExit::Returned));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn task_counts_follow_tasks_of_each_subsystem() -> Result<()> {
+        let supervisor = TaskSupervisor::new();
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        supervisor.spawn_counted("quick", async {}).await?;
+        let waiting = supervisor.spawn_counted("waiting", released);
+        supervisor
+            .spawn("supervised", RestartPolicy::Never, || {
+                futures::future::pending::<()>()
+            })
+            .await;
+
+        let counts = supervisor.task_counts();
+        let count = |subsystem| counts.get(subsystem).copied().unwrap_or_default();
+        assert_eq!(count("quick").spawned, 1);
+        assert_eq!(count("quick").completed, 1);
+        assert_eq!(count("waiting").spawned, 1);
+        assert_eq!(count("waiting").completed, 0);
+        // Supervised tasks are counted under their name too.
+        timeout(Duration::from_secs(5), async {
+            while !supervisor.task_counts().contains_key("supervised") {
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await?;
+        assert_eq!(supervisor.task_counts()["supervised"].spawned, 1);
+
+        release
+            .send(())
+            .map_err(|_| eyre::eyre!("task dropped its receiver"))?;
+        waiting.await??;
+        assert_eq!(supervisor.task_counts()["waiting"].completed, 1);
+        assert!(supervisor
+            .task_counts()
+            .values()
+            .all(|counts| counts.long_polls == 0));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn blocking_poll_is_detected() -> Result<()> {
+        let supervisor = TaskSupervisor::new();
+
+        supervisor
+            .spawn_counted("blocking", async {
+                std::thread::sleep(LONG_POLL_THRESHOLD * 2);
+            })
+            .await?;
+        supervisor
+            .spawn_counted("blocking", sleep(LONG_POLL_THRESHOLD * 2))
+            .await?;
+
+        // Only the poll blocking the thread counts, not the task merely taking long.
+        let counts = supervisor.task_counts()["blocking"];
+        assert_eq!(counts.spawned, 2);
+        assert_eq!(counts.completed, 2);
+        assert_eq!(counts.long_polls, 1);
+
+        Ok(())
+    }
+}
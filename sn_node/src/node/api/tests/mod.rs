@@ -8,27 +8,29 @@
 
 #![allow(dead_code, unused_imports)]
 
-use super::{Cmd, Comm, Dispatcher};
+use super::{Cmd, Comm, Dispatcher, MembershipEvents};
 
 use crate::dbs::UsedSpace;
 use crate::init_test_logger;
+use crate::membership_history::{self, MembershipSnapshot};
 use crate::node::{
     core::{
-        relocation_check, ChurnId, MsgEvent, Node, Proposal, RESOURCE_PROOF_DATA_SIZE,
-        RESOURCE_PROOF_DIFFICULTY,
+        relocation_check, solve_memory_proof, ChurnId, MsgEvent, Node, Proposal, ProposalBatch,
+        DEFAULT_RELOCATION_VALIDITY,
     },
     create_test_max_capacity_and_root_storage,
     messages::WireMsgUtils,
-    Error, Event, Result as RoutingResult,
+    Error, Event, MembershipEvent, Result as RoutingResult,
 };
 use sn_interface::elder_count;
 use sn_interface::messaging::{
+    serialisation::MESSAGING_PROTO_VERSION,
     system::{
-        JoinAsRelocatedRequest, JoinRequest, JoinResponse, KeyedSig, MembershipState,
-        NodeMsgAuthorityUtils, NodeState as NodeStateMsg, RelocateDetails, ResourceProofResponse,
-        SectionAuth, SystemMsg,
+        JoinAsRelocatedRequest, JoinAsRelocatedResponse, JoinRejectionReason, JoinRequest,
+        JoinResponse, KeyedSig, MembershipState, NodeMsgAuthorityUtils, NodeState as NodeStateMsg,
+        RelocateDetails, ResourceProofResponse, SectionAuth, SystemMsg,
     },
-    AuthKind, AuthorityProof, DstLocation, MsgId, MsgType, NodeAuth,
+    AuthKind, AuthorityProof, DstLocation, MsgId, MsgType, NodeAuth, ProtocolVersions,
     SectionAuth as MsgKindSectionAuth, WireMsg,
 };
 #[cfg(feature = "test-utils")]
@@ -39,6 +41,7 @@ use sn_interface::network_knowledge::{
     SectionAuthorityProvider, SectionKeyShare, FIRST_SECTION_MAX_AGE, FIRST_SECTION_MIN_AGE,
     MIN_ADULT_AGE,
 };
+use sn_interface::protocol_constants::ProtocolConstants;
 #[cfg(feature = "test-utils")]
 use sn_interface::types::{keyed_signed, SecretKeySet};
 
@@ -60,6 +63,7 @@ use std::{
     net::Ipv4Addr,
     ops::Deref,
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use tempfile::tempdir;
 use tokio::{
@@ -108,6 +112,8 @@ async fn receive_join_request_without_resource_proof_response() -> Result<()> {
         SystemMsg::JoinRequest(Box::new(JoinRequest {
             section_key,
             resource_proof_response: None,
+            protocol_digest: ProtocolConstants::current().digest(),
+            protocol_versions: ProtocolVersions::ours(),
         })),
         section_key,
     )?;
@@ -179,16 +185,21 @@ async fn membership_churn_starts_on_join_request_with_resource_proof() -> Result
     );
 
     let nonce: [u8; 32] = rand::random();
-    let serialized = bincode::serialize(&(new_node.name(), nonce))?;
+    let params = dispatcher.node.join_pressure.issue();
+    let serialized = params.signed_payload(&new_node.name(), &nonce)?;
     let nonce_signature = ed25519::sign(&serialized, &dispatcher.node.info.read().await.keypair);
 
-    let rp = ResourceProof::new(RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY);
+    let rp = ResourceProof::new(params.data_size, params.difficulty);
     let data = rp.create_proof_data(&nonce);
     let mut prover = rp.create_prover(data.clone());
     let solution = prover.solve();
     let resource_proof_response = ResourceProofResponse {
         solution,
         data,
+        difficulty: params.difficulty,
+        memory_kib: params.memory_kib,
+        memory_difficulty: params.memory_difficulty,
+        memory_solution: solve_memory_proof(&nonce, params.memory_kib, params.memory_difficulty),
         nonce,
         nonce_signature,
     };
@@ -201,6 +212,8 @@ async fn membership_churn_starts_on_join_request_with_resource_proof() -> Result
         SystemMsg::JoinRequest(Box::new(JoinRequest {
             section_key,
             resource_proof_response: Some(resource_proof_response.clone()),
+            protocol_digest: ProtocolConstants::current().digest(),
+            protocol_versions: ProtocolVersions::ours(),
         })),
         section_key,
     )?;
@@ -229,32 +242,3239 @@ async fn membership_churn_starts_on_join_request_with_resource_proof() -> Result
     assert!(
         !dispatcher
             .node
-            .validate_resource_proof_response(&random_peer.name(), resource_proof_response)
+            .validate_resource_proof_response(&random_peer.name(), resource_proof_response.clone())
             .await
     );
+    // ...and covers what the challenge takes to solve, for it not to be made easier
+    let easier = ResourceProofResponse {
+        memory_kib: params.memory_kib / 2,
+        ..resource_proof_response
+    };
+    assert!(
+        !dispatcher
+            .node
+            .validate_resource_proof_response(&new_node.name(), easier)
+            .await
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn membership_churn_starts_on_join_request_from_relocated_node() -> Result<()> {
+    init_test_logger();
+    let _span = tracing::info_span!("receive_join_request_from_relocated_node").entered();
+
+    let (section_auth, mut nodes, sk_set) = create_section_auth();
+
+    let pk_set = sk_set.public_keys();
+    let section_key = pk_set.public_key();
+
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let node = nodes.remove(0);
+    let relocated_node_old_name = node.name();
+    let relocated_node_old_keypair = node.keypair.clone();
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let relocated_node = NodeInfo::new(
+        ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE + 1),
+        gen_addr(),
+    );
+    let relocate_details = RelocateDetails {
+        previous_name: relocated_node_old_name,
+        dst: relocated_node_old_name,
+        dst_section_key: section_key,
+        age: relocated_node.age(),
+        issued_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        key_generation: dispatcher.node.network_knowledge().chain_len().await,
+        churn_id: None,
+    };
+
+    let _ = send_join_as_relocated_request(
+        &dispatcher,
+        &sk_set,
+        &relocated_node,
+        &relocated_node_old_keypair,
+        relocate_details,
+    )
+    .await?;
+
+    assert!(dispatcher
+        .node
+        .membership
+        .read()
+        .await
+        .as_ref()
+        .unwrap()
+        .is_churn_in_progress());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn join_request_with_stale_or_used_relocation_is_rejected() -> Result<()> {
+    init_test_logger();
+    let _span = tracing::info_span!("join_request_with_stale_or_used_relocation").entered();
+
+    let (section_auth, mut nodes, sk_set) = create_section_auth();
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let node = nodes.remove(0);
+    let relocated_node_old_name = node.name();
+    let relocated_node_old_keypair = node.keypair.clone();
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let relocated_node = NodeInfo::new(
+        ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE + 1),
+        gen_addr(),
+    );
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let fresh = RelocateDetails {
+        previous_name: relocated_node_old_name,
+        dst: relocated_node_old_name,
+        dst_section_key: section_key,
+        age: relocated_node.age(),
+        issued_at: now,
+        key_generation: dispatcher.node.network_knowledge().chain_len().await,
+        churn_id: None,
+    };
+
+    // Issued before the validity window.
+    let issued_at = now - DEFAULT_RELOCATION_VALIDITY.as_secs() - 1;
+    let stale = RelocateDetails {
+        issued_at,
+        ..fresh.clone()
+    };
+    let cmds = send_join_as_relocated_request(
+        &dispatcher,
+        &sk_set,
+        &relocated_node,
+        &relocated_node_old_keypair,
+        stale,
+    )
+    .await?;
+    assert_eq!(
+        relocation_rejection(cmds),
+        Some(JoinRejectionReason::RelocationExpired { issued_at })
+    );
+
+    // Claimed to be drawn on a churn which doesn't send the node here.
+    let undrawn = RelocateDetails {
+        churn_id: Some(vec![0; 96]),
+        ..fresh.clone()
+    };
+    let cmds = send_join_as_relocated_request(
+        &dispatcher,
+        &sk_set,
+        &relocated_node,
+        &relocated_node_old_keypair,
+        undrawn,
+    )
+    .await?;
+    assert_eq!(
+        relocation_rejection(cmds),
+        Some(JoinRejectionReason::RelocationNotDrawn)
+    );
+
+    // Fresh, it's accepted.
+    let cmds = send_join_as_relocated_request(
+        &dispatcher,
+        &sk_set,
+        &relocated_node,
+        &relocated_node_old_keypair,
+        fresh.clone(),
+    )
+    .await?;
+    assert_eq!(relocation_rejection(cmds), None);
+    assert!(dispatcher
+        .node
+        .membership
+        .read()
+        .await
+        .as_ref()
+        .unwrap()
+        .is_churn_in_progress());
+
+    // But not replayed from elsewhere.
+    let replayer = NodeInfo {
+        addr: gen_addr(),
+        ..relocated_node.clone()
+    };
+    let cmds = send_join_as_relocated_request(
+        &dispatcher,
+        &sk_set,
+        &replayer,
+        &relocated_node_old_keypair,
+        fresh,
+    )
+    .await?;
+    assert_eq!(
+        relocation_rejection(cmds),
+        Some(JoinRejectionReason::RelocationAlreadyUsed)
+    );
+
+    Ok(())
+}
+
+// Has `dispatcher` handle a request from `relocated_node` to join as relocated with the details,
+// signed by the section of `sk_set`.
+async fn send_join_as_relocated_request(
+    dispatcher: &Dispatcher,
+    sk_set: &SecretKeySet,
+    relocated_node: &NodeInfo,
+    old_keypair: &ed25519_dalek::Keypair,
+    relocate_details: RelocateDetails,
+) -> Result<Vec<Cmd>> {
+    let section_key = sk_set.public_keys().public_key();
+    let old_name = relocate_details.previous_name;
+    let node_state = NodeState::relocated(relocated_node.peer(), Some(old_name), relocate_details);
+    let relocate_proof = section_signed(sk_set.secret_key(), node_state.to_msg())?;
+
+    let signature_over_new_name = ed25519::sign(&relocated_node.name().0, old_keypair);
+
+    let wire_msg = WireMsg::single_src(
+        relocated_node,
+        DstLocation::Section {
+            name: XorName::from(PublicKey::Bls(section_key)),
+            section_pk: section_key,
+        },
+        SystemMsg::JoinAsRelocatedRequest(Box::new(JoinAsRelocatedRequest {
+            section_key,
+            relocate_proof,
+            signature_over_new_name,
+        })),
+        section_key,
+    )?;
+
+    Ok(dispatcher
+        .process_cmd(
+            Cmd::HandleMsg {
+                sender: relocated_node.peer(),
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+        .await?)
+}
+
+// The rejection of a join as relocated among the cmds, if any.
+fn relocation_rejection(cmds: Vec<Cmd>) -> Option<JoinRejectionReason> {
+    cmds.into_iter().find_map(|cmd| match cmd {
+        Cmd::SendMsg { wire_msg, .. } => match wire_msg.into_msg() {
+            Ok(MsgType::System {
+                msg: SystemMsg::JoinAsRelocatedResponse(response),
+                ..
+            }) => match *response {
+                JoinAsRelocatedResponse::Rejected(reason) => Some(reason),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+// Whether `cmd` is an adult telling its elders of the replicas it stored.
+fn is_replicas_stored(cmd: &Cmd) -> bool {
+    use sn_interface::messaging::system::NodeEvent;
+
+    matches!(cmd, Cmd::SendMsg { wire_msg, .. } if matches!(
+        wire_msg.clone().into_msg(),
+        Ok(MsgType::System {
+            msg: SystemMsg::NodeEvent(NodeEvent::ReplicasStored { .. }),
+            ..
+        })
+    ))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handle_agreement_on_online() -> Result<()> {
+    let (event_tx, mut event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+
+    let prefix = Prefix::default();
+
+    let (section_auth, mut nodes, sk_set) = gen_section_authority_provider(prefix, elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let node = nodes.remove(0);
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        node,
+        section,
+        Some(section_key_share),
+        event_tx,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let new_peer = create_peer(MIN_ADULT_AGE);
+
+    let status = handle_online_cmd(&new_peer, &sk_set, &dispatcher, &section_auth).await?;
+    assert!(status.node_approval_sent);
+
+    assert_matches!(event_rx.recv().await, Some(Event::MemberJoined { name, age, .. }) => {
+        assert_eq!(name, new_peer.name());
+        assert_eq!(age, MIN_ADULT_AGE);
+    });
+
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics_endpoint_serves_node_metrics_and_health() -> Result<()> {
+    use super::metrics;
+    use std::{net::SocketAddr, sync::Arc};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    async fn get(addr: SocketAddr, path: &str) -> Result<String> {
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, addr
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        let _len = stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+
+    let peer = create_peer(MIN_ADULT_AGE);
+    let _status = handle_online_cmd(&peer, &sk_set, &dispatcher, &section_auth).await?;
+
+    let addr = metrics::serve(&dispatcher, "127.0.0.1:0".parse()?)?;
+
+    let response = get(addr, "/metrics").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    for line in [
+        "sn_node_is_elder 1",
+        "sn_node_storage_healthy 1",
+        "sn_node_ready_read 1",
+        "sn_node_ready_write 1",
+        &format!("sn_node_storage_capacity_bytes {}", max_capacity),
+        "sn_node_cmd_duration_seconds_count{kind=\"HandleNewNodeOnline\"} 1",
+        "# TYPE sn_node_client_msgs_rejected_total counter",
+    ] {
+        assert!(
+            response.lines().any(|l| l == line),
+            "{} missing from:\n{}",
+            line,
+            response
+        );
+    }
+
+    for path in ["/health", "/health/read", "/health/write"] {
+        let response = get(addr, path).await?;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("ready: true\n"), "{}", response);
+    }
+
+    let response = get(addr, "/nothing").await?;
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn adult_restarting_within_its_window_is_spared_until_it_runs_out() -> Result<()> {
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData, ReplicatedDataAddress};
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let our_name = node.info.read().await.name();
+    let dispatcher = Dispatcher::new(node);
+
+    // Enough adults for one of them to stand out from the mean as dysfunctional.
+    let adults: Vec<_> = (0..15).map(|_| gen_info(MIN_ADULT_AGE, None)).collect();
+    for adult in &adults {
+        let _status = handle_online_cmd(&adult.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    }
+    let adult = &adults[0];
+    let adult_name = adult.name();
+
+    let send_from_adult = |msg| -> Result<Cmd> {
+        let wire_msg = WireMsg::single_src(
+            adult,
+            DstLocation::Node {
+                name: our_name,
+                section_pk: section_key,
+            },
+            SystemMsg::NodeCmd(msg),
+            section_key,
+        )?;
+        Ok(Cmd::HandleMsg {
+            sender: adult.peer(),
+            wire_msg,
+            original_bytes: None,
+        })
+    };
+    let announce_restart = |within| {
+        send_from_adult(NodeCmd::PrepareRestart {
+            node_id: PublicKey::from(adult.keypair.public),
+            section: adult_name,
+            within,
+        })
+    };
+    // The adult going unreachable while it restarts.
+    let go_away = || async {
+        for _ in 0..100 {
+            dispatcher.node.log_comm_issue(adult_name).await?;
+        }
+        Result::<()>::Ok(())
+    };
+
+    let _cmds = dispatcher
+        .process_cmd(announce_restart(Duration::from_secs(60))?, "cmd-id")
+        .await?;
+    go_away().await?;
+
+    // Neither proposed offline, nor suspected and replicated away from...
+    assert!(dispatcher
+        .node
+        .get_dysfunctional_node_names()
+        .await?
+        .is_empty());
+    assert!(dispatcher
+        .process_cmd(Cmd::ProposeOffline(BTreeSet::from([adult_name])), "cmd-id")
+        .await?
+        .is_empty());
+    assert!(dispatcher
+        .node
+        .notify_about_newly_suspect_nodes()
+        .await?
+        .is_empty());
+
+    // ...nor given new data to hold, even where it'd otherwise be a holder.
+    let all_adults = adults.iter().map(|info| info.name()).collect();
+    let chunk = iter::repeat_with(|| Chunk::new(random_bytes(100)))
+        .find(|chunk| {
+            let address = ReplicatedDataAddress::Chunk(*chunk.address());
+            dispatcher
+                .node
+                .compute_holders(&address, &all_adults)
+                .contains(&adult_name)
+        })
+        .ok_or_else(|| eyre!("no chunk held by the adult"))?;
+    for cmd in dispatcher
+        .node
+        .replicate_data(ReplicatedData::Chunk(chunk))
+        .await?
+    {
+        if let Cmd::SendMsg { recipients, .. } = cmd {
+            assert!(recipients.iter().all(|peer| peer.name() != adult_name));
+        }
+    }
+
+    // Back in time, its absence is forgiven.
+    let _cmds = dispatcher
+        .process_cmd(
+            send_from_adult(NodeCmd::RecordStorageHealth {
+                node_id: PublicKey::from(adult.keypair.public),
+                section: adult_name,
+                healthy: true,
+            })?,
+            "cmd-id",
+        )
+        .await?;
+    dispatcher.node.expire_restart_windows().await;
+    assert!(dispatcher
+        .node
+        .get_dysfunctional_node_names()
+        .await?
+        .is_empty());
+
+    // Not back in time, its absence counts as usual.
+    let _cmds = dispatcher
+        .process_cmd(announce_restart(Duration::from_millis(500))?, "cmd-id")
+        .await?;
+    go_away().await?;
+    assert!(dispatcher
+        .node
+        .get_dysfunctional_node_names()
+        .await?
+        .is_empty());
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    dispatcher.node.expire_restart_windows().await;
+    assert_eq!(
+        dispatcher.node.get_dysfunctional_node_names().await?,
+        BTreeSet::from([adult_name])
+    );
+    assert!(!dispatcher
+        .process_cmd(Cmd::ProposeOffline(BTreeSet::from([adult_name])), "cmd-id")
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn member_shutting_down_is_proposed_offline_even_within_a_restart_window() -> Result<()> {
+    use sn_interface::messaging::system::NodeCmd;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let our_name = node.info.read().await.name();
+    let dispatcher = Dispatcher::new(node);
+
+    let adult = gen_info(MIN_ADULT_AGE, None);
+    let _status = handle_online_cmd(&adult.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    let send = |sender: &NodeInfo, msg| -> Result<Cmd> {
+        let wire_msg = WireMsg::single_src(
+            sender,
+            DstLocation::Node {
+                name: our_name,
+                section_pk: section_key,
+            },
+            SystemMsg::NodeCmd(msg),
+            section_key,
+        )?;
+        Ok(Cmd::HandleMsg {
+            sender: sender.peer(),
+            wire_msg,
+            original_bytes: None,
+        })
+    };
+    let leaving = NodeCmd::Leaving {
+        node_id: PublicKey::from(adult.keypair.public),
+        section: adult.name(),
+    };
+    let proposed_offline = |cmds: Vec<Cmd>| {
+        cmds.into_iter().any(|cmd| match cmd {
+            Cmd::SendMsg { wire_msg, .. } => matches!(
+                wire_msg.into_msg(),
+                Ok(MsgType::System {
+                    msg: SystemMsg::Propose {
+                        proposal: sn_interface::messaging::system::Proposal::Offline(node_state),
+                        ..
+                    },
+                    ..
+                }) if node_state.name == adult.name()
+                    && node_state.state == MembershipState::Left
+            ),
+            _ => false,
+        })
+    };
+
+    // Only the member itself can say it's leaving.
+    let impostor = gen_info(MIN_ADULT_AGE, None);
+    let cmds = dispatcher
+        .process_cmd(send(&impostor, leaving.clone())?, "cmd-id")
+        .await?;
+    assert!(!proposed_offline(cmds));
+
+    let _cmds = dispatcher
+        .process_cmd(
+            send(
+                &adult,
+                NodeCmd::PrepareRestart {
+                    node_id: PublicKey::from(adult.keypair.public),
+                    section: adult.name(),
+                    within: Duration::from_secs(60),
+                },
+            )?,
+            "cmd-id",
+        )
+        .await?;
+    let cmds = dispatcher
+        .process_cmd(send(&adult, leaving)?, "cmd-id")
+        .await?;
+    assert!(proposed_offline(cmds));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_stops_taking_client_msgs_then_ends_the_event_stream() -> Result<()> {
+    use super::event_stream::EventStream;
+    use std::sync::Arc;
+
+    let (section_auth, _nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, _section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let (event_tx, event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let adult = Node::new(
+        create_comm().await?,
+        gen_info(MIN_ADULT_AGE, None),
+        section,
+        None,
+        event_tx,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(adult));
+    let mut event_stream = EventStream::new(event_rx, dispatcher.shutdown.subscribe());
+    assert!(!dispatcher.shutdown.is_draining());
+
+    let shutting_down = tokio::spawn(dispatcher.clone().shut_down(Duration::from_secs(2)));
+    // A second shutdown waits for the first.
+    dispatcher.clone().shut_down(Duration::from_secs(2)).await?;
+    assert!(dispatcher.shutdown.is_draining());
+    shutting_down.await??;
+
+    // The node still holds its event sender, so the stream ended as we shut down.
+    assert!(timeout(Duration::from_secs(1), event_stream.next())
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn adult_shutting_down_for_its_restart_resumes_from_its_state_snapshot() -> Result<()> {
+    use crate::node::cfg::keypair_storage::store_network_keypair;
+    use std::sync::Arc;
+
+    let (section_auth, _nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, _section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let genesis_key = *section.genesis_key();
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let (event_tx, _event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let info = gen_info(MIN_ADULT_AGE, None);
+    let adult = Node::new(
+        create_comm().await?,
+        info.clone(),
+        section,
+        None,
+        event_tx,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    store_network_keypair(&root_storage_dir, info.keypair.to_bytes(), false).await?;
+    let dispatcher = Arc::new(Dispatcher::new(adult));
+
+    let _cmds = dispatcher
+        .node
+        .prepare_restart(Duration::from_secs(60), false)
+        .await?;
+    dispatcher.clone().shut_down(Duration::from_secs(2)).await?;
+
+    let (resumed, network_knowledge) =
+        super::resumable_state(&dispatcher.node.comm, genesis_key, &root_storage_dir)
+            .await?
+            .ok_or_else(|| eyre!("adult can't resume from its state snapshot"))?;
+    assert_eq!(resumed.name(), info.name());
+    assert_eq!(
+        network_knowledge.section_key().await,
+        sk_set.public_keys().public_key()
+    );
+    assert_eq!(
+        network_knowledge.section_members().await.len(),
+        elder_count()
+    );
+
+    // The snapshot is used once only.
+    assert!(
+        super::resumable_state(&dispatcher.node.comm, genesis_key, &root_storage_dir)
+            .await?
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn promoted_elder_rebuilds_its_holder_registry_in_paced_slices() -> Result<()> {
+    use sn_interface::messaging::system::NodeCmd;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let our_name = node.info.read().await.name();
+    let dispatcher = Dispatcher::new(node);
+
+    let adults: Vec<_> = (0..3).map(|_| gen_info(MIN_ADULT_AGE, None)).collect();
+    for adult in &adults {
+        let _status = handle_online_cmd(&adult.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    }
+    let holder = &adults[0];
+    let held: XorName = xor_name::rand::random();
+
+    let budget = 4;
+    dispatcher.node.holder_registry.set_slices_per_tick(budget);
+    dispatcher.node.start_holder_registry_rebuild().await;
+
+    let mut ticks = 0;
+    let mut last_slice: Option<Prefix> = None;
+    while dispatcher.node.holder_registry.is_rebuilding().await {
+        ticks += 1;
+        assert!(
+            ticks <= 64 / budget,
+            "rebuild took more than {} ticks",
+            ticks
+        );
+
+        // A budget's worth of slices, each requested of every adult, following on in prefix
+        // order from the previous tick's.
+        let mut requests = BTreeMap::<Prefix, BTreeSet<XorName>>::new();
+        for cmd in dispatcher.node.request_next_inventory_slices().await? {
+            let (recipients, wire_msg) = match cmd {
+                Cmd::SendMsgDeliveryGroup {
+                    recipients,
+                    wire_msg,
+                    ..
+                } => (recipients, wire_msg),
+                _ => continue,
+            };
+            let slice = match wire_msg.into_msg() {
+                Ok(MsgType::System {
+                    msg: SystemMsg::NodeCmd(NodeCmd::SendInventory { slice }),
+                    ..
+                }) => slice,
+                _ => continue,
+            };
+            requests
+                .entry(slice)
+                .or_default()
+                .extend(recipients.iter().map(|peer| peer.name()));
+        }
+        assert_eq!(requests.len(), budget);
+        assert!(requests.values().all(|asked| asked.len() == adults.len()));
+        let first = *requests.keys().next().ok_or_else(|| eyre!("no slices"))?;
+        if let Some(last) = last_slice {
+            assert!(last.lower_bound() < first.lower_bound());
+        }
+        last_slice = requests.keys().last().copied();
+
+        let covers_held = requests.keys().any(|slice| slice.matches(&held));
+        if covers_held {
+            assert_eq!(
+                dispatcher.node.holder_registry.holders_of(&held).await,
+                None
+            );
+        }
+
+        for (slice, _) in requests {
+            for adult in &adults {
+                let names = if adult.name() == holder.name() && slice.matches(&held) {
+                    BTreeSet::from([held])
+                } else {
+                    BTreeSet::new()
+                };
+                let wire_msg = WireMsg::single_src(
+                    adult,
+                    DstLocation::Node {
+                        name: our_name,
+                        section_pk: section_key,
+                    },
+                    SystemMsg::NodeCmd(NodeCmd::RecordInventory {
+                        node_id: PublicKey::from(adult.keypair.public),
+                        slice,
+                        names,
+                    }),
+                    section_key,
+                )?;
+                let _cmds = dispatcher
+                    .process_cmd(
+                        Cmd::HandleMsg {
+                            sender: adult.peer(),
+                            wire_msg,
+                            original_bytes: None,
+                        },
+                        "cmd-id",
+                    )
+                    .await?;
+            }
+        }
+
+        // Covered slices are used straight away.
+        let progress = usize::from(dispatcher.node.holder_registry.progress().await);
+        assert_eq!(progress, ticks * budget * 100 / 64);
+        if covers_held {
+            assert_eq!(
+                dispatcher.node.holder_registry.holders_of(&held).await,
+                Some(BTreeSet::from([holder.name()]))
+            );
+        }
+    }
+
+    assert_eq!(ticks, 64 / budget);
+    assert_eq!(dispatcher.node.holder_registry.progress().await, 100);
+    assert!(dispatcher
+        .node
+        .request_next_inventory_slices()
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn distribution_check_finds_and_repairs_a_misplaced_chunk() -> Result<()> {
+    use crate::node::core::{CheckOptions, CheckStatus, Severity};
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{
+        holders_for, utils::random_bytes, Chunk, ReplicatedData, ReplicatedDataAddress,
+    };
+
+    fn node_cmd(cmd: Cmd) -> Option<(Vec<Peer>, NodeCmd)> {
+        match cmd {
+            Cmd::SendMsgDeliveryGroup {
+                recipients,
+                wire_msg,
+                ..
+            } => match wire_msg.into_msg() {
+                Ok(MsgType::System {
+                    msg: SystemMsg::NodeCmd(cmd),
+                    ..
+                }) => Some((recipients, cmd)),
+                _ => None,
+            },
+            Cmd::SignOutgoingSystemMsg {
+                msg: SystemMsg::NodeCmd(cmd),
+                ..
+            } => Some((vec![], cmd)),
+            _ => None,
+        }
+    }
+
+    // Runs a check to completion, answering for the adults, and returns the repairs the elder
+    // asks for, as (source, addresses, target).
+    async fn check(
+        dispatcher: &Dispatcher,
+        adults: &BTreeMap<XorName, Node>,
+        repair: bool,
+    ) -> Result<Vec<(XorName, Vec<ReplicatedDataAddress>, XorName)>> {
+        let options = CheckOptions {
+            repair,
+            ..CheckOptions::default()
+        };
+        dispatcher.start_distribution_check(options).await?;
+        let (batch, names) = dispatcher
+            .node
+            .check_next_distribution_batch()
+            .await?
+            .into_iter()
+            .find_map(|cmd| match node_cmd(cmd) {
+                Some((_, NodeCmd::SendHeld { batch, names })) => Some((batch, names)),
+                _ => None,
+            })
+            .ok_or_else(|| eyre!("no batch sent"))?;
+
+        let mut repairs = vec![];
+        for (name, adult) in adults {
+            for cmd in adult.send_held(batch, names.clone(), *name).await? {
+                let held = match node_cmd(cmd) {
+                    Some((_, NodeCmd::RecordHeld { held, .. })) => held,
+                    _ => bail!("no answer from {:?}", name),
+                };
+                for cmd in dispatcher.node.record_held(*name, batch, held).await? {
+                    if let Some((recipients, NodeCmd::ReplicateDataTo { addresses, holder })) =
+                        node_cmd(cmd)
+                    {
+                        for source in recipients {
+                            repairs.push((source.name(), addresses.clone(), holder));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(repairs)
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let mut adults = BTreeMap::new();
+    let mut adult_roots = vec![];
+    for _ in 0..data_copy_count() + 2 {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let root = tempdir()?;
+        let adult = Node::new(
+            create_comm().await?,
+            info.clone(),
+            section.clone(),
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?;
+        adult_roots.push(root);
+        let _prev = adults.insert(info.name(), adult);
+    }
+    let adult_names = adults.keys().copied().collect::<BTreeSet<_>>();
+
+    // A chunk missing from one of its expected holders, held by another adult instead.
+    let data = ReplicatedData::Chunk(Chunk::new(random_bytes(100)));
+    let expected = holders_for(&data.name(), &adult_names, data_copy_count());
+    let missing = *expected.iter().next().ok_or_else(|| eyre!("no holders"))?;
+    let unexpected = *adult_names
+        .difference(&expected)
+        .next()
+        .ok_or_else(|| eyre!("no other adults"))?;
+    for holder in expected.iter().filter(|holder| **holder != missing) {
+        let _level = adults[holder].data_storage.store(&data).await?;
+    }
+    let _level = adults[&unexpected].data_storage.store(&data).await?;
+    dispatcher
+        .node
+        .holder_registry
+        .record_holders(data.name(), &expected)
+        .await?;
+
+    let repairs = check(&dispatcher, &adults, true).await?;
+    let report = dispatcher
+        .distribution_report()
+        .await
+        .ok_or_else(|| eyre!("no report"))?;
+    assert_eq!(report.status, CheckStatus::Done);
+    assert_eq!(report.checked, 1);
+    assert_eq!(report.misplacements.len(), 1);
+    let misplacement = &report.misplacements[0];
+    assert_eq!(misplacement.name, data.name());
+    assert_eq!(misplacement.severity, Severity::Misplaced);
+    assert_eq!(misplacement.expected_missing, BTreeSet::from([missing]));
+    assert_eq!(misplacement.unexpected, BTreeSet::from([unexpected]));
+    assert!(misplacement.repair_enqueued);
+
+    // The source of the repair, holding the chunk, replicates it to the expected holder.
+    assert_eq!(repairs.len(), 1);
+    let (source, addresses, target) = repairs[0].clone();
+    assert_eq!(addresses, vec![data.address()]);
+    assert_eq!(target, missing);
+    for cmd in adults[&source].replicate_data_to(addresses, target).await? {
+        match cmd {
+            Cmd::SignOutgoingSystemMsg {
+                msg: SystemMsg::NodeCmd(NodeCmd::ReplicateCheckedData(replicated)),
+                dst: DstLocation::Node { name, .. },
+            } if name == target => {
+                for (_, data) in replicated {
+                    let _level = adults[&target].data_storage.store(&data).await?;
+                }
+            }
+            _ => bail!("unexpected cmd"),
+        }
+    }
+
+    // All its expected holders have it now, with nothing left to repair.
+    assert!(check(&dispatcher, &adults, true).await?.is_empty());
+    let report = dispatcher
+        .distribution_report()
+        .await
+        .ok_or_else(|| eyre!("no report"))?;
+    assert_eq!(report.misplacements.len(), 1);
+    assert!(report.misplacements[0].expected_missing.is_empty());
+    assert!(!report.misplacements[0].repair_enqueued);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replication_job_restores_an_under_replicated_chunk() -> Result<()> {
+    use crate::node::core::{JobProgress, ReplicationTarget};
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{holders_for, utils::random_bytes, Chunk, ReplicatedData};
+
+    fn node_cmd(cmd: Cmd) -> Option<(Vec<Peer>, NodeCmd)> {
+        match cmd {
+            Cmd::SendMsgDeliveryGroup {
+                recipients,
+                wire_msg,
+                ..
+            } => match wire_msg.into_msg() {
+                Ok(MsgType::System {
+                    msg: SystemMsg::NodeCmd(cmd),
+                    ..
+                }) => Some((recipients, cmd)),
+                _ => None,
+            },
+            Cmd::SignOutgoingSystemMsg {
+                msg: SystemMsg::NodeCmd(cmd),
+                ..
+            } => Some((vec![], cmd)),
+            _ => None,
+        }
+    }
+
+    async fn progress(dispatcher: &Dispatcher, job: u64) -> Result<JobProgress> {
+        dispatcher
+            .replication_progress(job)
+            .await
+            .ok_or_else(|| eyre!("no job {}", job))
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let elder = node.info.read().await.name();
+    let dispatcher = Dispatcher::new(node);
+
+    let mut adults = BTreeMap::new();
+    let mut adult_roots = vec![];
+    for _ in 0..data_copy_count() + 2 {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let root = tempdir()?;
+        let adult = Node::new(
+            create_comm().await?,
+            info.clone(),
+            section.clone(),
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?;
+        adult_roots.push(root);
+        let _prev = adults.insert(info.name(), adult);
+    }
+    let adult_names = adults.keys().copied().collect::<BTreeSet<_>>();
+
+    // The registry is complete, with nothing to rebuild it from but what it's told below.
+    dispatcher
+        .node
+        .holder_registry
+        .start(Prefix::default(), BTreeSet::new())
+        .await?;
+
+    // A chunk two of its holders lost at once, as the registry has it.
+    let data = ReplicatedData::Chunk(Chunk::new(random_bytes(100)));
+    let expected = holders_for(&data.name(), &adult_names, data_copy_count());
+    let lost = expected.iter().take(2).copied().collect::<BTreeSet<_>>();
+    let survivors = expected.difference(&lost).copied().collect::<BTreeSet<_>>();
+    for holder in &survivors {
+        let _level = adults[holder].data_storage.store(&data).await?;
+    }
+    dispatcher
+        .node
+        .holder_registry
+        .record_holders(data.name(), &survivors)
+        .await?;
+
+    // Only what the registry has can be replicated.
+    let unknown = ReplicationTarget::Name(xor_name::rand::random());
+    assert!(dispatcher.start_replication_job(unknown).await.is_err());
+
+    let job = dispatcher
+        .start_replication_job(ReplicationTarget::Name(data.name()))
+        .await?;
+    let queued = progress(&dispatcher, job).await?;
+    assert_eq!(
+        (queued.queued, queued.transferred, queued.confirmed),
+        (2, 0, 0)
+    );
+    assert!(!queued.done);
+
+    // The survivor the elder picks replicates the chunk to the holders which lost it.
+    let mut transfers = 0;
+    for cmd in dispatcher.node.run_replication_jobs().await? {
+        if let Some((recipients, NodeCmd::ReplicateDataTo { addresses, holder })) = node_cmd(cmd) {
+            assert!(lost.contains(&holder));
+            assert_eq!(addresses, vec![data.address()]);
+            for source in recipients {
+                for cmd in adults[&source.name()]
+                    .replicate_data_to(addresses.clone(), holder)
+                    .await?
+                {
+                    match node_cmd(cmd) {
+                        Some((_, NodeCmd::ReplicateCheckedData(replicated))) => {
+                            for (_, data) in replicated {
+                                let _level = adults[&holder].data_storage.store(&data).await?;
+                            }
+                            transfers += 1;
+                        }
+                        _ => bail!("unexpected cmd"),
+                    }
+                }
+            }
+        }
+    }
+    assert_eq!(transfers, 2);
+    let transferred = progress(&dispatcher, job).await?;
+    assert_eq!(
+        (
+            transferred.queued,
+            transferred.transferred,
+            transferred.confirmed
+        ),
+        (0, 2, 0)
+    );
+
+    // The holders are asked whether they have it now, which they confirm.
+    let mut asked = BTreeSet::new();
+    for cmd in dispatcher.node.run_replication_jobs().await? {
+        if let Some((recipients, NodeCmd::SendHeld { batch, names })) = node_cmd(cmd) {
+            for holder in recipients {
+                let _new = asked.insert(holder.name());
+                for cmd in adults[&holder.name()]
+                    .send_held(batch, names.clone(), elder)
+                    .await?
+                {
+                    if let Some((_, NodeCmd::RecordHeld { held, .. })) = node_cmd(cmd) {
+                        let _cmds = dispatcher
+                            .node
+                            .record_held(holder.name(), batch, held)
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+    assert_eq!(asked, lost);
+    let confirmed = progress(&dispatcher, job).await?;
+    assert_eq!(
+        (
+            confirmed.queued,
+            confirmed.transferred,
+            confirmed.confirmed,
+            confirmed.failed
+        ),
+        (0, 0, 2, 0)
+    );
+    assert!(confirmed.done);
+    assert_eq!(
+        dispatcher
+            .node
+            .holder_registry
+            .holders_of(&data.name())
+            .await,
+        Some(expected.clone())
+    );
+    for holder in &expected {
+        assert!(adults[holder]
+            .data_storage
+            .get_for_replication(data.address())
+            .await
+            .is_ok());
+    }
+
+    // With every copy restored, a job over the whole section finds nothing left to do.
+    let whole = ReplicationTarget::Prefix {
+        prefix: Prefix::default(),
+        max: 10,
+    };
+    let job = dispatcher.start_replication_job(whole).await?;
+    let nothing = progress(&dispatcher, job).await?;
+    assert_eq!(
+        (nothing.queued, nothing.skipped, nothing.unsourced),
+        (0, 0, 0)
+    );
+    assert!(nothing.done);
+    assert!(dispatcher.node.run_replication_jobs().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn data_written_during_a_partition_is_reconciled_once_healed() -> Result<()> {
+    use crate::node::core::{
+        Fault, Partitions, LIVENESS_PROBE_INTERVAL, REPLICATION_AUDIT_INTERVAL,
+    };
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{holders_for, utils::random_bytes, Chunk, ReplicatedData};
+
+    // Delivers the node cmds sent by `from` between the in-process `nodes`, as long as its
+    // comm's partitions let them through, until none are left to deliver. Adults proposed
+    // offline are added to `proposed_offline`.
+    async fn deliver(
+        nodes: &BTreeMap<XorName, &Node>,
+        from: XorName,
+        cmds: Vec<Cmd>,
+        proposed_offline: &mut BTreeSet<XorName>,
+    ) -> Result<()> {
+        let mut pending = vec![(from, cmds)];
+        while let Some((from, cmds)) = pending.pop() {
+            let sender = nodes[&from];
+            for cmd in cmds {
+                let (recipients, node_cmd) = match cmd {
+                    Cmd::SendMsgDeliveryGroup {
+                        recipients,
+                        wire_msg,
+                        ..
+                    } => match wire_msg.into_msg()? {
+                        MsgType::System {
+                            msg: SystemMsg::NodeCmd(cmd),
+                            ..
+                        } => (recipients, cmd),
+                        _ => bail!("unexpected msg"),
+                    },
+                    Cmd::SignOutgoingSystemMsg {
+                        msg: SystemMsg::NodeCmd(cmd),
+                        dst: DstLocation::Node { name, .. },
+                    } => (vec![nodes[&name].info.read().await.peer()], cmd),
+                    Cmd::ProposeOffline(names) => {
+                        proposed_offline.extend(names);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                for recipient in recipients {
+                    if sender.comm.fault_towards(&recipient) == Some(Fault::Drop) {
+                        continue;
+                    }
+                    let node = nodes[&recipient.name()];
+                    let cmds = match node_cmd.clone() {
+                        NodeCmd::ReplicateCheckedData(data) => {
+                            node.store_checked_replicas(from, data).await?
+                        }
+                        NodeCmd::SendHeld { batch, names } => {
+                            node.send_held(batch, names, from).await?
+                        }
+                        NodeCmd::RecordHeld { batch, held, .. } => {
+                            node.record_held(from, batch, held).await?
+                        }
+                        NodeCmd::ReplicateDataTo { addresses, holder } => {
+                            node.replicate_data_to(addresses, holder).await?
+                        }
+                        NodeCmd::ProbeLiveness { probe, nonce, name } => {
+                            node.answer_liveness_probe(probe, nonce, name, from).await?
+                        }
+                        NodeCmd::RecordLiveness { probe, proof, .. } => {
+                            node.record_liveness(from, probe, proof).await?
+                        }
+                        NodeCmd::SendHoldingProofs {
+                            audit,
+                            nonce,
+                            names,
+                        } => node.send_holding_proofs(audit, nonce, names, from).await?,
+                        NodeCmd::RecordHoldingProofs { audit, proofs, .. } => {
+                            node.record_holding_proofs(from, audit, proofs).await?
+                        }
+                        cmd => bail!("unexpected cmd {:?}", cmd),
+                    };
+                    pending.push((recipient.name(), cmds));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let partitions = Partitions::default();
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let elder_info = nodes.remove(0);
+    let node = Node::new(
+        create_comm().await?.with_partitions(partitions.clone()),
+        elder_info.clone(),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let mut infos = vec![];
+    for _ in 0..data_copy_count() + 2 {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        infos.push(info);
+    }
+    // Each adult has its own knowledge of the section, as the elder's AE-Updates left it.
+    let members = section.section_signed_members().await;
+    let mut adults = BTreeMap::new();
+    let mut adult_roots = vec![];
+    for info in infos {
+        let (knowledge, _) = create_section(&sk_set, &section_auth).await?;
+        for member in &members {
+            let _updated = knowledge.update_member(member.clone()).await;
+        }
+        let root = tempdir()?;
+        let adult = Node::new(
+            create_comm().await?.with_partitions(partitions.clone()),
+            info.clone(),
+            knowledge,
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?;
+        adult_roots.push(root);
+        let _prev = adults.insert(info.name(), (info, adult));
+    }
+    let mut nodes = adults
+        .iter()
+        .map(|(name, (_, adult))| (*name, adult))
+        .collect::<BTreeMap<_, _>>();
+    let _prev = nodes.insert(elder_info.name(), &dispatcher.node);
+
+    // One of the holders of the chunk is cut off from the elder for 45s, as it's written.
+    let data = ReplicatedData::Chunk(Chunk::new(random_bytes(100)));
+    let adult_names = adults.keys().copied().collect::<BTreeSet<_>>();
+    let holders = holders_for(&data.name(), &adult_names, data_copy_count());
+    let cut_off = *holders.iter().next().ok_or_else(|| eyre!("no holders"))?;
+    let (cut_off_info, cut_off_node) = &adults[&cut_off];
+    // Nodes know each other by the made-up addresses they joined with, not their comms'.
+    partitions.set_group(
+        "elders",
+        [dispatcher.node.comm.our_connection_info(), elder_info.addr],
+    );
+    partitions.set_group(
+        "cut off",
+        [cut_off_node.comm.our_connection_info(), cut_off_info.addr],
+    );
+    let partition = partitions.partition(
+        "elders",
+        "cut off",
+        Fault::Drop,
+        Duration::ZERO,
+        Some(Duration::from_secs(45)),
+    );
+
+    let mut proposed_offline = BTreeSet::new();
+    let cmds = dispatcher.node.replicate_data(data.clone()).await?;
+    deliver(&nodes, elder_info.name(), cmds, &mut proposed_offline).await?;
+    for holder in &holders {
+        let held = adults[holder].1.data_storage.keys().await?;
+        assert_eq!(held.contains(&data.address()), *holder != cut_off);
+    }
+
+    // The elder's liveness probes and replication audits go on as they periodically do, through
+    // the partition and past its end, with nobody stepping in.
+    assert_eq!(LIVENESS_PROBE_INTERVAL, REPLICATION_AUDIT_INTERVAL);
+    for _ in 0..4 {
+        let mut cmds = dispatcher.node.probe_adults_liveness().await?;
+        cmds.extend(dispatcher.node.audit_next_chunks().await?);
+        deliver(&nodes, elder_info.name(), cmds, &mut proposed_offline).await?;
+        tokio::time::advance(LIVENESS_PROBE_INTERVAL).await;
+    }
+    assert!(partitions.affected(partition) > Some(0));
+
+    // The holder cut off got the chunk once the partition ended, from an audit of it.
+    for holder in &holders {
+        let held = adults[holder].1.data_storage.keys().await?;
+        assert!(held.contains(&data.address()));
+    }
+    assert!(
+        dispatcher
+            .node
+            .replication_audit
+            .stats()
+            .await
+            .missing_copies
+            > 0
+    );
+
+    // Missing a couple of probes didn't cost it its membership, and both sides of the
+    // partition agree on the section.
+    assert!(proposed_offline.is_empty());
+    let liveness = dispatcher.node.liveness_probes.adults().await;
+    assert_eq!(liveness[&cut_off].missed, 0);
+    assert!(liveness[&cut_off].answered > 0);
+    let elder_side = dispatcher.node.network_knowledge();
+    let cut_off_side = cut_off_node.network_knowledge();
+    assert_eq!(
+        cut_off_side.section_signed_authority_provider().await,
+        elder_side.section_signed_authority_provider().await
+    );
+    assert_eq!(
+        cut_off_side.section_signed_members().await,
+        elder_side.section_signed_members().await
+    );
+    assert!(elder_side.is_section_member(&cut_off).await);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fresh_adults_warm_up_to_their_full_share_of_data() -> Result<()> {
+    use crate::node::core::WarmUpPolicy;
+    use sn_interface::data_copy_count;
+    use sn_interface::types::holders_for;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let mut veterans = BTreeSet::new();
+    for _ in 0..2 * data_copy_count() {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let _new = veterans.insert(info.name());
+    }
+    dispatcher
+        .node
+        .warm_up
+        .set_policy(WarmUpPolicy {
+            period: Duration::from_secs(100),
+            initial_share: 0.2,
+        })
+        .await;
+    let fresh = gen_info(MIN_ADULT_AGE, None);
+    let _status = handle_online_cmd(&fresh.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    let mut adults = veterans.clone();
+    let _new = adults.insert(fresh.name());
+
+    let names = (0..1000)
+        .map(|_| xor_name::rand::random())
+        .collect::<Vec<XorName>>();
+    // The names the fresh adult is among the closest adults to.
+    let eligible = names
+        .iter()
+        .filter(|name| holders_for(name, &adults, data_copy_count()).contains(&fresh.name()))
+        .copied()
+        .collect::<Vec<_>>();
+    assert!(!eligible.is_empty());
+
+    for (tenure, expected_share) in [(0, 0.2), (50, 0.6), (50, 1.0)] {
+        dispatcher
+            .node
+            .warm_up
+            .backdate(&fresh.name(), Duration::from_secs(tenure))
+            .await;
+        let mut assigned = 0;
+        for name in &names {
+            let targets = dispatcher
+                .node
+                .get_adults_who_should_store_data(*name)
+                .await;
+            if targets.contains(&fresh.name()) {
+                assert!(eligible.contains(name));
+                assigned += 1;
+            }
+            if expected_share < 1.0 {
+                // The veterans hold all the copies the data is to be kept in while it warms up.
+                assert_eq!(
+                    targets.intersection(&veterans).count(),
+                    data_copy_count(),
+                    "{:?}",
+                    name
+                );
+            } else {
+                assert_eq!(targets, holders_for(name, &adults, data_copy_count()));
+            }
+        }
+        let share = assigned as f64 / eligible.len() as f64;
+        assert!(
+            (share - expected_share).abs() < 0.1,
+            "share of {} after {}s",
+            share,
+            tenure
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn corrupted_chunk_transfers_are_retried_then_sourced_elsewhere() -> Result<()> {
+    use crate::node::core::{Fault, Partitions};
+    use bytes::Bytes;
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+
+    // Delivers the node cmds sent by `from` between the in-process `adults`, until none are left
+    // to deliver. Chunks sent across a corrupting partition arrive with a bit flipped, as over a
+    // faulty link. Returns who fetched data from whom, in order.
+    async fn deliver(
+        adults: &BTreeMap<XorName, Node>,
+        from: XorName,
+        cmds: Vec<Cmd>,
+    ) -> Result<Vec<(XorName, XorName)>> {
+        let mut fetches = vec![];
+        let mut pending = vec![(from, cmds)];
+        while let Some((from, cmds)) = pending.pop() {
+            for cmd in cmds {
+                let (to, node_cmd) = match cmd {
+                    Cmd::SignOutgoingSystemMsg {
+                        msg: SystemMsg::NodeCmd(cmd),
+                        dst: DstLocation::Node { name, .. },
+                    } => (name, cmd),
+                    cmd => bail!("unexpected cmd {:?}", cmd),
+                };
+                let recipient = &adults[&to];
+                let peer = recipient.info.read().await.peer();
+                let corrupt = adults[&from].comm.fault_towards(&peer) == Some(Fault::Corrupt);
+                let cmds = match node_cmd {
+                    NodeCmd::ReplicateCheckedData(data) => {
+                        let data = data
+                            .into_iter()
+                            .map(|(address, data)| match data {
+                                ReplicatedData::Chunk(chunk) if corrupt => {
+                                    let mut value = chunk.value().to_vec();
+                                    value[0] ^= 1;
+                                    let chunk = Chunk::new(Bytes::from(value));
+                                    (address, ReplicatedData::Chunk(chunk))
+                                }
+                                data => (address, data),
+                            })
+                            .collect();
+                        let mut cmds = recipient.store_checked_replicas(from, data).await?;
+                        cmds.retain(|cmd| !is_replicas_stored(cmd));
+                        cmds
+                    }
+                    NodeCmd::FetchReplicateData(addresses) => {
+                        fetches.push((from, to));
+                        recipient.provide_replicas(from, addresses).await?
+                    }
+                    cmd => bail!("unexpected cmd {:?}", cmd),
+                };
+                pending.push((to, cmds));
+            }
+        }
+        Ok(fetches)
+    }
+
+    let partitions = Partitions::default();
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let mut adults = BTreeMap::new();
+    let mut adult_roots = vec![];
+    for _ in 0..data_copy_count() + 2 {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let root = tempdir()?;
+        let adult = Node::new(
+            create_comm().await?.with_partitions(partitions.clone()),
+            info.clone(),
+            section.clone(),
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?;
+        adult_roots.push(root);
+        let _prev = adults.insert(info.name(), adult);
+    }
+
+    // All adults but the target hold the chunk, and the link from the source to the target
+    // corrupts what goes over it.
+    let data = ReplicatedData::Chunk(Chunk::new(random_bytes(100)));
+    let mut names = adults.keys().copied();
+    let target = names.next().ok_or_else(|| eyre!("no adults"))?;
+    let source = names.next().ok_or_else(|| eyre!("no other adults"))?;
+    for name in adults.keys().filter(|name| **name != target) {
+        let _level = adults[name].data_storage.store(&data).await?;
+    }
+    let alternative = *adults[&target]
+        .adult_index()
+        .await
+        .closest(&data.name())
+        .find(|name| **name != target && **name != source)
+        .ok_or_else(|| eyre!("no alternative holder"))?;
+    let addr = |name: &XorName| adults[name].comm.our_connection_info();
+    partitions.set_group("source", [addr(&source)]);
+    partitions.set_group("target", [addr(&target)]);
+    let _id = partitions.partition("source", "target", Fault::Corrupt, Duration::ZERO, None);
+
+    // The target retries the corrupted chunk from the source once, then gets it from the other
+    // holder.
+    let cmds = adults[&source]
+        .replicate_data_to(vec![data.address()], target)
+        .await?;
+    let fetches = deliver(&adults, source, cmds).await?;
+    assert_eq!(fetches, vec![(target, source), (target, alternative)]);
+    assert!(adults[&target]
+        .data_storage
+        .keys()
+        .await?
+        .contains(&data.address()));
+
+    // Both corrupted copies count against the source only.
+    let checks = &adults[&target].transfer_checks;
+    assert_eq!(checks.corruptions_from(&source).await, 2);
+    assert_eq!(checks.corruptions_from(&alternative).await, 0);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunks_torn_while_replicated_are_not_held_until_written_whole() -> Result<()> {
+    use crate::dbs::FaultyDisk;
+    use sn_interface::messaging::system::NodeCmd;
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+    use walkdir::WalkDir;
+
+    // Delivers the replicas in the cmds of `from` to the in-process `adults`.
+    async fn deliver(
+        adults: &BTreeMap<XorName, Node>,
+        from: XorName,
+        cmds: Vec<Cmd>,
+    ) -> Result<()> {
+        for cmd in cmds {
+            match cmd {
+                Cmd::SignOutgoingSystemMsg {
+                    msg: SystemMsg::NodeCmd(NodeCmd::ReplicateCheckedData(data)),
+                    dst: DstLocation::Node { name, .. },
+                } => {
+                    let cmds = adults[&name].store_checked_replicas(from, data).await?;
+                    assert!(cmds.iter().all(is_replicas_stored));
+                }
+                cmd => bail!("unexpected cmd {:?}", cmd),
+            }
+        }
+        Ok(())
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let mut adults = BTreeMap::new();
+    let mut disks = BTreeMap::new();
+    let mut adult_roots = BTreeMap::new();
+    for _ in 0..2 {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let root = tempdir()?;
+        let disk = FaultyDisk::default();
+        let adult = Node::new(
+            create_comm().await?,
+            info.clone(),
+            section.clone(),
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?
+        .with_faulty_disk(disk.clone());
+        let _prev = adult_roots.insert(info.name(), root);
+        let _prev = disks.insert(info.name(), disk);
+        let _prev = adults.insert(info.name(), adult);
+    }
+    let mut names = adults.keys().copied();
+    let source = names.next().ok_or_else(|| eyre!("no adults"))?;
+    let target = names.next().ok_or_else(|| eyre!("no other adults"))?;
+
+    let data = ReplicatedData::Chunk(Chunk::new(random_bytes(100)));
+    let _level = adults[&source].data_storage.store(&data).await?;
+
+    // The target's disk tears the chunk's write halfway: it neither holds the chunk, nor keeps
+    // the half of it written.
+    disks[&target].update(|faults| faults.torn_writes = true);
+    let cmds = adults[&source]
+        .replicate_data_to(vec![data.address()], target)
+        .await?;
+    deliver(&adults, source, cmds).await?;
+    let target_storage = &adults[&target].data_storage;
+    assert!(!target_storage.keys().await?.contains(&data.address()));
+    assert!(target_storage
+        .get_from_local_store(&data.address())
+        .await
+        .is_err());
+    let partial_writes = WalkDir::new(adult_roots[&target].path())
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "tmp"))
+        .count();
+    assert_eq!(partial_writes, 0);
+
+    // Once the disk is healthy again, replicating the chunk again writes it whole.
+    disks[&target].heal();
+    let cmds = adults[&source]
+        .replicate_data_to(vec![data.address()], target)
+        .await?;
+    deliver(&adults, source, cmds).await?;
+    assert_eq!(
+        target_storage.get_from_local_store(&data.address()).await?,
+        data
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn read_and_write_readiness_change_independently() -> Result<()> {
+    use crate::node::core::{Condition, Readiness};
+    use sn_consensus::vote::{Ballot, Vote};
+    use sn_interface::network_knowledge::SapCandidate;
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+
+    fn failing(readiness: &Readiness) -> Vec<String> {
+        readiness
+            .report()
+            .lines()
+            .filter(|line| line.contains(": FAILING"))
+            .map(|line| {
+                line.trim_start()
+                    .split(':')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let elder = Dispatcher::new(
+        Node::new(
+            create_comm().await?,
+            nodes.remove(0),
+            section.clone(),
+            Some(section_key_share),
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root_storage_dir,
+        )
+        .await?,
+    );
+    assert_eq!(elder.readiness().await, Readiness::default());
+
+    // An adult of the section, with little space.
+    let adult_info = gen_info(MIN_ADULT_AGE, None);
+    let _status = handle_online_cmd(&adult_info.peer(), &sk_set, &elder, &section_auth).await?;
+    let capacity = 10_000;
+    let adult_root = tempdir()?;
+    let adult = Dispatcher::new(
+        Node::new(
+            create_comm().await?,
+            adult_info,
+            section,
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(capacity),
+            adult_root.path().to_path_buf(),
+        )
+        .await?,
+    );
+    assert!(adult.readiness().await.is_ready());
+
+    // A full adult still serves reads.
+    while !adult.node.data_storage.is_full().await {
+        let chunk = ReplicatedData::Chunk(Chunk::new(random_bytes(capacity / 10)));
+        let _level = adult.node.data_storage.store(&chunk).await?;
+    }
+    let readiness = adult.readiness().await;
+    assert!(readiness.can_read());
+    assert!(!readiness.can_write());
+    assert!(!readiness.is_ready());
+    assert_eq!(failing(&readiness), vec!["storage_full"]);
+
+    // As does one about to restart, which the elder keeps on serving both meanwhile.
+    let _cmds = adult
+        .node
+        .prepare_restart(Duration::from_secs(60), false)
+        .await?;
+    let readiness = adult.readiness().await;
+    assert!(readiness.can_read());
+    assert_eq!(failing(&readiness), vec!["storage_full", "maintenance"]);
+    assert!(elder.readiness().await.is_ready());
+
+    // Mid-handover, the elder serves neither, and says so for both.
+    {
+        let mut handover = elder.node.handover_voting.write().await;
+        let handover = handover
+            .as_mut()
+            .ok_or_else(|| eyre!("elder without handover"))?;
+        let candidate =
+            SapCandidate::ElderHandover(section_signed(sk_set.secret_key(), section_auth.clone())?);
+        let vote = handover.sign_vote(Vote {
+            gen: 0,
+            ballot: Ballot::Propose(candidate),
+            faults: BTreeSet::new(),
+        })?;
+        let _prev = handover.consensus.votes.insert(handover.id(), vote);
+    }
+    let readiness = elder.readiness().await;
+    assert!(!readiness.can_read());
+    assert!(!readiness.can_write());
+    assert_eq!(readiness.conditions, BTreeSet::from([Condition::Handover]));
+    assert_eq!(failing(&readiness), vec!["handover", "handover"]);
+
+    Ok(())
+}
+
+// Protocol constants differing from those of the section in one constant.
+fn other_protocol_constants() -> ProtocolConstants {
+    ProtocolConstants {
+        data_copy_count: ProtocolConstants::current().data_copy_count + 1,
+        ..ProtocolConstants::current()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn join_request_with_other_protocol_constants_is_rejected() -> Result<()> {
+    let prefix1 = Prefix::default().pushed(true);
+    let (section_auth, mut nodes, sk_set) = gen_section_authority_provider(prefix1, elder_count());
+    let section_key = sk_set.public_keys().public_key();
+
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let dispatcher = Dispatcher::new(
+        Node::new(
+            create_comm().await?,
+            nodes.remove(0),
+            section,
+            Some(section_key_share),
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root_storage_dir,
+        )
+        .await?,
+    );
+
+    let new_node = NodeInfo::new(
+        ed25519::gen_keypair(&prefix1.range_inclusive(), MIN_ADULT_AGE),
+        gen_addr(),
+    );
+    let wire_msg = WireMsg::single_src(
+        &new_node,
+        DstLocation::Section {
+            name: XorName::from(PublicKey::Bls(section_key)),
+            section_pk: section_key,
+        },
+        SystemMsg::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            resource_proof_response: None,
+            protocol_digest: other_protocol_constants().digest(),
+            protocol_versions: ProtocolVersions::ours(),
+        })),
+        section_key,
+    )?;
+
+    let cmds = dispatcher
+        .process_cmd(
+            Cmd::HandleMsg {
+                sender: new_node.peer(),
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+        .await?;
+
+    let response_wire_msg = cmds
+        .into_iter()
+        .find_map(|cmd| match cmd {
+            Cmd::SendMsg { wire_msg, .. } => Some(wire_msg),
+            _ => None,
+        })
+        .ok_or_else(|| eyre!("no JoinResponse sent"))?;
+    assert_matches!(
+        response_wire_msg.into_msg(),
+        Ok(MsgType::System {
+            msg: SystemMsg::JoinResponse(response),
+            ..
+        }) => assert_matches!(
+            *response,
+            JoinResponse::Rejected(JoinRejectionReason::ProtocolMismatch { section })
+                if section == section_auth.protocol_digest()
+        )
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn join_request_with_incompatible_protocol_versions_is_rejected() -> Result<()> {
+    let prefix1 = Prefix::default().pushed(true);
+    let (section_auth, mut nodes, sk_set) = gen_section_authority_provider(prefix1, elder_count());
+    let section_key = sk_set.public_keys().public_key();
+
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let dispatcher = Dispatcher::new(
+        Node::new(
+            create_comm().await?,
+            nodes.remove(0),
+            section,
+            Some(section_key_share),
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root_storage_dir,
+        )
+        .await?,
+    );
+
+    for protocol_versions in [
+        // As of a release that no longer writes msgs we can read.
+        ProtocolVersions {
+            current: MESSAGING_PROTO_VERSION + 1,
+            min_compatible: MESSAGING_PROTO_VERSION + 1,
+            max_compatible: MESSAGING_PROTO_VERSION + 1,
+        },
+        // As of the first release, whose msgs we no longer read.
+        ProtocolVersions {
+            current: 1,
+            min_compatible: 1,
+            max_compatible: 1,
+        },
+        // As of a release predating the negotiation.
+        ProtocolVersions::initial(),
+    ] {
+        let new_node = NodeInfo::new(
+            ed25519::gen_keypair(&prefix1.range_inclusive(), MIN_ADULT_AGE),
+            gen_addr(),
+        );
+        let wire_msg = WireMsg::single_src(
+            &new_node,
+            DstLocation::Section {
+                name: XorName::from(PublicKey::Bls(section_key)),
+                section_pk: section_key,
+            },
+            SystemMsg::JoinRequest(Box::new(JoinRequest {
+                section_key,
+                resource_proof_response: None,
+                protocol_digest: ProtocolConstants::current().digest(),
+                protocol_versions,
+            })),
+            section_key,
+        )?;
+
+        let cmds = dispatcher
+            .process_cmd(
+                Cmd::HandleMsg {
+                    sender: new_node.peer(),
+                    wire_msg,
+                    original_bytes: None,
+                },
+                "cmd-id",
+            )
+            .await?;
+
+        let response_wire_msg = cmds
+            .into_iter()
+            .find_map(|cmd| match cmd {
+                Cmd::SendMsg { wire_msg, .. } => Some(wire_msg),
+                _ => None,
+            })
+            .ok_or_else(|| eyre!("no JoinResponse sent"))?;
+        assert_matches!(
+            response_wire_msg.into_msg(),
+            Ok(MsgType::System {
+                msg: SystemMsg::JoinResponse(response),
+                ..
+            }) => assert_matches!(
+                *response,
+                JoinResponse::Rejected(JoinRejectionReason::IncompatibleVersion { section })
+                    if section == ProtocolVersions::ours()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn member_with_other_protocol_constants_sits_out_dkg_and_proposals() -> Result<()> {
+    use crate::node::core::Condition;
+    use sn_interface::messaging::system::DkgSessionId;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let mut node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let session_id = DkgSessionId {
+        prefix: section_auth.prefix(),
+        elders: section_auth
+            .elders()
+            .map(|elder| (elder.name(), elder.addr()))
+            .collect(),
+        section_chain_len: node.network_knowledge().chain_len().await,
+        bootstrap_members: BTreeSet::new(),
+        network_params: section_auth.network_params(),
+    };
+
+    // As after an upgrade to a build with one of the constants changed.
+    node.protocol_digest = other_protocol_constants().digest();
+    assert_eq!(
+        node.protocol_mismatch().await,
+        Some(section_auth.protocol_digest())
+    );
+
+    assert!(node
+        .readiness_conditions()
+        .await
+        .contains(&Condition::ProtocolMismatch));
+
+    assert!(node.handle_dkg_start(session_id.clone()).await?.is_empty());
+    let joining = NodeState::joined(gen_info(MIN_ADULT_AGE, None).peer(), None).to_msg();
+    assert!(node.propose_membership_change(joining).await?.is_empty());
+
+    // Back on the section's constants, the node takes part again.
+    node.protocol_digest = ProtocolConstants::current().digest();
+    assert_eq!(node.protocol_mismatch().await, None);
+    assert!(!node
+        .readiness_conditions()
+        .await
+        .contains(&Condition::ProtocolMismatch));
+    assert!(!node.handle_dkg_start(session_id).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn capacity_can_be_shrunk_below_usage_and_grown_back_at_runtime() -> Result<()> {
+    use crate::dbs::Error as DbError;
+    use sn_interface::messaging::{data::StorageLevel, system::NodeCmd};
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+
+    fn reported_level(cmds: &[Cmd]) -> Option<StorageLevel> {
+        cmds.iter().find_map(|cmd| match cmd {
+            Cmd::SignOutgoingSystemMsg {
+                msg: SystemMsg::NodeCmd(NodeCmd::RecordStorageLevel { level, .. }),
+                ..
+            } => Some(*level),
+            _ => None,
+        })
+    }
+
+    async fn capacity_changed(event_rx: &mut mpsc::Receiver<Event>) -> Option<(usize, usize)> {
+        while let Ok(event) = event_rx.try_recv() {
+            if let Event::CapacityChanged { max_capacity, used } = event {
+                return Some((max_capacity, used));
+            }
+        }
+        None
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let elder = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+
+    let adult_info = gen_info(MIN_ADULT_AGE, None);
+    let adult_id = PublicKey::from(adult_info.keypair.public);
+    let adult_root = tempdir()?;
+    let (event_tx, mut event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let adult = Node::new(
+        create_comm().await?,
+        adult_info,
+        section,
+        None,
+        event_tx,
+        UsedSpace::new(100_000),
+        adult_root.path().to_path_buf(),
+    )
+    .await?;
+
+    let chunk = || ReplicatedData::Chunk(Chunk::new(random_bytes(1_000)));
+    let mut stored = vec![];
+    for _ in 0..5 {
+        let data = chunk();
+        let _level = adult.data_storage.store(&data).await?;
+        stored.push(data);
+    }
+    let used = adult.data_storage.used_space().used();
+
+    // Shrunk below what it stores, the adult keeps it all but takes nothing more, and tells its
+    // elders it's full.
+    let cmds = adult.set_capacity(used / 2).await;
+    assert_eq!(
+        capacity_changed(&mut event_rx).await,
+        Some((used / 2, used))
+    );
+    let level = reported_level(&cmds).ok_or_else(|| eyre!("no storage level reported"))?;
+    assert_eq!(level.value(), StorageLevel::MAX);
+    assert!(adult.data_storage.is_full().await);
+    assert_matches!(
+        adult.data_storage.store(&chunk()).await,
+        Err(DbError::NotEnoughSpace)
+    );
+    for data in &stored {
+        assert_eq!(
+            &adult
+                .data_storage
+                .get_from_local_store(&data.address())
+                .await?,
+            data
+        );
+    }
+    assert!(elder.reset_storage_level(&adult_id, level).await);
+    assert!(elder.full_adults().await.contains(&XorName::from(adult_id)));
+
+    // Grown back, it takes data again, and its elders give it some again.
+    let cmds = adult.set_capacity(used * 20).await;
+    assert_eq!(
+        capacity_changed(&mut event_rx).await,
+        Some((used * 20, used))
+    );
+    let level = reported_level(&cmds).ok_or_else(|| eyre!("no storage level reported"))?;
+    assert_eq!(level.value(), 0);
+    assert!(!adult.data_storage.is_full().await);
+    let _level = adult.data_storage.store(&chunk()).await?;
+    assert!(elder.reset_storage_level(&adult_id, level).await);
+    assert!(elder.full_adults().await.is_empty());
+
+    // Set to the same capacity, it still says so.
+    let cmds = adult.set_capacity(used * 20).await;
+    assert!(reported_level(&cmds).is_none());
+    assert!(capacity_changed(&mut event_rx).await.is_some());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_levels_are_recorded_once_elders_agree_on_them() -> Result<()> {
+    use sn_interface::messaging::data::StorageLevel;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let adult = PublicKey::from(gen_info(MIN_ADULT_AGE, None).keypair.public);
+    for (level, full) in [(StorageLevel::MAX, true), (0, false)] {
+        let proposal = Proposal::StorageFull {
+            node: adult,
+            level: StorageLevel::from(level)?,
+        };
+        let sig = keyed_signed(sk_set.secret_key(), &proposal.as_signable_bytes()?);
+        let _cmds = dispatcher
+            .process_cmd(Cmd::HandleAgreement { proposal, sig }, "cmd-id")
+            .await?;
+        assert_eq!(
+            dispatcher
+                .node
+                .full_adults()
+                .await
+                .contains(&XorName::from(adult)),
+            full
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn diagnostics_are_served_to_all_and_control_only_to_the_privileged() -> Result<()> {
+    use super::diagnostics::{self, DIAGNOSTICS_SOCKET};
+    use crate::control::CONTROL_SOCKET;
+    use crate::node::cfg::capacity_storage::get_max_capacity;
+    use serde_json::{json, Value};
+    use std::{os::unix::fs::PermissionsExt, sync::Arc};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+    };
+
+    async fn request(socket: &Path, request: Value) -> Result<Value> {
+        let mut stream = UnixStream::connect(socket).await?;
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        let mut line = String::new();
+        let _len = BufReader::new(stream).read_line(&mut line).await?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    fn denial(response: &Value) -> Option<&str> {
+        response["error"]["kind"].as_str()
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+    diagnostics::serve(&dispatcher, &root_storage_dir, Some("secret".to_string()))?;
+
+    let public = root_storage_dir.join(DIAGNOSTICS_SOCKET);
+    let control = root_storage_dir.join(CONTROL_SOCKET);
+    let mode =
+        |path: &Path| -> Result<u32> { Ok(std::fs::metadata(path)?.permissions().mode() & 0o777) };
+    assert_eq!(mode(&public)?, 0o666);
+    assert_eq!(mode(&control)?, 0o600);
+    // Nothing is left of the dirs the sockets were bound in before being moved into place.
+    for entry in std::fs::read_dir(&root_storage_dir)? {
+        assert!(!entry?.file_name().to_string_lossy().starts_with(".sock"));
+    }
+
+    // The read-only tier is served on both sockets, without a token.
+    for socket in [&public, &control] {
+        let status = request(socket, json!({ "cmd": "status" })).await?;
+        assert_eq!(status["ok"]["is_elder"], json!(true));
+        let peers = request(socket, json!({ "cmd": "peers" })).await?;
+        assert_eq!(peers["ok"]["elders"], json!(elder_count()));
+    }
+
+    // The privileged tier isn't served on the public socket, even with the token.
+    let set_capacity = |token: Option<&str>| json!({ "cmd": "set_capacity", "args": { "bytes": 12_345 }, "token": token });
+    let response = request(&public, set_capacity(Some("secret"))).await?;
+    assert_eq!(denial(&response), Some("permission_denied"));
+
+    // Nor on the control socket without the right token.
+    for token in [None, Some("guess")] {
+        let response = request(&control, set_capacity(token)).await?;
+        assert_eq!(denial(&response), Some("unauthorized"));
+    }
+    assert_eq!(
+        dispatcher.node.data_storage.used_space().max_capacity(),
+        max_capacity
+    );
+
+    let response = request(&control, set_capacity(Some("secret"))).await?;
+    assert_eq!(response, json!({ "ok": null }));
+    assert_eq!(
+        dispatcher.node.data_storage.used_space().max_capacity(),
+        12_345
+    );
+    assert_eq!(get_max_capacity(&root_storage_dir).await?, Some(12_345));
+
+    let response = request(&control, json!({ "cmd": "wipe", "token": "secret" })).await?;
+    assert_eq!(denial(&response), Some("unknown_command"));
+
+    // Connections past the cap are refused, until one of those served is closed.
+    let mut held = Vec::new();
+    for _ in 0..diagnostics::MAX_CONNECTIONS {
+        held.push(UnixStream::connect(&public).await?);
+    }
+    // The refusal is sent right away, with no request read.
+    let mut line = String::new();
+    let _len = BufReader::new(UnixStream::connect(&public).await?)
+        .read_line(&mut line)
+        .await?;
+    assert_eq!(denial(&serde_json::from_str(&line)?), Some("failed"));
+    let response = request(&control, json!({ "cmd": "status" })).await?;
+    assert_eq!(response["ok"]["is_elder"], json!(true));
+
+    drop(held.pop());
+    let mut served = false;
+    for _ in 0..50 {
+        if let Ok(response) = request(&public, json!({ "cmd": "status" })).await {
+            if denial(&response).is_none() {
+                served = true;
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(served);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn background_tasks_are_shown_in_diagnostics() -> Result<()> {
+    use super::{
+        diagnostics::{self, DIAGNOSTICS_SOCKET},
+        supervisor::RestartPolicy,
+    };
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+    };
+
+    async fn request(socket: &Path, request: Value) -> Result<Value> {
+        let mut stream = UnixStream::connect(socket).await?;
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        let mut line = String::new();
+        let _len = BufReader::new(stream).read_line(&mut line).await?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+    diagnostics::serve(&dispatcher, &root_storage_dir, None)?;
+
+    dispatcher
+        .supervisor
+        .spawn("steady", RestartPolicy::Always, || {
+            futures::future::pending::<()>()
+        })
+        .await;
+    let policy = RestartPolicy::WithBackoff {
+        initial_delay: Duration::from_millis(1),
+        max_delay: Duration::from_secs(1),
+        max_restarts: 2,
+    };
+    dispatcher
+        .supervisor
+        .spawn("flaky", policy, || async { panic!("flaked") })
+        .await;
+
+    let tasks = |response: &Value| -> BTreeMap<String, Value> {
+        response["ok"]["tasks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|task| {
+                (
+                    task["name"].as_str().unwrap_or_default().to_string(),
+                    task.clone(),
+                )
+            })
+            .collect()
+    };
+    let mut response = Value::Null;
+    for _ in 0..100 {
+        response = request(
+            &root_storage_dir.join(DIAGNOSTICS_SOCKET),
+            json!({ "cmd": "tasks" }),
+        )
+        .await?;
+        if tasks(&response)["flaky"]["status"] == json!("gave_up") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let tasks = tasks(&response);
+    assert_eq!(tasks["steady"]["status"], json!("running"));
+    assert_eq!(tasks["steady"]["restarts"], json!(0));
+    assert_eq!(tasks["steady"]["last_exit"], Value::Null);
+    assert_eq!(tasks["flaky"]["status"], json!("gave_up"));
+    assert_eq!(tasks["flaky"]["restarts"], json!(2));
+    assert_eq!(tasks["flaky"]["last_exit"], json!("panicked: flaked"));
+    assert_eq!(response["ok"]["failed"], json!(true));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn msg_audit_dumps_what_became_of_msgs_received() -> Result<()> {
+    use super::{diagnostics, handle_received_msg};
+    use crate::control::ControlCmd;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = section_auth.section_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    node.msg_filter.enable(100, Duration::from_secs(60)).await;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+    diagnostics::serve(&dispatcher, &root_storage_dir, Some("secret".to_string()))?;
+
+    // A node msg received twice is handled once.
+    let sender = NodeInfo::new(
+        ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE),
+        gen_addr(),
+    );
+    let wire_msg = WireMsg::single_src(
+        &sender,
+        DstLocation::Section {
+            name: XorName::from(PublicKey::Bls(section_key)),
+            section_pk: section_key,
+        },
+        SystemMsg::AntiEntropyProbe(xor_name::rand::random()),
+        section_key,
+    )?;
+    let bytes = wire_msg.serialize()?;
+    for _ in 0..2 {
+        handle_received_msg(
+            dispatcher.clone(),
+            sender.peer(),
+            wire_msg.clone(),
+            bytes.clone(),
+        )
+        .await;
+    }
+
+    let dump = |token: Option<&str>| {
+        let cmd = ControlCmd::MsgAudit {
+            root_dir: root_storage_dir.clone(),
+            token: token.map(str::to_string),
+        };
+        tokio::task::spawn_blocking(move || {
+            let mut out = vec![];
+            cmd.run(&mut out).map(|()| out)
+        })
+    };
+    assert!(dump(None).await?.is_err());
+    let audit: Value = serde_json::from_slice(&dump(Some("secret")).await??)?;
+
+    let msg_id = hex::encode(wire_msg.msg_id().as_ref());
+    let received: Vec<_> = audit["msgs"]
+        .as_array()
+        .ok_or_else(|| eyre!("no msgs in {}", audit))?
+        .iter()
+        .filter(|msg| msg["direction"] == "in" && msg["msg_id"] == json!(msg_id))
+        .collect();
+    let dispositions: Vec<_> = received.iter().map(|msg| &msg["disposition"]).collect();
+    assert_eq!(dispositions, [&json!("handled"), &json!("duplicate")]);
+    for msg in received {
+        assert_eq!(msg["peer"]["addr"], json!(sender.addr.to_string()));
+        assert_eq!(msg["kind"], "node");
+        assert_eq!(msg["size"], json!(bytes.len()));
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn control_is_served_over_loopback_tcp_only_with_a_token() -> Result<()> {
+    use super::diagnostics;
+    use serde_json::{json, Value};
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpStream,
+    };
+
+    async fn request(addr: SocketAddr, request: Value) -> Result<Value> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        let mut line = String::new();
+        let _len = BufReader::new(stream).read_line(&mut line).await?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    fn denial(response: &Value) -> Option<&str> {
+        response["error"]["kind"].as_str()
+    }
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+
+    let public_addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+    let refused = diagnostics::serve_rpc(&dispatcher, public_addr, &root_storage_dir, None).await;
+    assert_matches!(refused, Err(Error::Configuration(_)));
+
+    // Without a token, only the read-only tier is served, as any local user can connect.
+    let loopback = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+    let open = diagnostics::serve_rpc(&dispatcher, loopback, &root_storage_dir, None).await?;
+    let prefix = request(open, json!({ "cmd": "prefix" })).await?;
+    assert_eq!(prefix["ok"]["prefix"], json!(""));
+    let set_log_level = |token: Option<&str>| json!({ "cmd": "set_log_level", "args": { "filter": "sn_node=trace" }, "token": token });
+    let response = request(open, set_log_level(Some("secret"))).await?;
+    assert_eq!(denial(&response), Some("permission_denied"));
+
+    let guarded = diagnostics::serve_rpc(
+        &dispatcher,
+        loopback,
+        &root_storage_dir,
+        Some("secret".to_string()),
+    )
+    .await?;
+    for token in [None, Some("guess")] {
+        let response = request(guarded, set_log_level(token)).await?;
+        assert_eq!(denial(&response), Some("unauthorized"));
+    }
+
+    // The filter can only be changed if whoever set up the logs lets us.
+    let response = request(guarded, set_log_level(Some("secret"))).await?;
+    assert_eq!(denial(&response), Some("failed"));
+    let filters = Arc::new(Mutex::new(vec![]));
+    let reloaded = filters.clone();
+    dispatcher
+        .set_log_filter_reloader(Box::new(move |filter| {
+            reloaded
+                .lock()
+                .map_err(|err| err.to_string())?
+                .push(filter.to_string());
+            Ok(())
+        }))
+        .await;
+    let response = request(guarded, set_log_level(Some("secret"))).await?;
+    assert_eq!(response, json!({ "ok": null }));
+    assert_eq!(
+        filters.lock().map_err(|err| eyre!("{}", err))?.clone(),
+        vec!["sn_node=trace".to_string()]
+    );
+
+    let scrub = request(guarded, json!({ "cmd": "scrub_chunks", "token": "secret" })).await?;
+    assert_eq!(scrub["ok"]["quarantined"], json!(0));
+    let peers = request(
+        guarded,
+        json!({ "cmd": "connected_peers", "token": "secret" }),
+    )
+    .await?;
+    assert_eq!(peers["ok"]["peers"], json!([]));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_is_attested_again_when_changed_at_runtime() -> Result<()> {
+    use crate::attestation::{self, REDACTED};
+    use crate::node::Config;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let info = nodes.remove(0);
+    let node_key = info.keypair.public;
+    let node = Node::new(
+        create_comm().await?,
+        info,
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    let dispatcher = Arc::new(Dispatcher::new(node));
+
+    let config = Config {
+        control_token: Some("secret".to_string()),
+        ..Config::default()
+    };
+    let attested = dispatcher.attest(config, root_storage_dir.clone()).await?;
+    attested.verify(&node_key)?;
+    assert_eq!(attestation::read(&root_storage_dir)?, attested);
+    assert_eq!(attested.config["max_capacity"], json!(max_capacity));
+    assert_eq!(attested.config["control_token"], json!(REDACTED));
+    assert_eq!(
+        attested.genesis_key,
+        hex::encode(sk_set.public_keys().public_key().to_bytes())
+    );
+
+    dispatcher.clone().set_capacity(12_345).await?;
+    let reattested = dispatcher
+        .attestation()
+        .await
+        .ok_or_else(|| eyre!("config no longer attested"))?;
+    assert_ne!(reattested.digest, attested.digest);
+    reattested.verify(&node_key)?;
+    assert_eq!(attestation::read(&root_storage_dir)?, reattested);
+    assert_eq!(reattested.config["max_capacity"], json!(12_345));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_data_sent_under_the_wrong_name_is_rejected() -> Result<()> {
+    use sn_interface::messaging::data::{
+        AddressProblem, CmdError, DataCmd, Error as ErrorMsg, ProcessingError, ServiceMsg,
+    };
+    use sn_interface::messaging::ServiceAuth;
+    use sn_interface::types::{utils::random_bytes, Chunk};
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let chunk = Chunk::new(random_bytes(100));
+    let chunk_name = *chunk.name();
+    let client = Keypair::new_ed25519();
+    let payload = WireMsg::serialize_msg_payload(&ServiceMsg::Cmd(DataCmd::StoreChunk(chunk)))?;
+    let auth = ServiceAuth {
+        public_key: client.public_key(),
+        signature: client.sign(&payload),
+    };
+    let wire_msg = WireMsg::new_msg(
+        MsgId::new(),
+        payload,
+        AuthKind::Service(auth),
+        DstLocation::Section {
+            name: xor_name::rand::random(),
+            section_pk: section_key,
+        },
+    )?;
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+
+    let cmds = dispatcher
+        .process_cmd(
+            Cmd::HandleMsg {
+                sender: client_peer,
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+        .await?;
+
+    // Only the client hears back, with what's wrong, and no adult is asked to store the chunk.
+    assert_eq!(cmds.len(), 1);
+    assert_matches!(&cmds[0], Cmd::SendMsg { recipients, wire_msg } => {
+        assert_eq!(recipients, &vec![client_peer]);
+        assert_matches!(
+            wire_msg.clone().into_msg(),
+            Ok(MsgType::Service {
+                msg: ServiceMsg::CmdError {
+                    error: CmdError::Data(ProcessingError {
+                        error: ErrorMsg::InvalidAddress { field, problem },
+                        ..
+                    }),
+                    ..
+                },
+                ..
+            }) => {
+                assert_eq!(field, "dst.name");
+                assert_eq!(problem, AddressProblem::NameMismatch { expected: chunk_name });
+            }
+        );
+    });
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_cmd_sent_again_is_acked_without_being_applied_again() -> Result<()> {
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::data::{DataCmd, ServiceMsg};
+    use sn_interface::messaging::ServiceAuth;
+    use sn_interface::types::{utils::random_bytes, Chunk};
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+    for _ in 0..data_copy_count() {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    }
+
+    let chunk = Chunk::new(random_bytes(100));
+    let chunk_name = *chunk.name();
+    let client = Keypair::new_ed25519();
+    let payload = WireMsg::serialize_msg_payload(&ServiceMsg::Cmd(DataCmd::StoreChunk(chunk)))?;
+    let auth = ServiceAuth {
+        public_key: client.public_key(),
+        signature: client.sign(&payload),
+    };
+    let msg_id = MsgId::new();
+    let wire_msg = WireMsg::new_msg(
+        msg_id,
+        payload,
+        AuthKind::Service(auth),
+        DstLocation::Section {
+            name: chunk_name,
+            section_pk: section_key,
+        },
+    )?;
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+    let handle = |wire_msg| {
+        dispatcher.process_cmd(
+            Cmd::HandleMsg {
+                sender: client_peer,
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+    };
+    let acks = |cmds: &[Cmd]| {
+        cmds.iter()
+            .filter(|cmd| {
+                matches!(cmd, Cmd::SendMsg { recipients, wire_msg }
+                if recipients == &vec![client_peer]
+                    && matches!(
+                        wire_msg.clone().into_msg(),
+                        Ok(MsgType::Service {
+                            msg: ServiceMsg::CmdAck { correlation_id, .. },
+                            ..
+                        }) if correlation_id == msg_id
+                    ))
+            })
+            .count()
+    };
+
+    // The first time, the chunk is sent to its holders and the client acked.
+    let cmds = handle(wire_msg.clone()).await?;
+    assert_eq!(acks(&cmds), 1);
+    assert_eq!(cmds.len(), data_copy_count() + 1);
+
+    // Sent again by the client, as after timing out on the ack, it's only acked again.
+    let cmds = handle(wire_msg).await?;
+    assert_eq!(acks(&cmds), 1);
+    assert_eq!(cmds.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_batch_is_sent_to_holders_all_or_nothing() -> Result<()> {
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::data::{
+        BatchQuery, BatchStatus, CmdError, DataCmd, Error as ErrorMsg, ProcessingError,
+        QueryResponse, ServiceMsg,
+    };
+    use sn_interface::messaging::ServiceAuth;
+    use sn_interface::types::{utils::random_bytes, Chunk};
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let chunks = vec![Chunk::new(random_bytes(100)), Chunk::new(random_bytes(100))];
+    let dst_name = *chunks[0].name();
+    let batch = ServiceMsg::BatchCmd(chunks.into_iter().map(DataCmd::StoreChunk).collect());
+    let client = Keypair::new_ed25519();
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+    let wire_msg = |msg_id, msg: &ServiceMsg| -> Result<WireMsg> {
+        let payload = WireMsg::serialize_msg_payload(msg)?;
+        let auth = ServiceAuth {
+            public_key: client.public_key(),
+            signature: client.sign(&payload),
+        };
+        Ok(WireMsg::new_msg(
+            msg_id,
+            payload,
+            AuthKind::Service(auth),
+            DstLocation::Section {
+                name: dst_name,
+                section_pk: section_key,
+            },
+        )?)
+    };
+    let handle = |wire_msg| {
+        dispatcher.process_cmd(
+            Cmd::HandleMsg {
+                sender: client_peer,
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+    };
+    let responses = |cmds: &[Cmd]| {
+        cmds.iter()
+            .filter_map(|cmd| match cmd {
+                Cmd::SendMsg {
+                    recipients,
+                    wire_msg,
+                } if recipients == &vec![client_peer] => match wire_msg.clone().into_msg() {
+                    Ok(MsgType::Service { msg, .. }) => Some(msg),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let status_of = |batch| async move {
+        let query = BatchQuery { batch, dst_name };
+        let cmds = handle(wire_msg(MsgId::new(), &ServiceMsg::BatchQuery(query))?).await?;
+        match responses(&cmds).as_slice() {
+            [ServiceMsg::QueryResponse {
+                response: QueryResponse::BatchStatus((Ok(status), _)),
+                ..
+            }] => Result::<_>::Ok(status.clone()),
+            other => panic!("Unexpected response to batch query: {:?}", other),
+        }
+    };
+
+    // Without adults to hold the data of its first cmd, nothing of the batch is sent.
+    let aborted = MsgId::new();
+    let cmds = handle(wire_msg(aborted, &batch)?).await?;
+    assert_eq!(cmds.len(), 1);
+    assert_matches!(
+        responses(&cmds).as_slice(),
+        [ServiceMsg::CmdError {
+            error: CmdError::Data(ProcessingError {
+                error: ErrorMsg::BatchAborted { index: 0, error },
+                ..
+            }),
+            ..
+        }] => assert_matches!(**error, ErrorMsg::InsufficientAdults { .. })
+    );
+    assert_matches!(
+        status_of(aborted).await?,
+        BatchStatus::Aborted(ErrorMsg::BatchAborted { index: 0, .. })
+    );
+
+    // With enough adults, the data of every cmd is sent to its holders and the client acked.
+    for _ in 0..data_copy_count() {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+    }
+    let committed = MsgId::new();
+    let cmds = handle(wire_msg(committed, &batch)?).await?;
+    assert_eq!(cmds.len(), 2 * data_copy_count() + 1);
+    assert_matches!(
+        responses(&cmds).as_slice(),
+        [ServiceMsg::CmdAck { correlation_id, .. }] if *correlation_id == committed
+    );
+    assert_eq!(status_of(committed).await?, BatchStatus::Committed);
+    assert_eq!(status_of(MsgId::new()).await?, BatchStatus::Unknown);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn holders_query_is_answered_by_elders() -> Result<()> {
+    use sn_interface::messaging::data::{DataHolders, HoldersQuery, QueryResponse, ServiceMsg};
+    use sn_interface::messaging::ServiceAuth;
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    let name = xor_name::rand::random();
+    let client = Keypair::new_ed25519();
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+    let payload = WireMsg::serialize_msg_payload(&ServiceMsg::HoldersQuery(HoldersQuery { name }))?;
+    let auth = ServiceAuth {
+        public_key: client.public_key(),
+        signature: client.sign(&payload),
+    };
+    let wire_msg = WireMsg::new_msg(
+        MsgId::new(),
+        payload,
+        AuthKind::Service(auth),
+        DstLocation::Section {
+            name,
+            section_pk: section_key,
+        },
+    )?;
+
+    let cmds = dispatcher
+        .process_cmd(
+            Cmd::HandleMsg {
+                sender: client_peer,
+                wire_msg,
+                original_bytes: None,
+            },
+            "cmd-id",
+        )
+        .await?;
+
+    // Without adults, nobody holds the data, and the elder says it only knows who should.
+    assert_eq!(cmds.len(), 1);
+    assert_matches!(&cmds[0], Cmd::SendMsg { recipients, wire_msg } => {
+        assert_eq!(recipients, &vec![client_peer]);
+        assert_matches!(wire_msg.clone().into_msg(), Ok(MsgType::Service {
+            msg: ServiceMsg::QueryResponse {
+                response: QueryResponse::GetDataHolders((Ok(DataHolders {
+                    prefix,
+                    section_key: key,
+                    holders,
+                    recorded,
+                }), _)),
+                ..
+            },
+            ..
+        }) => {
+            assert_eq!(prefix, Prefix::default());
+            assert_eq!(key, section_key);
+            assert!(holders.is_empty());
+            assert!(!recorded);
+        });
+    });
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consistent_query_is_held_until_adults_store_the_write() -> Result<()> {
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::data::{DataCmd, DataQuery, ServiceMsg};
+    use sn_interface::messaging::system::NodeEvent;
+    use sn_interface::messaging::ServiceAuth;
+    use sn_interface::types::{utils::random_bytes, Chunk, ChunkAddress};
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let elder_info = nodes.remove(0);
+    let node = Node::new(
+        create_comm().await?,
+        elder_info.clone(),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+    let mut adults = vec![];
+    for _ in 0..data_copy_count() {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        adults.push(info);
+    }
+
+    let chunk = Chunk::new(random_bytes(100));
+    let chunk_name = *chunk.name();
+    let client = Keypair::new_ed25519();
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+    let from_client = |msg: &ServiceMsg| -> Result<Cmd> {
+        let payload = WireMsg::serialize_msg_payload(msg)?;
+        let auth = ServiceAuth {
+            public_key: client.public_key(),
+            signature: client.sign(&payload),
+        };
+        let wire_msg = WireMsg::new_msg(
+            MsgId::new(),
+            payload,
+            AuthKind::Service(auth),
+            DstLocation::Section {
+                name: chunk_name,
+                section_pk: section_key,
+            },
+        )?;
+        Ok(Cmd::HandleMsg {
+            sender: client_peer,
+            wire_msg,
+            original_bytes: None,
+        })
+    };
+    let stored_by = |adult: &NodeInfo| -> Result<Cmd> {
+        let wire_msg = WireMsg::single_src(
+            adult,
+            DstLocation::Node {
+                name: elder_info.name(),
+                section_pk: section_key,
+            },
+            SystemMsg::NodeEvent(NodeEvent::ReplicasStored {
+                node_id: PublicKey::from(adult.keypair.public),
+                replicas: BTreeSet::from([chunk_name]),
+            }),
+            section_key,
+        )?;
+        Ok(Cmd::HandleMsg {
+            sender: adult.peer(),
+            wire_msg,
+            original_bytes: None,
+        })
+    };
+
+    // The ack of the write carries its token, issued by the elder.
+    let cmds = dispatcher
+        .process_cmd(
+            from_client(&ServiceMsg::Cmd(DataCmd::StoreChunk(chunk)))?,
+            "cmd-id",
+        )
+        .await?;
+    let token = cmds
+        .iter()
+        .find_map(|cmd| match cmd {
+            Cmd::SendMsg { wire_msg, .. } => match wire_msg.clone().into_msg() {
+                Ok(MsgType::Service {
+                    msg: ServiceMsg::CmdAck { token, .. },
+                    ..
+                }) => token,
+                _ => None,
+            },
+            _ => None,
+        })
+        .ok_or_else(|| eyre!("no token acked"))?;
+    assert_eq!(token.elder, elder_info.name());
+    assert_eq!(token.section_key, section_key);
+
+    // A query read the write is held until every holder stored it...
+    let query = ServiceMsg::ConsistentQuery {
+        query: DataQuery::GetChunk(ChunkAddress(chunk_name)),
+        tokens: vec![token],
+    };
+    assert!(dispatcher
+        .process_cmd(from_client(&query)?, "cmd-id")
+        .await?
+        .is_empty());
+    let (last, others) = adults.split_last().ok_or_else(|| eyre!("no adults"))?;
+    for adult in others {
+        assert!(dispatcher
+            .process_cmd(stored_by(adult)?, "cmd-id")
+            .await?
+            .is_empty());
+    }
+    // ...it's then read from them.
+    let cmds = dispatcher.process_cmd(stored_by(last)?, "cmd-id").await?;
+    let adult_names: BTreeSet<_> = adults.iter().map(NodeInfo::name).collect();
+    assert!(cmds.iter().any(|cmd| matches!(
+        cmd,
+        Cmd::SendMsg { recipients, .. } | Cmd::SendMsgDeliveryGroup { recipients, .. }
+            if recipients.iter().all(|peer| adult_names.contains(&peer.name()))
+    )));
+
+    // Once caught up, queries aren't held anymore.
+    assert!(!dispatcher
+        .process_cmd(from_client(&query)?, "cmd-id")
+        .await?
+        .is_empty());
 
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn membership_churn_starts_on_join_request_from_relocated_node() -> Result<()> {
-    init_test_logger();
-    let _span = tracing::info_span!("receive_join_request_from_relocated_node").entered();
-
-    let (section_auth, mut nodes, sk_set) = create_section_auth();
-
-    let pk_set = sk_set.public_keys();
-    let section_key = pk_set.public_key();
+async fn traced_query_records_every_node_handling_it() -> Result<()> {
+    use sn_interface::data_copy_count;
+    use sn_interface::messaging::data::{DataQuery, QueryResponse, ServiceMsg};
+    use sn_interface::messaging::{ServiceAuth, TraceContext};
+    use sn_interface::types::{utils::random_bytes, Chunk, ChunkAddress, ReplicatedData};
 
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
     let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
-    let node = nodes.remove(0);
-    let relocated_node_old_name = node.name();
-    let relocated_node_old_keypair = node.keypair.clone();
     let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let elder_info = nodes.remove(0);
     let node = Node::new(
         create_comm().await?,
-        node,
-        section,
+        elder_info.clone(),
+        section.clone(),
         Some(section_key_share),
         mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
         UsedSpace::new(max_capacity),
@@ -263,78 +3483,322 @@ async fn membership_churn_starts_on_join_request_from_relocated_node() -> Result
     .await?;
     let dispatcher = Dispatcher::new(node);
 
-    let relocated_node = NodeInfo::new(
-        ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE + 1),
-        gen_addr(),
-    );
+    let chunk = Chunk::new(random_bytes(100));
+    let data = ReplicatedData::Chunk(chunk.clone());
+    let mut adults = BTreeMap::new();
+    let mut adult_roots = vec![];
+    for _ in 0..data_copy_count() {
+        let info = gen_info(MIN_ADULT_AGE, None);
+        let _status = handle_online_cmd(&info.peer(), &sk_set, &dispatcher, &section_auth).await?;
+        let root = tempdir()?;
+        let adult = Node::new(
+            create_comm().await?,
+            info.clone(),
+            section.clone(),
+            None,
+            mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+            UsedSpace::new(max_capacity),
+            root.path().to_path_buf(),
+        )
+        .await?;
+        let _level = adult.data_storage.store(&data).await?;
+        adult_roots.push(root);
+        let _prev = adults.insert(info.name(), (info, adult));
+    }
 
-    let relocate_details = RelocateDetails {
-        previous_name: relocated_node_old_name,
-        dst: relocated_node_old_name,
-        dst_section_key: section_key,
-        age: relocated_node.age(),
+    // The client asks for the query to be traced.
+    let client = Keypair::new_ed25519();
+    let query = DataQuery::GetChunk(ChunkAddress(*chunk.name()));
+    let payload = WireMsg::serialize_msg_payload(&ServiceMsg::Query(query))?;
+    let auth = ServiceAuth {
+        public_key: client.public_key(),
+        signature: client.sign(&payload),
+    };
+    let mut wire_msg = WireMsg::new_msg(
+        MsgId::new(),
+        payload,
+        AuthKind::Service(auth),
+        DstLocation::Section {
+            name: *chunk.name(),
+            section_pk: section_key,
+        },
+    )?;
+    wire_msg.set_trace(Some(TraceContext::default()));
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
+
+    let duties = |wire_msg: &WireMsg| -> Vec<(XorName, String)> {
+        wire_msg
+            .trace()
+            .map(|trace| {
+                trace
+                    .hops
+                    .iter()
+                    .map(|hop| (hop.node, hop.duty.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let sent = |cmds: Vec<Cmd>| -> Vec<(Vec<Peer>, WireMsg)> {
+        cmds.into_iter()
+            .filter_map(|cmd| match cmd {
+                Cmd::SendMsg {
+                    recipients,
+                    wire_msg,
+                }
+                | Cmd::SendMsgDeliveryGroup {
+                    recipients,
+                    wire_msg,
+                    ..
+                } => Some((recipients, wire_msg)),
+                _ => None,
+            })
+            .collect()
     };
 
-    let node_state = NodeState::relocated(
-        relocated_node.peer(),
-        Some(relocated_node_old_name),
-        relocate_details,
+    // The elder asks the adults, each being told the elder took the query.
+    let to_adults = sent(
+        dispatcher
+            .node
+            .handle_msg(client_peer, wire_msg, None)
+            .await?,
     );
-    let relocate_proof = section_signed(sk_set.secret_key(), node_state.to_msg())?;
+    assert!(!to_adults.is_empty());
+    let elder_hop = (elder_info.name(), "elder: client query".to_string());
+    for (_, wire_msg) in &to_adults {
+        assert_eq!(duties(wire_msg), vec![elder_hop.clone()]);
+    }
 
-    let signature_over_new_name =
-        ed25519::sign(&relocated_node.name().0, &relocated_node_old_keypair);
+    // One of them responds, adding itself.
+    let (recipients, query_msg) = to_adults[0].clone();
+    let (adult_info, adult) = &adults[&recipients[0].name()];
+    let to_elder = sent(adult.handle_msg(elder_info.peer(), query_msg, None).await?);
+    assert_eq!(to_elder.len(), 1);
+    let adult_hop = (adult_info.name(), "adult: data query".to_string());
+    assert_eq!(
+        duties(&to_elder[0].1),
+        vec![elder_hop.clone(), adult_hop.clone()]
+    );
 
-    let wire_msg = WireMsg::single_src(
-        &relocated_node,
+    // And the client gets the chunk along with all of the hops it took.
+    let to_client = sent(
+        dispatcher
+            .node
+            .handle_msg(adult_info.peer(), to_elder[0].1.clone(), None)
+            .await?,
+    );
+    assert_eq!(to_client.len(), 1);
+    let (recipients, response) = &to_client[0];
+    assert_eq!(recipients, &vec![client_peer]);
+    assert_matches!(
+        response.clone().into_msg(),
+        Ok(MsgType::Service {
+            msg: ServiceMsg::QueryResponse {
+                response: QueryResponse::GetChunk(Ok(received)),
+                ..
+            },
+            ..
+        }) => assert_eq!(received, chunk)
+    );
+    assert_eq!(
+        duties(response),
+        vec![
+            elder_hop,
+            adult_hop,
+            (elder_info.name(), "elder: data query response".to_string())
+        ]
+    );
+
+    Ok(())
+}
+
+// Logs written to memory, for a test to look into.
+#[derive(Clone, Default)]
+struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(mut logs) = self.0.lock() {
+            logs.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedLogs {
+    // The `cmd_id` and `trace_id` of the cmds logged, as json lines.
+    fn cmd_ids(&self) -> Vec<(String, String)> {
+        let logs = self.0.lock().map(|logs| logs.clone()).unwrap_or_default();
+        String::from_utf8_lossy(&logs)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter(|line| line["span"]["name"] == "process_cmd")
+            .filter_map(|line| {
+                let span = &line["span"];
+                Some((
+                    span["cmd_id"].as_str()?.to_string(),
+                    span["trace_id"].as_str()?.to_string(),
+                ))
+            })
+            .collect()
+    }
+}
+
+// On a single thread, for the cmds spawned to log to the subscriber set for it.
+#[tokio::test]
+async fn logs_of_the_cmds_a_msg_leads_to_share_its_trace_id() -> Result<()> {
+    use sn_interface::messaging::data::{DataCmd, ServiceMsg};
+    use sn_interface::messaging::ServiceAuth;
+    use sn_interface::types::{utils::random_bytes, Chunk};
+
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::TRACE)
+        .with_writer({
+            let logs = logs.clone();
+            move || logs.clone()
+        })
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let section_key = sk_set.public_keys().public_key();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = std::sync::Arc::new(Dispatcher::new(node));
+
+    // A chunk sent under the wrong name, which the client is sent an error for.
+    let client = Keypair::new_ed25519();
+    let payload = WireMsg::serialize_msg_payload(&ServiceMsg::Cmd(DataCmd::StoreChunk(
+        Chunk::new(random_bytes(100)),
+    )))?;
+    let auth = ServiceAuth {
+        public_key: client.public_key(),
+        signature: client.sign(&payload),
+    };
+    let msg_id = MsgId::new();
+    let wire_msg = WireMsg::new_msg(
+        msg_id,
+        payload,
+        AuthKind::Service(auth),
         DstLocation::Section {
-            name: XorName::from(PublicKey::Bls(section_key)),
+            name: xor_name::rand::random(),
             section_pk: section_key,
         },
-        SystemMsg::JoinAsRelocatedRequest(Box::new(JoinAsRelocatedRequest {
-            section_key,
-            relocate_proof,
-            signature_over_new_name,
-        })),
-        section_key,
     )?;
+    let client_peer = Peer::new(XorName::from(client.public_key()), gen_addr());
 
-    let _ = dispatcher
-        .process_cmd(
+    let trace_id = super::msg_cmd_id(msg_id);
+    dispatcher
+        .handle_cmd_and_offshoots(
             Cmd::HandleMsg {
-                sender: relocated_node.peer(),
+                sender: client_peer,
                 wire_msg,
                 original_bytes: None,
             },
-            "cmd-id",
+            Some(trace_id.clone()),
         )
         .await?;
 
-    assert!(dispatcher
-        .node
-        .membership
-        .read()
-        .await
-        .as_ref()
-        .unwrap()
-        .is_churn_in_progress());
+    // Both handling the msg and sending the error are logged under the msg's id.
+    let sent = format!("{}.0", trace_id);
+    timeout(Duration::from_secs(10), async {
+        while !logs.cmd_ids().iter().any(|(cmd_id, _)| cmd_id == &sent) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    let cmd_ids = logs.cmd_ids();
+    assert!(cmd_ids.iter().any(|(cmd_id, _)| cmd_id == &trace_id));
+    for (cmd_id, logged_trace_id) in cmd_ids {
+        assert!(cmd_id.starts_with(&trace_id));
+        assert_eq!(logged_trace_id, trace_id);
+    }
 
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn handle_agreement_on_online() -> Result<()> {
-    let (event_tx, mut event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+async fn membership_changes_are_recorded_in_history() -> Result<()> {
+    let (event_tx, _event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        event_tx,
+        UsedSpace::new(max_capacity),
+        root_storage_dir.clone(),
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
 
-    let prefix = Prefix::default();
+    let mut joined = vec![];
+    for _ in 0..3 {
+        let peer = create_peer(MIN_ADULT_AGE);
+        let _status = handle_online_cmd(&peer, &sk_set, &dispatcher, &section_auth).await?;
+        joined.push(peer);
+    }
 
-    let (section_auth, mut nodes, sk_set) = gen_section_authority_provider(prefix, elder_count());
+    let left = section_signed(sk_set.secret_key(), NodeState::left(joined[0], None))?;
+    let _cmds = dispatcher
+        .node
+        .handle_node_left(left.value, left.sig)
+        .await?;
+
+    let history = membership_history::read_history(&root_storage_dir, None, None)?;
+    let adult_counts: Vec<_> = history
+        .iter()
+        .map(|snapshot| snapshot.adult_count)
+        .collect();
+    assert_eq!(adult_counts, [1, 2, 3, 2]);
+    assert!(history
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+
+    let elders: Vec<_> = section_auth.names().into_iter().collect();
+    for snapshot in &history {
+        assert_eq!(snapshot.prefix, Prefix::default());
+        assert_eq!(snapshot.elders, elders);
+        assert_eq!(
+            snapshot.section_key,
+            MembershipSnapshot::key_fingerprint(&section_auth.section_key())
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn membership_decisions_are_raised_to_every_subscriber() -> Result<()> {
+    let (event_tx, _event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
     let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
-    let node = nodes.remove(0);
     let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
     let node = Node::new(
         create_comm().await?,
-        node,
+        nodes.remove(0),
         section,
         Some(section_key_share),
         event_tx,
@@ -343,16 +3807,131 @@ async fn handle_agreement_on_online() -> Result<()> {
     )
     .await?;
     let dispatcher = Dispatcher::new(node);
+    let subscribe = || {
+        MembershipEvents::new(
+            dispatcher.node.subscribe_membership_events(),
+            dispatcher.shutdown.subscribe(),
+        )
+    };
+    let mut subscribers = [subscribe(), subscribe()];
 
-    let new_peer = create_peer(MIN_ADULT_AGE);
+    let peer = create_peer(MIN_ADULT_AGE);
+    let _status = handle_online_cmd(&peer, &sk_set, &dispatcher, &section_auth).await?;
+    let left = section_signed(sk_set.secret_key(), NodeState::left(peer, None))?;
+    let _cmds = dispatcher
+        .node
+        .handle_node_left(left.value, left.sig)
+        .await?;
 
-    let status = handle_online_cmd(&new_peer, &sk_set, &dispatcher, &section_auth).await?;
-    assert!(status.node_approval_sent);
+    // Agreeing on what joins already are raises nothing.
+    for allowed in [false, false, true] {
+        let proposal = Proposal::JoinsAllowed(allowed);
+        let sig = keyed_signed(sk_set.secret_key(), &proposal.as_signable_bytes()?);
+        let _cmds = dispatcher
+            .process_cmd(Cmd::HandleAgreement { proposal, sig }, "cmd-id")
+            .await?;
+    }
 
-    assert_matches!(event_rx.recv().await, Some(Event::MemberJoined { name, age, .. }) => {
-        assert_eq!(name, new_peer.name());
-        assert_eq!(age, MIN_ADULT_AGE);
-    });
+    for events in &mut subscribers {
+        assert_eq!(
+            events.next().await,
+            Some(MembershipEvent::PeerJoined {
+                name: peer.name(),
+                previous_name: None,
+                age: MIN_ADULT_AGE,
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(MembershipEvent::PeerLeft {
+                name: peer.name(),
+                age: MIN_ADULT_AGE,
+                relocated: false,
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(MembershipEvent::JoinsAllowedChanged { allowed: false })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(MembershipEvent::JoinsAllowedChanged { allowed: true })
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batched_proposals_each_agree_on_a_single_round_of_sig_shares() -> Result<()> {
+    let (section_auth, mut nodes, sk_set) =
+        gen_section_authority_provider(Prefix::default(), elder_count());
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+    let section_key = section_auth.section_key();
+    let proposals: Vec<_> = (0..3)
+        .map(|_| Proposal::Offline(NodeState::left(create_peer(MIN_ADULT_AGE), None)))
+        .collect();
+    let batch = ProposalBatch::new(proposals.clone());
+
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::channel(TEST_EVENT_CHANNEL_SIZE).0,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+
+    // The shares of the other elders, each sent once for the whole batch.
+    let mut agreed = vec![];
+    for (index, elder) in (1..=sk_set.threshold() + 1).zip(nodes) {
+        let sig_share = batch.sign_with_key_share(
+            sk_set.public_keys(),
+            index,
+            &sk_set.secret_key_share(index),
+        )?;
+        let wire_msg = WireMsg::single_src(
+            &elder,
+            DstLocation::Section {
+                name: XorName::from(PublicKey::Bls(section_key)),
+                section_pk: section_key,
+            },
+            SystemMsg::ProposeBatch {
+                proposals: batch.clone().into_msg(),
+                sig_share,
+            },
+            section_key,
+        )?;
+        let cmds = dispatcher
+            .process_cmd(
+                Cmd::HandleMsg {
+                    sender: elder.peer(),
+                    wire_msg,
+                    original_bytes: None,
+                },
+                "cmd-id",
+            )
+            .await?;
+        agreed.extend(cmds.into_iter().filter_map(|cmd| match cmd {
+            Cmd::HandleAgreement { proposal, sig } => Some((proposal, sig)),
+            _ => None,
+        }));
+    }
+
+    assert_eq!(
+        agreed
+            .iter()
+            .map(|(proposal, _)| proposal.clone())
+            .collect::<Vec<_>>(),
+        proposals
+    );
+    for (_, sig) in &agreed {
+        assert!(sig.verify(&batch.as_signable_bytes()?));
+    }
 
     Ok(())
 }
@@ -661,6 +4240,58 @@ async fn handle_agreement_on_offline_of_non_elder() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn adult_index_is_rebuilt_on_membership_changes_only() -> Result<()> {
+    init_test_logger();
+    let _span = tracing::info_span!("adult_index_is_rebuilt_on_membership_changes_only").entered();
+
+    let (section_auth, mut nodes, sk_set) = create_section_auth();
+    let (section, section_key_share) = create_section(&sk_set, &section_auth).await?;
+
+    let adult = create_peer(MIN_ADULT_AGE);
+    let node_state = section_signed(sk_set.secret_key(), NodeState::joined(adult, None))?;
+    let _updated = section.update_member(node_state).await;
+
+    let (event_tx, _event_rx) = mpsc::channel(TEST_EVENT_CHANNEL_SIZE);
+    let (max_capacity, root_storage_dir) = create_test_max_capacity_and_root_storage()?;
+    let node = Node::new(
+        create_comm().await?,
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        event_tx,
+        UsedSpace::new(max_capacity),
+        root_storage_dir,
+    )
+    .await?;
+    let dispatcher = Dispatcher::new(node);
+    let rebuilds = || async { dispatcher.node.adult_index.read().await.rebuilds() };
+
+    // Built once, however many times data holders are chosen...
+    for _ in 0..3 {
+        let index = dispatcher.node.adult_index().await;
+        assert_eq!(index.len(), 1);
+        assert!(index.contains(&adult.name()));
+    }
+    assert_eq!(rebuilds().await, 1);
+
+    // ...until an adult leaves.
+    let node_state = section_signed(sk_set.secret_key(), NodeState::left(adult, None))?;
+    assert!(
+        dispatcher
+            .node
+            .network_knowledge()
+            .update_member(node_state)
+            .await
+    );
+
+    assert!(dispatcher.node.adult_index().await.is_empty());
+    assert!(dispatcher.node.adult_index().await.is_empty());
+    assert_eq!(rebuilds().await, 2);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn handle_agreement_on_offline_of_elder() -> Result<()> {
     let (section_auth, mut nodes, sk_set) = create_section_auth();
@@ -0,0 +1,134 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::Result;
+use crate::persisted::{self, Error as PersistedError};
+use std::path::Path;
+
+// Filename for storing the max capacity last set at runtime, in bytes
+const MAX_CAPACITY_FILENAME: &str = "max_capacity";
+
+// Format of the file, the capacity as a little-endian u64. Version 0 is decimal text, and
+// migrated when read.
+const MAX_CAPACITY_VERSION: u16 = 1;
+
+/// Writes the max capacity set at runtime to disk, for it to be honoured across restarts.
+pub(crate) async fn store_max_capacity(root_dir: &Path, max_capacity: usize) -> Result<()> {
+    let path = root_dir.join(MAX_CAPACITY_FILENAME);
+    let bytes = (max_capacity as u64).to_le_bytes().to_vec();
+    persisted::write_async(path, MAX_CAPACITY_VERSION, bytes).await?;
+
+    Ok(())
+}
+
+/// Returns the max capacity last set at runtime, or None if it never was.
+///
+/// A damaged file is discarded, with a warning, the configured capacity applying again.
+pub(crate) async fn get_max_capacity(root_dir: &Path) -> Result<Option<usize>> {
+    let path = root_dir.join(MAX_CAPACITY_FILENAME);
+    match read_max_capacity(&path).await {
+        Ok(max_capacity) => Ok(max_capacity),
+        Err(error) => {
+            warn!(
+                "Discarding the max capacity set at runtime, falling back to the configured one: {}",
+                error
+            );
+            tokio::fs::remove_file(&path).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn read_max_capacity(path: &Path) -> Result<Option<usize>, PersistedError> {
+    let read = match persisted::read_async(path.to_path_buf()).await? {
+        Some(read) => read,
+        None => return Ok(None),
+    };
+    let max_capacity = match read.version {
+        MAX_CAPACITY_VERSION => {
+            let bytes: [u8; 8] = read
+                .payload
+                .as_slice()
+                .try_into()
+                .map_err(|_| PersistedError::corrupt(path, "not a u64"))?;
+            u64::from_le_bytes(bytes)
+        }
+        0 => {
+            let max_capacity = String::from_utf8_lossy(&read.payload)
+                .trim()
+                .parse()
+                .map_err(|err| PersistedError::corrupt(path, err))?;
+            info!("Migrating the max capacity at {}", path.display());
+            persisted::write_async(
+                path.to_path_buf(),
+                MAX_CAPACITY_VERSION,
+                u64::to_le_bytes(max_capacity).to_vec(),
+            )
+            .await
+            .map_err(|err| PersistedError::Io(path.to_path_buf(), err))?;
+            max_capacity
+        }
+        found => {
+            return Err(PersistedError::WrongVersion {
+                path: path.to_path_buf(),
+                found,
+                expected: MAX_CAPACITY_VERSION,
+            })
+        }
+    };
+    usize::try_from(max_capacity)
+        .map(Some)
+        .map_err(|err| PersistedError::corrupt(path, err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_max_capacity, store_max_capacity, MAX_CAPACITY_FILENAME};
+    use crate::persisted::damage;
+    use eyre::Result;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn max_capacity_to_and_from_file() -> Result<()> {
+        let root = tempdir()?;
+
+        assert_eq!(get_max_capacity(root.path()).await?, None);
+
+        store_max_capacity(root.path(), 50 * 1024 * 1024 * 1024).await?;
+        store_max_capacity(root.path(), 20 * 1024 * 1024 * 1024).await?;
+        assert_eq!(
+            get_max_capacity(root.path()).await?,
+            Some(20 * 1024 * 1024 * 1024)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn damaged_max_capacity_falls_back_to_the_configured_one() -> Result<()> {
+        let root = tempdir()?;
+        let path = root.path().join(MAX_CAPACITY_FILENAME);
+        store_max_capacity(root.path(), 1024).await?;
+
+        for way in 0..damage::ways(&path)? {
+            store_max_capacity(root.path(), 1024).await?;
+            damage::apply(&path, way)?;
+            assert_eq!(get_max_capacity(root.path()).await?, None);
+            assert!(!path.exists());
+        }
+
+        // Capacities set before files were framed are migrated.
+        fs::write(&path, "2048")?;
+        assert_eq!(get_max_capacity(root.path()).await?, Some(2048));
+        assert_ne!(fs::read(&path)?, b"2048");
+        assert_eq!(get_max_capacity(root.path()).await?, Some(2048));
+
+        Ok(())
+    }
+}
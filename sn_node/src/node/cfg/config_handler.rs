@@ -6,8 +6,30 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::{Error, NetworkConfig, Result};
+use crate::attestation::REDACTED;
+use crate::dbs::{ChunkBackendKind, Watermarks};
+use crate::dbs::{
+    DEFAULT_BACKGROUND_READ_BYTES_PER_SEC, DEFAULT_BACKGROUND_READ_IOPS,
+    DEFAULT_CHUNK_JOURNAL_FLUSH_INTERVAL, DEFAULT_INTERACTIVE_READ_LATENCY,
+};
+#[cfg(feature = "back-pressure")]
+use crate::node::core::{BackPressureStrategy, DEFAULT_BACK_PRESSURE_REPORT_INTERVAL};
+use crate::node::{
+    core::{
+        DEFAULT_AUDIT_CHUNKS_PER_TICK, DEFAULT_CLIENT_CONNS_PER_S,
+        DEFAULT_COMPRESSION_THRESHOLD_KB, DEFAULT_DATA_CACHE_MB, DEFAULT_DATA_CACHE_TTL,
+        DEFAULT_DISK_FAILURE_THRESHOLD, DEFAULT_DISK_RECOVERY_THRESHOLD, DEFAULT_HEDGE_DELAY,
+        DEFAULT_MAX_CLIENT_CONNS, DEFAULT_MAX_NODE_CONNS, DEFAULT_MSG_AUDIT_CAPACITY,
+        DEFAULT_MSG_FILTER_TTL, DEFAULT_NODE_CONNS_PER_S, DEFAULT_REGISTRY_SLICES_PER_TICK,
+        DEFAULT_RELAY_KBPS, DEFAULT_RELAY_MAX_PEERS, DEFAULT_RELOCATION_MAX_KEY_LAG,
+        DEFAULT_RELOCATION_VALIDITY, DEFAULT_RETRY_BASE_DELAY, DEFAULT_RETRY_MAX_ATTEMPTS,
+        DEFAULT_SCRUB_CHUNKS_PER_TICK, DEFAULT_STREAM_THRESHOLD_KB, DEFAULT_WARM_UP,
+        DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT,
+    },
+    Error, NetworkConfig, Result,
+};
 use serde::{Deserialize, Serialize};
+use sn_interface::types::KeySeed;
 use std::{
     collections::BTreeSet,
     io::{self},
@@ -29,6 +51,7 @@ const DEFAULT_ROOT_DIR_NAME: &str = "root_dir";
 const DEFAULT_MAX_CAPACITY: usize = 10 * 1024 * 1024 * 1024; // 10GB
 #[cfg(any(target_arch = "arm", target_arch = "armv7"))]
 const DEFAULT_MAX_CAPACITY: usize = usize::MAX; // This will be 2^32 on these architectures.
+const DEFAULT_DB_RESERVE_MB: usize = 100;
 
 /// Node configuration
 #[derive(Default, Clone, Debug, Serialize, Deserialize, StructOpt)]
@@ -69,14 +92,26 @@ pub struct Config {
     #[structopt(long, default_value = "100")] // 100*10mb files by default
     pub logs_uncompressed: usize,
     /// Attempt to self-update?
+    #[cfg(feature = "self-update")]
     #[structopt(long)]
     pub update: bool,
     /// Attempt to self-update without starting the node process
+    #[cfg(feature = "self-update")]
     #[structopt(long)]
     pub update_only: bool,
-    /// Outputs logs in json format for easier processing
+    /// Outputs logs in json format for easier processing, one object per line. Those of the
+    /// handling of a msg, and of everything it leads to, share the msg's id as their `trace_id`.
     #[structopt(short, long)]
     pub json_logs: bool,
+    /// Also write logs in a compact binary format, to `sn_node.blog` within the log directory.
+    /// Decode them with `sn_node logs read <file>`.
+    #[cfg(feature = "binary-logs")]
+    #[structopt(long)]
+    pub binary_logs: bool,
+    /// Write logs in the compact binary format only, skipping the text logs
+    #[cfg(feature = "binary-logs")]
+    #[structopt(long)]
+    pub binary_logs_only: bool,
     /// print node resourse usage to stdout
     #[structopt(long)]
     pub resource_logs: bool,
@@ -103,11 +138,205 @@ pub struct Config {
     /// be used, if specified.
     #[structopt(long, parse(try_from_str = parse_public_addr))]
     pub public_addr: Option<SocketAddr>,
+    /// Local address to listen for clients on, apart from nodes, each with its own limits.
+    ///
+    /// When unspecified, clients connect on the same address as nodes. Clients reaching the
+    /// address for nodes, and nodes reaching this one, are redirected to the right one.
+    #[structopt(long)]
+    pub client_local_addr: Option<SocketAddr>,
+    /// Connections the listener for nodes holds open at most. It covers clients too, unless
+    /// they have a listener of their own.
+    #[structopt(long)]
+    pub max_node_conns: Option<usize>,
+    /// New connections per second the listener for nodes accepts.
+    #[structopt(long)]
+    pub node_conns_per_sec: Option<u32>,
+    /// Connections the listener for clients holds open at most.
+    #[structopt(long)]
+    pub max_client_conns: Option<usize>,
+    /// New connections per second the listener for clients accepts.
+    #[structopt(long)]
+    pub client_conns_per_sec: Option<u32>,
+    /// Size in KiB of the msgs above which they're streamed to peers in frames, reassembled and
+    /// checked on receipt, rather than sent whole. 512 when unspecified, 0 disables streaming.
+    #[structopt(long)]
+    pub stream_threshold_kb: Option<usize>,
+    /// Size in KiB of the msgs above which they're compressed, over the connections whose peer
+    /// agreed on a codec when connecting. 8 when unspecified, 0 disables compression.
+    #[structopt(long)]
+    pub compression_threshold_kb: Option<usize>,
+    /// KiB per second received from any one peer at most, reading its msgs being held off past
+    /// it. Unset or 0 is uncapped.
+    #[structopt(long)]
+    pub peer_max_in_kbps: Option<u64>,
+    /// KiB per second sent to any one peer at most, sends to it being held off past it. Unset or
+    /// 0 is uncapped.
+    #[structopt(long)]
+    pub peer_max_out_kbps: Option<u64>,
+    /// Msgs received and sent last that are kept in memory, with what became of them, for the
+    /// control socket's `msg_audit` to dump. 1000 when unspecified, 0 keeps none.
+    #[structopt(long)]
+    pub msg_audit_len: Option<usize>,
+    /// KiB from which chunks are erasure coded by elders into 8 data and 4 parity shares, each
+    /// stored by a distinct adult, rather than replicated in full. Unset or 0 replicates all
+    /// chunks.
+    #[structopt(long)]
+    pub erasure_coding_threshold_kb: Option<usize>,
+    /// Nodes the network can't connect to which we relay msgs for at most. 8 when unspecified,
+    /// 0 disables relaying for others.
+    #[structopt(long)]
+    pub relay_max_peers: Option<usize>,
+    /// KiB per second relayed for any one node at most, either way together, its msgs past it
+    /// being dropped. 512 when unspecified.
+    #[structopt(long)]
+    pub relay_max_kbps: Option<u64>,
+    /// How the msgs per s section members can send us is decided: `equal-share`, `aimd`,
+    /// `token-bucket` or `latency-gradient`. Equal share when unspecified.
+    #[cfg(feature = "back-pressure")]
+    #[structopt(long)]
+    pub back_pressure_strategy: Option<BackPressureStrategy>,
+    /// Seconds between the times section members are told the msgs per s they can send us, if
+    /// it changed significantly. 60 when unspecified.
+    #[cfg(feature = "back-pressure")]
+    #[structopt(long)]
+    pub back_pressure_report_interval_secs: Option<u64>,
     /// This flag can be used to skip automated port forwarding using IGD. This is used when running
     /// a network on a LAN or when a node is connected to the internet directly, without a router,
     /// e.g. Digital Ocean droplets.
     #[structopt(long)]
     pub skip_auto_port_forwarding: bool,
+    /// Number of consecutive failed disk self-tests after which the node stops accepting new
+    /// data, until its disk recovers.
+    #[structopt(long)]
+    pub disk_failure_threshold: Option<usize>,
+    /// Number of consecutive passed disk self-tests after which a node with a failing disk
+    /// accepts new data again.
+    #[structopt(long)]
+    pub disk_recovery_threshold: Option<usize>,
+    /// Disk space, in MB, kept for the node's dbs (registers and other records). Chunks are
+    /// refused rather than eating into it.
+    #[structopt(long)]
+    pub db_reserve_mb: Option<usize>,
+    /// Percent of its max capacity past which an adult tells its elders it's full, for them to
+    /// stop giving it new data. 90 when unspecified.
+    #[structopt(long)]
+    pub storage_soft_watermark_percent: Option<u8>,
+    /// Percent of its max capacity past which an adult refuses new chunks. 95 when unspecified.
+    #[structopt(long)]
+    pub storage_hard_watermark_percent: Option<u8>,
+    /// Backend chunks are kept in, `files` (a file per chunk) or `sled` (an embedded db). A
+    /// store keeps the backend it was created with, files when unspecified.
+    #[structopt(long)]
+    pub chunk_backend: Option<ChunkBackendKind>,
+    /// Encrypt the chunks of a new store at rest, with a random key persisted along the node's
+    /// keys as `storage_key`, which must then be kept to read them back. A store keeps the encryption it
+    /// was created with, and one created unencrypted is refused with this set.
+    #[structopt(long)]
+    pub encrypt_chunks: bool,
+    /// Encrypt the node's secret keys at rest, sealed in a keystore with a passphrase taken from
+    /// the `SN_NODE_KEYSTORE_PASSPHRASE` env var, the OS keyring, or the terminal. Keys already
+    /// in the clear are encrypted with `sn_node keystore encrypt` first.
+    #[structopt(long)]
+    pub encrypt_keys: bool,
+    /// BIP-39 mnemonic the node's keys are derived from rather than generated at random: its
+    /// reward keypair, and the network keypair it first joins with. Backing up the mnemonic is
+    /// then enough to recreate them. Keys already in the root dir are kept.
+    #[structopt(long)]
+    pub key_seed: Option<String>,
+    /// Chunks an adult re-hashes against their names per 10s, scrubbing its storage for
+    /// corruption. 0 disables scrubbing.
+    #[structopt(long)]
+    pub scrub_chunks_per_tick: Option<usize>,
+    /// Chunks an elder challenges their holders to prove holding per 30s, auditing that they're
+    /// replicated, and replicating them again to those which can't. 0 disables audits.
+    #[structopt(long)]
+    pub audit_chunks_per_tick: Option<usize>,
+    /// Node msgs remembered, by id and sender, for those received again to be dropped rather
+    /// than handled twice, also across restarts. Unset or 0 disables the filter.
+    #[structopt(long)]
+    pub msg_filter_capacity: Option<usize>,
+    /// Seconds node msgs are remembered for by the msg filter.
+    #[structopt(long)]
+    pub msg_filter_ttl_secs: Option<u64>,
+    /// Number of slices of the namespace (out of 64) whose inventory a newly promoted elder
+    /// requests from the adults per second, while rebuilding its registry of data holders.
+    #[structopt(long)]
+    pub registry_slices_per_sec: Option<usize>,
+    /// Chunk reads per second the node's background passes (replication, checks) may make,
+    /// next to serving clients.
+    #[structopt(long)]
+    pub background_read_iops: Option<u32>,
+    /// MB of chunks per second the node's background passes may read.
+    #[structopt(long)]
+    pub background_read_mbps: Option<u64>,
+    /// Latency, in ms, of the reads serving clients above which background passes pause
+    /// reading, until it recovers.
+    #[structopt(long)]
+    pub interactive_read_latency_ms: Option<u64>,
+    /// Interval, in ms, within which chunk writes are committed together to the write-ahead
+    /// journal, sharing an fsync. 0 has each chunk synced on its own, without a journal.
+    #[structopt(long)]
+    pub chunk_journal_flush_ms: Option<u64>,
+    /// Seconds after being issued that a relocation can still be used to join our section.
+    #[structopt(long)]
+    pub relocation_validity_secs: Option<u64>,
+    /// Generations the section key a relocation was issued under can be behind ours, for it to
+    /// still be used to join our section.
+    #[structopt(long)]
+    pub relocation_max_key_lag: Option<u64>,
+    /// Seconds over which adults new to the network ramp up to their full share of data, as
+    /// elders assign it. 0 disables the warm-up.
+    #[structopt(long)]
+    pub warm_up_secs: Option<u64>,
+    /// Share of its data, in percent, an adult new to the network is assigned as it joins.
+    #[structopt(long)]
+    pub warm_up_initial_share_percent: Option<u8>,
+    /// MB of chunks elders cache as they relay them to clients, for queries of popular chunks to
+    /// be answered from the cache. 0 disables the cache.
+    #[structopt(long)]
+    pub data_cache_mb: Option<usize>,
+    /// Seconds chunks are kept in the data cache for.
+    #[structopt(long)]
+    pub data_cache_ttl_secs: Option<u64>,
+    /// Ms elders give the fastest known holder of a chunk to answer a client's query, before
+    /// querying the next one too, and so on. 0 queries all holders at once.
+    #[structopt(long)]
+    pub hedged_read_delay_ms: Option<u64>,
+    /// Attempts at sending a msg, the first included, before giving up on it when it fails to
+    /// reach its recipients. 0 or 1 disable retries.
+    #[structopt(long)]
+    pub send_retry_max_attempts: Option<u32>,
+    /// Ms before the first retry of a msg which failed to be sent, doubled for each retry after.
+    #[structopt(long)]
+    pub send_retry_base_delay_ms: Option<u64>,
+    /// Address to serve Prometheus metrics on, at `/metrics`, along with a health check at
+    /// `/health`. Nothing is served when unspecified.
+    #[cfg(feature = "metrics")]
+    #[structopt(long)]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Address tokio-console connects to the node's async runtime on. Defaults to the
+    /// `TOKIO_CONSOLE_BIND` env var, or else 127.0.0.1:6669.
+    #[cfg(feature = "tokio-console")]
+    #[structopt(long)]
+    pub console_addr: Option<SocketAddr>,
+    /// OTLP collector (e.g. Jaeger or an OpenTelemetry Collector) to export the node's spans to
+    /// over gRPC, such as `http://localhost:4317`. Spans aren't exported when not given.
+    #[cfg(feature = "otlp")]
+    #[structopt(long)]
+    pub otlp_endpoint: Option<String>,
+    /// Serve diagnostics to local processes on `diagnostics.sock`, and control on
+    /// `control.sock`, both in the root dir. Unix only.
+    #[structopt(long)]
+    pub control_sockets: bool,
+    /// Token privileged cmds must carry on `control.sock`, on top of coming from the node's own
+    /// user.
+    #[structopt(long)]
+    pub control_token: Option<String>,
+    /// Loopback address to serve diagnostics and control on over TCP, in the protocol of the
+    /// control sockets, e.g. `127.0.0.1:12001`. Privileged cmds are only served on it with
+    /// `control_token` set, as any local user can connect. Nothing is served when unspecified.
+    #[structopt(long)]
+    pub rpc_addr: Option<SocketAddr>,
     /// Hard Coded contacts
     #[structopt(
         short,
@@ -116,6 +345,19 @@ pub struct Config {
         parse(try_from_str = serde_json::from_str)
     )]
     pub hard_coded_contacts: BTreeSet<SocketAddr>,
+    /// Bootstrap from the hard-coded contacts only, rather than first trying the elders of the
+    /// latest prefix map cached in the root dir.
+    #[structopt(long)]
+    pub skip_cached_contacts: bool,
+    /// DNS name whose TXT records publish the network's contacts, signed with a key certified
+    /// by its genesis key, bootstrapped from after the cached contacts and before the hard-coded
+    /// ones. Resolving it with no resolver to be found is an error.
+    #[structopt(long)]
+    pub bootstrap_dns: Option<String>,
+    /// DNS resolver to resolve `bootstrap_dns` with, e.g. `1.1.1.1:53`. The first nameserver of
+    /// /etc/resolv.conf when unspecified.
+    #[structopt(long)]
+    pub dns_resolver: Option<SocketAddr>,
     /// Genesis key of the network in hex format.
     #[structopt(long)]
     pub genesis_key: Option<String>,
@@ -199,6 +441,11 @@ impl Config {
             }
         }
 
+        #[cfg(feature = "binary-logs")]
+        if (self.binary_logs || self.binary_logs_only) && self.log_dir.is_none() {
+            return Err("Must specify --log-dir to write binary logs.".to_string());
+        }
+
         let local_ip_unspecified = self
             .local_addr
             .map(|addr| addr.ip().is_unspecified())
@@ -211,6 +458,17 @@ impl Config {
                 .to_string());
         }
 
+        if let (Some(soft), Some(hard)) = (
+            self.storage_soft_watermark_percent,
+            self.storage_hard_watermark_percent,
+        ) {
+            if soft > hard {
+                return Err(format!(
+                    "The soft storage watermark ({soft} %) cannot be above the hard one ({hard} %)."
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -225,6 +483,11 @@ impl Config {
         }
 
         self.json_logs = config.json_logs;
+        #[cfg(feature = "binary-logs")]
+        {
+            self.binary_logs = config.binary_logs;
+            self.binary_logs_only = config.binary_logs_only;
+        }
         self.resource_logs = config.resource_logs;
 
         if config.verbose > 0 {
@@ -244,8 +507,135 @@ impl Config {
         self.logs_max_lines = config.logs_max_lines();
         self.logs_uncompressed = config.logs_uncompressed();
 
-        self.update = config.update || self.update;
-        self.update_only = config.update_only || self.update_only;
+        if let Some(threshold) = config.disk_failure_threshold {
+            self.disk_failure_threshold = Some(threshold);
+        }
+
+        if let Some(threshold) = config.disk_recovery_threshold {
+            self.disk_recovery_threshold = Some(threshold);
+        }
+
+        if let Some(db_reserve_mb) = config.db_reserve_mb {
+            self.db_reserve_mb = Some(db_reserve_mb);
+        }
+
+        if let Some(soft) = config.storage_soft_watermark_percent {
+            self.storage_soft_watermark_percent = Some(soft);
+        }
+
+        if let Some(hard) = config.storage_hard_watermark_percent {
+            self.storage_hard_watermark_percent = Some(hard);
+        }
+
+        if let Some(chunk_backend) = config.chunk_backend {
+            self.chunk_backend = Some(chunk_backend);
+        }
+        self.encrypt_chunks = config.encrypt_chunks || self.encrypt_chunks;
+        self.encrypt_keys = config.encrypt_keys || self.encrypt_keys;
+        if let Some(key_seed) = config.key_seed {
+            self.key_seed = Some(key_seed);
+        }
+
+        if let Some(chunks) = config.scrub_chunks_per_tick {
+            self.scrub_chunks_per_tick = Some(chunks);
+        }
+
+        if let Some(chunks) = config.audit_chunks_per_tick {
+            self.audit_chunks_per_tick = Some(chunks);
+        }
+
+        if let Some(capacity) = config.msg_filter_capacity {
+            self.msg_filter_capacity = Some(capacity);
+        }
+
+        if let Some(ttl_secs) = config.msg_filter_ttl_secs {
+            self.msg_filter_ttl_secs = Some(ttl_secs);
+        }
+
+        if let Some(slices) = config.registry_slices_per_sec {
+            self.registry_slices_per_sec = Some(slices);
+        }
+
+        if let Some(iops) = config.background_read_iops {
+            self.background_read_iops = Some(iops);
+        }
+
+        if let Some(mbps) = config.background_read_mbps {
+            self.background_read_mbps = Some(mbps);
+        }
+
+        if let Some(latency_ms) = config.interactive_read_latency_ms {
+            self.interactive_read_latency_ms = Some(latency_ms);
+        }
+
+        if let Some(flush_ms) = config.chunk_journal_flush_ms {
+            self.chunk_journal_flush_ms = Some(flush_ms);
+        }
+
+        if let Some(validity_secs) = config.relocation_validity_secs {
+            self.relocation_validity_secs = Some(validity_secs);
+        }
+
+        if let Some(max_key_lag) = config.relocation_max_key_lag {
+            self.relocation_max_key_lag = Some(max_key_lag);
+        }
+
+        if let Some(warm_up_secs) = config.warm_up_secs {
+            self.warm_up_secs = Some(warm_up_secs);
+        }
+
+        if let Some(share) = config.warm_up_initial_share_percent {
+            self.warm_up_initial_share_percent = Some(share);
+        }
+
+        if let Some(cache_mb) = config.data_cache_mb {
+            self.data_cache_mb = Some(cache_mb);
+        }
+
+        if let Some(ttl_secs) = config.data_cache_ttl_secs {
+            self.data_cache_ttl_secs = Some(ttl_secs);
+        }
+
+        if let Some(delay_ms) = config.hedged_read_delay_ms {
+            self.hedged_read_delay_ms = Some(delay_ms);
+        }
+
+        if let Some(max_attempts) = config.send_retry_max_attempts {
+            self.send_retry_max_attempts = Some(max_attempts);
+        }
+
+        if let Some(base_delay_ms) = config.send_retry_base_delay_ms {
+            self.send_retry_base_delay_ms = Some(base_delay_ms);
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics_addr) = config.metrics_addr {
+            self.metrics_addr = Some(metrics_addr);
+        }
+
+        #[cfg(feature = "tokio-console")]
+        if let Some(console_addr) = config.console_addr {
+            self.console_addr = Some(console_addr);
+        }
+
+        #[cfg(feature = "otlp")]
+        if let Some(otlp_endpoint) = config.otlp_endpoint {
+            self.otlp_endpoint = Some(otlp_endpoint);
+        }
+
+        self.control_sockets = config.control_sockets || self.control_sockets;
+        if let Some(control_token) = config.control_token {
+            self.control_token = Some(control_token);
+        }
+        if let Some(rpc_addr) = config.rpc_addr {
+            self.rpc_addr = Some(rpc_addr);
+        }
+
+        #[cfg(feature = "self-update")]
+        {
+            self.update = config.update || self.update;
+            self.update_only = config.update_only || self.update_only;
+        }
         self.clear_data = config.clear_data || self.clear_data;
         self.first = config.first || self.first;
 
@@ -259,11 +649,82 @@ impl Config {
             self.network_config.external_ip = Some(public_addr.ip());
         }
 
+        if let Some(client_local_addr) = config.client_local_addr {
+            self.client_local_addr = Some(client_local_addr);
+        }
+
+        if let Some(max_conns) = config.max_node_conns {
+            self.max_node_conns = Some(max_conns);
+        }
+
+        if let Some(conns_per_sec) = config.node_conns_per_sec {
+            self.node_conns_per_sec = Some(conns_per_sec);
+        }
+
+        if let Some(max_conns) = config.max_client_conns {
+            self.max_client_conns = Some(max_conns);
+        }
+
+        if let Some(conns_per_sec) = config.client_conns_per_sec {
+            self.client_conns_per_sec = Some(conns_per_sec);
+        }
+
+        if let Some(threshold_kb) = config.stream_threshold_kb {
+            self.stream_threshold_kb = Some(threshold_kb);
+        }
+
+        if let Some(threshold_kb) = config.compression_threshold_kb {
+            self.compression_threshold_kb = Some(threshold_kb);
+        }
+
+        if let Some(max_in_kbps) = config.peer_max_in_kbps {
+            self.peer_max_in_kbps = Some(max_in_kbps);
+        }
+
+        if let Some(max_out_kbps) = config.peer_max_out_kbps {
+            self.peer_max_out_kbps = Some(max_out_kbps);
+        }
+
+        if let Some(len) = config.msg_audit_len {
+            self.msg_audit_len = Some(len);
+        }
+
+        if let Some(threshold_kb) = config.erasure_coding_threshold_kb {
+            self.erasure_coding_threshold_kb = Some(threshold_kb);
+        }
+
+        if let Some(relay_max_peers) = config.relay_max_peers {
+            self.relay_max_peers = Some(relay_max_peers);
+        }
+
+        if let Some(relay_max_kbps) = config.relay_max_kbps {
+            self.relay_max_kbps = Some(relay_max_kbps);
+        }
+
+        #[cfg(feature = "back-pressure")]
+        if let Some(strategy) = config.back_pressure_strategy {
+            self.back_pressure_strategy = Some(strategy);
+        }
+
+        #[cfg(feature = "back-pressure")]
+        if let Some(interval_secs) = config.back_pressure_report_interval_secs {
+            self.back_pressure_report_interval_secs = Some(interval_secs);
+        }
+
         self.network_config.forward_port = !config.skip_auto_port_forwarding;
 
         if !config.hard_coded_contacts.is_empty() {
             self.hard_coded_contacts = config.hard_coded_contacts;
         }
+        self.skip_cached_contacts = config.skip_cached_contacts || self.skip_cached_contacts;
+
+        if config.bootstrap_dns.is_some() {
+            self.bootstrap_dns = config.bootstrap_dns;
+        }
+
+        if config.dns_resolver.is_some() {
+            self.dns_resolver = config.dns_resolver;
+        }
 
         if config.genesis_key.is_some() {
             self.genesis_key = config.genesis_key;
@@ -297,11 +758,334 @@ impl Config {
         self.first
     }
 
-    /// Upper limit in bytes for allowed network storage on this node.
+    /// Seed the node's keys are derived from, if a mnemonic was given.
+    pub fn key_seed(&self) -> Result<Option<KeySeed>> {
+        self.key_seed
+            .as_deref()
+            .map(|mnemonic| {
+                KeySeed::from_mnemonic(mnemonic, "")
+                    .map_err(|err| Error::Configuration(format!("Invalid key seed: {}", err)))
+            })
+            .transpose()
+    }
+
+    /// Upper limit in bytes for allowed network storage on this node, unless another was set at
+    /// runtime through `NodeApi::set_capacity`: the last one set there takes precedence, across
+    /// restarts.
     pub fn max_capacity(&self) -> usize {
         DEFAULT_MAX_CAPACITY
     }
 
+    /// Consecutive failed disk self-tests after which the node stops accepting new data.
+    pub fn disk_failure_threshold(&self) -> usize {
+        self.disk_failure_threshold
+            .unwrap_or(DEFAULT_DISK_FAILURE_THRESHOLD)
+    }
+
+    /// Consecutive passed disk self-tests after which the node accepts new data again.
+    pub fn disk_recovery_threshold(&self) -> usize {
+        self.disk_recovery_threshold
+            .unwrap_or(DEFAULT_DISK_RECOVERY_THRESHOLD)
+    }
+
+    /// Disk space in bytes kept for the node's dbs, which chunks can't take.
+    pub fn db_reserve(&self) -> usize {
+        self.db_reserve_mb
+            .unwrap_or(DEFAULT_DB_RESERVE_MB)
+            .saturating_mul(1024 * 1024)
+    }
+
+    /// Percents of the max capacity past which the node reports being full, and refuses new
+    /// chunks. The hard one is capped at 100 %, and the soft one at the hard one.
+    pub fn storage_watermarks(&self) -> Watermarks {
+        let defaults = Watermarks::default();
+        let hard = self
+            .storage_hard_watermark_percent
+            .unwrap_or(defaults.hard)
+            .min(100);
+        let soft = self
+            .storage_soft_watermark_percent
+            .unwrap_or(defaults.soft)
+            .min(hard);
+        Watermarks { soft, hard }
+    }
+
+    /// Chunks re-hashed per scrubbing tick, 0 if scrubbing is disabled.
+    pub fn scrub_chunks_per_tick(&self) -> usize {
+        self.scrub_chunks_per_tick
+            .unwrap_or(DEFAULT_SCRUB_CHUNKS_PER_TICK)
+    }
+
+    /// Chunks audited per replication audit tick, 0 if audits are disabled.
+    pub fn audit_chunks_per_tick(&self) -> usize {
+        self.audit_chunks_per_tick
+            .unwrap_or(DEFAULT_AUDIT_CHUNKS_PER_TICK)
+    }
+
+    /// Node msgs the msg filter remembers, 0 if it's disabled.
+    pub fn msg_filter_capacity(&self) -> usize {
+        self.msg_filter_capacity.unwrap_or(0)
+    }
+
+    /// How long the msg filter remembers node msgs for.
+    pub fn msg_filter_ttl(&self) -> Duration {
+        self.msg_filter_ttl_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_MSG_FILTER_TTL)
+    }
+
+    /// Slices of the namespace whose inventory is requested per second when rebuilding the
+    /// registry of data holders.
+    pub fn registry_slices_per_sec(&self) -> usize {
+        self.registry_slices_per_sec
+            .unwrap_or(DEFAULT_REGISTRY_SLICES_PER_TICK)
+    }
+
+    /// Chunk reads per second background passes may make.
+    pub fn background_read_iops(&self) -> u32 {
+        self.background_read_iops
+            .unwrap_or(DEFAULT_BACKGROUND_READ_IOPS)
+    }
+
+    /// Bytes of chunks per second background passes may read.
+    pub fn background_read_bytes_per_sec(&self) -> u64 {
+        self.background_read_mbps
+            .map(|mbps| mbps.saturating_mul(1024 * 1024))
+            .unwrap_or(DEFAULT_BACKGROUND_READ_BYTES_PER_SEC)
+    }
+
+    /// Latency of client reads above which background passes pause reading.
+    pub fn interactive_read_latency(&self) -> Duration {
+        self.interactive_read_latency_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INTERACTIVE_READ_LATENCY)
+    }
+
+    /// Interval within which chunk writes are committed together, zero if they're not.
+    pub fn chunk_journal_flush_interval(&self) -> Duration {
+        self.chunk_journal_flush_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_CHUNK_JOURNAL_FLUSH_INTERVAL)
+    }
+
+    /// How long after being issued a relocation can be used to join our section.
+    pub fn relocation_validity(&self) -> Duration {
+        self.relocation_validity_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RELOCATION_VALIDITY)
+    }
+
+    /// Generations the key a relocation was issued under can be behind ours.
+    pub fn relocation_max_key_lag(&self) -> u64 {
+        self.relocation_max_key_lag
+            .unwrap_or(DEFAULT_RELOCATION_MAX_KEY_LAG)
+    }
+
+    /// How long adults new to the network warm up for.
+    pub fn warm_up(&self) -> Duration {
+        self.warm_up_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_WARM_UP)
+    }
+
+    /// Share of its data, in percent, an adult is assigned as it joins, up to 100.
+    pub fn warm_up_initial_share_percent(&self) -> u8 {
+        self.warm_up_initial_share_percent
+            .unwrap_or(DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT)
+            .min(100)
+    }
+
+    /// Bytes of chunks the data cache holds at most, 0 if it's disabled.
+    pub fn data_cache_bytes(&self) -> usize {
+        self.data_cache_mb
+            .unwrap_or(DEFAULT_DATA_CACHE_MB)
+            .saturating_mul(1024 * 1024)
+    }
+
+    /// How long chunks are kept in the data cache for.
+    pub fn data_cache_ttl(&self) -> Duration {
+        self.data_cache_ttl_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_DATA_CACHE_TTL)
+    }
+
+    /// How long the fastest known holder of a chunk has to answer before the next one is
+    /// queried too, zero if reads aren't hedged.
+    pub fn hedged_read_delay(&self) -> Duration {
+        self.hedged_read_delay_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_HEDGE_DELAY)
+    }
+
+    /// Attempts at sending a msg before giving up on it, the first included.
+    pub fn send_retry_max_attempts(&self) -> u32 {
+        self.send_retry_max_attempts
+            .unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS)
+    }
+
+    /// Delay before the first retry of a msg which failed to be sent.
+    pub fn send_retry_base_delay(&self) -> Duration {
+        self.send_retry_base_delay_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_RETRY_BASE_DELAY)
+    }
+
+    /// Connections the listener for nodes holds open at most.
+    pub fn max_node_conns(&self) -> usize {
+        self.max_node_conns.unwrap_or(DEFAULT_MAX_NODE_CONNS)
+    }
+
+    /// New connections per second the listener for nodes accepts.
+    pub fn node_conns_per_sec(&self) -> u32 {
+        self.node_conns_per_sec.unwrap_or(DEFAULT_NODE_CONNS_PER_S)
+    }
+
+    /// Connections the listener for clients holds open at most.
+    pub fn max_client_conns(&self) -> usize {
+        self.max_client_conns.unwrap_or(DEFAULT_MAX_CLIENT_CONNS)
+    }
+
+    /// New connections per second the listener for clients accepts.
+    pub fn client_conns_per_sec(&self) -> u32 {
+        self.client_conns_per_sec
+            .unwrap_or(DEFAULT_CLIENT_CONNS_PER_S)
+    }
+
+    /// Size of the msgs above which they're streamed to peers, in bytes, 0 if streaming is
+    /// disabled.
+    pub fn stream_threshold(&self) -> usize {
+        self.stream_threshold_kb
+            .unwrap_or(DEFAULT_STREAM_THRESHOLD_KB)
+            .saturating_mul(1024)
+    }
+
+    /// Size of the msgs above which they're compressed, in bytes, 0 if compression is disabled.
+    pub fn compression_threshold(&self) -> usize {
+        self.compression_threshold_kb
+            .unwrap_or(DEFAULT_COMPRESSION_THRESHOLD_KB)
+            .saturating_mul(1024)
+    }
+
+    /// Msgs received and sent last that are kept in memory, 0 if none are.
+    pub fn msg_audit_len(&self) -> usize {
+        self.msg_audit_len.unwrap_or(DEFAULT_MSG_AUDIT_CAPACITY)
+    }
+
+    /// Bytes from which chunks are erasure coded, if they are.
+    pub fn erasure_coding_threshold(&self) -> Option<usize> {
+        self.erasure_coding_threshold_kb
+            .filter(|kb| *kb > 0)
+            .map(|kb| kb.saturating_mul(1024))
+    }
+
+    /// Bytes per second received from any one peer at most, if capped.
+    pub fn peer_max_in_bytes_per_sec(&self) -> Option<u64> {
+        self.peer_max_in_kbps
+            .filter(|kbps| *kbps > 0)
+            .map(|kbps| kbps.saturating_mul(1024))
+    }
+
+    /// Bytes per second sent to any one peer at most, if capped.
+    pub fn peer_max_out_bytes_per_sec(&self) -> Option<u64> {
+        self.peer_max_out_kbps
+            .filter(|kbps| *kbps > 0)
+            .map(|kbps| kbps.saturating_mul(1024))
+    }
+
+    /// Nodes we relay msgs for at most, 0 if we don't relay for others.
+    pub fn relay_max_peers(&self) -> usize {
+        self.relay_max_peers.unwrap_or(DEFAULT_RELAY_MAX_PEERS)
+    }
+
+    /// Bytes per second relayed for any one node at most.
+    pub fn relay_max_bytes_per_sec(&self) -> u64 {
+        self.relay_max_kbps
+            .unwrap_or(DEFAULT_RELAY_KBPS)
+            .saturating_mul(1024)
+    }
+
+    /// How the msgs per s section members can send us is decided.
+    #[cfg(feature = "back-pressure")]
+    pub fn back_pressure_strategy(&self) -> BackPressureStrategy {
+        self.back_pressure_strategy.unwrap_or_default()
+    }
+
+    /// How often section members are told the msgs per s they can send us, at least every
+    /// second.
+    #[cfg(feature = "back-pressure")]
+    pub fn back_pressure_report_interval(&self) -> Duration {
+        self.back_pressure_report_interval_secs
+            .map(|secs| Duration::from_secs(secs.max(1)))
+            .unwrap_or(DEFAULT_BACK_PRESSURE_REPORT_INTERVAL)
+    }
+
+    /// The effective config the node attests to running with: its limits resolved to the values
+    /// in force, `max_capacity` being the one in force at runtime, and secrets redacted.
+    pub(crate) fn attested(&self, max_capacity: usize) -> Result<serde_json::Value> {
+        let mut attested = serde_json::to_value(self)?;
+        if let Some(fields) = attested.as_object_mut() {
+            let resolved = serde_json::json!({
+                "max_capacity": max_capacity,
+                "disk_failure_threshold": self.disk_failure_threshold(),
+                "disk_recovery_threshold": self.disk_recovery_threshold(),
+                "db_reserve_mb": self.db_reserve_mb.unwrap_or(DEFAULT_DB_RESERVE_MB),
+                "storage_soft_watermark_percent": self.storage_watermarks().soft,
+                "storage_hard_watermark_percent": self.storage_watermarks().hard,
+                "scrub_chunks_per_tick": self.scrub_chunks_per_tick(),
+                "audit_chunks_per_tick": self.audit_chunks_per_tick(),
+                "msg_filter_capacity": self.msg_filter_capacity(),
+                "msg_filter_ttl_secs": self.msg_filter_ttl().as_secs(),
+                "registry_slices_per_sec": self.registry_slices_per_sec(),
+                "background_read_iops": self.background_read_iops(),
+                "background_read_mbps": self.background_read_bytes_per_sec() / (1024 * 1024),
+                "interactive_read_latency_ms": self.interactive_read_latency().as_millis() as u64,
+                "chunk_journal_flush_ms": self.chunk_journal_flush_interval().as_millis() as u64,
+                "relocation_validity_secs": self.relocation_validity().as_secs(),
+                "relocation_max_key_lag": self.relocation_max_key_lag(),
+                "warm_up_secs": self.warm_up().as_secs(),
+                "warm_up_initial_share_percent": self.warm_up_initial_share_percent(),
+                "data_cache_mb": self.data_cache_bytes() / (1024 * 1024),
+                "data_cache_ttl_secs": self.data_cache_ttl().as_secs(),
+                "hedged_read_delay_ms": self.hedged_read_delay().as_millis() as u64,
+                "send_retry_max_attempts": self.send_retry_max_attempts(),
+                "send_retry_base_delay_ms": self.send_retry_base_delay().as_millis() as u64,
+                "max_node_conns": self.max_node_conns(),
+                "node_conns_per_sec": self.node_conns_per_sec(),
+                "max_client_conns": self.max_client_conns(),
+                "client_conns_per_sec": self.client_conns_per_sec(),
+                "stream_threshold_kb": self.stream_threshold() / 1024,
+                "compression_threshold_kb": self.compression_threshold() / 1024,
+                "peer_max_in_kbps": self.peer_max_in_bytes_per_sec().map(|bytes| bytes / 1024),
+                "peer_max_out_kbps": self.peer_max_out_bytes_per_sec().map(|bytes| bytes / 1024),
+                "msg_audit_len": self.msg_audit_len(),
+                "erasure_coding_threshold_kb": self.erasure_coding_threshold().map(|bytes| bytes / 1024),
+                "relay_max_peers": self.relay_max_peers(),
+                "relay_max_kbps": self.relay_max_bytes_per_sec() / 1024,
+            });
+            if let serde_json::Value::Object(resolved) = resolved {
+                fields.extend(resolved);
+            }
+            #[cfg(feature = "back-pressure")]
+            {
+                let _prev = fields.insert(
+                    "back_pressure_strategy".to_string(),
+                    self.back_pressure_strategy().to_string().into(),
+                );
+                let _prev = fields.insert(
+                    "back_pressure_report_interval_secs".to_string(),
+                    self.back_pressure_report_interval().as_secs().into(),
+                );
+            }
+            if self.control_token.is_some() {
+                let _prev = fields.insert("control_token".to_string(), REDACTED.into());
+            }
+            if self.key_seed.is_some() {
+                let _prev = fields.insert("key_seed".to_string(), REDACTED.into());
+            }
+        }
+        Ok(attested)
+    }
+
     /// Root directory for dbs and cached state. If not set, it defaults to
     /// `DEFAULT_ROOT_DIR_NAME` within the project's data directory (see `Config::root_dir` for the
     /// directories on each platform).
@@ -374,11 +1158,13 @@ impl Config {
     }
 
     /// Attempt to self-update?
+    #[cfg(feature = "self-update")]
     pub fn update(&self) -> bool {
         self.update
     }
 
     /// Attempt to self-update without starting the node process
+    #[cfg(feature = "self-update")]
     pub fn update_only(&self) -> bool {
         self.update_only
     }
@@ -516,7 +1302,10 @@ fn smoke() {
     // NOTE: IF this value is being changed due to a change in the config,
     // the change in config also be handled in Config::merge()
     // and in examples/config_handling.rs
-    let expected_size = 456;
+    #[cfg(not(feature = "back-pressure"))]
+    let expected_size = 1088;
+    #[cfg(feature = "back-pressure")]
+    let expected_size = 1104;
 
     assert_eq!(std::mem::size_of::<Config>(), expected_size);
 }
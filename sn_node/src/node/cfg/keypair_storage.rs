@@ -6,102 +6,500 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::dbs::{deserialise, serialise};
+use crate::keystore;
 use crate::node::{Error, Result};
-use ed25519_dalek::{Keypair, PublicKey, KEYPAIR_LENGTH};
-use hex::{decode, encode};
-use std::path::Path;
-use tokio::fs;
+use crate::persisted::{self, Error as PersistedError};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, KEYPAIR_LENGTH};
+use hex::decode;
+use rand_07::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-// Filename for storing the node's reward (Ed25519 hex-encoded) public key
+// Filename for storing the node's reward (Ed25519) public key
 const REWARD_PUBLIC_KEY_FILENAME: &str = "reward_public_key";
-// Filename for storing the node's reward (Ed25519 hex-encoded) secret key
+// Filename for storing the node's reward (Ed25519) secret key
 const REWARD_SECRET_KEY_FILENAME: &str = "reward_secret_key";
+// Filename for storing the reward keys rotated away from, oldest first
+const REWARD_KEY_HISTORY_FILENAME: &str = "reward_key_history";
+// Filename for storing the secret keys of the reward keys rotated away from, suffixed with
+// their index in the history
+const RETIRED_REWARD_SECRET_KEY_FILENAME: &str = "retired_reward_secret_key";
 
 const NETWORK_KEYPAIR_FILENAME: &str = "network_keypair";
 
-/// Writes the network keypair to disk.
+// Filename for storing the secret key the network's DNS contacts are signed with, by the node
+// which started it
+const CONTACTS_SECRET_KEY_FILENAME: &str = "contacts_secret_key";
+// Filename for storing the certificate of the contacts key by the network's genesis key
+const CONTACTS_KEY_CERTIFICATE_FILENAME: &str = "contacts_key_certificate";
+
+// Filename for storing the key the chunks of an encrypted chunk store are encrypted with
+const STORAGE_KEY_FILENAME: &str = "storage_key";
+
+// Format of the key files, raw key bytes. Version 0 is hex-encoded, and migrated when read.
+const KEY_FILE_VERSION: u16 = 1;
+// Format of the secret key files sealed in the keystore, see `crate::keystore`.
+const SEALED_KEY_FILE_VERSION: u16 = 2;
+// Format of the reward key history, its `RetiredRewardKey`s serialised with bincode.
+const REWARD_KEY_HISTORY_VERSION: u16 = 1;
+
+/// The key the network's DNS contacts are signed with, kept by the node which started the
+/// network, with the genesis key which certified it, whose secret key isn't kept.
+pub(crate) struct ContactsKey {
+    pub(crate) secret_key: bls::SecretKey,
+    pub(crate) genesis_key: bls::PublicKey,
+    /// The signature of the genesis key over the contacts key, see `crate::dns_contacts`.
+    pub(crate) certificate: bls::Signature,
+}
+
+/// A reward key the node rotated away from. Its secret key is kept, for rewards still paid to
+/// it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RetiredRewardKey {
+    pub(crate) public_key: PublicKey,
+    /// When it was rotated away from, in seconds since the Unix epoch.
+    pub(crate) retired_at: u64,
+}
+
+/// Writes the network keypair to disk, sealed in the keystore if `encrypt` is set or it
+/// already was.
 pub(crate) async fn store_network_keypair(
     root_dir: &Path,
     keypair_as_bytes: [u8; KEYPAIR_LENGTH],
+    encrypt: bool,
 ) -> Result<()> {
     let keypair_path = root_dir.join(NETWORK_KEYPAIR_FILENAME);
-    fs::write(keypair_path, encode(keypair_as_bytes)).await?;
-
-    Ok(())
+    write_secret(keypair_path, keypair_as_bytes.to_vec(), encrypt).await
 }
 
 /// Returns Some(KeyPair) or None if file doesn't exist.
-#[allow(dead_code)]
+///
+/// A damaged file is an error rather than a reason to make up a new identity.
 pub(crate) async fn get_network_keypair(root_dir: &Path) -> Result<Option<Keypair>> {
     let path = root_dir.join(NETWORK_KEYPAIR_FILENAME);
-    if !path.is_file() {
-        return Ok(None);
-    }
-
-    let keypair_hex_bytes = fs::read(&path).await?;
-    let keypair_bytes = decode(keypair_hex_bytes).map_err(|err| {
-        Error::Configuration(format!(
-            "couldn't hex-decode network keypair bytes read from {}: {}",
-            path.display(),
-            err
-        ))
-    })?;
+    let bytes = match read_key_file(&path, "network keypair").await? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
 
-    let keypair = Keypair::from_bytes(&keypair_bytes).map_err(|err| {
-        Error::Configuration(format!(
-            "invalid network keypair bytes read from {}: {}",
-            path.display(),
-            err
-        ))
+    let keypair = Keypair::from_bytes(&bytes).map_err(|err| {
+        damaged(
+            PersistedError::corrupt(&path, format!("invalid keypair: {}", err)),
+            "network keypair",
+        )
     })?;
 
     Ok(Some(keypair))
 }
 
-/// Writes the public and secret key (hex-encoded) to different locations at disk.
-pub(crate) async fn store_new_reward_keypair(root_dir: &Path, keypair: &Keypair) -> Result<()> {
+/// Writes the public and secret key to different locations at disk, the secret one sealed in
+/// the keystore if `encrypt` is set. The public key is always kept in the clear.
+pub(crate) async fn store_new_reward_keypair(
+    root_dir: &Path,
+    keypair: &Keypair,
+    encrypt: bool,
+) -> Result<()> {
     let secret_key_path = root_dir.join(REWARD_SECRET_KEY_FILENAME);
     let public_key_path = root_dir.join(REWARD_PUBLIC_KEY_FILENAME);
-    fs::write(secret_key_path, encode(keypair.secret.to_bytes())).await?;
-    fs::write(public_key_path, encode(keypair.public.to_bytes())).await?;
+    write_secret(secret_key_path, keypair.secret.to_bytes().to_vec(), encrypt).await?;
+    persisted::write_async(
+        public_key_path,
+        KEY_FILE_VERSION,
+        keypair.public.to_bytes().to_vec(),
+    )
+    .await?;
 
     Ok(())
 }
 
-/// Returns Some(PublicKey) or None if file doesn't exist. It assumes it's hex-encoded.
-pub(crate) async fn get_reward_pk(root_dir: &Path) -> Result<Option<PublicKey>> {
-    let path = root_dir.join(REWARD_PUBLIC_KEY_FILENAME);
-    if !path.is_file() {
-        return Ok(None);
+/// Replaces the reward keypair with a new random one, returning its public key. The keypair
+/// replaced is kept, sealed in the keystore if it was, and recorded in the reward key history.
+///
+/// The new key isn't derived from the key seed, so it can't be recovered from its mnemonic.
+/// Only our own record of the key changes: there's no registration of reward keys with the
+/// elders yet for a new one to supersede the old one with them.
+pub(crate) async fn rotate_reward_keypair(root_dir: &Path) -> Result<PublicKey> {
+    let secret_key_path = reward_secret_key_path(root_dir);
+    let no_key = || Error::Configuration("there's no reward key to rotate".to_string());
+    let public_key = get_reward_pk(root_dir).await?.ok_or_else(no_key)?;
+    let sealed = matches!(
+        persisted::read_async(secret_key_path.clone()).await,
+        Ok(Some(read)) if read.version == SEALED_KEY_FILE_VERSION
+    );
+    let secret_key = read_key_file(&secret_key_path, "reward secret key")
+        .await?
+        .ok_or_else(no_key)?;
+
+    let mut history = get_reward_key_history(root_dir).await?;
+    let retired_path = retired_reward_secret_key_path(root_dir, history.len());
+    write_secret(retired_path, secret_key, sealed).await?;
+    history.push(RetiredRewardKey {
+        public_key,
+        retired_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default(),
+    });
+    let payload = serialise(&history)?;
+    let history_path = root_dir.join(REWARD_KEY_HISTORY_FILENAME);
+    persisted::write_async(history_path, REWARD_KEY_HISTORY_VERSION, payload).await?;
+
+    let keypair = Keypair::generate(&mut OsRng);
+    store_new_reward_keypair(root_dir, &keypair, sealed).await?;
+    info!(
+        "Rotated our reward key from {} to {}",
+        hex::encode(public_key.to_bytes()),
+        hex::encode(keypair.public.to_bytes())
+    );
+
+    Ok(keypair.public)
+}
+
+/// Returns the reward keys rotated away from, oldest first.
+pub(crate) async fn get_reward_key_history(root_dir: &Path) -> Result<Vec<RetiredRewardKey>> {
+    let path = root_dir.join(REWARD_KEY_HISTORY_FILENAME);
+    let what = "reward key history";
+    let read = persisted::read_async(path.clone())
+        .await
+        .map_err(|error| damaged(error, what))?;
+    match read {
+        Some(read) if read.version == REWARD_KEY_HISTORY_VERSION => deserialise(&read.payload)
+            .map_err(|error| damaged(PersistedError::corrupt(&path, error), what)),
+        Some(read) => Err(damaged(
+            PersistedError::WrongVersion {
+                path,
+                found: read.version,
+                expected: REWARD_KEY_HISTORY_VERSION,
+            },
+            what,
+        )),
+        None => Ok(Vec::new()),
     }
+}
 
-    let pk_hex_bytes = fs::read(&path).await?;
-    let pk_bytes = decode(pk_hex_bytes).map_err(|err| {
-        Error::Configuration(format!(
-            "couldn't hex-decode rewards Ed25519 public key bytes from {}: {}",
-            path.display(),
-            err
-        ))
-    })?;
+fn retired_reward_secret_key_path(root_dir: &Path, index: usize) -> PathBuf {
+    root_dir.join(format!("{}.{}", RETIRED_REWARD_SECRET_KEY_FILENAME, index))
+}
 
-    let pk = PublicKey::from_bytes(&pk_bytes).map_err(|err| {
-        Error::Configuration(format!(
-            "invalid rewards Ed25519 public key bytes read from {}: {}",
-            path.display(),
-            err
-        ))
+/// Writes the contacts key to disk, its secret key sealed in the keystore if `encrypt` is set,
+/// and its certificate in the clear.
+pub(crate) async fn store_contacts_key(
+    root_dir: &Path,
+    key: &ContactsKey,
+    encrypt: bool,
+) -> Result<()> {
+    let path = root_dir.join(CONTACTS_SECRET_KEY_FILENAME);
+    write_secret(path, key.secret_key.to_bytes().to_vec(), encrypt).await?;
+    let mut certificate = key.genesis_key.to_bytes().to_vec();
+    certificate.extend_from_slice(&key.certificate.to_bytes());
+    let path = root_dir.join(CONTACTS_KEY_CERTIFICATE_FILENAME);
+    persisted::write_async(path, KEY_FILE_VERSION, certificate).await?;
+
+    Ok(())
+}
+
+/// Returns Some(ContactsKey) or None if file doesn't exist, i.e. the node in `root_dir` didn't
+/// start the network.
+pub(crate) async fn get_contacts_key(root_dir: &Path) -> Result<Option<ContactsKey>> {
+    let path = root_dir.join(CONTACTS_SECRET_KEY_FILENAME);
+    let bytes = match read_key_file(&path, "contacts secret key").await? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+    let secret_key = bytes
+        .try_into()
+        .ok()
+        .and_then(|bytes| bls::SecretKey::from_bytes(bytes).ok())
+        .ok_or_else(|| {
+            damaged(
+                PersistedError::corrupt(&path, "invalid BLS secret key"),
+                "contacts secret key",
+            )
+        })?;
+
+    let path = root_dir.join(CONTACTS_KEY_CERTIFICATE_FILENAME);
+    let what = "contacts key certificate";
+    let invalid = |reason| damaged(PersistedError::corrupt(&path, reason), what);
+    let bytes = read_key_file(&path, what)
+        .await?
+        .ok_or_else(|| invalid("missing"))?;
+    if bytes.len() != bls::PK_SIZE + bls::SIG_SIZE {
+        return Err(invalid("not a genesis key and signature"));
+    }
+    let (genesis_key, certificate) = bytes.split_at(bls::PK_SIZE);
+    let genesis_key = genesis_key
+        .try_into()
+        .ok()
+        .and_then(|bytes| bls::PublicKey::from_bytes(bytes).ok())
+        .ok_or_else(|| invalid("invalid BLS public key"))?;
+    let certificate = certificate
+        .try_into()
+        .ok()
+        .and_then(|bytes| bls::Signature::from_bytes(bytes).ok())
+        .ok_or_else(|| invalid("invalid BLS signature"))?;
+
+    Ok(Some(ContactsKey {
+        secret_key,
+        genesis_key,
+        certificate,
+    }))
+}
+
+/// Returns Some(PublicKey) or None if file doesn't exist.
+///
+/// A damaged file is an error rather than a reason to generate a new reward key, which would
+/// send rewards elsewhere.
+pub(crate) async fn get_reward_pk(root_dir: &Path) -> Result<Option<PublicKey>> {
+    let path = root_dir.join(REWARD_PUBLIC_KEY_FILENAME);
+    let bytes = match read_key_file(&path, "reward public key").await? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+
+    let pk = PublicKey::from_bytes(&bytes).map_err(|err| {
+        damaged(
+            PersistedError::corrupt(&path, format!("invalid Ed25519 public key: {}", err)),
+            "reward public key",
+        )
     })?;
 
     Ok(Some(pk))
 }
 
+/// Returns Some(SecretKey) or None if file doesn't exist.
+///
+/// Read as the chunk store is opened, to take the key its chunks were encrypted with over from
+/// a store encrypted before it had a key of its own, hence sync and leaving a file which
+/// predates framed files as it is.
+pub(crate) fn read_reward_secret_key(
+    root_dir: &Path,
+) -> std::result::Result<Option<SecretKey>, PersistedError> {
+    let path = reward_secret_key_path(root_dir);
+    let bytes = match read_secret(&path)? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+
+    SecretKey::from_bytes(&bytes)
+        .map(Some)
+        .map_err(|err| PersistedError::corrupt(&path, format!("invalid secret key: {}", err)))
+}
+
+/// Where the reward secret key is persisted in `root_dir`.
+pub(crate) fn reward_secret_key_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(REWARD_SECRET_KEY_FILENAME)
+}
+
+/// Writes the key the chunks of an encrypted chunk store are encrypted with to disk, sealed in
+/// the keystore if `encrypt` is set.
+pub(crate) async fn store_storage_key(root_dir: &Path, key: [u8; 32], encrypt: bool) -> Result<()> {
+    write_secret(storage_key_path(root_dir), key.to_vec(), encrypt).await
+}
+
+/// Returns Some(key) or None if file doesn't exist.
+///
+/// Read as the chunk store is opened, hence sync.
+pub(crate) fn read_storage_key(
+    root_dir: &Path,
+) -> std::result::Result<Option<[u8; 32]>, PersistedError> {
+    let path = storage_key_path(root_dir);
+    match read_secret(&path)? {
+        Some(bytes) => bytes
+            .try_into()
+            .map(Some)
+            .map_err(|_| PersistedError::corrupt(&path, "not a 32 bytes key")),
+        None => Ok(None),
+    }
+}
+
+/// Where the key of an encrypted chunk store is persisted in `root_dir`.
+pub(crate) fn storage_key_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(STORAGE_KEY_FILENAME)
+}
+
+/// Returns the secret key files of `root_dir` which are still in the clear.
+pub(crate) fn unsealed_keys(root_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut unsealed = Vec::new();
+    for path in secret_key_paths(root_dir) {
+        let read = persisted::read(&path).map_err(|error| damaged(error, "secret key"))?;
+        if matches!(read, Some(read) if read.version != SEALED_KEY_FILE_VERSION) {
+            unsealed.push(path);
+        }
+    }
+    Ok(unsealed)
+}
+
+/// Whether any secret key file of `root_dir` is sealed in the keystore.
+pub(crate) fn has_sealed_keys(root_dir: &Path) -> Result<bool> {
+    for path in secret_key_paths(root_dir) {
+        let read = persisted::read(&path).map_err(|error| damaged(error, "secret key"))?;
+        if matches!(read, Some(read) if read.version == SEALED_KEY_FILE_VERSION) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Seals the secret key files of `root_dir` which are still in the clear in the keystore,
+/// returning those sealed.
+pub(crate) fn seal_keys(root_dir: &Path) -> Result<Vec<PathBuf>> {
+    let unsealed = unsealed_keys(root_dir)?;
+    if unsealed.is_empty() {
+        return Ok(unsealed);
+    }
+    let passphrase = keystore::passphrase().map_err(|error| locked(error, root_dir))?;
+    for path in &unsealed {
+        let read = persisted::read(path)
+            .map_err(|error| damaged(error, "secret key"))?
+            .ok_or_else(|| Error::Configuration(format!("{} went away", path.display())))?;
+        let bytes = if read.version == 0 {
+            decode(&read.payload).map_err(|err| {
+                damaged(
+                    PersistedError::corrupt(path, format!("couldn't hex-decode: {}", err)),
+                    "secret key",
+                )
+            })?
+        } else {
+            read.payload
+        };
+        let sealed = keystore::seal(&passphrase, &bytes).map_err(|error| locked(error, path))?;
+        persisted::write(path, SEALED_KEY_FILE_VERSION, &sealed)?;
+        info!("Sealed {} in the keystore", path.display());
+    }
+    Ok(unsealed)
+}
+
+fn secret_key_paths(root_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![
+        root_dir.join(NETWORK_KEYPAIR_FILENAME),
+        root_dir.join(REWARD_SECRET_KEY_FILENAME),
+        root_dir.join(CONTACTS_SECRET_KEY_FILENAME),
+        root_dir.join(STORAGE_KEY_FILENAME),
+    ];
+    paths.extend(
+        (0..)
+            .map(|index| retired_reward_secret_key_path(root_dir, index))
+            .take_while(|path| path.exists()),
+    );
+    paths
+}
+
+// Writes a secret key to `path`, sealed in the keystore if `encrypt` is set or the key there
+// already was, for a node run without `--encrypt-keys` not to leave it in the clear again.
+async fn write_secret(path: PathBuf, bytes: Vec<u8>, encrypt: bool) -> Result<()> {
+    let was_sealed = matches!(
+        persisted::read_async(path.clone()).await,
+        Ok(Some(read)) if read.version == SEALED_KEY_FILE_VERSION
+    );
+    if encrypt || was_sealed {
+        let sealed = keystore::seal_async(bytes)
+            .await
+            .map_err(|error| locked(error, &path))?;
+        persisted::write_async(path, SEALED_KEY_FILE_VERSION, sealed).await?;
+    } else {
+        persisted::write_async(path, KEY_FILE_VERSION, bytes).await?;
+    }
+    Ok(())
+}
+
+// Reads the raw bytes of a secret key file for the chunk store, sync and without migrating it.
+fn read_secret(path: &Path) -> std::result::Result<Option<Vec<u8>>, PersistedError> {
+    match persisted::read(path)? {
+        Some(read) if read.version == KEY_FILE_VERSION => Ok(Some(read.payload)),
+        Some(read) if read.version == SEALED_KEY_FILE_VERSION => {
+            open_sealed(path, &read.payload).map(Some)
+        }
+        Some(read) if read.version == 0 => decode(&read.payload)
+            .map(Some)
+            .map_err(|err| PersistedError::corrupt(path, format!("couldn't hex-decode: {}", err))),
+        Some(read) => Err(PersistedError::WrongVersion {
+            path: path.to_path_buf(),
+            found: read.version,
+            expected: KEY_FILE_VERSION,
+        }),
+        None => Ok(None),
+    }
+}
+
+// Opens a key sealed in the keystore, read from `path` by the chunk store.
+fn open_sealed(path: &Path, sealed: &[u8]) -> std::result::Result<Vec<u8>, PersistedError> {
+    keystore::passphrase()
+        .and_then(|passphrase| keystore::open(&passphrase, sealed))
+        .map_err(|error| PersistedError::Locked {
+            path: path.to_path_buf(),
+            reason: error.to_string(),
+        })
+}
+
+// Reads the raw bytes of a key file, migrating it from hex if it predates framed files.
+async fn read_key_file(path: &Path, what: &str) -> Result<Option<Vec<u8>>> {
+    let read = persisted::read_async(path.to_path_buf())
+        .await
+        .map_err(|error| damaged(error, what))?;
+    match read {
+        Some(read) if read.version == KEY_FILE_VERSION => Ok(Some(read.payload)),
+        Some(read) if read.version == SEALED_KEY_FILE_VERSION => keystore::open_async(read.payload)
+            .await
+            .map(Some)
+            .map_err(|error| locked(error, path)),
+        Some(read) if read.version == 0 => {
+            let bytes = decode(&read.payload).map_err(|err| {
+                damaged(
+                    PersistedError::corrupt(path, format!("couldn't hex-decode: {}", err)),
+                    what,
+                )
+            })?;
+            info!("Migrating the {} at {}", what, path.display());
+            persisted::write_async(path.to_path_buf(), KEY_FILE_VERSION, bytes.clone()).await?;
+            Ok(Some(bytes))
+        }
+        Some(read) => Err(damaged(
+            PersistedError::WrongVersion {
+                path: path.to_path_buf(),
+                found: read.version,
+                expected: KEY_FILE_VERSION,
+            },
+            what,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn locked(error: impl std::fmt::Display, path: &Path) -> Error {
+    Error::Configuration(format!(
+        "can't use the keystore for {} ({}). Check the passphrase given in {}, the OS keyring or \
+        on the terminal",
+        path.display(),
+        error,
+        keystore::PASSPHRASE_ENV_VAR
+    ))
+}
+
+fn damaged(error: PersistedError, what: &str) -> Error {
+    Error::Configuration(format!(
+        "can't use the {} persisted by a previous run ({}). Restore it from a backup, or \
+        delete it for a new one to be generated",
+        what, error
+    ))
+}
+
 #[cfg(test)]
 mod test {
     use super::{
-        get_network_keypair, get_reward_pk, store_network_keypair, store_new_reward_keypair,
+        get_network_keypair, get_reward_key_history, get_reward_pk, read_key_file,
+        read_reward_secret_key, retired_reward_secret_key_path, reward_secret_key_path,
+        rotate_reward_keypair, seal_keys, store_network_keypair, store_new_reward_keypair,
+        unsealed_keys, NETWORK_KEYPAIR_FILENAME, REWARD_PUBLIC_KEY_FILENAME,
     };
+    use crate::{keystore, node::Error, persisted::damage};
     use eyre::{eyre, Result};
+    use hex::encode;
     use rand_07::rngs::OsRng;
+    use std::{fs, path::Path};
     use tempfile::{tempdir, TempDir};
 
     #[tokio::test(flavor = "multi_thread")]
@@ -111,7 +509,7 @@ mod test {
 
         let root = create_temp_root()?;
         let root_dir = root.path();
-        store_new_reward_keypair(root_dir, &keypair).await?;
+        store_new_reward_keypair(root_dir, &keypair, false).await?;
         let pk_result = get_reward_pk(root_dir).await?;
 
         assert_eq!(pk_result, Some(keypair.public));
@@ -129,7 +527,7 @@ mod test {
         let keypair_result = get_network_keypair(root_dir).await?;
         assert!(keypair_result.is_none());
 
-        store_network_keypair(root_dir, keypair.to_bytes()).await?;
+        store_network_keypair(root_dir, keypair.to_bytes(), false).await?;
         let keypair_result = get_network_keypair(root_dir).await?;
         if let Some(kp) = keypair_result {
             assert_eq!(kp.public, keypair.public);
@@ -139,6 +537,172 @@ mod test {
         }
     }
 
+    // A damaged key file must stop the node from starting, naming the file.
+    fn assert_refused<T: std::fmt::Debug>(
+        read: crate::node::Result<Option<T>>,
+        path: &Path,
+        way: usize,
+    ) -> Result<()> {
+        match read {
+            Err(Error::Configuration(message)) if message.contains(&path.display().to_string()) => {
+                Ok(())
+            }
+            other => Err(eyre!("damage #{} not refused: {:?}", way, other)),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn damaged_keys_are_refused() -> Result<()> {
+        let mut rng = OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut rng);
+        let root = create_temp_root()?;
+        let root_dir = root.path();
+
+        let path = root_dir.join(NETWORK_KEYPAIR_FILENAME);
+        store_network_keypair(root_dir, keypair.to_bytes(), false).await?;
+        for way in 0..damage::ways(&path)? {
+            store_network_keypair(root_dir, keypair.to_bytes(), false).await?;
+            damage::apply(&path, way)?;
+            assert_refused(get_network_keypair(root_dir).await, &path, way)?;
+        }
+
+        let path = root_dir.join(REWARD_PUBLIC_KEY_FILENAME);
+        store_new_reward_keypair(root_dir, &keypair, false).await?;
+        for way in 0..damage::ways(&path)? {
+            store_new_reward_keypair(root_dir, &keypair, false).await?;
+            damage::apply(&path, way)?;
+            assert_refused(get_reward_pk(root_dir).await, &path, way)?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hex_keys_are_migrated() -> Result<()> {
+        let mut rng = OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut rng);
+        let root = create_temp_root()?;
+        let root_dir = root.path();
+
+        let path = root_dir.join(REWARD_PUBLIC_KEY_FILENAME);
+        fs::write(&path, encode(keypair.public.to_bytes()))?;
+        assert_eq!(get_reward_pk(root_dir).await?, Some(keypair.public));
+        assert_ne!(
+            fs::read(&path)?,
+            encode(keypair.public.to_bytes()).as_bytes()
+        );
+        assert_eq!(get_reward_pk(root_dir).await?, Some(keypair.public));
+
+        fs::write(
+            root_dir.join(NETWORK_KEYPAIR_FILENAME),
+            encode(keypair.to_bytes()),
+        )?;
+        let migrated = get_network_keypair(root_dir).await?;
+        assert_eq!(migrated.map(|keypair| keypair.public), Some(keypair.public));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sealed_keys_stay_out_of_the_clear() -> Result<()> {
+        keystore::unlock_with("test passphrase");
+        let mut rng = OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut rng);
+        let root = create_temp_root()?;
+        let root_dir = root.path();
+
+        store_new_reward_keypair(root_dir, &keypair, true).await?;
+        store_network_keypair(root_dir, keypair.to_bytes(), true).await?;
+        assert!(unsealed_keys(root_dir)?.is_empty());
+        let secret_key = keypair.secret.to_bytes();
+        for path in [
+            root_dir.join(NETWORK_KEYPAIR_FILENAME),
+            reward_secret_key_path(root_dir),
+        ] {
+            let content = fs::read(&path)?;
+            assert!(!content.windows(secret_key.len()).any(|w| w == secret_key));
+        }
+
+        let read = get_network_keypair(root_dir).await?;
+        assert_eq!(read.map(|keypair| keypair.public), Some(keypair.public));
+        let read = read_reward_secret_key(root_dir)?;
+        assert_eq!(read.map(|key| key.to_bytes()), Some(secret_key));
+        // The public key needn't be unlocked.
+        assert_eq!(get_reward_pk(root_dir).await?, Some(keypair.public));
+
+        // A sealed key stays so when stored again without `encrypt`.
+        store_network_keypair(root_dir, keypair.to_bytes(), false).await?;
+        assert!(unsealed_keys(root_dir)?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keys_in_the_clear_are_sealed_on_request() -> Result<()> {
+        keystore::unlock_with("test passphrase");
+        let mut rng = OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut rng);
+        let root = create_temp_root()?;
+        let root_dir = root.path();
+
+        store_new_reward_keypair(root_dir, &keypair, false).await?;
+        fs::write(
+            root_dir.join(NETWORK_KEYPAIR_FILENAME),
+            encode(keypair.to_bytes()),
+        )?;
+        let unsealed = unsealed_keys(root_dir)?;
+        assert_eq!(unsealed.len(), 2);
+
+        assert_eq!(seal_keys(root_dir)?, unsealed);
+        assert!(unsealed_keys(root_dir)?.is_empty());
+        assert!(seal_keys(root_dir)?.is_empty());
+
+        let read = get_network_keypair(root_dir).await?;
+        assert_eq!(read.map(|keypair| keypair.public), Some(keypair.public));
+        let read = read_reward_secret_key(root_dir)?;
+        assert_eq!(
+            read.map(|key| key.to_bytes()),
+            Some(keypair.secret.to_bytes())
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rotated_reward_keys_are_kept_in_the_history() -> Result<()> {
+        keystore::unlock_with("test passphrase");
+        let mut rng = OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut rng);
+        let root = create_temp_root()?;
+        let root_dir = root.path();
+
+        assert!(rotate_reward_keypair(root_dir).await.is_err());
+        store_new_reward_keypair(root_dir, &keypair, true).await?;
+
+        let rotated = rotate_reward_keypair(root_dir).await?;
+        assert_ne!(rotated, keypair.public);
+        assert_eq!(get_reward_pk(root_dir).await?, Some(rotated));
+        let read = read_reward_secret_key(root_dir)?;
+        assert_eq!(
+            read.map(|key| ed25519_dalek::PublicKey::from(&key)),
+            Some(rotated)
+        );
+
+        // The old key is kept, sealed as it was.
+        let history = get_reward_key_history(root_dir).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].public_key, keypair.public);
+        let retired_path = retired_reward_secret_key_path(root_dir, 0);
+        let retired = read_key_file(&retired_path, "retired reward secret key").await?;
+        assert_eq!(retired, Some(keypair.secret.to_bytes().to_vec()));
+        assert!(unsealed_keys(root_dir)?.is_empty());
+
+        let rotated_again = rotate_reward_keypair(root_dir).await?;
+        let history = get_reward_key_history(root_dir).await?;
+        let retired: Vec<_> = history.iter().map(|key| key.public_key).collect();
+        assert_eq!(retired, [keypair.public, rotated]);
+        assert_eq!(get_reward_pk(root_dir).await?, Some(rotated_again));
+        assert!(!retired_reward_secret_key_path(root_dir, 2).exists());
+        Ok(())
+    }
+
     // creates a temp dir
     fn create_temp_root() -> Result<TempDir> {
         tempdir().map_err(|e| eyre!("Failed to create temp dir: {}", e))
@@ -6,6 +6,9 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+/// File storage for the max capacity set at runtime
+pub(crate) mod capacity_storage;
+
 /// Configuration
 pub(crate) mod config_handler;
 
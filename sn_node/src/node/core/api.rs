@@ -10,8 +10,9 @@ use super::{delivery_group, Comm, Node};
 
 use crate::node::{
     api::cmds::Cmd,
+    dkg::DkgSessionState,
     error::{Error, Result},
-    Event,
+    Event, MembershipEvent,
 };
 use crate::UsedSpace;
 use sn_interface::messaging::WireMsg;
@@ -22,7 +23,7 @@ use sn_interface::types::log_markers::LogMarker;
 
 use secured_linked_list::SecuredLinkedList;
 use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use xor_name::XorName;
 
 impl Node {
@@ -125,11 +126,39 @@ impl Node {
         }
     }
 
+    /// Subscribes to the membership events raised from now on.
+    pub(crate) fn subscribe_membership_events(&self) -> broadcast::Receiver<MembershipEvent> {
+        self.membership_events_tx.subscribe()
+    }
+
+    pub(crate) fn send_membership_event(&self, event: MembershipEvent) {
+        trace!("Membership event: {:?}", event);
+        // Nobody subscribed is fine: the event is just dropped.
+        let _ = self.membership_events_tx.send(event);
+    }
+
+    /// Allows new nodes to join, or not, raising a membership event if that's a change.
+    pub(crate) async fn set_joins_allowed(&self, allowed: bool) {
+        let mut joins_allowed = self.joins_allowed.write().await;
+        if *joins_allowed != allowed {
+            *joins_allowed = allowed;
+            self.send_membership_event(MembershipEvent::JoinsAllowedChanged { allowed });
+        }
+    }
+
+    /// Where each of the DKG sessions we take part in is at.
+    pub(crate) fn dkg_sessions(&self) -> Vec<DkgSessionState> {
+        self.dkg_voter.sessions_state()
+    }
+
     // ----------------------------------------------------------------------------------------
     //   ---------------------------------- Mut ------------------------------------------
     // ----------------------------------------------------------------------------------------
 
     pub(crate) async fn handle_timeout(&self, token: u64) -> Result<Vec<Cmd>> {
+        if let Some(cmds) = self.handle_hedge_timeout(token).await? {
+            return Ok(cmds);
+        }
         self.dkg_voter.handle_timeout(
             &self.info.read().await.clone(),
             token,
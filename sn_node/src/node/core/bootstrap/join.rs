@@ -6,23 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{read_prefix_map_from_disk, UsedRecipientSaps};
+use super::UsedRecipientSaps;
 use crate::node::{
-    core::{Comm, DeliveryStatus, MsgEvent},
+    core::{solve_memory_proof, Comm, DeliveryStatus, MsgEvent, OurRelay, RELAY_LEASE},
     messages::WireMsgUtils,
     Error, Result,
 };
 use sn_interface::messaging::{
     system::{
-        JoinRejectionReason, JoinRequest, JoinResponse, ResourceProofResponse, SectionAuth,
-        SystemMsg,
+        JoinRejectionReason, JoinRequest, JoinResponse, RelayMsg, ResourceProofResponse,
+        SectionAuth, SystemMsg,
     },
-    AuthKind, DstLocation, MsgType, NodeAuth, WireMsg,
+    AuthKind, DstLocation, MsgType, NodeAuth, ProtocolVersions, WireMsg,
 };
 use sn_interface::network_knowledge::{
-    prefix_map::NetworkPrefixMap, NetworkKnowledge, NodeInfo, SectionAuthUtils, MIN_ADULT_AGE,
+    prefix_map::NetworkPrefixMap, InvalidSapCounts, NetworkKnowledge, NodeInfo, SectionAuthUtils,
+    MIN_ADULT_AGE,
 };
 
+use sn_interface::protocol_constants::{ProtocolConstants, ProtocolDigest};
 use sn_interface::types::{keys::ed25519, log_markers::LogMarker, Peer};
 
 use backoff::{backoff::Backoff, ExponentialBackoff};
@@ -30,7 +32,10 @@ use bls::PublicKey as BlsPublicKey;
 use futures::future;
 use resource_proof::ResourceProof;
 use std::net::SocketAddr;
-use tokio::{sync::mpsc, time::sleep, time::Duration};
+use tokio::{
+    sync::mpsc,
+    time::{sleep, Duration, Instant},
+};
 use tracing::Instrument;
 use xor_name::Prefix;
 
@@ -44,16 +49,15 @@ pub(crate) async fn join_network(
     comm: &Comm,
     incoming_msgs: &mut mpsc::Receiver<MsgEvent>,
     bootstrap_addr: SocketAddr,
-    genesis_key: BlsPublicKey,
+    prefix_map: NetworkPrefixMap,
+    our_relay: &OurRelay,
 ) -> Result<(NodeInfo, NetworkKnowledge)> {
     let (outgoing_msgs_sender, outgoing_msgs_receiver) = mpsc::channel(1);
 
     let span = trace_span!("bootstrap");
 
-    // Read prefix map from cache if available
-    let prefix_map = read_prefix_map_from_disk(genesis_key).await?;
-
-    let state = Join::new(node, outgoing_msgs_sender, incoming_msgs, prefix_map);
+    let state = Join::new(node, outgoing_msgs_sender, incoming_msgs, prefix_map)
+        .with_our_relay(our_relay.clone());
 
     future::join(
         state.run(bootstrap_addr),
@@ -74,6 +78,18 @@ struct Join<'a> {
     prefix_map: NetworkPrefixMap,
     backoff: ExponentialBackoff,
     aggregated: bool,
+    // Number of invalid SAPs received, per responder.
+    invalid_saps: InvalidSapCounts,
+    // Digest of the protocol constants we run with, presented to the section.
+    protocol_digest: ProtocolDigest,
+    // Messaging protocol versions we read and write, presented to the section.
+    protocol_versions: ProtocolVersions,
+    // The peer relaying our msgs, if the section can't connect to us.
+    our_relay: OurRelay,
+    // Those we last sent our join request to ourselves, to ask to relay for us if need be.
+    relay_candidates: Vec<Peer>,
+    // Our last join request, sent again through a relay once one takes us.
+    pending_join: Option<(JoinRequest, BlsPublicKey)>,
 }
 
 impl<'a> Join<'a> {
@@ -101,9 +117,21 @@ impl<'a> Join<'a> {
             prefix_map,
             backoff,
             aggregated: false,
+            invalid_saps: InvalidSapCounts::default(),
+            protocol_digest: ProtocolConstants::current().digest(),
+            protocol_versions: ProtocolVersions::ours(),
+            our_relay: OurRelay::default(),
+            relay_candidates: vec![],
+            pending_join: None,
         }
     }
 
+    // Has our msgs go through a relay, should the section be unable to connect to us.
+    fn with_our_relay(mut self, our_relay: OurRelay) -> Self {
+        self.our_relay = our_relay;
+        self
+    }
+
     // Send `JoinRequest` and wait for the response. If the response is:
     // - `Retry`: repeat with the new info.
     // - `Redirect`: repeat with the new set of addresses.
@@ -117,7 +145,16 @@ impl<'a> Join<'a> {
 
         let (target_section_key, recipients) =
             if let Ok(sap) = self.prefix_map.section_by_name(&bootstrap_peer.name()) {
-                (sap.section_key(), sap.elders_vec())
+                let mut recipients = sap.elders_vec();
+                // The elders cached may have all gone since, while whoever we bootstrapped from
+                // is there to tell us of those there are now.
+                if !recipients
+                    .iter()
+                    .any(|elder| elder.addr() == bootstrap_addr)
+                {
+                    recipients.push(bootstrap_peer);
+                }
+                (sap.section_key(), recipients)
             } else {
                 (genesis_key, vec![bootstrap_peer])
             };
@@ -143,6 +180,8 @@ impl<'a> Join<'a> {
         // `JoinRequest` again with it.
         let join_request = JoinRequest {
             section_key,
+            protocol_digest: self.protocol_digest,
+            protocol_versions: self.protocol_versions,
             resource_proof_response: None,
         };
 
@@ -156,16 +195,55 @@ impl<'a> Join<'a> {
             let (response, sender) = self.receive_join_response().await?;
             match response {
                 JoinResponse::Rejected(JoinRejectionReason::NodeNotReachable(addr)) => {
-                    error!(
-                        "Node cannot join the network since it is not externally reachable: {}",
+                    if self.our_relay.is_needed().await {
+                        // Rejected again, by the other elders or through a relay which can't
+                        // be reached either. Another relay is asked once the one asked refuses.
+                        trace!("Ignoring another rejection as unreachable from {}", sender);
+                        continue;
+                    }
+                    warn!(
+                        "Not externally reachable at {}, asking a peer to relay for us",
                         addr
                     );
-                    return Err(Error::NodeNotReachable(addr));
+                    if !self.ask_next_relay().await? {
+                        error!(
+                            "Node cannot join the network since it is not externally reachable: {}",
+                            addr
+                        );
+                        return Err(Error::NodeNotReachable(addr));
+                    }
                 }
                 JoinResponse::Rejected(JoinRejectionReason::JoinsDisallowed) => {
                     error!("Network is set to not taking any new joining node, try join later.");
                     return Err(Error::TryJoinLater);
                 }
+                JoinResponse::Rejected(JoinRejectionReason::ProtocolMismatch { section }) => {
+                    error!(
+                        "Node cannot join the network since it runs with other protocol \
+                        constants than the section: ours {}, the section's {}",
+                        self.protocol_digest, section
+                    );
+                    return Err(Error::ProtocolMismatch {
+                        ours: self.protocol_digest,
+                        section,
+                    });
+                }
+                JoinResponse::Rejected(JoinRejectionReason::IncompatibleVersion { section }) => {
+                    error!(
+                        "Node cannot join the network since it can't read the section's \
+                        messages or the other way round: ours {}, the section's {}",
+                        self.protocol_versions, section
+                    );
+                    return Err(Error::IncompatibleVersion {
+                        ours: self.protocol_versions,
+                        section,
+                    });
+                }
+                JoinResponse::Rejected(reason) => {
+                    // Only joins as relocated are rejected over their relocation.
+                    trace!("Ignore rejection not for a join like ours: {:?}", reason);
+                    continue;
+                }
                 JoinResponse::Approval {
                     section_auth,
                     genesis_key,
@@ -193,6 +271,14 @@ impl<'a> Join<'a> {
 
                     // Building our network knowledge instance will validate SAP and section chain.
                     let section_auth = section_auth.into_authed_state();
+                    if self
+                        .invalid_saps
+                        .check(&section_auth.value, &sender)
+                        .await
+                        .is_err()
+                    {
+                        continue;
+                    }
 
                     let network_knowledge = NetworkKnowledge::new(
                         genesis_key,
@@ -210,6 +296,14 @@ impl<'a> Join<'a> {
                     expected_age,
                 } => {
                     let section_auth = section_auth.into_state();
+                    if self
+                        .invalid_saps
+                        .check(&section_auth, &sender)
+                        .await
+                        .is_err()
+                    {
+                        continue;
+                    }
 
                     trace!(
                         "Joining node {:?} - {:?}/{:?} received a Retry from {} with SAP {:?}, expected_age: {}, our age: {}",
@@ -292,6 +386,8 @@ impl<'a> Join<'a> {
                     section_key = section_auth.section_key();
                     let join_request = JoinRequest {
                         section_key,
+                        protocol_digest: self.protocol_digest,
+                        protocol_versions: self.protocol_versions,
                         resource_proof_response: None,
                     };
 
@@ -301,15 +397,16 @@ impl<'a> Join<'a> {
                 }
                 JoinResponse::Redirect(section_auth) => {
                     trace!("Received a redirect/retry JoinResponse from {}. Sending request to the latest contacts", sender);
-                    if section_auth.elders.is_empty() {
-                        error!(
-                            "Invalid JoinResponse::Redirect, empty list of Elders: {:?}",
-                            section_auth
-                        );
+                    let section_auth = section_auth.into_state();
+                    if self
+                        .invalid_saps
+                        .check(&section_auth, &sender)
+                        .await
+                        .is_err()
+                    {
                         continue;
                     }
 
-                    let section_auth = section_auth.into_state();
                     if !section_auth.prefix().matches(&self.node.name()) {
                         warn!(
                             "Ignoring newer JoinResponse::Redirect response not for us {:?}, SAP {:?} from {:?}",
@@ -347,6 +444,8 @@ impl<'a> Join<'a> {
 
                     let join_request = JoinRequest {
                         section_key,
+                        protocol_digest: self.protocol_digest,
+                        protocol_versions: self.protocol_versions,
                         resource_proof_response: None,
                     };
 
@@ -356,6 +455,8 @@ impl<'a> Join<'a> {
                 JoinResponse::ResourceChallenge {
                     data_size,
                     difficulty,
+                    memory_kib,
+                    memory_difficulty,
                     nonce,
                     nonce_signature,
                 } => {
@@ -364,12 +465,19 @@ impl<'a> Join<'a> {
                     let data = rp.create_proof_data(&nonce);
                     let mut prover = rp.create_prover(data.clone());
                     let solution = prover.solve();
+                    let memory_solution = solve_memory_proof(&nonce, memory_kib, memory_difficulty);
 
                     let join_request = JoinRequest {
                         section_key,
+                        protocol_digest: self.protocol_digest,
+                        protocol_versions: self.protocol_versions,
                         resource_proof_response: Some(ResourceProofResponse {
                             solution,
                             data,
+                            difficulty,
+                            memory_kib,
+                            memory_difficulty,
+                            memory_solution,
                             nonce,
                             nonce_signature,
                         }),
@@ -405,9 +513,10 @@ impl<'a> Join<'a> {
         }
 
         info!("Sending {:?} to {:?}", join_request, recipients);
+        self.pending_join = Some((join_request.clone(), section_key));
 
         let node_msg = SystemMsg::JoinRequest(Box::new(join_request));
-        let wire_msg = WireMsg::single_src(
+        let mut wire_msg = WireMsg::single_src(
             &self.node,
             DstLocation::Section {
                 name: self.node.name(),
@@ -416,15 +525,103 @@ impl<'a> Join<'a> {
             node_msg,
             section_key,
         )?;
+        let mut recipients = recipients.to_vec();
+
+        if let Some(relay) = self.our_relay.current().await {
+            let msg = RelayMsg::Forward {
+                recipients,
+                msg: wire_msg.serialize()?,
+            };
+            wire_msg = self.relay_wire_msg(relay, msg, section_key)?;
+            recipients = vec![relay];
+        } else {
+            self.relay_candidates = recipients.clone();
+        }
 
-        let _res = self
-            .outgoing_msgs
-            .send((wire_msg, recipients.to_vec()))
-            .await;
+        let _res = self.outgoing_msgs.send((wire_msg, recipients)).await;
 
         Ok(())
     }
 
+    // Asks the next of those we sent our join request to to relay for us, returning whether
+    // there was any left to ask.
+    async fn ask_next_relay(&mut self) -> Result<bool> {
+        let relay = match self.our_relay.next(&self.relay_candidates).await {
+            Some(relay) => relay,
+            None => return Ok(false),
+        };
+        info!("Asking {} to relay for us", relay);
+        let section_key = self
+            .pending_join
+            .as_ref()
+            .map_or_else(|| self.prefix_map.genesis_key(), |(_, key)| *key);
+        let msg = RelayMsg::Register { lease: RELAY_LEASE };
+        let wire_msg = self.relay_wire_msg(relay, msg, section_key)?;
+        let _res = self.outgoing_msgs.send((wire_msg, vec![relay])).await;
+        Ok(true)
+    }
+
+    // Handles the reply of a peer we asked to relay for us, sending our join request again
+    // through it if it took us.
+    async fn handle_relay_reply(&mut self, sender: Peer, msg: RelayMsg) -> Result<()> {
+        if !self.our_relay.was_asked(&sender.name()).await {
+            trace!("Ignoring a relay msg we didn't ask {} for", sender);
+            return Ok(());
+        }
+        match msg {
+            RelayMsg::Registered { lease } => {
+                self.our_relay
+                    .registered(sender, lease, Instant::now())
+                    .await;
+                if let Some((join_request, section_key)) = self.pending_join.clone() {
+                    self.send_join_requests(
+                        join_request,
+                        &self.relay_candidates.clone(),
+                        section_key,
+                        false,
+                    )
+                    .await?;
+                }
+                Ok(())
+            }
+            RelayMsg::Refused(refusal) => {
+                info!("{} won't relay for us: {:?}", sender, refusal);
+                self.our_relay.lost(&sender.name()).await;
+                if self.ask_next_relay().await? {
+                    Ok(())
+                } else {
+                    error!("Node cannot join the network since no peer relays for it");
+                    Err(Error::NodeNotReachable(self.node.addr))
+                }
+            }
+            msg => {
+                trace!(
+                    "Ignoring relay msg from {} while joining: {:?}",
+                    sender,
+                    msg
+                );
+                Ok(())
+            }
+        }
+    }
+
+    fn relay_wire_msg(
+        &self,
+        relay: Peer,
+        msg: RelayMsg,
+        section_key: BlsPublicKey,
+    ) -> Result<WireMsg> {
+        WireMsg::single_src(
+            &self.node,
+            DstLocation::Node {
+                name: relay.name(),
+                section_pk: section_key,
+            },
+            SystemMsg::Relay(msg),
+            section_key,
+        )
+    }
+
     // TODO: receive JoinResponse from the JoinResponse handler directly,
     // analogous to the JoinAsRelocated flow.
     #[tracing::instrument(skip(self))]
@@ -449,6 +646,13 @@ impl<'a> Join<'a> {
                             msg: SystemMsg::JoinResponse(resp),
                             ..
                         }) => (*resp, sender),
+                        Ok(MsgType::System {
+                            msg: SystemMsg::Relay(msg),
+                            ..
+                        }) => {
+                            self.handle_relay_reply(sender, msg).await?;
+                            continue;
+                        }
                         Ok(MsgType::Service { msg_id, .. } | MsgType::System { msg_id, .. }) => {
                             trace!(
                                 "Bootstrap message discarded: sender: {:?} msg_id: {:?}",
@@ -463,6 +667,11 @@ impl<'a> Join<'a> {
                         }
                     },
                 },
+                // Peers don't know of us yet, so only reach us on the listener for them.
+                MsgEvent::Misdirected { sender, .. } => {
+                    trace!("Bootstrap message discarded: misdirected from {:?}", sender);
+                    continue;
+                }
             };
 
             return Ok((join_response, sender));
@@ -511,7 +720,10 @@ mod tests {
 
     use crate::init_test_logger;
     use sn_interface::elder_count;
-    use sn_interface::messaging::SectionAuthorityProvider as SectionAuthorityProviderMsg;
+    use sn_interface::messaging::{
+        serialisation::MESSAGING_PROTO_VERSION,
+        SectionAuthorityProvider as SectionAuthorityProviderMsg,
+    };
     use sn_interface::types::PublicKey;
 
     use assert_matches::assert_matches;
@@ -694,6 +906,8 @@ mod tests {
                         public_key_set: new_pk_set.clone(),
                         elders: new_bootstrap_addrs.clone(),
                         members: BTreeMap::new(),
+                        protocol_digest: ProtocolConstants::current().digest(),
+                        network_params: new_section_auth.network_params(),
                     },
                 ))),
                 &bootstrap_node,
@@ -784,6 +998,8 @@ mod tests {
                         public_key_set: new_pk_set.clone(),
                         elders: BTreeMap::new(),
                         members: BTreeMap::new(),
+                        protocol_digest: ProtocolConstants::current().digest(),
+                        network_params: new_section_auth.network_params(),
                     },
                 ))),
                 &bootstrap_node,
@@ -803,6 +1019,8 @@ mod tests {
                         public_key_set: new_pk_set.clone(),
                         elders: addrs.clone(),
                         members: BTreeMap::new(),
+                        protocol_digest: ProtocolConstants::current().digest(),
+                        network_params: new_section_auth.network_params(),
                     },
                 ))),
                 &bootstrap_node,
@@ -884,6 +1102,69 @@ mod tests {
         test_result
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn join_incompatible_version_response() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, mut recv_rx) = mpsc::channel(1);
+
+        let (section_auth, mut nodes, sk_set) =
+            gen_section_authority_provider(Prefix::default(), elder_count());
+        let bootstrap_node = nodes.remove(0);
+
+        let node = NodeInfo::new(
+            ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE),
+            gen_addr(),
+        );
+
+        let section_key = sk_set.secret_key().public_key();
+        let state = Join::new(
+            node,
+            send_tx,
+            &mut recv_rx,
+            NetworkPrefixMap::new(section_key),
+        );
+        let section_versions = ProtocolVersions {
+            current: MESSAGING_PROTO_VERSION + 1,
+            min_compatible: MESSAGING_PROTO_VERSION + 1,
+            max_compatible: MESSAGING_PROTO_VERSION + 1,
+        };
+
+        let bootstrap_task = state.run(bootstrap_node.addr);
+        let test_task = async {
+            let (wire_msg, _) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| eyre!("JoinRequest was not received"))?;
+
+            assert_matches!(wire_msg.into_msg(), Ok(MsgType::System { msg, .. }) =>
+                assert_matches!(msg, SystemMsg::JoinRequest(request) =>
+                    assert_eq!(request.protocol_versions, ProtocolVersions::ours())));
+
+            send_response(
+                &recv_tx,
+                SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                    JoinRejectionReason::IncompatibleVersion {
+                        section: section_versions,
+                    },
+                ))),
+                &bootstrap_node,
+                section_auth.section_key(),
+            )?;
+
+            Ok(())
+        };
+
+        let (join_result, test_result) = future::join(bootstrap_task, test_task).await;
+
+        assert_matches!(
+            join_result,
+            Err(RoutingError::IncompatibleVersion { ours, section })
+                if ours == ProtocolVersions::ours() && section == section_versions
+        );
+
+        test_result
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn join_invalid_retry_prefix_response() -> Result<()> {
         init_test_logger();
@@ -12,59 +12,80 @@ mod relocate;
 pub(crate) use join::join_network;
 pub(crate) use relocate::JoiningAsRelocated;
 
-#[cfg(not(test))]
-use crate::node::Error;
-use crate::node::Result;
+use super::PrefixMapDb;
+use crate::node::{Error, Result};
 use sn_interface::network_knowledge::prefix_map::NetworkPrefixMap;
 
 use bls::PublicKey as BlsPublicKey;
-use std::{collections::HashSet, net::SocketAddr};
-#[cfg(not(test))]
-use tokio::{fs::File, io::AsyncReadExt};
+use std::{collections::HashSet, net::SocketAddr, path::Path};
+use xor_name::XorName;
 
 type UsedRecipientSaps = HashSet<(SocketAddr, BlsPublicKey)>;
 
-#[cfg(not(test))]
-// Reads PrefixMap from '~/.safe/prefix_map' if present.
-async fn read_prefix_map_from_disk(genesis_key: BlsPublicKey) -> Result<NetworkPrefixMap> {
-    let read_prefix_map = match dirs_next::home_dir() {
-        None => None,
-        Some(mut prefix_map_dir) => {
-            // Read NetworkPrefixMap from disk if present
-            prefix_map_dir.push(".safe");
-            prefix_map_dir.push("prefix_maps");
+/// The latest prefix map we know of the network with `genesis_key`: the one cached in our
+/// `root_dir` if any, else the one shared in `~/.safe/prefix_maps`, else a fresh one.
+pub(crate) async fn cached_prefix_map(
+    genesis_key: BlsPublicKey,
+    root_dir: &Path,
+) -> Result<NetworkPrefixMap> {
+    if let Some(prefix_map) = PrefixMapDb::new(root_dir).read(genesis_key).await {
+        info!(
+            "Read the PrefixMap cached in {}, of {} sections",
+            root_dir.display(),
+            prefix_map.len()
+        );
+        return Ok(prefix_map);
+    }
+    match read_shared_prefix_map(genesis_key).await {
+        Some(prefix_map) if prefix_map.genesis_key() != genesis_key => {
+            Err(Error::InvalidGenesisKey(prefix_map.genesis_key()))
+        }
+        Some(prefix_map) => Ok(prefix_map),
+        None => Ok(NetworkPrefixMap::new(genesis_key)),
+    }
+}
 
-            if let Ok(mut prefix_map_file) = File::open(prefix_map_dir.clone()).await {
-                let mut prefix_map_contents = vec![];
-                match prefix_map_file.read_to_end(&mut prefix_map_contents).await {
-                    Ok(_) => rmp_serde::from_slice(&prefix_map_contents)
-                        .ok()
-                        .map(|map: NetworkPrefixMap| (map, prefix_map_dir)),
-                    Err(_) => None,
-                }
-            } else {
-                None
-            }
+/// The elders known from `prefix_map` to bootstrap from, those of the section closest to `name`
+/// first.
+pub(crate) fn cached_contacts(prefix_map: &NetworkPrefixMap, name: &XorName) -> Vec<SocketAddr> {
+    let mut saps = prefix_map.all();
+    saps.sort_by(|lhs, rhs| lhs.prefix().cmp_distance(&rhs.prefix(), name));
+    let mut contacts = vec![];
+    for addr in saps
+        .iter()
+        .flat_map(|sap| sap.elders_vec())
+        .map(|elder| elder.addr())
+    {
+        if !contacts.contains(&addr) {
+            contacts.push(addr);
         }
-    };
+    }
+    contacts
+}
 
-    match read_prefix_map {
-        Some((prefix_map, dir)) => {
+#[cfg(not(test))]
+// Reads the PrefixMap shared in `~/.safe/prefix_maps`, if present.
+async fn read_shared_prefix_map(genesis_key: BlsPublicKey) -> Option<NetworkPrefixMap> {
+    let path = dirs_next::home_dir()?
+        .join(".safe")
+        .join("prefix_maps")
+        .join(format!("{:?}", genesis_key));
+    match sn_interface::network_knowledge::utils::read_prefix_map_from_disk(&path).await {
+        Ok(prefix_map) => {
             info!(
                 "Read PrefixMap from disk successfully from {}",
-                dir.display()
+                path.display()
             );
-            if prefix_map.genesis_key() != genesis_key {
-                Err(Error::InvalidGenesisKey(prefix_map.genesis_key()))
-            } else {
-                Ok(prefix_map)
-            }
+            Some(prefix_map)
+        }
+        Err(error) => {
+            debug!("No PrefixMap read from {}: {:?}", path.display(), error);
+            None
         }
-        None => Ok(NetworkPrefixMap::new(genesis_key)),
     }
 }
 
 #[cfg(test)]
-async fn read_prefix_map_from_disk(genesis_key: BlsPublicKey) -> Result<NetworkPrefixMap> {
-    Ok(NetworkPrefixMap::new(genesis_key))
+async fn read_shared_prefix_map(_genesis_key: BlsPublicKey) -> Option<NetworkPrefixMap> {
+    None
 }
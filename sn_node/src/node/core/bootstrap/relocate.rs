@@ -83,6 +83,7 @@ impl JoiningAsRelocated {
     // - `Redirect`: repeat join request with the new set of addresses.
     // - `Approval`: returns the `Section` to use by this node, completing the relocation.
     // - `NodeNotReachable`: returns an error, completing the relocation attempt.
+    // - `Rejected`: returns an error, completing the relocation attempt.
     pub(crate) async fn handle_join_response(
         &mut self,
         join_response: JoinAsRelocatedResponse,
@@ -186,6 +187,12 @@ impl JoiningAsRelocated {
                 );
                 Err(Error::NodeNotReachable(addr))
             }
+            JoinAsRelocatedResponse::Rejected(reason) => {
+                error!(
+                    "Node cannot join as relocated since the relocation was rejected: {reason:?}"
+                );
+                Err(Error::RelocationRejected(reason))
+            }
         }
     }
 
@@ -0,0 +1,309 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_interface::messaging::AuthKind;
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// Connections a listener holds open at most, by default.
+pub(crate) const DEFAULT_MAX_NODE_CONNS: usize = 1_000;
+pub(crate) const DEFAULT_MAX_CLIENT_CONNS: usize = 5_000;
+/// New connections per s a listener accepts, by default, on top of a burst of as many.
+pub(crate) const DEFAULT_NODE_CONNS_PER_S: u32 = 100;
+pub(crate) const DEFAULT_CLIENT_CONNS_PER_S: u32 = 200;
+
+/// The kind of peers a listener is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ListenerKind {
+    Nodes,
+    Clients,
+}
+
+impl ListenerKind {
+    /// The kind of the peer that sent a msg with the given auth.
+    pub(crate) fn of(auth: &AuthKind) -> Self {
+        match auth {
+            AuthKind::Service(_) => Self::Clients,
+            AuthKind::Node(_) | AuthKind::NodeBlsShare(_) => Self::Nodes,
+        }
+    }
+
+    /// Names the listener for the given kind of peers, "all" for one taking both.
+    pub(crate) fn label(kind: Option<Self>) -> &'static str {
+        match kind {
+            Some(Self::Nodes) => "nodes",
+            Some(Self::Clients) => "clients",
+            None => "all",
+        }
+    }
+}
+
+impl fmt::Display for ListenerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Self::label(Some(*self)))
+    }
+}
+
+/// Limits on the connections a listener takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ConnLimits {
+    /// Connections held open at once.
+    pub(crate) max_conns: usize,
+    /// New connections accepted per s, on top of a burst of as many.
+    pub(crate) conns_per_s: u32,
+}
+
+impl ConnLimits {
+    pub(crate) fn nodes() -> Self {
+        Self {
+            max_conns: DEFAULT_MAX_NODE_CONNS,
+            conns_per_s: DEFAULT_NODE_CONNS_PER_S,
+        }
+    }
+
+    pub(crate) fn clients() -> Self {
+        Self {
+            max_conns: DEFAULT_MAX_CLIENT_CONNS,
+            conns_per_s: DEFAULT_CLIENT_CONNS_PER_S,
+        }
+    }
+}
+
+/// Decides which connections a listener takes, within its own budget of open connections and
+/// rate of new ones, and which peers it sends on to the listener for their kind.
+///
+/// A listener serving both kinds of peers (the default, where clients and nodes share a socket)
+/// redirects no one.
+#[derive(Clone)]
+pub(crate) struct Admission {
+    inner: Arc<Mutex<Gate>>,
+    open: Arc<AtomicUsize>,
+    refused: Arc<AtomicU64>,
+}
+
+struct Gate {
+    serves: Option<ListenerKind>,
+    redirect_to: Option<SocketAddr>,
+    limits: ConnLimits,
+    // Accept rate, as a token bucket holding up to `limits.conns_per_s` tokens.
+    tokens: f64,
+    refilled_at: Option<Instant>,
+}
+
+/// Held for as long as an admitted connection is open, giving back its place in the budget
+/// when dropped.
+#[derive(Debug)]
+pub(crate) struct ConnPermit {
+    open: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnPermit {
+    fn drop(&mut self) {
+        let _prev = self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Admission {
+    /// A listener for the given kind of peers, or for both when `None`.
+    pub(crate) fn new(serves: Option<ListenerKind>, limits: ConnLimits) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Gate {
+                serves,
+                redirect_to: None,
+                limits,
+                tokens: f64::from(limits.conns_per_s),
+                refilled_at: None,
+            })),
+            open: Arc::new(AtomicUsize::new(0)),
+            refused: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Has the listener only serve the given kind of peers, sending the others on to `other`,
+    /// the listener for them.
+    pub(crate) fn serve_only(&self, kind: ListenerKind, other: SocketAddr) {
+        let mut gate = self.gate();
+        gate.serves = Some(kind);
+        gate.redirect_to = Some(other);
+    }
+
+    pub(crate) fn set_limits(&self, limits: ConnLimits) {
+        let mut gate = self.gate();
+        gate.tokens = gate.tokens.min(f64::from(limits.conns_per_s));
+        gate.limits = limits;
+    }
+
+    /// The kind of peers the listener serves, or `None` if both.
+    pub(crate) fn serves(&self) -> Option<ListenerKind> {
+        self.gate().serves
+    }
+
+    /// Takes a place in the budget for a new connection, if there's one left and the accept rate
+    /// allows for it.
+    pub(crate) fn try_admit(&self) -> Option<ConnPermit> {
+        self.try_admit_at(Instant::now())
+    }
+
+    fn try_admit_at(&self, now: Instant) -> Option<ConnPermit> {
+        let mut gate = self.gate();
+        let limits = gate.limits;
+
+        let burst = f64::from(limits.conns_per_s);
+        if let Some(refilled_at) = gate.refilled_at {
+            let elapsed = now.saturating_duration_since(refilled_at).as_secs_f64();
+            gate.tokens = (gate.tokens + elapsed * burst).min(burst);
+        }
+        gate.refilled_at = Some(now);
+
+        if gate.tokens < 1.0 || self.open.load(Ordering::SeqCst) >= limits.max_conns {
+            let _prev = self.refused.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        gate.tokens -= 1.0;
+        let _prev = self.open.fetch_add(1, Ordering::SeqCst);
+
+        Some(ConnPermit {
+            open: self.open.clone(),
+        })
+    }
+
+    /// Where to send a peer whose first msg carried the given auth, if not to us.
+    pub(crate) fn redirect_for(&self, auth: &AuthKind) -> Option<SocketAddr> {
+        let gate = self.gate();
+        match gate.serves {
+            Some(kind) if kind != ListenerKind::of(auth) => gate.redirect_to,
+            _ => None,
+        }
+    }
+
+    /// Connections currently held open.
+    pub(crate) fn open_conns(&self) -> usize {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    /// Connections refused so far, over budget or rate.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn refused_conns(&self) -> u64 {
+        self.refused.load(Ordering::Relaxed)
+    }
+
+    fn gate(&self) -> std::sync::MutexGuard<'_, Gate> {
+        // The lock is only held for plain arithmetic, which doesn't panic.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sn_interface::messaging::{NodeAuth, ServiceAuth};
+    use sn_interface::types::keys::ed25519;
+    use sn_interface::types::Keypair;
+    use std::time::Duration;
+    use xor_name::Prefix;
+
+    fn client_auth() -> AuthKind {
+        let keypair = Keypair::new_ed25519();
+        AuthKind::Service(ServiceAuth {
+            public_key: keypair.public_key(),
+            signature: keypair.sign(b"msg"),
+        })
+    }
+
+    fn node_auth() -> AuthKind {
+        let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), 5);
+        AuthKind::Node(
+            NodeAuth::authorize(bls::SecretKey::random().public_key(), &keypair, b"msg")
+                .into_inner(),
+        )
+    }
+
+    fn limits(max_conns: usize, conns_per_s: u32) -> ConnLimits {
+        ConnLimits {
+            max_conns,
+            conns_per_s,
+        }
+    }
+
+    #[test]
+    fn each_listener_takes_its_own_kind_of_peers() {
+        let nodes_addr: SocketAddr = ([127, 0, 0, 1], 12_000).into();
+        let clients_addr: SocketAddr = ([127, 0, 0, 1], 12_001).into();
+
+        let shared = Admission::new(None, ConnLimits::nodes());
+        assert_eq!(shared.redirect_for(&client_auth()), None);
+        assert_eq!(shared.redirect_for(&node_auth()), None);
+
+        let nodes = Admission::new(None, ConnLimits::nodes());
+        nodes.serve_only(ListenerKind::Nodes, clients_addr);
+        let clients = Admission::new(Some(ListenerKind::Clients), ConnLimits::clients());
+        clients.serve_only(ListenerKind::Clients, nodes_addr);
+
+        assert_eq!(nodes.redirect_for(&node_auth()), None);
+        assert_eq!(clients.redirect_for(&client_auth()), None);
+
+        // the wrong kind of peer is sent on to the listener for it
+        assert_eq!(nodes.redirect_for(&client_auth()), Some(clients_addr));
+        assert_eq!(clients.redirect_for(&node_auth()), Some(nodes_addr));
+    }
+
+    #[test]
+    fn exhausted_client_budget_leaves_nodes_unaffected() {
+        let nodes = Admission::new(Some(ListenerKind::Nodes), limits(3, 100));
+        let clients = Admission::new(Some(ListenerKind::Clients), limits(2, 100));
+
+        let client_permits: Vec<_> = (0..2).filter_map(|_| clients.try_admit()).collect();
+        assert_eq!(client_permits.len(), 2);
+        assert!(clients.try_admit().is_none());
+
+        let node_permits: Vec<_> = (0..3).filter_map(|_| nodes.try_admit()).collect();
+        assert_eq!(node_permits.len(), 3);
+        assert_eq!(nodes.open_conns(), 3);
+
+        // a closed client connection frees its place, for clients only
+        drop(client_permits);
+        assert_eq!(clients.open_conns(), 0);
+        assert!(clients.try_admit().is_some());
+        assert!(nodes.try_admit().is_none());
+    }
+
+    #[test]
+    fn new_conns_are_accepted_at_the_configured_rate() {
+        let admission = Admission::new(None, limits(100, 2));
+        let start = Instant::now();
+
+        // a burst of as many as accepted per s..
+        let burst: Vec<_> = (0..3)
+            .filter_map(|_| admission.try_admit_at(start))
+            .collect();
+        assert_eq!(burst.len(), 2);
+
+        // ..then refilling over time, regardless of the open conns
+        let half_s = start + Duration::from_millis(500);
+        assert!(admission.try_admit_at(half_s).is_some());
+        assert!(admission.try_admit_at(half_s).is_none());
+
+        let later = start + Duration::from_secs(10);
+        let refilled: Vec<_> = (0..5)
+            .filter_map(|_| admission.try_admit_at(later))
+            .collect();
+        assert_eq!(refilled.len(), 2);
+    }
+}
@@ -6,6 +6,8 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::MsgClass;
+
 use std::{
     collections::BTreeMap,
     sync::{
@@ -14,7 +16,7 @@ use std::{
     },
     time::Duration,
 };
-use sysinfo::{LoadAvg, RefreshKind, System, SystemExt};
+use sysinfo::{LoadAvg, ProcessExt, ProcessRefreshKind, RefreshKind, System, SystemExt};
 use tokio::{sync::RwLock, time::MissedTickBehavior};
 
 pub(crate) const INITIAL_MSGS_PER_S: f64 = 100.0;
@@ -29,6 +31,16 @@ const INITIAL_MSGS_PER_MINUTE: f64 = ONE_MINUTE_AS_SECONDS as f64 * INITIAL_MSGS
 const MAX_CPU_LOAD: f64 = 0.8; // unit: percent
 const DEFAULT_LOAD_PER_MSG: f64 = MAX_CPU_LOAD / INITIAL_MSGS_PER_S; // unit: percent-seconds per msg
 
+// Past any of these, we take fewer msgs, in proportion: the CPU our process uses, as a share of
+// all cores...
+const MAX_PROCESS_CPU: f64 = MAX_CPU_LOAD;
+// ...the memory resident for it, as a share of the system's...
+const MAX_MEMORY_SHARE: f64 = 0.8;
+// ...and the I/Os in flight on the busiest disk.
+const MAX_DISK_QUEUE_DEPTH: f64 = 32.0;
+// However short of resources, we take at least this share of the msgs we'd take otherwise.
+const MIN_HEADROOM: f64 = 0.1;
+
 const ORDER: Ordering = Ordering::SeqCst;
 
 /// Measure and return the rate of msgs per second that we can handle
@@ -37,8 +49,12 @@ const ORDER: Ordering = Ordering::SeqCst;
 pub(crate) struct LoadMonitoring {
     system: Arc<RwLock<System>>,
     load_sample: Arc<RwLock<LoadAvg>>,
+    resource_sample: Arc<RwLock<ResourceUsage>>,
     msg_samples: BTreeMap<Duration, MsgCount>,
     msgs_per_s: BTreeMap<Duration, Arc<RwLock<f64>>>,
+    // Msgs of each class over the shortest sampling interval, and the msgs per s they came at.
+    class_samples: BTreeMap<MsgClass, MsgCount>,
+    class_msgs_per_s: Arc<RwLock<BTreeMap<MsgClass, f64>>>,
 }
 
 /// We have background tasks which update values at specific intervals,
@@ -73,11 +89,19 @@ impl LoadMonitoring {
 
         let load_sample = Arc::new(RwLock::new(normalize(system.load_average())));
 
+        let class_samples = MsgClass::ALL
+            .iter()
+            .map(|class| (*class, MsgCount::new()))
+            .collect();
+
         let instance = Self {
             system: Arc::new(RwLock::new(system)),
             msg_samples,
             msgs_per_s,
             load_sample,
+            resource_sample: Arc::new(RwLock::new(ResourceUsage::default())),
+            class_samples,
+            class_msgs_per_s: Arc::new(RwLock::new(BTreeMap::new())),
         };
 
         for (period, _) in instance.msg_samples.iter() {
@@ -92,12 +116,22 @@ impl LoadMonitoring {
         instance
     }
 
-    pub(crate) fn count_msg(&self) {
+    pub(crate) fn count_msg(&self, class: MsgClass) {
         self.msg_samples
             .iter()
             .for_each(|(_, count)| count.increment());
+        if let Some(count) = self.class_samples.get(&class) {
+            count.increment();
+        }
     }
 
+    /// The msgs per s we got of each class, over the last sampling interval.
+    pub(crate) async fn msgs_per_s_by_class(&self) -> BTreeMap<MsgClass, f64> {
+        self.class_msgs_per_s.read().await.clone()
+    }
+
+    /// The msgs per s we can take, given the load of the system, and what our process uses of its
+    /// CPU, memory and disks.
     pub(crate) async fn msgs_per_s(&self) -> f64 {
         let mut sum = 0.0;
         let mut number_of_points = 0;
@@ -107,11 +141,50 @@ impl LoadMonitoring {
             number_of_points += 1;
         }
 
-        if number_of_points > 0 {
+        let msgs_per_s = if number_of_points > 0 {
             sum / number_of_points as f64
         } else {
             // should be unreachable, since self.msgs_per_s is always > 0 len
             INITIAL_MSGS_PER_S
+        };
+
+        msgs_per_s * self.resource_sample.read().await.headroom()
+    }
+
+    /// What our process used of the system's resources, when last sampled.
+    #[cfg(feature = "metrics")]
+    pub(crate) async fn resource_usage(&self) -> ResourceUsage {
+        *self.resource_sample.read().await
+    }
+
+    async fn sample_resources(&self) -> ResourceUsage {
+        let (cpu, memory) = {
+            let mut system = self.system.write().await;
+            system.refresh_memory();
+            let total_memory = system.total_memory() as f64;
+            let process = match sysinfo::get_current_pid() {
+                Ok(pid)
+                    if system
+                        .refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu()) =>
+                {
+                    system.process(pid)
+                }
+                _ => None,
+            };
+            match process {
+                Some(process) if total_memory > 0.0 => (
+                    // unit: percent of a core
+                    f64::from(process.cpu_usage()) / 100.0 / num_cpus::get() as f64,
+                    process.memory() as f64 / total_memory,
+                ),
+                _ => (0.0, 0.0),
+            }
+        };
+
+        ResourceUsage {
+            cpu,
+            memory,
+            disk_queue_depth: disk_queue_depth().await,
         }
     }
 
@@ -127,6 +200,17 @@ impl LoadMonitoring {
                 }
                 *self.load_sample.write().await =
                     normalize(self.system.read().await.load_average());
+
+                let resources = self.sample_resources().await;
+                debug!("Resource usage sample {:?}", resources);
+                *self.resource_sample.write().await = resources;
+
+                let mut class_msgs_per_s = self.class_msgs_per_s.write().await;
+                for (class, sample) in &self.class_samples {
+                    sample.snapshot();
+                    let _prev = class_msgs_per_s
+                        .insert(*class, sample.read() as f64 / period.as_secs_f64());
+                }
             } else {
                 // allow the one min interval to come first and wait for it to finish
                 tokio::time::sleep(Duration::from_secs(5)).await;
@@ -174,6 +258,63 @@ impl LoadMonitoring {
     }
 }
 
+/// What our process used of the system's resources.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ResourceUsage {
+    /// CPU used by our process, as a share of all cores.
+    pub(crate) cpu: f64,
+    /// Memory resident for our process, as a share of the system's.
+    pub(crate) memory: f64,
+    /// I/Os in flight on the busiest disk, where known.
+    pub(crate) disk_queue_depth: Option<f64>,
+}
+
+impl ResourceUsage {
+    /// The share of the msgs we'd take otherwise that we can take, with the resources used: all
+    /// of them while none is used past its max, fewer the further one is.
+    pub(crate) fn headroom(&self) -> f64 {
+        let headroom = |used: f64, max: f64| if used > max { max / used } else { 1.0 };
+        let headroom = [
+            headroom(self.cpu, MAX_PROCESS_CPU),
+            headroom(self.memory, MAX_MEMORY_SHARE),
+            headroom(self.disk_queue_depth.unwrap_or(0.0), MAX_DISK_QUEUE_DEPTH),
+        ]
+        .into_iter()
+        .fold(1.0, f64::min);
+        f64::max(MIN_HEADROOM, headroom)
+    }
+}
+
+// I/Os in flight on the busiest of the system's disks, from `/proc/diskstats`.
+#[cfg(target_os = "linux")]
+async fn disk_queue_depth() -> Option<f64> {
+    let diskstats = tokio::fs::read_to_string("/proc/diskstats").await.ok()?;
+    // Partitions' I/Os are counted in their disk's too, so only disks are looked at.
+    max_queue_depth(&diskstats, |name| {
+        std::path::Path::new("/sys/block").join(name).exists()
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn disk_queue_depth() -> Option<f64> {
+    None
+}
+
+// The most I/Os in flight on any of the devices in `diskstats` which `is_disk`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn max_queue_depth(diskstats: &str, is_disk: impl Fn(&str) -> bool) -> Option<f64> {
+    diskstats
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            // major, minor, name, then the stats, the 9th being the I/Os in flight
+            let name = fields.get(2)?;
+            let in_flight: u64 = fields.get(11)?.parse().ok()?;
+            is_disk(name).then_some(in_flight as f64)
+        })
+        .reduce(f64::max)
+}
+
 fn normalize(load: LoadAvg) -> LoadAvg {
     // Normalize the reading (e.g. `load=4` when `cores=4` => `normalized_load=1`)
     let cores = num_cpus::get_physical() as f64;
@@ -212,3 +353,41 @@ impl MsgCount {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resources_used_past_their_max_cut_the_msgs_we_take() {
+        let idle = ResourceUsage::default();
+        assert_eq!(idle.headroom(), 1.0);
+
+        // The disk being the furthest past its max, it's what counts.
+        let grinding = ResourceUsage {
+            cpu: 0.9,
+            memory: 0.5,
+            disk_queue_depth: Some(4.0 * MAX_DISK_QUEUE_DEPTH),
+        };
+        assert_eq!(grinding.headroom(), 0.25);
+
+        // However short of resources, we still take some msgs.
+        let swamped = ResourceUsage {
+            disk_queue_depth: Some(f64::INFINITY),
+            ..idle
+        };
+        assert_eq!(swamped.headroom(), MIN_HEADROOM);
+    }
+
+    #[test]
+    fn the_busiest_disk_sets_the_queue_depth() {
+        let diskstats = "\
+   8       0 sda 3096 1227 234994 1580 2048 1904 77840 9092 12 3320 10672 0 0 0 0
+   8       1 sda1 2806 1227 219914 1476 2048 1904 77840 9092 12 3236 10568 0 0 0 0
+ 259       0 nvme0n1 5 0 40 1 0 0 0 0 3 2 1 0 0 0 0
+   7       0 loop0 bad line";
+        let is_disk = |name: &str| ["sda", "nvme0n1", "loop0"].contains(&name);
+        assert_eq!(max_queue_depth(diskstats, is_disk), Some(12.0));
+        assert_eq!(max_queue_depth("", is_disk), None);
+    }
+}
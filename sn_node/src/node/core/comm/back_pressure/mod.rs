@@ -7,12 +7,23 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod load_monitoring;
+mod strategy;
+
+pub use self::strategy::BackPressureStrategy;
+
+#[cfg(feature = "metrics")]
+pub(crate) use self::load_monitoring::ResourceUsage;
 
 use self::load_monitoring::{LoadMonitoring, INITIAL_MSGS_PER_S};
+use self::strategy::{Load, ReportStrategy};
+use super::{msg_queue::QueueDelay, MsgClass};
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 use tokio::{sync::RwLock, time::Instant};
 
+/// How often we tell our section members what they can send us, if it changed significantly.
+pub(crate) const DEFAULT_BACK_PRESSURE_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
 const SANITY_MAX_PER_S_AND_PEER: f64 = INITIAL_MSGS_PER_S;
 const SANITY_MIN_PER_S_AND_PEER: f64 = 1.0; // 1 every s
 
@@ -21,19 +32,65 @@ type OutgoingReport = (Instant, f64);
 #[derive(Clone)]
 pub(crate) struct BackPressure {
     monitoring: LoadMonitoring,
-    last_report: Arc<RwLock<Option<OutgoingReport>>>,
+    queue_delay: QueueDelay,
+    reporting: Arc<RwLock<Reporting>>,
+}
+
+// How reports are decided, and sent.
+struct Reporting {
+    strategy: Box<dyn ReportStrategy>,
+    interval: Duration,
+    last_report: Option<OutgoingReport>,
 }
 
 impl BackPressure {
-    pub(crate) fn new() -> Self {
+    /// Back pressure on the msgs waiting in a queue taking `queue_delay` to be handed over.
+    pub(crate) fn new(queue_delay: QueueDelay) -> Self {
         Self {
             monitoring: LoadMonitoring::new(),
-            last_report: Arc::new(RwLock::new(None)),
+            queue_delay,
+            reporting: Arc::new(RwLock::new(Reporting {
+                strategy: BackPressureStrategy::default().strategy(),
+                interval: DEFAULT_BACK_PRESSURE_REPORT_INTERVAL,
+                last_report: None,
+            })),
         }
     }
 
-    pub(crate) fn count_msg(&self) {
-        self.monitoring.count_msg();
+    /// Has reports decided with `strategy`, starting over with no history, and sent every
+    /// `interval`.
+    pub(crate) async fn set_strategy(&self, strategy: BackPressureStrategy, interval: Duration) {
+        let mut reporting = self.reporting.write().await;
+        reporting.strategy = strategy.strategy();
+        reporting.interval = interval;
+    }
+
+    /// How often reports are sent.
+    pub(crate) async fn report_interval(&self) -> Duration {
+        self.reporting.read().await.interval
+    }
+
+    pub(crate) fn count_msg(&self, class: MsgClass) {
+        self.monitoring.count_msg(class);
+    }
+
+    /// Our current load, as msgs per s.
+    #[cfg(feature = "metrics")]
+    pub(crate) async fn msgs_per_s(&self) -> f64 {
+        self.monitoring.msgs_per_s().await
+    }
+
+    /// What our process used of the system's resources, when last sampled.
+    #[cfg(feature = "metrics")]
+    pub(crate) async fn resource_usage(&self) -> ResourceUsage {
+        self.monitoring.resource_usage().await
+    }
+
+    /// The msgs per s each class of msgs is tolerated at, out of all we can take.
+    pub(crate) async fn tolerated_msgs_per_s_by_class(&self) -> BTreeMap<MsgClass, f64> {
+        let msgs_per_s = 10.0 * self.monitoring.msgs_per_s().await;
+        let received = self.monitoring.msgs_per_s_by_class().await;
+        tolerated_by_class(msgs_per_s, &received)
     }
 
     /// Sent to nodes calling us, if the value has changed significantly.
@@ -44,66 +101,401 @@ impl BackPressure {
     }
 
     async fn try_get_new_value(&self, sessions_count: usize, now: Instant) -> Option<f64> {
-        let msgs_per_s = 10.0 * self.monitoring.msgs_per_s().await;
-        let num_callers = sessions_count as f64;
+        // Nodes calling us share what's tolerated of the classes of msgs they send.
+        let msgs_per_s = self
+            .tolerated_msgs_per_s_by_class()
+            .await
+            .into_iter()
+            .filter(|(class, _)| class.is_from_nodes())
+            .map(|(_, tolerated)| tolerated)
+            .sum();
+        let received_msgs_per_s = self
+            .monitoring
+            .msgs_per_s_by_class()
+            .await
+            .into_iter()
+            .filter(|(class, _)| class.is_from_nodes())
+            .map(|(_, received)| received)
+            .sum();
+        let load = Load {
+            msgs_per_s,
+            received_msgs_per_s,
+            queue_delay: self.queue_delay.get(),
+            sessions_count,
+            now,
+        };
 
-        // avoid divide by 0 errors
-        let msgs_per_s_and_peer = msgs_per_s / f64::max(1.0, num_callers);
+        let mut reporting = self.reporting.write().await;
+        let last_reported = reporting.last_report.map(|(_, previous)| previous);
+        let report = reporting.strategy.next_report(last_reported, &load)?;
+        debug!("Recording changes");
+        reporting.last_report = Some((now, report));
+        Some(report)
+    }
+}
 
-        // make sure not more than sanity max
-        let msgs_per_s_and_peer = f64::min(SANITY_MAX_PER_S_AND_PEER, msgs_per_s_and_peer);
+/// Shares out the `msgs_per_s` we can take between the classes of msgs, given the msgs per s we
+/// got of each. A class getting its share or more is tolerated at its share at least, whatever
+/// the others get, so a flood of one can't crowd out the others. What a class leaves
+/// of its share goes to those getting more than theirs, highest priority first, and what's still
+/// left over is shared out like the rest.
+fn tolerated_by_class(
+    msgs_per_s: f64,
+    received: &BTreeMap<MsgClass, f64>,
+) -> BTreeMap<MsgClass, f64> {
+    let msgs_per_s = f64::max(0.0, msgs_per_s);
+    let received = |class| f64::max(0.0, received.get(&class).copied().unwrap_or(0.0));
 
-        // make sure not less than sanity min
-        let msgs_per_s_and_peer = f64::max(SANITY_MIN_PER_S_AND_PEER, msgs_per_s_and_peer);
+    let mut tolerated: BTreeMap<_, _> = MsgClass::ALL
+        .iter()
+        .map(|class| {
+            let share = msgs_per_s * class.share();
+            (*class, f64::min(received(*class), share))
+        })
+        .collect();
 
-        debug!("Number of callers {:?}", num_callers);
-        debug!("Msgs per s and peer {:?}", msgs_per_s_and_peer);
+    let mut spare = f64::max(0.0, msgs_per_s - tolerated.values().sum::<f64>());
+    for (class, tolerated) in tolerated.iter_mut() {
+        let more = f64::min(spare, received(*class) - *tolerated);
+        *tolerated += more;
+        spare -= more;
+    }
 
-        let prev = *self.last_report.read().await;
+    for (class, tolerated) in tolerated.iter_mut() {
+        *tolerated += spare * class.share();
+    }
 
-        // bound update rates by require some minimum level of change
-        let significant_change = |change_ratio| {
-            // if current val is 5 % worse, or 10 % better, then update our peer with it
-            0.95 >= change_ratio || change_ratio >= 1.1 || change_ratio == 0.0
-        };
+    tolerated
+}
+
+/// What each of `sessions_count` callers can send us per s, out of the `msgs_per_s` we can take,
+/// within sanity bounds.
+fn msgs_per_s_and_peer(msgs_per_s: f64, sessions_count: usize) -> f64 {
+    let num_callers = sessions_count as f64;
+
+    // avoid divide by 0 errors
+    let msgs_per_s_and_peer = msgs_per_s / f64::max(1.0, num_callers);
+
+    // make sure not more than sanity max
+    let msgs_per_s_and_peer = f64::min(SANITY_MAX_PER_S_AND_PEER, msgs_per_s_and_peer);
+
+    // make sure not less than sanity min, nor NaN
+    let msgs_per_s_and_peer = f64::max(SANITY_MIN_PER_S_AND_PEER, msgs_per_s_and_peer);
+
+    debug!("Number of callers {:?}", num_callers);
+    debug!("Msgs per s and peer {:?}", msgs_per_s_and_peer);
+
+    msgs_per_s_and_peer
+}
+
+/// The value to report to our callers, if it changed significantly from the one we last reported,
+/// or from the default they assume if we never did.
+///
+/// It's always compared with what callers last heard, so that however slowly the value drifts,
+/// it's reported once it has drifted far enough from it.
+fn next_report(last_reported: Option<f64>, msgs_per_s: f64, sessions_count: usize) -> Option<f64> {
+    worth_reporting(
+        last_reported,
+        msgs_per_s_and_peer(msgs_per_s, sessions_count),
+    )
+}
+
+/// `msgs_per_s_and_peer`, if it's 5 % lower or 10 % higher than what callers last heard.
+fn worth_reporting(last_reported: Option<f64>, msgs_per_s_and_peer: f64) -> Option<f64> {
+    let previous = last_reported.unwrap_or(SANITY_MAX_PER_S_AND_PEER);
+
+    // bound update rates by require some minimum level of change
+    // (both values being at least the sanity min, the ratio is always positive)
+    let change_ratio = msgs_per_s_and_peer / previous;
+    // if current val is 5 % worse, or 10 % better, then update our peer with it
+    if 0.95 >= change_ratio || change_ratio >= 1.1 {
+        Some(msgs_per_s_and_peer)
+    } else {
+        debug!(
+            "No significant change of backpressure value (previous: {}, ratio: {})",
+            previous, change_ratio
+        );
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::vec, prelude::*};
+
+    // Whether callers assuming `believed` should be told `actual` instead.
+    fn is_significant(actual: f64, believed: f64) -> bool {
+        let change_ratio = actual / believed;
+        0.95 >= change_ratio || change_ratio >= 1.1
+    }
+
+    // Our capacity, as msgs per s, and the number of callers sharing it, at each report.
+    fn loads() -> impl Strategy<Value = Vec<(f64, usize)>> {
+        vec(
+            (
+                prop_oneof![
+                    0.0..10_000.0,
+                    Just(0.0),
+                    Just(f64::NAN),
+                    Just(f64::INFINITY)
+                ],
+                0..100usize,
+            ),
+            1..200,
+        )
+    }
+
+    // Our capacity drifting by at most 1 % per report, the callers staying.
+    fn drifting_loads() -> impl Strategy<Value = (f64, Vec<f64>, usize)> {
+        (1.0..2_000.0, vec(-0.01..0.01, 1..500), 1..20usize)
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_reports_stay_within_bounds_and_track_the_load(loads in loads()) {
+            let mut reported = None;
+            for (msgs_per_s, sessions_count) in loads {
+                let believed = reported.unwrap_or(SANITY_MAX_PER_S_AND_PEER);
+                if let Some(report) = next_report(reported, msgs_per_s, sessions_count) {
+                    prop_assert!(report >= SANITY_MIN_PER_S_AND_PEER);
+                    prop_assert!(report <= SANITY_MAX_PER_S_AND_PEER);
+                    // Only significant changes are worth a report...
+                    prop_assert!(is_significant(report, believed));
+                    reported = Some(report);
+                }
+
+                // ...but callers are never left with a value significantly off.
+                let actual = msgs_per_s_and_peer(msgs_per_s, sessions_count);
+                let believed = reported.unwrap_or(SANITY_MAX_PER_S_AND_PEER);
+                prop_assert!(!is_significant(actual, believed));
+
+                // And a steady load is reported once.
+                prop_assert_eq!(next_report(reported, msgs_per_s, sessions_count), None);
+            }
+        }
 
-        let (record_changes, worthwhile_reporting) = if let Some((_, previous)) = prev {
-            let change_ratio = msgs_per_s_and_peer / previous;
-            if significant_change(change_ratio) {
-                // we want to store the value, and update the node
-                (true, true)
-            } else {
-                debug!(
-                    "No significant change of backpressure value (previous: {}, ratio: {})",
-                    previous, change_ratio
-                );
-                // we neither want to store the value, nor update the sender
-                (false, false)
+        #[test]
+        fn proptest_slow_drift_is_never_suppressed_indefinitely(
+            (start, drift, sessions_count) in drifting_loads()
+        ) {
+            let mut msgs_per_s = start;
+            let mut reported = None;
+            for change in drift {
+                msgs_per_s *= 1.0 + change;
+                if let Some(report) = next_report(reported, msgs_per_s, sessions_count) {
+                    reported = Some(report);
+                }
+                let actual = msgs_per_s_and_peer(msgs_per_s, sessions_count);
+                let believed = reported.unwrap_or(SANITY_MAX_PER_S_AND_PEER);
+                prop_assert!(!is_significant(actual, believed));
             }
-        } else {
-            let change_ratio = msgs_per_s_and_peer / SANITY_MAX_PER_S_AND_PEER;
-            if significant_change(change_ratio) {
-                // we want to store the value, and update the node
-                (true, true)
-            } else {
-                debug!(
-                    "No significant change of sender default backpressure value (ratio: {})",
-                    change_ratio
-                );
-                // we want to store the value, but not update the sender
-                (true, false)
+        }
+    }
+
+    // Our capacity, as msgs per s, and the msgs per s we got of each class.
+    fn class_loads() -> impl Strategy<Value = (f64, BTreeMap<MsgClass, f64>)> {
+        (
+            0.0..10_000.0,
+            vec(0.0..10_000.0, MsgClass::ALL.len())
+                .prop_map(|received| MsgClass::ALL.iter().copied().zip(received).collect()),
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_classes_share_out_all_we_can_take_and_keep_their_share(
+            (msgs_per_s, received) in class_loads()
+        ) {
+            let tolerated = tolerated_by_class(msgs_per_s, &received);
+
+            let total: f64 = tolerated.values().sum();
+            prop_assert!((total - msgs_per_s).abs() <= 1e-6 * f64::max(1.0, msgs_per_s));
+
+            for class in MsgClass::ALL {
+                let share = msgs_per_s * class.share();
+                let tolerated = tolerated[&class];
+                prop_assert!(tolerated >= 0.0);
+                if received[&class] >= share {
+                    prop_assert!(tolerated >= share - 1e-9 * f64::max(1.0, msgs_per_s));
+                }
             }
+        }
+    }
+
+    #[test]
+    fn a_flood_of_client_queries_leaves_node_msgs_their_share() {
+        let msgs_per_s = 1000.0;
+        let node_msgs_per_s = |received: &BTreeMap<MsgClass, f64>| -> f64 {
+            tolerated_by_class(msgs_per_s, received)
+                .into_iter()
+                .filter(|(class, _)| class.is_from_nodes())
+                .map(|(_, tolerated)| tolerated)
+                .sum()
         };
 
-        if record_changes {
-            debug!("Recording changes");
-            *self.last_report.write().await = Some((now, msgs_per_s_and_peer));
+        // Nodes sending more than their share take what clients leave...
+        let busy_nodes = BTreeMap::from([
+            (MsgClass::AntiEntropy, 500.0),
+            (MsgClass::NodeSystem, 500.0),
+        ]);
+        assert_eq!(node_msgs_per_s(&busy_nodes), msgs_per_s);
+
+        // ...but client queries flooding in only ever take theirs, and what nodes leave.
+        let mut flooded = busy_nodes.clone();
+        let _prev = flooded.insert(MsgClass::ClientQuery, 100_000.0);
+        assert_eq!(node_msgs_per_s(&flooded), 0.8 * msgs_per_s);
+        assert_eq!(
+            tolerated_by_class(msgs_per_s, &flooded)[&MsgClass::ClientQuery],
+            200.0
+        );
+    }
+
+    #[test]
+    fn a_value_never_reported_isnt_compared_against() {
+        // Close enough to the default callers assume not to be reported...
+        assert_eq!(next_report(None, 96.0, 1), None);
+        // ...so later values are still compared with the default, not with it.
+        assert_eq!(next_report(None, 91.5, 1), Some(91.5));
+    }
+
+    // Our capacity, the msgs per s nodes sent us, and how long they waited, at each report.
+    fn strategy_loads() -> impl Strategy<Value = Vec<(f64, f64, u64)>> {
+        vec(
+            (
+                prop_oneof![0.0..10_000.0, Just(f64::NAN), Just(f64::INFINITY)],
+                prop_oneof![0.0..20_000.0, Just(f64::NAN)],
+                0..1_000u64,
+            ),
+            1..200,
+        )
+    }
+
+    fn load(msgs_per_s: f64, received_msgs_per_s: f64, queue_delay_ms: u64, now: Instant) -> Load {
+        Load {
+            msgs_per_s,
+            received_msgs_per_s,
+            queue_delay: Duration::from_millis(queue_delay_ms),
+            sessions_count: 10,
+            now,
         }
+    }
+
+    // Reports on `loads` in turn, callers having last been told `reported`, returning the reports
+    // made.
+    fn reports(
+        strategy: &mut dyn ReportStrategy,
+        mut reported: Option<f64>,
+        loads: &[Load],
+    ) -> Vec<Option<f64>> {
+        loads
+            .iter()
+            .map(|load| {
+                let report = strategy.next_report(reported, load);
+                reported = report.or(reported);
+                report
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_every_strategy_reports_within_bounds(loads in strategy_loads()) {
+            let start = Instant::now();
+            let loads: Vec<_> = loads
+                .into_iter()
+                .enumerate()
+                .map(|(i, (msgs_per_s, received, delay_ms))| {
+                    let now = start + DEFAULT_BACK_PRESSURE_REPORT_INTERVAL * i as u32;
+                    load(msgs_per_s, received, delay_ms, now)
+                })
+                .collect();
+            for kind in [
+                BackPressureStrategy::EqualShare,
+                BackPressureStrategy::Aimd,
+                BackPressureStrategy::TokenBucket,
+                BackPressureStrategy::LatencyGradient,
+            ] {
+                for report in reports(&mut *kind.strategy(), None, &loads).into_iter().flatten() {
+                    prop_assert!(report >= SANITY_MIN_PER_S_AND_PEER, "{}: {}", kind, report);
+                    prop_assert!(report <= SANITY_MAX_PER_S_AND_PEER, "{}: {}", kind, report);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn aimd_halves_what_is_tolerated_when_overloaded_and_recovers_slowly() {
+        let now = Instant::now();
+        let mut aimd = BackPressureStrategy::Aimd.strategy();
+        // 10 callers sharing 500 msgs/s, sending 600
+        let overloaded = load(500.0, 600.0, 0, now);
+        assert_eq!(aimd.next_report(None, &overloaded), Some(25.0));
+        assert_eq!(aimd.next_report(Some(25.0), &overloaded), Some(12.5));
+
+        // Recovering by a msg per s and caller at each report.
+        let keeping_up = load(500.0, 100.0, 0, now);
+        let recovering = reports(&mut *aimd, Some(12.5), &[keeping_up; 3]);
+        assert_eq!(recovering, vec![None, Some(14.5), None]);
+
+        // Msgs waiting long in our queues is being overloaded too.
+        let queued = load(500.0, 100.0, 200, now);
+        assert_eq!(aimd.next_report(Some(14.5), &queued), Some(7.75));
+    }
+
+    #[test]
+    fn token_bucket_tolerates_a_burst_out_of_what_was_left_over() {
+        let start = Instant::now();
+        let mut bucket = BackPressureStrategy::TokenBucket.strategy();
+        // 10 callers sharing 100 msgs/s, with a full bucket: twice that for a while.
+        assert_eq!(
+            bucket.next_report(None, &load(100.0, 0.0, 0, start)),
+            Some(20.0)
+        );
+
+        // Sending 150 msgs/s for 10s empties it...
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            bucket.next_report(Some(20.0), &load(100.0, 150.0, 0, later)),
+            Some(15.0)
+        );
+        let later = later + Duration::from_secs(10);
+        assert_eq!(
+            bucket.next_report(Some(15.0), &load(100.0, 150.0, 0, later)),
+            Some(10.0)
+        );
+
+        // ...and sending less refills it.
+        let later = later + Duration::from_secs(20);
+        assert_eq!(
+            bucket.next_report(Some(10.0), &load(100.0, 50.0, 0, later)),
+            Some(20.0)
+        );
+    }
+
+    #[test]
+    fn latency_gradient_tolerates_fewer_msgs_as_they_wait_longer() {
+        let now = Instant::now();
+        let mut gradient = BackPressureStrategy::LatencyGradient.strategy();
+        // 10 callers sharing 400 msgs/s, grown by its square root while msgs barely wait
+        assert_eq!(
+            gradient.next_report(None, &load(400.0, 0.0, 1, now)),
+            Some(42.0)
+        );
+
+        // Msgs waiting 100ms rather than 1ms, the gradient is cut to its minimum of a half.
+        assert_eq!(
+            gradient.next_report(Some(42.0), &load(400.0, 0.0, 100, now)),
+            Some(420.0_f64.mul_add(0.5, 420.0_f64.sqrt()) / 10.0)
+        );
+    }
 
-        if worthwhile_reporting {
-            Some(msgs_per_s_and_peer)
-        } else {
-            None
+    #[test]
+    fn back_pressure_strategies_are_named_in_config() {
+        for name in ["equal-share", "aimd", "token-bucket", "latency-gradient"] {
+            let strategy: BackPressureStrategy = name.parse().unwrap();
+            assert_eq!(strategy.to_string(), name);
         }
+        assert!("fastest".parse::<BackPressureStrategy>().is_err());
     }
 }
@@ -0,0 +1,237 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{msgs_per_s_and_peer, next_report, worth_reporting, SANITY_MAX_PER_S_AND_PEER};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+// AIMD: the share of what's tolerated taken off when we're overloaded...
+const AIMD_DECREASE: f64 = 0.5;
+// ...and the msgs per s added, for each caller, when we aren't.
+const AIMD_INCREASE_PER_PEER: f64 = 1.0;
+// AIMD: msgs waiting longer than this in our queues means we're overloaded.
+const AIMD_MAX_QUEUE_DELAY: Duration = Duration::from_millis(100);
+
+// Token bucket: how long we can take twice the msgs we can sustain, after having taken few.
+const TOKEN_BUCKET_BURST: Duration = Duration::from_secs(10);
+
+// Latency gradient: how much longer than the shortest we've seen msgs can wait in our queues,
+// before we tolerate fewer of them...
+const GRADIENT_TOLERANCE: Duration = Duration::from_millis(20);
+// ...at most halving what's tolerated at once.
+const GRADIENT_MIN: f64 = 0.5;
+// Latency gradient: below this, delays are no different, so the shortest we've seen is at least
+// this.
+const GRADIENT_MIN_DELAY: Duration = Duration::from_millis(1);
+// Latency gradient: how much the shortest delay we've seen rises at each report, so a lasting
+// rise of it is adopted.
+const GRADIENT_BASELINE_DRIFT: f64 = 1.05;
+
+/// The strategies back pressure reports can be decided with, chosen with
+/// `--back-pressure-strategy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackPressureStrategy {
+    /// Callers share out what we can take equally, told anew when it's 5 % lower or 10 % higher
+    /// than what they last heard.
+    #[default]
+    EqualShare,
+    /// What callers are tolerated at grows steadily while we keep up, and halves when we don't:
+    /// when nodes send us more than we can take, or their msgs wait too long to be handled.
+    Aimd,
+    /// Callers can send us more than we can sustain for a while, out of what they left over
+    /// before, up to twice as much for 10s.
+    TokenBucket,
+    /// What callers are tolerated at follows how long their msgs wait to be handled: shrinking
+    /// as the wait grows beyond the shortest we've seen, and growing otherwise.
+    LatencyGradient,
+}
+
+impl BackPressureStrategy {
+    const ALL: [Self; 4] = [
+        Self::EqualShare,
+        Self::Aimd,
+        Self::TokenBucket,
+        Self::LatencyGradient,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::EqualShare => "equal-share",
+            Self::Aimd => "aimd",
+            Self::TokenBucket => "token-bucket",
+            Self::LatencyGradient => "latency-gradient",
+        }
+    }
+
+    /// A strategy of this kind, with no history.
+    pub(crate) fn strategy(self) -> Box<dyn ReportStrategy> {
+        match self {
+            Self::EqualShare => Box::new(EqualShare),
+            Self::Aimd => Box::new(Aimd::default()),
+            Self::TokenBucket => Box::new(TokenBucket::default()),
+            Self::LatencyGradient => Box::new(LatencyGradient::default()),
+        }
+    }
+}
+
+impl Display for BackPressureStrategy {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.name())
+    }
+}
+
+impl FromStr for BackPressureStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown back pressure strategy {:?}, expected equal-share, aimd, \
+                    token-bucket or latency-gradient",
+                    name
+                )
+            })
+    }
+}
+
+/// What reports are decided on, as of one being due.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Load {
+    /// The msgs per s from nodes we can take.
+    pub(crate) msgs_per_s: f64,
+    /// The msgs per s nodes sent us lately.
+    pub(crate) received_msgs_per_s: f64,
+    /// How long msgs waited in our queues lately.
+    pub(crate) queue_delay: Duration,
+    /// The callers sharing what we can take.
+    pub(crate) sessions_count: usize,
+    pub(crate) now: Instant,
+}
+
+/// Decides the msgs per s our callers are to be told they can send us.
+pub(crate) trait ReportStrategy: Send + Sync {
+    /// What each caller is to be told, given our `load` and what they were last told, if
+    /// anything; `None` if they needn't be told anything new.
+    fn next_report(&mut self, last_reported: Option<f64>, load: &Load) -> Option<f64>;
+}
+
+struct EqualShare;
+
+impl ReportStrategy for EqualShare {
+    fn next_report(&mut self, last_reported: Option<f64>, load: &Load) -> Option<f64> {
+        next_report(last_reported, load.msgs_per_s, load.sessions_count)
+    }
+}
+
+#[derive(Default)]
+struct Aimd {
+    // Msgs per s tolerated from all callers.
+    tolerated: Option<f64>,
+}
+
+impl ReportStrategy for Aimd {
+    fn next_report(&mut self, last_reported: Option<f64>, load: &Load) -> Option<f64> {
+        let msgs_per_s = f64::max(0.0, load.msgs_per_s);
+        let tolerated = self.tolerated.unwrap_or(msgs_per_s);
+        let overloaded =
+            load.received_msgs_per_s > msgs_per_s || load.queue_delay > AIMD_MAX_QUEUE_DELAY;
+        let tolerated = if overloaded {
+            debug!(
+                "Overloaded ({} msgs/s received, {:?} queued), decreasing what's tolerated",
+                load.received_msgs_per_s, load.queue_delay
+            );
+            tolerated * AIMD_DECREASE
+        } else {
+            tolerated + AIMD_INCREASE_PER_PEER * f64::max(1.0, load.sessions_count as f64)
+        };
+        // never more than we can take, nor NaN
+        let tolerated = f64::max(0.0, f64::min(msgs_per_s, tolerated));
+        self.tolerated = Some(tolerated);
+
+        worth_reporting(
+            last_reported,
+            msgs_per_s_and_peer(tolerated, load.sessions_count),
+        )
+    }
+}
+
+#[derive(Default)]
+struct TokenBucket {
+    // Msgs we could take but weren't sent, and when they were last counted.
+    tokens: Option<(f64, Instant)>,
+}
+
+impl ReportStrategy for TokenBucket {
+    fn next_report(&mut self, last_reported: Option<f64>, load: &Load) -> Option<f64> {
+        let msgs_per_s = f64::max(0.0, load.msgs_per_s);
+        let capacity = msgs_per_s * TOKEN_BUCKET_BURST.as_secs_f64();
+        // the bucket starts full
+        let (tokens, since) = self.tokens.unwrap_or((capacity, load.now));
+        let elapsed = load.now.saturating_duration_since(since).as_secs_f64();
+        let left_over = msgs_per_s - f64::max(0.0, load.received_msgs_per_s);
+        let tokens = f64::max(0.0, f64::min(capacity, tokens + left_over * elapsed));
+        self.tokens = Some((tokens, load.now));
+
+        // what's left over is spent over the length of a burst
+        let tolerated = msgs_per_s + tokens / TOKEN_BUCKET_BURST.as_secs_f64();
+        worth_reporting(
+            last_reported,
+            msgs_per_s_and_peer(tolerated, load.sessions_count),
+        )
+    }
+}
+
+#[derive(Default)]
+struct LatencyGradient {
+    // Msgs per s tolerated from all callers.
+    tolerated: Option<f64>,
+    // The shortest wait in our queues we've seen.
+    baseline: Option<Duration>,
+}
+
+impl ReportStrategy for LatencyGradient {
+    fn next_report(&mut self, last_reported: Option<f64>, load: &Load) -> Option<f64> {
+        let delay = Duration::max(GRADIENT_MIN_DELAY, load.queue_delay);
+        let baseline = match self.baseline {
+            Some(baseline) => Duration::min(delay, baseline.mul_f64(GRADIENT_BASELINE_DRIFT)),
+            None => delay,
+        };
+        self.baseline = Some(baseline);
+
+        let gradient = (baseline + GRADIENT_TOLERANCE).as_secs_f64() / delay.as_secs_f64();
+        let gradient = gradient.clamp(GRADIENT_MIN, 1.0);
+
+        // grows by its square root while msgs don't wait longer
+        let tolerated = self
+            .tolerated
+            .unwrap_or_else(|| f64::max(0.0, load.msgs_per_s));
+        let tolerated = tolerated * gradient + tolerated.sqrt();
+        // never more than all callers can be told, nor NaN
+        let callers = f64::max(1.0, load.sessions_count as f64);
+        let tolerated = f64::max(
+            0.0,
+            f64::min(SANITY_MAX_PER_S_AND_PEER * callers, tolerated),
+        );
+        self.tolerated = Some(tolerated);
+
+        worth_reporting(
+            last_reported,
+            msgs_per_s_and_peer(tolerated, load.sessions_count),
+        )
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use dashmap::DashMap;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+// Peers whose bytes are accounted for one by one, those past it being only in the totals.
+const MAX_ACCOUNTED_PEERS: usize = 10_000;
+
+/// Bytes per second exchanged with any one peer at most, either way. `None` is uncapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct BandwidthCaps {
+    pub(crate) bytes_in_per_sec: Option<u64>,
+    pub(crate) bytes_out_per_sec: Option<u64>,
+}
+
+/// Bytes exchanged with a peer since we started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PeerBytes {
+    pub(crate) received: u64,
+    pub(crate) sent: u64,
+}
+
+// Bytes a peer may send or be sent right away, refilled at the cap, a second's worth at most,
+// which a new peer starts with. It can go into debt, for a msg larger than a second's worth to
+// still go, once it's been waited for.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            tokens: f64::MAX,
+            refilled_at: now,
+        }
+    }
+
+    // Takes `bytes` from the bucket, returning how long to wait for them to be covered.
+    fn take(&mut self, bytes: usize, per_sec: u64, now: Instant) -> Duration {
+        let rate = per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        self.refilled_at = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PeerBandwidth {
+    bytes: PeerBytes,
+    incoming: Bucket,
+    outgoing: Bucket,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    caps: RwLock<BandwidthCaps>,
+    peers: DashMap<XorName, PeerBandwidth>,
+    received: AtomicU64,
+    sent: AtomicU64,
+}
+
+/// Accounting of the bytes we exchange with each peer, capping how fast any one of them can
+/// use our bandwidth when caps are set. Nothing is dropped over a cap: sends to the peer are held
+/// off, and so is reading its next msg, until the bytes used are back under it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Bandwidth {
+    inner: Arc<Inner>,
+}
+
+impl Bandwidth {
+    pub(crate) fn set_caps(&self, caps: BandwidthCaps) {
+        info!("Bandwidth per peer capped to {:?}", caps);
+        if let Ok(mut current) = self.inner.caps.write() {
+            *current = caps;
+        }
+    }
+
+    /// Accounts for a msg of `bytes` received from `peer`, returning how long to wait before
+    /// reading its next one.
+    pub(crate) fn received(&self, peer: XorName, bytes: usize) -> Duration {
+        self.account(peer, bytes, true, Instant::now())
+    }
+
+    /// Accounts for a msg of `bytes` about to be sent to `peer`, returning how long to wait
+    /// before sending it.
+    pub(crate) fn sent(&self, peer: XorName, bytes: usize) -> Duration {
+        self.account(peer, bytes, false, Instant::now())
+    }
+
+    fn account(&self, peer: XorName, bytes: usize, incoming: bool, now: Instant) -> Duration {
+        let caps = self.inner.caps.read().map(|caps| *caps).unwrap_or_default();
+        let (total, cap) = if incoming {
+            (&self.inner.received, caps.bytes_in_per_sec)
+        } else {
+            (&self.inner.sent, caps.bytes_out_per_sec)
+        };
+        let _prev = total.fetch_add(bytes as u64, Ordering::Relaxed);
+
+        if !self.inner.peers.contains_key(&peer) && self.inner.peers.len() >= MAX_ACCOUNTED_PEERS {
+            return Duration::ZERO;
+        }
+        let mut entry = self
+            .inner
+            .peers
+            .entry(peer)
+            .or_insert_with(|| PeerBandwidth {
+                bytes: PeerBytes::default(),
+                incoming: Bucket::new(now),
+                outgoing: Bucket::new(now),
+            });
+        let PeerBandwidth {
+            bytes: counts,
+            incoming: in_bucket,
+            outgoing: out_bucket,
+        } = &mut *entry;
+        let (counter, bucket) = if incoming {
+            (&mut counts.received, in_bucket)
+        } else {
+            (&mut counts.sent, out_bucket)
+        };
+        *counter += bytes as u64;
+        match cap {
+            Some(per_sec) if per_sec > 0 => bucket.take(bytes, per_sec, now),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Bytes received and sent in all, since we started.
+    pub(crate) fn totals(&self) -> PeerBytes {
+        PeerBytes {
+            received: self.inner.received.load(Ordering::Relaxed),
+            sent: self.inner.sent.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Bytes received from and sent to each peer, since we started.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn per_peer(&self) -> Vec<(XorName, PeerBytes)> {
+        self.inner
+            .peers
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().bytes))
+            .collect()
+    }
+
+    /// Forgets the peers we no longer have a session with, keeping their bytes in the totals.
+    pub(crate) fn retain(&self, peers: impl Fn(&XorName) -> bool) {
+        self.inner.peers.retain(|peer, _| peers(peer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_accounted_per_peer_and_in_all() {
+        let bandwidth = Bandwidth::default();
+        let (a, b) = (xor_name::rand::random(), xor_name::rand::random());
+        assert_eq!(bandwidth.received(a, 100), Duration::ZERO);
+        assert_eq!(bandwidth.sent(a, 50), Duration::ZERO);
+        assert_eq!(bandwidth.received(b, 10), Duration::ZERO);
+
+        assert_eq!(
+            bandwidth.totals(),
+            PeerBytes {
+                received: 110,
+                sent: 50
+            }
+        );
+        bandwidth.retain(|peer| *peer == b);
+        assert_eq!(bandwidth.inner.peers.len(), 1);
+        assert_eq!(bandwidth.totals().received, 110);
+    }
+
+    #[test]
+    fn peers_over_their_cap_are_held_off_for_as_long_as_the_excess_takes() {
+        let bandwidth = Bandwidth::default();
+        bandwidth.set_caps(BandwidthCaps {
+            bytes_in_per_sec: Some(1000),
+            bytes_out_per_sec: None,
+        });
+        let (peer, other) = (xor_name::rand::random(), xor_name::rand::random());
+        let start = Instant::now();
+
+        // A second's worth goes through right away, the excess being waited for.
+        let after = |millis| start + Duration::from_millis(millis);
+        assert_eq!(
+            bandwidth.account(peer, 1500, true, after(1000)),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            bandwidth.account(peer, 1000, true, after(1500)),
+            Duration::from_secs(1)
+        );
+        // Other peers, and what's sent, aren't held off.
+        assert_eq!(
+            bandwidth.account(other, 500, true, after(1500)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            bandwidth.account(peer, 10_000, false, after(1500)),
+            Duration::ZERO
+        );
+        // Once the debt is paid off, up to a second's worth goes through right away.
+        assert_eq!(
+            bandwidth.account(peer, 1000, true, after(10_000)),
+            Duration::ZERO
+        );
+    }
+}
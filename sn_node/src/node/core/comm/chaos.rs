@@ -0,0 +1,131 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use rand::Rng;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+/// Msgs we send dropped on purpose, as if the links to our peers were lossy, for the handling
+/// of failed sends to be seen at work on a running node. Built with the `chaos` feature only.
+///
+/// Shared by the clones of a `Comm`, which consult it for each msg they send.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MsgChaos {
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    // Probability of a msg to any peer being dropped...
+    drop_rate: f64,
+    // ...unless there's one for the peer.
+    peer_drop_rates: BTreeMap<SocketAddr, f64>,
+    dropped: u64,
+}
+
+impl MsgChaos {
+    /// Has `rate` of the msgs to `peer`, or to all peers without one of their own if `None`,
+    /// dropped.
+    pub(crate) fn set_drop_rate(&self, peer: Option<SocketAddr>, rate: f64) {
+        let rate = if rate.is_nan() {
+            0.0
+        } else {
+            rate.clamp(0.0, 1.0)
+        };
+        let mut inner = self.write();
+        match peer {
+            Some(peer) => {
+                let _prev = inner.peer_drop_rates.insert(peer, rate);
+            }
+            None => inner.drop_rate = rate,
+        }
+        warn!(
+            "Chaos: dropping {}% of msgs to {}",
+            rate * 100.0,
+            peer.map_or_else(|| "all peers".to_string(), |peer| peer.to_string())
+        );
+    }
+
+    /// Whether a msg to `recipient` is to be dropped, counting it if so.
+    pub(crate) fn drops(&self, recipient: &SocketAddr) -> bool {
+        let rate = {
+            let inner = self.read();
+            *inner
+                .peer_drop_rates
+                .get(recipient)
+                .unwrap_or(&inner.drop_rate)
+        };
+        let drops = rate > 0.0 && rand::thread_rng().gen_bool(rate);
+        if drops {
+            self.write().dropped += 1;
+        }
+        drops
+    }
+
+    /// Number of msgs dropped so far.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.read().dropped
+    }
+
+    /// Stops dropping msgs.
+    pub(crate) fn heal(&self) {
+        let mut inner = self.write();
+        inner.drop_rate = 0.0;
+        inner.peer_drop_rates.clear();
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Inner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Inner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    #[test]
+    fn msgs_are_dropped_at_the_rate_of_their_recipient() {
+        let chaos = MsgChaos::default();
+        assert!(!chaos.drops(&addr(1)));
+
+        chaos.set_drop_rate(None, 1.0);
+        chaos.set_drop_rate(Some(addr(2)), 0.0);
+        assert!(chaos.drops(&addr(1)));
+        assert!(chaos.drops(&addr(3)));
+        // A peer's own rate takes precedence.
+        assert!(!chaos.drops(&addr(2)));
+        assert_eq!(chaos.dropped(), 2);
+
+        // Rates out of bounds are brought within them.
+        chaos.set_drop_rate(Some(addr(2)), 7.0);
+        assert!(chaos.drops(&addr(2)));
+        chaos.set_drop_rate(None, f64::NAN);
+        assert!(!chaos.drops(&addr(1)));
+
+        chaos.heal();
+        assert!(!chaos.drops(&addr(2)));
+        assert_eq!(chaos.dropped(), 3);
+    }
+}
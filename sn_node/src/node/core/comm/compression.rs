@@ -0,0 +1,341 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Msgs above a size, such as those replicating data, are compressed over the connections whose
+//! ends agreed on a codec for it. Whoever opens a connection offers the codecs it has in a
+//! hello, the other end accepting the one it prefers in a hello back, and msgs go compressed
+//! either way over that connection from then on. Nodes not compressing refuse hellos, as they do
+//! streamed frames, and so never accept a codec nor get a compressed msg.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use dashmap::DashMap;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Size of the msgs above which they're compressed when unspecified, in KiB.
+pub(crate) const DEFAULT_COMPRESSION_THRESHOLD_KB: usize = 8;
+/// Largest msg decompressed.
+const MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+// Level msgs are compressed at with zstd, favouring speed.
+const ZSTD_LEVEL: i32 = 1;
+
+// Lead hellos and compressed msgs. Read as a wire msg header, they claim a length and a version
+// no wire msg has, so that nodes not compressing refuse them rather than misreading them.
+const HELLO_MAGIC: [u8; 4] = [0xff, 0xff, 0xff, 0xfe];
+const COMPRESSED_MAGIC: [u8; 4] = [0xff, 0xff, 0xff, 0xfd];
+// Magic, kind of hello and codecs.
+const HELLO_LEN: usize = HELLO_MAGIC.len() + 2;
+// Magic, codec and msg length.
+const COMPRESSED_HEADER_LEN: usize = COMPRESSED_MAGIC.len() + 1 + 8;
+
+// Kinds of hello.
+const OFFER: u8 = 0;
+const ACCEPT: u8 = 1;
+
+/// Codec msgs are compressed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Codec {
+    Zstd,
+    Lz4,
+}
+
+impl Codec {
+    // In order of preference.
+    const ALL: [Self; 2] = [Self::Zstd, Self::Lz4];
+
+    fn flag(self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Lz4 => 2,
+        }
+    }
+
+    // The one of `flags` we prefer, if any.
+    fn preferred(flags: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| flags & codec.flag() != 0)
+    }
+
+    fn compress(self, msg: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::bulk::compress(msg, ZSTD_LEVEL).ok(),
+            Self::Lz4 => Some(lz4_flex::compress(msg)),
+        }
+    }
+
+    // `None` unless `data` decompresses to `len` bytes exactly.
+    fn decompress(self, data: &[u8], len: usize) -> Option<Vec<u8>> {
+        let msg = match self {
+            Self::Zstd => zstd::bulk::decompress(data, len).ok()?,
+            Self::Lz4 => lz4_flex::decompress(data, len).ok()?,
+        };
+        (msg.len() == len).then_some(msg)
+    }
+}
+
+/// What was received over a connection, once taken in.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Received {
+    /// A msg, decompressed if it came compressed.
+    Msg(Bytes),
+    /// A hello, to be answered with this one, if any.
+    Hello(Option<Bytes>),
+    /// A malformed hello or compressed msg, which was dropped.
+    Dropped,
+}
+
+/// Compression of the msgs sent over each connection. Shared by all the links and listeners of a
+/// `Comm`.
+#[derive(Clone, Debug)]
+pub(crate) struct Compression {
+    // Size of the msgs above which they're compressed, 0 if compression is disabled.
+    threshold: Arc<AtomicUsize>,
+    // Codec agreed on for each connection, by id.
+    codecs: Arc<DashMap<usize, Codec>>,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            threshold: Arc::new(AtomicUsize::new(DEFAULT_COMPRESSION_THRESHOLD_KB * 1024)),
+            codecs: Arc::new(DashMap::new()),
+        }
+    }
+}
+
+impl Compression {
+    pub(crate) fn set_threshold(&self, bytes: usize) {
+        self.threshold.store(bytes, Ordering::Relaxed);
+    }
+
+    fn enabled(&self) -> bool {
+        self.threshold.load(Ordering::Relaxed) > 0
+    }
+
+    /// The hello offering our codecs, to be sent first over a connection we open, unless
+    /// compression is disabled.
+    pub(crate) fn offer(&self) -> Option<Bytes> {
+        let flags = Codec::ALL
+            .iter()
+            .fold(0, |flags, codec| flags | codec.flag());
+        self.enabled().then(|| hello(OFFER, flags))
+    }
+
+    /// The codec agreed on for connection `conn_id`, if any.
+    pub(crate) fn codec(&self, conn_id: usize) -> Option<Codec> {
+        self.codecs.get(&conn_id).map(|codec| *codec)
+    }
+
+    /// Forgets the codec agreed on for connection `conn_id`, once it's closed.
+    pub(crate) fn forget(&self, conn_id: usize) {
+        let _ = self.codecs.remove(&conn_id);
+    }
+
+    /// `msg` to be sent over connection `conn_id`: compressed if it's over the threshold, a codec
+    /// was agreed on for the connection, and it does get smaller, as it is otherwise.
+    pub(crate) fn compress(&self, conn_id: usize, msg: Bytes) -> Bytes {
+        let threshold = self.threshold.load(Ordering::Relaxed);
+        if threshold == 0 || msg.len() <= threshold {
+            return msg;
+        }
+        let codec = match self.codec(conn_id) {
+            Some(codec) => codec,
+            None => return msg,
+        };
+        match codec.compress(&msg) {
+            Some(data) if COMPRESSED_HEADER_LEN + data.len() < msg.len() => {
+                let mut compressed = BytesMut::with_capacity(COMPRESSED_HEADER_LEN + data.len());
+                compressed.put_slice(&COMPRESSED_MAGIC);
+                compressed.put_u8(codec.flag());
+                compressed.put_u64(msg.len() as u64);
+                compressed.put_slice(&data);
+                compressed.freeze()
+            }
+            _ => msg,
+        }
+    }
+
+    /// Takes in what was received over connection `conn_id`: a msg sent as it is is handed back
+    /// so, a compressed one decompressed, and a hello agrees on the connection's codec.
+    pub(crate) fn receive(&self, conn_id: usize, bytes: Bytes) -> Received {
+        if bytes.starts_with(&HELLO_MAGIC) {
+            self.receive_hello(conn_id, &bytes)
+        } else if bytes.starts_with(&COMPRESSED_MAGIC) {
+            match decompress(&bytes) {
+                Ok(msg) => Received::Msg(msg),
+                Err(error) => {
+                    debug!("Dropping a malformed compressed msg: {}", error);
+                    Received::Dropped
+                }
+            }
+        } else {
+            Received::Msg(bytes)
+        }
+    }
+
+    fn receive_hello(&self, conn_id: usize, bytes: &Bytes) -> Received {
+        if bytes.len() != HELLO_LEN {
+            debug!("Dropping a malformed hello from connection {}", conn_id);
+            return Received::Dropped;
+        }
+        let (kind, flags) = (bytes[HELLO_MAGIC.len()], bytes[HELLO_MAGIC.len() + 1]);
+        match kind {
+            OFFER => {
+                // None is accepted while our compression is disabled.
+                let codec = Codec::preferred(flags).filter(|_| self.enabled());
+                self.agree(conn_id, codec);
+                Received::Hello(Some(hello(ACCEPT, codec.map_or(0, Codec::flag))))
+            }
+            ACCEPT => {
+                self.agree(conn_id, Codec::preferred(flags));
+                Received::Hello(None)
+            }
+            _ => {
+                debug!(
+                    "Dropping a hello of unknown kind from connection {}",
+                    conn_id
+                );
+                Received::Dropped
+            }
+        }
+    }
+
+    fn agree(&self, conn_id: usize, codec: Option<Codec>) {
+        trace!("Compressing with {:?} over connection {}", codec, conn_id);
+        match codec {
+            Some(codec) => {
+                let _ = self.codecs.insert(conn_id, codec);
+            }
+            None => self.forget(conn_id),
+        }
+    }
+}
+
+fn hello(kind: u8, flags: u8) -> Bytes {
+    let mut hello = BytesMut::with_capacity(HELLO_LEN);
+    hello.put_slice(&HELLO_MAGIC);
+    hello.put_u8(kind);
+    hello.put_u8(flags);
+    hello.freeze()
+}
+
+fn decompress(bytes: &Bytes) -> Result<Bytes, &'static str> {
+    if bytes.len() < COMPRESSED_HEADER_LEN {
+        return Err("truncated header");
+    }
+    let codec = Codec::ALL
+        .into_iter()
+        .find(|codec| codec.flag() == bytes[COMPRESSED_MAGIC.len()])
+        .ok_or("unknown codec")?;
+    let mut be = [0; 8];
+    be.copy_from_slice(&bytes[COMPRESSED_MAGIC.len() + 1..COMPRESSED_HEADER_LEN]);
+    let len = u64::from_be_bytes(be) as usize;
+    if len > MAX_DECOMPRESSED_LEN {
+        return Err("msg too large");
+    }
+    codec
+        .decompress(&bytes[COMPRESSED_HEADER_LEN..], len)
+        .map(Bytes::from)
+        .ok_or("doesn't decompress to its length")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A msg of `len` bytes which compresses well.
+    fn compressible(len: usize) -> Bytes {
+        Bytes::from((0..len).map(|i| (i % 7) as u8).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn msgs_go_compressed_once_a_codec_is_agreed_on() {
+        let (dialer, acceptor) = (Compression::default(), Compression::default());
+        let (dialer_conn, acceptor_conn) = (1, 2);
+        let msg = compressible(64 * 1024);
+
+        // Nothing's compressed before the hellos are exchanged.
+        assert_eq!(dialer.compress(dialer_conn, msg.clone()), msg);
+
+        let offer = dialer.offer().expect("compression is enabled by default");
+        let accept = match acceptor.receive(acceptor_conn, offer) {
+            Received::Hello(Some(accept)) => accept,
+            other => panic!("offer not answered: {:?}", other),
+        };
+        assert_eq!(acceptor.codec(acceptor_conn), Some(Codec::Zstd));
+        assert_eq!(dialer.receive(dialer_conn, accept), Received::Hello(None));
+        assert_eq!(dialer.codec(dialer_conn), Some(Codec::Zstd));
+
+        let compressed = dialer.compress(dialer_conn, msg.clone());
+        assert!(compressed.len() < msg.len() / 10);
+        assert_eq!(
+            acceptor.receive(acceptor_conn, compressed),
+            Received::Msg(msg.clone())
+        );
+
+        // Small msgs, and those which don't get smaller, go as they are.
+        let small = compressible(1024);
+        assert_eq!(acceptor.compress(acceptor_conn, small.clone()), small);
+        let random = sn_interface::types::utils::random_bytes(64 * 1024);
+        assert_eq!(acceptor.compress(acceptor_conn, random.clone()), random);
+        assert_eq!(
+            dialer.receive(dialer_conn, random.clone()),
+            Received::Msg(random)
+        );
+
+        acceptor.forget(acceptor_conn);
+        assert_eq!(acceptor.compress(acceptor_conn, msg.clone()), msg);
+    }
+
+    #[test]
+    fn no_codec_is_accepted_with_compression_disabled() {
+        let (dialer, acceptor) = (Compression::default(), Compression::default());
+        acceptor.set_threshold(0);
+
+        let offer = dialer.offer().expect("compression is enabled by default");
+        let accept = match acceptor.receive(2, offer) {
+            Received::Hello(Some(accept)) => accept,
+            other => panic!("offer not answered: {:?}", other),
+        };
+        assert_eq!(dialer.receive(1, accept), Received::Hello(None));
+        assert_eq!(dialer.codec(1), None);
+        assert_eq!(acceptor.offer(), None);
+
+        // Only codecs offered are accepted.
+        let acceptor = Compression::default();
+        let _ = acceptor.receive(2, hello(OFFER, Codec::Lz4.flag()));
+        assert_eq!(acceptor.codec(2), Some(Codec::Lz4));
+    }
+
+    #[test]
+    fn malformed_compressed_msgs_are_dropped() {
+        let (sender, recipient) = (Compression::default(), Compression::default());
+        let _ = sender.receive(1, hello(ACCEPT, Codec::Lz4.flag()));
+        let msg = compressible(64 * 1024);
+        let compressed = sender.compress(1, msg.clone());
+        assert_eq!(recipient.receive(2, compressed.clone()), Received::Msg(msg));
+
+        let truncated = compressed.slice(..compressed.len() - 1);
+        assert_eq!(recipient.receive(2, truncated), Received::Dropped);
+
+        let mut too_large = compressed.to_vec();
+        too_large[COMPRESSED_MAGIC.len() + 1..COMPRESSED_HEADER_LEN]
+            .copy_from_slice(&(MAX_DECOMPRESSED_LEN as u64 + 1).to_be_bytes());
+        assert_eq!(
+            recipient.receive(2, Bytes::from(too_large)),
+            Received::Dropped
+        );
+        assert_eq!(
+            recipient.receive(2, Bytes::from_static(&HELLO_MAGIC)),
+            Received::Dropped
+        );
+    }
+}
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::MsgListener;
+use super::{stream::Frames, MsgListener};
 
 use sn_interface::types::{log_markers::LogMarker, Peer};
 
@@ -135,6 +135,7 @@ impl Link {
             queue_len,
             self.peer
         );
+        let msg = self.listener.compression().compress(conn.id(), msg);
         match conn.send_with(msg, priority, retry_config).await {
             Ok(()) => {
                 #[cfg(feature = "back-pressure")]
@@ -158,6 +159,37 @@ impl Link {
         }
     }
 
+    /// Streams a message too large to be sent whole to the peer, in frames sent one after the
+    /// other over the same connection, so that the peer can reassemble them.
+    pub(crate) async fn send_streamed(&self, msg: Bytes) -> Result<(), SendToOneError> {
+        let conn = self.get_or_connect().await?;
+        let frames = Frames::new(self.listener.compression().compress(conn.id(), msg));
+        trace!(
+            "Streaming a msg in {} frames to node {:?}.",
+            frames.frame_count(),
+            self.peer
+        );
+        for frame in frames {
+            if let Err(error) = conn.send_with(frame, 0, None).await {
+                // as with msgs sent whole, the failing connection is dropped at once
+                let id = &conn.id();
+                {
+                    let _ = self.connections.write().await.remove(id);
+                }
+                {
+                    let _ = self.queue.write().await.remove(id);
+                }
+                conn.close(Some(format!("{:?}", error)));
+                return Err(SendToOneError::Send(error));
+            }
+        }
+
+        #[cfg(feature = "back-pressure")]
+        self.listener.count_msg().await;
+
+        Ok(())
+    }
+
     async fn get_or_connect(&self) -> Result<qp2p::Connection, SendToOneError> {
         // get the most recently used connection
         let res = { self.queue.read().await.peek_max().map(|(id, _prio)| *id) };
@@ -222,6 +254,16 @@ impl Link {
 
         self.listener.listen(conn.clone(), incoming_msgs);
 
+        // Offers the peer to compress msgs over the connection, which it accepts in its reply.
+        if let Some(offer) = self.listener.compression().offer() {
+            if let Err(error) = conn.send(offer).await {
+                debug!(
+                    "Failed to offer compression to {:?}: {:?}",
+                    self.peer, error
+                );
+            }
+        }
+
         Ok(conn)
     }
 
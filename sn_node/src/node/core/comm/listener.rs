@@ -6,12 +6,22 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::admission::{Admission, ConnPermit};
+use super::bandwidth::Bandwidth;
+use super::compression::{Compression, Received};
+use super::msg_class::MsgClass;
+use super::msg_queue::MsgQueue;
+#[cfg(feature = "back-pressure")]
+use super::msg_queue::QueueDelay;
+use super::reputation::{Misbehaviour, Reputation, Verdict};
+use super::stream::{Reassembly, ReassemblyBudget};
 use super::MsgEvent;
 
-use sn_interface::messaging::WireMsg;
+use sn_interface::messaging::{Error as MessagingError, WireMsg};
 use sn_interface::types::{log_markers::LogMarker, Peer};
 
 use qp2p::ConnectionIncoming;
+use std::net::SocketAddr;
 use tokio::sync::mpsc;
 use tokio::task;
 use tracing::Instrument;
@@ -22,56 +32,184 @@ pub(crate) enum ListenerEvent {
         peer: Peer,
         connection: qp2p::Connection,
     },
+    /// The peer at `addr` misbehaved too often, and is to be disconnected from.
+    Misbehaved { addr: SocketAddr },
 }
 
 #[derive(Clone)]
 pub(crate) struct MsgListener {
     add_connection: mpsc::Sender<ListenerEvent>,
-    receive_msg: mpsc::Sender<MsgEvent>,
-    count_msg: mpsc::Sender<()>,
+    queue: MsgQueue,
+    count_msg: mpsc::Sender<MsgClass>,
+    bandwidth: Bandwidth,
+    compression: Compression,
+    reputation: Reputation,
+    reassembly_budget: ReassemblyBudget,
 }
 
 impl MsgListener {
     pub(crate) fn new(
         add_connection: mpsc::Sender<ListenerEvent>,
-        receive_msg: mpsc::Sender<MsgEvent>,
-        count_msg: mpsc::Sender<()>,
+        queue: MsgQueue,
+        count_msg: mpsc::Sender<MsgClass>,
+        bandwidth: Bandwidth,
+        compression: Compression,
+        reputation: Reputation,
     ) -> Self {
         Self {
             add_connection,
             count_msg,
-            receive_msg,
+            queue,
+            bandwidth,
+            compression,
+            reputation,
+            reassembly_budget: ReassemblyBudget::default(),
+        }
+    }
+
+    /// Compression of the msgs sent over the connections we listen on.
+    pub(crate) fn compression(&self) -> &Compression {
+        &self.compression
+    }
+
+    /// Whether the peer at `addr` is banned, its connections then being refused.
+    pub(crate) fn is_banned(&self, addr: &SocketAddr) -> bool {
+        self.reputation.is_banned(addr)
+    }
+
+    /// How long the msgs we listen for waited to be handed over lately.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn queue_delay(&self) -> QueueDelay {
+        self.queue.delay()
+    }
+
+    /// A listener handing over connections and msgs just like this one, but counting msgs on
+    /// `count_msg`.
+    pub(crate) fn counting_into(&self, count_msg: mpsc::Sender<MsgClass>) -> Self {
+        Self {
+            count_msg,
+            ..self.clone()
         }
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) fn listen(&self, conn: qp2p::Connection, incoming_msgs: ConnectionIncoming) {
         let clone = self.clone();
-        let _ = task::spawn(clone.listen_internal(conn, incoming_msgs).in_current_span());
+        let _ = task::spawn(
+            clone
+                .listen_internal(conn, incoming_msgs, None)
+                .in_current_span(),
+        );
     }
 
+    /// Listens on a connection a peer opened to one of our listeners, holding its place in
+    /// that listener's budget for as long as it's open.
     #[tracing::instrument(skip_all)]
-    async fn listen_internal(self, conn: qp2p::Connection, mut incoming_msgs: ConnectionIncoming) {
+    pub(crate) fn listen_incoming(
+        &self,
+        conn: qp2p::Connection,
+        incoming_msgs: ConnectionIncoming,
+        admission: Admission,
+        permit: ConnPermit,
+    ) {
+        let clone = self.clone();
+        let _ = task::spawn(
+            clone
+                .listen_internal(conn, incoming_msgs, Some((admission, permit)))
+                .in_current_span(),
+        );
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn listen_internal(
+        self,
+        conn: qp2p::Connection,
+        mut incoming_msgs: ConnectionIncoming,
+        gate: Option<(Admission, ConnPermit)>,
+    ) {
         let conn_id = conn.id();
         let remote_address = conn.remote_address();
         let mut first = true;
+        // Where the peer is sent on to, if this is the listener for the other kind of peers.
+        let mut redirect_to = None;
+        // Msgs streamed to us over this connection, being reassembled.
+        let mut reassembly = Reassembly::new(self.reassembly_budget.clone());
 
-        while let Some(result) = incoming_msgs.next().await.transpose() {
+        loop {
+            // Msgs being reassembled are dropped once idle, even if nothing else comes in.
+            let next = match reassembly.stale_at() {
+                Some(stale_at) => {
+                    match tokio::time::timeout_at(stale_at, incoming_msgs.next()).await {
+                        Ok(next) => next,
+                        Err(_elapsed) => {
+                            reassembly.drop_stale();
+                            continue;
+                        }
+                    }
+                }
+                None => incoming_msgs.next().await,
+            };
+            let result = match next.transpose() {
+                Some(result) => result,
+                None => break,
+            };
             match result {
                 Ok(msg_bytes) => {
+                    let msg_bytes = match reassembly.receive(msg_bytes) {
+                        Some(msg_bytes) => msg_bytes,
+                        None => continue,
+                    };
+                    // What went over the wire, as accounted for against the peer's cap.
+                    let wire_len = msg_bytes.len();
+                    let msg_bytes = match self.compression.receive(conn_id, msg_bytes) {
+                        Received::Msg(msg_bytes) => msg_bytes,
+                        Received::Hello(reply) => {
+                            if let Some(reply) = reply {
+                                if let Err(error) = conn.send(reply).await {
+                                    debug!(
+                                        "Failed to answer hello from {}: {:?}",
+                                        remote_address, error
+                                    );
+                                }
+                            }
+                            continue;
+                        }
+                        Received::Dropped => continue,
+                    };
                     let wire_msg = match WireMsg::from(msg_bytes.clone()) {
                         Ok(wire_msg) => wire_msg,
+                        Err(error @ MessagingError::IncompatibleVersion { .. }) => {
+                            warn!("Dropping message from {}: {}", remote_address, error);
+                            continue;
+                        }
                         Err(error) => {
-                            // TODO: should perhaps rather drop this connection.. as it is a spam vector
                             debug!("Failed to deserialize message: {:?}", error);
-                            continue;
+                            // the peer's name is in the msg we couldn't read
+                            match self.reputation.record(
+                                remote_address,
+                                None,
+                                Misbehaviour::MalformedMsg,
+                            ) {
+                                Verdict::Tolerated => continue,
+                                _ => {
+                                    self.drop_misbehaving(&conn, "Too many malformed msgs")
+                                        .await;
+                                    break;
+                                }
+                            }
                         }
                     };
 
                     let src_name = wire_msg.msg_kind().src().name();
+                    let class = MsgClass::of(&wire_msg);
+                    // Held off reading the peer's next msg while it's over its cap.
+                    let hold_off = self.bandwidth.received(src_name, wire_len);
 
                     if first {
                         first = false;
+                        if let Some((admission, _)) = &gate {
+                            redirect_to = admission.redirect_for(wire_msg.msg_kind());
+                        }
                         let _ = self
                             .add_connection
                             .send(ListenerEvent::Connected {
@@ -81,17 +219,54 @@ impl MsgListener {
                             .await;
                     }
 
-                    let _send_res = self
-                        .receive_msg
-                        .send(MsgEvent::Received {
-                            sender: Peer::new(src_name, remote_address),
-                            wire_msg,
-                            original_bytes: msg_bytes,
-                        })
+                    if let Some(redirect_to) = redirect_to {
+                        self.queue
+                            .push(
+                                class,
+                                MsgEvent::Misdirected {
+                                    sender: Peer::new(src_name, remote_address),
+                                    wire_msg,
+                                    original_bytes: msg_bytes,
+                                    redirect_to,
+                                },
+                            )
+                            .await;
+                        continue;
+                    }
+
+                    self.queue
+                        .push(
+                            class,
+                            MsgEvent::Received {
+                                sender: Peer::new(src_name, remote_address),
+                                wire_msg,
+                                original_bytes: msg_bytes,
+                            },
+                        )
                         .await;
 
                     // count incoming msgs..
-                    let _ = self.count_msg.send(());
+                    if let Err(err) = self.count_msg.try_send(class) {
+                        debug!("Error when trying to count incoming msg..! {}", err);
+                    }
+                    #[cfg(feature = "back-pressure")]
+                    if class.is_from_nodes()
+                        && self.reputation.count_node_msg(remote_address, src_name)
+                            != Verdict::Tolerated
+                    {
+                        self.drop_misbehaving(&conn, "Over the msgs per s told")
+                            .await;
+                        break;
+                    }
+
+                    if !hold_off.is_zero() {
+                        trace!(
+                            "{} over its bandwidth cap, reading its next msg in {:?}",
+                            remote_address,
+                            hold_off
+                        );
+                        tokio::time::sleep(hold_off).await;
+                    }
                 }
                 Err(error) => {
                     // TODO: should we propagate this?
@@ -101,12 +276,27 @@ impl MsgListener {
         }
 
         trace!(%conn_id, %remote_address, "{}", LogMarker::ConnectionClosed);
+        self.compression.forget(conn_id);
+        // gives back the connection's place in the listener's budget
+        drop(gate);
+    }
+
+    // Closes the connection of a peer which misbehaved too often, and has us disconnect from
+    // it.
+    async fn drop_misbehaving(&self, conn: &qp2p::Connection, reason: &str) {
+        conn.close(Some(reason.to_string()));
+        let _ = self
+            .add_connection
+            .send(ListenerEvent::Misbehaved {
+                addr: conn.remote_address(),
+            })
+            .await;
     }
 
-    // count outgoing msgs
+    // count outgoing msgs, as part of what we do for other nodes
     #[cfg(feature = "back-pressure")]
     pub(crate) async fn count_msg(&self) {
-        if let Err(err) = self.count_msg.send(()).await {
+        if let Err(err) = self.count_msg.send(MsgClass::NodeSystem).await {
             // this is really a problem as we rely on this counting, make sure this doesn't normally error!
             debug!("Error when trying to count outgoing msg..! {}", err);
         }
@@ -6,19 +6,60 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod admission;
 #[cfg(feature = "back-pressure")]
 mod back_pressure;
-
+mod bandwidth;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod compression;
 mod link;
 mod listener;
+mod msg_audit;
+mod msg_class;
+mod msg_queue;
+#[cfg(test)]
+mod partitions;
 mod peer_session;
+mod peer_sessions;
+mod reputation;
+mod stream;
 
 #[cfg(feature = "back-pressure")]
 use self::back_pressure::BackPressure;
-
+#[cfg(feature = "back-pressure")]
+pub use self::back_pressure::BackPressureStrategy;
+#[cfg(all(feature = "back-pressure", feature = "metrics"))]
+use self::back_pressure::ResourceUsage;
+#[cfg(feature = "back-pressure")]
+pub(crate) use self::back_pressure::DEFAULT_BACK_PRESSURE_REPORT_INTERVAL;
+
+use self::bandwidth::Bandwidth;
+pub(crate) use self::bandwidth::{BandwidthCaps, PeerBytes};
+#[cfg(feature = "chaos")]
+use self::chaos::MsgChaos;
+use self::compression::Compression;
+pub(crate) use self::compression::DEFAULT_COMPRESSION_THRESHOLD_KB;
+
+use self::admission::Admission;
+pub(crate) use self::admission::{
+    ConnLimits, ListenerKind, DEFAULT_CLIENT_CONNS_PER_S, DEFAULT_MAX_CLIENT_CONNS,
+    DEFAULT_MAX_NODE_CONNS, DEFAULT_NODE_CONNS_PER_S,
+};
 use self::link::Link;
 use self::listener::{ListenerEvent, MsgListener};
+use self::msg_audit::MsgAudit;
+pub(crate) use self::msg_audit::{Direction, Disposition, DEFAULT_MSG_AUDIT_CAPACITY};
+pub(crate) use self::msg_class::MsgClass;
+use self::msg_queue::MsgQueue;
+#[cfg(test)]
+pub(crate) use self::partitions::{Fault, Partitions};
 use self::peer_session::{PeerSession, SendWatcher};
+use self::peer_sessions::PeerSessions;
+pub(crate) use self::reputation::Misbehaviour;
+use self::reputation::{PeerReputation, Reputation, Verdict};
+use self::stream::StreamThreshold;
+pub(crate) use self::stream::DEFAULT_STREAM_THRESHOLD_KB;
 
 use crate::node::core::comm::peer_session::SendStatus;
 use crate::node::error::{Error, Result};
@@ -29,20 +70,45 @@ use bytes::Bytes;
 use futures::stream::{FuturesUnordered, StreamExt};
 use qp2p::{Endpoint, IncomingConnections};
 use std::time::Duration;
-use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
-use tokio::{
-    sync::{mpsc, RwLock},
-    task,
-};
+use std::{collections::BTreeSet, net::SocketAddr, sync::Arc};
+use tokio::{sync::mpsc, task};
+#[cfg(feature = "metrics")]
+use xor_name::XorName;
 
 // Communication component of the node to interact with other nodes.
 #[derive(Clone)]
 pub(crate) struct Comm {
     our_endpoint: Endpoint,
     msg_listener: MsgListener,
+    // Admission to the listener nodes connect to, and clients too unless they have their own.
+    admission: Admission,
+    clients: Option<ClientListener>,
+    #[cfg(feature = "back-pressure")]
+    back_pressure: BackPressure,
+    sessions: Arc<PeerSessions>,
+    stream_threshold: StreamThreshold,
+    // Codecs agreed on for our connections, and the size of the msgs compressed with them
+    compression: Compression,
+    // Bytes exchanged with each peer, and the caps on them
+    bandwidth: Bandwidth,
+    // Scores of the peers which misbehaved
+    reputation: Reputation,
+    // The last msgs received and sent, and what became of them
+    msg_audit: MsgAudit,
+    #[cfg(feature = "chaos")]
+    chaos: MsgChaos,
+    #[cfg(test)]
+    partitions: Partitions,
+}
+
+// A listener for clients only, on a socket of its own.
+#[derive(Clone)]
+struct ClientListener {
+    endpoint: Endpoint,
+    admission: Admission,
+    // Clients' msgs are accounted for apart from the nodes', which our back pressure is about.
     #[cfg(feature = "back-pressure")]
     back_pressure: BackPressure,
-    sessions: Arc<RwLock<BTreeMap<Peer, PeerSession>>>,
 }
 
 impl Comm {
@@ -62,6 +128,93 @@ impl Comm {
         Ok(comm)
     }
 
+    /// Has clients connect to a listener of their own, bound to `local_addr`, with its own
+    /// budget of connections and accounting of msgs. Clients reaching the listener for nodes,
+    /// and nodes reaching this one, are sent on to the right one.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn with_client_listener(
+        mut self,
+        local_addr: SocketAddr,
+        config: qp2p::Config,
+    ) -> Result<Self> {
+        // Any external port configured is the one nodes reach us on.
+        let config = qp2p::Config {
+            external_port: None,
+            ..config
+        };
+        let (endpoint, incoming_connections, _) =
+            Endpoint::new_peer(local_addr, Default::default(), config).await?;
+
+        let clients_addr = endpoint.public_addr();
+        let nodes_addr = self.our_connection_info();
+        info!("Listening for clients on {clients_addr}, apart from nodes on {nodes_addr}");
+
+        self.admission.serve_only(ListenerKind::Nodes, clients_addr);
+        let admission = Admission::new(Some(ListenerKind::Clients), ConnLimits::clients());
+        admission.serve_only(ListenerKind::Clients, nodes_addr);
+
+        #[cfg(feature = "back-pressure")]
+        let back_pressure = BackPressure::new(self.msg_listener.queue_delay());
+        let msg_listener = self.msg_listener.counting_into(spawn_msg_counting(
+            #[cfg(feature = "back-pressure")]
+            back_pressure.clone(),
+        ));
+
+        listen(msg_listener, admission.clone(), incoming_connections);
+
+        self.clients = Some(ClientListener {
+            endpoint,
+            admission,
+            #[cfg(feature = "back-pressure")]
+            back_pressure,
+        });
+
+        Ok(self)
+    }
+
+    /// Sets the limits on the connections each of our listeners takes. Where clients share the
+    /// listener for nodes, the limits for nodes cover them both.
+    pub(crate) fn set_listener_limits(&self, nodes: ConnLimits, clients: ConnLimits) {
+        self.admission.set_limits(nodes);
+        if let Some(listener) = &self.clients {
+            listener.admission.set_limits(clients);
+        }
+    }
+
+    /// Sets the size of the msgs above which they're streamed to peers in frames rather than
+    /// sent whole, 0 disabling streaming.
+    pub(crate) fn set_stream_threshold(&self, bytes: usize) {
+        self.stream_threshold.set(bytes);
+    }
+
+    /// Sets the size of the msgs above which they're compressed over the connections a codec
+    /// was agreed on for, 0 disabling compression, which peers are then told when they connect.
+    pub(crate) fn set_compression_threshold(&self, bytes: usize) {
+        self.compression.set_threshold(bytes);
+    }
+
+    /// Caps the bytes per second exchanged with any one peer, either way.
+    pub(crate) fn set_bandwidth_caps(&self, caps: BandwidthCaps) {
+        self.bandwidth.set_caps(caps);
+    }
+
+    /// The last msgs received and sent, and what became of them.
+    pub(crate) fn msg_audit(&self) -> &MsgAudit {
+        &self.msg_audit
+    }
+
+    /// Bytes received and sent in all, since we started.
+    pub(crate) fn bandwidth_totals(&self) -> PeerBytes {
+        self.bandwidth.totals()
+    }
+
+    /// Bytes received from and sent to each peer, since we started or last had a session with
+    /// it.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn peer_bandwidth(&self) -> Vec<(XorName, PeerBytes)> {
+        self.bandwidth.per_peer()
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) async fn bootstrap(
         local_addr: SocketAddr,
@@ -73,9 +226,17 @@ impl Comm {
         let (our_endpoint, incoming_connections, bootstrap_node) =
             Endpoint::new_peer(local_addr, bootstrap_nodes, config).await?;
 
+        let (connection, incoming_msgs) = match bootstrap_node {
+            Some(bootstrap_node) => bootstrap_node,
+            None => {
+                // Freeing our address for another attempt, with other contacts.
+                our_endpoint.close();
+                return Err(Error::BootstrapFailed);
+            }
+        };
+
         let (comm, msg_listener) = setup_comms(our_endpoint, incoming_connections, receive_msg);
 
-        let (connection, incoming_msgs) = bootstrap_node.ok_or(Error::BootstrapFailed)?;
         let remote_address = connection.remote_address();
 
         msg_listener.listen(connection, incoming_msgs);
@@ -87,39 +248,123 @@ impl Comm {
         self.our_endpoint.public_addr()
     }
 
-    pub(crate) async fn cleanup_peers(&self) {
-        let sessions = self.sessions.read().await;
-        let mut peers_to_cleanup = vec![];
-        for (peer, session) in sessions.iter() {
-            session.remove_expired().await;
-            let is_connected = session.is_connected().await;
-
-            if !is_connected {
-                peers_to_cleanup.push(*peer);
+    /// The address clients connect to us on, which is the one for nodes unless clients have a
+    /// listener of their own.
+    pub(crate) fn our_client_connection_info(&self) -> SocketAddr {
+        self.clients
+            .as_ref()
+            .map(|listener| listener.endpoint.public_addr())
+            .unwrap_or_else(|| self.our_connection_info())
+    }
+
+    /// The connections open on each of our listeners, by the kind of peers it's for (`None` when
+    /// it takes both).
+    pub(crate) fn open_conns(&self) -> Vec<(Option<ListenerKind>, usize)> {
+        self.admissions()
+            .map(|admission| (admission.serves(), admission.open_conns()))
+            .collect()
+    }
+
+    /// The connections refused so far by each of our listeners, by the kind of peers it's for.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn refused_conns(&self) -> Vec<(Option<ListenerKind>, u64)> {
+        self.admissions()
+            .map(|admission| (admission.serves(), admission.refused_conns()))
+            .collect()
+    }
+
+    /// Records `peer` misbehaving, disconnecting from it if it did so too often lately.
+    pub(crate) async fn report_misbehaviour(&self, peer: &Peer, misbehaviour: Misbehaviour) {
+        let addr = peer.addr();
+        match self
+            .reputation
+            .record(addr, Some(peer.name()), misbehaviour)
+        {
+            Verdict::Tolerated => {}
+            verdict => {
+                let removed = self.sessions.remove_addr(&addr).await;
+                warn!(
+                    "{:?} misbehaved too often ({}), {:?}: closed {} session(s) with it",
+                    peer, misbehaviour, verdict, removed
+                );
             }
         }
+    }
+
+    /// The standing of every peer which misbehaved lately, worst first.
+    pub(crate) fn peer_reputations(&self) -> Vec<PeerReputation> {
+        self.reputation.scores()
+    }
+
+    /// The peers we have a connection open with.
+    pub(crate) async fn connected_peers(&self) -> Vec<Peer> {
+        let mut peers = vec![];
+        for (peer, session) in self.sessions.all().await {
+            if session.is_connected().await {
+                peers.push(peer);
+            }
+        }
+        peers
+    }
+
+    fn admissions(&self) -> impl Iterator<Item = &Admission> {
+        std::iter::once(&self.admission)
+            .chain(self.clients.as_ref().map(|listener| &listener.admission))
+    }
+
+    /// Has `rate` of the msgs we send to `peer`, or to all peers without a rate of their own if
+    /// `None`, dropped on purpose.
+    #[cfg(feature = "chaos")]
+    pub(crate) fn set_msg_drop_rate(&self, peer: Option<SocketAddr>, rate: f64) {
+        self.chaos.set_drop_rate(peer, rate);
+    }
+
+    /// Number of msgs dropped on purpose so far.
+    #[cfg(feature = "chaos")]
+    pub(crate) fn msgs_dropped_by_chaos(&self) -> u64 {
+        self.chaos.dropped()
+    }
+
+    /// Stops dropping msgs on purpose.
+    #[cfg(feature = "chaos")]
+    pub(crate) fn heal_msg_chaos(&self) {
+        self.chaos.heal();
+    }
 
-        // explicitly drop the read here.
-        drop(sessions);
+    /// Has the msgs we send go through the artificial `partitions`, shared with the other
+    /// in-process nodes.
+    #[cfg(test)]
+    pub(crate) fn with_partitions(mut self, partitions: Partitions) -> Self {
+        self.partitions = partitions;
+        self
+    }
 
+    /// The fault of the artificial partitions a msg we send to `recipient` gets, if any.
+    #[cfg(test)]
+    pub(crate) fn fault_towards(&self, recipient: &Peer) -> Option<Fault> {
+        self.partitions
+            .fault(&self.our_connection_info(), &recipient.addr())
+    }
+
+    pub(crate) async fn cleanup_peers(&self) {
         // cleanup any and all conns that are not connected
         // TODO: check if we need to remove client conns manually, or if we can assume they're disconnected...
         // Perhaps above a threshold we cleanup non-section conns?
-        if !peers_to_cleanup.is_empty() {
-            for peer in peers_to_cleanup {
-                let mut sessions_write_guard = self.sessions.write().await;
-                let perhaps_peer = sessions_write_guard.remove(&peer);
-                drop(sessions_write_guard);
-
-                if let Some(session) = perhaps_peer {
-                    session.disconnect().await
-                };
-            }
-        }
+        let evicted = self.sessions.evict_disconnected().await;
+        let remaining: BTreeSet<_> = self
+            .sessions
+            .all()
+            .await
+            .into_iter()
+            .map(|(peer, _)| peer.name())
+            .collect();
+        self.bandwidth.retain(|name| remaining.contains(name));
+        self.reputation.forget_expired();
 
         debug!(
-            "PeerLink count post-cleanup: ${:?}",
-            self.sessions.read().await.len()
+            "PeerLink count post-cleanup: ${:?} ({} evicted)",
+            self.sessions.len().await,
+            evicted
         );
     }
 
@@ -157,8 +402,82 @@ impl Comm {
     #[cfg(feature = "back-pressure")]
     /// Returns our caller-specific tolerated msgs per s, if the value has changed significantly.
     pub(crate) async fn tolerated_msgs_per_s(&self) -> Option<f64> {
-        let sessions = self.sessions.read().await.len();
-        self.back_pressure.tolerated_msgs_per_s(sessions).await
+        let sessions = self.sessions.len().await;
+        let report = self.back_pressure.tolerated_msgs_per_s(sessions).await;
+        if let Some(msgs_per_s) = report {
+            self.reputation.set_tolerated_msgs_per_s(msgs_per_s);
+        }
+        report
+    }
+
+    #[cfg(feature = "back-pressure")]
+    /// Has the msgs per s peers are told they can send us decided with `strategy`, and told
+    /// every `interval` if it changed significantly.
+    pub(crate) async fn set_back_pressure_strategy(
+        &self,
+        strategy: BackPressureStrategy,
+        interval: Duration,
+    ) {
+        self.back_pressure.set_strategy(strategy, interval).await;
+    }
+
+    #[cfg(feature = "back-pressure")]
+    /// Returns how often peers are told the msgs per s they can send us.
+    pub(crate) async fn back_pressure_report_interval(&self) -> Duration {
+        self.back_pressure.report_interval().await
+    }
+
+    #[cfg(all(feature = "back-pressure", feature = "metrics"))]
+    /// Returns our current load, as msgs per s, without affecting what's reported to peers.
+    pub(crate) async fn load_msgs_per_s(&self) -> f64 {
+        self.back_pressure.msgs_per_s().await
+    }
+
+    #[cfg(all(feature = "back-pressure", feature = "metrics"))]
+    /// Returns what our process used of the system's resources, when last sampled: its CPU and
+    /// memory, as shares of the system's, and the I/Os in flight on the busiest disk.
+    pub(crate) async fn resource_usage(&self) -> (f64, f64, Option<f64>) {
+        let ResourceUsage {
+            cpu,
+            memory,
+            disk_queue_depth,
+        } = self.back_pressure.resource_usage().await;
+        (cpu, memory, disk_queue_depth)
+    }
+
+    #[cfg(all(feature = "back-pressure", feature = "metrics"))]
+    /// Returns the msgs per s each class of msgs is tolerated at, out of all we can take.
+    pub(crate) async fn tolerated_msgs_per_s_by_class(&self) -> Vec<(MsgClass, f64)> {
+        self.back_pressure
+            .tolerated_msgs_per_s_by_class()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[cfg(all(feature = "back-pressure", feature = "metrics"))]
+    /// Returns the load from clients, as msgs per s, if they have a listener of their own.
+    pub(crate) async fn client_load_msgs_per_s(&self) -> Option<f64> {
+        match &self.clients {
+            Some(listener) => Some(listener.back_pressure.msgs_per_s().await),
+            None => None,
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    /// Returns the send throughput, in msgs per s, and send success ratio of each peer we have a
+    /// session with.
+    pub(crate) async fn peer_send_stats(&self) -> Vec<(Peer, f64, f64)> {
+        let sessions = self.sessions.all().await;
+        let mut stats = Vec::with_capacity(sessions.len());
+        for (peer, session) in sessions {
+            stats.push((
+                peer,
+                session.throughput().await,
+                session.success_ratio().await,
+            ));
+        }
+        stats
     }
 
     #[cfg(feature = "back-pressure")]
@@ -170,6 +489,7 @@ impl Comm {
     }
 
     /// Sends a message to a client. Reuses an existing or creates a connection if none.
+    #[tracing::instrument(skip(self, wire_msg), fields(msg_id = ?wire_msg.msg_id()))]
     pub(crate) async fn send_to_client(
         &self,
         recipient: &Peer,
@@ -189,10 +509,12 @@ impl Comm {
         // TODO: rework priority so this we dont need to deserialise payload to determine priority.
         let priority = wire_msg.into_msg()?.priority();
 
+        let size = bytes.len();
         let (_, result) = self
             .send_to_one(*recipient, wire_msg.msg_id(), priority, bytes)
             .await;
 
+        let sent = async {
         match result {
             Err(error) => {
                 // there is only one type of error returned: [`Error::InvalidState`]
@@ -275,6 +597,22 @@ impl Comm {
                 }
             }
         }
+        }
+        .await;
+
+        let disposition = if sent.is_ok() {
+            Disposition::Sent
+        } else {
+            Disposition::SendFailed
+        };
+        self.msg_audit.record(
+            Direction::Outbound,
+            &wire_msg,
+            *recipient,
+            size,
+            disposition,
+        );
+        sent
     }
 
     /// Sends a message to multiple recipients. Attempts to send to `delivery_group_size`
@@ -286,7 +624,10 @@ impl Comm {
     /// `DeliveryStatus::MinDeliveryGroupSizeReached` or `DeliveryStatus::MinDeliveryGroupSizeFailed` depending
     /// on if the minimum delivery group size is met or not. The failed recipients are sent along
     /// with the status. It returns a `DeliveryStatus::AllRecipients` if message is sent to all the recipients.
-    #[tracing::instrument(skip(self))]
+    #[tracing::instrument(
+        skip(self, wire_msg),
+        fields(msg_id = ?wire_msg.msg_id(), dst = ?wire_msg.dst_location())
+    )]
     pub(crate) async fn send(
         &self,
         recipients: &[Peer],
@@ -334,6 +675,13 @@ impl Comm {
 
         let mut try_next = |error, recipient, tasks: &mut FuturesUnordered<_>| {
             warn!("during sending, received error {:?}", error);
+            self.msg_audit.record(
+                Direction::Outbound,
+                &wire_msg,
+                recipient,
+                msg_bytes.len(),
+                Disposition::SendFailed,
+            );
             failed_recipients.push(recipient);
 
             if next < recipients.len() {
@@ -362,6 +710,13 @@ impl Comm {
                     loop {
                         match watcher.await_change().await {
                             SendStatus::Sent => {
+                                self.msg_audit.record(
+                                    Direction::Outbound,
+                                    &wire_msg,
+                                    recipient,
+                                    msg_bytes.len(),
+                                    Disposition::Sent,
+                                );
                                 successes += 1;
                                 break; // we now move to checking next recipient send task..
                             }
@@ -433,54 +788,20 @@ impl Comm {
         }
     }
 
-    async fn get(&self, id: &Peer) -> Option<PeerSession> {
-        let sessions = self.sessions.read().await;
-        sessions.get(id).cloned()
-    }
-
     async fn get_or_create(&self, peer: &Peer) -> PeerSession {
-        if let Some(session) = self.get(peer).await {
-            return session;
-        }
-
-        // if peer is not in list, the entire list needs to be locked
-        // i.e. first comms to any node, will impact all sending at that instant..
-        // however, first comms should be a minor part of total time spent using link,
-        // so that is ok
-        let mut sessions = self.sessions.write().await;
-        let res = match sessions.get(peer).cloned() {
-            // someone else inserted in the meanwhile, so use that
-            Some(session) => session,
-            // still not in list, go ahead and create + insert
-            None => {
+        self.sessions
+            .get_or_insert_with(peer, || {
                 let link = Link::new(*peer, self.our_endpoint.clone(), self.msg_listener.clone());
-                let session = PeerSession::new(link);
-                let _ = sessions.insert(*peer, session.clone());
-                session
-            }
-        };
-
-        res
+                PeerSession::new(link, self.stream_threshold.clone())
+            })
+            .await
     }
 
     /// Any number of incoming qp2p:Connections can be added.
     /// We will eventually converge to the same one in our comms with the peer.
     async fn add_incoming(&self, peer: &Peer, conn: qp2p::Connection) {
-        {
-            let session = self.sessions.read().await;
-            if let Some(c) = session.get(peer) {
-                // peer exists, add to it
-                c.add(conn).await;
-                return;
-            }
-            // else still not in list, go ahead and insert
-        }
-        let mut sessions = self.sessions.write().await;
-        match sessions.get(peer) {
-            // someone else inserted in the meanwhile, add to it
-            Some(c) => c.add(conn).await,
-            // still not in list, go ahead and insert
-            None => {
+        self.sessions
+            .add_connection(peer, conn, |conn| async {
                 let link = Link::new_with(
                     *peer,
                     self.our_endpoint.clone(),
@@ -488,13 +809,13 @@ impl Comm {
                     conn,
                 )
                 .await;
-                let session = PeerSession::new(link);
-                let _ = sessions.insert(*peer, session);
-            }
-        }
+                PeerSession::new(link, self.stream_threshold.clone())
+            })
+            .await
     }
 
     // Helper to send a message to a single recipient.
+    #[tracing::instrument(skip(self, msg_bytes), fields(bytes = msg_bytes.len()))]
     async fn send_to_one(
         &self,
         recipient: Peer,
@@ -509,7 +830,73 @@ impl Comm {
             recipient,
         );
 
+        #[cfg(test)]
+        let fault = self.fault_towards(&recipient);
+        #[cfg(test)]
+        match fault {
+            Some(Fault::Drop) => {
+                trace!(
+                    "Partitioned from {:?}, dropping msg {:?}",
+                    recipient,
+                    msg_id
+                );
+                let watcher = SendWatcher::settled(SendStatus::MaxRetriesReached(0));
+                return (recipient, Ok(watcher));
+            }
+            Some(Fault::Delay(delay)) => {
+                trace!(
+                    "Partitioned from {:?}, delaying msg {:?}",
+                    recipient,
+                    msg_id
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Some(Fault::Duplicate) | Some(Fault::Corrupt) | None => {}
+        }
+        #[cfg(test)]
+        let msg_bytes = if fault == Some(Fault::Corrupt) {
+            trace!(
+                "Partitioned from {:?}, corrupting msg {:?}",
+                recipient,
+                msg_id
+            );
+            let mut corrupted = msg_bytes.to_vec();
+            if let Some(last) = corrupted.last_mut() {
+                *last ^= 1;
+            }
+            Bytes::from(corrupted)
+        } else {
+            msg_bytes
+        };
+
+        #[cfg(feature = "chaos")]
+        if self.chaos.drops(&recipient.addr()) {
+            trace!("Chaos: dropping msg {:?} to {:?}", msg_id, recipient);
+            let watcher = SendWatcher::settled(SendStatus::MaxRetriesReached(0));
+            return (recipient, Ok(watcher));
+        }
+
+        let hold_off = self.bandwidth.sent(recipient.name(), msg_bytes.len());
+        if !hold_off.is_zero() {
+            trace!(
+                "{:?} over its bandwidth cap, sending msg {:?} in {:?}",
+                recipient,
+                msg_id,
+                hold_off
+            );
+            tokio::time::sleep(hold_off).await;
+        }
+
         let peer = self.get_or_create(&recipient).await;
+        #[cfg(test)]
+        if fault == Some(Fault::Duplicate) {
+            trace!(
+                "Partitioned from {:?}, duplicating msg {:?}",
+                recipient,
+                msg_id
+            );
+            let _duplicate = peer.send(msg_id, msg_priority, msg_bytes.clone()).await;
+        }
         let result = peer.send(msg_id, msg_priority, msg_bytes).await;
 
         (recipient, result)
@@ -524,59 +911,112 @@ fn setup_comms(
 ) -> (Comm, MsgListener) {
     let (comm, msg_listener) = setup(our_endpoint, receive_msg);
 
-    listen(msg_listener.clone(), incoming_connections);
+    listen(
+        msg_listener.clone(),
+        comm.admission.clone(),
+        incoming_connections,
+    );
 
     (comm, msg_listener)
 }
 
 #[tracing::instrument(skip_all)]
 fn setup(our_endpoint: Endpoint, receive_msg: mpsc::Sender<MsgEvent>) -> (Comm, MsgListener) {
+    let queue = MsgQueue::new(receive_msg);
     #[cfg(feature = "back-pressure")]
-    let back_pressure = BackPressure::new();
+    let back_pressure = BackPressure::new(queue.delay());
 
     let (add_connection, conn_receiver) = mpsc::channel(100);
-    #[cfg(feature = "back-pressure")]
-    let (count_msg, msg_counter) = mpsc::channel(1000);
-    #[cfg(not(feature = "back-pressure"))]
-    let (count_msg, _msg_counter) = mpsc::channel(1000);
-
-    let msg_listener = MsgListener::new(add_connection, receive_msg, count_msg);
+    let count_msg = spawn_msg_counting(
+        #[cfg(feature = "back-pressure")]
+        back_pressure.clone(),
+    );
+
+    let bandwidth = Bandwidth::default();
+    let compression = Compression::default();
+    let reputation = Reputation::default();
+    let msg_listener = MsgListener::new(
+        add_connection,
+        queue,
+        count_msg,
+        bandwidth.clone(),
+        compression.clone(),
+        reputation.clone(),
+    );
 
     let comm = Comm {
         our_endpoint,
         msg_listener: msg_listener.clone(),
+        admission: Admission::new(None, ConnLimits::nodes()),
+        clients: None,
         #[cfg(feature = "back-pressure")]
         back_pressure: back_pressure.clone(),
-        sessions: Arc::new(RwLock::new(BTreeMap::new())),
+        sessions: Arc::new(PeerSessions::default()),
+        stream_threshold: StreamThreshold::default(),
+        compression,
+        bandwidth,
+        reputation,
+        msg_audit: MsgAudit::default(),
+        #[cfg(feature = "chaos")]
+        chaos: MsgChaos::default(),
+        #[cfg(test)]
+        partitions: Partitions::default(),
     };
 
-    #[cfg(feature = "back-pressure")]
-    let _ = task::spawn(count_msgs(back_pressure, msg_counter));
-
     let _ = task::spawn(receive_conns(comm.clone(), conn_receiver));
 
     (comm, msg_listener)
 }
 
+// Returns the sender msgs are counted on, by class, into the given back pressure.
+fn spawn_msg_counting(
+    #[cfg(feature = "back-pressure")] back_pressure: BackPressure,
+) -> mpsc::Sender<MsgClass> {
+    #[cfg(feature = "back-pressure")]
+    let (count_msg, msg_counter) = mpsc::channel(1000);
+    #[cfg(not(feature = "back-pressure"))]
+    let (count_msg, _msg_counter) = mpsc::channel(1000);
+
+    #[cfg(feature = "back-pressure")]
+    let _ = task::spawn(count_msgs(back_pressure, msg_counter));
+
+    count_msg
+}
+
 #[tracing::instrument(skip_all)]
 #[cfg(feature = "back-pressure")]
-async fn count_msgs(back_pressure: BackPressure, mut msg_counter: mpsc::Receiver<()>) {
+async fn count_msgs(back_pressure: BackPressure, mut msg_counter: mpsc::Receiver<MsgClass>) {
     debug!("Entered msg counting listener loop.");
-    while let Some(()) = msg_counter.recv().await {
-        back_pressure.count_msg();
+    while let Some(class) = msg_counter.recv().await {
+        back_pressure.count_msg(class);
     }
     debug!("Exited msg counting listener loop..!");
 }
 
 #[tracing::instrument(skip_all)]
 async fn receive_conns(comm: Comm, mut conn_receiver: mpsc::Receiver<ListenerEvent>) {
-    while let Some(ListenerEvent::Connected { peer, connection }) = conn_receiver.recv().await {
-        comm.add_incoming(&peer, connection).await;
+    while let Some(event) = conn_receiver.recv().await {
+        match event {
+            ListenerEvent::Connected { peer, connection } => {
+                comm.add_incoming(&peer, connection).await;
+            }
+            ListenerEvent::Misbehaved { addr } => {
+                let removed = comm.sessions.remove_addr(&addr).await;
+                warn!(
+                    "{} misbehaved too often: closed {} session(s) with it",
+                    addr, removed
+                );
+            }
+        }
     }
 }
 
 #[tracing::instrument(skip_all)]
-fn listen(msg_listener: MsgListener, mut incoming_connections: IncomingConnections) {
+fn listen(
+    msg_listener: MsgListener,
+    admission: Admission,
+    mut incoming_connections: IncomingConnections,
+) {
     let _ = task::spawn(async move {
         while let Some((connection, incoming_msgs)) = incoming_connections.next().await {
             trace!(
@@ -585,7 +1025,33 @@ fn listen(msg_listener: MsgListener, mut incoming_connections: IncomingConnectio
                 connection.id()
             );
 
-            msg_listener.listen(connection, incoming_msgs);
+            if msg_listener.is_banned(&connection.remote_address()) {
+                debug!(
+                    "Refusing connection from {:?}, banned for misbehaving",
+                    connection.remote_address()
+                );
+                connection.close(Some("Banned".to_string()));
+                continue;
+            }
+
+            match admission.try_admit() {
+                Some(permit) => {
+                    msg_listener.listen_incoming(
+                        connection,
+                        incoming_msgs,
+                        admission.clone(),
+                        permit,
+                    );
+                }
+                None => {
+                    debug!(
+                        "Refusing connection from {:?}, over the budget of the listener for {:?}",
+                        connection.remote_address(),
+                        admission.serves()
+                    );
+                    connection.close(Some("Too many connections".to_string()));
+                }
+            }
         }
     });
 }
@@ -595,6 +1061,9 @@ impl Drop for Comm {
         // Close all existing connections and stop accepting new ones.
         // FIXME: this may be broken – `Comm` is clone, so this will break any clones?
         self.our_endpoint.close();
+        if let Some(listener) = &self.clients {
+            listener.endpoint.close();
+        }
     }
 }
 
@@ -605,6 +1074,14 @@ pub(crate) enum MsgEvent {
         wire_msg: WireMsg,
         original_bytes: Bytes,
     },
+    /// A msg that reached the listener for the other kind of peers than its sender, which is to
+    /// be sent on to the one at `redirect_to`.
+    Misdirected {
+        sender: Peer,
+        wire_msg: WireMsg,
+        original_bytes: Bytes,
+        redirect_to: SocketAddr,
+    },
 }
 
 /// Returns the status of the send operation.
@@ -623,10 +1100,13 @@ mod tests {
     use futures::future;
     use qp2p::Config;
     use sn_interface::messaging::data::{DataQuery, ServiceMsg};
-    use sn_interface::messaging::{AuthKind, DstLocation, MsgId, ServiceAuth};
-    use sn_interface::types::{ChunkAddress, Keypair, Peer};
+    use sn_interface::messaging::{
+        system::SystemMsg, AuthKind, DstLocation, MsgId, NodeAuth, ServiceAuth,
+    };
+    use sn_interface::types::{keys::ed25519, ChunkAddress, Keypair, Peer};
     use std::{net::Ipv4Addr, time::Duration};
     use tokio::{net::UdpSocket, sync::mpsc, time};
+    use xor_name::Prefix;
 
     const TIMEOUT: Duration = Duration::from_secs(1);
 
@@ -845,7 +1325,216 @@ mod tests {
         Ok(())
     }
 
-    fn new_test_msg() -> Result<WireMsg> {
+    #[tokio::test(flavor = "multi_thread")]
+    async fn send_across_a_partition_is_dropped_until_healed() -> Result<()> {
+        let partitions = Partitions::default();
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::first_node(local_addr(), Config::default(), tx)
+            .await?
+            .with_partitions(partitions.clone());
+        let peer = get_invalid_peer().await?;
+        partitions.set_group("us", [comm.our_connection_info()]);
+        partitions.set_group("them", [peer.addr()]);
+        let id = partitions.partition("us", "them", Fault::Drop, Duration::ZERO, None);
+
+        // The msg is given up on without trying to reach the peer.
+        let status = comm.send(&[peer], 1, new_test_msg()?).await?;
+        assert_matches!(status, DeliveryStatus::MinDeliveryGroupSizeFailed(failed) => {
+            assert_eq!(failed, vec![peer])
+        });
+        assert_eq!(comm.sessions.len().await, 0);
+        assert_eq!(partitions.heal(id), Some(1));
+        assert_eq!(comm.fault_towards(&peer), None);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn large_msgs_are_streamed_and_reassembled() -> Result<()> {
+        use sn_interface::messaging::system::NodeCmd;
+        use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let comm = Comm::first_node(local_addr(), Config::default(), tx).await?;
+        let (node_tx, _node_rx) = mpsc::channel(1);
+        let node = Comm::first_node(local_addr(), Config::default(), node_tx).await?;
+        node.set_stream_threshold(64 * 1024);
+
+        let chunk = Chunk::new(random_bytes(1024 * 1024));
+        let msg = new_node_msg(
+            SystemMsg::NodeCmd(NodeCmd::ReplicateData(vec![ReplicatedData::Chunk(chunk)])),
+            DstLocation::Node {
+                name: xor_name::rand::random(),
+                section_pk: bls::SecretKey::random().public_key(),
+            },
+        )?;
+        let peer = Peer::new(xor_name::rand::random(), comm.our_connection_info());
+        let status = node.send(&[peer], 1, msg.clone()).await?;
+        assert_matches!(status, DeliveryStatus::AllRecipients);
+
+        assert_matches!(
+            time::timeout(10 * TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { wire_msg, .. }) => assert_eq!(wire_msg, msg)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn large_msgs_go_compressed_once_the_codec_is_agreed_on() -> Result<()> {
+        use sn_interface::messaging::system::NodeCmd;
+        use sn_interface::types::{Chunk, ReplicatedData};
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let comm = Comm::first_node(local_addr(), Config::default(), tx).await?;
+        let (node_tx, _node_rx) = mpsc::channel(1);
+        let node = Comm::first_node(local_addr(), Config::default(), node_tx).await?;
+        let peer = Peer::new(xor_name::rand::random(), comm.our_connection_info());
+
+        // The first msg opens the connection, the codec being agreed on meanwhile.
+        let msg = new_test_msg()?;
+        assert_matches!(
+            node.send(&[peer], 1, msg.clone()).await?,
+            DeliveryStatus::AllRecipients
+        );
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { wire_msg, .. }) => assert_eq!(wire_msg, msg)
+        );
+        let received_before = comm.bandwidth_totals().received;
+
+        let chunk = Chunk::new(Bytes::from(vec![0; 512 * 1024]));
+        let msg = new_node_msg(
+            SystemMsg::NodeCmd(NodeCmd::ReplicateData(vec![ReplicatedData::Chunk(chunk)])),
+            DstLocation::Node {
+                name: xor_name::rand::random(),
+                section_pk: bls::SecretKey::random().public_key(),
+            },
+        )?;
+        let msg_len = msg.serialize()?.len();
+        assert_matches!(
+            node.send(&[peer], 1, msg.clone()).await?,
+            DeliveryStatus::AllRecipients
+        );
+        assert_matches!(
+            time::timeout(10 * TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { wire_msg, .. }) => assert_eq!(wire_msg, msg)
+        );
+        let received = comm.bandwidth_totals().received - received_before;
+        assert!(
+            received < msg_len as u64 / 10,
+            "{} bytes received",
+            received
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn clients_and_nodes_are_each_taken_on_their_own_listener() -> Result<()> {
+        let (tx, mut rx) = mpsc::channel(10);
+        let comm = Comm::first_node(local_addr(), Config::default(), tx)
+            .await?
+            .with_client_listener(local_addr(), Config::default())
+            .await?;
+        let nodes_addr = comm.our_connection_info();
+        let clients_addr = comm.our_client_connection_info();
+        assert_ne!(nodes_addr, clients_addr);
+
+        let (client_tx, _client_rx) = mpsc::channel(1);
+        let client = Comm::first_node(local_addr(), Config::default(), client_tx).await?;
+        let (node_tx, _node_rx) = mpsc::channel(1);
+        let node = Comm::first_node(local_addr(), Config::default(), node_tx).await?;
+        let name = xor_name::rand::random();
+
+        // each reaching the listener for its kind is taken in..
+        let _status = client
+            .send(&[Peer::new(name, clients_addr)], 1, new_test_msg()?)
+            .await?;
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { .. })
+        );
+        let _status = node
+            .send(&[Peer::new(name, nodes_addr)], 1, new_node_test_msg()?)
+            .await?;
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { .. })
+        );
+        assert_eq!(
+            comm.open_conns(),
+            vec![
+                (Some(ListenerKind::Nodes), 1),
+                (Some(ListenerKind::Clients), 1)
+            ]
+        );
+
+        // ..while reaching the other one has it sent on to the right one
+        let _status = client
+            .send(&[Peer::new(name, nodes_addr)], 1, new_test_msg()?)
+            .await?;
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Misdirected { redirect_to, .. }) => assert_eq!(redirect_to, clients_addr)
+        );
+        let _status = node
+            .send(&[Peer::new(name, clients_addr)], 1, new_node_test_msg()?)
+            .await?;
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Misdirected { redirect_to, .. }) => assert_eq!(redirect_to, nodes_addr)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exhausted_client_listener_leaves_node_conns_unaffected() -> Result<()> {
+        let (tx, mut rx) = mpsc::channel(10);
+        let comm = Comm::first_node(local_addr(), Config::default(), tx)
+            .await?
+            .with_client_listener(local_addr(), Config::default())
+            .await?;
+        comm.set_listener_limits(
+            ConnLimits::nodes(),
+            ConnLimits {
+                max_conns: 1,
+                conns_per_s: 100,
+            },
+        );
+        let name = xor_name::rand::random();
+        let clients = Peer::new(name, comm.our_client_connection_info());
+        let nodes = Peer::new(name, comm.our_connection_info());
+
+        let (client_tx, _client_rx) = mpsc::channel(1);
+        let client = Comm::first_node(local_addr(), Config::default(), client_tx).await?;
+        let _status = client.send(&[clients], 1, new_test_msg()?).await?;
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { .. })
+        );
+
+        // the client budget is spent, so another client is refused..
+        let (client_tx, _client_rx) = mpsc::channel(1);
+        let other_client = Comm::first_node(local_addr(), Config::default(), client_tx).await?;
+        let _res = other_client.send(&[clients], 1, new_test_msg()?).await;
+        assert_matches!(time::timeout(TIMEOUT, rx.recv()).await, Err(_));
+
+        // ..while nodes still get through
+        let (node_tx, _node_rx) = mpsc::channel(1);
+        let node = Comm::first_node(local_addr(), Config::default(), node_tx).await?;
+        let status = node.send(&[nodes], 1, new_node_test_msg()?).await?;
+        assert_matches!(status, DeliveryStatus::AllRecipients);
+        assert_matches!(
+            time::timeout(TIMEOUT, rx.recv()).await?,
+            Some(MsgEvent::Received { .. })
+        );
+
+        Ok(())
+    }
+
+    pub(super) fn new_test_msg() -> Result<WireMsg> {
         let dst_location = DstLocation::Node {
             name: xor_name::rand::random(),
             section_pk: bls::SecretKey::random().public_key(),
@@ -867,6 +1556,28 @@ mod tests {
         Ok(wire_msg)
     }
 
+    fn new_node_test_msg() -> Result<WireMsg> {
+        let dst_location = DstLocation::Node {
+            name: xor_name::rand::random(),
+            section_pk: bls::SecretKey::random().public_key(),
+        };
+        new_node_msg(
+            SystemMsg::AntiEntropyProbe(dst_location.name()),
+            dst_location,
+        )
+    }
+
+    fn new_node_msg(msg: SystemMsg, dst_location: DstLocation) -> Result<WireMsg> {
+        let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), 5);
+        let payload = WireMsg::serialize_msg_payload(&msg)?;
+        let auth = NodeAuth::authorize(bls::SecretKey::random().public_key(), &keypair, &payload)
+            .into_inner();
+
+        let wire_msg = WireMsg::new_msg(MsgId::new(), payload, AuthKind::Node(auth), dst_location)?;
+
+        Ok(wire_msg)
+    }
+
     async fn new_peer() -> Result<(Peer, mpsc::Receiver<Bytes>)> {
         let (endpoint, mut incoming_connections, _) =
             Endpoint::new_peer(local_addr(), &[], Config::default()).await?;
@@ -0,0 +1,251 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The last msgs a node received and sent, with what became of them, kept in memory for them to
+//! be dumped after the fact, e.g. once the node is found to have dropped or misrouted clients'
+//! msgs. Only a few fields of each msg are kept, for recording them to cost next to nothing.
+
+use sn_interface::messaging::{AuthKind, MsgId, WireMsg};
+use sn_interface::types::Peer;
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+use xor_name::XorName;
+
+/// Msgs kept when unspecified.
+pub(crate) const DEFAULT_MSG_AUDIT_CAPACITY: usize = 1000;
+
+/// Whether a msg was received or sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Inbound => "in",
+            Self::Outbound => "out",
+        }
+    }
+}
+
+/// What became of a msg.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Disposition {
+    /// Received, and handed over to be handled.
+    Handled,
+    /// Received from a client, and queued to be handled in its turn.
+    Queued,
+    /// Received already, and dropped.
+    Duplicate,
+    /// Received from a client with too many msgs queued, and refused.
+    RefusedBusy,
+    /// Received from a client while we were shutting down, and refused.
+    RefusedDraining,
+    /// Received on the wrong listener, and redirected to the right one.
+    Redirected,
+    /// Received on the wrong listener, and dropped as it couldn't be redirected.
+    RedirectFailed,
+    /// Sent.
+    Sent,
+    /// Not sent, after retrying or as the link to the peer was dropped.
+    SendFailed,
+}
+
+impl Disposition {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Handled => "handled",
+            Self::Queued => "queued",
+            Self::Duplicate => "duplicate",
+            Self::RefusedBusy => "refused_busy",
+            Self::RefusedDraining => "refused_draining",
+            Self::Redirected => "redirected",
+            Self::RedirectFailed => "redirect_failed",
+            Self::Sent => "sent",
+            Self::SendFailed => "send_failed",
+        }
+    }
+}
+
+/// A msg received or sent.
+#[derive(Clone, Debug)]
+pub(crate) struct AuditEntry {
+    pub(crate) at: SystemTime,
+    pub(crate) direction: Direction,
+    pub(crate) msg_id: MsgId,
+    /// The sender of a msg received, the recipient of one sent.
+    pub(crate) peer: Peer,
+    /// The name the msg is addressed to.
+    pub(crate) dst: XorName,
+    /// The kind of its auth: `service` for clients' msgs, `node` or `node_bls_share` for nodes'.
+    pub(crate) kind: &'static str,
+    /// Its size on the wire, in bytes.
+    pub(crate) size: usize,
+    pub(crate) disposition: Disposition,
+}
+
+struct Ring {
+    capacity: usize,
+    entries: VecDeque<AuditEntry>,
+}
+
+/// The last msgs received and sent, the oldest being dropped as new ones are recorded. Shared by
+/// all the clones of a `Comm`.
+#[derive(Clone)]
+pub(crate) struct MsgAudit(Arc<Mutex<Ring>>);
+
+impl Default for MsgAudit {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Ring {
+            capacity: DEFAULT_MSG_AUDIT_CAPACITY,
+            entries: VecDeque::new(),
+        })))
+    }
+}
+
+impl MsgAudit {
+    /// Keeps the last `capacity` msgs from now on, 0 keeping none.
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        let mut ring = self.lock();
+        ring.capacity = capacity;
+        while ring.entries.len() > capacity {
+            let _oldest = ring.entries.pop_front();
+        }
+        ring.entries.shrink_to(capacity);
+    }
+
+    /// Most msgs kept.
+    pub(crate) fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Records what became of `wire_msg`, of `size` bytes, received from or sent to `peer`.
+    pub(crate) fn record(
+        &self,
+        direction: Direction,
+        wire_msg: &WireMsg,
+        peer: Peer,
+        size: usize,
+        disposition: Disposition,
+    ) {
+        let entry = AuditEntry {
+            at: SystemTime::now(),
+            direction,
+            msg_id: wire_msg.msg_id(),
+            peer,
+            dst: wire_msg.dst_location().name(),
+            kind: kind(wire_msg),
+            size,
+            disposition,
+        };
+        let mut ring = self.lock();
+        if ring.capacity == 0 {
+            return;
+        }
+        if ring.entries.len() >= ring.capacity {
+            let _oldest = ring.entries.pop_front();
+        }
+        ring.entries.push_back(entry);
+    }
+
+    /// The msgs kept, oldest first.
+    pub(crate) fn entries(&self) -> Vec<AuditEntry> {
+        self.lock().entries.iter().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ring> {
+        // The lock is only held for updates of the ring, which don't panic.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn kind(wire_msg: &WireMsg) -> &'static str {
+    match wire_msg.msg_kind() {
+        AuthKind::Service(_) => "service",
+        AuthKind::Node(_) => "node",
+        AuthKind::NodeBlsShare(_) => "node_bls_share",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::core::comm::tests::new_test_msg;
+
+    use eyre::Result;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    fn peer() -> Peer {
+        Peer::new(
+            xor_name::rand::random(),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 12000)),
+        )
+    }
+
+    #[test]
+    fn only_the_last_msgs_are_kept() -> Result<()> {
+        let audit = MsgAudit::default();
+        audit.set_capacity(3);
+
+        let msgs = (0..5).map(|_| new_test_msg()).collect::<Result<Vec<_>>>()?;
+        for msg in &msgs {
+            audit.record(Direction::Inbound, msg, peer(), 100, Disposition::Handled);
+        }
+        let kept: Vec<_> = audit.entries().iter().map(|entry| entry.msg_id).collect();
+        let last: Vec<_> = msgs[2..].iter().map(WireMsg::msg_id).collect();
+        assert_eq!(kept, last);
+
+        // Shrinking drops the oldest of those kept, and 0 keeps none.
+        audit.set_capacity(1);
+        assert_eq!(audit.entries()[0].msg_id, msgs[4].msg_id());
+        audit.set_capacity(0);
+        audit.record(
+            Direction::Outbound,
+            &msgs[0],
+            peer(),
+            100,
+            Disposition::Sent,
+        );
+        assert!(audit.entries().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn entries_tell_what_became_of_msgs() -> Result<()> {
+        let audit = MsgAudit::default();
+        let msg = new_test_msg()?;
+        let sender = peer();
+        audit.record(
+            Direction::Inbound,
+            &msg,
+            sender,
+            42,
+            Disposition::RefusedBusy,
+        );
+
+        let entries = audit.entries();
+        let entry = &entries[0];
+        assert_eq!(entry.direction, Direction::Inbound);
+        assert_eq!(entry.peer, sender);
+        assert_eq!(entry.dst, msg.dst_location().name());
+        assert_eq!(entry.kind, "service");
+        assert_eq!(entry.size, 42);
+        assert_eq!(entry.disposition.as_str(), "refused_busy");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_interface::messaging::{data::ServiceMsg, system::SystemMsg, AuthKind, WireMsg};
+
+/// The classes incoming msgs are queued and accounted for by, highest priority first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum MsgClass {
+    /// Anti-entropy, which gets nodes' knowledge of the network up to date.
+    AntiEntropy,
+    /// Any other msg between nodes: membership, DKG, data replication, etc.
+    NodeSystem,
+    /// Clients' cmds.
+    ClientCmd,
+    /// Clients' queries, and whatever else clients send.
+    ClientQuery,
+}
+
+impl MsgClass {
+    /// All of them, highest priority first.
+    pub(crate) const ALL: [Self; 4] = [
+        Self::AntiEntropy,
+        Self::NodeSystem,
+        Self::ClientCmd,
+        Self::ClientQuery,
+    ];
+
+    /// The class of `wire_msg`, going by its payload alone, without verifying its signature.
+    /// Msgs whose payload can't be read go with the lowest class of their kind, they'll be
+    /// dropped when handled anyway.
+    pub(crate) fn of(wire_msg: &WireMsg) -> Self {
+        match wire_msg.msg_kind() {
+            AuthKind::Service(_) => match rmp_serde::from_slice(&wire_msg.payload) {
+                Ok(ServiceMsg::Cmd(_) | ServiceMsg::SpentbookCmd(_) | ServiceMsg::BatchCmd(_)) => {
+                    Self::ClientCmd
+                }
+                _ => Self::ClientQuery,
+            },
+            AuthKind::Node(_) | AuthKind::NodeBlsShare(_) => {
+                match rmp_serde::from_slice(&wire_msg.payload) {
+                    Ok(
+                        SystemMsg::AntiEntropyRetry { .. }
+                        | SystemMsg::AntiEntropyRedirect { .. }
+                        | SystemMsg::AntiEntropyUpdate { .. }
+                        | SystemMsg::AntiEntropyProbe(_)
+                        | SystemMsg::ListenerRedirect { .. }
+                        | SystemMsg::Relay(_),
+                    ) => Self::AntiEntropy,
+                    _ => Self::NodeSystem,
+                }
+            }
+        }
+    }
+
+    /// How many msgs of the class can wait to be handled, before the connections they come on
+    /// are held up.
+    pub(crate) fn queue_len(self) -> usize {
+        match self {
+            Self::AntiEntropy | Self::NodeSystem => 1000,
+            Self::ClientCmd | Self::ClientQuery => 100,
+        }
+    }
+
+    /// The share of the msgs we can take guaranteed to the class, however busy the others are.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn share(self) -> f64 {
+        match self {
+            Self::AntiEntropy => 0.2,
+            Self::NodeSystem => 0.4,
+            Self::ClientCmd => 0.2,
+            Self::ClientQuery => 0.2,
+        }
+    }
+
+    /// Whether the class is about msgs from other nodes, which our back pressure reports go to.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn is_from_nodes(self) -> bool {
+        matches!(self, Self::AntiEntropy | Self::NodeSystem)
+    }
+
+    #[cfg(all(feature = "back-pressure", feature = "metrics"))]
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::AntiEntropy => "anti_entropy",
+            Self::NodeSystem => "node_system",
+            Self::ClientCmd => "client_cmd",
+            Self::ClientQuery => "client_query",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use sn_interface::messaging::{
+        data::{DataCmd, DataQuery},
+        DstLocation, MsgId, NodeAuth, ServiceAuth,
+    };
+    use sn_interface::types::{keys::ed25519, Chunk, ChunkAddress, Keypair};
+    use xor_name::Prefix;
+
+    fn client_msg(msg: &ServiceMsg) -> Result<WireMsg> {
+        let keypair = Keypair::new_ed25519();
+        let payload = WireMsg::serialize_msg_payload(msg)?;
+        let auth = ServiceAuth {
+            public_key: keypair.public_key(),
+            signature: keypair.sign(&payload),
+        };
+        Ok(WireMsg::new_msg(
+            MsgId::new(),
+            payload,
+            AuthKind::Service(auth),
+            dst_location(),
+        )?)
+    }
+
+    fn node_msg(msg: &SystemMsg) -> Result<WireMsg> {
+        let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), 5);
+        let payload = WireMsg::serialize_msg_payload(msg)?;
+        let auth = NodeAuth::authorize(bls::SecretKey::random().public_key(), &keypair, &payload)
+            .into_inner();
+        Ok(WireMsg::new_msg(
+            MsgId::new(),
+            payload,
+            AuthKind::Node(auth),
+            dst_location(),
+        )?)
+    }
+
+    fn dst_location() -> DstLocation {
+        DstLocation::Node {
+            name: xor_name::rand::random(),
+            section_pk: bls::SecretKey::random().public_key(),
+        }
+    }
+
+    #[test]
+    fn msgs_are_classified_by_their_payload() -> Result<()> {
+        let query = ServiceMsg::Query(DataQuery::GetChunk(ChunkAddress(xor_name::rand::random())));
+        assert_eq!(MsgClass::of(&client_msg(&query)?), MsgClass::ClientQuery);
+
+        let cmd = ServiceMsg::Cmd(DataCmd::StoreChunk(Chunk::new(vec![1, 2, 3].into())));
+        assert_eq!(MsgClass::of(&client_msg(&cmd)?), MsgClass::ClientCmd);
+
+        let probe = SystemMsg::AntiEntropyProbe(xor_name::rand::random());
+        assert_eq!(MsgClass::of(&node_msg(&probe)?), MsgClass::AntiEntropy);
+
+        let connectivity_test = SystemMsg::StartConnectivityTest(xor_name::rand::random());
+        assert_eq!(
+            MsgClass::of(&node_msg(&connectivity_test)?),
+            MsgClass::NodeSystem
+        );
+
+        // An unreadable payload doesn't get a msg a higher priority.
+        let mut garbled = client_msg(&cmd)?;
+        garbled.payload = vec![0xc1; 8].into();
+        assert_eq!(MsgClass::of(&garbled), MsgClass::ClientQuery);
+
+        Ok(())
+    }
+}
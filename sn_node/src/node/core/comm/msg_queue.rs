@@ -0,0 +1,176 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{msg_class::MsgClass, MsgEvent};
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::mpsc, task, time::Instant};
+
+// Msgs queued, with when they were.
+type Queued = (Instant, MsgEvent);
+
+/// Incoming msgs waiting to be handled, in a queue per class. Msgs are handed over highest class
+/// first, so a flood of client queries can't hold up the msgs keeping the section together.
+#[derive(Clone)]
+pub(crate) struct MsgQueue {
+    // Indexed by class, highest priority first.
+    queues: [mpsc::Sender<Queued>; 4],
+    #[cfg(feature = "back-pressure")]
+    delay: QueueDelay,
+}
+
+/// How long msgs waited in the queues lately, before being handed over: a moving average, each
+/// msg handed over weighing an eighth in it.
+#[derive(Clone, Default)]
+pub(crate) struct QueueDelay(Arc<AtomicU64>); // unit: µs
+
+impl QueueDelay {
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn get(&self) -> Duration {
+        Duration::from_micros(self.0.load(Ordering::Relaxed))
+    }
+
+    fn record(&self, waited: Duration) {
+        let waited = u64::try_from(waited.as_micros()).unwrap_or(u64::MAX);
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                Some(average - average / 8 + waited / 8)
+            });
+    }
+}
+
+impl MsgQueue {
+    /// Hands the msgs queued over to `receive_msg`, as it takes them.
+    pub(crate) fn new(receive_msg: mpsc::Sender<MsgEvent>) -> Self {
+        let [anti_entropy, node_system, client_cmd, client_query] =
+            MsgClass::ALL.map(|class| mpsc::channel(class.queue_len()));
+
+        let delay = QueueDelay::default();
+        let _handle = task::spawn(hand_over(
+            [anti_entropy.1, node_system.1, client_cmd.1, client_query.1],
+            receive_msg,
+            delay.clone(),
+        ));
+
+        Self {
+            queues: [anti_entropy.0, node_system.0, client_cmd.0, client_query.0],
+            #[cfg(feature = "back-pressure")]
+            delay,
+        }
+    }
+
+    /// How long msgs waited in the queues lately.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn delay(&self) -> QueueDelay {
+        self.delay.clone()
+    }
+
+    /// Queues `event`, waiting for room if its class' queue is full.
+    pub(crate) async fn push(&self, class: MsgClass, event: MsgEvent) {
+        let queued = (Instant::now(), event);
+        if self.queues[class as usize].send(queued).await.is_err() {
+            debug!("Dropping a {:?} msg, as msgs are no longer taken", class);
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn hand_over(
+    queues: [mpsc::Receiver<Queued>; 4],
+    receive_msg: mpsc::Sender<MsgEvent>,
+    delay: QueueDelay,
+) {
+    let [mut anti_entropy, mut node_system, mut client_cmd, mut client_query] = queues;
+    loop {
+        let (queued_at, event) = tokio::select! {
+            biased;
+            Some(event) = anti_entropy.recv() => event,
+            Some(event) = node_system.recv() => event,
+            Some(event) = client_cmd.recv() => event,
+            Some(event) = client_query.recv() => event,
+            else => break,
+        };
+        delay.record(queued_at.elapsed());
+        if receive_msg.send(event).await.is_err() {
+            break;
+        }
+    }
+    debug!("Exited msg queue loop..!");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use eyre::Result;
+    use sn_interface::messaging::{
+        system::SystemMsg, AuthKind, DstLocation, MsgId, NodeAuth, WireMsg,
+    };
+    use sn_interface::types::{keys::ed25519, Peer};
+    use std::net::Ipv4Addr;
+    use xor_name::Prefix;
+
+    fn event(msg_id: MsgId) -> Result<MsgEvent> {
+        let dst_location = DstLocation::Node {
+            name: xor_name::rand::random(),
+            section_pk: bls::SecretKey::random().public_key(),
+        };
+        let keypair = ed25519::gen_keypair(&Prefix::default().range_inclusive(), 5);
+        let payload =
+            WireMsg::serialize_msg_payload(&SystemMsg::AntiEntropyProbe(dst_location.name()))?;
+        let auth = NodeAuth::authorize(bls::SecretKey::random().public_key(), &keypair, &payload)
+            .into_inner();
+        let wire_msg = WireMsg::new_msg(msg_id, payload, AuthKind::Node(auth), dst_location)?;
+        Ok(MsgEvent::Received {
+            sender: Peer::new(xor_name::rand::random(), (Ipv4Addr::LOCALHOST, 1).into()),
+            wire_msg,
+            original_bytes: Bytes::new(),
+        })
+    }
+
+    fn msg_id(event: Option<MsgEvent>) -> Option<MsgId> {
+        match event? {
+            MsgEvent::Received { wire_msg, .. } | MsgEvent::Misdirected { wire_msg, .. } => {
+                Some(wire_msg.msg_id())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn higher_classes_are_handed_over_first() -> Result<()> {
+        let (receive_msg, mut received) = mpsc::channel(1);
+        let queue = MsgQueue::new(receive_msg);
+
+        // The first msg is handed over at once, filling the channel; the rest wait in their
+        // queues.
+        let ids: Vec<_> = (0..4).map(|_| MsgId::new()).collect();
+        queue.push(MsgClass::ClientQuery, event(ids[0])?).await;
+        task::yield_now().await;
+        queue.push(MsgClass::ClientQuery, event(ids[1])?).await;
+        queue.push(MsgClass::ClientCmd, event(ids[2])?).await;
+        queue.push(MsgClass::AntiEntropy, event(ids[3])?).await;
+
+        // Lower classes wait while there's anything of a higher class.
+        let mut handed_over = vec![];
+        for _ in 0..4 {
+            handed_over.push(msg_id(received.recv().await));
+        }
+        let expected: Vec<_> = [0, 3, 2, 1].iter().map(|i| Some(ids[*i])).collect();
+        assert_eq!(handed_over, expected);
+
+        Ok(())
+    }
+}
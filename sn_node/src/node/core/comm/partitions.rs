@@ -0,0 +1,221 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// What happens to the msgs sent across a partition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Fault {
+    /// The msgs are lost, as if the recipient was unreachable.
+    Drop,
+    /// The msgs are held back for the duration before being sent.
+    Delay(Duration),
+    /// The msgs are sent twice.
+    Duplicate,
+    /// The last bit of the msgs is flipped, as by a faulty link.
+    Corrupt,
+}
+
+/// Identifies a partition, for it to be healed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct PartitionId(u64);
+
+/// Artificial network partitions between named groups of in-process nodes, for tests and
+/// simulations.
+///
+/// Shared by the `Comm`s of the nodes, which consult it for each msg they send. A msg between
+/// two groups of a partition gets its fault while the partition is in effect. Partitions are
+/// consulted in the order they were made, so the same msgs get the same faults on every run.
+/// Time is tokio's, so paused time moves partitions in and out of effect too.
+#[derive(Clone, Default)]
+pub(crate) struct Partitions {
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    groups: BTreeMap<String, BTreeSet<SocketAddr>>,
+    partitions: BTreeMap<PartitionId, Partition>,
+    next_id: u64,
+}
+
+struct Partition {
+    groups: (String, String),
+    fault: Fault,
+    start: Instant,
+    end: Option<Instant>,
+    // Msgs which got the fault.
+    affected: u64,
+}
+
+impl Partition {
+    fn separates(
+        &self,
+        groups: &BTreeMap<String, BTreeSet<SocketAddr>>,
+        a: &SocketAddr,
+        b: &SocketAddr,
+    ) -> bool {
+        let is_in = |group: &String, addr| groups.get(group).map_or(false, |g| g.contains(addr));
+        let (x, y) = &self.groups;
+        (is_in(x, a) && is_in(y, b)) || (is_in(y, a) && is_in(x, b))
+    }
+
+    fn in_effect(&self, now: Instant) -> bool {
+        self.start <= now && self.end.map_or(true, |end| now < end)
+    }
+}
+
+impl Partitions {
+    /// Names the group of nodes at `addrs`, replacing any group of that name.
+    pub(crate) fn set_group(&self, name: &str, addrs: impl IntoIterator<Item = SocketAddr>) {
+        let _prev = self
+            .write()
+            .groups
+            .insert(name.to_string(), addrs.into_iter().collect());
+    }
+
+    /// Partitions groups `a` and `b`, applying `fault` to the msgs sent between them, either
+    /// way, from `start` after now until `end` after now, or until healed.
+    pub(crate) fn partition(
+        &self,
+        a: &str,
+        b: &str,
+        fault: Fault,
+        start: Duration,
+        end: Option<Duration>,
+    ) -> PartitionId {
+        let now = Instant::now();
+        let mut inner = self.write();
+        let id = PartitionId(inner.next_id);
+        inner.next_id += 1;
+        let _prev = inner.partitions.insert(
+            id,
+            Partition {
+                groups: (a.to_string(), b.to_string()),
+                fault,
+                start: now + start,
+                end: end.map(|end| now + end),
+                affected: 0,
+            },
+        );
+        id
+    }
+
+    /// The fault of a msg from `sender` to `recipient`, counting it against the partition
+    /// applying it, if any.
+    pub(crate) fn fault(&self, sender: &SocketAddr, recipient: &SocketAddr) -> Option<Fault> {
+        let now = Instant::now();
+        let mut inner = self.write();
+        let Inner {
+            groups, partitions, ..
+        } = &mut *inner;
+        let partition = partitions
+            .values_mut()
+            .find(|p| p.in_effect(now) && p.separates(groups, sender, recipient))?;
+        partition.affected += 1;
+        Some(partition.fault)
+    }
+
+    /// Number of msgs the partition applied its fault to so far.
+    pub(crate) fn affected(&self, id: PartitionId) -> Option<u64> {
+        self.read().partitions.get(&id).map(|p| p.affected)
+    }
+
+    /// Ends the partition, returning the number of msgs it applied its fault to.
+    pub(crate) fn heal(&self, id: PartitionId) -> Option<u64> {
+        self.write().partitions.remove(&id).map(|p| p.affected)
+    }
+
+    /// Ends all partitions.
+    pub(crate) fn heal_all(&self) {
+        self.write().partitions.clear();
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Inner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Inner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partitions_apply_between_their_groups_within_their_window() {
+        let partitions = Partitions::default();
+        partitions.set_group("elders", [addr(1), addr(2)]);
+        partitions.set_group("adults", [addr(3)]);
+        let id = partitions.partition(
+            "elders",
+            "adults",
+            Fault::Drop,
+            Duration::from_secs(1),
+            Some(Duration::from_secs(3)),
+        );
+
+        // Not yet in effect.
+        assert_eq!(partitions.fault(&addr(1), &addr(3)), None);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(partitions.fault(&addr(1), &addr(3)), Some(Fault::Drop));
+        assert_eq!(partitions.fault(&addr(3), &addr(2)), Some(Fault::Drop));
+        // Within a group, or to nodes outside of the groups, msgs get through.
+        assert_eq!(partitions.fault(&addr(1), &addr(2)), None);
+        assert_eq!(partitions.fault(&addr(3), &addr(4)), None);
+        assert_eq!(partitions.affected(id), Some(2));
+
+        // No longer in effect, but counted until healed.
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(partitions.fault(&addr(1), &addr(3)), None);
+        assert_eq!(partitions.heal(id), Some(2));
+        assert_eq!(partitions.affected(id), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn earlier_partitions_take_precedence_until_healed() {
+        let partitions = Partitions::default();
+        partitions.set_group("a", [addr(1)]);
+        partitions.set_group("b", [addr(2)]);
+        let delayed = Fault::Delay(Duration::from_millis(500));
+        let first = partitions.partition("a", "b", delayed, Duration::ZERO, None);
+        let second = partitions.partition("b", "a", Fault::Duplicate, Duration::ZERO, None);
+
+        assert_eq!(partitions.fault(&addr(1), &addr(2)), Some(delayed));
+        assert_eq!(partitions.heal(first), Some(1));
+        assert_eq!(partitions.fault(&addr(1), &addr(2)), Some(Fault::Duplicate));
+        assert_eq!(partitions.affected(second), Some(1));
+
+        // Regrouping takes effect on partitions already made.
+        partitions.set_group("b", [addr(3)]);
+        assert_eq!(partitions.fault(&addr(1), &addr(2)), None);
+        assert_eq!(partitions.fault(&addr(1), &addr(3)), Some(Fault::Duplicate));
+
+        partitions.heal_all();
+        assert_eq!(partitions.fault(&addr(1), &addr(3)), None);
+    }
+}
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Link;
+use super::{Link, StreamThreshold};
 
 use crate::node::{Error, Result};
 use sn_interface::messaging::MsgId;
@@ -32,6 +32,7 @@ const SLEEP_TIME: Duration = Duration::from_millis(200);
 #[derive(Clone)]
 pub(crate) struct PeerSession {
     link: Link,
+    stream_threshold: StreamThreshold,
     msg_queue: Arc<RwLock<PriorityQueue<SendJob, Priority>>>,
     sent: MsgThroughput,
     attempted: MsgThroughput,
@@ -40,9 +41,10 @@ pub(crate) struct PeerSession {
 }
 
 impl PeerSession {
-    pub(crate) fn new(link: Link) -> Self {
+    pub(crate) fn new(link: Link, stream_threshold: StreamThreshold) -> Self {
         let session = Self {
             link,
+            stream_threshold,
             msg_queue: Arc::new(RwLock::new(PriorityQueue::new())),
             sent: MsgThroughput::default(),
             attempted: MsgThroughput::default(),
@@ -133,6 +135,17 @@ impl PeerSession {
         *self.disconnnected.read().await
     }
 
+    #[cfg(test)]
+    pub(crate) async fn is_disconnected(&self) -> bool {
+        self.disconnected().await
+    }
+
+    // Whether both are clones of the same session.
+    #[cfg(test)]
+    pub(crate) fn is_same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.msg_queue, &other.msg_queue)
+    }
+
     async fn keep_sending(&self) {
         loop {
             if self.disconnected().await {
@@ -173,7 +186,12 @@ impl PeerSession {
 
                     break; // this means we will stop all sending to this peer!
                 }
-                if let Err(err) = self.link.send(job.msg_bytes.clone()).await {
+                let result = if self.stream_threshold.applies_to(job.msg_bytes.len()) {
+                    self.link.send_streamed(job.msg_bytes.clone()).await
+                } else {
+                    self.link.send(job.msg_bytes.clone()).await
+                };
+                if let Err(err) = result {
                     job.retries += 1;
                     if err.is_local_close() {
                         job.reporter.send(SendStatus::PeerLinkDropped);
@@ -270,6 +288,14 @@ impl SendWatcher {
         self.receiver.borrow().clone()
     }
 
+    /// A watcher which already got its final `status`, of a msg that won't be sent.
+    #[cfg(any(test, feature = "chaos"))]
+    pub(crate) fn settled(status: SendStatus) -> Self {
+        let (watcher, reporter) = status_watching();
+        reporter.send(status);
+        watcher
+    }
+
     /// Waits until a new status arrives.
     pub(crate) async fn await_change(&mut self) -> SendStatus {
         if self.receiver.changed().await.is_ok() {
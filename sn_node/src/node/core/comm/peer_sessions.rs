@@ -0,0 +1,254 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::peer_session::PeerSession;
+
+use sn_interface::types::Peer;
+
+use std::{collections::BTreeMap, future::Future, net::SocketAddr};
+use tokio::sync::RwLock;
+
+/// The sessions we hold with peers, at most one per peer.
+///
+/// Every change to the map is a single compound operation done under its lock, so that
+/// concurrent callers can't interleave a check with the act depending on it: two of them
+/// creating a session for the same peer each, or a session being evicted for lacking a
+/// connection right after one was added to it.
+#[derive(Default)]
+pub(crate) struct PeerSessions {
+    sessions: RwLock<BTreeMap<Peer, PeerSession>>,
+}
+
+impl PeerSessions {
+    /// Returns the session with `peer`, creating it with `new` if there's none.
+    pub(crate) async fn get_or_insert_with<F>(&self, peer: &Peer, new: F) -> PeerSession
+    where
+        F: FnOnce() -> PeerSession,
+    {
+        if let Some(session) = self.sessions.read().await.get(peer) {
+            return session.clone();
+        }
+
+        // if peer is not in list, the entire list needs to be locked
+        // i.e. first comms to any node, will impact all sending at that instant..
+        // however, first comms should be a minor part of total time spent using link,
+        // so that is ok
+        self.sessions
+            .write()
+            .await
+            .entry(*peer)
+            // someone else inserted in the meanwhile, so use that
+            .or_insert_with(new)
+            .clone()
+    }
+
+    /// Adds `conn` to the session with `peer`, or creates the session with it using `new`.
+    ///
+    /// The session can't be evicted while the connection is being added, so is connected once
+    /// this returns, unless it was disconnected from already.
+    pub(crate) async fn add_connection<F, Fut>(&self, peer: &Peer, conn: qp2p::Connection, new: F)
+    where
+        F: FnOnce(qp2p::Connection) -> Fut,
+        Fut: Future<Output = PeerSession>,
+    {
+        {
+            let sessions = self.sessions.read().await;
+            if let Some(session) = sessions.get(peer) {
+                // peer exists, add to it
+                session.add(conn).await;
+                return;
+            }
+        }
+
+        let mut sessions = self.sessions.write().await;
+        match sessions.get(peer) {
+            // someone else inserted in the meanwhile, add to it
+            Some(session) => session.add(conn).await,
+            // still not in list, go ahead and insert
+            None => {
+                let session = new(conn).await;
+                let _ = sessions.insert(*peer, session);
+            }
+        }
+    }
+
+    /// Evicts the sessions which are no longer connected, disconnecting them. Returns how many
+    /// were evicted.
+    ///
+    /// Whether a session is connected is confirmed again under the write lock, so a session
+    /// which got a connection since it was first found disconnected is kept.
+    pub(crate) async fn evict_disconnected(&self) -> usize {
+        let mut candidates = vec![];
+        for (peer, session) in self.sessions.read().await.iter() {
+            session.remove_expired().await;
+            if !session.is_connected().await {
+                candidates.push(*peer);
+            }
+        }
+        if candidates.is_empty() {
+            return 0;
+        }
+
+        let mut evicted = vec![];
+        {
+            let mut sessions = self.sessions.write().await;
+            for peer in candidates {
+                let connected = match sessions.get(&peer) {
+                    Some(session) => session.is_connected().await,
+                    None => continue,
+                };
+                if !connected {
+                    evicted.extend(sessions.remove(&peer));
+                }
+            }
+        }
+
+        // No longer reachable through the map, so they can be disconnected without the lock.
+        let count = evicted.len();
+        for session in evicted {
+            session.disconnect().await;
+        }
+        count
+    }
+
+    /// Removes the sessions with the peers at `addr`, disconnecting them. Returns how many
+    /// were removed.
+    pub(crate) async fn remove_addr(&self, addr: &SocketAddr) -> usize {
+        let removed: Vec<_> = {
+            let mut sessions = self.sessions.write().await;
+            let peers: Vec<_> = sessions
+                .keys()
+                .filter(|peer| peer.addr() == *addr)
+                .copied()
+                .collect();
+            peers
+                .iter()
+                .filter_map(|peer| sessions.remove(peer))
+                .collect()
+        };
+
+        // No longer reachable through the map, so they can be disconnected without the lock.
+        let count = removed.len();
+        for session in removed {
+            session.disconnect().await;
+        }
+        count
+    }
+
+    /// Number of sessions held.
+    pub(crate) async fn len(&self) -> usize {
+        self.sessions.read().await.len()
+    }
+
+    /// Returns each peer we hold a session with, along with its session.
+    pub(crate) async fn all(&self) -> Vec<(Peer, PeerSession)> {
+        self.sessions
+            .read()
+            .await
+            .iter()
+            .map(|(peer, session)| (*peer, session.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::core::comm::{
+        bandwidth::Bandwidth, compression::Compression, link::Link, listener::MsgListener,
+        msg_queue::MsgQueue, reputation::Reputation, stream::StreamThreshold,
+    };
+
+    use eyre::Result;
+    use futures::future::try_join_all;
+    use qp2p::{Config, Endpoint};
+    use rand::Rng;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::Arc,
+    };
+    use tokio::sync::{mpsc, Mutex};
+
+    const PEERS: u16 = 8;
+    const TASKS: usize = 16;
+    const OPS_PER_TASK: usize = 500;
+
+    // Runs adds and evictions against the same few peers from many tasks, then checks that every
+    // session handed out is either the one held for its peer or was disconnected when evicted:
+    // none is duplicated, and none is dropped from the map while still live.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn compound_ops_keep_one_session_per_peer_under_contention() -> Result<()> {
+        let config = Config {
+            forward_port: false,
+            ..Config::default()
+        };
+        let local = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+        let (endpoint, _incoming, _) = Endpoint::new_peer(local, &[], config).await?;
+        let listener = {
+            let (add_connection, _) = mpsc::channel(1);
+            let (receive_msg, _) = mpsc::channel(1);
+            let (count_msg, _) = mpsc::channel(1);
+            MsgListener::new(
+                add_connection,
+                MsgQueue::new(receive_msg),
+                count_msg,
+                Bandwidth::default(),
+                Compression::default(),
+                Reputation::default(),
+            )
+        };
+        let peers: Vec<_> = (1..=PEERS)
+            .map(|port| Peer::new(xor_name::rand::random(), (Ipv4Addr::LOCALHOST, port).into()))
+            .collect();
+
+        let sessions = Arc::new(PeerSessions::default());
+        let handed_out = Arc::new(Mutex::new(vec![]));
+
+        let tasks = (0..TASKS).map(|task| {
+            let sessions = sessions.clone();
+            let handed_out = handed_out.clone();
+            let endpoint = endpoint.clone();
+            let listener = listener.clone();
+            let peers = peers.clone();
+            tokio::spawn(async move {
+                for _ in 0..OPS_PER_TASK {
+                    if task % 4 == 0 {
+                        let _ = sessions.evict_disconnected().await;
+                        continue;
+                    }
+                    let peer = peers[rand::thread_rng().gen_range(0..peers.len())];
+                    let session = sessions
+                        .get_or_insert_with(&peer, || {
+                            let link = Link::new(peer, endpoint.clone(), listener.clone());
+                            PeerSession::new(link, StreamThreshold::default())
+                        })
+                        .await;
+                    handed_out.lock().await.push((peer, session));
+                }
+            })
+        });
+        let _ = try_join_all(tasks).await?;
+
+        let held = sessions.sessions.read().await.clone();
+        for (peer, session) in handed_out.lock().await.iter() {
+            if !session.is_disconnected().await {
+                let held = held.get(peer).expect("a live session is held");
+                assert!(session.is_same(held), "duplicate session with {}", peer);
+            }
+        }
+
+        // None of them has a connection, so evicting leaves nothing held.
+        assert_eq!(sessions.evict_disconnected().await, held.len());
+        assert_eq!(sessions.len().await, 0);
+        for (_, session) in handed_out.lock().await.iter() {
+            assert!(session.is_disconnected().await);
+        }
+
+        Ok(())
+    }
+}
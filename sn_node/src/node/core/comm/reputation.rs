@@ -0,0 +1,407 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use dashmap::DashMap;
+#[cfg(feature = "back-pressure")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+// Peers whose misbehaviour is kept track of, those past it being let off.
+const MAX_TRACKED_PEERS: usize = 10_000;
+// How long it takes for a score to halve, when the peer behaves.
+const SCORE_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+// Scores under this are forgotten, along with what the peer did.
+const FORGOTTEN_SCORE: f64 = 1.0;
+// At this score, we disconnect from the peer...
+const DISCONNECT_SCORE: f64 = 50.0;
+// ...and at this one, we refuse its connections for a while.
+const BAN_SCORE: f64 = 100.0;
+const BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
+// Back pressure: a peer sending more than this many times what it was told it could, over a
+// window, violates it...
+#[cfg(feature = "back-pressure")]
+const BACK_PRESSURE_SLACK: f64 = 2.0;
+// ...with the msgs counted over windows of this length.
+#[cfg(feature = "back-pressure")]
+const BACK_PRESSURE_WINDOW: Duration = Duration::from_secs(10);
+
+/// What peers can do wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Misbehaviour {
+    /// Sent a msg whose signature didn't verify.
+    InvalidSignature,
+    /// Sent bytes which couldn't be deserialised as a msg.
+    MalformedMsg,
+    /// Sent us more msgs than it was told it could.
+    #[cfg_attr(not(feature = "back-pressure"), allow(dead_code))]
+    BackPressureViolation,
+    /// Couldn't be reached when its connectivity was tested.
+    FailedLivenessProbe,
+    /// Kept sending msgs we had to answer with errors which weren't ours.
+    ErrorFlood,
+}
+
+impl Misbehaviour {
+    fn penalty(self) -> f64 {
+        match self {
+            Self::InvalidSignature => 25.0,
+            Self::MalformedMsg => 10.0,
+            Self::BackPressureViolation => 10.0,
+            Self::FailedLivenessProbe => 20.0,
+            Self::ErrorFlood => 10.0,
+        }
+    }
+}
+
+impl Display for Misbehaviour {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            Self::InvalidSignature => "invalid_signature",
+            Self::MalformedMsg => "malformed_msg",
+            Self::BackPressureViolation => "back_pressure_violation",
+            Self::FailedLivenessProbe => "failed_liveness_probe",
+            Self::ErrorFlood => "error_flood",
+        };
+        write!(formatter, "{}", name)
+    }
+}
+
+/// What's to be done about a peer, after it misbehaved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// Nothing, yet.
+    Tolerated,
+    /// Its connections are to be closed.
+    Disconnect,
+    /// Its connections are to be closed, and new ones refused until the ban is over.
+    Ban,
+}
+
+/// The standing of a peer, as of now.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PeerReputation {
+    pub(crate) addr: SocketAddr,
+    /// Its name, if it was known when it last misbehaved.
+    pub(crate) name: Option<XorName>,
+    pub(crate) score: f64,
+    /// Times it did each kind of wrong, since it was last forgotten.
+    pub(crate) counts: BTreeMap<Misbehaviour, u64>,
+    /// How long it's still banned for, if it is.
+    pub(crate) banned_for: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct PeerRecord {
+    name: Option<XorName>,
+    score: f64,
+    scored_at: Instant,
+    counts: BTreeMap<Misbehaviour, u64>,
+    banned_until: Option<Instant>,
+    // Node msgs received over the current window, and when it started.
+    #[cfg(feature = "back-pressure")]
+    window: Option<(Instant, u64)>,
+}
+
+impl PeerRecord {
+    fn new(now: Instant) -> Self {
+        Self {
+            name: None,
+            score: 0.0,
+            scored_at: now,
+            counts: BTreeMap::new(),
+            banned_until: None,
+            #[cfg(feature = "back-pressure")]
+            window: None,
+        }
+    }
+
+    // The score, decayed up to `now`.
+    fn score(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.scored_at);
+        self.score * 0.5_f64.powf(elapsed.as_secs_f64() / SCORE_HALF_LIFE.as_secs_f64())
+    }
+
+    fn is_banned(&self, now: Instant) -> bool {
+        self.banned_until.is_some_and(|until| until > now)
+    }
+
+    fn is_forgotten(&self, now: Instant) -> bool {
+        !self.is_banned(now) && self.score(now) < FORGOTTEN_SCORE
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    peers: DashMap<SocketAddr, PeerRecord>,
+    // Node msgs per s each peer was last told it could send us, as the bits of an `f64`, 0 if
+    // none was told yet.
+    #[cfg(feature = "back-pressure")]
+    tolerated_msgs_per_s: AtomicU64,
+}
+
+/// Scores of the peers which misbehaved, by address, decaying as time goes by. Peers scoring
+/// high enough are disconnected from, and higher still banned for a while.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Reputation {
+    inner: Arc<Inner>,
+}
+
+impl Reputation {
+    /// Records the peer at `addr` misbehaving, returning what's to be done about it.
+    pub(crate) fn record(
+        &self,
+        addr: SocketAddr,
+        name: Option<XorName>,
+        misbehaviour: Misbehaviour,
+    ) -> Verdict {
+        self.record_at(addr, name, misbehaviour, Instant::now())
+    }
+
+    fn record_at(
+        &self,
+        addr: SocketAddr,
+        name: Option<XorName>,
+        misbehaviour: Misbehaviour,
+        now: Instant,
+    ) -> Verdict {
+        if !self.inner.peers.contains_key(&addr) && self.inner.peers.len() >= MAX_TRACKED_PEERS {
+            return Verdict::Tolerated;
+        }
+        let mut record = self
+            .inner
+            .peers
+            .entry(addr)
+            .or_insert_with(|| PeerRecord::new(now));
+        if name.is_some() {
+            record.name = name;
+        }
+        record.score = record.score(now) + misbehaviour.penalty();
+        record.scored_at = now;
+        *record.counts.entry(misbehaviour).or_default() += 1;
+
+        let verdict = if record.score >= BAN_SCORE {
+            record.banned_until = Some(now + BAN_DURATION);
+            Verdict::Ban
+        } else if record.score >= DISCONNECT_SCORE || record.is_banned(now) {
+            Verdict::Disconnect
+        } else {
+            Verdict::Tolerated
+        };
+        debug!(
+            "{} ({:?}) misbehaved: {}, now scoring {:.1}, {:?}",
+            addr, record.name, misbehaviour, record.score, verdict
+        );
+        verdict
+    }
+
+    /// Whether the peer at `addr` is banned.
+    pub(crate) fn is_banned(&self, addr: &SocketAddr) -> bool {
+        self.inner
+            .peers
+            .get(addr)
+            .is_some_and(|record| record.is_banned(Instant::now()))
+    }
+
+    /// Has the node msgs peers send us checked against the msgs per s they were last told they
+    /// could send.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn set_tolerated_msgs_per_s(&self, msgs_per_s: f64) {
+        self.inner
+            .tolerated_msgs_per_s
+            .store(f64::max(0.0, msgs_per_s).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Counts a node msg received from the peer at `addr`, recording it violating our back
+    /// pressure if it sent us too many over the last window.
+    #[cfg(feature = "back-pressure")]
+    pub(crate) fn count_node_msg(&self, addr: SocketAddr, name: XorName) -> Verdict {
+        self.count_node_msg_at(addr, name, Instant::now())
+    }
+
+    #[cfg(feature = "back-pressure")]
+    fn count_node_msg_at(&self, addr: SocketAddr, name: XorName, now: Instant) -> Verdict {
+        let tolerated = f64::from_bits(self.inner.tolerated_msgs_per_s.load(Ordering::Relaxed));
+        if tolerated <= 0.0
+            || !self.inner.peers.contains_key(&addr) && self.inner.peers.len() >= MAX_TRACKED_PEERS
+        {
+            return Verdict::Tolerated;
+        }
+
+        let received = {
+            let mut record = self
+                .inner
+                .peers
+                .entry(addr)
+                .or_insert_with(|| PeerRecord::new(now));
+            let (since, count) = record.window.get_or_insert((now, 0));
+            *count += 1;
+            let elapsed = now.saturating_duration_since(*since);
+            if elapsed < BACK_PRESSURE_WINDOW {
+                return Verdict::Tolerated;
+            }
+            let received = *count as f64 / elapsed.as_secs_f64();
+            record.window = Some((now, 0));
+            received
+        };
+
+        if received > tolerated * BACK_PRESSURE_SLACK {
+            debug!(
+                "{} sent us {:.1} msgs/s, while told it could send {:.1}",
+                addr, received, tolerated
+            );
+            self.record_at(addr, Some(name), Misbehaviour::BackPressureViolation, now)
+        } else {
+            Verdict::Tolerated
+        }
+    }
+
+    /// The standing of every peer which misbehaved and isn't forgotten yet, worst first.
+    pub(crate) fn scores(&self) -> Vec<PeerReputation> {
+        let now = Instant::now();
+        let mut scores: Vec<_> = self
+            .inner
+            .peers
+            .iter()
+            .filter(|entry| !entry.value().counts.is_empty() && !entry.value().is_forgotten(now))
+            .map(|entry| {
+                let record = entry.value();
+                PeerReputation {
+                    addr: *entry.key(),
+                    name: record.name,
+                    score: record.score(now),
+                    counts: record.counts.clone(),
+                    banned_for: record
+                        .banned_until
+                        .and_then(|until| until.checked_duration_since(now)),
+                }
+            })
+            .collect();
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scores
+    }
+
+    /// Forgets the peers whose score decayed away, and whose ban is over.
+    pub(crate) fn forget_expired(&self) {
+        let now = Instant::now();
+        self.inner
+            .peers
+            .retain(|_, record| !record.is_forgotten(now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn peers_are_disconnected_then_banned_as_they_keep_misbehaving() {
+        let reputation = Reputation::default();
+        let (peer, other) = (addr(1), addr(2));
+        let now = Instant::now();
+
+        let verdicts: Vec<_> = (0..4)
+            .map(|_| reputation.record_at(peer, None, Misbehaviour::InvalidSignature, now))
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![
+                Verdict::Tolerated,
+                Verdict::Disconnect,
+                Verdict::Disconnect,
+                Verdict::Ban
+            ]
+        );
+        assert!(reputation.is_banned(&peer));
+        // Others aren't affected.
+        assert_eq!(
+            reputation.record_at(other, None, Misbehaviour::MalformedMsg, now),
+            Verdict::Tolerated
+        );
+        assert!(!reputation.is_banned(&other));
+
+        let scores = reputation.scores();
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].addr, peer);
+        assert_eq!(scores[0].counts[&Misbehaviour::InvalidSignature], 4);
+        assert!(scores[0].banned_for.is_some());
+    }
+
+    #[test]
+    fn scores_decay_and_bans_expire() {
+        let reputation = Reputation::default();
+        let (peer, name) = (addr(1), xor_name::rand::random());
+        let start = Instant::now();
+
+        let _ = reputation.record_at(peer, Some(name), Misbehaviour::FailedLivenessProbe, start);
+        let _ = reputation.record_at(peer, None, Misbehaviour::FailedLivenessProbe, start);
+        // Halved, so the next one doesn't get it disconnected...
+        let later = start + SCORE_HALF_LIFE;
+        assert_eq!(
+            reputation.record_at(peer, None, Misbehaviour::FailedLivenessProbe, later),
+            Verdict::Tolerated
+        );
+        assert_eq!(
+            reputation.record_at(peer, None, Misbehaviour::FailedLivenessProbe, later),
+            Verdict::Disconnect
+        );
+        // ...and the name is kept from when it was known.
+        assert_eq!(reputation.inner.peers.get(&peer).unwrap().name, Some(name));
+
+        for _ in 0..10 {
+            let _ = reputation.record_at(peer, None, Misbehaviour::InvalidSignature, start);
+        }
+        let record = reputation.inner.peers.get(&peer).unwrap();
+        assert!(record.is_banned(start + BAN_DURATION / 2));
+        assert!(!record.is_banned(start + BAN_DURATION));
+        assert!(record.is_forgotten(start + BAN_DURATION + 20 * SCORE_HALF_LIFE));
+    }
+
+    #[cfg(feature = "back-pressure")]
+    #[test]
+    fn peers_sending_well_over_what_they_were_told_violate_back_pressure() {
+        let reputation = Reputation::default();
+        let (peer, name) = (addr(1), xor_name::rand::random());
+        let start = Instant::now();
+
+        let send = |msgs_per_s: u64, from: Instant| {
+            let msgs = msgs_per_s * BACK_PRESSURE_WINDOW.as_secs();
+            (0..=msgs)
+                .map(|i| {
+                    let at = from + BACK_PRESSURE_WINDOW.mul_f64(i as f64 / msgs as f64);
+                    reputation.count_node_msg_at(peer, name, at)
+                })
+                .filter(|verdict| *verdict != Verdict::Tolerated)
+                .count()
+        };
+
+        // Nothing is violated until peers are told what they can send.
+        assert_eq!(send(1000, start), 0);
+        assert!(reputation.scores().is_empty());
+
+        reputation.set_tolerated_msgs_per_s(10.0);
+        assert_eq!(send(15, start), 0);
+        assert!(reputation.scores().is_empty());
+        let _ = send(30, start + BACK_PRESSURE_WINDOW * 2);
+        assert_eq!(
+            reputation.scores()[0].counts[&Misbehaviour::BackPressureViolation],
+            1
+        );
+    }
+}
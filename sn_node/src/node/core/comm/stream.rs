@@ -0,0 +1,503 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Msgs too large to be sent whole, such as those carrying max-size chunks, are streamed in
+//! frames instead, each sent once the previous one went through, and reassembled by the
+//! recipient, which checks them against the digest of the whole msg before handling it.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tiny_keccak::{Hasher, Sha3};
+use tokio::time::Instant;
+
+/// Size of the msgs above which they're streamed when unspecified, in KiB.
+pub(crate) const DEFAULT_STREAM_THRESHOLD_KB: usize = 512;
+/// Bytes of a msg per frame.
+const FRAME_SIZE: usize = 256 * 1024;
+/// Largest msg reassembled.
+const MAX_STREAMED_LEN: usize = 64 * 1024 * 1024;
+/// Msgs reassembled at once per connection. Starting another drops the oldest.
+const MAX_PARTIAL_PER_CONN: usize = 4;
+/// Bytes of msgs being reassembled buffered per connection, enough for one msg of the
+/// largest size. Frames past it drop the oldest msgs, or the one they're of.
+const MAX_PARTIAL_BYTES_PER_CONN: usize = MAX_STREAMED_LEN;
+/// Bytes of msgs being reassembled buffered over all the connections of a node. Frames past it
+/// drop the msg they're of.
+const MAX_PARTIAL_BYTES_PER_NODE: usize = 256 * 1024 * 1024;
+/// How long a msg being reassembled is kept without any new frame.
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+// Leads every frame. Read as a wire msg header, it claims a length and a version no wire msg
+// has, so that nodes not streaming refuse frames rather than misreading them.
+const MAGIC: [u8; 4] = [0xff; 4];
+// Magic, transfer id, index, count, msg length and msg digest.
+const HEADER_LEN: usize = MAGIC.len() + 8 + 4 + 4 + 8 + 32;
+
+/// Size of the msgs above which they're streamed, 0 if streaming is disabled. Shared by all the
+/// sessions of a `Comm`.
+#[derive(Clone, Debug)]
+pub(crate) struct StreamThreshold(Arc<AtomicUsize>);
+
+impl Default for StreamThreshold {
+    fn default() -> Self {
+        Self(Arc::new(AtomicUsize::new(
+            DEFAULT_STREAM_THRESHOLD_KB * 1024,
+        )))
+    }
+}
+
+impl StreamThreshold {
+    pub(crate) fn set(&self, bytes: usize) {
+        self.0.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Whether a msg of `len` bytes is to be streamed.
+    pub(crate) fn applies_to(&self, len: usize) -> bool {
+        let threshold = self.0.load(Ordering::Relaxed);
+        threshold > 0 && len > threshold
+    }
+}
+
+/// The bytes left for the msgs being reassembled over all the connections of a node, frames
+/// being buffered before their msg is authenticated. Shared by the listeners of a `Comm`.
+#[derive(Clone, Debug)]
+pub(crate) struct ReassemblyBudget(Arc<AtomicUsize>);
+
+impl Default for ReassemblyBudget {
+    fn default() -> Self {
+        Self::new(MAX_PARTIAL_BYTES_PER_NODE)
+    }
+}
+
+impl ReassemblyBudget {
+    fn new(bytes: usize) -> Self {
+        Self(Arc::new(AtomicUsize::new(bytes)))
+    }
+
+    // Takes `bytes` from the budget, unless there aren't that many left.
+    fn reserve(&self, bytes: usize) -> bool {
+        self.0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |left| {
+                left.checked_sub(bytes)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, bytes: usize) {
+        let _prev = self.0.fetch_add(bytes, Ordering::AcqRel);
+    }
+}
+
+fn digest(msg: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3::v256();
+    let mut digest = [0; 32];
+    hasher.update(msg);
+    hasher.finalize(&mut digest);
+    digest
+}
+
+/// The frames a msg is streamed in, made one at a time so that only the one being sent is
+/// held on top of the msg.
+pub(crate) struct Frames {
+    transfer: u64,
+    digest: [u8; 32],
+    msg: Bytes,
+    frame_size: usize,
+    count: u32,
+    next: u32,
+}
+
+impl Frames {
+    pub(crate) fn new(msg: Bytes) -> Self {
+        Self::with_frame_size(msg, FRAME_SIZE)
+    }
+
+    fn with_frame_size(msg: Bytes, frame_size: usize) -> Self {
+        let count = (msg.len() + frame_size - 1) / frame_size;
+        Self {
+            transfer: rand::random(),
+            digest: digest(&msg),
+            frame_size,
+            count: count as u32,
+            next: 0,
+            msg,
+        }
+    }
+
+    pub(crate) fn frame_count(&self) -> usize {
+        self.count as usize
+    }
+}
+
+impl Iterator for Frames {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        if self.next >= self.count {
+            return None;
+        }
+        let start = self.next as usize * self.frame_size;
+        let end = (start + self.frame_size).min(self.msg.len());
+
+        let mut frame = BytesMut::with_capacity(HEADER_LEN + end - start);
+        frame.put_slice(&MAGIC);
+        frame.put_u64(self.transfer);
+        frame.put_u32(self.next);
+        frame.put_u32(self.count);
+        frame.put_u64(self.msg.len() as u64);
+        frame.put_slice(&self.digest);
+        frame.put_slice(&self.msg[start..end]);
+        self.next += 1;
+        Some(frame.freeze())
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    transfer: u64,
+    index: u32,
+    count: u32,
+    len: usize,
+    digest: [u8; 32],
+    data: Bytes,
+}
+
+impl Frame {
+    // `None` if `bytes` aren't a frame, `Some(Err)` if they're a malformed one.
+    fn parse(bytes: &Bytes) -> Option<Result<Self, &'static str>> {
+        if !bytes.starts_with(&MAGIC) {
+            return None;
+        }
+        if bytes.len() < HEADER_LEN {
+            return Some(Err("truncated header"));
+        }
+        let u32_at = |at: usize| {
+            let mut be = [0; 4];
+            be.copy_from_slice(&bytes[at..at + 4]);
+            u32::from_be_bytes(be)
+        };
+        let u64_at = |at: usize| {
+            let mut be = [0; 8];
+            be.copy_from_slice(&bytes[at..at + 8]);
+            u64::from_be_bytes(be)
+        };
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&bytes[28..HEADER_LEN]);
+        let frame = Self {
+            transfer: u64_at(4),
+            index: u32_at(12),
+            count: u32_at(16),
+            len: u64_at(20) as usize,
+            digest,
+            data: bytes.slice(HEADER_LEN..),
+        };
+        if frame.len > MAX_STREAMED_LEN {
+            Some(Err("msg too large"))
+        } else if frame.index >= frame.count {
+            Some(Err("index out of range"))
+        } else if frame.data.is_empty() || frame.count as usize > frame.len {
+            Some(Err("more frames than the msg has bytes"))
+        } else {
+            Some(Ok(frame))
+        }
+    }
+}
+
+// A msg of which some frames arrived.
+#[derive(Debug)]
+struct Partial {
+    count: u32,
+    len: usize,
+    digest: [u8; 32],
+    frames: BTreeMap<u32, Bytes>,
+    received: usize,
+    last_frame: Instant,
+}
+
+/// Reassembly of the msgs streamed over a connection.
+///
+/// The frames buffered are bounded per connection, by count of msgs and by bytes, and per node
+/// by the `ReassemblyBudget`, whose bytes are given back as msgs complete, are dropped, or the
+/// connection closes.
+#[derive(Debug)]
+pub(crate) struct Reassembly {
+    partial: BTreeMap<u64, Partial>,
+    budget: ReassemblyBudget,
+    conn_limit: usize,
+    buffered: usize,
+}
+
+impl Drop for Reassembly {
+    fn drop(&mut self) {
+        self.budget.release(self.buffered);
+    }
+}
+
+impl Reassembly {
+    pub(crate) fn new(budget: ReassemblyBudget) -> Self {
+        Self {
+            partial: BTreeMap::new(),
+            budget,
+            conn_limit: MAX_PARTIAL_BYTES_PER_CONN,
+            buffered: 0,
+        }
+    }
+
+    /// When the msgs being reassembled are to be dropped if no frame comes in by then, if
+    /// there are any.
+    pub(crate) fn stale_at(&self) -> Option<Instant> {
+        self.partial
+            .values()
+            .map(|partial| partial.last_frame + STALE_AFTER)
+            .min()
+    }
+
+    /// Drops the msgs being reassembled which got no frame for too long.
+    pub(crate) fn drop_stale(&mut self) {
+        let stale = self
+            .partial
+            .iter()
+            .filter(|(_, partial)| partial.last_frame.elapsed() >= STALE_AFTER)
+            .map(|(transfer, _)| *transfer)
+            .collect::<Vec<_>>();
+        for transfer in stale {
+            debug!("Dropping streamed msg {}, it went idle", transfer);
+            let _dropped = self.remove(transfer);
+        }
+    }
+
+    // Drops the oldest msg being reassembled, other than `keep`.
+    fn drop_oldest(&mut self, keep: u64) -> bool {
+        let oldest = self
+            .partial
+            .iter()
+            .filter(|(transfer, _)| **transfer != keep)
+            .min_by_key(|(_, partial)| partial.last_frame)
+            .map(|(transfer, _)| *transfer);
+        match oldest {
+            Some(oldest) => {
+                debug!("Dropping streamed msg {} to make room for another", oldest);
+                let _dropped = self.remove(oldest);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Removes a msg being reassembled, giving its bytes back.
+    fn remove(&mut self, transfer: u64) -> Option<Partial> {
+        let partial = self.partial.remove(&transfer)?;
+        self.buffered -= partial.received;
+        self.budget.release(partial.received);
+        Some(partial)
+    }
+
+    /// Takes in what was received over the connection: a msg sent whole is handed back as it
+    /// is, a frame is buffered, handing back the msg it completes, if any, once checked.
+    pub(crate) fn receive(&mut self, bytes: Bytes) -> Option<Bytes> {
+        let frame = match Frame::parse(&bytes) {
+            None => return Some(bytes),
+            Some(Ok(frame)) => frame,
+            Some(Err(error)) => {
+                debug!("Dropping a malformed frame: {}", error);
+                return None;
+            }
+        };
+
+        self.drop_stale();
+        let transfer = frame.transfer;
+        if !self.partial.contains_key(&transfer) && self.partial.len() >= MAX_PARTIAL_PER_CONN {
+            let _dropped = self.drop_oldest(transfer);
+        }
+
+        let partial = self.partial.entry(transfer).or_insert_with(|| Partial {
+            count: frame.count,
+            len: frame.len,
+            digest: frame.digest,
+            frames: BTreeMap::new(),
+            received: 0,
+            last_frame: Instant::now(),
+        });
+        if (partial.count, partial.len, partial.digest) != (frame.count, frame.len, frame.digest)
+            || partial.received + frame.data.len() > partial.len
+        {
+            debug!("Dropping streamed msg {}, its frames disagree", transfer);
+            let _dropped = self.remove(transfer);
+            return None;
+        }
+        partial.last_frame = Instant::now();
+        if partial.frames.contains_key(&frame.index) {
+            return None;
+        }
+
+        let len = frame.data.len();
+        while self.buffered + len > self.conn_limit {
+            if !self.drop_oldest(transfer) {
+                debug!(
+                    "Dropping streamed msg {}, over the bytes buffered per connection",
+                    transfer
+                );
+                let _dropped = self.remove(transfer);
+                return None;
+            }
+        }
+        if !self.budget.reserve(len) {
+            debug!(
+                "Dropping streamed msg {}, over the bytes buffered per node",
+                transfer
+            );
+            let _dropped = self.remove(transfer);
+            return None;
+        }
+        self.buffered += len;
+        let partial = self.partial.get_mut(&transfer)?;
+        let _none = partial.frames.insert(frame.index, frame.data);
+        partial.received += len;
+        if partial.frames.len() < partial.count as usize {
+            return None;
+        }
+
+        let partial = self.remove(transfer)?;
+        let mut msg = BytesMut::with_capacity(partial.len);
+        for data in partial.frames.values() {
+            msg.put_slice(data);
+        }
+        if msg.len() != partial.len || digest(&msg) != partial.digest {
+            warn!(
+                "Dropping streamed msg {}, it doesn't match its digest",
+                transfer
+            );
+            return None;
+        }
+        Some(msg.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_interface::types::utils::random_bytes;
+
+    #[test]
+    fn msgs_are_reassembled_from_their_frames_in_any_order() {
+        let msg = random_bytes(10 * 1024 + 7);
+        let mut frames = Frames::with_frame_size(msg.clone(), 1024).collect::<Vec<_>>();
+        assert_eq!(frames.len(), 11);
+        frames.reverse();
+
+        let mut reassembly = Reassembly::new(ReassemblyBudget::default());
+        let last = frames.pop();
+        for frame in frames {
+            assert_eq!(reassembly.receive(frame), None);
+        }
+        assert_eq!(last.and_then(|frame| reassembly.receive(frame)), Some(msg));
+
+        // Msgs sent whole go straight through.
+        let whole = random_bytes(100);
+        assert_eq!(reassembly.receive(whole.clone()), Some(whole));
+    }
+
+    #[test]
+    fn corrupt_frames_fail_the_integrity_check() {
+        let msg = random_bytes(4 * 1024);
+        let mut frames = Frames::with_frame_size(msg, 1024).collect::<Vec<_>>();
+        let mut corrupt = frames[1].to_vec();
+        if let Some(last) = corrupt.last_mut() {
+            *last ^= 1;
+        }
+        frames[1] = Bytes::from(corrupt);
+
+        let mut reassembly = Reassembly::new(ReassemblyBudget::default());
+        assert!(frames
+            .into_iter()
+            .all(|frame| reassembly.receive(frame).is_none()));
+        assert!(reassembly.partial.is_empty());
+    }
+
+    #[test]
+    fn only_so_many_msgs_are_reassembled_at_once() {
+        let mut reassembly = Reassembly::new(ReassemblyBudget::default());
+        let firsts = (0..=MAX_PARTIAL_PER_CONN)
+            .map(|_| Frames::with_frame_size(random_bytes(2048), 1024))
+            .collect::<Vec<_>>();
+        for mut frames in firsts {
+            assert_eq!(frames.next().and_then(|f| reassembly.receive(f)), None);
+        }
+        assert_eq!(reassembly.partial.len(), MAX_PARTIAL_PER_CONN);
+    }
+
+    #[test]
+    fn frames_buffered_are_bounded_per_connection_and_per_node() {
+        let budget = ReassemblyBudget::new(6 * 1024);
+        let mut first = Reassembly::new(budget.clone());
+        first.conn_limit = 4 * 1024;
+
+        // Past the connection's bytes, the oldest msgs are dropped for the newest.
+        let mut old = Frames::with_frame_size(random_bytes(4 * 1024), 1024);
+        for frame in old.by_ref().take(3) {
+            assert_eq!(first.receive(frame), None);
+        }
+        let mut new = Frames::with_frame_size(random_bytes(4 * 1024), 1024);
+        for frame in new.by_ref().take(2) {
+            assert_eq!(first.receive(frame), None);
+        }
+        assert_eq!(first.partial.len(), 1);
+        assert_eq!(first.buffered, 2 * 1024);
+
+        // Past the node's bytes, the msgs of other connections are left be.
+        let mut second = Reassembly::new(budget.clone());
+        let mut other = Frames::with_frame_size(random_bytes(8 * 1024), 1024);
+        for frame in other.by_ref().take(4) {
+            assert_eq!(second.receive(frame), None);
+        }
+        assert_eq!(other.next().and_then(|f| second.receive(f)), None);
+        assert!(second.partial.is_empty());
+        assert_eq!(first.partial.len(), 1);
+
+        // Bytes are given back as msgs complete or connections close.
+        let msg = random_bytes(3 * 1024);
+        let frames = Frames::with_frame_size(msg.clone(), 1024).collect::<Vec<_>>();
+        let last = frames.into_iter().map(|f| second.receive(f)).last();
+        assert_eq!(last, Some(Some(msg)));
+        drop(first);
+        assert!(budget.reserve(6 * 1024));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_msgs_are_dropped() {
+        let budget = ReassemblyBudget::new(4 * 1024);
+        let mut reassembly = Reassembly::new(budget.clone());
+        assert_eq!(reassembly.stale_at(), None);
+        let mut frames = Frames::with_frame_size(random_bytes(4 * 1024), 1024);
+        assert_eq!(frames.next().and_then(|f| reassembly.receive(f)), None);
+        let stale_at = reassembly.stale_at();
+        assert_eq!(stale_at, Some(Instant::now() + STALE_AFTER));
+
+        tokio::time::sleep(STALE_AFTER).await;
+        reassembly.drop_stale();
+        assert!(reassembly.partial.is_empty());
+        assert_eq!(reassembly.stale_at(), None);
+        assert!(budget.reserve(4 * 1024));
+    }
+
+    #[test]
+    fn threshold_decides_what_is_streamed() {
+        let threshold = StreamThreshold::default();
+        assert!(!threshold.applies_to(DEFAULT_STREAM_THRESHOLD_KB * 1024));
+        assert!(threshold.applies_to(DEFAULT_STREAM_THRESHOLD_KB * 1024 + 1));
+        threshold.set(0);
+        assert!(!threshold.applies_to(usize::MAX));
+    }
+}
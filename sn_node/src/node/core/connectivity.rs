@@ -48,18 +48,24 @@ impl Node {
             .filter(|peer| !names.contains(&peer.name()))
             .cloned()
             .collect();
-        let mut result: Vec<Cmd> = Vec::new();
+        let restarting = self.planned_restarts.pending().await;
+        let mut proposals = vec![];
         for name in names.iter() {
+            if restarting.contains(name) {
+                debug!(
+                    "Not proposing {} offline, it's within its restart window",
+                    name
+                );
+                continue;
+            }
             if let Some(info) = self.network_knowledge.get_section_member(name).await {
-                let info = info.leave()?;
-                if let Ok(cmds) = self
-                    .send_proposal(elders.clone(), Proposal::Offline(info))
-                    .await
-                {
-                    result.extend(cmds);
-                }
+                proposals.push(Proposal::Offline(info.leave()?));
             }
         }
-        Ok(result)
+        // Batched, for a single round of sig shares however many nodes went offline.
+        Ok(self
+            .send_proposals(elders, proposals)
+            .await
+            .unwrap_or_default())
     }
 }
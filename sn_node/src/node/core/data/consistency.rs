@@ -0,0 +1,235 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Writes of clients we sent on to adults, until the adults confirm storing them, and the
+//! consistent queries held on them meanwhile. Writes are indexed in the order we applied them,
+//! a query being held until no write up to the index of its token awaits confirmation.
+
+use crate::dbs::serialise;
+
+use sn_interface::types::ReplicatedData;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+// Writes not confirmed by all their holders within this time are given up on, for an adult
+// gone missing not to hold queries on them for good.
+const WRITE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+// Queries are held at most this long, clients having retried them by then anyway.
+const MAX_HOLD: Duration = Duration::from_secs(20);
+// This prevents the writes awaiting confirmation from growing unbound, the oldest being given
+// up on past it...
+const MAX_PENDING_WRITES: usize = 10_000;
+// ...and the queries held, those past it being dropped.
+const MAX_HELD_QUERIES: usize = 1_000;
+
+/// Id of a replica of data, for adults to confirm storing that very write: the name of a chunk,
+/// the hash of a register op.
+pub(crate) fn replica_id(data: &ReplicatedData) -> Option<XorName> {
+    match data {
+        ReplicatedData::Chunk(chunk) => Some(*chunk.name()),
+        data => serialise(data)
+            .ok()
+            .map(|bytes| XorName::from_content(&bytes)),
+    }
+}
+
+#[derive(Debug)]
+struct Write {
+    replica: XorName,
+    holders: BTreeSet<XorName>,
+    since: Instant,
+}
+
+#[derive(Debug)]
+struct Held<Q> {
+    index: u64,
+    query: Q,
+    since: Instant,
+}
+
+#[derive(Debug)]
+struct Inner<Q> {
+    // Index of the last write applied.
+    last: u64,
+    pending: BTreeMap<u64, Write>,
+    held: Vec<Held<Q>>,
+}
+
+/// Writes awaiting confirmation from their holders, and the queries held on them.
+#[derive(Clone, Debug)]
+pub(crate) struct ConsistentReads<Q>(Arc<Mutex<Inner<Q>>>);
+
+impl<Q> Default for ConsistentReads<Q> {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            last: 0,
+            pending: BTreeMap::new(),
+            held: vec![],
+        })))
+    }
+}
+
+impl<Q> ConsistentReads<Q> {
+    /// Records a write of the replica `replica` sent to `holders`, returning its index. Writes
+    /// with no holders to confirm them are caught up on straight away.
+    pub(crate) fn record_write(&self, replica: XorName, holders: BTreeSet<XorName>) -> u64 {
+        self.record_write_at(replica, holders, Instant::now())
+    }
+
+    fn record_write_at(&self, replica: XorName, holders: BTreeSet<XorName>, now: Instant) -> u64 {
+        let mut inner = self.lock();
+        inner.sweep(now);
+        inner.last += 1;
+        let index = inner.last;
+        if !holders.is_empty() {
+            if inner.pending.len() >= MAX_PENDING_WRITES {
+                let _oldest = inner.pending.pop_first();
+            }
+            let _prev = inner.pending.insert(
+                index,
+                Write {
+                    replica,
+                    holders,
+                    since: now,
+                },
+            );
+        }
+        index
+    }
+
+    /// Records `holder` confirming storing `replicas`, returning the queries held which are
+    /// caught up on now.
+    pub(crate) fn record_stored(&self, holder: XorName, replicas: &BTreeSet<XorName>) -> Vec<Q> {
+        self.record_stored_at(holder, replicas, Instant::now())
+    }
+
+    fn record_stored_at(
+        &self,
+        holder: XorName,
+        replicas: &BTreeSet<XorName>,
+        now: Instant,
+    ) -> Vec<Q> {
+        let mut inner = self.lock();
+        inner.pending.retain(|_, write| {
+            if replicas.contains(&write.replica) {
+                let _removed = write.holders.remove(&holder);
+            }
+            !write.holders.is_empty()
+        });
+        inner.sweep(now);
+
+        let (released, held) = std::mem::take(&mut inner.held)
+            .into_iter()
+            .partition(|held| inner.caught_up(held.index));
+        inner.held = held;
+        released
+            .into_iter()
+            .map(|held: Held<Q>| held.query)
+            .collect()
+    }
+
+    /// Holds `query` until the writes up to `index` are confirmed, giving it back if they are
+    /// already, for it to be answered now. Queries beyond what's held at most are dropped.
+    pub(crate) fn hold(&self, index: u64, query: Q) -> Option<Q> {
+        self.hold_at(index, query, Instant::now())
+    }
+
+    fn hold_at(&self, index: u64, query: Q, now: Instant) -> Option<Q> {
+        let mut inner = self.lock();
+        inner.sweep(now);
+        if inner.caught_up(index) {
+            return Some(query);
+        }
+        if inner.held.len() < MAX_HELD_QUERIES {
+            inner.held.push(Held {
+                index,
+                query,
+                since: now,
+            });
+        } else {
+            warn!("Held queries limit reached, dropping query awaiting write {index}");
+        }
+        None
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<Q>> {
+        // The lock is only held for updates of the records, which don't panic.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<Q> Inner<Q> {
+    fn caught_up(&self, index: u64) -> bool {
+        self.pending.range(..=index).next().is_none()
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        self.pending
+            .retain(|_, write| now.saturating_duration_since(write.since) < WRITE_CONFIRM_TIMEOUT);
+        self.held
+            .retain(|held| now.saturating_duration_since(held.since) < MAX_HOLD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xor_name::rand::random as random_name;
+
+    #[test]
+    fn queries_are_held_until_every_write_up_to_their_token_is_confirmed() {
+        let reads = ConsistentReads::default();
+        let start = Instant::now();
+        let (adult_a, adult_b) = (random_name(), random_name());
+        let (first, second) = (random_name(), random_name());
+
+        let holders = BTreeSet::from([adult_a, adult_b]);
+        assert_eq!(reads.record_write_at(first, holders.clone(), start), 1);
+        assert_eq!(reads.record_write_at(second, holders, start), 2);
+
+        // Reads of the first write are held on it, those of the second on both.
+        assert_eq!(reads.hold_at(1, "first", start), None);
+        assert_eq!(reads.hold_at(2, "second", start), None);
+
+        let stored =
+            |adult, replica| reads.record_stored_at(adult, &BTreeSet::from([replica]), start);
+        assert!(stored(adult_a, first).is_empty());
+        assert_eq!(stored(adult_b, first), vec!["first"]);
+        assert!(stored(adult_b, second).is_empty());
+        assert_eq!(stored(adult_a, second), vec!["second"]);
+
+        // Once caught up, queries aren't held anymore.
+        assert_eq!(reads.hold_at(2, "again", start), Some("again"));
+    }
+
+    #[test]
+    fn writes_unconfirmed_for_too_long_are_given_up_on() {
+        let reads = ConsistentReads::default();
+        let start = Instant::now();
+        let index = reads.record_write_at(random_name(), BTreeSet::from([random_name()]), start);
+
+        assert_eq!(reads.hold_at(index, "held", start), None);
+        // Queries aren't held past their limit...
+        let later = start + MAX_HOLD;
+        assert!(reads
+            .record_stored_at(random_name(), &BTreeSet::new(), later)
+            .is_empty());
+        assert_eq!(reads.hold_at(index, "again", later), None);
+        // ...nor on writes past theirs.
+        let much_later = start + WRITE_CONFIRM_TIMEOUT;
+        assert_eq!(reads.hold_at(index, "last", much_later), Some("last"));
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use sn_interface::{
+    messaging::{
+        data::{DataQuery, QueryResponse, ServiceMsg},
+        DstLocation, EndUser, MsgId, WireMsg,
+    },
+    types::{Chunk, Peer},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Mutex, time::Instant};
+use xor_name::XorName;
+
+/// Size of the data cache when unspecified, in MiB.
+pub(crate) const DEFAULT_DATA_CACHE_MB: usize = 64;
+/// How long chunks are cached for when unspecified.
+pub(crate) const DEFAULT_DATA_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How elders cache the chunks they relay to clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheOptions {
+    /// Whether chunks are cached, and queries for them answered from the cache.
+    pub enabled: bool,
+    /// Bytes of chunks cached at most, the least recently used being evicted past it.
+    pub max_bytes: usize,
+    /// How long a chunk is cached for.
+    pub ttl: Duration,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: DEFAULT_DATA_CACHE_MB * 1024 * 1024,
+            ttl: DEFAULT_DATA_CACHE_TTL,
+        }
+    }
+}
+
+/// What the data cache holds and how it's been of use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Queries answered from the cache.
+    pub hits: u64,
+    /// Queries for chunks not cached, or no longer.
+    pub misses: u64,
+    /// Chunks evicted to make room for others.
+    pub evictions: u64,
+    /// Chunks cached.
+    pub entries: usize,
+    /// Bytes of the chunks cached.
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    chunk: Chunk,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    options: CacheOptions,
+    entries: HashMap<XorName, Entry>,
+    // Names of the entries by when they were last used, the least recently first.
+    by_use: BTreeMap<u64, XorName>,
+    uses: u64,
+    stats: CacheStats,
+}
+
+/// Elders' cache of the chunks they relay from their adults to clients, for queries of popular
+/// chunks to be answered without querying the adults again.
+///
+/// Chunks are immutable and named after their content, so whatever is cached under a name is
+/// what the adults would answer with, for as long as it's kept.
+#[derive(Clone, Default)]
+pub(crate) struct DataCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl DataCache {
+    /// Changes how chunks are cached. Disabling the cache empties it, shrinking it evicts the
+    /// least recently used chunks.
+    pub(crate) async fn set_options(&self, options: CacheOptions) {
+        let mut inner = self.inner.lock().await;
+        info!("Data cache options set to {:?}", options);
+        inner.options = options;
+        if !options.enabled {
+            inner.entries.clear();
+            inner.by_use.clear();
+            inner.stats.entries = 0;
+            inner.stats.bytes = 0;
+        }
+        inner.evict_past(options.max_bytes);
+    }
+
+    pub(crate) async fn options(&self) -> CacheOptions {
+        self.inner.lock().await.options
+    }
+
+    pub(crate) async fn stats(&self) -> CacheStats {
+        self.inner.lock().await.stats
+    }
+
+    /// The chunk named `name`, if it's cached and fresh.
+    pub(crate) async fn get(&self, name: &XorName) -> Option<Chunk> {
+        let mut inner = self.inner.lock().await;
+        if !inner.options.enabled {
+            return None;
+        }
+        let ttl = inner.options.ttl;
+        let (cached_at, last_used) = match inner.entries.get(name) {
+            Some(entry) => (entry.cached_at, entry.last_used),
+            None => {
+                inner.stats.misses += 1;
+                return None;
+            }
+        };
+        if cached_at.elapsed() > ttl {
+            inner.remove(name);
+            inner.stats.misses += 1;
+            return None;
+        }
+
+        let _prev = inner.by_use.remove(&last_used);
+        let used = inner.next_use();
+        let _prev = inner.by_use.insert(used, *name);
+        inner.stats.hits += 1;
+        let entry = inner.entries.get_mut(name)?;
+        entry.last_used = used;
+        Some(entry.chunk.clone())
+    }
+
+    /// Caches `chunk`, evicting the least recently used chunks to make room for it.
+    pub(crate) async fn insert(&self, chunk: Chunk) {
+        let mut inner = self.inner.lock().await;
+        let options = inner.options;
+        if !options.enabled || chunk.payload_size() > options.max_bytes {
+            return;
+        }
+
+        let name = *chunk.name();
+        inner.remove(&name);
+        let used = inner.next_use();
+        let _prev = inner.by_use.insert(used, name);
+        inner.stats.entries += 1;
+        inner.stats.bytes += chunk.payload_size();
+        let _prev = inner.entries.insert(
+            name,
+            Entry {
+                chunk,
+                cached_at: Instant::now(),
+                last_used: used,
+            },
+        );
+        inner.evict_past(options.max_bytes);
+    }
+}
+
+impl Inner {
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+
+    fn remove(&mut self, name: &XorName) {
+        if let Some(entry) = self.entries.remove(name) {
+            let _prev = self.by_use.remove(&entry.last_used);
+            self.stats.entries -= 1;
+            self.stats.bytes -= entry.chunk.payload_size();
+        }
+    }
+
+    fn evict_past(&mut self, max_bytes: usize) {
+        while self.stats.bytes > max_bytes {
+            let name = match self.by_use.values().next() {
+                Some(name) => *name,
+                None => break,
+            };
+            self.remove(&name);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+impl Node {
+    /// Answers a client's query for a chunk from our data cache, as an elder, if it's cached.
+    pub(crate) async fn answer_from_cache(
+        &self,
+        query: &DataQuery,
+        origin: Peer,
+    ) -> Result<Option<Vec<Cmd>>> {
+        let address = match query {
+            DataQuery::GetChunk(address) => address,
+            _ => return Ok(None),
+        };
+        let chunk = match self.data_cache.get(address.name()).await {
+            Some(chunk) => chunk,
+            None => return Ok(None),
+        };
+        trace!("Answering query for chunk {:?} from our cache", address);
+
+        let msg = ServiceMsg::QueryResponse {
+            response: QueryResponse::GetChunk(Ok(chunk)),
+            correlation_id: MsgId::from_xor_name(*address.name()),
+        };
+        let (msg_kind, payload) = self.ed_sign_client_msg(&msg).await?;
+        let dst = DstLocation::EndUser(EndUser(origin.name()));
+        let wire_msg = WireMsg::new_msg(MsgId::new(), payload, msg_kind, dst)?;
+        Ok(Some(vec![Cmd::SendMsg {
+            recipients: vec![origin],
+            wire_msg,
+        }]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_interface::types::utils::random_bytes;
+
+    fn chunk(len: usize) -> Chunk {
+        Chunk::new(random_bytes(len))
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_chunks_are_evicted_past_the_size_bound() {
+        let cache = DataCache::default();
+        cache
+            .set_options(CacheOptions {
+                max_bytes: 3 * 1024,
+                ..CacheOptions::default()
+            })
+            .await;
+        let [a, b, c, d] = [0; 4].map(|_| chunk(1024));
+        for chunk in [&a, &b, &c] {
+            cache.insert(chunk.clone()).await;
+        }
+        // Using a makes b the least recently used.
+        assert_eq!(cache.get(a.name()).await, Some(a.clone()));
+        cache.insert(d.clone()).await;
+
+        assert_eq!(cache.get(b.name()).await, None);
+        for chunk in [&a, &c, &d] {
+            assert_eq!(cache.get(chunk.name()).await.as_ref(), Some(chunk));
+        }
+        let stats = cache.stats().await;
+        assert_eq!((stats.entries, stats.bytes), (3, 3 * 1024));
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (4, 1, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chunks_expire_after_their_ttl() {
+        let cache = DataCache::default();
+        let chunk = chunk(100);
+        cache.insert(chunk.clone()).await;
+        assert!(cache.get(chunk.name()).await.is_some());
+
+        tokio::time::advance(DEFAULT_DATA_CACHE_TTL + Duration::from_secs(1)).await;
+        assert_eq!(cache.get(chunk.name()).await, None);
+        assert_eq!(cache.stats().await.entries, 0);
+    }
+
+    #[tokio::test]
+    async fn disabling_the_cache_empties_it() {
+        let cache = DataCache::default();
+        let chunk = chunk(100);
+        cache.insert(chunk.clone()).await;
+
+        let disabled = CacheOptions {
+            enabled: false,
+            ..CacheOptions::default()
+        };
+        cache.set_options(disabled).await;
+        assert_eq!(cache.get(chunk.name()).await, None);
+        cache.insert(chunk.clone()).await;
+        assert_eq!(cache.stats().await.entries, 0);
+
+        cache.set_options(CacheOptions::default()).await;
+        cache.insert(chunk.clone()).await;
+        assert_eq!(cache.get(chunk.name()).await, Some(chunk));
+    }
+}
@@ -0,0 +1,460 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Erasure coding of large chunks: rather than each of the holders of a chunk storing it in
+//! full, elders split it into `DATA_SHARES` shares of its content and `PARITY_SHARES` of parity,
+//! each stored by a distinct adult, and rebuild it from any `DATA_SHARES` of them on reads. This
+//! takes 1.5 times the size of the chunk across the section rather than `data_copy_count()`
+//! times, while still surviving the loss of `PARITY_SHARES` holders.
+
+use crate::node::{
+    api::cmds::Cmd,
+    core::{Node, DATA_QUERY_TIMEOUT},
+    Error, Result,
+};
+
+use bytes::Bytes;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sn_interface::messaging::{
+    data::{chunk_operation_id, QueryResponse},
+    system::{NodeCmd, SystemMsg},
+    DstLocation, MsgId,
+};
+use sn_interface::types::{Chunk, ChunkAddress, ChunkShare, PublicKey};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// Shares holding the content of a chunk, any this many of its shares rebuilding it.
+pub(crate) const DATA_SHARES: u8 = 8;
+/// Shares holding parity, as many of the holders of a chunk's shares being lost without it.
+pub(crate) const PARITY_SHARES: u8 = 4;
+/// Shares a chunk is split into, each stored by a distinct adult.
+pub(crate) const TOTAL_SHARES: usize = DATA_SHARES as usize + PARITY_SHARES as usize;
+
+/// Splits `chunk` into `data_shares` shares of its content, the last one padded with zeros,
+/// and `parity_shares` of parity.
+pub(crate) fn encode(chunk: &Chunk, data_shares: u8, parity_shares: u8) -> Result<Vec<ChunkShare>> {
+    let codec = codec(data_shares, parity_shares)?;
+    let content = chunk.value();
+    let chunk_len = u32::try_from(content.len()).map_err(|_| {
+        Error::ErasureCoding(format!("chunk {:?} is too large to code", chunk.name()))
+    })?;
+
+    let data_shares_len = usize::from(data_shares);
+    let share_len = ((content.len() + data_shares_len - 1) / data_shares_len).max(1);
+    let mut shards: Vec<Vec<u8>> = content.chunks(share_len).map(<[u8]>::to_vec).collect();
+    shards.resize(data_shares_len + usize::from(parity_shares), vec![]);
+    for shard in &mut shards {
+        shard.resize(share_len, 0);
+    }
+    codec.encode(&mut shards).map_err(coding_error)?;
+
+    Ok(shards
+        .into_iter()
+        .enumerate()
+        .map(|(index, bytes)| ChunkShare {
+            chunk: *chunk.name(),
+            index: index as u8,
+            data_shares,
+            parity_shares,
+            chunk_len,
+            bytes: Bytes::from(bytes),
+        })
+        .collect())
+}
+
+/// Rebuilds a chunk from `shares`, of which at least `data_shares` of distinct indexes are to be
+/// of the same coding as the first one, those which aren't being ignored. Fails unless the chunk
+/// rebuilt is the one the shares are of.
+pub(crate) fn decode(shares: &[ChunkShare]) -> Result<Chunk> {
+    let first = shares
+        .first()
+        .ok_or_else(|| Error::ErasureCoding("no shares to rebuild a chunk from".to_string()))?;
+    let codec = codec(first.data_shares, first.parity_shares)?;
+
+    let mut shards: Vec<Option<Vec<u8>>> = vec![None; first.total_shares()];
+    for share in shares.iter().filter(|share| share.matches(first)) {
+        if let Some(shard) = shards.get_mut(usize::from(share.index)) {
+            *shard = Some(share.bytes.to_vec());
+        }
+    }
+    codec.reconstruct_data(&mut shards).map_err(coding_error)?;
+
+    let chunk_len = first.chunk_len as usize;
+    let mut content = Vec::with_capacity(chunk_len);
+    for shard in shards
+        .into_iter()
+        .take(usize::from(first.data_shares))
+        .flatten()
+    {
+        content.extend(shard);
+    }
+    content.truncate(chunk_len);
+
+    let chunk = Chunk::new(Bytes::from(content));
+    if *chunk.name() != first.chunk {
+        return Err(Error::ErasureCoding(format!(
+            "shares of chunk {:?} rebuilt another one, {:?}",
+            first.chunk,
+            chunk.name()
+        )));
+    }
+    Ok(chunk)
+}
+
+fn codec(data_shares: u8, parity_shares: u8) -> Result<ReedSolomon> {
+    ReedSolomon::new(usize::from(data_shares), usize::from(parity_shares)).map_err(coding_error)
+}
+
+fn coding_error(error: reed_solomon_erasure::Error) -> Error {
+    Error::ErasureCoding(format!("{error:?}"))
+}
+
+#[derive(Debug)]
+struct Rebuild {
+    started: Instant,
+    shares: BTreeMap<u8, ChunkShare>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    // Holders of the shares of each chunk coded, by share index.
+    holders: BTreeMap<XorName, Vec<XorName>>,
+    // Chunks being rebuilt for reads, with the shares received so far.
+    rebuilds: BTreeMap<XorName, Rebuild>,
+}
+
+/// Elders' erasure coding of the chunks they're given to store past a threshold: the holders
+/// of the shares of those coded, and the rebuilds of them underway.
+///
+/// Which adults hold the shares of a chunk is only known to the elders which coded it, so a
+/// chunk coded before an elder joined, or restarted, isn't found through it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ErasureCoding {
+    inner: Arc<RwLock<Inner>>,
+    // Bytes from which chunks are coded, 0 coding none.
+    threshold: Arc<AtomicUsize>,
+}
+
+impl ErasureCoding {
+    /// Sets the size from which chunks are coded, `None` having them all replicated in full.
+    pub(crate) fn set_threshold(&self, threshold: Option<usize>) {
+        self.threshold
+            .store(threshold.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Whether `chunk` is to be coded rather than replicated in full.
+    pub(crate) fn should_code(&self, chunk: &Chunk) -> bool {
+        let threshold = self.threshold.load(Ordering::Relaxed);
+        threshold > 0 && chunk.value().len() >= threshold
+    }
+
+    /// Records `holders` as holding the shares of the chunk `name`, by share index.
+    pub(crate) async fn record_holders(&self, name: XorName, holders: Vec<XorName>) {
+        let _prev = self.inner.write().await.holders.insert(name, holders);
+    }
+
+    /// The holders of the shares of the chunk `name`, if it was coded.
+    pub(crate) async fn holders_of(&self, name: &XorName) -> Option<BTreeSet<XorName>> {
+        self.inner
+            .read()
+            .await
+            .holders
+            .get(name)
+            .map(|holders| holders.iter().copied().collect())
+    }
+
+    /// Starts rebuilding the chunk `name`, unless that's underway already, rebuilds timed out
+    /// being dropped.
+    pub(crate) async fn start_rebuild(&self, name: XorName, now: Instant) -> bool {
+        let mut inner = self.inner.write().await;
+        inner
+            .rebuilds
+            .retain(|_, rebuild| now.duration_since(rebuild.started) < DATA_QUERY_TIMEOUT);
+        if inner.rebuilds.contains_key(&name) {
+            return false;
+        }
+        let _prev = inner.rebuilds.insert(
+            name,
+            Rebuild {
+                started: now,
+                shares: BTreeMap::new(),
+            },
+        );
+        true
+    }
+
+    /// Adds `shares` to the rebuild of the chunk `name`, returning the shares to rebuild it
+    /// from, and so ending the rebuild, once there are enough of them.
+    pub(crate) async fn add_shares(
+        &self,
+        name: XorName,
+        shares: Vec<ChunkShare>,
+    ) -> Option<Vec<ChunkShare>> {
+        let mut inner = self.inner.write().await;
+        let rebuild = inner.rebuilds.get_mut(&name)?;
+        for share in shares {
+            let matches = rebuild
+                .shares
+                .values()
+                .next()
+                .map_or(true, |first| first.matches(&share));
+            if share.chunk == name && matches {
+                let _prev = rebuild.shares.insert(share.index, share);
+            }
+        }
+
+        let enough = rebuild.shares.values().next().map_or(false, |first| {
+            rebuild.shares.len() >= usize::from(first.data_shares)
+        });
+        if !enough {
+            return None;
+        }
+        inner
+            .rebuilds
+            .remove(&name)
+            .map(|rebuild| rebuild.shares.into_values().collect())
+    }
+}
+
+impl Node {
+    /// Splits `chunk` into shares, each sent to a distinct adult of those closest to it, if it's
+    /// large enough to be coded and we have enough adults to take its shares. Returns `None` for
+    /// it to be replicated in full otherwise.
+    pub(crate) async fn store_erasure_coded(&self, chunk: &Chunk) -> Result<Option<Vec<Cmd>>> {
+        if !self.erasure_coding.should_code(chunk) {
+            return Ok(None);
+        }
+        let holders = self
+            .get_adults_who_should_store(*chunk.name(), TOTAL_SHARES)
+            .await;
+        if holders.len() < TOTAL_SHARES {
+            debug!(
+                "Replicating chunk {:?} in full, as only {} adults can take its {} shares",
+                chunk.name(),
+                holders.len(),
+                TOTAL_SHARES
+            );
+            return Ok(None);
+        }
+
+        let shares = encode(chunk, DATA_SHARES, PARITY_SHARES)?;
+        let holders: Vec<_> = holders.into_iter().collect();
+        self.erasure_coding
+            .record_holders(*chunk.name(), holders.clone())
+            .await;
+        info!(
+            "Erasure coding chunk {:?} into {} shares held by {:?}",
+            chunk.name(),
+            shares.len(),
+            holders
+        );
+
+        let mut cmds = vec![];
+        for (share, holder) in shares.into_iter().zip(holders) {
+            let msg = SystemMsg::NodeCmd(NodeCmd::StoreChunkShares(vec![share]));
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([holder]))
+                    .await?,
+            );
+        }
+        Ok(Some(cmds))
+    }
+
+    /// Asks the holders of the shares of the chunk at `address` for them, if it was coded.
+    /// Returns `None` for it to be read from the holders of its replicas otherwise.
+    pub(crate) async fn read_erasure_coded(
+        &self,
+        address: &ChunkAddress,
+    ) -> Result<Option<Vec<Cmd>>> {
+        let name = *address.name();
+        let holders = match self.erasure_coding.holders_of(&name).await {
+            Some(holders) => holders,
+            None => return Ok(None),
+        };
+        if !self
+            .erasure_coding
+            .start_rebuild(name, Instant::now())
+            .await
+        {
+            return Ok(Some(vec![]));
+        }
+
+        trace!(
+            "Rebuilding chunk {:?} from the shares of {:?}",
+            name,
+            holders
+        );
+        let msg = SystemMsg::NodeCmd(NodeCmd::SendChunkShares { chunk: name });
+        self.send_node_msg_to_nodes(msg, holders).await.map(Some)
+    }
+
+    /// Stores the shares of chunks our elders sent us, as an adult.
+    pub(crate) async fn store_chunk_shares(&self, shares: Vec<ChunkShare>) -> Result<Vec<Cmd>> {
+        let mut cmds = vec![];
+        for share in shares {
+            match self.data_storage.store_share(&share).await {
+                Ok(level_report) => {
+                    cmds.extend(self.record_storage_level_if_any(level_report).await);
+                }
+                Err(error) => error!(
+                    "Failed to store share {} of chunk {:?}: {error}",
+                    share.index, share.chunk
+                ),
+            }
+        }
+        Ok(cmds)
+    }
+
+    /// Sends the shares we hold of the chunk `name` to the elder asking for them, as an adult.
+    pub(crate) async fn send_chunk_shares(
+        &self,
+        name: XorName,
+        elder: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let shares = self.data_storage.chunk_shares(&name).await?;
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        let msg = SystemMsg::NodeCmd(NodeCmd::RecordChunkShares {
+            node_id,
+            chunk: name,
+            shares,
+        });
+        let dst = DstLocation::Node {
+            name: elder,
+            section_pk: self.network_knowledge.section_key().await,
+        };
+        Ok(vec![Cmd::SignOutgoingSystemMsg { msg, dst }])
+    }
+
+    /// Adds the shares of the chunk `name` an adult sent us to its rebuild, answering the clients
+    /// waiting for the chunk once there are enough of them.
+    pub(crate) async fn record_chunk_shares(
+        &self,
+        name: XorName,
+        shares: Vec<ChunkShare>,
+    ) -> Result<Vec<Cmd>> {
+        let shares = match self.erasure_coding.add_shares(name, shares).await {
+            Some(shares) => shares,
+            None => return Ok(vec![]),
+        };
+        let chunk = match decode(&shares) {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                // The clients waiting are left to time out and try again.
+                error!("Failed to rebuild chunk {name:?}: {error}");
+                return Ok(vec![]);
+            }
+        };
+        debug!("Rebuilt chunk {:?} from {} shares", name, shares.len());
+
+        let op_id = chunk_operation_id(chunk.address())?;
+        let waiting_peers = match self.pending_data_queries.remove(&op_id).await {
+            Some(peers) => peers,
+            None => {
+                trace!("No one is waiting for rebuilt chunk {:?} anymore", name);
+                return Ok(vec![]);
+            }
+        };
+        self.data_cache.insert(chunk.clone()).await;
+
+        self.respond_to_waiting_peers(
+            &waiting_peers,
+            QueryResponse::GetChunk(Ok(chunk)),
+            MsgId::from_xor_name(name),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use sn_interface::types::utils::random_bytes;
+
+    #[test]
+    fn chunks_are_rebuilt_from_any_data_shares_of_them() -> Result<()> {
+        let chunk = Chunk::new(random_bytes(1000));
+        let shares = encode(&chunk, DATA_SHARES, PARITY_SHARES)?;
+        assert_eq!(shares.len(), TOTAL_SHARES);
+
+        // The data shares, the parity ones, or a mix of them.
+        assert_eq!(decode(&shares[..8])?, chunk);
+        assert_eq!(decode(&shares[4..])?, chunk);
+        let mixed: Vec<_> = shares
+            .iter()
+            .step_by(2)
+            .chain(&shares[6..10])
+            .cloned()
+            .collect();
+        assert_eq!(decode(&mixed)?, chunk);
+
+        // Even a chunk of less bytes than there are data shares.
+        let tiny = Chunk::new(Bytes::from_static(b"tiny"));
+        let shares = encode(&tiny, DATA_SHARES, PARITY_SHARES)?;
+        assert_eq!(decode(&shares[TOTAL_SHARES - 8..])?, tiny);
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_are_not_rebuilt_from_too_few_or_altered_shares() -> Result<()> {
+        let chunk = Chunk::new(random_bytes(1000));
+        let mut shares = encode(&chunk, DATA_SHARES, PARITY_SHARES)?;
+
+        assert!(decode(&shares[..7]).is_err());
+        assert!(decode(&[]).is_err());
+
+        // A share altered in transit or at rest rebuilds something else.
+        shares[0].bytes = Bytes::from(vec![0; shares[0].bytes.len()]);
+        assert!(decode(&shares[..8]).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rebuilds_end_once_enough_shares_of_the_chunk_are_in() -> Result<()> {
+        let coding = ErasureCoding::default();
+        let chunk = Chunk::new(random_bytes(1000));
+        let name = *chunk.name();
+        let shares = encode(&chunk, DATA_SHARES, PARITY_SHARES)?;
+
+        // Shares of no rebuild underway are dropped.
+        assert!(coding.add_shares(name, shares.clone()).await.is_none());
+
+        assert!(coding.start_rebuild(name, Instant::now()).await);
+        assert!(!coding.start_rebuild(name, Instant::now()).await);
+
+        // Shares received twice, or of other chunks, don't count.
+        let other = encode(&Chunk::new(random_bytes(1000)), DATA_SHARES, PARITY_SHARES)?;
+        assert!(coding.add_shares(name, other).await.is_none());
+        assert!(coding
+            .add_shares(name, shares[..4].to_vec())
+            .await
+            .is_none());
+        assert!(coding
+            .add_shares(name, shares[..4].to_vec())
+            .await
+            .is_none());
+        let rebuilt = coding.add_shares(name, shares[8..].to_vec()).await;
+        assert_eq!(decode(&rebuilt.unwrap_or_default())?, chunk);
+
+        // The rebuild is over, one can start again.
+        assert!(coding.start_rebuild(name, Instant::now()).await);
+
+        Ok(())
+    }
+}
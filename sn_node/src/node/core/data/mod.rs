@@ -6,8 +6,29 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod consistency;
+mod data_cache;
+mod erasure;
 mod records;
 mod storage;
+mod transfer_checks;
 
-pub(crate) use self::records::{Capacity, MIN_LEVEL_WHEN_FULL};
-pub(crate) use self::storage::DataStorage;
+pub(crate) use self::consistency::{replica_id, ConsistentReads};
+pub use self::data_cache::{CacheOptions, CacheStats};
+pub(crate) use self::data_cache::{DataCache, DEFAULT_DATA_CACHE_MB, DEFAULT_DATA_CACHE_TTL};
+pub(crate) use self::erasure::ErasureCoding;
+pub(crate) use self::records::{
+    holding_proof, AdultIndex, Capacity, CheckOptions, DistributionCheck, DistributionReport,
+    HedgedReads, HolderRegistry, JobProgress, ReplicationAudit, ReplicationJobs, ReplicationTarget,
+    SplitHandover, WarmUp, WarmUpPolicy, DEFAULT_AUDIT_CHUNKS_PER_TICK, DEFAULT_HEDGE_DELAY,
+    DEFAULT_REGISTRY_SLICES_PER_TICK, DEFAULT_WARM_UP, DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT,
+    DISTRIBUTION_CHECK_INTERVAL, MIN_LEVEL_WHEN_FULL, REGISTRY_BOOTSTRAP_INTERVAL,
+    REPLICATION_AUDIT_INTERVAL,
+};
+#[cfg(test)]
+pub(crate) use self::records::{CheckStatus, Severity};
+pub(crate) use self::storage::{
+    DataStorage, DEFAULT_DISK_FAILURE_THRESHOLD, DEFAULT_DISK_RECOVERY_THRESHOLD,
+    DEFAULT_SCRUB_CHUNKS_PER_TICK, SCRUB_INTERVAL,
+};
+pub(crate) use self::transfer_checks::{replicate_checked, Refetch, TransferChecks};
@@ -24,6 +24,8 @@ pub(crate) const MIN_LEVEL_WHEN_FULL: u8 = 9; // considered full when >= 90 %.
 #[derive(Clone, Default)]
 pub(crate) struct Capacity {
     adult_levels: Arc<RwLock<BTreeMap<XorName, Arc<RwLock<StorageLevel>>>>>,
+    // Adults whose disk is failing its self-test
+    unhealthy_adults: Arc<RwLock<BTreeSet<XorName>>>,
 }
 
 impl Capacity {
@@ -95,6 +97,21 @@ impl Capacity {
         set
     }
 
+    /// Adults which reported their storage as unhealthy, and should be given no new data.
+    pub(super) async fn unhealthy_adults(&self) -> BTreeSet<XorName> {
+        self.unhealthy_adults.read().await.clone()
+    }
+
+    /// Returns whether the adult's health changed or not.
+    pub(super) async fn set_adult_health(&self, adult: XorName, healthy: bool) -> bool {
+        let mut unhealthy_adults = self.unhealthy_adults.write().await;
+        if healthy {
+            unhealthy_adults.remove(&adult)
+        } else {
+            unhealthy_adults.insert(adult)
+        }
+    }
+
     pub(super) async fn set_adult_levels(&self, levels: BTreeMap<XorName, StorageLevel>) {
         for (name, level) in levels {
             let _changed = self.set_adult_level(name, level).await;
@@ -139,6 +156,21 @@ impl Capacity {
         }
     }
 
+    /// Sets the level of the adult, even lower than it was, as when it grew its capacity.
+    /// Returns whether the level changed or not.
+    pub(super) async fn reset_adult_level(&self, adult: XorName, new_level: StorageLevel) -> bool {
+        let mut all_levels = self.adult_levels.write().await;
+        if let Some(level) = all_levels.get(&adult) {
+            let mut level = level.write().await;
+            let changed = *level != new_level;
+            *level = new_level;
+            changed
+        } else {
+            let _level = all_levels.insert(adult, Arc::new(RwLock::new(new_level)));
+            true
+        }
+    }
+
     /// Registered holders not present in provided list of members
     /// will be removed from adult_levels and no longer tracked for liveness.
     pub(super) async fn retain_members_only(&self, members: &BTreeSet<XorName>) {
@@ -152,5 +184,37 @@ impl Capacity {
         for adult in &absent_adults {
             let _level = adult_levels.remove(adult);
         }
+
+        self.unhealthy_adults
+            .write()
+            .await
+            .retain(|adult| members.contains(adult));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unhealthy_adults_are_tracked_until_they_recover_or_leave() {
+        let capacity = Capacity::default();
+        let adult = xor_name::rand::random();
+        let other = xor_name::rand::random();
+
+        assert!(capacity.set_adult_health(adult, false).await);
+        assert!(!capacity.set_adult_health(adult, false).await);
+        assert!(capacity.set_adult_health(other, false).await);
+        assert_eq!(
+            capacity.unhealthy_adults().await,
+            BTreeSet::from([adult, other])
+        );
+
+        assert!(capacity.set_adult_health(adult, true).await);
+        assert!(!capacity.set_adult_health(adult, true).await);
+        assert_eq!(capacity.unhealthy_adults().await, BTreeSet::from([other]));
+
+        capacity.retain_members_only(&BTreeSet::from([adult])).await;
+        assert!(capacity.unhealthy_adults().await.is_empty());
     }
 }
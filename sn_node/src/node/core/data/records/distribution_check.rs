@@ -0,0 +1,703 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{data::replicate_checked, Cmd, Node},
+    Error, Result,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use sn_interface::{
+    data_copy_count,
+    messaging::{
+        system::{NodeCmd, SystemMsg},
+        DstLocation,
+    },
+    types::{weighted_holders_for, PublicKey, ReplicatedDataAddress, Weights},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// Names looked up per `DISTRIBUTION_CHECK_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_CHECK_NAMES_PER_TICK: usize = 64;
+/// How often a new batch of names is looked up while checking where data is held.
+pub(crate) const DISTRIBUTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long adults have to answer for a batch before being reported unresponsive.
+const BATCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to check, and how.
+#[derive(Clone, Debug)]
+pub(crate) struct CheckOptions {
+    /// Check only this many names of the registry, picked at random from `seed`.
+    pub(crate) sample: Option<usize>,
+    /// Seed of the sample, for a check to be reproduced.
+    pub(crate) seed: u64,
+    /// Have data missing from its expected holders replicated to them.
+    pub(crate) repair: bool,
+    /// Names looked up per `DISTRIBUTION_CHECK_INTERVAL`.
+    pub(crate) names_per_tick: usize,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            sample: None,
+            seed: 0,
+            repair: false,
+            names_per_tick: DEFAULT_CHECK_NAMES_PER_TICK,
+        }
+    }
+}
+
+/// How bad a misplacement is, from the least to the most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Severity {
+    /// At least as many copies as expected, some of them at holders not expected to.
+    Misplaced,
+    /// Fewer copies than expected.
+    UnderReplicated,
+    /// No copies at all.
+    Lost,
+}
+
+/// Data not held where `weighted_holders_for` says it should be.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Misplacement {
+    pub(crate) name: XorName,
+    /// Expected holders which don't hold it.
+    pub(crate) expected_missing: BTreeSet<XorName>,
+    /// Holders which aren't expected to.
+    pub(crate) unexpected: BTreeSet<XorName>,
+    pub(crate) severity: Severity,
+    /// Whether it was replicated to the expected holders missing it.
+    pub(crate) repair_enqueued: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CheckStatus {
+    Running,
+    Done,
+    Aborted,
+}
+
+/// Outcome of a check, so far if it's still running.
+#[derive(Clone, Debug)]
+pub(crate) struct DistributionReport {
+    pub(crate) status: CheckStatus,
+    pub(crate) seed: u64,
+    /// Names to check.
+    pub(crate) total: usize,
+    /// Names checked so far.
+    pub(crate) checked: usize,
+    /// By decreasing severity.
+    pub(crate) misplacements: Vec<Misplacement>,
+    /// Adults which didn't answer for a batch in time. What they hold isn't accounted for.
+    pub(crate) unresponsive: BTreeSet<XorName>,
+}
+
+// A batch of names looked up at every adult.
+#[derive(Debug)]
+struct Batch {
+    sent_at: Instant,
+    // Holders of each name, as per `weighted_holders_for` when the batch was sent.
+    expected: BTreeMap<XorName, BTreeSet<XorName>>,
+    // Adults yet to answer.
+    awaiting: BTreeSet<XorName>,
+    // Adults holding each name, with its address.
+    held: BTreeMap<XorName, (ReplicatedDataAddress, BTreeSet<XorName>)>,
+}
+
+#[derive(Debug)]
+struct Check {
+    options: CheckOptions,
+    pending: VecDeque<XorName>,
+    batches: BTreeMap<u64, Batch>,
+    report: DistributionReport,
+}
+
+/// Data to replicate to an expected holder missing it, from one holding it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Repair {
+    pub(crate) address: ReplicatedDataAddress,
+    pub(crate) source: XorName,
+    pub(crate) target: XorName,
+}
+
+/// Elders' check of whether the data in their holder registry is held where the holder
+/// selection says it should be, as per what the adults say they hold.
+///
+/// Names are looked up a batch per tick, so as not to flood the adults, and the check can be
+/// aborted at any point. The report of the last check is kept until the next one starts.
+#[derive(Clone, Default)]
+pub(crate) struct DistributionCheck {
+    check: Arc<RwLock<Option<Check>>>,
+    next_batch: Arc<AtomicU64>,
+}
+
+impl DistributionCheck {
+    /// Starts checking `names`, or the sample of them `options` asks for.
+    pub(crate) async fn start(&self, names: Vec<XorName>, options: CheckOptions) -> Result<()> {
+        let mut check = self.check.write().await;
+        if matches!(&*check, Some(check) if check.report.status == CheckStatus::Running) {
+            return Err(Error::DistributionCheckRunning);
+        }
+
+        let mut names = match options.sample {
+            Some(count) => {
+                let mut rng = StdRng::seed_from_u64(options.seed);
+                names
+                    .choose_multiple(&mut rng, count)
+                    .copied()
+                    .collect::<Vec<_>>()
+            }
+            None => names,
+        };
+        names.sort();
+
+        info!(
+            "Checking the distribution of {} pieces of data, with {:?}",
+            names.len(),
+            options
+        );
+        *check = Some(Check {
+            report: DistributionReport {
+                status: CheckStatus::Running,
+                seed: options.seed,
+                total: names.len(),
+                checked: 0,
+                misplacements: vec![],
+                unresponsive: BTreeSet::new(),
+            },
+            options,
+            pending: names.into(),
+            batches: BTreeMap::new(),
+        });
+        Ok(())
+    }
+
+    /// Aborts the running check, returning whether there was one.
+    pub(crate) async fn abort(&self) -> bool {
+        let mut check = self.check.write().await;
+        match &mut *check {
+            Some(check) if check.report.status == CheckStatus::Running => {
+                info!("Distribution check aborted");
+                check.report.status = CheckStatus::Aborted;
+                check.pending.clear();
+                check.batches.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// A new batch id, for lookups made outside of checks to be told apart from theirs.
+    pub(crate) fn batch_id(&self) -> u64 {
+        self.next_batch.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) async fn is_running(&self) -> bool {
+        matches!(
+            &*self.check.read().await,
+            Some(check) if check.report.status == CheckStatus::Running
+        )
+    }
+
+    /// The report of the running check, or of the last one.
+    pub(crate) async fn report(&self) -> Option<DistributionReport> {
+        self.check
+            .read()
+            .await
+            .as_ref()
+            .map(|check| check.report.clone())
+    }
+
+    /// Takes the next batch of names to look up at `adults`, of the given warm-up `weights`, if
+    /// any, along with the repairs of the batches they took too long to answer for.
+    pub(crate) async fn next_batch(
+        &self,
+        adults: &BTreeSet<XorName>,
+        weights: &Weights,
+    ) -> (Option<(u64, BTreeSet<XorName>)>, Vec<Repair>) {
+        let mut guard = self.check.write().await;
+        let check = match &mut *guard {
+            Some(check) if check.report.status == CheckStatus::Running => check,
+            _ => return (None, vec![]),
+        };
+
+        let expired = check
+            .batches
+            .iter()
+            .filter(|(_, batch)| batch.sent_at.elapsed() > BATCH_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut repairs = vec![];
+        for id in expired {
+            if let Some(batch) = check.batches.remove(&id) {
+                warn!(
+                    "Adults {:?} didn't answer for distribution check batch {}",
+                    batch.awaiting, id
+                );
+                check.report.unresponsive.extend(batch.awaiting.iter());
+                repairs.extend(check.conclude(batch));
+            }
+        }
+
+        let count = check.options.names_per_tick.min(check.pending.len());
+        let names = check.pending.drain(..count).collect::<BTreeSet<_>>();
+        let next = if names.is_empty() || adults.is_empty() {
+            // With no adults to ask, the names are concluded as they are.
+            if !names.is_empty() {
+                repairs.extend(check.conclude(Batch::new(&names, adults, weights)));
+            }
+            None
+        } else {
+            let id = self.batch_id();
+            let _prev = check
+                .batches
+                .insert(id, Batch::new(&names, adults, weights));
+            Some((id, names))
+        };
+        check.finish_if_done();
+
+        (next, repairs)
+    }
+
+    /// Records which of the names of `batch` `adult` holds, returning the repairs to be made
+    /// once every adult asked has answered.
+    pub(crate) async fn record_held(
+        &self,
+        adult: XorName,
+        batch: u64,
+        held: BTreeSet<ReplicatedDataAddress>,
+    ) -> Vec<Repair> {
+        let mut guard = self.check.write().await;
+        let check = match &mut *guard {
+            Some(check) => check,
+            None => return vec![],
+        };
+        let pending = match check.batches.get_mut(&batch) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        if !pending.awaiting.remove(&adult) {
+            return vec![];
+        }
+        for address in held {
+            if pending.expected.contains_key(address.name()) {
+                let (_, holders) = pending
+                    .held
+                    .entry(*address.name())
+                    .or_insert_with(|| (address, BTreeSet::new()));
+                let _new = holders.insert(adult);
+            }
+        }
+
+        if !pending.awaiting.is_empty() {
+            return vec![];
+        }
+        let repairs = match check.batches.remove(&batch) {
+            Some(batch) => check.conclude(batch),
+            None => vec![],
+        };
+        check.finish_if_done();
+        repairs
+    }
+}
+
+impl Batch {
+    fn new(names: &BTreeSet<XorName>, adults: &BTreeSet<XorName>, weights: &Weights) -> Self {
+        Self {
+            sent_at: Instant::now(),
+            expected: names
+                .iter()
+                .map(|name| {
+                    let holders = weighted_holders_for(name, adults, data_copy_count(), weights);
+                    (*name, holders)
+                })
+                .collect(),
+            awaiting: adults.clone(),
+            held: BTreeMap::new(),
+        }
+    }
+}
+
+impl Check {
+    // Reports the misplacements of a batch answered for, returning the repairs to be made.
+    fn conclude(&mut self, mut batch: Batch) -> Vec<Repair> {
+        let mut repairs = vec![];
+        for (name, expected) in batch.expected {
+            self.report.checked += 1;
+            let (address, actual) = match batch.held.remove(&name) {
+                Some((address, actual)) => (Some(address), actual),
+                None => (None, BTreeSet::new()),
+            };
+            let expected_missing = expected
+                .difference(&actual)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            let unexpected = actual
+                .difference(&expected)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            if expected_missing.is_empty() && unexpected.is_empty() {
+                continue;
+            }
+
+            let severity = if actual.is_empty() {
+                Severity::Lost
+            } else if actual.len() < expected.len() {
+                Severity::UnderReplicated
+            } else {
+                Severity::Misplaced
+            };
+            let mut repair_enqueued = false;
+            if let (true, Some(address), Some(source)) =
+                (self.options.repair, address, actual.iter().next())
+            {
+                repairs.extend(expected_missing.iter().map(|target| Repair {
+                    address,
+                    source: *source,
+                    target: *target,
+                }));
+                repair_enqueued = !expected_missing.is_empty();
+            }
+            warn!(
+                "{:?} data {:?}: missing from expected holders {:?}, held by unexpected {:?}",
+                severity, name, expected_missing, unexpected
+            );
+
+            self.report.misplacements.push(Misplacement {
+                name,
+                expected_missing,
+                unexpected,
+                severity,
+                repair_enqueued,
+            });
+        }
+        self.report.misplacements.sort_by(|lhs, rhs| {
+            rhs.severity
+                .cmp(&lhs.severity)
+                .then(lhs.name.cmp(&rhs.name))
+        });
+        repairs
+    }
+
+    fn finish_if_done(&mut self) {
+        if self.pending.is_empty() && self.batches.is_empty() {
+            info!(
+                "Distribution check done: {} misplaced out of {} checked",
+                self.report.misplacements.len(),
+                self.report.checked
+            );
+            self.report.status = CheckStatus::Done;
+        }
+    }
+}
+
+impl Node {
+    /// Starts checking that the data in our holder registry is held where it should be, as an
+    /// elder.
+    pub(crate) async fn start_distribution_check(&self, options: CheckOptions) -> Result<()> {
+        if !self.is_elder().await {
+            return Err(Error::InvalidState);
+        }
+        let names = self.holder_registry.names().await;
+        self.distribution_check.start(names, options).await
+    }
+
+    /// Asks our adults which of the next batch of names of the running distribution check they
+    /// hold.
+    pub(crate) async fn check_next_distribution_batch(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await || !self.distribution_check.is_running().await {
+            return Ok(vec![]);
+        }
+
+        let adults = self
+            .network_knowledge
+            .adults()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .collect::<BTreeSet<_>>();
+        let weights = self.warm_up.weights().await;
+        let (next, repairs) = self.distribution_check.next_batch(&adults, &weights).await;
+
+        let mut cmds = self.repair_distribution(repairs).await?;
+        if let Some((batch, names)) = next {
+            trace!(
+                "Asking adults which of {} names of batch {} they hold",
+                names.len(),
+                batch
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendHeld { batch, names });
+            cmds.extend(self.send_node_msg_to_nodes(msg, adults).await?);
+        }
+        Ok(cmds)
+    }
+
+    /// Records what an adult holds among a batch of names of the running distribution check, or
+    /// of those a replication job asked it about.
+    pub(crate) async fn record_held(
+        &self,
+        adult: XorName,
+        batch: u64,
+        held: BTreeSet<ReplicatedDataAddress>,
+    ) -> Result<Vec<Cmd>> {
+        let names = held.iter().map(|address| *address.name()).collect();
+        if self.warm_up.record_held(adult, batch, &names).await {
+            return Ok(vec![]);
+        }
+        self.confirm_replications(adult, &held).await?;
+        let repairs = self
+            .distribution_check
+            .record_held(adult, batch, held)
+            .await;
+        self.repair_distribution(repairs).await
+    }
+
+    // Asks the holders of misplaced data to replicate it to its expected holders.
+    pub(super) async fn repair_distribution(&self, repairs: Vec<Repair>) -> Result<Vec<Cmd>> {
+        let mut by_holders = BTreeMap::<(XorName, XorName), Vec<ReplicatedDataAddress>>::new();
+        for repair in repairs {
+            by_holders
+                .entry((repair.source, repair.target))
+                .or_default()
+                .push(repair.address);
+        }
+
+        let mut cmds = vec![];
+        for ((source, target), addresses) in by_holders {
+            info!(
+                "Having {:?} replicate {} pieces of data to {:?}",
+                source,
+                addresses.len(),
+                target
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::ReplicateDataTo {
+                addresses,
+                holder: target,
+            });
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([source]))
+                    .await?,
+            );
+        }
+        Ok(cmds)
+    }
+
+    /// On adults, sends the addresses of the data we hold among `names` to the requesting elder,
+    /// or adult of our sibling section handing its data over to us.
+    pub(crate) async fn send_held(
+        &self,
+        batch: u64,
+        names: BTreeSet<XorName>,
+        requester: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let held = self
+            .data_storage
+            .keys()
+            .await?
+            .into_iter()
+            .filter(|address| names.contains(address.name()))
+            .collect();
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: SystemMsg::NodeCmd(NodeCmd::RecordHeld {
+                node_id,
+                batch,
+                held,
+            }),
+            dst: DstLocation::Node {
+                name: requester,
+                section_pk: self.section_key_by_name(&requester).await,
+            },
+        }])
+    }
+
+    /// On adults, replicates the data at `addresses` we hold to `holder`.
+    pub(crate) async fn replicate_data_to(
+        &self,
+        addresses: Vec<ReplicatedDataAddress>,
+        holder: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let mut data = vec![];
+        for address in addresses {
+            match self.data_storage.get_for_replication(address).await {
+                Ok(held) => data.push(held),
+                Err(error) => warn!("Not replicating {:?} to {:?}: {}", address, holder, error),
+            }
+        }
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: replicate_checked(data),
+            dst: DstLocation::Node {
+                name: holder,
+                section_pk: self.section_key_by_name(&holder).await,
+            },
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_interface::types::{holders_for, utils::random_bytes, Chunk};
+
+    fn names(count: usize) -> Vec<XorName> {
+        (0..count).map(|_| xor_name::rand::random()).collect()
+    }
+
+    #[tokio::test]
+    async fn samples_are_reproducible_from_their_seed() -> Result<()> {
+        let names = names(100);
+        let sampled = |seed| {
+            let names = names.clone();
+            async move {
+                let check = DistributionCheck::default();
+                let options = CheckOptions {
+                    sample: Some(10),
+                    seed,
+                    names_per_tick: 100,
+                    ..CheckOptions::default()
+                };
+                check.start(names, options).await?;
+                let adults = BTreeSet::from([xor_name::rand::random()]);
+                let (next, _) = check.next_batch(&adults, &Weights::new()).await;
+                Ok::<_, Error>(next.map(|(_, names)| names).unwrap_or_default())
+            }
+        };
+
+        let sample = sampled(7).await?;
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sampled(7).await?, sample);
+        assert_ne!(sampled(8).await?, sample);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checks_are_paced_and_abortable() -> Result<()> {
+        let check = DistributionCheck::default();
+        let options = CheckOptions {
+            names_per_tick: 3,
+            ..CheckOptions::default()
+        };
+        check.start(names(10), options.clone()).await?;
+        assert!(matches!(
+            check.start(names(1), options).await,
+            Err(Error::DistributionCheckRunning)
+        ));
+
+        let adults = BTreeSet::from([xor_name::rand::random()]);
+        let (next, _) = check.next_batch(&adults, &Weights::new()).await;
+        assert_eq!(next.map(|(_, names)| names.len()), Some(3));
+
+        assert!(check.abort().await);
+        assert!(!check.is_running().await);
+        assert_eq!(check.next_batch(&adults, &Weights::new()).await.0, None);
+        let report = check.report().await.ok_or(Error::InvalidState)?;
+        assert_eq!(report.status, CheckStatus::Aborted);
+        assert_eq!(report.checked, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn misplacements_are_classified_by_severity() -> Result<()> {
+        let copies = data_copy_count();
+        let adults = names(copies + 2).into_iter().collect::<BTreeSet<_>>();
+        let chunk = |_| ReplicatedDataAddress::Chunk(*Chunk::new(random_bytes(10)).address());
+        let [placed, misplaced, under, lost]: [ReplicatedDataAddress; 4] = [0, 1, 2, 3].map(chunk);
+        let expected = |address: &ReplicatedDataAddress| {
+            holders_for(address.name(), &adults, copies)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        let unexpected = |address: &ReplicatedDataAddress| {
+            let expected = expected(address);
+            adults
+                .iter()
+                .copied()
+                .find(|adult| !expected.contains(adult))
+                .ok_or(Error::InvalidState)
+        };
+
+        // Where each adult holds what.
+        let mut held = BTreeMap::<XorName, BTreeSet<_>>::new();
+        for address in [placed, misplaced, under] {
+            for holder in expected(&address) {
+                let _new = held.entry(holder).or_default().insert(address);
+            }
+        }
+        let moved = expected(&misplaced)[0];
+        let _removed = held.entry(moved).or_default().remove(&misplaced);
+        let _new = held
+            .entry(unexpected(&misplaced)?)
+            .or_default()
+            .insert(misplaced);
+        let missing = expected(&under)[0];
+        let _removed = held.entry(missing).or_default().remove(&under);
+
+        let check = DistributionCheck::default();
+        let options = CheckOptions {
+            repair: true,
+            ..CheckOptions::default()
+        };
+        let all = [placed, misplaced, under, lost];
+        check
+            .start(all.iter().map(|address| *address.name()).collect(), options)
+            .await?;
+        let (batch, _) = check
+            .next_batch(&adults, &Weights::new())
+            .await
+            .0
+            .ok_or(Error::InvalidState)?;
+        let mut repairs = vec![];
+        for adult in &adults {
+            let held = held.get(adult).cloned().unwrap_or_default();
+            repairs.extend(check.record_held(*adult, batch, held).await);
+        }
+
+        let report = check.report().await.ok_or(Error::InvalidState)?;
+        assert_eq!(report.status, CheckStatus::Done);
+        assert_eq!(report.checked, 4);
+        let severities = report
+            .misplacements
+            .iter()
+            .map(|misplacement| (misplacement.name, misplacement.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            severities,
+            vec![
+                (*lost.name(), Severity::Lost),
+                (*under.name(), Severity::UnderReplicated),
+                (*misplaced.name(), Severity::Misplaced),
+            ]
+        );
+
+        // Everything held somewhere gets to its missing expected holders.
+        let mut targets = repairs
+            .iter()
+            .map(|repair| (repair.address, repair.target))
+            .collect::<Vec<_>>();
+        targets.sort();
+        let mut expected_targets = vec![(misplaced, moved), (under, missing)];
+        expected_targets.sort();
+        assert_eq!(targets, expected_targets);
+        Ok(())
+    }
+}
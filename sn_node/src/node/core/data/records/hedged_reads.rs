@@ -0,0 +1,412 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    api::cmds::next_timer_token,
+    core::{Cmd, Node, DATA_QUERY_TIMEOUT},
+    Result,
+};
+
+use sn_dysfunction::IssueType;
+use sn_interface::messaging::{data::OperationId, system::SystemMsg};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// How long the fastest known holder of some data has to answer a query before the next one is
+/// queried too, when unspecified.
+pub(crate) const DEFAULT_HEDGE_DELAY: Duration = Duration::from_millis(200);
+/// Weight of the latest response in the average latency of an adult.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// Holders to query for a read, and the timer to schedule for the next hedge, if any.
+#[derive(Debug)]
+pub(crate) struct Hedge {
+    pub(crate) targets: BTreeSet<XorName>,
+    pub(crate) msg: SystemMsg,
+    pub(crate) timer: Option<(u64, Duration)>,
+}
+
+/// What hedging reads has done so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct HedgeStats {
+    /// Reads started.
+    pub(crate) reads: u64,
+    /// Holders queried past the first one of a read, for it being slow or not having the data.
+    pub(crate) hedges: u64,
+    /// Queries cancelled for another holder having answered first.
+    pub(crate) cancelled: u64,
+}
+
+#[derive(Debug)]
+struct Read {
+    started: Instant,
+    msg: SystemMsg,
+    // Holders yet to be queried, fastest known first.
+    next: VecDeque<XorName>,
+    // Holders queried, and when.
+    queried: BTreeMap<XorName, Instant>,
+    // Timer of the next hedge, if any.
+    timer: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    reads: BTreeMap<OperationId, Read>,
+    timers: BTreeMap<u64, OperationId>,
+    latencies: BTreeMap<XorName, Duration>,
+    stats: HedgeStats,
+}
+
+/// Elders' hedging of the reads they make of their adults on behalf of clients.
+///
+/// Rather than querying all holders of some data at once, the fastest known one is queried
+/// first, and the next one only once the hedge delay passed without an answer, or right away if
+/// the data wasn't found, and so on down the holders. The first holder to answer with the data
+/// wins, the timer of the next hedge being cancelled, and the others queried no longer awaited.
+/// A delay of 0 queries all holders at once.
+#[derive(Clone, Debug)]
+pub(crate) struct HedgedReads {
+    inner: Arc<RwLock<Inner>>,
+    delay_ms: Arc<AtomicU64>,
+}
+
+impl Default for HedgedReads {
+    fn default() -> Self {
+        Self {
+            inner: Arc::default(),
+            delay_ms: Arc::new(AtomicU64::new(DEFAULT_HEDGE_DELAY.as_millis() as u64)),
+        }
+    }
+}
+
+impl HedgedReads {
+    /// Sets how long a holder has to answer before the next one is queried, 0 disabling hedging.
+    pub(crate) fn set_delay(&self, delay: Duration) {
+        self.delay_ms
+            .store(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms.load(Ordering::Relaxed))
+    }
+
+    pub(crate) async fn stats(&self) -> HedgeStats {
+        self.inner.read().await.stats
+    }
+
+    /// Starts the read `op_id` of the data held by `holders`, returning the first of them to
+    /// send `msg` to.
+    pub(crate) async fn start(
+        &self,
+        op_id: OperationId,
+        holders: BTreeSet<XorName>,
+        msg: SystemMsg,
+        now: Instant,
+    ) -> Hedge {
+        let mut inner = self.inner.write().await;
+        inner.expire(now);
+        inner.stats.reads += 1;
+
+        let delay = self.delay();
+        let mut next = inner.fastest_first(holders);
+        let first = if delay.is_zero() {
+            next.drain(..).collect::<BTreeSet<_>>()
+        } else {
+            next.pop_front().into_iter().collect()
+        };
+        let timer = (!next.is_empty()).then(next_timer_token);
+        if let Some(token) = timer {
+            let _prev = inner.timers.insert(token, op_id);
+        }
+        let _prev = inner.reads.insert(
+            op_id,
+            Read {
+                started: now,
+                msg: msg.clone(),
+                next,
+                queried: first.iter().map(|holder| (*holder, now)).collect(),
+                timer,
+            },
+        );
+        Hedge {
+            targets: first,
+            msg,
+            timer: timer.map(|token| (token, delay)),
+        }
+    }
+
+    /// Queries the next holder of the read the timer `token` is for, if it's still on.
+    pub(crate) async fn timed_out(&self, token: u64, now: Instant) -> Option<(OperationId, Hedge)> {
+        let mut inner = self.inner.write().await;
+        let op_id = inner.timers.remove(&token)?;
+        let hedge = inner.hedge(op_id, self.delay(), now)?;
+        Some((op_id, hedge))
+    }
+
+    /// Records `adult` answering the read `op_id` with the data, ending it. Returns the other
+    /// holders queried, whose queries are cancelled.
+    pub(crate) async fn answered(
+        &self,
+        op_id: OperationId,
+        adult: XorName,
+        now: Instant,
+    ) -> BTreeSet<XorName> {
+        let mut inner = self.inner.write().await;
+        inner.record_latency(op_id, adult, now);
+        let read = match inner.reads.remove(&op_id) {
+            Some(read) => read,
+            None => return BTreeSet::new(),
+        };
+        if let Some(token) = read.timer {
+            let _prev = inner.timers.remove(&token);
+        }
+        let cancelled = read
+            .queried
+            .into_keys()
+            .filter(|holder| *holder != adult)
+            .collect::<BTreeSet<_>>();
+        inner.stats.cancelled += cancelled.len() as u64;
+        cancelled
+    }
+
+    /// Records `adult` not having the data of the read `op_id`, returning the next holder to
+    /// query right away, if any.
+    pub(crate) async fn not_found(
+        &self,
+        op_id: OperationId,
+        adult: XorName,
+        now: Instant,
+    ) -> Option<Hedge> {
+        let mut inner = self.inner.write().await;
+        inner.record_latency(op_id, adult, now);
+        let read = inner.reads.get_mut(&op_id)?;
+        let _answered = read.queried.remove(&adult);
+        if let Some(token) = read.timer.take() {
+            let _prev = inner.timers.remove(&token);
+        }
+        inner.hedge(op_id, self.delay(), now)
+    }
+}
+
+impl Inner {
+    // Forgets the reads which timed out along with the queries of their clients.
+    fn expire(&mut self, now: Instant) {
+        let expired = self
+            .reads
+            .iter()
+            .filter(|(_, read)| now.saturating_duration_since(read.started) > DATA_QUERY_TIMEOUT)
+            .map(|(op_id, _)| *op_id)
+            .collect::<Vec<_>>();
+        for op_id in expired {
+            if let Some(token) = self.reads.remove(&op_id).and_then(|read| read.timer) {
+                let _prev = self.timers.remove(&token);
+            }
+        }
+    }
+
+    // Holders by increasing latency, those we know nothing of yet coming last.
+    fn fastest_first(&self, holders: BTreeSet<XorName>) -> VecDeque<XorName> {
+        let mut holders = holders.into_iter().collect::<Vec<_>>();
+        holders.sort_by_key(|holder| match self.latencies.get(holder) {
+            Some(latency) => (false, *latency),
+            None => (true, Duration::ZERO),
+        });
+        holders.into()
+    }
+
+    fn record_latency(&mut self, op_id: OperationId, adult: XorName, now: Instant) {
+        let sent_at = match self
+            .reads
+            .get(&op_id)
+            .and_then(|read| read.queried.get(&adult))
+        {
+            Some(sent_at) => *sent_at,
+            None => return,
+        };
+        let took = now.saturating_duration_since(sent_at);
+        let latency = self.latencies.entry(adult).or_insert(took);
+        *latency = latency.mul_f64(1.0 - LATENCY_WEIGHT) + took.mul_f64(LATENCY_WEIGHT);
+    }
+
+    // Queries the next holder of the read `op_id`, scheduling the hedge after it if any.
+    fn hedge(&mut self, op_id: OperationId, delay: Duration, now: Instant) -> Option<Hedge> {
+        let read = self.reads.get_mut(&op_id)?;
+        let holder = read.next.pop_front()?;
+        let _prev = read.queried.insert(holder, now);
+        read.timer = (!read.next.is_empty()).then(next_timer_token);
+        let hedge = Hedge {
+            targets: BTreeSet::from([holder]),
+            msg: read.msg.clone(),
+            timer: read.timer.map(|token| (token, delay)),
+        };
+        if let Some(token) = read.timer {
+            let _prev = self.timers.insert(token, op_id);
+        }
+        self.stats.hedges += 1;
+        Some(hedge)
+    }
+}
+
+impl Node {
+    /// Sends the query of a hedged read to its next holders, tracking it as pending against
+    /// them, and schedules the next hedge.
+    pub(crate) async fn send_hedge(&self, op_id: OperationId, hedge: Hedge) -> Result<Vec<Cmd>> {
+        for target in &hedge.targets {
+            trace!("adding pending req for {target:?} in dysfunction tracking");
+            self.dysfunction_tracking
+                .track_issue(*target, IssueType::PendingRequestOperation(Some(op_id)))
+                .await?;
+        }
+        let mut cmds = self
+            .send_node_msg_to_nodes(hedge.msg, hedge.targets)
+            .await?;
+        if let Some((token, duration)) = hedge.timer {
+            cmds.push(Cmd::ScheduleTimeout { duration, token });
+        }
+        Ok(cmds)
+    }
+
+    /// Queries the next holder of a hedged read whose current holders are taking too long, if
+    /// `token` is the timer of one.
+    pub(crate) async fn handle_hedge_timeout(&self, token: u64) -> Result<Option<Vec<Cmd>>> {
+        match self.hedged_reads.timed_out(token, Instant::now()).await {
+            Some((op_id, hedge)) => {
+                trace!("Hedging read {:?} with {:?}", op_id, hedge.targets);
+                Ok(Some(self.send_hedge(op_id, hedge).await?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_interface::messaging::system::NodeCmd;
+
+    fn msg() -> SystemMsg {
+        SystemMsg::NodeCmd(NodeCmd::SendHeld {
+            batch: 0,
+            names: BTreeSet::new(),
+        })
+    }
+
+    fn op_id() -> OperationId {
+        xor_name::rand::random::<XorName>().0
+    }
+
+    fn holders(count: usize) -> Vec<XorName> {
+        let mut holders = (0..count)
+            .map(|_| xor_name::rand::random())
+            .collect::<Vec<XorName>>();
+        holders.sort();
+        holders
+    }
+
+    #[tokio::test]
+    async fn holders_are_queried_one_more_per_delay_fastest_first() {
+        let reads = HedgedReads::default();
+        let holders = holders(3);
+        let now = Instant::now();
+
+        // Teach the reads that the last holder is the fastest, and the second one is slow.
+        let warm_up = op_id();
+        let hedge = reads
+            .start(warm_up, holders.iter().copied().collect(), msg(), now)
+            .await;
+        assert_eq!(hedge.targets, BTreeSet::from([holders[0]]));
+        let (token, _) = hedge.timer.expect("a hedge timer");
+        let hedge = reads.timed_out(token, now).await.expect("a hedge").1;
+        assert_eq!(hedge.targets, BTreeSet::from([holders[1]]));
+        let (token, _) = hedge.timer.expect("a hedge timer");
+        let hedge = reads.timed_out(token, now).await.expect("a hedge").1;
+        assert_eq!(hedge.targets, BTreeSet::from([holders[2]]));
+        assert!(hedge.timer.is_none());
+        let _cancelled = reads
+            .not_found(warm_up, holders[1], now + Duration::from_secs(2))
+            .await;
+        let cancelled = reads
+            .answered(warm_up, holders[2], now + Duration::from_millis(10))
+            .await;
+        assert_eq!(cancelled, BTreeSet::from([holders[0]]));
+
+        let op_id = op_id();
+        let hedge = reads
+            .start(op_id, holders.iter().copied().collect(), msg(), now)
+            .await;
+        assert_eq!(hedge.targets, BTreeSet::from([holders[2]]));
+        assert_eq!(
+            hedge.timer.map(|(_, delay)| delay),
+            Some(DEFAULT_HEDGE_DELAY)
+        );
+
+        // The data not being found moves on to the next holder right away, cancelling the
+        // timer of the hedge.
+        let (token, _) = hedge.timer.expect("a hedge timer");
+        let hedge = reads
+            .not_found(op_id, holders[2], now)
+            .await
+            .expect("a hedge");
+        assert_eq!(hedge.targets, BTreeSet::from([holders[1]]));
+        assert!(reads.timed_out(token, now).await.is_none());
+
+        // The first holder to answer wins, the others being cancelled.
+        let (token, _) = hedge.timer.expect("a hedge timer");
+        let hedge = reads.timed_out(token, now).await.expect("a hedge").1;
+        assert_eq!(hedge.targets, BTreeSet::from([holders[0]]));
+        assert_eq!(
+            reads.answered(op_id, holders[0], now).await,
+            BTreeSet::from([holders[1]])
+        );
+        assert!(reads.answered(op_id, holders[1], now).await.is_empty());
+
+        assert_eq!(
+            reads.stats().await,
+            HedgeStats {
+                reads: 2,
+                hedges: 4,
+                cancelled: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_delay_queries_all_holders_at_once() {
+        let reads = HedgedReads::default();
+        reads.set_delay(Duration::ZERO);
+        let holders = holders(3);
+        let now = Instant::now();
+
+        let op_id = op_id();
+        let all = holders.iter().copied().collect::<BTreeSet<_>>();
+        let hedge = reads.start(op_id, all.clone(), msg(), now).await;
+        assert_eq!(hedge.targets, all);
+        assert!(hedge.timer.is_none());
+        assert!(reads.not_found(op_id, holders[0], now).await.is_none());
+
+        // Reads not answered are forgotten once timed out.
+        let _hedge = reads
+            .start(
+                self::op_id(),
+                all,
+                msg(),
+                now + DATA_QUERY_TIMEOUT + Duration::from_secs(1),
+            )
+            .await;
+        assert!(reads.answered(op_id, holders[1], now).await.is_empty());
+        assert_eq!(reads.inner.read().await.reads.len(), 1);
+    }
+}
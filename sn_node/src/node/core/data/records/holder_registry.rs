@@ -0,0 +1,618 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise, SLED_FLUSH_TIME_MS};
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use serde::{Deserialize, Serialize};
+use sn_interface::{
+    messaging::{
+        system::{NodeCmd, SystemMsg},
+        DstLocation,
+    },
+    types::PublicKey,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::RwLock;
+use xor_name::{Prefix, XorName};
+
+const REGISTRY_DB_NAME: &str = "holder_registry";
+const HOLDERS_TREE: &[u8] = b"holders";
+const PROGRESS_KEY: &[u8] = b"progress";
+
+/// The registry is rebuilt in slices of the section's namespace, the prefixes this many bits
+/// longer than the section's.
+pub(crate) const REGISTRY_SLICE_BITS: usize = 6;
+/// Slices requested from the adults per `REGISTRY_BOOTSTRAP_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_REGISTRY_SLICES_PER_TICK: usize = 4;
+/// How often a new batch of slices is requested while rebuilding the registry.
+pub(crate) const REGISTRY_BOOTSTRAP_INTERVAL: Duration = Duration::from_secs(1);
+
+// What's been rebuilt so far, persisted to resume rebuilding after a restart.
+#[derive(Debug, Serialize, Deserialize)]
+struct Progress {
+    prefix: Prefix,
+    covered: BTreeSet<Prefix>,
+    complete: bool,
+}
+
+#[derive(Debug)]
+struct Bootstrap {
+    prefix: Prefix,
+    // In prefix order.
+    slices: Vec<Prefix>,
+    // Slices yet to be requested, with the adults to request them from.
+    queued: BTreeMap<Prefix, BTreeSet<XorName>>,
+    // Slices requested, with the adults yet to answer.
+    awaiting: BTreeMap<Prefix, BTreeSet<XorName>>,
+}
+
+impl Bootstrap {
+    fn is_covered(&self, slice: &Prefix) -> bool {
+        !self.queued.contains_key(slice) && !self.awaiting.contains_key(slice)
+    }
+
+    fn covered(&self) -> BTreeSet<Prefix> {
+        self.slices
+            .iter()
+            .filter(|slice| self.is_covered(slice))
+            .copied()
+            .collect()
+    }
+
+    fn is_done(&self) -> bool {
+        self.queued.is_empty() && self.awaiting.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // Adults holding each piece of data.
+    holders: BTreeMap<XorName, BTreeSet<XorName>>,
+    bootstrap: Option<Bootstrap>,
+    // Prefix of the section the registry is complete for, if any.
+    complete_for: Option<Prefix>,
+}
+
+/// Elders' registry of which adults hold which data.
+///
+/// A newly promoted elder rebuilds it in the background from the adults' inventories, a few
+/// slices of the namespace at a time so as not to flood them. Lookups within slices already
+/// rebuilt use the registry straight away, others fall back to the adults closest to the data.
+#[derive(Clone)]
+pub(crate) struct HolderRegistry {
+    db: sled::Db,
+    holders_tree: sled::Tree,
+    state: Arc<RwLock<State>>,
+    slices_per_tick: Arc<AtomicUsize>,
+}
+
+impl HolderRegistry {
+    pub(crate) fn new(root_dir: &Path) -> Result<Self> {
+        let db = sled::Config::default()
+            .path(root_dir.join("db").join(REGISTRY_DB_NAME))
+            .flush_every_ms(SLED_FLUSH_TIME_MS)
+            .open()?;
+        let holders_tree = db.open_tree(HOLDERS_TREE)?;
+
+        let mut holders = BTreeMap::new();
+        for entry in holders_tree.iter() {
+            let (name, adults) = entry?;
+            let _prev = holders.insert(deserialise(&name)?, deserialise(&adults)?);
+        }
+        let complete_for = match db.get(PROGRESS_KEY)? {
+            Some(bytes) => {
+                let progress: Progress = deserialise(&bytes)?;
+                progress.complete.then(|| progress.prefix)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            db,
+            holders_tree,
+            state: Arc::new(RwLock::new(State {
+                holders,
+                bootstrap: None,
+                complete_for,
+            })),
+            slices_per_tick: Arc::new(AtomicUsize::new(DEFAULT_REGISTRY_SLICES_PER_TICK)),
+        })
+    }
+
+    /// Sets how many slices are requested per `REGISTRY_BOOTSTRAP_INTERVAL`.
+    pub(crate) fn set_slices_per_tick(&self, slices_per_tick: usize) {
+        self.slices_per_tick
+            .store(slices_per_tick.max(1), Ordering::Relaxed);
+    }
+
+    /// Starts rebuilding the registry for the section of `prefix` from `adults`, carrying on
+    /// from where a previous rebuild for the same prefix got to.
+    pub(crate) async fn start(&self, prefix: Prefix, adults: BTreeSet<XorName>) -> Result<()> {
+        let mut state = self.state.write().await;
+        if state.complete_for == Some(prefix)
+            || matches!(&state.bootstrap, Some(bootstrap) if bootstrap.prefix == prefix)
+        {
+            return Ok(());
+        }
+
+        let covered = match self.db.get(PROGRESS_KEY)? {
+            Some(bytes) => {
+                let progress: Progress = deserialise(&bytes)?;
+                if progress.prefix == prefix {
+                    progress.covered
+                } else {
+                    BTreeSet::new()
+                }
+            }
+            None => BTreeSet::new(),
+        };
+        if covered.is_empty() {
+            // Whatever we have is of another section.
+            state.holders.clear();
+            self.holders_tree.clear()?;
+        } else {
+            state.holders.retain(|name, _| prefix.matches(name));
+        }
+        state.complete_for = None;
+
+        let slices = slices_of(prefix);
+        let queued = if adults.is_empty() {
+            BTreeMap::new()
+        } else {
+            slices
+                .iter()
+                .filter(|slice| !covered.contains(slice))
+                .map(|slice| (*slice, adults.clone()))
+                .collect()
+        };
+        info!(
+            "Rebuilding the holder registry of {:?}: {} of {} slices left",
+            prefix,
+            queued.len(),
+            slices.len()
+        );
+        state.bootstrap = Some(Bootstrap {
+            prefix,
+            slices,
+            queued,
+            awaiting: BTreeMap::new(),
+        });
+        self.finish_if_done(&mut state).await
+    }
+
+    /// Takes the next batch of slices to request, with the adults to request each from.
+    pub(crate) async fn next_requests(&self) -> Vec<(Prefix, BTreeSet<XorName>)> {
+        let budget = self.slices_per_tick.load(Ordering::Relaxed);
+        let mut state = self.state.write().await;
+        let bootstrap = match &mut state.bootstrap {
+            Some(bootstrap) => bootstrap,
+            None => return vec![],
+        };
+
+        let batch: Vec<_> = bootstrap
+            .slices
+            .iter()
+            .filter(|slice| bootstrap.queued.contains_key(slice))
+            .take(budget)
+            .copied()
+            .collect();
+        batch
+            .into_iter()
+            .filter_map(|slice| {
+                let adults = bootstrap.queued.remove(&slice)?;
+                bootstrap
+                    .awaiting
+                    .entry(slice)
+                    .or_default()
+                    .extend(adults.iter().copied());
+                Some((slice, adults))
+            })
+            .collect()
+    }
+
+    /// Merges the inventory `adult` sent for `slice`.
+    pub(crate) async fn record_inventory(
+        &self,
+        adult: XorName,
+        slice: Prefix,
+        names: BTreeSet<XorName>,
+    ) -> Result<()> {
+        let mut state = self.state.write().await;
+        let bootstrap = match &mut state.bootstrap {
+            Some(bootstrap) => bootstrap,
+            None => return Ok(()),
+        };
+        let awaited = match bootstrap.awaiting.get_mut(&slice) {
+            Some(awaited) => awaited,
+            None => return Ok(()),
+        };
+        if !awaited.remove(&adult) {
+            return Ok(());
+        }
+        if awaited.is_empty() {
+            let _prev = bootstrap.awaiting.remove(&slice);
+        }
+
+        for name in names.into_iter().filter(|name| slice.matches(name)) {
+            let holders = state.holders.entry(name).or_default();
+            if holders.insert(adult) {
+                let _prev = self
+                    .holders_tree
+                    .insert(serialise(&name)?, serialise(holders)?)?;
+            }
+        }
+        self.finish_if_done(&mut state).await
+    }
+
+    /// Records `adults` as holders of the data named `name`.
+    pub(crate) async fn record_holders(
+        &self,
+        name: XorName,
+        adults: &BTreeSet<XorName>,
+    ) -> Result<()> {
+        let mut state = self.state.write().await;
+        let holders = state.holders.entry(name).or_default();
+        holders.extend(adults.iter().copied());
+        let _prev = self
+            .holders_tree
+            .insert(serialise(&name)?, serialise(holders)?)?;
+        Ok(())
+    }
+
+    /// Forgets `adult` as a holder of the data named `name`, which it no longer holds.
+    pub(crate) async fn forget_holder(&self, name: XorName, adult: XorName) -> Result<()> {
+        let mut state = self.state.write().await;
+        let holders = match state.holders.get_mut(&name) {
+            Some(holders) => holders,
+            None => return Ok(()),
+        };
+        if !holders.remove(&adult) {
+            return Ok(());
+        }
+        if holders.is_empty() {
+            let _prev = state.holders.remove(&name);
+            let _prev = self.holders_tree.remove(serialise(&name)?)?;
+        } else {
+            let _prev = self
+                .holders_tree
+                .insert(serialise(&name)?, serialise(holders)?)?;
+        }
+        Ok(())
+    }
+
+    /// Makes sure the inventory of an adult that just joined is requested: it may be handed data
+    /// by the other adults, which we're not told about.
+    pub(crate) async fn adult_joined(&self, adult: XorName) -> Result<()> {
+        let mut state = self.state.write().await;
+        if state.bootstrap.is_none() {
+            match state.complete_for {
+                Some(prefix) => {
+                    // Restart the rebuild, for the new adult's inventory alone.
+                    state.complete_for = None;
+                    state.bootstrap = Some(Bootstrap {
+                        prefix,
+                        slices: slices_of(prefix),
+                        queued: BTreeMap::new(),
+                        awaiting: BTreeMap::new(),
+                    });
+                }
+                None => return Ok(()),
+            }
+        }
+
+        if let Some(bootstrap) = &mut state.bootstrap {
+            for slice in &bootstrap.slices {
+                let _new = bootstrap.queued.entry(*slice).or_default().insert(adult);
+            }
+        }
+        self.persist_progress(&state)
+    }
+
+    /// Forgets the adults not in `adults`, no longer waiting on any of their inventories.
+    pub(crate) async fn retain_adults(&self, adults: &BTreeSet<XorName>) -> Result<()> {
+        let mut state = self.state.write().await;
+
+        let mut emptied = vec![];
+        for (name, holders) in state.holders.iter_mut() {
+            let count = holders.len();
+            holders.retain(|holder| adults.contains(holder));
+            if holders.len() != count {
+                if holders.is_empty() {
+                    emptied.push(*name);
+                    let _prev = self.holders_tree.remove(serialise(name)?)?;
+                } else {
+                    let _prev = self
+                        .holders_tree
+                        .insert(serialise(name)?, serialise(holders)?)?;
+                }
+            }
+        }
+        for name in emptied {
+            let _prev = state.holders.remove(&name);
+        }
+
+        if let Some(bootstrap) = &mut state.bootstrap {
+            for pending in [&mut bootstrap.queued, &mut bootstrap.awaiting] {
+                pending.retain(|_, pending_adults| {
+                    pending_adults.retain(|adult| adults.contains(adult));
+                    !pending_adults.is_empty()
+                });
+            }
+        }
+        self.finish_if_done(&mut state).await
+    }
+
+    /// The holders of the data named `name`, if the registry covers it.
+    pub(crate) async fn holders_of(&self, name: &XorName) -> Option<BTreeSet<XorName>> {
+        let state = self.state.read().await;
+        let covered = match (&state.bootstrap, state.complete_for) {
+            (Some(bootstrap), _) => {
+                bootstrap.prefix.matches(name)
+                    && bootstrap.is_covered(&slice_of(&bootstrap.prefix, name))
+            }
+            (None, Some(prefix)) => prefix.matches(name),
+            (None, None) => false,
+        };
+        if covered {
+            state.holders.get(name).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Names of all the data in the registry, in order.
+    pub(crate) async fn names(&self) -> Vec<XorName> {
+        self.state.read().await.holders.keys().copied().collect()
+    }
+
+    /// Percentage of the section's namespace the registry covers.
+    pub(crate) async fn progress(&self) -> u8 {
+        let state = self.state.read().await;
+        match (&state.bootstrap, state.complete_for) {
+            (Some(bootstrap), _) => {
+                (bootstrap.covered().len() * 100 / bootstrap.slices.len().max(1)) as u8
+            }
+            (None, Some(_)) => 100,
+            (None, None) => 0,
+        }
+    }
+
+    /// Whether the registry is being rebuilt.
+    pub(crate) async fn is_rebuilding(&self) -> bool {
+        self.state.read().await.bootstrap.is_some()
+    }
+
+    async fn finish_if_done(&self, state: &mut State) -> Result<()> {
+        if let Some(bootstrap) = &state.bootstrap {
+            if bootstrap.is_done() {
+                info!("Holder registry of {:?} rebuilt", bootstrap.prefix);
+                state.complete_for = Some(bootstrap.prefix);
+                state.bootstrap = None;
+            }
+        }
+        self.persist_progress(state)?;
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+
+    fn persist_progress(&self, state: &State) -> Result<()> {
+        let progress = match (&state.bootstrap, state.complete_for) {
+            (Some(bootstrap), _) => Progress {
+                prefix: bootstrap.prefix,
+                covered: bootstrap.covered(),
+                complete: false,
+            },
+            (None, Some(prefix)) => Progress {
+                prefix,
+                covered: slices_of(prefix).into_iter().collect(),
+                complete: true,
+            },
+            (None, None) => return Ok(()),
+        };
+        let _prev = self.db.insert(PROGRESS_KEY, serialise(&progress)?)?;
+        Ok(())
+    }
+}
+
+// The slices of the namespace of `prefix`, in prefix order.
+fn slices_of(prefix: Prefix) -> Vec<Prefix> {
+    (0..1_usize << REGISTRY_SLICE_BITS)
+        .map(|index| {
+            (0..REGISTRY_SLICE_BITS)
+                .rev()
+                .fold(prefix, |slice, bit| slice.pushed(index & (1 << bit) != 0))
+        })
+        .collect()
+}
+
+fn slice_of(prefix: &Prefix, name: &XorName) -> Prefix {
+    Prefix::new(prefix.bit_count() + REGISTRY_SLICE_BITS, *name)
+}
+
+impl Node {
+    /// Starts rebuilding our holder registry, as a newly promoted elder.
+    pub(crate) async fn start_holder_registry_rebuild(&self) {
+        let prefix = self.network_knowledge.prefix().await;
+        let adults = self
+            .network_knowledge
+            .adults()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .collect();
+        if let Err(error) = self.holder_registry.start(prefix, adults).await {
+            error!(
+                "Failed to start rebuilding the holder registry: {:?}",
+                error
+            );
+        }
+    }
+
+    /// Requests the next batch of slices of the adults' inventories, while rebuilding our
+    /// holder registry.
+    pub(crate) async fn request_next_inventory_slices(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await || !self.holder_registry.is_rebuilding().await {
+            return Ok(vec![]);
+        }
+
+        let mut cmds = vec![];
+        for (slice, adults) in self.holder_registry.next_requests().await {
+            trace!(
+                "Requesting inventory of slice {:?} from {:?}",
+                slice,
+                adults
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendInventory { slice });
+            cmds.extend(self.send_node_msg_to_nodes(msg, adults).await?);
+        }
+        debug!(
+            "Holder registry rebuilt at {}%",
+            self.holder_registry.progress().await
+        );
+        Ok(cmds)
+    }
+
+    /// On adults, sends the names of the data we hold within `slice` to the requesting elder.
+    pub(crate) async fn send_inventory(&self, slice: Prefix, elder: XorName) -> Result<Vec<Cmd>> {
+        let names = self
+            .data_storage
+            .keys()
+            .await?
+            .iter()
+            .map(|address| *address.name())
+            .filter(|name| slice.matches(name))
+            .collect();
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: SystemMsg::NodeCmd(NodeCmd::RecordInventory {
+                node_id,
+                slice,
+                names,
+            }),
+            dst: DstLocation::Node {
+                name: elder,
+                section_pk: self.network_knowledge.section_key().await,
+            },
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    fn adults(count: usize) -> BTreeSet<XorName> {
+        (0..count).map(|_| xor_name::rand::random()).collect()
+    }
+
+    #[test]
+    fn slices_are_in_prefix_order() {
+        let prefix = Prefix::default().pushed(true);
+        let slices = slices_of(prefix);
+
+        assert_eq!(slices.len(), 1 << REGISTRY_SLICE_BITS);
+        assert!(slices.iter().all(|slice| slice.is_extension_of(&prefix)));
+        assert!(slices
+            .windows(2)
+            .all(|pair| pair[0].lower_bound() < pair[1].lower_bound()));
+    }
+
+    #[tokio::test]
+    async fn rebuild_resumes_from_persisted_progress() -> Result<()> {
+        let root = tempdir()?;
+        let prefix = Prefix::default();
+        let adults = adults(3);
+        let name = xor_name::rand::random();
+
+        let registry = HolderRegistry::new(root.path())?;
+        registry.set_slices_per_tick(8);
+        registry.start(prefix, adults.clone()).await?;
+        let requests = registry.next_requests().await;
+        assert_eq!(requests.len(), 8);
+        for (slice, asked) in &requests {
+            for adult in asked {
+                let names = if slice.matches(&name) {
+                    BTreeSet::from([name])
+                } else {
+                    BTreeSet::new()
+                };
+                registry.record_inventory(*adult, *slice, names).await?;
+            }
+        }
+        assert_eq!(registry.progress().await, 12);
+        drop(registry);
+
+        let registry = HolderRegistry::new(root.path())?;
+        registry.set_slices_per_tick(8);
+        registry.start(prefix, adults).await?;
+        assert_eq!(registry.progress().await, 12);
+
+        // Only the slices not covered before the restart are requested.
+        let covered: BTreeSet<_> = requests.iter().map(|(slice, _)| *slice).collect();
+        let resumed = registry.next_requests().await;
+        assert!(resumed.iter().all(|(slice, _)| !covered.contains(slice)));
+        if covered.contains(&slice_of(&prefix, &name)) {
+            assert_eq!(registry.holders_of(&name).await.map(|h| h.len()), Some(3));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn churn_updates_what_is_waited_on() -> Result<()> {
+        let root = tempdir()?;
+        let prefix = Prefix::default();
+        let (stayed, left, joined) = (
+            xor_name::rand::random(),
+            xor_name::rand::random(),
+            xor_name::rand::random(),
+        );
+
+        let registry = HolderRegistry::new(root.path())?;
+        registry.set_slices_per_tick(1 << REGISTRY_SLICE_BITS);
+        registry
+            .start(prefix, BTreeSet::from([stayed, left]))
+            .await?;
+        for (slice, _) in registry.next_requests().await {
+            registry
+                .record_inventory(stayed, slice, BTreeSet::new())
+                .await?;
+        }
+        assert_eq!(registry.progress().await, 0);
+
+        // No longer waiting on the adult that left.
+        registry.retain_adults(&BTreeSet::from([stayed])).await?;
+        assert_eq!(registry.progress().await, 100);
+        assert!(!registry.is_rebuilding().await);
+
+        // The inventory of an adult that joins is requested, and only its.
+        registry.adult_joined(joined).await?;
+        assert!(registry.is_rebuilding().await);
+        let requests = registry.next_requests().await;
+        assert_eq!(requests.len(), 1 << REGISTRY_SLICE_BITS);
+        assert!(requests
+            .iter()
+            .all(|(_, adults)| adults == &BTreeSet::from([joined])));
+
+        Ok(())
+    }
+}
@@ -7,37 +7,85 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod capacity;
+mod distribution_check;
+mod hedged_reads;
+mod holder_registry;
+mod replication_audit;
+mod replication_jobs;
+mod split_handover;
+mod warm_up;
 
 pub(crate) use self::capacity::{Capacity, MIN_LEVEL_WHEN_FULL};
+pub(crate) use self::distribution_check::{
+    CheckOptions, DistributionCheck, DistributionReport, DISTRIBUTION_CHECK_INTERVAL,
+};
+#[cfg(test)]
+pub(crate) use self::distribution_check::{CheckStatus, Severity};
+pub(crate) use self::hedged_reads::{HedgedReads, DEFAULT_HEDGE_DELAY};
+pub(crate) use self::holder_registry::{
+    HolderRegistry, DEFAULT_REGISTRY_SLICES_PER_TICK, REGISTRY_BOOTSTRAP_INTERVAL,
+};
+pub(crate) use self::replication_audit::{
+    holding_proof, ReplicationAudit, DEFAULT_AUDIT_CHUNKS_PER_TICK, REPLICATION_AUDIT_INTERVAL,
+};
+pub(crate) use self::replication_jobs::{JobProgress, ReplicationJobs, ReplicationTarget};
+pub(crate) use self::split_handover::SplitHandover;
+pub(crate) use self::warm_up::{
+    WarmUp, WarmUpPolicy, DEFAULT_WARM_UP, DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT,
+};
 
 use crate::node::{
-    core::{Cmd, Node, Prefix, MAX_WAITING_PEERS_PER_QUERY},
+    core::{data::replicate_checked, Cmd, Node, Prefix, MAX_WAITING_PEERS_PER_QUERY},
     error::convert_to_error_msg,
     messages::WireMsgUtils,
     Error, Result,
 };
 use dashmap::DashSet;
-use itertools::Itertools;
-use sn_dysfunction::IssueType;
 use sn_interface::data_copy_count;
 use sn_interface::messaging::{
-    data::{CmdError, DataQuery, MetadataExchange, StorageLevel},
-    system::{NodeCmd, NodeQuery, SystemMsg},
+    data::{
+        CmdError, DataHolders, DataQuery, HoldersQuery, MetadataExchange, QueryResponse,
+        StorageLevel,
+    },
+    system::{NodeQuery, SystemMsg},
     AuthorityProof, DstLocation, EndUser, MsgId, ServiceAuth, WireMsg,
 };
 use sn_interface::types::{
-    log_markers::LogMarker, Peer, PublicKey, ReplicatedData, ReplicatedDataAddress,
+    holders_for, log_markers::LogMarker, weighted_holders, ClosenessIndex, Peer, PublicKey,
+    ReplicatedData, ReplicatedDataAddress,
 };
 use std::{cmp::Ordering, collections::BTreeSet, sync::Arc};
+use tokio::time::Instant;
 use tracing::info;
 use xor_name::XorName;
 
+/// Our adults, indexed for those closest to data to be found without sorting them all, as of
+/// the members version and section key they were read at.
+#[derive(Default)]
+pub(crate) struct AdultIndex {
+    as_of: Option<(u64, bls::PublicKey)>,
+    index: Arc<ClosenessIndex>,
+    rebuilds: u64,
+}
+
+impl AdultIndex {
+    /// How many times the index was rebuilt, i.e. how many membership changes it went through.
+    #[cfg(any(test, feature = "metrics"))]
+    pub(crate) fn rebuilds(&self) -> u64 {
+        self.rebuilds
+    }
+}
+
 impl Node {
     // Locate ideal holders for this data, line up wiremsgs for those to instruct them to store the data
     pub(crate) async fn replicate_data(&self, data: ReplicatedData) -> Result<Vec<Cmd>> {
         trace!("{:?}: {:?}", LogMarker::DataStoreReceivedAtElder, data);
         if self.is_elder().await {
             let targets = self.get_adults_who_should_store_data(data.name()).await;
+            self.holder_registry
+                .record_holders(data.name(), &targets)
+                .await?;
+            self.warm_up.record_assigned(data.name(), &targets).await;
 
             info!(
                 "Replicating data {:?} to holders {:?}",
@@ -45,7 +93,7 @@ impl Node {
                 &targets,
             );
 
-            let msg = SystemMsg::NodeCmd(NodeCmd::ReplicateData(vec![data]));
+            let msg = replicate_checked(vec![data]);
             self.send_node_msg_to_nodes(msg, targets).await
         } else {
             Err(Error::InvalidState)
@@ -55,11 +103,11 @@ impl Node {
     pub(crate) async fn read_data_from_adults(
         &self,
         query: DataQuery,
+        address: ReplicatedDataAddress,
         msg_id: MsgId,
         auth: AuthorityProof<ServiceAuth>,
         origin: Peer,
     ) -> Result<Vec<Cmd>> {
-        let address = query.address();
         let operation_id = query.operation_id()?;
         trace!(
             "{:?} preparing to query adults for data at {:?} with op_id: {:?}",
@@ -68,6 +116,10 @@ impl Node {
             operation_id
         );
 
+        if let Some(cmds) = self.answer_from_cache(&query, origin).await? {
+            return Ok(cmds);
+        }
+
         let targets = self.get_adults_holding_data(address.name()).await;
 
         if targets.is_empty() {
@@ -76,7 +128,7 @@ impl Node {
 
             debug!("No targets found for {msg_id:?}");
             return self
-                .send_cmd_error_response(CmdError::Data(error), origin, msg_id)
+                .send_cmd_error_response(CmdError::Data(error.into()), origin, msg_id)
                 .await;
         }
 
@@ -102,17 +154,6 @@ impl Node {
         // otherwise we've appended to the Peers above
         // we rely on the data query cache timeout to decide as/when we'll be re-sending a query to adults
         if !op_was_already_underway {
-            // ensure we only add a pending request when we're actually sending out requests.
-            for target in &targets {
-                trace!("adding pending req for {target:?} in dysfunction tracking");
-                self.dysfunction_tracking
-                    .track_issue(
-                        *target,
-                        IssueType::PendingRequestOperation(Some(operation_id)),
-                    )
-                    .await?;
-            }
-
             trace!("Adding to pending data queries");
 
             let _prior_value = self
@@ -120,6 +161,13 @@ impl Node {
                 .set(operation_id, waiting_peers, None)
                 .await;
 
+            // Chunks erasure coded are rebuilt from the shares of them instead.
+            if let DataQuery::GetChunk(address) = &query {
+                if let Some(cmds) = self.read_erasure_coded(address).await? {
+                    return Ok(cmds);
+                }
+            }
+
             let msg = SystemMsg::NodeQuery(NodeQuery::Data {
                 query,
                 auth: auth.into_inner(),
@@ -127,13 +175,37 @@ impl Node {
                 correlation_id: MsgId::from_xor_name(*address.name()),
             });
 
-            self.send_node_msg_to_nodes(msg, targets).await
+            // Holders are queried one after the other as they take too long to answer, fastest
+            // known first.
+            let hedge = self
+                .hedged_reads
+                .start(operation_id, targets, msg, Instant::now())
+                .await;
+            self.send_hedge(operation_id, hedge).await
         } else {
             // we don't do anything as we're still within data query timeout
             Ok(vec![])
         }
     }
 
+    /// Answers a client with the adults holding the data of a name, as far as we know.
+    pub(crate) async fn handle_holders_query(
+        &self,
+        query: HoldersQuery,
+        msg_id: MsgId,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let recorded = self.holder_registry.holders_of(&query.name).await.is_some();
+        let holders = DataHolders {
+            prefix: self.network_knowledge.prefix().await,
+            section_key: self.network_knowledge.section_key().await,
+            holders: self.get_adults_holding_data(&query.name).await,
+            recorded,
+        };
+        let response = QueryResponse::GetDataHolders((Ok(holders), query.operation_id()?));
+        self.send_query_response(origin, response, msg_id).await
+    }
+
     pub(crate) async fn get_metadata_of(&self, prefix: &Prefix) -> MetadataExchange {
         // Load tracked adult_levels
         let adult_levels = self.capacity.levels_matching(*prefix).await;
@@ -151,39 +223,67 @@ impl Node {
         // full adults
         self.capacity.retain_members_only(&members).await;
 
+        self.holder_registry.retain_adults(&members).await?;
+
+        self.warm_up.retain_members_only(&members).await;
+
         // stop tracking liveness of absent holders
         let _ = self.dysfunction_tracking.retain_members_only(members).await;
 
         Ok(())
     }
 
-    /// Adds the new adult to the Capacity and Liveness trackers.
-    pub(crate) async fn add_new_adult_to_trackers(&self, adult: XorName) {
+    /// Adds the new adult to the Capacity and Liveness trackers, and to those warming up unless
+    /// it was relocated to us.
+    pub(crate) async fn add_new_adult_to_trackers(&self, adult: XorName, relocated: bool) {
         info!("Adding new Adult: {adult} to trackers");
         self.capacity.add_new_adult(adult).await;
 
+        if !relocated {
+            self.warm_up.adult_joined(adult).await;
+        }
+
         let _ = self.dysfunction_tracking.add_new_node(adult).await;
+
+        if let Err(error) = self.holder_registry.adult_joined(adult).await {
+            error!("Failed to request the inventory of new Adult {adult}: {error:?}");
+        }
+    }
+
+    pub(crate) async fn full_adults(&self) -> BTreeSet<XorName> {
+        self.capacity.full_adults().await
     }
 
-    /// Set storage level of a given node.
+    /// Set the storage level of a given node as agreed by the elders, lower than it was or not.
     /// Returns whether the level changed or not.
-    pub(crate) async fn set_storage_level(&self, node_id: &PublicKey, level: StorageLevel) -> bool {
-        info!("Setting new storage level..");
-        let changed = self
-            .capacity
-            .set_adult_level(XorName::from(*node_id), level)
-            .await;
-        let avg_usage = self.capacity.avg_usage().await;
-        info!(
-            "Avg storage usage among Adults is between {}-{} %",
-            avg_usage * 10,
-            (avg_usage + 1) * 10
-        );
+    pub(crate) async fn reset_storage_level(
+        &self,
+        node_id: &PublicKey,
+        level: StorageLevel,
+    ) -> bool {
+        let adult = XorName::from(*node_id);
+        let changed = self.capacity.reset_adult_level(adult, level).await;
+        if changed {
+            info!("Storage level of Adult {adult} is now {}", level.value());
+            let avg_usage = self.capacity.avg_usage().await;
+            info!(
+                "Avg storage usage among Adults is between {}-{} %",
+                avg_usage * 10,
+                (avg_usage + 1) * 10
+            );
+        }
         changed
     }
 
-    pub(crate) async fn full_adults(&self) -> BTreeSet<XorName> {
-        self.capacity.full_adults().await
+    /// Set whether the storage of a given node is healthy.
+    /// Returns whether its health changed or not.
+    pub(crate) async fn set_storage_health(&self, node_id: &PublicKey, healthy: bool) -> bool {
+        let adult = XorName::from(*node_id);
+        let changed = self.capacity.set_adult_health(adult, healthy).await;
+        if changed {
+            info!("Storage of Adult {adult} is now healthy: {healthy}");
+        }
+        changed
     }
 
     pub(crate) fn compute_holders(
@@ -191,28 +291,60 @@ impl Node {
         addr: &ReplicatedDataAddress,
         adult_list: &BTreeSet<XorName>,
     ) -> BTreeSet<XorName> {
-        adult_list
-            .iter()
-            .sorted_by(|lhs, rhs| addr.name().cmp_distance(lhs, rhs))
-            .take(data_copy_count())
-            .cloned()
-            .collect()
+        holders_for(addr.name(), adult_list, data_copy_count())
+    }
+
+    /// Our adults, indexed by closeness. Rebuilt only once our section's members or elders
+    /// changed, rather than sorting them all for every piece of data.
+    pub(crate) async fn adult_index(&self) -> Arc<ClosenessIndex> {
+        let as_of = (
+            self.network_knowledge.members_version(),
+            self.network_knowledge.section_key().await,
+        );
+        if let Some(index) = self.adult_index_as_of(as_of).await {
+            return index;
+        }
+
+        let index = Arc::new(
+            self.network_knowledge
+                .adults()
+                .await
+                .iter()
+                .map(|peer| peer.name())
+                .collect::<ClosenessIndex>(),
+        );
+        let mut cached = self.adult_index.write().await;
+        cached.as_of = Some(as_of);
+        cached.index = index.clone();
+        cached.rebuilds += 1;
+        trace!("Rebuilt the index of our {} adults", index.len());
+        index
+    }
+
+    async fn adult_index_as_of(&self, as_of: (u64, bls::PublicKey)) -> Option<Arc<ClosenessIndex>> {
+        let cached = self.adult_index.read().await;
+        (cached.as_of == Some(as_of)).then(|| cached.index.clone())
     }
 
     // Used to fetch the list of holders for given data name.
     async fn get_adults_holding_data(&self, target: &XorName) -> BTreeSet<XorName> {
-        let full_adults = self.full_adults().await;
-        // TODO: reuse our_adults_sorted_by_distance_to API when core is merged into upper layer
-        let adults = self.network_knowledge().adults().await;
+        if let Some(holders) = self.holder_registry.holders_of(target).await {
+            trace!("Holders of {:?} from the registry: {:?}", target, holders);
+            return holders;
+        }
 
-        let adults_names = adults.iter().map(|p2p_node| p2p_node.name());
+        let full_adults = self.full_adults().await;
+        let weights = self.warm_up.weights().await;
+        let adults = self.adult_index().await;
 
-        let mut candidates = adults_names
-            .into_iter()
-            .sorted_by(|lhs, rhs| target.cmp_distance(lhs, rhs))
-            .filter(|peer| !full_adults.contains(peer))
-            .take(data_copy_count())
-            .collect::<BTreeSet<_>>();
+        let mut candidates = weighted_holders(
+            target,
+            adults
+                .closest(target)
+                .filter(|peer| !full_adults.contains(peer)),
+            data_copy_count(),
+            &weights,
+        );
 
         trace!(
             "Chunk holders of {:?} are empty adults: {:?} and full adults: {:?}",
@@ -244,27 +376,48 @@ impl Node {
     }
 
     // Used to fetch the list of holders for given name of data.
-    async fn get_adults_who_should_store_data(&self, target: XorName) -> BTreeSet<XorName> {
+    pub(crate) async fn get_adults_who_should_store_data(
+        &self,
+        target: XorName,
+    ) -> BTreeSet<XorName> {
+        self.get_adults_who_should_store(target, data_copy_count())
+            .await
+    }
+
+    // The `count` adults closest to `target` which can take new data, as weighted by how far
+    // along warming up they are.
+    pub(crate) async fn get_adults_who_should_store(
+        &self,
+        target: XorName,
+        count: usize,
+    ) -> BTreeSet<XorName> {
         let full_adults = self.full_adults().await;
-        // TODO: reuse our_adults_sorted_by_distance_to API when core is merged into upper layer
-        let adults = self.network_knowledge().adults().await;
+        let unhealthy_adults = self.capacity.unhealthy_adults().await;
+        let restarting_adults = self.planned_restarts.pending().await;
+        let weights = self.warm_up.weights().await;
+        let adults = self.adult_index().await;
 
         trace!("Total adults known about: {:?}", adults.len());
 
-        let adults_names = adults.iter().map(|p2p_node| p2p_node.name());
-
-        let candidates = adults_names
-            .into_iter()
-            .sorted_by(|lhs, rhs| target.cmp_distance(lhs, rhs))
-            .filter(|peer| !full_adults.contains(peer))
-            .take(data_copy_count())
-            .collect::<BTreeSet<_>>();
+        let candidates = weighted_holders(
+            &target,
+            adults.closest(&target).filter(|peer| {
+                !full_adults.contains(peer)
+                    && !unhealthy_adults.contains(peer)
+                    && !restarting_adults.contains(peer)
+            }),
+            count,
+            &weights,
+        );
 
         trace!(
-            "Target holders of {:?} are empty adults: {:?} and full adults that were ignored: {:?}",
+            "Target holders of {:?} are empty adults: {:?}, ignoring full adults: {:?}, unhealthy adults: {:?} and restarting adults: {:?}, with adults warming up: {:?}",
             target,
             candidates,
-            full_adults
+            full_adults,
+            unhealthy_adults,
+            restarting_adults,
+            weights
         );
 
         candidates
@@ -273,7 +426,7 @@ impl Node {
     // Takes a message for specified targets, and builds internal send cmds
     // for sending to each of the targets.
     // Targets are XorName specified so must be within the section
-    async fn send_node_msg_to_nodes(
+    pub(crate) async fn send_node_msg_to_nodes(
         &self,
         msg: SystemMsg,
         targets: BTreeSet<XorName>,
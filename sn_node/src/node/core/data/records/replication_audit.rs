@@ -0,0 +1,489 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::distribution_check::Repair;
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use rand::{seq::SliceRandom, Rng};
+use sn_interface::{
+    data_copy_count,
+    messaging::{
+        system::{NodeCmd, SystemMsg},
+        DstLocation,
+    },
+    types::{
+        weighted_holders_for, ChunkAddress, PublicKey, ReplicatedData, ReplicatedDataAddress,
+        Weights,
+    },
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tiny_keccak::{Hasher, Sha3};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// Chunks audited per `REPLICATION_AUDIT_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_AUDIT_CHUNKS_PER_TICK: usize = 4;
+/// How often a new sample of chunks is audited.
+pub(crate) const REPLICATION_AUDIT_INTERVAL: Duration = Duration::from_secs(30);
+/// How long adults have to answer a challenge before their copies are counted missing.
+const AUDIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Proof of holding a chunk: the sha3 of the nonce of the challenge followed by its content.
+pub(crate) fn holding_proof(nonce: &[u8; 32], content: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3::v256();
+    let mut proof = [0; 32];
+    hasher.update(nonce);
+    hasher.update(content);
+    hasher.finalize(&mut proof);
+    proof
+}
+
+/// What the audits found so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AuditStats {
+    /// Audits concluded.
+    pub(crate) audits: u64,
+    /// Chunks whose holders were challenged.
+    pub(crate) chunks_audited: u64,
+    /// Copies expected holders couldn't prove, replicated to them again when another holder
+    /// could.
+    pub(crate) missing_copies: u64,
+    /// Chunks no holder could prove, which may be registers rather than chunks.
+    pub(crate) unproven: u64,
+    /// Chunks whose holders disagreed on the proof, with no majority to repair from.
+    pub(crate) disputed: u64,
+}
+
+/// A challenge to send to the holders of a sample of chunks.
+#[derive(Debug)]
+pub(crate) struct Challenge {
+    pub(crate) audit: u64,
+    pub(crate) nonce: [u8; 32],
+    pub(crate) names: BTreeSet<XorName>,
+    /// Adults expected to hold any of the chunks.
+    pub(crate) holders: BTreeSet<XorName>,
+}
+
+#[derive(Debug)]
+struct Audit {
+    sent_at: Instant,
+    // Holders of each chunk, as per `weighted_holders_for` when the challenge was sent.
+    expected: BTreeMap<XorName, BTreeSet<XorName>>,
+    // Adults yet to answer.
+    awaiting: BTreeSet<XorName>,
+    // Proof each adult gave of each chunk.
+    proofs: BTreeMap<XorName, BTreeMap<XorName, [u8; 32]>>,
+}
+
+/// Elders' audit of chunks being replicated to their expected holders.
+///
+/// Every tick, a random sample of the chunks in the holder registry is picked, and their
+/// expected holders challenged to hash each of them along with a fresh nonce, which can't be
+/// done without the content. The proof most holders agree on is taken for the right one,
+/// holders which don't give it have the chunk replicated to them again from one which did.
+#[derive(Clone)]
+pub(crate) struct ReplicationAudit {
+    audits: Arc<RwLock<BTreeMap<u64, Audit>>>,
+    stats: Arc<RwLock<AuditStats>>,
+    next_audit: Arc<AtomicU64>,
+    chunks_per_tick: Arc<AtomicUsize>,
+}
+
+impl Default for ReplicationAudit {
+    fn default() -> Self {
+        Self {
+            audits: Arc::default(),
+            stats: Arc::default(),
+            next_audit: Arc::default(),
+            chunks_per_tick: Arc::new(AtomicUsize::new(DEFAULT_AUDIT_CHUNKS_PER_TICK)),
+        }
+    }
+}
+
+impl ReplicationAudit {
+    /// Sets how many chunks are audited per `REPLICATION_AUDIT_INTERVAL`, 0 disabling audits.
+    pub(crate) fn set_chunks_per_tick(&self, chunks_per_tick: usize) {
+        self.chunks_per_tick
+            .store(chunks_per_tick, Ordering::Relaxed);
+    }
+
+    pub(crate) async fn stats(&self) -> AuditStats {
+        *self.stats.read().await
+    }
+
+    /// Samples the next chunks to audit among `names`, held by `adults` of the given warm-up
+    /// `weights`, along with the repairs of the audits their holders took too long to answer.
+    pub(crate) async fn next(
+        &self,
+        names: &[XorName],
+        adults: &BTreeSet<XorName>,
+        weights: &Weights,
+    ) -> (Option<Challenge>, Vec<Repair>) {
+        let mut audits = self.audits.write().await;
+        let expired = audits
+            .iter()
+            .filter(|(_, audit)| audit.sent_at.elapsed() > AUDIT_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut repairs = vec![];
+        for id in expired {
+            if let Some(audit) = audits.remove(&id) {
+                warn!(
+                    "Adults {:?} didn't answer for replication audit {}",
+                    audit.awaiting, id
+                );
+                repairs.extend(self.conclude(audit).await);
+            }
+        }
+
+        let count = self.chunks_per_tick.load(Ordering::Relaxed);
+        if count == 0 || adults.is_empty() {
+            return (None, repairs);
+        }
+        let names = names
+            .choose_multiple(&mut rand::thread_rng(), count)
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if names.is_empty() {
+            return (None, repairs);
+        }
+
+        let expected = names
+            .iter()
+            .map(|name| {
+                let holders = weighted_holders_for(name, adults, data_copy_count(), weights);
+                (*name, holders)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let holders = expected
+            .values()
+            .flatten()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let audit = self.next_audit.fetch_add(1, Ordering::Relaxed);
+        let _prev = audits.insert(
+            audit,
+            Audit {
+                sent_at: Instant::now(),
+                expected,
+                awaiting: holders.clone(),
+                proofs: BTreeMap::new(),
+            },
+        );
+
+        let challenge = Challenge {
+            audit,
+            nonce: rand::thread_rng().gen(),
+            names,
+            holders,
+        };
+        (Some(challenge), repairs)
+    }
+
+    /// Records the proofs `adult` gave for `audit`, returning the repairs to be made once every
+    /// holder challenged has answered.
+    pub(crate) async fn record_proofs(
+        &self,
+        adult: XorName,
+        audit: u64,
+        proofs: BTreeMap<XorName, [u8; 32]>,
+    ) -> Vec<Repair> {
+        let mut audits = self.audits.write().await;
+        let pending = match audits.get_mut(&audit) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        if !pending.awaiting.remove(&adult) {
+            return vec![];
+        }
+        for (name, proof) in proofs {
+            if pending.expected.contains_key(&name) {
+                let _prev = pending.proofs.entry(name).or_default().insert(adult, proof);
+            }
+        }
+
+        if !pending.awaiting.is_empty() {
+            return vec![];
+        }
+        match audits.remove(&audit) {
+            Some(audit) => self.conclude(audit).await,
+            None => vec![],
+        }
+    }
+
+    // Compares the proofs of an audit answered for, returning the repairs to be made.
+    async fn conclude(&self, mut audit: Audit) -> Vec<Repair> {
+        let mut stats = self.stats.write().await;
+        stats.audits += 1;
+
+        let mut repairs = vec![];
+        for (name, expected) in audit.expected {
+            stats.chunks_audited += 1;
+            let proofs = audit.proofs.remove(&name).unwrap_or_default();
+
+            let mut by_proof = BTreeMap::<[u8; 32], BTreeSet<XorName>>::new();
+            for (adult, proof) in &proofs {
+                let _new = by_proof.entry(*proof).or_default().insert(*adult);
+            }
+            let most = by_proof.values().map(BTreeSet::len).max().unwrap_or(0);
+            let mut agreeing = by_proof.values().filter(|adults| adults.len() == most);
+            let provers = match (agreeing.next(), agreeing.next()) {
+                (Some(provers), None) => provers,
+                (None, _) => {
+                    // Registers are in the registry too, and aren't proven.
+                    trace!("No holder of {:?} proved holding it", name);
+                    stats.unproven += 1;
+                    continue;
+                }
+                (Some(_), Some(_)) => {
+                    warn!(
+                        "Holders of chunk {:?} disagree on its proof: {:?}",
+                        name, by_proof
+                    );
+                    stats.disputed += 1;
+                    continue;
+                }
+            };
+
+            let missing = expected.difference(provers).copied().collect::<Vec<_>>();
+            if missing.is_empty() {
+                continue;
+            }
+            warn!(
+                "Holders {:?} of chunk {:?} couldn't prove holding it",
+                missing, name
+            );
+            stats.missing_copies += missing.len() as u64;
+            if let Some(source) = provers.iter().next() {
+                let address = ReplicatedDataAddress::Chunk(ChunkAddress(name));
+                repairs.extend(missing.into_iter().map(|target| Repair {
+                    address,
+                    source: *source,
+                    target,
+                }));
+            }
+        }
+        repairs
+    }
+}
+
+impl Node {
+    /// Challenges the holders of the next sample of chunks of our holder registry to prove they
+    /// hold them, as an elder.
+    pub(crate) async fn audit_next_chunks(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await {
+            return Ok(vec![]);
+        }
+
+        let adults = self
+            .network_knowledge
+            .adults()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .collect::<BTreeSet<_>>();
+        let weights = self.warm_up.weights().await;
+        let names = self.holder_registry.names().await;
+        let (challenge, repairs) = self.replication_audit.next(&names, &adults, &weights).await;
+
+        let mut cmds = self.repair_distribution(repairs).await?;
+        if let Some(challenge) = challenge {
+            trace!(
+                "Challenging {} adults to prove holding {} chunks, for audit {}",
+                challenge.holders.len(),
+                challenge.names.len(),
+                challenge.audit
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendHoldingProofs {
+                audit: challenge.audit,
+                nonce: challenge.nonce,
+                names: challenge.names,
+            });
+            cmds.extend(self.send_node_msg_to_nodes(msg, challenge.holders).await?);
+        }
+        Ok(cmds)
+    }
+
+    /// Records the proofs an adult gave of holding the chunks of a replication audit.
+    pub(crate) async fn record_holding_proofs(
+        &self,
+        adult: XorName,
+        audit: u64,
+        proofs: BTreeMap<XorName, [u8; 32]>,
+    ) -> Result<Vec<Cmd>> {
+        let repairs = self
+            .replication_audit
+            .record_proofs(adult, audit, proofs)
+            .await;
+        self.repair_distribution(repairs).await
+    }
+
+    /// On adults, sends the proofs of holding the chunks we hold among `names` to the
+    /// challenging elder.
+    pub(crate) async fn send_holding_proofs(
+        &self,
+        audit: u64,
+        nonce: [u8; 32],
+        names: BTreeSet<XorName>,
+        elder: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let mut proofs = BTreeMap::new();
+        for name in names {
+            let address = ReplicatedDataAddress::Chunk(ChunkAddress(name));
+            if let Ok(ReplicatedData::Chunk(chunk)) =
+                self.data_storage.get_for_replication(address).await
+            {
+                let _prev = proofs.insert(name, holding_proof(&nonce, chunk.value()));
+            }
+        }
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: SystemMsg::NodeCmd(NodeCmd::RecordHoldingProofs {
+                node_id,
+                audit,
+                proofs,
+            }),
+            dst: DstLocation::Node {
+                name: elder,
+                section_pk: self.network_knowledge.section_key().await,
+            },
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_interface::types::{holders_for, utils::random_bytes, Chunk};
+
+    fn adults(count: usize) -> BTreeSet<XorName> {
+        (0..count).map(|_| xor_name::rand::random()).collect()
+    }
+
+    #[tokio::test]
+    async fn holders_not_giving_the_agreed_proof_get_the_chunk_again() {
+        let adults = adults(data_copy_count() + 2);
+        let chunk = Chunk::new(random_bytes(100));
+        let name = *chunk.name();
+        let holders = holders_for(&name, &adults, data_copy_count())
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let audit = ReplicationAudit::default();
+        let challenge = audit
+            .next(&[name], &adults, &Weights::new())
+            .await
+            .0
+            .expect("a challenge");
+        assert_eq!(challenge.names, BTreeSet::from([name]));
+        assert_eq!(challenge.holders, holders.iter().copied().collect());
+
+        // The first holder lost the chunk, the second holds a corrupt copy.
+        let proof = holding_proof(&challenge.nonce, chunk.value());
+        let corrupt = holding_proof(&challenge.nonce, b"corrupt");
+        let mut repairs = vec![];
+        for (i, holder) in holders.iter().enumerate() {
+            let proofs = match i {
+                0 => BTreeMap::new(),
+                1 => BTreeMap::from([(name, corrupt)]),
+                _ => BTreeMap::from([(name, proof)]),
+            };
+            repairs.extend(audit.record_proofs(*holder, challenge.audit, proofs).await);
+        }
+
+        let mut targets = repairs
+            .iter()
+            .map(|repair| repair.target)
+            .collect::<Vec<_>>();
+        targets.sort();
+        let mut expected = holders[..2].to_vec();
+        expected.sort();
+        assert_eq!(targets, expected);
+        assert!(repairs
+            .iter()
+            .all(|repair| holders[2..].contains(&repair.source)));
+
+        let stats = audit.stats().await;
+        assert_eq!(stats.audits, 1);
+        assert_eq!(stats.missing_copies, 2);
+        assert_eq!(stats.disputed, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn holders_not_answering_in_time_count_as_missing_their_copy() {
+        let adults = adults(data_copy_count());
+        let chunk = Chunk::new(random_bytes(100));
+        let name = *chunk.name();
+
+        let audit = ReplicationAudit::default();
+        audit.set_chunks_per_tick(1);
+        let challenge = audit
+            .next(&[name], &adults, &Weights::new())
+            .await
+            .0
+            .expect("a challenge");
+        let mut holders = challenge.holders.iter();
+        let answering = *holders.next().expect("a holder");
+        let proof = holding_proof(&challenge.nonce, chunk.value());
+        assert!(audit
+            .record_proofs(answering, challenge.audit, BTreeMap::from([(name, proof)]))
+            .await
+            .is_empty());
+
+        tokio::time::advance(AUDIT_TIMEOUT + Duration::from_secs(1)).await;
+        let (_, repairs) = audit.next(&[], &adults, &Weights::new()).await;
+        assert_eq!(repairs.len(), data_copy_count() - 1);
+        assert!(repairs.iter().all(|repair| repair.source == answering));
+        assert_eq!(audit.stats().await.missing_copies, repairs.len() as u64);
+
+        audit.set_chunks_per_tick(0);
+        assert!(audit
+            .next(&[name], &adults, &Weights::new())
+            .await
+            .0
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn ties_between_proofs_are_disputed_rather_than_repaired() {
+        let adults = adults(2);
+        let chunk = Chunk::new(random_bytes(100));
+        let name = *chunk.name();
+
+        let audit = ReplicationAudit::default();
+        let challenge = audit
+            .next(&[name], &adults, &Weights::new())
+            .await
+            .0
+            .expect("a challenge");
+        let proofs = [
+            holding_proof(&challenge.nonce, chunk.value()),
+            holding_proof(&challenge.nonce, b"corrupt"),
+        ];
+        let mut repairs = vec![];
+        for (holder, proof) in challenge.holders.iter().zip(proofs) {
+            repairs.extend(
+                audit
+                    .record_proofs(*holder, challenge.audit, BTreeMap::from([(name, proof)]))
+                    .await,
+            );
+        }
+        assert!(repairs.is_empty());
+        assert_eq!(audit.stats().await.disputed, 1);
+    }
+}
@@ -0,0 +1,602 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise, SLED_FLUSH_TIME_MS};
+use crate::node::{
+    core::{Cmd, Node},
+    Error, Result,
+};
+
+use serde::{Deserialize, Serialize};
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::{ChunkAddress, ReplicatedDataAddress},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::{Prefix, XorName};
+
+const JOBS_DB_NAME: &str = "replication_jobs";
+/// Duties handed out per `DISTRIBUTION_CHECK_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_DUTIES_PER_TICK: usize = 16;
+// How often the target of a transferred duty is asked whether it holds the data.
+const CONFIRM_INTERVAL: Duration = Duration::from_secs(5);
+// How long a transferred duty may go unconfirmed before it's transferred again.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+// How many times a duty is transferred before it's given up on.
+const MAX_TRANSFERS: u8 = 3;
+
+/// What an operator asked to be replicated to its missing holders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ReplicationTarget {
+    /// The chunk of this name.
+    Name(XorName),
+    /// The chunks within this prefix, filling at most `max` gaps.
+    Prefix { prefix: Prefix, max: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum DutyState {
+    Queued,
+    Transferred,
+    Confirmed,
+    Failed,
+}
+
+// A copy of a chunk to be made at one of its missing holders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Duty {
+    name: XorName,
+    // A holder of the chunk, to replicate it from.
+    source: XorName,
+    // The missing holder.
+    target: XorName,
+    // Copies of the chunk left when the job started. The fewer, the sooner it's transferred.
+    copies: usize,
+    state: DutyState,
+    transfers: u8,
+    // Not persisted: after a restart, transferred duties are asked about and timed anew.
+    #[serde(skip)]
+    transferred_at: Option<Instant>,
+    #[serde(skip)]
+    asked_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Job {
+    target: ReplicationTarget,
+    duties: Vec<Duty>,
+    // Gaps left out for being beyond the max.
+    skipped: usize,
+    // Gaps with no holder left to replicate from.
+    unsourced: usize,
+}
+
+/// Where a replication job is at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JobProgress {
+    pub(crate) id: u64,
+    pub(crate) target: ReplicationTarget,
+    /// Copies yet to be made.
+    pub(crate) queued: usize,
+    /// Copies made, not yet confirmed by their holders.
+    pub(crate) transferred: usize,
+    /// Copies their holders confirmed holding.
+    pub(crate) confirmed: usize,
+    /// Copies given up on, after `MAX_TRANSFERS` unconfirmed transfers.
+    pub(crate) failed: usize,
+    /// Gaps left out for being beyond the max.
+    pub(crate) skipped: usize,
+    /// Gaps with no holder left to replicate from.
+    pub(crate) unsourced: usize,
+    /// Whether nothing is left to do.
+    pub(crate) done: bool,
+}
+
+/// A gap found in the holders of a chunk, as the canonical selection has them.
+pub(crate) struct Gap {
+    pub(crate) name: XorName,
+    pub(crate) source: Option<XorName>,
+    pub(crate) target: XorName,
+    pub(crate) copies: usize,
+}
+
+/// What to send for a round of duties.
+#[derive(Debug, Default)]
+pub(crate) struct Round {
+    /// Chunks to replicate, by (source, target).
+    pub(crate) transfers: BTreeMap<(XorName, XorName), Vec<ReplicatedDataAddress>>,
+    /// Names to ask each target about, whether it holds them now.
+    pub(crate) asks: BTreeMap<XorName, BTreeSet<XorName>>,
+}
+
+/// Elders' jobs of replicating chunks to their missing holders, as operators ask for during
+/// incidents rather than waiting for a distribution check to find the gaps.
+///
+/// Each job's copies are handed out to their sources a few per tick, those of the chunks with
+/// the fewest copies left first, then confirmed by asking their targets what they hold. Jobs are
+/// persisted as they go, so they carry on after a restart.
+#[derive(Clone)]
+pub(crate) struct ReplicationJobs {
+    db: sled::Db,
+    jobs: Arc<RwLock<BTreeMap<u64, Job>>>,
+    duties_per_tick: Arc<AtomicUsize>,
+}
+
+impl ReplicationJobs {
+    pub(crate) fn new(root_dir: &Path) -> Result<Self> {
+        let db = sled::Config::default()
+            .path(root_dir.join("db").join(JOBS_DB_NAME))
+            .flush_every_ms(SLED_FLUSH_TIME_MS)
+            .open()?;
+
+        let mut jobs = BTreeMap::new();
+        for entry in db.iter() {
+            let (id, job) = entry?;
+            let _prev = jobs.insert(deserialise(&id)?, deserialise(&job)?);
+        }
+
+        Ok(Self {
+            db,
+            jobs: Arc::new(RwLock::new(jobs)),
+            duties_per_tick: Arc::new(AtomicUsize::new(DEFAULT_DUTIES_PER_TICK)),
+        })
+    }
+
+    /// Sets how many duties are handed out per `DISTRIBUTION_CHECK_INTERVAL`.
+    #[cfg(test)]
+    pub(crate) fn set_duties_per_tick(&self, duties_per_tick: usize) {
+        self.duties_per_tick
+            .store(duties_per_tick.max(1), Ordering::Relaxed);
+    }
+
+    /// Adds a job filling `gaps`, the most urgent first up to the max of `target`, returning
+    /// its id.
+    pub(crate) async fn add(&self, target: ReplicationTarget, mut gaps: Vec<Gap>) -> Result<u64> {
+        gaps.sort_by_key(|gap| (gap.copies, gap.name));
+        let max = match target {
+            ReplicationTarget::Name(_) => usize::MAX,
+            ReplicationTarget::Prefix { max, .. } => max,
+        };
+
+        let mut job = Job {
+            target,
+            duties: vec![],
+            skipped: 0,
+            unsourced: 0,
+        };
+        for gap in gaps {
+            let source = match gap.source {
+                Some(source) => source,
+                None => {
+                    job.unsourced += 1;
+                    continue;
+                }
+            };
+            if job.duties.len() == max {
+                job.skipped += 1;
+                continue;
+            }
+            job.duties.push(Duty {
+                name: gap.name,
+                source,
+                target: gap.target,
+                copies: gap.copies,
+                state: DutyState::Queued,
+                transfers: 0,
+                transferred_at: None,
+                asked_at: None,
+            });
+        }
+
+        let mut jobs = self.jobs.write().await;
+        let id = jobs.keys().next_back().map_or(0, |id| id + 1);
+        info!(
+            "Replication job {} for {:?}: {} copies to make, {} beyond the max, {} without a source",
+            id,
+            target,
+            job.duties.len(),
+            job.skipped,
+            job.unsourced
+        );
+        self.persist(id, &job).await?;
+        let _prev = jobs.insert(id, job);
+        Ok(id)
+    }
+
+    /// Takes the next round of duties: the transfers of the most urgent queued copies, up to the
+    /// duties per tick, and the asks of the targets of earlier transfers.
+    pub(crate) async fn next_round(&self) -> Result<Round> {
+        let budget = self.duties_per_tick.load(Ordering::Relaxed);
+        let mut jobs = self.jobs.write().await;
+        let mut round = Round::default();
+        let mut changed = BTreeSet::new();
+
+        for (id, job) in jobs.iter_mut() {
+            for duty in &mut job.duties {
+                if duty.state != DutyState::Transferred {
+                    continue;
+                }
+                let transferred_at = *duty.transferred_at.get_or_insert_with(Instant::now);
+                if transferred_at.elapsed() > CONFIRM_TIMEOUT {
+                    duty.state = if duty.transfers >= MAX_TRANSFERS {
+                        warn!(
+                            "Giving up on replicating {:?} to {:?}, unconfirmed after {} transfers",
+                            duty.name, duty.target, duty.transfers
+                        );
+                        DutyState::Failed
+                    } else {
+                        DutyState::Queued
+                    };
+                    let _new = changed.insert(*id);
+                } else if duty
+                    .asked_at
+                    .map_or(true, |asked_at| asked_at.elapsed() >= CONFIRM_INTERVAL)
+                {
+                    duty.asked_at = Some(Instant::now());
+                    let _new = round.asks.entry(duty.target).or_default().insert(duty.name);
+                }
+            }
+        }
+
+        let mut queued = jobs
+            .iter_mut()
+            .flat_map(|(id, job)| job.duties.iter_mut().map(move |duty| (*id, duty)))
+            .filter(|(_, duty)| duty.state == DutyState::Queued)
+            .collect::<Vec<_>>();
+        queued.sort_by_key(|(id, duty)| (duty.copies, *id));
+        for (id, duty) in queued.into_iter().take(budget) {
+            duty.state = DutyState::Transferred;
+            duty.transfers += 1;
+            duty.transferred_at = Some(Instant::now());
+            duty.asked_at = None;
+            round
+                .transfers
+                .entry((duty.source, duty.target))
+                .or_default()
+                .push(ReplicatedDataAddress::Chunk(ChunkAddress(duty.name)));
+            let _new = changed.insert(id);
+        }
+
+        for id in changed {
+            if let Some(job) = jobs.get(&id) {
+                self.persist(id, job).await?;
+            }
+        }
+        Ok(round)
+    }
+
+    /// Confirms the copies `adult` was to hold among `held`, returning their names.
+    pub(crate) async fn confirm(
+        &self,
+        adult: XorName,
+        held: &BTreeSet<XorName>,
+    ) -> Result<BTreeSet<XorName>> {
+        let mut jobs = self.jobs.write().await;
+        let mut confirmed = BTreeSet::new();
+        for (id, job) in jobs.iter_mut() {
+            let mut changed = false;
+            for duty in &mut job.duties {
+                if duty.state == DutyState::Transferred
+                    && duty.target == adult
+                    && held.contains(&duty.name)
+                {
+                    duty.state = DutyState::Confirmed;
+                    let _new = confirmed.insert(duty.name);
+                    changed = true;
+                }
+            }
+            if changed {
+                self.persist(*id, job).await?;
+                if job.is_done() {
+                    info!("Replication job {} done", id);
+                }
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// The progress of the job `id`, if there's one.
+    pub(crate) async fn progress(&self, id: u64) -> Option<JobProgress> {
+        let jobs = self.jobs.read().await;
+        let job = jobs.get(&id)?;
+        let count = |state| job.duties.iter().filter(|duty| duty.state == state).count();
+        Some(JobProgress {
+            id,
+            target: job.target,
+            queued: count(DutyState::Queued),
+            transferred: count(DutyState::Transferred),
+            confirmed: count(DutyState::Confirmed),
+            failed: count(DutyState::Failed),
+            skipped: job.skipped,
+            unsourced: job.unsourced,
+            done: job.is_done(),
+        })
+    }
+
+    async fn persist(&self, id: u64, job: &Job) -> Result<()> {
+        let _prev = self.db.insert(serialise(&id)?, serialise(job)?)?;
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+impl Job {
+    fn is_done(&self) -> bool {
+        self.duties
+            .iter()
+            .all(|duty| matches!(duty.state, DutyState::Confirmed | DutyState::Failed))
+    }
+}
+
+impl Node {
+    /// Starts replicating the chunks of `target` to their holders missing them, as an elder,
+    /// returning the id of the job doing it.
+    ///
+    /// The holders a chunk is missing from are those of the canonical selection which our holder
+    /// registry doesn't have as holding it, so the target must be within our section, and
+    /// within the slices of the registry rebuilt so far.
+    pub(crate) async fn start_replication_job(&self, target: ReplicationTarget) -> Result<u64> {
+        if !self.is_elder().await {
+            return Err(Error::InvalidState);
+        }
+        let our_prefix = self.network_knowledge.prefix().await;
+        let names = match target {
+            ReplicationTarget::Name(name) => {
+                if !our_prefix.matches(&name) {
+                    return Err(Error::InvalidReplicationTarget(format!(
+                        "{:?} isn't within our section {:?}",
+                        name, our_prefix
+                    )));
+                }
+                if self.holder_registry.holders_of(&name).await.is_none() {
+                    return Err(Error::InvalidReplicationTarget(format!(
+                        "{:?} isn't in our holder registry",
+                        name
+                    )));
+                }
+                vec![name]
+            }
+            ReplicationTarget::Prefix { prefix, .. } => {
+                if !our_prefix.is_compatible(&prefix) {
+                    return Err(Error::InvalidReplicationTarget(format!(
+                        "{:?} doesn't overlap our section {:?}",
+                        prefix, our_prefix
+                    )));
+                }
+                self.holder_registry
+                    .names()
+                    .await
+                    .into_iter()
+                    .filter(|name| prefix.matches(name) && our_prefix.matches(name))
+                    .collect()
+            }
+        };
+
+        let adults = self
+            .network_knowledge
+            .adults()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .collect::<BTreeSet<_>>();
+        let mut gaps = vec![];
+        for name in names {
+            let holders = match self.holder_registry.holders_of(&name).await {
+                Some(holders) => holders,
+                // Not rebuilt yet, we can't tell where it's missing from.
+                None => continue,
+            };
+            let holders = holders
+                .intersection(&adults)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            let expected = self.get_adults_who_should_store_data(name).await;
+            gaps.extend(expected.difference(&holders).map(|target| Gap {
+                name,
+                source: holders.iter().next().copied(),
+                target: *target,
+                copies: holders.len(),
+            }));
+        }
+        self.replication_jobs.add(target, gaps).await
+    }
+
+    /// Hands out the next round of duties of the replication jobs: the sources of the copies to
+    /// make replicate them to their targets, and the targets of those made are asked whether
+    /// they hold them.
+    pub(crate) async fn run_replication_jobs(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await {
+            return Ok(vec![]);
+        }
+        let round = self.replication_jobs.next_round().await?;
+
+        let mut cmds = vec![];
+        for ((source, target), addresses) in round.transfers {
+            debug!(
+                "Having {:?} replicate {} chunks to {:?}",
+                source,
+                addresses.len(),
+                target
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::ReplicateDataTo {
+                addresses,
+                holder: target,
+            });
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([source]))
+                    .await?,
+            );
+        }
+        for (target, names) in round.asks {
+            // Answered for with `RecordHeld`, which the distribution check ignores, as it's
+            // for a batch of its own.
+            let batch = self.distribution_check.batch_id();
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendHeld { batch, names });
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([target]))
+                    .await?,
+            );
+        }
+        Ok(cmds)
+    }
+
+    /// Has the chunks `adult` found corrupt and dropped replicated to it again from their other
+    /// holders, as an elder, a replication job per chunk.
+    pub(crate) async fn replicate_lost_chunks(
+        &self,
+        adult: XorName,
+        names: BTreeSet<XorName>,
+    ) -> Result<()> {
+        if !self.is_elder().await {
+            return Ok(());
+        }
+        for name in names {
+            self.holder_registry.forget_holder(name, adult).await?;
+            match self
+                .start_replication_job(ReplicationTarget::Name(name))
+                .await
+            {
+                Ok(id) => info!(
+                    "Replicating {:?}, lost by {:?}, back to it with job {}",
+                    name, adult, id
+                ),
+                Err(error) => warn!(
+                    "Can't replicate {:?}, lost by {:?}, back to it: {}",
+                    name, adult, error
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    // Confirms the copies of the replication jobs `adult` holds among `held`, recording it as
+    // their holder.
+    pub(super) async fn confirm_replications(
+        &self,
+        adult: XorName,
+        held: &BTreeSet<ReplicatedDataAddress>,
+    ) -> Result<()> {
+        let held = held.iter().map(|address| *address.name()).collect();
+        for name in self.replication_jobs.confirm(adult, &held).await? {
+            self.holder_registry
+                .record_holders(name, &BTreeSet::from([adult]))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn gap(copies: usize) -> Gap {
+        Gap {
+            name: xor_name::rand::random(),
+            source: Some(xor_name::rand::random()),
+            target: xor_name::rand::random(),
+            copies,
+        }
+    }
+
+    #[tokio::test]
+    async fn gaps_are_bounded_and_the_most_urgent_go_first() -> Result<()> {
+        let root = tempdir()?;
+        let jobs = ReplicationJobs::new(root.path())?;
+        jobs.set_duties_per_tick(1);
+
+        let (one_copy, two_copies, sourceless) = (
+            gap(1),
+            gap(2),
+            Gap {
+                source: None,
+                ..gap(0)
+            },
+        );
+        let (urgent, next) = (one_copy.name, two_copies.name);
+        let target = ReplicationTarget::Prefix {
+            prefix: Prefix::default(),
+            max: 2,
+        };
+        let id = jobs
+            .add(target, vec![two_copies, gap(3), one_copy, sourceless])
+            .await?;
+        let progress = jobs.progress(id).await.ok_or(Error::InvalidState)?;
+        assert_eq!(
+            (progress.queued, progress.skipped, progress.unsourced),
+            (2, 1, 1)
+        );
+
+        let transferred = |round: &Round| {
+            round
+                .transfers
+                .values()
+                .flatten()
+                .map(|address| *address.name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(transferred(&jobs.next_round().await?), vec![urgent]);
+        let round = jobs.next_round().await?;
+        assert_eq!(transferred(&round), vec![next]);
+        assert_eq!(
+            round.asks.values().flatten().collect::<Vec<_>>(),
+            vec![&urgent]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn jobs_carry_on_after_a_restart() -> Result<()> {
+        let root = tempdir()?;
+        let gaps = vec![gap(1), gap(1)];
+        let (done, pending) = (
+            (gaps[0].name, gaps[0].target),
+            (gaps[1].name, gaps[1].target),
+        );
+        let id = {
+            let jobs = ReplicationJobs::new(root.path())?;
+            let id = jobs.add(ReplicationTarget::Name(done.0), gaps).await?;
+            let _round = jobs.next_round().await?;
+            let confirmed = jobs.confirm(done.1, &BTreeSet::from([done.0])).await?;
+            assert_eq!(confirmed, BTreeSet::from([done.0]));
+            id
+        };
+
+        let jobs = ReplicationJobs::new(root.path())?;
+        let progress = jobs.progress(id).await.ok_or(Error::InvalidState)?;
+        assert_eq!((progress.transferred, progress.confirmed), (1, 1));
+        assert!(!progress.done);
+
+        // The target of the transfer made before the restart is asked about it again.
+        let round = jobs.next_round().await?;
+        assert!(round.transfers.is_empty());
+        assert_eq!(
+            round.asks,
+            BTreeMap::from([(pending.1, BTreeSet::from([pending.0]))])
+        );
+        let _confirmed = jobs
+            .confirm(pending.1, &BTreeSet::from([pending.0]))
+            .await?;
+        assert!(jobs
+            .progress(id)
+            .await
+            .map_or(false, |progress| progress.done));
+        assert_eq!(jobs.progress(id + 1).await, None);
+        Ok(())
+    }
+}
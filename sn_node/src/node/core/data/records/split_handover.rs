@@ -0,0 +1,529 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise, SLED_FLUSH_TIME_MS};
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use serde::{Deserialize, Serialize};
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::{PublicKey, ReplicatedDataAddress},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::{Prefix, XorName};
+
+const HANDOVER_DB_NAME: &str = "split_handover";
+/// Copies handed over per `DISTRIBUTION_CHECK_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_HANDOVERS_PER_TICK: usize = 16;
+// How often the target of a copy handed over is asked whether it holds the data.
+const CONFIRM_INTERVAL: Duration = Duration::from_secs(5);
+// How long a copy may go unconfirmed before it's handed over again.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+// How many times a copy is handed over before it's given up on.
+const MAX_TRANSFERS: u8 = 3;
+// Batch the targets are asked what they hold for, which they echo back and we don't need.
+const HANDOVER_BATCH: u64 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum CopyState {
+    Queued,
+    Sent,
+    Confirmed,
+    Failed,
+}
+
+// A copy of data of ours to be held by an adult of the sibling section.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct HandoverCopy {
+    address: ReplicatedDataAddress,
+    target: XorName,
+    state: CopyState,
+    transfers: u8,
+    // Not persisted: after a restart, copies sent are asked about and timed anew.
+    #[serde(skip)]
+    sent_at: Option<Instant>,
+    #[serde(skip)]
+    asked_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Handover {
+    copies: Vec<HandoverCopy>,
+    // Whether our elders were told it's done.
+    signalled: bool,
+}
+
+/// Where the handover of our data to a sibling section is at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HandoverProgress {
+    pub(crate) sibling: Prefix,
+    /// Copies yet to be handed over.
+    pub(crate) queued: usize,
+    /// Copies handed over, not yet confirmed by their holders.
+    pub(crate) sent: usize,
+    /// Copies their holders confirmed holding.
+    pub(crate) confirmed: usize,
+    /// Copies given up on, after `MAX_TRANSFERS` unconfirmed transfers.
+    pub(crate) failed: usize,
+    /// Whether nothing is left to do.
+    pub(crate) done: bool,
+}
+
+/// What to send for a round of the handovers.
+#[derive(Debug, Default)]
+pub(crate) struct HandoverRound {
+    /// Data to hand over, by target.
+    pub(crate) transfers: BTreeMap<XorName, Vec<ReplicatedDataAddress>>,
+    /// Names to ask each target about, whether it holds them now.
+    pub(crate) asks: BTreeMap<XorName, BTreeSet<XorName>>,
+}
+
+/// Adults' handovers of the data they hold which belongs to a sibling section after a split,
+/// to the adults of that section which are to hold it.
+///
+/// Copies are handed over a few per tick, then confirmed by asking their targets what they hold,
+/// and the data dropped once all of its copies are confirmed. Handovers are persisted as they go,
+/// so they carry on after a restart, and our elders are told once each is done.
+#[derive(Clone)]
+pub(crate) struct SplitHandover {
+    db: sled::Db,
+    handovers: Arc<RwLock<BTreeMap<Prefix, Handover>>>,
+    copies_per_tick: Arc<AtomicUsize>,
+}
+
+impl SplitHandover {
+    pub(crate) fn new(root_dir: &Path) -> Result<Self> {
+        let db = sled::Config::default()
+            .path(root_dir.join("db").join(HANDOVER_DB_NAME))
+            .flush_every_ms(SLED_FLUSH_TIME_MS)
+            .open()?;
+
+        let mut handovers = BTreeMap::new();
+        for entry in db.iter() {
+            let (sibling, handover) = entry?;
+            let _prev = handovers.insert(deserialise(&sibling)?, deserialise(&handover)?);
+        }
+
+        Ok(Self {
+            db,
+            handovers: Arc::new(RwLock::new(handovers)),
+            copies_per_tick: Arc::new(AtomicUsize::new(DEFAULT_HANDOVERS_PER_TICK)),
+        })
+    }
+
+    /// Sets how many copies are handed over per `DISTRIBUTION_CHECK_INTERVAL`.
+    #[cfg(test)]
+    pub(crate) fn set_copies_per_tick(&self, copies_per_tick: usize) {
+        self.copies_per_tick
+            .store(copies_per_tick.max(1), Ordering::Relaxed);
+    }
+
+    /// Adds the `copies` to hand over to the `sibling` section, each of the data at an address
+    /// to its target, leaving out those already being handed over.
+    pub(crate) async fn add(
+        &self,
+        sibling: Prefix,
+        copies: Vec<(ReplicatedDataAddress, XorName)>,
+    ) -> Result<()> {
+        let mut handovers = self.handovers.write().await;
+        let handover = handovers.entry(sibling).or_default();
+        let known = handover
+            .copies
+            .iter()
+            .map(|copy| (copy.address, copy.target))
+            .collect::<BTreeSet<_>>();
+        let mut added = 0;
+        for (address, target) in copies {
+            if known.contains(&(address, target)) {
+                continue;
+            }
+            handover.copies.push(HandoverCopy {
+                address,
+                target,
+                state: CopyState::Queued,
+                transfers: 0,
+                sent_at: None,
+                asked_at: None,
+            });
+            added += 1;
+        }
+        if added > 0 {
+            handover.signalled = false;
+        }
+        info!(
+            "Handing over {} copies of our data to the sibling section {:?}",
+            added, sibling
+        );
+        self.persist(&sibling, handover).await
+    }
+
+    /// Takes the next round of the handovers: the transfers of the queued copies, up to the
+    /// copies per tick, and the asks of the targets of earlier transfers.
+    pub(crate) async fn next_round(&self) -> Result<HandoverRound> {
+        let mut budget = self.copies_per_tick.load(Ordering::Relaxed);
+        let mut handovers = self.handovers.write().await;
+        let mut round = HandoverRound::default();
+
+        for (sibling, handover) in handovers.iter_mut() {
+            let mut changed = false;
+            for copy in &mut handover.copies {
+                match copy.state {
+                    CopyState::Sent => {
+                        let sent_at = *copy.sent_at.get_or_insert_with(Instant::now);
+                        if sent_at.elapsed() > CONFIRM_TIMEOUT {
+                            copy.state = if copy.transfers >= MAX_TRANSFERS {
+                                warn!(
+                                    "Giving up on handing {:?} over to {:?}, unconfirmed after {} transfers",
+                                    copy.address, copy.target, copy.transfers
+                                );
+                                CopyState::Failed
+                            } else {
+                                CopyState::Queued
+                            };
+                            changed = true;
+                        } else if copy
+                            .asked_at
+                            .map_or(true, |asked_at| asked_at.elapsed() >= CONFIRM_INTERVAL)
+                        {
+                            copy.asked_at = Some(Instant::now());
+                            let _new = round
+                                .asks
+                                .entry(copy.target)
+                                .or_default()
+                                .insert(*copy.address.name());
+                        }
+                    }
+                    CopyState::Queued if budget > 0 => {
+                        budget -= 1;
+                        copy.state = CopyState::Sent;
+                        copy.transfers += 1;
+                        copy.sent_at = Some(Instant::now());
+                        copy.asked_at = None;
+                        round
+                            .transfers
+                            .entry(copy.target)
+                            .or_default()
+                            .push(copy.address);
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+            if changed {
+                self.persist(sibling, handover).await?;
+            }
+        }
+        Ok(round)
+    }
+
+    /// Confirms the copies `adult` was to hold among `held`, returning the addresses of the
+    /// data all of whose copies are now confirmed, which we needn't hold anymore.
+    pub(crate) async fn confirm(
+        &self,
+        adult: XorName,
+        held: &BTreeSet<XorName>,
+    ) -> Result<Vec<ReplicatedDataAddress>> {
+        let mut handovers = self.handovers.write().await;
+        let mut handed_over = vec![];
+        for (sibling, handover) in handovers.iter_mut() {
+            let mut confirmed = BTreeSet::new();
+            for copy in &mut handover.copies {
+                if copy.state == CopyState::Sent
+                    && copy.target == adult
+                    && held.contains(copy.address.name())
+                {
+                    copy.state = CopyState::Confirmed;
+                    let _new = confirmed.insert(copy.address);
+                }
+            }
+            if confirmed.is_empty() {
+                continue;
+            }
+            self.persist(sibling, handover).await?;
+            handed_over.extend(confirmed.into_iter().filter(|address| {
+                handover
+                    .copies
+                    .iter()
+                    .filter(|copy| copy.address == *address)
+                    .all(|copy| copy.state == CopyState::Confirmed)
+            }));
+        }
+        Ok(handed_over)
+    }
+
+    /// Takes the progress of the handovers done which our elders weren't told about yet.
+    pub(crate) async fn take_done(&self) -> Result<Vec<HandoverProgress>> {
+        let mut handovers = self.handovers.write().await;
+        let mut done = vec![];
+        for (sibling, handover) in handovers.iter_mut() {
+            if handover.signalled || !handover.is_done() {
+                continue;
+            }
+            handover.signalled = true;
+            self.persist(sibling, handover).await?;
+            done.push(handover.progress(*sibling));
+        }
+        Ok(done)
+    }
+
+    /// The progress of each handover.
+    pub(crate) async fn progress(&self) -> Vec<HandoverProgress> {
+        self.handovers
+            .read()
+            .await
+            .iter()
+            .map(|(sibling, handover)| handover.progress(*sibling))
+            .collect()
+    }
+
+    async fn persist(&self, sibling: &Prefix, handover: &Handover) -> Result<()> {
+        let _prev = self.db.insert(serialise(sibling)?, serialise(handover)?)?;
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+impl Handover {
+    fn is_done(&self) -> bool {
+        self.copies
+            .iter()
+            .all(|copy| matches!(copy.state, CopyState::Confirmed | CopyState::Failed))
+    }
+
+    fn progress(&self, sibling: Prefix) -> HandoverProgress {
+        let count = |state| {
+            self.copies
+                .iter()
+                .filter(|copy| copy.state == state)
+                .count()
+        };
+        HandoverProgress {
+            sibling,
+            queued: count(CopyState::Queued),
+            sent: count(CopyState::Sent),
+            confirmed: count(CopyState::Confirmed),
+            failed: count(CopyState::Failed),
+            done: self.is_done(),
+        }
+    }
+}
+
+impl Node {
+    /// Starts handing the data we hold which belongs to our sibling section over to its adults,
+    /// as an adult whose section split from `old_prefix`. The adults of the sibling section are
+    /// those among the `old_adults` of the section which aren't its elders.
+    pub(crate) async fn start_split_handover(
+        &self,
+        old_prefix: Prefix,
+        old_adults: &BTreeSet<XorName>,
+    ) -> Result<()> {
+        let our_prefix = self.network_knowledge.prefix().await;
+        if self.is_elder().await
+            || our_prefix == old_prefix
+            || !our_prefix.is_extension_of(&old_prefix)
+        {
+            return Ok(());
+        }
+        let sibling = our_prefix.sibling();
+        let sibling_elders = self
+            .network_knowledge
+            .section_by_name(&sibling.name())
+            .map(|sap| sap.names())
+            .unwrap_or_default();
+        let sibling_adults = old_adults
+            .iter()
+            .filter(|name| sibling.matches(name) && !sibling_elders.contains(name))
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        let mut copies = vec![];
+        for address in self.data_storage.keys().await? {
+            if sibling.matches(address.name()) {
+                copies.extend(
+                    self.compute_holders(&address, &sibling_adults)
+                        .into_iter()
+                        .map(|target| (address, target)),
+                );
+            }
+        }
+        if copies.is_empty() {
+            return Ok(());
+        }
+        self.split_handover.add(sibling, copies).await
+    }
+
+    /// Hands out the next round of our handovers, as an adult: the copies to hand over are sent
+    /// to their targets, the targets of those sent are asked whether they hold them, and our
+    /// elders are told of the handovers done.
+    pub(crate) async fn run_split_handover(&self) -> Result<Vec<Cmd>> {
+        if self.is_elder().await {
+            return Ok(vec![]);
+        }
+        let round = self.split_handover.next_round().await?;
+
+        let mut cmds = vec![];
+        for (target, addresses) in round.transfers {
+            debug!(
+                "Handing {} copies of our data over to {:?}",
+                addresses.len(),
+                target
+            );
+            cmds.extend(self.replicate_data_to(addresses, target).await?);
+        }
+        for (target, names) in round.asks {
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendHeld {
+                batch: HANDOVER_BATCH,
+                names,
+            });
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([target]))
+                    .await?,
+            );
+        }
+
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        for progress in self.split_handover.take_done().await? {
+            info!(
+                "Handed our data over to the sibling section {:?}: {} copies confirmed, {} given up on",
+                progress.sibling, progress.confirmed, progress.failed
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::RecordSplitHandover {
+                node_id,
+                section: XorName::from(node_id),
+                sibling: progress.sibling,
+                handed_over: progress.confirmed as u64,
+                failed: progress.failed as u64,
+            });
+            cmds.push(self.send_msg_to_our_elders(msg).await?);
+        }
+        Ok(cmds)
+    }
+
+    // Confirms the copies handed over to `adult` it holds among `held`, dropping the data all
+    // of whose copies are confirmed.
+    pub(crate) async fn confirm_split_handover(
+        &self,
+        adult: XorName,
+        held: &BTreeSet<ReplicatedDataAddress>,
+    ) -> Result<()> {
+        let held = held.iter().map(|address| *address.name()).collect();
+        for address in self.split_handover.confirm(adult, &held).await? {
+            trace!("Dropping {:?}, handed over to our sibling section", address);
+            self.data_storage.remove(&address).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Error;
+    use sn_interface::types::ChunkAddress;
+    use tempfile::tempdir;
+
+    fn chunk_address() -> ReplicatedDataAddress {
+        ReplicatedDataAddress::Chunk(ChunkAddress(xor_name::rand::random()))
+    }
+
+    fn transferred(round: &HandoverRound) -> Vec<(XorName, ReplicatedDataAddress)> {
+        round
+            .transfers
+            .iter()
+            .flat_map(|(target, addresses)| addresses.iter().map(|address| (*target, *address)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn handovers_are_throttled_and_data_dropped_once_confirmed() -> Result<()> {
+        let root = tempdir()?;
+        let handover = SplitHandover::new(root.path())?;
+        handover.set_copies_per_tick(2);
+
+        let sibling = Prefix::default().pushed(true);
+        let address = chunk_address();
+        let (first, second, third) = (
+            xor_name::rand::random(),
+            xor_name::rand::random(),
+            xor_name::rand::random(),
+        );
+        let copies = vec![(address, first), (address, second), (address, third)];
+        handover.add(sibling, copies.clone()).await?;
+        // Adding the same copies again doesn't hand them over twice.
+        handover.add(sibling, copies).await?;
+
+        assert_eq!(transferred(&handover.next_round().await?).len(), 2);
+        let round = handover.next_round().await?;
+        assert_eq!(transferred(&round), vec![(third, address)]);
+        assert_eq!(round.asks.len(), 2);
+
+        let held = BTreeSet::from([*address.name()]);
+        assert!(handover.confirm(first, &held).await?.is_empty());
+        assert!(handover.confirm(second, &held).await?.is_empty());
+        assert!(handover.take_done().await?.is_empty());
+        // Only once the last copy is confirmed can the data be dropped.
+        assert_eq!(handover.confirm(third, &held).await?, vec![address]);
+
+        let done = handover.take_done().await?;
+        assert_eq!(done.len(), 1);
+        assert_eq!((done[0].sibling, done[0].confirmed), (sibling, 3));
+        assert!(done[0].done);
+        // Our elders are told only once.
+        assert!(handover.take_done().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn handovers_carry_on_after_a_restart() -> Result<()> {
+        let root = tempdir()?;
+        let sibling = Prefix::default().pushed(false);
+        let (confirmed, pending) = (
+            (chunk_address(), xor_name::rand::random()),
+            (chunk_address(), xor_name::rand::random()),
+        );
+        {
+            let handover = SplitHandover::new(root.path())?;
+            handover.add(sibling, vec![confirmed, pending]).await?;
+            let _round = handover.next_round().await?;
+            let dropped = handover
+                .confirm(confirmed.1, &BTreeSet::from([*confirmed.0.name()]))
+                .await?;
+            assert_eq!(dropped, vec![confirmed.0]);
+        }
+
+        let handover = SplitHandover::new(root.path())?;
+        let progress = handover.progress().await.pop().ok_or(Error::InvalidState)?;
+        assert_eq!((progress.sent, progress.confirmed), (1, 1));
+        assert!(!progress.done);
+
+        // The target of the copy sent before the restart is asked about it again.
+        let round = handover.next_round().await?;
+        assert!(round.transfers.is_empty());
+        assert_eq!(
+            round.asks,
+            BTreeMap::from([(pending.1, BTreeSet::from([*pending.0.name()]))])
+        );
+        let _dropped = handover
+            .confirm(pending.1, &BTreeSet::from([*pending.0.name()]))
+            .await?;
+        assert_eq!(handover.take_done().await?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::Weights,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// How long adults new to the network warm up for, when unspecified.
+pub(crate) const DEFAULT_WARM_UP: Duration = Duration::from_secs(60 * 60);
+/// Share of its data an adult is assigned as it joins, in percent, when unspecified.
+pub(crate) const DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT: u8 = 10;
+/// How often each adult warming up is asked whether it holds data it was assigned.
+pub(crate) const WARM_UP_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+// Names last assigned to an adult warming up, which it's asked about.
+const PROBED_NAMES: usize = 32;
+// How long an adult is given to store a name it's assigned, before it's asked about it.
+const PROBE_GRACE: Duration = Duration::from_secs(10);
+
+/// How adults new to the network are eased into holding data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct WarmUpPolicy {
+    /// How long it takes an adult to be assigned its full share of data. No adult warms up
+    /// if it's zero.
+    pub(crate) period: Duration,
+    /// Share of its data an adult is assigned as it joins, from 0 to 1.
+    pub(crate) initial_share: f64,
+}
+
+impl WarmUpPolicy {
+    /// No warm-up, as until a policy is set from the config.
+    pub(crate) fn disabled() -> Self {
+        Self {
+            period: Duration::ZERO,
+            initial_share: 1.0,
+        }
+    }
+
+    /// The share of its data an adult is assigned after `tenure`, ramping up linearly from the
+    /// initial share to all of it over the period.
+    pub(crate) fn weight(&self, tenure: Duration) -> f64 {
+        if tenure >= self.period {
+            return 1.0;
+        }
+        let ramp = tenure.as_secs_f64() / self.period.as_secs_f64();
+        let initial = self.initial_share.clamp(0.0, 1.0);
+        initial + (1.0 - initial) * ramp
+    }
+}
+
+#[derive(Debug)]
+struct Probe {
+    batch: u64,
+    names: BTreeSet<XorName>,
+}
+
+#[derive(Debug)]
+struct WarmingUp {
+    joined_at: Instant,
+    // Names last assigned, with when.
+    assigned: VecDeque<(XorName, Instant)>,
+    last_probe: Option<Instant>,
+    probe: Option<Probe>,
+}
+
+impl WarmingUp {
+    fn new(now: Instant) -> Self {
+        Self {
+            joined_at: now,
+            assigned: VecDeque::new(),
+            last_probe: None,
+            probe: None,
+        }
+    }
+}
+
+/// Elders' tracking of the adults new to the network as they warm up.
+///
+/// An adult warming up is assigned only a share of the data it's among the closest adults to,
+/// as per its weight in `weighted_holders`, ramping up over the warm-up period, so its
+/// unproven disk doesn't hold copies that count towards the data's redundancy. It's asked
+/// every `WARM_UP_PROBE_INTERVAL` whether it holds what it was assigned, and starts its
+/// warm-up over if it doesn't.
+///
+/// Adults relocated to our section warm up no more, being proven elsewhere, nor do those we
+/// didn't see join, e.g. before we were promoted.
+#[derive(Clone, Debug)]
+pub(crate) struct WarmUp {
+    policy: Arc<RwLock<WarmUpPolicy>>,
+    adults: Arc<RwLock<BTreeMap<XorName, WarmingUp>>>,
+}
+
+impl Default for WarmUp {
+    fn default() -> Self {
+        Self {
+            policy: Arc::new(RwLock::new(WarmUpPolicy::disabled())),
+            adults: Arc::default(),
+        }
+    }
+}
+
+impl WarmUp {
+    pub(crate) async fn set_policy(&self, policy: WarmUpPolicy) {
+        *self.policy.write().await = policy;
+    }
+
+    /// Starts the warm-up of `adult`, which just joined the network.
+    pub(crate) async fn adult_joined(&self, adult: XorName) {
+        if self.policy.read().await.period.is_zero() {
+            return;
+        }
+        info!("Adult {adult} is warming up");
+        let _prev = self
+            .adults
+            .write()
+            .await
+            .insert(adult, WarmingUp::new(Instant::now()));
+    }
+
+    /// Stops tracking the adults which aren't among `members`.
+    pub(crate) async fn retain_members_only(&self, members: &BTreeSet<XorName>) {
+        self.adults
+            .write()
+            .await
+            .retain(|adult, _| members.contains(adult));
+    }
+
+    /// The weights of the adults warming up, for the holders of data to be chosen with, as
+    /// `weighted_holders`. Adults done warming up are dropped.
+    pub(crate) async fn weights(&self) -> Weights {
+        let policy = *self.policy.read().await;
+        let now = Instant::now();
+        let mut adults = self.adults.write().await;
+        adults.retain(|adult, warming_up| {
+            let done = now.duration_since(warming_up.joined_at) >= policy.period;
+            if done {
+                info!("Adult {adult} is done warming up");
+            }
+            !done
+        });
+        adults
+            .iter()
+            .map(|(adult, warming_up)| {
+                let weight = policy.weight(now.duration_since(warming_up.joined_at));
+                (*adult, weight)
+            })
+            .collect()
+    }
+
+    /// Records that the data named `name` was assigned to `holders`, for those warming up to
+    /// be asked about it.
+    pub(crate) async fn record_assigned(&self, name: XorName, holders: &BTreeSet<XorName>) {
+        let now = Instant::now();
+        let mut adults = self.adults.write().await;
+        for holder in holders {
+            if let Some(warming_up) = adults.get_mut(holder) {
+                warming_up.assigned.push_back((name, now));
+                if warming_up.assigned.len() > PROBED_NAMES {
+                    let _oldest = warming_up.assigned.pop_front();
+                }
+            }
+        }
+    }
+
+    /// The adults due to be asked whether they hold the names they were assigned, with the
+    /// batch id of each, from `batch_id`. A probe still unanswered counts as failed.
+    pub(crate) async fn next_probes(
+        &self,
+        mut batch_id: impl FnMut() -> u64,
+    ) -> Vec<(XorName, u64, BTreeSet<XorName>)> {
+        let now = Instant::now();
+        let mut adults = self.adults.write().await;
+        let mut probes = vec![];
+        for (adult, warming_up) in adults.iter_mut() {
+            let due = warming_up.last_probe.map_or(true, |last| {
+                now.duration_since(last) >= WARM_UP_PROBE_INTERVAL
+            });
+            if !due {
+                continue;
+            }
+            if warming_up.probe.take().is_some() {
+                warn!("Adult {adult} didn't answer its warm-up probe, starting its warm-up over");
+                *warming_up = WarmingUp::new(now);
+                continue;
+            }
+            let names = warming_up
+                .assigned
+                .iter()
+                .filter(|(_, assigned_at)| now.duration_since(*assigned_at) >= PROBE_GRACE)
+                .map(|(name, _)| *name)
+                .collect::<BTreeSet<_>>();
+            if names.is_empty() {
+                continue;
+            }
+            let batch = batch_id();
+            warming_up.last_probe = Some(now);
+            warming_up.probe = Some(Probe {
+                batch,
+                names: names.clone(),
+            });
+            probes.push((*adult, batch, names));
+        }
+        probes
+    }
+
+    /// Records which of the names of its probe `batch` `adult` holds, returning whether it was
+    /// such a probe. An adult missing any of them starts its warm-up over.
+    pub(crate) async fn record_held(
+        &self,
+        adult: XorName,
+        batch: u64,
+        held: &BTreeSet<XorName>,
+    ) -> bool {
+        let mut adults = self.adults.write().await;
+        let warming_up = match adults.get_mut(&adult) {
+            Some(warming_up) => warming_up,
+            None => return false,
+        };
+        let probe = match warming_up.probe.take() {
+            Some(probe) if probe.batch == batch => probe,
+            other => {
+                warming_up.probe = other;
+                return false;
+            }
+        };
+
+        let missing = probe.names.difference(held).count();
+        if missing == 0 {
+            debug!(
+                "Adult {adult} holds the {} names of its warm-up probe",
+                probe.names.len()
+            );
+        } else {
+            warn!(
+                "Adult {adult} is missing {missing} of the {} names of its warm-up probe, \
+                starting its warm-up over",
+                probe.names.len()
+            );
+            *warming_up = WarmingUp::new(Instant::now());
+        }
+        true
+    }
+
+    /// Moves the warm-up of `adult`, and what it was assigned, `by` back in time.
+    #[cfg(test)]
+    pub(crate) async fn backdate(&self, adult: &XorName, by: Duration) {
+        if let Some(warming_up) = self.adults.write().await.get_mut(adult) {
+            warming_up.joined_at -= by;
+            for (_, assigned_at) in warming_up.assigned.iter_mut() {
+                *assigned_at -= by;
+            }
+            if let Some(last_probe) = &mut warming_up.last_probe {
+                *last_probe -= by;
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Asks the adults warming up whether they hold data they were assigned, as an elder.
+    pub(crate) async fn probe_warming_up_adults(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await {
+            return Ok(vec![]);
+        }
+        let mut cmds = vec![];
+        let probes = self
+            .warm_up
+            .next_probes(|| self.distribution_check.batch_id())
+            .await;
+        for (adult, batch, names) in probes {
+            trace!(
+                "Asking adult {adult} whether it holds {} names",
+                names.len()
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::SendHeld { batch, names });
+            cmds.extend(
+                self.send_node_msg_to_nodes(msg, BTreeSet::from([adult]))
+                    .await?,
+            );
+        }
+        Ok(cmds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> WarmUpPolicy {
+        WarmUpPolicy {
+            period: Duration::from_secs(100),
+            initial_share: 0.2,
+        }
+    }
+
+    #[test]
+    fn weight_ramps_up_linearly_over_the_period() {
+        let policy = policy();
+        let weight = |secs| policy.weight(Duration::from_secs(secs));
+        assert!((weight(0) - 0.2).abs() < 1e-9);
+        assert!((weight(50) - 0.6).abs() < 1e-9);
+        assert!((weight(100) - 1.0).abs() < 1e-9);
+        assert!((weight(1000) - 1.0).abs() < 1e-9);
+        assert!((WarmUpPolicy::disabled().weight(Duration::ZERO) - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn adults_missing_what_they_were_assigned_start_over() {
+        let warm_up = WarmUp::default();
+        let adult = xor_name::rand::random();
+        warm_up.adult_joined(adult).await;
+        assert!(warm_up.weights().await.is_empty());
+
+        warm_up.set_policy(policy()).await;
+        warm_up.adult_joined(adult).await;
+        let names = (0..3)
+            .map(|_| xor_name::rand::random())
+            .collect::<BTreeSet<XorName>>();
+        for name in &names {
+            warm_up
+                .record_assigned(*name, &BTreeSet::from([adult]))
+                .await;
+        }
+        let mut batches = 0..;
+        let mut batch_id = || batches.next().unwrap_or_default();
+        // Nothing to ask about until the adult had time to store it.
+        assert!(warm_up.next_probes(&mut batch_id).await.is_empty());
+
+        warm_up.backdate(&adult, Duration::from_secs(50)).await;
+        let probes = warm_up.next_probes(&mut batch_id).await;
+        assert_eq!(probes, vec![(adult, 0, names.clone())]);
+        assert!(warm_up.record_held(adult, 0, &names).await);
+        assert!(!warm_up.record_held(adult, 0, &names).await);
+        assert!((warm_up.weights().await[&adult] - 0.6).abs() < 0.01);
+
+        // Not due again before the interval.
+        assert!(warm_up.next_probes(&mut batch_id).await.is_empty());
+        warm_up.backdate(&adult, WARM_UP_PROBE_INTERVAL).await;
+        let probes = warm_up.next_probes(&mut batch_id).await;
+        assert_eq!(probes.len(), 1);
+        let missing_one = names.iter().skip(1).copied().collect();
+        assert!(warm_up.record_held(adult, 1, &missing_one).await);
+        assert!((warm_up.weights().await[&adult] - 0.2).abs() < 0.01);
+
+        warm_up.backdate(&adult, Duration::from_secs(100)).await;
+        assert!(warm_up.weights().await.is_empty());
+    }
+}
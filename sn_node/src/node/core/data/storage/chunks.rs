@@ -6,7 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::dbs::{convert_to_error_msg, ChunkStore, Error, Result};
+#[cfg(any(test, feature = "chaos"))]
+use crate::dbs::FaultyDisk;
+#[cfg(feature = "metrics")]
+use crate::dbs::ReadPacing;
+use crate::dbs::{
+    convert_to_error_msg, ChunkBackendKind, ChunkStore, Error, ReadClass, ReadThrottle,
+    Reconciliation, Result, Scrubbed,
+};
 use crate::UsedSpace;
 use sn_interface::messaging::system::NodeQueryResponse;
 use sn_interface::types::{log_markers::LogMarker, Chunk, ChunkAddress};
@@ -15,8 +22,10 @@ use std::{
     fmt::{self, Display, Formatter},
     io::ErrorKind,
     path::Path,
+    time::Duration,
 };
 use tracing::info;
+use xor_name::XorName;
 
 /// Operations on data chunks.
 #[derive(Clone)]
@@ -31,20 +40,110 @@ impl ChunkStorage {
         })
     }
 
+    pub(crate) async fn self_test(&self) -> Result<()> {
+        self.db.self_test().await
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_disk(self, disk: FaultyDisk) -> Self {
+        Self {
+            db: self.db.with_disk(disk),
+        }
+    }
+
+    #[cfg(any(test, feature = "chaos"))]
+    pub(crate) fn disk(&self) -> &FaultyDisk {
+        self.db.disk()
+    }
+
+    pub(crate) fn approaching_db_reserve(&self) -> bool {
+        self.db.approaching_db_reserve()
+    }
+
+    pub(crate) fn headroom(&self) -> usize {
+        self.db.chunk_headroom()
+    }
+
     pub(crate) fn keys(&self) -> Result<Vec<ChunkAddress>> {
         self.db.list_all_chunk_addresses()
     }
 
+    pub(crate) fn count(&self) -> usize {
+        self.db.chunk_count()
+    }
+
+    pub(crate) fn backend_kind(&self) -> ChunkBackendKind {
+        self.db.backend_kind()
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.db.is_encrypted()
+    }
+
+    /// Bytes the backend takes on disk, off the async runtime as it may walk the chunk dir.
+    pub(crate) async fn backend_used_space(&self) -> Result<u64> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db.backend_used_space())
+            .await
+            .map_err(|error| Error::Io(std::io::Error::new(ErrorKind::Other, error)))?
+    }
+
+    /// Reconciles the chunk index with the chunk files, off the async runtime as it walks the
+    /// whole chunk dir.
+    pub(crate) async fn reconcile(&self) -> Result<Reconciliation> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db.reconcile())
+            .await
+            .map_err(|error| Error::Io(std::io::Error::new(ErrorKind::Other, error)))?
+    }
+
+    /// Addresses of up to `count` chunks held with names after `after`, or from the first
+    /// without one, in order of their names.
+    pub(crate) fn addresses_after(
+        &self,
+        after: Option<&XorName>,
+        count: usize,
+    ) -> Result<Vec<ChunkAddress>> {
+        self.db.chunk_addresses_after(after, count)
+    }
+
+    /// Checks the chunk at `address` still hashes to its name, quarantining it if it doesn't.
+    pub(crate) async fn scrub(&self, address: &ChunkAddress) -> Result<Scrubbed> {
+        self.db.scrub_chunk(address).await
+    }
+
     #[allow(dead_code)]
     pub(crate) async fn remove_chunk(&self, address: &ChunkAddress) -> Result<()> {
         trace!("Removing chunk, {:?}", address);
         self.db.delete_chunk(address).await
     }
 
-    pub(crate) async fn get_chunk(&self, address: &ChunkAddress) -> Result<Chunk> {
+    pub(crate) fn set_read_throttle(&self, throttle: ReadThrottle) {
+        self.db.set_read_throttle(throttle)
+    }
+
+    pub(crate) fn set_journal_flush_interval(&self, interval: Duration) {
+        self.db.set_journal_flush_interval(interval)
+    }
+
+    pub(crate) async fn replay_journal(&self) -> Result<usize> {
+        self.db.replay_journal().await
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn read_pacing(&self) -> ReadPacing {
+        self.db.read_pacing()
+    }
+
+    #[instrument(skip(self))]
+    pub(crate) async fn get_chunk(
+        &self,
+        address: &ChunkAddress,
+        class: ReadClass,
+    ) -> Result<Chunk> {
         debug!("Getting chunk {:?}", address);
 
-        match self.db.read_chunk(address).await {
+        match self.db.read_chunk(address, class).await {
             Ok(res) => Ok(res),
             Err(error) => match error {
                 Error::Io(io_error) if io_error.kind() == ErrorKind::NotFound => {
@@ -58,14 +157,15 @@ impl ChunkStorage {
     // Read chunk from local store and return NodeQueryResponse
     pub(crate) async fn get(&self, address: &ChunkAddress) -> NodeQueryResponse {
         trace!("{:?}", LogMarker::ChunkQueryReceviedAtAdult);
-        NodeQueryResponse::GetChunk(self.get_chunk(address).await.map_err(convert_to_error_msg))
+        let result = self.get_chunk(address, ReadClass::Interactive).await;
+        NodeQueryResponse::GetChunk(result.map_err(convert_to_error_msg))
     }
 
     /// Store a chunk in the local disk store
     /// If that chunk was already in the local store, just overwrites it
-    #[instrument(skip_all)]
+    #[instrument(skip_all, fields(address = ?data.address()))]
     pub(super) async fn store(&self, data: &Chunk) -> Result<()> {
-        if self.db.chunk_file_exists(data.address())? {
+        if self.db.has_chunk(data.address())? {
             info!(
                 "{}: Chunk already exists, not storing: {:?}",
                 self,
@@ -78,6 +178,7 @@ impl ChunkStorage {
         // cheap extra security check for space (prone to race conditions)
         // just so we don't go too much overboard
         // should not be triggered as chunks should not be sent to full adults
+        // this also keeps chunks out of the space reserved for the dbs
         if !self.db.can_add(data.value().len()) {
             return Err(Error::NotEnoughSpace);
         }
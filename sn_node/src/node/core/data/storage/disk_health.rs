@@ -0,0 +1,291 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{Cmd, Node},
+    Event, Result,
+};
+
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::PublicKey,
+};
+
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use xor_name::XorName;
+
+/// Consecutive failed disk self-tests after which our storage is considered unhealthy.
+pub(crate) const DEFAULT_DISK_FAILURE_THRESHOLD: usize = 3;
+/// Consecutive passed disk self-tests after which unhealthy storage is considered recovered.
+pub(crate) const DEFAULT_DISK_RECOVERY_THRESHOLD: usize = 5;
+
+/// A change of our storage health, following a disk self-test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HealthChange {
+    BecameUnhealthy,
+    Recovered,
+}
+
+#[derive(Debug)]
+struct HealthState {
+    healthy: bool,
+    consecutive_failures: usize,
+    consecutive_passes: usize,
+    failure_threshold: usize,
+    recovery_threshold: usize,
+}
+
+/// Tracks the results of the periodic disk self-test, deciding whether our storage can be
+/// trusted with new data.
+#[derive(Clone, Debug)]
+pub(crate) struct DiskHealth {
+    state: Arc<RwLock<HealthState>>,
+}
+
+impl Default for DiskHealth {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_DISK_FAILURE_THRESHOLD,
+            DEFAULT_DISK_RECOVERY_THRESHOLD,
+        )
+    }
+}
+
+impl DiskHealth {
+    pub(crate) fn new(failure_threshold: usize, recovery_threshold: usize) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(HealthState {
+                healthy: true,
+                consecutive_failures: 0,
+                consecutive_passes: 0,
+                failure_threshold: failure_threshold.max(1),
+                recovery_threshold: recovery_threshold.max(1),
+            })),
+        }
+    }
+
+    pub(crate) async fn set_thresholds(&self, failure_threshold: usize, recovery_threshold: usize) {
+        let mut state = self.state.write().await;
+        state.failure_threshold = failure_threshold.max(1);
+        state.recovery_threshold = recovery_threshold.max(1);
+    }
+
+    pub(crate) async fn is_healthy(&self) -> bool {
+        self.state.read().await.healthy
+    }
+
+    /// Records the outcome of a self-test, returning the resulting change of health, if any.
+    pub(crate) async fn record(&self, passed: bool) -> Option<HealthChange> {
+        let mut state = self.state.write().await;
+        if passed {
+            state.consecutive_failures = 0;
+            state.consecutive_passes += 1;
+            if !state.healthy && state.consecutive_passes >= state.recovery_threshold {
+                state.healthy = true;
+                return Some(HealthChange::Recovered);
+            }
+        } else {
+            state.consecutive_passes = 0;
+            state.consecutive_failures += 1;
+            if state.healthy && state.consecutive_failures >= state.failure_threshold {
+                state.healthy = false;
+                return Some(HealthChange::BecameUnhealthy);
+            }
+        }
+        None
+    }
+}
+
+impl Node {
+    /// Runs the disk self-test on adults. When our storage health changes as a result, our
+    /// elders are told, so they stop or resume sending us new data to store.
+    pub(crate) async fn check_disk_health(&self) -> Result<Vec<Cmd>> {
+        if self.is_elder().await {
+            return Ok(vec![]);
+        }
+
+        let change = match self.data_storage.run_disk_self_test().await {
+            Some(change) => change,
+            None => return Ok(vec![]),
+        };
+        let healthy = change == HealthChange::Recovered;
+        if healthy {
+            info!("Storage passed its disk self-tests again, accepting new data");
+        } else {
+            error!("Storage keeps failing its disk self-test, not accepting new data");
+        }
+
+        self.send_event(Event::StorageHealthChanged { healthy })
+            .await;
+
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        let msg = SystemMsg::NodeCmd(NodeCmd::RecordStorageHealth {
+            node_id,
+            section: XorName::from(node_id),
+            healthy,
+        });
+        Ok(vec![self.send_msg_to_our_elders(msg).await?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbs::{Error as DbError, FaultyDisk};
+    use crate::node::{
+        api::tests::create_comm, core::data::DataStorage, create_test_max_capacity_and_root_storage,
+    };
+    use crate::UsedSpace;
+
+    use sn_interface::{
+        elder_count,
+        network_knowledge::{
+            test_utils::{gen_section_authority_provider, section_signed},
+            NetworkKnowledge, NodeInfo, MIN_ADULT_AGE,
+        },
+        types::{keys::ed25519, Chunk, ReplicatedData},
+    };
+
+    use assert_matches::assert_matches;
+    use bytes::Bytes;
+    use eyre::Result;
+    use secured_linked_list::SecuredLinkedList;
+    use tempfile::tempdir;
+    use tokio::sync::mpsc;
+    use xor_name::Prefix;
+
+    #[tokio::test]
+    async fn health_changes_after_consecutive_results() {
+        let health = DiskHealth::new(3, 2);
+
+        // Failures need to be consecutive.
+        assert_eq!(health.record(false).await, None);
+        assert_eq!(health.record(false).await, None);
+        assert_eq!(health.record(true).await, None);
+        assert_eq!(health.record(false).await, None);
+        assert_eq!(health.record(false).await, None);
+        assert!(health.is_healthy().await);
+        assert_eq!(
+            health.record(false).await,
+            Some(HealthChange::BecameUnhealthy)
+        );
+        assert!(!health.is_healthy().await);
+        assert_eq!(health.record(false).await, None);
+
+        // So do passes.
+        assert_eq!(health.record(true).await, None);
+        assert_eq!(health.record(false).await, None);
+        assert_eq!(health.record(true).await, None);
+        assert!(!health.is_healthy().await);
+        assert_eq!(health.record(true).await, Some(HealthChange::Recovered));
+        assert!(health.is_healthy().await);
+        assert_eq!(health.record(true).await, None);
+    }
+
+    #[tokio::test]
+    async fn unhealthy_storage_refuses_new_chunks_but_serves_stored_ones() -> Result<()> {
+        let root = tempdir()?;
+        let storage = DataStorage::new(root.path(), UsedSpace::new(usize::MAX))?;
+        storage.disk_health().set_thresholds(2, 2).await;
+
+        let stored = ReplicatedData::Chunk(Chunk::new(Bytes::from("stored before failing")));
+        let _level = storage.store(&stored).await?;
+
+        // Writes which don't make it to disk fail the self-test, as much as corrupting reads.
+        storage
+            .faulty_disk()
+            .update(|faults| faults.fsync_fails = true);
+        assert_eq!(storage.run_disk_self_test().await, None);
+        assert_eq!(
+            storage.run_disk_self_test().await,
+            Some(HealthChange::BecameUnhealthy)
+        );
+
+        let new = ReplicatedData::Chunk(Chunk::new(Bytes::from("stored while failing")));
+        assert_matches!(storage.store(&new).await, Err(DbError::StorageUnhealthy));
+        assert_eq!(
+            storage.get_from_local_store(&stored.address()).await?,
+            stored
+        );
+
+        storage.faulty_disk().heal();
+        assert_eq!(storage.run_disk_self_test().await, None);
+        assert_eq!(
+            storage.run_disk_self_test().await,
+            Some(HealthChange::Recovered)
+        );
+        let _level = storage.store(&new).await?;
+        assert_eq!(storage.get_from_local_store(&new.address()).await?, new);
+
+        Ok(())
+    }
+
+    // An adult in a section of `elder_count()` elders, on the given disk.
+    async fn adult(disk: FaultyDisk) -> Result<Node> {
+        let (sap, _, sk_set) = gen_section_authority_provider(Prefix::default(), elder_count());
+        let section_chain = SecuredLinkedList::new(sk_set.public_keys().public_key());
+        let signed_sap = section_signed(sk_set.secret_key(), sap)?;
+        let network_knowledge =
+            NetworkKnowledge::new(*section_chain.root_key(), section_chain, signed_sap, None)?;
+
+        let comm = create_comm().await?;
+        let info = NodeInfo::new(
+            ed25519::gen_keypair(&Prefix::default().range_inclusive(), MIN_ADULT_AGE),
+            comm.our_connection_info(),
+        );
+        let (max_capacity, root_dir) = create_test_max_capacity_and_root_storage()?;
+        let node = Node::new(
+            comm,
+            info,
+            network_knowledge,
+            None,
+            mpsc::channel(10).0,
+            UsedSpace::new(max_capacity),
+            root_dir,
+        )
+        .await?
+        .with_faulty_disk(disk);
+        Ok(node)
+    }
+
+    fn is_storage_health_report(cmd: &Cmd, expected: bool) -> bool {
+        matches!(
+            cmd,
+            Cmd::SendMsg { wire_msg, .. } if matches!(
+                wire_msg.clone().into_msg(),
+                Ok(sn_interface::messaging::MsgType::System {
+                    msg: SystemMsg::NodeCmd(NodeCmd::RecordStorageHealth { healthy, .. }),
+                    ..
+                }) if healthy == expected
+            )
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn adult_reports_health_changes_to_its_elders() -> Result<()> {
+        let disk = FaultyDisk::default();
+        let node = adult(disk.clone()).await?;
+        node.data_storage.disk_health().set_thresholds(2, 1).await;
+
+        disk.update(|faults| faults.corrupt_reads = true);
+        assert!(node.check_disk_health().await?.is_empty());
+        let cmds = node.check_disk_health().await?;
+        assert_eq!(cmds.len(), 1);
+        assert!(is_storage_health_report(&cmds[0], false));
+        assert!(!node.data_storage.disk_health().is_healthy().await);
+
+        disk.heal();
+        let cmds = node.check_disk_health().await?;
+        assert_eq!(cmds.len(), 1);
+        assert!(is_storage_health_report(&cmds[0], true));
+        assert!(node.data_storage.disk_health().is_healthy().await);
+
+        Ok(())
+    }
+}
@@ -7,11 +7,19 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod chunks;
+mod disk_health;
 mod registers;
+mod scrubber;
+mod shares;
 
+#[cfg(any(test, feature = "chaos"))]
+use crate::dbs::FaultyDisk;
 use crate::{
-    dbs::Result,
-    node::core::{Cmd, Node},
+    dbs::{ChunkBackendKind, Error, ReadClass, ReadThrottle, Reconciliation, Result},
+    node::{
+        core::{Cmd, Node, MIN_LEVEL_WHEN_FULL},
+        Event,
+    },
     UsedSpace,
 };
 
@@ -20,17 +28,30 @@ use sn_interface::messaging::{
     system::{NodeCmd, NodeQueryResponse, SystemMsg},
     DstLocation,
 };
-use sn_interface::types::{register::User, ReplicatedData, ReplicatedDataAddress as DataAddress};
+use sn_interface::types::{
+    register::User, ChunkShare, ReplicatedData, ReplicatedDataAddress as DataAddress,
+};
 
 pub(crate) use chunks::ChunkStorage;
+pub(crate) use disk_health::{DEFAULT_DISK_FAILURE_THRESHOLD, DEFAULT_DISK_RECOVERY_THRESHOLD};
+
+use disk_health::{DiskHealth, HealthChange};
 pub(crate) use registers::RegisterStorage;
+pub(crate) use scrubber::{DEFAULT_SCRUB_CHUNKS_PER_TICK, SCRUB_INTERVAL};
+
+use scrubber::ChunkScrubber;
+use shares::ShareStorage;
 
 use sn_interface::types::ReplicatedDataAddress;
 use std::collections::btree_map::Entry;
 use std::{
     collections::{BTreeMap, BTreeSet},
     path::Path,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use tokio::sync::RwLock;
 use tracing::info;
@@ -41,8 +62,12 @@ use xor_name::XorName;
 pub(crate) struct DataStorage {
     chunks: ChunkStorage,
     registers: RegisterStorage,
+    shares: ShareStorage,
     used_space: UsedSpace,
     last_recorded_level: Arc<RwLock<StorageLevel>>,
+    disk_health: DiskHealth,
+    scrubber: ChunkScrubber,
+    db_reserve_warned: Arc<AtomicBool>,
 }
 
 impl DataStorage {
@@ -50,17 +75,120 @@ impl DataStorage {
         Ok(Self {
             chunks: ChunkStorage::new(path, used_space.clone())?,
             registers: RegisterStorage::new(path, used_space.clone())?,
+            shares: ShareStorage::new(path, used_space.clone())?,
             used_space,
             last_recorded_level: Arc::new(RwLock::new(StorageLevel::zero())),
+            disk_health: DiskHealth::default(),
+            scrubber: ChunkScrubber::default(),
+            db_reserve_warned: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    pub(crate) fn disk_health(&self) -> &DiskHealth {
+        &self.disk_health
+    }
+
+    /// Runs the disk self-test, returning the resulting change of our storage health, if any.
+    pub(crate) async fn run_disk_self_test(&self) -> Option<HealthChange> {
+        let result = self.chunks.self_test().await;
+        if let Err(error) = &result {
+            warn!("Disk self-test failed: {error}");
+        }
+        self.disk_health.record(result.is_ok()).await
+    }
+
+    pub(crate) fn scrubber(&self) -> &ChunkScrubber {
+        &self.scrubber
+    }
+
+    /// Scrubs the next batch of our chunks, returning the names of those found corrupt and
+    /// quarantined.
+    pub(crate) async fn scrub_next_chunks(&self) -> Result<BTreeSet<XorName>> {
+        self.scrubber.scrub_next(&self.chunks).await
+    }
+
+    /// Returns how many more bytes of chunks we can take, if chunks just came within one db
+    /// reserve's worth of the space reserved for the dbs. Reported once per approach.
+    pub(crate) fn newly_approaching_db_reserve(&self) -> Option<usize> {
+        let approaching = self.chunks.approaching_db_reserve();
+        let warned = self.db_reserve_warned.swap(approaching, Ordering::Relaxed);
+        (approaching && !warned).then(|| self.chunks.headroom())
+    }
+
+    /// Whether we've reported being full, as per the storage level last recorded.
+    pub(crate) async fn is_full(&self) -> bool {
+        self.last_recorded_level.read().await.value() >= MIN_LEVEL_WHEN_FULL
+    }
+
+    pub(crate) fn used_space(&self) -> &UsedSpace {
+        &self.used_space
+    }
+
+    /// Bytes of chunks we can still take.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn chunk_headroom(&self) -> usize {
+        self.chunks.headroom()
+    }
+
+    /// Changes our max capacity, returning our storage level if that changed it. A lowered
+    /// capacity doesn't evict anything: what doesn't fit is kept, new data is refused.
+    pub(crate) async fn set_max_capacity(&self, max_capacity: usize) -> Option<StorageLevel> {
+        self.used_space.set_max_capacity(max_capacity);
+
+        let level = self.current_level()?;
+        let mut last_recorded_level = self.last_recorded_level.write().await;
+        if *last_recorded_level == level {
+            return None;
+        }
+        *last_recorded_level = level;
+        Some(level)
+    }
+
+    // Our storage level: the share of our capacity used, in 10 %-points, but at least
+    // `MIN_LEVEL_WHEN_FULL` past the soft watermark, for our elders to give us no new data before
+    // we have to refuse it.
+    fn current_level(&self) -> Option<StorageLevel> {
+        let used_space_level = ((10.0 * self.used_space.ratio()) as u8).min(StorageLevel::MAX);
+        let level = if self.used_space.past_soft_watermark() {
+            used_space_level.max(MIN_LEVEL_WHEN_FULL)
+        } else {
+            used_space_level
+        };
+        StorageLevel::from(level).ok()
+    }
+
+    /// Writes any buffered changes to disk, chunks being written as they're stored.
+    pub(crate) async fn flush(&self) -> Result<()> {
+        self.registers.flush().await
+    }
+
+    /// Has our chunks read and written through the given `disk`, whose faults are scripted by
+    /// the test holding a clone of it.
+    #[cfg(test)]
+    pub(crate) fn with_faulty_disk(mut self, disk: FaultyDisk) -> Self {
+        self.chunks = self.chunks.with_disk(disk);
+        self
+    }
+
+    /// The disk our chunks are read and written through.
+    #[cfg(any(test, feature = "chaos"))]
+    pub(crate) fn faulty_disk(&self) -> &FaultyDisk {
+        self.chunks.disk()
+    }
+
     /// Store data in the local store
     #[instrument(skip(self))]
     pub(crate) async fn store(&self, data: &ReplicatedData) -> Result<Option<StorageLevel>> {
         debug!("Replicating {data:?}");
         match data.clone() {
-            ReplicatedData::Chunk(chunk) => self.chunks.store(&chunk).await?,
+            ReplicatedData::Chunk(chunk) => {
+                // Keep serving what we hold, but don't take on new chunks while our disk is
+                // failing.
+                if !self.disk_health.is_healthy().await {
+                    return Err(Error::StorageUnhealthy);
+                }
+                self.chunks.store(&chunk).await?
+            }
             ReplicatedData::RegisterLog(data) => {
                 self.registers
                     .update(RegisterStoreExport(vec![data]))
@@ -69,22 +197,43 @@ impl DataStorage {
             ReplicatedData::RegisterWrite(cmd) => self.registers.write(cmd).await?,
         };
 
-        // check if we've filled another approx. 10%-points of our storage
-        // if so, update the recorded level
+        Ok(self.new_level_reached().await)
+    }
+
+    /// Store a share of an erasure coded chunk in the local store
+    #[instrument(skip(self))]
+    pub(crate) async fn store_share(&self, share: &ChunkShare) -> Result<Option<StorageLevel>> {
+        // As with chunks, no new shares are taken on while our disk is failing.
+        if !self.disk_health.is_healthy().await {
+            return Err(Error::StorageUnhealthy);
+        }
+        self.shares.store(share).await?;
+
+        Ok(self.new_level_reached().await)
+    }
+
+    /// The shares of the erasure coded chunk `name` we hold.
+    pub(crate) async fn chunk_shares(&self, name: &XorName) -> Result<Vec<ChunkShare>> {
+        self.shares.shares_of(name).await
+    }
+
+    // Our storage level, if data just stored got us to a new one.
+    async fn new_level_reached(&self) -> Option<StorageLevel> {
+        // check if we've filled another approx. 10%-points of our storage, or gone past our soft
+        // watermark; if so, update the recorded level
         let last_recorded_level = { *self.last_recorded_level.read().await };
-        if let Ok(next_level) = last_recorded_level.next() {
-            // used_space_ratio is a heavy task that's why we don't do it all the time
-            let used_space_ratio = self.used_space.ratio();
-            let used_space_level = 10.0 * used_space_ratio;
+        if last_recorded_level.value() < StorageLevel::MAX {
             // every level represents 10 percentage points
-            if used_space_level as u8 >= next_level.value() {
-                debug!("Next level for storage has been reached");
-                *self.last_recorded_level.write().await = next_level;
-                return Ok(Some(next_level));
+            if let Some(level) = self.current_level() {
+                if level.value() > last_recorded_level.value() {
+                    debug!("Next level for storage has been reached");
+                    *self.last_recorded_level.write().await = level;
+                    return Some(level);
+                }
             }
         }
 
-        Ok(None)
+        None
     }
 
     // Query the local store and return NodeQueryResponse
@@ -103,9 +252,11 @@ impl DataStorage {
         address: &DataAddress,
     ) -> Result<ReplicatedData> {
         match address {
-            DataAddress::Chunk(addr) => {
-                self.chunks.get_chunk(addr).await.map(ReplicatedData::Chunk)
-            }
+            DataAddress::Chunk(addr) => self
+                .chunks
+                .get_chunk(addr, ReadClass::Interactive)
+                .await
+                .map(ReplicatedData::Chunk),
             DataAddress::Register(addr) => self
                 .registers
                 .get_register_replica(addr)
@@ -114,7 +265,6 @@ impl DataStorage {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) async fn remove(&self, address: &DataAddress) -> Result<()> {
         match address {
             DataAddress::Chunk(addr) => self.chunks.remove_chunk(addr).await,
@@ -122,7 +272,32 @@ impl DataStorage {
         }
     }
 
-    async fn keys(&self) -> Result<Vec<DataAddress>> {
+    /// Number of chunks held, as per the chunk index.
+    pub(crate) fn chunk_count(&self) -> usize {
+        self.chunks.count()
+    }
+
+    /// The backend our chunks are kept in.
+    pub(crate) fn chunk_backend(&self) -> ChunkBackendKind {
+        self.chunks.backend_kind()
+    }
+
+    /// Whether our chunks are kept encrypted at rest.
+    pub(crate) fn chunks_encrypted(&self) -> bool {
+        self.chunks.is_encrypted()
+    }
+
+    /// Bytes the backend of our chunks takes on disk, with its overheads.
+    pub(crate) async fn chunk_backend_used_space(&self) -> Result<u64> {
+        self.chunks.backend_used_space().await
+    }
+
+    /// Reconciles the chunk index with the chunk files, repairing any mismatch.
+    pub(crate) async fn reconcile_chunk_index(&self) -> Result<Reconciliation> {
+        self.chunks.reconcile().await
+    }
+
+    pub(crate) async fn keys(&self) -> Result<Vec<DataAddress>> {
         let chunk_keys = self.chunks.keys()?.into_iter().map(DataAddress::Chunk);
         let reg_keys = self
             .registers
@@ -133,15 +308,67 @@ impl DataStorage {
         Ok(reg_keys.chain(chunk_keys).collect())
     }
 
+    /// Reads data to be replicated, which as a background read is held back for client reads.
     pub(crate) async fn get_for_replication(
         &self,
         data_address: ReplicatedDataAddress,
     ) -> Result<ReplicatedData> {
-        self.get_from_local_store(&data_address).await
+        match data_address {
+            DataAddress::Chunk(addr) => self
+                .chunks
+                .get_chunk(&addr, ReadClass::Background)
+                .await
+                .map(ReplicatedData::Chunk),
+            DataAddress::Register(_) => self.get_from_local_store(&data_address).await,
+        }
+    }
+
+    /// Sets how background chunk reads are throttled.
+    pub(crate) fn set_read_throttle(&self, throttle: ReadThrottle) {
+        self.chunks.set_read_throttle(throttle)
+    }
+
+    /// Sets the interval within which chunk writes are committed together to the journal, zero
+    /// having each synced on its own.
+    pub(crate) fn set_chunk_journal_flush_interval(&self, interval: Duration) {
+        self.chunks.set_journal_flush_interval(interval)
+    }
+
+    /// Applies the chunk writes and deletions a crash left in the journal. To be done before
+    /// the storage is used.
+    pub(crate) async fn replay_chunk_journal(&self) -> Result<usize> {
+        self.chunks.replay_journal().await
+    }
+
+    /// Where the pacing of background chunk reads is at.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn read_pacing(&self) -> crate::dbs::ReadPacing {
+        self.chunks.read_pacing()
     }
 }
 
 impl Node {
+    /// Changes our max capacity at runtime. As an adult, our elders are told of our resulting
+    /// storage level if it changed, so they stop, or resume, giving us new data. Either way,
+    /// `Event::CapacityChanged` is raised once the new capacity applies.
+    pub(crate) async fn set_capacity(&self, max_capacity: usize) -> Vec<Cmd> {
+        info!("Setting our max capacity to {} bytes", max_capacity);
+        let level = self.data_storage.set_max_capacity(max_capacity).await;
+        let cmds = if self.is_elder().await {
+            vec![]
+        } else {
+            self.record_storage_level_if_any(level).await
+        };
+
+        self.send_event(Event::CapacityChanged {
+            max_capacity,
+            used: self.data_storage.used_space().used(),
+        })
+        .await;
+
+        cmds
+    }
+
     #[allow(clippy::mutable_key_type)]
     pub(crate) async fn reorganize_data(
         &self,
@@ -151,8 +378,10 @@ impl Node {
     ) -> Result<Vec<Cmd>, crate::node::Error> {
         let data = self.data_storage.clone();
         let keys = data.keys().await?;
+        let our_prefix = self.network_knowledge.prefix().await;
         let mut data_for_replication = BTreeMap::new();
-        for addr in keys.iter() {
+        // The data of our sibling section, after a split, is handed over to its adults instead.
+        for addr in keys.iter().filter(|addr| our_prefix.matches(addr.name())) {
             if let Some((data, holders)) = self
                 .get_replica_targets(addr, &new_adults, &lost_adults, &remaining)
                 .await
@@ -220,7 +449,7 @@ impl Node {
                 new_adult_is_holder,
                 lost_old_holder
             );
-            let data = match storage.get_from_local_store(address).await {
+            let data = match storage.get_for_replication(*address).await {
                 Ok(data) => {
                     info!("Data found for replication: {address:?}");
                     Ok(data)
@@ -241,9 +470,11 @@ impl Node {
 
 #[cfg(test)]
 mod tests {
+    use super::registers::test::create_public_register;
     use crate::dbs::Error;
-    use crate::node::core::data::DataStorage;
-    use crate::UsedSpace;
+    use crate::node::core::{data::DataStorage, MIN_LEVEL_WHEN_FULL};
+    use crate::{UsedSpace, Watermarks};
+    use assert_matches::assert_matches;
     use eyre::Result;
     use proptest::{
         collection::SizeRange,
@@ -315,6 +546,87 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn chunks_stop_at_the_db_reserve_while_registers_carry_on() -> Result<()> {
+        const CHUNK_SIZE: usize = 1024;
+        const DB_RESERVE: usize = 8 * CHUNK_SIZE;
+
+        // A small disk with room for the reserve and four chunks.
+        let tmp_dir = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX)
+            .with_db_reserve(DB_RESERVE)
+            .with_simulated_disk(DB_RESERVE + 4 * CHUNK_SIZE);
+        let storage = DataStorage::new(tmp_dir.path(), used_space)?;
+        let chunk = |size| ReplicatedData::Chunk(Chunk::new(random_bytes(size)));
+
+        for _ in 0..3 {
+            let _level = storage.store(&chunk(CHUNK_SIZE)).await?;
+        }
+        assert_eq!(storage.chunks.headroom(), CHUNK_SIZE);
+        assert_eq!(storage.newly_approaching_db_reserve(), Some(CHUNK_SIZE));
+        assert_eq!(storage.newly_approaching_db_reserve(), None);
+
+        // Chunks can fill the disk right up to the reserve, but not a byte beyond.
+        assert_matches!(
+            storage.store(&chunk(CHUNK_SIZE + 1)).await,
+            Err(Error::NotEnoughSpace)
+        );
+        let _level = storage.store(&chunk(CHUNK_SIZE)).await?;
+        assert_eq!(storage.chunks.headroom(), 0);
+        assert_matches!(storage.store(&chunk(1)).await, Err(Error::NotEnoughSpace));
+
+        // Registers have the reserve to themselves.
+        for _ in 0..3 {
+            let (cmd, _) = create_public_register()?;
+            let _level = storage.store(&ReplicatedData::RegisterWrite(cmd)).await?;
+        }
+        assert_eq!(storage.chunks.headroom(), 0);
+        assert_matches!(storage.store(&chunk(1)).await, Err(Error::NotEnoughSpace));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adults_report_being_full_past_the_soft_watermark_and_refuse_past_the_hard_one(
+    ) -> Result<()> {
+        const CAPACITY: usize = 10_000;
+        const CHUNK_SIZE: usize = 500;
+
+        let tmp_dir = tempdir()?;
+        let used_space =
+            UsedSpace::new(CAPACITY).with_watermarks(Watermarks { soft: 50, hard: 80 });
+        let storage = DataStorage::new(tmp_dir.path(), used_space)?;
+        let chunk = || ReplicatedData::Chunk(Chunk::new(random_bytes(CHUNK_SIZE)));
+
+        // Below the soft watermark, levels follow the space used.
+        let mut levels = vec![];
+        while storage.used_space().used() + CHUNK_SIZE < CAPACITY / 2 {
+            if let Some(level) = storage.store(&chunk()).await? {
+                levels.push(level.value());
+            }
+        }
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|level| *level < MIN_LEVEL_WHEN_FULL));
+        assert!(!storage.is_full().await);
+
+        // Past it, we tell our elders we're full, while still taking chunks.
+        let mut level = None;
+        while level.is_none() {
+            level = storage.store(&chunk()).await?;
+        }
+        assert_eq!(level.map(|level| level.value()), Some(MIN_LEVEL_WHEN_FULL));
+        assert!(storage.is_full().await);
+
+        // Up to the hard watermark, but not a byte beyond.
+        while storage.used_space().used() + CHUNK_SIZE <= CAPACITY * 8 / 10 {
+            let _level = storage.store(&chunk()).await?;
+        }
+        assert_matches!(storage.store(&chunk()).await, Err(Error::NotEnoughSpace));
+        assert!(storage.used_space().used() <= CAPACITY * 8 / 10);
+
+        Ok(())
+    }
+
     // Model-based testing where random sets of Operations are performed on the Storage module and
     // a hashmap. The behaviour of both the models should be identical.
     proptest! {
@@ -7,13 +7,14 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::dbs::{
-    convert_to_error_msg, Error, EventStore, LruCache, Result, UsedSpace, SLED_FLUSH_TIME_MS,
+    convert_to_error_msg, AppliedOps, Error, EventStore, LruCache, Result, SpaceCategory,
+    UsedSpace, SLED_FLUSH_TIME_MS,
 };
 use sn_interface::messaging::{
     data::{
         CreateRegister, DeleteRegister, EditRegister, ExtendRegister, OperationId, RegisterCmd,
         RegisterQuery, RegisterStoreExport, ReplicatedRegisterLog, SignedRegisterCreate,
-        SignedRegisterDelete, SignedRegisterEdit, SignedRegisterExtend,
+        SignedRegisterDelete, SignedRegisterEdit, SignedRegisterExtend, MAX_HISTORY_RANGE,
     },
     system::NodeQueryResponse,
     SectionAuth, VerifyAuthority,
@@ -27,6 +28,7 @@ use bincode::serialize;
 use rayon::prelude::*;
 use sled::Db;
 use std::{
+    collections::BTreeSet,
     fmt::{self, Display, Formatter},
     path::Path,
     sync::Arc,
@@ -52,6 +54,8 @@ pub(crate) struct RegisterStorage {
     reg_db: Db,
     cache: Cache,
     used_space: UsedSpace,
+    // Ops applied lately, for retries not to be applied again
+    applied_ops: AppliedOps,
 }
 
 #[derive(Clone, Debug)]
@@ -65,22 +69,32 @@ impl RegisterStorage {
     /// Create new RegisterStorage
     pub(crate) fn new(path: &Path, used_space: UsedSpace) -> Result<Self> {
         let create_path = |name: &str| path.join("db").join(name);
-        let create_db = |db_dir| {
+        let create_db = |db_dir: &Path| {
             sled::Config::default()
-                .path(&db_dir)
+                .path(db_dir)
                 .flush_every_ms(SLED_FLUSH_TIME_MS)
                 .open()
                 .map_err(Error::from)
         };
 
+        let key_db = create_db(&create_path(KEY_DB_NAME))?;
+        let reg_db = create_db(&create_path(REG_DB_NAME))?;
         Ok(Self {
             used_space,
             cache: Cache::new(CACHE_SIZE),
-            key_db: create_db(&create_path(KEY_DB_NAME))?,
-            reg_db: create_db(&create_path(REG_DB_NAME))?,
+            key_db,
+            applied_ops: AppliedOps::new(&reg_db)?,
+            reg_db,
         })
     }
 
+    /// Writes any buffered changes to disk.
+    pub(crate) async fn flush(&self) -> Result<()> {
+        let _bytes = self.key_db.flush_async().await?;
+        let _bytes = self.reg_db.flush_async().await?;
+        Ok(())
+    }
+
     /// --- Node Synching ---
     /// These are node internal functions, not to be exposed to users.
     #[allow(dead_code)]
@@ -272,6 +286,8 @@ impl RegisterStorage {
         // nested loops, slow..
         for data in registers {
             let key = data.address.id()?;
+            // The ops we hold already may be older than what's kept of applied ops.
+            let logged = self.logged_op_keys(&key)?;
             for replicated_cmd in data.op_log {
                 if replicated_cmd.dst_address() != data.address {
                     warn!(
@@ -280,6 +296,9 @@ impl RegisterStorage {
                     );
                     continue;
                 }
+                if logged.contains(&op_key(&replicated_cmd)?) {
+                    continue;
+                }
                 let _ = self.apply(replicated_cmd).await?;
             }
         }
@@ -305,6 +324,16 @@ impl RegisterStorage {
         let address = cmd.dst_address();
         let key = address.id()?;
 
+        // A retry of an op we've applied, e.g. as our ack got lost.
+        let op_key = op_key(&cmd)?;
+        if self.applied_ops.contains(&op_key)? {
+            trace!("Register op {:?} was applied already", op_key);
+            return match cmd {
+                Create { .. } => Err(Error::DataExists),
+                _ => Ok(()),
+            };
+        }
+
         use RegisterCmd::*;
         match cmd.clone() {
             Create {
@@ -337,8 +366,9 @@ impl RegisterStorage {
                 }
 
                 // insert the op to the event log
-                let _ = store.append(cmd)?;
-                self.used_space.increase(required_space);
+                if store.append_once(cmd, &op_key, &self.applied_ops)? {
+                    self.used_space.increase(required_space, SpaceCategory::Dbs);
+                }
 
                 Ok(())
             }
@@ -366,8 +396,10 @@ impl RegisterStorage {
                     .map_err(Error::NetworkData);
 
                 if result.is_ok() {
-                    entry.store.append(cmd)?;
-                    self.used_space.increase(required_space);
+                    // Applying an edit twice to the register itself makes no difference.
+                    if entry.store.append_once(cmd, &op_key, &self.applied_ops)? {
+                        self.used_space.increase(required_space, SpaceCategory::Dbs);
+                    }
                     trace!("Editing Register success!");
                 } else {
                     trace!("Editing Register failed!");
@@ -424,7 +456,9 @@ impl RegisterStorage {
                 let ExtendRegister { extend_with, .. } = op;
 
                 let entry = self.try_load_cache_entry(&key).await?;
-                entry.store.append(cmd)?;
+                if !entry.store.append_once(cmd, &op_key, &self.applied_ops)? {
+                    return Ok(());
+                }
 
                 let mut write = entry.state.write().await;
                 let prev = write.cap();
@@ -436,7 +470,7 @@ impl RegisterStorage {
                     prev + extend_with,
                 );
 
-                self.used_space.increase(required_space);
+                self.used_space.increase(required_space, SpaceCategory::Dbs);
                 Ok(())
             }
         }
@@ -467,6 +501,18 @@ impl RegisterStorage {
                 self.get_user_permissions(*address, *user, requester, operation_id)
                     .await
             }
+            GetVersion { address, version } => {
+                self.get_version(*address, *version, requester, operation_id)
+                    .await
+            }
+            GetHistoryRange {
+                address,
+                start,
+                end,
+            } => {
+                self.get_history_range(*address, *start, *end, requester, operation_id)
+                    .await
+            }
         }
     }
 
@@ -592,6 +638,56 @@ impl RegisterStorage {
         NodeQueryResponse::GetRegisterPolicy((result, operation_id))
     }
 
+    async fn get_version(
+        &self,
+        address: Address,
+        version: u64,
+        requester: User,
+        operation_id: OperationId,
+    ) -> NodeQueryResponse {
+        let result = match self
+            .get_register(&address, Action::Read, requester)
+            .await
+            .and_then(|register| {
+                usize::try_from(version)
+                    .ok()
+                    .and_then(|version| register.history().into_iter().nth(version))
+                    .ok_or(Error::NetworkData(sn_interface::types::Error::NoSuchEntry))
+            }) {
+            Ok(res) => Ok(res),
+            Err(error) => Err(convert_to_error_msg(error)),
+        };
+
+        NodeQueryResponse::GetRegisterVersion((result, operation_id))
+    }
+
+    async fn get_history_range(
+        &self,
+        address: Address,
+        start: u64,
+        end: u64,
+        requester: User,
+        operation_id: OperationId,
+    ) -> NodeQueryResponse {
+        let end = end.min(start.saturating_add(MAX_HISTORY_RANGE));
+        let result = match self
+            .get_register(&address, Action::Read, requester)
+            .await
+            .map(|register| {
+                register
+                    .history()
+                    .into_iter()
+                    .skip(usize::try_from(start).unwrap_or(usize::MAX))
+                    .take(end.saturating_sub(start) as usize)
+                    .collect()
+            }) {
+            Ok(res) => Ok(res),
+            Err(error) => Err(convert_to_error_msg(error)),
+        };
+
+        NodeQueryResponse::GetRegisterHistory((result, operation_id))
+    }
+
     /// ========================================================================
     /// =========================== Helpers ====================================
     /// ========================================================================
@@ -601,6 +697,15 @@ impl RegisterStorage {
         RegOpStore::new(id, self.reg_db.clone()).map_err(Error::from)
     }
 
+    // keys of the ops in the log of a given register
+    fn logged_op_keys(&self, id: &XorName) -> Result<BTreeSet<OperationId>> {
+        self.get_or_create_store(id)?
+            .get_all()?
+            .iter()
+            .map(op_key)
+            .collect()
+    }
+
     // helper that drops the sled tree for a given register
     // decreases the used space by a rough estimate of the size before deletion
     // as with addition this estimate ignores the extra space used by sled
@@ -611,11 +716,16 @@ impl RegisterStorage {
         let len = reg_tree.len();
         let key_used_space = len * regcmd_size;
 
+        // Were the register created anew, its ops are to be applied anew.
+        for op_key in self.logged_op_keys(&key)? {
+            self.applied_ops.forget(&op_key)?;
+        }
+
         let _removed = self.key_db.remove(key)?;
         let _removed = self.reg_db.drop_tree(key)?;
 
         self.cache.remove(&key).await;
-        self.used_space.decrease(key_used_space);
+        self.used_space.decrease(key_used_space, SpaceCategory::Dbs);
 
         Ok(())
     }
@@ -706,14 +816,21 @@ impl Display for RegisterStorage {
     }
 }
 
+fn op_key(cmd: &RegisterCmd) -> Result<OperationId> {
+    cmd.idempotency_key().map_err(|_| Error::NoOperationId)
+}
+
 #[cfg(test)]
-mod test {
-    use super::RegisterStorage;
+pub(super) mod test {
+    use super::{Address, RegisterStorage};
 
     use crate::node::{Error, Result};
     use crate::UsedSpace;
     use sn_interface::messaging::{
-        data::{CreateRegister, RegisterCmd, RegisterQuery, SignedRegisterCreate},
+        data::{
+            CreateRegister, EditRegister, ExtendRegister, RegisterCmd, RegisterQuery,
+            SignedRegisterCreate, SignedRegisterEdit, SignedRegisterExtend,
+        },
         system::NodeQueryResponse,
         SectionAuth, ServiceAuth,
     };
@@ -722,6 +839,7 @@ mod test {
     use sn_interface::types::{register::User, Keypair};
 
     use rand::Rng;
+    use std::collections::BTreeSet;
     use tempfile::tempdir;
     use xor_name::Prefix;
 
@@ -757,6 +875,53 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retried_ops_are_applied_once() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let store = RegisterStorage::new(tmp_dir.path(), used_space.clone())?;
+
+        let (create, authority, keypair) = create_register_of_size(100)?;
+        let address = create.dst_address();
+        store.write(create).await?;
+
+        let op = ExtendRegister {
+            address,
+            extend_with: 50,
+        };
+        let auth = ServiceAuth {
+            public_key: keypair.public_key(),
+            signature: keypair.sign(&bincode::serialize(&op)?),
+        };
+        let extend = RegisterCmd::Extend {
+            cmd: SignedRegisterExtend { op, auth },
+            section_auth: section_auth(),
+        };
+
+        // Delivered twice, as when our ack got lost, the second time by another elder.
+        store.write(extend.clone()).await?;
+        let ratio = used_space.ratio();
+        let mut retried = extend.clone();
+        if let RegisterCmd::Extend {
+            section_auth: auth, ..
+        } = &mut retried
+        {
+            *auth = section_auth();
+        }
+        store.write(retried).await?;
+        assert_eq!(cap_of(&store, address, authority).await, 150);
+        assert_eq!(used_space.ratio(), ratio);
+
+        // And once more after a restart, with the register loaded back from disk.
+        drop(store);
+        let store = RegisterStorage::new(tmp_dir.path(), used_space.clone())?;
+        store.write(extend).await?;
+        assert_eq!(cap_of(&store, address, authority).await, 150);
+        assert_eq!(used_space.ratio(), ratio);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_register_non_existing_permissions() -> Result<()> {
         register_non_existing_permissions(create_private_register).await?;
@@ -913,6 +1078,70 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn register_versions_follow_its_history() -> Result<()> {
+        let store = new_store()?;
+        let (create, authority, keypair) = create_register_of_size(100)?;
+        let address = create.dst_address();
+        store.write(create).await?;
+
+        // Three entries, each written on top of the previous one.
+        let mut register = match store.read(&RegisterQuery::Get(address), authority).await {
+            NodeQueryResponse::GetRegister((Ok(register), _)) => register,
+            response => panic!("Could not read! {:?}", response),
+        };
+        let mut written = vec![];
+        let mut children = BTreeSet::new();
+        for entry in [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()] {
+            let (hash, edit) = register.write(entry.clone(), children)?;
+            let op = EditRegister { address, edit };
+            let auth = ServiceAuth {
+                public_key: keypair.public_key(),
+                signature: keypair.sign(&bincode::serialize(&op)?),
+            };
+            store
+                .write(RegisterCmd::Edit(SignedRegisterEdit { op, auth }))
+                .await?;
+            written.push((hash, entry));
+            children = BTreeSet::from([hash]);
+        }
+
+        let query = RegisterQuery::GetVersion {
+            address,
+            version: 1,
+        };
+        match store.read(&query, authority).await {
+            NodeQueryResponse::GetRegisterVersion((Ok(version), _)) => {
+                assert_eq!(version, written[1])
+            }
+            response => panic!("Could not read! {:?}", response),
+        }
+        let query = RegisterQuery::GetVersion {
+            address,
+            version: 3,
+        };
+        match store.read(&query, authority).await {
+            NodeQueryResponse::GetRegisterVersion((Err(e), _)) => {
+                assert_eq!(e, sn_interface::messaging::data::Error::NoSuchEntry)
+            }
+            response => panic!("Version past the last one found! {:?}", response),
+        }
+
+        let query = RegisterQuery::GetHistoryRange {
+            address,
+            start: 1,
+            end: 10,
+        };
+        match store.read(&query, authority).await {
+            NodeQueryResponse::GetRegisterHistory((Ok(history), _)) => {
+                assert_eq!(history, written[1..])
+            }
+            response => panic!("Could not read! {:?}", response),
+        }
+
+        Ok(())
+    }
+
     async fn register_non_existing_permissions<F>(create_register: F) -> Result<()>
     where
         F: Fn() -> Result<(RegisterCmd, User)>,
@@ -962,6 +1191,35 @@ mod test {
         (authority, keypair)
     }
 
+    fn create_register_of_size(size: u16) -> Result<(RegisterCmd, User, Keypair)> {
+        let (authority, keypair) = random_user();
+        let op = CreateRegister::Empty {
+            name: xor_name::rand::random(),
+            tag: 1,
+            size,
+            policy: Policy::Private(PrivatePolicy {
+                owner: authority,
+                permissions: Default::default(),
+            }),
+        };
+        let auth = ServiceAuth {
+            public_key: keypair.public_key(),
+            signature: keypair.sign(&bincode::serialize(&op)?),
+        };
+        let cmd = RegisterCmd::Create {
+            cmd: SignedRegisterCreate { op, auth },
+            section_auth: section_auth(),
+        };
+        Ok((cmd, authority, keypair))
+    }
+
+    async fn cap_of(store: &RegisterStorage, address: Address, authority: User) -> u16 {
+        match store.read(&RegisterQuery::Get(address), authority).await {
+            NodeQueryResponse::GetRegister((Ok(register), _)) => register.cap(),
+            response => panic!("Could not read! {:?}", response),
+        }
+    }
+
     fn create_private_register() -> Result<(RegisterCmd, User)> {
         let (authority, keypair) = random_user();
         let policy = Policy::Private(PrivatePolicy {
@@ -971,7 +1229,7 @@ mod test {
         Ok((create_reg_w_policy(policy, keypair)?, authority))
     }
 
-    fn create_public_register() -> Result<(RegisterCmd, User)> {
+    pub(crate) fn create_public_register() -> Result<(RegisterCmd, User)> {
         let (authority, keypair) = random_user();
         let policy = Policy::Public(PublicPolicy {
             owner: authority,
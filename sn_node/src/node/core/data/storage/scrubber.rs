@@ -0,0 +1,192 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::ChunkStorage;
+
+use crate::dbs::{Result, Scrubbed};
+use crate::node::{
+    core::{Cmd, Node},
+    Result as NodeResult,
+};
+
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::PublicKey,
+};
+
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::RwLock;
+use xor_name::XorName;
+
+/// Chunks scrubbed per `SCRUB_INTERVAL` when unspecified.
+pub(crate) const DEFAULT_SCRUB_CHUNKS_PER_TICK: usize = 8;
+/// How often a new batch of chunks is scrubbed.
+pub(crate) const SCRUB_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What scrubbing found since we started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ScrubStats {
+    /// Chunks checked.
+    pub(crate) scrubbed: u64,
+    /// Chunks found corrupt, and quarantined.
+    pub(crate) quarantined: u64,
+    /// Passes completed over all the chunks held.
+    pub(crate) passes: u64,
+}
+
+#[derive(Debug, Default)]
+struct ScrubState {
+    // Name of the last chunk scrubbed in the current pass.
+    cursor: Option<XorName>,
+    stats: ScrubStats,
+}
+
+/// Adults' background scrub of the chunks they hold, re-hashing them against their names a
+/// batch per tick, in order of their names, and starting over once through.
+///
+/// The chunks are read back as background reads, so scrubbing yields to serving clients.
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkScrubber {
+    state: Arc<RwLock<ScrubState>>,
+    chunks_per_tick: Arc<AtomicUsize>,
+}
+
+impl Default for ChunkScrubber {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(RwLock::new(ScrubState::default())),
+            chunks_per_tick: Arc::new(AtomicUsize::new(DEFAULT_SCRUB_CHUNKS_PER_TICK)),
+        }
+    }
+}
+
+impl ChunkScrubber {
+    /// Sets how many chunks are scrubbed per `SCRUB_INTERVAL`, 0 to stop scrubbing.
+    pub(crate) fn set_chunks_per_tick(&self, chunks_per_tick: usize) {
+        self.chunks_per_tick
+            .store(chunks_per_tick, Ordering::Relaxed);
+    }
+
+    pub(crate) async fn stats(&self) -> ScrubStats {
+        self.state.read().await.stats
+    }
+
+    /// Scrubs the next batch of `chunks`, returning the names of those found corrupt and
+    /// quarantined.
+    pub(super) async fn scrub_next(&self, chunks: &ChunkStorage) -> Result<BTreeSet<XorName>> {
+        let count = self.chunks_per_tick.load(Ordering::Relaxed);
+        let mut state = self.state.write().await;
+        if count == 0 {
+            return Ok(BTreeSet::new());
+        }
+
+        let addresses = chunks.addresses_after(state.cursor.as_ref(), count)?;
+        if addresses.is_empty() && state.cursor.is_none() {
+            // Nothing held.
+            return Ok(BTreeSet::new());
+        }
+
+        let mut corrupt = BTreeSet::new();
+        for address in &addresses {
+            match chunks.scrub(address).await? {
+                Scrubbed::Intact => state.stats.scrubbed += 1,
+                Scrubbed::Quarantined => {
+                    state.stats.scrubbed += 1;
+                    state.stats.quarantined += 1;
+                    let _new = corrupt.insert(*address.name());
+                }
+                Scrubbed::Gone => {}
+            }
+            state.cursor = Some(*address.name());
+        }
+
+        if addresses.len() < count {
+            state.cursor = None;
+            state.stats.passes += 1;
+            debug!("Scrub pass completed: {:?}", state.stats);
+        }
+        Ok(corrupt)
+    }
+}
+
+impl Node {
+    /// Scrubs the next chunks we hold, as an adult. Those found corrupt are quarantined, and
+    /// our elders asked to have them replicated to us again from their other holders.
+    pub(crate) async fn scrub_chunks(&self) -> NodeResult<Vec<Cmd>> {
+        if self.is_elder().await {
+            return Ok(vec![]);
+        }
+
+        let names = self.data_storage.scrub_next_chunks().await?;
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        error!(
+            "Quarantined {} corrupt chunks, asking our elders to replicate them to us again",
+            names.len()
+        );
+
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        let msg = SystemMsg::NodeCmd(NodeCmd::RequestChunkReplication {
+            node_id,
+            section: XorName::from(node_id),
+            names,
+        });
+        Ok(vec![self.send_msg_to_our_elders(msg).await?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::core::data::DataStorage;
+    use crate::UsedSpace;
+
+    use sn_interface::types::{utils::random_bytes, Chunk, ReplicatedData};
+
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn scrubbing_goes_through_all_the_chunks_a_batch_per_tick() -> Result<()> {
+        let root = tempdir()?;
+        let storage = DataStorage::new(root.path(), UsedSpace::new(usize::MAX))?;
+
+        // Nothing held, nothing to go through.
+        assert!(storage.scrub_next_chunks().await?.is_empty());
+        assert_eq!(storage.scrubber().stats().await, ScrubStats::default());
+
+        for _ in 0..5 {
+            let chunk = Chunk::new(random_bytes(16));
+            let _level = storage.store(&ReplicatedData::Chunk(chunk)).await?;
+        }
+        storage.scrubber().set_chunks_per_tick(2);
+        for _ in 0..3 {
+            assert!(storage.scrub_next_chunks().await?.is_empty());
+        }
+        let stats = storage.scrubber().stats().await;
+        assert_eq!((stats.scrubbed, stats.passes), (5, 1));
+
+        // The next pass starts over.
+        assert!(storage.scrub_next_chunks().await?.is_empty());
+        assert_eq!(storage.scrubber().stats().await.scrubbed, 7);
+
+        storage.scrubber().set_chunks_per_tick(0);
+        assert!(storage.scrub_next_chunks().await?.is_empty());
+        assert_eq!(storage.scrubber().stats().await.scrubbed, 7);
+
+        Ok(())
+    }
+}
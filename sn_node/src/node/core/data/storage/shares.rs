@@ -0,0 +1,170 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise, Error, Result, SpaceCategory};
+use crate::persisted;
+use crate::UsedSpace;
+
+use sn_interface::types::ChunkShare;
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+use xor_name::XorName;
+
+const SHARES_DIR: &str = "chunk_shares";
+/// Format of the files shares are kept in.
+const CHUNK_SHARE_VERSION: u16 = 1;
+
+/// Shares of erasure coded chunks, as stored by an adult. Each share is a file of its own, in a
+/// dir per chunk, counted against our capacity like chunks are.
+#[derive(Clone)]
+pub(crate) struct ShareStorage {
+    dir: PathBuf,
+    used_space: UsedSpace,
+}
+
+impl ShareStorage {
+    /// Opens the shares kept under `path`, accounting for the space they take.
+    pub(crate) fn new(path: &Path, used_space: UsedSpace) -> Result<Self> {
+        let dir = path.join(SHARES_DIR);
+        fs::create_dir_all(&dir).map_err(|error| Error::io_at(&dir, error))?;
+
+        let used = used_by_files(&dir)?;
+        used_space.increase(used, SpaceCategory::Chunks);
+
+        Ok(Self { dir, used_space })
+    }
+
+    /// Stores `share`, unless we hold it already.
+    pub(crate) async fn store(&self, share: &ChunkShare) -> Result<()> {
+        let chunk_dir = self.chunk_dir(&share.chunk);
+        let path = chunk_dir.join(share.index.to_string());
+        if path.exists() {
+            trace!(
+                "Share {} of chunk {:?} already exists, not storing it",
+                share.index,
+                share.chunk
+            );
+            return Ok(());
+        }
+
+        let payload = serialise(share)?;
+        let size = persisted::encode(CHUNK_SHARE_VERSION, &payload).len();
+        if !self.used_space.can_add_chunk(size, &self.dir) {
+            return Err(Error::NotEnoughSpace);
+        }
+
+        tokio::fs::create_dir_all(&chunk_dir).await?;
+        persisted::write_async(path, CHUNK_SHARE_VERSION, payload).await?;
+        self.used_space.increase(size, SpaceCategory::Chunks);
+
+        Ok(())
+    }
+
+    /// The shares of the chunk `name` we hold, skipping any found damaged.
+    pub(crate) async fn shares_of(&self, name: &XorName) -> Result<Vec<ChunkShare>> {
+        let mut entries = match tokio::fs::read_dir(self.chunk_dir(name)).await {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut shares = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            match persisted::read_async(entry.path()).await {
+                Ok(Some(persisted)) if persisted.version == CHUNK_SHARE_VERSION => {
+                    shares.push(deserialise(&persisted.payload)?)
+                }
+                Ok(Some(persisted)) => warn!(
+                    "Skipping share {:?} of unknown format version {}",
+                    entry.path(),
+                    persisted.version
+                ),
+                Ok(None) => {}
+                Err(error) => warn!("Skipping damaged share: {error}"),
+            }
+        }
+        shares.retain(|share: &ChunkShare| share.chunk == *name);
+        shares.sort_by_key(|share| share.index);
+
+        Ok(shares)
+    }
+
+    fn chunk_dir(&self, name: &XorName) -> PathBuf {
+        self.dir.join(hex::encode(name))
+    }
+}
+
+// Bytes taken by the files under `dir`, one level of dirs down.
+fn used_by_files(dir: &Path) -> io::Result<usize> {
+    let mut used = 0;
+    for chunk_dir in fs::read_dir(dir)? {
+        let chunk_dir = chunk_dir?;
+        if !chunk_dir.file_type()?.is_dir() {
+            continue;
+        }
+        for share in fs::read_dir(chunk_dir.path())? {
+            used += share?.metadata()?.len() as usize;
+        }
+    }
+    Ok(used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    fn share(chunk: XorName, index: u8) -> ChunkShare {
+        ChunkShare {
+            chunk,
+            index,
+            data_shares: 2,
+            parity_shares: 1,
+            chunk_len: 6,
+            bytes: Bytes::from(vec![index; 3]),
+        }
+    }
+
+    #[tokio::test]
+    async fn shares_are_kept_per_chunk_and_counted_once() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let used_space = UsedSpace::new(usize::MAX);
+        let storage = ShareStorage::new(tmp_dir.path(), used_space.clone())?;
+
+        let chunk = xor_name::rand::random();
+        let other = xor_name::rand::random();
+        storage.store(&share(chunk, 2)).await?;
+        storage.store(&share(chunk, 0)).await?;
+        storage.store(&share(other, 1)).await?;
+        let used = used_space.used();
+        storage.store(&share(chunk, 0)).await?;
+        assert_eq!(used_space.used(), used);
+
+        assert_eq!(
+            storage.shares_of(&chunk).await?,
+            vec![share(chunk, 0), share(chunk, 2)]
+        );
+        assert!(storage
+            .shares_of(&xor_name::rand::random())
+            .await?
+            .is_empty());
+
+        // The space they take is accounted for again on restart.
+        let reopened_space = UsedSpace::new(usize::MAX);
+        let _reopened = ShareStorage::new(tmp_dir.path(), reopened_space.clone())?;
+        assert_eq!(reopened_space.used(), used_space.used());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_interface::messaging::system::{NodeCmd, SystemMsg};
+use sn_interface::types::{Cache, ReplicatedData, ReplicatedDataAddress};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::RwLock;
+use xor_name::XorName;
+
+// Number of corrupted copies of a chunk after which we stop sourcing it again.
+const MAX_CORRUPTED_COPIES: usize = 3;
+// How long we wait for a chunk sourced again, before giving up on it.
+const REFETCH_TIMEOUT: Duration = Duration::from_secs(60);
+// Number of chunks sourced again at once, at most.
+const MAX_REFETCHES: usize = 10_000;
+
+/// Where to source a chunk which arrived corrupted from again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Refetch {
+    /// From the same sender, the first time one of its copies arrived corrupted.
+    Retry,
+    /// From another holder than the senders of the corrupted copies so far.
+    Elsewhere { tried: BTreeSet<XorName> },
+    /// Nowhere, too many copies of it arrived corrupted already.
+    GiveUp,
+}
+
+/// Checks on the chunks other nodes transfer to us: their content must hash to the address
+/// their sender holds them at. Tracks the chunks sourced again after arriving corrupted, and
+/// the number of corrupted chunks received from each sender, to help find bad hosts or links.
+#[derive(Clone)]
+pub(crate) struct TransferChecks {
+    // Senders of the corrupted copies of a chunk, in the order they arrived, while it's sourced
+    // again.
+    refetches: Arc<Cache<ReplicatedDataAddress, Vec<XorName>>>,
+    corruptions: Arc<RwLock<BTreeMap<XorName, u64>>>,
+}
+
+impl Default for TransferChecks {
+    fn default() -> Self {
+        Self {
+            refetches: Arc::new(Cache::with_expiry_duration_and_capacity(
+                REFETCH_TIMEOUT,
+                MAX_REFETCHES,
+            )),
+            corruptions: Arc::default(),
+        }
+    }
+}
+
+impl TransferChecks {
+    /// Whether `data` is what was sent as held at `address`. The address of a chunk is the hash
+    /// of its content, which it was given when deserialised on arrival, so that's all compared.
+    pub(crate) fn verify(address: &ReplicatedDataAddress, data: &ReplicatedData) -> bool {
+        data.address() == *address
+    }
+
+    /// Counts a corrupted copy of the chunk at `address` against its `sender`, returning where
+    /// to source the chunk from next.
+    pub(crate) async fn corrupted(
+        &self,
+        address: ReplicatedDataAddress,
+        sender: XorName,
+    ) -> Refetch {
+        let count = {
+            let mut corruptions = self.corruptions.write().await;
+            let count = corruptions.entry(sender).or_default();
+            *count += 1;
+            *count
+        };
+        warn!(
+            "Corrupted copy of {:?} received from {} ({} so far)",
+            address, sender, count
+        );
+
+        let mut senders = self.refetches.get(&address).await.unwrap_or_default();
+        senders.push(sender);
+        let refetch = if senders.len() >= MAX_CORRUPTED_COPIES {
+            Refetch::GiveUp
+        } else if senders.len() == 1 {
+            Refetch::Retry
+        } else {
+            Refetch::Elsewhere {
+                tried: senders.iter().copied().collect(),
+            }
+        };
+        if refetch == Refetch::GiveUp {
+            let _prev = self.refetches.remove(&address).await;
+        } else {
+            let _prev = self.refetches.set(address, senders, None).await;
+        }
+        refetch
+    }
+
+    /// Stops sourcing the data at `address` again, now an intact copy of it arrived.
+    pub(crate) async fn arrived(&self, address: &ReplicatedDataAddress) {
+        if self.refetches.remove(address).await.is_some() {
+            info!("Intact copy of {:?} received", address);
+        }
+    }
+
+    /// Number of corrupted chunks received from the sender with the given name.
+    #[cfg(test)]
+    pub(crate) async fn corruptions_from(&self, sender: &XorName) -> u64 {
+        self.corruptions
+            .read()
+            .await
+            .get(sender)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Number of corrupted chunks received, per sender name.
+    #[cfg(feature = "metrics")]
+    pub(crate) async fn corruptions(&self) -> BTreeMap<XorName, u64> {
+        self.corruptions.read().await.clone()
+    }
+}
+
+/// The msg replicating `data` to other nodes, each piece along with the address we hold it at,
+/// for them to check it arrived intact.
+pub(crate) fn replicate_checked(data: Vec<ReplicatedData>) -> SystemMsg {
+    SystemMsg::NodeCmd(NodeCmd::ReplicateCheckedData(
+        data.into_iter()
+            .map(|data| (data.address(), data))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use sn_interface::types::Chunk;
+
+    #[tokio::test]
+    async fn corrupted_chunks_are_retried_then_sourced_elsewhere_then_given_up() {
+        let checks = TransferChecks::default();
+        let chunk = ReplicatedData::Chunk(Chunk::new(Bytes::from("intact")));
+        let corrupted = ReplicatedData::Chunk(Chunk::new(Bytes::from("intacu")));
+        let address = chunk.address();
+        assert!(TransferChecks::verify(&address, &chunk));
+        assert!(!TransferChecks::verify(&address, &corrupted));
+
+        let (sender, other) = (xor_name::rand::random(), xor_name::rand::random());
+        assert_eq!(checks.corrupted(address, sender).await, Refetch::Retry);
+        assert_eq!(
+            checks.corrupted(address, sender).await,
+            Refetch::Elsewhere {
+                tried: BTreeSet::from([sender])
+            }
+        );
+        assert_eq!(checks.corrupted(address, other).await, Refetch::GiveUp);
+        assert_eq!(checks.corruptions_from(&sender).await, 2);
+        assert_eq!(checks.corruptions_from(&other).await, 1);
+
+        // Once an intact copy arrived, a later corrupted one is retried afresh.
+        assert_eq!(checks.corrupted(address, other).await, Refetch::Retry);
+        checks.arrived(&address).await;
+        assert_eq!(checks.corrupted(address, sender).await, Refetch::Retry);
+    }
+}
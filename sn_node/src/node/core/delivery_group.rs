@@ -7,9 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::node::{Error, Result};
-use sn_interface::elder_count;
 use sn_interface::messaging::DstLocation;
-use sn_interface::network_knowledge::{supermajority, NetworkKnowledge};
+use sn_interface::network_knowledge::NetworkKnowledge;
 use sn_interface::types::Peer;
 
 use itertools::Itertools;
@@ -115,7 +114,9 @@ async fn candidates(
     // .collect_vec();
 
     // gives at least 1 honest target among recipients.
-    let min_dg_size = 1 + elder_count() - supermajority(elder_count());
+    let network_params = network_knowledge.network_params().await;
+    let elder_count = network_params.elder_count();
+    let min_dg_size = 1 + elder_count - network_params.supermajority(elder_count);
     let mut dg_size = min_dg_size;
     let mut candidates = Vec::new();
     for (idx, (prefix, len, connected)) in sections.iter().enumerate() {
@@ -170,7 +171,8 @@ async fn get_peer(name: &XorName, network_knowledge: &NetworkKnowledge) -> Optio
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sn_interface::network_knowledge::test_utils::section_signed;
+    use sn_interface::elder_count;
+    use sn_interface::network_knowledge::{supermajority, test_utils::section_signed};
     use sn_interface::types::keys::ed25519;
 
     use eyre::{ContextCompat, Result};
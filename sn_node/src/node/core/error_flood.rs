@@ -0,0 +1,136 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Error responses sent to each peer lately. A peer whose msgs keep failing, e.g. as they're
+//! malformed on purpose, is told once to back off rather than answered for each of its msgs,
+//! for a flood of bad msgs not to be met with as large a flood of errors.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// Errors are counted over windows of this length...
+const ERROR_WINDOW: Duration = Duration::from_secs(10);
+// ...and a peer is only answered with this many of them per window.
+const MAX_ERRORS_PER_WINDOW: u32 = 20;
+// Peers whose errors are counted, those past it being answered.
+const MAX_TRACKED_PEERS: usize = 10_000;
+
+/// What's to be done about an error response to a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ErrorVerdict {
+    /// It's to be sent.
+    Respond,
+    /// It's to be dropped, the peer told instead to back off for the given time, and its
+    /// reputation hit.
+    BackOff(Duration),
+    /// It's to be dropped, the peer having been told to back off already.
+    Suppress,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    errors: u32,
+}
+
+/// Counts of the error responses sent to each peer, over the current window.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ErrorFlood(Arc<Mutex<HashMap<SocketAddr, Window>>>);
+
+impl ErrorFlood {
+    /// Counts an error response to the peer at `addr`, returning what's to be done about it.
+    pub(crate) fn record(&self, addr: SocketAddr) -> ErrorVerdict {
+        self.record_at(addr, Instant::now())
+    }
+
+    fn record_at(&self, addr: SocketAddr, now: Instant) -> ErrorVerdict {
+        // The lock is only held for updates of the counts, which don't panic.
+        let mut windows = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !windows.contains_key(&addr) && windows.len() >= MAX_TRACKED_PEERS {
+            windows
+                .retain(|_, window| now.saturating_duration_since(window.started) < ERROR_WINDOW);
+            if windows.len() >= MAX_TRACKED_PEERS {
+                return ErrorVerdict::Respond;
+            }
+        }
+
+        let window = windows.entry(addr).or_insert(Window {
+            started: now,
+            errors: 0,
+        });
+        let elapsed = now.saturating_duration_since(window.started);
+        if elapsed >= ERROR_WINDOW {
+            *window = Window {
+                started: now,
+                errors: 0,
+            };
+        }
+        window.errors += 1;
+
+        if window.errors <= MAX_ERRORS_PER_WINDOW {
+            ErrorVerdict::Respond
+        } else if window.errors == MAX_ERRORS_PER_WINDOW + 1 {
+            let elapsed = now.saturating_duration_since(window.started);
+            ErrorVerdict::BackOff(ERROR_WINDOW.saturating_sub(elapsed))
+        } else {
+            ErrorVerdict::Suppress
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    #[test]
+    fn peers_are_told_once_to_back_off() {
+        let flood = ErrorFlood::default();
+        let start = Instant::now();
+
+        for _ in 0..MAX_ERRORS_PER_WINDOW {
+            assert_eq!(flood.record_at(addr(1), start), ErrorVerdict::Respond);
+        }
+        let later = start + Duration::from_secs(4);
+        assert_eq!(
+            flood.record_at(addr(1), later),
+            ErrorVerdict::BackOff(ERROR_WINDOW - Duration::from_secs(4))
+        );
+        assert_eq!(flood.record_at(addr(1), later), ErrorVerdict::Suppress);
+
+        // Other peers are answered as usual.
+        assert_eq!(flood.record_at(addr(2), later), ErrorVerdict::Respond);
+    }
+
+    #[test]
+    fn peers_are_answered_again_once_the_window_is_over() {
+        let flood = ErrorFlood::default();
+        let start = Instant::now();
+
+        for _ in 0..=MAX_ERRORS_PER_WINDOW {
+            let _verdict = flood.record_at(addr(1), start);
+        }
+        assert_eq!(flood.record_at(addr(1), start), ErrorVerdict::Suppress);
+        assert_eq!(
+            flood.record_at(addr(1), start + ERROR_WINDOW),
+            ErrorVerdict::Respond
+        );
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Resource proof challenges issued to joining peers lately. The more of them were issued, the
+//! harder the next ones are to solve, each doubling of their count past those issued at the base
+//! difficulty doubling the cost of solving them: the work, the data echoed back, and the memory
+//! of the memory-hard part. Joining a whole lot of nodes at once, as for a Sybil attack, gets
+//! costlier the more of them there are, while a few nodes joining barely notice.
+
+use super::{RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+// Challenges are counted over this window...
+const JOIN_PRESSURE_WINDOW: Duration = Duration::from_secs(60);
+// ...this many of them being issued at the base difficulty...
+const BASE_CHALLENGES_PER_WINDOW: usize = 10;
+// ...and those past it up to this many doublings harder.
+const MAX_PRESSURE_LEVEL: u8 = 6;
+// Memory of the memory-hard part at the base difficulty, in KiB...
+const BASE_MEMORY_KIB: u32 = 1024;
+// ...and the leading zero bits of the hash it takes, which sets how many tries it's expected to
+// take to find, memory being filled anew on each.
+const MEMORY_DIFFICULTY: u8 = 2;
+
+/// What a resource proof challenge takes to solve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ChallengeParams {
+    pub(crate) data_size: usize,
+    pub(crate) difficulty: u8,
+    pub(crate) memory_kib: u32,
+    pub(crate) memory_difficulty: u8,
+}
+
+impl ChallengeParams {
+    /// The challenge `level` doublings harder than the base one.
+    fn at_level(level: u8) -> Self {
+        Self {
+            data_size: RESOURCE_PROOF_DATA_SIZE << level,
+            difficulty: RESOURCE_PROOF_DIFFICULTY + level,
+            memory_kib: BASE_MEMORY_KIB << level,
+            memory_difficulty: MEMORY_DIFFICULTY,
+        }
+    }
+
+    /// What the signature of the challenge for `peer_name` covers: the peer, the nonce, and
+    /// what the challenge takes to solve, for the peer not to get away with solving an easier
+    /// one.
+    pub(crate) fn signed_payload(
+        &self,
+        peer_name: &XorName,
+        nonce: &[u8; 32],
+    ) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(&(
+            peer_name,
+            nonce,
+            self.data_size as u64,
+            self.difficulty,
+            self.memory_kib,
+            self.memory_difficulty,
+        ))
+    }
+
+    /// Whether the challenge is no harder than the hardest one issued, for the memory of the
+    /// memory-hard part of any challenge validated to be bounded.
+    pub(crate) fn is_within_bounds(&self) -> bool {
+        let hardest = Self::at_level(MAX_PRESSURE_LEVEL);
+        self.data_size <= hardest.data_size
+            && self.difficulty <= hardest.difficulty
+            && self.memory_kib <= hardest.memory_kib
+            && self.memory_difficulty <= hardest.memory_difficulty
+    }
+}
+
+/// Times of the challenges issued, over the current window.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct JoinPressure(Arc<Mutex<VecDeque<Instant>>>);
+
+impl JoinPressure {
+    /// Counts a challenge issued, returning what it takes to solve.
+    pub(crate) fn issue(&self) -> ChallengeParams {
+        self.issue_at(Instant::now())
+    }
+
+    fn issue_at(&self, now: Instant) -> ChallengeParams {
+        // The lock is only held for updates of the times, which don't panic.
+        let mut issued = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while let Some(oldest) = issued.front() {
+            if now.saturating_duration_since(*oldest) < JOIN_PRESSURE_WINDOW {
+                break;
+            }
+            let _expired = issued.pop_front();
+        }
+        // Past this many, challenges are as hard as they get, so older ones needn't be kept.
+        if issued.len() >= BASE_CHALLENGES_PER_WINDOW << MAX_PRESSURE_LEVEL {
+            let _oldest = issued.pop_front();
+        }
+        issued.push_back(now);
+
+        let mut level = 0;
+        while level < MAX_PRESSURE_LEVEL && BASE_CHALLENGES_PER_WINDOW << level < issued.len() {
+            level += 1;
+        }
+        ChallengeParams::at_level(level)
+    }
+}
+
+/// Finds the solution to the memory-hard part of the challenge of `nonce`: the first counter
+/// whose Argon2id hash, filling `memory_kib` of memory, has `difficulty` leading zero bits.
+pub(crate) fn solve_memory_proof(nonce: &[u8; 32], memory_kib: u32, difficulty: u8) -> u64 {
+    (0..)
+        .find(|solution| validate_memory_proof(nonce, memory_kib, difficulty, *solution))
+        .unwrap_or_default()
+}
+
+/// Whether `solution` solves the memory-hard part of the challenge of `nonce`.
+pub(crate) fn validate_memory_proof(
+    nonce: &[u8; 32],
+    memory_kib: u32,
+    difficulty: u8,
+    solution: u64,
+) -> bool {
+    let params = match Params::new(memory_kib, 1, 1, Some(32)) {
+        Ok(params) => params,
+        Err(_) => return false,
+    };
+    let mut hash = [0; 32];
+    if Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(&solution.to_le_bytes(), nonce, &mut hash)
+        .is_err()
+    {
+        return false;
+    }
+    leading_zero_bits(&hash) >= u32::from(difficulty)
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_get_harder_with_join_pressure() {
+        let pressure = JoinPressure::default();
+        let start = Instant::now();
+
+        for _ in 0..BASE_CHALLENGES_PER_WINDOW {
+            assert_eq!(pressure.issue_at(start), ChallengeParams::at_level(0));
+        }
+        // Each doubling of the challenges issued doubles what the next one takes.
+        assert_eq!(pressure.issue_at(start), ChallengeParams::at_level(1));
+        for _ in 0..BASE_CHALLENGES_PER_WINDOW {
+            let _params = pressure.issue_at(start);
+        }
+        assert_eq!(pressure.issue_at(start), ChallengeParams::at_level(2));
+
+        // Up to a point.
+        for _ in 0..BASE_CHALLENGES_PER_WINDOW << MAX_PRESSURE_LEVEL {
+            let _params = pressure.issue_at(start);
+        }
+        let hardest = pressure.issue_at(start);
+        assert_eq!(hardest, ChallengeParams::at_level(MAX_PRESSURE_LEVEL));
+        assert!(hardest.is_within_bounds());
+        assert!(!ChallengeParams {
+            memory_kib: hardest.memory_kib * 2,
+            ..hardest
+        }
+        .is_within_bounds());
+
+        // Once the window is over, challenges are back to the base difficulty.
+        let later = start + JOIN_PRESSURE_WINDOW;
+        assert_eq!(pressure.issue_at(later), ChallengeParams::at_level(0));
+    }
+
+    #[test]
+    fn memory_proofs_are_checked_against_their_challenge() {
+        let nonce = [7; 32];
+        let (memory_kib, difficulty) = (64, 3);
+
+        let solution = solve_memory_proof(&nonce, memory_kib, difficulty);
+        assert!(validate_memory_proof(
+            &nonce, memory_kib, difficulty, solution
+        ));
+        // The solution found is the first one.
+        assert!(!(0..solution)
+            .any(|other| validate_memory_proof(&nonce, memory_kib, difficulty, other)));
+        // It doesn't solve a much harder challenge.
+        assert!(!validate_memory_proof(&nonce, memory_kib, 32, solution));
+    }
+}
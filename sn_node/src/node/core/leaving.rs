@@ -0,0 +1,65 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{Cmd, Node},
+    Result,
+};
+
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::PublicKey,
+};
+
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+impl Node {
+    /// Gets us ready to shut down for good: flushes our dbs, then tells our section we're
+    /// going offline. As an elder we propose it ourselves, to the other elders. As an adult we
+    /// ask our elders to.
+    pub(crate) async fn prepare_leaving(&self) -> Result<Vec<Cmd>> {
+        self.data_storage.flush().await?;
+        self.write_prefix_map().await;
+        self.msg_filter.checkpoint().await?;
+
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        let our_name = XorName::from(node_id);
+        if self.is_elder().await {
+            info!("Proposing ourselves offline, as we're shutting down");
+            return self
+                .cast_offline_proposals(&BTreeSet::from([our_name]))
+                .await;
+        }
+
+        info!("Telling our elders we're going offline, as we're shutting down");
+        let msg = SystemMsg::NodeCmd(NodeCmd::Leaving {
+            node_id,
+            section: our_name,
+        });
+        Ok(vec![self.send_msg_to_our_elders(msg).await?])
+    }
+
+    /// On elders, proposes offline a member which told us it's shutting down, whether or not
+    /// it announced a restart earlier.
+    pub(crate) async fn record_leaving(&self, name: XorName) -> Result<Vec<Cmd>> {
+        if self
+            .network_knowledge
+            .get_section_member(&name)
+            .await
+            .is_none()
+        {
+            warn!("Ignoring {} leaving, as it isn't one of our members", name);
+            return Ok(vec![]);
+        }
+
+        info!("Member {} is shutting down, proposing it offline", name);
+        self.planned_restarts.forget(&name).await;
+        self.cast_offline_proposals(&BTreeSet::from([name])).await
+    }
+}
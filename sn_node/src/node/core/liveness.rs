@@ -0,0 +1,398 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{data::holding_proof, Cmd, Node};
+use crate::node::Result;
+
+use rand::{seq::SliceRandom, Rng};
+use sn_interface::{
+    messaging::{
+        system::{NodeCmd, SystemMsg},
+        DstLocation,
+    },
+    types::{ChunkAddress, PublicKey, ReplicatedData, ReplicatedDataAddress},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// How often each adult is probed.
+pub(crate) const LIVENESS_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long adults have to answer a probe before it's counted missed.
+const PROBE_DEADLINE: Duration = Duration::from_secs(20);
+/// Answers slower than this are tracked as comm issues against the adult.
+const SLOW_ANSWER: Duration = Duration::from_secs(5);
+/// Probes missed in a row for an adult to be proposed offline.
+const MAX_MISSED_PROBES: u32 = 3;
+/// Names of the registry tried when looking for a chunk an adult should hold.
+const CHUNK_PICKS: usize = 8;
+/// Weight of the latest answer in the average latency of an adult.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// How an adult has been answering our probes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct AdultLiveness {
+    /// Moving average of the time taken to answer, `None` until the first answer.
+    pub(crate) latency: Option<Duration>,
+    /// Probes missed since the last answer.
+    pub(crate) missed: u32,
+    pub(crate) probed: u64,
+    pub(crate) answered: u64,
+}
+
+/// A probe to send to an adult.
+#[derive(Debug)]
+pub(crate) struct Probe {
+    pub(crate) adult: XorName,
+    pub(crate) probe: u64,
+    pub(crate) nonce: [u8; 32],
+}
+
+/// Probes to send, and adults which missed too many of them in a row.
+#[derive(Debug, Default)]
+pub(crate) struct ProbeRound {
+    pub(crate) probes: Vec<Probe>,
+    pub(crate) unresponsive: BTreeSet<XorName>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    adult: XorName,
+    sent_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_probe: u64,
+    pending: BTreeMap<u64, Pending>,
+    adults: BTreeMap<XorName, AdultLiveness>,
+}
+
+/// Elders' probing of their adults for liveness.
+///
+/// Every tick, each adult without a probe in flight is sent one, challenging it to prove it
+/// holds one of the chunks it should, within `PROBE_DEADLINE`. Adults missing
+/// `MAX_MISSED_PROBES` in a row are proposed offline, rather than being noticed only once
+/// client reads fail, while slow answers count as comm issues for the dysfunction tracking.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LivenessProbes {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl LivenessProbes {
+    /// Counts the probes past their deadline as missed, forgets the adults which aren't among
+    /// `adults` anymore, and returns the probes to send to the others.
+    pub(crate) async fn next(&self, adults: &BTreeSet<XorName>, now: Instant) -> ProbeRound {
+        let mut inner = self.inner.write().await;
+        let Inner {
+            next_probe,
+            pending,
+            adults: tracked,
+        } = &mut *inner;
+
+        pending.retain(|probe, pending| {
+            if now.saturating_duration_since(pending.sent_at) <= PROBE_DEADLINE {
+                return true;
+            }
+            if let Some(liveness) = tracked.get_mut(&pending.adult) {
+                liveness.missed += 1;
+                warn!(
+                    "Adult {:?} didn't answer liveness probe {}, {} missed in a row",
+                    pending.adult, probe, liveness.missed
+                );
+            }
+            false
+        });
+        tracked.retain(|adult, _| adults.contains(adult));
+        pending.retain(|_, pending| adults.contains(&pending.adult));
+
+        let probing = pending
+            .values()
+            .map(|pending| pending.adult)
+            .collect::<BTreeSet<_>>();
+        let mut round = ProbeRound::default();
+        for adult in adults {
+            let liveness = tracked.entry(*adult).or_default();
+            if liveness.missed >= MAX_MISSED_PROBES {
+                let _new = round.unresponsive.insert(*adult);
+            }
+            if probing.contains(adult) {
+                continue;
+            }
+            liveness.probed += 1;
+            let probe = *next_probe;
+            *next_probe += 1;
+            let _prev = pending.insert(
+                probe,
+                Pending {
+                    adult: *adult,
+                    sent_at: now,
+                },
+            );
+            round.probes.push(Probe {
+                adult: *adult,
+                probe,
+                nonce: rand::thread_rng().gen(),
+            });
+        }
+        round
+    }
+
+    /// Records that `adult` answered `probe`, returning the time it took if it was awaited.
+    pub(crate) async fn record(
+        &self,
+        adult: XorName,
+        probe: u64,
+        now: Instant,
+    ) -> Option<Duration> {
+        let mut inner = self.inner.write().await;
+        match inner.pending.get(&probe) {
+            Some(pending) if pending.adult == adult => {}
+            _ => return None,
+        }
+        let sent_at = inner.pending.remove(&probe)?.sent_at;
+        let took = now.saturating_duration_since(sent_at);
+        let liveness = inner.adults.entry(adult).or_default();
+        liveness.missed = 0;
+        liveness.answered += 1;
+        liveness.latency = Some(match liveness.latency {
+            Some(latency) => latency.mul_f64(1.0 - LATENCY_WEIGHT) + took.mul_f64(LATENCY_WEIGHT),
+            None => took,
+        });
+        Some(took)
+    }
+
+    /// How each adult probed has been answering.
+    pub(crate) async fn adults(&self) -> BTreeMap<XorName, AdultLiveness> {
+        self.inner.read().await.adults.clone()
+    }
+}
+
+impl Node {
+    /// Probes our adults for liveness, as an elder, proposing offline those which missed too
+    /// many probes in a row, unless they announced a restart.
+    pub(crate) async fn probe_adults_liveness(&self) -> Result<Vec<Cmd>> {
+        if !self.is_elder().await {
+            return Ok(vec![]);
+        }
+
+        let adults = self
+            .network_knowledge
+            .adults()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .collect::<BTreeSet<_>>();
+        let round = self.liveness_probes.next(&adults, Instant::now()).await;
+
+        let mut cmds = vec![];
+        let restarting = self.planned_restarts.pending().await;
+        let unresponsive = round
+            .unresponsive
+            .difference(&restarting)
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if !unresponsive.is_empty() {
+            warn!(
+                "Proposing offline adults unresponsive to liveness probes: {:?}",
+                unresponsive
+            );
+            cmds.push(Cmd::ProposeOffline(unresponsive));
+        }
+
+        let names = self.holder_registry.names().await;
+        let section_pk = self.network_knowledge.section_key().await;
+        for probe in round.probes {
+            let name = self.chunk_held_by(probe.adult, &names).await;
+            cmds.push(Cmd::SignOutgoingSystemMsg {
+                msg: SystemMsg::NodeCmd(NodeCmd::ProbeLiveness {
+                    probe: probe.probe,
+                    nonce: probe.nonce,
+                    name,
+                }),
+                dst: DstLocation::Node {
+                    name: probe.adult,
+                    section_pk,
+                },
+            });
+        }
+        Ok(cmds)
+    }
+
+    // A chunk `adult` should hold, if one is found among a few random picks of `names`.
+    async fn chunk_held_by(&self, adult: XorName, names: &[XorName]) -> Option<XorName> {
+        let picks = names
+            .choose_multiple(&mut rand::thread_rng(), CHUNK_PICKS)
+            .copied()
+            .collect::<Vec<_>>();
+        for name in picks {
+            if let Some(holders) = self.holder_registry.holders_of(&name).await {
+                if holders.contains(&adult) {
+                    return Some(name);
+                }
+            }
+        }
+        None
+    }
+
+    /// Records an adult's answer to a liveness probe, tracking a comm issue against it if it
+    /// was slow to come.
+    pub(crate) async fn record_liveness(
+        &self,
+        adult: XorName,
+        probe: u64,
+        proof: Option<[u8; 32]>,
+    ) -> Result<Vec<Cmd>> {
+        let took = match self
+            .liveness_probes
+            .record(adult, probe, Instant::now())
+            .await
+        {
+            Some(took) => took,
+            None => return Ok(vec![]),
+        };
+        trace!(
+            "Adult {:?} answered liveness probe {} in {:?}, proving a chunk: {}",
+            adult,
+            probe,
+            took,
+            proof.is_some()
+        );
+        if took > SLOW_ANSWER {
+            warn!(
+                "Adult {:?} took {:?} to answer liveness probe {}",
+                adult, took, probe
+            );
+            self.log_comm_issue(adult).await?;
+        }
+        Ok(vec![])
+    }
+
+    /// On adults, answers an elder's liveness probe, proving we hold the chunk named if we do.
+    pub(crate) async fn answer_liveness_probe(
+        &self,
+        probe: u64,
+        nonce: [u8; 32],
+        name: Option<XorName>,
+        elder: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let mut proof = None;
+        if let Some(name) = name {
+            let address = ReplicatedDataAddress::Chunk(ChunkAddress(name));
+            if let Ok(ReplicatedData::Chunk(chunk)) =
+                self.data_storage.get_for_replication(address).await
+            {
+                proof = Some(holding_proof(&nonce, chunk.value()));
+            }
+        }
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: SystemMsg::NodeCmd(NodeCmd::RecordLiveness {
+                node_id,
+                probe,
+                proof,
+            }),
+            dst: DstLocation::Node {
+                name: elder,
+                section_pk: self.network_knowledge.section_key().await,
+            },
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adults(count: usize) -> BTreeSet<XorName> {
+        (0..count).map(|_| xor_name::rand::random()).collect()
+    }
+
+    #[tokio::test]
+    async fn adults_missing_probes_in_a_row_are_unresponsive() {
+        let adults = adults(3);
+        let (dead, alive) = {
+            let mut iter = adults.iter().copied();
+            (iter.next().unwrap_or_default(), iter.collect::<Vec<_>>())
+        };
+        let probes = LivenessProbes::default();
+        let mut now = Instant::now();
+
+        for round in 0..=MAX_MISSED_PROBES {
+            let next = probes.next(&adults, now).await;
+            if round < MAX_MISSED_PROBES {
+                assert!(next.unresponsive.is_empty());
+            } else {
+                assert_eq!(next.unresponsive, BTreeSet::from([dead]));
+            }
+            assert_eq!(next.probes.len(), adults.len());
+            let answered_at = now + Duration::from_millis(100);
+            for probe in next.probes.iter().filter(|probe| probe.adult != dead) {
+                assert_eq!(
+                    probes.record(probe.adult, probe.probe, answered_at).await,
+                    Some(Duration::from_millis(100))
+                );
+            }
+            now += PROBE_DEADLINE + Duration::from_secs(1);
+        }
+
+        let tracked = probes.adults().await;
+        assert_eq!(tracked[&dead].missed, MAX_MISSED_PROBES);
+        assert_eq!(tracked[&dead].latency, None);
+        for adult in &alive {
+            assert_eq!(tracked[adult].missed, 0);
+            assert_eq!(tracked[adult].latency, Some(Duration::from_millis(100)));
+        }
+
+        // An answer resets the count.
+        let next = probes.next(&adults, now).await;
+        let probe = next
+            .probes
+            .iter()
+            .find(|probe| probe.adult == dead)
+            .map(|probe| probe.probe)
+            .unwrap_or_default();
+        assert!(probes.record(dead, probe, now).await.is_some());
+        assert!(probes.next(&adults, now).await.unresponsive.is_empty());
+    }
+
+    #[tokio::test]
+    async fn answers_are_only_recorded_once_from_the_adult_probed() {
+        let adults = adults(2);
+        let probes = LivenessProbes::default();
+        let now = Instant::now();
+        let next = probes.next(&adults, now).await;
+        let first = &next.probes[0];
+        let other = next.probes[1].adult;
+
+        assert_eq!(probes.record(other, first.probe, now).await, None);
+        assert!(probes.record(first.adult, first.probe, now).await.is_some());
+        assert_eq!(probes.record(first.adult, first.probe, now).await, None);
+
+        // A probe in flight isn't sent again, and adults gone are forgotten.
+        let next = probes
+            .next(&BTreeSet::from([first.adult, other]), now)
+            .await;
+        assert_eq!(
+            next.probes
+                .iter()
+                .map(|probe| probe.adult)
+                .collect::<Vec<_>>(),
+            vec![first.adult]
+        );
+        let _next = probes.next(&BTreeSet::from([other]), now).await;
+        assert_eq!(
+            probes.adults().await.into_keys().collect::<Vec<_>>(),
+            vec![other]
+        );
+    }
+}
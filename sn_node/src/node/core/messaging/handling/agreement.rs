@@ -9,7 +9,7 @@
 use crate::node::{
     api::cmds::Cmd,
     core::{relocation::ChurnId, Node, Proposal},
-    Event, Result,
+    Event, MembershipEvent, Result, MIN_LEVEL_WHEN_FULL,
 };
 use sn_consensus::Generation;
 use sn_interface::messaging::system::{KeyedSig, MembershipState, SectionAuth};
@@ -40,7 +40,15 @@ impl Node {
                 Ok(vec![])
             }
             Proposal::JoinsAllowed(joins_allowed) => {
-                *self.joins_allowed.write().await = joins_allowed;
+                self.set_joins_allowed(joins_allowed).await;
+                Ok(vec![])
+            }
+            Proposal::StorageFull { node, level } => {
+                let changed = self.reset_storage_level(&node, level).await;
+                if changed && level.value() >= MIN_LEVEL_WHEN_FULL {
+                    // ..then we accept a new node in place of the full node
+                    self.set_joins_allowed(true).await;
+                }
                 Ok(vec![])
             }
         }
@@ -95,7 +103,8 @@ impl Node {
             return Ok(vec![]);
         }
 
-        self.add_new_adult_to_trackers(new_info.name()).await;
+        self.add_new_adult_to_trackers(new_info.name(), new_info.previous_name().is_some())
+            .await;
 
         info!("handle Online: {} at {}", new_info.name(), new_info.addr());
 
@@ -106,15 +115,21 @@ impl Node {
             age: new_info.age(),
         })
         .await;
+        self.send_membership_event(MembershipEvent::PeerJoined {
+            name: new_info.name(),
+            previous_name: new_info.previous_name(),
+            age: new_info.age(),
+        });
 
         self.log_section_stats().await;
+        self.record_membership_snapshot().await;
 
         // Do not disable node joins in first section.
         let our_prefix = self.network_knowledge.prefix().await;
         if !our_prefix.is_empty() {
             // ..otherwise, switch off joins_allowed on a node joining.
             // TODO: fix racing issues here? https://github.com/maidsafe/safe_network/issues/890
-            *self.joins_allowed.write().await = false;
+            self.set_joins_allowed(false).await;
         }
 
         let churn_id = ChurnId(new_info.sig.signature.to_bytes().to_vec());
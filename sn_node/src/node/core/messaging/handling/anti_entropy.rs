@@ -58,6 +58,13 @@ impl Node {
             )
             .await?;
 
+        // Should our section have split, the data of the sibling's is handed over to its adults.
+        if let Err(error) = self
+            .start_split_handover(snapshot.prefix, &old_adults)
+            .await
+        {
+            error!("Failed to start handing data over to our sibling section: {error}");
+        }
         let mut cmds = self.try_reorganize_data(old_adults).await?;
 
         // always run this, only changes will trigger events
@@ -0,0 +1,156 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    api::cmds::Cmd,
+    core::{data::replica_id, Node},
+    error::convert_to_error_msg,
+    Result,
+};
+
+use sn_interface::{
+    data_copy_count,
+    messaging::{
+        data::{BatchQuery, BatchStatus, CmdError, DataCmd, Error as ErrorMsg, QueryResponse},
+        AuthorityProof, MsgId, ServiceAuth,
+    },
+    types::{Peer, PublicKey, ReplicatedData, ReplicatedDataAddress},
+};
+
+impl Node {
+    /// Handles a client batch of data cmds, all or nothing of it being sent to the holders of
+    /// its data: the data of each cmd gets its holders reserved first, and only once all of them
+    /// have some are the msgs to the holders formed, then sent together.
+    ///
+    /// The batch is all or nothing at the elders only. The holders storing their data aren't
+    /// awaited, nor is anything they stored undone when some of them fail to.
+    pub(crate) async fn handle_batch_cmd(
+        &self,
+        cmds: Vec<DataCmd>,
+        addresses: Vec<ReplicatedDataAddress>,
+        msg_id: MsgId,
+        auth: AuthorityProof<ServiceAuth>,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let key = (msg_id, auth.public_key);
+
+        // A batch sent again, by a client which timed out on our response to it, is answered
+        // as it was the first time rather than applied twice.
+        if let Some(status) = self.batches.get(&key).await {
+            debug!("Replaying the status of batch {:?} received again", msg_id);
+            return match status {
+                BatchStatus::Committed => self.send_cmd_ack(origin, msg_id).await,
+                BatchStatus::Aborted(error) => {
+                    self.send_cmd_error_response(CmdError::Data(error.into()), origin, msg_id)
+                        .await
+                }
+                _ => Ok(vec![]),
+            };
+        }
+
+        // Reserve
+        let prefix = self.network_knowledge().prefix().await;
+        let mut batch = Vec::with_capacity(cmds.len());
+        for (index, (cmd, address)) in cmds.into_iter().zip(addresses).enumerate() {
+            let data = match cmd {
+                DataCmd::StoreChunk(chunk) => ReplicatedData::Chunk(chunk),
+                DataCmd::Register(cmd) => ReplicatedData::RegisterWrite(cmd),
+            };
+            let name = *address.name();
+            let found = self.get_adults_who_should_store_data(name).await.len();
+            let error = if !prefix.matches(&name) {
+                Some(ErrorMsg::InvalidOperation(format!(
+                    "{:?} is not in our section {:?}",
+                    name, prefix
+                )))
+            } else if found < data_copy_count() {
+                Some(ErrorMsg::InsufficientAdults {
+                    prefix,
+                    expected: data_copy_count() as u8,
+                    found: found as u8,
+                })
+            } else {
+                None
+            };
+            if let Some(error) = error {
+                return self.abort_batch(key, index, error, origin).await;
+            }
+            batch.push((index, data, address));
+        }
+        let _prev = self.batches.set(key, BatchStatus::Reserved, None).await;
+
+        // Apply, the chunks ahead of the register writes which may point at them
+        batch.sort_by_key(|(_, data, _)| !matches!(data, ReplicatedData::Chunk(_)));
+        let mut msgs = vec![];
+        let mut writes = vec![];
+        for (index, data, address) in batch {
+            let replica = replica_id(&data);
+            match self.store_data(data, &address).await {
+                Ok((cmds, holders)) => {
+                    msgs.extend(cmds);
+                    writes.push((replica, holders));
+                }
+                Err(error) => {
+                    return self
+                        .abort_batch(key, index, convert_to_error_msg(error), origin)
+                        .await
+                }
+            }
+        }
+        let _prev = self.batches.set(key, BatchStatus::Applied, None).await;
+
+        // Commit, the token of the last write covering all those of the batch
+        let mut token = None;
+        for (replica, holders) in writes {
+            token = self.record_write(replica, holders).await.or(token);
+        }
+        let _prev = self.batches.set(key, BatchStatus::Committed, None).await;
+        msgs.extend(self.send_cmd_ack_with_token(origin, msg_id, token).await?);
+        Ok(msgs)
+    }
+
+    // Records the batch as aborted for the cmd at `index` failing with `error`, and tells the
+    // client so. Nothing of the batch is sent to the holders of its data.
+    async fn abort_batch(
+        &self,
+        key: (MsgId, PublicKey),
+        index: usize,
+        error: ErrorMsg,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let (msg_id, _) = key;
+        warn!("Aborting batch {:?} at cmd {}: {}", msg_id, index, error);
+        let error = ErrorMsg::BatchAborted {
+            index: index as u16,
+            error: Box::new(error),
+        };
+        let _prev = self
+            .batches
+            .set(key, BatchStatus::Aborted(error.clone()), None)
+            .await;
+        self.send_cmd_error_response(CmdError::Data(error.into()), origin, msg_id)
+            .await
+    }
+
+    /// Answers a client with the status of a batch it sent us.
+    pub(crate) async fn handle_batch_query(
+        &self,
+        query: BatchQuery,
+        msg_id: MsgId,
+        auth: AuthorityProof<ServiceAuth>,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let status = self
+            .batches
+            .get(&(query.batch, auth.public_key))
+            .await
+            .unwrap_or(BatchStatus::Unknown);
+        let response = QueryResponse::BatchStatus((Ok(status), query.operation_id()?));
+        self.send_query_response(origin, response, msg_id).await
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{api::cmds::Cmd, core::Node, Result};
+
+use sn_interface::{
+    messaging::{
+        data::{DataQuery, SessionToken},
+        AuthorityProof, MsgId, ServiceAuth,
+    },
+    types::{Peer, PublicKey, ReplicatedDataAddress},
+};
+
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// A consistent query held until our adults caught up on the write it reads.
+#[derive(Debug)]
+pub(crate) struct HeldQuery {
+    query: DataQuery,
+    address: ReplicatedDataAddress,
+    msg_id: MsgId,
+    auth: AuthorityProof<ServiceAuth>,
+    origin: Peer,
+}
+
+impl Node {
+    /// Records a client write of the replica `replica` we sent to `holders`, returning the
+    /// token to ack it with.
+    pub(super) async fn record_write(
+        &self,
+        replica: Option<XorName>,
+        holders: BTreeSet<XorName>,
+    ) -> Option<SessionToken> {
+        let index = self.consistent_reads.record_write(replica?, holders);
+        Some(SessionToken {
+            section_key: self.network_knowledge().section_key().await,
+            elder: self.info.read().await.name(),
+            index,
+        })
+    }
+
+    /// Answers a client query once our adults caught up on the write of the token we issued
+    /// among `tokens`, right away if there's none such.
+    pub(super) async fn handle_consistent_query(
+        &self,
+        query: DataQuery,
+        address: ReplicatedDataAddress,
+        tokens: Vec<SessionToken>,
+        msg_id: MsgId,
+        auth: AuthorityProof<ServiceAuth>,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let our_name = self.info.read().await.name();
+        let token = tokens
+            .into_iter()
+            .filter(|token| token.elder == our_name)
+            .max_by_key(|token| token.index);
+        // Tokens of keys we don't know of weren't issued by us.
+        let index = match token {
+            Some(token) => self
+                .network_knowledge()
+                .has_chain_key(&token.section_key)
+                .await
+                .then_some(token.index),
+            None => None,
+        };
+        let index = match index {
+            Some(index) => index,
+            None => {
+                return self
+                    .read_data_from_adults(query, address, msg_id, auth, origin)
+                    .await
+            }
+        };
+
+        let held = HeldQuery {
+            query,
+            address,
+            msg_id,
+            auth,
+            origin,
+        };
+        match self.consistent_reads.hold(index, held) {
+            Some(held) => self.answer_held_query(held).await,
+            None => {
+                debug!("Holding query {:?} until write {} is stored", msg_id, index);
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Records our adult `node_id` storing `replicas`, answering the queries held which are
+    /// caught up on now.
+    pub(crate) async fn record_replicas_stored(
+        &self,
+        node_id: PublicKey,
+        replicas: BTreeSet<XorName>,
+    ) -> Result<Vec<Cmd>> {
+        let mut cmds = vec![];
+        for held in self
+            .consistent_reads
+            .record_stored(XorName::from(node_id), &replicas)
+        {
+            cmds.extend(self.answer_held_query(held).await?);
+        }
+        Ok(cmds)
+    }
+
+    async fn answer_held_query(&self, held: HeldQuery) -> Result<Vec<Cmd>> {
+        let HeldQuery {
+            query,
+            address,
+            msg_id,
+            auth,
+            origin,
+        } = held;
+        self.read_data_from_adults(query, address, msg_id, auth, origin)
+            .await
+    }
+}
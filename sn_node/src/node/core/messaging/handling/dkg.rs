@@ -8,7 +8,7 @@
 
 use crate::node::{
     api::cmds::Cmd,
-    core::{Node, Proposal},
+    core::{role_state::PromotionStep, Node, Proposal},
     dkg::DkgFailureSigSetUtils,
     messages::WireMsgUtils,
     Error, Result,
@@ -28,12 +28,28 @@ use xor_name::XorName;
 
 impl Node {
     pub(crate) async fn handle_dkg_start(&self, session_id: DkgSessionId) -> Result<Vec<Cmd>> {
+        if self.sits_out("DKG").await {
+            return Ok(vec![]);
+        }
         let current_generation = self.network_knowledge.chain_len().await;
         if session_id.section_chain_len < current_generation {
             trace!("Skipping DkgStart for older generation: {:?}", &session_id);
             return Ok(vec![]);
         }
         let section_auth = self.network_knowledge().authority_provider().await;
+        if session_id.network_params != section_auth.network_params() {
+            warn!(
+                "Skipping DkgStart with other network params than our section's {:?}: {:?}",
+                section_auth.network_params(),
+                &session_id
+            );
+            return Ok(vec![]);
+        }
+
+        if session_id.contains_elder(self.info.read().await.name()) {
+            self.record_promotion_step(PromotionStep::ProposalObserved(session_id.clone()))
+                .await;
+        }
 
         let mut peers = vec![];
         for session_peer in session_id.elder_peers() {
@@ -261,6 +277,8 @@ impl Node {
         // Add our new keyshare to our cache, we will then use
         // it to sign any msg that needs section agreement.
         self.section_keys_provider.insert(key_share.clone()).await;
+        self.record_promotion_step(PromotionStep::KeyShareReceived((&key_share).into()))
+            .await;
 
         let snapshot = self.state_snapshot().await;
 
@@ -11,11 +11,11 @@ use crate::node::{
     core::{relocation::RelocateDetailsUtils, Node},
     Result,
 };
-use sn_interface::elder_count;
 use sn_interface::messaging::system::{
     JoinAsRelocatedRequest, JoinAsRelocatedResponse, JoinRejectionReason, JoinRequest,
     JoinResponse, MembershipState, NodeState, SystemMsg,
 };
+use sn_interface::messaging::ProtocolVersions;
 use sn_interface::network_knowledge::{SectionAuthUtils, FIRST_SECTION_MAX_AGE, MIN_ADULT_AGE};
 use sn_interface::types::{log_markers::LogMarker, Peer};
 
@@ -33,6 +33,61 @@ impl Node {
     ) -> Result<Vec<Cmd>> {
         debug!("Received {:?} from {}", join_request, peer);
 
+        // Nodes running with other protocol constants than ours would disagree with us about
+        // fundamentals of the network.
+        let our_protocol = self
+            .network_knowledge
+            .authority_provider()
+            .await
+            .protocol_digest();
+        if join_request.protocol_digest != our_protocol {
+            if self.is_not_elder().await {
+                return Ok(vec![]);
+            }
+            warn!(
+                "Rejecting JoinRequest from {} - protocol constants {} aren't ours {}",
+                peer, join_request.protocol_digest, our_protocol
+            );
+            let node_msg = SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                JoinRejectionReason::ProtocolMismatch {
+                    section: our_protocol,
+                },
+            )));
+            trace!("{}", LogMarker::SendJoinRejected);
+
+            trace!("Sending {:?} to {}", node_msg, peer);
+            let section_key = self.network_knowledge.section_key().await;
+            return Ok(vec![
+                self.send_direct_msg(peer, node_msg, section_key).await?,
+            ]);
+        }
+
+        // Nodes of releases which can't read our msgs, or write ones we can't read, are told so
+        // rather than left to fail on every msg exchanged.
+        let our_versions = ProtocolVersions::ours();
+        if !our_versions.compatible_with(&join_request.protocol_versions) {
+            if self.is_not_elder().await {
+                return Ok(vec![]);
+            }
+            warn!(
+                "Rejecting JoinRequest from {} - messaging protocol versions {} aren't compatible \
+                with ours {}",
+                peer, join_request.protocol_versions, our_versions
+            );
+            let node_msg = SystemMsg::JoinResponse(Box::new(JoinResponse::Rejected(
+                JoinRejectionReason::IncompatibleVersion {
+                    section: our_versions,
+                },
+            )));
+            trace!("{}", LogMarker::SendJoinRejected);
+
+            trace!("Sending {:?} to {}", node_msg, peer);
+            let section_key = self.network_knowledge.section_key().await;
+            return Ok(vec![
+                self.send_direct_msg(peer, node_msg, section_key).await?,
+            ]);
+        }
+
         // Require resource signed if joining as a new node.
         if let Some(response) = join_request.resource_proof_response {
             if !self
@@ -182,8 +237,9 @@ impl Node {
             // Forces the joining node to be younger than the youngest elder in genesis section
             // avoiding unnecessary churn.
 
-            // Check if `elder_count()` Elders are already present
-            if elders.len() == elder_count() {
+            // Check if the section's full count of Elders are already present
+            let elder_count = self.network_knowledge.network_params().await.elder_count();
+            if elders.len() == elder_count {
                 // Check if the joining node is younger than the youngest elder and older than
                 // MIN_ADULT_AGE in the first section, to avoid unnecessary churn during genesis.
                 let expected_age = FIRST_SECTION_MIN_ELDER_AGE - current_section_size as u8 * 2;
@@ -261,6 +317,34 @@ impl Node {
             return Ok(vec![]);
         }
 
+        // Don't let relocations be joined with when they don't follow from the churn they were
+        // drawn from, long after they were issued, or by another node than the one which first
+        // used them.
+        let admitted = if relocate_details.verify_draw() {
+            self.relocation_freshness
+                .admit(
+                    relocate_details,
+                    &peer,
+                    self.network_knowledge.chain_len().await,
+                )
+                .await
+        } else {
+            Err(JoinRejectionReason::RelocationNotDrawn)
+        };
+        if let Err(reason) = admitted {
+            debug!("Rejecting JoinAsRelocatedRequest from {peer} - {reason:?}.");
+            let node_msg = SystemMsg::JoinAsRelocatedResponse(Box::new(
+                JoinAsRelocatedResponse::Rejected(reason),
+            ));
+            trace!("{}", LogMarker::SendJoinAsRelocatedResponse);
+
+            trace!("Sending {:?} to {}", node_msg, peer);
+            return Ok(vec![
+                self.send_direct_msg(peer, node_msg, self.network_knowledge.section_key().await)
+                    .await?,
+            ]);
+        }
+
         // Finally do reachability check
         if self.comm.is_reachable(&peer.addr()).await.is_err() {
             let node_msg = SystemMsg::JoinAsRelocatedResponse(Box::new(
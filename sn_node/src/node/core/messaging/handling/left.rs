@@ -9,6 +9,7 @@ use sn_interface::{
 use crate::node::{
     api::cmds::Cmd,
     core::{relocation::ChurnId, Node, Result},
+    MembershipEvent,
 };
 
 impl Node {
@@ -46,6 +47,12 @@ impl Node {
             node_state.name(),
             node_state.addr()
         );
+        self.record_membership_snapshot().await;
+        self.send_membership_event(MembershipEvent::PeerLeft {
+            name: node_state.name(),
+            age: node_state.age(),
+            relocated: node_state.is_relocated(),
+        });
 
         // If this is an Offline agreement where the new node state is Relocated,
         // we then need to send the Relocate msg to the peer attaching the signed NodeState
@@ -85,7 +92,7 @@ impl Node {
                 .collect(),
         )
         .await?;
-        *self.joins_allowed.write().await = true;
+        self.set_joins_allowed(true).await;
 
         Ok(cmds)
     }
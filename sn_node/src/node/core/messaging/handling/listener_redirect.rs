@@ -0,0 +1,82 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{api::cmds::Cmd, core::Node, messages::WireMsgUtils, Result};
+use sn_interface::messaging::{system::SystemMsg, WireMsg};
+use sn_interface::types::Peer;
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+
+impl Node {
+    /// Bounces a msg that reached the listener for the other kind of peers than `sender`, back
+    /// to it along with the address of the listener for its kind.
+    pub(crate) async fn redirect_to_listener(
+        &self,
+        sender: Peer,
+        wire_msg: &WireMsg,
+        original_bytes: Bytes,
+        redirect_to: SocketAddr,
+    ) -> Result<Cmd> {
+        trace!(
+            "Redirecting {:?} from {:?} to our listener at {}",
+            wire_msg.msg_id(),
+            sender,
+            redirect_to
+        );
+
+        let msg = SystemMsg::ListenerRedirect {
+            addr: redirect_to,
+            bounced_msg: original_bytes,
+        };
+        let wire_msg = WireMsg::single_src(
+            &self.info.read().await.clone(),
+            wire_msg.msg_kind().src().to_dst(),
+            msg,
+            self.network_knowledge.section_key().await,
+        )?;
+
+        Ok(Cmd::SendMsg {
+            recipients: vec![sender],
+            wire_msg,
+        })
+    }
+
+    /// Resends a msg of ours that reached the listener for clients of `sender`, to its listener
+    /// for nodes at `addr`.
+    pub(crate) async fn handle_listener_redirect(
+        &self,
+        sender: Peer,
+        addr: SocketAddr,
+        bounced_msg: Bytes,
+    ) -> Result<Vec<Cmd>> {
+        let wire_msg = WireMsg::from(bounced_msg)?;
+
+        // Only our own msgs are resent, so no one can have us send theirs anywhere.
+        if wire_msg.msg_kind().src().name() != self.info.read().await.name() {
+            warn!(
+                "Dropping listener redirect from {:?}, of a msg that isn't ours: {:?}",
+                sender,
+                wire_msg.msg_id()
+            );
+            return Ok(vec![]);
+        }
+
+        debug!(
+            "Resending {:?} to the listener for nodes of {:?}, at {}",
+            wire_msg.msg_id(),
+            sender,
+            addr
+        );
+
+        Ok(vec![Cmd::SendMsg {
+            recipients: vec![Peer::new(sender.name(), addr)],
+            wire_msg,
+        }])
+    }
+}
@@ -23,6 +23,9 @@ impl Node {
         &self,
         node_state: NodeState,
     ) -> Result<Vec<Cmd>> {
+        if self.sits_out("membership proposals").await {
+            return Ok(vec![]);
+        }
         info!(
             "Proposing membership change: {} - {:?}",
             node_state.name, node_state.state
@@ -8,34 +8,42 @@
 
 mod agreement;
 mod anti_entropy;
+mod batch;
+mod consistency;
 mod dkg;
 mod handover;
 mod join;
 mod left;
+mod listener_redirect;
 mod membership;
 mod proposals;
 mod relocation;
+mod replication;
 mod resource_proof;
 mod service_msgs;
 mod update_section;
 
-pub(crate) use proposals::handle_proposal;
+pub(crate) use consistency::HeldQuery;
+pub(crate) use proposals::{handle_proposal, handle_proposal_batch};
 
-use crate::dbs::Error as DbError;
 use crate::node::{
     api::cmds::Cmd,
-    core::{DkgSessionInfo, Node, Proposal as CoreProposal, DATA_QUERY_LIMIT},
+    core::{
+        DkgSessionInfo, Misbehaviour, Node, Proposal as CoreProposal, ProposalBatch,
+        DATA_QUERY_LIMIT,
+    },
     messages::WireMsgUtils,
-    Error, Event, MessageReceived, Result, MIN_LEVEL_WHEN_FULL,
+    Error, Event, MembershipEvent, MessageReceived, Result,
 };
 use sn_interface::messaging::{
     data::{ServiceMsg, StorageLevel},
     signature_aggregator::Error as AggregatorError,
     system::{
-        JoinResponse, NodeCmd, NodeEvent, NodeMsgAuthorityUtils, NodeQuery,
-        Proposal as ProposalMsg, SystemMsg,
+        JoinAsRelocatedResponse, JoinResponse, MembershipState, NodeCmd, NodeEvent,
+        NodeMsgAuthorityUtils, NodeQuery, SystemMsg,
     },
-    AuthorityProof, DstLocation, MsgId, MsgType, NodeMsgAuthority, SectionAuth, WireMsg,
+    AuthKind, AuthorityProof, DstLocation, Error as MessagingError, MsgId, MsgType,
+    NodeMsgAuthority, SectionAuth, WireMsg,
 };
 use sn_interface::network_knowledge::NetworkKnowledge;
 use sn_interface::types::{log_markers::LogMarker, Peer, PublicKey};
@@ -59,9 +67,83 @@ impl Node {
         sender: Peer,
         wire_msg: WireMsg,
         original_bytes: Option<Bytes>,
+    ) -> Result<Vec<Cmd>> {
+        // A traced msg gets us recorded as one of its hops, and the trace passed on with the
+        // msgs handling it has us send.
+        let trace = match wire_msg.trace() {
+            Some(trace) => {
+                let mut trace = trace.clone();
+                let duty = self.duty_of(&wire_msg).await;
+                trace.add_hop(self.info.read().await.name(), &duty);
+                Some(trace)
+            }
+            None => None,
+        };
+
+        let mut cmds = self
+            .handle_wire_msg(sender, wire_msg, original_bytes)
+            .await?;
+
+        if let Some(trace) = trace {
+            for cmd in &mut cmds {
+                match cmd {
+                    Cmd::SendMsg { wire_msg, .. } | Cmd::SendMsgDeliveryGroup { wire_msg, .. }
+                        if wire_msg.trace().is_none() =>
+                    {
+                        wire_msg.set_trace(Some(trace.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(cmds)
+    }
+
+    // What we do with the msg, as recorded in its trace.
+    async fn duty_of(&self, wire_msg: &WireMsg) -> String {
+        let role = if self.is_elder().await {
+            "elder"
+        } else {
+            "adult"
+        };
+        let what = match wire_msg.msg_kind() {
+            AuthKind::Service(_) => match rmp_serde::from_slice(&wire_msg.payload) {
+                Ok(ServiceMsg::Cmd(_)) => "client cmd",
+                Ok(ServiceMsg::Query(_)) => "client query",
+                Ok(ServiceMsg::ConsistentQuery { .. }) => "consistent query",
+                Ok(ServiceMsg::SpentbookCmd(_)) => "spentbook cmd",
+                Ok(ServiceMsg::SpentbookQuery(_)) => "spentbook query",
+                Ok(ServiceMsg::BatchCmd(_)) => "client batch",
+                Ok(ServiceMsg::BatchQuery(_)) => "batch query",
+                Ok(ServiceMsg::HoldersQuery(_)) => "holders query",
+                _ => "client msg",
+            },
+            AuthKind::Node(_) | AuthKind::NodeBlsShare(_) => {
+                match rmp_serde::from_slice(&wire_msg.payload) {
+                    Ok(SystemMsg::NodeQuery(_)) => "data query",
+                    Ok(SystemMsg::NodeQueryResponse { .. }) => "data query response",
+                    Ok(SystemMsg::NodeCmd(_)) => "node cmd",
+                    _ => "node msg",
+                }
+            }
+        };
+        format!("{}: {}", role, what)
+    }
+
+    async fn handle_wire_msg(
+        &self,
+        sender: Peer,
+        wire_msg: WireMsg,
+        original_bytes: Option<Bytes>,
     ) -> Result<Vec<Cmd>> {
         let mut cmds = vec![];
 
+        // Msgs for the nodes we relay for are only passed on
+        if let Some(relayed) = self.relay_inbound(&wire_msg).await {
+            return Ok(relayed);
+        }
+
         // Deserialize the payload of the incoming message
         let msg_id = wire_msg.msg_id();
         // payload needed for aggregation
@@ -69,11 +151,20 @@ impl Node {
 
         let message_type = match wire_msg.into_msg() {
             Ok(message_type) => message_type,
+            Err(error @ MessagingError::IncompatibleVersion { .. }) => {
+                warn!("Dropping message {:?} from {}: {}", msg_id, sender, error);
+                return Ok(cmds);
+            }
             Err(error) => {
                 error!(
                     "Failed to deserialize message payload ({:?}): {:?}",
                     msg_id, error
                 );
+                let misbehaviour = match error {
+                    MessagingError::InvalidSignature => Misbehaviour::InvalidSignature,
+                    _ => Misbehaviour::MalformedMsg,
+                };
+                self.comm.report_misbehaviour(&sender, misbehaviour).await;
                 return Ok(cmds);
             }
         };
@@ -123,6 +214,8 @@ impl Node {
                         SystemMsg::AntiEntropyRetry { .. }
                         | SystemMsg::AntiEntropyUpdate { .. }
                         | SystemMsg::AntiEntropyRedirect { .. }
+                        | SystemMsg::ListenerRedirect { .. }
+                        | SystemMsg::Relay(_)
                         | SystemMsg::JoinRequest(_)
                         | SystemMsg::JoinAsRelocatedRequest(_) => {
                             trace!(
@@ -133,7 +226,10 @@ impl Node {
                         _ => match dst_location.section_pk() {
                             None => {}
                             Some(dst_section_pk) => {
-                                let msg_bytes = original_bytes.unwrap_or(wire_msg.serialize()?);
+                                let msg_bytes = match original_bytes {
+                                    Some(bytes) => bytes,
+                                    None => wire_msg.serialize()?,
+                                };
 
                                 if let Some(ae_cmd) = self
                                     .check_for_entropy(
@@ -214,7 +310,7 @@ impl Node {
                 }
 
                 // First we check if it's query and we have too many on the go at the moment...
-                if let ServiceMsg::Query(_) = msg {
+                if let ServiceMsg::Query(_) | ServiceMsg::ConsistentQuery { .. } = msg {
                     // we have a query, check if we have too many on the go....
                     let pending_query_length = self.pending_data_queries.len().await;
 
@@ -234,7 +330,10 @@ impl Node {
                     }
                 };
 
-                let msg_bytes = original_bytes.unwrap_or(wire_msg.serialize()?);
+                let msg_bytes = match original_bytes {
+                    Some(bytes) => bytes,
+                    None => wire_msg.serialize()?,
+                };
                 if let Some(cmd) = self
                     .check_for_entropy(
                         // a cheap clone w/ Bytes
@@ -274,6 +373,7 @@ impl Node {
         known_keys: Vec<BlsPublicKey>,
     ) -> Result<Vec<Cmd>> {
         trace!("{:?}", LogMarker::SystemMsgToBeHandled);
+        self.note_heard_from(&sender.name()).await;
 
         // We assume to be aggregated if it contains a BLS Share sig as authority.
         match self
@@ -319,8 +419,10 @@ impl Node {
                 members,
             } => {
                 trace!("Handling msg: AE-Update from {}: {:?}", sender, msg_id,);
+                let section_auth = section_auth.into_state();
+                self.invalid_saps.check(&section_auth, &sender).await?;
                 self.handle_anti_entropy_update_msg(
-                    section_auth.into_state(),
+                    section_auth,
                     section_signed,
                     proof_chain,
                     members,
@@ -349,6 +451,13 @@ impl Node {
             }
             SystemMsg::JoinAsRelocatedResponse(join_response) => {
                 trace!("Handling msg: JoinAsRelocatedResponse from {}", sender);
+                if let JoinAsRelocatedResponse::Retry(sap)
+                | JoinAsRelocatedResponse::Redirect(sap) = &*join_response
+                {
+                    self.invalid_saps
+                        .check(&sap.clone().into_state(), &sender)
+                        .await?;
+                }
                 if let Some(ref mut joining_as_relocated) = *self.relocate_state.write().await {
                     if let Some(cmd) = joining_as_relocated
                         .handle_join_response(*join_response, sender.addr())
@@ -385,8 +494,10 @@ impl Node {
                 bounced_msg,
             } => {
                 trace!("Handling msg: AE-Retry from {}: {:?}", sender, msg_id,);
+                let section_auth = section_auth.into_state();
+                self.invalid_saps.check(&section_auth, &sender).await?;
                 self.handle_anti_entropy_retry_msg(
-                    section_auth.into_state(),
+                    section_auth,
                     section_signed,
                     proof_chain,
                     bounced_msg,
@@ -401,8 +512,10 @@ impl Node {
                 bounced_msg,
             } => {
                 trace!("Handling msg: AE-Redirect from {}: {:?}", sender, msg_id);
+                let section_auth = section_auth.into_state();
+                self.invalid_saps.check(&section_auth, &sender).await?;
                 self.handle_anti_entropy_redirect_msg(
-                    section_auth.into_state(),
+                    section_auth,
                     section_signed,
                     section_chain,
                     bounced_msg,
@@ -414,6 +527,19 @@ impl Node {
                 trace!("Received Probe message from {}: {:?}", sender, msg_id);
                 Ok(vec![])
             }
+            SystemMsg::ListenerRedirect { addr, bounced_msg } => {
+                trace!(
+                    "Handling msg: Listener-Redirect from {}: {:?}",
+                    sender,
+                    msg_id
+                );
+                self.handle_listener_redirect(sender, addr, bounced_msg)
+                    .await
+            }
+            SystemMsg::Relay(msg) => {
+                trace!("Handling msg: Relay from {}: {:?}", sender, msg_id);
+                self.handle_relay_msg(sender, msg).await
+            }
             #[cfg(feature = "back-pressure")]
             SystemMsg::BackPressure(msgs_per_s) => {
                 trace!(
@@ -482,6 +608,10 @@ impl Node {
                                 new_keypair,
                             })
                             .await;
+                            self.send_membership_event(MembershipEvent::Relocated {
+                                previous_name,
+                                new_name,
+                            });
 
                             trace!("{}", LogMarker::RelocateEnd);
                         } else {
@@ -535,17 +665,10 @@ impl Node {
                 trace!("Handling msg: Propose from {}: {:?}", sender, msg_id);
 
                 // lets convert our message into a usable proposal for core
-                let core_proposal = match proposal {
-                    ProposalMsg::Offline(node_state) => {
-                        CoreProposal::Offline(node_state.into_state())
-                    }
-                    ProposalMsg::SectionInfo { sap, generation } => CoreProposal::SectionInfo {
-                        sap: sap.into_state(),
-                        generation,
-                    },
-                    ProposalMsg::NewElders(sap) => CoreProposal::NewElders(sap.into_authed_state()),
-                    ProposalMsg::JoinsAllowed(allowed) => CoreProposal::JoinsAllowed(allowed),
-                };
+                let core_proposal = CoreProposal::from_msg(proposal);
+                if !self.would_vote_for(&core_proposal, &sender).await? {
+                    return Ok(vec![]);
+                }
 
                 handle_proposal(
                     msg_id,
@@ -557,6 +680,38 @@ impl Node {
                 )
                 .await
             }
+            SystemMsg::ProposeBatch {
+                proposals,
+                sig_share,
+            } => {
+                trace!("Handling msg: ProposeBatch from {}: {:?}", sender, msg_id);
+
+                let batch = ProposalBatch::from_msg(proposals);
+                for proposal in batch.proposals() {
+                    if !proposal.is_batchable() {
+                        warn!(
+                            "Dropped ProposeBatch from {} with a proposal which can't be \
+                            batched: {:?}",
+                            sender, msg_id
+                        );
+                        return Ok(vec![]);
+                    }
+                    // Our share is over the whole batch, so we can't vote for only part of it.
+                    if !self.would_vote_for(proposal, &sender).await? {
+                        return Ok(vec![]);
+                    }
+                }
+
+                handle_proposal_batch(
+                    msg_id,
+                    batch,
+                    sig_share,
+                    sender,
+                    &self.network_knowledge,
+                    &self.proposal_aggregator,
+                )
+                .await
+            }
             SystemMsg::DkgStart(session_id) => {
                 trace!("Handling msg: Dkg-Start {:?} from {}", session_id, sender);
                 let our_name = self.info.read().await.name();
@@ -615,13 +770,205 @@ impl Node {
                     .await
             }
             SystemMsg::NodeCmd(NodeCmd::RecordStorageLevel { node_id, level, .. }) => {
-                let changed = self.set_storage_level(&node_id, level).await;
-                if changed && level.value() == MIN_LEVEL_WHEN_FULL {
-                    // ..then we accept a new node in place of the full node
-                    *self.joins_allowed.write().await = true;
+                // As reported by the adult itself, which may have lowered it by growing its
+                // capacity. It's only recorded once the elders agree on it.
+                if self.is_not_elder().await || sender.name() != XorName::from(node_id) {
+                    return Ok(vec![]);
+                }
+                self.propose(CoreProposal::StorageFull {
+                    node: node_id,
+                    level,
+                })
+                .await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordStorageHealth {
+                node_id, healthy, ..
+            }) => {
+                let _changed = self.set_storage_health(&node_id, healthy).await;
+                Ok(vec![])
+            }
+            SystemMsg::NodeCmd(NodeCmd::PrepareRestart {
+                node_id, within, ..
+            }) => {
+                let adult = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == adult {
+                    self.record_planned_restart(adult, within).await;
+                }
+                Ok(vec![])
+            }
+            SystemMsg::NodeCmd(NodeCmd::Leaving { node_id, .. }) => {
+                let name = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == name {
+                    self.record_leaving(name).await
+                } else {
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeCmd(NodeCmd::SendInventory { slice }) => {
+                if self.is_elder().await {
+                    return Ok(vec![]);
+                }
+                trace!("Sending inventory of {:?} to {:?}", slice, sender);
+                self.send_inventory(slice, sender.name()).await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordInventory {
+                node_id,
+                slice,
+                names,
+            }) => {
+                let adult = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == adult {
+                    self.holder_registry
+                        .record_inventory(adult, slice, names)
+                        .await?;
+                }
+                Ok(vec![])
+            }
+            SystemMsg::NodeCmd(NodeCmd::SendHeld { batch, names }) => {
+                if self.is_elder().await {
+                    return Ok(vec![]);
+                }
+                trace!(
+                    "Sending which of {} names we hold to {:?}",
+                    names.len(),
+                    sender
+                );
+                self.send_held(batch, names, sender.name()).await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordHeld {
+                node_id,
+                batch,
+                held,
+            }) => {
+                let adult = XorName::from(node_id);
+                if sender.name() != adult {
+                    Ok(vec![])
+                } else if self.is_elder().await {
+                    self.record_held(adult, batch, held).await
+                } else {
+                    // As an adult, only asked for data we're handing over after a split.
+                    self.confirm_split_handover(adult, &held).await?;
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeCmd(NodeCmd::SendHoldingProofs {
+                audit,
+                nonce,
+                names,
+            }) => {
+                if self.is_elder().await || !self.network_knowledge.is_elder(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                trace!(
+                    "Proving which of {} chunks we hold to {:?}",
+                    names.len(),
+                    sender
+                );
+                self.send_holding_proofs(audit, nonce, names, sender.name())
+                    .await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordHoldingProofs {
+                node_id,
+                audit,
+                proofs,
+            }) => {
+                let adult = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == adult {
+                    self.record_holding_proofs(adult, audit, proofs).await
+                } else {
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeCmd(NodeCmd::StoreChunkShares(shares)) => {
+                // Only our elders may have us store shares of chunks.
+                if self.is_elder().await || !self.network_knowledge.is_elder(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                self.store_chunk_shares(shares).await
+            }
+            SystemMsg::NodeCmd(NodeCmd::SendChunkShares { chunk }) => {
+                if self.is_elder().await || !self.network_knowledge.is_elder(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                self.send_chunk_shares(chunk, sender.name()).await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordChunkShares {
+                node_id,
+                chunk,
+                shares,
+            }) => {
+                if self.is_elder().await && sender.name() == XorName::from(node_id) {
+                    self.record_chunk_shares(chunk, shares).await
+                } else {
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordSpentProofShare(share)) => {
+                // Only shares of our fellow elders are logged.
+                if self.is_not_elder().await
+                    || !self.network_knowledge.is_elder(&sender.name()).await
+                {
+                    return Ok(vec![]);
+                }
+                self.record_spent_proof_share(share).await?;
+                Ok(vec![])
+            }
+            SystemMsg::NodeCmd(NodeCmd::ProbeLiveness { probe, nonce, name }) => {
+                if self.is_elder().await || !self.network_knowledge.is_elder(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                self.answer_liveness_probe(probe, nonce, name, sender.name())
+                    .await
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordLiveness {
+                node_id,
+                probe,
+                proof,
+            }) => {
+                let adult = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == adult {
+                    self.record_liveness(adult, probe, proof).await
+                } else {
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeEvent(NodeEvent::ReplicasStored { node_id, replicas }) => {
+                if self.is_elder().await && sender.name() == XorName::from(node_id) {
+                    self.record_replicas_stored(node_id, replicas).await
+                } else {
+                    Ok(vec![])
+                }
+            }
+            SystemMsg::NodeCmd(NodeCmd::RecordSplitHandover {
+                node_id,
+                sibling,
+                handed_over,
+                failed,
+                ..
+            }) => {
+                let adult = XorName::from(node_id);
+                if self.is_elder().await && sender.name() == adult {
+                    info!(
+                        "{:?} handed its data over to our sibling section {:?}: {} copies confirmed, {} given up on",
+                        adult, sibling, handed_over, failed
+                    );
+                }
+                Ok(vec![])
+            }
+            SystemMsg::NodeCmd(NodeCmd::RequestChunkReplication { node_id, names, .. }) => {
+                let adult = XorName::from(node_id);
+                if sender.name() == adult {
+                    self.replicate_lost_chunks(adult, names).await?;
                 }
                 Ok(vec![])
             }
+            SystemMsg::NodeCmd(NodeCmd::ReplicateDataTo { addresses, holder }) => {
+                // Only our elders may have us push data around.
+                if self.is_elder().await || !self.network_knowledge.is_elder(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                self.replicate_data_to(addresses, holder).await
+            }
             SystemMsg::NodeCmd(NodeCmd::ReceiveMetadata { metadata }) => {
                 info!("Processing received MetadataExchange packet: {:?}", msg_id);
                 self.set_adult_levels(metadata).await;
@@ -638,16 +985,18 @@ impl Node {
                 );
 
                 if self.is_elder().await {
-                    if full {
-                        let changed = self
-                            .set_storage_level(&node_id, StorageLevel::from(StorageLevel::MAX)?)
-                            .await;
-                        if changed {
-                            // ..then we accept a new node in place of the full node
-                            *self.joins_allowed.write().await = true;
-                        }
+                    let mut cmds = vec![];
+                    if full && sender.name() == XorName::from(node_id) {
+                        cmds.extend(
+                            self.propose(CoreProposal::StorageFull {
+                                node: node_id,
+                                level: StorageLevel::from(StorageLevel::MAX)?,
+                            })
+                            .await?,
+                        );
                     }
-                    self.replicate_data(data).await
+                    cmds.extend(self.replicate_data(data).await?);
+                    Ok(cmds)
                 } else {
                     error!("Received unexpected message while Adult");
                     Ok(vec![])
@@ -667,43 +1016,17 @@ impl Node {
                     error!("Received unexpected message while Elder");
                     Ok(vec![])
                 } else {
-                    let mut cmds = vec![];
-
-                    for data in data_collection {
-                        // We are an adult here, so just store away!
-                        // This may return a DatabaseFull error... but we should have reported storage increase
-                        // well before this
-                        match self.data_storage.store(&data).await {
-                            Ok(level_report) => {
-                                info!("Storage level report: {:?}", level_report);
-                                cmds.extend(self.record_storage_level_if_any(level_report).await);
-                            }
-                            Err(error) => {
-                                match error {
-                                    DbError::NotEnoughSpace => {
-                                        // db full
-                                        error!("Not enough space to store more data");
-
-                                        let node_id =
-                                            PublicKey::from(self.info.read().await.keypair.public);
-                                        let msg =
-                                            SystemMsg::NodeEvent(NodeEvent::CouldNotStoreData {
-                                                node_id,
-                                                data,
-                                                full: true,
-                                            });
-
-                                        cmds.push(self.send_msg_to_our_elders(msg).await?)
-                                    }
-                                    _ => {
-                                        error!("Problem storing data, but it was ignored: {error}");
-                                    } // the rest seem to be non-problematic errors.. (?)
-                                }
-                            }
-                        }
-                    }
-
-                    Ok(cmds)
+                    self.store_replicas(data_collection).await
+                };
+            }
+            SystemMsg::NodeCmd(NodeCmd::ReplicateCheckedData(data_collection)) => {
+                info!("ReplicateCheckedData MsgId: {:?}", msg_id);
+                return if self.is_elder().await {
+                    error!("Received unexpected message while Elder");
+                    Ok(vec![])
+                } else {
+                    self.store_checked_replicas(sender.name(), data_collection)
+                        .await
                 };
             }
             SystemMsg::NodeCmd(NodeCmd::SendReplicateDataAddress(data_addresses)) => {
@@ -773,49 +1096,12 @@ impl Node {
                 };
             }
             SystemMsg::NodeCmd(NodeCmd::FetchReplicateData(data_addresses)) => {
-                let mut cmds = vec![];
                 info!("FetchReplicateData MsgId: {:?}", msg_id);
                 return if self.is_elder().await {
                     error!("Received unexpected message while Elder");
                     Ok(vec![])
                 } else {
-                    // Chunk the full list into REPLICATION_BATCH_SIZE addresses a batch
-                    let mut addresses = vec![];
-                    for chunked_data_address in
-                        &data_addresses.into_iter().chunks(REPLICATION_BATCH_SIZE)
-                    {
-                        let data_address_collection = chunked_data_address.collect_vec();
-                        addresses.push(data_address_collection);
-                    }
-
-                    // Process each batch
-                    for chunked_addresses in addresses {
-                        let mut data_collection = vec![];
-                        for data_address in chunked_addresses {
-                            match self.data_storage.get_for_replication(data_address).await {
-                                Ok(data) => {
-                                    info!("Providing {data_address:?} for replication");
-
-                                    data_collection.push(data);
-                                }
-                                Err(e) => {
-                                    warn!("Error providing data for replication: {e}");
-                                    return Ok(vec![]);
-                                }
-                            }
-                        }
-
-                        cmds.push(Cmd::SignOutgoingSystemMsg {
-                            msg: SystemMsg::NodeCmd(NodeCmd::ReplicateData(data_collection)),
-                            dst: sn_interface::messaging::DstLocation::Node {
-                                name: sender.name(),
-                                section_pk: self.section_key_by_name(&sender.name()).await,
-                            },
-                        });
-                    }
-
-                    // Provide the requested data
-                    Ok(cmds)
+                    self.provide_replicas(sender.name(), data_addresses).await
                 };
             }
             SystemMsg::NodeCmd(node_cmd) => {
@@ -974,10 +1260,36 @@ impl Node {
         }
     }
 
-    async fn record_storage_level_if_any(&self, level: Option<StorageLevel>) -> Vec<Cmd> {
+    // Whether we'd sign the `proposal` from `sender` too, for it to aggregate.
+    async fn would_vote_for(&self, proposal: &CoreProposal, sender: &Peer) -> Result<bool> {
+        match proposal {
+            CoreProposal::SectionInfo { sap, .. } => self.invalid_saps.check(sap, sender).await?,
+            CoreProposal::NewElders(signed_sap) => {
+                self.invalid_saps.check(&signed_sap.value, sender).await?
+            }
+            CoreProposal::Offline(node_state) => {
+                if node_state.state() == MembershipState::Left
+                    && self.planned_restarts.is_pending(&node_state.name()).await
+                {
+                    debug!(
+                        "Not voting {} offline, it's within its restart window",
+                        node_state.name()
+                    );
+                    return Ok(false);
+                }
+            }
+            CoreProposal::JoinsAllowed(_) | CoreProposal::StorageFull { .. } => {}
+        }
+        Ok(true)
+    }
+
+    pub(crate) async fn record_storage_level_if_any(
+        &self,
+        level: Option<StorageLevel>,
+    ) -> Vec<Cmd> {
         let mut cmds = vec![];
         if let Some(level) = level {
-            info!("Storage has now passed {} % used.", 10 * level.value());
+            info!("Storage is now at {} % used.", 10 * level.value());
             let node_id = PublicKey::from(self.info.read().await.keypair.public);
             let node_xorname = XorName::from(node_id);
 
@@ -6,7 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::{api::cmds::Cmd, core::Proposal, dkg::SigShare, Result};
+use crate::node::{
+    api::cmds::Cmd,
+    core::{Proposal, ProposalBatch},
+    dkg::SigShare,
+    Result,
+};
 use sn_interface::messaging::{
     signature_aggregator::{Error as AggregatorError, SignatureAggregator},
     MsgId,
@@ -23,8 +28,6 @@ pub(crate) async fn handle_proposal(
     network_knowledge: &NetworkKnowledge,
     proposal_aggregator: &SignatureAggregator,
 ) -> Result<Vec<Cmd>> {
-    let sig_share_pk = &sig_share.public_key_set.public_key();
-
     // Any other proposal than SectionInfo needs to be signed by a known section key.
     if let Proposal::SectionInfo { sap, .. } = &proposal {
         let section_auth = sap;
@@ -47,28 +50,9 @@ pub(crate) async fn handle_proposal(
                 return Ok(vec![]);
             }
         }
-    } else {
-        // Proposal from other section shall be ignored.
-        // TODO: check this is for our prefix , or a child prefix, otherwise just drop it
-        if !network_knowledge.prefix().await.matches(&sender.name()) {
-            trace!(
-                "Ignore proposal {:?} from other section, src {}: {:?}",
-                proposal,
-                sender,
-                msg_id
-            );
-            return Ok(vec![]);
-        }
-
-        // Let's now verify the section key in the msg authority is trusted
-        // based on our current knowledge of the network and sections chains.
-        if !network_knowledge.has_chain_key(sig_share_pk).await {
-            warn!(
-                "Dropped Propose msg ({:?}) with untrusted sig share from {}: {:?}",
-                msg_id, sender, proposal
-            );
-            return Ok(vec![]);
-        }
+    } else if !is_from_our_section(msg_id, &sig_share, &sender, network_knowledge).await {
+        trace!("Ignored proposal {:?} from {}", proposal, sender);
+        return Ok(vec![]);
     }
 
     let mut cmds = vec![];
@@ -108,3 +92,90 @@ pub(crate) async fn handle_proposal(
 
     Ok(cmds)
 }
+
+// Insert the batch of proposals into the proposal aggregator and handle each of them if the
+// batch aggregated.
+pub(crate) async fn handle_proposal_batch(
+    msg_id: MsgId,
+    batch: ProposalBatch,
+    sig_share: SigShare,
+    sender: Peer,
+    network_knowledge: &NetworkKnowledge,
+    proposal_aggregator: &SignatureAggregator,
+) -> Result<Vec<Cmd>> {
+    if !is_from_our_section(msg_id, &sig_share, &sender, network_knowledge).await {
+        trace!("Ignored proposal batch {:?} from {}", batch, sender);
+        return Ok(vec![]);
+    }
+
+    let serialised_batch = match batch.as_signable_bytes() {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            error!(
+                "Failed to serialise proposal batch from {}, {:?}: {:?}",
+                sender, msg_id, error
+            );
+            return Ok(vec![]);
+        }
+    };
+
+    match proposal_aggregator.add(&serialised_batch, sig_share).await {
+        Ok(sig) => Ok(batch
+            .into_proposals()
+            .into_iter()
+            .map(|proposal| Cmd::HandleAgreement {
+                proposal,
+                sig: sig.clone(),
+            })
+            .collect()),
+        Err(AggregatorError::NotEnoughShares) => {
+            trace!(
+                "Proposal batch from {} inserted in aggregator, not enough sig shares yet: {:?}",
+                sender,
+                msg_id
+            );
+            Ok(vec![])
+        }
+        Err(error) => {
+            error!(
+                "Failed to add proposal batch from {}, {:?}: {:?}",
+                sender, msg_id, error
+            );
+            Ok(vec![])
+        }
+    }
+}
+
+// Whether the proposal is from our section and signed with a key share of a section key we
+// trust. Proposals from other sections shall be ignored.
+async fn is_from_our_section(
+    msg_id: MsgId,
+    sig_share: &SigShare,
+    sender: &Peer,
+    network_knowledge: &NetworkKnowledge,
+) -> bool {
+    // TODO: check this is for our prefix , or a child prefix, otherwise just drop it
+    if !network_knowledge.prefix().await.matches(&sender.name()) {
+        trace!(
+            "Ignore proposal from other section, src {}: {:?}",
+            sender,
+            msg_id
+        );
+        return false;
+    }
+
+    // Let's now verify the section key in the msg authority is trusted
+    // based on our current knowledge of the network and sections chains.
+    if !network_knowledge
+        .has_chain_key(&sig_share.public_key_set.public_key())
+        .await
+    {
+        warn!(
+            "Dropped proposal msg ({:?}) with untrusted sig share from {}",
+            msg_id, sender
+        );
+        return false;
+    }
+
+    true
+}
@@ -15,7 +15,6 @@ use crate::node::{
     },
     Event, Result,
 };
-use sn_interface::elder_count;
 use sn_interface::messaging::system::{
     MembershipState, NodeState as NodeStateMsg, RelocateDetails, SectionAuth,
 };
@@ -39,17 +38,12 @@ impl Node {
         }
 
         // Do not carry out relocation when there is not enough elder nodes.
-        if self
-            .network_knowledge
-            .authority_provider()
-            .await
-            .elder_count()
-            < elder_count()
-        {
+        let sap = self.network_knowledge.authority_provider().await;
+        if sap.elder_count() < sap.network_params().elder_count() {
             return Ok(vec![]);
         }
 
-        let mut cmds = vec![];
+        let mut proposals = vec![];
         for (node_state, relocate_details) in
             find_nodes_to_relocate(&self.network_knowledge, &churn_id, excluded).await
         {
@@ -60,13 +54,10 @@ impl Node {
                 churn_id
             );
 
-            cmds.extend(
-                self.propose(Proposal::Offline(node_state.relocate(relocate_details)))
-                    .await?,
-            );
+            proposals.push(Proposal::Offline(node_state.relocate(relocate_details)));
         }
 
-        Ok(cmds)
+        self.propose_all(proposals).await
     }
 
     pub(crate) async fn relocate_rejoining_peer(
@@ -75,8 +66,14 @@ impl Node {
         age: u8,
     ) -> Result<Vec<Cmd>> {
         let peer = node_state.peer();
-        let relocate_details =
-            RelocateDetails::with_age(&self.network_knowledge, peer, peer.name(), age);
+        let relocate_details = RelocateDetails::with_age(
+            &self.network_knowledge,
+            peer,
+            peer.name(),
+            age,
+            self.network_knowledge.chain_len().await,
+            None,
+        );
 
         trace!(
             "Relocating {:?} to {} with age {} due to rejoin",
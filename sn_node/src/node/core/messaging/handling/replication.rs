@@ -0,0 +1,209 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::REPLICATION_BATCH_SIZE;
+use crate::dbs::Error as DbError;
+use crate::node::{
+    api::cmds::Cmd,
+    core::{
+        data::{replica_id, replicate_checked, Refetch, TransferChecks},
+        Node,
+    },
+    Event, Result,
+};
+use sn_interface::messaging::{
+    system::{NodeCmd, NodeEvent, SystemMsg},
+    DstLocation,
+};
+use sn_interface::types::{PublicKey, ReplicatedData, ReplicatedDataAddress};
+
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+impl Node {
+    /// On adults, stores the replicas of data sent to us, having our elders store what we can't
+    /// elsewhere, and telling them of those stored.
+    pub(crate) async fn store_replicas(&self, data: Vec<ReplicatedData>) -> Result<Vec<Cmd>> {
+        let mut cmds = vec![];
+        let mut stored = BTreeSet::new();
+
+        for data in data {
+            // We are an adult here, so just store away!
+            // This may return a DatabaseFull error... but we should have reported storage increase
+            // well before this
+            match self.data_storage.store(&data).await {
+                Ok(level_report) => {
+                    stored.extend(replica_id(&data));
+                    info!("Storage level report: {:?}", level_report);
+                    cmds.extend(self.record_storage_level_if_any(level_report).await);
+                    if let Some(chunk_headroom) = self.data_storage.newly_approaching_db_reserve() {
+                        warn!(
+                            "Chunks are within {} bytes of the space reserved for \
+                            our dbs, new chunks will be refused beyond that",
+                            chunk_headroom
+                        );
+                        self.send_event(Event::DbReserveApproached { chunk_headroom })
+                            .await;
+                    }
+                }
+                Err(error) => {
+                    match error {
+                        DbError::NotEnoughSpace => {
+                            // db full
+                            error!("Not enough space to store more data");
+
+                            let node_id = PublicKey::from(self.info.read().await.keypair.public);
+                            let msg = SystemMsg::NodeEvent(NodeEvent::CouldNotStoreData {
+                                node_id,
+                                data,
+                                full: true,
+                            });
+
+                            cmds.push(self.send_msg_to_our_elders(msg).await?)
+                        }
+                        DbError::StorageUnhealthy => {
+                            // our disk is failing, have the elders store it elsewhere
+                            warn!("Not storing new data while storage is unhealthy");
+
+                            let node_id = PublicKey::from(self.info.read().await.keypair.public);
+                            let msg = SystemMsg::NodeEvent(NodeEvent::CouldNotStoreData {
+                                node_id,
+                                data,
+                                full: false,
+                            });
+
+                            cmds.push(self.send_msg_to_our_elders(msg).await?)
+                        }
+                        _ => {
+                            error!("Problem storing data, but it was ignored: {error}");
+                        } // the rest seem to be non-problematic errors.. (?)
+                    }
+                }
+            }
+        }
+
+        if !stored.is_empty() {
+            let node_id = PublicKey::from(self.info.read().await.keypair.public);
+            let msg = SystemMsg::NodeEvent(NodeEvent::ReplicasStored {
+                node_id,
+                replicas: stored,
+            });
+            cmds.push(self.send_msg_to_our_elders(msg).await?);
+        }
+
+        Ok(cmds)
+    }
+
+    /// On adults, stores the replicas `sender` sent us which arrived intact, and sources those
+    /// which arrived corrupted again.
+    pub(crate) async fn store_checked_replicas(
+        &self,
+        sender: XorName,
+        data: Vec<(ReplicatedDataAddress, ReplicatedData)>,
+    ) -> Result<Vec<Cmd>> {
+        let mut intact = vec![];
+        let mut cmds = vec![];
+        for (address, data) in data {
+            if TransferChecks::verify(&address, &data) {
+                self.transfer_checks.arrived(&address).await;
+                intact.push(data);
+            } else {
+                cmds.extend(self.refetch_corrupted(address, sender).await?);
+            }
+        }
+        cmds.extend(self.store_replicas(intact).await?);
+        Ok(cmds)
+    }
+
+    // Fetches the data at `address` again, after `sender`'s copy arrived corrupted: from the same
+    // sender the first time, then from the closest other adult.
+    async fn refetch_corrupted(
+        &self,
+        address: ReplicatedDataAddress,
+        sender: XorName,
+    ) -> Result<Vec<Cmd>> {
+        let source = match self.transfer_checks.corrupted(address, sender).await {
+            // Elders don't hold data, so there's no retrying from them.
+            Refetch::Retry if !self.network_knowledge.is_elder(&sender).await => Some(sender),
+            Refetch::Retry => {
+                self.alternative_holder(&address, BTreeSet::from([sender]))
+                    .await
+            }
+            Refetch::Elsewhere { tried } => self.alternative_holder(&address, tried).await,
+            Refetch::GiveUp => None,
+        };
+
+        let source = match source {
+            Some(source) => source,
+            None => {
+                error!("Giving up on fetching an intact copy of {:?}", address);
+                return Ok(vec![]);
+            }
+        };
+        info!("Fetching {:?} again from {}", address, source);
+        Ok(vec![Cmd::SignOutgoingSystemMsg {
+            msg: SystemMsg::NodeCmd(NodeCmd::FetchReplicateData(vec![address])),
+            dst: DstLocation::Node {
+                name: source,
+                section_pk: self.section_key_by_name(&source).await,
+            },
+        }])
+    }
+
+    // The adult closest to the data at `address`, other than us and those `excluded`.
+    async fn alternative_holder(
+        &self,
+        address: &ReplicatedDataAddress,
+        mut excluded: BTreeSet<XorName>,
+    ) -> Option<XorName> {
+        let _inserted = excluded.insert(self.info.read().await.name());
+        self.adult_index()
+            .await
+            .closest(address.name())
+            .find(|adult| !excluded.contains(adult))
+            .copied()
+    }
+
+    /// On adults, sends the data we hold at `addresses` to the adult asking for it, in batches.
+    pub(crate) async fn provide_replicas(
+        &self,
+        requester: XorName,
+        addresses: Vec<ReplicatedDataAddress>,
+    ) -> Result<Vec<Cmd>> {
+        let mut cmds = vec![];
+
+        // Process each batch of REPLICATION_BATCH_SIZE addresses
+        for chunked_addresses in addresses.chunks(REPLICATION_BATCH_SIZE) {
+            let mut data_collection = vec![];
+            for &data_address in chunked_addresses {
+                match self.data_storage.get_for_replication(data_address).await {
+                    Ok(data) => {
+                        info!("Providing {data_address:?} for replication");
+
+                        data_collection.push(data);
+                    }
+                    Err(e) => {
+                        warn!("Error providing data for replication: {e}");
+                        return Ok(vec![]);
+                    }
+                }
+            }
+
+            cmds.push(Cmd::SignOutgoingSystemMsg {
+                msg: replicate_checked(data_collection),
+                dst: DstLocation::Node {
+                    name: requester,
+                    section_pk: self.section_key_by_name(&requester).await,
+                },
+            });
+        }
+
+        // Provide the requested data
+        Ok(cmds)
+    }
+}
@@ -8,13 +8,17 @@
 
 use crate::node::{
     api::cmds::Cmd,
-    core::{Node, RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY},
+    core::{
+        join_pressure::{validate_memory_proof, ChallengeParams},
+        Node,
+    },
     Error, Result,
 };
 use sn_interface::messaging::system::{JoinResponse, ResourceProofResponse, SystemMsg};
 use sn_interface::types::{keys::ed25519, log_markers::LogMarker, Peer};
 
 use ed25519_dalek::Verifier;
+use resource_proof::ResourceProof;
 use xor_name::XorName;
 
 // Resource signed
@@ -24,7 +28,13 @@ impl Node {
         peer_name: &XorName,
         response: ResourceProofResponse,
     ) -> bool {
-        let serialized = if let Ok(serialized) = bincode::serialize(&(peer_name, &response.nonce)) {
+        let params = ChallengeParams {
+            data_size: response.data.len(),
+            difficulty: response.difficulty,
+            memory_kib: response.memory_kib,
+            memory_difficulty: response.memory_difficulty,
+        };
+        let serialized = if let Ok(serialized) = params.signed_payload(peer_name, &response.nonce) {
             serialized
         } else {
             return false;
@@ -41,18 +51,34 @@ impl Node {
         {
             return false;
         }
+        // Signed by us, but never too hard for us to check.
+        if !params.is_within_bounds() {
+            return false;
+        }
 
-        self.resource_proof
-            .validate_all(&response.nonce, &response.data, response.solution)
+        ResourceProof::new(params.data_size, params.difficulty).validate_all(
+            &response.nonce,
+            &response.data,
+            response.solution,
+        ) && validate_memory_proof(
+            &response.nonce,
+            params.memory_kib,
+            params.memory_difficulty,
+            response.memory_solution,
+        )
     }
 
     pub(crate) async fn send_resource_proof_challenge(&self, peer: Peer) -> Result<Cmd> {
         let nonce: [u8; 32] = rand::random();
-        let serialized =
-            bincode::serialize(&(peer.name(), &nonce)).map_err(|_| Error::InvalidMessage)?;
+        let params = self.join_pressure.issue();
+        let serialized = params
+            .signed_payload(&peer.name(), &nonce)
+            .map_err(|_| Error::InvalidMessage)?;
         let response = SystemMsg::JoinResponse(Box::new(JoinResponse::ResourceChallenge {
-            data_size: RESOURCE_PROOF_DATA_SIZE,
-            difficulty: RESOURCE_PROOF_DIFFICULTY,
+            data_size: params.data_size,
+            difficulty: params.difficulty,
+            memory_kib: params.memory_kib,
+            memory_difficulty: params.memory_difficulty,
             nonce,
             nonce_signature: ed25519::sign(&serialized, &self.info.read().await.keypair),
         }));
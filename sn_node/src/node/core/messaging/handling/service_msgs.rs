@@ -6,19 +6,34 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::{api::cmds::Cmd, core::Node, Result};
+use crate::node::{
+    api::cmds::Cmd,
+    core::{data::replica_id, Node},
+    messages::WireMsgUtils,
+    Result,
+};
 use sn_interface::data_copy_count;
 use sn_interface::messaging::{
-    data::{CmdError, DataCmd, DataQuery, Error as ErrorMsg, ServiceMsg},
+    data::{
+        validate_batch, validate_cmd, validate_query, validate_spentbook_msg, BatchStatus,
+        CmdError, DataCmd, DataQuery, Error as ErrorMsg, QueryResponse, RegisterCmd, ServiceMsg,
+        SignedRegisterEdit,
+    },
     system::{NodeQueryResponse, SystemMsg},
     AuthorityProof, DstLocation, EndUser, MsgId, ServiceAuth, WireMsg,
 };
 // use crate::node::{api::cmds::Cmd, core::Node, Result};
 // use sn_interface::types::{log_markers::LogMarker, register::User, Peer, PublicKey, ReplicatedData};
 use sn_interface::types::{
-    log_markers::LogMarker, register::User, Peer, PublicKey, ReplicatedData,
+    convert_dt_error_to_error_msg,
+    log_markers::LogMarker,
+    register::{check_entry_size, User},
+    Peer, PublicKey, ReplicatedData, ReplicatedDataAddress,
 };
 
+use dashmap::DashSet;
+use std::collections::BTreeSet;
+use tokio::time::Instant;
 use xor_name::XorName;
 
 impl Node {
@@ -46,14 +61,16 @@ impl Node {
             user,
         };
 
-        // Setup node authority on this response and send this back to our elders
+        // Setup node authority on this response and send this back to our elders. Signed here
+        // rather than as a cmd of its own, for the trace of the query, if any, to go along.
         let section_pk = self.network_knowledge().section_key().await;
         let dst = DstLocation::Node {
             name: requesting_elder,
             section_pk,
         };
+        let wire_msg = WireMsg::single_src(&*self.info.read().await, dst, msg, section_pk)?;
 
-        cmds.push(Cmd::SignOutgoingSystemMsg { msg, dst });
+        cmds.extend(self.send_msg_to_nodes(wire_msg).await?);
 
         Ok(cmds)
     }
@@ -69,7 +86,6 @@ impl Node {
         user: EndUser,
         sending_node_pk: PublicKey,
     ) -> Result<Vec<Cmd>> {
-        let msg_id = MsgId::new();
         let mut cmds = vec![];
         debug!(
             "Handling data read @ elders, received from {:?} ",
@@ -125,11 +141,49 @@ impl Node {
                 .set(op_id, waiting_peers.clone(), None)
                 .await;
             trace!("Node {:?}, reported data not found ", sending_node_pk);
+            // ...and have the next holder queried right away rather than once the hedge delay
+            // has passed.
+            if let Some(hedge) = self
+                .hedged_reads
+                .not_found(op_id, node_id, Instant::now())
+                .await
+            {
+                cmds.extend(self.send_hedge(op_id, hedge).await?);
+            }
             return Ok(cmds);
         }
 
+        // The other holders queried won't be waited for anymore, nor held to it.
+        for holder in self
+            .hedged_reads
+            .answered(op_id, node_id, Instant::now())
+            .await
+        {
+            let _cancelled = self
+                .dysfunction_tracking
+                .request_operation_fulfilled(&holder, op_id)
+                .await;
+        }
+
+        if let QueryResponse::GetChunk(Ok(chunk)) = &query_response {
+            self.data_cache.insert(chunk.clone()).await;
+        }
+
+        self.respond_to_waiting_peers(&waiting_peers, query_response, correlation_id)
+            .await
+    }
+
+    /// Responds to the clients waiting for a query with the first response to it.
+    pub(crate) async fn respond_to_waiting_peers(
+        &self,
+        waiting_peers: &DashSet<Peer>,
+        response: QueryResponse,
+        correlation_id: MsgId,
+    ) -> Result<Vec<Cmd>> {
+        let msg_id = MsgId::new();
+        let mut cmds = vec![];
         let msg = ServiceMsg::QueryResponse {
-            response: query_response,
+            response,
             correlation_id,
         };
         let (msg_kind, payload) = self.ed_sign_client_msg(&msg).await?;
@@ -149,11 +203,13 @@ impl Node {
         Ok(cmds)
     }
 
-    /// Handle ServiceMsgs received from EndUser
+    /// Handle ServiceMsgs received from EndUser, of which `addresses` are the canonical
+    /// addresses of the data they're on, as validated.
     pub(crate) async fn handle_service_msg_received(
         &self,
         msg_id: MsgId,
         msg: ServiceMsg,
+        addresses: Vec<ReplicatedDataAddress>,
         auth: AuthorityProof<ServiceAuth>,
         origin: Peer,
     ) -> Result<Vec<Cmd>> {
@@ -162,16 +218,43 @@ impl Node {
             return Ok(vec![]);
         }
         // extract the data from the request
-        let data = match msg {
+        let (data, address) = match (msg, addresses.as_slice()) {
             // These reads/writes are for adult nodes...
-            ServiceMsg::Cmd(DataCmd::Register(cmd)) => ReplicatedData::RegisterWrite(cmd),
-            ServiceMsg::Cmd(DataCmd::StoreChunk(chunk)) => ReplicatedData::Chunk(chunk),
-            ServiceMsg::Query(query) => {
+            (ServiceMsg::Cmd(DataCmd::Register(cmd)), [address]) => {
+                (ReplicatedData::RegisterWrite(cmd), *address)
+            }
+            (ServiceMsg::Cmd(DataCmd::StoreChunk(chunk)), [address]) => {
+                (ReplicatedData::Chunk(chunk), *address)
+            }
+            (ServiceMsg::Query(query), [address]) => {
+                return self
+                    .read_data_from_adults(query, *address, msg_id, auth, origin)
+                    .await
+            }
+            // ...while the spentbook is kept by elders.
+            (ServiceMsg::SpentbookCmd(cmd), _) => {
+                return self.handle_spentbook_cmd(cmd, msg_id, origin).await
+            }
+            (ServiceMsg::SpentbookQuery(query), _) => {
+                return self.handle_spentbook_query(query, msg_id, origin).await
+            }
+            (ServiceMsg::ConsistentQuery { query, tokens }, [address]) => {
+                return self
+                    .handle_consistent_query(query, *address, tokens, msg_id, auth, origin)
+                    .await
+            }
+            (ServiceMsg::BatchCmd(cmds), _) => {
                 return self
-                    .read_data_from_adults(query, msg_id, auth, origin)
+                    .handle_batch_cmd(cmds, addresses.clone(), msg_id, auth, origin)
                     .await
             }
-            _ => {
+            (ServiceMsg::BatchQuery(query), _) => {
+                return self.handle_batch_query(query, msg_id, auth, origin).await
+            }
+            (ServiceMsg::HoldersQuery(query), _) => {
+                return self.handle_holders_query(query, msg_id, origin).await
+            }
+            (msg, _) => {
                 warn!(
                     "!!!! Unexpected ServiceMsg received, and it was not handled: {:?}",
                     msg
@@ -179,22 +262,52 @@ impl Node {
                 return Ok(vec![]);
             }
         };
-        // build the replication cmds
-        let mut cmds = self.replicate_data(data).await?;
+        let replica = replica_id(&data);
+        let (mut cmds, holders) = self.store_data(data, &address).await?;
         // make sure the expected replication factor is achieved
         if data_copy_count() > cmds.len() {
             error!("InsufficientAdults for storing data reliably");
-            let error = CmdError::Data(ErrorMsg::InsufficientAdults {
-                prefix: self.network_knowledge().prefix().await,
-                expected: data_copy_count() as u8,
-                found: cmds.len() as u8,
-            });
+            let error = CmdError::Data(
+                ErrorMsg::InsufficientAdults {
+                    prefix: self.network_knowledge().prefix().await,
+                    expected: data_copy_count() as u8,
+                    found: cmds.len() as u8,
+                }
+                .into(),
+            );
             return self.send_cmd_error_response(error, origin, msg_id).await;
         }
-        cmds.extend(self.send_cmd_ack(origin, msg_id).await?);
+        // Only the cmds applied are remembered, those lacking adults being fine to retry.
+        let _prev = self
+            .cmd_outcomes
+            .set((msg_id, auth.public_key), None, None)
+            .await;
+        let token = self.record_write(replica, holders).await;
+        cmds.extend(self.send_cmd_ack_with_token(origin, msg_id, token).await?);
         Ok(cmds)
     }
 
+    // Builds the cmds sending `data` to its holders, chunks large enough being erasure coded
+    // instead of replicated, along with the holders to confirm storing it. Holders of shares of
+    // chunks don't confirm storing them.
+    pub(super) async fn store_data(
+        &self,
+        data: ReplicatedData,
+        address: &ReplicatedDataAddress,
+    ) -> Result<(Vec<Cmd>, BTreeSet<XorName>)> {
+        let coded = match &data {
+            ReplicatedData::Chunk(chunk) => self.store_erasure_coded(chunk).await?,
+            _ => None,
+        };
+        match coded {
+            Some(cmds) => Ok((cmds, BTreeSet::new())),
+            None => {
+                let holders = self.get_adults_who_should_store_data(*address.name()).await;
+                Ok((self.replicate_data(data).await?, holders))
+            }
+        }
+    }
+
     /// Handle incoming data msgs.
     pub(crate) async fn handle_service_msg(
         &self,
@@ -213,7 +326,82 @@ impl Node {
             return Ok(vec![]);
         }
 
-        self.handle_service_msg_received(msg_id, msg, auth, user)
+        // A cmd sent again, by a client which timed out on our response to it, is answered as
+        // it was the first time rather than applied twice.
+        let cmd_key = matches!(msg, ServiceMsg::Cmd(_)).then(|| (msg_id, auth.public_key));
+        if let Some(key) = cmd_key {
+            if let Some(outcome) = self.cmd_outcomes.get(&key).await {
+                debug!("Replaying the outcome of cmd {:?} received again", msg_id);
+                return match outcome {
+                    None => self.send_cmd_ack(user, msg_id).await,
+                    Some(error) => self.send_cmd_error_response(error, user, msg_id).await,
+                };
+            }
+        }
+
+        // Nothing is to be keyed on a name or address before it's been validated, and only on
+        // the canonical addresses validation returns from then on.
+        let validation = match &msg {
+            ServiceMsg::Cmd(cmd) => validate_cmd(cmd, &dst_location.name())
+                .and_then(|address| check_register_entry(cmd).map(|()| vec![address])),
+            ServiceMsg::Query(query) | ServiceMsg::ConsistentQuery { query, .. } => {
+                validate_query(query, &dst_location.name()).map(|address| vec![address])
+            }
+            ServiceMsg::SpentbookCmd(cmd) => {
+                validate_spentbook_msg(cmd.key_image(), &dst_location.name()).map(|()| vec![])
+            }
+            ServiceMsg::SpentbookQuery(query) => {
+                validate_spentbook_msg(query.key_image(), &dst_location.name()).map(|()| vec![])
+            }
+            ServiceMsg::BatchCmd(cmds) => validate_batch(cmds, &dst_location.name())
+                .and_then(|addresses| check_batch_register_entries(cmds).map(|()| addresses)),
+            _ => Ok(vec![]),
+        };
+        let addresses = match validation {
+            Ok(addresses) => addresses,
+            Err(error) => {
+                warn!("Rejecting invalid service msg {:?}: {}", msg_id, error);
+                if let (Some(key), ServiceMsg::Cmd(cmd)) = (cmd_key, &msg) {
+                    let _prev = self
+                        .cmd_outcomes
+                        .set(key, Some(cmd.error(error.clone())), None)
+                        .await;
+                }
+                if let ServiceMsg::BatchCmd(_) = &msg {
+                    let _prev = self
+                        .batches
+                        .set(
+                            (msg_id, auth.public_key),
+                            BatchStatus::Aborted(error.clone()),
+                            None,
+                        )
+                        .await;
+                }
+                return self.send_error_response(&msg, error, user, msg_id).await;
+            }
+        };
+
+        self.handle_service_msg_received(msg_id, msg, addresses, auth, user)
             .await
     }
 }
+
+// Register entries too big are refused here, rather than routed to the adults to be refused by
+// each of them.
+fn check_register_entry(cmd: &DataCmd) -> std::result::Result<(), ErrorMsg> {
+    match cmd {
+        DataCmd::Register(RegisterCmd::Edit(SignedRegisterEdit { op, .. })) => {
+            check_entry_size(&op.edit.crdt_op.value).map_err(convert_dt_error_to_error_msg)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_batch_register_entries(cmds: &[DataCmd]) -> std::result::Result<(), ErrorMsg> {
+    cmds.iter().enumerate().try_for_each(|(index, cmd)| {
+        check_register_entry(cmd).map_err(|error| ErrorMsg::BatchAborted {
+            index: index as u16,
+            error: Box::new(error),
+        })
+    })
+}
@@ -21,6 +21,9 @@ impl Node {
         &self,
         sap_candidates: SapCandidate,
     ) -> Result<Vec<Cmd>> {
+        if self.sits_out("handover").await {
+            return Ok(vec![]);
+        }
         let mut wlock = self.handover_voting.write().await;
         match &*wlock {
             Some(handover_voting_state) => {
@@ -8,7 +8,7 @@
 
 use crate::node::{
     api::cmds::Cmd,
-    core::{Node, Proposal},
+    core::{Node, Proposal, ProposalBatch},
     messages::WireMsgUtils,
     Result,
 };
@@ -27,6 +27,16 @@ impl Node {
         self.send_proposal(elders, proposal).await
     }
 
+    /// Send proposals raised together to all our elders, batched where they can be.
+    pub(crate) async fn propose_all(&self, proposals: Vec<Proposal>) -> Result<Vec<Cmd>> {
+        let elders = self
+            .network_knowledge
+            .authority_provider()
+            .await
+            .elders_vec();
+        self.send_proposals(elders, proposals).await
+    }
+
     /// Send `proposal` to `recipients`.
     pub(crate) async fn send_proposal(
         &self,
@@ -48,6 +58,57 @@ impl Node {
             .await
     }
 
+    /// Send proposals raised together to `recipients`: those which can be batched as one batch,
+    /// so the elders exchange a single sig share each for them all, and the others on their own.
+    pub(crate) async fn send_proposals(
+        &self,
+        recipients: Vec<Peer>,
+        proposals: Vec<Proposal>,
+    ) -> Result<Vec<Cmd>> {
+        if proposals.is_empty() {
+            return Ok(vec![]);
+        }
+        let (batchable, others): (Vec<_>, Vec<_>) =
+            proposals.into_iter().partition(Proposal::is_batchable);
+
+        let section_key = self.network_knowledge.section_key().await;
+        let key_share = self
+            .section_keys_provider
+            .key_share(&section_key)
+            .await
+            .map_err(|err| {
+                trace!("Can't propose {:?}: {:?}", batchable, err);
+                err
+            })?;
+
+        let mut cmds = vec![];
+        for proposal in others {
+            cmds.extend(
+                self.send_proposal_with(recipients.clone(), proposal, &key_share)
+                    .await?,
+            );
+        }
+        if batchable.len() == 1 {
+            // On its own, for nodes which don't know of batches.
+            for proposal in batchable {
+                cmds.extend(
+                    self.send_proposal_with(recipients.clone(), proposal, &key_share)
+                        .await?,
+                );
+            }
+        } else if !batchable.is_empty() {
+            cmds.extend(
+                self.send_proposal_batch_with(
+                    recipients,
+                    ProposalBatch::new(batchable),
+                    &key_share,
+                )
+                .await?,
+            );
+        }
+        Ok(cmds)
+    }
+
     /// Send `proposal` to `recipients` signing it with the provided key share.
     pub(crate) async fn send_proposal_with(
         &self,
@@ -55,6 +116,9 @@ impl Node {
         proposal: Proposal,
         key_share: &SectionKeyShare,
     ) -> Result<Vec<Cmd>> {
+        if self.sits_out("proposals").await {
+            return Ok(vec![]);
+        }
         trace!(
             "Propose {:?}, key_share: {:?}, aggregators: {:?}",
             proposal,
@@ -73,19 +137,7 @@ impl Node {
             proposal: proposal.clone().into_msg(),
             sig_share: sig_share.clone(),
         };
-        // Name of the section_pk may not matches the section prefix.
-        // Carry out a substitution to prevent the dst_location becomes other section.
-        let section_key = self.network_knowledge.section_key().await;
-        let wire_msg = WireMsg::single_src(
-            &self.info.read().await.clone(),
-            DstLocation::Section {
-                name: self.network_knowledge.prefix().await.name(),
-                section_pk: section_key,
-            },
-            node_msg,
-            section_key,
-        )?;
-
+        let wire_msg = self.proposal_wire_msg(node_msg).await?;
         let msg_id = wire_msg.msg_id();
 
         let mut cmds = vec![];
@@ -107,19 +159,98 @@ impl Node {
             }
         }
 
+        cmds.extend(
+            self.send_proposal_msg_to_others(recipients, wire_msg)
+                .await?,
+        );
+
+        Ok(cmds)
+    }
+
+    /// Send `batch` to `recipients` signing it as a whole with the provided key share.
+    async fn send_proposal_batch_with(
+        &self,
+        recipients: Vec<Peer>,
+        batch: ProposalBatch,
+        key_share: &SectionKeyShare,
+    ) -> Result<Vec<Cmd>> {
+        if self.sits_out("proposals").await {
+            return Ok(vec![]);
+        }
+        trace!(
+            "Propose batch {:?}, key_share: {:?}, aggregators: {:?}",
+            batch,
+            key_share,
+            recipients,
+        );
+
+        let sig_share = batch.sign_with_key_share(
+            key_share.public_key_set.clone(),
+            key_share.index,
+            &key_share.secret_key_share,
+        )?;
+
+        let node_msg = SystemMsg::ProposeBatch {
+            proposals: batch.clone().into_msg(),
+            sig_share: sig_share.clone(),
+        };
+        let wire_msg = self.proposal_wire_msg(node_msg).await?;
+        let msg_id = wire_msg.msg_id();
+
+        let mut cmds = vec![];
+        let our_name = self.info.read().await.name();
+        if let Some(ourself) = recipients.iter().find(|peer| peer.name() == our_name) {
+            cmds.extend(
+                super::super::handle_proposal_batch(
+                    msg_id,
+                    batch,
+                    sig_share,
+                    *ourself,
+                    &self.network_knowledge,
+                    &self.proposal_aggregator,
+                )
+                .await?,
+            );
+        }
+
+        cmds.extend(
+            self.send_proposal_msg_to_others(recipients, wire_msg)
+                .await?,
+        );
+
+        Ok(cmds)
+    }
+
+    // The wire msg of a proposal msg, to the elders of our section.
+    async fn proposal_wire_msg(&self, node_msg: SystemMsg) -> Result<WireMsg> {
+        // Name of the section_pk may not matches the section prefix.
+        // Carry out a substitution to prevent the dst_location becomes other section.
+        let section_key = self.network_knowledge.section_key().await;
+        WireMsg::single_src(
+            &self.info.read().await.clone(),
+            DstLocation::Section {
+                name: self.network_knowledge.prefix().await.name(),
+                section_pk: section_key,
+            },
+            node_msg,
+            section_key,
+        )
+    }
+
+    // Sends a proposal msg to the `recipients` other than ourselves.
+    async fn send_proposal_msg_to_others(
+        &self,
+        recipients: Vec<Peer>,
+        wire_msg: WireMsg,
+    ) -> Result<Vec<Cmd>> {
+        let our_name = self.info.read().await.name();
         // remove ourself from recipients
         let recipients = recipients
             .into_iter()
             .filter(|peer| peer.name() != our_name)
             .collect();
 
-        cmds.extend(
-            self.send_messages_to_all_nodes_or_directly_handle_for_accumulation(
-                recipients, wire_msg,
-            )
-            .await?,
-        );
-
-        Ok(cmds)
+        self.send_messages_to_all_nodes_or_directly_handle_for_accumulation(recipients, wire_msg)
+            .await
     }
 }
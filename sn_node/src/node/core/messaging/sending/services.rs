@@ -6,9 +6,13 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::{api::cmds::Cmd, core::Node, Result};
+use crate::node::{
+    api::cmds::Cmd,
+    core::{error_flood::ErrorVerdict, Misbehaviour, Node},
+    Result,
+};
 use sn_interface::messaging::{
-    data::{CmdError, ServiceMsg},
+    data::{CmdError, Error as ErrorMsg, QueryResponse, ServiceError, ServiceMsg, SessionToken},
     AuthKind, DstLocation, EndUser, MsgId, ServiceAuth, WireMsg,
 };
 use sn_interface::types::{Peer, PublicKey, Signature};
@@ -17,29 +21,165 @@ use bytes::Bytes;
 use ed25519_dalek::Signer;
 
 impl Node {
-    /// Forms a CmdError msg to send back to the client
+    /// Forms a CmdError msg to send back to the client, with our section key for the client to
+    /// update its knowledge to before retrying.
     pub(crate) async fn send_cmd_error_response(
         &self,
         error: CmdError,
         target: Peer,
         msg_id: MsgId,
     ) -> Result<Vec<Cmd>> {
+        let section_key = self.network_knowledge().section_key().await;
+        let CmdError::Data(error) = error.with_section_key(section_key);
+        let retryable = error.retryable;
         let the_error_msg = ServiceMsg::CmdError {
-            error,
+            error: CmdError::Data(error),
             correlation_id: msg_id,
         };
-        self.send_cmd_response(target, the_error_msg).await
+        self.send_error_msg(target, the_error_msg, retryable).await
     }
 
     /// Forms a CmdAck msg to send back to the client
     pub(crate) async fn send_cmd_ack(&self, target: Peer, msg_id: MsgId) -> Result<Vec<Cmd>> {
+        self.send_cmd_ack_with_token(target, msg_id, None).await
+    }
+
+    /// Forms a CmdAck msg to send back to the client, with the token of its write if any
+    pub(crate) async fn send_cmd_ack_with_token(
+        &self,
+        target: Peer,
+        msg_id: MsgId,
+        token: Option<SessionToken>,
+    ) -> Result<Vec<Cmd>> {
         let the_ack_msg = ServiceMsg::CmdAck {
             correlation_id: msg_id,
+            token,
         };
         self.send_cmd_response(target, the_ack_msg).await
     }
 
+    /// Forms a `NodeBusy` error response to a client cmd or query we won't process, so the
+    /// client knows to back off and retry. Nothing is sent for other msgs.
+    pub(crate) async fn send_busy_response(
+        &self,
+        msg: &ServiceMsg,
+        target: Peer,
+        msg_id: MsgId,
+    ) -> Result<Vec<Cmd>> {
+        self.send_error_response(msg, ErrorMsg::NodeBusy, target, msg_id)
+            .await
+    }
+
+    /// Forms the error response to a client cmd or query, of the kind the client awaits for it.
+    /// Nothing is sent for other msgs.
+    pub(crate) async fn send_error_response(
+        &self,
+        msg: &ServiceMsg,
+        error: ErrorMsg,
+        target: Peer,
+        msg_id: MsgId,
+    ) -> Result<Vec<Cmd>> {
+        match msg {
+            ServiceMsg::Cmd(cmd) => {
+                self.send_cmd_error_response(cmd.error(error), target, msg_id)
+                    .await
+            }
+            ServiceMsg::Query(query) | ServiceMsg::ConsistentQuery { query, .. } => {
+                let retryable = error.is_retryable();
+                let response = ServiceMsg::QueryResponse {
+                    response: query.error(error)?,
+                    correlation_id: msg_id,
+                };
+                self.send_error_msg(target, response, retryable).await
+            }
+            ServiceMsg::SpentbookCmd(cmd) => {
+                self.send_cmd_error_response(cmd.error(error), target, msg_id)
+                    .await
+            }
+            ServiceMsg::SpentbookQuery(query) => {
+                let retryable = error.is_retryable();
+                let response = ServiceMsg::QueryResponse {
+                    response: query.error(error)?,
+                    correlation_id: msg_id,
+                };
+                self.send_error_msg(target, response, retryable).await
+            }
+            ServiceMsg::BatchCmd(_) => {
+                self.send_cmd_error_response(CmdError::Data(error.into()), target, msg_id)
+                    .await
+            }
+            ServiceMsg::BatchQuery(query) => {
+                let retryable = error.is_retryable();
+                let response = ServiceMsg::QueryResponse {
+                    response: query.error(error)?,
+                    correlation_id: msg_id,
+                };
+                self.send_error_msg(target, response, retryable).await
+            }
+            ServiceMsg::HoldersQuery(query) => {
+                let retryable = error.is_retryable();
+                let response = ServiceMsg::QueryResponse {
+                    response: query.error(error)?,
+                    correlation_id: msg_id,
+                };
+                self.send_error_msg(target, response, retryable).await
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Forms a cmd to send an error response to the client. Errors which aren't retryable, thus
+    /// down to the client's msgs, are counted, a client past too many of them being told once
+    /// to back off and losing reputation, and its errors dropped until the window is over.
+    async fn send_error_msg(
+        &self,
+        target: Peer,
+        msg: ServiceMsg,
+        retryable: bool,
+    ) -> Result<Vec<Cmd>> {
+        if retryable {
+            return self.send_cmd_response(target, msg).await;
+        }
+        match self.error_flood.record(target.addr()) {
+            ErrorVerdict::Respond => self.send_cmd_response(target, msg).await,
+            ErrorVerdict::BackOff(back_off) => {
+                warn!(
+                    "Too many msgs of {:?} failed, telling it to back off for {:?}",
+                    target, back_off
+                );
+                self.comm
+                    .report_misbehaviour(&target, Misbehaviour::ErrorFlood)
+                    .await;
+                let notice = ServiceMsg::ServiceError(ServiceError {
+                    reason: Some(ErrorMsg::TooManyErrors {
+                        back_off_secs: back_off.as_secs().max(1),
+                    }),
+                    source_message: None,
+                });
+                self.send_cmd_response(target, notice).await
+            }
+            ErrorVerdict::Suppress => {
+                trace!("Dropping error response to {:?}, told to back off", target);
+                Ok(vec![])
+            }
+        }
+    }
+
     /// Forms a cmd to send a cmd response error/ack to the client
+    /// Forms the response to a client query we answer ourselves, rather than our adults.
+    pub(crate) async fn send_query_response(
+        &self,
+        target: Peer,
+        response: QueryResponse,
+        correlation_id: MsgId,
+    ) -> Result<Vec<Cmd>> {
+        let msg = ServiceMsg::QueryResponse {
+            response,
+            correlation_id,
+        };
+        self.send_cmd_response(target, msg).await
+    }
+
     async fn send_cmd_response(&self, target: Peer, msg: ServiceMsg) -> Result<Vec<Cmd>> {
         let dst = DstLocation::EndUser(EndUser(target.name()));
 
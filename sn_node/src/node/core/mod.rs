@@ -12,33 +12,98 @@ mod comm;
 mod connectivity;
 mod data;
 mod delivery_group;
+mod error_flood;
+mod join_pressure;
+mod leaving;
+mod liveness;
 mod messaging;
+mod msg_filter;
+mod planned_restart;
+mod prefix_map_db;
 mod proposal;
+mod protocol_check;
+mod readiness;
+mod relay;
 mod relocation;
+mod retry_queue;
+mod role_state;
+mod spentbook;
+mod split;
 mod split_barrier;
-
-pub(crate) use bootstrap::{join_network, JoiningAsRelocated};
-pub(crate) use comm::{Comm, DeliveryStatus, MsgEvent};
-pub(crate) use data::MIN_LEVEL_WHEN_FULL;
-pub(crate) use proposal::Proposal;
+mod state_db;
+
+pub(crate) use bootstrap::{cached_contacts, cached_prefix_map, join_network, JoiningAsRelocated};
+#[cfg(feature = "back-pressure")]
+pub use comm::BackPressureStrategy;
+#[cfg(all(feature = "back-pressure", feature = "metrics"))]
+pub(crate) use comm::MsgClass;
+#[cfg(feature = "back-pressure")]
+pub(crate) use comm::DEFAULT_BACK_PRESSURE_REPORT_INTERVAL;
+pub(crate) use comm::{
+    BandwidthCaps, Comm, ConnLimits, DeliveryStatus, Direction, Disposition, ListenerKind,
+    Misbehaviour, MsgEvent, DEFAULT_CLIENT_CONNS_PER_S, DEFAULT_COMPRESSION_THRESHOLD_KB,
+    DEFAULT_MAX_CLIENT_CONNS, DEFAULT_MAX_NODE_CONNS, DEFAULT_MSG_AUDIT_CAPACITY,
+    DEFAULT_NODE_CONNS_PER_S, DEFAULT_STREAM_THRESHOLD_KB,
+};
+#[cfg(test)]
+pub(crate) use comm::{Fault, Partitions};
+pub use data::{CacheOptions, CacheStats};
+pub(crate) use data::{
+    CheckOptions, DistributionReport, JobProgress, ReplicationTarget, WarmUpPolicy,
+    DEFAULT_AUDIT_CHUNKS_PER_TICK, DEFAULT_DATA_CACHE_MB, DEFAULT_DATA_CACHE_TTL,
+    DEFAULT_DISK_FAILURE_THRESHOLD, DEFAULT_DISK_RECOVERY_THRESHOLD, DEFAULT_HEDGE_DELAY,
+    DEFAULT_REGISTRY_SLICES_PER_TICK, DEFAULT_SCRUB_CHUNKS_PER_TICK, DEFAULT_WARM_UP,
+    DEFAULT_WARM_UP_INITIAL_SHARE_PERCENT, DISTRIBUTION_CHECK_INTERVAL, MIN_LEVEL_WHEN_FULL,
+    REGISTRY_BOOTSTRAP_INTERVAL, REPLICATION_AUDIT_INTERVAL, SCRUB_INTERVAL,
+};
+#[cfg(test)]
+pub(crate) use data::{CheckStatus, Severity};
+pub(crate) use join_pressure::solve_memory_proof;
+pub(crate) use liveness::LIVENESS_PROBE_INTERVAL;
+pub(crate) use msg_filter::{DEFAULT_MSG_FILTER_TTL, MSG_FILTER_CHECKPOINT_INTERVAL};
+pub(crate) use prefix_map_db::PrefixMapDb;
+pub(crate) use proposal::{Proposal, ProposalBatch};
+pub(crate) use readiness::{Condition, Readiness};
+pub(crate) use relay::{
+    OurRelay, RelayLimits, RelayService, DEFAULT_RELAY_KBPS, DEFAULT_RELAY_MAX_PEERS, RELAY_LEASE,
+    RELAY_RENEWAL_INTERVAL,
+};
 #[cfg(test)]
 pub(crate) use relocation::{check as relocation_check, ChurnId};
-
-use self::{data::DataStorage, split_barrier::SplitBarrier};
+pub(crate) use relocation::{
+    RelocationLimits, DEFAULT_RELOCATION_MAX_KEY_LAG, DEFAULT_RELOCATION_VALIDITY,
+};
+pub(crate) use retry_queue::{
+    Retry, RetryPolicy, DEFAULT_RETRY_BASE_DELAY, DEFAULT_RETRY_MAX_ATTEMPTS, RETRY_INTERVAL,
+};
+pub(crate) use split::Shortfall;
+pub(crate) use state_db::StateDb;
+
+use self::{
+    data::DataStorage,
+    error_flood::ErrorFlood,
+    join_pressure::JoinPressure,
+    liveness::LivenessProbes,
+    messaging::HeldQuery,
+    msg_filter::MsgFilter,
+    planned_restart::PlannedRestarts,
+    relocation::RelocationFreshness,
+    retry_queue::RetryQueue,
+    role_state::{PromotionStep, RoleStore},
+    spentbook::Spentbook,
+    split_barrier::SplitBarrier,
+};
 use sn_interface::{
     network_knowledge::{
-        recommended_section_size, supermajority, NetworkKnowledge, NodeInfo, SectionKeyShare,
-        SectionKeysProvider,
+        InvalidSapCounts, NetworkKnowledge, NodeInfo, SectionKeyShare, SectionKeysProvider,
     },
+    protocol_constants::{ProtocolConstants, ProtocolDigest},
     types::keys::ed25519::Digest256,
 };
 
 use super::{
-    api::cmds::Cmd,
-    dkg::DkgVoter,
-    handover::Handover,
-    membership::{split, Membership},
-    Elders, Event, NodeElderChange,
+    api::cmds::Cmd, dkg::DkgVoter, handover::Handover, membership::Membership, Elders, Event,
+    MembershipEvent, NodeElderChange,
 };
 
 use crate::node::{
@@ -46,34 +111,42 @@ use crate::node::{
     membership::elder_candidates,
 };
 use sn_interface::messaging::{
-    data::OperationId,
+    data::{BatchStatus, CmdError, OperationId},
     signature_aggregator::SignatureAggregator,
     system::{DkgSessionId, NodeEvent, NodeState, SystemMsg},
-    AuthorityProof, DstLocation, SectionAuth, SectionAuthorityProvider,
+    AuthorityProof, DstLocation, MsgId, SectionAuth, SectionAuthorityProvider,
 };
-use sn_interface::types::{log_markers::LogMarker, Cache, Peer};
+use sn_interface::types::{log_markers::LogMarker, Cache, Peer, PublicKey};
 
+use crate::membership_history::{MembershipHistory, MembershipSnapshot};
 use crate::UsedSpace;
 use sn_interface::network_knowledge::utils::compare_and_write_prefix_map_to_disk;
 
 use backoff::ExponentialBackoff;
 use dashmap::DashSet;
-use data::Capacity;
+use data::{
+    AdultIndex, Capacity, ConsistentReads, DataCache, DistributionCheck, ErasureCoding,
+    HedgedReads, HolderRegistry, ReplicationAudit, ReplicationJobs, SplitHandover, TransferChecks,
+    WarmUp,
+};
 use itertools::Itertools;
-use resource_proof::ResourceProof;
 use sn_dysfunction::{DysfunctionDetection, DysfunctionSeverity, IssueType};
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::{broadcast, mpsc, RwLock},
+    time::Instant,
 };
-use tokio::sync::{mpsc, RwLock};
 use uluru::LRUCache;
 use xor_name::{Prefix, XorName};
 
-pub(super) const RESOURCE_PROOF_DATA_SIZE: usize = 128;
-pub(super) const RESOURCE_PROOF_DIFFICULTY: u8 = 10;
+pub(super) use sn_interface::protocol_constants::{
+    RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY,
+};
 
 const BACKOFF_CACHE_LIMIT: usize = 100;
 
@@ -91,11 +164,21 @@ const DATA_QUERY_TIMEOUT: Duration = Duration::from_secs(15);
 /// and relevant flows should be triggered. (So a throttle on supect flows pehaps)
 const SUSPECT_NODE_RETENTION_DURATION: Duration = Duration::from_secs(60 * 25 /* 25 mins */);
 
+// How long the outcome of a client cmd is remembered, for the client retrying it after timing out
+// on our response to get that outcome again rather than have the cmd applied twice. Longer than
+// clients keep retrying a cmd by default.
+const CMD_OUTCOME_RETENTION_DURATION: Duration = Duration::from_secs(5 * 60);
+// This prevents the cmd outcomes remembered from growing unbound
+const CMD_OUTCOME_LIMIT: usize = 10_000;
+
 // This prevents pending query limit unbound growth
 pub(crate) const DATA_QUERY_LIMIT: usize = 100;
 // per query we can have this many peers, so the total peers waiting can be QUERY_LIMIT * MAX_WAITING_PEERS_PER_QUERY
 pub(crate) const MAX_WAITING_PEERS_PER_QUERY: usize = 100;
 
+/// How many membership events a subscriber can fall behind on before missing the oldest.
+pub(crate) const MEMBERSHIP_EVENT_CHANNEL_SIZE: usize = 64;
+
 #[derive(Debug, Clone)]
 pub(crate) struct DkgSessionInfo {
     pub(crate) session_id: DkgSessionId,
@@ -109,13 +192,18 @@ pub(crate) type AeBackoffCache =
 // Core state + logic of a node.
 pub(crate) struct Node {
     pub(super) event_tx: mpsc::Sender<Event>,
+    // Membership events, to all those subscribed to them
+    membership_events_tx: broadcast::Sender<MembershipEvent>,
     pub(crate) info: Arc<RwLock<NodeInfo>>,
 
     pub(crate) comm: Comm,
 
     pub(super) data_storage: DataStorage, // Adult only before cache
+    // Checks on the chunks other nodes transfer to us
+    pub(crate) transfer_checks: TransferChecks,
 
-    resource_proof: ResourceProof,
+    // Resource proof challenges issued to joining peers lately
+    pub(crate) join_pressure: JoinPressure,
     // Network resources
     pub(crate) section_keys_provider: SectionKeysProvider,
     network_knowledge: NetworkKnowledge,
@@ -127,6 +215,12 @@ pub(crate) struct Node {
     dkg_sessions: Arc<RwLock<HashMap<Digest256, DkgSessionInfo>>>,
     dkg_voter: DkgVoter,
     relocate_state: Arc<RwLock<Option<Box<JoiningAsRelocated>>>>,
+    // Relocations accepted to join with, and those already joined with
+    pub(crate) relocation_freshness: RelocationFreshness,
+    // Progress of our promotion, persisted to resume it across restarts
+    role_store: RoleStore,
+    // Snapshots of our section's membership over time, for post-incident analysis
+    membership_history: Arc<MembershipHistory>,
     // ======================== Elder only ========================
     pub(crate) membership: Arc<RwLock<Option<Membership>>>,
     // Section handover consensus state (Some for Elders, None for others)
@@ -135,11 +229,63 @@ pub(crate) struct Node {
     // Trackers
     capacity: Capacity,
     dysfunction_tracking: DysfunctionDetection,
+    // Adults about to restart, spared from offline proposals and new data meanwhile
+    planned_restarts: PlannedRestarts,
+    // When our own announced restart is due, as an adult
+    restart_deadline: Arc<RwLock<Option<Instant>>>,
+    // Which adults hold which data, rebuilt from their inventories upon promotion
+    pub(crate) holder_registry: HolderRegistry,
+    // Check of whether the data in the registry is held where it should be
+    pub(crate) distribution_check: DistributionCheck,
+    // Audit of chunks being held by their expected holders, challenged to prove it
+    pub(crate) replication_audit: ReplicationAudit,
+    // Probes of our adults' liveness, for unresponsive ones to be proposed offline
+    pub(crate) liveness_probes: LivenessProbes,
+    // Operators' jobs of replicating chunks to their missing holders
+    pub(crate) replication_jobs: ReplicationJobs,
+    // Our handovers of the data belonging to our sibling section after a split, as an adult
+    pub(crate) split_handover: SplitHandover,
+    // Adults new to the network, assigned a growing share of data as they warm up
+    pub(crate) warm_up: WarmUp,
+    // Our adults by closeness, for choosing data holders
+    pub(crate) adult_index: Arc<RwLock<AdultIndex>>,
     pending_data_queries: Arc<Cache<OperationId, Arc<DashSet<Peer>>>>,
+    // Reads of our adults on behalf of clients, hedged across the holders of the data
+    pub(crate) hedged_reads: HedgedReads,
+    // Chunks erasure coded into shares held by distinct adults, and their rebuilds for reads
+    pub(crate) erasure_coding: ErasureCoding,
+    // Client writes awaiting confirmation from our adults, and the consistent queries held on them
+    pub(crate) consistent_reads: ConsistentReads<HeldQuery>,
+    // Outcomes of the client cmds we handled lately, by msg id and client, `None` being an ack
+    pub(crate) cmd_outcomes: Arc<Cache<(MsgId, PublicKey), Option<CmdError>>>,
+    // Status of the client batches we handled lately, by msg id and client
+    pub(crate) batches: Arc<Cache<(MsgId, PublicKey), BatchStatus>>,
+    // Error responses sent to each peer lately, for floods of them to be cut short
+    pub(crate) error_flood: ErrorFlood,
+    // Chunks relayed from our adults to clients, for popular ones to be served from
+    pub(crate) data_cache: DataCache,
     /// Timed cache of suspect nodes and their score
     known_suspect_nodes: Arc<Cache<XorName, usize>>,
+    /// Number of invalid SAPs received, per sender
+    pub(crate) invalid_saps: InvalidSapCounts,
+    /// Digest of the protocol constants we run with
+    pub(crate) protocol_digest: ProtocolDigest,
     // Caches
     ae_backoff_cache: AeBackoffCache,
+    // Node msgs received lately, for those received again to be dropped
+    pub(crate) msg_filter: MsgFilter,
+    // Msgs which failed to be sent, to be sent again
+    pub(crate) retry_queue: RetryQueue,
+    // DBCs logged as spent, as an elder
+    pub(crate) spentbook: Spentbook,
+    // Where we snapshot our state on shutdown, to resume from on restart
+    state_db: StateDb,
+    // Where we cache the latest prefix map we know of, to bootstrap from on restart
+    prefix_map_db: PrefixMapDb,
+    // Nodes the network can't connect to, whose msgs we relay
+    pub(crate) relay_service: RelayService,
+    // The peer relaying our msgs, when the network can't connect to us
+    pub(crate) our_relay: OurRelay,
 }
 
 impl Node {
@@ -155,10 +301,8 @@ impl Node {
         root_storage_dir: PathBuf,
     ) -> Result<Self> {
         let membership = if let Some(key) = section_key_share.clone() {
-            let n_elders = network_knowledge
-                .section_signed_authority_provider()
-                .await
-                .elder_count();
+            let sap = network_knowledge.authority_provider().await;
+            let n_elders = sap.elder_count();
 
             // TODO: the bootstrap members should come from handover
             let bootstrap_members = BTreeSet::from_iter(
@@ -174,6 +318,7 @@ impl Node {
                 key.public_key_set,
                 n_elders,
                 bootstrap_members,
+                sap.network_params(),
             ))
         } else {
             None
@@ -185,6 +330,17 @@ impl Node {
         info.addr = comm.our_connection_info();
 
         let data_storage = DataStorage::new(&root_storage_dir, used_space.clone())?;
+        let _replayed = data_storage.replay_chunk_journal().await?;
+        let role_store = RoleStore::new(&root_storage_dir)?;
+        let holder_registry = HolderRegistry::new(&root_storage_dir)?;
+        let replication_jobs = ReplicationJobs::new(&root_storage_dir)?;
+        let split_handover = SplitHandover::new(&root_storage_dir)?;
+        let membership_history = Arc::new(MembershipHistory::open(&root_storage_dir)?);
+        let msg_filter = MsgFilter::new(&root_storage_dir);
+        let retry_queue = RetryQueue::new(&root_storage_dir);
+        let spentbook = Spentbook::new(&root_storage_dir);
+        let state_db = StateDb::new(&root_storage_dir);
+        let prefix_map_db = PrefixMapDb::new(&root_storage_dir);
 
         info!("Creating DysfunctionDetection checks");
         let node_dysfunction_detector = DysfunctionDetection::new(
@@ -224,22 +380,73 @@ impl Node {
             message_aggregator: SignatureAggregator::default(),
             dkg_voter: DkgVoter::default(),
             relocate_state: Arc::new(RwLock::new(None)),
+            relocation_freshness: RelocationFreshness::default(),
+            role_store,
+            membership_history,
             event_tx,
+            membership_events_tx: broadcast::channel(MEMBERSHIP_EVENT_CHANNEL_SIZE).0,
             handover_voting: Arc::new(RwLock::new(handover)),
             joins_allowed: Arc::new(RwLock::new(true)),
-            resource_proof: ResourceProof::new(RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY),
+            join_pressure: JoinPressure::default(),
             data_storage,
+            transfer_checks: TransferChecks::default(),
             capacity: Capacity::default(),
             dysfunction_tracking: node_dysfunction_detector,
+            planned_restarts: PlannedRestarts::default(),
+            restart_deadline: Arc::new(RwLock::new(None)),
+            holder_registry,
+            distribution_check: DistributionCheck::default(),
+            replication_audit: ReplicationAudit::default(),
+            liveness_probes: LivenessProbes::default(),
+            replication_jobs,
+            split_handover,
+            warm_up: WarmUp::default(),
+            adult_index: Arc::default(),
             pending_data_queries: Arc::new(Cache::with_expiry_duration(DATA_QUERY_TIMEOUT)),
+            hedged_reads: HedgedReads::default(),
+            erasure_coding: ErasureCoding::default(),
+            consistent_reads: ConsistentReads::default(),
+            cmd_outcomes: Arc::new(Cache::with_expiry_duration_and_capacity(
+                CMD_OUTCOME_RETENTION_DURATION,
+                CMD_OUTCOME_LIMIT,
+            )),
+            batches: Arc::new(Cache::with_expiry_duration_and_capacity(
+                CMD_OUTCOME_RETENTION_DURATION,
+                CMD_OUTCOME_LIMIT,
+            )),
+            error_flood: ErrorFlood::default(),
+            data_cache: DataCache::default(),
             known_suspect_nodes: Arc::new(Cache::with_expiry_duration(
                 SUSPECT_NODE_RETENTION_DURATION,
             )),
+            invalid_saps: InvalidSapCounts::default(),
+            protocol_digest: ProtocolConstants::current().digest(),
             ae_backoff_cache: AeBackoffCache::default(),
+            msg_filter,
+            retry_queue,
+            spentbook,
+            state_db,
+            prefix_map_db,
+            relay_service: RelayService::default(),
+            our_relay: OurRelay::default(),
             membership: Arc::new(RwLock::new(membership)),
         })
     }
 
+    /// Has our chunks read and written through the given `disk`, whose faults are scripted by
+    /// the test holding a clone of it.
+    #[cfg(test)]
+    pub(crate) fn with_faulty_disk(mut self, disk: crate::dbs::FaultyDisk) -> Self {
+        self.data_storage = self.data_storage.with_faulty_disk(disk);
+        self
+    }
+
+    /// Has our msgs go through the relay we joined through, if any.
+    pub(crate) fn with_our_relay(mut self, our_relay: OurRelay) -> Self {
+        self.our_relay = our_relay;
+        self
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // Miscellaneous
     ////////////////////////////////////////////////////////////////////////////
@@ -271,19 +478,29 @@ impl Node {
     }
 
     /// returns names that are relatively dysfunctional
+    /// leaving out adults within their restart window
     pub(crate) async fn get_dysfunctional_node_names(&self) -> Result<BTreeSet<XorName>> {
-        self.dysfunction_tracking
+        let restarting = self.planned_restarts.pending().await;
+        Ok(self
+            .dysfunction_tracking
             .get_nodes_beyond_severity(DysfunctionSeverity::Dysfunctional)
-            .await
-            .map_err(Error::from)
+            .await?
+            .into_iter()
+            .filter(|name| !restarting.contains(name))
+            .collect())
     }
 
     /// returns names that are relatively dysfunctional
+    /// leaving out adults within their restart window
     async fn get_suspicious_node_names(&self) -> Result<BTreeSet<XorName>> {
-        self.dysfunction_tracking
+        let restarting = self.planned_restarts.pending().await;
+        Ok(self
+            .dysfunction_tracking
             .get_nodes_beyond_severity(DysfunctionSeverity::Suspicious)
-            .await
-            .map_err(Error::from)
+            .await?
+            .into_iter()
+            .filter(|name| !restarting.contains(name))
+            .collect())
     }
 
     /// form Cmds about any newly suspicious nodes
@@ -338,6 +555,7 @@ impl Node {
 
     /// Log a communication problem
     pub(crate) async fn log_comm_issue(&self, name: XorName) -> Result<()> {
+        self.planned_restarts.mark_away(&name).await;
         self.dysfunction_tracking
             .track_issue(name, IssueType::Communication)
             .await
@@ -349,8 +567,13 @@ impl Node {
         // TODO: Make this serialization human readable
 
         let prefix_map = self.network_knowledge.prefix_map().clone();
+        let prefix_map_db = self.prefix_map_db.clone();
 
         let _ = tokio::spawn(async move {
+            // Cache it in our root dir, for us to bootstrap from its elders on restart
+            if let Err(e) = prefix_map_db.write(&prefix_map).await {
+                error!("Error caching PrefixMap in our root dir: {:?}", e);
+            }
             // Compare and write Prefix to `~/.safe/prefix_maps` dir
             if let Err(e) = compare_and_write_prefix_map_to_disk(&prefix_map).await {
                 error!("Error writing PrefixMap to `~/.safe` dir: {:?}", e);
@@ -367,33 +590,6 @@ impl Node {
         }
     }
 
-    pub(crate) async fn get_split_info(
-        &self,
-        prefix: Prefix,
-    ) -> Option<(BTreeSet<NodeState>, BTreeSet<NodeState>)> {
-        let members = self
-            .membership
-            .read()
-            .await
-            .as_ref()?
-            .current_section_members();
-
-        // TODO: super::split(..) should return (BTreeSet<NodeState>, BTreeSet<NodeState>)
-        //       instead of (BTreeSet<XorName>, BTreeSet<XorName>);
-        let (zero, one) = split(&prefix, members.keys().copied())?;
-
-        // If none of the two new sections would contain enough entries, return `None`.
-        let split_threshold = recommended_section_size();
-        if zero.len() < split_threshold || one.len() < split_threshold {
-            return None;
-        }
-
-        Some((
-            BTreeSet::from_iter(zero.into_iter().map(|n| members[&n].clone())),
-            BTreeSet::from_iter(one.into_iter().map(|n| members[&n].clone())),
-        ))
-    }
-
     // Tries to split our section.
     // If we have enough nodes for both subsections, returns the DkgSessionId's
     // of the two subsections. Otherwise returns `None`.
@@ -402,47 +598,21 @@ impl Node {
         excluded_names: &BTreeSet<XorName>,
     ) -> Option<(DkgSessionId, DkgSessionId)> {
         trace!("{}", LogMarker::SplitAttempt);
-        let prefix = self.network_knowledge.prefix().await;
-
-        let (zero, one) = self.get_split_info(prefix).await?;
-        debug!(
-            "Upon section split attempt, section size: zero {:?}, one {:?}",
-            zero.len(),
-            one.len()
-        );
+        let report = self.rehearse_split(excluded_names).await?;
+        info!("Upon section split attempt, rehearsed {}", report);
+        if !report.is_ready() {
+            return None;
+        }
 
         let sap = self.network_knowledge.authority_provider().await;
-
-        let zero_prefix = prefix.pushed(false);
-        let zero_elders = elder_candidates(
-            zero.iter()
-                .cloned()
-                .filter(|name| !excluded_names.contains(&name.name)),
-            &sap,
-        );
-
-        let one_prefix = prefix.pushed(true);
-        let one_elders = elder_candidates(
-            one.iter()
-                .cloned()
-                .filter(|name| !excluded_names.contains(&name.name)),
-            &sap,
-        );
-
         let generation = self.network_knowledge.chain_len().await;
-
-        let zero_id = DkgSessionId {
-            prefix: zero_prefix,
-            elders: BTreeMap::from_iter(zero_elders.iter().map(|node| (node.name, node.addr))),
-            section_chain_len: generation,
-            bootstrap_members: zero,
-        };
-        let one_id = DkgSessionId {
-            prefix: one_prefix,
-            elders: BTreeMap::from_iter(one_elders.iter().map(|node| (node.name, node.addr))),
+        let [zero_id, one_id] = [report.zero, report.one].map(|half| DkgSessionId {
+            prefix: half.prefix,
+            elders: BTreeMap::from_iter(half.elders.iter().map(|node| (node.name, node.addr))),
             section_chain_len: generation,
-            bootstrap_members: one,
-        };
+            bootstrap_members: half.members,
+            network_params: sap.network_params(),
+        });
 
         Some((zero_id, one_id))
     }
@@ -493,7 +663,8 @@ impl Node {
             .eq(current_elders.iter())
         {
             vec![]
-        } else if elder_candidates.len() < supermajority(current_elders.len()) {
+        } else if elder_candidates.len() < sap.network_params().supermajority(current_elders.len())
+        {
             warn!("ignore attempt to reduce the number of elders too much");
             vec![]
         } else if elder_candidates.len() < current_elders.len() {
@@ -517,6 +688,7 @@ impl Node {
                 ),
                 section_chain_len: generation,
                 bootstrap_members: BTreeSet::from_iter(members.into_values()),
+                network_params: sap.network_params(),
             };
             vec![session_id]
         }
@@ -535,6 +707,7 @@ impl Node {
             key.public_key_set,
             sap.elders.len(),
             BTreeSet::from_iter(sap.members.into_values()),
+            sap.network_params,
         ));
 
         Ok(())
@@ -579,6 +752,46 @@ impl Node {
         Ok(())
     }
 
+    /// Sets up our elder state for the current section key, for which we must hold a key share.
+    /// Safe to re-run, e.g. when resuming a promotion after a restart.
+    pub(crate) async fn complete_elder_setup(&self) -> Result<Vec<Cmd>> {
+        let mut cmds = vec![];
+        self.initialize_elder_state().await?;
+
+        cmds.extend(
+            self.promote_and_demote_elders_except(&BTreeSet::new())
+                .await?,
+        );
+
+        // NB TODO make sure this in only called once (after handover)
+        // and that it cannot interfere with the handover voting process as it resets the handover state completely
+        // NB TODO we should keep a copy of old handover states (since they contain valuable information like who is faulty)
+        self.initialize_handover().await?;
+
+        self.start_holder_registry_rebuild().await;
+
+        // Our shares of the spent proofs still pending were signed with our previous key.
+        match self.resign_pending_spent_proofs().await {
+            Ok(resigned) => cmds.extend(resigned),
+            Err(error) => warn!("Failed to re-sign the pending spent proofs: {}", error),
+        }
+
+        // Whenever there is an elders change, casting a round of joins_allowed
+        // proposals to sync.
+        cmds.extend(
+            self.propose(Proposal::JoinsAllowed(*self.joins_allowed.read().await))
+                .await?,
+        );
+
+        let section_key = self.network_knowledge.section_key().await;
+        if let Ok(key_share) = self.section_keys_provider.key_share(&section_key).await {
+            self.record_promotion_step(PromotionStep::SetupCompleted((&key_share).into()))
+                .await;
+        }
+
+        Ok(cmds)
+    }
+
     /// Generate cmds and fire events based upon any node state changes.
     pub(super) async fn update_self_for_new_node_state(
         &self,
@@ -611,24 +824,7 @@ impl Node {
 
                 if we_have_our_key_share_for_new_section_key {
                     // The section-key has changed, we are now able to function as an elder.
-                    self.initialize_elder_state().await?;
-
-                    cmds.extend(
-                        self.promote_and_demote_elders_except(&BTreeSet::new())
-                            .await?,
-                    );
-
-                    // NB TODO make sure this in only called once (after handover)
-                    // and that it cannot interfere with the handover voting process as it resets the handover state completely
-                    // NB TODO we should keep a copy of old handover states (since they contain valuable information like who is faulty)
-                    self.initialize_handover().await?;
-
-                    // Whenever there is an elders change, casting a round of joins_allowed
-                    // proposals to sync.
-                    cmds.extend(
-                        self.propose(Proposal::JoinsAllowed(*self.joins_allowed.read().await))
-                            .await?,
-                    );
+                    cmds.extend(self.complete_elder_setup().await?);
                 }
 
                 self.print_network_stats().await;
@@ -637,6 +833,7 @@ impl Node {
                 // if not elder
                 let mut handover_voting = self.handover_voting.write().await;
                 *handover_voting = None;
+                self.clear_role_transition().await;
             }
 
             if new.is_elder || old.is_elder {
@@ -658,10 +855,18 @@ impl Node {
 
             let self_status_change = if !old.is_elder && new.is_elder {
                 info!("{}: {:?}", LogMarker::PromotedToElder, new.prefix);
+                self.send_membership_event(MembershipEvent::BecameElder {
+                    prefix: new.prefix,
+                    section_key: new.section_key,
+                });
                 NodeElderChange::Promoted
             } else if old.is_elder && !new.is_elder {
                 info!("{}", LogMarker::DemotedFromElder);
+                self.send_membership_event(MembershipEvent::StoppedBeingElder {
+                    prefix: new.prefix,
+                });
                 self.section_keys_provider.wipe().await;
+                self.clear_role_transition().await;
                 NodeElderChange::Demoted
             } else {
                 NodeElderChange::None
@@ -672,6 +877,11 @@ impl Node {
             // We also need to update other nodes w/ our known data.
             let event = if (new.prefix != old.prefix) && new.is_elder {
                 info!("{}: {:?}", LogMarker::SplitSuccess, new.prefix);
+                self.send_membership_event(MembershipEvent::SplitOccurred {
+                    previous_prefix: old.prefix,
+                    prefix: new.prefix,
+                    section_key: new.section_key,
+                });
 
                 if old.is_elder {
                     info!("{}: {:?}", LogMarker::StillElderAfterSplit, new.prefix);
@@ -711,6 +921,8 @@ impl Node {
                 }
             };
 
+            self.record_membership_snapshot().await;
+
             cmds.extend(
                 self.send_metadata_updates_to_nodes(
                     self.network_knowledge
@@ -762,6 +974,29 @@ impl Node {
             .print();
     }
 
+    /// Appends what we currently know of our section's membership to the membership history.
+    pub(crate) async fn record_membership_snapshot(&self) {
+        let snapshot = MembershipSnapshot {
+            timestamp: SystemTime::now(),
+            prefix: self.network_knowledge.prefix().await,
+            section_key: MembershipSnapshot::key_fingerprint(
+                &self.network_knowledge.section_key().await,
+            ),
+            elders: self
+                .network_knowledge
+                .authority_provider()
+                .await
+                .names()
+                .into_iter()
+                .collect(),
+            adult_count: self.network_knowledge.adults().await.len(),
+            generation: self.network_knowledge.chain_len().await,
+        };
+        if let Err(error) = self.membership_history.append(&snapshot) {
+            error!("Failed to record membership snapshot: {:?}", error);
+        }
+    }
+
     pub(super) async fn log_section_stats(&self) {
         if let Some(m) = self.membership.read().await.as_ref() {
             let adults = self.network_knowledge.adults().await.len();
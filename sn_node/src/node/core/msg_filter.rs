@@ -0,0 +1,246 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise};
+use crate::node::Result;
+use crate::persisted;
+
+use sn_interface::messaging::MsgId;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+use xor_name::XorName;
+
+const MSG_FILTER_FILE: &str = "msg_filter";
+// Format of the checkpoint file: the msgs remembered, oldest first.
+const MSG_FILTER_VERSION: u16 = 1;
+
+/// How long node msgs are remembered when unspecified.
+pub(crate) const DEFAULT_MSG_FILTER_TTL: Duration = Duration::from_secs(10 * 60);
+/// How often the msgs remembered are checkpointed to disk.
+pub(crate) const MSG_FILTER_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+// A msg, by its id and the node which sent it.
+type Key = (MsgId, XorName);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Seen {
+    key: Key,
+    // When it was first received, in seconds since the Unix epoch.
+    at: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    keys: BTreeSet<Key>,
+    // The msgs remembered, oldest first.
+    seen: VecDeque<Seen>,
+    capacity: usize,
+    ttl: Duration,
+    // Whether anything changed since the last checkpoint.
+    dirty: bool,
+}
+
+impl State {
+    // Forgets the msgs older than the TTL, then the oldest ones over capacity.
+    fn evict(&mut self, now: u64) {
+        let ttl = self.ttl.as_secs();
+        while let Some(oldest) = self.seen.front() {
+            if self.seen.len() <= self.capacity && oldest.at.saturating_add(ttl) > now {
+                break;
+            }
+            if let Some(oldest) = self.seen.pop_front() {
+                let _removed = self.keys.remove(&oldest.key);
+                self.dirty = true;
+            }
+        }
+    }
+}
+
+/// Node msgs received lately, by id and sender, for those received again to be dropped rather
+/// than handled twice. Checkpointed to the node's root dir, so a restarted node still knows the
+/// msgs it handled before it went down.
+///
+/// Disabled, remembering nothing, until given a capacity.
+#[derive(Clone, Debug)]
+pub(crate) struct MsgFilter {
+    state: Arc<RwLock<State>>,
+    path: PathBuf,
+}
+
+impl MsgFilter {
+    pub(crate) fn new(root_dir: &Path) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State::default())),
+            path: root_dir.join(MSG_FILTER_FILE),
+        }
+    }
+
+    /// Enables the filter, remembering up to `capacity` msgs for `ttl`, starting with those
+    /// of the last checkpoint still within it. A checkpoint which can't be read is left out, as
+    /// the filter only spares us handling msgs twice.
+    pub(crate) async fn enable(&self, capacity: usize, ttl: Duration) {
+        let seen = match persisted::read_async(self.path.clone()).await {
+            Ok(Some(read)) if read.version == MSG_FILTER_VERSION => {
+                match deserialise::<Vec<Seen>>(&read.payload) {
+                    Ok(seen) => seen,
+                    Err(error) => {
+                        warn!(
+                            "Discarding the unreadable msg filter checkpoint: {:?}",
+                            error
+                        );
+                        vec![]
+                    }
+                }
+            }
+            Ok(Some(read)) => {
+                warn!(
+                    "Discarding the msg filter checkpoint of unknown version {}",
+                    read.version
+                );
+                vec![]
+            }
+            Ok(None) => vec![],
+            Err(error) => {
+                warn!("Discarding the damaged msg filter checkpoint: {}", error);
+                vec![]
+            }
+        };
+
+        let mut state = self.state.write().await;
+        state.capacity = capacity;
+        state.ttl = ttl;
+        for seen in seen {
+            if state.keys.insert(seen.key) {
+                state.seen.push_back(seen);
+            }
+        }
+        state.evict(now());
+        info!(
+            "Filtering node msgs received again, {} remembered from before",
+            state.seen.len()
+        );
+    }
+
+    /// Remembers the msg `msg_id` from `sender`, returning whether it's new to us. Every msg is
+    /// new while the filter is disabled.
+    pub(crate) async fn insert(&self, msg_id: MsgId, sender: XorName) -> bool {
+        self.insert_at(msg_id, sender, now()).await
+    }
+
+    async fn insert_at(&self, msg_id: MsgId, sender: XorName, now: u64) -> bool {
+        let mut state = self.state.write().await;
+        if state.capacity == 0 {
+            return true;
+        }
+        state.evict(now);
+        let key = (msg_id, sender);
+        if !state.keys.insert(key) {
+            return false;
+        }
+        state.seen.push_back(Seen { key, at: now });
+        state.dirty = true;
+        state.evict(now);
+        true
+    }
+
+    /// Writes the msgs remembered to disk, if there's been any change since last time.
+    pub(crate) async fn checkpoint(&self) -> Result<()> {
+        let payload = {
+            let mut state = self.state.write().await;
+            if state.capacity == 0 {
+                return Ok(());
+            }
+            state.evict(now());
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            serialise(&state.seen)?
+        };
+        persisted::write_async(self.path.clone(), MSG_FILTER_VERSION, payload).await?;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persisted::damage;
+
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn msgs_received_again_are_filtered_until_forgotten() {
+        let root = tempdir().expect("Failed to create temp dir");
+        let filter = MsgFilter::new(root.path());
+        let (msg_id, sender) = (MsgId::new(), xor_name::rand::random());
+
+        // Disabled, everything goes through.
+        assert!(filter.insert(msg_id, sender).await);
+        assert!(filter.insert(msg_id, sender).await);
+
+        filter.enable(2, Duration::from_secs(60)).await;
+        assert!(filter.insert_at(msg_id, sender, 1000).await);
+        assert!(!filter.insert_at(msg_id, sender, 1000).await);
+        // The same id from another sender is another msg.
+        let other_sender = xor_name::rand::random();
+        assert!(filter.insert_at(msg_id, other_sender, 1000).await);
+
+        // Over capacity, the oldest are forgotten.
+        assert!(filter.insert_at(MsgId::new(), sender, 1000).await);
+        assert!(filter.insert_at(msg_id, sender, 1000).await);
+
+        // As are those older than the TTL.
+        assert!(!filter.insert_at(msg_id, sender, 1059).await);
+        assert!(filter.insert_at(msg_id, sender, 1061).await);
+    }
+
+    #[tokio::test]
+    async fn msgs_are_remembered_across_restarts() -> Result<()> {
+        let root = tempdir()?;
+        let (msg_id, sender) = (MsgId::new(), xor_name::rand::random());
+
+        let filter = MsgFilter::new(root.path());
+        filter.enable(100, DEFAULT_MSG_FILTER_TTL).await;
+        assert!(filter.insert(msg_id, sender).await);
+        filter.checkpoint().await?;
+        drop(filter);
+
+        let filter = MsgFilter::new(root.path());
+        filter.enable(100, DEFAULT_MSG_FILTER_TTL).await;
+        assert!(!filter.insert(msg_id, sender).await);
+
+        // A damaged checkpoint is left out rather than stopping the node.
+        let path = root.path().join(MSG_FILTER_FILE);
+        for way in 0..damage::ways(&path)? {
+            filter.checkpoint().await?;
+            damage::apply(&path, way)?;
+            let restarted = MsgFilter::new(root.path());
+            restarted.enable(100, DEFAULT_MSG_FILTER_TTL).await;
+            let _new = restarted.insert(msg_id, sender).await;
+            // Something changed for the next checkpoint to be written again.
+            assert!(filter.insert(MsgId::new(), sender).await);
+        }
+
+        Ok(())
+    }
+}
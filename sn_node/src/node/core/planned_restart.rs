@@ -0,0 +1,248 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{
+    core::{Cmd, Node},
+    Error, Result,
+};
+
+use sn_interface::{
+    messaging::system::{NodeCmd, SystemMsg},
+    types::PublicKey,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// Longest absence an adult can announce. Adults away for longer are handled as usual.
+pub(crate) const MAX_RESTART_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug)]
+struct PlannedRestart {
+    deadline: Instant,
+    // Whether we've failed to reach the adult since it announced its restart.
+    away: bool,
+}
+
+/// Adults which announced they're about to restart, tracked by elders until they're back or
+/// their window runs out. Comm issues keep being tracked against them meanwhile, so once a
+/// window runs out the usual offline handling picks up as if there had been no window.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PlannedRestarts {
+    restarts: Arc<RwLock<BTreeMap<XorName, PlannedRestart>>>,
+}
+
+impl PlannedRestarts {
+    /// Records that `adult` will restart within `within`, capped at `MAX_RESTART_WINDOW`.
+    pub(crate) async fn announce(&self, adult: XorName, within: Duration) {
+        let deadline = Instant::now() + within.min(MAX_RESTART_WINDOW);
+        let _prev = self.restarts.write().await.insert(
+            adult,
+            PlannedRestart {
+                deadline,
+                away: false,
+            },
+        );
+    }
+
+    /// Adults still within their restart window.
+    pub(crate) async fn pending(&self) -> BTreeSet<XorName> {
+        let now = Instant::now();
+        self.restarts
+            .read()
+            .await
+            .iter()
+            .filter(|(_, restart)| restart.deadline > now)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    pub(crate) async fn is_pending(&self, adult: &XorName) -> bool {
+        self.restarts
+            .read()
+            .await
+            .get(adult)
+            .map_or(false, |restart| restart.deadline > Instant::now())
+    }
+
+    /// Notes that we couldn't reach `adult`, presumably as it's restarting.
+    pub(crate) async fn mark_away(&self, adult: &XorName) {
+        if let Some(restart) = self.restarts.write().await.get_mut(adult) {
+            restart.away = true;
+        }
+    }
+
+    /// Notes that we heard from `adult`, returning whether it's thus back from its restart.
+    pub(crate) async fn heard_from(&self, adult: &XorName) -> bool {
+        if self.restarts.read().await.is_empty() {
+            return false;
+        }
+        let mut restarts = self.restarts.write().await;
+        match restarts.get(adult) {
+            Some(restart) if restart.away => restarts.remove(adult).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Stops tracking `adult`, which won't be back.
+    pub(crate) async fn forget(&self, adult: &XorName) {
+        let _prev = self.restarts.write().await.remove(adult);
+    }
+
+    /// Stops tracking the adults whose window has run out, returning them.
+    pub(crate) async fn expire(&self) -> BTreeSet<XorName> {
+        let now = Instant::now();
+        let mut restarts = self.restarts.write().await;
+        let expired: BTreeSet<_> = restarts
+            .iter()
+            .filter(|(_, restart)| restart.deadline <= now)
+            .map(|(name, _)| *name)
+            .collect();
+        restarts.retain(|name, _| !expired.contains(name));
+        expired
+    }
+}
+
+impl Node {
+    /// Gets this adult ready to restart: flushes its dbs and tells its elders it'll be back
+    /// within `within`, so they neither propose it offline nor give it new data meanwhile. With
+    /// `drain`, the chunks it holds are also pushed to the adults that would replace it as their
+    /// holders, for them to fetch whatever copies they lack.
+    pub(crate) async fn prepare_restart(&self, within: Duration, drain: bool) -> Result<Vec<Cmd>> {
+        if self.is_elder().await {
+            // Elders leaving are replaced through a handover, which a short window can't skip.
+            warn!("Elders can't announce a restart to their section");
+            return Err(Error::InvalidState);
+        }
+        let within = within.min(MAX_RESTART_WINDOW);
+
+        self.data_storage.flush().await?;
+        self.write_prefix_map().await;
+
+        let node_id = PublicKey::from(self.info.read().await.keypair.public);
+        let msg = SystemMsg::NodeCmd(NodeCmd::PrepareRestart {
+            node_id,
+            section: XorName::from(node_id),
+            within,
+        });
+        let mut cmds = vec![self.send_msg_to_our_elders(msg).await?];
+        *self.restart_deadline.write().await = Some(Instant::now() + within);
+
+        if drain {
+            let our_name = XorName::from(node_id);
+            let remaining = self
+                .network_knowledge
+                .adults()
+                .await
+                .iter()
+                .map(|peer| peer.name())
+                .filter(|name| *name != our_name)
+                .collect();
+            cmds.extend(
+                self.reorganize_data(BTreeSet::new(), BTreeSet::from([our_name]), remaining)
+                    .await?,
+            );
+        }
+
+        info!("Prepared to restart within {:?}", within);
+        Ok(cmds)
+    }
+
+    /// Gets us ready to shut down for the restart we announced: flushes our dbs, without
+    /// telling our section we're going, as we'll be back within our window.
+    pub(crate) async fn prepare_resuming(&self) -> Result<()> {
+        self.data_storage.flush().await?;
+        self.write_prefix_map().await;
+        self.msg_filter.checkpoint().await?;
+        Ok(())
+    }
+
+    /// Whether we announced a restart that's still due.
+    pub(crate) async fn is_restart_pending(&self) -> bool {
+        self.restart_deadline
+            .read()
+            .await
+            .map_or(false, |deadline| deadline > Instant::now())
+    }
+
+    /// On elders, records an adult's announced restart.
+    pub(crate) async fn record_planned_restart(&self, adult: XorName, within: Duration) {
+        let adults = self.network_knowledge.adults().await;
+        if adults.iter().any(|peer| peer.name() == adult) {
+            info!("Adult {} will restart within {:?}", adult, within);
+            self.planned_restarts.announce(adult, within).await;
+        } else {
+            warn!(
+                "Ignoring restart announced by {}, which isn't one of our adults",
+                adult
+            );
+        }
+    }
+
+    /// Forgives the comm issues an adult back from its restart had while away.
+    pub(crate) async fn note_heard_from(&self, name: &XorName) {
+        if self.planned_restarts.heard_from(name).await {
+            info!("Adult {} is back from its restart", name);
+            self.dysfunction_tracking.clear_communication_issues(name);
+        }
+    }
+
+    /// Stops sparing the adults whose restart window ran out. Any comm issues they had meanwhile
+    /// now count against them.
+    pub(crate) async fn expire_restart_windows(&self) {
+        for adult in self.planned_restarts.expire().await {
+            warn!(
+                "Adult {} didn't come back within its restart window, handling it as usual",
+                adult
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_are_tracked_until_back_or_expired() {
+        let restarts = PlannedRestarts::default();
+        let (back, late) = (xor_name::rand::random(), xor_name::rand::random());
+
+        restarts.announce(back, Duration::from_secs(60)).await;
+        restarts.announce(late, Duration::from_secs(60)).await;
+        assert_eq!(restarts.pending().await, BTreeSet::from([back, late]));
+
+        // Hearing from an adult before it went away doesn't end its window.
+        assert!(!restarts.heard_from(&back).await);
+        restarts.mark_away(&back).await;
+        assert!(restarts.heard_from(&back).await);
+        assert!(!restarts.is_pending(&back).await);
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(!restarts.is_pending(&late).await);
+        assert_eq!(restarts.expire().await, BTreeSet::from([late]));
+        assert!(restarts.expire().await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn windows_are_capped() {
+        let restarts = PlannedRestarts::default();
+        let adult = xor_name::rand::random();
+
+        restarts
+            .announce(adult, Duration::from_secs(24 * 3600))
+            .await;
+        tokio::time::advance(MAX_RESTART_WINDOW).await;
+        assert_eq!(restarts.expire().await, BTreeSet::from([adult]));
+    }
+}
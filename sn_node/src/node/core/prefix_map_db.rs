@@ -0,0 +1,135 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise};
+use crate::node::Result;
+use crate::persisted;
+
+use sn_interface::network_knowledge::prefix_map::NetworkPrefixMap;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+const PREFIX_MAP_FILE: &str = "prefix_map";
+// Format of the prefix map file: a bincode `NetworkPrefixMap`.
+const PREFIX_MAP_VERSION: u16 = 1;
+
+/// Where the node keeps the latest prefix map it knows of in its root dir, for the elders in it
+/// to be contacted when bootstrapping again, even once the contacts it was first given are gone.
+#[derive(Clone, Debug)]
+pub(crate) struct PrefixMapDb {
+    root_dir: PathBuf,
+    // Has writes land in the order they were made, the latest map being the one left on disk.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl PrefixMapDb {
+    pub(crate) fn new(root_dir: &Path) -> Self {
+        Self {
+            root_dir: root_dir.to_path_buf(),
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.root_dir.join(PREFIX_MAP_FILE)
+    }
+
+    pub(crate) async fn write(&self, prefix_map: &NetworkPrefixMap) -> Result<()> {
+        let payload = serialise(prefix_map)?;
+        let _lock = self.write_lock.lock().await;
+        persisted::write_async(self.path(), PREFIX_MAP_VERSION, payload).await?;
+        Ok(())
+    }
+
+    /// Reads the prefix map last written of the network with `genesis_key`, if any. One which
+    /// can't be read, or is of another network, is left out, as the node can always bootstrap
+    /// from its hard-coded contacts instead.
+    pub(crate) async fn read(&self, genesis_key: bls::PublicKey) -> Option<NetworkPrefixMap> {
+        let prefix_map: NetworkPrefixMap = match persisted::read_async(self.path()).await {
+            Ok(Some(read)) if read.version == PREFIX_MAP_VERSION => {
+                match deserialise(&read.payload) {
+                    Ok(prefix_map) => prefix_map,
+                    Err(error) => {
+                        warn!("Ignoring the unreadable cached prefix map: {:?}", error);
+                        return None;
+                    }
+                }
+            }
+            Ok(Some(read)) => {
+                warn!(
+                    "Ignoring the cached prefix map of unknown version {}",
+                    read.version
+                );
+                return None;
+            }
+            Ok(None) => return None,
+            Err(error) => {
+                warn!("Ignoring the damaged cached prefix map: {}", error);
+                return None;
+            }
+        };
+
+        if prefix_map.genesis_key() != genesis_key {
+            warn!(
+                "Ignoring the cached prefix map of another network, with genesis key {:?}",
+                prefix_map.genesis_key()
+            );
+            return None;
+        }
+        Some(prefix_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persisted::damage;
+
+    use eyre::{eyre, Result};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn prefix_map_is_read_back_for_its_network_only() -> Result<()> {
+        let root_dir = tempdir()?;
+        let db = PrefixMapDb::new(root_dir.path());
+        let genesis_key = bls::SecretKey::random().public_key();
+        assert!(db.read(genesis_key).await.is_none());
+
+        db.write(&NetworkPrefixMap::new(genesis_key)).await?;
+        let read = db
+            .read(genesis_key)
+            .await
+            .ok_or_else(|| eyre!("prefix map not read back"))?;
+        assert_eq!(read.genesis_key(), genesis_key);
+
+        let other_network = bls::SecretKey::random().public_key();
+        assert!(db.read(other_network).await.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn damaged_prefix_map_is_ignored() -> Result<()> {
+        let root_dir = tempdir()?;
+        let db = PrefixMapDb::new(root_dir.path());
+        let genesis_key = bls::SecretKey::random().public_key();
+        db.write(&NetworkPrefixMap::new(genesis_key)).await?;
+
+        for way in 0..damage::ways(&db.path())? {
+            damage::apply(&db.path(), way)?;
+            assert!(db.read(genesis_key).await.is_none(), "damage {}", way);
+            db.write(&NetworkPrefixMap::new(genesis_key)).await?;
+        }
+
+        Ok(())
+    }
+}
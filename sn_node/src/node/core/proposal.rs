@@ -8,8 +8,12 @@
 
 use crate::node::{dkg::SigShare, Result};
 use sn_consensus::Generation;
-use sn_interface::messaging::system::{Proposal as ProposalMsg, SectionAuth};
+use sn_interface::messaging::{
+    data::StorageLevel,
+    system::{Proposal as ProposalMsg, SectionAuth},
+};
 use sn_interface::network_knowledge::{NodeState, SectionAuthorityProvider};
+use sn_interface::types::PublicKey;
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, PartialEq)]
@@ -21,6 +25,10 @@ pub(crate) enum Proposal {
     },
     NewElders(SectionAuth<SectionAuthorityProvider>),
     JoinsAllowed(bool),
+    StorageFull {
+        node: PublicKey,
+        level: StorageLevel,
+    },
 }
 
 impl Proposal {
@@ -45,11 +53,34 @@ impl Proposal {
             Self::SectionInfo { sap, generation: _ } => bincode::serialize(sap),
             Self::NewElders(info) => bincode::serialize(&info.sig.public_key),
             Self::JoinsAllowed(joins_allowed) => bincode::serialize(&joins_allowed),
+            Self::StorageFull { node, level } => bincode::serialize(&(node, level)),
         }?)
     }
 
+    /// Whether the proposal can be batched with others. Those of a new SAP aren't, their
+    /// signature being needed on its own.
+    pub(crate) fn is_batchable(&self) -> bool {
+        match self {
+            Self::Offline(_) | Self::JoinsAllowed(_) | Self::StorageFull { .. } => true,
+            Self::SectionInfo { .. } | Self::NewElders(_) => false,
+        }
+    }
+
     // Add conversion methods to/from `messaging::...::Proposal`
     // We prefer this over `From<...>` to make it easier to read the conversion.
+    pub(crate) fn from_msg(msg: ProposalMsg) -> Self {
+        match msg {
+            ProposalMsg::Offline(node_state) => Self::Offline(node_state.into_state()),
+            ProposalMsg::SectionInfo { sap, generation } => Self::SectionInfo {
+                sap: sap.into_state(),
+                generation,
+            },
+            ProposalMsg::NewElders(sap) => Self::NewElders(sap.into_authed_state()),
+            ProposalMsg::JoinsAllowed(allowed) => Self::JoinsAllowed(allowed),
+            ProposalMsg::StorageFull { node, level } => Self::StorageFull { node, level },
+        }
+    }
+
     pub(crate) fn into_msg(self) -> ProposalMsg {
         match self {
             Self::Offline(node_state) => ProposalMsg::Offline(node_state.to_msg()),
@@ -59,12 +90,66 @@ impl Proposal {
             },
             Self::NewElders(sap) => ProposalMsg::NewElders(sap.into_authed_msg()),
             Self::JoinsAllowed(allowed) => ProposalMsg::JoinsAllowed(allowed),
+            Self::StorageFull { node, level } => ProposalMsg::StorageFull { node, level },
         }
     }
 }
 
-// impl Proposal {
-// }
+/// Proposals raised together, e.g. several `Offline` ones on churn, signed and aggregated as
+/// one, for a single round of sig shares between the elders.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProposalBatch(Vec<Proposal>);
+
+impl ProposalBatch {
+    // Prefix of the signable bytes of a batch, for them never to be those of a lone proposal.
+    const SIGNABLE_DOMAIN: &'static [u8] = b"ProposalBatch";
+
+    /// Batches `proposals`, which must all be batchable, in the order given.
+    pub(crate) fn new(proposals: Vec<Proposal>) -> Self {
+        debug_assert!(proposals.iter().all(Proposal::is_batchable));
+        Self(proposals)
+    }
+
+    pub(crate) fn proposals(&self) -> &[Proposal] {
+        &self.0
+    }
+
+    pub(crate) fn into_proposals(self) -> Vec<Proposal> {
+        self.0
+    }
+
+    /// Create SigShare for the whole batch.
+    pub(crate) fn sign_with_key_share(
+        &self,
+        public_key_set: bls::PublicKeySet,
+        index: usize,
+        secret_key_share: &bls::SecretKeyShare,
+    ) -> Result<SigShare> {
+        Ok(SigShare::new(
+            public_key_set,
+            index,
+            secret_key_share,
+            &self.as_signable_bytes()?,
+        ))
+    }
+
+    pub(crate) fn as_signable_bytes(&self) -> Result<Vec<u8>> {
+        let proposals = self
+            .0
+            .iter()
+            .map(Proposal::as_signable_bytes)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(bincode::serialize(&(Self::SIGNABLE_DOMAIN, proposals))?)
+    }
+
+    pub(crate) fn from_msg(msgs: Vec<ProposalMsg>) -> Self {
+        Self(msgs.into_iter().map(Proposal::from_msg).collect())
+    }
+
+    pub(crate) fn into_msg(self) -> Vec<ProposalMsg> {
+        self.0.into_iter().map(Proposal::into_msg).collect()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -96,6 +181,36 @@ mod tests {
         let proposal = Proposal::NewElders(section_signed_auth);
         verify_serialize_for_signing(&proposal, &new_pk)?;
 
+        // Proposal::StorageFull
+        let node = PublicKey::from(bls::SecretKey::random().public_key());
+        let level = StorageLevel::from(StorageLevel::MAX)?;
+        let proposal = Proposal::StorageFull { node, level };
+        verify_serialize_for_signing(&proposal, &(node, level))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn batches_are_signed_as_a_whole() -> Result<()> {
+        let node = PublicKey::from(bls::SecretKey::random().public_key());
+        let storage_full = Proposal::StorageFull {
+            node,
+            level: StorageLevel::from(StorageLevel::MAX)?,
+        };
+        let joins_allowed = Proposal::JoinsAllowed(true);
+
+        let batch = ProposalBatch::new(vec![storage_full.clone(), joins_allowed.clone()]);
+        let reordered = ProposalBatch::new(vec![joins_allowed.clone(), storage_full]);
+        let lone = ProposalBatch::new(vec![joins_allowed.clone()]);
+        assert_ne!(batch.as_signable_bytes()?, reordered.as_signable_bytes()?);
+        assert_ne!(
+            lone.as_signable_bytes()?,
+            joins_allowed.as_signable_bytes()?
+        );
+
+        let back = ProposalBatch::from_msg(batch.clone().into_msg());
+        assert_eq!(back, batch);
+        assert_eq!(back.as_signable_bytes()?, batch.as_signable_bytes()?);
         Ok(())
     }
 
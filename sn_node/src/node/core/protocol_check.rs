@@ -0,0 +1,40 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+
+use sn_interface::protocol_constants::ProtocolDigest;
+
+impl Node {
+    /// The digest of our section's protocol constants, if it isn't the one of ours, as after
+    /// we were upgraded to a build differing from our section's in one of them.
+    pub(crate) async fn protocol_mismatch(&self) -> Option<ProtocolDigest> {
+        let section = self
+            .network_knowledge
+            .authority_provider()
+            .await
+            .protocol_digest();
+        (section != self.protocol_digest).then(|| section)
+    }
+
+    /// Whether we keep out of `activity`, running with other protocol constants than our
+    /// section, until we run with the same ones again.
+    pub(crate) async fn sits_out(&self, activity: &str) -> bool {
+        match self.protocol_mismatch().await {
+            Some(section) => {
+                error!(
+                    "Not taking part in {}: we run with other protocol constants than our \
+                    section, ours {}, the section's {}",
+                    activity, self.protocol_digest, section
+                );
+                true
+            }
+            None => false,
+        }
+    }
+}
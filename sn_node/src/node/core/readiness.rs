@@ -0,0 +1,236 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+
+use std::{collections::BTreeSet, fmt::Write};
+
+/// A condition keeping the node from serving client reads, writes, or both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Condition {
+    /// A background task of the node failed for good.
+    BackgroundTaskFailed,
+    /// The node isn't a member of a section (yet, or anymore).
+    Standby,
+    /// The node's elders are being handed over to new ones.
+    Handover,
+    /// The node's runtime is too busy to handle msgs in time.
+    EventLoopLag,
+    /// The elder is rebuilding its registry of which adults hold which data.
+    RegistryRebuilding,
+    /// The node's disk is failing its self-tests.
+    StorageUnhealthy,
+    /// The node's storage is close to its capacity.
+    StorageFull,
+    /// The node is about to restart.
+    Maintenance,
+    /// The node runs with other protocol constants than its section.
+    ProtocolMismatch,
+}
+
+impl Condition {
+    const ALL: [Self; 9] = [
+        Self::BackgroundTaskFailed,
+        Self::Standby,
+        Self::Handover,
+        Self::EventLoopLag,
+        Self::RegistryRebuilding,
+        Self::StorageUnhealthy,
+        Self::StorageFull,
+        Self::Maintenance,
+        Self::ProtocolMismatch,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::BackgroundTaskFailed => "background_task_failed",
+            Self::Standby => "standby",
+            Self::Handover => "handover",
+            Self::EventLoopLag => "event_loop_lag",
+            Self::RegistryRebuilding => "registry_rebuilding",
+            Self::StorageUnhealthy => "storage_unhealthy",
+            Self::StorageFull => "storage_full",
+            Self::Maintenance => "maintenance",
+            Self::ProtocolMismatch => "protocol_mismatch",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::BackgroundTaskFailed => "a background task of the node failed for good",
+            Self::Standby => "the node isn't a member of a section",
+            Self::Handover => "the section's elders are being handed over",
+            Self::EventLoopLag => "the node's runtime lags behind",
+            Self::RegistryRebuilding => "the elder is rebuilding its registry of data holders",
+            Self::StorageUnhealthy => "the node's disk is failing its self-tests",
+            Self::StorageFull => "the node's storage is full",
+            Self::Maintenance => "the node is about to restart",
+            Self::ProtocolMismatch => {
+                "the node runs with other protocol constants than its section"
+            }
+        }
+    }
+
+    /// Whether the condition keeps the node from serving client reads.
+    pub(crate) fn blocks_reads(&self) -> bool {
+        match self {
+            Self::BackgroundTaskFailed
+            | Self::Standby
+            | Self::Handover
+            | Self::EventLoopLag
+            | Self::RegistryRebuilding => true,
+            // What's stored can still be read.
+            Self::StorageUnhealthy
+            | Self::StorageFull
+            | Self::Maintenance
+            | Self::ProtocolMismatch => false,
+        }
+    }
+
+    /// Whether the condition keeps the node from serving client writes.
+    pub(crate) fn blocks_writes(&self) -> bool {
+        match self {
+            Self::BackgroundTaskFailed
+            | Self::Standby
+            | Self::Handover
+            | Self::EventLoopLag
+            | Self::StorageUnhealthy
+            | Self::StorageFull
+            | Self::Maintenance
+            | Self::ProtocolMismatch => true,
+            // New data is placed by closeness regardless of the registry.
+            Self::RegistryRebuilding => false,
+        }
+    }
+}
+
+/// Whether the node can serve client reads and writes, and what's keeping it from it if not.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Readiness {
+    pub(crate) conditions: BTreeSet<Condition>,
+}
+
+impl Readiness {
+    pub(crate) fn can_read(&self) -> bool {
+        !self.conditions.iter().any(Condition::blocks_reads)
+    }
+
+    pub(crate) fn can_write(&self) -> bool {
+        !self.conditions.iter().any(Condition::blocks_writes)
+    }
+
+    /// The readiness of old, before reads and writes were told apart.
+    pub(crate) fn is_ready(&self) -> bool {
+        self.can_read() && self.can_write()
+    }
+
+    /// Renders the readiness for operators, listing the conditions each indicator is derived
+    /// from and which of them currently hold.
+    pub(crate) fn report(&self) -> String {
+        let mut report = String::new();
+        let mut indicator = |name, available, blocks: fn(&Condition) -> bool| {
+            let status = if available {
+                "available"
+            } else {
+                "unavailable"
+            };
+            let _ = writeln!(report, "{}: {}", name, status);
+            for condition in Condition::ALL.iter().filter(|condition| blocks(condition)) {
+                let state = if self.conditions.contains(condition) {
+                    "FAILING"
+                } else {
+                    "ok"
+                };
+                let _ = writeln!(
+                    report,
+                    "  {}: {} ({})",
+                    condition.name(),
+                    state,
+                    condition.description()
+                );
+            }
+        };
+        indicator("read", self.can_read(), Condition::blocks_reads);
+        indicator("write", self.can_write(), Condition::blocks_writes);
+        let _ = writeln!(report, "ready: {}", self.is_ready());
+        report
+    }
+}
+
+impl Node {
+    /// The conditions of the node itself keeping it from serving clients.
+    pub(crate) async fn readiness_conditions(&self) -> BTreeSet<Condition> {
+        let mut conditions = BTreeSet::new();
+        let mut hold = |condition, holds| {
+            if holds {
+                let _new = conditions.insert(condition);
+            }
+        };
+
+        let our_name = self.info.read().await.name();
+        hold(
+            Condition::Standby,
+            !self.network_knowledge.is_section_member(&our_name).await,
+        );
+        let is_elder = self.is_elder().await;
+        hold(
+            Condition::Handover,
+            is_elder && self.is_handover_underway().await,
+        );
+        hold(
+            Condition::RegistryRebuilding,
+            is_elder && self.holder_registry.is_rebuilding().await,
+        );
+        hold(
+            Condition::StorageUnhealthy,
+            !self.data_storage.disk_health().is_healthy().await,
+        );
+        hold(Condition::StorageFull, self.data_storage.is_full().await);
+        hold(Condition::Maintenance, self.is_restart_pending().await);
+        hold(
+            Condition::ProtocolMismatch,
+            self.protocol_mismatch().await.is_some(),
+        );
+
+        conditions
+    }
+
+    // Whether elders are voting on handing over to new ones, without a decision yet.
+    async fn is_handover_underway(&self) -> bool {
+        self.handover_voting
+            .read()
+            .await
+            .as_ref()
+            .map_or(false, |handover| {
+                !handover.consensus.votes.is_empty() && handover.consensus.decision.is_none()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_conditions_only_block_writes() {
+        let readiness = Readiness {
+            conditions: BTreeSet::from([Condition::StorageFull, Condition::Maintenance]),
+        };
+
+        assert!(readiness.can_read());
+        assert!(!readiness.can_write());
+        assert!(!readiness.is_ready());
+
+        let report = readiness.report();
+        assert!(report.contains("read: available\n"));
+        assert!(report.contains("write: unavailable\n"));
+        assert!(report.contains("  storage_full: FAILING"));
+        assert!(report.contains("  maintenance: FAILING"));
+        assert!(report.ends_with("ready: false\n"));
+    }
+}
@@ -0,0 +1,677 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node::{api::cmds::Cmd, core::Node, messages::WireMsgUtils, Result};
+
+use sn_interface::{
+    messaging::{
+        system::{RelayMsg, RelayRefusal, SystemMsg},
+        DstLocation, WireMsg,
+    },
+    types::Peer,
+};
+
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::Instant};
+use xor_name::XorName;
+
+/// Longest a relay registration holds without being renewed.
+pub(crate) const RELAY_LEASE: Duration = Duration::from_secs(120);
+/// How often relayed nodes renew their registration, and relays forget the lapsed ones.
+pub(crate) const RELAY_RENEWAL_INTERVAL: Duration = Duration::from_secs(30);
+/// Nodes relayed for at most when unspecified.
+pub(crate) const DEFAULT_RELAY_MAX_PEERS: usize = 8;
+/// KiB per second relayed for any one node at most when unspecified, either way together.
+pub(crate) const DEFAULT_RELAY_KBPS: u64 = 512;
+
+/// How much we relay for others. No peers at all disables relaying.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RelayLimits {
+    pub(crate) max_peers: usize,
+    pub(crate) bytes_per_sec: u64,
+}
+
+impl Default for RelayLimits {
+    fn default() -> Self {
+        Self {
+            max_peers: DEFAULT_RELAY_MAX_PEERS,
+            bytes_per_sec: DEFAULT_RELAY_KBPS * 1024,
+        }
+    }
+}
+
+/// What we relayed for others since we started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct RelayStats {
+    /// Nodes we relay for right now.
+    pub(crate) relayed_peers: usize,
+    pub(crate) forwarded_msgs: u64,
+    pub(crate) forwarded_bytes: u64,
+    /// Msgs dropped for going over the rate relayed for their node.
+    pub(crate) throttled_msgs: u64,
+    pub(crate) refused_registrations: u64,
+}
+
+/// What's to be done with a msg for or from a node we may relay for.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Relaying {
+    /// We don't relay for the node.
+    NotRelayed,
+    /// The msg is to be passed on, the node being at the address of the `Peer` given.
+    Forward(Peer),
+    /// The node went over its rate, the msg is to be dropped.
+    Throttled,
+}
+
+#[derive(Debug)]
+struct Registration {
+    // The node, at the address it connected to us from.
+    peer: Peer,
+    expires: Instant,
+    // Bytes which can be relayed right away, refilled at the rate relayed for each node.
+    allowance: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ServiceState {
+    limits: RelayLimits,
+    registrations: BTreeMap<XorName, Registration>,
+    stats: RelayStats,
+}
+
+/// The nodes we relay msgs for: those the network can't connect to, which keep a connection
+/// open to us instead. Msgs reaching us for them are passed on over that connection, and theirs
+/// are sent on from our address, each node's either way together being held to a rate.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RelayService {
+    state: Arc<RwLock<ServiceState>>,
+}
+
+impl RelayService {
+    pub(crate) async fn set_limits(&self, limits: RelayLimits) {
+        info!("Relaying for others limited to {:?}", limits);
+        self.state.write().await.limits = limits;
+    }
+
+    /// Registers `peer` to be relayed for, or renews its registration, for `lease` at most.
+    /// Returns how long the registration holds.
+    pub(crate) async fn register(
+        &self,
+        peer: Peer,
+        lease: Duration,
+        now: Instant,
+    ) -> std::result::Result<Duration, RelayRefusal> {
+        let mut state = self.state.write().await;
+        let limits = state.limits;
+        let refusal = if limits.max_peers == 0 {
+            Some(RelayRefusal::Disabled)
+        } else if !state.registrations.contains_key(&peer.name())
+            && state.registrations.len() >= limits.max_peers
+        {
+            Some(RelayRefusal::Full)
+        } else {
+            None
+        };
+        if let Some(refusal) = refusal {
+            state.stats.refused_registrations += 1;
+            return Err(refusal);
+        }
+
+        let lease = lease.min(RELAY_LEASE);
+        let registration = state
+            .registrations
+            .entry(peer.name())
+            .or_insert_with(|| Registration {
+                peer,
+                expires: now,
+                allowance: limits.bytes_per_sec as f64,
+                refilled_at: now,
+            });
+        // The node may have come back through another port of its NAT.
+        registration.peer = peer;
+        registration.expires = now + lease;
+        Ok(lease)
+    }
+
+    /// The connection of the node `name`, if we relay for it.
+    pub(crate) async fn connection(&self, name: &XorName) -> Option<Peer> {
+        self.state
+            .read()
+            .await
+            .registrations
+            .get(name)
+            .map(|registration| registration.peer)
+    }
+
+    pub(crate) async fn deregister(&self, name: &XorName) {
+        let _prev = self.state.write().await.registrations.remove(name);
+    }
+
+    /// Accounts for a msg of `bytes` for or from the node `name`, telling what's to be done
+    /// with it.
+    pub(crate) async fn admit(&self, name: &XorName, bytes: usize, now: Instant) -> Relaying {
+        let mut state = self.state.write().await;
+        let rate = state.limits.bytes_per_sec as f64;
+        let registration = match state.registrations.get_mut(name) {
+            Some(registration) if registration.expires > now => registration,
+            _ => return Relaying::NotRelayed,
+        };
+
+        let elapsed = now.saturating_duration_since(registration.refilled_at);
+        registration.allowance = (registration.allowance + elapsed.as_secs_f64() * rate).min(rate);
+        registration.refilled_at = now;
+        // A msg larger than a second's worth goes once the allowance is full, leaving a debt.
+        if registration.allowance < (bytes as f64).min(rate) {
+            state.stats.throttled_msgs += 1;
+            return Relaying::Throttled;
+        }
+        registration.allowance -= bytes as f64;
+        let peer = registration.peer;
+
+        state.stats.forwarded_msgs += 1;
+        state.stats.forwarded_bytes += bytes as u64;
+        Relaying::Forward(peer)
+    }
+
+    /// Forgets the nodes whose registration lapsed, returning them.
+    pub(crate) async fn expire(&self, now: Instant) -> BTreeSet<XorName> {
+        let mut state = self.state.write().await;
+        let lapsed: BTreeSet<_> = state
+            .registrations
+            .iter()
+            .filter(|(_, registration)| registration.expires <= now)
+            .map(|(name, _)| *name)
+            .collect();
+        state.registrations.retain(|name, _| !lapsed.contains(name));
+        lapsed
+    }
+
+    pub(crate) async fn stats(&self) -> RelayStats {
+        let state = self.state.read().await;
+        RelayStats {
+            relayed_peers: state.registrations.len(),
+            ..state.stats
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Selection {
+    // The relay we're registered with.
+    current: Option<Peer>,
+    // Until when it confirmed it relays for us.
+    confirmed_until: Option<Instant>,
+    // The relay we asked last, whether or not it's taken us yet.
+    asked: Option<Peer>,
+    // Relays asked since one last took us, not to be asked again until all were.
+    tried: BTreeSet<XorName>,
+}
+
+/// The peer relaying our msgs, when the network can't connect to us, and those we asked to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OurRelay {
+    selection: Arc<RwLock<Selection>>,
+}
+
+impl OurRelay {
+    /// The relay we're registered with, if any.
+    pub(crate) async fn current(&self) -> Option<Peer> {
+        self.selection.read().await.current
+    }
+
+    /// Whether we ever needed a relay, and so still do.
+    pub(crate) async fn is_needed(&self) -> bool {
+        let selection = self.selection.read().await;
+        selection.current.is_some() || selection.asked.is_some()
+    }
+
+    /// Picks the relay to ask next among `candidates`, preferring those not asked since one
+    /// last took us. Records it as asked.
+    pub(crate) async fn next(&self, candidates: &[Peer]) -> Option<Peer> {
+        let mut selection = self.selection.write().await;
+        let untried = |selection: &Selection| {
+            candidates
+                .iter()
+                .find(|peer| !selection.tried.contains(&peer.name()))
+                .copied()
+        };
+        let next = match untried(&selection) {
+            Some(next) => Some(next),
+            None => {
+                selection.tried.clear();
+                untried(&selection)
+            }
+        };
+        if let Some(next) = next {
+            let _new = selection.tried.insert(next.name());
+            selection.asked = Some(next);
+        }
+        next
+    }
+
+    /// Whether `relay` is the one we asked last.
+    pub(crate) async fn was_asked(&self, relay: &XorName) -> bool {
+        matches!(self.selection.read().await.asked, Some(asked) if asked.name() == *relay)
+    }
+
+    /// Records that `relay` took us for `lease`.
+    pub(crate) async fn registered(&self, relay: Peer, lease: Duration, now: Instant) {
+        let mut selection = self.selection.write().await;
+        if selection.current.map(|current| current.name()) != Some(relay.name()) {
+            info!("Our msgs are relayed by {}", relay);
+        }
+        selection.current = Some(relay);
+        selection.confirmed_until = Some(now + lease);
+        selection.tried.clear();
+    }
+
+    /// Stops using `relay`, which refused us or stopped confirming it relays for us.
+    pub(crate) async fn lost(&self, relay: &XorName) {
+        let mut selection = self.selection.write().await;
+        if selection.current.map(|current| current.name()) == Some(*relay) {
+            warn!("Our relay {} no longer relays for us", relay);
+            selection.current = None;
+            selection.confirmed_until = None;
+        }
+    }
+
+    /// Whether our relay's registration lapsed without being renewed.
+    pub(crate) async fn has_lapsed(&self, now: Instant) -> bool {
+        let selection = self.selection.read().await;
+        selection.current.is_some()
+            && !matches!(selection.confirmed_until, Some(confirmed_until) if confirmed_until > now)
+    }
+}
+
+impl Node {
+    /// Handles a msg between a node the network can't connect to and its relay, on either end.
+    pub(crate) async fn handle_relay_msg(&self, sender: Peer, msg: RelayMsg) -> Result<Vec<Cmd>> {
+        match msg {
+            RelayMsg::Register { lease } => {
+                let reply = if self.our_relay.current().await.is_some() {
+                    // We can't be reached ourselves.
+                    Err(RelayRefusal::Disabled)
+                } else {
+                    self.relay_service
+                        .register(sender, lease, Instant::now())
+                        .await
+                };
+                let reply = match reply {
+                    Ok(lease) => {
+                        debug!("Relaying for {} for {:?}", sender, lease);
+                        RelayMsg::Registered { lease }
+                    }
+                    Err(refusal) => {
+                        debug!("Refusing to relay for {}: {:?}", sender, refusal);
+                        RelayMsg::Refused(refusal)
+                    }
+                };
+                Ok(vec![self.send_relay_msg(sender, reply).await?])
+            }
+            RelayMsg::Registered { lease } => {
+                if self.our_relay.was_asked(&sender.name()).await {
+                    self.our_relay
+                        .registered(sender, lease, Instant::now())
+                        .await;
+                } else {
+                    warn!("Ignoring a relay registration we didn't ask {} for", sender);
+                }
+                Ok(vec![])
+            }
+            RelayMsg::Refused(refusal) => {
+                if !self.our_relay.was_asked(&sender.name()).await {
+                    return Ok(vec![]);
+                }
+                info!("{} won't relay for us: {:?}", sender, refusal);
+                self.our_relay.lost(&sender.name()).await;
+                self.ask_next_relay().await
+            }
+            RelayMsg::Deregister => {
+                self.relay_service.deregister(&sender.name()).await;
+                Ok(vec![])
+            }
+            RelayMsg::Forward { recipients, msg } => self.forward(sender, recipients, msg).await,
+        }
+    }
+
+    /// Passes on a msg for a node we relay for, returning `None` if we don't, for the msg to be
+    /// handled as usual.
+    pub(crate) async fn relay_inbound(&self, wire_msg: &WireMsg) -> Option<Vec<Cmd>> {
+        let dst = wire_msg.dst_location().name();
+        if dst == self.info.read().await.name() {
+            return None;
+        }
+        let bytes = wire_msg.payload.len();
+        match self.relay_service.admit(&dst, bytes, Instant::now()).await {
+            Relaying::NotRelayed => None,
+            Relaying::Forward(peer) => {
+                trace!("Relaying {:?} to {}", wire_msg.msg_id(), peer);
+                Some(vec![Cmd::SendMsg {
+                    recipients: vec![peer],
+                    wire_msg: wire_msg.clone(),
+                }])
+            }
+            Relaying::Throttled => {
+                debug!(
+                    "Dropping {:?} for {}, over the rate we relay for it",
+                    wire_msg.msg_id(),
+                    dst
+                );
+                Some(vec![])
+            }
+        }
+    }
+
+    /// Has `wire_msg` go through our relay, if we have one, returning the recipients and msg
+    /// to send instead.
+    pub(crate) async fn via_our_relay(
+        &self,
+        recipients: &[Peer],
+        wire_msg: WireMsg,
+    ) -> Result<(Vec<Peer>, WireMsg)> {
+        let relay = match self.our_relay.current().await {
+            Some(relay) => relay,
+            None => return Ok((recipients.to_vec(), wire_msg)),
+        };
+        if matches!(
+            wire_msg.into_msg(),
+            Ok(sn_interface::messaging::MsgType::System {
+                msg: SystemMsg::Relay(_),
+                ..
+            })
+        ) {
+            return Ok((recipients.to_vec(), wire_msg));
+        }
+        let msg = RelayMsg::Forward {
+            recipients: recipients.to_vec(),
+            msg: wire_msg.serialize()?,
+        };
+        let wire_msg = self.relay_wire_msg(relay, msg).await?;
+        Ok((vec![relay], wire_msg))
+    }
+
+    /// Renews our relay registration, failing over to another relay if ours lapsed, and
+    /// forgets the nodes we relayed for whose registration lapsed.
+    pub(crate) async fn renew_relays(&self) -> Result<Vec<Cmd>> {
+        let now = Instant::now();
+        for name in self.relay_service.expire(now).await {
+            debug!("No longer relaying for {}, its registration lapsed", name);
+        }
+
+        if !self.our_relay.is_needed().await {
+            return Ok(vec![]);
+        }
+        if let Some(relay) = self.our_relay.current().await {
+            if !self.our_relay.has_lapsed(now).await {
+                let msg = RelayMsg::Register { lease: RELAY_LEASE };
+                return Ok(vec![self.send_relay_msg(relay, msg).await?]);
+            }
+            self.our_relay.lost(&relay.name()).await;
+        }
+        // Our section keeps sending to the address we joined with, so failing over only keeps
+        // our own msgs going, until we join again through the new relay.
+        self.ask_next_relay().await
+    }
+
+    // Asks the next of our section's members to relay for us.
+    async fn ask_next_relay(&self) -> Result<Vec<Cmd>> {
+        let our_name = self.info.read().await.name();
+        let mut candidates = self.network_knowledge.elders().await;
+        candidates.extend(self.network_knowledge.adults().await);
+        candidates.retain(|peer| peer.name() != our_name);
+
+        match self.our_relay.next(&candidates).await {
+            Some(relay) => {
+                info!("Asking {} to relay for us", relay);
+                let msg = RelayMsg::Register { lease: RELAY_LEASE };
+                Ok(vec![self.send_relay_msg(relay, msg).await?])
+            }
+            None => {
+                warn!("No peer left to ask to relay for us");
+                Ok(vec![])
+            }
+        }
+    }
+
+    // Sends on a msg from a node we relay for.
+    async fn forward(&self, sender: Peer, recipients: Vec<Peer>, msg: Bytes) -> Result<Vec<Cmd>> {
+        let relaying = self
+            .relay_service
+            .admit(&sender.name(), msg.len(), Instant::now())
+            .await;
+        match relaying {
+            Relaying::Forward(_) => {}
+            Relaying::NotRelayed => {
+                warn!(
+                    "Dropping a msg to forward from {}, not relayed by us",
+                    sender
+                );
+                return Ok(vec![]);
+            }
+            Relaying::Throttled => {
+                debug!(
+                    "Dropping a msg to forward from {}, over the rate we relay for it",
+                    sender
+                );
+                return Ok(vec![]);
+            }
+        }
+
+        let wire_msg = WireMsg::from(msg)?;
+        // Only the node's own msgs are sent on, so no one can have us send theirs anywhere.
+        if wire_msg.msg_kind().src().name() != sender.name() {
+            warn!(
+                "Dropping a msg to forward from {}, which isn't its own: {:?}",
+                sender,
+                wire_msg.msg_id()
+            );
+            return Ok(vec![]);
+        }
+
+        // The node sees the peers whose msgs we relayed to it at our address, as the network
+        // does the other nodes we relay for, so those are looked up by name.
+        let our_info = self.info.read().await.clone();
+        let mut resolved = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            if recipient.addr() != our_info.addr || recipient.name() == our_info.name() {
+                resolved.push(recipient);
+            } else if let Some(peer) = self.relay_service.connection(&recipient.name()).await {
+                resolved.push(peer);
+            } else if let Some(peer) = self.lookup_peer(&recipient.name()).await {
+                resolved.push(peer);
+            } else {
+                debug!(
+                    "Not forwarding to {}, which we don't know",
+                    recipient.name()
+                );
+            }
+        }
+
+        trace!(
+            "Forwarding {:?} from {} to {:?}",
+            wire_msg.msg_id(),
+            sender,
+            resolved
+        );
+        Ok(vec![Cmd::SendMsg {
+            recipients: resolved,
+            wire_msg,
+        }])
+    }
+
+    // The address of the node `name`, if it's a member of our section or an elder we know of.
+    async fn lookup_peer(&self, name: &XorName) -> Option<Peer> {
+        if let Some(member) = self.network_knowledge.get_section_member(name).await {
+            return Some(*member.peer());
+        }
+        self.network_knowledge
+            .section_by_name(name)
+            .ok()?
+            .elders()
+            .find(|peer| peer.name() == *name)
+            .copied()
+    }
+
+    async fn relay_wire_msg(&self, recipient: Peer, msg: RelayMsg) -> Result<WireMsg> {
+        WireMsg::single_src(
+            &self.info.read().await.clone(),
+            DstLocation::Node {
+                name: recipient.name(),
+                section_pk: self.network_knowledge.section_key().await,
+            },
+            SystemMsg::Relay(msg),
+            self.network_knowledge.section_key().await,
+        )
+    }
+
+    async fn send_relay_msg(&self, recipient: Peer, msg: RelayMsg) -> Result<Cmd> {
+        Ok(Cmd::SendMsg {
+            recipients: vec![recipient],
+            wire_msg: self.relay_wire_msg(recipient, msg).await?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sn_interface::network_knowledge::test_utils::gen_addr;
+
+    fn peer() -> Peer {
+        Peer::new(xor_name::rand::random(), gen_addr())
+    }
+
+    #[tokio::test]
+    async fn relays_take_as_many_nodes_as_they_may_for_as_long_as_they_renew() {
+        let service = RelayService::default();
+        service
+            .set_limits(RelayLimits {
+                max_peers: 1,
+                bytes_per_sec: 1000,
+            })
+            .await;
+        let (relayed, other) = (peer(), peer());
+        let now = Instant::now();
+
+        assert_eq!(
+            service
+                .register(relayed, Duration::from_secs(600), now)
+                .await,
+            Ok(RELAY_LEASE)
+        );
+        assert_eq!(
+            service.register(other, RELAY_LEASE, now).await,
+            Err(RelayRefusal::Full)
+        );
+        assert_eq!(
+            service.admit(&relayed.name(), 10, now).await,
+            Relaying::Forward(relayed)
+        );
+        assert_eq!(
+            service.admit(&other.name(), 10, now).await,
+            Relaying::NotRelayed
+        );
+
+        // Renewed, it holds on.
+        let later = now + RELAY_LEASE / 2;
+        assert!(service.register(relayed, RELAY_LEASE, later).await.is_ok());
+        assert!(service.expire(now + RELAY_LEASE).await.is_empty());
+        // Not renewed, it lapses, making room for others.
+        let lapsed = later + RELAY_LEASE;
+        assert_eq!(
+            service.admit(&relayed.name(), 10, lapsed).await,
+            Relaying::NotRelayed
+        );
+        assert_eq!(
+            service.expire(lapsed).await,
+            BTreeSet::from([relayed.name()])
+        );
+        assert!(service.register(other, RELAY_LEASE, lapsed).await.is_ok());
+
+        service
+            .set_limits(RelayLimits {
+                max_peers: 0,
+                bytes_per_sec: 1000,
+            })
+            .await;
+        assert_eq!(
+            service.register(relayed, RELAY_LEASE, lapsed).await,
+            Err(RelayRefusal::Disabled)
+        );
+        let stats = service.stats().await;
+        assert_eq!(stats.relayed_peers, 1);
+        assert_eq!(stats.forwarded_msgs, 1);
+        assert_eq!(stats.refused_registrations, 2);
+    }
+
+    #[tokio::test]
+    async fn nodes_relayed_over_their_rate_are_throttled() {
+        let service = RelayService::default();
+        service
+            .set_limits(RelayLimits {
+                max_peers: 1,
+                bytes_per_sec: 1000,
+            })
+            .await;
+        let relayed = peer();
+        let now = Instant::now();
+        assert!(service.register(relayed, RELAY_LEASE, now).await.is_ok());
+
+        // A second's worth goes right away, a larger msg too but leaving a debt.
+        assert_eq!(
+            service.admit(&relayed.name(), 5000, now).await,
+            Relaying::Forward(relayed)
+        );
+        assert_eq!(
+            service.admit(&relayed.name(), 10, now).await,
+            Relaying::Throttled
+        );
+        let paid_off = now + Duration::from_secs(5);
+        assert_eq!(
+            service.admit(&relayed.name(), 10, paid_off).await,
+            Relaying::Forward(relayed)
+        );
+        assert_eq!(service.stats().await.throttled_msgs, 1);
+    }
+
+    #[tokio::test]
+    async fn relayed_nodes_fail_over_to_relays_not_tried_yet() {
+        let our_relay = OurRelay::default();
+        let candidates = [peer(), peer(), peer()];
+        let now = Instant::now();
+        assert!(!our_relay.is_needed().await);
+
+        let first = our_relay.next(&candidates).await;
+        assert_eq!(first, Some(candidates[0]));
+        assert!(our_relay.is_needed().await);
+        assert!(our_relay.current().await.is_none());
+
+        // Refused, the next one is asked.
+        assert_eq!(our_relay.next(&candidates).await, Some(candidates[1]));
+        our_relay.registered(candidates[1], RELAY_LEASE, now).await;
+        assert_eq!(our_relay.current().await, Some(candidates[1]));
+        assert!(!our_relay.has_lapsed(now).await);
+
+        // Lapsed, all are candidates again, as one took us since they were tried.
+        assert!(our_relay.has_lapsed(now + RELAY_LEASE).await);
+        our_relay.lost(&candidates[1].name()).await;
+        assert!(our_relay.current().await.is_none());
+        assert_eq!(our_relay.next(&candidates).await, Some(candidates[0]));
+        assert_eq!(our_relay.next(&candidates).await, Some(candidates[1]));
+        assert_eq!(our_relay.next(&candidates).await, Some(candidates[2]));
+        // All tried, they're asked over again.
+        assert_eq!(our_relay.next(&candidates).await, Some(candidates[0]));
+    }
+}
@@ -8,19 +8,32 @@
 
 //! Relocation related types and utilities.
 
-use sn_interface::elder_count;
-use sn_interface::messaging::system::RelocateDetails;
-use sn_interface::network_knowledge::{recommended_section_size, NetworkKnowledge, NodeState};
+use sn_interface::messaging::system::{JoinRejectionReason, RelocateDetails};
+use sn_interface::network_knowledge::{NetworkKnowledge, NodeState};
 use sn_interface::types::{keys::ed25519, Peer};
 
 use ed25519_dalek::{Signature, Verifier};
 use std::{
     cmp::min,
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::{self, Display, Formatter},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use tokio::sync::RwLock;
 use xor_name::XorName;
 
+/// Default for how long after being issued a relocation can still be joined with.
+pub(crate) const DEFAULT_RELOCATION_VALIDITY: Duration = Duration::from_secs(30 * 60);
+/// Default for how many generations the key a relocation was issued under can be behind the key
+/// of the section it's joined with.
+pub(crate) const DEFAULT_RELOCATION_MAX_KEY_LAG: u64 = 8;
+// Relocations are stamped to the minute, as each elder stamps the relocation it proposes and
+// their signature shares only aggregate if they agree on it.
+const RELOCATION_TIMESTAMP_GRANULARITY_SECS: u64 = 60;
+// Relocations remembered as used to join, beyond which the oldest ones are forgotten.
+const MAX_CONSUMED_RELOCATIONS: usize = 10_000;
+
 // Unique identifier for a churn event, which is used to select nodes to relocate.
 pub(crate) struct ChurnId(pub(crate) Vec<u8>);
 
@@ -44,14 +57,15 @@ pub(super) async fn find_nodes_to_relocate(
     // relocating too many nodes at the same time.
     // Capped by criteria that cannot relocate too many node at once.
     let joined_nodes = network_knowledge.section_members().await;
+    let network_params = network_knowledge.network_params().await;
 
-    if joined_nodes.len() < recommended_section_size() {
+    if joined_nodes.len() < network_params.recommended_section_size() {
         return vec![];
     }
 
-    let max_reloctions = elder_count() / 2;
+    let max_reloctions = network_params.elder_count() / 2;
     let allowed_relocations = min(
-        joined_nodes.len() - recommended_section_size(),
+        joined_nodes.len() - network_params.recommended_section_size(),
         max_reloctions,
     );
 
@@ -73,13 +87,20 @@ pub(super) async fn find_nodes_to_relocate(
         return vec![];
     };
 
+    let key_generation = network_knowledge.chain_len().await;
     let mut relocating_nodes = vec![];
     for node_state in candidates {
         if node_state.age() == max_age {
             let dst = dst(&node_state.name(), churn_id);
             let age = node_state.age().saturating_add(1);
-            let relocate_details =
-                RelocateDetails::with_age(network_knowledge, node_state.peer(), dst, age);
+            let relocate_details = RelocateDetails::with_age(
+                network_knowledge,
+                node_state.peer(),
+                dst,
+                age,
+                key_generation,
+                Some(churn_id),
+            );
             relocating_nodes.push((node_state, relocate_details));
         }
     }
@@ -93,14 +114,22 @@ pub(super) async fn find_nodes_to_relocate(
 /// Details of a relocation: which node to relocate, where to relocate it to
 /// and what age it should get once relocated.
 pub(super) trait RelocateDetailsUtils {
+    /// Issues the relocation now, under our section key of the given generation.
     fn with_age(
         network_knowledge: &NetworkKnowledge,
         peer: &Peer,
         dst: XorName,
         age: u8,
+        key_generation: u64,
+        churn_id: Option<&ChurnId>,
     ) -> RelocateDetails;
 
     fn verify_identity(&self, new_name: &XorName, new_name_sig: &Signature) -> bool;
+
+    /// Whether the relocation follows from the churn it was drawn from, if any: the node was
+    /// old enough to be drawn, and is relocated to where the draw has it. Whether it was among
+    /// the oldest drawn, and those drawn few enough, only the issuing section knows.
+    fn verify_draw(&self) -> bool;
 }
 
 impl RelocateDetailsUtils for RelocateDetails {
@@ -109,6 +138,8 @@ impl RelocateDetailsUtils for RelocateDetails {
         peer: &Peer,
         dst: XorName,
         age: u8,
+        key_generation: u64,
+        churn_id: Option<&ChurnId>,
     ) -> RelocateDetails {
         let genesis_key = *network_knowledge.genesis_key();
 
@@ -121,6 +152,10 @@ impl RelocateDetailsUtils for RelocateDetails {
             dst,
             dst_section_key,
             age,
+            issued_at: now_secs() / RELOCATION_TIMESTAMP_GRANULARITY_SECS
+                * RELOCATION_TIMESTAMP_GRANULARITY_SECS,
+            key_generation,
+            churn_id: churn_id.map(|churn_id| churn_id.0.clone()),
         }
     }
 
@@ -133,6 +168,120 @@ impl RelocateDetailsUtils for RelocateDetails {
 
         pub_key.verify(&new_name.0, new_name_sig).is_ok()
     }
+
+    fn verify_draw(&self) -> bool {
+        let churn_id = match &self.churn_id {
+            Some(churn_id) => ChurnId(churn_id.clone()),
+            None => return true,
+        };
+        // Nodes are relocated one year older than they were when drawn.
+        self.dst == dst(&self.previous_name, &churn_id)
+            && check(self.age.saturating_sub(1), &churn_id)
+    }
+}
+
+/// Limits on the relocations a section accepts to be joined with.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RelocationLimits {
+    /// How long after being issued a relocation can still be joined with.
+    pub(crate) validity: Duration,
+    /// How many generations the issuing key can be behind our section key.
+    pub(crate) max_key_lag: u64,
+}
+
+impl Default for RelocationLimits {
+    fn default() -> Self {
+        Self {
+            validity: DEFAULT_RELOCATION_VALIDITY,
+            max_key_lag: DEFAULT_RELOCATION_MAX_KEY_LAG,
+        }
+    }
+}
+
+/// The relocations elders accept joins with: those within the limits, and not already used by
+/// another node. Used relocations are remembered until they're no longer valid anyway, or until
+/// `MAX_CONSUMED_RELOCATIONS` more recent ones were used.
+#[derive(Clone, Default)]
+pub(crate) struct RelocationFreshness {
+    inner: Arc<RwLock<Freshness>>,
+}
+
+#[derive(Default)]
+struct Freshness {
+    limits: RelocationLimits,
+    // The node which used each relocation to join.
+    consumed: BTreeMap<RelocateDetails, Peer>,
+    // The relocations in `consumed`, in the order they were used.
+    order: VecDeque<RelocateDetails>,
+}
+
+impl RelocationFreshness {
+    pub(crate) async fn set_limits(&self, limits: RelocationLimits) {
+        self.inner.write().await.limits = limits;
+    }
+
+    /// Checks that `peer` can join with the relocation, given the generation of our section key,
+    /// recording it as used by `peer` if so. The same node joining with it again is fine, as it
+    /// does when it retries.
+    pub(crate) async fn admit(
+        &self,
+        details: &RelocateDetails,
+        peer: &Peer,
+        our_key_generation: u64,
+    ) -> Result<(), JoinRejectionReason> {
+        self.admit_at(details, peer, our_key_generation, now_secs())
+            .await
+    }
+
+    async fn admit_at(
+        &self,
+        details: &RelocateDetails,
+        peer: &Peer,
+        our_key_generation: u64,
+        now: u64,
+    ) -> Result<(), JoinRejectionReason> {
+        let mut inner = self.inner.write().await;
+        let validity = inner.limits.validity.as_secs();
+
+        if now.saturating_sub(details.issued_at) > validity {
+            return Err(JoinRejectionReason::RelocationExpired {
+                issued_at: details.issued_at,
+            });
+        }
+
+        let generations_behind = our_key_generation.saturating_sub(details.key_generation);
+        if generations_behind > inner.limits.max_key_lag {
+            return Err(JoinRejectionReason::RelocationKeyOutdated { generations_behind });
+        }
+
+        let Freshness {
+            consumed, order, ..
+        } = &mut *inner;
+        consumed.retain(|used, _| now.saturating_sub(used.issued_at) <= validity);
+        order.retain(|used| consumed.contains_key(used));
+
+        match consumed.get(details) {
+            Some(user) if user == peer => return Ok(()),
+            Some(_) => return Err(JoinRejectionReason::RelocationAlreadyUsed),
+            None => (),
+        }
+
+        if order.len() >= MAX_CONSUMED_RELOCATIONS {
+            if let Some(oldest) = order.pop_front() {
+                let _user = consumed.remove(&oldest);
+            }
+        }
+        let _prev = consumed.insert(details.clone(), *peer);
+        order.push_back(details.clone());
+
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
 }
 
 // Relocation check - returns whether a member with the given age is a candidate for relocation on
@@ -169,7 +318,7 @@ mod tests {
     use super::*;
 
     use sn_interface::elder_count;
-    use sn_interface::network_knowledge::test_utils::section_signed;
+    use sn_interface::network_knowledge::{recommended_section_size, test_utils::section_signed};
     #[cfg(feature = "test-utils")]
     use sn_interface::types::SecretKeySet;
 
@@ -194,6 +343,111 @@ mod tests {
         assert_eq!(trailing_zeros(&[2, 0]), 9);
     }
 
+    #[tokio::test]
+    async fn relocations_are_admitted_while_fresh_and_unused() -> Result<()> {
+        let freshness = RelocationFreshness::default();
+        let limits = RelocationLimits::default();
+        let validity = limits.validity.as_secs();
+        let issued_at = 1_650_000_000;
+        let details = RelocateDetails {
+            previous_name: xor_name::rand::random(),
+            dst: xor_name::rand::random(),
+            dst_section_key: bls::SecretKey::random().public_key(),
+            age: MIN_ADULT_AGE,
+            issued_at,
+            key_generation: 10,
+            churn_id: None,
+        };
+        let peer = Peer::new(xor_name::rand::random(), "127.0.0.1:1".parse()?);
+        let replayer = Peer::new(peer.name(), "127.0.0.1:2".parse()?);
+
+        // Fresh, and its node retrying with it is fine.
+        let now = issued_at + validity;
+        assert_eq!(freshness.admit_at(&details, &peer, 10, now).await, Ok(()));
+        assert_eq!(freshness.admit_at(&details, &peer, 10, now).await, Ok(()));
+
+        // But not another node, even with the same name.
+        assert_eq!(
+            freshness.admit_at(&details, &replayer, 10, now).await,
+            Err(JoinRejectionReason::RelocationAlreadyUsed)
+        );
+
+        // Stale, by time or by key.
+        assert_eq!(
+            freshness.admit_at(&details, &peer, 10, now + 1).await,
+            Err(JoinRejectionReason::RelocationExpired { issued_at })
+        );
+        let generations_behind = limits.max_key_lag + 1;
+        assert_eq!(
+            freshness
+                .admit_at(&details, &peer, 10 + generations_behind, now)
+                .await,
+            Err(JoinRejectionReason::RelocationKeyOutdated { generations_behind })
+        );
+
+        // Within tighter limits, it's stale sooner.
+        freshness
+            .set_limits(RelocationLimits {
+                validity: Duration::from_secs(60),
+                max_key_lag: 0,
+            })
+            .await;
+        assert_eq!(
+            freshness
+                .admit_at(&details, &peer, 10, issued_at + 61)
+                .await,
+            Err(JoinRejectionReason::RelocationExpired { issued_at })
+        );
+        assert_eq!(
+            freshness.admit_at(&details, &peer, 11, issued_at).await,
+            Err(JoinRejectionReason::RelocationKeyOutdated {
+                generations_behind: 1
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn relocations_are_checked_against_their_draw() {
+        let churn_id = ChurnId(
+            signature_with_trailing_zeros(MIN_ADULT_AGE as u32)
+                .to_bytes()
+                .to_vec(),
+        );
+        let previous_name = xor_name::rand::random();
+        let drawn = RelocateDetails {
+            previous_name,
+            dst: dst(&previous_name, &churn_id),
+            dst_section_key: bls::SecretKey::random().public_key(),
+            age: MIN_ADULT_AGE + 1,
+            issued_at: 1_650_000_000,
+            key_generation: 10,
+            churn_id: Some(churn_id.0.clone()),
+        };
+        assert!(drawn.verify_draw());
+
+        // Not relocated elsewhere than drawn to...
+        let elsewhere = RelocateDetails {
+            dst: xor_name::rand::random(),
+            ..drawn.clone()
+        };
+        assert!(!elsewhere.verify_draw());
+        // ...nor when too old to be drawn.
+        let too_old = RelocateDetails {
+            age: 2 * MIN_ADULT_AGE,
+            ..drawn.clone()
+        };
+        assert!(!too_old.verify_draw());
+
+        // Relocations not drawn on churn are left to their issuing section.
+        let rejoin = RelocateDetails {
+            churn_id: None,
+            ..elsewhere
+        };
+        assert!(rejoin.verify_draw());
+    }
+
     const MAX_AGE: u8 = MIN_ADULT_AGE + 3;
 
     proptest! {
@@ -290,6 +544,8 @@ mod tests {
         // NOTE: `zip` works here, as both collections are sorted by the same criteria.
         for (peer, details) in expected_relocated_peers.into_iter().zip(relocations) {
             assert_eq!(peer.name(), details.previous_name);
+            // And that the destination can check it against the churn.
+            assert!(details.verify_draw());
         }
 
         Ok(())
@@ -335,8 +591,8 @@ mod tests {
         count: impl Into<SizeRange>,
         age: impl Strategy<Value = u8>,
     ) -> impl Strategy<Value = Vec<Peer>> {
-        proptest::collection::btree_map(arbitrary_bytes(), (any::<SocketAddr>(), age), count)
-            .prop_map(|peers| {
+        proptest::collection::btree_map(arbitrary_bytes(), (arbitrary_addr(), age), count).prop_map(
+            |peers| {
                 peers
                     .into_iter()
                     .map(|(mut bytes, (addr, age))| {
@@ -345,7 +601,14 @@ mod tests {
                         Peer::new(name, addr)
                     })
                     .collect()
-            })
+            },
+        )
+    }
+
+    // Addresses a node could actually be reached at, as SAPs with any other are rejected.
+    fn arbitrary_addr() -> impl Strategy<Value = SocketAddr> {
+        (any::<[u8; 3]>(), 1..=u16::MAX)
+            .prop_map(|([a, b, c], port)| SocketAddr::from(([10, a, b, c], port)))
     }
 
     fn arbitrary_bytes() -> impl Strategy<Value = [u8; XOR_NAME_LEN]> {
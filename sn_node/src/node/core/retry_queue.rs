@@ -0,0 +1,396 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise};
+use crate::node::Result;
+use crate::persisted;
+
+use sn_interface::{messaging::WireMsg, types::Peer};
+
+use bytes::Bytes;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+
+const RETRY_QUEUE_FILE: &str = "retry_queue";
+// Format of the checkpoint file: the msgs queued, soonest due first.
+const RETRY_QUEUE_VERSION: u16 = 1;
+
+/// Attempts at sending a msg, the first included, before it's given up on, when unspecified.
+pub(crate) const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry of a msg when unspecified, doubled for each retry after it.
+pub(crate) const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+// Longest delay between two retries of a msg.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+// Msgs queued at most, those failing past it being given up on right away.
+const RETRY_QUEUE_CAPACITY: usize = 10_000;
+/// How often msgs due are retried, and the queue checkpointed to disk.
+pub(crate) const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How msgs which failed to be sent are retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RetryPolicy {
+    /// Attempts at sending a msg, the first included. 0 or 1 disable retries.
+    pub(crate) max_attempts: u32,
+    /// Delay before the first retry, doubled for each retry after it, up to a minute. Each
+    /// delay is shortened by up to half at random, for msgs which failed together not to be
+    /// retried together.
+    pub(crate) base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Delay before the next attempt after `attempts`, given a `jitter` between 0 and 1.
+    fn delay(&self, attempts: u32, jitter: f64) -> Duration {
+        let doublings = attempts.saturating_sub(1).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << doublings)
+            .min(MAX_RETRY_DELAY);
+        delay.mul_f64(1.0 - jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// A msg which failed to be sent, to be sent again once due.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Retry {
+    /// Recipients it's yet to reach.
+    pub(crate) recipients: Vec<Peer>,
+    /// How many of them must receive it.
+    pub(crate) delivery_group_size: usize,
+    msg: Bytes,
+    /// Attempts made at sending it so far.
+    pub(crate) attempts: u32,
+    // When it's next due, in ms since the Unix epoch.
+    due: u64,
+}
+
+impl Retry {
+    /// The msg to send again.
+    pub(crate) fn wire_msg(&self) -> Result<WireMsg> {
+        Ok(WireMsg::from(self.msg.clone())?)
+    }
+
+    /// This retry, after failing again to reach `delivery_group_size` of `recipients`.
+    pub(crate) fn narrowed(self, recipients: Vec<Peer>, delivery_group_size: usize) -> Self {
+        Self {
+            recipients,
+            delivery_group_size,
+            ..self
+        }
+    }
+}
+
+/// Counts of what's been through the retry queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct RetryStats {
+    /// Msgs queued now.
+    pub(crate) queued: usize,
+    /// Attempts made at sending msgs again.
+    pub(crate) retried: u64,
+    /// Msgs given up on after their last attempt failed, or for the queue being full.
+    pub(crate) dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    policy: RetryPolicy,
+    // The msgs queued, by when they're due and the order they were queued in.
+    queued: BTreeMap<(u64, u64), Retry>,
+    next_seq: u64,
+    stats: RetryStats,
+    // Whether anything changed since the last checkpoint.
+    dirty: bool,
+}
+
+impl State {
+    fn schedule(&mut self, mut retry: Retry, now: u64) {
+        if retry.attempts >= self.policy.max_attempts {
+            warn!(
+                "Giving up on sending a msg to {:?} after {} attempts",
+                retry.recipients, retry.attempts
+            );
+            self.stats.dropped += 1;
+            return;
+        }
+        if self.queued.len() >= RETRY_QUEUE_CAPACITY {
+            warn!(
+                "Retry queue full, giving up on sending a msg to {:?}",
+                retry.recipients
+            );
+            self.stats.dropped += 1;
+            return;
+        }
+        let jitter = rand::thread_rng().gen_range(0.0..=1.0);
+        let delay = self.policy.delay(retry.attempts, jitter);
+        retry.due = now.saturating_add(delay.as_millis() as u64);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let _prev = self.queued.insert((retry.due, seq), retry);
+        self.stats.queued = self.queued.len();
+        self.dirty = true;
+    }
+}
+
+/// Msgs which failed to be sent, to peers or clients, retried with exponential backoff until
+/// they're sent or have been attempted as many times as allowed. Checkpointed to the node's root
+/// dir, so msgs still queued when it goes down are retried once it's back.
+#[derive(Clone, Debug)]
+pub(crate) struct RetryQueue {
+    state: Arc<RwLock<State>>,
+    path: PathBuf,
+}
+
+impl RetryQueue {
+    pub(crate) fn new(root_dir: &Path) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State::default())),
+            path: root_dir.join(RETRY_QUEUE_FILE),
+        }
+    }
+
+    /// Retries msgs per `policy`, starting with those queued at the last checkpoint. A
+    /// checkpoint which can't be read is left out, losing only the msgs it held.
+    pub(crate) async fn enable(&self, policy: RetryPolicy) {
+        let queued = match persisted::read_async(self.path.clone()).await {
+            Ok(Some(read)) if read.version == RETRY_QUEUE_VERSION => {
+                match deserialise::<Vec<Retry>>(&read.payload) {
+                    Ok(queued) => queued,
+                    Err(error) => {
+                        warn!(
+                            "Discarding the unreadable retry queue checkpoint: {:?}",
+                            error
+                        );
+                        vec![]
+                    }
+                }
+            }
+            Ok(Some(read)) => {
+                warn!(
+                    "Discarding the retry queue checkpoint of unknown version {}",
+                    read.version
+                );
+                vec![]
+            }
+            Ok(None) => vec![],
+            Err(error) => {
+                warn!("Discarding the damaged retry queue checkpoint: {}", error);
+                vec![]
+            }
+        };
+
+        let mut state = self.state.write().await;
+        state.policy = policy;
+        for retry in queued {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            let _prev = state.queued.insert((retry.due, seq), retry);
+        }
+        state.stats.queued = state.queued.len();
+        info!(
+            "Retrying msgs which fail to be sent with {:?}, {} queued from before",
+            policy, state.stats.queued
+        );
+    }
+
+    /// Queues `wire_msg`, which failed to reach `delivery_group_size` of `recipients` on its
+    /// first attempt, to be sent again.
+    pub(crate) async fn push(
+        &self,
+        recipients: Vec<Peer>,
+        delivery_group_size: usize,
+        wire_msg: &WireMsg,
+    ) -> Result<()> {
+        let retry = Retry {
+            recipients,
+            delivery_group_size,
+            msg: wire_msg.serialize()?,
+            attempts: 1,
+            due: 0,
+        };
+        self.state.write().await.schedule(retry, now());
+        Ok(())
+    }
+
+    /// Queues `retry` again after another failed attempt, unless it's had its last.
+    pub(crate) async fn requeue(&self, mut retry: Retry) {
+        retry.attempts += 1;
+        self.state.write().await.schedule(retry, now());
+    }
+
+    /// Takes the msgs due to be sent again.
+    pub(crate) async fn take_due(&self) -> Vec<Retry> {
+        self.take_due_at(now()).await
+    }
+
+    async fn take_due_at(&self, now: u64) -> Vec<Retry> {
+        let mut state = self.state.write().await;
+        let not_due = state.queued.split_off(&(now.saturating_add(1), 0));
+        let due = std::mem::replace(&mut state.queued, not_due);
+        if !due.is_empty() {
+            state.stats.retried += due.len() as u64;
+            state.stats.queued = state.queued.len();
+            state.dirty = true;
+        }
+        due.into_values().collect()
+    }
+
+    pub(crate) async fn stats(&self) -> RetryStats {
+        self.state.read().await.stats
+    }
+
+    /// Writes the msgs queued to disk, if there's been any change since last time.
+    pub(crate) async fn checkpoint(&self) -> Result<()> {
+        let payload = {
+            let mut state = self.state.write().await;
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            serialise(&state.queued.values().collect::<Vec<_>>())?
+        };
+        persisted::write_async(self.path.clone(), RETRY_QUEUE_VERSION, payload).await?;
+        Ok(())
+    }
+}
+
+// Ms since the Unix epoch, which retries are due by, to still be meaningful after a restart.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persisted::damage;
+
+    use eyre::Result;
+    use sn_interface::messaging::{
+        data::{DataQuery, ServiceMsg},
+        AuthKind, DstLocation, MsgId, ServiceAuth,
+    };
+    use sn_interface::types::{ChunkAddress, Keypair};
+    use std::net::{Ipv4Addr, SocketAddr};
+    use tempfile::tempdir;
+
+    fn wire_msg() -> Result<WireMsg> {
+        let src_keypair = Keypair::new_ed25519();
+        let query = DataQuery::GetChunk(ChunkAddress(xor_name::rand::random()));
+        let payload = WireMsg::serialize_msg_payload(&ServiceMsg::Query(query))?;
+        let auth = ServiceAuth {
+            public_key: src_keypair.public_key(),
+            signature: src_keypair.sign(&payload),
+        };
+        Ok(WireMsg::new_msg(
+            MsgId::new(),
+            payload,
+            AuthKind::Service(auth),
+            DstLocation::EndUser(sn_interface::messaging::EndUser(xor_name::rand::random())),
+        )?)
+    }
+
+    fn peer() -> Peer {
+        Peer::new(
+            xor_name::rand::random(),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 12000)),
+        )
+    }
+
+    #[test]
+    fn delays_double_up_to_the_max_with_jitter_shortening_them_by_up_to_half() {
+        let policy = RetryPolicy::default();
+        let secs = |attempts, jitter| policy.delay(attempts, jitter).as_secs_f64();
+        assert_eq!(secs(1, 0.0), 1.0);
+        assert_eq!(secs(2, 0.0), 2.0);
+        assert_eq!(secs(4, 0.0), 8.0);
+        assert_eq!(secs(4, 1.0), 4.0);
+        assert_eq!(secs(30, 0.0), 60.0);
+        assert_eq!(secs(u32::MAX, 0.5), 45.0);
+    }
+
+    #[tokio::test]
+    async fn msgs_are_retried_once_due_until_their_last_attempt() -> Result<()> {
+        let root = tempdir()?;
+        let queue = RetryQueue::new(root.path());
+        queue
+            .enable(RetryPolicy {
+                max_attempts: 2,
+                ..RetryPolicy::default()
+            })
+            .await;
+        let wire_msg = wire_msg()?;
+        queue.push(vec![peer()], 1, &wire_msg).await?;
+
+        // Not due before the shortest the first delay can be.
+        assert!(queue.take_due_at(now() + 400).await.is_empty());
+        let due = queue.take_due_at(now() + 1000).await;
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].wire_msg()?.msg_id(), wire_msg.msg_id());
+
+        // Failing on its last attempt, it's given up on.
+        for retry in due {
+            queue.requeue(retry).await;
+        }
+        assert_eq!(
+            queue.stats().await,
+            RetryStats {
+                queued: 0,
+                retried: 1,
+                dropped: 1,
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn msgs_queued_are_retried_across_restarts() -> Result<()> {
+        let root = tempdir()?;
+        let wire_msg = wire_msg()?;
+
+        let queue = RetryQueue::new(root.path());
+        queue.enable(RetryPolicy::default()).await;
+        queue.push(vec![peer()], 1, &wire_msg).await?;
+        queue.checkpoint().await?;
+        drop(queue);
+
+        let queue = RetryQueue::new(root.path());
+        queue.enable(RetryPolicy::default()).await;
+        let due = queue.take_due_at(now() + 1000).await;
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].wire_msg()?.msg_id(), wire_msg.msg_id());
+
+        // A damaged checkpoint is left out rather than stopping the node.
+        let path = root.path().join(RETRY_QUEUE_FILE);
+        for way in 0..damage::ways(&path)? {
+            queue.push(vec![peer()], 1, &wire_msg).await?;
+            queue.checkpoint().await?;
+            damage::apply(&path, way)?;
+            let restarted = RetryQueue::new(root.path());
+            restarted.enable(RetryPolicy::default()).await;
+            let _due = queue.take_due_at(now() + 1000).await;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+
+use crate::dbs::{deserialise, serialise, SLED_FLUSH_TIME_MS};
+use crate::node::{api::cmds::Cmd, Result};
+
+use bls::serde_impl::SerdeSecret;
+use serde::{Deserialize, Serialize};
+use sn_interface::{messaging::system::DkgSessionId, network_knowledge::SectionKeyShare};
+use std::{
+    fmt::{self, Debug, Formatter},
+    path::Path,
+};
+use xor_name::XorName;
+
+const ROLE_DB_NAME: &str = "role_state";
+const TRANSITION_KEY: &[u8] = b"transition";
+
+/// Milestones of a node's promotion to elder, in the order they are reached.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) enum PromotionStep {
+    /// We were picked as an elder candidate and started the DKG session.
+    ProposalObserved(DkgSessionId),
+    /// DKG completed and produced our key share for the new section key.
+    KeyShareReceived(PersistedKeyShare),
+    /// We have set up our elder state (membership, handover) for the new section key.
+    SetupCompleted(PersistedKeyShare),
+}
+
+/// The in-progress (or last completed) role transition of this node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RoleTransition {
+    /// Name of the node the transition belongs to. A node restarting under a different
+    /// identity must not resume someone else's transition.
+    pub(crate) name: XorName,
+    pub(crate) step: PromotionStep,
+}
+
+/// Serializable form of a `SectionKeyShare`.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct PersistedKeyShare {
+    public_key_set: bls::PublicKeySet,
+    index: usize,
+    secret_key_share: SerdeSecret<bls::SecretKeyShare>,
+}
+
+impl Debug for PersistedKeyShare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistedKeyShare")
+            .field("public_key", &self.public_key_set.public_key())
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl PersistedKeyShare {
+    pub(crate) fn public_key(&self) -> bls::PublicKey {
+        self.public_key_set.public_key()
+    }
+}
+
+impl From<&SectionKeyShare> for PersistedKeyShare {
+    fn from(key_share: &SectionKeyShare) -> Self {
+        Self {
+            public_key_set: key_share.public_key_set.clone(),
+            index: key_share.index,
+            secret_key_share: SerdeSecret(key_share.secret_key_share.clone()),
+        }
+    }
+}
+
+impl From<PersistedKeyShare> for SectionKeyShare {
+    fn from(key_share: PersistedKeyShare) -> Self {
+        Self {
+            public_key_set: key_share.public_key_set,
+            index: key_share.index,
+            secret_key_share: key_share.secret_key_share.0,
+        }
+    }
+}
+
+/// Db-backed record of the node's role transition.
+///
+/// The record is a single sled entry, so each milestone update replaces the previous one
+/// atomically, and is flushed before returning so it survives a crash.
+#[derive(Clone)]
+pub(crate) struct RoleStore {
+    db: sled::Db,
+}
+
+impl RoleStore {
+    pub(crate) fn new(root_dir: &Path) -> Result<Self> {
+        let db = sled::Config::default()
+            .path(root_dir.join("db").join(ROLE_DB_NAME))
+            .flush_every_ms(SLED_FLUSH_TIME_MS)
+            .open()?;
+        Ok(Self { db })
+    }
+
+    pub(crate) fn get(&self) -> Result<Option<RoleTransition>> {
+        match self.db.get(TRANSITION_KEY)? {
+            Some(bytes) => Ok(Some(deserialise(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) async fn set(&self, transition: &RoleTransition) -> Result<()> {
+        let _prev = self.db.insert(TRANSITION_KEY, serialise(transition)?)?;
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn clear(&self) -> Result<()> {
+        let _prev = self.db.remove(TRANSITION_KEY)?;
+        let _bytes = self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+impl Node {
+    // Records reaching `step` of our promotion.
+    pub(crate) async fn record_promotion_step(&self, step: PromotionStep) {
+        let transition = RoleTransition {
+            name: self.info.read().await.name(),
+            step,
+        };
+        if let Err(error) = self.role_store.set(&transition).await {
+            error!(
+                "Failed to persist role transition {:?}: {:?}",
+                transition, error
+            );
+        }
+    }
+
+    // Forgets any role transition, e.g. upon demotion.
+    pub(crate) async fn clear_role_transition(&self) {
+        if let Err(error) = self.role_store.clear().await {
+            error!("Failed to clear persisted role transition: {:?}", error);
+        }
+    }
+
+    /// Resumes a promotion interrupted by a restart, from the last milestone recorded
+    /// before the node went down. Every step is safe to re-run.
+    pub(crate) async fn resume_role_transition(&self) -> Result<Vec<Cmd>> {
+        let transition = match self.role_store.get()? {
+            Some(transition) => transition,
+            None => return Ok(vec![]),
+        };
+
+        if transition.name != self.info.read().await.name() {
+            debug!(
+                "Discarding role transition of a previous identity: {:?}",
+                transition
+            );
+            self.clear_role_transition().await;
+            return Ok(vec![]);
+        }
+
+        info!("Resuming role transition: {:?}", transition.step);
+        let section_key = self.network_knowledge.section_key().await;
+
+        match transition.step {
+            PromotionStep::ProposalObserved(session_id) => {
+                if session_id.section_chain_len < self.network_knowledge.chain_len().await {
+                    debug!("DKG session we were part of is outdated, not resuming it");
+                    self.clear_role_transition().await;
+                    return Ok(vec![]);
+                }
+                // Our local DKG state was lost: rejoin the session to obtain our key share.
+                self.handle_dkg_start(session_id).await
+            }
+            PromotionStep::KeyShareReceived(key_share) => {
+                let is_current = key_share.public_key() == section_key;
+                self.section_keys_provider.insert(key_share.into()).await;
+                if is_current {
+                    self.complete_elder_setup().await
+                } else {
+                    // The SAP for our key share hasn't been applied yet. The regular flow
+                    // completes our setup once it is, now that our key share is in place.
+                    Ok(vec![])
+                }
+            }
+            PromotionStep::SetupCompleted(key_share) => {
+                if key_share.public_key() == section_key {
+                    self.section_keys_provider.insert(key_share.into()).await;
+                    self.complete_elder_setup().await
+                } else {
+                    // The section moved on while we were down, so we're no longer an elder
+                    // with that key.
+                    self.clear_role_transition().await;
+                    Ok(vec![])
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{api::tests::create_comm, create_test_max_capacity_and_root_storage};
+    use crate::UsedSpace;
+
+    use sn_interface::{
+        elder_count,
+        network_knowledge::{
+            test_utils::{gen_section_authority_provider, section_signed},
+            NetworkKnowledge, NodeInfo, NodeState,
+        },
+    };
+
+    use eyre::Result;
+    use secured_linked_list::SecuredLinkedList;
+    use std::{collections::BTreeSet, path::PathBuf};
+    use tokio::sync::mpsc;
+    use xor_name::Prefix;
+
+    // A section in which we are the first elder, along with our key share for its current key.
+    struct Env {
+        info: NodeInfo,
+        network_knowledge: NetworkKnowledge,
+        key_share: SectionKeyShare,
+        root_dir: PathBuf,
+    }
+
+    impl Env {
+        async fn new() -> Result<Self> {
+            let (sap, mut nodes, sk_set) =
+                gen_section_authority_provider(Prefix::default(), elder_count());
+            let section_chain = SecuredLinkedList::new(sk_set.public_keys().public_key());
+            let signed_sap = section_signed(sk_set.secret_key(), sap.clone())?;
+            let network_knowledge =
+                NetworkKnowledge::new(*section_chain.root_key(), section_chain, signed_sap, None)?;
+            for peer in sap.elders() {
+                let node_state =
+                    section_signed(sk_set.secret_key(), NodeState::joined(*peer, None))?;
+                let _updated = network_knowledge.update_member(node_state).await;
+            }
+
+            let key_share = SectionKeyShare {
+                public_key_set: sk_set.public_keys(),
+                index: 0,
+                secret_key_share: sk_set.secret_key_share(0),
+            };
+            let (_, root_dir) = create_test_max_capacity_and_root_storage()?;
+
+            Ok(Self {
+                info: nodes.remove(0),
+                network_knowledge,
+                key_share,
+                root_dir,
+            })
+        }
+
+        // Persists `step` as if reached before a restart.
+        async fn seed(&self, name: XorName, step: PromotionStep) -> Result<()> {
+            let store = RoleStore::new(&self.root_dir)?;
+            store.set(&RoleTransition { name, step }).await?;
+            Ok(())
+        }
+
+        // Starts the node anew, without any of the in-memory elder state.
+        async fn restart_node(&self) -> Result<Node> {
+            let (max_capacity, _) = create_test_max_capacity_and_root_storage()?;
+            let node = Node::new(
+                create_comm().await?,
+                self.info.clone(),
+                self.network_knowledge.clone(),
+                None,
+                mpsc::channel(1).0,
+                UsedSpace::new(max_capacity),
+                self.root_dir.clone(),
+            )
+            .await?;
+            Ok(node)
+        }
+
+        async fn session_id(&self, section_chain_len: u64) -> DkgSessionId {
+            let sap = self.network_knowledge.authority_provider().await;
+            DkgSessionId {
+                prefix: sap.prefix(),
+                elders: sap
+                    .elders()
+                    .map(|peer| (peer.name(), peer.addr()))
+                    .collect(),
+                section_chain_len,
+                bootstrap_members: BTreeSet::new(),
+                network_params: sap.network_params(),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn resumes_dkg_after_restart_following_proposal() -> Result<()> {
+        let env = Env::new().await?;
+        let session_id = env
+            .session_id(env.network_knowledge.chain_len().await)
+            .await;
+        env.seed(
+            env.info.name(),
+            PromotionStep::ProposalObserved(session_id.clone()),
+        )
+        .await?;
+
+        let node = env.restart_node().await?;
+        let cmds = node.resume_role_transition().await?;
+
+        // We rejoined the DKG session, sending our part to the other participants.
+        assert!(cmds.iter().any(|cmd| matches!(cmd, Cmd::SendMsg { .. })));
+        assert_matches::assert_matches!(
+            node.role_store.get()?,
+            Some(RoleTransition { step: PromotionStep::ProposalObserved(id), .. }) if id == session_id
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn outdated_dkg_session_is_not_resumed() -> Result<()> {
+        let env = Env::new().await?;
+        let session_id = env.session_id(0).await;
+        env.seed(env.info.name(), PromotionStep::ProposalObserved(session_id))
+            .await?;
+
+        let node = env.restart_node().await?;
+        assert!(node.resume_role_transition().await?.is_empty());
+        assert!(node.role_store.get()?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn completes_setup_after_restart_following_key_share() -> Result<()> {
+        let env = Env::new().await?;
+        env.seed(
+            env.info.name(),
+            PromotionStep::KeyShareReceived((&env.key_share).into()),
+        )
+        .await?;
+
+        let node = env.restart_node().await?;
+        assert!(node.membership.read().await.is_none());
+
+        let _cmds = node.resume_role_transition().await?;
+
+        let section_key = env.key_share.public_key_set.public_key();
+        assert!(node
+            .section_keys_provider
+            .key_share(&section_key)
+            .await
+            .is_ok());
+        assert!(node.membership.read().await.is_some());
+        assert!(node.handover_voting.read().await.is_some());
+        assert_matches::assert_matches!(
+            node.role_store.get()?,
+            Some(RoleTransition { step: PromotionStep::SetupCompleted(key_share), .. })
+                if key_share.public_key() == section_key
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reruns_setup_after_restart_following_completion() -> Result<()> {
+        let env = Env::new().await?;
+        env.seed(
+            env.info.name(),
+            PromotionStep::SetupCompleted((&env.key_share).into()),
+        )
+        .await?;
+
+        let node = env.restart_node().await?;
+        let _cmds = node.resume_role_transition().await?;
+
+        assert!(node.membership.read().await.is_some());
+        assert!(node.handover_voting.read().await.is_some());
+        assert_matches::assert_matches!(
+            node.role_store.get()?,
+            Some(RoleTransition {
+                step: PromotionStep::SetupCompleted(_),
+                ..
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn completed_setup_for_outdated_key_is_cleared() -> Result<()> {
+        let env = Env::new().await?;
+        let (_, _, other_sk_set) = gen_section_authority_provider(Prefix::default(), 1);
+        let other_key_share = SectionKeyShare {
+            public_key_set: other_sk_set.public_keys(),
+            index: 0,
+            secret_key_share: other_sk_set.secret_key_share(0),
+        };
+        env.seed(
+            env.info.name(),
+            PromotionStep::SetupCompleted((&other_key_share).into()),
+        )
+        .await?;
+
+        let node = env.restart_node().await?;
+        assert!(node.resume_role_transition().await?.is_empty());
+
+        assert!(node.membership.read().await.is_none());
+        assert!(node.role_store.get()?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transition_of_another_identity_is_cleared() -> Result<()> {
+        let env = Env::new().await?;
+        env.seed(
+            xor_name::rand::random(),
+            PromotionStep::KeyShareReceived((&env.key_share).into()),
+        )
+        .await?;
+
+        let node = env.restart_node().await?;
+        assert!(node.resume_role_transition().await?.is_empty());
+
+        assert!(node.membership.read().await.is_none());
+        assert!(node.role_store.get()?.is_none());
+
+        Ok(())
+    }
+}
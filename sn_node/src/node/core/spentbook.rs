@@ -0,0 +1,593 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+
+use crate::dbs::{deserialise, serialise};
+use crate::node::{api::cmds::Cmd, error::convert_to_error_msg, Error, Result};
+use crate::persisted::{self, Error as PersistedError};
+
+use sn_dbc::{
+    Commitment, Hash, IndexedSignatureShare, KeyImage, RingCtTransaction, SpentProof,
+    SpentProofContent, SpentProofShare,
+};
+use sn_interface::{
+    messaging::{
+        data::{CmdError, Error as ErrorMsg, QueryResponse, SpentbookCmd, SpentbookQuery},
+        system::{NodeCmd, SystemMsg},
+        MsgId,
+    },
+    network_knowledge::SectionKeyShare,
+    types::Peer,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use xor_name::XorName;
+
+const SPENTBOOK_DIR: &str = "spentbook";
+// Format of the file of each DBC logged as spent: its spent proof content, the shares of the
+// proof logged so far, and the proof once aggregated.
+const SPENTBOOK_ENTRY_VERSION: u16 = 1;
+
+/// What the spentbook holds of a DBC logged as spent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    /// What the elders sign the DBC as spent in, the tx hash included, fixed on the first
+    /// share logged.
+    content: SpentProofContent,
+    /// Shares of the spent proof logged, by any of our section keys.
+    shares: Vec<SpentProofShare>,
+    /// The spent proof, once enough shares by the same section key were logged.
+    proof: Option<SpentProof>,
+}
+
+/// The spentbook of the DBCs whose key images our section is closest to, as kept by an elder:
+/// a file per DBC under the `spentbook` dir of the node's root dir. It's append-only: the tx a
+/// DBC is logged as spent in never changes, shares of its spent proof are only ever added, and
+/// the proof is only set once.
+#[derive(Clone, Debug)]
+pub(crate) struct Spentbook {
+    dir: PathBuf,
+    // Entries are read, updated and written back whole, one at a time.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl Spentbook {
+    pub(crate) fn new(root_dir: &Path) -> Self {
+        Self {
+            dir: root_dir.join(SPENTBOOK_DIR),
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Logs `share`, aggregating the spent proof if it's the last one needed. Fails with
+    /// `DbcAlreadySpent` if the DBC is logged as spent in another tx, the share being dropped.
+    /// Returns whether the share is new to us.
+    pub(crate) async fn log_share(&self, share: SpentProofShare) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        let key_image = *share.key_image();
+
+        let mut entry = match self.entry(&key_image).await? {
+            Some(entry) => entry,
+            None => Entry {
+                content: share.content.clone(),
+                shares: vec![],
+                proof: None,
+            },
+        };
+        if entry.content.transaction_hash != share.transaction_hash() {
+            return Err(Error::DbcAlreadySpent(key_image));
+        }
+        if entry.content != share.content {
+            return Err(Error::InvalidSpentProofShare(format!(
+                "public commitments differ from those logged for {:?}",
+                key_image
+            )));
+        }
+        if entry.shares.contains(&share) {
+            return Ok(false);
+        }
+
+        entry.shares.push(share);
+        if entry.proof.is_none() {
+            entry.proof = aggregate(&entry.content, &entry.shares);
+            if entry.proof.is_some() {
+                debug!("Spent proof of DBC {:?} aggregated", key_image);
+            }
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        persisted::write_async(
+            self.path_of(&key_image),
+            SPENTBOOK_ENTRY_VERSION,
+            serialise(&entry)?,
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    /// The shares of the spent proof of the DBC of `key_image` logged so far.
+    pub(crate) async fn shares(&self, key_image: &KeyImage) -> Result<Vec<SpentProofShare>> {
+        Ok(self
+            .entry(key_image)
+            .await?
+            .map(|entry| entry.shares)
+            .unwrap_or_default())
+    }
+
+    /// The spent proof of the DBC of `key_image`, if aggregated yet.
+    pub(crate) async fn proof(&self, key_image: &KeyImage) -> Result<Option<SpentProof>> {
+        Ok(self.entry(key_image).await?.and_then(|entry| entry.proof))
+    }
+
+    /// What the DBCs logged as spent whose spent proof isn't aggregated yet are to be signed
+    /// in.
+    pub(crate) async fn pending(&self) -> Result<Vec<SpentProofContent>> {
+        let mut files = match tokio::fs::read_dir(&self.dir).await {
+            Ok(files) => files,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+        let mut pending = vec![];
+        while let Some(file) = files.next_entry().await? {
+            let path = file.path();
+            // Left by an interrupted write.
+            if path.extension().is_some() {
+                continue;
+            }
+            if let Some(entry) = self.entry_at(path).await? {
+                if entry.proof.is_none() {
+                    pending.push(entry.content);
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    async fn entry(&self, key_image: &KeyImage) -> Result<Option<Entry>> {
+        self.entry_at(self.path_of(key_image)).await
+    }
+
+    async fn entry_at(&self, path: PathBuf) -> Result<Option<Entry>> {
+        let read = match persisted::read_async(path.clone()).await {
+            Ok(Some(read)) => read,
+            Ok(None) => return Ok(None),
+            Err(PersistedError::Io(_, error)) => return Err(error.into()),
+            // Unlike a cache, a damaged entry can't just be dropped, or the DBC could be spent
+            // again.
+            Err(error) => return Err(Error::UnreadableSpentbookEntry(error.to_string())),
+        };
+        if read.version != SPENTBOOK_ENTRY_VERSION {
+            let error = PersistedError::WrongVersion {
+                path,
+                found: read.version,
+                expected: SPENTBOOK_ENTRY_VERSION,
+            };
+            return Err(Error::UnreadableSpentbookEntry(error.to_string()));
+        }
+        Ok(Some(deserialise(&read.payload)?))
+    }
+
+    fn path_of(&self, key_image: &KeyImage) -> PathBuf {
+        self.dir.join(hex::encode(key_image.to_bytes()))
+    }
+}
+
+// The spent proof of `content`, if enough of `shares` were signed by the same section key.
+fn aggregate(content: &SpentProofContent, shares: &[SpentProofShare]) -> Option<SpentProof> {
+    let hash = content.hash();
+    let key_sets: BTreeSet<_> = shares.iter().map(|share| &share.spentbook_pks).collect();
+    key_sets.into_iter().find_map(|pks| {
+        let sig_shares: Vec<_> = shares
+            .iter()
+            .filter(|share| share.spentbook_pks == *pks)
+            .map(|share| share.spentbook_sig_share.threshold_crypto())
+            .collect();
+        if sig_shares.len() <= pks.threshold() {
+            return None;
+        }
+        let spentbook_sig = pks.combine_signatures(sig_shares).ok()?;
+        pks.public_key()
+            .verify(&spentbook_sig, hash)
+            .then(|| SpentProof {
+                content: content.clone(),
+                spentbook_pub_key: pks.public_key(),
+                spentbook_sig,
+            })
+    })
+}
+
+// Our share of the spent proof of `content`, signed with our section key share.
+fn sign_share(key_share: &SectionKeyShare, content: SpentProofContent) -> SpentProofShare {
+    let spentbook_sig_share = IndexedSignatureShare::new(
+        key_share.index as u64,
+        key_share.secret_key_share.sign(content.hash()),
+    );
+    SpentProofShare {
+        content,
+        spentbook_pks: key_share.public_key_set.clone(),
+        spentbook_sig_share,
+    }
+}
+
+// The public commitments of the input of `tx` whose key image is `key_image`, once `tx` is
+// checked to hold up with `public_commitments`.
+//
+// TODO(safe_network#synth-2808): the commitments are the client's word. All that's checked is
+// that the tx holds up with them, and, as it's logged, that they're those the DBC was already
+// logged as spent with, if it was. Nothing ties them to the outputs of the txs the ring members
+// were created in, which the spentbook doesn't hold, so a tx over made-up commitments is signed.
+fn check_spend(
+    key_image: &KeyImage,
+    tx: &RingCtTransaction,
+    public_commitments: &[Vec<Commitment>],
+) -> std::result::Result<Vec<Commitment>, ErrorMsg> {
+    let input = tx
+        .mlsags
+        .iter()
+        .position(|mlsag| KeyImage::from(mlsag.key_image) == *key_image)
+        .ok_or_else(|| {
+            ErrorMsg::InvalidTransaction(format!("{:?} is not an input of the tx", key_image))
+        })?;
+    if public_commitments.len() != tx.mlsags.len() {
+        return Err(ErrorMsg::InvalidTransaction(format!(
+            "{} sets of public commitments given for {} inputs",
+            public_commitments.len(),
+            tx.mlsags.len()
+        )));
+    }
+    tx.verify(public_commitments)
+        .map_err(|error| ErrorMsg::InvalidTransaction(error.to_string()))?;
+    Ok(public_commitments[input].clone())
+}
+
+impl Node {
+    /// Logs the DBC of a client's spend as spent, as an elder, signing our share of its spent
+    /// proof and sending it to the other elders to log too.
+    pub(crate) async fn handle_spentbook_cmd(
+        &self,
+        cmd: SpentbookCmd,
+        msg_id: MsgId,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let SpentbookCmd::Spend {
+            key_image,
+            tx,
+            public_commitments,
+        } = &cmd;
+        let public_commitments = match check_spend(key_image, tx, public_commitments) {
+            Ok(public_commitments) => public_commitments,
+            Err(error) => {
+                warn!("Refusing to log DBC {:?} as spent: {}", key_image, error);
+                return self
+                    .send_cmd_error_response(cmd.error(error), origin, msg_id)
+                    .await;
+            }
+        };
+
+        let content = SpentProofContent {
+            key_image: *key_image,
+            transaction_hash: Hash::from(tx.hash()),
+            public_commitments,
+        };
+        let share = sign_share(&self.key_share().await?, content);
+
+        if let Err(error) = self.spentbook.log_share(share.clone()).await {
+            warn!("Failed to log DBC {:?} as spent: {}", key_image, error);
+            let error = CmdError::Data(convert_to_error_msg(error).into());
+            return self.send_cmd_error_response(error, origin, msg_id).await;
+        }
+        info!("DBC {:?} logged as spent", key_image);
+
+        let msg = SystemMsg::NodeCmd(NodeCmd::RecordSpentProofShare(share));
+        let mut cmds = self
+            .send_node_msg_to_nodes(msg, self.other_elders().await)
+            .await?;
+        cmds.extend(self.send_cmd_ack(origin, msg_id).await?);
+        Ok(cmds)
+    }
+
+    /// Signs our share of the spent proofs not aggregated yet anew, with our new section key,
+    /// logging it and sending it to the other elders. The shares signed with the previous key
+    /// are no longer taken by the elders, so the proofs could never be aggregated otherwise.
+    pub(crate) async fn resign_pending_spent_proofs(&self) -> Result<Vec<Cmd>> {
+        let pending = self.spentbook.pending().await?;
+        if pending.is_empty() {
+            return Ok(vec![]);
+        }
+        let key_share = self.key_share().await?;
+        let elders = self.other_elders().await;
+        let mut cmds = vec![];
+        for content in pending {
+            let key_image = content.key_image;
+            let share = sign_share(&key_share, content);
+            match self.spentbook.log_share(share.clone()).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(error) => {
+                    warn!(
+                        "Failed to re-sign the spend of DBC {:?}: {}",
+                        key_image, error
+                    );
+                    continue;
+                }
+            }
+            debug!(
+                "Spend of DBC {:?} re-signed with our new section key",
+                key_image
+            );
+            let msg = SystemMsg::NodeCmd(NodeCmd::RecordSpentProofShare(share));
+            cmds.extend(self.send_node_msg_to_nodes(msg, elders.clone()).await?);
+        }
+        Ok(cmds)
+    }
+
+    async fn other_elders(&self) -> BTreeSet<XorName> {
+        let our_name = self.info.read().await.name();
+        self.network_knowledge
+            .elders()
+            .await
+            .iter()
+            .map(|peer| peer.name())
+            .filter(|name| *name != our_name)
+            .collect()
+    }
+
+    /// Logs the share of a spent proof another elder of ours signed.
+    pub(crate) async fn record_spent_proof_share(&self, share: SpentProofShare) -> Result<()> {
+        let section_key = self.network_knowledge.section_key().await;
+        let pks = &share.spentbook_pks;
+        let (index, sig_share) = share.spentbook_sig_share.threshold_crypto();
+        if pks.public_key() != section_key
+            || !pks
+                .public_key_share(index)
+                .verify(sig_share, share.content.hash())
+        {
+            warn!(
+                "Dropping spent proof share of DBC {:?} not signed with our section key",
+                share.key_image()
+            );
+            return Ok(());
+        }
+
+        let key_image = *share.key_image();
+        match self.spentbook.log_share(share).await {
+            Ok(_) => Ok(()),
+            Err(Error::DbcAlreadySpent(_)) => {
+                warn!(
+                    "Another elder logged DBC {:?} as spent in another tx than we did",
+                    key_image
+                );
+                Ok(())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Answers a wallet's spentbook query, as an elder.
+    pub(crate) async fn handle_spentbook_query(
+        &self,
+        query: SpentbookQuery,
+        msg_id: MsgId,
+        origin: Peer,
+    ) -> Result<Vec<Cmd>> {
+        let operation_id = query.operation_id()?;
+        let response = match &query {
+            SpentbookQuery::SpentProofShares(key_image) => QueryResponse::SpentProofShares((
+                self.spentbook
+                    .shares(key_image)
+                    .await
+                    .map_err(convert_to_error_msg),
+                operation_id,
+            )),
+            SpentbookQuery::SpentProof(key_image) => QueryResponse::SpentProof((
+                self.spentbook
+                    .proof(key_image)
+                    .await
+                    .map_err(convert_to_error_msg),
+                operation_id,
+            )),
+        };
+        self.send_query_response(origin, response, msg_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use eyre::Result;
+    use sn_dbc::{
+        bls_ringct::{bls_bulletproofs::PedersenGens, group::Curve},
+        GenesisMaterial,
+    };
+    use tempfile::tempdir;
+
+    // Shares of the spent proof of the genesis DBC spent in a tx of `tx_hash`, signed by each
+    // of the elders holding a share of `sk_set`.
+    fn shares(sk_set: &bls::SecretKeySet, tx_hash: [u8; 32], count: u64) -> Vec<SpentProofShare> {
+        let genesis = GenesisMaterial::default();
+        let commitment = genesis.ringct_material.inputs[0]
+            .true_input
+            .revealed_commitment()
+            .commit(&PedersenGens::default())
+            .to_affine();
+        let content = SpentProofContent {
+            key_image: genesis.input_key_image,
+            transaction_hash: Hash::from(tx_hash),
+            public_commitments: vec![commitment],
+        };
+        (0..count)
+            .map(|index| SpentProofShare {
+                content: content.clone(),
+                spentbook_pks: sk_set.public_keys(),
+                spentbook_sig_share: IndexedSignatureShare::new(
+                    index,
+                    sk_set.secret_key_share(index).sign(content.hash()),
+                ),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn spent_proof_is_aggregated_once_past_the_threshold() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let spentbook = Spentbook::new(tmp_dir.path());
+        let sk_set = bls::SecretKeySet::random(2, &mut rand::thread_rng());
+        let shares = shares(&sk_set, [1; 32], 3);
+        let key_image = *shares[0].key_image();
+
+        assert!(spentbook.log_share(shares[0].clone()).await?);
+        assert!(!spentbook.log_share(shares[0].clone()).await?);
+        assert!(spentbook.log_share(shares[1].clone()).await?);
+        assert_eq!(spentbook.proof(&key_image).await?, None);
+
+        assert!(spentbook.log_share(shares[2].clone()).await?);
+        let proof = spentbook.proof(&key_image).await?.expect("no spent proof");
+        assert_eq!(proof.spentbook_pub_key, sk_set.public_keys().public_key());
+        assert!(proof
+            .spentbook_pub_key
+            .verify(&proof.spentbook_sig, proof.content.hash()));
+
+        // It's all still there on restart.
+        let reopened = Spentbook::new(tmp_dir.path());
+        assert_eq!(reopened.shares(&key_image).await?, shares);
+        assert_eq!(reopened.proof(&key_image).await?, Some(proof));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dbc_spent_in_a_tx_cannot_be_logged_in_another() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let spentbook = Spentbook::new(tmp_dir.path());
+        let sk_set = bls::SecretKeySet::random(1, &mut rand::thread_rng());
+        let spent = shares(&sk_set, [1; 32], 1);
+        let double_spent = shares(&sk_set, [2; 32], 2);
+        let key_image = *spent[0].key_image();
+
+        assert!(spentbook.log_share(spent[0].clone()).await?);
+        for share in double_spent {
+            assert!(matches!(
+                spentbook.log_share(share).await,
+                Err(Error::DbcAlreadySpent(spent_key_image)) if spent_key_image == key_image
+            ));
+        }
+        assert_eq!(spentbook.shares(&key_image).await?, spent);
+        assert_eq!(spentbook.proof(&key_image).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pending_spends_aggregate_once_signed_anew_with_a_new_section_key() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let spentbook = Spentbook::new(tmp_dir.path());
+        let old_set = bls::SecretKeySet::random(1, &mut rand::thread_rng());
+        let new_set = bls::SecretKeySet::random(1, &mut rand::thread_rng());
+        let old_shares = shares(&old_set, [1; 32], 1);
+        let key_image = *old_shares[0].key_image();
+        assert!(spentbook.pending().await?.is_empty());
+
+        // Churn before the proof was aggregated: one share under the old key is all there is.
+        assert!(spentbook.log_share(old_shares[0].clone()).await?);
+        let pending = spentbook.pending().await?;
+        assert_eq!(pending, [old_shares[0].content.clone()]);
+
+        for index in 0..2 {
+            let key_share = SectionKeyShare {
+                public_key_set: new_set.public_keys(),
+                index: index as usize,
+                secret_key_share: new_set.secret_key_share(index),
+            };
+            assert!(
+                spentbook
+                    .log_share(sign_share(&key_share, pending[0].clone()))
+                    .await?
+            );
+        }
+        let proof = spentbook.proof(&key_image).await?.expect("no spent proof");
+        assert_eq!(proof.spentbook_pub_key, new_set.public_keys().public_key());
+        assert!(spentbook.pending().await?.is_empty());
+
+        Ok(())
+    }
+
+    // The spentbook's trust boundary: the public commitments of a spend are the client's, only
+    // held to those the DBC was already logged as spent with.
+    #[tokio::test]
+    async fn commitments_are_only_checked_against_the_spend_already_logged() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let spentbook = Spentbook::new(tmp_dir.path());
+        let sk_set = bls::SecretKeySet::random(1, &mut rand::thread_rng());
+        let logged = shares(&sk_set, [1; 32], 2);
+        assert!(spentbook.log_share(logged[0].clone()).await?);
+
+        let mut other_commitments = logged[1].clone();
+        other_commitments.content.public_commitments = vec![];
+        assert!(matches!(
+            spentbook.log_share(other_commitments).await,
+            Err(Error::InvalidSpentProofShare(_))
+        ));
+        assert!(spentbook.log_share(logged[1].clone()).await?);
+
+        // A DBC never logged is taken with whatever commitments the tx holds up with.
+        let fresh_dir = tempdir()?;
+        let fresh = Spentbook::new(fresh_dir.path());
+        let mut content = logged[0].content.clone();
+        content.public_commitments = vec![];
+        let key_share = SectionKeyShare {
+            public_key_set: sk_set.public_keys(),
+            index: 0,
+            secret_key_share: sk_set.secret_key_share(0_u64),
+        };
+        let made_up = sign_share(&key_share, content);
+        assert!(fresh.log_share(made_up).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn spends_are_checked_against_the_tx() -> Result<()> {
+        let genesis = GenesisMaterial::default();
+        let (tx, revealed_commitments) = genesis.ringct_material.sign(sn_dbc::rng::thread_rng())?;
+        let commitment = genesis.ringct_material.inputs[0]
+            .true_input
+            .revealed_commitment()
+            .commit(&PedersenGens::default())
+            .to_affine();
+        let key_image = genesis.input_key_image;
+
+        assert_eq!(
+            check_spend(&key_image, &tx, &[vec![commitment]]).ok(),
+            Some(vec![commitment])
+        );
+
+        let not_an_input = bls::SecretKey::random().public_key();
+        assert!(check_spend(&not_an_input, &tx, &[vec![commitment]]).is_err());
+        assert!(check_spend(&key_image, &tx, &[]).is_err());
+
+        // The tx doesn't hold up with commitments other than those of its inputs.
+        let other = revealed_commitments[0]
+            .commit(&PedersenGens::default())
+            .to_affine();
+        assert!(matches!(
+            check_spend(&key_image, &tx, &[vec![other]]),
+            Err(ErrorMsg::InvalidTransaction(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Rehearsal of a split of our section: the sibling sections it would result in, simulated out
+//! of our members and the holders of our data, without proposing anything. A split is only
+//! proposed once neither half falls short, and the report of how close it is can be read by
+//! operators too.
+
+use super::Node;
+use crate::node::membership::{elder_candidates, split};
+
+use sn_interface::{
+    data_copy_count, messaging::system::NodeState, network_knowledge::SectionAuthorityProvider,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use xor_name::{Prefix, XorName};
+
+/// What a half of the split would lack to be a section of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Shortfall {
+    /// Too few members for the split to be worth it.
+    Members { have: usize, need: usize },
+    /// Too few candidates to make up its elders.
+    Elders { have: usize, need: usize },
+    /// Too few adults to hold as many copies of its data as there should be.
+    Adults { have: usize, need: usize },
+}
+
+/// One of the sibling sections the split would result in.
+#[derive(Clone, Debug)]
+pub(crate) struct HalfReport {
+    pub(crate) prefix: Prefix,
+    pub(crate) members: BTreeSet<NodeState>,
+    pub(crate) elders: BTreeSet<NodeState>,
+    /// Members which wouldn't be elders, holding its data.
+    pub(crate) adults: usize,
+    /// Data of ours falling in it.
+    pub(crate) chunks: usize,
+    /// Copies of that data its adults would have to make, not holding enough of them yet.
+    pub(crate) copies_to_make: usize,
+    pub(crate) shortfalls: Vec<Shortfall>,
+}
+
+/// The split of a section, as rehearsed.
+#[derive(Clone, Debug)]
+pub(crate) struct SplitReport {
+    pub(crate) prefix: Prefix,
+    pub(crate) zero: HalfReport,
+    pub(crate) one: HalfReport,
+}
+
+impl SplitReport {
+    /// Whether neither half falls short, so the split can be proposed.
+    pub(crate) fn is_ready(&self) -> bool {
+        self.zero.shortfalls.is_empty() && self.one.shortfalls.is_empty()
+    }
+}
+
+impl fmt::Display for SplitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "split of {:?}: ", self.prefix)?;
+        for half in [&self.zero, &self.one] {
+            write!(
+                f,
+                "{:?} with {} members, {} elders, {} adults, {} chunks, {} copies to make, \
+                 short of {:?}; ",
+                half.prefix,
+                half.members.len(),
+                half.elders.len(),
+                half.adults,
+                half.chunks,
+                half.copies_to_make,
+                half.shortfalls,
+            )?;
+        }
+        write!(f, "ready: {}", self.is_ready())
+    }
+}
+
+/// Simulates the split of the section of `prefix` into its sibling halves, out of its `members`,
+/// but the `excluded` ones as elders, and the `holders` of its data. `None` if the prefix can't
+/// be extended.
+pub(crate) fn validate(
+    prefix: &Prefix,
+    members: &BTreeMap<XorName, NodeState>,
+    excluded: &BTreeSet<XorName>,
+    sap: &SectionAuthorityProvider,
+    holders: &BTreeMap<XorName, BTreeSet<XorName>>,
+) -> Option<SplitReport> {
+    let (zero, one) = split(prefix, members.keys().copied())?;
+    let half = |bit, names: BTreeSet<XorName>| {
+        let members = names
+            .iter()
+            .map(|name| members[name].clone())
+            .collect::<BTreeSet<_>>();
+        simulate_half(prefix.pushed(bit), members, excluded, sap, holders)
+    };
+
+    Some(SplitReport {
+        prefix: *prefix,
+        zero: half(false, zero),
+        one: half(true, one),
+    })
+}
+
+fn simulate_half(
+    prefix: Prefix,
+    members: BTreeSet<NodeState>,
+    excluded: &BTreeSet<XorName>,
+    sap: &SectionAuthorityProvider,
+    holders: &BTreeMap<XorName, BTreeSet<XorName>>,
+) -> HalfReport {
+    let network_params = sap.network_params();
+    let elders = elder_candidates(
+        members
+            .iter()
+            .filter(|node| !excluded.contains(&node.name))
+            .cloned(),
+        sap,
+    );
+    let adults = members
+        .iter()
+        .filter(|node| !elders.contains(*node))
+        .map(|node| node.name)
+        .collect::<BTreeSet<_>>();
+
+    let copy_count = data_copy_count();
+    let (chunks, copies_to_make) = holders
+        .iter()
+        .filter(|(name, _)| prefix.matches(name))
+        .fold((0, 0), |(chunks, copies), (_, holders)| {
+            let held = holders.intersection(&adults).count();
+            (chunks + 1, copies + copy_count.saturating_sub(held))
+        });
+
+    let mut shortfalls = vec![];
+    let section_size = network_params.recommended_section_size();
+    if members.len() < section_size {
+        shortfalls.push(Shortfall::Members {
+            have: members.len(),
+            need: section_size,
+        });
+    }
+    if elders.len() < network_params.elder_count() {
+        shortfalls.push(Shortfall::Elders {
+            have: elders.len(),
+            need: network_params.elder_count(),
+        });
+    }
+    if adults.len() < copy_count {
+        shortfalls.push(Shortfall::Adults {
+            have: adults.len(),
+            need: copy_count,
+        });
+    }
+
+    HalfReport {
+        prefix,
+        members,
+        elders,
+        adults: adults.len(),
+        chunks,
+        copies_to_make,
+        shortfalls,
+    }
+}
+
+impl Node {
+    /// Rehearses the split of our section, but the `excluded` members as elders. `None` if we
+    /// aren't an elder, or our prefix can't be extended.
+    pub(crate) async fn rehearse_split(&self, excluded: &BTreeSet<XorName>) -> Option<SplitReport> {
+        let members = self
+            .membership
+            .read()
+            .await
+            .as_ref()?
+            .current_section_members();
+        let prefix = self.network_knowledge.prefix().await;
+        let sap = self.network_knowledge.authority_provider().await;
+
+        let mut holders = BTreeMap::new();
+        for name in self.holder_registry.names().await {
+            if let Some(holders_of) = self.holder_registry.holders_of(&name).await {
+                let _prev = holders.insert(name, holders_of);
+            }
+        }
+
+        validate(&prefix, &members, excluded, &sap, &holders)
+    }
+}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+    use super::*;
+    use sn_interface::network_knowledge::{
+        elder_count,
+        test_utils::{gen_section_authority_provider, gen_sorted_nodes},
+    };
+
+    fn gen_members(prefix: &Prefix, count: usize) -> BTreeMap<XorName, NodeState> {
+        gen_sorted_nodes(prefix, count, false)
+            .into_iter()
+            .map(|node| (node.name(), NodeState::joined(node.name(), node.addr, None)))
+            .collect()
+    }
+
+    #[test]
+    fn split_is_ready_once_neither_half_falls_short() {
+        let prefix = Prefix::default();
+        let (sap, _, _) = gen_section_authority_provider(prefix, elder_count());
+        let section_size = sap.network_params().recommended_section_size();
+
+        let zero = gen_members(&prefix.pushed(false), section_size);
+        let mut members = zero.clone();
+        members.extend(gen_members(&prefix.pushed(true), section_size - 1));
+
+        // Data of the zero half, held by one of its nodes.
+        let chunk = prefix
+            .pushed(false)
+            .substituted_in(xor_name::rand::random());
+        let holder = *zero.keys().next_back().expect("no members");
+        let holders = BTreeMap::from([(chunk, BTreeSet::from([holder]))]);
+
+        let report = validate(&prefix, &members, &BTreeSet::new(), &sap, &holders)
+            .expect("prefix can't be extended");
+        assert!(report.zero.shortfalls.is_empty());
+        assert_eq!(report.zero.chunks, 1);
+        assert_eq!(report.one.chunks, 0);
+        assert_eq!(
+            report.one.shortfalls,
+            vec![Shortfall::Members {
+                have: section_size - 1,
+                need: section_size
+            }]
+        );
+        assert!(!report.is_ready());
+
+        members.extend(gen_members(&prefix.pushed(true), 1));
+        let report = validate(&prefix, &members, &BTreeSet::new(), &sap, &holders)
+            .expect("prefix can't be extended");
+        assert!(report.is_ready());
+        assert_eq!(report.zero.elders.len(), elder_count());
+        assert_eq!(report.zero.adults, section_size - elder_count());
+        // The chunk's holder is an adult of its half only if it wasn't made an elder.
+        let held = usize::from(!report.zero.elders.iter().any(|node| node.name == holder));
+        assert_eq!(report.zero.copies_to_make, data_copy_count() - held);
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dbs::{deserialise, serialise};
+use crate::node::{cfg::keypair_storage::get_reward_pk, core::Node, Error, Result};
+use crate::persisted;
+
+use sn_interface::{
+    messaging::{
+        system::{NodeState, SectionAuth},
+        SectionAuthorityProvider,
+    },
+    network_knowledge::{prefix_map::NetworkPrefixMap, NetworkKnowledge},
+    types::PublicKey,
+};
+
+use secured_linked_list::SecuredLinkedList;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::time::Instant;
+use xor_name::XorName;
+
+const STATE_SNAPSHOT_FILE: &str = "state_snapshot";
+// Format of the snapshot file: a bincode `SavedState`.
+const STATE_SNAPSHOT_VERSION: u16 = 1;
+
+/// What a node knew of the network and held as it shut down, for it to pick up from there when
+/// restarted rather than bootstrapping and being given its data all over again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SavedState {
+    /// Our name and address when we shut down.
+    pub(crate) name: XorName,
+    pub(crate) addr: SocketAddr,
+    pub(crate) genesis_key: bls::PublicKey,
+    /// Our section's chain, SAP and members.
+    pub(crate) section_chain: SecuredLinkedList,
+    pub(crate) section_auth: SectionAuth<SectionAuthorityProvider>,
+    pub(crate) members: Vec<SectionAuth<NodeState>>,
+    pub(crate) prefix_map: NetworkPrefixMap,
+    /// The key our rewards are paid to, if we had one.
+    pub(crate) reward_key: Option<PublicKey>,
+    /// Chunks in our chunk index.
+    pub(crate) chunks: usize,
+    /// When the restart window we announced to our elders runs out, in secs since the Unix
+    /// epoch. Without one, we told our section we were leaving, and have to join anew.
+    pub(crate) resume_by: Option<u64>,
+}
+
+impl SavedState {
+    /// Whether we can still resume as a member of our section from this snapshot.
+    pub(crate) fn is_resumable(&self) -> bool {
+        matches!(self.resume_by, Some(resume_by) if resume_by > now())
+    }
+
+    /// The network knowledge snapshotted, checked against the network's `genesis_key`.
+    pub(crate) async fn network_knowledge(
+        &self,
+        genesis_key: bls::PublicKey,
+    ) -> Result<NetworkKnowledge> {
+        if self.genesis_key != genesis_key {
+            return Err(Error::InvalidGenesisKey(self.genesis_key));
+        }
+        let network_knowledge = NetworkKnowledge::new(
+            genesis_key,
+            self.section_chain.clone(),
+            self.section_auth.clone().into_authed_state(),
+            Some(self.prefix_map.clone()),
+        )?;
+        let members = self
+            .members
+            .iter()
+            .cloned()
+            .map(|member| member.into_authed_state())
+            .collect();
+        let _updated = network_knowledge.merge_members(members).await?;
+        Ok(network_knowledge)
+    }
+}
+
+/// Where the node's `SavedState` is kept in its root dir.
+#[derive(Clone, Debug)]
+pub(crate) struct StateDb {
+    root_dir: PathBuf,
+}
+
+impl StateDb {
+    pub(crate) fn new(root_dir: &Path) -> Self {
+        Self {
+            root_dir: root_dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.root_dir.join(STATE_SNAPSHOT_FILE)
+    }
+
+    pub(crate) async fn write(&self, snapshot: &SavedState) -> Result<()> {
+        let payload = serialise(snapshot)?;
+        persisted::write_async(self.path(), STATE_SNAPSHOT_VERSION, payload).await?;
+        Ok(())
+    }
+
+    /// Reads the snapshot left by our last shutdown, if any, and removes it so it's never used
+    /// twice. A snapshot which can't be read is left out, as the node can always bootstrap
+    /// instead.
+    pub(crate) async fn take(&self) -> Option<SavedState> {
+        let read = persisted::read_async(self.path()).await;
+        if let Err(error) = tokio::fs::remove_file(self.path()).await {
+            if error.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove the state snapshot: {:?}", error);
+            }
+        }
+        match read {
+            Ok(Some(read)) if read.version == STATE_SNAPSHOT_VERSION => {
+                match deserialise(&read.payload) {
+                    Ok(snapshot) => Some(snapshot),
+                    Err(error) => {
+                        warn!("Discarding the unreadable state snapshot: {:?}", error);
+                        None
+                    }
+                }
+            }
+            Ok(Some(read)) => {
+                warn!(
+                    "Discarding the state snapshot of unknown version {}",
+                    read.version
+                );
+                None
+            }
+            Ok(None) => None,
+            Err(error) => {
+                warn!("Discarding the damaged state snapshot: {}", error);
+                None
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Snapshots what we know of the network and hold, for our next start to pick up from.
+    pub(crate) async fn write_state_snapshot(&self) -> Result<()> {
+        let info = self.info.read().await.clone();
+        let resume_by = self.restart_deadline.read().await.and_then(|deadline| {
+            let left = deadline.checked_duration_since(Instant::now())?;
+            Some(now() + left.as_secs())
+        });
+        let snapshot = SavedState {
+            name: info.name(),
+            addr: info.addr,
+            genesis_key: *self.network_knowledge.genesis_key(),
+            section_chain: self.network_knowledge.section_chain().await,
+            section_auth: self
+                .network_knowledge
+                .section_signed_authority_provider()
+                .await
+                .into_authed_msg(),
+            members: self
+                .network_knowledge
+                .section_signed_members()
+                .await
+                .into_iter()
+                .map(|member| member.into_authed_msg())
+                .collect(),
+            prefix_map: self.network_knowledge.prefix_map().clone(),
+            reward_key: get_reward_pk(&self.state_db.root_dir)
+                .await?
+                .map(PublicKey::Ed25519),
+            chunks: self.data_storage.chunk_count(),
+            resume_by,
+        };
+        self.state_db.write(&snapshot).await?;
+        info!(
+            "Snapshotted our state, resumable until {:?}",
+            snapshot.resume_by
+        );
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persisted::damage;
+
+    use sn_interface::network_knowledge::test_utils::{
+        gen_section_authority_provider, section_signed,
+    };
+
+    use eyre::Result;
+    use tempfile::tempdir;
+    use xor_name::Prefix;
+
+    fn snapshot(resume_by: Option<u64>) -> Result<SavedState> {
+        let (section_auth, nodes, sk_set) = gen_section_authority_provider(Prefix::default(), 3);
+        let genesis_key = sk_set.public_keys().public_key();
+        let members = nodes
+            .iter()
+            .map(|node| {
+                section_signed(
+                    sk_set.secret_key(),
+                    sn_interface::network_knowledge::NodeState::joined(node.peer(), None).to_msg(),
+                )
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(SavedState {
+            name: nodes[0].name(),
+            addr: nodes[0].addr,
+            genesis_key,
+            section_chain: SecuredLinkedList::new(genesis_key),
+            section_auth: section_signed(sk_set.secret_key(), section_auth.to_msg())?,
+            members,
+            prefix_map: NetworkPrefixMap::new(genesis_key),
+            reward_key: None,
+            chunks: 42,
+            resume_by,
+        })
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_restored_once() -> Result<()> {
+        let root = tempdir()?;
+        let db = StateDb::new(root.path());
+        assert!(db.take().await.is_none());
+
+        let written = snapshot(Some(now() + 60))?;
+        db.write(&written).await?;
+        let taken = db.take().await.ok_or_else(|| eyre::eyre!("no snapshot"))?;
+        assert!(taken.is_resumable());
+        assert_eq!(taken.chunks, 42);
+        let network_knowledge = taken.network_knowledge(written.genesis_key).await?;
+        assert_eq!(network_knowledge.section_members().await.len(), 3);
+        assert!(taken
+            .network_knowledge(bls::SecretKey::random().public_key())
+            .await
+            .is_err());
+
+        // Taken, it's gone.
+        assert!(db.take().await.is_none());
+
+        // Without a restart window, or past it, it can't be resumed from.
+        assert!(!snapshot(None)?.is_resumable());
+        assert!(!snapshot(Some(now() - 1))?.is_resumable());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn damaged_snapshots_are_left_out() -> Result<()> {
+        let root = tempdir()?;
+        let db = StateDb::new(root.path());
+        let path = db.path();
+        let written = snapshot(None)?;
+        db.write(&written).await?;
+        for way in 0..damage::ways(&path)? {
+            db.write(&written).await?;
+            damage::apply(&path, way)?;
+            let _taken = db.take().await;
+            assert!(!path.exists());
+        }
+        Ok(())
+    }
+}
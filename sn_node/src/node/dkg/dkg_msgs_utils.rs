@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use sn_interface::messaging::system::{DkgFailureSig, DkgFailureSigSet, DkgSessionId, NodeState};
-use sn_interface::network_knowledge::supermajority;
+use sn_interface::network_knowledge::NetworkParams;
 use sn_interface::types::keys::ed25519::{self, Digest256, Keypair, Verifier};
 use std::collections::{BTreeMap, BTreeSet};
 use std::net::SocketAddr;
@@ -22,6 +22,7 @@ pub(crate) trait DkgSessionIdUtils {
         elder: BTreeMap<XorName, SocketAddr>,
         generation: u64,
         bootstrap_members: BTreeSet<NodeState>,
+        network_params: NetworkParams,
     ) -> Self;
 }
 
@@ -31,6 +32,7 @@ impl DkgSessionIdUtils for DkgSessionId {
         elders: BTreeMap<XorName, SocketAddr>,
         generation: u64,
         bootstrap_members: BTreeSet<NodeState>,
+        network_params: NetworkParams,
     ) -> Self {
         assert!(elders
             .keys()
@@ -42,6 +44,7 @@ impl DkgSessionIdUtils for DkgSessionId {
             elders,
             section_chain_len: generation,
             bootstrap_members,
+            network_params,
         }
     }
 }
@@ -105,7 +108,7 @@ impl DkgFailureSigSetUtils for DkgFailureSigSet {
     // Check whether we have enough signatures to reach agreement on the failure. The contained signatures
     // are assumed valid.
     fn has_agreement(&self, session_id: &DkgSessionId) -> bool {
-        has_failure_agreement(session_id.elders.len(), self.sigs.len())
+        has_failure_agreement(session_id, self.sigs.len())
     }
 
     fn verify(&self, reference_session_id: &DkgSessionId) -> bool {
@@ -120,15 +123,16 @@ impl DkgFailureSigSetUtils for DkgFailureSigSet {
             })
             .count();
 
-        has_failure_agreement(reference_session_id.elders.len(), votes)
+        has_failure_agreement(reference_session_id, votes)
     }
 }
 
 // Check whether we have enough signeds to reach agreement on the failure. We only need
 // `N - supermajority(N) + 1` signeds, because that already makes a supermajority agreement on a
 // successful outcome impossible.
-fn has_failure_agreement(num_participants: usize, num_votes: usize) -> bool {
-    num_votes > num_participants - supermajority(num_participants)
+fn has_failure_agreement(session_id: &DkgSessionId, num_votes: usize) -> bool {
+    let num_participants = session_id.elders.len();
+    num_votes > num_participants - session_id.network_params.supermajority(num_participants)
 }
 
 // Create a value whose signature serves as proof that a failure of a DKG session with the given
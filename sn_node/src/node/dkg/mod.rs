@@ -10,5 +10,7 @@ pub(crate) mod dkg_msgs_utils;
 mod session;
 mod voter;
 
-pub(crate) use self::{dkg_msgs_utils::DkgFailureSigSetUtils, voter::DkgVoter};
+pub(crate) use self::{
+    dkg_msgs_utils::DkgFailureSigSetUtils, session::DkgSessionState, voter::DkgVoter,
+};
 pub(crate) use sn_interface::messaging::system::{KeyedSig, SigShare};
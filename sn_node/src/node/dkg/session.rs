@@ -28,7 +28,7 @@ use sn_consensus::Generation;
 use std::{
     collections::{BTreeMap, BTreeSet},
     iter, mem,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use xor_name::XorName;
 
@@ -38,6 +38,12 @@ const DKG_PROGRESS_INTERVAL: Duration = Duration::from_secs(6);
 // Retry sending messages when they timeout
 const MAX_TIMEOUT_RETRIES: usize = 3;
 
+// Time a session has to complete before it's considered stalled and restarted.
+const DKG_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Restarts of a stalled session before its blockers are reported as failed for good.
+const MAX_SESSION_RESTARTS: usize = 2;
+
 // Data for a DKG participant.
 pub(crate) struct Session {
     pub(crate) session_id: DkgSessionId,
@@ -55,6 +61,25 @@ pub(crate) struct Session {
     pub(crate) retries: usize,
     // Membership generation
     pub(crate) generation: Generation,
+
+    // When the current run of the session started, and how many times it was restarted
+    pub(crate) started: Instant,
+    pub(crate) restarts: usize,
+}
+
+/// Where a DKG session is at, for operators to see what holds up an elder handover.
+#[derive(Clone, Debug)]
+pub(crate) struct DkgSessionState {
+    pub(crate) session_id: DkgSessionId,
+    pub(crate) generation: Generation,
+    pub(crate) phase: Phase,
+    /// Participants whose contributions to the current phase we're still missing.
+    pub(crate) missing: BTreeSet<XorName>,
+    /// Time since the current run of the session started.
+    pub(crate) elapsed: Duration,
+    pub(crate) restarts: usize,
+    pub(crate) retries: usize,
+    pub(crate) complete: bool,
 }
 
 fn is_dkg_behind(expected: Phase, actual: Phase) -> bool {
@@ -67,10 +92,51 @@ fn is_dkg_behind(expected: Phase, actual: Phase) -> bool {
 }
 
 impl Session {
+    // Creates the session of participant `name`, returning the messages to start it with.
+    pub(crate) fn new(
+        name: XorName,
+        session_id: DkgSessionId,
+        participant_index: usize,
+        generation: Generation,
+    ) -> std::result::Result<(Self, Vec<MessageAndTarget>), DkgError> {
+        let (key_gen, messages) = initialize_key_gen(name, &session_id)?;
+        let session = Self {
+            key_gen,
+            failures: DkgFailureSigSet::from(session_id.clone()),
+            session_id,
+            participant_index,
+            timer_token: 0,
+            complete: false,
+            last_message_broadcast: messages.clone(),
+            retries: 0,
+            generation,
+            started: Instant::now(),
+            restarts: 0,
+        };
+        Ok((session, messages))
+    }
+
     pub(crate) fn timer_token(&self) -> u64 {
         self.timer_token
     }
 
+    pub(crate) fn state(&self) -> DkgSessionState {
+        DkgSessionState {
+            session_id: self.session_id.clone(),
+            generation: self.generation,
+            phase: self.key_gen.phase(),
+            missing: if self.complete {
+                BTreeSet::new()
+            } else {
+                self.key_gen.possible_blockers()
+            },
+            elapsed: self.started.elapsed(),
+            restarts: self.restarts,
+            retries: self.retries,
+            complete: self.complete,
+        }
+    }
+
     fn send_dkg_not_ready(
         &mut self,
         node: &NodeInfo,
@@ -253,6 +319,10 @@ impl Session {
             return Ok(vec![]);
         }
 
+        if self.started.elapsed() >= DKG_SESSION_TIMEOUT {
+            return self.handle_stall(node, section_pk);
+        }
+
         trace!("DKG progressing for {:?}", self.session_id);
 
         match self.key_gen.timed_phase_transition(&mut rand::thread_rng()) {
@@ -288,12 +358,64 @@ impl Session {
                         error
                     );
                     let failed_participants = self.key_gen.possible_blockers();
-                    self.report_failure(node, failed_participants, section_pk)
+                    let mut cmds = self.report_failure(node, failed_participants, section_pk)?;
+                    // Should the failure never be agreed on, the session is restarted once it
+                    // times out.
+                    cmds.push(self.reset_timer());
+                    Ok(cmds)
                 }
             }
         }
     }
 
+    // The session didn't complete in time: it's restarted from scratch, unless it already was
+    // too many times, in which case whoever blocks it is reported as failed, for the elders to
+    // start a new session without them.
+    fn handle_stall(&mut self, node: &NodeInfo, section_pk: BlsPublicKey) -> Result<Vec<Cmd>> {
+        let blockers = self.key_gen.possible_blockers();
+        if self.restarts >= MAX_SESSION_RESTARTS {
+            warn!(
+                "{} {:?} in {:?} after {} restarts, blocked by {:?}",
+                LogMarker::DkgSessionStalled,
+                self.session_id,
+                self.key_gen.phase(),
+                self.restarts,
+                blockers
+            );
+            return self.report_failure(node, blockers, section_pk);
+        }
+
+        let (key_gen, messages) = match initialize_key_gen(node.name(), &self.session_id) {
+            Ok(initialized) => initialized,
+            Err(error) => {
+                error!(
+                    "DKG failed to restart for {:?}: {:?}",
+                    self.session_id, error
+                );
+                return self.report_failure(node, blockers, section_pk);
+            }
+        };
+
+        self.restarts += 1;
+        info!(
+            "{} {:?} ({}/{}), stalled in {:?} blocked by {:?}",
+            LogMarker::DkgSessionRestarted,
+            self.session_id,
+            self.restarts,
+            MAX_SESSION_RESTARTS,
+            self.key_gen.phase(),
+            blockers
+        );
+        self.key_gen = key_gen;
+        self.retries = 0;
+        self.started = Instant::now();
+        self.last_message_broadcast = messages.clone();
+
+        let mut cmds = self.broadcast(node, messages, section_pk)?;
+        cmds.push(self.reset_timer());
+        Ok(cmds)
+    }
+
     // Check whether a key generator is finalized to give a DKG outcome.
     fn check(&mut self, node: &NodeInfo, section_pk: BlsPublicKey) -> Result<Vec<Cmd>> {
         if self.complete {
@@ -490,7 +612,7 @@ impl Session {
         }
     }
 
-    fn reset_timer(&mut self) -> Cmd {
+    pub(crate) fn reset_timer(&mut self) -> Cmd {
         self.timer_token = next_timer_token();
         Cmd::ScheduleTimeout {
             duration: DKG_PROGRESS_INTERVAL,
@@ -499,6 +621,15 @@ impl Session {
     }
 }
 
+fn initialize_key_gen(
+    name: XorName,
+    session_id: &DkgSessionId,
+) -> std::result::Result<(KeyGen, Vec<MessageAndTarget>), DkgError> {
+    let threshold = session_id.network_params.threshold(session_id.elders.len());
+    let participants = session_id.elder_names().collect();
+    KeyGen::initialize(name, threshold, participants)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -508,7 +639,7 @@ mod tests {
     use sn_interface::messaging::MsgType;
     #[cfg(feature = "test-utils")]
     use sn_interface::network_knowledge::test_utils::gen_addr;
-    use sn_interface::network_knowledge::{NodeInfo, MIN_ADULT_AGE};
+    use sn_interface::network_knowledge::{NetworkParams, NodeInfo, MIN_ADULT_AGE};
 
     #[cfg(feature = "test-utils")]
     use sn_interface::types::keys::ed25519::{self, proptesting::arbitrary_keypair};
@@ -546,6 +677,7 @@ mod tests {
             elders,
             section_chain_len: 0,
             bootstrap_members,
+            network_params: NetworkParams::default(),
         };
 
         let cmds = voter.start(&node, session_id, section_pk, 0).await?;
@@ -554,6 +686,91 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn stalled_session_is_restarted_then_its_blockers_reported() -> Result<()> {
+        let prefix = Prefix::default();
+        let section_pk = bls::SecretKey::random().public_key();
+        let nodes: Vec<_> = (0..3)
+            .map(|_| {
+                NodeInfo::new(
+                    ed25519::gen_keypair(&prefix.range_inclusive(), MIN_ADULT_AGE),
+                    gen_addr(),
+                )
+            })
+            .collect();
+        let session_id = DkgSessionId {
+            prefix,
+            elders: nodes.iter().map(|n| (n.name(), n.addr)).collect(),
+            section_chain_len: 0,
+            bootstrap_members: nodes
+                .iter()
+                .map(|n| NodeState::joined(n.name(), n.addr, None))
+                .collect(),
+            network_params: NetworkParams::default(),
+        };
+        let node = &nodes[0];
+        let others: BTreeSet<_> = nodes[1..].iter().map(|n| n.name()).collect();
+
+        // The session times out even if no msg ever comes in, the others not contributing.
+        let voter = DkgVoter::default();
+        let cmds = voter.start(node, session_id.clone(), section_pk, 1).await?;
+        assert!(cmds
+            .iter()
+            .any(|cmd| matches!(cmd, Cmd::ScheduleTimeout { .. })));
+        let states = voter.sessions_state();
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].phase, Phase::Initialization);
+        assert_eq!(states[0].missing, others);
+        assert!(!states[0].complete);
+
+        let index = session_id
+            .elder_index(node.name())
+            .context("not a participant")?;
+        let (mut session, _) = Session::new(node.name(), session_id, index, 1)?;
+        let stall = |session: &mut Session| -> Result<()> {
+            session.started = Instant::now()
+                .checked_sub(DKG_SESSION_TIMEOUT)
+                .context("too early to stall")?;
+            Ok(())
+        };
+
+        for restart in 1..=MAX_SESSION_RESTARTS {
+            stall(&mut session)?;
+            let cmds = session.handle_timeout(node, section_pk)?;
+            assert_eq!(session.restarts, restart);
+            assert!(session.started.elapsed() < DKG_SESSION_TIMEOUT);
+            assert!(cmds
+                .iter()
+                .any(|cmd| matches!(cmd, Cmd::ScheduleTimeout { .. })));
+        }
+
+        // Restarted too many times, the session's blockers are reported as failed.
+        stall(&mut session)?;
+        let cmds = session.handle_timeout(node, section_pk)?;
+        assert_eq!(session.restarts, MAX_SESSION_RESTARTS);
+        let failed = cmds
+            .into_iter()
+            .find_map(|cmd| match cmd {
+                Cmd::SendMsg { wire_msg, .. } => match wire_msg.into_msg() {
+                    Ok(MsgType::System {
+                        msg:
+                            SystemMsg::DkgFailureObservation {
+                                failed_participants,
+                                ..
+                            },
+                        ..
+                    }) => Some(failed_participants),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .context("no failure reported")?;
+        assert_eq!(failed, others);
+
+        Ok(())
+    }
+
     proptest! {
         // Run a DKG session where every participant handles every message sent to them.
         // Expect the session to successfully complete without timed transitions.
@@ -582,6 +799,7 @@ mod tests {
                     .iter()
                     .map(|n| NodeState::joined(n.name(), n.addr, None)),
             ),
+            network_params: NetworkParams::default(),
         };
 
         let mut actors: HashMap<_, _> = nodes
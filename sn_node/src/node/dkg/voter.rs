@@ -6,22 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::{api::cmds::Cmd, dkg::session::Session, messages::WireMsgUtils, Result};
+use crate::node::{
+    api::cmds::Cmd,
+    dkg::session::{DkgSessionState, Session},
+    messages::WireMsgUtils,
+    Result,
+};
 use sn_interface::{
     messaging::{
-        system::{DkgFailureSig, DkgFailureSigSet, DkgSessionId, SystemMsg},
+        system::{DkgFailureSig, DkgSessionId, SystemMsg},
         DstLocation, WireMsg,
     },
     types::keys::ed25519::Digest256,
 };
 
-use sn_interface::network_knowledge::{
-    supermajority, NodeInfo, SectionAuthorityProvider, SectionKeyShare,
-};
+use sn_interface::network_knowledge::{NodeInfo, SectionAuthorityProvider, SectionKeyShare};
 use sn_interface::types::{keys::ed25519, Peer};
 
 use bls::PublicKey as BlsPublicKey;
-use bls_dkg::key_gen::{message::Message as DkgMessage, KeyGen};
+use bls_dkg::key_gen::message::Message as DkgMessage;
 use dashmap::DashMap;
 use sn_consensus::Generation;
 use std::{collections::BTreeSet, sync::Arc};
@@ -96,27 +99,14 @@ impl DkgVoter {
             }]);
         }
 
-        let threshold = supermajority(session_id.elders.len()) - 1;
-        let participants = session_id.elder_names().collect();
-
-        match KeyGen::initialize(name, threshold, participants) {
-            Ok((key_gen, messages)) => {
+        match Session::new(name, session_id.clone(), participant_index, generation) {
+            Ok((mut session, messages)) => {
                 trace!("DKG starting for {session_id:?}");
 
-                let mut session = Session {
-                    key_gen,
-                    session_id: session_id.clone(),
-                    participant_index,
-                    timer_token: 0,
-                    failures: DkgFailureSigSet::from(session_id.clone()),
-                    complete: false,
-                    last_message_broadcast: vec![],
-                    retries: 0,
-                    generation,
-                };
-
                 let mut cmds = vec![];
                 cmds.extend(session.broadcast(node, messages, section_pk)?);
+                // Lest the session stall with no msg ever coming in.
+                cmds.push(session.reset_timer());
 
                 // This is to avoid the case that between the above existence check
                 // and the insertion, there is another thread created and updated the session.
@@ -214,6 +204,17 @@ impl DkgVoter {
             .process_failure(session_id, failed_participants, signed)
     }
 
+    // The state of each session we take part in, oldest first.
+    pub(crate) fn sessions_state(&self) -> Vec<DkgSessionState> {
+        let mut states: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|session| session.value().state())
+            .collect();
+        states.sort_by_key(|state| state.session_id.section_chain_len);
+        states
+    }
+
     pub(crate) fn get_cached_msgs(&self, session_id: &DkgSessionId) -> Vec<DkgMessage> {
         if let Some(session) = self.sessions.get_mut(&session_id.hash()) {
             session.get_cached_msgs()
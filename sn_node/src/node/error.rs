@@ -8,8 +8,12 @@
 
 use super::Prefix;
 
+use crate::dbs::{Error as DbError, StorageInitError};
 use crate::node::handover::Error as HandoverError;
+use sn_interface::data_copy_count;
 use sn_interface::messaging::data::Error as ErrorMsg;
+use sn_interface::messaging::{system::JoinRejectionReason, ProtocolVersions};
+use sn_interface::protocol_constants::ProtocolDigest;
 use sn_interface::types::{convert_dt_error_to_error_msg, DataAddress, Peer, PublicKey};
 
 use secured_linked_list::error::Error as SecuredLinkedListError;
@@ -43,6 +47,8 @@ pub enum Error {
     PermitAcquisitionFailed,
     #[error("Section authority provider cannot be trusted: {0}")]
     UntrustedSectionAuthProvider(String),
+    #[error("Section authority provider is invalid: {0}")]
+    InvalidSap(#[from] sn_interface::network_knowledge::SapValidationError),
     #[error("Proof chain cannot be trusted: {0}")]
     UntrustedProofChain(String),
     #[error("Invalid genesis key of provided prefix map: {}", hex::encode(_0.to_bytes()))]
@@ -89,6 +95,24 @@ pub enum Error {
     NoMatchingElder,
     #[error("Node cannot join the network since it is not externally reachable: {0}")]
     NodeNotReachable(SocketAddr),
+    #[error("The destination section rejected our relocation: {0:?}")]
+    RelocationRejected(JoinRejectionReason),
+    #[error(
+        "Node cannot join the network since it runs with other protocol constants than the \
+        section: ours {ours}, the section's {section}"
+    )]
+    ProtocolMismatch {
+        ours: ProtocolDigest,
+        section: ProtocolDigest,
+    },
+    #[error(
+        "Node cannot join the network since it can't read the section's messages or the other \
+        way round: ours {ours}, the section's {section}"
+    )]
+    IncompatibleVersion {
+        ours: ProtocolVersions,
+        section: ProtocolVersions,
+    },
     /// Timeout when trying to join the network
     #[error("Timeout when trying to join the network")]
     JoinTimeout,
@@ -152,6 +176,34 @@ pub enum Error {
     /// Elder Handover related errors
     #[error("Handover Error:: {0}")]
     HandoverError(#[from] HandoverError),
+    /// A check of where data is held is already running.
+    #[error("A distribution check is already running")]
+    DistributionCheckRunning,
+    /// What an operator asked to replicate can't be.
+    #[error("Invalid replication target: {0}")]
+    InvalidReplicationTarget(String),
+    /// A chunk couldn't be split into shares, or rebuilt from them.
+    #[error("Erasure coding error: {0}")]
+    ErasureCoding(String),
+    /// The DBC of the key image is already logged as spent, in another tx.
+    #[error("DBC of key image {0:?} is already spent")]
+    DbcAlreadySpent(bls::PublicKey),
+    /// A share of a spent proof doesn't match what's logged for its DBC.
+    #[error("Invalid spent proof share: {0}")]
+    InvalidSpentProofShare(String),
+    /// An entry of the spentbook couldn't be read back.
+    #[error("Unreadable spentbook entry: {0}")]
+    UnreadableSpentbookEntry(String),
+}
+
+impl Error {
+    /// Why the node's chunk store couldn't be initialised, if that's what failed.
+    pub fn storage_init(&self) -> Option<&StorageInitError> {
+        match self {
+            Self::Database(DbError::StorageInit(error)) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl From<qp2p::ClientEndpointError> for Error {
@@ -177,7 +229,14 @@ pub(crate) fn convert_to_error_msg(error: Error) -> ErrorMsg {
         Error::InvalidOwner(key) => ErrorMsg::InvalidOwner(key),
         Error::NoSuchData(address) => ErrorMsg::DataNotFound(address),
         Error::DataExists => ErrorMsg::DataExists,
+        Error::NoAdults(prefix) => ErrorMsg::InsufficientAdults {
+            prefix,
+            expected: data_copy_count() as u8,
+            found: 0,
+        },
         Error::NetworkData(error) => convert_dt_error_to_error_msg(error),
-        other => ErrorMsg::InvalidOperation(format!("Failed to perform operation: {:?}", other)),
+        Error::Database(error) => crate::dbs::convert_to_error_msg(error),
+        Error::DbcAlreadySpent(key_image) => ErrorMsg::DbcAlreadySpent(key_image),
+        other => ErrorMsg::Internal(format!("Failed to perform operation: {:?}", other)),
     }
 }
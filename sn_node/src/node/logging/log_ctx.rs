@@ -6,9 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::node::api::dispatcher::Dispatcher;
+use crate::node::api::{
+    dispatcher::Dispatcher,
+    supervisor::{BackgroundTaskStatus, TaskCounts, TaskSupervisor},
+};
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use xor_name::Prefix;
 
 pub(crate) struct LogCtx {
@@ -23,4 +26,20 @@ impl LogCtx {
     pub(crate) async fn prefix(&self) -> Prefix {
         self.cmds_dispatcher.node.network_knowledge().prefix().await
     }
+
+    pub(crate) fn supervisor(&self) -> TaskSupervisor {
+        self.cmds_dispatcher.supervisor.clone()
+    }
+
+    pub(crate) async fn background_tasks(&self) -> Vec<BackgroundTaskStatus> {
+        self.cmds_dispatcher.supervisor.statuses().await
+    }
+
+    pub(crate) fn has_failed_background_task(&self) -> bool {
+        self.cmds_dispatcher.supervisor.has_failed_task()
+    }
+
+    pub(crate) fn task_counts(&self) -> BTreeMap<&'static str, TaskCounts> {
+        self.cmds_dispatcher.supervisor.task_counts()
+    }
 }
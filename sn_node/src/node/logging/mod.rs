@@ -10,12 +10,13 @@ pub(super) mod log_ctx;
 mod system;
 
 use self::log_ctx::LogCtx;
-use std::time::Duration;
+use crate::node::api::supervisor::RestartPolicy;
+use std::{sync::Arc, time::Duration};
 use sysinfo::PidExt;
 use sysinfo::{System, SystemExt};
 use system::Process;
 use tokio::time::MissedTickBehavior;
-use tracing::trace;
+use tracing::{trace, warn};
 
 const LOG_INTERVAL: Duration = std::time::Duration::from_secs(60);
 
@@ -23,15 +24,26 @@ pub(super) async fn run_system_logger(ctx: LogCtx, print_resources_usage: bool)
     let mut system = System::new_all();
     initial_log(&mut system, &ctx).await;
 
-    let _handle = tokio::task::spawn(async move {
-        let mut interval = tokio::time::interval(LOG_INTERVAL);
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip); // default is `Burst`, probably not what we want
-        loop {
-            let _instant = interval.tick().await;
-            system.refresh_all();
-            log(&mut system, &ctx, print_resources_usage).await;
-        }
-    });
+    let ctx = Arc::new(ctx);
+    let supervisor = ctx.supervisor();
+    supervisor
+        .spawn(
+            "system_logger",
+            RestartPolicy::default_backoff(),
+            move || log_periodically(ctx.clone(), print_resources_usage),
+        )
+        .await;
+}
+
+async fn log_periodically(ctx: Arc<LogCtx>, print_resources_usage: bool) {
+    let mut system = System::new_all();
+    let mut interval = tokio::time::interval(LOG_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip); // default is `Burst`, probably not what we want
+    loop {
+        let _instant = interval.tick().await;
+        system.refresh_all();
+        log(&mut system, &ctx, print_resources_usage).await;
+    }
 }
 
 async fn initial_log(system: &mut System, ctx: &LogCtx) {
@@ -75,4 +87,12 @@ async fn log(system: &mut System, ctx: &LogCtx, print_resources_usage: bool) {
             )
         }
     }
+
+    let background_tasks = ctx.background_tasks().await;
+    if ctx.has_failed_background_task() {
+        warn!("{}: Background tasks: {:?}", prefix, background_tasks);
+    } else {
+        trace!("{}: Background tasks: {:?}", prefix, background_tasks);
+    }
+    trace!("{}: Tasks by subsystem: {:?}", prefix, ctx.task_counts());
 }
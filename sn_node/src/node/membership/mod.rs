@@ -4,7 +4,7 @@ use bls_dkg::{PublicKeySet, SecretKeyShare};
 use core::fmt::Debug;
 use sn_interface::{
     messaging::system::{MembershipState, NodeState},
-    network_knowledge::{recommended_section_size, SectionAuthorityProvider, MIN_ADULT_AGE},
+    network_knowledge::{NetworkParams, SectionAuthorityProvider, MIN_ADULT_AGE},
 };
 use thiserror::Error;
 use xor_name::{Prefix, XorName};
@@ -78,7 +78,7 @@ pub(crate) fn elder_candidates(
     candidates
         .into_iter()
         .sorted_by(|lhs, rhs| cmp_elder_candidates(lhs, rhs, current_elders))
-        .take(sn_interface::elder_count())
+        .take(current_elders.network_params().elder_count())
         .collect()
 }
 
@@ -88,6 +88,7 @@ pub(crate) struct Membership {
     bootstrap_members: BTreeSet<NodeState>,
     gen: Generation,
     history: BTreeMap<Generation, (Decision<NodeState>, Consensus<NodeState>)>,
+    network_params: NetworkParams,
 }
 
 impl Membership {
@@ -96,12 +97,14 @@ impl Membership {
         elders: PublicKeySet,
         n_elders: usize,
         bootstrap_members: BTreeSet<NodeState>,
+        network_params: NetworkParams,
     ) -> Self {
         Membership {
             consensus: Consensus::from(secret_key, elders, n_elders),
             bootstrap_members,
             gen: 0,
             history: BTreeMap::default(),
+            network_params,
         }
     }
 
@@ -353,7 +356,7 @@ impl Membership {
         members: impl IntoIterator<Item = XorName>,
     ) -> bool {
         // We multiply by two to allow a buffer for when nodes are joining sequentially.
-        let split_section_size_cap = recommended_section_size() * 2;
+        let split_section_size_cap = self.network_params.recommended_section_size() * 2;
 
         match split(prefix, members) {
             Some((zeros, ones)) => {
@@ -24,13 +24,19 @@ mod messages;
 
 use sn_interface::types::Peer;
 
+#[cfg(feature = "back-pressure")]
+pub use self::core::BackPressureStrategy;
 pub use self::{
     api::{
-        event::{Elders, Event, MessageReceived, NodeElderChange},
-        event_stream::EventStream,
-        NodeApi,
+        event::{Elders, Event, MembershipEvent, MessageReceived, NodeElderChange},
+        event_stream::{EventStream, MembershipEvents},
+        fair_scheduler::ClientQueueStats,
+        shutdown::ShutdownHandle,
+        supervisor::{BackgroundTaskStatus, RestartPolicy, TaskCounts, TaskExit, TaskStatus},
+        LogFilterReloader, NodeApi,
     },
     cfg::config_handler::{add_connection_info, set_connection_info, Config},
+    core::{CacheOptions, CacheStats},
     error::{Error, Result},
 };
 pub use qp2p::{Config as NetworkConfig, SendStream};
@@ -42,6 +48,10 @@ pub use xor_name::{Prefix, XorName, XOR_NAME_LEN}; // TODO remove pub on API upd
 #[cfg(feature = "test-utils")]
 pub use test_utils::*;
 
+pub(crate) use self::cfg::keypair_storage::{
+    get_contacts_key, read_reward_secret_key, read_storage_key, reward_secret_key_path, seal_keys,
+    storage_key_path,
+};
 pub(crate) use self::core::MIN_LEVEL_WHEN_FULL;
 
 #[cfg(any(test, feature = "test-utils"))]
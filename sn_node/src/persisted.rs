@@ -0,0 +1,291 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Files the node persists in its root dir, and reads back.
+//!
+//! Each file is framed as:
+//!
+//! ```text
+//! magic (4) | format version (2) | payload length (4) | payload | checksum (8)
+//! ```
+//!
+//! the checksum being the start of the SHA3-256 of all that precedes it. Files are written to a
+//! temporary name, synced, then renamed in place, so a crash leaves either the previous file or
+//! the new one. Reading one back tells a truncated file from a corrupt one and from one of
+//! another format version, for each caller to recover as it documents: by regenerating the
+//! file, migrating it, or refusing to start.
+//!
+//! Files written before they were framed have no magic, and are read as format version 0, their
+//! whole content being the payload.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tiny_keccak::{Hasher, Sha3};
+
+/// Start of every framed file.
+pub(crate) const MAGIC: [u8; 4] = *b"SNPF";
+// Magic, version and payload length.
+const HEADER_SIZE: usize = 4 + 2 + 4;
+const CHECKSUM_SIZE: usize = 8;
+
+/// Why a persisted file couldn't be read back.
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    /// The file ends before its payload or checksum does, e.g. after a torn write.
+    #[error("{} is truncated", .0.display())]
+    Truncated(PathBuf),
+    /// The file doesn't match its checksum, or its payload can't be parsed.
+    #[error("{} is corrupt: {}", .path.display(), .reason)]
+    Corrupt {
+        /// The file.
+        path: PathBuf,
+        /// What's wrong with it.
+        reason: String,
+    },
+    /// The file is of a format version this node doesn't read.
+    #[error("{} is of format version {}, expected {}", .path.display(), .found, .expected)]
+    WrongVersion {
+        /// The file.
+        path: PathBuf,
+        /// Its version.
+        found: u16,
+        /// The version read.
+        expected: u16,
+    },
+    /// The file is encrypted, and couldn't be decrypted.
+    #[error("{} is locked: {}", .path.display(), .reason)]
+    Locked {
+        /// The file.
+        path: PathBuf,
+        /// Why it couldn't be decrypted.
+        reason: String,
+    },
+    /// The file couldn't be read.
+    #[error("couldn't read {}: {}", .0.display(), .1)]
+    Io(PathBuf, #[source] io::Error),
+}
+
+impl Error {
+    /// A payload of `path` that can't be parsed.
+    pub(crate) fn corrupt(path: &Path, reason: impl ToString) -> Self {
+        Self::Corrupt {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Content of a persisted file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Persisted {
+    /// Format version, 0 for a file written before files were framed.
+    pub(crate) version: u16,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Frames `payload` as of format `version`.
+pub(crate) fn encode(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len() + CHECKSUM_SIZE);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bytes
+}
+
+/// Unframes the content of the file at `path`, of whichever format version.
+pub(crate) fn decode(path: &Path, bytes: &[u8]) -> Result<Persisted, Error> {
+    let truncated = || Error::Truncated(path.to_path_buf());
+    if bytes.len() < MAGIC.len() {
+        // Nothing legible was written, or the magic was cut short.
+        return if MAGIC.starts_with(bytes) {
+            Err(truncated())
+        } else {
+            Ok(Persisted {
+                version: 0,
+                payload: bytes.to_vec(),
+            })
+        };
+    }
+    if bytes[..MAGIC.len()] != MAGIC {
+        return Ok(Persisted {
+            version: 0,
+            payload: bytes.to_vec(),
+        });
+    }
+
+    let header = bytes.get(..HEADER_SIZE).ok_or_else(truncated)?;
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    let len = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let framed_len = HEADER_SIZE + len + CHECKSUM_SIZE;
+    if bytes.len() < framed_len {
+        return Err(truncated());
+    }
+    if bytes.len() > framed_len {
+        return Err(Error::corrupt(path, "trailing bytes"));
+    }
+
+    let (content, expected) = bytes.split_at(HEADER_SIZE + len);
+    if checksum(content) != expected {
+        return Err(Error::corrupt(path, "checksum mismatch"));
+    }
+    Ok(Persisted {
+        version,
+        payload: content[HEADER_SIZE..].to_vec(),
+    })
+}
+
+/// Reads the file at `path`, if there's one.
+pub(crate) fn read(path: &Path) -> Result<Option<Persisted>, Error> {
+    match fs::read(path) {
+        Ok(bytes) => decode(path, &bytes).map(Some),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::Io(path.to_path_buf(), err)),
+    }
+}
+
+/// Atomically replaces the file at `path` with `payload`, framed as of format `version`.
+pub(crate) fn write(path: &Path, version: u16, payload: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(&encode(version, payload))?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // Make the rename itself durable.
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// `read`, off the async runtime.
+pub(crate) async fn read_async(path: PathBuf) -> Result<Option<Persisted>, Error> {
+    let io_path = path.clone();
+    tokio::task::spawn_blocking(move || read(&path))
+        .await
+        .map_err(|err| Error::Io(io_path, io::Error::new(io::ErrorKind::Other, err)))?
+}
+
+/// `write`, off the async runtime.
+pub(crate) async fn write_async(path: PathBuf, version: u16, payload: Vec<u8>) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || write(&path, version, &payload))
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+}
+
+fn checksum(content: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut hasher = Sha3::v256();
+    hasher.update(content);
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    let mut checksum = [0; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&hash[..CHECKSUM_SIZE]);
+    checksum
+}
+
+/// Damage done to persisted files in tests, of the kinds every reader must survive: each
+/// truncation, then each single bit flip.
+#[cfg(test)]
+pub(crate) mod damage {
+    use std::{fs, io, path::Path};
+
+    /// Number of ways the file at `path` can be damaged.
+    pub(crate) fn ways(path: &Path) -> io::Result<usize> {
+        let len = fs::read(path)?.len();
+        Ok(len + len * 8)
+    }
+
+    /// Damages the file at `path` the `way`th way.
+    pub(crate) fn apply(path: &Path, way: usize) -> io::Result<()> {
+        let mut bytes = fs::read(path)?;
+        let len = bytes.len();
+        if way < len {
+            bytes.truncate(way);
+        } else {
+            let bit = way - len;
+            bytes[bit / 8] ^= 1 << (bit % 8);
+        }
+        fs::write(path, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn written_files_read_back() -> Result<()> {
+        let root = tempdir()?;
+        let path = root.path().join("file");
+        assert!(read(&path)?.is_none());
+
+        write(&path, 3, b"payload")?;
+        write(&path, 3, b"new payload")?;
+        assert_eq!(
+            read(&path)?,
+            Some(Persisted {
+                version: 3,
+                payload: b"new payload".to_vec()
+            })
+        );
+        assert!(!root.path().join("file.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn unframed_files_are_version_zero() -> Result<()> {
+        let path = Path::new("legacy");
+        let read = decode(path, b"deadbeef")?;
+        assert_eq!(read.version, 0);
+        assert_eq!(read.payload, b"deadbeef");
+        Ok(())
+    }
+
+    #[test]
+    fn any_truncation_is_detected() {
+        let path = Path::new("file");
+        let bytes = encode(1, b"some payload");
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(decode(path, &bytes[..len]), Err(Error::Truncated(_))),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn any_bit_flip_is_detected() {
+        let path = Path::new("file");
+        let bytes = encode(1, b"some payload");
+        for bit in 0..bytes.len() * 8 {
+            let mut flipped = bytes.clone();
+            flipped[bit / 8] ^= 1 << (bit % 8);
+            match decode(path, &flipped) {
+                // A flip in the magic makes it look unframed, which its reader then can't parse.
+                Ok(read) => assert!(bit < MAGIC.len() * 8 && read.version == 0),
+                Err(Error::Corrupt { .. } | Error::Truncated(_)) => {}
+                Err(error) => panic!("unexpected {:?} from flipping bit {}", error, bit),
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! How the node's last start went, for supervisors to tell why a node didn't come up without
+//! going through its logs.
+//!
+//! The node records in its root dir when it starts, has started, or has failed to, the latter
+//! along with the error and, for storage errors, their details and what to do about them.
+
+use crate::{node::Error, persisted, StorageInitError};
+
+use serde::{Deserialize, Serialize};
+use std::{io, path::Path};
+
+/// Name of the startup status file in the node's root dir.
+pub const STARTUP_STATUS_FILE: &str = "startup_status";
+// Format of the file, JSON.
+const STARTUP_STATUS_VERSION: u16 = 1;
+
+/// Where the node's last start is at.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum StartupStatus {
+    /// The node is starting.
+    Starting,
+    /// The node has joined the network and runs.
+    Started,
+    /// The node failed to start.
+    Failed {
+        /// The error it failed with.
+        error: String,
+        /// Why its chunk store couldn't be initialised, if that's what failed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        storage: Option<StorageInitError>,
+        /// What to do about it, if known.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suggestion: Option<String>,
+    },
+}
+
+impl StartupStatus {
+    /// The status of a start which failed with `error`.
+    pub fn failed(error: &Error) -> Self {
+        let storage = error.storage_init().cloned();
+        Self::Failed {
+            error: error.to_string(),
+            suggestion: storage.as_ref().map(StorageInitError::suggestion),
+            storage,
+        }
+    }
+}
+
+/// Records the startup status in the root dir of its node.
+pub fn write(root_dir: &Path, status: &StartupStatus) -> io::Result<()> {
+    let json = serde_json::to_vec(status)?;
+    persisted::write(
+        &root_dir.join(STARTUP_STATUS_FILE),
+        STARTUP_STATUS_VERSION,
+        &json,
+    )
+}
+
+/// Reads back the startup status in the given root dir, if one was recorded.
+pub fn read(root_dir: &Path) -> Result<Option<StartupStatus>, String> {
+    let path = root_dir.join(STARTUP_STATUS_FILE);
+    let read = match persisted::read(&path).map_err(|err| err.to_string())? {
+        Some(read) => read,
+        None => return Ok(None),
+    };
+    if read.version != STARTUP_STATUS_VERSION {
+        let error = persisted::Error::WrongVersion {
+            path,
+            found: read.version,
+            expected: STARTUP_STATUS_VERSION,
+        };
+        return Err(error.to_string());
+    }
+    serde_json::from_slice(&read.payload)
+        .map(Some)
+        .map_err(|err| persisted::Error::corrupt(&path, err).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbs::Error as DbError;
+    use eyre::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn storage_failures_are_recorded_with_their_details() -> Result<()> {
+        let root = tempdir()?;
+        assert_eq!(read(root.path()), Ok(None));
+
+        let storage = StorageInitError::InsufficientSpace {
+            path: root.path().to_path_buf(),
+            required: 100,
+            available: 10,
+        };
+        let error = Error::from(DbError::from(storage.clone()));
+        write(root.path(), &StartupStatus::failed(&error))?;
+
+        let recorded = read(root.path()).map_err(|err| eyre::eyre!(err))?;
+        assert_eq!(
+            recorded,
+            Some(StartupStatus::Failed {
+                error: error.to_string(),
+                suggestion: Some(storage.suggestion()),
+                storage: Some(storage),
+            })
+        );
+        let payload = persisted::read(&root.path().join(STARTUP_STATUS_FILE))?
+            .map(|persisted| persisted.payload)
+            .unwrap_or_default();
+        let json: serde_json::Value = serde_json::from_slice(&payload)?;
+        assert_eq!(json["state"], "failed");
+        assert_eq!(json["storage"]["kind"], "insufficient_space");
+        assert_eq!(json["storage"]["required"], 100);
+
+        write(root.path(), &StartupStatus::Started)?;
+        assert_eq!(read(root.path()), Ok(Some(StartupStatus::Started)));
+
+        Ok(())
+    }
+}